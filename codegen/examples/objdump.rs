@@ -0,0 +1,23 @@
+//! Dumps the functions of a code cache, with their code and relocations.
+//!
+//! ```sh
+//! cargo run --example objdump -- a.vcmc
+//! ```
+
+use std::{env, fs, process};
+use vicis_codegen::codegen::code_cache::CodeCache;
+
+fn main() {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("usage: objdump <code cache>");
+        process::exit(1);
+    };
+    let cache = fs::File::open(&path)
+        .map_err(Into::into)
+        .and_then(CodeCache::read)
+        .unwrap_or_else(|err| {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
+        });
+    print!("{}", cache);
+}
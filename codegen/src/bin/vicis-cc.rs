@@ -0,0 +1,82 @@
+//! Compiles a `.ll` or `.bc` file to an executable, a shared library or an
+//! object file.
+//!
+//! ```sh
+//! vicis-cc [-c | -shared] [-pie | -no-pie] [--target=<triple>]
+//!          [--linker=<path>] [-o <output>] <input> [linker args]...
+//! ```
+//!
+//! Arguments starting with `-l`, `-L` or `-Wl,`, and any other after `--`,
+//! are passed on to the linker.
+
+use std::{env, fs, path::Path, process};
+use vicis_codegen::codegen::driver::{compile_to_file, LinkOptions, OutputKind};
+use vicis_core::{bitcode, ir::module};
+
+const USAGE: &str = "usage: vicis-cc [-c | -shared] [-pie | -no-pie] [--target=<triple>] \
+                     [--linker=<path>] [-o <output>] <input> [linker args]...";
+
+fn main() {
+    let mut options = LinkOptions::new();
+    let mut input = None;
+    let mut output = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        options = match arg.as_str() {
+            "-c" => options.with_output_kind(OutputKind::Object),
+            "-shared" => options.with_output_kind(OutputKind::SharedLibrary),
+            "-pie" => options.with_pie(true),
+            "-no-pie" => options.with_pie(false),
+            "-o" => {
+                output = Some(args.next().unwrap_or_else(|| usage()));
+                options
+            }
+            "--" => args.by_ref().fold(options, LinkOptions::with_arg),
+            _ if arg.starts_with("--target=") => options.with_target(&arg["--target=".len()..]),
+            _ if arg.starts_with("--linker=") => options.with_linker(&arg["--linker=".len()..]),
+            _ if arg.starts_with("-l") || arg.starts_with("-L") || arg.starts_with("-Wl,") => {
+                options.with_arg(arg)
+            }
+            _ if arg.starts_with('-') || input.is_some() => usage(),
+            _ => {
+                input = Some(arg);
+                options
+            }
+        }
+    }
+    let Some(input) = input else { usage() };
+    let output = output.unwrap_or_else(|| match options.output_kind {
+        OutputKind::Executable => "a.out".to_owned(),
+        OutputKind::SharedLibrary => Path::new(&input)
+            .with_extension("so")
+            .to_string_lossy()
+            .into_owned(),
+        OutputKind::Object => Path::new(&input)
+            .with_extension("o")
+            .to_string_lossy()
+            .into_owned(),
+    });
+
+    let module = match fs::read(&input) {
+        Ok(bytes) if input.ends_with(".bc") => {
+            bitcode::parse(&bytes).map_err(|err| err.to_string())
+        }
+        Ok(bytes) => String::from_utf8(bytes)
+            .map_err(|err| err.to_string())
+            .and_then(|source| module::parse_assembly(&source).map_err(|err| err.to_string())),
+        Err(err) => Err(err.to_string()),
+    }
+    .unwrap_or_else(|err| {
+        eprintln!("{}: {}", input, err);
+        process::exit(1);
+    });
+    if let Err(err) = compile_to_file(&module, &options, &output) {
+        eprintln!("{}: {}", input, err);
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
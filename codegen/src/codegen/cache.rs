@@ -0,0 +1,204 @@
+use super::{
+    function::{data::Data, layout::Layout, slot::Slots, Function as MachFunction},
+    isa::TargetIsa,
+    lower::{compile_function, compile_module_with_options, finish_module, personality_symbol},
+    module::Module as MachModule,
+    object::symbol_name,
+    options::{AsmSyntax, CodeModel, CodegenOptions, RelocModel},
+};
+use anyhow::Result;
+use id_arena::Arena;
+use rustc_hash::FxHashMap;
+use std::{
+    any, fs,
+    hash::Hasher,
+    io,
+    path::{Path, PathBuf},
+};
+use vicis_core::ir::{
+    function::{hash::StableHasher, Function as IrFunction},
+    module::Module as IrModule,
+};
+
+/// Targets that can print the assembly of a single function.
+pub trait PrintFunction: TargetIsa {
+    /// Prints `func`, which is the `fn_idx`-th function of its module, in
+    /// `syntax`.
+    fn print_function(func: &MachFunction<Self>, fn_idx: usize, syntax: AsmSyntax) -> String;
+}
+
+/// Caches the generated assembly of each function, keyed by the structural
+/// hash of its IR, so that unchanged functions are not compiled again.
+///
+/// If a directory is given, entries are also stored there and survive across
+/// driver invocations. The `CodegenOptions` are part of the key, so a cache
+/// may be shared between different options.
+#[derive(Debug, Default)]
+pub struct CodegenCache {
+    dir: Option<PathBuf>,
+    entries: FxHashMap<u64, String>,
+    hits: usize,
+    misses: usize,
+}
+
+impl CodegenCache {
+    /// Creates a cache that only lives in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a cache backed by `dir`, creating the directory if needed.
+    pub fn open<P: AsRef<Path>>(dir: P) -> io::Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(Self {
+            dir: Some(dir.as_ref().to_path_buf()),
+            ..Self::default()
+        })
+    }
+
+    /// Returns the key for `func` placed at `fn_idx` in its module when compiled for `T`
+    /// with `options`.
+    pub fn key<T: TargetIsa>(func: &IrFunction, fn_idx: usize, options: &CodegenOptions<T>) -> u64 {
+        let mut hasher = StableHasher::new();
+        hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.write(any::type_name::<T>().as_bytes());
+        hash_options(options, &mut hasher);
+        hasher.write_u64(fn_idx as u64);
+        hasher.write_u64(func.structural_hash());
+        hasher.finish()
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<&str> {
+        if !self.entries.contains_key(&key) {
+            let cached = self
+                .dir
+                .as_ref()
+                .and_then(|dir| fs::read_to_string(entry_path(dir, key)).ok());
+            match cached {
+                Some(asm) => {
+                    self.entries.insert(key, asm);
+                }
+                None => {
+                    self.misses += 1;
+                    return None;
+                }
+            }
+        }
+        self.hits += 1;
+        self.entries.get(&key).map(String::as_str)
+    }
+
+    pub fn insert(&mut self, key: u64, asm: String) -> io::Result<()> {
+        if let Some(dir) = &self.dir {
+            fs::write(entry_path(dir, key), &asm)?;
+        }
+        self.entries.insert(key, asm);
+        Ok(())
+    }
+
+    /// Returns how many lookups found an entry.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Returns how many lookups found no entry.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+}
+
+/// Creates a body-less function standing for `function`, whose assembly is taken from a cache.
+fn placeholder<T: TargetIsa>(isa: T, function: &IrFunction) -> MachFunction<T> {
+    MachFunction {
+        name: function.name.to_owned(),
+        is_var_arg: function.is_var_arg,
+        result_ty: function.result_ty,
+        params: function.params.clone(),
+        linkage: function.linkage,
+        preemption_specifier: function.preemption_specifier,
+        visibility: function.visibility,
+        attributes: function.func_attrs.clone(),
+        personality: personality_symbol(function),
+        section: function.section.clone(),
+        comdat: function.comdat.as_ref().map(symbol_name),
+        align: function.align,
+        data: Data::new(),
+        layout: Layout::new(),
+        slots: Slots::new(isa),
+        constant_pool: vec![],
+        types: function.types.clone(),
+        is_prototype: true,
+        isa,
+        call_conv: T::default_call_conv(),
+    }
+}
+
+/// Feeds `options` to `hasher`, with each enum written as a fixed byte so
+/// that keys don't depend on the host.
+fn hash_options<T: TargetIsa>(options: &CodegenOptions<T>, hasher: &mut StableHasher) {
+    hasher.write_u8(match options.asm_syntax {
+        AsmSyntax::Intel => 0,
+        AsmSyntax::Att => 1,
+    });
+    hasher.write_u8(match options.reloc_model {
+        RelocModel::Static => 0,
+        RelocModel::Pic => 1,
+    });
+    hasher.write_u8(match options.code_model {
+        CodeModel::Small => 0,
+        CodeModel::Medium => 1,
+        CodeModel::Large => 2,
+    });
+    hasher.write_u8(options.emit_cfi as u8);
+    hasher.write_u8(options.function_sections as u8);
+}
+
+fn entry_path(dir: &Path, key: u64) -> PathBuf {
+    dir.join(format!("{:016x}.s", key))
+}
+
+/// Same as `compile_module_with_options`, but reuses the assembly of functions
+/// found in `cache` and stores the assembly of the newly compiled ones.
+///
+/// Reused functions are left as prototypes in the returned module and their
+/// assembly is kept in `MachModule::precompiled`. Custom machine passes can't
+/// be told apart by a key, so with any in `options` the cache is not used.
+pub fn compile_module_cached<T: PrintFunction>(
+    isa: T,
+    module: &IrModule,
+    options: &CodegenOptions<T>,
+    cache: &mut CodegenCache,
+) -> Result<MachModule<T>> {
+    if !options.extra_module_passes.is_empty() {
+        return compile_module_with_options(isa, module, options);
+    }
+
+    let mut functions = Arena::new();
+    let mut precompiled = FxHashMap::default();
+    let mut compiled = vec![];
+
+    for (i, (_, function)) in module.functions().iter().enumerate() {
+        if function.is_prototype() {
+            functions.alloc(compile_function(isa, function)?);
+            continue;
+        }
+        let key = CodegenCache::key(function, i, options);
+        if let Some(asm) = cache.get(key) {
+            precompiled.insert(i, asm.to_owned());
+            functions.alloc(placeholder(isa, function));
+            continue;
+        }
+        let id = functions.alloc(compile_function(isa, function)?);
+        compiled.push((id, i, key));
+    }
+
+    let mut mach_module = finish_module(isa, module, functions, options)?;
+    mach_module.precompiled = precompiled;
+
+    for (id, i, key) in compiled {
+        let asm = T::print_function(&mach_module.functions[id], i, mach_module.asm_syntax);
+        cache.insert(key, asm)?;
+    }
+
+    Ok(mach_module)
+}
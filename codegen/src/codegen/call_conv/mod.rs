@@ -1,4 +1,12 @@
+pub mod testing;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum CallConvKind {
     SystemV,
+    /// The C convention of i386, which passes all arguments on the stack.
+    Cdecl,
+    /// The procedure call standard of AArch64.
+    AAPCS64,
+    /// The C ABI of WebAssembly, which passes arguments as parameters of the function.
+    Wasm,
 }
@@ -0,0 +1,241 @@
+//! A kit for testing calling conventions against golden ABI tables.
+//!
+//! A golden table lists signatures of integers, floating-point numbers and
+//! structs of growing size, each with where the ABI puts its arguments. For
+//! each signature, [`arg_locations`] compiles a call passing a distinct
+//! constant in every piece of every argument, and reads back from the
+//! machine code which registers the constants were moved to. [`check`]
+//! compares that against the table.
+//!
+//! Signatures a backend cannot compile yet are listed as such in the table,
+//! so that both a regression and newly gained support show up in the report.
+
+use crate::codegen::{
+    function::instruction::InstructionData, isa::TargetIsa, lower::compile_module,
+    register::RegUnit, register::RegisterInfo,
+};
+use anyhow::Result;
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+};
+use vicis_core::ir::module::parse_assembly;
+
+/// The type of an argument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgTy {
+    I32,
+    I64,
+    F32,
+    F64,
+    /// A struct of `n` `i32`s, passed by value.
+    Struct(usize),
+}
+
+/// Where an argument is passed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgLocation {
+    /// In registers, one per eightbyte (or the target's unit) of the argument.
+    Regs(Vec<RegUnit>),
+    /// In memory, e.g. on the stack.
+    Memory,
+}
+
+/// Where the ABI passes an argument, as written in a golden table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Loc {
+    /// In `n` integer registers, from the `i`th integer argument register on.
+    Int(usize, usize),
+    /// In the `i`th floating-point argument register.
+    Float(usize),
+    /// In memory.
+    Memory,
+}
+
+/// Where the arguments of a signature are passed, and whether the backend
+/// supports the signature yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expect {
+    Supported(&'static [Loc]),
+    Unsupported(&'static [Loc]),
+}
+
+/// A row of a golden table.
+pub type Golden = (&'static [ArgTy], Expect);
+
+/// The argument registers of a target, in the order the ABI assigns them.
+pub struct ArgRegs<'a> {
+    pub int: &'a [RegUnit],
+    pub float: &'a [RegUnit],
+}
+
+impl ArgRegs<'_> {
+    fn location(&self, loc: Loc) -> ArgLocation {
+        match loc {
+            Loc::Int(i, n) => ArgLocation::Regs(self.int[i..i + n].to_vec()),
+            Loc::Float(i) => ArgLocation::Regs(vec![self.float[i]]),
+            Loc::Memory => ArgLocation::Memory,
+        }
+    }
+}
+
+/// A signature whose arguments weren't passed as the golden table says.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub signature: Vec<ArgTy>,
+    pub expected: Vec<ArgLocation>,
+    pub actual: Vec<ArgLocation>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AbiReport {
+    pub passed: usize,
+    /// Signatures that failed to compile although the table expects them to.
+    pub unsupported: Vec<Vec<ArgTy>>,
+    /// Signatures that compiled although the table lists them as unsupported.
+    pub newly_supported: Vec<Vec<ArgTy>>,
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl AbiReport {
+    /// Returns true if every signature behaved as the golden table says.
+    pub fn is_ok(&self) -> bool {
+        self.unsupported.is_empty() && self.newly_supported.is_empty() && self.mismatches.is_empty()
+    }
+}
+
+/// Returns a module where `@caller` calls `@callee` with the arguments of
+/// `signature`. The `j`-th piece of the `i`-th argument is `(i + 1) << 8 | j`.
+pub fn call_module(signature: &[ArgTy]) -> String {
+    let tys: Vec<String> = signature.iter().map(|ty| ty.to_string()).collect();
+    let args: Vec<String> = signature
+        .iter()
+        .enumerate()
+        .map(|(i, ty)| {
+            let piece = |j: usize| (i + 1) << 8 | j;
+            match ty {
+                ArgTy::I32 | ArgTy::I64 => format!("{} {}", ty, piece(0)),
+                ArgTy::F32 | ArgTy::F64 => format!("{} {}.0", ty, piece(0)),
+                ArgTy::Struct(n) => {
+                    let fields: Vec<String> =
+                        (0..*n).map(|j| format!("i32 {}", piece(j))).collect();
+                    format!("{} {{ {} }}", ty, fields.join(", "))
+                }
+            }
+        })
+        .collect();
+    format!(
+        "declare i32 @callee({})\n\n\
+         define i32 @caller() {{\n  \
+         %1 = call i32 @callee({})\n  \
+         ret i32 %1\n\
+         }}\n",
+        tys.join(", "),
+        args.join(", ")
+    )
+}
+
+/// Compiles a call with the arguments of `signature` and returns where each
+/// argument was passed. An argument not found in a register is taken to be
+/// passed in memory. Floating-point arguments are found where they are loaded
+/// from the constant pool.
+pub fn arg_locations<T: TargetIsa>(isa: T, signature: &[ArgTy]) -> Result<Vec<ArgLocation>> {
+    let module = parse_assembly(&call_module(signature))?;
+    let mach_module = compile_module(isa, &module)?;
+    let (_, caller) = mach_module
+        .functions
+        .iter()
+        .find(|(_, f)| f.name == "caller")
+        .unwrap();
+
+    let mut regs = vec![vec![]; signature.len()];
+    'outer: for block in caller.layout.block_iter() {
+        for inst in caller.layout.inst_iter(block) {
+            let data = &caller.data.inst_ref(inst).data;
+            if data.is_call() {
+                break 'outer;
+            }
+            let Some((reg, imm)) = data.imm_move().or_else(|| {
+                let (reg, i) = data.pool_load()?;
+                Some((reg, pool_entry_value(&caller.constant_pool[i])))
+            }) else {
+                continue;
+            };
+            let arg = ((imm & 0xffff) >> 8) as usize;
+            if arg == 0 || arg > signature.len() {
+                continue;
+            }
+            let unit = T::RegInfo::to_reg_unit(reg);
+            if !regs[arg - 1].contains(&unit) {
+                regs[arg - 1].push(unit);
+            }
+        }
+    }
+    Ok(regs
+        .into_iter()
+        .map(|regs| {
+            if regs.is_empty() {
+                ArgLocation::Memory
+            } else {
+                ArgLocation::Regs(regs)
+            }
+        })
+        .collect())
+}
+
+/// Returns the integer value of the `float` or `double` constant `bytes`.
+fn pool_entry_value(bytes: &[u8]) -> i64 {
+    match *bytes {
+        [a, b, c, d] => f32::from_le_bytes([a, b, c, d]) as i64,
+        [a, b, c, d, e, f, g, h] => f64::from_le_bytes([a, b, c, d, e, f, g, h]) as i64,
+        _ => 0,
+    }
+}
+
+/// Checks the placement of the arguments of every signature of `golden`,
+/// whose locations refer to `regs`.
+pub fn check<T: TargetIsa>(isa: T, regs: &ArgRegs, golden: &[Golden]) -> AbiReport {
+    let mut report = AbiReport::default();
+    for &(signature, expected) in golden {
+        let signature = signature.to_vec();
+        // Backends may still panic on what they don't support.
+        let actual = panic::catch_unwind(AssertUnwindSafe(|| arg_locations(isa, &signature)))
+            .ok()
+            .and_then(Result::ok);
+        let (actual, expected) = match (actual, expected) {
+            (Some(actual), Expect::Supported(expected)) => (actual, expected),
+            (None, Expect::Unsupported(_)) => continue,
+            (None, Expect::Supported(_)) => {
+                report.unsupported.push(signature);
+                continue;
+            }
+            (Some(_), Expect::Unsupported(_)) => {
+                report.newly_supported.push(signature);
+                continue;
+            }
+        };
+        let expected: Vec<ArgLocation> = expected.iter().map(|&loc| regs.location(loc)).collect();
+        if actual == expected {
+            report.passed += 1;
+        } else {
+            report.mismatches.push(Mismatch {
+                signature,
+                expected,
+                actual,
+            });
+        }
+    }
+    report
+}
+
+impl fmt::Display for ArgTy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::I32 => write!(f, "i32"),
+            Self::I64 => write!(f, "i64"),
+            Self::F32 => write!(f, "float"),
+            Self::F64 => write!(f, "double"),
+            Self::Struct(n) => write!(f, "{{ {} }}", vec!["i32"; *n].join(", ")),
+        }
+    }
+}
@@ -0,0 +1,443 @@
+//! A versioned binary container for compiled machine code.
+//!
+//! Unlike [`CodegenCache`](super::cache::CodegenCache), which keeps assembly
+//! text, a [`CodeCache`] holds the encoded bytes of each function along with
+//! its relocations, so that a JIT or driver can skip compilation altogether
+//! on a warm start. Each function records the structural hash of the IR it
+//! was compiled from and is only handed out for the same IR.
+//!
+//! The file layout is as follows, with integers in little endian and strings
+//! as a `u32` byte length followed by UTF-8:
+//!
+//! ```text
+//! magic "VCMC", version: u16
+//! target triple: str, features: u32 count, str...
+//! symbols: u32 count, str...
+//! functions: u32 count, then for each:
+//!     name: str, ir hash: u64, code: u32 length, bytes...
+//!     relocations: u32 count, then for each:
+//!         offset: u32, kind: u8, symbol index: u32, addend: i64
+//! checksum of everything above: u64
+//! ```
+//!
+//! A cache is displayed as a dump of its functions, with the code in rows of
+//! [`DUMP_ROW`] bytes, each followed by the relocations within it:
+//!
+//! ```text
+//! main:
+//!   0000  55 48 89 e5 e8 00 00 00 00 5d c3
+//!           0005  call4 foo-4
+//! ```
+//!
+//! Instructions are not disassembled. For x86-64, the code is that of
+//! [`encode_function`](super::isa::x86_64::encode::encode_function).
+
+use std::{
+    error::Error,
+    fmt, fs,
+    hash::Hasher,
+    io::{self, Read, Write},
+    path::Path,
+};
+use vicis_core::ir::function::{hash::StableHasher, Function as IrFunction};
+
+pub const MAGIC: &[u8; 4] = b"VCMC";
+
+/// Bumped on every change to the layout. Files of other versions are rejected.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// The number of code bytes per row in dumps.
+pub const DUMP_ROW: usize = 16;
+
+/// What the code was compiled for. A cache is only loaded for the same target.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TargetInfo {
+    pub triple: String,
+    /// Enabled target features, e.g. `sse4.2`. Kept sorted.
+    pub features: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocKind {
+    /// The 8-byte absolute address of the symbol.
+    Abs8,
+    /// The 4-byte offset of the symbol from the end of the relocated field.
+    PcRel4,
+    /// Same as `PcRel4`, for the target of a call.
+    Call4,
+    /// The 4-byte offset of the GOT entry of the symbol, in the same way as
+    /// `PcRel4`, for a `mov` loading the address.
+    GotPcRel4,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Relocation {
+    /// Offset of the relocated field in the code.
+    pub offset: u32,
+    pub kind: RelocKind,
+    /// Index of the symbol in [`CodeCache::symbols`].
+    pub symbol: u32,
+    pub addend: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledFunction {
+    pub name: String,
+    /// The [`structural_hash`](IrFunction::structural_hash) of the IR function.
+    pub ir_hash: u64,
+    pub code: Vec<u8>,
+    pub relocs: Vec<Relocation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CodeCache {
+    target: TargetInfo,
+    symbols: Vec<String>,
+    functions: Vec<CompiledFunction>,
+}
+
+#[derive(Debug)]
+pub enum CodeCacheError {
+    Io(io::Error),
+    /// The file is not a code cache.
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// The file was truncated or modified.
+    Corrupt,
+    /// The code was compiled for another target.
+    TargetMismatch {
+        expected: TargetInfo,
+        found: TargetInfo,
+    },
+}
+
+impl TargetInfo {
+    pub fn new<T: Into<String>>(triple: T, features: &[&str]) -> Self {
+        let mut features: Vec<String> = features.iter().map(|f| f.to_string()).collect();
+        features.sort();
+        features.dedup();
+        Self {
+            triple: triple.into(),
+            features,
+        }
+    }
+}
+
+impl RelocKind {
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Abs8 => 0,
+            Self::PcRel4 => 1,
+            Self::Call4 => 2,
+            Self::GotPcRel4 => 3,
+        }
+    }
+
+    fn from_u8(kind: u8) -> Option<Self> {
+        match kind {
+            0 => Some(Self::Abs8),
+            1 => Some(Self::PcRel4),
+            2 => Some(Self::Call4),
+            3 => Some(Self::GotPcRel4),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Abs8 => "abs8",
+            Self::PcRel4 => "pcrel4",
+            Self::Call4 => "call4",
+            Self::GotPcRel4 => "gotpcrel4",
+        }
+    }
+
+    /// Returns the size of the relocated field in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            Self::Abs8 => 8,
+            Self::PcRel4 | Self::Call4 | Self::GotPcRel4 => 4,
+        }
+    }
+}
+
+impl CodeCache {
+    pub fn new(target: TargetInfo) -> Self {
+        Self {
+            target,
+            ..Self::default()
+        }
+    }
+
+    pub fn target(&self) -> &TargetInfo {
+        &self.target
+    }
+
+    /// Returns the symbol table referred to by relocations.
+    pub fn symbols(&self) -> &[String] {
+        &self.symbols
+    }
+
+    /// Returns the index of `name` in the symbol table, adding it if needed.
+    pub fn symbol(&mut self, name: &str) -> u32 {
+        match self.symbols.iter().position(|s| s == name) {
+            Some(i) => i as u32,
+            None => {
+                self.symbols.push(name.to_owned());
+                self.symbols.len() as u32 - 1
+            }
+        }
+    }
+
+    pub fn functions(&self) -> &[CompiledFunction] {
+        &self.functions
+    }
+
+    /// Adds `func`, replacing any function of the same name.
+    pub fn insert(&mut self, func: CompiledFunction) {
+        match self.functions.iter_mut().find(|f| f.name == func.name) {
+            Some(f) => *f = func,
+            None => self.functions.push(func),
+        }
+    }
+
+    /// Returns the code of the function `name` if it was compiled from IR
+    /// whose structural hash is `ir_hash`.
+    pub fn get(&self, name: &str, ir_hash: u64) -> Option<&CompiledFunction> {
+        self.functions
+            .iter()
+            .find(|f| f.name == name && f.ir_hash == ir_hash)
+    }
+
+    /// Returns the code compiled from `func` if it has not changed since.
+    pub fn get_for(&self, func: &IrFunction) -> Option<&CompiledFunction> {
+        self.get(func.name(), func.structural_hash())
+    }
+
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let mut buf = vec![];
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        write_str(&mut buf, &self.target.triple);
+        write_u32(&mut buf, self.target.features.len());
+        for feature in &self.target.features {
+            write_str(&mut buf, feature);
+        }
+        write_u32(&mut buf, self.symbols.len());
+        for symbol in &self.symbols {
+            write_str(&mut buf, symbol);
+        }
+        write_u32(&mut buf, self.functions.len());
+        for func in &self.functions {
+            write_str(&mut buf, &func.name);
+            buf.extend_from_slice(&func.ir_hash.to_le_bytes());
+            write_u32(&mut buf, func.code.len());
+            buf.extend_from_slice(&func.code);
+            write_u32(&mut buf, func.relocs.len());
+            for reloc in &func.relocs {
+                buf.extend_from_slice(&reloc.offset.to_le_bytes());
+                buf.push(reloc.kind.to_u8());
+                buf.extend_from_slice(&reloc.symbol.to_le_bytes());
+                buf.extend_from_slice(&reloc.addend.to_le_bytes());
+            }
+        }
+        buf.extend_from_slice(&checksum(&buf).to_le_bytes());
+        w.write_all(&buf)
+    }
+
+    /// Reads a cache written by [`write`](Self::write), checking that it is
+    /// intact and that every relocation is within its code.
+    pub fn read<R: Read>(mut r: R) -> Result<Self, CodeCacheError> {
+        let mut buf = vec![];
+        r.read_to_end(&mut buf)?;
+        if !buf.starts_with(MAGIC) {
+            return Err(CodeCacheError::BadMagic);
+        }
+        let mut reader = Reader(&buf[MAGIC.len()..]);
+        let version = u16::from_le_bytes(reader.bytes()?);
+        if version != FORMAT_VERSION {
+            return Err(CodeCacheError::UnsupportedVersion(version));
+        }
+        let body_len = buf.len().checked_sub(8).ok_or(CodeCacheError::Corrupt)?;
+        let (body, sum) = buf.split_at(body_len);
+        if checksum(body).to_le_bytes() != sum {
+            return Err(CodeCacheError::Corrupt);
+        }
+        let mut reader = Reader(&body[MAGIC.len() + 2..]);
+
+        let triple = reader.str()?;
+        let features = (0..reader.u32()?)
+            .map(|_| reader.str())
+            .collect::<Result<_, _>>()?;
+        let symbols: Vec<String> = (0..reader.u32()?)
+            .map(|_| reader.str())
+            .collect::<Result<_, _>>()?;
+        let mut functions = vec![];
+        for _ in 0..reader.u32()? {
+            let name = reader.str()?;
+            let ir_hash = u64::from_le_bytes(reader.bytes()?);
+            let len = reader.u32()? as usize;
+            let code = reader.take(len)?.to_vec();
+            let mut relocs = vec![];
+            for _ in 0..reader.u32()? {
+                let reloc = Relocation {
+                    offset: reader.u32()?,
+                    kind: RelocKind::from_u8(reader.bytes::<1>()?[0])
+                        .ok_or(CodeCacheError::Corrupt)?,
+                    symbol: reader.u32()?,
+                    addend: i64::from_le_bytes(reader.bytes()?),
+                };
+                if reloc.offset as usize + reloc.kind.size() > code.len()
+                    || reloc.symbol as usize >= symbols.len()
+                {
+                    return Err(CodeCacheError::Corrupt);
+                }
+                relocs.push(reloc);
+            }
+            functions.push(CompiledFunction {
+                name,
+                ir_hash,
+                code,
+                relocs,
+            });
+        }
+        if !reader.0.is_empty() {
+            return Err(CodeCacheError::Corrupt);
+        }
+
+        Ok(Self {
+            target: TargetInfo { triple, features },
+            symbols,
+            functions,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut buf = vec![];
+        self.write(&mut buf)?;
+        fs::write(path, buf)
+    }
+
+    /// Loads the cache at `path`, which must have been compiled for `target`.
+    pub fn load<P: AsRef<Path>>(path: P, target: &TargetInfo) -> Result<Self, CodeCacheError> {
+        let cache = Self::read(fs::File::open(path)?)?;
+        if &cache.target != target {
+            return Err(CodeCacheError::TargetMismatch {
+                expected: target.clone(),
+                found: cache.target,
+            });
+        }
+        Ok(cache)
+    }
+}
+
+fn write_u32(buf: &mut Vec<u8>, n: usize) {
+    buf.extend_from_slice(&(n as u32).to_le_bytes());
+}
+
+fn write_str(buf: &mut Vec<u8>, s: &str) {
+    write_u32(buf, s.len());
+    buf.extend_from_slice(s.as_bytes());
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CodeCacheError> {
+        if self.0.len() < len {
+            return Err(CodeCacheError::Corrupt);
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], CodeCacheError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> Result<u32, CodeCacheError> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn str(&mut self) -> Result<String, CodeCacheError> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| CodeCacheError::Corrupt)
+    }
+}
+
+impl fmt::Display for CodeCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "target: {}", self.target.triple)?;
+        if !self.target.features.is_empty() {
+            write!(f, " [{}]", self.target.features.join(","))?;
+        }
+        writeln!(f)?;
+        for func in &self.functions {
+            writeln!(f, "\n{}:", func.name)?;
+            let mut relocs: Vec<&Relocation> = func.relocs.iter().collect();
+            relocs.sort_by_key(|r| r.offset);
+            let mut relocs = relocs.into_iter().peekable();
+            for (i, row) in func.code.chunks(DUMP_ROW).enumerate() {
+                let start = i * DUMP_ROW;
+                write!(f, "  {:04x} ", start)?;
+                for byte in row {
+                    write!(f, " {:02x}", byte)?;
+                }
+                writeln!(f)?;
+                while let Some(reloc) = relocs.next_if(|r| (r.offset as usize) < start + row.len())
+                {
+                    let symbol = &self.symbols[reloc.symbol as usize];
+                    write!(
+                        f,
+                        "          {:04x}  {} {}",
+                        reloc.offset,
+                        reloc.kind.name(),
+                        symbol
+                    )?;
+                    match reloc.addend {
+                        0 => writeln!(f)?,
+                        a if a < 0 => writeln!(f, "{}", a)?,
+                        a => writeln!(f, "+{}", a)?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<io::Error> for CodeCacheError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl Error for CodeCacheError {}
+
+impl fmt::Display for CodeCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::BadMagic => write!(f, "not a code cache"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported code cache version {}", version)
+            }
+            Self::Corrupt => write!(f, "corrupt code cache"),
+            Self::TargetMismatch { expected, found } => write!(
+                f,
+                "code cache is for {} [{}], not {} [{}]",
+                found.triple,
+                found.features.join(","),
+                expected.triple,
+                expected.features.join(",")
+            ),
+        }
+    }
+}
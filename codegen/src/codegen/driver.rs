@@ -0,0 +1,216 @@
+//! Compiling a module to an executable or a shared library.
+//!
+//! [`compile_to_file`] lowers a module for x86-64, writes it as an object
+//! file in the format of the target, and runs the system linker driver,
+//! `cc` unless told otherwise, on it:
+//!
+//! ```text
+//! cc <object> -o <output> [-pie | -no-pie | -shared] <args>...
+//! ```
+//!
+//! The target is the one given by [`LinkOptions::with_target`], else that of
+//! the module, else the host. It decides the object format, ELF or Mach-O,
+//! and the flags of the linker. A target of another system than the host is
+//! passed on to the linker by `--target`, which only clang understands.
+//!
+//! Executables are position independent unless told otherwise, as most
+//! linkers produce by default. Position-independent executables and shared
+//! libraries are compiled with [`RelocModel::Pic`], others with
+//! [`RelocModel::Static`].
+
+use super::{
+    isa::x86_64::{encode::encode_module, X86_64},
+    lower::compile_module_with_options,
+    object::{elf, macho},
+    options::{CodegenOptions, RelocModel},
+};
+use anyhow::Result;
+use std::{
+    error::Error,
+    fmt, fs,
+    path::Path,
+    process::{Command, ExitStatus},
+};
+use vicis_core::ir::module::Module as IrModule;
+
+/// The target triple of the host, if supported.
+pub const HOST_TRIPLE: Option<&str> = if cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+    Some("x86_64-unknown-linux-gnu")
+} else if cfg!(all(target_arch = "x86_64", target_os = "macos")) {
+    Some("x86_64-apple-darwin")
+} else {
+    None
+};
+
+/// What [`compile_to_file`] produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputKind {
+    #[default]
+    Executable,
+    SharedLibrary,
+    /// A relocatable object file, without running the linker.
+    Object,
+}
+
+/// Options controlling [`compile_to_file`].
+#[derive(Debug, Clone)]
+pub struct LinkOptions {
+    pub output_kind: OutputKind,
+    /// The target triple, e.g. `x86_64-unknown-linux-gnu`.
+    pub target: Option<String>,
+    /// Whether executables are position independent.
+    pub pie: bool,
+    /// The linker driver run.
+    pub linker: String,
+    /// Passed on to the linker after the object, e.g. `-lm`.
+    pub args: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum DriverError {
+    /// The target is not one code can be generated for.
+    UnsupportedTarget(String),
+    /// The linker exited with `status`.
+    LinkFailed(ExitStatus),
+}
+
+/// The object formats written, with the flags of their linkers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Elf,
+    MachO,
+}
+
+impl Default for LinkOptions {
+    fn default() -> Self {
+        Self {
+            output_kind: OutputKind::default(),
+            target: None,
+            pie: true,
+            linker: "cc".to_owned(),
+            args: vec![],
+        }
+    }
+}
+
+impl LinkOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_output_kind(mut self, kind: OutputKind) -> Self {
+        self.output_kind = kind;
+        self
+    }
+
+    pub fn with_target<T: Into<String>>(mut self, triple: T) -> Self {
+        self.target = Some(triple.into());
+        self
+    }
+
+    pub fn with_pie(mut self, pie: bool) -> Self {
+        self.pie = pie;
+        self
+    }
+
+    pub fn with_linker<T: Into<String>>(mut self, linker: T) -> Self {
+        self.linker = linker.into();
+        self
+    }
+
+    pub fn with_arg<T: Into<String>>(mut self, arg: T) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Returns the relocation model the module is compiled with.
+    pub fn reloc_model(&self) -> RelocModel {
+        match self.output_kind {
+            OutputKind::SharedLibrary => RelocModel::Pic,
+            _ if self.pie => RelocModel::Pic,
+            _ => RelocModel::Static,
+        }
+    }
+}
+
+/// Compiles `module` to `output`, as [`LinkOptions::output_kind`] says.
+pub fn compile_to_file(
+    module: &IrModule,
+    options: &LinkOptions,
+    output: impl AsRef<Path>,
+) -> Result<()> {
+    let output = output.as_ref();
+    let triple = options
+        .target
+        .as_deref()
+        .or_else(|| Some(module.target().triple()).filter(|t| !t.is_empty()))
+        .or(HOST_TRIPLE)
+        .ok_or_else(|| DriverError::UnsupportedTarget("host".to_owned()))?;
+    let format = Format::of(triple)?;
+
+    let codegen_options = CodegenOptions::new().with_reloc_model(options.reloc_model());
+    let mach_module = compile_module_with_options(X86_64, module, &codegen_options)?;
+    let obj = encode_module(&mach_module)?;
+    let write = match format {
+        Format::Elf => elf::write_to_file,
+        Format::MachO => macho::write_to_file,
+    };
+    if options.output_kind == OutputKind::Object {
+        return write(&obj, output);
+    }
+
+    let obj_path = output.with_extension(format!("{}.o", std::process::id()));
+    write(&obj, &obj_path)?;
+    let mut command = Command::new(&options.linker);
+    command.arg(&obj_path).arg("-o").arg(output);
+    command.args(format.flags(options));
+    if HOST_TRIPLE.and_then(|host| Format::of(host).ok()) != Some(format) {
+        command.arg(format!("--target={}", triple));
+    }
+    command.args(&options.args);
+    let status = command.status();
+    fs::remove_file(&obj_path)?;
+    match status? {
+        status if status.success() => Ok(()),
+        status => Err(DriverError::LinkFailed(status).into()),
+    }
+}
+
+impl Format {
+    fn of(triple: &str) -> Result<Self, DriverError> {
+        let unsupported = || DriverError::UnsupportedTarget(triple.to_owned());
+        if triple.split('-').next() != Some("x86_64") {
+            return Err(unsupported());
+        }
+        if triple.contains("linux") {
+            Ok(Self::Elf)
+        } else if triple.contains("darwin") || triple.contains("macos") {
+            Ok(Self::MachO)
+        } else {
+            Err(unsupported())
+        }
+    }
+
+    /// Returns the flags of the linker for the output of `options`.
+    fn flags(self, options: &LinkOptions) -> Vec<&'static str> {
+        match (self, options.output_kind) {
+            (Self::Elf, OutputKind::SharedLibrary) => vec!["-shared"],
+            (Self::Elf, _) if options.pie => vec!["-pie"],
+            (Self::Elf, _) => vec!["-no-pie"],
+            // Executables are always position independent on macOS.
+            (Self::MachO, OutputKind::SharedLibrary) => vec!["-dynamiclib"],
+            (Self::MachO, _) => vec![],
+        }
+    }
+}
+
+impl Error for DriverError {}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedTarget(triple) => write!(f, "unsupported target {}", triple),
+            Self::LinkFailed(status) => write!(f, "linker failed: {}", status),
+        }
+    }
+}
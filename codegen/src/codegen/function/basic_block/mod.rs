@@ -1,24 +1,27 @@
 use id_arena::Id;
 use rustc_hash::FxHashSet;
+use vicis_core::traits::basic_block;
 
 pub type BasicBlockId = Id<BasicBlock>;
 
+#[derive(Debug, Default)]
 pub struct BasicBlock {
     pub preds: FxHashSet<BasicBlockId>,
     pub succs: FxHashSet<BasicBlockId>,
 }
 
-impl Default for BasicBlock {
-    fn default() -> Self {
-        Self {
-            preds: FxHashSet::default(),
-            succs: FxHashSet::default(),
-        }
-    }
-}
-
 impl BasicBlock {
     pub fn new() -> Self {
         Self::default()
     }
 }
+
+impl basic_block::BasicBlock for BasicBlock {
+    fn preds(&self) -> &FxHashSet<BasicBlockId> {
+        &self.preds
+    }
+
+    fn succs(&self) -> &FxHashSet<BasicBlockId> {
+        &self.succs
+    }
+}
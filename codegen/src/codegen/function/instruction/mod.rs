@@ -26,6 +26,13 @@ pub trait InstructionData: Clone + fmt::Debug {
     );
     fn is_copy(&self) -> bool;
     fn is_call(&self) -> bool;
+    /// Returns the register and the immediate if this moves an immediate to a physical register.
+    fn imm_move(&self) -> Option<(Reg, i64)>;
+    /// Returns the register and the index of the constant pool entry if this loads the entry to a
+    /// physical register.
+    fn pool_load(&self) -> Option<(Reg, usize)>;
+    /// Renames the symbols referred to by this instruction, for which `rename` returns a new name.
+    fn rename_symbols(&mut self, rename: &dyn Fn(&str) -> Option<String>);
 }
 
 pub trait InstructionInfo {
@@ -66,3 +73,43 @@ impl<Data: InstructionData> Instruction<Data> {
         }
     }
 }
+
+/// A call frame information directive, placed among the instructions by the
+/// prologue and epilogue inserters so that unwinders can walk the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CfiDirective {
+    StartProc,
+    EndProc,
+    /// The CFA is at `reg + offset`.
+    DefCfa(Reg, i32),
+    DefCfaOffset(i32),
+    DefCfaRegister(Reg),
+    /// `reg` is saved at `CFA + offset`.
+    Offset(Reg, i32),
+    /// `reg` holds its value from the caller again.
+    Restore(Reg),
+    RememberState,
+    RestoreState,
+}
+
+impl CfiDirective {
+    /// Writes the directive as the GNU assembler takes it, naming registers
+    /// by `reg_name`.
+    pub fn write(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        reg_name: impl Fn(&Reg) -> String,
+    ) -> fmt::Result {
+        match self {
+            Self::StartProc => writeln!(f, "  .cfi_startproc"),
+            Self::EndProc => writeln!(f, "  .cfi_endproc"),
+            Self::DefCfa(r, offset) => writeln!(f, "  .cfi_def_cfa {}, {}", reg_name(r), offset),
+            Self::DefCfaOffset(offset) => writeln!(f, "  .cfi_def_cfa_offset {}", offset),
+            Self::DefCfaRegister(r) => writeln!(f, "  .cfi_def_cfa_register {}", reg_name(r)),
+            Self::Offset(r, offset) => writeln!(f, "  .cfi_offset {}, {}", reg_name(r), offset),
+            Self::Restore(r) => writeln!(f, "  .cfi_restore {}", reg_name(r)),
+            Self::RememberState => writeln!(f, "  .cfi_remember_state"),
+            Self::RestoreState => writeln!(f, "  .cfi_restore_state"),
+        }
+    }
+}
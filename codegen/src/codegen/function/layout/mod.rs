@@ -51,14 +51,14 @@ impl<InstData: InstructionData> Layout<InstData> {
         Self::default()
     }
 
-    pub fn block_iter(&self) -> BasicBlockIter<InstData> {
+    pub fn block_iter(&self) -> BasicBlockIter<'_, InstData> {
         BasicBlockIter {
             layout: self,
             cur: self.first_block,
         }
     }
 
-    pub fn inst_iter(&self, block: BasicBlockId) -> InstructionIter<InstData> {
+    pub fn inst_iter(&self, block: BasicBlockId) -> InstructionIter<'_, InstData> {
         InstructionIter {
             layout: self,
             block,
@@ -177,9 +177,9 @@ impl<InstData: InstructionData> Layout<InstData> {
 
         self.instructions.get_mut(&after).unwrap().next = Some(inst);
 
-        // if self.basic_blocks[&block].first_inst == Some(before) {
-        //     self.basic_blocks.get_mut(&block).unwrap().first_inst = Some(inst);
-        // }
+        if self.basic_blocks[&block].last_inst == Some(after) {
+            self.basic_blocks.get_mut(&block).unwrap().last_inst = Some(inst);
+        }
     }
 
     pub fn append_inst(&mut self, inst: InstructionId<InstData>, block: BasicBlockId) {
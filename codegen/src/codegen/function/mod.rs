@@ -6,12 +6,19 @@ pub mod slot;
 
 use super::{call_conv::CallConvKind, isa::TargetIsa};
 use crate::codegen::function::instruction::InstructionInfo;
+use basic_block::{BasicBlock, BasicBlockId};
 use instruction::InstructionId;
 use std::fmt;
-use vicis_core::ir::{
-    function::Parameter,
-    module::{attributes::Attribute, preemption_specifier::PreemptionSpecifier},
-    types::{Type, Types},
+use vicis_core::{
+    ir::{
+        function::Parameter,
+        module::{
+            attributes::Attribute, linkage::Linkage, preemption_specifier::PreemptionSpecifier,
+            visibility::Visibility,
+        },
+        types::{Type, Types},
+    },
+    traits::basic_block::{BasicBlockData, BasicBlockLayout},
 };
 
 pub struct Function<T: TargetIsa> {
@@ -19,11 +26,26 @@ pub struct Function<T: TargetIsa> {
     pub is_var_arg: bool,
     pub result_ty: Type,
     pub params: Vec<Parameter>,
+    pub linkage: Linkage,
     pub preemption_specifier: PreemptionSpecifier,
+    pub visibility: Visibility,
     pub attributes: Vec<Attribute>,
+    /// The symbol of the personality function, which the unwinder calls for
+    /// the landing pads of this function.
+    pub personality: Option<String>,
+    /// The section the function is placed in, instead of `.text`.
+    pub section: Option<String>,
+    /// The name of the comdat the function belongs to, i.e. of the section
+    /// group it is placed in.
+    pub comdat: Option<String>,
+    /// The alignment of the function in bytes, or 0 if unspecified.
+    pub align: u32,
     pub data: data::Data<<T::InstInfo as InstructionInfo>::Data>,
     pub layout: layout::Layout<<T::InstInfo as InstructionInfo>::Data>,
     pub slots: slot::Slots<T>,
+    /// The constants the code loads from memory, by index, in little-endian
+    /// bytes, e.g. those of floating point and vectors on x86-64.
+    pub constant_pool: Vec<Vec<u8>>,
     pub types: Types,
     pub is_prototype: bool,
     pub call_conv: CallConvKind,
@@ -39,6 +61,18 @@ impl<T: TargetIsa> Function<T> {
     }
 }
 
+impl<T: TargetIsa> BasicBlockData<BasicBlock> for Function<T> {
+    fn get(&self, id: BasicBlockId) -> &BasicBlock {
+        &self.data.basic_blocks[id]
+    }
+}
+
+impl<T: TargetIsa> BasicBlockLayout<BasicBlock> for Function<T> {
+    fn order(&self) -> Box<dyn Iterator<Item = BasicBlockId> + '_> {
+        Box::new(self.layout.block_iter())
+    }
+}
+
 impl<T: TargetIsa> fmt::Debug for Function<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.is_prototype {
@@ -1,6 +1,16 @@
-use crate::codegen::isa::TargetIsa;
+//! Stack slots.
+//!
+//! A [`Slot`] is a piece of the stack frame of a function, created for an
+//! `alloca` when lowering, for a virtual register by the spiller, for the
+//! variadic arguments of a call on targets passing them in memory, or as
+//! scratch memory the lowering takes values apart in. Slots
+//! are given their offsets when the frame is finalized, i.e. once no slot is
+//! added anymore, and can then be enumerated with [`Slots::iter`], e.g. to
+//! draw the frame or to locate variables in a debugger.
+
+use crate::codegen::{isa::TargetIsa, register::VReg};
 use id_arena::{Arena, Id};
-use vicis_core::ir::types::Type;
+use vicis_core::ir::{function::instruction::InstructionId, types::Type};
 
 pub type SlotId = Id<Slot>;
 
@@ -12,12 +22,27 @@ pub struct Slots<T: TargetIsa> {
 #[derive(Debug, Clone)]
 pub struct Slot {
     pub(crate) size: u32,
-    #[allow(dead_code)]
     pub(crate) ty: Type,
     #[allow(dead_code)]
     pub(crate) num_elements: u32,
     #[allow(dead_code)]
     pub(crate) align: u32,
+    pub(crate) origin: SlotOrigin,
+    pub(crate) offset: Option<i32>,
+}
+
+/// What a slot was created for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotOrigin {
+    /// The IR `alloca` instruction.
+    Alloca(InstructionId),
+    /// A spilled virtual register.
+    Spill(VReg),
+    /// The variadic arguments of a call, on targets passing them in memory.
+    VarArgs,
+    /// Memory the lowering takes values apart in, e.g. the lanes of vectors
+    /// with no instruction for them on x86-64.
+    Scratch,
 }
 
 impl<T: TargetIsa> Slots<T> {
@@ -28,12 +53,14 @@ impl<T: TargetIsa> Slots<T> {
         }
     }
 
-    pub fn add_slot(&mut self, ty: Type, size: u32) -> SlotId {
+    pub fn add_slot(&mut self, ty: Type, size: u32, origin: SlotOrigin) -> SlotId {
         self.arena.alloc(Slot {
             size,
             ty,
             num_elements: 0,
             align: 0,
+            origin,
+            offset: None,
         })
     }
 
@@ -41,6 +68,24 @@ impl<T: TargetIsa> Slots<T> {
         &self.arena[id]
     }
 
+    /// Sets the offset of `id` from the frame pointer.
+    pub fn set_offset(&mut self, id: SlotId, offset: i32) {
+        self.arena[id].offset = Some(offset);
+    }
+
+    /// Returns the slots in the order they were created.
+    pub fn iter(&self) -> impl Iterator<Item = (SlotId, &Slot)> {
+        self.arena.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.arena.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arena.len() == 0
+    }
+
     pub fn unaligned_size(&self) -> u32 {
         let mut total = 0;
         for (_, slot) in &self.arena {
@@ -49,3 +94,23 @@ impl<T: TargetIsa> Slots<T> {
         total
     }
 }
+
+impl Slot {
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn ty(&self) -> Type {
+        self.ty
+    }
+
+    pub fn origin(&self) -> SlotOrigin {
+        self.origin
+    }
+
+    /// Returns the offset of the slot from the frame pointer, or `None` if
+    /// the frame isn't finalized yet or the slot is never accessed.
+    pub fn offset(&self) -> Option<i32> {
+        self.offset
+    }
+}
@@ -0,0 +1,306 @@
+use crate::codegen::{
+    cache::PrintFunction,
+    function::Function,
+    isa::aarch64::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        register::reg_to_str,
+        AArch64,
+    },
+    module::Module,
+    object::asm::{
+        print_binding, print_function_section, print_global_variables, print_stack_note,
+        print_visibility,
+    },
+    options::AsmSyntax,
+};
+use std::fmt;
+
+pub fn print(f: &mut fmt::Formatter<'_>, module: &Module<AArch64>) -> fmt::Result {
+    writeln!(f, "  .text")?;
+
+    let mut section = None;
+    for (i, (_, func)) in module.functions.iter().enumerate() {
+        let precompiled = module.precompiled.get(&i);
+        if precompiled.is_some() || !func.is_prototype {
+            print_function_section(f, module, func, &mut section)?;
+        }
+        match precompiled {
+            Some(asm) => f.write_str(asm)?,
+            None => print_function(f, func, i)?,
+        }
+    }
+
+    print_global_variables(f, module)?;
+    print_stack_note(f)
+}
+
+pub fn print_function(
+    f: &mut fmt::Formatter<'_>,
+    function: &Function<AArch64>,
+    fn_idx: usize,
+) -> fmt::Result {
+    print_binding(
+        f,
+        &function.name,
+        Some(function.linkage),
+        !function.is_prototype,
+    )?;
+    print_visibility(f, &function.name, function.visibility)?;
+    if function.is_prototype {
+        return Ok(());
+    }
+
+    // Instructions are 4-byte aligned, even after strings in the same section.
+    writeln!(f, "  .p2align 2")?;
+    writeln!(f, "{}:", function.name)?;
+
+    for block in function.layout.block_iter() {
+        writeln!(f, ".LBL{}_{}:", fn_idx, block.index())?;
+        for inst in function.layout.inst_iter(block) {
+            let inst = function.data.inst_ref(inst);
+            print_inst(f, &inst.data, fn_idx)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_inst(f: &mut fmt::Formatter<'_>, inst: &InstructionData, fn_idx: usize) -> fmt::Result {
+    if let (
+        Opcode::CFI,
+        [Operand {
+            data: OperandData::Cfi(cfi),
+            ..
+        }],
+    ) = (inst.opcode, &inst.operands[..])
+    {
+        return cfi.write(f, |r| reg_to_str(r).to_owned());
+    }
+
+    let mut operands = vec![];
+    let mut mem = None;
+    let mut i = 0;
+    while i < inst.operands.len() {
+        let operand = &inst.operands[i];
+        if operand.implicit {
+            i += 1;
+            continue;
+        }
+        if matches!(operand.data, OperandData::MemStart) {
+            mem = Some(&inst.operands[i + 1..i + 6]);
+            operands.push(mem_op(&inst.operands[i + 1..i + 6]));
+            i += 6;
+            continue;
+        }
+        operands.push(operand_to_string(&operand.data, fn_idx));
+        i += 1;
+    }
+
+    match inst.opcode {
+        Opcode::STPpre64 => writeln!(f, "  stp {}, {}, [sp, #-16]!", operands[0], operands[1]),
+        Opcode::LDPpost64 => writeln!(f, "  ldp {}, {}, [sp], #16", operands[0], operands[1]),
+        Opcode::ADDlo12 => writeln!(
+            f,
+            "  add {}, {}, :lo12:{}",
+            operands[0], operands[0], operands[1]
+        ),
+        Opcode::LEA64 => {
+            let mem = mem.unwrap();
+            writeln!(
+                f,
+                "  add {}, {}, #{}",
+                operands[0],
+                operand_to_string(&mem[2].data, fn_idx),
+                mem_imm(mem)
+            )
+        }
+        Opcode::MOVri32 | Opcode::MOVri64 => {
+            let (imm, bits) = match inst.operands[1].data {
+                OperandData::Int32(i) => (i as u32 as u64, 32),
+                OperandData::Int64(i) => (i as u64, 64),
+                _ => unreachable!("moves of immediates take an integer"),
+            };
+            print_mov_imm(f, &operands[0], imm, bits)
+        }
+        opcode if opcode.access_size().is_some() => {
+            let mem = mem.unwrap();
+            let imm = mem_imm(mem);
+            // Negative offsets and those not a multiple of the size of the
+            // access are unscaled, e.g. `ldur` for `ldr`.
+            let size = opcode.access_size().unwrap() as i32;
+            let unscaled = matches!(mem[3].data, OperandData::None) && (imm < 0 || imm % size != 0);
+            let mnemonic = opcode.to_string();
+            if unscaled {
+                writeln!(
+                    f,
+                    "  {}u{} {}",
+                    &mnemonic[..2],
+                    &mnemonic[2..],
+                    operands.join(", ")
+                )
+            } else {
+                writeln!(f, "  {} {}", mnemonic, operands.join(", "))
+            }
+        }
+        _ if operands.is_empty() => writeln!(f, "  {}", inst.opcode),
+        _ => writeln!(f, "  {} {}", inst.opcode, operands.join(", ")),
+    }
+}
+
+/// Prints a move of `imm`. An immediate that `mov` can't encode is built 16
+/// bits at a time with `movz` and `movk`.
+fn print_mov_imm(f: &mut fmt::Formatter<'_>, dst: &str, imm: u64, bits: u32) -> fmt::Result {
+    let mask = if bits == 32 { 0xffff_ffff } else { u64::MAX };
+    let chunks: Vec<u64> = (0..bits / 16).map(|i| imm >> (i * 16) & 0xffff).collect();
+    let zeros = chunks.iter().filter(|&&c| c == 0).count();
+    let ones = chunks.iter().filter(|&&c| c == 0xffff).count();
+    if zeros + 1 >= chunks.len() {
+        // `movz`
+        return writeln!(f, "  mov {}, #{}", dst, imm);
+    }
+    if ones + 1 >= chunks.len() {
+        // `movn`
+        return writeln!(f, "  mov {}, #{}", dst, imm as i64 | !mask as i64);
+    }
+    let mut first = true;
+    for (i, &chunk) in chunks.iter().enumerate() {
+        if chunk == 0 {
+            continue;
+        }
+        writeln!(
+            f,
+            "  {} {}, #{}, lsl #{}",
+            if first { "movz" } else { "movk" },
+            dst,
+            chunk,
+            i * 16
+        )?;
+        first = false;
+    }
+    Ok(())
+}
+
+impl PrintFunction for AArch64 {
+    fn print_function(func: &Function<Self>, fn_idx: usize, _syntax: AsmSyntax) -> String {
+        struct Printer<'a>(&'a Function<AArch64>, usize);
+        impl fmt::Display for Printer<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                print_function(f, self.0, self.1)
+            }
+        }
+        Printer(func, fn_idx).to_string()
+    }
+}
+
+impl fmt::Display for Module<AArch64> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        print(f, self)
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::STPpre64 => "stp",
+                Self::LDPpost64 => "ldp",
+                Self::ADDri32
+                | Self::ADDrr32
+                | Self::ADDri64
+                | Self::ADDrr64
+                | Self::ADDlo12
+                | Self::LEA64 => "add",
+                Self::SUBri32 | Self::SUBrr32 | Self::SUBri64 | Self::SUBrr64 => "sub",
+                Self::MULrr32 | Self::MULrr64 => "mul",
+                Self::SDIVrr32 | Self::SDIVrr64 => "sdiv",
+                Self::LSLri32 | Self::LSLrr32 | Self::LSLri64 | Self::LSLrr64 => "lsl",
+                Self::LSRri32 | Self::LSRrr32 | Self::LSRri64 | Self::LSRrr64 => "lsr",
+                Self::MOVrr32 | Self::MOVrr64 | Self::MOVri32 | Self::MOVri64 => "mov",
+                Self::SXTWr64r32 => "sxtw",
+                Self::SXTBr32 => "sxtb",
+                Self::SXTHr32 => "sxth",
+                Self::UXTBr32 => "uxtb",
+                Self::UXTHr32 => "uxth",
+                Self::ADRP => "adrp",
+                Self::LDRBrm32 => "ldrb",
+                Self::LDRHrm32 => "ldrh",
+                Self::LDRrm32 | Self::LDRrm64 => "ldr",
+                Self::LDRSWrm64 => "ldrsw",
+                Self::STRBmr32 => "strb",
+                Self::STRHmr32 => "strh",
+                Self::STRmr32 | Self::STRmr64 => "str",
+                Self::CMPri32 | Self::CMPrr32 | Self::CMPri64 | Self::CMPrr64 => "cmp",
+                Self::B => "b",
+                Self::BEQ => "b.eq",
+                Self::BNE => "b.ne",
+                Self::BLE => "b.le",
+                Self::BLT => "b.lt",
+                Self::BGE => "b.ge",
+                Self::BGT => "b.gt",
+                Self::BL => "bl",
+                Self::RET => "ret",
+                Self::CFI => "CFI",
+                Self::Phi => "PHI",
+            }
+        )
+    }
+}
+
+fn operand_to_string(op: &OperandData, fn_idx: usize) -> String {
+    match op {
+        OperandData::Reg(r) => reg_to_str(r).to_string(),
+        OperandData::VReg(r) => format!("%{}", r.0),
+        OperandData::Slot(slot) => format!("{:?}", slot),
+        OperandData::Int32(i) => format!("#{}", i),
+        OperandData::Int64(i) => format!("#{}", i),
+        OperandData::Block(block) => format!(".LBL{}_{}", fn_idx, block.index()),
+        OperandData::Label(name) => name.to_string(),
+        OperandData::MemStart => "".to_string(),
+        OperandData::GlobalAddress(name) => name.to_string(),
+        OperandData::Cfi(cfi) => format!("{:?}", cfi),
+        OperandData::None => "none".to_string(),
+    }
+}
+
+fn mem_imm(args: &[Operand]) -> i32 {
+    match args[1].data {
+        OperandData::Int32(imm) => imm,
+        _ => 0,
+    }
+}
+
+fn mem_op(args: &[Operand]) -> String {
+    assert!(matches!(&args[0].data, &OperandData::None)); // assure slot is eliminated
+    match (&args[1].data, &args[2].data, &args[3].data, &args[4].data) {
+        (OperandData::Int32(0) | OperandData::None, base, OperandData::None, OperandData::None) => {
+            format!("[{}]", operand_to_string(base, 0))
+        }
+        (OperandData::Int32(imm), base, OperandData::None, OperandData::None) => {
+            format!("[{}, #{}]", operand_to_string(base, 0), imm)
+        }
+        (
+            OperandData::Int32(0) | OperandData::None,
+            base,
+            index,
+            OperandData::Int32(scale @ (1 | 2 | 4 | 8)),
+        ) => {
+            if *scale == 1 {
+                format!(
+                    "[{}, {}]",
+                    operand_to_string(base, 0),
+                    operand_to_string(index, 0)
+                )
+            } else {
+                format!(
+                    "[{}, {}, lsl #{}]",
+                    operand_to_string(base, 0),
+                    operand_to_string(index, 0),
+                    scale.trailing_zeros()
+                )
+            }
+        }
+        _ => unreachable!("no addressing mode takes both an offset and an index"),
+    }
+}
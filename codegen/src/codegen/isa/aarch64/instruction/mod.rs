@@ -0,0 +1,516 @@
+use crate::codegen::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{
+            CfiDirective, Instruction, InstructionData as ID, InstructionId, InstructionInfo as II,
+        },
+        slot::SlotId,
+        Function,
+    },
+    isa::{aarch64::register::reg_to_str, TargetIsa},
+    register::{Reg, VReg, VRegUsers},
+};
+use std::fmt;
+
+pub struct InstructionInfo;
+
+#[derive(Clone)]
+pub struct InstructionData {
+    pub opcode: Opcode,
+    pub operands: Vec<Operand>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum Opcode {
+    /// `stp x29, x30, [sp, #-16]!`
+    STPpre64,
+    /// `ldp x29, x30, [sp], #16`
+    LDPpost64,
+    ADDri32,
+    ADDrr32,
+    ADDri64,
+    ADDrr64,
+    SUBri32,
+    SUBrr32,
+    SUBri64,
+    SUBrr64,
+    MULrr32,
+    MULrr64,
+    SDIVrr32,
+    SDIVrr64,
+    LSLri32,
+    LSLrr32,
+    LSLri64,
+    LSLrr64,
+    LSRri32,
+    LSRrr32,
+    LSRri64,
+    LSRrr64,
+    MOVrr32,
+    MOVrr64,
+    /// Moves any 32-bit immediate, with a `movk` for the upper half if needed.
+    MOVri32,
+    /// Moves any 64-bit immediate, with `movk`s for the upper halves if needed.
+    MOVri64,
+    SXTWr64r32,
+    SXTBr32,
+    SXTHr32,
+    UXTBr32,
+    UXTHr32,
+    /// Loads the page of a global address.
+    ADRP,
+    /// Adds the offset of a global address within its page.
+    ADDlo12,
+    /// Computes the address of a memory operand.
+    LEA64,
+    LDRBrm32,
+    LDRHrm32,
+    LDRrm32,
+    LDRrm64,
+    LDRSWrm64,
+    STRBmr32,
+    STRHmr32,
+    STRmr32,
+    STRmr64,
+    CMPri32,
+    CMPrr32,
+    CMPri64,
+    CMPrr64,
+    B,
+    BEQ,
+    BNE,
+    BLE,
+    BLT,
+    BGE,
+    BGT,
+    BL,
+    RET,
+    /// A call frame information directive, given by the operand. Emits no
+    /// code.
+    CFI,
+
+    // TODO
+    Phi,
+}
+
+#[derive(Clone)]
+pub struct Operand {
+    pub data: OperandData,
+    pub input: bool,
+    pub output: bool,
+    pub implicit: bool,
+}
+
+#[derive(Clone)]
+pub enum OperandData {
+    Reg(Reg),
+    VReg(VReg),
+    Int32(i32),
+    Int64(i64),
+    MemStart, // followed by: Slot, Imm, Reg(basically sp), Reg, Scale
+    Slot(SlotId),
+    Block(BasicBlockId),
+    Label(String),
+    GlobalAddress(String),
+    Cfi(CfiDirective),
+    None,
+}
+
+impl II for InstructionInfo {
+    type Data = InstructionData;
+
+    fn store_vreg_to_slot<T: TargetIsa>(
+        f: &Function<T>,
+        vreg: VReg,
+        slot: SlotId,
+        block: BasicBlockId,
+    ) -> Instruction<Self::Data> {
+        let ty = f.data.vregs.type_for(vreg);
+        Instruction::new(
+            InstructionData {
+                opcode: Opcode::store(T::type_size(&f.types, ty)),
+                operands: vec![
+                    Operand::input(vreg.into()),
+                    Operand::new(OperandData::MemStart),
+                    Operand::new(OperandData::Slot(slot)),
+                    Operand::new(OperandData::None),
+                    Operand::input(OperandData::None),
+                    Operand::input(OperandData::None),
+                    Operand::new(OperandData::None),
+                ],
+            },
+            block,
+        )
+    }
+
+    fn load_from_slot<T: TargetIsa>(
+        f: &Function<T>,
+        vreg: VReg,
+        slot: SlotId,
+        block: BasicBlockId,
+    ) -> Instruction<Self::Data> {
+        let ty = f.data.vregs.type_for(vreg);
+        Instruction::new(
+            InstructionData {
+                opcode: Opcode::load(T::type_size(&f.types, ty)),
+                operands: vec![
+                    Operand::output(vreg.into()),
+                    Operand::new(OperandData::MemStart),
+                    Operand::new(OperandData::Slot(slot)),
+                    Operand::new(OperandData::None),
+                    Operand::input(OperandData::None),
+                    Operand::input(OperandData::None),
+                    Operand::new(OperandData::None),
+                ],
+            },
+            block,
+        )
+    }
+}
+
+impl ID for InstructionData {
+    fn input_vregs(&self) -> Vec<VReg> {
+        let mut vrs = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::VReg(vr),
+                input: true,
+                ..
+            } = operand
+            {
+                vrs.push(*vr)
+            }
+        }
+        vrs
+    }
+
+    fn output_vregs(&self) -> Vec<VReg> {
+        let mut vrs = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::VReg(vr),
+                output: true,
+                ..
+            } = operand
+            {
+                vrs.push(*vr)
+            }
+        }
+        vrs
+    }
+
+    fn all_vregs(&self) -> Vec<VReg> {
+        let mut list = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::VReg(r),
+                ..
+            } = operand
+            {
+                list.push(*r)
+            }
+        }
+        list
+    }
+
+    fn input_regs(&self) -> Vec<Reg> {
+        let mut rs = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::Reg(r),
+                input: true,
+                ..
+            } = operand
+            {
+                rs.push(*r)
+            }
+        }
+        rs
+    }
+
+    fn output_regs(&self) -> Vec<Reg> {
+        let mut rs = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::Reg(r),
+                output: true,
+                ..
+            } = operand
+            {
+                rs.push(*r)
+            }
+        }
+        rs
+    }
+
+    fn all_regs(&self) -> Vec<Reg> {
+        let mut list = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::Reg(r),
+                ..
+            } = operand
+            {
+                list.push(*r)
+            }
+        }
+        list
+    }
+
+    fn rewrite(&mut self, vreg: VReg, reg: Reg) {
+        for operand in &mut self.operands {
+            match operand.data {
+                OperandData::VReg(vr) if vr == vreg => operand.data = OperandData::Reg(reg),
+                _ => {}
+            }
+        }
+    }
+
+    fn replace_vreg(
+        &mut self,
+        self_id: InstructionId<Self>,
+        users: &mut VRegUsers<Self>,
+        from: VReg,
+        to: VReg,
+    ) {
+        let u = users.remove_use(from, self_id).unwrap();
+        users.add_use(to, self_id, u.read, u.write);
+        for operand in &mut self.operands {
+            match operand.data {
+                OperandData::VReg(r) if r == from => operand.data = OperandData::VReg(to),
+                _ => {}
+            }
+        }
+    }
+
+    fn is_copy(&self) -> bool {
+        matches!(self.opcode, Opcode::MOVrr32 | Opcode::MOVrr64)
+    }
+
+    fn is_call(&self) -> bool {
+        matches!(self.opcode, Opcode::BL)
+    }
+
+    fn imm_move(&self) -> Option<(Reg, i64)> {
+        match (self.opcode, &self.operands[..]) {
+            (
+                Opcode::MOVri32,
+                [Operand {
+                    data: OperandData::Reg(r),
+                    ..
+                }, Operand {
+                    data: OperandData::Int32(i),
+                    ..
+                }],
+            ) => Some((*r, *i as i64)),
+            (
+                Opcode::MOVri64,
+                [Operand {
+                    data: OperandData::Reg(r),
+                    ..
+                }, Operand {
+                    data: OperandData::Int64(i),
+                    ..
+                }],
+            ) => Some((*r, *i)),
+            _ => None,
+        }
+    }
+
+    fn pool_load(&self) -> Option<(Reg, usize)> {
+        None
+    }
+
+    fn rename_symbols(&mut self, rename: &dyn Fn(&str) -> Option<String>) {
+        for operand in &mut self.operands {
+            if let OperandData::Label(name) | OperandData::GlobalAddress(name) = &mut operand.data {
+                if let Some(new) = rename(name) {
+                    *name = new;
+                }
+            }
+        }
+    }
+}
+
+impl Opcode {
+    /// Returns the opcode loading an integer or a pointer of `size` bytes.
+    /// Integers narrower than 32 bits are zero-extended to the register.
+    pub fn load(size: u32) -> Self {
+        match size {
+            1 => Self::LDRBrm32,
+            2 => Self::LDRHrm32,
+            4 => Self::LDRrm32,
+            _ => Self::LDRrm64,
+        }
+    }
+
+    /// Returns the opcode storing the low `size` bytes of a register.
+    pub fn store(size: u32) -> Self {
+        match size {
+            1 => Self::STRBmr32,
+            2 => Self::STRHmr32,
+            4 => Self::STRmr32,
+            _ => Self::STRmr64,
+        }
+    }
+
+    /// Returns the number of bytes a load or a store accesses.
+    pub fn access_size(&self) -> Option<u32> {
+        match self {
+            Self::LDRBrm32 | Self::STRBmr32 => Some(1),
+            Self::LDRHrm32 | Self::STRHmr32 => Some(2),
+            Self::LDRrm32 | Self::LDRSWrm64 | Self::STRmr32 => Some(4),
+            Self::LDRrm64 | Self::STRmr64 => Some(8),
+            _ => None,
+        }
+    }
+}
+
+impl Operand {
+    pub fn new(data: OperandData) -> Self {
+        Self {
+            data,
+            input: false,
+            output: false,
+            implicit: false,
+        }
+    }
+
+    pub fn input(data: OperandData) -> Self {
+        Self {
+            data,
+            input: true,
+            output: false,
+            implicit: false,
+        }
+    }
+
+    pub fn output(data: OperandData) -> Self {
+        Self {
+            data,
+            input: false,
+            output: true,
+            implicit: false,
+        }
+    }
+
+    pub fn implicit_output(data: OperandData) -> Self {
+        Self {
+            data,
+            input: false,
+            output: true,
+            implicit: true,
+        }
+    }
+
+    pub fn input_output(data: OperandData) -> Self {
+        Self {
+            data,
+            input: true,
+            output: true,
+            implicit: false,
+        }
+    }
+}
+
+impl OperandData {
+    pub fn as_reg(&self) -> &Reg {
+        match self {
+            Self::Reg(r) => r,
+            _ => panic!("{:?} is not a register", self),
+        }
+    }
+
+    pub fn as_vreg(&self) -> &VReg {
+        match self {
+            Self::VReg(r) => r,
+            _ => panic!("{:?} is not a virtual register", self),
+        }
+    }
+
+    pub fn as_block(&self) -> &BasicBlockId {
+        match self {
+            Self::Block(b) => b,
+            _ => panic!("{:?} is not a block", self),
+        }
+    }
+}
+
+impl From<VReg> for OperandData {
+    fn from(r: VReg) -> Self {
+        OperandData::VReg(r)
+    }
+}
+
+impl From<Reg> for OperandData {
+    fn from(r: Reg) -> Self {
+        OperandData::Reg(r)
+    }
+}
+
+impl From<i32> for OperandData {
+    fn from(i: i32) -> Self {
+        OperandData::Int32(i)
+    }
+}
+
+impl From<i64> for OperandData {
+    fn from(i: i64) -> Self {
+        OperandData::Int64(i)
+    }
+}
+
+impl fmt::Debug for InstructionData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ", self.opcode)?;
+        for (i, op) in self.operands.iter().enumerate() {
+            write!(f, "{:?}", op)?;
+            if i < self.operands.len() - 1 {
+                write!(f, ", ")?
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut flags = vec![];
+        if self.output {
+            flags.push("def")
+        }
+        if self.implicit {
+            flags.push("imp")
+        }
+        write!(f, "{:?}", self.data)?;
+        if !flags.is_empty() {
+            write!(f, "<")?;
+            for (i, flag) in flags.iter().enumerate() {
+                write!(f, "{}", flag)?;
+                if i < flags.len() - 1 {
+                    write!(f, ", ")?
+                }
+            }
+            write!(f, ">")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for OperandData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Reg(r) => write!(f, "{}", reg_to_str(r)),
+            Self::VReg(vr) => write!(f, "%{}", vr.0),
+            Self::Int32(i) => write!(f, "{}", i),
+            Self::Int64(i) => write!(f, "{}", i),
+            Self::MemStart => write!(f, "$MemStart$"),
+            Self::Slot(slot) => write!(f, "slot.{}", slot.index()),
+            Self::Block(id) => write!(f, "block.{}", id.index()),
+            Self::Label(name) => write!(f, "{}", name),
+            Self::GlobalAddress(name) => write!(f, "{}", name),
+            Self::Cfi(cfi) => write!(f, "{:?}", cfi),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
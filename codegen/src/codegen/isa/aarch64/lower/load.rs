@@ -0,0 +1,41 @@
+use super::{addr_mem, merged_sext_of, new_empty_inst_output, push, reg_class};
+use crate::codegen::{
+    isa::aarch64::{
+        instruction::{Opcode, Operand as MOperand},
+        AArch64,
+    },
+    isa::TargetIsa,
+    lower::LoweringContext,
+};
+use anyhow::Result;
+use vicis_core::ir::{function::instruction::InstructionId, types::Type, value::ValueId};
+
+pub fn lower_load(
+    ctx: &mut LoweringContext<AArch64>,
+    id: InstructionId,
+    tys: &[Type],
+    addr: ValueId,
+    _align: u32,
+) -> Result<()> {
+    reg_class(ctx, tys[0])?;
+    let size = AArch64::type_size(ctx.types, tys[0]);
+    let mem = addr_mem(ctx, tys[1], addr, size)?;
+
+    let (opcode, output) = match merged_sext_of(ctx.ir_data, id) {
+        Some(sext) => (
+            Opcode::LDRSWrm64,
+            new_empty_inst_output(ctx, ctx.ir_data.inst_ref(sext).operand.types()[1], sext)?,
+        ),
+        None => (Opcode::load(size), new_empty_inst_output(ctx, tys[0], id)?),
+    };
+    push(
+        ctx,
+        opcode,
+        vec![MOperand::output(output.into())]
+            .into_iter()
+            .chain(mem)
+            .collect(),
+    );
+
+    Ok(())
+}
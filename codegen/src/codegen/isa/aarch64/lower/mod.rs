@@ -0,0 +1,698 @@
+pub mod load;
+pub mod store;
+
+use crate::codegen::{
+    function::{instruction::Instruction as MachInstruction, slot::SlotId, slot::SlotOrigin},
+    isa::aarch64::{
+        instruction::{InstructionData, Opcode, Operand as MO, OperandData},
+        register::{is_held_in_register, RegClass, RegInfo, GR64},
+        AArch64,
+    },
+    isa::TargetIsa,
+    lower::{Lower as LowerTrait, LoweringContext, LoweringError},
+    register::{Reg, RegisterClass, RegisterInfo, VReg},
+};
+use anyhow::Result;
+use load::lower_load;
+use store::lower_store;
+use vicis_core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        data::Data as IrData,
+        instruction::{
+            Alloca, Br, Call, Cast, CondBr, ICmp, ICmpCond, Instruction as IrInstruction,
+            InstructionId, IntBinary, Load, Opcode as IrOpcode, Operand, Phi, Ret, Store,
+        },
+        param_attrs::ParameterAttribute,
+        Parameter,
+    },
+    module::name::Name,
+    types::{self, Type},
+    value::{ConstantData, ConstantExpr, ConstantInt, Value, ValueId},
+};
+
+#[derive(Clone, Copy, Default)]
+pub struct Lower {}
+
+impl Lower {
+    pub fn new() -> Self {
+        Lower::default()
+    }
+}
+
+impl LowerTrait<AArch64> for Lower {
+    fn lower(ctx: &mut LoweringContext<AArch64>, inst: &IrInstruction) -> Result<()> {
+        lower(ctx, inst)
+    }
+
+    fn copy_args_to_vregs(ctx: &mut LoweringContext<AArch64>, params: &[Parameter]) -> Result<()> {
+        let args = RegInfo::arg_reg_list(&ctx.call_conv);
+        for (gpr_used, Parameter { name: _, ty, .. }) in params.iter().enumerate() {
+            // TODO: Take the rest from the stack.
+            let class = reg_class(ctx, *ty)?;
+            let reg = args.get(gpr_used).ok_or(LoweringError::Todo)?.apply(&class);
+            let output = ctx.mach_data.vregs.add_vreg_data(*ty);
+            push(
+                ctx,
+                mov_rr(&class),
+                vec![MO::output(output.into()), MO::input(reg.into())],
+            );
+            ctx.arg_idx_to_vreg.insert(gpr_used, output);
+        }
+        Ok(())
+    }
+}
+
+fn lower(ctx: &mut LoweringContext<AArch64>, inst: &IrInstruction) -> Result<()> {
+    match inst.operand {
+        Operand::Alloca(Alloca {
+            ref tys,
+            ref num_elements,
+            align,
+        }) => lower_alloca(ctx, inst.id.unwrap(), tys, num_elements, align),
+        Operand::Phi(Phi {
+            ty,
+            ref args,
+            ref blocks,
+        }) => lower_phi(ctx, inst.id.unwrap(), ty, args, blocks),
+        Operand::Load(Load {
+            ref tys,
+            addr,
+            align,
+        }) => lower_load(ctx, inst.id.unwrap(), tys, addr, align),
+        Operand::Store(Store {
+            ref tys,
+            ref args,
+            align,
+        }) => lower_store(ctx, tys, args, align),
+        Operand::IntBinary(IntBinary { ty, ref args, .. }) => {
+            lower_bin(ctx, inst.id.unwrap(), inst.opcode, ty, args)
+        }
+        Operand::Cast(Cast { ref tys, arg }) => {
+            lower_cast(ctx, inst.id.unwrap(), inst.opcode, tys, arg)
+        }
+        Operand::Br(Br { block }) => lower_br(ctx, block),
+        Operand::CondBr(CondBr { arg, blocks }) => lower_condbr(ctx, arg, blocks),
+        // Arguments passed `byval` to callees not expanded belong in the stack
+        // argument area, which is not supported yet.
+        Operand::Call(Call {
+            ref param_attrs, ..
+        }) if param_attrs
+            .iter()
+            .flatten()
+            .any(|a| a == &ParameterAttribute::ByVal) =>
+        {
+            Err(LoweringError::Todo.into())
+        }
+        Operand::Call(Call {
+            ref args, ref tys, ..
+        }) => lower_call(ctx, inst.id.unwrap(), tys, args),
+        Operand::Ret(Ret { val, ty }) => lower_return(ctx, ty, val),
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
+
+fn lower_alloca(
+    ctx: &mut LoweringContext<AArch64>,
+    id: InstructionId,
+    tys: &[Type],
+    _num_elements: &ConstantData,
+    _align: u32,
+) -> Result<()> {
+    let slot_id = ctx.slots.add_slot(
+        tys[0],
+        AArch64::type_size(ctx.types, tys[0]),
+        SlotOrigin::Alloca(id),
+    );
+    ctx.inst_id_to_slot_id.insert(id, slot_id);
+    Ok(())
+}
+
+fn lower_phi(
+    ctx: &mut LoweringContext<AArch64>,
+    id: InstructionId,
+    ty: Type,
+    args: &[ValueId],
+    blocks: &[BasicBlockId],
+) -> Result<()> {
+    let output = new_empty_inst_output(ctx, ty, id)?;
+    let mut operands = vec![MO::output(output.into())];
+    for (arg, block) in args.iter().zip(blocks.iter()) {
+        operands.push(MO::input(val_to_operand_data(ctx, ty, *arg)?));
+        operands.push(MO::new(OperandData::Block(ctx.block_map[block])))
+    }
+    push(ctx, Opcode::Phi, operands);
+    Ok(())
+}
+
+fn lower_bin(
+    ctx: &mut LoweringContext<AArch64>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    args: &[ValueId],
+) -> Result<()> {
+    // Narrower integers would have to be extended first.
+    let is_64 = match ty {
+        types::I32 => false,
+        types::I64 => true,
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    let (add, sub) = if is_64 {
+        (Opcode::ADDri64, Opcode::SUBri64)
+    } else {
+        (Opcode::ADDri32, Opcode::SUBri32)
+    };
+    let bits = if is_64 { 64 } else { 32 };
+
+    let lhs = val_to_vreg(ctx, ty, args[0])?;
+    let rhs = val_to_operand_data(ctx, ty, args[1])?;
+    let output = new_empty_inst_output(ctx, ty, id)?;
+
+    // `add` and `sub` take 12-bit unsigned immediates. Negative ones are
+    // selected as the opposite operation. Shifts by immediates take them
+    // modulo the width, the amounts beyond it being poison.
+    let imm = match rhs {
+        OperandData::Int32(imm) => Some(imm as i64),
+        OperandData::Int64(imm) => Some(imm),
+        _ => None,
+    };
+    if let Some(imm) = imm {
+        let shift = |ri32, ri64| {
+            let opcode = if is_64 { ri64 } else { ri32 };
+            Some((opcode, (imm & (bits - 1)) as i32))
+        };
+        let selected = match op {
+            IrOpcode::Add if is_arith_imm(imm) => Some((add, imm as i32)),
+            IrOpcode::Add if is_arith_imm(imm.wrapping_neg()) => Some((sub, -imm as i32)),
+            IrOpcode::Sub if is_arith_imm(imm) => Some((sub, imm as i32)),
+            IrOpcode::Sub if is_arith_imm(imm.wrapping_neg()) => Some((add, -imm as i32)),
+            IrOpcode::Shl => shift(Opcode::LSLri32, Opcode::LSLri64),
+            IrOpcode::LShr => shift(Opcode::LSRri32, Opcode::LSRri64),
+            _ => None,
+        };
+        if let Some((opcode, imm)) = selected {
+            push(
+                ctx,
+                opcode,
+                vec![
+                    MO::output(output.into()),
+                    MO::input(lhs.into()),
+                    MO::new(imm.into()),
+                ],
+            );
+            return Ok(());
+        }
+    }
+
+    let rhs = operand_to_vreg(ctx, ty, rhs)?;
+    let (rr32, rr64) = match op {
+        IrOpcode::Add => (Opcode::ADDrr32, Opcode::ADDrr64),
+        IrOpcode::Sub => (Opcode::SUBrr32, Opcode::SUBrr64),
+        IrOpcode::Mul => (Opcode::MULrr32, Opcode::MULrr64),
+        IrOpcode::SDiv => (Opcode::SDIVrr32, Opcode::SDIVrr64),
+        IrOpcode::Shl => (Opcode::LSLrr32, Opcode::LSLrr64),
+        IrOpcode::LShr => (Opcode::LSRrr32, Opcode::LSRrr64),
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    let opcode = if is_64 { rr64 } else { rr32 };
+    push(
+        ctx,
+        opcode,
+        vec![
+            MO::output(output.into()),
+            MO::input(lhs.into()),
+            MO::input(rhs.into()),
+        ],
+    );
+
+    Ok(())
+}
+
+/// Lowers the extensions of integers to `i32`s and of `i32`s to `i64`s, and
+/// the truncations of `i32`s, whose narrower results are the low bits of the
+/// register.
+fn lower_cast(
+    ctx: &mut LoweringContext<AArch64>,
+    self_id: InstructionId,
+    opcode: IrOpcode,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    let from = tys[0];
+    let to = tys[1];
+    let selected = match (opcode, from, to) {
+        (IrOpcode::Sext, types::I32, types::I64) => Opcode::SXTWr64r32,
+        (IrOpcode::Sext, types::I8, types::I32) => Opcode::SXTBr32,
+        (IrOpcode::Sext, types::I16, types::I32) => Opcode::SXTHr32,
+        (IrOpcode::Zext, types::I1 | types::I8, types::I32) => Opcode::UXTBr32,
+        (IrOpcode::Zext, types::I16, types::I32) => Opcode::UXTHr32,
+        (IrOpcode::Trunc, types::I32, types::I8 | types::I16) => Opcode::MOVrr32,
+        _ => return Err(LoweringError::Todo.into()),
+    };
+
+    let val = match ctx.ir_data.values[arg] {
+        Value::Instruction(id) => {
+            if merged_sext_of(ctx.ir_data, id) == Some(self_id) {
+                let output = new_empty_inst_output(ctx, to, self_id)?;
+                ctx.set_output_for_inst(id, output); // `ldrsw` loads and extends at once
+                return Ok(());
+            }
+
+            get_or_generate_inst_output(ctx, from, id)?
+        }
+        Value::Argument(idx) => ctx.arg_idx_to_vreg[&idx],
+        _ => val_to_vreg(ctx, from, arg)?,
+    };
+
+    let output = new_empty_inst_output(ctx, to, self_id)?;
+    push(
+        ctx,
+        selected,
+        vec![MO::output(output.into()), MO::input(val.into())],
+    );
+
+    Ok(())
+}
+
+fn lower_br(ctx: &mut LoweringContext<AArch64>, block: BasicBlockId) -> Result<()> {
+    let block = ctx.block_map[&block];
+    push(ctx, Opcode::B, vec![MO::new(OperandData::Block(block))]);
+    Ok(())
+}
+
+fn lower_condbr(
+    ctx: &mut LoweringContext<AArch64>,
+    arg: ValueId,
+    blocks: [BasicBlockId; 2],
+) -> Result<()> {
+    fn is_icmp<'a>(
+        data: &'a IrData,
+        val: &Value,
+    ) -> Option<(&'a Type, &'a [ValueId; 2], &'a ICmpCond)> {
+        match val {
+            Value::Instruction(id) => {
+                let inst = data.inst_ref(*id);
+                match &inst.operand {
+                    Operand::ICmp(ICmp { ty, args, cond }) => Some((ty, args, cond)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    let arg = ctx.ir_data.value_ref(arg);
+
+    let Some((&ty, args, cond)) = is_icmp(ctx.ir_data, arg) else {
+        return Err(LoweringError::Todo.into());
+    };
+    let (ri, rr) = match ty {
+        types::I32 => (Opcode::CMPri32, Opcode::CMPrr32),
+        types::I64 => (Opcode::CMPri64, Opcode::CMPrr64),
+        _ if ty.is_pointer(ctx.types) => (Opcode::CMPri64, Opcode::CMPrr64),
+        _ => return Err(LoweringError::Todo.into()),
+    };
+
+    let opcode = match cond {
+        ICmpCond::Eq => Opcode::BEQ,
+        ICmpCond::Ne => Opcode::BNE,
+        ICmpCond::Sle => Opcode::BLE,
+        ICmpCond::Slt => Opcode::BLT,
+        ICmpCond::Sge => Opcode::BGE,
+        ICmpCond::Sgt => Opcode::BGT,
+        _ => return Err(LoweringError::Todo.into()),
+    };
+
+    let lhs = val_to_vreg(ctx, ty, args[0])?;
+    match val_to_operand_data(ctx, ty, args[1])? {
+        OperandData::Int32(rhs) if is_arith_imm(rhs as i64) => {
+            push(ctx, ri, vec![MO::input(lhs.into()), MO::new(rhs.into())])
+        }
+        OperandData::Int64(rhs) if is_arith_imm(rhs) => push(
+            ctx,
+            ri,
+            vec![MO::input(lhs.into()), MO::new((rhs as i32).into())],
+        ),
+        rhs => {
+            let rhs = operand_to_vreg(ctx, ty, rhs)?;
+            push(ctx, rr, vec![MO::input(lhs.into()), MO::input(rhs.into())])
+        }
+    }
+
+    let (then, else_) = (ctx.block_map[&blocks[0]], ctx.block_map[&blocks[1]]);
+    push(ctx, opcode, vec![MO::new(OperandData::Block(then))]);
+    push(ctx, Opcode::B, vec![MO::new(OperandData::Block(else_))]);
+
+    Ok(())
+}
+
+fn lower_call(
+    ctx: &mut LoweringContext<AArch64>,
+    id: InstructionId,
+    tys: &[Type],
+    args: &[ValueId],
+) -> Result<()> {
+    let gpru = RegInfo::arg_reg_list(&ctx.call_conv);
+    for (gpr_used, (&arg, &ty)) in args[1..].iter().zip(tys[1..].iter()).enumerate() {
+        let arg = val_to_operand_data(ctx, ty, arg)?;
+        // TODO: Pass the rest on the stack.
+        let class = reg_class(ctx, ty)?;
+        let r = gpru.get(gpr_used).ok_or(LoweringError::Todo)?.apply(&class);
+        let opcode = match &arg {
+            OperandData::Int32(_) => Opcode::MOVri32,
+            OperandData::Int64(_) => Opcode::MOVri64,
+            OperandData::VReg(_) | OperandData::Reg(_) => mov_rr(&class),
+            _ => return Err(LoweringError::Todo.into()),
+        };
+        push(ctx, opcode, vec![MO::output(r.into()), MO::input(arg)]);
+    }
+
+    let name = match &ctx.ir_data.values[args[0]] {
+        Value::Constant(ConstantData::GlobalRef(Name::Name(name))) => name.to_string(),
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    push(
+        ctx,
+        Opcode::BL,
+        vec![
+            MO::implicit_output(Reg::from(GR64::X0).into()),
+            MO::new(OperandData::Label(name)),
+        ],
+    );
+
+    if !ctx.ir_data.users_of(id).is_empty() {
+        let class = reg_class(ctx, tys[0])?;
+        let output = new_empty_inst_output(ctx, tys[0], id)?;
+        let result_reg = RegInfo::to_reg_unit(GR64::X0.into()).apply(&class);
+        push(
+            ctx,
+            mov_rr(&class),
+            vec![MO::output(output.into()), MO::input(result_reg.into())],
+        );
+    }
+
+    Ok(())
+}
+
+fn lower_return(
+    ctx: &mut LoweringContext<AArch64>,
+    ty: Type,
+    value: Option<ValueId>,
+) -> Result<()> {
+    if let Some(value) = value {
+        let vreg = val_to_vreg(ctx, ty, value)?;
+        let class = reg_class(ctx, ty)?;
+        let result_reg = RegInfo::to_reg_unit(GR64::X0.into()).apply(&class);
+        push(
+            ctx,
+            mov_rr(&class),
+            vec![MO::output(result_reg.into()), MO::input(vreg.into())],
+        );
+    }
+    push(ctx, Opcode::RET, vec![]);
+    Ok(())
+}
+
+/// Returns the memory operands of a slot, `imm` bytes into it.
+fn slot_mem(slot: SlotId, imm: Option<i32>) -> Vec<MO> {
+    vec![
+        MO::new(OperandData::MemStart),
+        MO::new(OperandData::Slot(slot)),
+        MO::new(imm.map_or(OperandData::None, OperandData::Int32)),
+        MO::input(OperandData::None),
+        MO::input(OperandData::None),
+        MO::new(OperandData::None),
+    ]
+}
+
+/// Returns the memory operands of `addr`, of type `ty`, accessed `access_size`
+/// bytes at a time: an `alloca`ed slot, an element of an array in one, or a
+/// pointer in a register.
+fn addr_mem(
+    ctx: &mut LoweringContext<AArch64>,
+    ty: Type,
+    addr: ValueId,
+    access_size: u32,
+) -> Result<Vec<MO>> {
+    if let Value::Instruction(id) = ctx.ir_data.values[addr] {
+        if let Some(&slot_id) = ctx.inst_id_to_slot_id.get(&id) {
+            return Ok(slot_mem(slot_id, None));
+        }
+        if ctx.ir_data.instructions[id].opcode == IrOpcode::GetElementPtr {
+            return gep_mem(ctx, id, access_size);
+        }
+    }
+    let base = val_to_vreg(ctx, ty, addr)?;
+    Ok(vec![
+        MO::new(OperandData::MemStart),
+        MO::new(OperandData::None),
+        MO::new(OperandData::None),
+        MO::input(OperandData::VReg(base)),
+        MO::input(OperandData::None),
+        MO::new(OperandData::None),
+    ])
+}
+
+/// Returns the memory operands of the address computed by `gep_id`, an
+/// element in an `alloca`ed array, accessed `access_size` bytes at a time.
+fn gep_mem(
+    ctx: &mut LoweringContext<AArch64>,
+    gep_id: InstructionId,
+    access_size: u32,
+) -> Result<Vec<MO>> {
+    use {Constant as Const, ConstantData::Int, ConstantInt::Int64, Value::Constant};
+
+    let gep = &ctx.ir_data.instructions[gep_id];
+    let base_ty = gep.operand.types()[0];
+    let elem_ty = ctx.types.get_element(base_ty).ok_or(LoweringError::Todo)?;
+    let base_size = AArch64::type_size(ctx.types, base_ty) as i64;
+    let elem_size = AArch64::type_size(ctx.types, elem_ty) as i64;
+
+    let gep_args: Vec<&Value> = gep
+        .operand
+        .args()
+        .iter()
+        .map(|&arg| &ctx.ir_data.values[arg])
+        .collect();
+
+    match gep_args[..] {
+        [Value::Instruction(base_ptr), Const(Int(Int64(idx0))), Const(Int(Int64(idx1)))] => {
+            let base_ptr = *ctx
+                .inst_id_to_slot_id
+                .get(base_ptr)
+                .ok_or(LoweringError::Todo)?;
+            let offset = idx0 * base_size + idx1 * elem_size;
+            Ok(slot_mem(base_ptr, Some(offset as i32)))
+        }
+        [Value::Instruction(base_ptr), Const(Int(Int64(idx0))), Value::Instruction(idx1)] => {
+            // Registers can only be scaled by the size of the access.
+            if elem_size != access_size as i64 {
+                return Err(LoweringError::Todo.into());
+            }
+            let base_ptr = *ctx
+                .inst_id_to_slot_id
+                .get(base_ptr)
+                .ok_or(LoweringError::Todo)?;
+            let idx1 = *idx1;
+            let offset = idx0 * base_size;
+
+            let idx1_ty = gep.operand.types()[3];
+            if !idx1_ty.is_i64() {
+                return Err(LoweringError::Todo.into());
+            }
+            let idx1 = get_or_generate_inst_output(ctx, idx1_ty, idx1)?;
+
+            // A register offset can't be added to an immediate one, so the
+            // address of the slot is computed first.
+            let base = ctx.mach_data.vregs.add_vreg_data(types::I64);
+            push(
+                ctx,
+                Opcode::LEA64,
+                vec![MO::output(base.into())]
+                    .into_iter()
+                    .chain(slot_mem(base_ptr, Some(offset as i32)))
+                    .collect(),
+            );
+
+            Ok(vec![
+                MO::new(OperandData::MemStart),
+                MO::new(OperandData::None),
+                MO::new(OperandData::None),
+                MO::input(OperandData::VReg(base)),
+                MO::input(OperandData::VReg(idx1)),
+                MO::new(OperandData::Int32(elem_size as i32)),
+            ])
+        }
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
+
+/// Returns the `sext` of the `i32` loaded by `load`, if it is selected with
+/// the load as one `ldrsw`: the load must have no other user and be in the
+/// same block.
+fn merged_sext_of(data: &IrData, load: InstructionId) -> Option<InstructionId> {
+    let parent = data.inst_ref(load).parent;
+    if !data.inst_ref(load).opcode.is_load() {
+        return None;
+    }
+    data.only_one_user_of(load).filter(|&id| {
+        let inst = data.inst_ref(id);
+        let types = inst.operand.types();
+        inst.opcode == IrOpcode::Sext
+            && inst.parent == parent
+            && types[0].is_i32()
+            && types[1].is_i64()
+    })
+}
+
+// Get instruction output.
+// If the instruction is not placed in any basic block, place it in the current block.
+// If the instruction must be placed in another block except the current block(, which means
+// the instruction output must live out from its parent basic block to the current block),
+// just create a new virtual register to store the instruction output.
+fn get_or_generate_inst_output(
+    ctx: &mut LoweringContext<AArch64>,
+    ty: Type,
+    id: InstructionId,
+) -> Result<VReg> {
+    if let Some(vreg) = ctx.inst_id_to_vreg.get(&id) {
+        return Ok(*vreg);
+    }
+
+    if ctx.ir_data.inst_ref(id).parent != ctx.cur_block {
+        // The instruction indexed as `id` must be placed in another basic block
+        return new_empty_inst_output(ctx, ty, id);
+    }
+
+    let inst = ctx.ir_data.inst_ref(id);
+
+    if inst.opcode.has_side_effects() {
+        new_empty_inst_output(ctx, ty, id)
+    } else {
+        // TODO: What about instruction scheduling?
+        lower(ctx, inst)?;
+        get_or_generate_inst_output(ctx, ty, id)
+    }
+}
+
+fn new_empty_inst_output(
+    ctx: &mut LoweringContext<AArch64>,
+    ty: Type,
+    id: InstructionId,
+) -> Result<VReg> {
+    if let Some(vreg) = ctx.inst_id_to_vreg.get(&id) {
+        return Ok(*vreg);
+    }
+    reg_class(ctx, ty)?;
+    let vreg = ctx.mach_data.vregs.add_vreg_data(ty);
+    ctx.inst_id_to_vreg.insert(id, vreg);
+    Ok(vreg)
+}
+
+fn val_to_operand_data(
+    ctx: &mut LoweringContext<AArch64>,
+    ty: Type,
+    val: ValueId,
+) -> Result<OperandData> {
+    match ctx.ir_data.values[val] {
+        Value::Instruction(id) => Ok(get_or_generate_inst_output(ctx, ty, id)?.into()),
+        Value::Argument(idx) => Ok(ctx.arg_idx_to_vreg[&idx].into()),
+        Value::Constant(ConstantData::Int(ConstantInt::Int1(i))) => {
+            Ok(OperandData::Int32(i as i32))
+        }
+        Value::Constant(ConstantData::Int(ConstantInt::Int8(i))) => {
+            Ok(OperandData::Int32(i as i32))
+        }
+        Value::Constant(ConstantData::Int(ConstantInt::Int16(i))) => {
+            Ok(OperandData::Int32(i as i32))
+        }
+        Value::Constant(ConstantData::Int(ConstantInt::Int32(i))) => Ok(OperandData::Int32(i)),
+        Value::Constant(ConstantData::Int(ConstantInt::Int64(i))) => Ok(OperandData::Int64(i)),
+        Value::Constant(ConstantData::Expr(ConstantExpr::GetElementPtr {
+            inbounds: _,
+            tys: _,
+            ref args,
+        })) => {
+            let all_indices_0 = args[1..]
+                .iter()
+                .all(|arg| matches!(arg, ConstantData::Int(ConstantInt::Int64(0))));
+            if !ty.is_pointer(ctx.types)
+                || !matches!(args[0], ConstantData::GlobalRef(_))
+                || !all_indices_0
+            {
+                return Err(LoweringError::Todo.into());
+            }
+            let name = args[0].as_global_ref().as_str().to_string();
+            let dst = ctx.mach_data.vregs.add_vreg_data(ty);
+            push(
+                ctx,
+                Opcode::ADRP,
+                vec![
+                    MO::output(dst.into()),
+                    MO::new(OperandData::GlobalAddress(name.clone())),
+                ],
+            );
+            push(
+                ctx,
+                Opcode::ADDlo12,
+                vec![
+                    MO::input_output(dst.into()),
+                    MO::new(OperandData::GlobalAddress(name)),
+                ],
+            );
+            Ok(dst.into())
+        }
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
+
+fn val_to_vreg(ctx: &mut LoweringContext<AArch64>, ty: Type, val: ValueId) -> Result<VReg> {
+    let data = val_to_operand_data(ctx, ty, val)?;
+    operand_to_vreg(ctx, ty, data)
+}
+
+/// Moves an immediate to a new virtual register. Registers are returned as is.
+fn operand_to_vreg(
+    ctx: &mut LoweringContext<AArch64>,
+    ty: Type,
+    data: OperandData,
+) -> Result<VReg> {
+    let opcode = match data {
+        OperandData::VReg(vr) => return Ok(vr),
+        OperandData::Int32(_) => Opcode::MOVri32,
+        OperandData::Int64(_) => Opcode::MOVri64,
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    reg_class(ctx, ty)?;
+    let output = ctx.mach_data.vregs.add_vreg_data(ty);
+    push(ctx, opcode, vec![MO::output(output.into()), MO::new(data)]);
+    Ok(output)
+}
+
+fn push(ctx: &mut LoweringContext<AArch64>, opcode: Opcode, operands: Vec<MO>) {
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData { opcode, operands },
+        ctx.block_map[&ctx.cur_block],
+    ));
+}
+
+/// Returns the class of the registers holding `ty`, or an error if no
+/// register holds it.
+fn reg_class(ctx: &LoweringContext<AArch64>, ty: Type) -> Result<RegClass> {
+    if !is_held_in_register(ctx.types, ty) {
+        return Err(LoweringError::Todo.into());
+    }
+    Ok(RegClass::for_type(ctx.types, ty))
+}
+
+fn mov_rr(class: &RegClass) -> Opcode {
+    match class {
+        RegClass::GR32 => Opcode::MOVrr32,
+        RegClass::GR64 => Opcode::MOVrr64,
+    }
+}
+
+fn is_arith_imm(imm: i64) -> bool {
+    (0..4096).contains(&imm)
+}
@@ -0,0 +1,36 @@
+use super::{addr_mem, push, reg_class, val_to_vreg};
+use crate::codegen::{
+    isa::aarch64::{
+        instruction::{Opcode, Operand as MOperand},
+        AArch64,
+    },
+    isa::TargetIsa,
+    lower::LoweringContext,
+};
+use anyhow::Result;
+use vicis_core::ir::{types::Type, value::ValueId};
+
+pub fn lower_store(
+    ctx: &mut LoweringContext<AArch64>,
+    tys: &[Type],
+    args: &[ValueId],
+    _align: u32,
+) -> Result<()> {
+    reg_class(ctx, tys[0])?;
+    let size = AArch64::type_size(ctx.types, tys[0]);
+    let mem = addr_mem(ctx, tys[1], args[1], size)?;
+
+    // There are no stores of immediates, so they are moved to a register first.
+    let src = val_to_vreg(ctx, tys[0], args[0])?;
+
+    push(
+        ctx,
+        Opcode::store(size),
+        vec![MOperand::input(src.into())]
+            .into_iter()
+            .chain(mem)
+            .collect(),
+    );
+
+    Ok(())
+}
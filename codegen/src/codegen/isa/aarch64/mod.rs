@@ -0,0 +1,54 @@
+pub mod asm;
+pub mod instruction;
+pub mod lower;
+pub mod pass;
+pub mod register;
+
+use super::{ModulePass, TargetIsa};
+use crate::codegen::lower::expand::ExpandHooks;
+use crate::codegen::{
+    call_conv::CallConvKind,
+    isa::aarch64,
+    pass::{private_symbols, regalloc},
+};
+use vicis_core::ir::{
+    module::data_layout::DataLayout,
+    types::{Type, Types},
+};
+
+#[derive(Copy, Clone)]
+pub struct AArch64;
+
+impl TargetIsa for AArch64 {
+    type InstInfo = instruction::InstructionInfo;
+    type Lower = aarch64::lower::Lower;
+    type RegClass = register::RegClass;
+    type RegInfo = register::RegInfo;
+
+    fn module_pass_list() -> Vec<ModulePass<Self>> {
+        vec![
+            regalloc::run_on_module,
+            pass::phi_elimination::run_on_module, // TODO: should be target independent
+            pass::simple_reg_coalescing::run_on_module,
+            pass::eliminate_slot::run_on_module,
+            pass::pro_epi_inserter::run_on_module,
+            private_symbols::run_on_module,
+        ]
+    }
+
+    fn default_call_conv() -> CallConvKind {
+        CallConvKind::AAPCS64
+    }
+
+    fn data_layout() -> DataLayout {
+        "e-m:e-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128"
+            .parse()
+            .unwrap()
+    }
+}
+
+impl ExpandHooks for AArch64 {
+    fn size_of(&self, types: &Types, ty: Type) -> u32 {
+        Self::type_size(types, ty)
+    }
+}
@@ -0,0 +1,75 @@
+use super::pro_epi_inserter::slot_offsets;
+use crate::codegen::{
+    function::Function,
+    isa::aarch64::{instruction::OperandData, register::GR64, AArch64},
+    module::Module,
+};
+use anyhow::Result;
+
+pub fn run_on_module(module: &mut Module<AArch64>) -> Result<()> {
+    for (_, func) in &mut module.functions {
+        run_on_function(func);
+    }
+    Ok(())
+}
+
+/// Replaces slots with their addresses relative to `sp`. The frame pointer is
+/// `frame_size` bytes above `sp`, and slots are below it, so that offsets
+/// from `sp` are positive and fit in scaled immediates.
+pub fn run_on_function(function: &mut Function<AArch64>) {
+    let mut worklist = vec![];
+
+    for block in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block) {
+            let inst = function.data.inst_ref(inst_id);
+            if inst
+                .data
+                .operands
+                .iter()
+                .any(|op| matches!(op.data, OperandData::Slot(_)))
+            {
+                worklist.push(inst_id);
+            }
+        }
+    }
+
+    let (offsets, frame_size) = slot_offsets(function);
+
+    while let Some(inst_id) = worklist.pop() {
+        let mut inst = function.data.instructions[inst_id].clone();
+
+        let mut i = 0;
+        let len = inst.data.operands.len();
+
+        while i < len {
+            // MemStart indicates the beginning of memory arguments
+            if !matches!(&inst.data.operands[i].data, &OperandData::MemStart) {
+                i += 1;
+                continue;
+            }
+
+            i += 1;
+
+            let mem = &mut inst.data.operands[i..i + 5];
+            i += 5;
+
+            // Memory addressed by registers is left as is.
+            let OperandData::Slot(slot) = mem[0].data else {
+                continue;
+            };
+            let imm = match mem[1].data {
+                OperandData::Int32(imm) => imm,
+                _ => 0,
+            };
+            mem[0].data = OperandData::None;
+            mem[1].data = OperandData::Int32(frame_size as i32 - offsets[&slot] as i32 + imm);
+            mem[2].data = OperandData::Reg(GR64::SP.into());
+        }
+
+        function.data.instructions[inst_id] = inst;
+    }
+
+    for (slot, off) in offsets {
+        function.slots.set_offset(slot, -(off as i32));
+    }
+}
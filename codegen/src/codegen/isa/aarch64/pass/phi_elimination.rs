@@ -0,0 +1,103 @@
+use crate::codegen::{
+    function::{basic_block::BasicBlockId, instruction::Instruction, Function},
+    isa::aarch64::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        register::RegClass,
+        AArch64,
+    },
+    module::Module,
+    register::Reg,
+};
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+
+pub fn run_on_module(module: &mut Module<AArch64>) -> Result<()> {
+    for (_, func) in &mut module.functions {
+        run_on_function(func);
+    }
+    Ok(())
+}
+
+pub fn run_on_function(function: &mut Function<AArch64>) {
+    let mut worklist = vec![];
+    let mut map: FxHashMap<Reg, Vec<(OperandData, BasicBlockId)>> = FxHashMap::default();
+
+    for block_id in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block_id) {
+            let inst = function.data.inst_ref(inst_id);
+            if !matches!(inst.data.opcode, Opcode::Phi) {
+                continue;
+            }
+            worklist.push(inst_id);
+            let output = *inst.data.operands[0].data.as_reg();
+            for i in (0..inst.data.operands[1..].len()).step_by(2) {
+                let val = inst.data.operands[1 + i].data.clone();
+                let block = *inst.data.operands[1 + i + 1].data.as_block();
+                map.entry(output).or_default().push((val, block));
+            }
+        }
+    }
+
+    for (output, args) in map {
+        let is_64 = output.0 == RegClass::GR64 as u16;
+        for (arg, block) in args {
+            // Copies go before the branches ending the block. They don't
+            // change the flags a conditional branch tests.
+            let mut first_branch = function.layout.last_inst_of(block).unwrap();
+            while let Some(prev) = function.layout.prev_inst_of(first_branch) {
+                if !matches!(
+                    function.data.inst_ref(prev).data.opcode,
+                    Opcode::B
+                        | Opcode::BEQ
+                        | Opcode::BNE
+                        | Opcode::BLE
+                        | Opcode::BLT
+                        | Opcode::BGE
+                        | Opcode::BGT
+                ) {
+                    break;
+                }
+                first_branch = prev;
+            }
+            let copy = match arg {
+                OperandData::Int32(_) | OperandData::Int64(_) => Instruction::new(
+                    InstructionData {
+                        opcode: if is_64 {
+                            Opcode::MOVri64
+                        } else {
+                            Opcode::MOVri32
+                        },
+                        operands: vec![
+                            Operand::output(OperandData::Reg(output)),
+                            Operand::new(arg),
+                        ],
+                    },
+                    block,
+                ),
+                OperandData::Reg(_) => Instruction::new(
+                    InstructionData {
+                        opcode: if is_64 {
+                            Opcode::MOVrr64
+                        } else {
+                            Opcode::MOVrr32
+                        },
+                        operands: vec![
+                            Operand::output(OperandData::Reg(output)),
+                            Operand::input(arg),
+                        ],
+                    },
+                    block,
+                ),
+                _ => unreachable!("arguments of phis are registers or immediates"),
+            };
+            let copy = function.data.create_inst(copy);
+            function
+                .layout
+                .insert_inst_before(first_branch, copy, block);
+        }
+    }
+
+    for inst_id in worklist {
+        function.remove_inst(inst_id);
+    }
+}
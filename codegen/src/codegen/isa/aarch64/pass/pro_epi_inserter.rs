@@ -0,0 +1,202 @@
+use crate::codegen::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{CfiDirective, Instruction, InstructionId},
+        slot::SlotId,
+        Function,
+    },
+    isa::aarch64::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        register::GR64,
+        AArch64,
+    },
+    module::Module,
+};
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+
+pub fn run_on_module(module: &mut Module<AArch64>) -> Result<()> {
+    for (_, func) in &mut module.functions {
+        run_on_function(func, module.emit_cfi);
+    }
+    Ok(())
+}
+
+/// Inserts the prologue, which saves the frame pointer and the link register
+/// and allocates the slots below them, and an epilogue before each `ret`.
+///
+/// ```text
+/// stp x29, x30, [sp, #-16]!
+/// mov x29, sp
+/// sub sp, sp, #frame_size
+/// ...
+/// add sp, sp, #frame_size
+/// ldp x29, x30, [sp], #16
+/// ret
+/// ```
+///
+/// With `emit_cfi`, call frame information describes the frame, whose CFA is
+/// kept at `x29 + 16` from the `mov` of the prologue to the `ldp` of each
+/// epilogue.
+pub fn run_on_function(function: &mut Function<AArch64>, emit_cfi: bool) {
+    let adj = frame_size(function) as i32;
+
+    // insert prologue
+    if let Some(entry) = function.layout.first_block {
+        if adj > 0 {
+            let sub = function.data.create_inst(Instruction::new(
+                InstructionData {
+                    opcode: Opcode::SUBri64,
+                    operands: vec![
+                        Operand::output(OperandData::Reg(GR64::SP.into())),
+                        Operand::input(OperandData::Reg(GR64::SP.into())),
+                        Operand::new(OperandData::Int32(adj)),
+                    ],
+                },
+                entry,
+            ));
+            function.layout.insert_inst_at_start(sub, entry);
+        }
+        let mov = function.data.create_inst(Instruction::new(
+            InstructionData {
+                opcode: Opcode::MOVrr64,
+                operands: vec![
+                    Operand::output(OperandData::Reg(GR64::X29.into())),
+                    Operand::input(OperandData::Reg(GR64::SP.into())),
+                ],
+            },
+            entry,
+        ));
+        function.layout.insert_inst_at_start(mov, entry);
+        let stp = function.data.create_inst(Instruction::new(
+            InstructionData {
+                opcode: Opcode::STPpre64,
+                operands: vec![
+                    Operand::input(OperandData::Reg(GR64::X29.into())),
+                    Operand::input(OperandData::Reg(GR64::X30.into())),
+                ],
+            },
+            entry,
+        ));
+        function.layout.insert_inst_at_start(stp, entry);
+
+        if emit_cfi {
+            let start = cfi(function, CfiDirective::StartProc, entry);
+            function.layout.insert_inst_at_start(start, entry);
+            for directive in [
+                CfiDirective::Offset(GR64::X29.into(), -16),
+                CfiDirective::Offset(GR64::X30.into(), -8),
+                CfiDirective::DefCfaOffset(16),
+            ] {
+                let inst = cfi(function, directive, entry);
+                function.layout.insert_inst_after(stp, inst, entry);
+            }
+            let inst = cfi(function, CfiDirective::DefCfa(GR64::X29.into(), 16), entry);
+            function.layout.insert_inst_after(mov, inst, entry);
+        }
+    }
+
+    // insert epilogue
+    let mut epilogues = vec![];
+    for block in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block) {
+            let inst = function.data.inst_ref(inst_id);
+            if !matches!(inst.data.opcode, Opcode::RET) {
+                continue;
+            }
+            epilogues.push((block, inst_id));
+        }
+    }
+    let last_inst = function
+        .layout
+        .last_block
+        .and_then(|block| function.layout.last_inst_of(block));
+    for (block, ret_id) in epilogues {
+        if adj > 0 {
+            let add = function.data.create_inst(Instruction::new(
+                InstructionData {
+                    opcode: Opcode::ADDri64,
+                    operands: vec![
+                        Operand::output(OperandData::Reg(GR64::SP.into())),
+                        Operand::input(OperandData::Reg(GR64::SP.into())),
+                        Operand::new(OperandData::Int32(adj)),
+                    ],
+                },
+                block,
+            ));
+            function.layout.insert_inst_before(ret_id, add, block);
+        }
+        let ldp = function.data.create_inst(Instruction::new(
+            InstructionData {
+                opcode: Opcode::LDPpost64,
+                operands: vec![
+                    Operand::output(OperandData::Reg(GR64::X29.into())),
+                    Operand::output(OperandData::Reg(GR64::X30.into())),
+                ],
+            },
+            block,
+        ));
+        function.layout.insert_inst_before(ret_id, ldp, block);
+
+        if emit_cfi {
+            for directive in [
+                CfiDirective::Restore(GR64::X29.into()),
+                CfiDirective::Restore(GR64::X30.into()),
+                CfiDirective::DefCfa(GR64::SP.into(), 0),
+            ] {
+                let inst = cfi(function, directive, block);
+                function.layout.insert_inst_after(ldp, inst, block);
+            }
+            // Code after this epilogue is still in the frame.
+            if last_inst != Some(ret_id) {
+                let remember = cfi(function, CfiDirective::RememberState, block);
+                function.layout.insert_inst_before(ldp, remember, block);
+                let restore = cfi(function, CfiDirective::RestoreState, block);
+                function.layout.insert_inst_after(ret_id, restore, block);
+            }
+        }
+    }
+
+    if let (true, Some(last_block)) = (emit_cfi, function.layout.last_block) {
+        let end = cfi(function, CfiDirective::EndProc, last_block);
+        function.layout.append_inst(end, last_block);
+    }
+}
+
+fn cfi(
+    function: &mut Function<AArch64>,
+    directive: CfiDirective,
+    block: BasicBlockId,
+) -> InstructionId<InstructionData> {
+    function.data.create_inst(Instruction::new(
+        InstructionData {
+            opcode: Opcode::CFI,
+            operands: vec![Operand::new(OperandData::Cfi(directive))],
+        },
+        block,
+    ))
+}
+
+/// Returns the size of the slots, rounded up to keep `sp` 16-byte aligned.
+pub fn frame_size(function: &Function<AArch64>) -> u32 {
+    slot_offsets(function).1
+}
+
+/// Returns the offsets of the slots below the frame pointer, laid out in the
+/// order they were created and each aligned to its size up to 8 bytes, so
+/// that loads and stores of them take scaled offsets, and the size of the
+/// frame.
+pub fn slot_offsets(function: &Function<AArch64>) -> (FxHashMap<SlotId, u32>, u32) {
+    let mut offsets = FxHashMap::default();
+    let mut offset = 0;
+    for (id, slot) in function.slots.iter() {
+        let align = slot.size().next_power_of_two().min(8) as i32;
+        offset = roundup(offset + slot.size() as i32, align);
+        offsets.insert(id, offset as u32);
+    }
+    (offsets, roundup(offset, 16) as u32)
+}
+
+fn roundup(n: i32, align: i32) -> i32 {
+    (n + align - 1) & !(align - 1)
+}
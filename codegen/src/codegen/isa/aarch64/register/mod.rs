@@ -0,0 +1,243 @@
+use crate::codegen::{
+    call_conv::CallConvKind,
+    register::{Reg, RegUnit, RegisterClass, RegisterInfo},
+};
+use std::fmt;
+use vicis_core::ir::types::{self, Type, Types};
+
+pub struct RegInfo;
+
+#[derive(Clone, Copy)]
+pub enum GR32 {
+    W0,
+    W1,
+    W2,
+    W3,
+    W4,
+    W5,
+    W6,
+    W7,
+    W8,
+    W9,
+    W10,
+    W11,
+    W12,
+    W13,
+    W14,
+    W15,
+    W16,
+    W17,
+    W18,
+    W19,
+    W20,
+    W21,
+    W22,
+    W23,
+    W24,
+    W25,
+    W26,
+    W27,
+    W28,
+    W29,
+    W30,
+}
+
+#[derive(Clone, Copy)]
+pub enum GR64 {
+    X0,
+    X1,
+    X2,
+    X3,
+    X4,
+    X5,
+    X6,
+    X7,
+    X8,
+    X9,
+    X10,
+    X11,
+    X12,
+    X13,
+    X14,
+    X15,
+    X16,
+    X17,
+    X18,
+    X19,
+    X20,
+    X21,
+    X22,
+    X23,
+    X24,
+    X25,
+    X26,
+    X27,
+    X28,
+    /// The frame pointer.
+    X29,
+    /// The link register.
+    X30,
+    SP,
+}
+
+pub enum RegClass {
+    GR32,
+    GR64,
+}
+
+impl From<GR32> for Reg {
+    fn from(r: GR32) -> Self {
+        Reg(RegClass::GR32 as u16, r as u16)
+    }
+}
+
+impl From<GR64> for Reg {
+    fn from(r: GR64) -> Self {
+        Reg(RegClass::GR64 as u16, r as u16)
+    }
+}
+
+impl From<GR32> for RegUnit {
+    fn from(r: GR32) -> Self {
+        RegUnit(RegClass::GR64 as u16, r as u16)
+    }
+}
+
+impl From<GR64> for RegUnit {
+    fn from(r: GR64) -> Self {
+        RegUnit(RegClass::GR64 as u16, r as u16)
+    }
+}
+
+const ARG_REGS: [RegUnit; 8] = [
+    RegUnit(RegClass::GR64 as u16, GR64::X0 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X1 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X2 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X3 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X4 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X5 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X6 as u16),
+    RegUnit(RegClass::GR64 as u16, GR64::X7 as u16),
+];
+
+impl RegisterInfo for RegInfo {
+    fn arg_reg_list(cc: &CallConvKind) -> &'static [RegUnit] {
+        match cc {
+            CallConvKind::AAPCS64 => &ARG_REGS,
+            CallConvKind::SystemV => panic!("System V is not a calling convention of AArch64"),
+            CallConvKind::Wasm => panic!("Wasm is not a calling convention of AArch64"),
+            CallConvKind::Cdecl => panic!("cdecl is not a calling convention of AArch64"),
+        }
+    }
+
+    fn to_reg_unit(r: Reg) -> RegUnit {
+        match r {
+            Reg(/*GR32*/ 0, x) => RegUnit(RegClass::GR64 as u16, x),
+            Reg(/*GR64*/ 1, x) => RegUnit(RegClass::GR64 as u16, x),
+            _ => panic!(),
+        }
+    }
+}
+
+impl RegisterClass for RegClass {
+    /// Returns the class of the registers holding `ty`. The lowering only
+    /// creates virtual registers of the types [`is_held_in_register`] accepts.
+    fn for_type(types: &Types, ty: Type) -> Self {
+        match ty {
+            types::I1 | types::I8 | types::I16 | types::I32 => RegClass::GR32,
+            types::I64 => RegClass::GR64,
+            _ if ty.is_pointer(types) => RegClass::GR64,
+            _ => unreachable!("no register holds {}", types.to_string(ty)),
+        }
+    }
+
+    fn gpr_list(&self) -> Vec<Reg> {
+        // Only the temporaries x9 to x15 are allocated. They are caller-saved
+        // and not used to pass arguments, so neither calls nor the prologue
+        // need to care about them.
+        match self {
+            RegClass::GR32 => vec![
+                GR32::W9,
+                GR32::W10,
+                GR32::W11,
+                GR32::W12,
+                GR32::W13,
+                GR32::W14,
+                GR32::W15,
+            ]
+            .into_iter()
+            .map(|r| r.into())
+            .collect(),
+            RegClass::GR64 => vec![
+                GR64::X9,
+                GR64::X10,
+                GR64::X11,
+                GR64::X12,
+                GR64::X13,
+                GR64::X14,
+                GR64::X15,
+            ]
+            .into_iter()
+            .map(|r| r.into())
+            .collect(),
+        }
+    }
+
+    fn apply_for(&self, ru: RegUnit) -> Reg {
+        match self {
+            Self::GR32 => Reg(RegClass::GR32 as u16, ru.1),
+            Self::GR64 => Reg(RegClass::GR64 as u16, ru.1),
+        }
+    }
+}
+
+impl fmt::Debug for GR64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", reg_to_str(&(*self).into()))
+    }
+}
+
+impl fmt::Display for GR64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl fmt::Debug for GR32 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", reg_to_str(&(*self).into()))
+    }
+}
+
+impl fmt::Display for GR32 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Returns whether values of `ty` are held in general-purpose registers:
+/// integers of up to 64 bits and pointers.
+pub fn is_held_in_register(types: &Types, ty: Type) -> bool {
+    matches!(
+        ty,
+        types::I1 | types::I8 | types::I16 | types::I32 | types::I64
+    ) || ty.is_pointer(types)
+}
+
+pub fn reg_to_str(r: &Reg) -> &'static str {
+    let gr32 = [
+        "w0", "w1", "w2", "w3", "w4", "w5", "w6", "w7", "w8", "w9", "w10", "w11", "w12", "w13",
+        "w14", "w15", "w16", "w17", "w18", "w19", "w20", "w21", "w22", "w23", "w24", "w25", "w26",
+        "w27", "w28", "w29", "w30",
+    ];
+    let gr64 = [
+        "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13",
+        "x14", "x15", "x16", "x17", "x18", "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26",
+        "x27", "x28", "x29", "x30", "sp",
+    ];
+    match r {
+        Reg(0, i) => gr32[*i as usize],
+        Reg(1, i) => gr64[*i as usize],
+        e => unreachable!("{:?} is not a register of AArch64", e),
+    }
+}
@@ -0,0 +1,102 @@
+pub mod register;
+
+use super::{ModulePass, TargetIsa};
+use crate::codegen::lower::expand::ExpandHooks;
+use crate::codegen::{
+    cache::PrintFunction,
+    call_conv::CallConvKind,
+    function::Function,
+    isa::x86_64::{self, instruction, pass, X86Family},
+    module::Module,
+    options::{AsmSyntax, RelocModel},
+    pass::{private_symbols, regalloc},
+    register::Reg,
+};
+use anyhow::{bail, Result};
+use register::{RegClass, GR32};
+use std::fmt;
+use vicis_core::ir::{
+    module::data_layout::DataLayout,
+    types::{Type, Types},
+};
+
+/// 32-bit x86, with the cdecl calling convention. Only 32-bit registers are
+/// used, and pointers fit in them.
+///
+/// The instructions, the lowering, the passes and the printer of
+/// [`x86_64`](super::x86_64) are reused.
+#[derive(Copy, Clone)]
+pub struct I686;
+
+impl TargetIsa for I686 {
+    type InstInfo = instruction::InstructionInfo;
+    type Lower = x86_64::lower::Lower;
+    type RegClass = register::RegClass;
+    type RegInfo = register::RegInfo;
+
+    fn module_pass_list() -> Vec<ModulePass<Self>> {
+        vec![
+            regalloc::run_on_module,
+            pass::phi_elimination::run_on_module, // TODO: should be target independent
+            pass::simple_reg_coalescing::run_on_module,
+            pass::eliminate_slot::run_on_module,
+            pass::pro_epi_inserter::run_on_module,
+            check_reloc_model,
+            private_symbols::run_on_module,
+        ]
+    }
+
+    fn default_call_conv() -> CallConvKind {
+        CallConvKind::Cdecl
+    }
+
+    fn data_layout() -> DataLayout {
+        "e-m:e-p:32:32-p270:32:32-p271:32:32-p272:64:64-i64:32:64-i128:128-f64:32:64-f80:32-n8:16:32-S128"
+            .parse()
+            .unwrap()
+    }
+}
+
+impl X86Family for I686 {
+    const WORD_SIZE: u32 = 4;
+    const SP: Reg = Reg(RegClass::GR32 as u16, GR32::ESP as u16);
+    const FP: Reg = Reg(RegClass::GR32 as u16, GR32::EBP as u16);
+}
+
+/// Rejects position-independent code, which needs a register holding the
+/// address of the GOT on i686.
+fn check_reloc_model(module: &mut Module<I686>) -> Result<()> {
+    if module.reloc_model == RelocModel::Pic {
+        bail!("position-independent code is not supported for i686")
+    }
+    Ok(())
+}
+
+impl ExpandHooks for I686 {
+    fn size_of(&self, types: &Types, ty: Type) -> u32 {
+        Self::type_size(types, ty)
+    }
+
+    // Without 64-bit registers, `i64` values are not loaded or stored.
+    fn max_copy_bits(&self) -> u32 {
+        32
+    }
+}
+
+impl PrintFunction for I686 {
+    fn print_function(func: &Function<Self>, fn_idx: usize, syntax: AsmSyntax) -> String {
+        struct Printer<'a>(&'a Function<I686>, usize, AsmSyntax);
+        impl fmt::Display for Printer<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                x86_64::asm::print_function(f, self.0, self.1, self.2)
+            }
+        }
+        Printer(func, fn_idx, syntax).to_string()
+    }
+}
+
+impl fmt::Display for Module<I686> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        x86_64::asm::print(f, self)
+    }
+}
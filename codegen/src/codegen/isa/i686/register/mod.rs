@@ -0,0 +1,59 @@
+use crate::codegen::{
+    call_conv::CallConvKind,
+    register::{Reg, RegUnit, RegisterClass, RegisterInfo},
+};
+use vicis_core::ir::types::{self, Type, Types};
+
+pub use crate::codegen::isa::x86_64::register::GR32;
+
+pub struct RegInfo;
+
+/// The only register class. Its registers are numbered as the 32-bit
+/// registers of x86-64, so that they are printed the same way.
+pub enum RegClass {
+    GR32,
+}
+
+impl RegisterInfo for RegInfo {
+    fn arg_reg_list(cc: &CallConvKind) -> &'static [RegUnit] {
+        match cc {
+            CallConvKind::Cdecl => &[],
+            CallConvKind::SystemV => panic!("System V is not a calling convention of i686"),
+            CallConvKind::AAPCS64 => panic!("AAPCS64 is not a calling convention of i686"),
+            CallConvKind::Wasm => panic!("Wasm is not a calling convention of i686"),
+        }
+    }
+
+    fn to_reg_unit(r: Reg) -> RegUnit {
+        match r {
+            Reg(/*GR32*/ 0, x) => RegUnit(RegClass::GR32 as u16, x),
+            _ => panic!(),
+        }
+    }
+}
+
+impl RegisterClass for RegClass {
+    fn for_type(types: &Types, ty: Type) -> Self {
+        match ty {
+            types::I1 | types::I8 | types::I16 | types::I32 => RegClass::GR32,
+            _ if ty.is_pointer(types) => RegClass::GR32,
+            _ => todo!(),
+        }
+    }
+
+    fn gpr_list(&self) -> Vec<Reg> {
+        match self {
+            // TODO: Add more general-purpose registers
+            RegClass::GR32 => vec![GR32::EAX, GR32::ECX, GR32::EDX]
+                .into_iter()
+                .map(|r| r.into())
+                .collect(),
+        }
+    }
+
+    fn apply_for(&self, ru: RegUnit) -> Reg {
+        match self {
+            Self::GR32 => Reg(RegClass::GR32 as u16, ru.1),
+        }
+    }
+}
@@ -1,22 +1,35 @@
+pub mod aarch64;
+pub mod i686;
+pub mod wasm32;
 pub mod x86_64;
 
 use crate::codegen::{
     call_conv::CallConvKind,
     function::instruction::InstructionInfo,
-    lower,
+    lower::{self, expand::ExpandHooks},
     module::Module,
     register::{RegisterClass, RegisterInfo},
 };
 use anyhow::Result;
-use vicis_core::ir::types::{Type, Types};
+use vicis_core::ir::{
+    module::data_layout::DataLayout,
+    types::{Type, Types},
+};
+
+pub type ModulePass<T> = fn(&mut Module<T>) -> Result<()>;
 
-pub trait TargetIsa: Copy {
+pub trait TargetIsa: Copy + ExpandHooks {
     type InstInfo: InstructionInfo;
     type RegClass: RegisterClass;
     type RegInfo: RegisterInfo;
     type Lower: lower::Lower<Self>;
 
-    fn module_pass_list() -> Vec<fn(&mut Module<Self>) -> Result<()>>;
+    fn module_pass_list() -> Vec<ModulePass<Self>>;
     fn default_call_conv() -> CallConvKind;
-    fn type_size(types: &Types, ty: Type) -> u32;
+    fn data_layout() -> DataLayout;
+
+    /// Returns the size of `ty` in bytes, laid out by [`TargetIsa::data_layout`].
+    fn type_size(types: &Types, ty: Type) -> u32 {
+        types.size_and_align(ty, &Self::data_layout()).0
+    }
 }
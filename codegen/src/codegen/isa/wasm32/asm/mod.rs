@@ -0,0 +1,482 @@
+use crate::codegen::{
+    cache::PrintFunction,
+    function::{instruction::InstructionData as _, Function},
+    isa::{
+        wasm32::{
+            instruction::{InstructionData, Opcode, Operand, OperandData},
+            pass::stack_frame::STACK_POINTER,
+            register::RegClass,
+            structurize::{structurize, Label, Structured},
+            Wasm32,
+        },
+        TargetIsa,
+    },
+    module::Module,
+    options::AsmSyntax,
+    register::{RegisterClass, VReg},
+};
+use rustc_hash::FxHashMap;
+use std::{collections::BTreeSet, fmt};
+use vicis_core::ir::{
+    module::name::Name,
+    types::{Type, Types},
+    value::{ConstantData, ConstantExpr},
+};
+
+/// Where global variables start in the linear memory. Lower addresses are
+/// left unused, so that null pointers point to nothing.
+const GLOBAL_BASE: u32 = 1024;
+
+/// The size of the stack, placed after global variables.
+const STACK_SIZE: u32 = 64 * 1024;
+
+const PAGE_SIZE: u32 = 64 * 1024;
+
+/// Prints the module in the WebAssembly text format.
+pub fn print(f: &mut fmt::Formatter<'_>, module: &Module<Wasm32>) -> fmt::Result {
+    writeln!(f, "(module")?;
+
+    // Functions reused from a cache are left as prototypes, but defined.
+    for (i, (_, func)) in module.functions.iter().enumerate() {
+        if func.is_prototype && !module.precompiled.contains_key(&i) {
+            writeln!(
+                f,
+                "  (import \"env\" \"{}\" (func ${}{}))",
+                func.name,
+                func.name,
+                signature(func)
+            )?;
+        }
+    }
+
+    let addrs = global_addresses(module);
+    let data_end = module
+        .global_variables
+        .values()
+        .map(|gv| addrs[&gv.name] + Wasm32::type_size(&module.types, gv.ty))
+        .max()
+        .unwrap_or(GLOBAL_BASE);
+    let stack_top = data_end.next_multiple_of(16) + STACK_SIZE;
+    writeln!(
+        f,
+        "  (memory (export \"memory\") {})",
+        stack_top.div_ceil(PAGE_SIZE)
+    )?;
+    writeln!(
+        f,
+        "  (global ${} (mut i32) (i32.const {}))",
+        STACK_POINTER, stack_top
+    )?;
+
+    for gv in module.global_variables.values() {
+        let addr = addrs[&gv.name];
+        writeln!(f, "  (global ${} i32 (i32.const {}))", gv.name, addr)?;
+        let mut bytes = vec![];
+        if let Some(init) = &gv.init {
+            write_init(&mut bytes, &module.types, gv.ty, init, &addrs);
+        }
+        if bytes.iter().any(|&b| b != 0) {
+            writeln!(f, "  (data (i32.const {}) \"{}\")", addr, escape(&bytes))?;
+        }
+    }
+
+    for (i, (_, func)) in module.functions.iter().enumerate() {
+        match module.precompiled.get(&i) {
+            Some(wat) => f.write_str(wat)?,
+            None => print_function(f, func, i)?,
+        }
+    }
+
+    writeln!(f, ")")
+}
+
+pub fn print_function(
+    f: &mut fmt::Formatter<'_>,
+    function: &Function<Wasm32>,
+    _fn_idx: usize,
+) -> fmt::Result {
+    if function.is_prototype {
+        return Ok(());
+    }
+
+    writeln!(
+        f,
+        "  (func ${} (export \"{}\"){}",
+        function.name,
+        function.name,
+        signature(function)
+    )?;
+
+    // Parameters are the first virtual registers. The others are locals.
+    let mut locals = BTreeSet::new();
+    for block in function.layout.block_iter() {
+        for inst in function.layout.inst_iter(block) {
+            locals.extend(
+                function
+                    .data
+                    .inst_ref(inst)
+                    .data
+                    .all_vregs()
+                    .into_iter()
+                    .map(|vr| vr.0),
+            );
+        }
+    }
+    for vr in locals.range(function.params.len() as u32..) {
+        let ty = function.data.vregs.type_for(VReg(*vr));
+        writeln!(
+            f,
+            "    (local $v{} {})",
+            vr,
+            RegClass::for_type(&function.types, ty).val_type()
+        )?;
+    }
+
+    let body = structurize(function);
+    print_structured(f, function, &body, 4)?;
+    // The end of a construct can't be reached, but it leaves nothing to return.
+    if !function.result_ty.is_void()
+        && matches!(
+            body.last(),
+            Some(Structured::Block(..) | Structured::Loop(..) | Structured::If(..))
+        )
+    {
+        writeln!(f, "    unreachable")?;
+    }
+
+    writeln!(f, "  )")
+}
+
+fn print_structured(
+    f: &mut fmt::Formatter<'_>,
+    function: &Function<Wasm32>,
+    code: &[Structured],
+    indent: usize,
+) -> fmt::Result {
+    let pad = " ".repeat(indent);
+    for s in code {
+        match s {
+            Structured::Block(block, body) => {
+                writeln!(f, "{}(block $B{}", pad, block.index())?;
+                print_structured(f, function, body, indent + 2)?;
+                writeln!(f, "{})", pad)?;
+            }
+            Structured::Loop(block, body) => {
+                writeln!(f, "{}(loop $L{}", pad, block.index())?;
+                print_structured(f, function, body, indent + 2)?;
+                writeln!(f, "{})", pad)?;
+            }
+            Structured::If(cond, then, else_) => {
+                writeln!(f, "{}(if {}", pad, operand_to_string(cond))?;
+                writeln!(f, "{}  (then", pad)?;
+                print_structured(f, function, then, indent + 4)?;
+                writeln!(f, "{}  )", pad)?;
+                writeln!(f, "{}  (else", pad)?;
+                print_structured(f, function, else_, indent + 4)?;
+                writeln!(f, "{}  )", pad)?;
+                writeln!(f, "{})", pad)?;
+            }
+            Structured::Code(block) => {
+                for inst in function.layout.inst_iter(*block) {
+                    let inst = &function.data.inst_ref(inst).data;
+                    if matches!(inst.opcode, Opcode::Br | Opcode::BrIf) {
+                        continue;
+                    }
+                    writeln!(f, "{}{}", pad, inst_to_string(inst))?;
+                }
+            }
+            Structured::Br(Label::Block(block)) => writeln!(f, "{}(br $B{})", pad, block.index())?,
+            Structured::Br(Label::Loop(block)) => writeln!(f, "{}(br $L{})", pad, block.index())?,
+        }
+    }
+    Ok(())
+}
+
+/// Returns the instruction as a folded expression.
+fn inst_to_string(inst: &InstructionData) -> String {
+    let mut output = None;
+    let mut inputs = vec![];
+    let mut mem = None;
+    let mut i = 0;
+    while i < inst.operands.len() {
+        let operand = &inst.operands[i];
+        match operand.data {
+            OperandData::MemStart => {
+                mem = Some(&inst.operands[i + 1..i + 4]);
+                i += 4;
+                continue;
+            }
+            OperandData::VReg(vr) if operand.output => output = Some(vr),
+            _ if operand.input => inputs.push(operand_to_string(&operand.data)),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let expr = match inst.opcode {
+        Opcode::Copy => inputs[0].clone(),
+        Opcode::Lea => {
+            let (offset, base) = mem_op(mem.unwrap());
+            if offset == 0 {
+                base
+            } else {
+                format!("(i32.add {} (i32.const {}))", base, offset)
+            }
+        }
+        Opcode::I32Load | Opcode::I64Load => {
+            let (offset, base) = mem_op(mem.unwrap());
+            format!("({}{} {})", inst.opcode, offset_to_string(offset), base)
+        }
+        Opcode::I32Store | Opcode::I64Store => {
+            let (offset, base) = mem_op(mem.unwrap());
+            format!(
+                "({}{} {} {})",
+                inst.opcode,
+                offset_to_string(offset),
+                base,
+                inputs[0]
+            )
+        }
+        Opcode::GlobalSet => {
+            let OperandData::Global(name) = &inst.operands[0].data else {
+                unreachable!()
+            };
+            format!("(global.set ${} {})", name, inputs[0])
+        }
+        Opcode::Call => {
+            let name = inst
+                .operands
+                .iter()
+                .find_map(|op| match &op.data {
+                    OperandData::Label(name) => Some(name),
+                    _ => None,
+                })
+                .unwrap();
+            let mut s = format!("(call ${}", name);
+            for input in &inputs {
+                s.push(' ');
+                s.push_str(input);
+            }
+            s + ")"
+        }
+        Opcode::Return if inputs.is_empty() => "(return)".to_string(),
+        Opcode::Return => format!("(return {})", inputs[0]),
+        Opcode::Br | Opcode::BrIf | Opcode::Phi => unreachable!(),
+        _ => format!("({} {})", inst.opcode, inputs.join(" ")),
+    };
+
+    match output {
+        Some(vr) => format!("(local.set $v{} {})", vr.0, expr),
+        None => expr,
+    }
+}
+
+impl PrintFunction for Wasm32 {
+    fn print_function(func: &Function<Self>, fn_idx: usize, _syntax: AsmSyntax) -> String {
+        struct Printer<'a>(&'a Function<Wasm32>, usize);
+        impl fmt::Display for Printer<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                print_function(f, self.0, self.1)
+            }
+        }
+        Printer(func, fn_idx).to_string()
+    }
+}
+
+impl fmt::Display for Module<Wasm32> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        print(f, self)
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::I32Add => "i32.add",
+                Self::I32Sub => "i32.sub",
+                Self::I32Mul => "i32.mul",
+                Self::I64Add => "i64.add",
+                Self::I64Sub => "i64.sub",
+                Self::I64Mul => "i64.mul",
+                Self::I32Eq => "i32.eq",
+                Self::I32Ne => "i32.ne",
+                Self::I32LtS => "i32.lt_s",
+                Self::I32LeS => "i32.le_s",
+                Self::I32GtS => "i32.gt_s",
+                Self::I32GeS => "i32.ge_s",
+                Self::I32LtU => "i32.lt_u",
+                Self::I32LeU => "i32.le_u",
+                Self::I32GtU => "i32.gt_u",
+                Self::I32GeU => "i32.ge_u",
+                Self::I64ExtendI32S => "i64.extend_i32_s",
+                Self::I32WrapI64 => "i32.wrap_i64",
+                Self::I32Load => "i32.load",
+                Self::I64Load => "i64.load",
+                Self::I32Store => "i32.store",
+                Self::I64Store => "i64.store",
+                Self::GlobalSet => "global.set",
+                Self::Call => "call",
+                Self::Return => "return",
+                Self::Br => "br",
+                Self::BrIf => "br_if",
+                Self::Copy | Self::Lea => "local.set",
+                Self::Phi => "PHI",
+            }
+        )
+    }
+}
+
+/// Returns the input as an expression pushing it.
+fn operand_to_string(op: &OperandData) -> String {
+    match op {
+        OperandData::VReg(r) => format!("(local.get $v{})", r.0),
+        OperandData::Int32(i) => format!("(i32.const {})", i),
+        OperandData::Int64(i) => format!("(i64.const {})", i),
+        OperandData::Global(name) => format!("(global.get ${})", name),
+        OperandData::Slot(slot) => format!("{:?}", slot),
+        OperandData::Block(block) => format!("$B{}", block.index()),
+        OperandData::Label(name) => format!("${}", name),
+        OperandData::MemStart => "".to_string(),
+        OperandData::None => "none".to_string(),
+    }
+}
+
+/// Returns the offset and the base address of a memory operand.
+fn mem_op(args: &[Operand]) -> (i32, String) {
+    assert!(matches!(&args[0].data, &OperandData::None)); // assure slot is eliminated
+    let offset = match args[1].data {
+        OperandData::Int32(imm) => imm,
+        _ => 0,
+    };
+    (offset, operand_to_string(&args[2].data))
+}
+
+fn offset_to_string(offset: i32) -> String {
+    if offset == 0 {
+        "".to_string()
+    } else {
+        format!(" offset={}", offset)
+    }
+}
+
+/// Returns the parameters and the result of `func`. Variadic arguments are
+/// passed by the address of a buffer holding them.
+fn signature(func: &Function<Wasm32>) -> String {
+    let mut s = String::new();
+    for (i, param) in func.params.iter().enumerate() {
+        s.push_str(&format!(
+            " (param $v{} {})",
+            i,
+            RegClass::for_type(&func.types, param.ty).val_type()
+        ));
+    }
+    if func.is_var_arg {
+        s.push_str(" (param $varargs i32)");
+    }
+    if !func.result_ty.is_void() {
+        s.push_str(&format!(
+            " (result {})",
+            RegClass::for_type(&func.types, func.result_ty).val_type()
+        ));
+    }
+    s
+}
+
+/// Returns the address of each global variable, laid out from
+/// [`GLOBAL_BASE`].
+fn global_addresses(module: &Module<Wasm32>) -> FxHashMap<Name, u32> {
+    let layout = Wasm32::data_layout();
+    let mut addrs = FxHashMap::default();
+    let mut addr = GLOBAL_BASE;
+    for gv in module.global_variables.values() {
+        let align = gv.align.max(layout.align_of(&module.types, gv.ty)).max(1);
+        addr = addr.next_multiple_of(align);
+        addrs.insert(gv.name, addr);
+        addr += Wasm32::type_size(&module.types, gv.ty);
+    }
+    addrs
+}
+
+/// Appends the little-endian bytes of `init`, a constant of `ty`.
+fn write_init(
+    bytes: &mut Vec<u8>,
+    types: &Types,
+    ty: Type,
+    init: &ConstantData,
+    addrs: &FxHashMap<Name, u32>,
+) {
+    let start = bytes.len();
+    let size = Wasm32::type_size(types, ty) as usize;
+    match init {
+        ConstantData::Int(int) => {
+            bytes.extend_from_slice(&int.cast_to_i64().to_le_bytes()[..size]);
+        }
+        ConstantData::Float(fp) => bytes.extend_from_slice(&fp.to_le_bytes()),
+        ConstantData::Array(arr) => {
+            for elem in &arr.elems {
+                write_init(bytes, types, arr.elem_ty, elem, addrs);
+            }
+        }
+        ConstantData::Vector(v) => {
+            for elem in &v.elems {
+                write_init(bytes, types, v.elem_ty, elem, addrs);
+            }
+        }
+        ConstantData::Struct(s) => {
+            let layout = Wasm32::data_layout();
+            for (i, (elem, &elem_ty)) in s.elems.iter().zip(s.elems_ty.iter()).enumerate() {
+                let offset = layout.field_offset(types, ty, i).unwrap() as usize;
+                bytes.resize(start + offset, 0);
+                write_init(bytes, types, elem_ty, elem, addrs);
+            }
+        }
+        ConstantData::GlobalRef(name) => {
+            bytes.extend_from_slice(&addrs[name].to_le_bytes());
+        }
+        ConstantData::Expr(ConstantExpr::Bitcast { tys, arg }) => {
+            write_init(bytes, types, tys[0], arg, addrs);
+        }
+        ConstantData::Expr(ConstantExpr::GetElementPtr { tys, args, .. }) => {
+            let ConstantData::GlobalRef(name) = &args[0] else {
+                todo!()
+            };
+            let layout = Wasm32::data_layout();
+            let mut offset = 0i64;
+            let mut elem_ty = tys[0];
+            for (i, idx) in args[1..].iter().enumerate() {
+                let idx = match idx {
+                    ConstantData::Int(int) => int.cast_to_i64(),
+                    _ => todo!(),
+                };
+                if i == 0 {
+                    offset += idx * Wasm32::type_size(types, elem_ty) as i64;
+                } else if types.is_struct(elem_ty) {
+                    offset += layout.field_offset(types, elem_ty, idx as usize).unwrap() as i64;
+                    elem_ty = types.base().element_at(elem_ty, idx as usize).unwrap();
+                } else {
+                    elem_ty = types.get_element(elem_ty).unwrap();
+                    offset += idx * Wasm32::type_size(types, elem_ty) as i64;
+                }
+            }
+            bytes.extend_from_slice(&((addrs[name] as i64 + offset) as u32).to_le_bytes());
+        }
+        ConstantData::AggregateZero | ConstantData::Null | ConstantData::Undef => {}
+    }
+    bytes.resize(start + size, 0);
+}
+
+/// Escapes `bytes` for a string of the text format.
+fn escape(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => s.push_str(&format!("\\{}", b as char)),
+            0x20..=0x7e => s.push(b as char),
+            _ => s.push_str(&format!("\\{:02x}", b)),
+        }
+    }
+    s
+}
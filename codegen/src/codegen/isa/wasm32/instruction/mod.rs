@@ -0,0 +1,358 @@
+use crate::codegen::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{Instruction, InstructionData as ID, InstructionId, InstructionInfo as II},
+        slot::SlotId,
+        Function,
+    },
+    isa::TargetIsa,
+    register::{Reg, VReg, VRegUsers},
+};
+use std::fmt;
+
+pub struct InstructionInfo;
+
+/// An instruction in register form: it reads its inputs, which are pushed on
+/// the operand stack when printed, and sets its output local.
+#[derive(Clone)]
+pub struct InstructionData {
+    pub opcode: Opcode,
+    pub operands: Vec<Operand>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Opcode {
+    /// Sets the output to the input: a local, a constant or a global.
+    Copy,
+    I32Add,
+    I32Sub,
+    I32Mul,
+    I64Add,
+    I64Sub,
+    I64Mul,
+    I32Eq,
+    I32Ne,
+    I32LtS,
+    I32LeS,
+    I32GtS,
+    I32GeS,
+    I32LtU,
+    I32LeU,
+    I32GtU,
+    I32GeU,
+    I64ExtendI32S,
+    I32WrapI64,
+    /// Computes the address of a memory operand.
+    Lea,
+    I32Load,
+    I64Load,
+    /// The stored value comes first, then the memory operand.
+    I32Store,
+    I64Store,
+    GlobalSet,
+    Call,
+    Return,
+    /// Branches to a block. Only printed as part of the structured control flow.
+    Br,
+    /// Branches to the first block if the input is not zero, and to the
+    /// second one otherwise. Only printed as part of the structured control
+    /// flow.
+    BrIf,
+
+    // TODO
+    Phi,
+}
+
+#[derive(Clone)]
+pub struct Operand {
+    pub data: OperandData,
+    pub input: bool,
+    pub output: bool,
+}
+
+#[derive(Clone)]
+pub enum OperandData {
+    VReg(VReg),
+    Int32(i32),
+    Int64(i64),
+    MemStart, // followed by: Slot, Imm (the offset), Base
+    Slot(SlotId),
+    Block(BasicBlockId),
+    Label(String),
+    /// A global of the module. Globals holding the addresses of global
+    /// variables are named after them.
+    Global(String),
+    None,
+}
+
+impl II for InstructionInfo {
+    type Data = InstructionData;
+
+    fn store_vreg_to_slot<T: TargetIsa>(
+        f: &Function<T>,
+        vreg: VReg,
+        slot: SlotId,
+        block: BasicBlockId,
+    ) -> Instruction<Self::Data> {
+        let ty = f.data.vregs.type_for(vreg);
+        Instruction::new(
+            InstructionData {
+                opcode: if ty.is_i64() {
+                    Opcode::I64Store
+                } else {
+                    Opcode::I32Store
+                },
+                operands: vec![
+                    Operand::input(vreg.into()),
+                    Operand::new(OperandData::MemStart),
+                    Operand::new(OperandData::Slot(slot)),
+                    Operand::new(OperandData::None),
+                    Operand::input(OperandData::None),
+                ],
+            },
+            block,
+        )
+    }
+
+    fn load_from_slot<T: TargetIsa>(
+        f: &Function<T>,
+        vreg: VReg,
+        slot: SlotId,
+        block: BasicBlockId,
+    ) -> Instruction<Self::Data> {
+        let ty = f.data.vregs.type_for(vreg);
+        Instruction::new(
+            InstructionData {
+                opcode: if ty.is_i64() {
+                    Opcode::I64Load
+                } else {
+                    Opcode::I32Load
+                },
+                operands: vec![
+                    Operand::output(vreg.into()),
+                    Operand::new(OperandData::MemStart),
+                    Operand::new(OperandData::Slot(slot)),
+                    Operand::new(OperandData::None),
+                    Operand::input(OperandData::None),
+                ],
+            },
+            block,
+        )
+    }
+}
+
+impl ID for InstructionData {
+    fn input_vregs(&self) -> Vec<VReg> {
+        let mut vrs = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::VReg(vr),
+                input: true,
+                ..
+            } = operand
+            {
+                vrs.push(*vr)
+            }
+        }
+        vrs
+    }
+
+    fn output_vregs(&self) -> Vec<VReg> {
+        let mut vrs = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::VReg(vr),
+                output: true,
+                ..
+            } = operand
+            {
+                vrs.push(*vr)
+            }
+        }
+        vrs
+    }
+
+    fn all_vregs(&self) -> Vec<VReg> {
+        let mut list = vec![];
+        for operand in &self.operands {
+            if let Operand {
+                data: OperandData::VReg(r),
+                ..
+            } = operand
+            {
+                list.push(*r)
+            }
+        }
+        list
+    }
+
+    fn input_regs(&self) -> Vec<Reg> {
+        vec![]
+    }
+
+    fn output_regs(&self) -> Vec<Reg> {
+        vec![]
+    }
+
+    fn all_regs(&self) -> Vec<Reg> {
+        vec![]
+    }
+
+    fn rewrite(&mut self, _vreg: VReg, _reg: Reg) {
+        unreachable!("wasm32 has no registers")
+    }
+
+    fn replace_vreg(
+        &mut self,
+        self_id: InstructionId<Self>,
+        users: &mut VRegUsers<Self>,
+        from: VReg,
+        to: VReg,
+    ) {
+        let u = users.remove_use(from, self_id).unwrap();
+        users.add_use(to, self_id, u.read, u.write);
+        for operand in &mut self.operands {
+            match operand.data {
+                OperandData::VReg(r) if r == from => operand.data = OperandData::VReg(to),
+                _ => {}
+            }
+        }
+    }
+
+    fn is_copy(&self) -> bool {
+        matches!(
+            (self.opcode, &self.operands[..]),
+            (
+                Opcode::Copy,
+                [
+                    _,
+                    Operand {
+                        data: OperandData::VReg(_),
+                        ..
+                    }
+                ]
+            )
+        )
+    }
+
+    fn is_call(&self) -> bool {
+        matches!(self.opcode, Opcode::Call)
+    }
+
+    fn imm_move(&self) -> Option<(Reg, i64)> {
+        None
+    }
+
+    fn pool_load(&self) -> Option<(Reg, usize)> {
+        None
+    }
+
+    fn rename_symbols(&mut self, rename: &dyn Fn(&str) -> Option<String>) {
+        for operand in &mut self.operands {
+            if let OperandData::Label(name) | OperandData::Global(name) = &mut operand.data {
+                if let Some(new) = rename(name) {
+                    *name = new;
+                }
+            }
+        }
+    }
+}
+
+impl Operand {
+    pub fn new(data: OperandData) -> Self {
+        Self {
+            data,
+            input: false,
+            output: false,
+        }
+    }
+
+    pub fn input(data: OperandData) -> Self {
+        Self {
+            data,
+            input: true,
+            output: false,
+        }
+    }
+
+    pub fn output(data: OperandData) -> Self {
+        Self {
+            data,
+            input: false,
+            output: true,
+        }
+    }
+}
+
+impl OperandData {
+    pub fn as_vreg(&self) -> &VReg {
+        match self {
+            Self::VReg(r) => r,
+            _ => todo!(),
+        }
+    }
+
+    pub fn as_block(&self) -> &BasicBlockId {
+        match self {
+            Self::Block(b) => b,
+            _ => todo!(),
+        }
+    }
+}
+
+impl From<VReg> for OperandData {
+    fn from(r: VReg) -> Self {
+        OperandData::VReg(r)
+    }
+}
+
+impl From<i32> for OperandData {
+    fn from(i: i32) -> Self {
+        OperandData::Int32(i)
+    }
+}
+
+impl From<i64> for OperandData {
+    fn from(i: i64) -> Self {
+        OperandData::Int64(i)
+    }
+}
+
+impl fmt::Debug for InstructionData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ", self.opcode)?;
+        for (i, op) in self.operands.iter().enumerate() {
+            write!(f, "{:?}", op)?;
+            if i < self.operands.len() - 1 {
+                write!(f, ", ")?
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.data)?;
+        if self.output {
+            write!(f, "<def>")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for OperandData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::VReg(vr) => write!(f, "%{}", vr.0),
+            Self::Int32(i) => write!(f, "{}", i),
+            Self::Int64(i) => write!(f, "{}", i),
+            Self::MemStart => write!(f, "$MemStart$"),
+            Self::Slot(slot) => write!(f, "slot.{}", slot.index()),
+            Self::Block(id) => write!(f, "block.{}", id.index()),
+            Self::Label(name) => write!(f, "{}", name),
+            Self::Global(name) => write!(f, "${}", name),
+            Self::None => write!(f, "none"),
+        }
+    }
+}
@@ -0,0 +1,41 @@
+use super::{class_of, new_empty_inst_output, ptr_mem, push};
+use crate::codegen::{
+    isa::wasm32::{
+        instruction::{Opcode, Operand as MOperand},
+        register::RegClass,
+        Wasm32,
+    },
+    lower::{LoweringContext, LoweringError},
+};
+use anyhow::Result;
+use vicis_core::ir::{function::instruction::InstructionId, types::Type, value::ValueId};
+
+pub fn lower_load(
+    ctx: &mut LoweringContext<Wasm32>,
+    id: InstructionId,
+    tys: &[Type],
+    addr: ValueId,
+    _align: u32,
+) -> Result<()> {
+    // Narrower integers would need `load8` and `load16`. TODO
+    let opcode = match class_of(ctx.types, tys[0])? {
+        _ if tys[0].is_i1() || tys[0].is_i8() || tys[0].is_i16() => {
+            return Err(LoweringError::Todo.into())
+        }
+        RegClass::I32 => Opcode::I32Load,
+        RegClass::I64 => Opcode::I64Load,
+    };
+
+    let mem = ptr_mem(ctx, addr)?;
+    let output = new_empty_inst_output(ctx, tys[0], id);
+    push(
+        ctx,
+        opcode,
+        vec![MOperand::output(output.into())]
+            .into_iter()
+            .chain(mem)
+            .collect(),
+    );
+
+    Ok(())
+}
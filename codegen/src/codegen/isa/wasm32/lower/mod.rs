@@ -0,0 +1,614 @@
+pub mod load;
+pub mod store;
+
+use crate::codegen::{
+    function::{instruction::Instruction as MachInstruction, slot::SlotId, slot::SlotOrigin},
+    isa::wasm32::{
+        instruction::{InstructionData, Opcode, Operand as MO, OperandData},
+        register::RegClass,
+        Wasm32,
+    },
+    isa::TargetIsa,
+    lower::{Lower as LowerTrait, LoweringContext, LoweringError},
+    register::VReg,
+};
+use anyhow::Result;
+use load::lower_load;
+use store::lower_store;
+use vicis_core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{
+            Alloca, Br, Call, Cast, CondBr, GetElementPtr, ICmp, ICmpCond,
+            Instruction as IrInstruction, InstructionId, IntBinary, Load, Opcode as IrOpcode,
+            Operand, Phi, Ret, Store,
+        },
+        Parameter,
+    },
+    module::name::Name,
+    types::{self, CompoundType, Type, Types},
+    value::{ConstantData, ConstantExpr, ConstantInt, Value, ValueId},
+};
+
+#[derive(Clone, Copy, Default)]
+pub struct Lower {}
+
+impl Lower {
+    pub fn new() -> Self {
+        Lower::default()
+    }
+}
+
+impl LowerTrait<Wasm32> for Lower {
+    fn lower(ctx: &mut LoweringContext<Wasm32>, inst: &IrInstruction) -> Result<()> {
+        lower(ctx, inst)
+    }
+
+    fn copy_args_to_vregs(ctx: &mut LoweringContext<Wasm32>, params: &[Parameter]) -> Result<()> {
+        // Parameters are locals already. Their virtual registers are created
+        // before any other, so that the `i`-th parameter is `VReg(i)`.
+        for (i, Parameter { ty, .. }) in params.iter().enumerate() {
+            class_of(ctx.types, *ty)?;
+            let vreg = ctx.mach_data.vregs.add_vreg_data(*ty);
+            debug_assert_eq!(vreg.0 as usize, i);
+            ctx.arg_idx_to_vreg.insert(i, vreg);
+        }
+        Ok(())
+    }
+}
+
+fn lower(ctx: &mut LoweringContext<Wasm32>, inst: &IrInstruction) -> Result<()> {
+    match inst.operand {
+        Operand::Alloca(Alloca {
+            ref tys,
+            ref num_elements,
+            align,
+        }) => lower_alloca(ctx, inst.id.unwrap(), tys, num_elements, align),
+        Operand::Phi(Phi {
+            ty,
+            ref args,
+            ref blocks,
+        }) => lower_phi(ctx, inst.id.unwrap(), ty, args, blocks),
+        Operand::Load(Load {
+            ref tys,
+            addr,
+            align,
+        }) => lower_load(ctx, inst.id.unwrap(), tys, addr, align),
+        Operand::Store(Store {
+            ref tys,
+            ref args,
+            align,
+        }) => lower_store(ctx, tys, args, align),
+        Operand::IntBinary(IntBinary { ty, ref args, .. }) => {
+            lower_bin(ctx, inst.id.unwrap(), inst.opcode, ty, args)
+        }
+        Operand::ICmp(ICmp { ty, args, cond }) => lower_icmp(ctx, inst.id.unwrap(), ty, args, cond),
+        Operand::Cast(Cast { ref tys, arg }) => {
+            lower_cast(ctx, inst.id.unwrap(), inst.opcode, tys, arg)
+        }
+        Operand::GetElementPtr(GetElementPtr { ref tys, .. }) => {
+            lower_gep(ctx, inst.id.unwrap(), tys[1])
+        }
+        Operand::Br(Br { block }) => lower_br(ctx, block),
+        Operand::CondBr(CondBr { arg, blocks }) => lower_condbr(ctx, arg, blocks),
+        Operand::Call(Call {
+            ref args, ref tys, ..
+        }) => lower_call(ctx, inst.id.unwrap(), tys, args),
+        Operand::Ret(Ret { val, ty }) => lower_return(ctx, ty, val),
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
+
+fn lower_alloca(
+    ctx: &mut LoweringContext<Wasm32>,
+    id: InstructionId,
+    tys: &[Type],
+    _num_elements: &ConstantData,
+    _align: u32,
+) -> Result<()> {
+    let slot_id = ctx.slots.add_slot(
+        tys[0],
+        Wasm32::type_size(ctx.types, tys[0]),
+        SlotOrigin::Alloca(id),
+    );
+    ctx.inst_id_to_slot_id.insert(id, slot_id);
+    Ok(())
+}
+
+fn lower_phi(
+    ctx: &mut LoweringContext<Wasm32>,
+    id: InstructionId,
+    ty: Type,
+    args: &[ValueId],
+    blocks: &[BasicBlockId],
+) -> Result<()> {
+    class_of(ctx.types, ty)?;
+    let output = new_empty_inst_output(ctx, ty, id);
+    let mut operands = vec![MO::output(output.into())];
+    for (arg, block) in args.iter().zip(blocks.iter()) {
+        operands.push(MO::input(val_to_operand_data(ctx, ty, *arg)?));
+        operands.push(MO::new(OperandData::Block(ctx.block_map[block])))
+    }
+    push(ctx, Opcode::Phi, operands);
+    Ok(())
+}
+
+fn lower_bin(
+    ctx: &mut LoweringContext<Wasm32>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    args: &[ValueId],
+) -> Result<()> {
+    // Narrower integers would need their upper bits cleared.
+    let opcode = match (op, ty) {
+        (IrOpcode::Add, types::I32) => Opcode::I32Add,
+        (IrOpcode::Sub, types::I32) => Opcode::I32Sub,
+        (IrOpcode::Mul, types::I32) => Opcode::I32Mul,
+        (IrOpcode::Add, types::I64) => Opcode::I64Add,
+        (IrOpcode::Sub, types::I64) => Opcode::I64Sub,
+        (IrOpcode::Mul, types::I64) => Opcode::I64Mul,
+        _ => return Err(LoweringError::Todo.into()),
+    };
+
+    let lhs = val_to_operand_data(ctx, ty, args[0])?;
+    let rhs = val_to_operand_data(ctx, ty, args[1])?;
+    let output = new_empty_inst_output(ctx, ty, id);
+    push(
+        ctx,
+        opcode,
+        vec![MO::output(output.into()), MO::input(lhs), MO::input(rhs)],
+    );
+
+    Ok(())
+}
+
+fn lower_icmp(
+    ctx: &mut LoweringContext<Wasm32>,
+    id: InstructionId,
+    ty: Type,
+    args: [ValueId; 2],
+    cond: ICmpCond,
+) -> Result<()> {
+    if class_of(ctx.types, ty)? != RegClass::I32 || ty.is_i1() || ty.is_i8() || ty.is_i16() {
+        return Err(LoweringError::Todo.into());
+    }
+
+    let opcode = match cond {
+        ICmpCond::Eq => Opcode::I32Eq,
+        ICmpCond::Ne => Opcode::I32Ne,
+        ICmpCond::Slt => Opcode::I32LtS,
+        ICmpCond::Sle => Opcode::I32LeS,
+        ICmpCond::Sgt => Opcode::I32GtS,
+        ICmpCond::Sge => Opcode::I32GeS,
+        ICmpCond::Ult => Opcode::I32LtU,
+        ICmpCond::Ule => Opcode::I32LeU,
+        ICmpCond::Ugt => Opcode::I32GtU,
+        ICmpCond::Uge => Opcode::I32GeU,
+    };
+
+    let lhs = val_to_operand_data(ctx, ty, args[0])?;
+    let rhs = val_to_operand_data(ctx, ty, args[1])?;
+    let output = new_empty_inst_output(ctx, types::I1, id);
+    push(
+        ctx,
+        opcode,
+        vec![MO::output(output.into()), MO::input(lhs), MO::input(rhs)],
+    );
+
+    Ok(())
+}
+
+fn lower_cast(
+    ctx: &mut LoweringContext<Wasm32>,
+    id: InstructionId,
+    op: IrOpcode,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    let [from, to] = *tys;
+    let opcode = match op {
+        IrOpcode::Sext if from.is_i32() && to.is_i64() => Opcode::I64ExtendI32S,
+        IrOpcode::Trunc if from.is_i64() && to.is_i32() => Opcode::I32WrapI64,
+        // Booleans are 0 or 1 in their locals already.
+        IrOpcode::Zext if from.is_i1() && to.is_i32() => Opcode::Copy,
+        IrOpcode::Bitcast if from.is_pointer(ctx.types) && to.is_pointer(ctx.types) => Opcode::Copy,
+        _ => return Err(LoweringError::Todo.into()),
+    };
+
+    let val = val_to_operand_data(ctx, from, arg)?;
+    let output = new_empty_inst_output(ctx, to, id);
+    push(ctx, opcode, vec![MO::output(output.into()), MO::input(val)]);
+
+    Ok(())
+}
+
+fn lower_gep(ctx: &mut LoweringContext<Wasm32>, id: InstructionId, ptr_ty: Type) -> Result<()> {
+    let mem = gep_mem(ctx, id)?;
+    let output = new_empty_inst_output(ctx, ptr_ty, id);
+    push(
+        ctx,
+        Opcode::Lea,
+        vec![MO::output(output.into())]
+            .into_iter()
+            .chain(mem)
+            .collect(),
+    );
+    Ok(())
+}
+
+fn lower_br(ctx: &mut LoweringContext<Wasm32>, block: BasicBlockId) -> Result<()> {
+    let block = ctx.block_map[&block];
+    push(ctx, Opcode::Br, vec![MO::new(OperandData::Block(block))]);
+    Ok(())
+}
+
+fn lower_condbr(
+    ctx: &mut LoweringContext<Wasm32>,
+    arg: ValueId,
+    blocks: [BasicBlockId; 2],
+) -> Result<()> {
+    let cond = val_to_operand_data(ctx, types::I1, arg)?;
+    let (then, else_) = (ctx.block_map[&blocks[0]], ctx.block_map[&blocks[1]]);
+    push(
+        ctx,
+        Opcode::BrIf,
+        vec![
+            MO::input(cond),
+            MO::new(OperandData::Block(then)),
+            MO::new(OperandData::Block(else_)),
+        ],
+    );
+    Ok(())
+}
+
+fn lower_call(
+    ctx: &mut LoweringContext<Wasm32>,
+    id: InstructionId,
+    tys: &[Type],
+    args: &[ValueId],
+) -> Result<()> {
+    let name = match &ctx.ir_data.values[args[0]] {
+        Value::Constant(ConstantData::GlobalRef(Name::Name(name))) => name.to_string(),
+        _ => return Err(LoweringError::Todo.into()),
+    };
+
+    // Calls to variadic functions are typed with the whole function type.
+    let result_ty = match ctx.types.get(tys[0]).as_deref() {
+        Some(CompoundType::Function(func_ty)) => func_ty.ret,
+        _ => tys[0],
+    };
+    let mut operands = vec![];
+    if !result_ty.is_void() {
+        class_of(ctx.types, result_ty)?;
+        let output = new_empty_inst_output(ctx, result_ty, id);
+        operands.push(MO::output(output.into()));
+    }
+    operands.push(MO::new(OperandData::Label(name)));
+    for (&arg, &ty) in args[1..].iter().zip(tys[1..].iter()) {
+        class_of(ctx.types, ty)?;
+        operands.push(MO::input(val_to_operand_data(ctx, ty, arg)?));
+    }
+    push(ctx, Opcode::Call, operands);
+
+    Ok(())
+}
+
+fn lower_return(ctx: &mut LoweringContext<Wasm32>, ty: Type, value: Option<ValueId>) -> Result<()> {
+    let mut operands = vec![];
+    if let Some(value) = value {
+        class_of(ctx.types, ty)?;
+        operands.push(MO::input(val_to_operand_data(ctx, ty, value)?));
+    }
+    push(ctx, Opcode::Return, operands);
+    Ok(())
+}
+
+/// Returns the memory operands of a slot, `imm` bytes into it.
+fn slot_mem(slot: SlotId, imm: i32) -> Vec<MO> {
+    vec![
+        MO::new(OperandData::MemStart),
+        MO::new(OperandData::Slot(slot)),
+        MO::new(OperandData::Int32(imm)),
+        MO::input(OperandData::None),
+    ]
+}
+
+/// Returns the memory operands of `imm` bytes after `base`, a local or a
+/// global.
+fn base_mem(ctx: &mut LoweringContext<Wasm32>, base: OperandData, imm: i64) -> Result<Vec<MO>> {
+    // Offsets of loads and stores are unsigned.
+    let (base, imm) = match i32::try_from(imm) {
+        Ok(imm) if imm >= 0 => (base, imm),
+        _ => {
+            let imm = i32::try_from(imm).map_err(|_| LoweringError::Todo)?;
+            let addr = ctx.mach_data.vregs.add_vreg_data(ptr_ty(ctx.types));
+            push(
+                ctx,
+                Opcode::I32Add,
+                vec![
+                    MO::output(addr.into()),
+                    MO::input(base),
+                    MO::input(imm.into()),
+                ],
+            );
+            (addr.into(), 0)
+        }
+    };
+    Ok(vec![
+        MO::new(OperandData::MemStart),
+        MO::new(OperandData::None),
+        MO::new(OperandData::Int32(imm)),
+        MO::input(base),
+    ])
+}
+
+/// Returns the memory operands of the address `ptr`. Slots and
+/// `getelementptr`s in the current block are folded into them.
+fn ptr_mem(ctx: &mut LoweringContext<Wasm32>, ptr: ValueId) -> Result<Vec<MO>> {
+    if let Value::Instruction(id) = ctx.ir_data.values[ptr] {
+        if let Some(&slot) = ctx.inst_id_to_slot_id.get(&id) {
+            return Ok(slot_mem(slot, 0));
+        }
+        let inst = ctx.ir_data.inst_ref(id);
+        if inst.opcode == IrOpcode::GetElementPtr && inst.parent == ctx.cur_block {
+            return gep_mem(ctx, id);
+        }
+    }
+    let base = val_to_operand_data(ctx, ptr_ty(ctx.types), ptr)?;
+    base_mem(ctx, base, 0)
+}
+
+/// Returns the memory operands of the address computed by `gep_id`.
+/// Constant indices become the offset, and variable ones are added to the
+/// base.
+fn gep_mem(ctx: &mut LoweringContext<Wasm32>, gep_id: InstructionId) -> Result<Vec<MO>> {
+    let gep = &ctx.ir_data.instructions[gep_id];
+    let tys = gep.operand.types().to_vec();
+    let args = gep.operand.args().to_vec();
+
+    let mut mem = ptr_mem(ctx, args[0])?;
+    let mut offset = 0i64;
+    let mut ty = tys[0];
+    for (i, (&idx, &idx_ty)) in args[1..].iter().zip(tys[2..].iter()).enumerate() {
+        let idx = match ctx.ir_data.values[idx] {
+            Value::Constant(ConstantData::Int(int)) => Ok(int.cast_to_i64()),
+            _ => Err(idx),
+        };
+        let (elem_ty, scale) = match idx {
+            Ok(idx) => {
+                let (off, elem_ty) = index_offset(ctx.types, ty, idx, i == 0)?;
+                offset += off;
+                ty = elem_ty;
+                continue;
+            }
+            Err(_) if i == 0 => (ty, Wasm32::type_size(ctx.types, ty)),
+            Err(_) if ctx.types.is_struct(ty) => return Err(LoweringError::Todo.into()),
+            Err(_) => {
+                let elem_ty = ctx.types.get_element(ty).ok_or(LoweringError::Todo)?;
+                (elem_ty, Wasm32::type_size(ctx.types, elem_ty))
+            }
+        };
+        ty = elem_ty;
+
+        let mut idx = val_to_operand_data(ctx, idx_ty, idx.unwrap_err())?;
+        if idx_ty.is_i64() {
+            let wrapped = ctx.mach_data.vregs.add_vreg_data(types::I32);
+            push(
+                ctx,
+                Opcode::I32WrapI64,
+                vec![MO::output(wrapped.into()), MO::input(idx)],
+            );
+            idx = wrapped.into();
+        } else if !idx_ty.is_i32() {
+            return Err(LoweringError::Todo.into());
+        }
+        if scale != 1 {
+            let scaled = ctx.mach_data.vregs.add_vreg_data(types::I32);
+            push(
+                ctx,
+                Opcode::I32Mul,
+                vec![
+                    MO::output(scaled.into()),
+                    MO::input(idx),
+                    MO::input(OperandData::Int32(scale as i32)),
+                ],
+            );
+            idx = scaled.into();
+        }
+
+        // The base becomes a local holding the address so far.
+        let base = ctx.mach_data.vregs.add_vreg_data(ptr_ty(ctx.types));
+        push(
+            ctx,
+            Opcode::Lea,
+            vec![MO::output(base.into())]
+                .into_iter()
+                .chain(mem)
+                .collect(),
+        );
+        let sum = ctx.mach_data.vregs.add_vreg_data(ptr_ty(ctx.types));
+        push(
+            ctx,
+            Opcode::I32Add,
+            vec![
+                MO::output(sum.into()),
+                MO::input(base.into()),
+                MO::input(idx),
+            ],
+        );
+        mem = base_mem(ctx, sum.into(), 0)?;
+    }
+
+    if offset == 0 {
+        return Ok(mem);
+    }
+    match (&mem[1].data, &mem[2].data) {
+        (OperandData::Slot(_), &OperandData::Int32(imm)) => {
+            let imm = i32::try_from(imm as i64 + offset).map_err(|_| LoweringError::Todo)?;
+            mem[2].data = imm.into();
+            Ok(mem)
+        }
+        (_, &OperandData::Int32(imm)) => {
+            let base = mem[3].data.clone();
+            base_mem(ctx, base, imm as i64 + offset)
+        }
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
+
+/// Returns the offset of the index `idx` into `ty`, and the type indexed. The
+/// first index of a `getelementptr` steps over whole `ty`s.
+fn index_offset(types: &Types, ty: Type, idx: i64, first: bool) -> Result<(i64, Type)> {
+    if first {
+        return Ok((idx * Wasm32::type_size(types, ty) as i64, ty));
+    }
+    if types.is_struct(ty) {
+        let layout = Wasm32::data_layout();
+        let offset = layout
+            .field_offset(types, ty, idx as usize)
+            .ok_or(LoweringError::Todo)?;
+        let elem_ty = types
+            .base()
+            .element_at(ty, idx as usize)
+            .ok_or(LoweringError::Todo)?;
+        return Ok((offset as i64, elem_ty));
+    }
+    let elem_ty = types.get_element(ty).ok_or(LoweringError::Todo)?;
+    Ok((idx * Wasm32::type_size(types, elem_ty) as i64, elem_ty))
+}
+
+// Get instruction output.
+// If the instruction is not placed in any basic block, place it in the current block.
+// If the instruction must be placed in another block except the current block(, which means
+// the instruction output must live out from its parent basic block to the current block),
+// just create a new virtual register to store the instruction output.
+fn get_or_generate_inst_output(
+    ctx: &mut LoweringContext<Wasm32>,
+    ty: Type,
+    id: InstructionId,
+) -> Result<VReg> {
+    if let Some(vreg) = ctx.inst_id_to_vreg.get(&id) {
+        return Ok(*vreg);
+    }
+
+    if ctx.ir_data.inst_ref(id).parent != ctx.cur_block {
+        // The instruction indexed as `id` must be placed in another basic block
+        let vreg = new_empty_inst_output(ctx, ty, id);
+        return Ok(vreg);
+    }
+
+    let inst = ctx.ir_data.inst_ref(id);
+
+    if inst.opcode.has_side_effects() {
+        let vreg = new_empty_inst_output(ctx, ty, id);
+        Ok(vreg)
+    } else {
+        // TODO: What about instruction scheduling?
+        lower(ctx, inst)?;
+        get_or_generate_inst_output(ctx, ty, id)
+    }
+}
+
+fn new_empty_inst_output(ctx: &mut LoweringContext<Wasm32>, ty: Type, id: InstructionId) -> VReg {
+    if let Some(vreg) = ctx.inst_id_to_vreg.get(&id) {
+        return *vreg;
+    }
+    let vreg = ctx.mach_data.vregs.add_vreg_data(ty);
+    ctx.inst_id_to_vreg.insert(id, vreg);
+    vreg
+}
+
+fn val_to_operand_data(
+    ctx: &mut LoweringContext<Wasm32>,
+    ty: Type,
+    val: ValueId,
+) -> Result<OperandData> {
+    match ctx.ir_data.values[val] {
+        Value::Instruction(id) => {
+            // The address of a slot is only computed when used as a value.
+            if let Some(&slot) = ctx.inst_id_to_slot_id.get(&id) {
+                let addr = ctx.mach_data.vregs.add_vreg_data(ty);
+                push(
+                    ctx,
+                    Opcode::Lea,
+                    vec![MO::output(addr.into())]
+                        .into_iter()
+                        .chain(slot_mem(slot, 0))
+                        .collect(),
+                );
+                return Ok(addr.into());
+            }
+            Ok(get_or_generate_inst_output(ctx, ty, id)?.into())
+        }
+        Value::Argument(idx) => Ok(ctx.arg_idx_to_vreg[&idx].into()),
+        Value::Constant(ref konst) => const_to_operand_data(ctx, ty, konst),
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
+
+fn const_to_operand_data(
+    ctx: &mut LoweringContext<Wasm32>,
+    ty: Type,
+    konst: &ConstantData,
+) -> Result<OperandData> {
+    match konst {
+        ConstantData::Int(ConstantInt::Int64(i)) => Ok(OperandData::Int64(*i)),
+        ConstantData::Int(int) => Ok(OperandData::Int32(int.cast_to_i64() as i32)),
+        ConstantData::Null => Ok(OperandData::Int32(0)),
+        ConstantData::GlobalRef(name) => Ok(OperandData::Global(name.to_string())),
+        ConstantData::Expr(ConstantExpr::Bitcast { arg, .. }) => {
+            const_to_operand_data(ctx, ty, arg)
+        }
+        ConstantData::Expr(ConstantExpr::GetElementPtr { tys, args, .. }) => {
+            let mut offset = 0;
+            let mut elem_ty = tys[0];
+            for (i, idx) in args[1..].iter().enumerate() {
+                let ConstantData::Int(idx) = idx else {
+                    return Err(LoweringError::Todo.into());
+                };
+                let (off, ty) = index_offset(ctx.types, elem_ty, idx.cast_to_i64(), i == 0)?;
+                offset += off;
+                elem_ty = ty;
+            }
+            let base = const_to_operand_data(ctx, ty, &args[0])?;
+            if offset == 0 {
+                return Ok(base);
+            }
+            let mem = base_mem(ctx, base, offset)?;
+            let addr = ctx.mach_data.vregs.add_vreg_data(ty);
+            push(
+                ctx,
+                Opcode::Lea,
+                vec![MO::output(addr.into())]
+                    .into_iter()
+                    .chain(mem)
+                    .collect(),
+            );
+            Ok(addr.into())
+        }
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
+
+/// Returns the class of the locals of `ty`, or an error if `ty` is not held
+/// in locals.
+fn class_of(types: &Types, ty: Type) -> Result<RegClass> {
+    match ty {
+        types::I1 | types::I8 | types::I16 | types::I32 => Ok(RegClass::I32),
+        types::I64 => Ok(RegClass::I64),
+        _ if ty.is_pointer(types) => Ok(RegClass::I32),
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
+
+fn ptr_ty(types: &Types) -> Type {
+    types.ptr_to(types::I8)
+}
+
+fn push(ctx: &mut LoweringContext<Wasm32>, opcode: Opcode, operands: Vec<MO>) {
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData { opcode, operands },
+        ctx.block_map[&ctx.cur_block],
+    ));
+}
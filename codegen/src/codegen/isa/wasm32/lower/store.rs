@@ -0,0 +1,37 @@
+use super::{class_of, ptr_mem, push, val_to_operand_data};
+use crate::codegen::{
+    isa::wasm32::{
+        instruction::{Opcode, Operand as MOperand},
+        register::RegClass,
+        Wasm32,
+    },
+    lower::{LoweringContext, LoweringError},
+};
+use anyhow::Result;
+use vicis_core::ir::{types::Type, value::ValueId};
+
+pub fn lower_store(
+    ctx: &mut LoweringContext<Wasm32>,
+    tys: &[Type],
+    args: &[ValueId],
+    _align: u32,
+) -> Result<()> {
+    // Narrower integers would need `store8` and `store16`. TODO
+    let opcode = match class_of(ctx.types, tys[0])? {
+        _ if tys[0].is_i1() || tys[0].is_i8() || tys[0].is_i16() => {
+            return Err(LoweringError::Todo.into())
+        }
+        RegClass::I32 => Opcode::I32Store,
+        RegClass::I64 => Opcode::I64Store,
+    };
+
+    let src = val_to_operand_data(ctx, tys[0], args[0])?;
+    let mem = ptr_mem(ctx, args[1])?;
+    push(
+        ctx,
+        opcode,
+        vec![MOperand::input(src)].into_iter().chain(mem).collect(),
+    );
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+pub mod asm;
+pub mod instruction;
+pub mod lower;
+pub mod pass;
+pub mod register;
+pub mod structurize;
+
+use super::{ModulePass, TargetIsa};
+use crate::codegen::lower::expand::ExpandHooks;
+use crate::codegen::{call_conv::CallConvKind, isa::wasm32};
+use vicis_core::ir::{
+    module::data_layout::DataLayout,
+    types::{Type, Types},
+};
+
+/// WebAssembly with 32-bit addresses.
+///
+/// There are no registers: every virtual register becomes a local of its
+/// function, so no register allocation is run. Control flow is turned back
+/// into `block`s, `loop`s and `if`s when printing, by [`structurize`].
+#[derive(Copy, Clone)]
+pub struct Wasm32;
+
+impl TargetIsa for Wasm32 {
+    type InstInfo = instruction::InstructionInfo;
+    type Lower = wasm32::lower::Lower;
+    type RegClass = register::RegClass;
+    type RegInfo = register::RegInfo;
+
+    fn module_pass_list() -> Vec<ModulePass<Self>> {
+        vec![
+            pass::phi_elimination::run_on_module,
+            pass::variadic_calls::run_on_module,
+            pass::stack_frame::run_on_module,
+        ]
+    }
+
+    fn default_call_conv() -> CallConvKind {
+        CallConvKind::Wasm
+    }
+
+    fn data_layout() -> DataLayout {
+        "e-m:e-p:32:32-p10:8:8-p20:8:8-i64:64-n32:64-S128-ni:1:10:20"
+            .parse()
+            .unwrap()
+    }
+}
+
+impl ExpandHooks for Wasm32 {
+    fn size_of(&self, types: &Types, ty: Type) -> u32 {
+        Self::type_size(types, ty)
+    }
+}
@@ -0,0 +1,3 @@
+pub mod phi_elimination;
+pub mod stack_frame;
+pub mod variadic_calls;
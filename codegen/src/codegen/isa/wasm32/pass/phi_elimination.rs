@@ -0,0 +1,128 @@
+use crate::codegen::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{Instruction, InstructionId},
+        Function,
+    },
+    isa::wasm32::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        Wasm32,
+    },
+    module::Module,
+    register::VReg,
+};
+use anyhow::Result;
+
+/// A predecessor and the block it branches to.
+type Edge = (BasicBlockId, BasicBlockId);
+
+pub fn run_on_module(module: &mut Module<Wasm32>) -> Result<()> {
+    for (_, func) in &mut module.functions {
+        run_on_function(func);
+    }
+    Ok(())
+}
+
+/// Replaces phis with copies on the edges coming into their blocks. The
+/// copies go before the branch ending the predecessor, or into a new block
+/// splitting the edge if the predecessor branches elsewhere too.
+pub fn run_on_function(function: &mut Function<Wasm32>) {
+    let mut worklist = vec![];
+    // The copies of each edge, in the order of the phis.
+    let mut edges: Vec<(Edge, Vec<(VReg, OperandData)>)> = vec![];
+
+    for block_id in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block_id) {
+            let inst = function.data.inst_ref(inst_id);
+            if !matches!(inst.data.opcode, Opcode::Phi) {
+                continue;
+            }
+            worklist.push(inst_id);
+            let output = *inst.data.operands[0].data.as_vreg();
+            for pair in inst.data.operands[1..].chunks(2) {
+                let edge = (*pair[1].data.as_block(), block_id);
+                let copy = (output, pair[0].data.clone());
+                match edges.iter_mut().find(|(e, _)| *e == edge) {
+                    Some((_, copies)) => copies.push(copy),
+                    None => edges.push((edge, vec![copy])),
+                }
+            }
+        }
+    }
+
+    for ((pred, block), copies) in edges {
+        let branch = edge_branch(function, pred, block);
+        let parent = function.data.inst_ref(branch).parent;
+
+        // The copies are done at once. If one reads the output of another,
+        // all are done through temporaries.
+        let reads_output = copies.iter().any(|(_, arg)| {
+            matches!(arg, OperandData::VReg(vr) if copies.iter().any(|(output, _)| output == vr))
+        });
+        let mut seq = vec![];
+        if reads_output {
+            let mut temps = vec![];
+            for (output, arg) in copies {
+                let temp = function.data.vregs.create_from(output);
+                seq.push((temp, arg));
+                temps.push((output, OperandData::VReg(temp)));
+            }
+            seq.extend(temps);
+        } else {
+            seq = copies;
+        }
+
+        for (output, arg) in seq {
+            let copy = function.data.create_inst(Instruction::new(
+                InstructionData {
+                    opcode: Opcode::Copy,
+                    operands: vec![Operand::output(output.into()), Operand::input(arg)],
+                },
+                parent,
+            ));
+            function.layout.insert_inst_before(branch, copy, parent);
+        }
+    }
+
+    for inst_id in worklist {
+        function.remove_inst(inst_id);
+    }
+}
+
+/// Returns the branch taken only along the edge from `pred` to `block`,
+/// splitting the edge if `pred` ends with a conditional branch.
+fn edge_branch(
+    function: &mut Function<Wasm32>,
+    pred: BasicBlockId,
+    block: BasicBlockId,
+) -> InstructionId<InstructionData> {
+    let branch = function.layout.last_inst_of(pred).unwrap();
+    if matches!(function.data.inst_ref(branch).data.opcode, Opcode::Br) {
+        return branch;
+    }
+
+    let mid = function.data.create_block();
+    function.layout.append_block(mid);
+    let br = function.data.create_inst(Instruction::new(
+        InstructionData {
+            opcode: Opcode::Br,
+            operands: vec![Operand::new(OperandData::Block(block))],
+        },
+        mid,
+    ));
+    function.layout.append_inst(br, mid);
+
+    for operand in &mut function.data.inst_ref_mut(branch).data.operands {
+        if matches!(operand.data, OperandData::Block(b) if b == block) {
+            operand.data = OperandData::Block(mid);
+        }
+    }
+    function.data.block_ref_mut(pred).succs.remove(&block);
+    function.data.block_ref_mut(pred).succs.insert(mid);
+    function.data.block_ref_mut(block).preds.remove(&pred);
+    function.data.block_ref_mut(block).preds.insert(mid);
+    function.data.block_ref_mut(mid).preds.insert(pred);
+    function.data.block_ref_mut(mid).succs.insert(block);
+
+    br
+}
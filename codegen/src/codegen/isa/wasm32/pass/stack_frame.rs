@@ -0,0 +1,156 @@
+use crate::codegen::{
+    function::{instruction::Instruction, Function},
+    isa::{
+        wasm32::{
+            instruction::{InstructionData, Opcode, Operand, OperandData},
+            Wasm32,
+        },
+        TargetIsa,
+    },
+    module::Module,
+};
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use vicis_core::ir::types;
+
+/// The global holding the stack pointer, as in the C ABI of WebAssembly.
+pub const STACK_POINTER: &str = "__stack_pointer";
+
+pub fn run_on_module(module: &mut Module<Wasm32>) -> Result<()> {
+    for (_, func) in &mut module.functions {
+        run_on_function(func);
+    }
+    Ok(())
+}
+
+/// Places the slots in the linear memory, in a frame below the stack
+/// pointer, and replaces them with offsets from a local holding the address
+/// of the frame. The prologue moves the stack pointer down by the size of the
+/// frame, and an epilogue before each `return` moves it back.
+///
+/// ```text
+/// (local.set $fp (i32.sub (global.get $__stack_pointer) (i32.const frame_size)))
+/// (global.set $__stack_pointer (local.get $fp))
+/// ...
+/// (local.set $t (i32.add (local.get $fp) (i32.const frame_size)))
+/// (global.set $__stack_pointer (local.get $t))
+/// (return ...)
+/// ```
+pub fn run_on_function(function: &mut Function<Wasm32>) {
+    if function.slots.is_empty() {
+        return;
+    }
+
+    let layout = Wasm32::data_layout();
+    let mut offset_map = FxHashMap::default();
+    let mut size = 0u32;
+    for (slot, s) in function.slots.iter() {
+        let align = layout.align_of(&function.types, s.ty()).max(1);
+        size = size.next_multiple_of(align);
+        offset_map.insert(slot, size);
+        size += s.size();
+    }
+    let frame_size = size.next_multiple_of(16) as i32;
+    for (&slot, &off) in &offset_map {
+        function.slots.set_offset(slot, off as i32);
+    }
+
+    let ptr_ty = function.types.ptr_to(types::I8);
+    let fp = function.data.vregs.add_vreg_data(ptr_ty);
+
+    let mut worklist = vec![];
+    let mut returns = vec![];
+    for block in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block) {
+            let inst = function.data.inst_ref(inst_id);
+            if matches!(inst.data.opcode, Opcode::Return) {
+                returns.push((block, inst_id));
+            }
+            if inst
+                .data
+                .operands
+                .iter()
+                .any(|op| matches!(op.data, OperandData::Slot(_)))
+            {
+                worklist.push(inst_id);
+            }
+        }
+    }
+
+    for inst_id in worklist {
+        let inst = function.data.inst_ref_mut(inst_id);
+        for i in 0..inst.data.operands.len() {
+            // MemStart indicates the beginning of memory arguments
+            if !matches!(inst.data.operands[i].data, OperandData::MemStart) {
+                continue;
+            }
+            let mem = &mut inst.data.operands[i + 1..i + 4];
+            let OperandData::Slot(slot) = mem[0].data else {
+                continue;
+            };
+            let imm = match mem[1].data {
+                OperandData::Int32(imm) => imm,
+                _ => 0,
+            };
+            mem[0].data = OperandData::None;
+            mem[1].data = OperandData::Int32(offset_map[&slot] as i32 + imm);
+            mem[2].data = OperandData::VReg(fp);
+        }
+        function.data.vreg_users.add_use(fp, inst_id, true, false);
+    }
+
+    // insert prologue
+    if let Some(entry) = function.layout.first_block {
+        let set = function.data.create_inst(Instruction::new(
+            InstructionData {
+                opcode: Opcode::GlobalSet,
+                operands: vec![
+                    Operand::new(OperandData::Global(STACK_POINTER.to_string())),
+                    Operand::input(fp.into()),
+                ],
+            },
+            entry,
+        ));
+        function.layout.insert_inst_at_start(set, entry);
+        let sub = function.data.create_inst(Instruction::new(
+            InstructionData {
+                opcode: Opcode::I32Sub,
+                operands: vec![
+                    Operand::output(fp.into()),
+                    Operand::input(OperandData::Global(STACK_POINTER.to_string())),
+                    Operand::input(frame_size.into()),
+                ],
+            },
+            entry,
+        ));
+        function.layout.insert_inst_at_start(sub, entry);
+    }
+
+    // insert epilogue
+    for (block, ret_id) in returns {
+        let sp = function.data.vregs.add_vreg_data(ptr_ty);
+        let add = function.data.create_inst(Instruction::new(
+            InstructionData {
+                opcode: Opcode::I32Add,
+                operands: vec![
+                    Operand::output(sp.into()),
+                    Operand::input(fp.into()),
+                    Operand::input(frame_size.into()),
+                ],
+            },
+            block,
+        ));
+        function.layout.insert_inst_before(ret_id, add, block);
+        let set = function.data.create_inst(Instruction::new(
+            InstructionData {
+                opcode: Opcode::GlobalSet,
+                operands: vec![
+                    Operand::new(OperandData::Global(STACK_POINTER.to_string())),
+                    Operand::input(sp.into()),
+                ],
+            },
+            block,
+        ));
+        function.layout.insert_inst_before(ret_id, set, block);
+    }
+}
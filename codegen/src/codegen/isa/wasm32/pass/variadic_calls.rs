@@ -0,0 +1,124 @@
+use crate::codegen::{
+    function::{instruction::Instruction, slot::SlotOrigin, Function},
+    isa::wasm32::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        Wasm32,
+    },
+    module::Module,
+};
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use vicis_core::ir::types;
+
+pub fn run_on_module(module: &mut Module<Wasm32>) -> Result<()> {
+    let variadic: FxHashMap<String, usize> = module
+        .functions
+        .iter()
+        .filter(|(_, func)| func.is_var_arg)
+        .map(|(_, func)| (func.name.clone(), func.params.len()))
+        .collect();
+    for (_, func) in &mut module.functions {
+        run_on_function(func, &variadic);
+    }
+    Ok(())
+}
+
+/// Passes the variadic arguments of calls as the C ABI of WebAssembly does:
+/// they are stored to a buffer on the stack, each at its natural alignment,
+/// and its address is passed as the last parameter instead. `variadic` maps
+/// the names of variadic functions to their numbers of fixed parameters.
+pub fn run_on_function(function: &mut Function<Wasm32>, variadic: &FxHashMap<String, usize>) {
+    let mut calls = vec![];
+    for block in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block) {
+            let inst = function.data.inst_ref(inst_id);
+            if !matches!(inst.data.opcode, Opcode::Call) {
+                continue;
+            }
+            let callee = inst
+                .data
+                .operands
+                .iter()
+                .position(|op| matches!(op.data, OperandData::Label(_)))
+                .unwrap();
+            let OperandData::Label(name) = &inst.data.operands[callee].data else {
+                unreachable!()
+            };
+            if let Some(&num_fixed) = variadic.get(name) {
+                calls.push((block, inst_id, callee + 1 + num_fixed));
+            }
+        }
+    }
+
+    for (block, call_id, first_variadic) in calls {
+        let args = function.data.inst_ref_mut(call_id).data.operands[first_variadic..].to_vec();
+        if args.is_empty() {
+            let operands = &mut function.data.inst_ref_mut(call_id).data.operands;
+            operands.push(Operand::input(OperandData::Int32(0)));
+            continue;
+        }
+
+        let mut offsets = vec![];
+        let mut size = 0;
+        for arg in &args {
+            let is_64 = match &arg.data {
+                OperandData::VReg(vr) => function.data.vregs.type_for(*vr).is_i64(),
+                OperandData::Int64(_) => true,
+                _ => false,
+            };
+            let arg_size = if is_64 { 8 } else { 4 };
+            size = u32::next_multiple_of(size, arg_size);
+            offsets.push((size as i32, is_64));
+            size += arg_size;
+        }
+        let slot = function
+            .slots
+            .add_slot(types::I64, size, SlotOrigin::VarArgs);
+
+        for (arg, (offset, is_64)) in args.into_iter().zip(offsets) {
+            if let OperandData::VReg(vr) = arg.data {
+                function.data.vreg_users.remove_use(vr, call_id);
+            }
+            let store = function.data.create_inst(Instruction::new(
+                InstructionData {
+                    opcode: if is_64 {
+                        Opcode::I64Store
+                    } else {
+                        Opcode::I32Store
+                    },
+                    operands: vec![
+                        arg,
+                        Operand::new(OperandData::MemStart),
+                        Operand::new(OperandData::Slot(slot)),
+                        Operand::new(OperandData::Int32(offset)),
+                        Operand::input(OperandData::None),
+                    ],
+                },
+                block,
+            ));
+            function.layout.insert_inst_before(call_id, store, block);
+        }
+
+        let ptr_ty = function.types.ptr_to(types::I8);
+        let buf = function.data.vregs.add_vreg_data(ptr_ty);
+        let lea = function.data.create_inst(Instruction::new(
+            InstructionData {
+                opcode: Opcode::Lea,
+                operands: vec![
+                    Operand::output(buf.into()),
+                    Operand::new(OperandData::MemStart),
+                    Operand::new(OperandData::Slot(slot)),
+                    Operand::new(OperandData::Int32(0)),
+                    Operand::input(OperandData::None),
+                ],
+            },
+            block,
+        ));
+        function.layout.insert_inst_before(call_id, lea, block);
+
+        let operands = &mut function.data.inst_ref_mut(call_id).data.operands;
+        operands.truncate(first_variadic);
+        operands.push(Operand::input(buf.into()));
+        function.data.vreg_users.add_use(buf, call_id, true, false);
+    }
+}
@@ -0,0 +1,57 @@
+use crate::codegen::{
+    call_conv::CallConvKind,
+    register::{Reg, RegUnit, RegisterClass, RegisterInfo},
+};
+use vicis_core::ir::types::{self, Type, Types};
+
+/// WebAssembly has no registers. These only give virtual registers the type
+/// of the local they become.
+pub struct RegInfo;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegClass {
+    I32,
+    I64,
+}
+
+impl RegisterInfo for RegInfo {
+    fn arg_reg_list(cc: &CallConvKind) -> &'static [RegUnit] {
+        match cc {
+            CallConvKind::Wasm => &[],
+            _ => panic!("{:?} is not a calling convention of wasm32", cc),
+        }
+    }
+
+    fn to_reg_unit(_: Reg) -> RegUnit {
+        unreachable!("wasm32 has no registers")
+    }
+}
+
+impl RegisterClass for RegClass {
+    fn for_type(types: &Types, ty: Type) -> Self {
+        match ty {
+            types::I1 | types::I8 | types::I16 | types::I32 => RegClass::I32,
+            types::I64 => RegClass::I64,
+            _ if ty.is_pointer(types) => RegClass::I32,
+            _ => todo!(),
+        }
+    }
+
+    fn gpr_list(&self) -> Vec<Reg> {
+        vec![]
+    }
+
+    fn apply_for(&self, _: RegUnit) -> Reg {
+        unreachable!("wasm32 has no registers")
+    }
+}
+
+impl RegClass {
+    /// Returns the name of the value type of locals of this class.
+    pub fn val_type(&self) -> &'static str {
+        match self {
+            Self::I32 => "i32",
+            Self::I64 => "i64",
+        }
+    }
+}
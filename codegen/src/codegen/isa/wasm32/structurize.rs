@@ -0,0 +1,225 @@
+//! Structured control flow.
+//!
+//! A WebAssembly branch can only leave an enclosing `block` or go back to the
+//! start of an enclosing `loop`. [`structurize`] rebuilds such constructs from
+//! the control flow graph of a function, following "Beyond Relooper" by
+//! Norman Ramsey:
+//!
+//! - Blocks are placed along the dominator tree, each after its immediate
+//!   dominator and before the blocks it dominates.
+//! - A loop header starts a `loop`, covering the blocks it dominates.
+//!   Branches back to the header continue the `loop`.
+//! - A merge node, i.e. a block with several forward predecessors, comes
+//!   right after the end of a `block` wrapping the code of its predecessors.
+//!   Branches to it leave the `block`.
+//! - Any other block has a single predecessor, and is placed at the branch to
+//!   it.
+//!
+//! Only reducible control flow, where each loop has a single header, can be
+//! structured.
+
+use crate::codegen::{
+    function::{
+        basic_block::{BasicBlock, BasicBlockId},
+        Function,
+    },
+    isa::wasm32::{
+        instruction::{Opcode, OperandData},
+        Wasm32,
+    },
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use vicis_core::pass::analysis::dom_tree::DominatorTree;
+
+/// Structured control flow, in which branches go to the constructs of their
+/// target blocks.
+#[derive(Debug, Clone)]
+pub enum Structured {
+    /// A `block`, followed by the merge node it is labeled for.
+    Block(BasicBlockId, Vec<Structured>),
+    /// A `loop` starting with the loop header it is labeled for.
+    Loop(BasicBlockId, Vec<Structured>),
+    /// An `if` with a `then` and an `else` arm.
+    If(OperandData, Vec<Structured>, Vec<Structured>),
+    /// The instructions of a block, except the branches ending it.
+    Code(BasicBlockId),
+    /// A `br` to the construct labeled for the block.
+    Br(Label),
+}
+
+/// What a branch targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Label {
+    /// The end of the `block` before the merge node.
+    Block(BasicBlockId),
+    /// The start of the `loop` of the loop header.
+    Loop(BasicBlockId),
+}
+
+/// Returns the structured control flow of `function`.
+///
+/// # Panics
+///
+/// Panics if the control flow is irreducible.
+pub fn structurize(function: &Function<Wasm32>) -> Vec<Structured> {
+    let Some(entry) = function.layout.first_block else {
+        return vec![];
+    };
+    Context::new(function, entry).do_tree(entry)
+}
+
+struct Context<'a> {
+    function: &'a Function<Wasm32>,
+    dom_tree: DominatorTree<BasicBlock>,
+    /// The reverse postorder number of each reachable block.
+    rpo: FxHashMap<BasicBlockId, usize>,
+    loop_headers: FxHashSet<BasicBlockId>,
+    merge_nodes: FxHashSet<BasicBlockId>,
+}
+
+impl<'a> Context<'a> {
+    fn new(function: &'a Function<Wasm32>, entry: BasicBlockId) -> Self {
+        let mut postorder = vec![];
+        let mut visited = FxHashSet::default();
+        let mut stack = vec![(entry, 0)];
+        visited.insert(entry);
+        while let Some((block, i)) = stack.pop() {
+            let succs = succs_of(function, block);
+            match succs.get(i) {
+                Some(&succ) => {
+                    stack.push((block, i + 1));
+                    if visited.insert(succ) {
+                        stack.push((succ, 0));
+                    }
+                }
+                None => postorder.push(block),
+            }
+        }
+        let rpo: FxHashMap<BasicBlockId, usize> = postorder
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, &block)| (block, i))
+            .collect();
+        let dom_tree = DominatorTree::new(function);
+
+        let mut loop_headers = FxHashSet::default();
+        let mut merge_nodes = FxHashSet::default();
+        for (&block, &num) in &rpo {
+            let mut forward_preds = 0;
+            for pred in &function.data.block_ref(block).preds {
+                let Some(&pred_num) = rpo.get(pred) else {
+                    continue;
+                };
+                if pred_num < num {
+                    forward_preds += 1;
+                    continue;
+                }
+                assert!(
+                    dom_tree.dominates(block, *pred),
+                    "irreducible control flow is not supported"
+                );
+                loop_headers.insert(block);
+            }
+            if forward_preds > 1 {
+                merge_nodes.insert(block);
+            }
+        }
+
+        Self {
+            function,
+            dom_tree,
+            rpo,
+            loop_headers,
+            merge_nodes,
+        }
+    }
+
+    fn do_tree(&self, x: BasicBlockId) -> Vec<Structured> {
+        let mut merges: Vec<BasicBlockId> = self
+            .dom_tree
+            .children_of(x)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|child| self.merge_nodes.contains(child))
+            .collect();
+        merges.sort_by_key(|child| self.rpo[child]);
+        let code = self.node_within(x, &merges);
+        if self.loop_headers.contains(&x) {
+            vec![Structured::Loop(x, code)]
+        } else {
+            code
+        }
+    }
+
+    /// Returns the code of `x` followed by the merge nodes it immediately
+    /// dominates, in reverse postorder. The later a merge node comes, the
+    /// outer its `block` is.
+    fn node_within(&self, x: BasicBlockId, merges: &[BasicBlockId]) -> Vec<Structured> {
+        match merges.split_last() {
+            Some((&y, merges)) => {
+                let mut code = vec![Structured::Block(y, self.node_within(x, merges))];
+                code.extend(self.do_tree(y));
+                code
+            }
+            None => {
+                let mut code = vec![Structured::Code(x)];
+                code.extend(self.branches_of(x));
+                code
+            }
+        }
+    }
+
+    fn branches_of(&self, x: BasicBlockId) -> Vec<Structured> {
+        let Some(last) = self.function.layout.last_inst_of(x) else {
+            return vec![];
+        };
+        let inst = &self.function.data.inst_ref(last).data;
+        match inst.opcode {
+            Opcode::Br => self.do_branch(x, *inst.operands[0].data.as_block()),
+            Opcode::BrIf => {
+                let then = *inst.operands[1].data.as_block();
+                let else_ = *inst.operands[2].data.as_block();
+                if then == else_ {
+                    return self.do_branch(x, then);
+                }
+                vec![Structured::If(
+                    inst.operands[0].data.clone(),
+                    self.do_branch(x, then),
+                    self.do_branch(x, else_),
+                )]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn do_branch(&self, x: BasicBlockId, y: BasicBlockId) -> Vec<Structured> {
+        if self.rpo[&y] <= self.rpo[&x] {
+            vec![Structured::Br(Label::Loop(y))]
+        } else if self.merge_nodes.contains(&y) {
+            vec![Structured::Br(Label::Block(y))]
+        } else {
+            self.do_tree(y)
+        }
+    }
+}
+
+/// Returns the targets of the branches ending `block`, in order.
+fn succs_of(function: &Function<Wasm32>, block: BasicBlockId) -> Vec<BasicBlockId> {
+    let Some(last) = function.layout.last_inst_of(block) else {
+        return vec![];
+    };
+    let inst = &function.data.inst_ref(last).data;
+    match inst.opcode {
+        Opcode::Br | Opcode::BrIf => inst
+            .operands
+            .iter()
+            .filter_map(|op| match op.data {
+                OperandData::Block(block) => Some(block),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
@@ -1,91 +1,506 @@
 use crate::codegen::{
-    function::Function,
+    cache::PrintFunction,
+    function::{basic_block::BasicBlockId, instruction::CfiDirective, Function},
     isa::x86_64::{
-        instruction::{Opcode, Operand, OperandData},
-        register::reg_to_str,
-        X86_64,
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        register::{reg_to_str, RegClass},
+        X86Family, X86_64,
     },
     module::Module,
+    object::asm::{
+        print_binding, print_function_section, print_global_variables, print_stack_note,
+        print_visibility,
+    },
+    options::AsmSyntax,
+    register::Reg,
 };
 use std::fmt;
 
-pub fn print(f: &mut fmt::Formatter<'_>, module: &Module<X86_64>) -> fmt::Result {
+pub fn print<T: X86Family>(f: &mut fmt::Formatter<'_>, module: &Module<T>) -> fmt::Result {
     writeln!(f, "  .text")?;
-    writeln!(f, "  .intel_syntax noprefix")?;
+    if module.asm_syntax == AsmSyntax::Intel {
+        writeln!(f, "  .intel_syntax noprefix")?;
+    }
 
-    for gv in module.global_variables.values() {
-        if let Some(init) = &gv.init {
-            let arr = init.as_array();
-            if !arr.is_string {
-                continue;
-            }
-            let mut s = vec![];
-            for elem in &arr.elems {
-                s.push(*elem.as_int().as_i8() as u8)
-            }
-            let s: Vec<u8> = s
-                .into_iter()
-                .flat_map(::std::ascii::escape_default)
-                .collect();
-            let s = ::std::str::from_utf8(s.as_slice()).unwrap().to_string();
-            let s = s.trim_end_matches("\\x00"); // TODO
-            debug!(&s);
-            writeln!(f, "{}:", gv.name.as_string())?;
-            writeln!(f, "  .string \"{}\"", s)?;
+    let mut section = None;
+    for (i, (_, func)) in module.functions.iter().enumerate() {
+        let precompiled = module.precompiled.get(&i);
+        if precompiled.is_some() || !func.is_prototype {
+            print_function_section(f, module, func, &mut section)?;
+        }
+        match precompiled {
+            Some(asm) => f.write_str(asm)?,
+            None => print_function(f, func, i, module.asm_syntax)?,
         }
     }
 
-    for (i, (_, func)) in module.functions.iter().enumerate() {
-        print_function(f, func, i)?
+    print_global_variables(f, module)?;
+
+    // The personality routines are referred to through a pointer, which the
+    // linker merges across objects.
+    let mut personalities = vec![];
+    for (_, func) in &module.functions {
+        match &func.personality {
+            Some(p) if !func.is_prototype && !personalities.contains(&p) => personalities.push(p),
+            _ => {}
+        }
+    }
+    for p in personalities {
+        writeln!(f, "  .hidden DW.ref.{}", p)?;
+        writeln!(f, "  .weak DW.ref.{}", p)?;
+        writeln!(
+            f,
+            "  .section .data.DW.ref.{0},\"awG\",@progbits,DW.ref.{0},comdat",
+            p
+        )?;
+        writeln!(f, "  .p2align {}", T::WORD_SIZE.trailing_zeros())?;
+        writeln!(f, "  .type DW.ref.{},@object", p)?;
+        writeln!(f, "  .size DW.ref.{}, {}", p, T::WORD_SIZE)?;
+        writeln!(f, "DW.ref.{}:", p)?;
+        match T::WORD_SIZE {
+            4 => writeln!(f, "  .long {}", p)?,
+            _ => writeln!(f, "  .quad {}", p)?,
+        }
     }
 
-    Ok(())
+    print_stack_note(f)
 }
 
-pub fn print_function(
+pub fn print_function<T: X86Family>(
     f: &mut fmt::Formatter<'_>,
-    function: &Function<X86_64>,
+    function: &Function<T>,
     fn_idx: usize,
+    syntax: AsmSyntax,
 ) -> fmt::Result {
+    print_binding(
+        f,
+        &function.name,
+        Some(function.linkage),
+        !function.is_prototype,
+    )?;
+    print_visibility(f, &function.name, function.visibility)?;
     if function.is_prototype {
         return Ok(());
     }
 
-    writeln!(f, "  .globl {}", function.name)?;
     writeln!(f, "{}:", function.name)?;
 
+    // The ranges of calls that may unwind, and their landing pads
+    let mut call_sites = vec![];
     for block in function.layout.block_iter() {
         writeln!(f, ".LBL{}_{}:", fn_idx, block.index())?;
         for inst in function.layout.inst_iter(block) {
             let inst = function.data.inst_ref(inst);
-            write!(f, "  {} ", inst.data.opcode)?;
-            let mut i = 0;
-            while i < inst.data.operands.len() {
-                let operand = &inst.data.operands[i];
-                if operand.implicit {
-                    i += 1;
-                    continue;
-                }
-                if matches!(operand.data, OperandData::MemStart) {
-                    i += 1;
-                    write!(f, "{} ptr ", mem_size(&inst.data.opcode))?;
-                    write!(f, "{}", mem_op(&inst.data.operands[i..i + 5]))?;
-                    i += 5 - 1;
-                } else {
-                    write_operand(f, &operand.data, fn_idx)?;
-                }
-                if i < inst.data.operands.len() - 1 {
-                    write!(f, ", ")?
+            let landing_pad = inst.data.operands.iter().find_map(|op| match op.data {
+                OperandData::Block(b) if op.implicit => Some(b),
+                _ => None,
+            });
+            if landing_pad.is_some() {
+                writeln!(f, ".LEH{}_{}:", fn_idx, call_sites.len())?;
+            }
+            match (syntax, &inst.data.operands[..]) {
+                (_, _) if matches!(inst.data.opcode, Opcode::LANDINGPAD) => {}
+                (
+                    _,
+                    [Operand {
+                        data: OperandData::Cfi(cfi),
+                        ..
+                    }],
+                ) => {
+                    cfi.write(f, |r| match syntax {
+                        AsmSyntax::Intel => reg_to_str(r).to_owned(),
+                        AsmSyntax::Att => format!("%{}", reg_to_str(r)),
+                    })?;
+                    if let (CfiDirective::StartProc, Some(personality)) =
+                        (cfi, &function.personality)
+                    {
+                        writeln!(f, "  .cfi_personality 155, DW.ref.{}", personality)?;
+                        writeln!(f, "  .cfi_lsda 27, .Lexception{}", fn_idx)?;
+                    }
                 }
-                i += 1;
+                (AsmSyntax::Intel, _) => write_intel(f, &inst.data.with_sized_regs(), fn_idx)?,
+                (AsmSyntax::Att, _) => write_att(f, &inst.data.with_sized_regs(), fn_idx)?,
+            }
+            if let Some(landing_pad) = landing_pad {
+                writeln!(f, ".LEH{}_{}_end:", fn_idx, call_sites.len())?;
+                call_sites.push(landing_pad);
             }
-            writeln!(f)?;
         }
     }
 
+    if function.personality.is_some() {
+        writeln!(f, ".Lfunc_end{}:", fn_idx)?;
+        print_lsda(f, function, fn_idx, &call_sites)?;
+    }
+
+    print_constant_pool(f, function, fn_idx)
+}
+
+/// Prints the constants the function loads, each in the section of
+/// mergeable constants of its size.
+fn print_constant_pool<T: X86Family>(
+    f: &mut fmt::Formatter<'_>,
+    function: &Function<T>,
+    fn_idx: usize,
+) -> fmt::Result {
+    for (i, bytes) in function.constant_pool.iter().enumerate() {
+        let size = bytes.len();
+        writeln!(
+            f,
+            "  .pushsection .rodata.cst{0},\"aM\",@progbits,{0}",
+            size
+        )?;
+        writeln!(f, "  .p2align {}", size.trailing_zeros())?;
+        writeln!(f, ".LCPI{}_{}:", fn_idx, i)?;
+        if size == 4 {
+            let bits = u32::from_le_bytes(bytes[..].try_into().unwrap());
+            writeln!(f, "  .long {:#x}", bits)?;
+        } else {
+            for quad in bytes.chunks(8) {
+                let bits = u64::from_le_bytes(quad.try_into().unwrap());
+                writeln!(f, "  .quad {:#x}", bits)?;
+            }
+        }
+        writeln!(f, "  .popsection")?;
+    }
     Ok(())
 }
 
+/// Prints the language-specific data area of the function, read by the
+/// personality routine. The call-site table covers the whole function: calls
+/// outside of `invoke` get no landing pad, so that unwinding through them
+/// goes on to the caller. No actions are recorded, so landing pads are
+/// entered as cleanups.
+fn print_lsda<T: X86Family>(
+    f: &mut fmt::Formatter<'_>,
+    function: &Function<T>,
+    fn_idx: usize,
+    call_sites: &[BasicBlockId],
+) -> fmt::Result {
+    let start = &function.name;
+    // Back to the section of the function, whichever it is. The table goes
+    // with the function if its section group is discarded.
+    match &function.comdat {
+        Some(group) => writeln!(
+            f,
+            "  .pushsection .gcc_except_table.{},\"aG\",@progbits,{},comdat",
+            function.name, group
+        )?,
+        None => writeln!(f, "  .pushsection .gcc_except_table,\"a\",@progbits")?,
+    }
+    writeln!(f, "  .p2align 2")?;
+    writeln!(f, ".Lexception{}:", fn_idx)?;
+    writeln!(f, "  .byte 255")?; // @LPStart encoding: omitted
+    writeln!(f, "  .byte 255")?; // @TType encoding: omitted
+    writeln!(f, "  .byte 1")?; // Call-site encoding: uleb128
+    writeln!(f, "  .uleb128 .Lcst_end{0}-.Lcst_begin{0}", fn_idx)?;
+    writeln!(f, ".Lcst_begin{}:", fn_idx)?;
+    let mut prev = start.to_owned();
+    for (i, landing_pad) in call_sites.iter().enumerate() {
+        let begin = format!(".LEH{}_{}", fn_idx, i);
+        writeln!(f, "  .uleb128 {}-{}", prev, start)?;
+        writeln!(f, "  .uleb128 {}-{}", begin, prev)?;
+        writeln!(f, "  .uleb128 0")?;
+        writeln!(f, "  .uleb128 0")?;
+        writeln!(f, "  .uleb128 {}-{}", begin, start)?;
+        writeln!(f, "  .uleb128 {}_end-{}", begin, begin)?;
+        writeln!(
+            f,
+            "  .uleb128 .LBL{}_{}-{}",
+            fn_idx,
+            landing_pad.index(),
+            start
+        )?;
+        writeln!(f, "  .uleb128 0")?;
+        prev = format!("{}_end", begin);
+    }
+    writeln!(f, "  .uleb128 {}-{}", prev, start)?;
+    writeln!(f, "  .uleb128 .Lfunc_end{}-{}", fn_idx, prev)?;
+    writeln!(f, "  .uleb128 0")?;
+    writeln!(f, "  .uleb128 0")?;
+    writeln!(f, ".Lcst_end{}:", fn_idx)?;
+    writeln!(f, "  .popsection")
+}
+
+fn write_intel(f: &mut fmt::Formatter<'_>, inst: &InstructionData, fn_idx: usize) -> fmt::Result {
+    write!(f, "  {} ", inst.opcode)?;
+    let mut i = 0;
+    while i < inst.operands.len() {
+        let operand = &inst.operands[i];
+        if operand.implicit {
+            i += 1;
+            continue;
+        }
+        if matches!(operand.data, OperandData::MemStart) {
+            i += 1;
+            write!(f, "{} ptr ", mem_size(&inst.opcode))?;
+            write!(f, "{}", mem_op(&inst.operands[i..i + 5]))?;
+            i += 5 - 1;
+        } else {
+            write_operand(f, inst.opcode, &operand.data, fn_idx)?;
+        }
+        if inst.operands[i + 1..].iter().any(|op| !op.implicit) {
+            write!(f, ", ")?
+        }
+        i += 1;
+    }
+    writeln!(f)
+}
+
+/// Writes `inst` with the size suffix of its mnemonic and its operands from
+/// source to destination.
+fn write_att(f: &mut fmt::Formatter<'_>, inst: &InstructionData, fn_idx: usize) -> fmt::Result {
+    let mut operands = vec![];
+    let mut i = 0;
+    while i < inst.operands.len() {
+        let operand = &inst.operands[i];
+        if operand.implicit {
+            i += 1;
+        } else if matches!(operand.data, OperandData::MemStart) {
+            operands.push(att_mem_op(&inst.operands[i + 1..i + 6]));
+            i += 6;
+        } else {
+            operands.push(att_operand(inst.opcode, &operand.data, fn_idx));
+            i += 1;
+        }
+    }
+    operands.reverse();
+
+    match inst.opcode {
+        // `movsxd` is `movslq`, from a long to a quad.
+        Opcode::MOVSXDr64r32 | Opcode::MOVSXDr64m32 => write!(f, "  movslq")?,
+        Opcode::CDQ => write!(f, "  cltd")?,
+        Opcode::CQO => write!(f, "  cqto")?,
+        // `movsx` and `movzx` take the suffixes of both sizes, e.g. `movsbl`.
+        Opcode::MOVSXr32r8
+        | Opcode::MOVSXr32m8
+        | Opcode::MOVSXr32r16
+        | Opcode::MOVSXr32m16
+        | Opcode::MOVZXr32r8
+        | Opcode::MOVZXr32m8
+        | Opcode::MOVZXr32r16
+        | Opcode::MOVZXr32m16 => write!(
+            f,
+            "  mov{}{}{}",
+            match inst.opcode {
+                Opcode::MOVSXr32r8
+                | Opcode::MOVSXr32m8
+                | Opcode::MOVSXr32r16
+                | Opcode::MOVSXr32m16 => "s",
+                _ => "z",
+            },
+            match mem_size(&inst.opcode) {
+                "byte" => "b",
+                _ => "w",
+            },
+            att_suffix(inst)
+        )?,
+        opcode => write!(f, "  {}{}", opcode, att_suffix(inst))?,
+    }
+    if !operands.is_empty() {
+        write!(f, " {}", operands.join(", "))?;
+    }
+    writeln!(f)
+}
+
+/// Returns the suffix of the operand size, which AT&T syntax puts on
+/// mnemonics instead of `ptr` on memory operands.
+fn att_suffix(inst: &InstructionData) -> &'static str {
+    match inst.opcode {
+        Opcode::JMP
+        | Opcode::JE
+        | Opcode::JNE
+        | Opcode::JLE
+        | Opcode::JL
+        | Opcode::JGE
+        | Opcode::JG
+        | Opcode::JA
+        | Opcode::JAE
+        | Opcode::JB
+        | Opcode::JBE
+        | Opcode::JP
+        | Opcode::JNP
+        // `setcc` only writes bytes, and `setb` is one of them.
+        | Opcode::SETE
+        | Opcode::SETNE
+        | Opcode::SETLE
+        | Opcode::SETL
+        | Opcode::SETGE
+        | Opcode::SETG
+        | Opcode::SETA
+        | Opcode::SETAE
+        | Opcode::SETB
+        | Opcode::SETBE
+        | Opcode::SETP
+        | Opcode::SETNP
+        // The operands of SSE instructions tell their sizes.
+        | Opcode::MOVAPSrr
+        | Opcode::MOVSSrm
+        | Opcode::MOVSDrm
+        | Opcode::MOVSSmr
+        | Opcode::MOVSDmr
+        | Opcode::ADDSSrr
+        | Opcode::ADDSDrr
+        | Opcode::SUBSSrr
+        | Opcode::SUBSDrr
+        | Opcode::MULSSrr
+        | Opcode::MULSDrr
+        | Opcode::DIVSSrr
+        | Opcode::DIVSDrr
+        | Opcode::XORPSrr
+        | Opcode::UCOMISSrr
+        | Opcode::UCOMISDrr
+        | Opcode::CVTSI2SSrr
+        | Opcode::CVTSI2SDrr
+        | Opcode::CVTTSS2SIrr
+        | Opcode::CVTTSD2SIrr
+        | Opcode::CVTSS2SDrr
+        | Opcode::CVTSD2SSrr
+        | Opcode::MOVUPSrm
+        | Opcode::MOVUPSmr
+        | Opcode::PADDBrr
+        | Opcode::PADDWrr
+        | Opcode::PADDDrr
+        | Opcode::PADDQrr
+        | Opcode::PSUBBrr
+        | Opcode::PSUBWrr
+        | Opcode::PSUBDrr
+        | Opcode::PSUBQrr
+        | Opcode::PMULLWrr
+        | Opcode::ADDPSrr
+        | Opcode::ADDPDrr
+        | Opcode::SUBPSrr
+        | Opcode::SUBPDrr
+        | Opcode::MULPSrr
+        | Opcode::MULPDrr
+        | Opcode::DIVPSrr
+        | Opcode::DIVPDrr
+        | Opcode::PANDrr
+        | Opcode::PORrr
+        | Opcode::PXORrr
+        | Opcode::PCMPEQBrr
+        | Opcode::PCMPEQWrr
+        | Opcode::PCMPEQDrr
+        | Opcode::PCMPGTBrr
+        | Opcode::PCMPGTWrr
+        | Opcode::PCMPGTDrr
+        | Opcode::CMPPSrri
+        | Opcode::CMPPDrri
+        | Opcode::PSHUFDrri
+        | Opcode::SHUFPSrri
+        | Opcode::MOVDr32x
+        | Opcode::MOVQr64x
+        | Opcode::PEXTRWrri
+        | Opcode::PINSRWrri
+        | Opcode::CALL
+        | Opcode::CALLplt
+        | Opcode::CALLr64
+        | Opcode::RET
+        | Opcode::CFI
+        | Opcode::LANDINGPAD
+        | Opcode::Phi => "",
+        Opcode::PUSH64 | Opcode::POP64 => "q",
+        Opcode::PUSH32 | Opcode::PUSHi32 | Opcode::POP32 => "l",
+        // The size of the register operands, or that of the memory operand
+        _ => {
+            let mut operands = inst.operands.iter().filter(|op| !op.implicit);
+            while let Some(op) = operands.next() {
+                match op.data {
+                    OperandData::Reg(Reg(class, _)) if class == RegClass::GR64 as u16 => {
+                        return "q"
+                    }
+                    OperandData::Reg(Reg(class, _)) if class == RegClass::GR8 as u16 => return "b",
+                    OperandData::Reg(Reg(class, _)) if class == RegClass::GR16 as u16 => {
+                        return "w"
+                    }
+                    OperandData::Reg(_) => return "l",
+                    OperandData::MemStart => {
+                        operands.nth(4);
+                    }
+                    _ => {}
+                }
+            }
+            match mem_size(&inst.opcode) {
+                "byte" => "b",
+                "word" => "w",
+                "dword" => "l",
+                _ => "q",
+            }
+        }
+    }
+}
+
+fn att_operand(opcode: Opcode, op: &OperandData, fn_idx: usize) -> String {
+    match (opcode, op) {
+        (Opcode::LEAr64rip, OperandData::GlobalAddress(name)) => return format!("{}(%rip)", name),
+        (Opcode::MOVr64got, OperandData::GlobalAddress(name)) => {
+            return format!("{}@GOTPCREL(%rip)", name)
+        }
+        (Opcode::CALLplt, OperandData::Label(name)) => return format!("{}@PLT", name),
+        (Opcode::CALLr64, OperandData::Reg(r)) => return format!("*%{}", reg_to_str(r)),
+        _ => {}
+    }
+    match op {
+        OperandData::Reg(r) => format!("%{}", reg_to_str(r)),
+        OperandData::VReg(r) => format!("%{}", r.0),
+        OperandData::Slot(slot) => format!("{:?}", slot),
+        OperandData::Int32(i) => format!("${}", i),
+        OperandData::Int64(i) => format!("${}", i),
+        OperandData::Block(block) => format!(".LBL{}_{}", fn_idx, block.index()),
+        OperandData::Label(name) => name.to_string(),
+        OperandData::MemStart => String::new(),
+        OperandData::GlobalAddress(name) => format!("${}", name),
+        OperandData::ConstantPool(i) => format!(".LCPI{}_{}(%rip)", fn_idx, i),
+        OperandData::Cfi(cfi) => format!("{:?}", cfi),
+        OperandData::None => "none".to_string(),
+    }
+}
+
+fn att_mem_op(args: &[Operand]) -> String {
+    assert!(matches!(&args[0].data, &OperandData::None)); // assure slot is eliminated
+    match (
+        disp(&args[1].data),
+        &args[2].data,
+        &args[3].data,
+        &args[4].data,
+    ) {
+        (imm, OperandData::Reg(reg), OperandData::None, OperandData::None) => {
+            format!("{}(%{})", imm, reg_to_str(reg))
+        }
+        (imm, OperandData::Reg(reg1), OperandData::Reg(reg2), OperandData::Int32(shift)) => {
+            format!(
+                "{}(%{},%{},{})",
+                imm,
+                reg_to_str(reg1),
+                reg_to_str(reg2),
+                shift
+            )
+        }
+        _ => todo!(),
+    }
+}
+
+/// Returns the displacement of a memory operand, 0 if it has none.
+fn disp(data: &OperandData) -> i32 {
+    match data {
+        OperandData::Int32(imm) => *imm,
+        OperandData::None => 0,
+        _ => todo!(),
+    }
+}
+
+impl PrintFunction for X86_64 {
+    fn print_function(func: &Function<Self>, fn_idx: usize, syntax: AsmSyntax) -> String {
+        struct Printer<'a>(&'a Function<X86_64>, usize, AsmSyntax);
+        impl fmt::Display for Printer<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                print_function(f, self.0, self.1, self.2)
+            }
+        }
+        Printer(func, fn_idx, syntax).to_string()
+    }
+}
+
 impl fmt::Display for Module<X86_64> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         print(f, self)
@@ -98,20 +513,107 @@ impl fmt::Display for Opcode {
             f,
             "{}",
             match self {
-                Self::PUSH64 => "push",
-                Self::POP64 => "pop",
+                Self::PUSH64 | Self::PUSH32 | Self::PUSHi32 => "push",
+                Self::POP64 | Self::POP32 => "pop",
                 Self::ADDr64i32 => "add",
                 Self::ADDri32 => "add",
                 Self::ADDrr32 => "add",
                 Self::SUBri32 | Self::SUBrr32 | Self::SUBr64i32 => "sub",
+                Self::ADCri32 | Self::ADCrr32 => "adc",
+                Self::SBBri32 | Self::SBBrr32 => "sbb",
+                Self::ANDri32 | Self::ANDrr32 => "and",
+                Self::ORri32 | Self::ORrr32 => "or",
+                Self::NEGr32 => "neg",
+                Self::IMULrr32 => "imul",
+                Self::MULr64 => "mul",
+                Self::CDQ => "cdq",
+                Self::CQO => "cqo",
+                Self::IDIVr32 => "idiv",
+                Self::SHLri8 | Self::SHLrCL => "shl",
+                Self::SHRri8 | Self::SHRrCL => "shr",
+                Self::SARri8 => "sar",
+                Self::SHLDrri8 | Self::SHLDrrCL => "shld",
+                Self::SHRDrri8 | Self::SHRDrrCL => "shrd",
+                Self::TESTri32 => "test",
+                Self::CMOVNErr32 => "cmovne",
                 Self::MOVrr32 => "mov",
                 Self::MOVrr64 => "mov",
                 Self::MOVri32 => "mov",
+                Self::MOVri64 => "movabs",
                 Self::MOVrm32 => "mov",
                 Self::MOVmi32 => "mov",
                 Self::MOVmr32 => "mov",
+                Self::MOVrm64 | Self::MOVmr64 | Self::MOVm64i32 => "mov",
+                Self::MOVmr8 | Self::MOVmi8 | Self::MOVmr16 | Self::MOVmi16 => "mov",
+                Self::MOVZXr64r32 => "mov",
                 Self::MOVSXDr64r32 | Self::MOVSXDr64m32 => "movsxd",
-                Self::CMPri32 => "cmp",
+                Self::MOVSXr32r8 | Self::MOVSXr32m8 | Self::MOVSXr32r16 | Self::MOVSXr32m16 => {
+                    "movsx"
+                }
+                Self::MOVZXr32r8 | Self::MOVZXr32m8 | Self::MOVZXr32r16 | Self::MOVZXr32m16 => {
+                    "movzx"
+                }
+                Self::CMPri8
+                | Self::CMPri16
+                | Self::CMPri32
+                | Self::CMPrr8
+                | Self::CMPrr16
+                | Self::CMPrr32 => "cmp",
+                Self::MOVAPSrr => "movaps",
+                Self::MOVSSrm | Self::MOVSSmr => "movss",
+                Self::MOVSDrm | Self::MOVSDmr => "movsd",
+                Self::ADDSSrr => "addss",
+                Self::ADDSDrr => "addsd",
+                Self::SUBSSrr => "subss",
+                Self::SUBSDrr => "subsd",
+                Self::MULSSrr => "mulss",
+                Self::MULSDrr => "mulsd",
+                Self::DIVSSrr => "divss",
+                Self::DIVSDrr => "divsd",
+                Self::XORPSrr => "xorps",
+                Self::UCOMISSrr => "ucomiss",
+                Self::UCOMISDrr => "ucomisd",
+                Self::CVTSI2SSrr => "cvtsi2ss",
+                Self::CVTSI2SDrr => "cvtsi2sd",
+                Self::CVTTSS2SIrr => "cvttss2si",
+                Self::CVTTSD2SIrr => "cvttsd2si",
+                Self::CVTSS2SDrr => "cvtss2sd",
+                Self::CVTSD2SSrr => "cvtsd2ss",
+                Self::MOVUPSrm | Self::MOVUPSmr => "movups",
+                Self::PADDBrr => "paddb",
+                Self::PADDWrr => "paddw",
+                Self::PADDDrr => "paddd",
+                Self::PADDQrr => "paddq",
+                Self::PSUBBrr => "psubb",
+                Self::PSUBWrr => "psubw",
+                Self::PSUBDrr => "psubd",
+                Self::PSUBQrr => "psubq",
+                Self::PMULLWrr => "pmullw",
+                Self::ADDPSrr => "addps",
+                Self::ADDPDrr => "addpd",
+                Self::SUBPSrr => "subps",
+                Self::SUBPDrr => "subpd",
+                Self::MULPSrr => "mulps",
+                Self::MULPDrr => "mulpd",
+                Self::DIVPSrr => "divps",
+                Self::DIVPDrr => "divpd",
+                Self::PANDrr => "pand",
+                Self::PORrr => "por",
+                Self::PXORrr => "pxor",
+                Self::PCMPEQBrr => "pcmpeqb",
+                Self::PCMPEQWrr => "pcmpeqw",
+                Self::PCMPEQDrr => "pcmpeqd",
+                Self::PCMPGTBrr => "pcmpgtb",
+                Self::PCMPGTWrr => "pcmpgtw",
+                Self::PCMPGTDrr => "pcmpgtd",
+                Self::CMPPSrri => "cmpps",
+                Self::CMPPDrri => "cmppd",
+                Self::PSHUFDrri => "pshufd",
+                Self::SHUFPSrri => "shufps",
+                Self::MOVDr32x => "movd",
+                Self::MOVQr64x => "movq",
+                Self::PEXTRWrri => "pextrw",
+                Self::PINSRWrri => "pinsrw",
                 Self::JMP => "jmp",
                 Self::JE => "je",
                 Self::JNE => "jne",
@@ -119,24 +621,67 @@ impl fmt::Display for Opcode {
                 Self::JL => "jl",
                 Self::JGE => "jge",
                 Self::JG => "jg",
-                Self::CALL => "call",
+                Self::JA => "ja",
+                Self::JAE => "jae",
+                Self::JB => "jb",
+                Self::JBE => "jbe",
+                Self::JP => "jp",
+                Self::JNP => "jnp",
+                Self::SETE => "sete",
+                Self::SETNE => "setne",
+                Self::SETLE => "setle",
+                Self::SETL => "setl",
+                Self::SETGE => "setge",
+                Self::SETG => "setg",
+                Self::SETA => "seta",
+                Self::SETAE => "setae",
+                Self::SETB => "setb",
+                Self::SETBE => "setbe",
+                Self::SETP => "setp",
+                Self::SETNP => "setnp",
+                Self::LEAr64rip => "lea",
+                Self::MOVr64got => "mov",
+                Self::MOVABSr64 => "movabs",
+                Self::CALL | Self::CALLplt | Self::CALLr64 => "call",
                 Self::RET => "ret",
+                Self::CFI => "CFI",
+                Self::LANDINGPAD => "LANDINGPAD",
                 Self::Phi => "PHI",
             }
         )
     }
 }
 
-fn write_operand(f: &mut fmt::Formatter<'_>, op: &OperandData, fn_idx: usize) -> fmt::Result {
+fn write_operand(
+    f: &mut fmt::Formatter<'_>,
+    opcode: Opcode,
+    op: &OperandData,
+    fn_idx: usize,
+) -> fmt::Result {
+    match (opcode, op) {
+        (Opcode::LEAr64rip, OperandData::GlobalAddress(name)) => {
+            return write!(f, "[rip + {}]", name)
+        }
+        (Opcode::MOVr64got, OperandData::GlobalAddress(name)) => {
+            return write!(f, "qword ptr [rip + {}@GOTPCREL]", name)
+        }
+        (Opcode::CALLplt, OperandData::Label(name)) => return write!(f, "{}@PLT", name),
+        _ => {}
+    }
     match op {
         OperandData::Reg(r) => write!(f, "{}", reg_to_str(r)),
         OperandData::VReg(r) => write!(f, "%{}", r.0),
         OperandData::Slot(slot) => write!(f, "{:?}", slot),
         OperandData::Int32(i) => write!(f, "{}", i),
+        OperandData::Int64(i) => write!(f, "{}", i),
         OperandData::Block(block) => write!(f, ".LBL{}_{}", fn_idx, block.index()),
         OperandData::Label(name) => write!(f, "{}", name),
         OperandData::MemStart => Ok(()),
         OperandData::GlobalAddress(name) => write!(f, "offset {}", name),
+        OperandData::ConstantPool(i) => {
+            write!(f, "{} ptr [rip + .LCPI{}_{}]", mem_size(&opcode), fn_idx, i)
+        }
+        OperandData::Cfi(cfi) => write!(f, "{:?}", cfi),
         OperandData::None => write!(f, "none"),
     }
 }
@@ -160,33 +705,56 @@ fn write_operand(f: &mut fmt::Formatter<'_>, op: &OperandData, fn_idx: usize) ->
 
 fn mem_size(opcode: &Opcode) -> &'static str {
     match opcode {
-        Opcode::MOVrm32 | Opcode::MOVmi32 | Opcode::MOVmr32 | Opcode::MOVSXDr64m32 => "dword",
+        Opcode::MOVmr8
+        | Opcode::MOVmi8
+        | Opcode::MOVSXr32r8
+        | Opcode::MOVSXr32m8
+        | Opcode::MOVZXr32r8
+        | Opcode::MOVZXr32m8 => "byte",
+        Opcode::MOVmr16
+        | Opcode::MOVmi16
+        | Opcode::MOVSXr32r16
+        | Opcode::MOVSXr32m16
+        | Opcode::MOVZXr32r16
+        | Opcode::MOVZXr32m16 => "word",
+        Opcode::MOVrm32
+        | Opcode::MOVmi32
+        | Opcode::MOVmr32
+        | Opcode::MOVSXDr64m32
+        | Opcode::MOVSSrm
+        | Opcode::MOVSSmr => "dword",
+        Opcode::MOVrm64
+        | Opcode::MOVmr64
+        | Opcode::MOVm64i32
+        | Opcode::MOVSDrm
+        | Opcode::MOVSDmr => "qword",
+        Opcode::MOVUPSrm | Opcode::MOVUPSmr => "xmmword",
         _ => todo!(),
     }
 }
 
 fn mem_op(args: &[Operand]) -> String {
     assert!(matches!(&args[0].data, &OperandData::None)); // assure slot is eliminated
-    match (&args[1].data, &args[2].data, &args[3].data, &args[4].data) {
-        (OperandData::Int32(imm), OperandData::Reg(reg), OperandData::None, OperandData::None) => {
+    match (
+        disp(&args[1].data),
+        &args[2].data,
+        &args[3].data,
+        &args[4].data,
+    ) {
+        (imm, OperandData::Reg(reg), OperandData::None, OperandData::None) => {
             format!(
                 "[{}{}{}]",
                 reg_to_str(reg),
-                if *imm < 0 { "" } else { "+" },
-                *imm
+                if imm < 0 { "" } else { "+" },
+                imm
             )
         }
-        (
-            OperandData::Int32(imm),
-            OperandData::Reg(reg1),
-            OperandData::Reg(reg2),
-            OperandData::Int32(shift),
-        ) => {
+        (imm, OperandData::Reg(reg1), OperandData::Reg(reg2), OperandData::Int32(shift)) => {
             format!(
                 "[{}{}{}+{}*{}]",
                 reg_to_str(reg1),
-                if *imm < 0 { "" } else { "+" },
-                *imm,
+                if imm < 0 { "" } else { "+" },
+                imm,
                 reg_to_str(reg2),
                 shift
             )
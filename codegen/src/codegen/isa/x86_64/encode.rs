@@ -0,0 +1,879 @@
+//! Machine code for x86-64.
+//!
+//! [`encode_function`] turns a function, after all passes have run, into the
+//! bytes of its instructions along with relocations, as kept by a
+//! [`CodeCache`](crate::codegen::code_cache::CodeCache) or written to an
+//! object file by [`encode_module`]. Each instruction is encoded as the
+//! assembler would encode its line in the printed assembly:
+//!
+//! - The operand size is that of the registers, e.g. `add rax, 1` for
+//!   `ADDri32` on a 64-bit register, or of the memory operand. Instructions
+//!   of 8 or 16 bits access the low parts of the registers allocated, as
+//!   given by [`InstructionData::with_sized_regs`].
+//! - Immediates of arithmetic take the short form when they fit in a byte.
+//!   Shift counts are always a byte, or `cl`.
+//! - Jumps between blocks always take a 32-bit displacement, so that the
+//!   layout of the code is known before their targets are.
+//! - Calls refer to their callees by [`Call4`](RelocKind::Call4)
+//!   relocations, which the linker directs to the PLT if needed.
+//! - The address of a global variable is computed relative to `rip` by a
+//!   [`PcRel4`](RelocKind::PcRel4) relocation, as `lea reg, [rip + name]`,
+//!   even in static code. It is loaded from the GOT by a
+//!   [`GotPcRel4`](RelocKind::GotPcRel4) relocation for `MOVr64got`, and
+//!   written in full by an [`Abs8`](RelocKind::Abs8) relocation for
+//!   `MOVABSr64`.
+//! - Floating-point and vector constants are loaded relative to `rip` from the entries
+//!   of the constant pool of the function, each a local read-only data
+//!   object named by [`constant_pool_symbol`].
+//! - Call frame information directives emit no code. No unwind tables are
+//!   written along with the code.
+
+use crate::codegen::{
+    code_cache::{CompiledFunction, RelocKind, Relocation},
+    function::{basic_block::BasicBlockId, Function},
+    isa::x86_64::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        register::RegClass,
+        X86_64,
+    },
+    module::Module,
+    object::{section_of, Binding, DataKind, DataObject, ObjectModule},
+    register::Reg,
+};
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use std::{error::Error, fmt};
+
+#[derive(Debug)]
+pub enum EncodingError {
+    /// The instruction or its operands have no encoding in 64-bit mode.
+    Unsupported(String),
+    /// The function was reused from a
+    /// [`CodegenCache`](crate::codegen::cache::CodegenCache), which only
+    /// keeps its assembly text.
+    Precompiled(String),
+}
+
+/// Returns the code of `function` and its relocations. The symbols they
+/// refer to are numbered by `symbol`, e.g. [`ObjectModule::symbol`].
+pub fn encode_function(
+    function: &Function<X86_64>,
+    mut symbol: impl FnMut(&str) -> u32,
+) -> Result<(Vec<u8>, Vec<Relocation>)> {
+    let mut enc = Encoder {
+        constant_pool: (0..function.constant_pool.len())
+            .map(|i| symbol(&constant_pool_symbol(&function.name, i)))
+            .collect(),
+        ..Default::default()
+    };
+    for block in function.layout.block_iter() {
+        enc.block_offsets.insert(block, enc.code.len() as u32);
+        for inst in function.layout.inst_iter(block) {
+            enc.encode(&function.data.inst_ref(inst).data, &mut symbol)?;
+        }
+    }
+    for (offset, block) in enc.fixups {
+        let rel = enc.block_offsets[&block] as i32 - (offset as i32 + 4);
+        enc.code[offset as usize..offset as usize + 4].copy_from_slice(&rel.to_le_bytes());
+    }
+    Ok((enc.code, enc.relocs))
+}
+
+/// Returns the encoded functions and the global variables of `module`, to
+/// be written to an object file. Prototypes are left as undefined symbols.
+pub fn encode_module(module: &Module<X86_64>) -> Result<ObjectModule> {
+    let mut obj = ObjectModule::new();
+    for (i, (_, func)) in module.functions.iter().enumerate() {
+        if module.precompiled.contains_key(&i) {
+            return Err(EncodingError::Precompiled(func.name.clone()).into());
+        }
+        obj.set_visibility(&func.name, func.visibility);
+        if func.is_prototype {
+            continue;
+        }
+        let binding = Binding::of_linkage(Some(func.linkage));
+        let func_section = func.section.clone();
+        let func_comdat = func.comdat.clone();
+        for (i, bytes) in func.constant_pool.iter().enumerate() {
+            let bytes = bytes.clone();
+            obj.add_data(DataObject {
+                name: constant_pool_symbol(&func.name, i),
+                kind: DataKind::ReadOnly,
+                binding: Binding::Local,
+                align: bytes.len() as u32,
+                bytes,
+                relocs: vec![],
+            });
+        }
+        let mut symbols = vec![];
+        let (code, relocs) = encode_function(func, |name| {
+            symbols.push(name.to_owned());
+            symbols.len() as u32 - 1
+        })?;
+        let func = CompiledFunction {
+            name: func.name.clone(),
+            ir_hash: 0,
+            code,
+            relocs,
+        };
+        if let Some(section) = section_of(
+            func_section.as_deref(),
+            ".text",
+            &func.name,
+            module.function_sections || func_comdat.is_some(),
+        ) {
+            obj.set_section(&func.name, section);
+        }
+        if let Some(comdat) = func_comdat {
+            obj.set_comdat(&func.name, comdat);
+        }
+        obj.add_function(&func, &symbols, binding);
+    }
+    obj.add_global_variables(module)?;
+    Ok(obj)
+}
+
+/// Returns the name of the symbol of the `i`th entry of the constant pool of
+/// the function `function`.
+pub fn constant_pool_symbol(function: &str, i: usize) -> String {
+    format!(".LCPI.{}.{}", function, i)
+}
+
+#[derive(Default)]
+struct Encoder {
+    code: Vec<u8>,
+    relocs: Vec<Relocation>,
+    /// The symbols of the entries of the constant pool.
+    constant_pool: Vec<u32>,
+    block_offsets: FxHashMap<BasicBlockId, u32>,
+    /// The offsets of the displacements of jumps, with their targets.
+    fixups: Vec<(u32, BasicBlockId)>,
+}
+
+/// A memory operand, `[base + disp + index*scale]`.
+struct Mem {
+    base: Reg,
+    disp: i32,
+    index: Option<(Reg, i32)>,
+}
+
+/// An explicit operand, as printed.
+enum Arg<'a> {
+    Data(&'a OperandData),
+    Mem(Mem),
+}
+
+impl Encoder {
+    fn encode(
+        &mut self,
+        inst: &InstructionData,
+        symbol: &mut impl FnMut(&str) -> u32,
+    ) -> Result<()> {
+        let inst = &*inst.with_sized_regs();
+        let unsupported = || EncodingError::Unsupported(format!("{:?}", inst));
+        let args = explicit_args(&inst.operands).ok_or_else(unsupported)?;
+        match (inst.opcode, args.as_slice()) {
+            (Opcode::PUSH64, [Arg::Data(OperandData::Reg(r))]) => self.short(0x50, *r),
+            (Opcode::POP64, [Arg::Data(OperandData::Reg(r))]) => self.short(0x58, *r),
+            (Opcode::PUSHi32, [Arg::Data(OperandData::Int32(i))]) => {
+                self.code.push(0x68);
+                self.imm32(*i);
+            }
+            (
+                Opcode::ADDr64i32
+                | Opcode::ADDri32
+                | Opcode::SUBr64i32
+                | Opcode::SUBri32
+                | Opcode::ADCri32
+                | Opcode::SBBri32
+                | Opcode::ANDri32
+                | Opcode::ORri32,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::Int32(i))],
+            ) => {
+                let ext = match inst.opcode {
+                    Opcode::ADDr64i32 | Opcode::ADDri32 => 0,
+                    Opcode::ORri32 => 1,
+                    Opcode::ADCri32 => 2,
+                    Opcode::SBBri32 => 3,
+                    Opcode::ANDri32 => 4,
+                    _ => 5,
+                };
+                self.arith_imm(ext, *r, *i);
+            }
+            (
+                Opcode::ADCrr32 | Opcode::SBBrr32,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => {
+                let op = match inst.opcode {
+                    Opcode::ADCrr32 => 0x11,
+                    _ => 0x19,
+                };
+                self.rr(&[op], is_64(*dst), *src, *dst);
+            }
+            (
+                Opcode::IMULrr32 | Opcode::CMOVNErr32,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => {
+                let op = match inst.opcode {
+                    Opcode::IMULrr32 => 0xaf,
+                    _ => 0x45,
+                };
+                self.rr(&[0x0f, op], is_64(*dst), *dst, *src);
+            }
+            (
+                Opcode::NEGr32 | Opcode::MULr64 | Opcode::IDIVr32,
+                [Arg::Data(OperandData::Reg(r))],
+            ) => {
+                let ext = match inst.opcode {
+                    Opcode::NEGr32 => 3,
+                    Opcode::MULr64 => 4,
+                    _ => 7,
+                };
+                self.rr(&[0xf7], is_64(*r), Reg(0, ext), *r)
+            }
+            (Opcode::CDQ, []) => self.code.push(0x99),
+            (Opcode::CQO, []) => self.code.extend_from_slice(&[0x48, 0x99]),
+            (
+                Opcode::SHLri8 | Opcode::SHRri8 | Opcode::SARri8,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::Int32(i))],
+            ) => {
+                self.rr(&[0xc1], is_64(*r), Reg(0, shift_ext(inst.opcode)), *r);
+                self.code.push(*i as u8);
+            }
+            (
+                Opcode::SHLrCL | Opcode::SHRrCL,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::Reg(_))],
+            ) => self.rr(&[0xd3], is_64(*r), Reg(0, shift_ext(inst.opcode)), *r),
+            (
+                Opcode::SHLDrri8 | Opcode::SHRDrri8,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src)), Arg::Data(OperandData::Int32(i))],
+            ) => {
+                let op = match inst.opcode {
+                    Opcode::SHLDrri8 => 0xa4,
+                    _ => 0xac,
+                };
+                self.rr(&[0x0f, op], is_64(*dst), *src, *dst);
+                self.code.push(*i as u8);
+            }
+            (
+                Opcode::SHLDrrCL | Opcode::SHRDrrCL,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src)), Arg::Data(OperandData::Reg(_))],
+            ) => {
+                let op = match inst.opcode {
+                    Opcode::SHLDrrCL => 0xa5,
+                    _ => 0xad,
+                };
+                self.rr(&[0x0f, op], is_64(*dst), *src, *dst);
+            }
+            (
+                Opcode::TESTri32,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::Int32(i))],
+            ) => {
+                self.rr(&[0xf7], is_64(*r), Reg(0, 0), *r);
+                self.imm32(*i);
+            }
+            (
+                Opcode::CMPri32,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::Int32(i))],
+            ) => self.arith_imm(7, *r, *i),
+            (
+                Opcode::CMPri8,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::Int32(i))],
+            ) => {
+                self.rr(&[0x80], false, Reg(0, 7), *r);
+                self.code.push(*i as u8);
+            }
+            (
+                Opcode::CMPri16,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::Int32(i))],
+            ) => {
+                self.code.push(0x66);
+                if let Ok(i) = i8::try_from(*i) {
+                    self.rr(&[0x83], false, Reg(0, 7), *r);
+                    self.code.push(i as u8);
+                } else {
+                    self.rr(&[0x81], false, Reg(0, 7), *r);
+                    self.code.extend_from_slice(&(*i as i16).to_le_bytes());
+                }
+            }
+            (
+                Opcode::ADDrr32
+                | Opcode::SUBrr32
+                | Opcode::ANDrr32
+                | Opcode::ORrr32
+                | Opcode::CMPrr32
+                | Opcode::MOVrr32
+                | Opcode::MOVrr64,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => {
+                let op = match inst.opcode {
+                    Opcode::ADDrr32 => 0x01,
+                    Opcode::ORrr32 => 0x09,
+                    Opcode::ANDrr32 => 0x21,
+                    Opcode::SUBrr32 => 0x29,
+                    Opcode::CMPrr32 => 0x39,
+                    _ => 0x89,
+                };
+                self.rr(&[op], is_64(*dst), *src, *dst);
+            }
+            (Opcode::CMPrr8, [Arg::Data(OperandData::Reg(a)), Arg::Data(OperandData::Reg(b))]) => {
+                self.rr(&[0x38], false, *b, *a)
+            }
+            (Opcode::CMPrr16, [Arg::Data(OperandData::Reg(a)), Arg::Data(OperandData::Reg(b))]) => {
+                self.code.push(0x66);
+                self.rr(&[0x39], false, *b, *a)
+            }
+            (
+                Opcode::MOVri32,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::Int32(i))],
+            ) => {
+                if is_64(*r) {
+                    self.rr(&[0xc7], true, Reg(0, 0), *r);
+                } else {
+                    self.short(0xb8, *r);
+                }
+                self.imm32(*i);
+            }
+            (
+                Opcode::MOVri64,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::Int64(i))],
+            ) => {
+                self.rex(true, Reg(0, 0), None, Some(*r));
+                self.code.push(0xb8 | hw(*r) & 7);
+                self.code.extend_from_slice(&i.to_le_bytes());
+            }
+            (
+                Opcode::MOVZXr64r32,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => self.rr(&[0x89], false, *src, *dst),
+            (
+                Opcode::MOVri32 | Opcode::LEAr64rip,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::GlobalAddress(name))],
+            ) => self.rip_relative(&[0x8d], *r, RelocKind::PcRel4, symbol(name)),
+            (
+                Opcode::MOVr64got,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::GlobalAddress(name))],
+            ) => self.rip_relative(&[0x8b], *r, RelocKind::GotPcRel4, symbol(name)),
+            (
+                Opcode::MOVABSr64,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::GlobalAddress(name))],
+            ) => {
+                self.rex(true, Reg(0, 0), None, Some(*r));
+                self.code.push(0xb8 | hw(*r) & 7);
+                self.relocs.push(Relocation {
+                    offset: self.code.len() as u32,
+                    kind: RelocKind::Abs8,
+                    symbol: symbol(name),
+                    addend: 0,
+                });
+                self.code.extend_from_slice(&[0; 8]);
+            }
+            (
+                Opcode::MOVrm32 | Opcode::MOVrm64,
+                [Arg::Data(OperandData::Reg(r)), Arg::Mem(mem)],
+            ) => self.rm(&[0x8b], is_64(*r), *r, mem),
+            (
+                Opcode::MOVmr32 | Opcode::MOVmr64,
+                [Arg::Mem(mem), Arg::Data(OperandData::Reg(r))],
+            ) => self.rm(&[0x89], is_64(*r), *r, mem),
+            (Opcode::MOVmr8, [Arg::Mem(mem), Arg::Data(OperandData::Reg(r))]) => {
+                self.rm(&[0x88], false, *r, mem)
+            }
+            (Opcode::MOVmr16, [Arg::Mem(mem), Arg::Data(OperandData::Reg(r))]) => {
+                self.code.push(0x66);
+                self.rm(&[0x89], false, *r, mem)
+            }
+            (Opcode::MOVmi8, [Arg::Mem(mem), Arg::Data(OperandData::Int32(i))]) => {
+                self.rm(&[0xc6], false, Reg(0, 0), mem);
+                self.code.push(*i as u8);
+            }
+            (Opcode::MOVmi16, [Arg::Mem(mem), Arg::Data(OperandData::Int32(i))]) => {
+                self.code.push(0x66);
+                self.rm(&[0xc7], false, Reg(0, 0), mem);
+                self.code.extend_from_slice(&(*i as i16).to_le_bytes());
+            }
+            (
+                Opcode::MOVmi32 | Opcode::MOVm64i32,
+                [Arg::Mem(mem), Arg::Data(OperandData::Int32(i))],
+            ) => {
+                let w = matches!(inst.opcode, Opcode::MOVm64i32);
+                self.rm(&[0xc7], w, Reg(0, 0), mem);
+                self.imm32(*i);
+            }
+            (
+                Opcode::MOVSXDr64r32,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => self.rr(&[0x63], true, *dst, *src),
+            (Opcode::MOVSXDr64m32, [Arg::Data(OperandData::Reg(r)), Arg::Mem(mem)]) => {
+                self.rm(&[0x63], true, *r, mem)
+            }
+            (
+                Opcode::MOVSXr32r8 | Opcode::MOVSXr32r16 | Opcode::MOVZXr32r8 | Opcode::MOVZXr32r16,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => self.rr(&[0x0f, ext_opcode(inst.opcode)], is_64(*dst), *dst, *src),
+            (
+                Opcode::MOVSXr32m8 | Opcode::MOVSXr32m16 | Opcode::MOVZXr32m8 | Opcode::MOVZXr32m16,
+                [Arg::Data(OperandData::Reg(r)), Arg::Mem(mem)],
+            ) => self.rm(&[0x0f, ext_opcode(inst.opcode)], is_64(*r), *r, mem),
+            (
+                Opcode::MOVAPSrr | Opcode::XORPSrr,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => {
+                let op = match inst.opcode {
+                    Opcode::MOVAPSrr => 0x28,
+                    _ => 0x57,
+                };
+                self.rr(&[0x0f, op], false, *dst, *src);
+            }
+            (
+                Opcode::MOVSSrm | Opcode::MOVSDrm,
+                [Arg::Data(OperandData::Reg(r)), Arg::Mem(mem)],
+            ) => {
+                self.code.push(scalar_prefix(inst.opcode));
+                self.rm(&[0x0f, 0x10], false, *r, mem)
+            }
+            (
+                Opcode::MOVSSrm | Opcode::MOVSDrm,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::ConstantPool(i))],
+            ) => {
+                self.code.push(scalar_prefix(inst.opcode));
+                let symbol = self.constant_pool[*i];
+                self.rip_relative(&[0x0f, 0x10], *r, RelocKind::PcRel4, symbol)
+            }
+            (
+                Opcode::MOVSSmr | Opcode::MOVSDmr,
+                [Arg::Mem(mem), Arg::Data(OperandData::Reg(r))],
+            ) => {
+                self.code.push(scalar_prefix(inst.opcode));
+                self.rm(&[0x0f, 0x11], false, *r, mem)
+            }
+            (
+                Opcode::ADDSSrr
+                | Opcode::ADDSDrr
+                | Opcode::SUBSSrr
+                | Opcode::SUBSDrr
+                | Opcode::MULSSrr
+                | Opcode::MULSDrr
+                | Opcode::DIVSSrr
+                | Opcode::DIVSDrr
+                | Opcode::CVTSS2SDrr
+                | Opcode::CVTSD2SSrr,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => {
+                let op = match inst.opcode {
+                    Opcode::ADDSSrr | Opcode::ADDSDrr => 0x58,
+                    Opcode::MULSSrr | Opcode::MULSDrr => 0x59,
+                    Opcode::SUBSSrr | Opcode::SUBSDrr => 0x5c,
+                    Opcode::DIVSSrr | Opcode::DIVSDrr => 0x5e,
+                    _ => 0x5a,
+                };
+                self.code.push(scalar_prefix(inst.opcode));
+                self.rr(&[0x0f, op], false, *dst, *src);
+            }
+            (
+                Opcode::UCOMISSrr | Opcode::UCOMISDrr,
+                [Arg::Data(OperandData::Reg(a)), Arg::Data(OperandData::Reg(b))],
+            ) => {
+                if matches!(inst.opcode, Opcode::UCOMISDrr) {
+                    self.code.push(0x66);
+                }
+                self.rr(&[0x0f, 0x2e], false, *a, *b);
+            }
+            (
+                Opcode::CVTSI2SSrr | Opcode::CVTSI2SDrr,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => {
+                self.code.push(scalar_prefix(inst.opcode));
+                self.rr(&[0x0f, 0x2a], is_64(*src), *dst, *src);
+            }
+            (
+                Opcode::CVTTSS2SIrr | Opcode::CVTTSD2SIrr,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => {
+                self.code.push(scalar_prefix(inst.opcode));
+                self.rr(&[0x0f, 0x2c], is_64(*dst), *dst, *src);
+            }
+            (Opcode::MOVUPSrm, [Arg::Data(OperandData::Reg(r)), Arg::Mem(mem)]) => {
+                self.rm(&[0x0f, 0x10], false, *r, mem)
+            }
+            (
+                Opcode::MOVUPSrm,
+                [Arg::Data(OperandData::Reg(r)), Arg::Data(OperandData::ConstantPool(i))],
+            ) => {
+                let symbol = self.constant_pool[*i];
+                self.rip_relative(&[0x0f, 0x10], *r, RelocKind::PcRel4, symbol)
+            }
+            (Opcode::MOVUPSmr, [Arg::Mem(mem), Arg::Data(OperandData::Reg(r))]) => {
+                self.rm(&[0x0f, 0x11], false, *r, mem)
+            }
+            (
+                Opcode::PADDBrr
+                | Opcode::PADDWrr
+                | Opcode::PADDDrr
+                | Opcode::PADDQrr
+                | Opcode::PSUBBrr
+                | Opcode::PSUBWrr
+                | Opcode::PSUBDrr
+                | Opcode::PSUBQrr
+                | Opcode::PMULLWrr
+                | Opcode::ADDPSrr
+                | Opcode::ADDPDrr
+                | Opcode::SUBPSrr
+                | Opcode::SUBPDrr
+                | Opcode::MULPSrr
+                | Opcode::MULPDrr
+                | Opcode::DIVPSrr
+                | Opcode::DIVPDrr
+                | Opcode::PANDrr
+                | Opcode::PORrr
+                | Opcode::PXORrr
+                | Opcode::PCMPEQBrr
+                | Opcode::PCMPEQWrr
+                | Opcode::PCMPEQDrr
+                | Opcode::PCMPGTBrr
+                | Opcode::PCMPGTWrr
+                | Opcode::PCMPGTDrr,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => {
+                let (prefix, op) = packed_opcode(inst.opcode);
+                self.code.extend(prefix);
+                self.rr(&[0x0f, op], false, *dst, *src);
+            }
+            (
+                Opcode::CMPPSrri
+                | Opcode::CMPPDrri
+                | Opcode::PSHUFDrri
+                | Opcode::SHUFPSrri
+                | Opcode::PEXTRWrri
+                | Opcode::PINSRWrri,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src)), Arg::Data(OperandData::Int32(i))],
+            ) => {
+                let (prefix, op) = packed_opcode(inst.opcode);
+                self.code.extend(prefix);
+                self.rr(&[0x0f, op], false, *dst, *src);
+                self.code.push(*i as u8);
+            }
+            // The XMM register is in `ModRM.reg`.
+            (
+                Opcode::MOVDr32x | Opcode::MOVQr64x,
+                [Arg::Data(OperandData::Reg(dst)), Arg::Data(OperandData::Reg(src))],
+            ) => {
+                self.code.push(0x66);
+                self.rr(&[0x0f, 0x7e], is_64(*dst), *src, *dst);
+            }
+            (Opcode::JMP, [Arg::Data(OperandData::Block(block))]) => self.jump(&[0xe9], *block),
+            (
+                Opcode::JE
+                | Opcode::JNE
+                | Opcode::JLE
+                | Opcode::JL
+                | Opcode::JGE
+                | Opcode::JG
+                | Opcode::JA
+                | Opcode::JAE
+                | Opcode::JB
+                | Opcode::JBE
+                | Opcode::JP
+                | Opcode::JNP,
+                [Arg::Data(OperandData::Block(block))],
+            ) => self.jump(&[0x0f, 0x80 | condition(inst.opcode)], *block),
+            (
+                Opcode::SETE
+                | Opcode::SETNE
+                | Opcode::SETLE
+                | Opcode::SETL
+                | Opcode::SETGE
+                | Opcode::SETG
+                | Opcode::SETA
+                | Opcode::SETAE
+                | Opcode::SETB
+                | Opcode::SETBE
+                | Opcode::SETP
+                | Opcode::SETNP,
+                [Arg::Data(OperandData::Reg(r))],
+            ) => self.rr(&[0x0f, 0x90 | condition(inst.opcode)], false, Reg(0, 0), *r),
+            (Opcode::CALL | Opcode::CALLplt, [Arg::Data(OperandData::Label(name))]) => {
+                self.code.push(0xe8);
+                self.reloc(RelocKind::Call4, symbol(name));
+            }
+            (Opcode::CALLr64, [Arg::Data(OperandData::Reg(r))]) => {
+                self.rr(&[0xff], false, Reg(0, 2), *r)
+            }
+            (Opcode::RET, []) => self.code.push(0xc3),
+            (Opcode::CFI, _) => {}
+            _ => return Err(unsupported().into()),
+        }
+        Ok(())
+    }
+
+    /// Emits a REX prefix if any of its bits is set, or if a byte register
+    /// is `spl`, `bpl`, `sil` or `dil`, which are only named with one.
+    fn rex(&mut self, w: bool, reg: Reg, index: Option<Reg>, base: Option<Reg>) {
+        let bit = |r: Option<Reg>| r.map_or(0, |r| hw(r) >> 3);
+        let rex = (w as u8) << 3 | bit(Some(reg)) << 2 | bit(index) << 1 | bit(base);
+        let low_byte = |r: Option<Reg>| {
+            r.is_some_and(|r| r.0 == RegClass::GR8 as u16 && (4..8).contains(&hw(r)))
+        };
+        if rex != 0 || low_byte(Some(reg)) || low_byte(base) {
+            self.code.push(0x40 | rex);
+        }
+    }
+
+    /// Emits `op` with the register in its low bits, as `push` does.
+    fn short(&mut self, op: u8, r: Reg) {
+        self.rex(false, Reg(0, 0), None, Some(r));
+        self.code.push(op | hw(r) & 7);
+    }
+
+    /// Emits `op` with a register operand in `ModRM.reg` and another in
+    /// `ModRM.rm`.
+    fn rr(&mut self, op: &[u8], w: bool, reg: Reg, rm: Reg) {
+        self.rex(w, reg, None, Some(rm));
+        self.code.extend_from_slice(op);
+        self.code.push(modrm(0b11, hw(reg), hw(rm)));
+    }
+
+    /// Emits `op` with a register operand in `ModRM.reg` and a memory operand.
+    fn rm(&mut self, op: &[u8], w: bool, reg: Reg, mem: &Mem) {
+        self.rex(w, reg, mem.index.map(|(r, _)| r), Some(mem.base));
+        self.code.extend_from_slice(op);
+
+        let base = hw(mem.base) & 7;
+        // `rbp` and `r13` as base take a displacement even when it is zero.
+        let (mod_, disp) = match mem.disp {
+            0 if base != 0b101 => (0b00, vec![]),
+            d if i8::try_from(d).is_ok() => (0b01, vec![d as u8]),
+            d => (0b10, d.to_le_bytes().to_vec()),
+        };
+        match mem.index {
+            Some((index, scale)) => {
+                self.code.push(modrm(mod_, hw(reg), 0b100));
+                self.code.push(sib(scale, hw(index), base));
+            }
+            // `rsp` and `r12` as base take a SIB byte.
+            None if base == 0b100 => {
+                self.code.push(modrm(mod_, hw(reg), 0b100));
+                self.code.push(sib(1, 0b100, base));
+            }
+            None => self.code.push(modrm(mod_, hw(reg), base)),
+        }
+        self.code.extend_from_slice(&disp);
+    }
+
+    /// Emits `op` with `r` in `ModRM.reg` and a memory operand relative to
+    /// `rip`, relocated by `kind` against `symbol`.
+    fn rip_relative(&mut self, op: &[u8], r: Reg, kind: RelocKind, symbol: u32) {
+        self.rex(is_64(r), r, None, None);
+        self.code.extend_from_slice(op);
+        self.code.push(modrm(0b00, hw(r), 0b101));
+        self.reloc(kind, symbol);
+    }
+
+    /// Emits an arithmetic instruction of `r` and an immediate, e.g. `add` or
+    /// `cmp`, chosen by the opcode extension `ext`.
+    fn arith_imm(&mut self, ext: u8, r: Reg, i: i32) {
+        if let Ok(i) = i8::try_from(i) {
+            self.rr(&[0x83], is_64(r), Reg(0, ext as u16), r);
+            self.code.push(i as u8);
+        } else {
+            self.rr(&[0x81], is_64(r), Reg(0, ext as u16), r);
+            self.imm32(i);
+        }
+    }
+
+    fn imm32(&mut self, i: i32) {
+        self.code.extend_from_slice(&i.to_le_bytes());
+    }
+
+    fn jump(&mut self, op: &[u8], block: BasicBlockId) {
+        self.code.extend_from_slice(op);
+        self.fixups.push((self.code.len() as u32, block));
+        self.imm32(0);
+    }
+
+    /// Emits a 4-byte field relocated by `kind` against `symbol`, ending the
+    /// instruction.
+    fn reloc(&mut self, kind: RelocKind, symbol: u32) {
+        self.relocs.push(Relocation {
+            offset: self.code.len() as u32,
+            kind,
+            symbol,
+            addend: -4,
+        });
+        self.imm32(0);
+    }
+}
+
+/// Returns the explicit operands of an instruction, or `None` if a memory
+/// operand is not in its final form.
+fn explicit_args(operands: &[Operand]) -> Option<Vec<Arg<'_>>> {
+    let mut args = vec![];
+    let mut i = 0;
+    while i < operands.len() {
+        let operand = &operands[i];
+        i += 1;
+        if operand.implicit {
+            continue;
+        }
+        if !matches!(operand.data, OperandData::MemStart) {
+            args.push(Arg::Data(&operand.data));
+            continue;
+        }
+        let mem = match operands.get(i..i + 5)? {
+            [Operand {
+                data: OperandData::None,
+                ..
+            }, disp, base, index, scale] => {
+                let disp = match disp.data {
+                    OperandData::Int32(disp) => disp,
+                    OperandData::None => 0,
+                    _ => return None,
+                };
+                let OperandData::Reg(base) = base.data else {
+                    return None;
+                };
+                let index = match (&index.data, &scale.data) {
+                    (OperandData::Reg(index), OperandData::Int32(scale)) => Some((*index, *scale)),
+                    (OperandData::None, OperandData::None) => None,
+                    _ => return None,
+                };
+                Mem { base, disp, index }
+            }
+            _ => return None,
+        };
+        args.push(Arg::Mem(mem));
+        i += 5;
+    }
+    Some(args)
+}
+
+/// Returns the hardware number of `r`, from 0 for `rax` or `xmm0` to 15 for
+/// `r15` or `xmm15`.
+fn hw(r: Reg) -> u8 {
+    r.1 as u8
+}
+
+/// Returns the second byte of the opcode of `movsx` or `movzx`.
+fn ext_opcode(opcode: Opcode) -> u8 {
+    match opcode {
+        Opcode::MOVZXr32r8 | Opcode::MOVZXr32m8 => 0xb6,
+        Opcode::MOVZXr32r16 | Opcode::MOVZXr32m16 => 0xb7,
+        Opcode::MOVSXr32r8 | Opcode::MOVSXr32m8 => 0xbe,
+        _ => 0xbf,
+    }
+}
+
+/// Returns the prefix selecting the `float` form of a scalar SSE
+/// instruction, `F3`, or its `double` form, `F2`.
+fn scalar_prefix(opcode: Opcode) -> u8 {
+    match opcode {
+        Opcode::MOVSSrm
+        | Opcode::MOVSSmr
+        | Opcode::ADDSSrr
+        | Opcode::SUBSSrr
+        | Opcode::MULSSrr
+        | Opcode::DIVSSrr
+        | Opcode::CVTSI2SSrr
+        | Opcode::CVTTSS2SIrr
+        | Opcode::CVTSS2SDrr => 0xf3,
+        _ => 0xf2,
+    }
+}
+
+/// Returns the mandatory prefix, if any, and the second byte of the opcode of
+/// a packed SSE instruction, after `0F`.
+fn packed_opcode(opcode: Opcode) -> (Option<u8>, u8) {
+    match opcode {
+        Opcode::ADDPSrr => (None, 0x58),
+        Opcode::MULPSrr => (None, 0x59),
+        Opcode::SUBPSrr => (None, 0x5c),
+        Opcode::DIVPSrr => (None, 0x5e),
+        Opcode::CMPPSrri => (None, 0xc2),
+        Opcode::SHUFPSrri => (None, 0xc6),
+        Opcode::ADDPDrr => (Some(0x66), 0x58),
+        Opcode::MULPDrr => (Some(0x66), 0x59),
+        Opcode::SUBPDrr => (Some(0x66), 0x5c),
+        Opcode::DIVPDrr => (Some(0x66), 0x5e),
+        Opcode::CMPPDrri => (Some(0x66), 0xc2),
+        Opcode::PCMPGTBrr => (Some(0x66), 0x64),
+        Opcode::PCMPGTWrr => (Some(0x66), 0x65),
+        Opcode::PCMPGTDrr => (Some(0x66), 0x66),
+        Opcode::PSHUFDrri => (Some(0x66), 0x70),
+        Opcode::PCMPEQBrr => (Some(0x66), 0x74),
+        Opcode::PCMPEQWrr => (Some(0x66), 0x75),
+        Opcode::PCMPEQDrr => (Some(0x66), 0x76),
+        Opcode::PINSRWrri => (Some(0x66), 0xc4),
+        Opcode::PEXTRWrri => (Some(0x66), 0xc5),
+        Opcode::PADDQrr => (Some(0x66), 0xd4),
+        Opcode::PMULLWrr => (Some(0x66), 0xd5),
+        Opcode::PANDrr => (Some(0x66), 0xdb),
+        Opcode::PORrr => (Some(0x66), 0xeb),
+        Opcode::PXORrr => (Some(0x66), 0xef),
+        Opcode::PSUBBrr => (Some(0x66), 0xf8),
+        Opcode::PSUBWrr => (Some(0x66), 0xf9),
+        Opcode::PSUBDrr => (Some(0x66), 0xfa),
+        Opcode::PSUBQrr => (Some(0x66), 0xfb),
+        Opcode::PADDBrr => (Some(0x66), 0xfc),
+        Opcode::PADDWrr => (Some(0x66), 0xfd),
+        _ => (Some(0x66), 0xfe),
+    }
+}
+
+/// Returns the condition code of a jump or a `setcc`, in the low bits of
+/// their opcodes.
+fn condition(opcode: Opcode) -> u8 {
+    match opcode {
+        Opcode::JB | Opcode::SETB => 0x2,
+        Opcode::JAE | Opcode::SETAE => 0x3,
+        Opcode::JE | Opcode::SETE => 0x4,
+        Opcode::JNE | Opcode::SETNE => 0x5,
+        Opcode::JBE | Opcode::SETBE => 0x6,
+        Opcode::JA | Opcode::SETA => 0x7,
+        Opcode::JP | Opcode::SETP => 0xa,
+        Opcode::JNP | Opcode::SETNP => 0xb,
+        Opcode::JL | Opcode::SETL => 0xc,
+        Opcode::JGE | Opcode::SETGE => 0xd,
+        Opcode::JLE | Opcode::SETLE => 0xe,
+        _ => 0xf,
+    }
+}
+
+/// Returns the opcode extension of a shift, in `ModRM.reg`.
+fn shift_ext(opcode: Opcode) -> u16 {
+    match opcode {
+        Opcode::SHLri8 | Opcode::SHLrCL => 4,
+        Opcode::SHRri8 | Opcode::SHRrCL => 5,
+        _ => 7,
+    }
+}
+
+fn is_64(r: Reg) -> bool {
+    r.0 == RegClass::GR64 as u16
+}
+
+fn modrm(mod_: u8, reg: u8, rm: u8) -> u8 {
+    mod_ << 6 | (reg & 7) << 3 | rm & 7
+}
+
+fn sib(scale: i32, index: u8, base: u8) -> u8 {
+    let ss = match scale {
+        1 => 0,
+        2 => 1,
+        4 => 2,
+        _ => 3,
+    };
+    ss << 6 | (index & 7) << 3 | base & 7
+}
+
+impl Error for EncodingError {}
+
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported(inst) => write!(f, "cannot encode {}", inst),
+            Self::Precompiled(name) => {
+                write!(f, "function {} is only available as assembly", name)
+            }
+        }
+    }
+}
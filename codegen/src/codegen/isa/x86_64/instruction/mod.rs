@@ -1,14 +1,20 @@
 use crate::codegen::{
     function::{
         basic_block::BasicBlockId,
-        instruction::{Instruction, InstructionData as ID, InstructionId, InstructionInfo as II},
+        instruction::{
+            CfiDirective, Instruction, InstructionData as ID, InstructionId, InstructionInfo as II,
+        },
         slot::SlotId,
         Function,
     },
-    isa::{x86_64::register::reg_to_str, TargetIsa},
+    isa::{
+        x86_64::register::{reg_size, reg_to_str, sub_reg},
+        TargetIsa,
+    },
     register::{Reg, VReg, VRegUsers},
 };
-use std::fmt;
+use std::{borrow::Cow, fmt};
+use vicis_core::ir::types::{Type, Types};
 
 pub struct InstructionInfo;
 
@@ -22,21 +28,182 @@ pub struct InstructionData {
 pub enum Opcode {
     PUSH64,
     POP64,
+    PUSH32,
+    PUSHi32,
+    POP32,
     ADDr64i32,
     ADDri32,
     ADDrr32,
     SUBr64i32,
     SUBri32,
     SUBrr32,
+    /// `adc` and `sbb`, adding and subtracting the carry too.
+    ADCri32,
+    ADCrr32,
+    SBBri32,
+    SBBrr32,
+    ANDri32,
+    ANDrr32,
+    ORri32,
+    ORrr32,
+    /// `neg dst`.
+    NEGr32,
+    /// `imul dst, src`, the low half of the product.
+    IMULrr32,
+    /// `mul src`, the full unsigned product of `rax` and `src` in `rdx:rax`.
+    MULr64,
+    /// `cdq` and `cqo`, sign-extending `eax` into `edx`, or `rax` into
+    /// `rdx`.
+    CDQ,
+    CQO,
+    /// `idiv src`, dividing `edx:eax`, or `rdx:rax`, by `src`, the quotient
+    /// in `eax` and the remainder in `edx`.
+    IDIVr32,
+    SHLri8,
+    SHRri8,
+    SARri8,
+    /// Shifts by `cl`, given as the last operand.
+    SHLrCL,
+    SHRrCL,
+    /// `shld dst, src, imm`, shifting the high bits of `src` into `dst`,
+    /// and `shrd`, shifting in its low bits.
+    SHLDrri8,
+    SHRDrri8,
+    SHLDrrCL,
+    SHRDrrCL,
+    TESTri32,
+    CMOVNErr32,
+    /// `mov dst, src`, a copy of the low part of `src` of the size of
+    /// `dst`.
     MOVrr32,
     MOVrr64,
     MOVri32,
+    /// `movabs dst, imm`, a 64-bit immediate.
+    MOVri64,
     MOVrm32,
     MOVmi32,
     MOVmr32,
+    MOVrm64,
+    MOVmr64,
+    /// `mov qword ptr [mem], imm`, sign-extending a 32-bit immediate.
+    MOVm64i32,
+    MOVmr8,
+    MOVmi8,
+    MOVmr16,
+    MOVmi16,
     MOVSXDr64r32,
     MOVSXDr64m32,
+    MOVSXr32r8,
+    MOVSXr32m8,
+    MOVSXr32r16,
+    MOVSXr32m16,
+    MOVZXr32r8,
+    MOVZXr32m8,
+    MOVZXr32r16,
+    MOVZXr32m16,
+    /// `mov dst32, src32`, zero-extending the low half of `src` to the
+    /// 64-bit `dst`, as writing a 32-bit register clears the high half.
+    MOVZXr64r32,
+    /// `lea dst, [rip + sym]`, the address of a global of this module.
+    LEAr64rip,
+    /// `mov dst, qword ptr [rip + sym@GOTPCREL]`, the address of a global
+    /// loaded from the GOT.
+    MOVr64got,
+    /// `movabs dst, offset sym`, the 64-bit address of a global.
+    MOVABSr64,
+    CMPri8,
+    CMPri16,
     CMPri32,
+    CMPrr8,
+    CMPrr16,
+    CMPrr32,
+    /// `movaps dst, src`, a copy of a whole XMM register.
+    MOVAPSrr,
+    /// `movss` and `movsd`, loading and storing a `float` and a `double`.
+    /// The memory operand of loads may also be an entry of the constant
+    /// pool.
+    MOVSSrm,
+    MOVSDrm,
+    MOVSSmr,
+    MOVSDmr,
+    ADDSSrr,
+    ADDSDrr,
+    SUBSSrr,
+    SUBSDrr,
+    MULSSrr,
+    MULSDrr,
+    DIVSSrr,
+    DIVSDrr,
+    /// `xorps dst, src`, flipping the bits of `dst` set in `src`.
+    XORPSrr,
+    /// `ucomiss a, b` and `ucomisd`, setting the flags as `cmp` does for
+    /// unsigned integers, and all of `ZF`, `PF` and `CF` if either is a NaN.
+    UCOMISSrr,
+    UCOMISDrr,
+    /// `cvtsi2ss dst, src` and `cvtsi2sd`, converting a signed integer of the
+    /// size of `src`.
+    CVTSI2SSrr,
+    CVTSI2SDrr,
+    /// `cvttss2si dst, src` and `cvttsd2si`, truncating to a signed integer
+    /// of the size of `dst`.
+    CVTTSS2SIrr,
+    CVTTSD2SIrr,
+    CVTSS2SDrr,
+    CVTSD2SSrr,
+    /// `movups`, loading and storing a whole XMM register, of a vector. The
+    /// memory operand of loads may also be an entry of the constant pool.
+    MOVUPSrm,
+    MOVUPSmr,
+    /// The packed forms of arithmetic, on each lane of a vector.
+    PADDBrr,
+    PADDWrr,
+    PADDDrr,
+    PADDQrr,
+    PSUBBrr,
+    PSUBWrr,
+    PSUBDrr,
+    PSUBQrr,
+    /// `pmullw dst, src`, the low halves of the products of 16-bit lanes.
+    PMULLWrr,
+    ADDPSrr,
+    ADDPDrr,
+    SUBPSrr,
+    SUBPDrr,
+    MULPSrr,
+    MULPDrr,
+    DIVPSrr,
+    DIVPDrr,
+    PANDrr,
+    PORrr,
+    PXORrr,
+    /// `pcmpeqb dst, src` to `pcmpgtd`, setting every lane of `dst` to all
+    /// ones if the comparison holds, and else to zero. `pcmpgt` compares as
+    /// signed integers.
+    PCMPEQBrr,
+    PCMPEQWrr,
+    PCMPEQDrr,
+    PCMPGTBrr,
+    PCMPGTWrr,
+    PCMPGTDrr,
+    /// `cmpps dst, src, imm` and `cmppd`, comparing floating-point lanes by
+    /// the predicate of `imm`, e.g. 1 for `lt`, as `pcmpeq` does.
+    CMPPSrri,
+    CMPPDrri,
+    /// `pshufd dst, src, imm`, setting the `i`th dword of `dst` to the dword
+    /// of `src` chosen by the `i`th two bits of `imm`.
+    PSHUFDrri,
+    /// `shufps dst, src, imm`, setting the low two dwords of `dst` to those
+    /// of `dst` and the high two to those of `src` chosen by `imm`.
+    SHUFPSrri,
+    /// `movd dst, src` and `movq`, copying the low dword or qword of the XMM
+    /// register `src` to a general-purpose register.
+    MOVDr32x,
+    MOVQr64x,
+    /// `pextrw dst, src, imm`, zero-extending the 16-bit lane `imm` of `src`,
+    /// and `pinsrw dst, src, imm`, replacing that of `dst` by the low 16
+    /// bits of `src`.
+    PEXTRWrri,
+    PINSRWrri,
     JMP,
     JE,
     JNE,
@@ -44,8 +211,40 @@ pub enum Opcode {
     JL,
     JGE,
     JG,
+    /// The jumps on unsigned and floating-point comparisons, and on the
+    /// parity flag, set by unordered ones.
+    JA,
+    JAE,
+    JB,
+    JBE,
+    JP,
+    JNP,
+    /// `setcc dst`, setting the low byte of `dst` to 1 if the condition
+    /// holds and else to 0, for each of the jumps.
+    SETE,
+    SETNE,
+    SETLE,
+    SETL,
+    SETGE,
+    SETG,
+    SETA,
+    SETAE,
+    SETB,
+    SETBE,
+    SETP,
+    SETNP,
     CALL,
+    /// `call sym@PLT`.
+    CALLplt,
+    /// `call reg`, a call through a register holding the callee.
+    CALLr64,
     RET,
+    /// Receives the exception pointer in `rax` and the selector in `edx` at
+    /// the start of a landing pad. Emits no code.
+    LANDINGPAD,
+    /// A call frame information directive, given by the operand. Emits no
+    /// code.
+    CFI,
 
     // TODO
     Phi,
@@ -64,11 +263,16 @@ pub enum OperandData {
     Reg(Reg),
     VReg(VReg),
     Int32(i32),
+    Int64(i64),
     MemStart, // followed by: Slot, Imm, Reg(basically rbp), Reg, Shift
     Slot(SlotId),
     Block(BasicBlockId),
     Label(String),
     GlobalAddress(String),
+    /// An entry of the constant pool of the function, addressed relative to
+    /// `rip`.
+    ConstantPool(usize),
+    Cfi(CfiDirective),
     None,
 }
 
@@ -82,10 +286,13 @@ impl II for InstructionInfo {
         block: BasicBlockId,
     ) -> Instruction<Self::Data> {
         let ty = f.data.vregs.type_for(vreg);
-        assert!(ty.is_i32());
         Instruction::new(
             InstructionData {
-                opcode: Opcode::MOVmr32,
+                opcode: if ty.is_floating_point() || ty.is_vector(&f.types) {
+                    Opcode::store_xmm(T::type_size(&f.types, ty))
+                } else {
+                    Opcode::store(T::type_size(&f.types, ty))
+                },
                 operands: vec![
                     Operand::new(OperandData::MemStart),
                     Operand::new(OperandData::Slot(slot)),
@@ -107,10 +314,13 @@ impl II for InstructionInfo {
         block: BasicBlockId,
     ) -> Instruction<Self::Data> {
         let ty = f.data.vregs.type_for(vreg);
-        assert!(ty.is_i32());
         Instruction::new(
             InstructionData {
-                opcode: Opcode::MOVrm32,
+                opcode: if ty.is_floating_point() || ty.is_vector(&f.types) {
+                    Opcode::load_xmm(T::type_size(&f.types, ty))
+                } else {
+                    Opcode::load(T::type_size(&f.types, ty), false)
+                },
                 operands: vec![
                     Operand::output(vreg.into()),
                     Operand::new(OperandData::MemStart),
@@ -242,11 +452,239 @@ impl ID for InstructionData {
     }
 
     fn is_copy(&self) -> bool {
-        matches!(self.opcode, Opcode::MOVrr32 | Opcode::MOVrr64)
+        matches!(
+            self.opcode,
+            Opcode::MOVrr32 | Opcode::MOVrr64 | Opcode::MOVAPSrr
+        )
     }
 
     fn is_call(&self) -> bool {
-        matches!(self.opcode, Opcode::CALL)
+        matches!(
+            self.opcode,
+            Opcode::CALL | Opcode::CALLplt | Opcode::CALLr64
+        )
+    }
+
+    fn imm_move(&self) -> Option<(Reg, i64)> {
+        match (self.opcode, &self.operands[..]) {
+            (
+                Opcode::MOVri32,
+                [Operand {
+                    data: OperandData::Reg(r),
+                    ..
+                }, Operand {
+                    data: OperandData::Int32(i),
+                    ..
+                }],
+            ) => Some((*r, *i as i64)),
+            (
+                Opcode::MOVri64,
+                [Operand {
+                    data: OperandData::Reg(r),
+                    ..
+                }, Operand {
+                    data: OperandData::Int64(i),
+                    ..
+                }],
+            ) => Some((*r, *i)),
+            _ => None,
+        }
+    }
+
+    fn pool_load(&self) -> Option<(Reg, usize)> {
+        match (self.opcode, &self.operands[..]) {
+            (
+                Opcode::MOVSSrm | Opcode::MOVSDrm,
+                [Operand {
+                    data: OperandData::Reg(r),
+                    ..
+                }, Operand {
+                    data: OperandData::ConstantPool(i),
+                    ..
+                }],
+            ) => Some((*r, *i)),
+            _ => None,
+        }
+    }
+
+    fn rename_symbols(&mut self, rename: &dyn Fn(&str) -> Option<String>) {
+        for operand in &mut self.operands {
+            if let OperandData::Label(name) | OperandData::GlobalAddress(name) = &mut operand.data {
+                if let Some(new) = rename(name) {
+                    *name = new;
+                }
+            }
+        }
+    }
+}
+
+impl InstructionData {
+    /// Returns the instruction with its allocated registers named by the
+    /// parts of them it accesses, e.g. `al` of `eax` for the source of
+    /// `MOVmr8`, as it is printed and encoded.
+    pub fn with_sized_regs(&self) -> Cow<'_, Self> {
+        // The size of the registers read, and whether those written are
+        // of it too
+        let (size, outputs) = match self.opcode {
+            // Only the count in `rcx` is read as a byte.
+            Opcode::SHLrCL | Opcode::SHRrCL | Opcode::SHLDrrCL | Opcode::SHRDrrCL => {
+                let mut inst = self.clone();
+                if let Some(Operand {
+                    data: OperandData::Reg(r),
+                    ..
+                }) = inst.operands.last_mut()
+                {
+                    *r = sub_reg(*r, 1)
+                }
+                return Cow::Owned(inst);
+            }
+            Opcode::MOVmr8
+            | Opcode::CMPri8
+            | Opcode::CMPrr8
+            | Opcode::MOVSXr32r8
+            | Opcode::MOVZXr32r8 => (1, false),
+            Opcode::MOVmr16
+            | Opcode::CMPri16
+            | Opcode::CMPrr16
+            | Opcode::MOVSXr32r16
+            | Opcode::MOVZXr32r16 => (2, false),
+            Opcode::SETE
+            | Opcode::SETNE
+            | Opcode::SETLE
+            | Opcode::SETL
+            | Opcode::SETGE
+            | Opcode::SETG
+            | Opcode::SETA
+            | Opcode::SETAE
+            | Opcode::SETB
+            | Opcode::SETBE
+            | Opcode::SETP
+            | Opcode::SETNP => (1, true),
+            Opcode::MOVZXr64r32 => (4, true),
+            Opcode::MOVrr32 | Opcode::MOVrr64 => match self.operands[..] {
+                [Operand {
+                    data: OperandData::Reg(dst),
+                    ..
+                }, Operand {
+                    data: OperandData::Reg(src),
+                    ..
+                }] if reg_size(src) > reg_size(dst) => (reg_size(dst), false),
+                _ => return Cow::Borrowed(self),
+            },
+            _ => return Cow::Borrowed(self),
+        };
+        let mut inst = self.clone();
+        let mut operands = inst.operands.iter_mut();
+        while let Some(operand) = operands.next() {
+            match operand.data {
+                // The registers of memory operands are addresses.
+                OperandData::MemStart => {
+                    operands.nth(4);
+                }
+                OperandData::Reg(r) if outputs || !operand.output => {
+                    operand.data = OperandData::Reg(sub_reg(r, size))
+                }
+                _ => {}
+            }
+        }
+        Cow::Owned(inst)
+    }
+}
+
+impl Opcode {
+    /// Returns the opcode loading an integer or a pointer of `size` bytes.
+    /// Integers narrower than 32 bits are extended to the register, by sign
+    /// if `signed` and else by zero, as are those of 32 bits if `signed`.
+    pub fn load(size: u32, signed: bool) -> Self {
+        match (size, signed) {
+            (1, false) => Self::MOVZXr32m8,
+            (1, true) => Self::MOVSXr32m8,
+            (2, false) => Self::MOVZXr32m16,
+            (2, true) => Self::MOVSXr32m16,
+            (4, false) => Self::MOVrm32,
+            (4, true) => Self::MOVSXDr64m32,
+            _ => Self::MOVrm64,
+        }
+    }
+
+    /// Returns the opcode extending the low `size` bytes of a register, by
+    /// sign if `signed` and else by zero.
+    pub fn extend(size: u32, signed: bool) -> Self {
+        match (size, signed) {
+            (1, false) => Self::MOVZXr32r8,
+            (1, true) => Self::MOVSXr32r8,
+            (2, false) => Self::MOVZXr32r16,
+            (2, true) => Self::MOVSXr32r16,
+            (_, false) => Self::MOVZXr64r32,
+            (_, true) => Self::MOVSXDr64r32,
+        }
+    }
+
+    /// Returns the opcode loading an XMM register from `size` bytes, those of
+    /// a `float`, a `double` or a vector.
+    pub fn load_xmm(size: u32) -> Self {
+        match size {
+            4 => Self::MOVSSrm,
+            8 => Self::MOVSDrm,
+            _ => Self::MOVUPSrm,
+        }
+    }
+
+    /// Returns the opcode storing the low `size` bytes of an XMM register.
+    pub fn store_xmm(size: u32) -> Self {
+        match size {
+            4 => Self::MOVSSmr,
+            8 => Self::MOVSDmr,
+            _ => Self::MOVUPSmr,
+        }
+    }
+
+    /// Returns the opcode copying a register holding a value of `ty`.
+    pub fn copy(types: &Types, ty: Type) -> Self {
+        if ty.is_floating_point() || ty.is_vector(types) {
+            Self::MOVAPSrr
+        } else {
+            Self::MOVrr32
+        }
+    }
+
+    /// Returns the opcode storing the low `size` bytes of a register.
+    pub fn store(size: u32) -> Self {
+        match size {
+            1 => Self::MOVmr8,
+            2 => Self::MOVmr16,
+            4 => Self::MOVmr32,
+            _ => Self::MOVmr64,
+        }
+    }
+
+    /// Returns the opcode storing an immediate of `size` bytes.
+    pub fn store_imm(size: u32) -> Self {
+        match size {
+            1 => Self::MOVmi8,
+            2 => Self::MOVmi16,
+            4 => Self::MOVmi32,
+            _ => Self::MOVm64i32,
+        }
+    }
+
+    /// Returns the opcode comparing the low `size` bytes of a register with
+    /// an immediate.
+    pub fn cmp_imm(size: u32) -> Self {
+        match size {
+            1 => Self::CMPri8,
+            2 => Self::CMPri16,
+            _ => Self::CMPri32,
+        }
+    }
+
+    /// Returns the opcode comparing the low `size` bytes of two registers.
+    pub fn cmp(size: u32) -> Self {
+        match size {
+            1 => Self::CMPrr8,
+            2 => Self::CMPrr16,
+            _ => Self::CMPrr32,
+        }
     }
 }
 
@@ -390,11 +828,14 @@ impl fmt::Debug for OperandData {
             Self::Reg(r) => write!(f, "{}", reg_to_str(r)),
             Self::VReg(vr) => write!(f, "%{}", vr.0),
             Self::Int32(i) => write!(f, "{}", i),
+            Self::Int64(i) => write!(f, "{}", i),
             Self::MemStart => write!(f, "$MemStart$"),
             Self::Slot(slot) => write!(f, "slot.{}", slot.index()),
             Self::Block(id) => write!(f, "block.{}", id.index()),
             Self::Label(name) => write!(f, "{}", name),
             Self::GlobalAddress(name) => write!(f, "{}", name),
+            Self::ConstantPool(i) => write!(f, "cp.{}", i),
+            Self::Cfi(cfi) => write!(f, "{:?}", cfi),
             Self::None => write!(f, "none"),
         }
     }
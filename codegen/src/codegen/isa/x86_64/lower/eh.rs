@@ -0,0 +1,199 @@
+//! Lowering of exception handling, following the Itanium C++ ABI.
+//!
+//! An `invoke` is a call whose landing pad is kept as an implicit block
+//! operand, from which the printer emits the call-site table of the LSDA. The
+//! landing pad receives the exception pointer in `rax` and the selector in
+//! `edx`, and `resume` passes the exception on to `_Unwind_Resume`.
+
+use super::{get_or_generate_inst_output, lower_call, new_empty_inst_output, val_to_vreg};
+use crate::codegen::{
+    call_conv::CallConvKind,
+    function::instruction::Instruction as MachInstruction,
+    isa::x86_64::{
+        instruction::{InstructionData, Opcode, Operand as MO, OperandData},
+        register::{GR32, GR64},
+        X86Family,
+    },
+    lower::{LoweringContext, LoweringError},
+    register::Reg,
+};
+use anyhow::Result;
+use vicis_core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{ExtractValue, InsertValue, InstructionId, Opcode as IrOpcode, Operand},
+    },
+    types::{self, Type},
+    value::{ConstantData, ConstantInt, Value, ValueId},
+};
+
+pub fn lower_invoke<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    tys: &[Type],
+    args: &[ValueId],
+    blocks: &[BasicBlockId],
+) -> Result<()> {
+    // TODO: Arguments pushed for cdecl are not popped on the way to the
+    // landing pad.
+    if ctx.call_conv == CallConvKind::Cdecl {
+        return Err(LoweringError::Todo.into());
+    }
+
+    lower_call(ctx, id, tys, args)?;
+    let unwind = ctx.block_map[&blocks[1]];
+    let call = ctx
+        .inst_seq
+        .iter_mut()
+        .rev()
+        .find(|inst| matches!(inst.data.opcode, Opcode::CALL))
+        .unwrap();
+    call.data.operands.push(MO {
+        implicit: true,
+        ..MO::new(OperandData::Block(unwind))
+    });
+
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::JMP,
+            operands: vec![MO::new(OperandData::Block(ctx.block_map[&blocks[0]]))],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    Ok(())
+}
+
+/// Copies the exception pointer to the output of the landing pad, and the
+/// fields of it extracted by `extractvalue` to their outputs.
+pub fn lower_landingpad<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    ty: Type,
+) -> Result<()> {
+    let rax: Reg = GR64::RAX.into();
+    let edx: Reg = GR32::EDX.into();
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::LANDINGPAD,
+            operands: vec![
+                MO::implicit_output(rax.into()),
+                MO::implicit_output(edx.into()),
+            ],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    let mut outputs = vec![(id, 0)];
+    for &user in ctx.ir_data.users_of(id) {
+        if let Operand::ExtractValue(ExtractValue { ref args, .. }) =
+            ctx.ir_data.inst_ref(user).operand
+        {
+            outputs.push((user, field_index(ctx, args)?));
+        }
+    }
+    for (id, field) in outputs {
+        let field_ty = ctx
+            .types
+            .base()
+            .element_at(ty, field)
+            .ok_or(LoweringError::Todo)?;
+        let output = new_empty_inst_output(ctx, field_ty, id);
+        let (opcode, src) = if field == 0 {
+            (Opcode::MOVrr64, rax)
+        } else {
+            (Opcode::MOVrr32, edx)
+        };
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode,
+                operands: vec![MO::output(output.into()), MO::input(src.into())],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+    }
+    Ok(())
+}
+
+/// Lowers `extractvalue` from a landing pad, whose output is defined by
+/// `lower_landingpad`.
+pub fn lower_extractvalue<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    ty: Type,
+    args: &[ValueId],
+) -> Result<()> {
+    let Value::Instruction(agg) = ctx.ir_data.values[args[0]] else {
+        return Err(LoweringError::Todo.into());
+    };
+    if ctx.ir_data.inst_ref(agg).opcode != IrOpcode::LandingPad {
+        return Err(LoweringError::Todo.into());
+    }
+    let field = field_index(ctx, args)?;
+    let field_ty = ctx
+        .types
+        .base()
+        .element_at(ty, field)
+        .ok_or(LoweringError::Todo)?;
+    new_empty_inst_output(ctx, field_ty, id);
+    Ok(())
+}
+
+/// Calls `_Unwind_Resume` with the exception pointer of `arg`, which is a
+/// landing pad or built from one by `insertvalue`.
+pub fn lower_resume<T: X86Family>(ctx: &mut LoweringContext<T>, arg: ValueId) -> Result<()> {
+    let ptr_ty = ctx.types.ptr_to(types::I8);
+    let exn = exception_pointer(ctx, ptr_ty, arg)?;
+    let rdi: Reg = GR64::RDI.into();
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::MOVrr64,
+            operands: vec![MO::output(rdi.into()), MO::input(exn.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::CALL,
+            operands: vec![
+                MO::new(OperandData::Label("_Unwind_Resume".to_string())),
+                MO {
+                    implicit: true,
+                    ..MO::input(rdi.into())
+                },
+            ],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    Ok(())
+}
+
+fn exception_pointer<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    ptr_ty: Type,
+    arg: ValueId,
+) -> Result<crate::codegen::register::VReg> {
+    let Value::Instruction(id) = ctx.ir_data.values[arg] else {
+        return Err(LoweringError::Todo.into());
+    };
+    let inst = ctx.ir_data.inst_ref(id);
+    match inst.operand {
+        Operand::LandingPad(_) => get_or_generate_inst_output(ctx, ptr_ty, id),
+        Operand::InsertValue(InsertValue { ref args, .. }) => match ctx.ir_data.values[args[2]] {
+            Value::Constant(ConstantData::Int(ConstantInt::Int32(0))) if args.len() == 3 => {
+                val_to_vreg(ctx, ptr_ty, args[1])
+            }
+            _ => exception_pointer(ctx, ptr_ty, args[0]),
+        },
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
+
+/// Returns the field of `{ i8*, i32 }` extracted by `extractvalue` of `args`.
+fn field_index<T: X86Family>(ctx: &LoweringContext<T>, args: &[ValueId]) -> Result<usize> {
+    match (args, &ctx.ir_data.values[args[1]]) {
+        ([_, _], Value::Constant(ConstantData::Int(ConstantInt::Int32(i @ (0 | 1))))) => {
+            Ok(*i as usize)
+        }
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
@@ -0,0 +1,421 @@
+//! Lowering of `float` and `double`.
+//!
+//! With 64-bit registers, values of both types are held in the low part of
+//! XMM registers and operated on by scalar SSE instructions. They are not
+//! supported otherwise.
+//!
+//! - Constants are loaded from the constant pool of the function, as is the
+//!   sign bit `fneg` flips by `xorps`.
+//! - Arithmetic is done on a copy of the first operand, as that of integers.
+//!   `frem` is not supported.
+//! - `fcmp` is lowered by `ucomiss` or `ucomisd` and jumps on the flags, or
+//!   `setcc` for its result as a value. They are set as for unsigned
+//!   integers, with all of `ZF`, `PF` and `CF` set if the operands are
+//!   unordered. `olt` and `ole`, and `ugt` and `uge`, compare the operands
+//!   the other way around so that unordered operands go to the right block
+//!   by a single jump. `oeq` and `une` jump on `PF` first, or combine the
+//!   bytes set on it and on `ZF`.
+//! - `sitofp` and `fptosi` convert from and to integers of 32 or 64 bits, to
+//!   which `i8` and `i16` are extended first. `uitofp` and `fptoui` take the
+//!   next larger size, so that unsigned `i64` is not supported.
+//! - Arguments take the next of `xmm0` to `xmm7`, counted apart from those of
+//!   integers, and results are returned in `xmm0`. As callees may be
+//!   variadic, `al` is set to the number of those registers passed in.
+
+use super::{legalize, new_empty_inst_output, val_to_vreg};
+use crate::codegen::{
+    function::instruction::Instruction as MachInstruction,
+    isa::x86_64::{
+        instruction::{InstructionData, Opcode, Operand as MO, OperandData},
+        register::{FP_ARG_REGS, GR32, XMM},
+        X86Family,
+    },
+    lower::{LoweringContext, LoweringError},
+    register::{Reg, VReg},
+};
+use anyhow::Result;
+use vicis_core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{FCmpCond, InstructionId, Opcode as IrOpcode},
+    },
+    types::{self, Type},
+    value::{ConstantFloat, ValueId},
+};
+
+/// Returns an error unless values of `float` and `double` can be held in
+/// registers.
+pub fn supported<T: X86Family>() -> Result<()> {
+    if T::WORD_SIZE != 8 {
+        return Err(LoweringError::Todo.into());
+    }
+    Ok(())
+}
+
+/// Returns the register the `n`th `float` or `double` argument is passed in.
+pub fn arg_reg<T: X86Family>(n: usize) -> Result<Reg> {
+    supported::<T>()?;
+    Ok(FP_ARG_REGS
+        .into_iter()
+        .nth(n)
+        .ok_or(LoweringError::Todo)?
+        .into())
+}
+
+/// Returns the register `float` and `double` results are returned in.
+pub fn ret_reg() -> Reg {
+    XMM::XMM0.into()
+}
+
+/// Copies the `n`th `float` or `double` argument, of `ty`, to a new register.
+pub fn copy_arg<T: X86Family>(ctx: &mut LoweringContext<T>, ty: Type, n: usize) -> Result<VReg> {
+    let reg = arg_reg::<T>(n)?;
+    let output = ctx.mach_data.vregs.add_vreg_data(ty);
+    push(
+        ctx,
+        Opcode::MOVAPSrr,
+        vec![MO::output(output.into()), MO::input(reg.into())],
+    );
+    Ok(output)
+}
+
+/// Sets `al` to `n`, the number of XMM registers arguments are passed in,
+/// and returns the register.
+pub fn set_num_fp_args<T: X86Family>(ctx: &mut LoweringContext<T>, n: usize) -> Reg {
+    let eax: Reg = GR32::EAX.into();
+    push(
+        ctx,
+        Opcode::MOVri32,
+        vec![
+            MO::output(eax.into()),
+            MO::new(OperandData::Int32(n as i32)),
+        ],
+    );
+    eax
+}
+
+/// Loads the `i`th entry of the constant pool, of `ty`, to a new register.
+pub fn load_pool_entry<T: X86Family>(ctx: &mut LoweringContext<T>, ty: Type, i: usize) -> VReg {
+    let output = ctx.mach_data.vregs.add_vreg_data(ty);
+    push(
+        ctx,
+        scalar(ty, Opcode::MOVSSrm, Opcode::MOVSDrm),
+        vec![
+            MO::output(output.into()),
+            MO::new(OperandData::ConstantPool(i)),
+        ],
+    );
+    output
+}
+
+/// Loads the result of the load `id`, of `ty`, from the memory operand `mem`.
+pub fn load<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    ty: Type,
+    mem: Vec<MO>,
+) -> Result<()> {
+    supported::<T>()?;
+    let output = new_empty_inst_output(ctx, ty, id);
+    push(
+        ctx,
+        scalar(ty, Opcode::MOVSSrm, Opcode::MOVSDrm),
+        [MO::output(output.into())].into_iter().chain(mem).collect(),
+    );
+    Ok(())
+}
+
+/// Stores `src` of `ty` to the memory operand `mem`.
+pub fn store<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    mem: Vec<MO>,
+    ty: Type,
+    src: ValueId,
+) -> Result<()> {
+    supported::<T>()?;
+    let src = val_to_vreg(ctx, ty, src)?;
+    push(
+        ctx,
+        scalar(ty, Opcode::MOVSSmr, Opcode::MOVSDmr),
+        mem.into_iter().chain([MO::input(src.into())]).collect(),
+    );
+    Ok(())
+}
+
+pub fn lower_bin<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    args: &[ValueId],
+) -> Result<()> {
+    supported::<T>()?;
+    let opcode = match op {
+        IrOpcode::FAdd => scalar(ty, Opcode::ADDSSrr, Opcode::ADDSDrr),
+        IrOpcode::FSub => scalar(ty, Opcode::SUBSSrr, Opcode::SUBSDrr),
+        IrOpcode::FMul => scalar(ty, Opcode::MULSSrr, Opcode::MULSDrr),
+        IrOpcode::FDiv => scalar(ty, Opcode::DIVSSrr, Opcode::DIVSDrr),
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    let lhs = val_to_vreg(ctx, ty, args[0])?;
+    let rhs = val_to_vreg(ctx, ty, args[1])?;
+    let output = new_empty_inst_output(ctx, ty, id);
+    two_addr(ctx, opcode, output, lhs, rhs);
+    Ok(())
+}
+
+/// Flips the sign bit of the value by `xorps` with a `-0.0`.
+pub fn lower_fneg<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    ty: Type,
+    arg: ValueId,
+) -> Result<()> {
+    supported::<T>()?;
+    let val = val_to_vreg(ctx, ty, arg)?;
+    let sign = match ty {
+        types::FLOAT => ConstantFloat::from_f32(-0.0),
+        _ => ConstantFloat::from_f64(-0.0),
+    };
+    let i = ctx.constant_pool_index(sign.to_le_bytes());
+    let sign = load_pool_entry(ctx, ty, i);
+    let output = new_empty_inst_output(ctx, ty, id);
+    two_addr(ctx, Opcode::XORPSrr, output, val, sign);
+    Ok(())
+}
+
+pub fn lower_cast<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    opcode: IrOpcode,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    supported::<T>()?;
+    let [from, to] = *tys;
+    match opcode {
+        IrOpcode::FPExt | IrOpcode::FPTrunc => {
+            let val = val_to_vreg(ctx, from, arg)?;
+            let output = new_empty_inst_output(ctx, to, id);
+            push(
+                ctx,
+                scalar(from, Opcode::CVTSS2SDrr, Opcode::CVTSD2SSrr),
+                vec![MO::output(output.into()), MO::input(val.into())],
+            );
+        }
+        IrOpcode::SIToFP | IrOpcode::UIToFP => {
+            let signed = opcode == IrOpcode::SIToFP;
+            let size = legalize::int_size(ctx, from)?;
+            if signed && from.is_i1() || !signed && size == 8 {
+                return Err(LoweringError::Todo.into());
+            }
+            let mut val = val_to_vreg(ctx, from, arg)?;
+            // Extend to an integer of which the conversion takes the value.
+            if size < 4 || !signed {
+                let ty = if size < 4 { types::I32 } else { types::I64 };
+                let ext = ctx.mach_data.vregs.add_vreg_data(ty);
+                push(
+                    ctx,
+                    Opcode::extend(size, signed),
+                    vec![MO::output(ext.into()), MO::input(val.into())],
+                );
+                val = ext;
+            }
+            let output = new_empty_inst_output(ctx, to, id);
+            push(
+                ctx,
+                scalar(to, Opcode::CVTSI2SSrr, Opcode::CVTSI2SDrr),
+                vec![MO::output(output.into()), MO::input(val.into())],
+            );
+        }
+        _ => {
+            let signed = opcode == IrOpcode::FPToSI;
+            let size = legalize::int_size(ctx, to)?;
+            if to.is_i1() || !signed && size == 8 {
+                return Err(LoweringError::Todo.into());
+            }
+            let val = val_to_vreg(ctx, from, arg)?;
+            let opcode = scalar(from, Opcode::CVTTSS2SIrr, Opcode::CVTTSD2SIrr);
+            let output = new_empty_inst_output(ctx, to, id);
+            // Unsigned `i32`s are the low half of signed `i64`s.
+            if !signed && size == 4 {
+                let wide = ctx.mach_data.vregs.add_vreg_data(types::I64);
+                push(
+                    ctx,
+                    opcode,
+                    vec![MO::output(wide.into()), MO::input(val.into())],
+                );
+                push(
+                    ctx,
+                    Opcode::MOVrr32,
+                    vec![MO::output(output.into()), MO::input(wide.into())],
+                );
+            } else {
+                push(
+                    ctx,
+                    opcode,
+                    vec![MO::output(output.into()), MO::input(val.into())],
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Compares `args` of `ty` and jumps to `blocks[0]` if `cond` holds, or to
+/// `blocks[1]` otherwise.
+pub fn lower_condbr<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    ty: Type,
+    args: &[ValueId; 2],
+    cond: FCmpCond,
+    blocks: [BasicBlockId; 2],
+) -> Result<()> {
+    supported::<T>()?;
+    let (then, else_) = (ctx.block_map[&blocks[0]], ctx.block_map[&blocks[1]]);
+    // Whether the operands are compared the other way around, and the jumps
+    // on the flags, taken before the one to `else_`.
+    let (swapped, jumps) = match cond {
+        FCmpCond::False => (false, vec![]),
+        FCmpCond::True => (false, vec![(Opcode::JMP, then)]),
+        FCmpCond::Oeq => (false, vec![(Opcode::JP, else_), (Opcode::JE, then)]),
+        FCmpCond::Ogt => (false, vec![(Opcode::JA, then)]),
+        FCmpCond::Oge => (false, vec![(Opcode::JAE, then)]),
+        FCmpCond::Olt => (true, vec![(Opcode::JA, then)]),
+        FCmpCond::Ole => (true, vec![(Opcode::JAE, then)]),
+        FCmpCond::One => (false, vec![(Opcode::JNE, then)]),
+        FCmpCond::Ord => (false, vec![(Opcode::JNP, then)]),
+        FCmpCond::Ueq => (false, vec![(Opcode::JE, then)]),
+        FCmpCond::Ugt => (true, vec![(Opcode::JB, then)]),
+        FCmpCond::Uge => (true, vec![(Opcode::JBE, then)]),
+        FCmpCond::Ult => (false, vec![(Opcode::JB, then)]),
+        FCmpCond::Ule => (false, vec![(Opcode::JBE, then)]),
+        FCmpCond::Une => (false, vec![(Opcode::JP, then), (Opcode::JNE, then)]),
+        FCmpCond::Uno => (false, vec![(Opcode::JP, then)]),
+    };
+    if !matches!(cond, FCmpCond::False | FCmpCond::True) {
+        compare(ctx, ty, args, swapped)?;
+    }
+    for (opcode, block) in jumps {
+        push(ctx, opcode, vec![MO::new(OperandData::Block(block))]);
+    }
+    if cond != FCmpCond::True {
+        push(ctx, Opcode::JMP, vec![MO::new(OperandData::Block(else_))]);
+    }
+    Ok(())
+}
+
+/// Lowers `fcmp` to its result, by `setcc` on the flags compared as for
+/// [`lower_condbr`].
+pub fn lower_fcmp<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    ty: Type,
+    args: &[ValueId; 2],
+    cond: FCmpCond,
+) -> Result<()> {
+    supported::<T>()?;
+    // Whether the operands are compared the other way around, and the bytes
+    // set on the flags, of which `oeq` takes both and `une` either.
+    let (swapped, sets) = match cond {
+        FCmpCond::False | FCmpCond::True => {
+            let output = new_empty_inst_output(ctx, types::I1, id);
+            let imm = OperandData::Int32((cond == FCmpCond::True) as i32);
+            push(
+                ctx,
+                Opcode::MOVri32,
+                vec![MO::output(output.into()), MO::new(imm)],
+            );
+            return Ok(());
+        }
+        FCmpCond::Oeq => (false, vec![Opcode::SETNP, Opcode::SETE]),
+        FCmpCond::Ogt => (false, vec![Opcode::SETA]),
+        FCmpCond::Oge => (false, vec![Opcode::SETAE]),
+        FCmpCond::Olt => (true, vec![Opcode::SETA]),
+        FCmpCond::Ole => (true, vec![Opcode::SETAE]),
+        FCmpCond::One => (false, vec![Opcode::SETNE]),
+        FCmpCond::Ord => (false, vec![Opcode::SETNP]),
+        FCmpCond::Ueq => (false, vec![Opcode::SETE]),
+        FCmpCond::Ugt => (true, vec![Opcode::SETB]),
+        FCmpCond::Uge => (true, vec![Opcode::SETBE]),
+        FCmpCond::Ult => (false, vec![Opcode::SETB]),
+        FCmpCond::Ule => (false, vec![Opcode::SETBE]),
+        FCmpCond::Une => (false, vec![Opcode::SETP, Opcode::SETNE]),
+        FCmpCond::Uno => (false, vec![Opcode::SETP]),
+    };
+    compare(ctx, ty, args, swapped)?;
+    let output = new_empty_inst_output(ctx, types::I1, id);
+    push(ctx, sets[0], vec![MO::output(output.into())]);
+    if let Some(&set) = sets.get(1) {
+        let byte = ctx.mach_data.vregs.add_vreg_data(types::I1);
+        push(ctx, set, vec![MO::output(byte.into())]);
+        push(
+            ctx,
+            if cond == FCmpCond::Oeq {
+                Opcode::ANDrr32
+            } else {
+                Opcode::ORrr32
+            },
+            vec![MO::input_output(output.into()), MO::input(byte.into())],
+        );
+    }
+    Ok(())
+}
+
+/// Emits `ucomiss` or `ucomisd` of the operands, the other way around if
+/// `swapped`.
+fn compare<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    ty: Type,
+    args: &[ValueId; 2],
+    swapped: bool,
+) -> Result<()> {
+    let mut lhs = val_to_vreg(ctx, ty, args[0])?;
+    let mut rhs = val_to_vreg(ctx, ty, args[1])?;
+    if swapped {
+        (lhs, rhs) = (rhs, lhs);
+    }
+    push(
+        ctx,
+        scalar(ty, Opcode::UCOMISSrr, Opcode::UCOMISDrr),
+        vec![MO::input(lhs.into()), MO::input(rhs.into())],
+    );
+    Ok(())
+}
+
+/// Returns `ss`, the `float` form of an instruction, if `ty` is `float`, or
+/// `sd`, its `double` form.
+fn scalar(ty: Type, ss: Opcode, sd: Opcode) -> Opcode {
+    if ty.is_float() {
+        ss
+    } else {
+        sd
+    }
+}
+
+/// Emits `opcode dst, src` on a copy of `lhs` in `dst`.
+fn two_addr<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    opcode: Opcode,
+    dst: VReg,
+    lhs: VReg,
+    src: VReg,
+) {
+    push(
+        ctx,
+        Opcode::MOVAPSrr,
+        vec![MO::output(dst.into()), MO::input(lhs.into())],
+    );
+    push(
+        ctx,
+        opcode,
+        vec![MO::input_output(dst.into()), MO::input(src.into())],
+    );
+}
+
+fn push<T: X86Family>(ctx: &mut LoweringContext<T>, opcode: Opcode, operands: Vec<MO>) {
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData { opcode, operands },
+        ctx.block_map[&ctx.cur_block],
+    ));
+}
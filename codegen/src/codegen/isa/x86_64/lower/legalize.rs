@@ -0,0 +1,102 @@
+//! Legalization of integer types.
+//!
+//! Integers of every standard width are lowered to operations of their size:
+//!
+//! - `i32`, and `i64` and pointers with 64-bit registers, are held in
+//!   registers of their size.
+//! - `i1`, `i8` and `i16` are promoted to 32-bit registers, of which only the
+//!   low bits are defined. Additions, subtractions, multiplications, bitwise
+//!   operations, left shifts and copies are done on the whole register, as
+//!   the low bits of their results only depend on the low bits of their
+//!   operands. Everything else accesses the value at its own size: loads
+//!   zero-extend it, unless sign-extended for a `sext`, stores and
+//!   comparisons take the low part of the register, `sext` and `zext` extend
+//!   it by `movsx` and `movzx`, and divisions and right shifts extend their
+//!   operands to 32 bits first.
+//! - `i128`, with 64-bit registers, is held in pairs of them, of which the
+//!   lowering is in [`super::wide`].
+//! - Spilled virtual registers take slots of the size of their type, stored
+//!   and reloaded as values in memory are.
+//!
+//! `i1` values are kept as 0 or 1, so that zero-extending them is that of a
+//! byte. Comparisons give them by `setcc`, truncation to `i1` masks the low
+//! bit, and sign extension from it negates the value zero-extended.
+//!
+//! `float` and `double` are held in XMM registers, of which the lowering is
+//! in [`super::float`], and vectors in whole ones, of which the lowering is
+//! in [`super::vector`].
+
+use crate::codegen::{
+    function::instruction::Instruction as MachInstruction,
+    isa::x86_64::{
+        instruction::{InstructionData, Opcode, Operand as MO, OperandData},
+        X86Family,
+    },
+    lower::{LoweringContext, LoweringError},
+    register::VReg,
+};
+use anyhow::Result;
+use vicis_core::ir::{
+    types::{self, Type},
+    value::ConstantInt,
+};
+
+/// Returns the size in bytes of `ty`, an integer or a pointer, or an error if
+/// it has no register to be held in.
+pub fn int_size<T: X86Family>(ctx: &LoweringContext<T>, ty: Type) -> Result<u32> {
+    match ty {
+        types::I1 | types::I8 | types::I16 | types::I32 => Ok(T::type_size(ctx.types, ty)),
+        types::I64 if T::WORD_SIZE == 8 => Ok(8),
+        _ if ty.is_pointer(ctx.types) => Ok(T::WORD_SIZE),
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
+
+/// Returns the immediate of `int`. Those of 64 bits are only taken as such
+/// by `movabs` unless they fit in 32 bits, which instructions sign-extend.
+pub fn int_operand(int: ConstantInt) -> OperandData {
+    match int {
+        ConstantInt::Int64(i) => match i32::try_from(i) {
+            Ok(i) => OperandData::Int32(i),
+            Err(_) => OperandData::Int64(i),
+        },
+        int => OperandData::Int32(int.cast_to_i64() as i32),
+    }
+}
+
+/// Emits the truncation of `val` to `i1` in `dst`.
+pub fn trunc_to_i1<T: X86Family>(ctx: &mut LoweringContext<T>, dst: VReg, val: VReg) {
+    push(
+        ctx,
+        Opcode::MOVrr32,
+        vec![MO::output(dst.into()), MO::input(val.into())],
+    );
+    push(
+        ctx,
+        Opcode::ANDri32,
+        vec![MO::input_output(dst.into()), MO::new(OperandData::Int32(1))],
+    );
+}
+
+/// Emits the sign extension of `val`, an `i1`, to `dst` of `ty`, 0 or -1.
+pub fn sext_i1<T: X86Family>(ctx: &mut LoweringContext<T>, ty: Type, dst: VReg, val: VReg) {
+    let byte = ctx.mach_data.vregs.add_vreg_data(ty);
+    push(
+        ctx,
+        Opcode::MOVZXr32r8,
+        vec![MO::output(byte.into()), MO::input(val.into())],
+    );
+    push(
+        ctx,
+        Opcode::MOVrr32,
+        vec![MO::output(dst.into()), MO::input(byte.into())],
+    );
+    push(ctx, Opcode::NEGr32, vec![MO::input_output(dst.into())]);
+}
+
+fn push<T: X86Family>(ctx: &mut LoweringContext<T>, opcode: Opcode, operands: Vec<MO>) {
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData { opcode, operands },
+        ctx.block_map[&ctx.cur_block],
+    ));
+}
@@ -1,11 +1,12 @@
-use super::{get_or_generate_inst_output, new_empty_inst_output};
+use super::{
+    float, get_or_generate_inst_output, legalize, new_empty_inst_output, val_to_vreg, vector, wide,
+};
 use crate::codegen::{
     function::instruction::Instruction as MachInstruction,
     isa::x86_64::{
         instruction::{InstructionData, Opcode, Operand as MOperand, OperandData},
-        X86_64,
+        X86Family,
     },
-    isa::TargetIsa,
     lower::{LoweringContext, LoweringError},
 };
 use anyhow::Result;
@@ -15,83 +16,96 @@ use vicis_core::ir::{
     value::{ConstantData, ConstantInt, Value, ValueId},
 };
 
-pub fn lower_load(
-    ctx: &mut LoweringContext<X86_64>,
+pub fn lower_load<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     id: InstructionId,
     tys: &[Type],
     addr: ValueId,
     _align: u32,
 ) -> Result<()> {
-    let mut slot = None;
-
     // Very limited situation is supported now. TODO
-    let sext = ctx.ir_data.only_one_user_of(id).filter(|&id| {
-        let inst = ctx.ir_data.inst_ref(id);
-        let types = inst.operand.types();
-        inst.opcode == IrOpcode::Sext && types[0].is_i32() && types[1].is_i64()
-    });
+    let sext = sext_of_load(ctx, id);
 
     if let Value::Instruction(addr_id) = &ctx.ir_data.values[addr] {
-        if let Some(slot_id) = ctx.inst_id_to_slot_id.get(addr_id) {
-            slot = Some(*slot_id);
-        } else {
-            let opcode = ctx.ir_data.instructions[*addr_id].opcode;
-            if opcode == IrOpcode::GetElementPtr {
-                return lower_load_gep(ctx, id, tys, *addr_id, _align, sext);
-            }
+        let opcode = ctx.ir_data.instructions[*addr_id].opcode;
+        if !ctx.inst_id_to_slot_id.contains_key(addr_id) && opcode == IrOpcode::GetElementPtr {
+            return lower_load_gep(ctx, id, tys, *addr_id, _align, sext);
         }
-    } else {
-        panic!()
     }
 
-    if let Some(slot) = slot {
-        let src_ty = tys[0];
-        let mem = vec![
-            MOperand::new(OperandData::MemStart),
-            MOperand::new(OperandData::Slot(slot)),
-            MOperand::new(OperandData::None),
-            MOperand::input(OperandData::None),
-            MOperand::input(OperandData::None),
-            MOperand::new(OperandData::None),
-        ];
-
-        if sext.is_some() {
-            let output = ctx.inst_id_to_vreg[&id];
-            ctx.inst_seq.push(MachInstruction::new(
-                InstructionData {
-                    opcode: Opcode::MOVSXDr64m32,
-                    operands: vec![MOperand::output(output.into())]
-                        .into_iter()
-                        .chain(mem.into_iter())
-                        .collect(),
-                },
-                ctx.block_map[&ctx.cur_block],
-            ));
-            return Ok(());
-        }
+    let (slot, base) = addr_base(ctx, tys[1], addr)?;
+    let mem = vec![
+        MOperand::new(OperandData::MemStart),
+        MOperand::new(slot),
+        MOperand::new(OperandData::None),
+        MOperand::input(base),
+        MOperand::input(OperandData::None),
+        MOperand::new(OperandData::None),
+    ];
+    if wide::is_wide::<T>(tys[0]) {
+        return wide::load_pair(ctx, id, mem);
+    }
+    if tys[0].is_vector(ctx.types) {
+        return vector::load(ctx, id, tys[0], mem);
+    }
+    if tys[0].is_floating_point() {
+        return float::load(ctx, id, tys[0], mem);
+    }
+    let size = legalize::int_size(ctx, tys[0])?;
+
+    // The output of a merged `sext` if any
+    let output = new_empty_inst_output(ctx, tys[0], id);
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::load(size, sext.is_some()),
+            operands: vec![MOperand::output(output.into())]
+                .into_iter()
+                .chain(mem)
+                .collect(),
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+    Ok(())
+}
 
-        if src_ty.is_i32() {
-            // let output = new_empty_inst_output(ctx, src_ty, id);
-            let output = ctx.inst_id_to_vreg[&id];
-            ctx.inst_seq.push(MachInstruction::new(
-                InstructionData {
-                    opcode: Opcode::MOVrm32,
-                    operands: vec![MOperand::output(output.into())]
-                        .into_iter()
-                        .chain(mem.into_iter())
-                        .collect(),
-                },
-                ctx.block_map[&ctx.cur_block],
-            ));
-            return Ok(());
+/// Returns the slot and the base register of a memory operand at `addr`, of
+/// the pointer type `ty`: the slot of an `alloca`, or else a register holding
+/// the address, e.g. that of a global or an argument.
+pub fn addr_base<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    ty: Type,
+    addr: ValueId,
+) -> Result<(OperandData, OperandData)> {
+    if let Value::Instruction(id) = ctx.ir_data.values[addr] {
+        if let Some(&slot) = ctx.inst_id_to_slot_id.get(&id) {
+            return Ok((OperandData::Slot(slot), OperandData::None));
         }
     }
+    Ok((OperandData::None, val_to_vreg(ctx, ty, addr)?.into()))
+}
 
-    Err(LoweringError::Todo.into())
+/// Returns the `sext` of the result of the load `id`, if it is its only
+/// user, in which case the load extends the value itself into the output of
+/// the `sext`.
+pub fn sext_of_load<T: X86Family>(
+    ctx: &LoweringContext<T>,
+    id: InstructionId,
+) -> Option<InstructionId> {
+    if !ctx.ir_data.inst_ref(id).opcode.is_load() {
+        return None;
+    }
+    ctx.ir_data.only_one_user_of(id).filter(|&id| {
+        let inst = ctx.ir_data.inst_ref(id);
+        let types = inst.operand.types();
+        // Without 64-bit registers, the `sext` of an index is a no-op instead.
+        inst.opcode == IrOpcode::Sext
+            && !types[0].is_i1()
+            && (T::WORD_SIZE == 8 || !types[1].is_i64())
+    })
 }
 
-fn lower_load_gep(
-    ctx: &mut LoweringContext<X86_64>,
+fn lower_load_gep<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     id: InstructionId,
     tys: &[Type],
     gep_id: InstructionId,
@@ -109,71 +123,72 @@ fn lower_load_gep(
         .map(|&arg| &ctx.ir_data.values[arg])
         .collect();
 
-    let mem;
-
-    match &gep_args[..] {
-        [Value::Instruction(base_ptr), Const(Int(Int64(idx0))), Const(Int(Int64(idx1)))] => {
-            let base_ptr = ctx.inst_id_to_slot_id[base_ptr];
+    let mem = match &gep_args[..] {
+        [_, Const(Int(Int64(idx0))), Const(Int(Int64(idx1)))] => {
+            let (slot, base) = addr_base(ctx, gep.operand.types()[1], gep.operand.args()[0])?;
             let base_ty = gep.operand.types()[0];
-            let offset = idx0 * X86_64::type_size(ctx.types, base_ty) as i64
-                + idx1
-                    * X86_64::type_size(ctx.types, ctx.types.get_element(base_ty).unwrap()) as i64;
+            let offset = idx0 * T::type_size(ctx.types, base_ty) as i64
+                + idx1 * T::type_size(ctx.types, ctx.types.get_element(base_ty).unwrap()) as i64;
             // debug!(offset);
 
-            mem = vec![
+            vec![
                 MOperand::new(OperandData::MemStart),
-                MOperand::new(OperandData::Slot(base_ptr)),
+                MOperand::new(slot),
                 MOperand::new(OperandData::Int32(offset as i32)),
-                MOperand::input(OperandData::None),
+                MOperand::input(base),
                 MOperand::input(OperandData::None),
                 MOperand::new(OperandData::None),
-            ];
+            ]
         }
-        [Value::Instruction(base_ptr), Const(Int(Int64(idx0))), Value::Instruction(idx1)] => {
-            let base_ptr = ctx.inst_id_to_slot_id[base_ptr];
+        [_, Const(Int(Int64(idx0))), Value::Instruction(idx1)] => {
+            let (slot, base) = addr_base(ctx, gep.operand.types()[1], gep.operand.args()[0])?;
 
             let base_ty = gep.operand.types()[0];
-            let offset = idx0 * X86_64::type_size(ctx.types, base_ty) as i64;
+            let offset = idx0 * T::type_size(ctx.types, base_ty) as i64;
             // debug!(offset);
 
             let idx1_ty = gep.operand.types()[3];
             assert!(idx1_ty.is_i64());
             let idx1 = get_or_generate_inst_output(ctx, idx1_ty, *idx1)?;
 
-            assert!(X86_64::type_size(ctx.types, ctx.types.get_element(base_ty).unwrap()) == 4);
+            let scale = T::type_size(ctx.types, ctx.types.get_element(base_ty).unwrap());
+            if !matches!(scale, 1 | 2 | 4 | 8) {
+                return Err(LoweringError::Todo.into());
+            }
 
-            mem = vec![
+            vec![
                 MOperand::new(OperandData::MemStart),
-                MOperand::new(OperandData::Slot(base_ptr)),
+                MOperand::new(slot),
                 MOperand::new(OperandData::Int32(offset as i32)),
-                MOperand::input(OperandData::None),
+                MOperand::input(base),
                 MOperand::input(OperandData::VReg(idx1)),
-                MOperand::new(OperandData::Int32(X86_64::type_size(
-                    ctx.types,
-                    ctx.types.get_element(base_ty).unwrap(),
-                ) as i32)),
-            ];
+                MOperand::new(OperandData::Int32(scale as i32)),
+            ]
         }
         _ => return Err(LoweringError::Todo.into()),
-    }
+    };
 
-    let output = new_empty_inst_output(ctx, tys[0], sext.unwrap_or(id));
-
-    let src_ty = tys[0];
-    if src_ty.is_i32() {
-        ctx.inst_seq.append(&mut vec![MachInstruction::new(
-            InstructionData {
-                opcode: sext.map_or(Opcode::MOVrm32, |_| Opcode::MOVSXDr64m32),
-                operands: vec![MOperand::output(OperandData::VReg(output))]
-                    .into_iter()
-                    .chain(mem.into_iter())
-                    .collect(),
-            },
-            ctx.block_map[&ctx.cur_block],
-        )]);
-    } else {
-        return Err(LoweringError::Todo.into());
+    if wide::is_wide::<T>(tys[0]) {
+        return wide::load_pair(ctx, id, mem);
+    }
+    if tys[0].is_vector(ctx.types) {
+        return vector::load(ctx, id, tys[0], mem);
+    }
+    if tys[0].is_floating_point() {
+        return float::load(ctx, id, tys[0], mem);
     }
+    let size = legalize::int_size(ctx, tys[0])?;
+    let output = new_empty_inst_output(ctx, tys[0], id);
+    ctx.inst_seq.append(&mut vec![MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::load(size, sext.is_some()),
+            operands: vec![MOperand::output(OperandData::VReg(output))]
+                .into_iter()
+                .chain(mem.into_iter())
+                .collect(),
+        },
+        ctx.block_map[&ctx.cur_block],
+    )]);
 
     Ok(())
 }
@@ -1,62 +1,94 @@
+pub mod eh;
+pub mod float;
+pub mod legalize;
 pub mod load;
 pub mod store;
+pub mod vector;
+pub mod wide;
 
 use crate::codegen::{
-    function::instruction::Instruction as MachInstruction,
+    call_conv::CallConvKind,
+    function::{instruction::Instruction as MachInstruction, slot::SlotOrigin},
     isa::x86_64::{
         instruction::{InstructionData, Opcode, Operand as MO, OperandData},
-        register::{RegClass, RegInfo, GR32},
-        X86_64,
+        register::GR32,
+        X86Family,
     },
-    isa::TargetIsa,
     lower::{Lower as LowerTrait, LoweringContext, LoweringError},
-    register::{Reg, RegisterClass, RegisterInfo, VReg},
+    register::{Reg, RegUnit, RegisterClass, RegisterInfo, VReg},
 };
 use anyhow::Result;
-use load::lower_load;
+use eh::{lower_extractvalue, lower_invoke, lower_landingpad, lower_resume};
+use load::{lower_load, sext_of_load};
 use store::lower_store;
 use vicis_core::ir::{
     function::{
         basic_block::BasicBlockId,
         data::Data as IrData,
         instruction::{
-            Alloca, Br, Call, Cast, CondBr, ICmp, ICmpCond, Instruction as IrInstruction,
-            InstructionId, IntBinary, Load, Opcode as IrOpcode, Operand, Phi, Ret, Store,
+            Alloca, Br, Call, Cast, CondBr, ExtractElement, ExtractValue, FCmp, FloatBinary,
+            FloatUnary, ICmp, ICmpCond, InsertElement, Instruction as IrInstruction, InstructionId,
+            IntBinary, Invoke, LandingPad, Load, Opcode as IrOpcode, Operand, Phi, Resume, Ret,
+            ShuffleVector, Store,
         },
+        param_attrs::ParameterAttribute,
         Parameter,
     },
     module::name::Name,
-    types::Type,
+    types::{self, Type},
     value::{ConstantData, ConstantExpr, ConstantInt, Value, ValueId},
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 pub struct Lower {}
 
-impl Default for Lower {
-    fn default() -> Self {
-        Lower {}
-    }
-}
-
 impl Lower {
     pub fn new() -> Self {
         Lower::default()
     }
 }
 
-impl LowerTrait<X86_64> for Lower {
-    fn lower(ctx: &mut LoweringContext<X86_64>, inst: &IrInstruction) -> Result<()> {
+impl<T: X86Family> LowerTrait<T> for Lower {
+    fn lower(ctx: &mut LoweringContext<T>, inst: &IrInstruction) -> Result<()> {
         lower(ctx, inst)
     }
 
-    fn copy_args_to_vregs(ctx: &mut LoweringContext<X86_64>, params: &[Parameter]) -> Result<()> {
-        let args = RegInfo::arg_reg_list(&ctx.call_conv);
-        for (gpr_used, Parameter { name: _, ty, .. }) in params.iter().enumerate() {
-            let reg = args[gpr_used].apply(&RegClass::for_type(ctx.types, *ty));
+    fn copy_args_to_vregs(ctx: &mut LoweringContext<T>, params: &[Parameter]) -> Result<()> {
+        if ctx.call_conv == CallConvKind::Cdecl {
+            return copy_stack_args_to_vregs(ctx, params);
+        }
+        let args = T::RegInfo::arg_reg_list(&ctx.call_conv);
+        let mut gpr_used = 0;
+        let mut fp_used = 0;
+        for (i, Parameter { name: _, ty, .. }) in params.iter().enumerate() {
+            if ty.is_floating_point() || ty.is_vector(ctx.types) {
+                if ty.is_vector(ctx.types) {
+                    vector::check_arg(ctx, *ty)?;
+                }
+                let output = float::copy_arg(ctx, *ty, fp_used)?;
+                ctx.arg_idx_to_vreg.insert(i, output);
+                fp_used += 1;
+                continue;
+            }
+            if wide::is_wide::<T>(*ty) {
+                let class = T::RegClass::for_type(ctx.types, types::I64);
+                let regs =
+                    [gpr_used, gpr_used + 1].map(|i| args.get(i).map(|reg| reg.apply(&class)));
+                let [Some(lo), Some(hi)] = regs else {
+                    return Err(LoweringError::Todo.into());
+                };
+                let output = wide::copy_arg_to_pair(ctx, [lo, hi])?;
+                ctx.arg_idx_to_vreg.insert(i, output);
+                gpr_used += 2;
+                continue;
+            }
+            let reg = args
+                .get(gpr_used)
+                .ok_or(LoweringError::Todo)?
+                .apply(&T::RegClass::for_type(ctx.types, *ty));
             debug!(reg);
             // Copy reg to new vreg
-            assert!(ty.is_i32());
+            legalize::int_size(ctx, *ty)?;
             let output = ctx.mach_data.vregs.add_vreg_data(*ty);
             ctx.inst_seq.push(MachInstruction::new(
                 InstructionData {
@@ -65,13 +97,47 @@ impl LowerTrait<X86_64> for Lower {
                 },
                 ctx.block_map[&ctx.cur_block],
             ));
-            ctx.arg_idx_to_vreg.insert(gpr_used, output);
+            ctx.arg_idx_to_vreg.insert(i, output);
+            gpr_used += 1;
         }
         Ok(())
     }
 }
 
-fn lower(ctx: &mut LoweringContext<X86_64>, inst: &IrInstruction) -> Result<()> {
+/// Loads the arguments passed on the stack, above the saved frame pointer and
+/// the return address.
+fn copy_stack_args_to_vregs<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    params: &[Parameter],
+) -> Result<()> {
+    let mut offset = 2 * T::WORD_SIZE as i32;
+    for (i, Parameter { name: _, ty, .. }) in params.iter().enumerate() {
+        // Narrower integers are passed in 4 bytes too.
+        legalize::int_size(ctx, *ty)?;
+        let output = ctx.mach_data.vregs.add_vreg_data(*ty);
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::MOVrm32,
+                operands: vec![
+                    MO::output(output.into()),
+                    MO::new(OperandData::MemStart),
+                    MO::new(OperandData::None),
+                    MO::new(OperandData::Int32(offset)),
+                    MO::input(OperandData::Reg(T::FP)),
+                    MO::input(OperandData::None),
+                    MO::new(OperandData::None),
+                ],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+        ctx.arg_idx_to_vreg.insert(i, output);
+        offset += 4;
+    }
+    Ok(())
+}
+
+fn lower<T: X86Family>(ctx: &mut LoweringContext<T>, inst: &IrInstruction) -> Result<()> {
+    let is_vector = |ty: Type| ty.is_vector(ctx.types);
     match inst.operand {
         Operand::Alloca(Alloca {
             ref tys,
@@ -93,44 +159,124 @@ fn lower(ctx: &mut LoweringContext<X86_64>, inst: &IrInstruction) -> Result<()>
             ref args,
             align,
         }) => lower_store(ctx, tys, args, align),
+        Operand::IntBinary(IntBinary { ty, ref args, .. })
+        | Operand::FloatBinary(FloatBinary { ty, ref args })
+            if is_vector(ty) =>
+        {
+            vector::lower_bin(ctx, inst.id.unwrap(), inst.opcode, ty, args)
+        }
+        Operand::FloatUnary(FloatUnary { ty, arg })
+            if inst.opcode == IrOpcode::FNeg && is_vector(ty) =>
+        {
+            vector::lower_fneg(ctx, inst.id.unwrap(), ty, arg)
+        }
+        Operand::ICmp(ICmp { ty, ref args, cond }) if is_vector(ty) => {
+            vector::lower_icmp(ctx, inst.id.unwrap(), ty, args, cond)
+        }
+        Operand::FCmp(FCmp { ty, ref args, cond }) if is_vector(ty) => {
+            vector::lower_fcmp(ctx, inst.id.unwrap(), ty, args, cond)
+        }
+        Operand::Cast(Cast { ref tys, arg }) if is_vector(tys[0]) || is_vector(tys[1]) => {
+            vector::lower_cast(ctx, inst.id.unwrap(), inst.opcode, tys, arg)
+        }
+        Operand::ExtractElement(ExtractElement { ref tys, ref args }) => {
+            vector::lower_extractelement(ctx, inst.id.unwrap(), tys, args)
+        }
+        Operand::InsertElement(InsertElement { ref tys, ref args }) => {
+            vector::lower_insertelement(ctx, inst.id.unwrap(), tys, args)
+        }
+        Operand::ShuffleVector(ShuffleVector { ref tys, ref args }) => {
+            vector::lower_shufflevector(ctx, inst.id.unwrap(), tys, args)
+        }
         Operand::IntBinary(IntBinary { ty, ref args, .. }) => {
             lower_bin(ctx, inst.id.unwrap(), inst.opcode, ty, args)
         }
+        Operand::ICmp(ICmp { ty, ref args, cond }) => {
+            lower_icmp(ctx, inst.id.unwrap(), ty, args, cond)
+        }
+        Operand::FCmp(FCmp { ty, ref args, cond }) => {
+            float::lower_fcmp(ctx, inst.id.unwrap(), ty, args, cond)
+        }
+        Operand::FloatBinary(FloatBinary { ty, ref args }) => {
+            float::lower_bin(ctx, inst.id.unwrap(), inst.opcode, ty, args)
+        }
+        Operand::FloatUnary(FloatUnary { ty, arg }) if inst.opcode == IrOpcode::FNeg => {
+            float::lower_fneg(ctx, inst.id.unwrap(), ty, arg)
+        }
+        Operand::Cast(Cast { ref tys, arg }) if inst.opcode.is_fp_cast() => {
+            float::lower_cast(ctx, inst.id.unwrap(), inst.opcode, tys, arg)
+        }
         Operand::Cast(Cast { ref tys, arg }) if inst.opcode == IrOpcode::Sext => {
             lower_sext(ctx, inst.id.unwrap(), tys, arg)
         }
+        Operand::Cast(Cast { ref tys, arg }) if inst.opcode == IrOpcode::Zext => {
+            lower_zext(ctx, inst.id.unwrap(), tys, arg)
+        }
+        Operand::Cast(Cast { ref tys, arg }) if inst.opcode == IrOpcode::Trunc => {
+            lower_trunc(ctx, inst.id.unwrap(), tys, arg)
+        }
         Operand::Br(Br { block }) => lower_br(ctx, block),
         Operand::CondBr(CondBr { arg, blocks }) => lower_condbr(ctx, arg, blocks),
+        // Arguments passed `byval` to callees not expanded belong in the stack
+        // argument area, which is not supported yet.
+        Operand::Call(Call {
+            ref param_attrs, ..
+        })
+        | Operand::Invoke(Invoke {
+            ref param_attrs, ..
+        }) if param_attrs
+            .iter()
+            .flatten()
+            .any(|a| a == &ParameterAttribute::ByVal) =>
+        {
+            Err(LoweringError::Todo.into())
+        }
         Operand::Call(Call {
             ref args, ref tys, ..
         }) => lower_call(ctx, inst.id.unwrap(), tys, args),
+        Operand::Invoke(Invoke {
+            ref args,
+            ref tys,
+            ref blocks,
+            ..
+        }) => lower_invoke(ctx, inst.id.unwrap(), tys, args, blocks),
+        Operand::LandingPad(LandingPad { ty }) => lower_landingpad(ctx, inst.id.unwrap(), ty),
+        Operand::ExtractValue(ExtractValue { ty, ref args }) => {
+            lower_extractvalue(ctx, inst.id.unwrap(), ty, args)
+        }
+        Operand::Resume(Resume { arg, .. }) => lower_resume(ctx, arg),
         Operand::Ret(Ret { val: None, .. }) => Err(LoweringError::Todo.into()),
         Operand::Ret(Ret { val: Some(val), ty }) => lower_return(ctx, ty, val),
         _ => Err(LoweringError::Todo.into()),
     }
 }
 
-fn lower_alloca(
-    ctx: &mut LoweringContext<X86_64>,
+fn lower_alloca<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     id: InstructionId,
     tys: &[Type],
     _num_elements: &ConstantData,
     _align: u32,
 ) -> Result<()> {
-    let slot_id = ctx
-        .slots
-        .add_slot(tys[0], X86_64::type_size(ctx.types, tys[0]));
+    let slot_id = ctx.slots.add_slot(
+        tys[0],
+        T::type_size(ctx.types, tys[0]),
+        SlotOrigin::Alloca(id),
+    );
     ctx.inst_id_to_slot_id.insert(id, slot_id);
     Ok(())
 }
 
-fn lower_phi(
-    ctx: &mut LoweringContext<X86_64>,
+fn lower_phi<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     id: InstructionId,
     ty: Type,
     args: &[ValueId],
     blocks: &[BasicBlockId],
 ) -> Result<()> {
+    if wide::is_wide::<T>(ty) {
+        return wide::lower_phi(ctx, id, args, blocks);
+    }
     let output = new_empty_inst_output(ctx, ty, id);
     let mut operands = vec![MO::output(output.into())];
     for (arg, block) in args.iter().zip(blocks.iter()) {
@@ -147,93 +293,251 @@ fn lower_phi(
     Ok(())
 }
 
-fn lower_bin(
-    ctx: &mut LoweringContext<X86_64>,
+fn lower_bin<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     id: InstructionId,
     op: IrOpcode,
     ty: Type,
     args: &[ValueId],
 ) -> Result<()> {
+    if wide::is_wide::<T>(ty) {
+        return wide::lower_bin(ctx, id, op, args);
+    }
+    let size = legalize::int_size(ctx, ty)?;
+    let (rr, ri) = match op {
+        IrOpcode::Add => (Opcode::ADDrr32, Some(Opcode::ADDri32)),
+        IrOpcode::Sub => (Opcode::SUBrr32, Some(Opcode::SUBri32)),
+        IrOpcode::Mul => (Opcode::IMULrr32, None),
+        IrOpcode::And => (Opcode::ANDrr32, Some(Opcode::ANDri32)),
+        IrOpcode::Or => (Opcode::ORrr32, Some(Opcode::ORri32)),
+        IrOpcode::Shl | IrOpcode::LShr => return lower_shift(ctx, id, op, ty, size, args),
+        IrOpcode::SDiv | IrOpcode::SRem => return lower_div(ctx, id, op, ty, size, args),
+        _ => return Err(LoweringError::Todo.into()),
+    };
     let lhs = val_to_vreg(ctx, ty, args[0])?;
     let output = new_empty_inst_output(ctx, ty, id);
-
-    let insert_move = |ctx: &mut LoweringContext<X86_64>| {
-        ctx.inst_seq.push(MachInstruction::new(
-            InstructionData {
-                opcode: Opcode::MOVrr32,
-                operands: vec![MO::output(output.into()), MO::input(lhs.into())],
-            },
-            ctx.block_map[&ctx.cur_block],
-        ))
+    let (opcode, rhs) = match (val_to_operand_data(ctx, ty, args[1])?, ri) {
+        (OperandData::Int32(rhs), Some(ri)) => (ri, MO::new(rhs.into())),
+        (OperandData::VReg(rhs), _) => (rr, MO::input(rhs.into())),
+        _ => (rr, MO::input(val_to_vreg(ctx, ty, args[1])?.into())),
     };
+    two_addr(ctx, opcode, output, lhs, vec![rhs]);
+    Ok(())
+}
 
-    let rhs = val_to_operand_data(ctx, ty, args[1])?;
-
-    let data = match rhs {
-        OperandData::Int32(rhs) => {
-            insert_move(ctx);
-            InstructionData {
-                opcode: match op {
-                    IrOpcode::Add => Opcode::ADDri32,
-                    IrOpcode::Sub => Opcode::SUBri32,
-                    _ => return Err(LoweringError::Todo.into()),
-                },
-                operands: vec![MO::input_output(output.into()), MO::new(rhs.into())],
-            }
+/// Lowers `shl` or `lshr` of `ty` of `size` bytes. Values narrower than 32
+/// bits are zero-extended first when shifted right, so that the bits shifted
+/// in are zeros. Counts other than constants are passed in `cl`.
+fn lower_shift<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    size: u32,
+    args: &[ValueId],
+) -> Result<()> {
+    let (ri, rcl) = match op {
+        IrOpcode::Shl => (Opcode::SHLri8, Opcode::SHLrCL),
+        _ => (Opcode::SHRri8, Opcode::SHRrCL),
+    };
+    let mut lhs = val_to_vreg(ctx, ty, args[0])?;
+    if size < 4 && op == IrOpcode::LShr {
+        lhs = extend(ctx, size, lhs, false);
+    }
+    let (opcode, count) = match ctx.ir_data.value_ref(args[1]) {
+        // Counts of the size of the type or more give poison values.
+        Value::Constant(ConstantData::Int(count)) => {
+            let mask = 8 * size.max(4) as i64 - 1;
+            let count = (count.cast_to_i64() & mask) as i32;
+            (ri, MO::new(OperandData::Int32(count)))
         }
-        OperandData::VReg(rhs) => {
-            insert_move(ctx);
-            InstructionData {
-                opcode: match op {
-                    IrOpcode::Add => Opcode::ADDrr32,
-                    IrOpcode::Sub => Opcode::SUBrr32,
-                    _ => return Err(LoweringError::Todo.into()),
-                },
-                operands: vec![MO::input_output(output.into()), MO::input(rhs.into())],
-            }
+        _ => {
+            let count = val_to_vreg(ctx, ty, args[1])?;
+            let rcx = RegUnit::from(GR32::ECX).apply(&T::RegClass::for_type(ctx.types, ty));
+            push(
+                ctx,
+                Opcode::MOVrr32,
+                vec![MO::output(rcx.into()), MO::input(count.into())],
+            );
+            (rcl, MO::input(rcx.into()))
         }
-        _ => return Err(LoweringError::Todo.into()),
     };
+    let output = new_empty_inst_output(ctx, ty, id);
+    two_addr(ctx, opcode, output, lhs, vec![count]);
+    Ok(())
+}
 
-    ctx.inst_seq
-        .push(MachInstruction::new(data, ctx.block_map[&ctx.cur_block]));
+/// Lowers `sdiv` or `srem` of `ty` of `size` bytes by `idiv` of the first
+/// operand in `eax`, or `rax`, sign-extended into `edx`, or `rdx`. Operands
+/// narrower than 32 bits are sign-extended first.
+fn lower_div<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    size: u32,
+    args: &[ValueId],
+) -> Result<()> {
+    let class = T::RegClass::for_type(ctx.types, ty);
+    let [rax, rdx] = [GR32::EAX, GR32::EDX].map(|r| RegUnit::from(r).apply(&class));
+    let mut lhs = val_to_vreg(ctx, ty, args[0])?;
+    let mut rhs = val_to_vreg(ctx, ty, args[1])?;
+    if size < 4 {
+        lhs = extend(ctx, size, lhs, true);
+        rhs = extend(ctx, size, rhs, true);
+    }
+    let implicit = |op: MO| MO {
+        implicit: true,
+        ..op
+    };
+
+    push(
+        ctx,
+        Opcode::MOVrr32,
+        vec![MO::output(rax.into()), MO::input(lhs.into())],
+    );
+    push(
+        ctx,
+        if size == 8 { Opcode::CQO } else { Opcode::CDQ },
+        vec![
+            implicit(MO::input(rax.into())),
+            MO::implicit_output(rdx.into()),
+        ],
+    );
+    push(
+        ctx,
+        Opcode::IDIVr32,
+        vec![
+            implicit(MO::input_output(rax.into())),
+            implicit(MO::input_output(rdx.into())),
+            MO::input(rhs.into()),
+        ],
+    );
+
+    let output = new_empty_inst_output(ctx, ty, id);
+    let result = if op == IrOpcode::SDiv { rax } else { rdx };
+    push(
+        ctx,
+        Opcode::MOVrr32,
+        vec![MO::output(output.into()), MO::input(result.into())],
+    );
+    Ok(())
+}
+
+/// Lowers `icmp` to its result, by `setcc` on the flags of [`lower_cmp`].
+fn lower_icmp<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    ty: Type,
+    args: &[ValueId; 2],
+    cond: ICmpCond,
+) -> Result<()> {
+    lower_cmp(ctx, ty, args)?;
+    let output = new_empty_inst_output(ctx, types::I1, id);
+    push(ctx, cond_opcodes(cond).1, vec![MO::output(output.into())]);
+    Ok(())
+}
 
+/// Compares the operands of an `icmp` of `ty`, at its size.
+fn lower_cmp<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    ty: Type,
+    args: &[ValueId; 2],
+) -> Result<()> {
+    let size = legalize::int_size(ctx, ty)?;
+    let lhs = val_to_vreg(ctx, ty, args[0])?;
+    let (opcode, rhs) = match val_to_operand_data(ctx, ty, args[1])? {
+        OperandData::Int32(rhs) => (Opcode::cmp_imm(size), MO::new(rhs.into())),
+        OperandData::VReg(rhs) => (Opcode::cmp(size), MO::input(rhs.into())),
+        _ => (
+            Opcode::cmp(size),
+            MO::input(val_to_vreg(ctx, ty, args[1])?.into()),
+        ),
+    };
+    push(ctx, opcode, vec![MO::input(lhs.into()), rhs]);
     Ok(())
 }
 
-fn lower_sext(
-    ctx: &mut LoweringContext<X86_64>,
+/// Returns the jump and the `setcc` on `cond` of the flags of [`lower_cmp`].
+fn cond_opcodes(cond: ICmpCond) -> (Opcode, Opcode) {
+    match cond {
+        ICmpCond::Eq => (Opcode::JE, Opcode::SETE),
+        ICmpCond::Ne => (Opcode::JNE, Opcode::SETNE),
+        ICmpCond::Ugt => (Opcode::JA, Opcode::SETA),
+        ICmpCond::Uge => (Opcode::JAE, Opcode::SETAE),
+        ICmpCond::Ult => (Opcode::JB, Opcode::SETB),
+        ICmpCond::Ule => (Opcode::JBE, Opcode::SETBE),
+        ICmpCond::Sgt => (Opcode::JG, Opcode::SETG),
+        ICmpCond::Sge => (Opcode::JGE, Opcode::SETGE),
+        ICmpCond::Slt => (Opcode::JL, Opcode::SETL),
+        ICmpCond::Sle => (Opcode::JLE, Opcode::SETLE),
+    }
+}
+
+/// Returns a new `i32` register holding `val` of `size` bytes, extended by
+/// sign if `signed` and else by zero. It is of its own type so as to be
+/// spilled whole.
+fn extend<T: X86Family>(ctx: &mut LoweringContext<T>, size: u32, val: VReg, signed: bool) -> VReg {
+    let output = ctx.mach_data.vregs.add_vreg_data(types::I32);
+    push(
+        ctx,
+        Opcode::extend(size, signed),
+        vec![MO::output(output.into()), MO::input(val.into())],
+    );
+    output
+}
+
+fn lower_sext<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     self_id: InstructionId,
     tys: &[Type; 2],
     arg: ValueId,
 ) -> Result<()> {
     let from = tys[0];
     let to = tys[1];
-    // TODO
-    assert!(from.is_i32());
-    assert!(to.is_i64());
-
-    let val = match ctx.ir_data.values[arg] {
-        Value::Instruction(id) => {
-            let is_mergeable_load =
-                ctx.ir_data.inst_ref(id).opcode == IrOpcode::Load && from.is_i32();
-
-            if is_mergeable_load {
-                let output = new_empty_inst_output(ctx, to, self_id);
-                ctx.set_output_for_inst(id, output); // Use the same output register for `load` as `sext`
-                return Ok(());
-            }
+    if wide::is_wide::<T>(to) {
+        return wide::lower_ext(ctx, self_id, from, arg, true);
+    }
+    let from_size = legalize::int_size(ctx, from)?;
 
-            get_or_generate_inst_output(ctx, from, id)?
+    // Without 64-bit registers, only indices of `getelementptr`s are extended.
+    // They are truncated to the size of pointers anyway, so are left as is.
+    if T::WORD_SIZE < 8 && to.is_i64() {
+        let only_indices = ctx
+            .ir_data
+            .users_of(self_id)
+            .iter()
+            .all(|&user| ctx.ir_data.inst_ref(user).opcode == IrOpcode::GetElementPtr);
+        if !only_indices || !from.is_i32() {
+            return Err(LoweringError::Todo.into());
         }
-        _ => return Err(LoweringError::Todo.into()),
-    };
+        let val = val_to_vreg(ctx, from, arg)?;
+        ctx.set_output_for_inst(self_id, val);
+        return Ok(());
+    }
+    legalize::int_size(ctx, to)?;
 
+    if from.is_i1() {
+        let val = val_to_vreg(ctx, from, arg)?;
+        let output = new_empty_inst_output(ctx, to, self_id);
+        legalize::sext_i1(ctx, to, output, val);
+        return Ok(());
+    }
+
+    if let Value::Instruction(id) = ctx.ir_data.values[arg] {
+        if sext_of_load(ctx, id) == Some(self_id) {
+            let output = new_empty_inst_output(ctx, to, self_id);
+            ctx.set_output_for_inst(id, output); // Use the same output register for `load` as `sext`
+            return Ok(());
+        }
+    }
+
+    let val = val_to_vreg(ctx, from, arg)?;
     let output = new_empty_inst_output(ctx, to, self_id);
 
     ctx.inst_seq.push(MachInstruction::new(
         InstructionData {
-            opcode: Opcode::MOVSXDr64r32,
+            opcode: Opcode::extend(from_size, true),
             operands: vec![MO::output(output.into()), MO::input(val.into())],
         },
         ctx.block_map[&ctx.cur_block],
@@ -242,7 +546,65 @@ fn lower_sext(
     Ok(())
 }
 
-fn lower_br(ctx: &mut LoweringContext<X86_64>, block: BasicBlockId) -> Result<()> {
+fn lower_zext<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    self_id: InstructionId,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    if wide::is_wide::<T>(tys[1]) {
+        return wide::lower_ext(ctx, self_id, tys[0], arg, false);
+    }
+    let from_size = legalize::int_size(ctx, tys[0])?;
+    legalize::int_size(ctx, tys[1])?;
+
+    let val = val_to_vreg(ctx, tys[0], arg)?;
+    let output = new_empty_inst_output(ctx, tys[1], self_id);
+
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::extend(from_size, false),
+            operands: vec![MO::output(output.into()), MO::input(val.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
+
+/// Copies the low part of the value, which is all that is defined of the
+/// narrower type, but for `i1`, which is masked to its bit.
+fn lower_trunc<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    self_id: InstructionId,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    if wide::is_wide::<T>(tys[0]) {
+        return wide::lower_trunc(ctx, self_id, tys[1], arg);
+    }
+    legalize::int_size(ctx, tys[0])?;
+    legalize::int_size(ctx, tys[1])?;
+
+    let val = val_to_vreg(ctx, tys[0], arg)?;
+    let output = new_empty_inst_output(ctx, tys[1], self_id);
+    if tys[1].is_i1() {
+        legalize::trunc_to_i1(ctx, output, val);
+        return Ok(());
+    }
+
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::MOVrr32,
+            operands: vec![MO::output(output.into()), MO::input(val.into())],
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    Ok(())
+}
+
+fn lower_br<T: X86Family>(ctx: &mut LoweringContext<T>, block: BasicBlockId) -> Result<()> {
     ctx.inst_seq.push(MachInstruction::new(
         InstructionData {
             opcode: Opcode::JMP,
@@ -253,8 +615,8 @@ fn lower_br(ctx: &mut LoweringContext<X86_64>, block: BasicBlockId) -> Result<()
     Ok(())
 }
 
-fn lower_condbr(
-    ctx: &mut LoweringContext<X86_64>,
+fn lower_condbr<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     arg: ValueId,
     blocks: [BasicBlockId; 2],
 ) -> Result<()> {
@@ -274,82 +636,206 @@ fn lower_condbr(
         }
     }
 
-    let arg = ctx.ir_data.value_ref(arg);
+    fn is_fcmp<'a>(data: &'a IrData, val: &Value) -> Option<&'a FCmp> {
+        match val {
+            Value::Instruction(id) => match &data.inst_ref(*id).operand {
+                Operand::FCmp(fcmp) => Some(fcmp),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 
-    if let Some((ty, args, cond)) = is_icmp(ctx.ir_data, arg) {
-        let lhs = val_to_vreg(ctx, *ty, args[0])?;
-        let rhs = ctx.ir_data.value_ref(args[1]);
-        match rhs {
-            Value::Constant(ConstantData::Int(ConstantInt::Int32(rhs))) => {
-                ctx.inst_seq.push(MachInstruction::new(
-                    InstructionData {
-                        opcode: Opcode::CMPri32,
-                        operands: vec![MO::input(lhs.into()), MO::new(rhs.into())],
-                    },
-                    ctx.block_map[&ctx.cur_block],
-                ));
+    let val = ctx.ir_data.value_ref(arg);
+
+    if let Some(&FCmp { ty, args, cond }) = is_fcmp(ctx.ir_data, val) {
+        return float::lower_condbr(ctx, ty, &args, cond, blocks);
+    }
+
+    let jcc = if let Some((&ty, args, &cond)) = is_icmp(ctx.ir_data, val) {
+        lower_cmp(ctx, ty, args)?;
+        cond_opcodes(cond).0
+    } else {
+        // Any other `i1` is 0 or 1.
+        let cond = val_to_vreg(ctx, types::I1, arg)?;
+        push(
+            ctx,
+            Opcode::TESTri32,
+            vec![MO::input(cond.into()), MO::new(OperandData::Int32(1))],
+        );
+        Opcode::JNE
+    };
+    push(
+        ctx,
+        jcc,
+        vec![MO::new(OperandData::Block(ctx.block_map[&blocks[0]]))],
+    );
+    push(
+        ctx,
+        Opcode::JMP,
+        vec![MO::new(OperandData::Block(ctx.block_map[&blocks[1]]))],
+    );
+    Ok(())
+}
+
+fn lower_call<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    tys: &[Type],
+    args: &[ValueId],
+) -> Result<()> {
+    let output = new_empty_inst_output(ctx, tys[0], id);
+
+    if ctx.call_conv == CallConvKind::Cdecl {
+        return lower_cdecl_call(ctx, id, output, tys, args);
+    }
+
+    let gpru = T::RegInfo::arg_reg_list(&ctx.call_conv);
+    let mut arg_regs = vec![];
+    let mut gpr_used = 0;
+    let mut fp_used = 0;
+    for (&arg, &ty) in args[1..].iter().zip(tys[1..].iter()) {
+        if ty.is_floating_point() || ty.is_vector(ctx.types) {
+            if ty.is_vector(ctx.types) {
+                vector::check_arg(ctx, ty)?;
             }
-            _ => return Err(LoweringError::Todo.into()),
+            let r = float::arg_reg::<T>(fp_used)?;
+            let arg = val_to_vreg(ctx, ty, arg)?;
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode: Opcode::MOVAPSrr,
+                    operands: vec![MO::output(r.into()), MO::input(arg.into())],
+                },
+                ctx.block_map[&ctx.cur_block],
+            ));
+            arg_regs.push(r);
+            fp_used += 1;
+            continue;
         }
-
-        ctx.inst_seq.push(MachInstruction::new(
-            InstructionData {
-                opcode: match cond {
-                    ICmpCond::Eq => Opcode::JE,
-                    ICmpCond::Ne => Opcode::JNE,
-                    ICmpCond::Sle => Opcode::JLE,
-                    ICmpCond::Slt => Opcode::JL,
-                    ICmpCond::Sge => Opcode::JGE,
-                    ICmpCond::Sgt => Opcode::JG,
-                    _ => return Err(LoweringError::Todo.into()),
+        let (ty, halves) = if wide::is_wide::<T>(ty) {
+            (types::I64, wide::val_to_halves(ctx, arg)?.to_vec())
+        } else {
+            legalize::int_size(ctx, ty)?;
+            (ty, vec![val_to_operand_data(ctx, ty, arg)?])
+        };
+        for arg in halves {
+            // TODO: Pass the rest on the stack.
+            let r = gpru
+                .get(gpr_used)
+                .ok_or(LoweringError::Todo)?
+                .apply(&T::RegClass::for_type(ctx.types, ty));
+            ctx.inst_seq.push(MachInstruction::new(
+                InstructionData {
+                    opcode: match &arg {
+                        OperandData::Int32(_) => Opcode::MOVri32,
+                        OperandData::Int64(_) => Opcode::MOVri64,
+                        OperandData::VReg(_) | OperandData::Reg(_) => Opcode::MOVrr32,
+                        _ => return Err(LoweringError::Todo.into()),
+                    },
+                    operands: vec![MO::output(r.into()), MO::input(arg)],
                 },
-                operands: vec![MO::new(OperandData::Block(ctx.block_map[&blocks[0]]))],
-            },
-            ctx.block_map[&ctx.cur_block],
-        ));
+                ctx.block_map[&ctx.cur_block],
+            ));
+            arg_regs.push(r);
+            gpr_used += 1;
+        }
+    }
+    if fp_used > 0 {
+        arg_regs.push(float::set_num_fp_args(ctx, fp_used));
+    }
+
+    let name = match &ctx.ir_data.values[args[0]] {
+        Value::Constant(ConstantData::GlobalRef(Name::Name(name))) => name.to_string(),
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    let used = !ctx.ir_data.users_of(id).is_empty();
+    let result_regs = if used && wide::is_wide::<T>(tys[0]) {
+        wide::ret_regs().to_vec()
+    } else {
+        vec![result_reg(ctx, id, tys[0])?]
+    };
+    // The arguments are read by the call, so as not to be overwritten before.
+    let operands = result_regs
+        .iter()
+        .map(|&r| MO::implicit_output(r.into()))
+        .chain([MO::new(OperandData::Label(name))])
+        .chain(arg_regs.into_iter().map(|r| MO {
+            implicit: true,
+            ..MO::input(r.into())
+        }))
+        .collect();
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData {
+            opcode: Opcode::CALL,
+            operands,
+        },
+        ctx.block_map[&ctx.cur_block],
+    ));
+
+    if used && wide::is_wide::<T>(tys[0]) {
+        wide::copy_from_ret_regs(ctx, id)?;
+    } else if used {
         ctx.inst_seq.push(MachInstruction::new(
             InstructionData {
-                opcode: Opcode::JMP,
-                operands: vec![MO::new(OperandData::Block(ctx.block_map[&blocks[1]]))],
+                opcode: Opcode::copy(ctx.types, tys[0]),
+                operands: vec![MO::output(output.into()), MO::input(result_regs[0].into())],
             },
             ctx.block_map[&ctx.cur_block],
         ));
-        return Ok(());
     }
 
-    Err(LoweringError::Todo.into())
+    Ok(())
 }
 
-fn lower_call(
-    ctx: &mut LoweringContext<X86_64>,
+/// Pushes the arguments from right to left, keeping the stack aligned to 16
+/// bytes at the call, and pops them after it.
+fn lower_cdecl_call<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     id: InstructionId,
+    output: VReg,
     tys: &[Type],
     args: &[ValueId],
 ) -> Result<()> {
-    let output = new_empty_inst_output(ctx, tys[0], id);
+    let mut pushes = vec![];
+    for (&arg, &ty) in args[1..].iter().zip(tys[1..].iter()) {
+        // Narrower integers are pushed in 4 bytes too.
+        legalize::int_size(ctx, ty)?;
+        pushes.push(match val_to_operand_data(ctx, ty, arg)? {
+            OperandData::Int32(i) => (Opcode::PUSHi32, MO::new(i.into())),
+            arg @ OperandData::VReg(_) => (Opcode::PUSH32, MO::input(arg)),
+            _ => return Err(LoweringError::Todo.into()),
+        });
+    }
 
-    let gpru = RegInfo::arg_reg_list(&ctx.call_conv);
-    for (gpr_used, (&arg, &ty)) in args[1..].iter().zip(tys[1..].iter()).enumerate() {
-        let arg = val_to_operand_data(ctx, ty, arg)?;
-        let r = gpru[gpr_used].apply(&RegClass::for_type(ctx.types, ty));
+    let args_size = 4 * pushes.len() as i32;
+    let padding = (16 - args_size % 16) % 16;
+    if padding > 0 {
         ctx.inst_seq.push(MachInstruction::new(
             InstructionData {
-                opcode: match &arg {
-                    OperandData::Int32(_) => Opcode::MOVri32,
-                    OperandData::VReg(_) | OperandData::Reg(_) => Opcode::MOVrr32,
-                    _ => return Err(LoweringError::Todo.into()),
-                },
-                operands: vec![MO::output(r.into()), MO::input(arg)],
+                opcode: Opcode::SUBri32,
+                operands: vec![
+                    MO::input_output(OperandData::Reg(T::SP)),
+                    MO::new(padding.into()),
+                ],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+    }
+    for (opcode, arg) in pushes.into_iter().rev() {
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode,
+                operands: vec![arg],
             },
             ctx.block_map[&ctx.cur_block],
         ));
     }
 
     let name = match &ctx.ir_data.values[args[0]] {
-        Value::Constant(ConstantData::GlobalRef(Name::Name(name))) => name.clone(),
+        Value::Constant(ConstantData::GlobalRef(Name::Name(name))) => name.to_string(),
         _ => return Err(LoweringError::Todo.into()),
     };
-    let result_reg: Reg = GR32::EAX.into(); // TODO: do not hard code
+    let result_reg = result_reg(ctx, id, tys[0])?;
     ctx.inst_seq.push(MachInstruction::new(
         InstructionData {
             opcode: Opcode::CALL,
@@ -360,6 +846,18 @@ fn lower_call(
         },
         ctx.block_map[&ctx.cur_block],
     ));
+    if args_size + padding > 0 {
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::ADDri32,
+                operands: vec![
+                    MO::input_output(OperandData::Reg(T::SP)),
+                    MO::new((args_size + padding).into()),
+                ],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+    }
 
     if !ctx.ir_data.users_of(id).is_empty() {
         ctx.inst_seq.push(MachInstruction::new(
@@ -374,19 +872,33 @@ fn lower_call(
     Ok(())
 }
 
-fn lower_return(ctx: &mut LoweringContext<X86_64>, ty: Type, value: ValueId) -> Result<()> {
-    let vreg = val_to_vreg(ctx, ty, value)?;
-    assert!(ty.is_i32());
-    ctx.inst_seq.push(MachInstruction::new(
-        InstructionData {
-            opcode: Opcode::MOVrr32,
-            operands: vec![
-                MO::output(OperandData::Reg(GR32::EAX.into())),
-                MO::input(vreg.into()),
-            ],
-        },
-        ctx.block_map[&ctx.cur_block],
-    ));
+fn lower_return<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    ty: Type,
+    value: ValueId,
+) -> Result<()> {
+    if wide::is_wide::<T>(ty) {
+        wide::copy_to_ret_regs(ctx, value)?;
+    } else {
+        if ty.is_vector(ctx.types) {
+            vector::check_arg(ctx, ty)?;
+        } else if ty.is_floating_point() {
+            float::supported::<T>()?;
+        } else {
+            legalize::int_size(ctx, ty)?;
+        }
+        let vreg = val_to_vreg(ctx, ty, value)?;
+        ctx.inst_seq.push(MachInstruction::new(
+            InstructionData {
+                opcode: Opcode::copy(ctx.types, ty),
+                operands: vec![
+                    MO::output(OperandData::Reg(ret_reg(ctx, ty))),
+                    MO::input(vreg.into()),
+                ],
+            },
+            ctx.block_map[&ctx.cur_block],
+        ));
+    }
     ctx.inst_seq.push(MachInstruction::new(
         InstructionData {
             opcode: Opcode::RET,
@@ -397,13 +909,37 @@ fn lower_return(ctx: &mut LoweringContext<X86_64>, ty: Type, value: ValueId) ->
     Ok(())
 }
 
+/// Returns the register a value of `ty` is returned in.
+fn ret_reg<T: X86Family>(ctx: &LoweringContext<T>, ty: Type) -> Reg {
+    if ty.is_floating_point() || ty.is_vector(ctx.types) {
+        return float::ret_reg();
+    }
+    RegUnit::from(GR32::EAX).apply(&T::RegClass::for_type(ctx.types, ty))
+}
+
+/// Returns the register the result of the call `id` of `ty` is taken from,
+/// the one it is returned in if used.
+fn result_reg<T: X86Family>(ctx: &LoweringContext<T>, id: InstructionId, ty: Type) -> Result<Reg> {
+    if ctx.ir_data.users_of(id).is_empty() {
+        return Ok(GR32::EAX.into());
+    }
+    if ty.is_vector(ctx.types) {
+        vector::check_arg(ctx, ty)?;
+    } else if ty.is_floating_point() {
+        float::supported::<T>()?;
+    } else {
+        legalize::int_size(ctx, ty)?;
+    }
+    Ok(ret_reg(ctx, ty))
+}
+
 // Get instruction output.
 // If the instruction is not placed in any basic block, place it in the current block.
 // If the instruction must be placed in another block except the current block(, which means
 // the instruction output must live out from its parent basic block to the current block),
 // just create a new virtual register to store the instruction output.
-fn get_or_generate_inst_output(
-    ctx: &mut LoweringContext<X86_64>,
+fn get_or_generate_inst_output<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     ty: Type,
     id: InstructionId,
 ) -> Result<VReg> {
@@ -429,65 +965,140 @@ fn get_or_generate_inst_output(
     }
 }
 
-fn new_empty_inst_output(ctx: &mut LoweringContext<X86_64>, ty: Type, id: InstructionId) -> VReg {
+fn new_empty_inst_output<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    ty: Type,
+    id: InstructionId,
+) -> VReg {
     if let Some(vreg) = ctx.inst_id_to_vreg.get(&id) {
         return *vreg;
     }
-    let vreg = ctx.mach_data.vregs.add_vreg_data(ty);
+    let vreg = if wide::is_wide::<T>(ty) {
+        wide::new_pair(ctx)
+    } else if ty.is_vector(ctx.types) {
+        ctx.mach_data.vregs.add_vreg_data(vector::reg_ty(ctx, ty))
+    } else {
+        ctx.mach_data.vregs.add_vreg_data(ty)
+    };
     ctx.inst_id_to_vreg.insert(id, vreg);
     vreg
 }
 
-fn val_to_operand_data(
-    ctx: &mut LoweringContext<X86_64>,
+fn val_to_operand_data<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     ty: Type,
     val: ValueId,
 ) -> Result<OperandData> {
     match ctx.ir_data.values[val] {
         Value::Instruction(id) => Ok(get_or_generate_inst_output(ctx, ty, id)?.into()),
         Value::Argument(idx) => Ok(ctx.arg_idx_to_vreg[&idx].into()),
-        Value::Constant(ConstantData::Int(ConstantInt::Int32(i))) => Ok(OperandData::Int32(i)),
+        Value::Constant(ConstantData::Int(int)) => Ok(legalize::int_operand(int)),
+        Value::Constant(ConstantData::Float(konst)) => {
+            float::supported::<T>()?;
+            Ok(OperandData::ConstantPool(
+                ctx.constant_pool_index(konst.to_le_bytes()),
+            ))
+        }
+        Value::Constant(
+            ref
+            konst @ (ConstantData::Vector(_) | ConstantData::AggregateZero | ConstantData::Undef),
+        ) if ty.is_vector(ctx.types) => {
+            let bytes = vector::constant_bytes(ctx, ty, konst)?;
+            Ok(OperandData::ConstantPool(ctx.constant_pool_index(bytes)))
+        }
+        Value::Constant(ConstantData::GlobalRef(Name::Name(name))) if ty.is_pointer(ctx.types) => {
+            let name = name.to_string();
+            Ok(global_address(ctx, ty, name).into())
+        }
         Value::Constant(ConstantData::Expr(ConstantExpr::GetElementPtr {
             inbounds: _,
             tys: _,
             ref args,
         })) => {
-            // TODO: Split up into functions
-            assert!(ty.is_pointer(&ctx.types));
-            assert!(matches!(args[0], ConstantData::GlobalRef(_)));
             let all_indices_0 = args[1..]
                 .iter()
                 .all(|arg| matches!(arg, ConstantData::Int(ConstantInt::Int64(0))));
-            assert!(all_indices_0);
-            let src = OperandData::GlobalAddress(args[0].as_global_ref().as_string().clone());
-            let dst = ctx.mach_data.vregs.add_vreg_data(ty);
-            ctx.inst_seq.push(MachInstruction::new(
-                InstructionData {
-                    opcode: Opcode::MOVri32, // TODO: MOVri64 is correct
-                    operands: vec![MO::output(dst.into()), MO::new(src)],
-                },
-                ctx.block_map[&ctx.cur_block],
-            ));
-            Ok(dst.into())
+            match args[0] {
+                ConstantData::GlobalRef(Name::Name(name))
+                    if ty.is_pointer(ctx.types) && all_indices_0 =>
+                {
+                    let name = name.to_string();
+                    Ok(global_address(ctx, ty, name).into())
+                }
+                _ => Err(LoweringError::Todo.into()),
+            }
         }
         _ => Err(LoweringError::Todo.into()),
     }
 }
 
-fn val_to_vreg(ctx: &mut LoweringContext<X86_64>, ty: Type, val: ValueId) -> Result<VReg> {
+/// Returns a new register holding the address of the global `name`, of the
+/// pointer type `ty`. It is rewritten for the code and relocation models of
+/// the module by the `code_model` and `pic` passes.
+fn global_address<T: X86Family>(ctx: &mut LoweringContext<T>, ty: Type, name: String) -> VReg {
+    let dst = ctx.mach_data.vregs.add_vreg_data(ty);
+    push(
+        ctx,
+        Opcode::MOVri32, // TODO: MOVri64 is correct
+        vec![
+            MO::output(dst.into()),
+            MO::new(OperandData::GlobalAddress(name)),
+        ],
+    );
+    dst
+}
+
+fn val_to_vreg<T: X86Family>(ctx: &mut LoweringContext<T>, ty: Type, val: ValueId) -> Result<VReg> {
     match val_to_operand_data(ctx, ty, val)? {
-        OperandData::Int32(i) => {
+        imm @ (OperandData::Int32(_) | OperandData::Int64(_)) => {
             let output = ctx.mach_data.vregs.add_vreg_data(ty);
             ctx.inst_seq.push(MachInstruction::new(
                 InstructionData {
-                    opcode: Opcode::MOVri32,
-                    operands: vec![MO::output(output.into()), MO::new(i.into())],
+                    opcode: match imm {
+                        OperandData::Int32(_) => Opcode::MOVri32,
+                        _ => Opcode::MOVri64,
+                    },
+                    operands: vec![MO::output(output.into()), MO::new(imm)],
                 },
                 ctx.block_map[&ctx.cur_block],
             ));
             Ok(output)
         }
+        OperandData::ConstantPool(i) if ty.is_vector(ctx.types) => {
+            Ok(vector::load_pool_entry(ctx, ty, i))
+        }
+        OperandData::ConstantPool(i) => Ok(float::load_pool_entry(ctx, ty, i)),
         OperandData::VReg(vr) => Ok(vr),
         _ => Err(LoweringError::Todo.into()),
     }
 }
+
+/// Emits `opcode` with `dst` as its first operand, that it reads and writes,
+/// after copying `lhs` to it.
+fn two_addr<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    opcode: Opcode,
+    dst: VReg,
+    lhs: VReg,
+    srcs: Vec<MO>,
+) {
+    push(
+        ctx,
+        Opcode::MOVrr32,
+        vec![MO::output(dst.into()), MO::input(lhs.into())],
+    );
+    push(
+        ctx,
+        opcode,
+        std::iter::once(MO::input_output(dst.into()))
+            .chain(srcs)
+            .collect(),
+    );
+}
+
+fn push<T: X86Family>(ctx: &mut LoweringContext<T>, opcode: Opcode, operands: Vec<MO>) {
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData { opcode, operands },
+        ctx.block_map[&ctx.cur_block],
+    ));
+}
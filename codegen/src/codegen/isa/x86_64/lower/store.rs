@@ -1,11 +1,12 @@
-use super::get_or_generate_inst_output;
+use super::{
+    float, get_or_generate_inst_output, legalize, load::addr_base, val_to_vreg, vector, wide,
+};
 use crate::codegen::{
     function::instruction::Instruction as MachInstruction,
     isa::x86_64::{
         instruction::{InstructionData, Opcode, Operand as MOperand, OperandData},
-        X86_64,
+        X86Family,
     },
-    isa::TargetIsa,
     lower::{LoweringContext, LoweringError},
 };
 use anyhow::Result;
@@ -15,112 +16,87 @@ use vicis_core::ir::{
     value::{ConstantData, ConstantInt, Value, ValueId},
 };
 
-pub fn lower_store(
-    ctx: &mut LoweringContext<X86_64>,
+pub fn lower_store<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     tys: &[Type],
     args: &[ValueId],
     _align: u32,
 ) -> Result<()> {
-    let mut dst_slot = None;
-
-    match ctx.ir_data.value_ref(args[1]) {
-        Value::Instruction(id) => {
-            // Maybe Alloca
-            if let Some(slot_id) = ctx.inst_id_to_slot_id.get(id) {
-                dst_slot = Some(*slot_id);
-            } else if ctx.ir_data.instructions[*id].opcode == IrOpcode::GetElementPtr {
-                return lower_store_gep(ctx, tys, args, _align, *id);
-            }
+    if let Value::Instruction(id) = ctx.ir_data.value_ref(args[1]) {
+        let opcode = ctx.ir_data.instructions[*id].opcode;
+        if !ctx.inst_id_to_slot_id.contains_key(id) && opcode == IrOpcode::GetElementPtr {
+            return lower_store_gep(ctx, tys, args, _align, *id);
         }
-        _ => return Err(LoweringError::Todo.into()),
     }
 
-    let mut imm = None;
-    let mut inst = None;
-    let mut arg = None;
+    let (slot, base) = addr_base(ctx, tys[1], args[1])?;
+    let mem = vec![
+        MOperand::new(OperandData::MemStart),
+        MOperand::new(slot),
+        MOperand::new(OperandData::None),
+        MOperand::input(base),
+        MOperand::input(OperandData::None),
+        MOperand::new(OperandData::None),
+    ];
+    store_to(ctx, mem, tys[0], args[0])
+}
 
-    match ctx.ir_data.value_ref(args[0]) {
-        Value::Constant(ConstantData::Int(int)) => imm = Some(*int),
-        Value::Instruction(id) => inst = Some(*id),
-        Value::Argument(idx) => arg = ctx.arg_idx_to_vreg.get(idx).copied(),
-        _ => return Err(LoweringError::Todo.into()),
+/// Stores `src` of `ty` to the memory operand `mem`, by a `mov` of the size
+/// of `ty`, or by two if held in a pair of registers, or by `movss`, `movsd`
+/// or `movups` if held in an XMM register.
+fn store_to<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    mem: Vec<MOperand>,
+    ty: Type,
+    src: ValueId,
+) -> Result<()> {
+    if wide::is_wide::<T>(ty) {
+        return wide::store_pair(ctx, mem, src);
     }
-
-    match (dst_slot, inst, arg, imm) {
-        (Some(slot), Some(id), None, None) => {
-            let inst = get_or_generate_inst_output(ctx, tys[0], id)?;
-            ctx.inst_seq.append(&mut vec![MachInstruction::new(
-                InstructionData {
-                    opcode: Opcode::MOVmr32,
-                    operands: vec![
-                        MOperand::new(OperandData::MemStart),
-                        MOperand::new(OperandData::Slot(slot)),
-                        MOperand::new(OperandData::None),
-                        MOperand::input(OperandData::None),
-                        MOperand::input(OperandData::None),
-                        MOperand::new(OperandData::None),
-                        MOperand::input(inst.into()),
-                    ],
-                },
-                ctx.block_map[&ctx.cur_block],
-            )]);
-            Ok(())
-        }
-        (Some(slot), None, None, Some(ConstantInt::Int32(imm))) => {
-            ctx.inst_seq.append(&mut vec![MachInstruction::new(
-                InstructionData {
-                    opcode: Opcode::MOVmi32,
-                    operands: vec![
-                        MOperand::new(OperandData::MemStart),
-                        MOperand::new(OperandData::Slot(slot)),
-                        MOperand::new(OperandData::None),
-                        MOperand::input(OperandData::None),
-                        MOperand::input(OperandData::None),
-                        MOperand::new(OperandData::None),
-                        MOperand::input(imm.into()),
-                    ],
-                },
-                ctx.block_map[&ctx.cur_block],
-            )]);
-            Ok(())
-        }
-        (Some(slot), None, Some(arg), None) => {
-            ctx.inst_seq.append(&mut vec![MachInstruction::new(
-                InstructionData {
-                    opcode: Opcode::MOVmr32,
-                    operands: vec![
-                        MOperand::new(OperandData::MemStart),
-                        MOperand::new(OperandData::Slot(slot)),
-                        MOperand::new(OperandData::None),
-                        MOperand::input(OperandData::None),
-                        MOperand::input(OperandData::None),
-                        MOperand::new(OperandData::None),
-                        MOperand::input(arg.into()),
-                    ],
-                },
-                ctx.block_map[&ctx.cur_block],
-            )]);
-            Ok(())
-        }
-        _ => Err(LoweringError::Todo.into()),
+    if ty.is_vector(ctx.types) {
+        return vector::store(ctx, mem, ty, src);
     }
+    if ty.is_floating_point() {
+        return float::store(ctx, mem, ty, src);
+    }
+    let size = legalize::int_size(ctx, ty)?;
+    let (opcode, src) = match ctx.ir_data.value_ref(src) {
+        Value::Constant(ConstantData::Int(int)) => match legalize::int_operand(*int) {
+            imm @ OperandData::Int32(_) => (Opcode::store_imm(size), imm),
+            _ => (Opcode::store(size), val_to_vreg(ctx, ty, src)?.into()),
+        },
+        Value::Instruction(id) => (
+            Opcode::store(size),
+            get_or_generate_inst_output(ctx, ty, *id)?.into(),
+        ),
+        Value::Argument(idx) => match ctx.arg_idx_to_vreg.get(idx) {
+            Some(arg) => (Opcode::store(size), (*arg).into()),
+            None => return Err(LoweringError::Todo.into()),
+        },
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    ctx.inst_seq.append(&mut vec![MachInstruction::new(
+        InstructionData {
+            opcode,
+            operands: mem
+                .into_iter()
+                .chain(vec![MOperand::input(src)].into_iter())
+                .collect(),
+        },
+        ctx.block_map[&ctx.cur_block],
+    )]);
+    Ok(())
 }
 
-fn lower_store_gep(
-    ctx: &mut LoweringContext<X86_64>,
+fn lower_store_gep<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
     tys: &[Type],
     args: &[ValueId],
     _align: u32,
     gep_id: InstructionId,
 ) -> Result<()> {
-    use {
-        Constant as Const,
-        ConstantData::Int,
-        ConstantInt::{Int32, Int64},
-        Value::Constant,
-    };
+    use {Constant as Const, ConstantData::Int, ConstantInt::Int64, Value::Constant};
 
-    let mem;
     let gep = &ctx.ir_data.instructions[gep_id];
     let gep_args: Vec<&Value> = gep
         .operand
@@ -129,82 +105,50 @@ fn lower_store_gep(
         .map(|&arg| &ctx.ir_data.values[arg])
         .collect();
 
-    match &gep_args[..] {
-        [Value::Instruction(base_ptr), Const(Int(Int64(idx0))), Const(Int(Int64(idx1)))] => {
-            let base_ptr = ctx.inst_id_to_slot_id[base_ptr];
+    let mem = match &gep_args[..] {
+        [_, Const(Int(Int64(idx0))), Const(Int(Int64(idx1)))] => {
+            let (slot, base) = addr_base(ctx, gep.operand.types()[1], gep.operand.args()[0])?;
             let base_ty = gep.operand.types()[0];
-            let offset = idx0 * X86_64::type_size(ctx.types, base_ty) as i64
-                + idx1
-                    * X86_64::type_size(ctx.types, ctx.types.get_element(base_ty).unwrap()) as i64;
+            let offset = idx0 * T::type_size(ctx.types, base_ty) as i64
+                + idx1 * T::type_size(ctx.types, ctx.types.get_element(base_ty).unwrap()) as i64;
             // debug!(offset);
 
-            mem = vec![
+            vec![
                 MOperand::new(OperandData::MemStart),
-                MOperand::new(OperandData::Slot(base_ptr)),
+                MOperand::new(slot),
                 MOperand::new(OperandData::Int32(offset as i32)),
-                MOperand::input(OperandData::None),
+                MOperand::input(base),
                 MOperand::input(OperandData::None),
                 MOperand::new(OperandData::None),
-            ];
+            ]
         }
-        [Value::Instruction(base_ptr), Const(Int(Int64(idx0))), Value::Instruction(idx1)] => {
-            let base_ptr = ctx.inst_id_to_slot_id[base_ptr];
+        [_, Const(Int(Int64(idx0))), Value::Instruction(idx1)] => {
+            let (slot, base) = addr_base(ctx, gep.operand.types()[1], gep.operand.args()[0])?;
 
             let base_ty = gep.operand.types()[0];
-            let offset = idx0 * X86_64::type_size(ctx.types, base_ty) as i64;
+            let offset = idx0 * T::type_size(ctx.types, base_ty) as i64;
             // debug!(offset);
 
             let idx1_ty = gep.operand.types()[3];
             assert!(idx1_ty.is_i64());
             let idx1 = get_or_generate_inst_output(ctx, idx1_ty, *idx1)?;
 
-            assert!(X86_64::type_size(ctx.types, ctx.types.get_element(base_ty).unwrap()) == 4);
+            let scale = T::type_size(ctx.types, ctx.types.get_element(base_ty).unwrap());
+            if !matches!(scale, 1 | 2 | 4 | 8) {
+                return Err(LoweringError::Todo.into());
+            }
 
-            mem = vec![
+            vec![
                 MOperand::new(OperandData::MemStart),
-                MOperand::new(OperandData::Slot(base_ptr)),
+                MOperand::new(slot),
                 MOperand::new(OperandData::Int32(offset as i32)),
-                MOperand::input(OperandData::None),
+                MOperand::input(base),
                 MOperand::input(OperandData::VReg(idx1)),
-                MOperand::new(OperandData::Int32(X86_64::type_size(
-                    ctx.types,
-                    ctx.types.get_element(base_ty).unwrap(),
-                ) as i32)),
-            ];
+                MOperand::new(OperandData::Int32(scale as i32)),
+            ]
         }
         _ => return Err(LoweringError::Todo.into()),
-    }
-
-    let src = args[0];
-    let src_ty = tys[0];
-    match ctx.ir_data.value_ref(src) {
-        Const(Int(Int32(int))) => {
-            ctx.inst_seq.append(&mut vec![MachInstruction::new(
-                InstructionData {
-                    opcode: Opcode::MOVmi32,
-                    operands: mem
-                        .into_iter()
-                        .chain(vec![MOperand::input(int.into())].into_iter())
-                        .collect(),
-                },
-                ctx.block_map[&ctx.cur_block],
-            )]);
-        }
-        Value::Instruction(id) => {
-            let src = get_or_generate_inst_output(ctx, src_ty, *id)?;
-            ctx.inst_seq.append(&mut vec![MachInstruction::new(
-                InstructionData {
-                    opcode: Opcode::MOVmr32,
-                    operands: mem
-                        .into_iter()
-                        .chain(vec![MOperand::input(src.into())].into_iter())
-                        .collect(),
-                },
-                ctx.block_map[&ctx.cur_block],
-            )]);
-        }
-        _ => return Err(LoweringError::Todo.into()),
-    }
+    };
 
-    Ok(())
+    store_to(ctx, mem, tys[0], args[0])
 }
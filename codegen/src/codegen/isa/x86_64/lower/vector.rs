@@ -0,0 +1,962 @@
+//! Lowering of vectors.
+//!
+//! With 64-bit registers, vectors of 128 bits, e.g. `<4 x i32>` or
+//! `<2 x double>`, are held in XMM registers and operated on by the packed
+//! instructions of SSE2, which every x86-64 processor has. Neither wider
+//! vectors nor AVX, which would have to be asked for as a target feature, are
+//! supported.
+//!
+//! - `<N x i1>`, the results of comparisons, are held as the masks the
+//!   comparisons give, i.e. lanes of 128 / N bits each all ones or zero, in
+//!   registers of the vector of those lanes, e.g. `<4 x i32>` for `<4 x i1>`.
+//! - Constants are loaded from the constant pool of the function.
+//! - `add`, `sub`, `and` and `or` of integers, the arithmetic of floating
+//!   points but `frem`, and `mul` of 16-bit lanes, by `pmullw`, are done by a
+//!   packed instruction on a copy of the first operand, as that of scalars.
+//!   `fneg` flips the sign bits by `xorps`. Operations SSE2 has no instruction
+//!   for, i.e. other `mul`s and shifts, are scalarized: the operands are
+//!   stored to scratch slots, each lane of which is loaded to a
+//!   general-purpose register, operated on and stored back.
+//! - `icmp` is `pcmpeq` or `pcmpgt`, with the operands swapped for `slt`,
+//!   and its result inverted by `pxor` with all ones for `ne`, `sge` and
+//!   `sle`. Unsigned comparisons flip the sign bits of the operands first. Of
+//!   64-bit lanes, only `eq` and `ne` are supported, from both halves being
+//!   equal by `pcmpeqd`.
+//! - `fcmp` is `cmpps` or `cmppd` by the predicate of the condition, with the
+//!   operands swapped for those SSE2 only has the other way around. `one` and
+//!   `ueq` combine two comparisons.
+//! - `sext` of a mask to lanes of its size is a copy of it, and `zext` clears
+//!   all but the low bit of each lane. `bitcast`s between vectors are copies.
+//! - `extractelement` and `insertelement` of a constant index take the lane
+//!   by `pshufd`, `movd`, `movq`, `pextrw` and `pinsrw` where lanes are of
+//!   their sizes. `shufflevector` of 32-bit or 64-bit lanes is `pshufd` of
+//!   either vector, or `shufps` of the low half of the first and the high half
+//!   of the second. The others go through scratch slots.
+//! - Vectors are passed and returned as `float` and `double` are, but masks,
+//!   and loaded and stored by `movups`.
+
+use super::{float, new_empty_inst_output, val_to_vreg};
+use crate::codegen::{
+    function::{instruction::Instruction as MachInstruction, slot::SlotId, slot::SlotOrigin},
+    isa::x86_64::{
+        instruction::{InstructionData, Opcode, Operand as MO, OperandData},
+        register::{GR32, GR64},
+        X86Family,
+    },
+    lower::{LoweringContext, LoweringError},
+    register::{Reg, VReg},
+};
+use anyhow::Result;
+use std::ops::Range;
+use vicis_core::ir::{
+    function::instruction::{FCmpCond, ICmpCond, InstructionId, Opcode as IrOpcode},
+    types::{self, Type},
+    value::{ConstantData, ConstantInt, Value, ValueId},
+};
+
+/// The lanes of a vector held in an XMM register.
+#[derive(Clone, Copy)]
+struct Lanes {
+    num: u32,
+    /// The size of each lane in bytes.
+    size: u32,
+    /// The type of each lane, of `size` bytes even for masks.
+    elem: Type,
+    /// Whether the vector is of `i1`, held as a mask.
+    mask: bool,
+}
+
+/// Returns the lanes of the vector type `ty`, or an error if it cannot be
+/// held in an XMM register.
+fn lanes<T: X86Family>(ctx: &LoweringContext<T>, ty: Type) -> Result<Lanes> {
+    float::supported::<T>()?;
+    let (elem, num) = ctx.types.vector_elements(ty).ok_or(LoweringError::Todo)?;
+    let size = match elem {
+        types::I1 if num <= 16 => 16 / num,
+        types::I8 => 1,
+        types::I16 => 2,
+        types::I32 | types::FLOAT => 4,
+        types::I64 | types::DOUBLE => 8,
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    if size * num != 16 {
+        return Err(LoweringError::Todo.into());
+    }
+    Ok(Lanes {
+        num,
+        size,
+        elem: if elem.is_i1() {
+            int_of_size(size)
+        } else {
+            elem
+        },
+        mask: elem.is_i1(),
+    })
+}
+
+/// Returns the type of the registers holding vectors of `ty`, that of the
+/// lanes of masks for vectors of `i1`.
+pub fn reg_ty<T: X86Family>(ctx: &LoweringContext<T>, ty: Type) -> Type {
+    match lanes(ctx, ty) {
+        Ok(lanes) if lanes.mask => ctx.types.vector_of(lanes.elem, lanes.num),
+        _ => ty,
+    }
+}
+
+/// Returns an error unless vectors of `ty` are passed and returned in XMM
+/// registers.
+pub fn check_arg<T: X86Family>(ctx: &LoweringContext<T>, ty: Type) -> Result<()> {
+    if lanes(ctx, ty)?.mask {
+        return Err(LoweringError::Todo.into());
+    }
+    Ok(())
+}
+
+/// Returns the bytes of the vector constant `konst` of `ty`, as held in a
+/// register.
+pub fn constant_bytes<T: X86Family>(
+    ctx: &LoweringContext<T>,
+    ty: Type,
+    konst: &ConstantData,
+) -> Result<Vec<u8>> {
+    let lanes = lanes(ctx, ty)?;
+    let elems = match konst {
+        ConstantData::Undef | ConstantData::AggregateZero => return Ok(vec![0; 16]),
+        ConstantData::Vector(v) => &v.elems,
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    let mut bytes = vec![];
+    for elem in elems {
+        match elem {
+            ConstantData::Int(ConstantInt::Int1(b)) => {
+                bytes.extend(vec![if *b { 0xff } else { 0 }; lanes.size as usize])
+            }
+            ConstantData::Int(i) => {
+                bytes.extend_from_slice(&i.cast_to_i128().to_le_bytes()[..lanes.size as usize])
+            }
+            ConstantData::Float(f) => bytes.extend(f.to_le_bytes()),
+            ConstantData::Undef => bytes.extend(vec![0; lanes.size as usize]),
+            _ => return Err(LoweringError::Todo.into()),
+        }
+    }
+    Ok(bytes)
+}
+
+/// Loads the `i`th entry of the constant pool, of the vector type `ty`, to a
+/// new register.
+pub fn load_pool_entry<T: X86Family>(ctx: &mut LoweringContext<T>, ty: Type, i: usize) -> VReg {
+    let output = ctx.mach_data.vregs.add_vreg_data(reg_ty(ctx, ty));
+    push(
+        ctx,
+        Opcode::MOVUPSrm,
+        vec![
+            MO::output(output.into()),
+            MO::new(OperandData::ConstantPool(i)),
+        ],
+    );
+    output
+}
+
+/// Loads the result of the load `id`, of `ty`, from the memory operand `mem`.
+pub fn load<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    ty: Type,
+    mem: Vec<MO>,
+) -> Result<()> {
+    check_arg(ctx, ty)?;
+    let output = new_empty_inst_output(ctx, ty, id);
+    push(
+        ctx,
+        Opcode::MOVUPSrm,
+        [MO::output(output.into())].into_iter().chain(mem).collect(),
+    );
+    Ok(())
+}
+
+/// Stores `src` of `ty` to the memory operand `mem`.
+pub fn store<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    mem: Vec<MO>,
+    ty: Type,
+    src: ValueId,
+) -> Result<()> {
+    check_arg(ctx, ty)?;
+    let src = val_to_vreg(ctx, ty, src)?;
+    push(
+        ctx,
+        Opcode::MOVUPSmr,
+        mem.into_iter().chain([MO::input(src.into())]).collect(),
+    );
+    Ok(())
+}
+
+/// Lowers the arithmetic `op` of integers or floating points.
+pub fn lower_bin<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    args: &[ValueId],
+) -> Result<()> {
+    let lanes = lanes(ctx, ty)?;
+    let opcode = match (op, lanes.size) {
+        (IrOpcode::And, _) => Opcode::PANDrr,
+        (IrOpcode::Or, _) => Opcode::PORrr,
+        _ if lanes.mask => return Err(LoweringError::Todo.into()),
+        (IrOpcode::Add, 1) => Opcode::PADDBrr,
+        (IrOpcode::Add, 2) => Opcode::PADDWrr,
+        (IrOpcode::Add, 4) => Opcode::PADDDrr,
+        (IrOpcode::Add, _) => Opcode::PADDQrr,
+        (IrOpcode::Sub, 1) => Opcode::PSUBBrr,
+        (IrOpcode::Sub, 2) => Opcode::PSUBWrr,
+        (IrOpcode::Sub, 4) => Opcode::PSUBDrr,
+        (IrOpcode::Sub, _) => Opcode::PSUBQrr,
+        (IrOpcode::Mul, 2) => Opcode::PMULLWrr,
+        (IrOpcode::Mul | IrOpcode::Shl | IrOpcode::LShr, _) => {
+            return scalarize(ctx, id, op, ty, lanes, args)
+        }
+        (IrOpcode::FAdd, _) => packed(lanes, Opcode::ADDPSrr, Opcode::ADDPDrr),
+        (IrOpcode::FSub, _) => packed(lanes, Opcode::SUBPSrr, Opcode::SUBPDrr),
+        (IrOpcode::FMul, _) => packed(lanes, Opcode::MULPSrr, Opcode::MULPDrr),
+        (IrOpcode::FDiv, _) => packed(lanes, Opcode::DIVPSrr, Opcode::DIVPDrr),
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    let lhs = val_to_vreg(ctx, ty, args[0])?;
+    let rhs = val_to_vreg(ctx, ty, args[1])?;
+    let output = new_empty_inst_output(ctx, ty, id);
+    two_addr(ctx, opcode, output, lhs, rhs);
+    Ok(())
+}
+
+/// Flips the sign bits of the lanes by `xorps` with `-0.0`s.
+pub fn lower_fneg<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    ty: Type,
+    arg: ValueId,
+) -> Result<()> {
+    let lanes = lanes(ctx, ty)?;
+    let val = val_to_vreg(ctx, ty, arg)?;
+    let sign = constant(ctx, ty, splat(lanes, sign_bit(lanes)));
+    let output = new_empty_inst_output(ctx, ty, id);
+    two_addr(ctx, Opcode::XORPSrr, output, val, sign);
+    Ok(())
+}
+
+pub fn lower_icmp<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    ty: Type,
+    args: &[ValueId; 2],
+    cond: ICmpCond,
+) -> Result<()> {
+    let lanes = lanes(ctx, ty)?;
+    if lanes.mask {
+        return Err(LoweringError::Todo.into());
+    }
+    // Whether `lhs > rhs` is compared rather than `lhs == rhs`, whether the
+    // operands are compared the other way around, and whether the result is
+    // inverted
+    let (gt, swapped, inverted) = match cond {
+        ICmpCond::Eq => (false, false, false),
+        ICmpCond::Ne => (false, false, true),
+        ICmpCond::Sgt | ICmpCond::Ugt => (true, false, false),
+        ICmpCond::Slt | ICmpCond::Ult => (true, true, false),
+        ICmpCond::Sge | ICmpCond::Uge => (true, true, true),
+        ICmpCond::Sle | ICmpCond::Ule => (true, false, true),
+    };
+    let unsigned = matches!(
+        cond,
+        ICmpCond::Ugt | ICmpCond::Uge | ICmpCond::Ult | ICmpCond::Ule
+    );
+    if gt && lanes.size == 8 {
+        return Err(LoweringError::Todo.into());
+    }
+
+    let mut lhs = val_to_vreg(ctx, ty, args[0])?;
+    let mut rhs = val_to_vreg(ctx, ty, args[1])?;
+    if unsigned {
+        let sign = constant(ctx, ty, splat(lanes, sign_bit(lanes)));
+        [lhs, rhs] = [lhs, rhs].map(|val| {
+            let flipped = ctx.mach_data.vregs.add_vreg_data(ty);
+            two_addr(ctx, Opcode::PXORrr, flipped, val, sign);
+            flipped
+        });
+    }
+    if swapped {
+        (lhs, rhs) = (rhs, lhs);
+    }
+
+    let result_ty = ctx.types.vector_of(types::I1, lanes.num);
+    let output = new_empty_inst_output(ctx, result_ty, id);
+    let ones = inverted.then(|| constant(ctx, ty, vec![0xff; 16]));
+    let cmp = if inverted {
+        ctx.mach_data.vregs.add_vreg_data(ty)
+    } else {
+        output
+    };
+    let opcode = match (gt, lanes.size) {
+        (false, 1) => Opcode::PCMPEQBrr,
+        (false, 2) => Opcode::PCMPEQWrr,
+        (false, _) => Opcode::PCMPEQDrr,
+        (true, 1) => Opcode::PCMPGTBrr,
+        (true, 2) => Opcode::PCMPGTWrr,
+        (true, _) => Opcode::PCMPGTDrr,
+    };
+    if lanes.size == 8 {
+        // Both halves of each lane are equal.
+        let halves = ctx.mach_data.vregs.add_vreg_data(ty);
+        two_addr(ctx, opcode, halves, lhs, rhs);
+        let swapped_halves = ctx.mach_data.vregs.add_vreg_data(ty);
+        push(
+            ctx,
+            Opcode::PSHUFDrri,
+            vec![
+                MO::output(swapped_halves.into()),
+                MO::input(halves.into()),
+                MO::new(OperandData::Int32(0b10_11_00_01)),
+            ],
+        );
+        two_addr(ctx, Opcode::PANDrr, cmp, halves, swapped_halves);
+    } else {
+        two_addr(ctx, opcode, cmp, lhs, rhs);
+    }
+    if let Some(ones) = ones {
+        two_addr(ctx, Opcode::PXORrr, output, cmp, ones);
+    }
+    Ok(())
+}
+
+pub fn lower_fcmp<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    ty: Type,
+    args: &[ValueId; 2],
+    cond: FCmpCond,
+) -> Result<()> {
+    let lanes = lanes(ctx, ty)?;
+    let opcode = packed(lanes, Opcode::CMPPSrri, Opcode::CMPPDrri);
+    let result_ty = ctx.types.vector_of(types::I1, lanes.num);
+    let output = new_empty_inst_output(ctx, result_ty, id);
+
+    // The predicates of `cmpps`, and whether the operands are compared the
+    // other way around
+    const EQ: i32 = 0;
+    const LT: i32 = 1;
+    const LE: i32 = 2;
+    const UNORD: i32 = 3;
+    const NEQ: i32 = 4;
+    const NLT: i32 = 5;
+    const NLE: i32 = 6;
+    const ORD: i32 = 7;
+    let (preds, combine) = match cond {
+        FCmpCond::False | FCmpCond::True => {
+            let bits = if cond == FCmpCond::True { 0xff } else { 0 };
+            let konst = constant(ctx, ty, vec![bits; 16]);
+            push(
+                ctx,
+                Opcode::MOVAPSrr,
+                vec![MO::output(output.into()), MO::input(konst.into())],
+            );
+            return Ok(());
+        }
+        FCmpCond::Oeq => (vec![(EQ, false)], None),
+        FCmpCond::Ogt => (vec![(LT, true)], None),
+        FCmpCond::Oge => (vec![(LE, true)], None),
+        FCmpCond::Olt => (vec![(LT, false)], None),
+        FCmpCond::Ole => (vec![(LE, false)], None),
+        FCmpCond::Ord => (vec![(ORD, false)], None),
+        FCmpCond::Ugt => (vec![(NLE, false)], None),
+        FCmpCond::Uge => (vec![(NLT, false)], None),
+        FCmpCond::Ult => (vec![(NLE, true)], None),
+        FCmpCond::Ule => (vec![(NLT, true)], None),
+        FCmpCond::Une => (vec![(NEQ, false)], None),
+        FCmpCond::Uno => (vec![(UNORD, false)], None),
+        FCmpCond::One => (vec![(ORD, false), (NEQ, false)], Some(Opcode::PANDrr)),
+        FCmpCond::Ueq => (vec![(UNORD, false), (EQ, false)], Some(Opcode::PORrr)),
+    };
+
+    let lhs = val_to_vreg(ctx, ty, args[0])?;
+    let rhs = val_to_vreg(ctx, ty, args[1])?;
+    let mut cmps = vec![];
+    for (i, &(pred, swapped)) in preds.iter().enumerate() {
+        let (lhs, rhs) = if swapped { (rhs, lhs) } else { (lhs, rhs) };
+        let cmp = if i + 1 == preds.len() && combine.is_none() {
+            output
+        } else {
+            ctx.mach_data.vregs.add_vreg_data(ty)
+        };
+        push(
+            ctx,
+            Opcode::MOVAPSrr,
+            vec![MO::output(cmp.into()), MO::input(lhs.into())],
+        );
+        push(
+            ctx,
+            opcode,
+            vec![
+                MO::input_output(cmp.into()),
+                MO::input(rhs.into()),
+                MO::new(OperandData::Int32(pred)),
+            ],
+        );
+        cmps.push(cmp);
+    }
+    if let (Some(combine), &[a, b]) = (combine, &cmps[..]) {
+        two_addr(ctx, combine, output, a, b);
+    }
+    Ok(())
+}
+
+pub fn lower_cast<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    opcode: IrOpcode,
+    tys: &[Type; 2],
+    arg: ValueId,
+) -> Result<()> {
+    let [from, to] = *tys;
+    let (from_lanes, to_lanes) = (lanes(ctx, from)?, lanes(ctx, to)?);
+    let same_lanes = from_lanes.size == to_lanes.size && from_lanes.num == to_lanes.num;
+    match opcode {
+        // The mask is all ones already, or zero.
+        IrOpcode::Sext if from_lanes.mask && !to_lanes.mask && same_lanes => {
+            let val = val_to_vreg(ctx, from, arg)?;
+            let output = new_empty_inst_output(ctx, to, id);
+            push(
+                ctx,
+                Opcode::MOVAPSrr,
+                vec![MO::output(output.into()), MO::input(val.into())],
+            );
+        }
+        IrOpcode::Zext if from_lanes.mask && !to_lanes.mask && same_lanes => {
+            let val = val_to_vreg(ctx, from, arg)?;
+            let mut one = vec![0; to_lanes.size as usize];
+            one[0] = 1;
+            let ones = constant(ctx, to, splat(to_lanes, one));
+            let output = new_empty_inst_output(ctx, to, id);
+            two_addr(ctx, Opcode::PANDrr, output, val, ones);
+        }
+        IrOpcode::Bitcast if !from_lanes.mask && !to_lanes.mask => {
+            let val = val_to_vreg(ctx, from, arg)?;
+            let output = new_empty_inst_output(ctx, to, id);
+            push(
+                ctx,
+                Opcode::MOVAPSrr,
+                vec![MO::output(output.into()), MO::input(val.into())],
+            );
+        }
+        _ => return Err(LoweringError::Todo.into()),
+    }
+    Ok(())
+}
+
+/// Lowers `extractelement` of `args[0]` of `tys[0]` at the index `args[1]` of
+/// `tys[1]`.
+pub fn lower_extractelement<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    tys: &[Type; 2],
+    args: &[ValueId; 2],
+) -> Result<()> {
+    let lanes = lanes(ctx, tys[0])?;
+    let ty = ctx.types.vector_elements(tys[0]).unwrap().0;
+    let vec = val_to_vreg(ctx, tys[0], args[0])?;
+    let index = const_index(ctx, args[1], lanes);
+
+    match (index, lanes.size) {
+        (Some(i), 4 | 8) if !lanes.mask => {
+            // Move the lane to the low end of the register.
+            let low = if i == 0 {
+                vec
+            } else {
+                let low = ctx.mach_data.vregs.add_vreg_data(tys[0]);
+                let imm = if lanes.size == 4 { i } else { 0b11_10_11_10 };
+                push(
+                    ctx,
+                    Opcode::PSHUFDrri,
+                    vec![
+                        MO::output(low.into()),
+                        MO::input(vec.into()),
+                        MO::new(OperandData::Int32(imm as i32)),
+                    ],
+                );
+                low
+            };
+            let output = new_empty_inst_output(ctx, ty, id);
+            let opcode = match ty {
+                types::I32 => Opcode::MOVDr32x,
+                types::I64 => Opcode::MOVQr64x,
+                _ => Opcode::MOVAPSrr,
+            };
+            push(
+                ctx,
+                opcode,
+                vec![MO::output(output.into()), MO::input(low.into())],
+            );
+        }
+        (Some(i), 2) if !lanes.mask => {
+            let output = new_empty_inst_output(ctx, ty, id);
+            push(
+                ctx,
+                Opcode::PEXTRWrri,
+                vec![
+                    MO::output(output.into()),
+                    MO::input(vec.into()),
+                    MO::new(OperandData::Int32(i as i32)),
+                ],
+            );
+        }
+        _ => {
+            let slot = to_scratch(ctx, tys[0], vec);
+            let mem = lane_mem(ctx, slot, lanes, tys[1], args[1])?;
+            let output = new_empty_inst_output(ctx, ty, id);
+            if lanes.mask {
+                // Each byte of a lane is all ones or zero.
+                push(
+                    ctx,
+                    Opcode::load(1, false),
+                    [MO::output(output.into())].into_iter().chain(mem).collect(),
+                );
+                push(
+                    ctx,
+                    Opcode::SHRri8,
+                    vec![
+                        MO::input_output(output.into()),
+                        MO::new(OperandData::Int32(7)),
+                    ],
+                );
+            } else {
+                push(
+                    ctx,
+                    lane_load(lanes),
+                    [MO::output(output.into())].into_iter().chain(mem).collect(),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Lowers `insertelement` of `args[1]` of `tys[1]` into `args[0]` of `tys[0]`
+/// at the index `args[2]` of `tys[2]`.
+pub fn lower_insertelement<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    tys: &[Type; 3],
+    args: &[ValueId; 3],
+) -> Result<()> {
+    let lanes = lanes(ctx, tys[0])?;
+    if lanes.mask {
+        return Err(LoweringError::Todo.into());
+    }
+    let vec = val_to_vreg(ctx, tys[0], args[0])?;
+    let elem = val_to_vreg(ctx, tys[1], args[1])?;
+
+    if let (Some(i), 2) = (const_index(ctx, args[2], lanes), lanes.size) {
+        let output = new_empty_inst_output(ctx, tys[0], id);
+        push(
+            ctx,
+            Opcode::MOVAPSrr,
+            vec![MO::output(output.into()), MO::input(vec.into())],
+        );
+        push(
+            ctx,
+            Opcode::PINSRWrri,
+            vec![
+                MO::input_output(output.into()),
+                MO::input(elem.into()),
+                MO::new(OperandData::Int32(i as i32)),
+            ],
+        );
+        return Ok(());
+    }
+
+    let slot = to_scratch(ctx, tys[0], vec);
+    let mem = lane_mem(ctx, slot, lanes, tys[2], args[2])?;
+    push(
+        ctx,
+        lane_store(lanes),
+        mem.into_iter().chain([MO::input(elem.into())]).collect(),
+    );
+    let output = new_empty_inst_output(ctx, tys[0], id);
+    push(
+        ctx,
+        Opcode::MOVUPSrm,
+        [MO::output(output.into())]
+            .into_iter()
+            .chain(slot_mem(slot, 0))
+            .collect(),
+    );
+    Ok(())
+}
+
+/// Lowers `shufflevector` of `args[0]` and `args[1]` of `tys[0]` by the mask
+/// `args[2]` of `tys[1]`.
+pub fn lower_shufflevector<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    tys: &[Type; 2],
+    args: &[ValueId; 3],
+) -> Result<()> {
+    let lanes = lanes(ctx, tys[0])?;
+    let result_ty = ctx.types.vector_of(
+        ctx.types.vector_elements(tys[0]).unwrap().0,
+        ctx.types
+            .vector_elements(tys[1])
+            .ok_or(LoweringError::Todo)?
+            .1,
+    );
+    if lanes.num != ctx.types.vector_elements(result_ty).unwrap().1 {
+        return Err(LoweringError::Todo.into());
+    }
+    // The lane of the concatenation of the vectors each lane is taken from,
+    // or `None` if undefined
+    let mask: Vec<Option<u32>> = match ctx.ir_data.value_ref(args[2]) {
+        Value::Constant(ConstantData::AggregateZero) => vec![Some(0); lanes.num as usize],
+        Value::Constant(ConstantData::Undef) => vec![None; lanes.num as usize],
+        Value::Constant(ConstantData::Vector(mask)) => mask
+            .elems
+            .iter()
+            .map(|elem| match elem {
+                ConstantData::Int(i) => Ok(Some(i.cast_to_i64() as u32)),
+                ConstantData::Undef => Ok(None),
+                _ => Err(LoweringError::Todo),
+            })
+            .collect::<Result<_, _>>()?,
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    if mask.iter().flatten().any(|&i| i >= 2 * lanes.num) {
+        return Err(LoweringError::Todo.into());
+    }
+    let uses = |from: u32| mask.iter().flatten().any(|&i| i / lanes.num == from);
+    let x = val_to_vreg(ctx, tys[0], args[0])?;
+    let y = if uses(1) {
+        Some(val_to_vreg(ctx, tys[0], args[1])?)
+    } else {
+        None
+    };
+
+    if !lanes.mask && lanes.size >= 4 {
+        // The dword of the concatenation each dword is taken from
+        let per_lane = lanes.size / 4;
+        let dwords: Vec<Option<u32>> = mask
+            .iter()
+            .flat_map(|&i| (0..per_lane).map(move |j| i.map(|i| i * per_lane + j)))
+            .collect();
+        // The immediate of `pshufd` or `shufps` taking the dwords from the
+        // vectors they are in
+        let imm = dwords.iter().enumerate().fold(0, |imm, (d, &i)| {
+            imm | i.map_or(d as u32, |i| i % 4) << (2 * d)
+        });
+        let all_from =
+            |range: Range<usize>, from: u32| dwords[range].iter().flatten().all(|&i| i / 4 == from);
+        let single = match y {
+            None => Some(x),
+            Some(y) if all_from(0..4, 1) => Some(y),
+            _ => None,
+        };
+        let output = new_empty_inst_output(ctx, result_ty, id);
+        if let Some(src) = single {
+            push(
+                ctx,
+                Opcode::PSHUFDrri,
+                vec![
+                    MO::output(output.into()),
+                    MO::input(src.into()),
+                    MO::new(OperandData::Int32(imm as i32)),
+                ],
+            );
+            return Ok(());
+        }
+        if let (Some(y), true, true) = (y, all_from(0..2, 0), all_from(2..4, 1)) {
+            push(
+                ctx,
+                Opcode::MOVAPSrr,
+                vec![MO::output(output.into()), MO::input(x.into())],
+            );
+            push(
+                ctx,
+                Opcode::SHUFPSrri,
+                vec![
+                    MO::input_output(output.into()),
+                    MO::input(y.into()),
+                    MO::new(OperandData::Int32(imm as i32)),
+                ],
+            );
+            return Ok(());
+        }
+    }
+
+    // Copy the lanes one by one through general-purpose registers.
+    let srcs = [Some(x), y].map(|src| src.map(|src| to_scratch(ctx, tys[0], src)));
+    let dst = ctx.slots.add_slot(result_ty, 16, SlotOrigin::Scratch);
+    let lane_ty = int_of_size(lanes.size.max(4));
+    for (i, &from) in mask.iter().enumerate() {
+        let Some(from) = from else {
+            continue;
+        };
+        let src = srcs[(from / lanes.num) as usize].unwrap();
+        let lane = ctx.mach_data.vregs.add_vreg_data(lane_ty);
+        push(
+            ctx,
+            Opcode::load(lanes.size, false),
+            [MO::output(lane.into())]
+                .into_iter()
+                .chain(slot_mem(src, from % lanes.num * lanes.size))
+                .collect(),
+        );
+        push(
+            ctx,
+            Opcode::store(lanes.size),
+            slot_mem(dst, i as u32 * lanes.size)
+                .into_iter()
+                .chain([MO::input(lane.into())])
+                .collect(),
+        );
+    }
+    let output = new_empty_inst_output(ctx, result_ty, id);
+    push(
+        ctx,
+        Opcode::MOVUPSrm,
+        [MO::output(output.into())]
+            .into_iter()
+            .chain(slot_mem(dst, 0))
+            .collect(),
+    );
+    Ok(())
+}
+
+/// Lowers `op` of integers lane by lane in general-purpose registers, as SSE2
+/// has no instruction for it.
+fn scalarize<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    op: IrOpcode,
+    ty: Type,
+    lanes: Lanes,
+    args: &[ValueId],
+) -> Result<()> {
+    let lhs = val_to_vreg(ctx, ty, args[0])?;
+    let rhs = val_to_vreg(ctx, ty, args[1])?;
+    let lhs = to_scratch(ctx, ty, lhs);
+    let rhs = to_scratch(ctx, ty, rhs);
+    let lane_ty = int_of_size(lanes.size.max(4));
+    let rcx: Reg = if lanes.size == 8 {
+        GR64::RCX.into()
+    } else {
+        GR32::ECX.into()
+    };
+    for i in 0..lanes.num {
+        let [x, y] = [lhs, rhs].map(|slot| {
+            let lane = ctx.mach_data.vregs.add_vreg_data(lane_ty);
+            push(
+                ctx,
+                Opcode::load(lanes.size, false),
+                [MO::output(lane.into())]
+                    .into_iter()
+                    .chain(slot_mem(slot, i * lanes.size))
+                    .collect(),
+            );
+            lane
+        });
+        match op {
+            IrOpcode::Mul => push(
+                ctx,
+                Opcode::IMULrr32,
+                vec![MO::input_output(x.into()), MO::input(y.into())],
+            ),
+            _ => {
+                push(
+                    ctx,
+                    Opcode::MOVrr32,
+                    vec![MO::output(rcx.into()), MO::input(y.into())],
+                );
+                push(
+                    ctx,
+                    if op == IrOpcode::Shl {
+                        Opcode::SHLrCL
+                    } else {
+                        Opcode::SHRrCL
+                    },
+                    vec![MO::input_output(x.into()), MO::input(rcx.into())],
+                );
+            }
+        }
+        push(
+            ctx,
+            Opcode::store(lanes.size),
+            slot_mem(lhs, i * lanes.size)
+                .into_iter()
+                .chain([MO::input(x.into())])
+                .collect(),
+        );
+    }
+    let output = new_empty_inst_output(ctx, ty, id);
+    push(
+        ctx,
+        Opcode::MOVUPSrm,
+        [MO::output(output.into())]
+            .into_iter()
+            .chain(slot_mem(lhs, 0))
+            .collect(),
+    );
+    Ok(())
+}
+
+/// Returns the constant index `val` if it is that of a lane.
+fn const_index<T: X86Family>(ctx: &LoweringContext<T>, val: ValueId, lanes: Lanes) -> Option<u32> {
+    match ctx.ir_data.value_ref(val) {
+        Value::Constant(ConstantData::Int(i)) => u32::try_from(i.cast_to_i64())
+            .ok()
+            .filter(|&i| i < lanes.num),
+        _ => None,
+    }
+}
+
+/// Returns the memory operand of the lane at the index `index` of `ty` of the
+/// vector in `slot`.
+fn lane_mem<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    slot: SlotId,
+    lanes: Lanes,
+    ty: Type,
+    index: ValueId,
+) -> Result<Vec<MO>> {
+    if let Some(i) = const_index(ctx, index, lanes) {
+        return Ok(slot_mem(slot, i * lanes.size));
+    }
+    let index = match ty {
+        types::I64 => val_to_vreg(ctx, ty, index)?,
+        types::I32 => {
+            let index = val_to_vreg(ctx, ty, index)?;
+            let wide = ctx.mach_data.vregs.add_vreg_data(types::I64);
+            push(
+                ctx,
+                Opcode::MOVZXr64r32,
+                vec![MO::output(wide.into()), MO::input(index.into())],
+            );
+            wide
+        }
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    Ok(vec![
+        MO::new(OperandData::MemStart),
+        MO::new(OperandData::Slot(slot)),
+        MO::new(OperandData::None),
+        MO::input(OperandData::None),
+        MO::input(OperandData::VReg(index)),
+        MO::new(OperandData::Int32(lanes.size as i32)),
+    ])
+}
+
+/// Returns the opcode loading a lane to a register of its type.
+fn lane_load(lanes: Lanes) -> Opcode {
+    if lanes.elem.is_floating_point() {
+        Opcode::load_xmm(lanes.size)
+    } else {
+        Opcode::load(lanes.size, false)
+    }
+}
+
+/// Returns the opcode storing a lane from a register of its type.
+fn lane_store(lanes: Lanes) -> Opcode {
+    if lanes.elem.is_floating_point() {
+        Opcode::store_xmm(lanes.size)
+    } else {
+        Opcode::store(lanes.size)
+    }
+}
+
+/// Stores `val` of `ty` to a new scratch slot.
+fn to_scratch<T: X86Family>(ctx: &mut LoweringContext<T>, ty: Type, val: VReg) -> SlotId {
+    let slot = ctx.slots.add_slot(ty, 16, SlotOrigin::Scratch);
+    push(
+        ctx,
+        Opcode::MOVUPSmr,
+        slot_mem(slot, 0)
+            .into_iter()
+            .chain([MO::input(val.into())])
+            .collect(),
+    );
+    slot
+}
+
+/// Returns the memory operand `offset` bytes into `slot`.
+fn slot_mem(slot: SlotId, offset: u32) -> Vec<MO> {
+    vec![
+        MO::new(OperandData::MemStart),
+        MO::new(OperandData::Slot(slot)),
+        MO::new(OperandData::Int32(offset as i32)),
+        MO::input(OperandData::None),
+        MO::input(OperandData::None),
+        MO::new(OperandData::None),
+    ]
+}
+
+/// Loads the vector of `bytes` of `ty` from the constant pool to a new
+/// register.
+fn constant<T: X86Family>(ctx: &mut LoweringContext<T>, ty: Type, bytes: Vec<u8>) -> VReg {
+    let i = ctx.constant_pool_index(bytes);
+    load_pool_entry(ctx, ty, i)
+}
+
+/// Returns the bytes of a vector of which every lane is `lane`.
+fn splat(lanes: Lanes, lane: Vec<u8>) -> Vec<u8> {
+    lane.repeat(lanes.num as usize)
+}
+
+/// Returns the bytes of a lane of which only the sign bit is set.
+fn sign_bit(lanes: Lanes) -> Vec<u8> {
+    let mut lane = vec![0; lanes.size as usize];
+    lane[lanes.size as usize - 1] = 0x80;
+    lane
+}
+
+/// Returns the integer type of `size` bytes.
+fn int_of_size(size: u32) -> Type {
+    match size {
+        1 => types::I8,
+        2 => types::I16,
+        4 => types::I32,
+        _ => types::I64,
+    }
+}
+
+/// Returns `ps`, the `float` form of a packed instruction, if the lanes are
+/// `float`s, or `pd`, its `double` form.
+fn packed(lanes: Lanes, ps: Opcode, pd: Opcode) -> Opcode {
+    if lanes.size == 4 {
+        ps
+    } else {
+        pd
+    }
+}
+
+/// Emits `opcode dst, src` on a copy of `lhs` in `dst`.
+fn two_addr<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    opcode: Opcode,
+    dst: VReg,
+    lhs: VReg,
+    src: VReg,
+) {
+    push(
+        ctx,
+        Opcode::MOVAPSrr,
+        vec![MO::output(dst.into()), MO::input(lhs.into())],
+    );
+    push(
+        ctx,
+        opcode,
+        vec![MO::input_output(dst.into()), MO::input(src.into())],
+    );
+}
+
+fn push<T: X86Family>(ctx: &mut LoweringContext<T>, opcode: Opcode, operands: Vec<MO>) {
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData { opcode, operands },
+        ctx.block_map[&ctx.cur_block],
+    ));
+}
@@ -0,0 +1,586 @@
+//! Lowering of 128-bit integers.
+//!
+//! With 64-bit registers, `i128` values are held in pairs of them: the low
+//! half in the register the value is known by, as any other value, and the
+//! high half in the one [`LoweringContext::high_vregs`] maps it to.
+//!
+//! - Arguments take two registers in a row, e.g. `rdi` and `rsi`, and
+//!   results are returned in `rax` and `rdx`. Values in memory are loaded
+//!   and stored as two quads, the low one first.
+//! - Additions and subtractions carry from the low halves into the high
+//!   ones by `adc` and `sbb`.
+//! - Products are the full product of the low halves by `mul`, to the high
+//!   half of which the products of each low half by the other high half are
+//!   added.
+//! - Shifts by constants shift bits from a half into the other by `shld` or
+//!   `shrd`, or move a half into the other for counts of 64 or more. Shifts
+//!   by variables do the former by `cl`, then the latter by `cmovne` if the
+//!   count is 64 or more.
+//! - `zext` and `sext` extend the value into the low half, and set the high
+//!   one to zero or to copies of the sign bit. `trunc` takes the low half.
+//!
+//! Every register but the two of the result is written by a single
+//! instruction, or by a copy and an instruction of two operands, as the
+//! spiller expects.
+
+use super::{get_or_generate_inst_output, legalize, new_empty_inst_output, val_to_vreg};
+use crate::codegen::{
+    function::instruction::Instruction as MachInstruction,
+    isa::x86_64::{
+        instruction::{InstructionData, Opcode, Operand as MO, OperandData},
+        register::GR64,
+        X86Family,
+    },
+    lower::{LoweringContext, LoweringError},
+    register::{Reg, VReg},
+};
+use anyhow::Result;
+use std::iter;
+use vicis_core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{InstructionId, Opcode as IrOpcode},
+    },
+    types::{self, Type},
+    value::{ConstantData, ConstantInt, Value, ValueId},
+};
+
+/// Returns true if values of `ty` are held in pairs of registers.
+pub fn is_wide<T: X86Family>(ty: Type) -> bool {
+    T::WORD_SIZE == 8 && ty.is_i128()
+}
+
+/// Creates a pair of registers, returning that of the low half.
+pub fn new_pair<T: X86Family>(ctx: &mut LoweringContext<T>) -> VReg {
+    let lo = ctx.mach_data.vregs.add_vreg_data(types::I64);
+    let hi = ctx.mach_data.vregs.add_vreg_data(types::I64);
+    ctx.high_vregs.insert(lo, hi);
+    lo
+}
+
+/// Returns the register of the high half of the pair of `lo`.
+pub fn high<T: X86Family>(ctx: &LoweringContext<T>, lo: VReg) -> Result<VReg> {
+    Ok(*ctx.high_vregs.get(&lo).ok_or(LoweringError::Todo)?)
+}
+
+/// Returns the halves of `val`, in registers or as immediates.
+pub fn val_to_halves<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    val: ValueId,
+) -> Result<[OperandData; 2]> {
+    let lo = match ctx.ir_data.values[val] {
+        Value::Instruction(id) => get_or_generate_inst_output(ctx, types::I128, id)?,
+        Value::Argument(idx) => *ctx.arg_idx_to_vreg.get(&idx).ok_or(LoweringError::Todo)?,
+        Value::Constant(ConstantData::Int(int)) => {
+            let i = int.cast_to_i128();
+            return Ok([i as i64, (i >> 64) as i64]
+                .map(|half| legalize::int_operand(ConstantInt::Int64(half))));
+        }
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    Ok([lo.into(), high(ctx, lo)?.into()])
+}
+
+/// Returns the halves of `val` in registers.
+pub fn val_to_pair<T: X86Family>(ctx: &mut LoweringContext<T>, val: ValueId) -> Result<[VReg; 2]> {
+    let [lo, hi] = val_to_halves(ctx, val)?;
+    Ok([to_vreg(ctx, lo)?, to_vreg(ctx, hi)?])
+}
+
+/// Copies the arguments in `regs` to a new pair of registers.
+pub fn copy_arg_to_pair<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    regs: [Reg; 2],
+) -> Result<VReg> {
+    let lo = new_pair(ctx);
+    let hi = high(ctx, lo)?;
+    for (vreg, reg) in [lo, hi].into_iter().zip(regs) {
+        push(
+            ctx,
+            Opcode::MOVrr64,
+            vec![MO::output(vreg.into()), MO::input(reg.into())],
+        );
+    }
+    Ok(lo)
+}
+
+/// Copies `val` to the registers it is returned in.
+pub fn copy_to_ret_regs<T: X86Family>(ctx: &mut LoweringContext<T>, val: ValueId) -> Result<()> {
+    let halves = val_to_pair(ctx, val)?;
+    for (reg, half) in [GR64::RAX, GR64::RDX].into_iter().zip(halves) {
+        let reg: Reg = reg.into();
+        push(
+            ctx,
+            Opcode::MOVrr64,
+            vec![MO::output(reg.into()), MO::input(half.into())],
+        );
+    }
+    Ok(())
+}
+
+/// Copies the result of the call `id` from the registers it is returned in,
+/// given by [`ret_regs`].
+pub fn copy_from_ret_regs<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+) -> Result<()> {
+    let lo = new_empty_inst_output(ctx, types::I128, id);
+    let hi = high(ctx, lo)?;
+    for (vreg, reg) in [lo, hi].into_iter().zip(ret_regs()) {
+        push(
+            ctx,
+            Opcode::MOVrr64,
+            vec![MO::output(vreg.into()), MO::input(reg.into())],
+        );
+    }
+    Ok(())
+}
+
+/// Returns the registers a pair is returned in.
+pub fn ret_regs() -> [Reg; 2] {
+    [GR64::RAX.into(), GR64::RDX.into()]
+}
+
+pub fn lower_phi<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    args: &[ValueId],
+    blocks: &[BasicBlockId],
+) -> Result<()> {
+    let lo = new_empty_inst_output(ctx, types::I128, id);
+    let hi = high(ctx, lo)?;
+    let mut phis = [vec![MO::output(lo.into())], vec![MO::output(hi.into())]];
+    for (arg, block) in args.iter().zip(blocks.iter()) {
+        let halves = val_to_halves(ctx, *arg)?;
+        for (phi, half) in phis.iter_mut().zip(halves) {
+            phi.push(MO::input(half));
+            phi.push(MO::new(OperandData::Block(ctx.block_map[block])));
+        }
+    }
+    for phi in phis {
+        push(ctx, Opcode::Phi, phi);
+    }
+    Ok(())
+}
+
+pub fn lower_bin<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    op: IrOpcode,
+    args: &[ValueId],
+) -> Result<()> {
+    match op {
+        IrOpcode::Add | IrOpcode::Sub => lower_add_sub(ctx, id, op, args),
+        IrOpcode::Mul => lower_mul(ctx, id, args),
+        IrOpcode::Shl | IrOpcode::LShr => match ctx.ir_data.value_ref(args[1]) {
+            // Counts of 128 or more give poison values.
+            Value::Constant(ConstantData::Int(count)) => {
+                let count = (count.cast_to_i128() & 127) as i32;
+                lower_shift_imm(ctx, id, op, args[0], count)
+            }
+            _ => lower_shift(ctx, id, op, args),
+        },
+        _ => Err(LoweringError::Todo.into()),
+    }
+}
+
+fn lower_add_sub<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    op: IrOpcode,
+    args: &[ValueId],
+) -> Result<()> {
+    let [lhs_lo, lhs_hi] = val_to_pair(ctx, args[0])?;
+    let [rhs_lo, rhs_hi] = val_to_halves(ctx, args[1])?;
+    // Immediates are of 32 bits, sign-extended
+    let rhs_lo = to_arith_operand(ctx, rhs_lo)?;
+    let rhs_hi = to_arith_operand(ctx, rhs_hi)?;
+    let lo = new_empty_inst_output(ctx, types::I128, id);
+    let hi = high(ctx, lo)?;
+
+    let opcode = |rhs: &OperandData, rr: Opcode, ri: Opcode| match rhs {
+        OperandData::Int32(_) => ri,
+        _ => rr,
+    };
+    let (op_lo, op_hi) = if op == IrOpcode::Add {
+        (
+            opcode(&rhs_lo, Opcode::ADDrr32, Opcode::ADDri32),
+            opcode(&rhs_hi, Opcode::ADCrr32, Opcode::ADCri32),
+        )
+    } else {
+        (
+            opcode(&rhs_lo, Opcode::SUBrr32, Opcode::SUBri32),
+            opcode(&rhs_hi, Opcode::SBBrr32, Opcode::SBBri32),
+        )
+    };
+    // Copies leave the carry as is.
+    two_addr(ctx, op_lo, lo, lhs_lo.into(), vec![source(rhs_lo)]);
+    two_addr(ctx, op_hi, hi, lhs_hi.into(), vec![source(rhs_hi)]);
+    Ok(())
+}
+
+fn lower_mul<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    args: &[ValueId],
+) -> Result<()> {
+    let [lhs_lo, lhs_hi] = val_to_pair(ctx, args[0])?;
+    let [rhs_lo, rhs_hi] = val_to_pair(ctx, args[1])?;
+    let lo = new_empty_inst_output(ctx, types::I128, id);
+    let hi = high(ctx, lo)?;
+    let [rax, rdx] = ret_regs();
+
+    // Only the low halves of the cross products add to the result.
+    let cross = [(lhs_lo, rhs_hi), (lhs_hi, rhs_lo)].map(|(x, y)| {
+        let product = ctx.mach_data.vregs.add_vreg_data(types::I64);
+        two_addr(
+            ctx,
+            Opcode::IMULrr32,
+            product,
+            x.into(),
+            vec![MO::input(y.into())],
+        );
+        product
+    });
+
+    push(
+        ctx,
+        Opcode::MOVrr64,
+        vec![MO::output(rax.into()), MO::input(lhs_lo.into())],
+    );
+    push(
+        ctx,
+        Opcode::MULr64,
+        vec![
+            MO {
+                implicit: true,
+                ..MO::input_output(rax.into())
+            },
+            MO::implicit_output(rdx.into()),
+            MO::input(rhs_lo.into()),
+        ],
+    );
+    push(
+        ctx,
+        Opcode::MOVrr64,
+        vec![MO::output(lo.into()), MO::input(rax.into())],
+    );
+    let sum = ctx.mach_data.vregs.add_vreg_data(types::I64);
+    two_addr(
+        ctx,
+        Opcode::ADDrr32,
+        sum,
+        rdx.into(),
+        vec![MO::input(cross[0].into())],
+    );
+    two_addr(
+        ctx,
+        Opcode::ADDrr32,
+        hi,
+        sum.into(),
+        vec![MO::input(cross[1].into())],
+    );
+    Ok(())
+}
+
+fn lower_shift_imm<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    op: IrOpcode,
+    val: ValueId,
+    count: i32,
+) -> Result<()> {
+    let [src_lo, src_hi] = val_to_pair(ctx, val)?;
+    let lo = new_empty_inst_output(ctx, types::I128, id);
+    let hi = high(ctx, lo)?;
+    let imm = |i: i32| MO::new(OperandData::Int32(i));
+
+    match (op, count < 64) {
+        (IrOpcode::Shl, true) => {
+            two_addr(
+                ctx,
+                Opcode::SHLDrri8,
+                hi,
+                src_hi.into(),
+                vec![MO::input(src_lo.into()), imm(count)],
+            );
+            two_addr(ctx, Opcode::SHLri8, lo, src_lo.into(), vec![imm(count)]);
+        }
+        (IrOpcode::Shl, false) => {
+            two_addr(
+                ctx,
+                Opcode::SHLri8,
+                hi,
+                src_lo.into(),
+                vec![imm(count - 64)],
+            );
+            zero(ctx, lo);
+        }
+        (_, true) => {
+            two_addr(
+                ctx,
+                Opcode::SHRDrri8,
+                lo,
+                src_lo.into(),
+                vec![MO::input(src_hi.into()), imm(count)],
+            );
+            two_addr(ctx, Opcode::SHRri8, hi, src_hi.into(), vec![imm(count)]);
+        }
+        (_, false) => {
+            two_addr(
+                ctx,
+                Opcode::SHRri8,
+                lo,
+                src_hi.into(),
+                vec![imm(count - 64)],
+            );
+            zero(ctx, hi);
+        }
+    }
+    Ok(())
+}
+
+fn lower_shift<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    op: IrOpcode,
+    args: &[ValueId],
+) -> Result<()> {
+    let [src_lo, src_hi] = val_to_pair(ctx, args[0])?;
+    let [count, _] = val_to_halves(ctx, args[1])?;
+    let count = to_vreg(ctx, count)?;
+    let lo = new_empty_inst_output(ctx, types::I128, id);
+    let hi = high(ctx, lo)?;
+    let rcx: Reg = GR64::RCX.into();
+
+    let zero_reg = ctx.mach_data.vregs.add_vreg_data(types::I64);
+    zero(ctx, zero_reg);
+    push(
+        ctx,
+        Opcode::MOVrr64,
+        vec![MO::output(rcx.into()), MO::input(count.into())],
+    );
+
+    // The halves shifted by the count modulo 64, from the one bits are
+    // shifted out of to the other.
+    let shifted = [(); 2].map(|_| ctx.mach_data.vregs.add_vreg_data(types::I64));
+    let (from, to, src_from, src_to, shd, sh) = if op == IrOpcode::Shl {
+        (lo, hi, src_lo, src_hi, Opcode::SHLDrrCL, Opcode::SHLrCL)
+    } else {
+        (hi, lo, src_hi, src_lo, Opcode::SHRDrrCL, Opcode::SHRrCL)
+    };
+    two_addr(
+        ctx,
+        shd,
+        shifted[1],
+        src_to.into(),
+        vec![MO::input(src_from.into()), MO::input(rcx.into())],
+    );
+    two_addr(
+        ctx,
+        sh,
+        shifted[0],
+        src_from.into(),
+        vec![MO::input(rcx.into())],
+    );
+
+    // Counts of 64 or more move the halves by 64 bits more.
+    push(
+        ctx,
+        Opcode::TESTri32,
+        vec![MO::input(rcx.into()), MO::new(OperandData::Int32(64))],
+    );
+    two_addr(
+        ctx,
+        Opcode::CMOVNErr32,
+        to,
+        shifted[1].into(),
+        vec![MO::input(shifted[0].into())],
+    );
+    two_addr(
+        ctx,
+        Opcode::CMOVNErr32,
+        from,
+        shifted[0].into(),
+        vec![MO::input(zero_reg.into())],
+    );
+    Ok(())
+}
+
+/// Lowers `zext` or `sext`, if `signed`, of `arg` of `from`, an integer
+/// held in a register.
+pub fn lower_ext<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    from: Type,
+    arg: ValueId,
+    signed: bool,
+) -> Result<()> {
+    let size = legalize::int_size(ctx, from)?;
+    let val = val_to_vreg(ctx, from, arg)?;
+    let lo = new_empty_inst_output(ctx, types::I128, id);
+    let hi = high(ctx, lo)?;
+
+    if signed && from.is_i1() {
+        legalize::sext_i1(ctx, types::I64, lo, val);
+    } else {
+        let opcode = match size {
+            8 => Opcode::MOVrr64,
+            size => Opcode::extend(size, signed),
+        };
+        push(
+            ctx,
+            opcode,
+            vec![MO::output(lo.into()), MO::input(val.into())],
+        );
+    }
+    if signed {
+        two_addr(
+            ctx,
+            Opcode::SARri8,
+            hi,
+            lo.into(),
+            vec![MO::new(OperandData::Int32(63))],
+        );
+    } else {
+        zero(ctx, hi);
+    }
+    Ok(())
+}
+
+/// Lowers `trunc` of `arg` to `to`, copying the low part of its low half.
+pub fn lower_trunc<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    to: Type,
+    arg: ValueId,
+) -> Result<()> {
+    legalize::int_size(ctx, to)?;
+    let [lo, _] = val_to_halves(ctx, arg)?;
+    let lo = to_vreg(ctx, lo)?;
+    let output = new_empty_inst_output(ctx, to, id);
+    if to.is_i1() {
+        legalize::trunc_to_i1(ctx, output, lo);
+        return Ok(());
+    }
+    push(
+        ctx,
+        Opcode::MOVrr32,
+        vec![MO::output(output.into()), MO::input(lo.into())],
+    );
+    Ok(())
+}
+
+/// Loads the result of the load `id` from the memory operand `mem`.
+pub fn load_pair<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    id: InstructionId,
+    mem: Vec<MO>,
+) -> Result<()> {
+    let lo = new_empty_inst_output(ctx, types::I128, id);
+    let hi = high(ctx, lo)?;
+    for (vreg, mem) in [lo, hi].into_iter().zip([mem.clone(), high_half(mem)]) {
+        push(
+            ctx,
+            Opcode::MOVrm64,
+            iter::once(MO::output(vreg.into())).chain(mem).collect(),
+        );
+    }
+    Ok(())
+}
+
+/// Stores `src` to the memory operand `mem`.
+pub fn store_pair<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    mem: Vec<MO>,
+    src: ValueId,
+) -> Result<()> {
+    let halves = val_to_halves(ctx, src)?;
+    for (half, mem) in halves.into_iter().zip([mem.clone(), high_half(mem)]) {
+        let (opcode, half) = match to_arith_operand(ctx, half)? {
+            imm @ OperandData::Int32(_) => (Opcode::MOVm64i32, imm),
+            half => (Opcode::MOVmr64, half),
+        };
+        push(ctx, opcode, mem.into_iter().chain([source(half)]).collect());
+    }
+    Ok(())
+}
+
+/// Returns the memory operand `mem` displaced to the high half.
+fn high_half(mut mem: Vec<MO>) -> Vec<MO> {
+    mem[2].data = match mem[2].data {
+        OperandData::Int32(disp) => OperandData::Int32(disp + 8),
+        _ => OperandData::Int32(8),
+    };
+    mem
+}
+
+/// Returns `op` in a register, moving it to a new one if an immediate.
+fn to_vreg<T: X86Family>(ctx: &mut LoweringContext<T>, op: OperandData) -> Result<VReg> {
+    let opcode = match op {
+        OperandData::VReg(vreg) => return Ok(vreg),
+        OperandData::Int32(_) => Opcode::MOVri32,
+        OperandData::Int64(_) => Opcode::MOVri64,
+        _ => return Err(LoweringError::Todo.into()),
+    };
+    let vreg = ctx.mach_data.vregs.add_vreg_data(types::I64);
+    push(ctx, opcode, vec![MO::output(vreg.into()), MO::new(op)]);
+    Ok(vreg)
+}
+
+/// Returns `op` as a register or an immediate of 32 bits, which arithmetic
+/// takes.
+fn to_arith_operand<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    op: OperandData,
+) -> Result<OperandData> {
+    match op {
+        OperandData::Int64(_) => Ok(to_vreg(ctx, op)?.into()),
+        op => Ok(op),
+    }
+}
+
+/// Returns `op` as a source operand.
+fn source(op: OperandData) -> MO {
+    match op {
+        OperandData::VReg(_) | OperandData::Reg(_) => MO::input(op),
+        op => MO::new(op),
+    }
+}
+
+/// Emits `opcode dst, srcs...` on a copy of `lhs` in `dst`.
+fn two_addr<T: X86Family>(
+    ctx: &mut LoweringContext<T>,
+    opcode: Opcode,
+    dst: VReg,
+    lhs: OperandData,
+    srcs: Vec<MO>,
+) {
+    push(
+        ctx,
+        Opcode::MOVrr64,
+        vec![MO::output(dst.into()), MO::input(lhs)],
+    );
+    push(
+        ctx,
+        opcode,
+        iter::once(MO::input_output(dst.into()))
+            .chain(srcs)
+            .collect(),
+    );
+}
+
+fn zero<T: X86Family>(ctx: &mut LoweringContext<T>, dst: VReg) {
+    push(
+        ctx,
+        Opcode::MOVri32,
+        vec![MO::output(dst.into()), MO::new(OperandData::Int32(0))],
+    );
+}
+
+fn push<T: X86Family>(ctx: &mut LoweringContext<T>, opcode: Opcode, operands: Vec<MO>) {
+    ctx.inst_seq.push(MachInstruction::new(
+        InstructionData { opcode, operands },
+        ctx.block_map[&ctx.cur_block],
+    ));
+}
@@ -1,30 +1,56 @@
 pub mod asm;
+pub mod encode;
 pub mod instruction;
 pub mod lower;
 pub mod pass;
 pub mod register;
 
-use super::TargetIsa;
-use crate::codegen::{call_conv::CallConvKind, isa::x86_64, module::Module, pass::regalloc};
-use anyhow::Result;
-use vicis_core::ir::types::{self, ArrayType, CompoundType, Type, Types};
+use super::{ModulePass, TargetIsa};
+use crate::codegen::lower::expand::ExpandHooks;
+use crate::codegen::{
+    call_conv::CallConvKind,
+    isa::x86_64::{
+        self,
+        register::{RegClass, GR64},
+    },
+    pass::{private_symbols, regalloc},
+    register::Reg,
+};
+use vicis_core::ir::{
+    module::data_layout::DataLayout,
+    types::{Type, Types},
+};
 
 #[derive(Copy, Clone)]
 pub struct X86_64;
 
+/// An x86 target. The instructions, the lowering, the passes and the printer
+/// of this module are shared by all of them.
+pub trait X86Family: TargetIsa<InstInfo = instruction::InstructionInfo> {
+    /// The size of pointers and of the registers saved on the stack, in bytes.
+    const WORD_SIZE: u32;
+    /// The stack pointer.
+    const SP: Reg;
+    /// The frame pointer.
+    const FP: Reg;
+}
+
 impl TargetIsa for X86_64 {
     type InstInfo = instruction::InstructionInfo;
     type Lower = x86_64::lower::Lower;
     type RegClass = register::RegClass;
     type RegInfo = register::RegInfo;
 
-    fn module_pass_list() -> Vec<fn(&mut Module<Self>) -> Result<()>> {
+    fn module_pass_list() -> Vec<ModulePass<Self>> {
         vec![
             regalloc::run_on_module,
             pass::phi_elimination::run_on_module, // TODO: should be target independent
             pass::simple_reg_coalescing::run_on_module,
             pass::eliminate_slot::run_on_module,
             pass::pro_epi_inserter::run_on_module,
+            pass::code_model::run_on_module,
+            pass::pic::run_on_module,
+            private_symbols::run_on_module,
         ]
     }
 
@@ -32,28 +58,19 @@ impl TargetIsa for X86_64 {
         CallConvKind::SystemV
     }
 
-    fn type_size(types: &Types, ty: Type) -> u32 {
-        match types.get(ty) {
-            Some(ty) => match &*ty {
-                CompoundType::Pointer(_) => 8,
-                CompoundType::Array(ArrayType {
-                    inner,
-                    num_elements,
-                }) => Self::type_size(types, *inner) * num_elements,
-                CompoundType::Function(_) => 0,
-                CompoundType::Struct(_) => todo!(),
-                CompoundType::Metadata => todo!(),
-                CompoundType::Alias(_) => todo!(),
-            },
-            None => match ty {
-                types::VOID => 0,
-                types::I1 => 1,
-                types::I8 => 1,
-                types::I16 => 2,
-                types::I32 => 4,
-                types::I64 => 8,
-                _ => unreachable!(),
-            },
-        }
+    fn data_layout() -> DataLayout {
+        DataLayout::default()
+    }
+}
+
+impl X86Family for X86_64 {
+    const WORD_SIZE: u32 = 8;
+    const SP: Reg = Reg(RegClass::GR64 as u16, GR64::RSP as u16);
+    const FP: Reg = Reg(RegClass::GR64 as u16, GR64::RBP as u16);
+}
+
+impl ExpandHooks for X86_64 {
+    fn size_of(&self, types: &Types, ty: Type) -> u32 {
+        Self::type_size(types, ty)
     }
 }
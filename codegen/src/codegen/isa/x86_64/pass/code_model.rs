@@ -0,0 +1,82 @@
+use crate::codegen::{
+    function::{instruction::Instruction, Function},
+    isa::x86_64::{
+        instruction::{InstructionData, Opcode, Operand, OperandData},
+        register::GR64,
+        X86_64,
+    },
+    module::Module,
+    options::{CodeModel, RelocModel},
+    register::Reg,
+};
+use anyhow::Result;
+
+/// Rewrites references to globals in static code for the medium and large
+/// code models, where they may lie beyond 32-bit addresses:
+///
+/// - The address of a global variable is materialized by `movabs`.
+/// - In the large code model, the address of the callee is also
+///   materialized, into `r11`, and called through it. `r11` is not used for
+///   arguments, and not allocated to values.
+///
+/// Position-independent code is rewritten by the `pic` pass instead.
+pub fn run_on_module(module: &mut Module<X86_64>) -> Result<()> {
+    if module.reloc_model != RelocModel::Static || module.code_model == CodeModel::Small {
+        return Ok(());
+    }
+
+    let large = module.code_model == CodeModel::Large;
+    for (_, func) in &mut module.functions {
+        run_on_function(func, large);
+    }
+    Ok(())
+}
+
+fn run_on_function(function: &mut Function<X86_64>, large: bool) {
+    let r11: Reg = GR64::R11.into();
+    let mut movs = vec![];
+    let mut calls = vec![];
+    for block_id in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block_id) {
+            let inst = &function.data.inst_ref(inst_id).data;
+            match (inst.opcode, &inst.operands[..]) {
+                (Opcode::MOVri32, [_, op]) if matches!(op.data, OperandData::GlobalAddress(_)) => {
+                    movs.push(inst_id)
+                }
+                (Opcode::CALL, _) if large => calls.push((block_id, inst_id)),
+                _ => {}
+            }
+        }
+    }
+
+    for inst_id in movs {
+        function.data.inst_ref_mut(inst_id).data.opcode = Opcode::MOVABSr64;
+    }
+
+    for (block_id, inst_id) in calls {
+        let call = &mut function.data.inst_ref_mut(inst_id).data;
+        let Some(op) = call
+            .operands
+            .iter_mut()
+            .find(|op| matches!(op.data, OperandData::Label(_)))
+        else {
+            continue;
+        };
+        let OperandData::Label(name) = std::mem::replace(&mut op.data, r11.into()) else {
+            unreachable!()
+        };
+        op.input = true;
+        call.opcode = Opcode::CALLr64;
+        let mov = function.data.create_inst(Instruction::new(
+            InstructionData {
+                opcode: Opcode::MOVABSr64,
+                operands: vec![
+                    Operand::output(r11.into()),
+                    Operand::new(OperandData::GlobalAddress(name)),
+                ],
+            },
+            block_id,
+        ));
+        function.layout.insert_inst_before(inst_id, mov, block_id);
+    }
+}
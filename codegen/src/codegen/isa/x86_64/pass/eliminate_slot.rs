@@ -1,19 +1,19 @@
 use crate::codegen::{
     function::Function,
-    isa::x86_64::{instruction::OperandData, register::GR64, X86_64},
+    isa::x86_64::{instruction::OperandData, X86Family},
     module::Module,
 };
 use anyhow::Result;
 use rustc_hash::FxHashMap;
 
-pub fn run_on_module(module: &mut Module<X86_64>) -> Result<()> {
+pub fn run_on_module<T: X86Family>(module: &mut Module<T>) -> Result<()> {
     for (_, func) in &mut module.functions {
         run_on_function(func);
     }
     Ok(())
 }
 
-pub fn run_on_function(function: &mut Function<X86_64>) {
+pub fn run_on_function<T: X86Family>(function: &mut Function<T>) {
     let mut worklist = vec![];
 
     for block in function.layout.block_iter() {
@@ -58,7 +58,7 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
                     });
                     mem[0].data = OperandData::None;
                     mem[1].data = OperandData::Int32(-(*off as i32));
-                    mem[2].data = OperandData::Reg(GR64::RBP.into());
+                    mem[2].data = OperandData::Reg(T::FP);
                 }
                 (OperandData::Slot(slot), OperandData::Int32(imm)) => {
                     let off = offset_map.entry(*slot).or_insert_with(|| {
@@ -67,7 +67,7 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
                     });
                     mem[1].data = OperandData::Int32(*imm - *off as i32);
                     mem[0].data = OperandData::None;
-                    mem[2].data = OperandData::Reg(GR64::RBP.into());
+                    mem[2].data = OperandData::Reg(T::FP);
                 }
                 _ => todo!(),
             }
@@ -77,4 +77,8 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
 
         function.data.instructions[inst_id] = inst;
     }
+
+    for (slot, off) in offset_map {
+        function.slots.set_offset(slot, -(off as i32));
+    }
 }
@@ -1,4 +1,6 @@
+pub mod code_model;
 pub mod eliminate_slot;
 pub mod phi_elimination;
+pub mod pic;
 pub mod pro_epi_inserter;
 pub mod simple_reg_coalescing;
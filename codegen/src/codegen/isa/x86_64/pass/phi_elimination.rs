@@ -2,7 +2,8 @@ use crate::codegen::{
     function::{basic_block::BasicBlockId, instruction::Instruction, Function},
     isa::x86_64::{
         instruction::{InstructionData, Opcode, Operand, OperandData},
-        X86_64,
+        register::RegClass,
+        X86Family,
     },
     module::Module,
     register::Reg,
@@ -10,14 +11,14 @@ use crate::codegen::{
 use anyhow::Result;
 use rustc_hash::FxHashMap;
 
-pub fn run_on_module(module: &mut Module<X86_64>) -> Result<()> {
+pub fn run_on_module<T: X86Family>(module: &mut Module<T>) -> Result<()> {
     for (_, func) in &mut module.functions {
         run_on_function(func);
     }
     Ok(())
 }
 
-pub fn run_on_function(function: &mut Function<X86_64>) {
+pub fn run_on_function<T: X86Family>(function: &mut Function<T>) {
     let mut worklist = vec![];
     let mut map: FxHashMap<Reg, Vec<(OperandData, BasicBlockId)>> = FxHashMap::default();
 
@@ -30,11 +31,9 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
             worklist.push(inst_id);
             let output = *inst.data.operands[0].data.as_reg();
             for i in (0..inst.data.operands[1..].len()).step_by(2) {
-                let val = inst.data.operands[1 + i + 0].data.clone();
+                let val = inst.data.operands[1 + i].data.clone();
                 let block = *inst.data.operands[1 + i + 1].data.as_block();
-                map.entry(output)
-                    .or_insert_with(Vec::new)
-                    .push((val, block));
+                map.entry(output).or_default().push((val, block));
             }
         }
     }
@@ -44,9 +43,22 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
             let maybe_term = function.layout.last_inst_of(block).unwrap();
             // assert!(matches!(arg, OperandData::Int32(_)));
             let copy = match arg {
-                OperandData::Int32(_) => Instruction::new(
+                OperandData::Int32(_) | OperandData::Int64(_) => Instruction::new(
                     InstructionData {
-                        opcode: Opcode::MOVri32,
+                        opcode: match arg {
+                            OperandData::Int32(_) => Opcode::MOVri32,
+                            _ => Opcode::MOVri64,
+                        },
+                        operands: vec![
+                            Operand::output(OperandData::Reg(output)),
+                            Operand::new(arg),
+                        ],
+                    },
+                    block,
+                ),
+                OperandData::ConstantPool(i) => Instruction::new(
+                    InstructionData {
+                        opcode: Opcode::load_xmm(function.constant_pool[i].len() as u32),
                         operands: vec![
                             Operand::output(OperandData::Reg(output)),
                             Operand::new(arg),
@@ -56,7 +68,11 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
                 ),
                 OperandData::Reg(_) => Instruction::new(
                     InstructionData {
-                        opcode: Opcode::MOVrr32,
+                        opcode: if output.0 == RegClass::XMM as u16 {
+                            Opcode::MOVAPSrr
+                        } else {
+                            Opcode::MOVrr32
+                        },
                         operands: vec![
                             Operand::output(OperandData::Reg(output)),
                             Operand::input(arg),
@@ -0,0 +1,96 @@
+use crate::codegen::{
+    function::Function,
+    isa::x86_64::{
+        instruction::{Opcode, OperandData},
+        X86_64,
+    },
+    module::Module,
+    options::{CodeModel, RelocModel},
+};
+use anyhow::{bail, Result};
+use rustc_hash::FxHashSet;
+use vicis_core::ir::module::{
+    linkage::Linkage, preemption_specifier::PreemptionSpecifier, visibility::Visibility,
+};
+
+/// Rewrites references to globals for position-independent code, if the
+/// module is compiled for it:
+///
+/// - The address of a global variable with local linkage or `hidden` or
+///   `protected` visibility is computed relative to `rip`, and that of any
+///   other is loaded from the GOT, as it may be defined in another module. In
+///   the medium code model, where data may lie beyond 2 GiB, every address
+///   is loaded from the GOT.
+/// - Calls go through the PLT, except to `dso_local` functions and those of
+///   local linkage defined in the module, and to those of `hidden` or
+///   `protected` visibility.
+pub fn run_on_module(module: &mut Module<X86_64>) -> Result<()> {
+    if module.reloc_model != RelocModel::Pic {
+        return Ok(());
+    }
+    if module.code_model == CodeModel::Large {
+        bail!("the large code model is not supported for position-independent code")
+    }
+
+    let local_vars: FxHashSet<String> = module
+        .global_variables
+        .values()
+        .filter(|_| module.code_model == CodeModel::Small)
+        .filter(|gv| {
+            matches!(gv.linkage, Some(Linkage::Private | Linkage::Internal))
+                || gv.visibility != Visibility::Default
+        })
+        .filter_map(|gv| gv.name.to_str().map(str::to_owned))
+        .collect();
+    let local_funcs: FxHashSet<String> = module
+        .functions
+        .iter()
+        .filter(|(_, f)| {
+            (!f.is_prototype
+                && (matches!(f.preemption_specifier, PreemptionSpecifier::DsoLocal)
+                    || matches!(f.linkage, Linkage::Private | Linkage::Internal)))
+                || f.visibility != Visibility::Default
+        })
+        .map(|(_, f)| f.name.clone())
+        .collect();
+
+    for (_, func) in &mut module.functions {
+        run_on_function(func, &local_vars, &local_funcs);
+    }
+    Ok(())
+}
+
+fn run_on_function(
+    function: &mut Function<X86_64>,
+    local_vars: &FxHashSet<String>,
+    local_funcs: &FxHashSet<String>,
+) {
+    let mut worklist = vec![];
+    for block_id in function.layout.block_iter() {
+        for inst_id in function.layout.inst_iter(block_id) {
+            let inst = &function.data.inst_ref(inst_id).data;
+            let opcode = match (inst.opcode, &inst.operands[..]) {
+                (Opcode::MOVri32, [_, op]) => match &op.data {
+                    OperandData::GlobalAddress(name) if local_vars.contains(name) => {
+                        Opcode::LEAr64rip
+                    }
+                    OperandData::GlobalAddress(_) => Opcode::MOVr64got,
+                    _ => continue,
+                },
+                (Opcode::CALL, _) => match inst.operands.iter().find_map(|op| match &op.data {
+                    OperandData::Label(name) => Some(name),
+                    _ => None,
+                }) {
+                    Some(name) if !local_funcs.contains(name) => Opcode::CALLplt,
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            worklist.push((inst_id, opcode));
+        }
+    }
+
+    for (inst_id, opcode) in worklist {
+        function.data.inst_ref_mut(inst_id).data.opcode = opcode;
+    }
+}
@@ -1,38 +1,62 @@
 use crate::codegen::{
-    function::{instruction::Instruction, Function},
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{CfiDirective, Instruction, InstructionId},
+        Function,
+    },
     isa::x86_64::{
         instruction::{InstructionData, Opcode, Operand, OperandData},
-        register::GR64,
-        X86_64,
+        X86Family,
     },
     module::Module,
 };
 use anyhow::Result;
 
-pub fn run_on_module(module: &mut Module<X86_64>) -> Result<()> {
+pub fn run_on_module<T: X86Family>(module: &mut Module<T>) -> Result<()> {
     for (_, func) in &mut module.functions {
-        run_on_function(func);
+        // Unwinding through a function with landing pads needs its CFI.
+        let emit_cfi = module.emit_cfi || func.personality.is_some();
+        run_on_function(func, emit_cfi);
     }
     Ok(())
 }
 
-pub fn run_on_function(function: &mut Function<X86_64>) {
+pub fn run_on_function<T: X86Family>(function: &mut Function<T>, emit_cfi: bool) {
     let unaligned_slot_size = function.slots.unaligned_size();
-    let num_saved_64bit_regs = 1; // rbp TODO
+    let num_saved_regs = 1; // rbp TODO
+    let word = T::WORD_SIZE;
 
     let adj = roundup(
-        (unaligned_slot_size + num_saved_64bit_regs * 8 + 8/*=call*/) as i32,
+        (unaligned_slot_size + num_saved_regs * word + word/*=call*/) as i32,
         16,
-    ) - (num_saved_64bit_regs * 8 + 8) as i32;
+    ) - (num_saved_regs * word + word) as i32;
+    let (push, pop, mov, sub, add) = if word == 8 {
+        (
+            Opcode::PUSH64,
+            Opcode::POP64,
+            Opcode::MOVrr64,
+            Opcode::SUBr64i32,
+            Opcode::ADDr64i32,
+        )
+    } else {
+        (
+            Opcode::PUSH32,
+            Opcode::POP32,
+            Opcode::MOVrr32,
+            Opcode::SUBri32,
+            Opcode::ADDri32,
+        )
+    };
 
     // insert prologue
+    let mut prologue = None;
     if let Some(entry) = function.layout.first_block {
         if adj > 0 {
             let sub = function.data.create_inst(Instruction::new(
                 InstructionData {
-                    opcode: Opcode::SUBr64i32,
+                    opcode: sub,
                     operands: vec![
-                        Operand::input_output(OperandData::Reg(GR64::RSP.into())),
+                        Operand::input_output(OperandData::Reg(T::SP)),
                         Operand::input(OperandData::Int32(adj)),
                     ],
                 },
@@ -42,23 +66,24 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
         }
         let mov = function.data.create_inst(Instruction::new(
             InstructionData {
-                opcode: Opcode::MOVrr64,
+                opcode: mov,
                 operands: vec![
-                    Operand::output(OperandData::Reg(GR64::RBP.into())),
-                    Operand::input(OperandData::Reg(GR64::RSP.into())),
+                    Operand::output(OperandData::Reg(T::FP)),
+                    Operand::input(OperandData::Reg(T::SP)),
                 ],
             },
             entry,
         ));
         function.layout.insert_inst_at_start(mov, entry);
-        let push64 = function.data.create_inst(Instruction::new(
+        let push_fp = function.data.create_inst(Instruction::new(
             InstructionData {
-                opcode: Opcode::PUSH64,
-                operands: vec![Operand::input(OperandData::Reg(GR64::RBP.into()))],
+                opcode: push,
+                operands: vec![Operand::input(OperandData::Reg(T::FP))],
             },
             entry,
         ));
-        function.layout.insert_inst_at_start(push64, entry);
+        function.layout.insert_inst_at_start(push_fp, entry);
+        prologue = Some((entry, push_fp, mov));
     }
 
     // insert epilogue
@@ -72,13 +97,14 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
             epilogues.push((block, inst_id));
         }
     }
+    let mut pops = vec![];
     for (block, ret_id) in epilogues {
         if adj > 0 {
             let add = function.data.create_inst(Instruction::new(
                 InstructionData {
-                    opcode: Opcode::ADDr64i32,
+                    opcode: add,
                     operands: vec![
-                        Operand::output(OperandData::Reg(GR64::RSP.into())),
+                        Operand::output(OperandData::Reg(T::SP)),
                         Operand::input(OperandData::Int32(adj)),
                     ],
                 },
@@ -86,17 +112,73 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
             ));
             function.layout.insert_inst_before(ret_id, add, block);
         }
-        let pop64 = function.data.create_inst(Instruction::new(
+        let pop_fp = function.data.create_inst(Instruction::new(
             InstructionData {
-                opcode: Opcode::POP64,
-                operands: vec![Operand::input(OperandData::Reg(GR64::RBP.into()))],
+                opcode: pop,
+                operands: vec![Operand::input(OperandData::Reg(T::FP))],
             },
             block,
         ));
-        function.layout.insert_inst_before(ret_id, pop64, block);
+        function.layout.insert_inst_before(ret_id, pop_fp, block);
+        pops.push((block, pop_fp, ret_id));
+    }
+
+    if let (true, Some(prologue)) = (emit_cfi, prologue) {
+        insert_cfi(function, prologue, &pops);
     }
 }
 
+type InstId = InstructionId<InstructionData>;
+
+/// Describes the frame to unwinders. The CFA is kept at `fp + 2 * word` from
+/// the `mov` of the prologue to the `pop` of each epilogue. An epilogue
+/// followed by more code saves the state before it and restores it after the
+/// `ret`.
+fn insert_cfi<T: X86Family>(
+    function: &mut Function<T>,
+    (entry, push_fp, mov_fp): (BasicBlockId, InstId, InstId),
+    pops: &[(BasicBlockId, InstId, InstId)],
+) {
+    let word = T::WORD_SIZE as i32;
+    let cfi = |function: &mut Function<T>, directive, block| {
+        function.data.create_inst(Instruction::new(
+            InstructionData {
+                opcode: Opcode::CFI,
+                operands: vec![Operand::new(OperandData::Cfi(directive))],
+            },
+            block,
+        ))
+    };
+
+    let start = cfi(function, CfiDirective::StartProc, entry);
+    function.layout.insert_inst_at_start(start, entry);
+    for directive in [
+        CfiDirective::Offset(T::FP, -2 * word),
+        CfiDirective::DefCfaOffset(2 * word),
+    ] {
+        let inst = cfi(function, directive, entry);
+        function.layout.insert_inst_after(push_fp, inst, entry);
+    }
+    let inst = cfi(function, CfiDirective::DefCfaRegister(T::FP), entry);
+    function.layout.insert_inst_after(mov_fp, inst, entry);
+
+    let last_block = function.layout.last_block.unwrap();
+    let last_inst = function.layout.last_inst_of(last_block);
+    for &(block, pop_fp, ret_id) in pops {
+        let inst = cfi(function, CfiDirective::DefCfa(T::SP, word), block);
+        function.layout.insert_inst_after(pop_fp, inst, block);
+        if last_inst != Some(ret_id) {
+            let remember = cfi(function, CfiDirective::RememberState, block);
+            function.layout.insert_inst_before(pop_fp, remember, block);
+            let restore = cfi(function, CfiDirective::RestoreState, block);
+            function.layout.insert_inst_after(ret_id, restore, block);
+        }
+    }
+
+    let end = cfi(function, CfiDirective::EndProc, last_block);
+    function.layout.append_inst(end, last_block);
+}
+
 fn roundup(n: i32, align: i32) -> i32 {
     (n + align - 1) & !(align - 1)
 }
@@ -1,19 +1,19 @@
 use crate::codegen::{
     function::Function,
-    isa::x86_64::{instruction::Opcode, register::RegInfo, X86_64},
+    isa::x86_64::{instruction::Opcode, X86Family},
     module::Module,
     register::RegisterInfo,
 };
 use anyhow::Result;
 
-pub fn run_on_module(module: &mut Module<X86_64>) -> Result<()> {
+pub fn run_on_module<T: X86Family>(module: &mut Module<T>) -> Result<()> {
     for (_, func) in &mut module.functions {
         run_on_function(func);
     }
     Ok(())
 }
 
-pub fn run_on_function(function: &mut Function<X86_64>) {
+pub fn run_on_function<T: X86Family>(function: &mut Function<T>) {
     let mut worklist = vec![];
 
     for block_id in function.layout.block_iter() {
@@ -21,8 +21,8 @@ pub fn run_on_function(function: &mut Function<X86_64>) {
             let inst = function.data.inst_ref(inst_id);
             match inst.data.opcode {
                 Opcode::MOVrr32 | Opcode::MOVrr64
-                    if RegInfo::to_reg_unit(*inst.data.operands[0].data.as_reg())
-                        == RegInfo::to_reg_unit(*inst.data.operands[1].data.as_reg()) =>
+                    if T::RegInfo::to_reg_unit(*inst.data.operands[0].data.as_reg())
+                        == T::RegInfo::to_reg_unit(*inst.data.operands[1].data.as_reg()) =>
                 {
                     worklist.push(inst_id)
                 }
@@ -45,9 +45,36 @@ pub enum GR64 {
     R15,
 }
 
+pub enum XMM {
+    XMM0,
+    XMM1,
+    XMM2,
+    XMM3,
+    XMM4,
+    XMM5,
+    XMM6,
+    XMM7,
+    XMM8,
+    XMM9,
+    XMM10,
+    XMM11,
+    XMM12,
+    XMM13,
+    XMM14,
+    XMM15,
+}
+
+/// The classes of registers. Integers narrower than 32 bits are held in
+/// `GR32` registers; `GR8` and `GR16` only name the low parts of registers
+/// that instructions of those sizes access, and are never allocated.
+/// `float` and `double` are held in the low parts of `XMM` registers, and
+/// vectors in whole ones.
 pub enum RegClass {
     GR32,
     GR64,
+    GR8,
+    GR16,
+    XMM,
 }
 
 impl From<GR32> for Reg {
@@ -62,6 +89,12 @@ impl From<GR64> for Reg {
     }
 }
 
+impl From<XMM> for Reg {
+    fn from(r: XMM) -> Self {
+        Reg(RegClass::XMM as u16, r as u16)
+    }
+}
+
 impl From<GR32> for RegUnit {
     fn from(r: GR32) -> Self {
         RegUnit(RegClass::GR64 as u16, r as u16)
@@ -74,6 +107,12 @@ impl From<GR64> for RegUnit {
     }
 }
 
+impl From<XMM> for RegUnit {
+    fn from(r: XMM) -> Self {
+        RegUnit(RegClass::XMM as u16, r as u16)
+    }
+}
+
 const ARG_REGS: [RegUnit; 6] = [
     RegUnit(RegClass::GR64 as u16, GR64::RDI as u16),
     RegUnit(RegClass::GR64 as u16, GR64::RSI as u16),
@@ -83,44 +122,79 @@ const ARG_REGS: [RegUnit; 6] = [
     RegUnit(RegClass::GR64 as u16, GR64::R9 as u16),
 ];
 
+/// The registers `float` and `double` arguments are passed in, counted apart
+/// from those of integers.
+pub const FP_ARG_REGS: [XMM; 8] = [
+    XMM::XMM0,
+    XMM::XMM1,
+    XMM::XMM2,
+    XMM::XMM3,
+    XMM::XMM4,
+    XMM::XMM5,
+    XMM::XMM6,
+    XMM::XMM7,
+];
+
 impl RegisterInfo for RegInfo {
     fn arg_reg_list(cc: &CallConvKind) -> &'static [RegUnit] {
         match cc {
             CallConvKind::SystemV => &ARG_REGS,
+            CallConvKind::AAPCS64 => panic!("AAPCS64 is not a calling convention of x86-64"),
+            CallConvKind::Wasm => panic!("Wasm is not a calling convention of x86-64"),
+            CallConvKind::Cdecl => panic!("cdecl is not a calling convention of x86-64"),
         }
     }
 
     fn to_reg_unit(r: Reg) -> RegUnit {
-        match r {
-            Reg(/*GR32*/ 0, x) => RegUnit(RegClass::GR64 as u16, x),
-            Reg(/*GR64*/ 1, x) => RegUnit(RegClass::GR64 as u16, x),
-            _ => panic!(),
-        }
+        to_reg_unit(r)
     }
 }
 
 impl RegisterClass for RegClass {
     fn for_type(types: &Types, ty: Type) -> Self {
         match ty {
-            types::I32 => RegClass::GR32,
+            types::I1 | types::I8 | types::I16 | types::I32 => RegClass::GR32,
             types::I64 => RegClass::GR64,
+            types::FLOAT | types::DOUBLE => RegClass::XMM,
             _ if ty.is_pointer(types) => RegClass::GR64,
+            _ if ty.is_vector(types) => RegClass::XMM,
             _ => todo!(),
         }
     }
 
     fn gpr_list(&self) -> Vec<Reg> {
         match self {
-            // TODO: Add more general-purpose registers
-            RegClass::GR32 => vec![GR32::EAX, GR32::ECX, GR32::EDX]
-                .into_iter()
-                .map(|r| r.into())
-                .collect(),
-            // TODO: Add more general-purpose registers
-            RegClass::GR64 => vec![GR64::RAX, GR64::RCX, GR64::RDX]
-                .into_iter()
-                .map(|r| r.into())
-                .collect(),
+            // The caller-saved registers, but `r11`, which the large code
+            // model calls through. TODO: Add callee-saved registers
+            RegClass::GR32 => vec![
+                GR32::EAX,
+                GR32::ECX,
+                GR32::EDX,
+                GR32::ESI,
+                GR32::EDI,
+                GR32::R8D,
+                GR32::R9D,
+                GR32::R10D,
+            ]
+            .into_iter()
+            .map(|r| r.into())
+            .collect(),
+            RegClass::GR64 => vec![
+                GR64::RAX,
+                GR64::RCX,
+                GR64::RDX,
+                GR64::RSI,
+                GR64::RDI,
+                GR64::R8,
+                GR64::R9,
+                GR64::R10,
+            ]
+            .into_iter()
+            .map(|r| r.into())
+            .collect(),
+            RegClass::GR8 | RegClass::GR16 => vec![],
+            // All of them are caller-saved.
+            RegClass::XMM => (0..16).map(|i| Reg(RegClass::XMM as u16, i)).collect(),
         }
     }
 
@@ -128,18 +202,43 @@ impl RegisterClass for RegClass {
         match self {
             Self::GR32 => Reg(RegClass::GR32 as u16, ru.1),
             Self::GR64 => Reg(RegClass::GR64 as u16, ru.1),
+            Self::GR8 => Reg(RegClass::GR8 as u16, ru.1),
+            Self::GR16 => Reg(RegClass::GR16 as u16, ru.1),
+            Self::XMM => Reg(RegClass::XMM as u16, ru.1),
         }
     }
 }
 
 pub fn to_reg_unit(r: Reg) -> RegUnit {
     match r {
-        Reg(/*GR32*/ 0, x) => RegUnit(1, x),
-        Reg(/*GR64*/ 1, x) => RegUnit(1, x),
+        Reg(/*GR32, GR64, GR8 or GR16*/ 0..=3, x) => RegUnit(RegClass::GR64 as u16, x),
+        Reg(/*XMM*/ 4, x) => RegUnit(RegClass::XMM as u16, x),
         _ => panic!(),
     }
 }
 
+/// Returns the part of `r` of `size` bytes, e.g. `al` of `eax` for 1.
+pub fn sub_reg(r: Reg, size: u32) -> Reg {
+    let class = match size {
+        1 => RegClass::GR8,
+        2 => RegClass::GR16,
+        4 => RegClass::GR32,
+        _ => RegClass::GR64,
+    };
+    Reg(class as u16, r.1)
+}
+
+/// Returns the size in bytes of the registers of the class of `r`.
+pub fn reg_size(r: Reg) -> u32 {
+    match r.0 {
+        /*GR32*/ 0 => 4,
+        /*GR64*/ 1 => 8,
+        /*GR8*/ 2 => 1,
+        /*GR16*/ 3 => 2,
+        _ => 16,
+    }
+}
+
 impl fmt::Debug for GR64 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -187,7 +286,7 @@ impl fmt::Debug for GR32 {
                 Self::EBP => "ebp",
                 Self::ESI => "esi",
                 Self::EDI => "edi",
-                Self::R8D => "r8",
+                Self::R8D => "r8d",
                 Self::R9D => "r9d",
                 Self::R10D => "r10d",
                 Self::R11D => "r11d",
@@ -208,16 +307,31 @@ impl fmt::Display for GR32 {
 
 pub fn reg_to_str(r: &Reg) -> &'static str {
     let gr32 = [
-        "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8", "r9d", "r10d", "r11d",
+        "eax", "ecx", "edx", "ebx", "esp", "ebp", "esi", "edi", "r8d", "r9d", "r10d", "r11d",
         "r12d", "r13d", "r14d", "r15d",
     ];
     let gr64 = [
         "rax", "rcx", "rdx", "rbx", "rsp", "rbp", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12",
         "r13", "r14", "r15",
     ];
+    let gr8 = [
+        "al", "cl", "dl", "bl", "spl", "bpl", "sil", "dil", "r8b", "r9b", "r10b", "r11b", "r12b",
+        "r13b", "r14b", "r15b",
+    ];
+    let gr16 = [
+        "ax", "cx", "dx", "bx", "sp", "bp", "si", "di", "r8w", "r9w", "r10w", "r11w", "r12w",
+        "r13w", "r14w", "r15w",
+    ];
+    let xmm = [
+        "xmm0", "xmm1", "xmm2", "xmm3", "xmm4", "xmm5", "xmm6", "xmm7", "xmm8", "xmm9", "xmm10",
+        "xmm11", "xmm12", "xmm13", "xmm14", "xmm15",
+    ];
     match r {
         Reg(0, i) => gr32[*i as usize],
         Reg(1, i) => gr64[*i as usize],
+        Reg(2, i) => gr8[*i as usize],
+        Reg(3, i) => gr16[*i as usize],
+        Reg(4, i) => xmm[*i as usize],
         e => todo!("{:?}", e),
     }
 }
@@ -0,0 +1,555 @@
+//! Running the code of an [`ObjectModule`] in the host process.
+//!
+//! Functions are linked on demand, i.e. their code is copied to executable
+//! memory and their relocations applied on their first call. Each function
+//! gets a stub up front, jumping through a slot to the function once linked,
+//! and until then to a lazy entry that links it before jumping to it:
+//!
+//! ```text
+//! stub:  jmp [rip + slot]    ; slot: the lazy entry, then the function
+//! lazy:  push index
+//!        jmp resolver        ; saves the argument registers, links, and
+//!                            ; jumps to the function
+//! ```
+//!
+//! Calls to and addresses of the functions of the module refer to their
+//! stubs, so linking a function leaves its callees to be linked when called
+//! in turn. Global variables are laid out up front and relocated before the
+//! first function is linked.
+//!
+//! Symbols the module does not define are resolved, in order, to those
+//! registered by [`Jit::with_symbol`], e.g. Rust functions, to those of the
+//! libraries loaded by [`Jit::with_lib`], and to those of the host process as
+//! `dlsym` finds them. As they may be out of the range of 32-bit offsets,
+//! calls to them go through stubs of their own and loads of their addresses
+//! through the GOT, which code compiled with
+//! [`RelocModel::Pic`](super::options::RelocModel::Pic) does for data. Other
+//! references to them fail with [`JitError::OutOfRange`].
+//!
+//! The code and data of a [`Jit`] live in a single reservation of
+//! [`ARENA_SIZE`] bytes, within the range of 32-bit offsets of each other.
+//! Code is not to be run by several threads at once, as linking a function
+//! makes the memory around it writable for a moment.
+
+use super::{
+    code_cache::{CompiledFunction, RelocKind, Relocation},
+    isa::x86_64::{encode::encode_module, X86_64},
+    module::Module,
+    object::{DataObject, ObjectModule},
+};
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use std::{cell::RefCell, error::Error, ffi, fmt, io, ptr};
+
+/// The size of the address space reserved by each [`Jit`].
+pub const ARENA_SIZE: usize = 1 << 28;
+
+/// The size of the stub and lazy entry of each function.
+const STUB_SIZE: usize = 16;
+
+/// The size of the stub of each external function.
+const EXTERN_STUB_SIZE: usize = 8;
+
+/// The size of the argument registers saved by the resolver on the stack:
+/// `xmm0` to `xmm7` and 8 bytes keeping the stack aligned.
+const SAVED_XMM_SIZE: i32 = 8 * 16 + 8;
+
+/// The size of the integer registers saved by the resolver on the stack:
+/// those holding arguments, and `rax`, holding the number of vector
+/// registers used by variadic calls.
+const SAVED_GPR_SIZE: i32 = 7 * 8;
+
+pub struct Jit {
+    /// Boxed for the resolver to refer to.
+    linker: Box<RefCell<Linker>>,
+}
+
+#[derive(Debug)]
+pub enum JitError {
+    /// The memory of the JIT could not be mapped or protected.
+    Memory(io::Error),
+    /// The arena of [`ARENA_SIZE`] bytes is full.
+    OutOfMemory,
+    /// No definition of the symbol was found.
+    UndefinedSymbol(String),
+    /// The symbol is too far away from its reference.
+    OutOfRange(String),
+}
+
+struct Linker {
+    arena: Arena,
+    code: Space,
+    data: Space,
+    functions: Vec<CompiledFunction>,
+    data_objects: Vec<DataObject>,
+    /// The symbol table the relocations of `functions` and `data_objects`
+    /// refer to.
+    symbols: Vec<String>,
+    /// The index of each function in `functions`, by name.
+    indices: FxHashMap<String, usize>,
+    /// The stub of each function in `functions`.
+    stubs: Vec<usize>,
+    /// The slot each stub jumps through.
+    slots: Vec<usize>,
+    /// The code of each function in `functions`, once linked.
+    linked: Vec<Option<usize>>,
+    /// The address of each data object, by name.
+    data_addrs: FxHashMap<String, usize>,
+    data_relocated: bool,
+    /// The stubs of external functions, by name.
+    extern_stubs: FxHashMap<String, usize>,
+    /// The GOT entries, by name.
+    got: FxHashMap<String, usize>,
+    registered: FxHashMap<String, usize>,
+    libs: Vec<libloading::Library>,
+    host: libloading::os::unix::Library,
+}
+
+/// Address space reserved for a [`Jit`], handed out page by page.
+struct Arena {
+    base: *mut u8,
+    used: usize,
+    page_size: usize,
+}
+
+/// Memory of the same protection, handed out from pages of an [`Arena`].
+struct Space {
+    exec: bool,
+    next: usize,
+    end: usize,
+}
+
+impl Jit {
+    /// Lays out the functions and data of `obj`, none of which is linked yet.
+    pub fn new(obj: ObjectModule) -> Result<Self> {
+        let mut arena = Arena::new()?;
+        let mut code = Space::new(true);
+        let mut data = Space::new(false);
+
+        let functions: Vec<_> = obj.functions().map(|(func, _)| func.clone()).collect();
+        let indices = functions
+            .iter()
+            .enumerate()
+            .map(|(i, func)| (func.name.clone(), i))
+            .collect();
+        let mut data_addrs = FxHashMap::default();
+        for object in obj.data() {
+            let at = data.alloc(&mut arena, object.bytes.len(), object.align as usize)?;
+            data_addrs.insert(object.name.clone(), at);
+        }
+        let slots = (0..functions.len())
+            .map(|_| data.alloc(&mut arena, 8, 8))
+            .collect::<Result<_>>()?;
+        let resolver = resolver_code(0, 0).len();
+        let start = code.alloc(
+            &mut arena,
+            resolver + STUB_SIZE * functions.len(),
+            STUB_SIZE,
+        )?;
+        let stubs = (0..functions.len())
+            .map(|i| start + resolver + STUB_SIZE * i)
+            .collect();
+
+        let linked = vec![None; functions.len()];
+        let linker = Box::new(RefCell::new(Linker {
+            arena,
+            code,
+            data,
+            functions,
+            data_objects: obj.data().to_vec(),
+            symbols: obj.symbols().to_vec(),
+            indices,
+            stubs,
+            slots,
+            linked,
+            data_addrs,
+            data_relocated: false,
+            extern_stubs: FxHashMap::default(),
+            got: FxHashMap::default(),
+            registered: FxHashMap::default(),
+            libs: vec![],
+            host: libloading::os::unix::Library::this(),
+        }));
+        linker
+            .borrow_mut()
+            .write_stubs(start, &*linker as *const _ as u64)?;
+        Ok(Self { linker })
+    }
+
+    /// Encodes `module` and lays it out as [`Jit::new`] does.
+    pub fn from_module(module: &Module<X86_64>) -> Result<Self> {
+        Self::new(encode_module(module)?)
+    }
+
+    /// Resolves the symbol `name`, if not defined by the module, to `addr`,
+    /// e.g. that of an `extern "C"` Rust function.
+    pub fn with_symbol<N: Into<String>>(self, name: N, addr: *const u8) -> Self {
+        self.linker
+            .borrow_mut()
+            .registered
+            .insert(name.into(), addr as usize);
+        self
+    }
+
+    /// Resolves the symbols not defined by the module nor registered to
+    /// those of the library `lib`, before those of the host process.
+    pub fn with_lib<T: AsRef<ffi::OsStr>>(self, lib: T) -> Result<Self> {
+        let lib = unsafe { libloading::Library::new(lib)? };
+        self.linker.borrow_mut().libs.push(lib);
+        Ok(self)
+    }
+
+    /// Returns the address to call the function `name` of the module at,
+    /// that of its stub. It is linked on its first call.
+    pub fn get_function(&self, name: &str) -> Option<*const u8> {
+        let linker = self.linker.borrow();
+        let index = *linker.indices.get(name)?;
+        Some(linker.stubs[index] as *const u8)
+    }
+
+    /// Links the function `name` of the module now, rather than on its first
+    /// call, and returns the address of its code. Its callees are still
+    /// linked when called.
+    pub fn link_function(&self, name: &str) -> Result<*const u8> {
+        let mut linker = self.linker.borrow_mut();
+        let index = *linker
+            .indices
+            .get(name)
+            .ok_or_else(|| JitError::UndefinedSymbol(name.to_owned()))?;
+        Ok(linker.link(index)? as *const u8)
+    }
+
+    /// Returns true if the function `name` of the module has been linked.
+    pub fn is_linked(&self, name: &str) -> bool {
+        let linker = self.linker.borrow();
+        linker
+            .indices
+            .get(name)
+            .is_some_and(|&index| linker.linked[index].is_some())
+    }
+}
+
+impl Linker {
+    /// Writes the resolver at `start`, followed by the stubs, and points the
+    /// slots to the lazy entries.
+    fn write_stubs(&mut self, start: usize, linker: u64) -> Result<()> {
+        let mut code = resolver_code(linker, lazy_link as *const () as u64);
+        let resolver = start;
+        for (i, (&stub, &slot)) in self.stubs.iter().zip(self.slots.iter()).enumerate() {
+            let lazy = stub + 6;
+            let mut bytes = vec![0xff, 0x25];
+            bytes.extend_from_slice(&rel32(slot, lazy)?.to_le_bytes());
+            bytes.push(0x68);
+            bytes.extend_from_slice(&(i as u32).to_le_bytes());
+            bytes.push(0xe9);
+            bytes.extend_from_slice(&rel32(resolver, lazy + 10)?.to_le_bytes());
+            bytes.resize(STUB_SIZE, 0xcc);
+            code.extend_from_slice(&bytes);
+            unsafe { ptr::write(slot as *mut u64, lazy as u64) };
+        }
+        self.arena.write(&self.code, start, &code)
+    }
+
+    /// Links the function of `index`, if not yet, and returns the address of
+    /// its code.
+    fn link(&mut self, index: usize) -> Result<usize> {
+        if let Some(at) = self.linked[index] {
+            return Ok(at);
+        }
+        self.relocate_data()?;
+        let func = self.functions[index].clone();
+        let at = self.code.alloc(&mut self.arena, func.code.len(), 16)?;
+        let mut code = func.code;
+        for reloc in &func.relocs {
+            self.relocate(&mut code, at, reloc)?;
+        }
+        self.arena.write(&self.code, at, &code)?;
+        self.linked[index] = Some(at);
+        unsafe { ptr::write(self.slots[index] as *mut u64, at as u64) };
+        Ok(at)
+    }
+
+    /// Writes the data objects, relocated, to their addresses. Done once, as
+    /// late as possible for symbols to be registered.
+    fn relocate_data(&mut self) -> Result<()> {
+        if self.data_relocated {
+            return Ok(());
+        }
+        for object in std::mem::take(&mut self.data_objects) {
+            let at = self.data_addrs[&object.name];
+            let mut bytes = object.bytes;
+            for reloc in &object.relocs {
+                self.relocate(&mut bytes, at, reloc)?;
+            }
+            self.arena.write(&self.data, at, &bytes)?;
+        }
+        self.data_relocated = true;
+        Ok(())
+    }
+
+    /// Applies `reloc` to `bytes`, to be placed at `at`.
+    fn relocate(&mut self, bytes: &mut [u8], at: usize, reloc: &Relocation) -> Result<()> {
+        let name = self.symbols[reloc.symbol as usize].clone();
+        let offset = reloc.offset as usize;
+        let place = at + offset;
+        let target = match reloc.kind {
+            RelocKind::Abs8 => {
+                let value = (self.address(&name)? as i64).wrapping_add(reloc.addend);
+                bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+                return Ok(());
+            }
+            RelocKind::PcRel4 => self.address(&name)?,
+            RelocKind::Call4 => self.call_target(&name)?,
+            RelocKind::GotPcRel4 => self.got_entry(&name)?,
+        };
+        let value = (target as i64)
+            .wrapping_add(reloc.addend)
+            .wrapping_sub(place as i64);
+        let value = i32::try_from(value).map_err(|_| JitError::OutOfRange(name))?;
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        Ok(())
+    }
+
+    /// Returns the address of the symbol `name`, the stub of a function of the
+    /// module.
+    fn address(&mut self, name: &str) -> Result<usize> {
+        if let Some(&index) = self.indices.get(name) {
+            return Ok(self.stubs[index]);
+        }
+        if let Some(&at) = self.data_addrs.get(name) {
+            return Ok(at);
+        }
+        self.resolve_external(name)
+    }
+
+    /// Returns the address calls to the function `name` go to, within the
+    /// range of 32-bit offsets.
+    fn call_target(&mut self, name: &str) -> Result<usize> {
+        if self.indices.contains_key(name) || self.data_addrs.contains_key(name) {
+            return self.address(name);
+        }
+        if let Some(&stub) = self.extern_stubs.get(name) {
+            return Ok(stub);
+        }
+        let slot = self.got_entry(name)?;
+        let stub = self
+            .code
+            .alloc(&mut self.arena, EXTERN_STUB_SIZE, EXTERN_STUB_SIZE)?;
+        let mut bytes = vec![0xff, 0x25];
+        bytes.extend_from_slice(&rel32(slot, stub + 6)?.to_le_bytes());
+        bytes.resize(EXTERN_STUB_SIZE, 0xcc);
+        self.arena.write(&self.code, stub, &bytes)?;
+        self.extern_stubs.insert(name.to_owned(), stub);
+        Ok(stub)
+    }
+
+    /// Returns the GOT entry holding the address of the symbol `name`.
+    fn got_entry(&mut self, name: &str) -> Result<usize> {
+        if let Some(&entry) = self.got.get(name) {
+            return Ok(entry);
+        }
+        let addr = self.address(name)?;
+        let entry = self.data.alloc(&mut self.arena, 8, 8)?;
+        unsafe { ptr::write(entry as *mut u64, addr as u64) };
+        self.got.insert(name.to_owned(), entry);
+        Ok(entry)
+    }
+
+    /// Returns the address of the symbol `name` defined outside the module.
+    fn resolve_external(&self, name: &str) -> Result<usize> {
+        if let Some(&addr) = self.registered.get(name) {
+            return Ok(addr);
+        }
+        for lib in &self.libs {
+            if let Ok(sym) = unsafe { lib.get::<*const u8>(name.as_bytes()) } {
+                return Ok(*sym as usize);
+            }
+        }
+        match unsafe { self.host.get::<*const u8>(name.as_bytes()) } {
+            Ok(sym) => Ok(*sym as usize),
+            Err(_) => Err(JitError::UndefinedSymbol(name.to_owned()).into()),
+        }
+    }
+}
+
+/// Called by the resolver on the first call to the function of `index`.
+/// Errors cannot be returned to the caller, which is generated code, and
+/// abort the process.
+extern "C" fn lazy_link(linker: *const RefCell<Linker>, index: usize) -> usize {
+    let linker = unsafe { &*linker };
+    let result = linker.borrow_mut().link(index);
+    match result {
+        Ok(at) => at,
+        Err(e) => panic!("cannot link function #{}: {}", index, e),
+    }
+}
+
+/// Returns the code of the resolver, entered by the lazy entry of a function
+/// with its index pushed on the stack. It saves the registers arguments may
+/// be passed in, calls `lazy_link` with `linker` and the index, restores the
+/// registers, pops the index and jumps to the function.
+fn resolver_code(linker: u64, lazy_link: u64) -> Vec<u8> {
+    const GPRS: [&[u8]; 7] = [
+        &[0x57],       // rdi
+        &[0x56],       // rsi
+        &[0x52],       // rdx
+        &[0x51],       // rcx
+        &[0x41, 0x50], // r8
+        &[0x41, 0x51], // r9
+        &[0x50],       // rax
+    ];
+    let mut code = vec![];
+    for push in GPRS {
+        code.extend_from_slice(push);
+    }
+    // sub rsp, SAVED_XMM_SIZE
+    code.extend_from_slice(&[0x48, 0x81, 0xec]);
+    code.extend_from_slice(&SAVED_XMM_SIZE.to_le_bytes());
+    for i in 0..8u8 {
+        // movdqu [rsp + 16 * i], xmm{i}
+        code.extend_from_slice(&[0xf3, 0x0f, 0x7f, 0x44 | (i << 3), 0x24, 16 * i]);
+    }
+    // mov rdi, linker
+    code.extend_from_slice(&[0x48, 0xbf]);
+    code.extend_from_slice(&linker.to_le_bytes());
+    // mov rsi, [rsp + SAVED_XMM_SIZE + SAVED_GPR_SIZE]
+    code.extend_from_slice(&[0x48, 0x8b, 0xb4, 0x24]);
+    code.extend_from_slice(&(SAVED_XMM_SIZE + SAVED_GPR_SIZE).to_le_bytes());
+    // mov rax, lazy_link; call rax; mov r11, rax
+    code.extend_from_slice(&[0x48, 0xb8]);
+    code.extend_from_slice(&lazy_link.to_le_bytes());
+    code.extend_from_slice(&[0xff, 0xd0, 0x49, 0x89, 0xc3]);
+    for i in 0..8u8 {
+        // movdqu xmm{i}, [rsp + 16 * i]
+        code.extend_from_slice(&[0xf3, 0x0f, 0x6f, 0x44 | (i << 3), 0x24, 16 * i]);
+    }
+    // add rsp, SAVED_XMM_SIZE
+    code.extend_from_slice(&[0x48, 0x81, 0xc4]);
+    code.extend_from_slice(&SAVED_XMM_SIZE.to_le_bytes());
+    for push in GPRS.iter().rev() {
+        // The pop of each register is its push plus 8.
+        let (last, rex) = push.split_last().unwrap();
+        code.extend_from_slice(rex);
+        code.push(last + 8);
+    }
+    // add rsp, 8 (the index); jmp r11
+    code.extend_from_slice(&[0x48, 0x83, 0xc4, 0x08, 0x41, 0xff, 0xe3]);
+    code.resize(code.len().div_ceil(STUB_SIZE) * STUB_SIZE, 0xcc);
+    code
+}
+
+/// Returns the offset of `target` from `next`, the end of the instruction
+/// referring to it.
+fn rel32(target: usize, next: usize) -> Result<i32> {
+    i32::try_from(target as i64 - next as i64)
+        .map_err(|_| JitError::OutOfRange(format!("{:#x}", target)).into())
+}
+
+impl Arena {
+    fn new() -> Result<Self> {
+        let base = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                ARENA_SIZE,
+                libc::PROT_NONE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_NORESERVE,
+                -1,
+                0,
+            )
+        };
+        if base == libc::MAP_FAILED {
+            return Err(JitError::Memory(io::Error::last_os_error()).into());
+        }
+        Ok(Self {
+            base: base as *mut u8,
+            used: 0,
+            page_size: unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize,
+        })
+    }
+
+    /// Returns the address of `size` bytes of new pages, readable and
+    /// writable.
+    fn alloc_pages(&mut self, size: usize) -> Result<usize> {
+        let size = self.page_ceil(size);
+        if self.used + size > ARENA_SIZE {
+            return Err(JitError::OutOfMemory.into());
+        }
+        let at = self.base as usize + self.used;
+        self.protect(at, size, libc::PROT_READ | libc::PROT_WRITE)?;
+        self.used += size;
+        Ok(at)
+    }
+
+    /// Writes `bytes` at `at` in `space`, making the pages of code writable
+    /// meanwhile.
+    fn write(&self, space: &Space, at: usize, bytes: &[u8]) -> Result<()> {
+        let start = at / self.page_size * self.page_size;
+        let size = self.page_ceil(at + bytes.len()) - start;
+        if space.exec {
+            self.protect(start, size, libc::PROT_READ | libc::PROT_WRITE)?;
+        }
+        unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), at as *mut u8, bytes.len()) };
+        if space.exec {
+            self.protect(start, size, libc::PROT_READ | libc::PROT_EXEC)?;
+        }
+        Ok(())
+    }
+
+    fn protect(&self, at: usize, size: usize, prot: libc::c_int) -> Result<()> {
+        if unsafe { libc::mprotect(at as *mut libc::c_void, size, prot) } != 0 {
+            return Err(JitError::Memory(io::Error::last_os_error()).into());
+        }
+        Ok(())
+    }
+
+    fn page_ceil(&self, size: usize) -> usize {
+        size.div_ceil(self.page_size) * self.page_size
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.base as *mut libc::c_void, ARENA_SIZE) };
+    }
+}
+
+impl Space {
+    fn new(exec: bool) -> Self {
+        Self {
+            exec,
+            next: 0,
+            end: 0,
+        }
+    }
+
+    /// Returns the address of `size` bytes aligned to `align`, taking new
+    /// pages from `arena` if needed.
+    fn alloc(&mut self, arena: &mut Arena, size: usize, align: usize) -> Result<usize> {
+        let align = align.max(1);
+        let at = self.next.div_ceil(align) * align;
+        if self.end != 0 && at + size <= self.end {
+            self.next = at + size;
+            return Ok(at);
+        }
+        let at = arena.alloc_pages(size)?;
+        self.next = at + size;
+        self.end = at + arena.page_ceil(size);
+        if self.exec {
+            arena.protect(at, self.end - at, libc::PROT_READ | libc::PROT_EXEC)?;
+        }
+        Ok(at)
+    }
+}
+
+impl Error for JitError {}
+
+impl fmt::Display for JitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory(e) => write!(f, "cannot map memory: {}", e),
+            Self::OutOfMemory => write!(f, "the JIT arena is full"),
+            Self::UndefinedSymbol(name) => write!(f, "undefined symbol {}", name),
+            Self::OutOfRange(name) => write!(f, "symbol {} is out of range", name),
+        }
+    }
+}
@@ -0,0 +1,271 @@
+//! Target-independent expansions.
+//!
+//! Some operations are lowered the same way on every target, in terms of
+//! simpler IR. The expansions here rewrite an IR function before instruction
+//! selection, so that a target only selects what is specific to it. They are
+//! parameterized by [`ExpandHooks`], which every [`TargetIsa`] implements.
+//!
+//! - Calls to `llvm.memcpy`, `llvm.memmove` and `llvm.memset` of a constant
+//!   length of at most [`ExpandHooks::max_inline_bytes`] become loads and
+//!   stores of the widest integers the target accesses at once.
+//! - Arguments passed `byval` to functions with `internal` or `private`
+//!   linkage defined in the module are copied to the stack of the caller, and
+//!   the copy is passed by pointer instead. Every caller of those functions is
+//!   compiled here, so they need not follow the ABI of the target. Other
+//!   callees expect the copy in the stack argument area, which is left to the
+//!   target.
+//!
+//! Integers wider than a register are left to type legalization, and the IR
+//! has no `select` to expand into branches yet.
+//!
+//! Drivers run [`run_on_module`] on the IR before
+//! [`compile_module`](super::compile_module).
+//!
+//! [`TargetIsa`]: crate::codegen::isa::TargetIsa
+
+use rustc_hash::FxHashSet;
+use vicis_core::ir::{
+    function::{
+        builder::Builder,
+        instruction::{Call, InstructionId, Opcode, Operand},
+        param_attrs::ParameterAttribute,
+        Function,
+    },
+    module::{linkage::Linkage, name::Name, Module},
+    types::{Type, Types, I32, I64, I8},
+    value::{ConstantData, ConstantInt, Value, ValueId},
+};
+
+/// What a target tells the expansions about itself.
+pub trait ExpandHooks {
+    /// Returns the size of `ty` in bytes.
+    fn size_of(&self, types: &Types, ty: Type) -> u32;
+
+    /// Returns the width in bits of the widest integer loaded or stored by
+    /// a single instruction.
+    fn max_copy_bits(&self) -> u32 {
+        64
+    }
+
+    /// Returns the longest copy or fill, in bytes, expanded inline. Longer
+    /// ones are left as calls.
+    fn max_inline_bytes(&self) -> u64 {
+        128
+    }
+}
+
+/// What a call to a memory intrinsic does.
+enum MemOp {
+    /// `memcpy` or `memmove` from the second argument.
+    Copy(ValueId),
+    /// `memset` to the byte.
+    Fill(i8),
+}
+
+/// Expands the operations of the functions in `module` and returns how many
+/// were expanded.
+pub fn run_on_module<H: ExpandHooks>(module: &mut Module, hooks: &H) -> usize {
+    let local: FxHashSet<Name> = module
+        .functions()
+        .iter()
+        .filter(|(_, func)| {
+            !func.is_prototype() && matches!(func.linkage, Linkage::Internal | Linkage::Private)
+        })
+        .map(|(_, func)| Name::from(func.name.as_str()))
+        .collect();
+    module
+        .functions_mut()
+        .iter_mut()
+        .map(|(_, func)| expand(func, hooks, &local))
+        .sum()
+}
+
+/// Expands the operations of `func` and returns how many were expanded.
+/// Arguments passed `byval` are left alone, as the callees are unknown
+/// without the module.
+pub fn run_on_function<H: ExpandHooks>(func: &mut Function, hooks: &H) -> usize {
+    expand(func, hooks, &FxHashSet::default())
+}
+
+/// Expands the operations of `func`, copying the `byval` arguments of calls
+/// to the functions in `local`.
+fn expand<H: ExpandHooks>(func: &mut Function, hooks: &H, local: &FxHashSet<Name>) -> usize {
+    if func.is_prototype() {
+        return 0;
+    }
+
+    let calls: Vec<InstructionId> = func
+        .layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .filter(|&inst| func.data.inst_ref(inst).opcode.is_call())
+        .collect();
+    let mut expanded = 0;
+    for call in calls {
+        expanded += expand_byval_args(func, call, hooks, local);
+        if expand_mem_intrinsic(func, call, hooks) {
+            expanded += 1;
+        }
+    }
+    expanded
+}
+
+fn expand_mem_intrinsic<H: ExpandHooks>(
+    func: &mut Function,
+    call: InstructionId,
+    hooks: &H,
+) -> bool {
+    let Operand::Call(Call { args, .. }) = &func.data.inst_ref(call).operand else {
+        return false;
+    };
+    let Value::Constant(ConstantData::GlobalRef(Name::Name(callee))) = func.data.value_ref(args[0])
+    else {
+        return false;
+    };
+    let int_arg = |i: usize| match func.data.value_ref(args[i]) {
+        Value::Constant(ConstantData::Int(c)) => Some(c.cast_to_i64()),
+        _ => None,
+    };
+    let op = if callee.starts_with("llvm.memcpy.") || callee.starts_with("llvm.memmove.") {
+        MemOp::Copy(args[2])
+    } else if callee.starts_with("llvm.memset.") {
+        match int_arg(2) {
+            Some(byte) => MemOp::Fill(byte as i8),
+            None => return false,
+        }
+    } else {
+        return false;
+    };
+    let dst = args[1];
+    let Some(len) = int_arg(3).filter(|&len| len >= 0 && len as u64 <= hooks.max_inline_bytes())
+    else {
+        return false;
+    };
+
+    let mut builder = Builder::new(func);
+    builder.position_before(call);
+    match op {
+        MemOp::Copy(src) => copy(&mut builder, dst, src, len as u32, hooks),
+        MemOp::Fill(byte) => fill(&mut builder, dst, byte, len as u32, hooks),
+    }
+    func.erase_instruction(call);
+    true
+}
+
+/// Copies the `byval` arguments of `call` to allocas of the caller, passed
+/// instead, if the callee is in `local`. Returns how many were copied.
+fn expand_byval_args<H: ExpandHooks>(
+    func: &mut Function,
+    call: InstructionId,
+    hooks: &H,
+    local: &FxHashSet<Name>,
+) -> usize {
+    let Operand::Call(Call {
+        args,
+        tys,
+        param_attrs,
+        ..
+    }) = &func.data.inst_ref(call).operand
+    else {
+        return 0;
+    };
+    match func.data.value_ref(args[0]) {
+        Value::Constant(ConstantData::GlobalRef(callee)) if local.contains(callee) => {}
+        _ => return 0,
+    }
+    let byvals: Vec<(usize, ValueId, Type)> = param_attrs
+        .iter()
+        .enumerate()
+        .filter(|(_, attrs)| attrs.contains(&ParameterAttribute::ByVal))
+        .map(|(i, _)| (i, args[i + 1], tys[i + 1]))
+        .collect();
+
+    let entry = func.entry_block().unwrap();
+    let i8_ptr = func.types.ptr_to(I8);
+    for &(i, arg, ptr_ty) in &byvals {
+        let ty = func.types.get_element(ptr_ty).unwrap();
+        let size = hooks.size_of(&func.types, ty);
+        let mut builder = Builder::new(func);
+        builder.position_at_start(entry);
+        let slot = builder.build_alloca(ty);
+        builder.position_before(call);
+        let dst = builder.build_cast(Opcode::Bitcast, ptr_ty, i8_ptr, slot);
+        let src = builder.build_cast(Opcode::Bitcast, ptr_ty, i8_ptr, arg);
+        copy(&mut builder, dst, src, size, hooks);
+
+        func.data.remove_uses(call);
+        let Operand::Call(Call {
+            args, param_attrs, ..
+        }) = &mut func.data.inst_ref_mut(call).operand
+        else {
+            unreachable!()
+        };
+        args[i + 1] = slot;
+        param_attrs[i].retain(|attr| attr != &ParameterAttribute::ByVal);
+        func.data.validate_inst_uses(call);
+    }
+    byvals.len()
+}
+
+/// Copies `len` bytes from the `i8*` `src` to the `i8*` `dst`. Everything is
+/// loaded before anything is stored, so the copy may overlap.
+fn copy<H: ExpandHooks>(builder: &mut Builder, dst: ValueId, src: ValueId, len: u32, hooks: &H) {
+    let vals: Vec<(Type, u32, ValueId)> = chunks(len, hooks)
+        .into_iter()
+        .map(|(ty, offset)| {
+            let addr = address(builder, src, ty, offset);
+            (ty, offset, builder.build_load(ty, addr))
+        })
+        .collect();
+    for (ty, offset, val) in vals {
+        let addr = address(builder, dst, ty, offset);
+        builder.build_store(ty, val, addr);
+    }
+}
+
+/// Sets `len` bytes from the `i8*` `dst` to `byte`.
+fn fill<H: ExpandHooks>(builder: &mut Builder, dst: ValueId, byte: i8, len: u32, hooks: &H) {
+    let splat = (byte as u8 as u64).wrapping_mul(0x0101_0101_0101_0101);
+    for (ty, offset) in chunks(len, hooks) {
+        let val = match ty {
+            I64 => ConstantInt::Int64(splat as i64),
+            I32 => ConstantInt::Int32(splat as i32),
+            _ => ConstantInt::Int8(byte),
+        };
+        let val = builder.value(val);
+        let addr = address(builder, dst, ty, offset);
+        builder.build_store(ty, val, addr);
+    }
+}
+
+/// Splits `len` bytes into integer types and their offsets, widest first.
+fn chunks<H: ExpandHooks>(len: u32, hooks: &H) -> Vec<(Type, u32)> {
+    let widths = [(I64, 8), (I32, 4), (I8, 1)];
+    let mut chunks = vec![];
+    let mut offset = 0;
+    while offset < len {
+        let &(ty, size) = widths
+            .iter()
+            .find(|&&(_, size)| size * 8 <= hooks.max_copy_bits() && offset + size <= len)
+            .unwrap_or(&(I8, 1));
+        chunks.push((ty, offset));
+        offset += size;
+    }
+    chunks
+}
+
+/// Returns a `ty*` to the `offset`-th byte from the `i8*` `base`.
+fn address(builder: &mut Builder, base: ValueId, ty: Type, offset: u32) -> ValueId {
+    let i8_ptr = builder.func().types.ptr_to(I8);
+    let addr = if offset == 0 {
+        base
+    } else {
+        let offset = builder.value(ConstantInt::Int64(offset as i64));
+        builder.build_gep(true, vec![I8, i8_ptr, I64], vec![base, offset])
+    };
+    if ty == I8 {
+        return addr;
+    }
+    let ptr = builder.func().types.ptr_to(ty);
+    builder.build_cast(Opcode::Bitcast, i8_ptr, ptr, addr)
+}
@@ -1,3 +1,5 @@
+pub mod expand;
+
 use super::{
     call_conv::CallConvKind,
     function::{
@@ -10,6 +12,8 @@ use super::{
     },
     isa::TargetIsa,
     module::Module as MachModule,
+    object::symbol_name,
+    options::CodegenOptions,
     register::VReg,
 };
 use anyhow::Result;
@@ -20,11 +24,12 @@ use vicis_core::ir::{
     function::{
         basic_block::BasicBlockId as IrBasicBlockId,
         data::Data as IrData,
-        instruction::{Instruction as IrInstruction, InstructionId as IrInstructionId, Opcode},
+        instruction::{Instruction as IrInstruction, InstructionId as IrInstructionId},
         Function as IrFunction, Parameter,
     },
-    module::Module as IrModule,
+    module::{comdat::SelectionKind, name::Name, Module as IrModule},
     types::Types,
+    value::{ConstantData, ConstantExpr},
 };
 
 pub trait Lower<T: TargetIsa> {
@@ -37,11 +42,15 @@ pub struct LoweringContext<'a, T: TargetIsa> {
     pub ir_data: &'a IrData,
     pub mach_data: &'a mut Data<<T::InstInfo as II>::Data>,
     pub slots: &'a mut Slots<T>,
+    pub constant_pool: &'a mut Vec<Vec<u8>>,
     pub inst_id_to_slot_id: &'a mut FxHashMap<IrInstructionId, SlotId>,
     pub arg_idx_to_vreg: &'a mut FxHashMap<usize, VReg>,
     pub inst_seq: &'a mut Vec<MachInstruction<<T::InstInfo as II>::Data>>,
     pub types: &'a Types,
     pub inst_id_to_vreg: &'a mut FxHashMap<IrInstructionId, VReg>,
+    /// The registers holding the high halves of values split into two, by
+    /// those holding their low halves.
+    pub high_vregs: &'a mut FxHashMap<VReg, VReg>,
     pub merged_inst: &'a mut FxHashSet<IrInstructionId>,
     pub block_map: &'a FxHashMap<IrBasicBlockId, MachBasicBlockId>,
     pub call_conv: CallConvKind,
@@ -54,12 +63,31 @@ pub enum LoweringError {
 }
 
 pub fn compile_module<T: TargetIsa>(isa: T, module: &IrModule) -> Result<MachModule<T>> {
+    compile_module_with_options(isa, module, &CodegenOptions::default())
+}
+
+pub fn compile_module_with_options<T: TargetIsa>(
+    isa: T,
+    module: &IrModule,
+    options: &CodegenOptions<T>,
+) -> Result<MachModule<T>> {
     let mut functions = Arena::new();
 
     for (_, function) in module.functions() {
         functions.alloc(compile_function(isa, function)?);
     }
 
+    finish_module(isa, module, functions, options)
+}
+
+/// Builds a machine module out of compiled `functions` and runs the machine passes on it.
+pub(crate) fn finish_module<T: TargetIsa>(
+    isa: T,
+    module: &IrModule,
+    functions: Arena<MachFunction<T>>,
+    options: &CodegenOptions<T>,
+) -> Result<MachModule<T>> {
+    debug_assert!(functions.iter().all(|(_, f)| f.types.ptr_eq(&module.types)));
     let mut mach_module = MachModule {
         name: module.name().to_owned(),
         source_filename: module.source_filename().to_owned(),
@@ -68,18 +96,53 @@ pub fn compile_module<T: TargetIsa>(isa: T, module: &IrModule) -> Result<MachMod
         attributes: module.attributes().to_owned(),
         global_variables: module.global_variables().to_owned(),
         types: module.types.clone(),
+        precompiled: FxHashMap::default(),
+        asm_syntax: options.asm_syntax,
+        reloc_model: options.reloc_model,
+        code_model: options.code_model,
+        emit_cfi: options.emit_cfi,
+        function_sections: options.function_sections,
         isa,
     };
 
+    // Members of `nodeduplicate` comdats are all kept, as if in no comdat.
+    // Other selection kinds are only told apart on COFF, and are all placed
+    // in section groups, of which the linker keeps any one.
+    let no_dedup = |comdat: &Name| {
+        matches!(
+            module.comdats().get(comdat),
+            Some(SelectionKind::NoDeduplicate)
+        )
+    };
+    for (_, func) in mach_module.functions.iter_mut() {
+        if func
+            .comdat
+            .as_ref()
+            .is_some_and(|comdat| no_dedup(&Name::from(comdat.as_str())))
+        {
+            func.comdat = None;
+        }
+    }
+    for gv in mach_module.global_variables.values_mut() {
+        if gv.comdat.as_ref().is_some_and(no_dedup) {
+            gv.comdat = None;
+        }
+    }
+
     for pass in T::module_pass_list() {
         pass(&mut mach_module)?
     }
 
+    for pass in &options.extra_module_passes {
+        pass(&mut mach_module)?
+    }
+
     Ok(mach_module)
 }
 
 pub fn compile_function<T: TargetIsa>(isa: T, function: &IrFunction) -> Result<MachFunction<T>> {
     let mut slots = Slots::new(isa);
+    let mut constant_pool = vec![];
     let mut data = Data::new();
     let mut layout = Layout::new();
     let mut block_map = FxHashMap::default();
@@ -107,26 +170,28 @@ pub fn compile_function<T: TargetIsa>(isa: T, function: &IrFunction) -> Result<M
     let mut inst_id_to_slot_id = FxHashMap::default();
     let mut inst_id_to_vreg = FxHashMap::default();
     let mut arg_idx_to_vreg = FxHashMap::default();
+    let mut high_vregs = FxHashMap::default();
     let mut merged_inst = FxHashSet::default();
     let call_conv = T::default_call_conv();
 
-    for (i, block_id) in function.layout.block_iter().enumerate() {
+    for block_id in function.layout.block_iter() {
         let mut insts_seq = vec![];
         let mut inst_seq = vec![];
         let mut prologue_seq = vec![];
 
-        // entry block
-        if i == 0 {
+        if function.is_entry_block(block_id) {
             T::Lower::copy_args_to_vregs(
                 &mut LoweringContext {
                     ir_data: &function.data,
                     mach_data: &mut data,
                     slots: &mut slots,
+                    constant_pool: &mut constant_pool,
                     inst_id_to_slot_id: &mut inst_id_to_slot_id,
                     inst_seq: &mut prologue_seq,
                     arg_idx_to_vreg: &mut arg_idx_to_vreg,
                     types: &function.types,
                     inst_id_to_vreg: &mut inst_id_to_vreg,
+                    high_vregs: &mut high_vregs,
                     merged_inst: &mut merged_inst,
                     block_map: &block_map,
                     call_conv,
@@ -140,7 +205,7 @@ pub fn compile_function<T: TargetIsa>(isa: T, function: &IrFunction) -> Result<M
         // TODO: Refactoring
         for inst_id in function.layout.inst_iter(block_id) {
             let inst = function.data.inst_ref(inst_id);
-            if inst.opcode != Opcode::Alloca && inst.opcode != Opcode::Phi {
+            if !inst.opcode.is_alloca() && !inst.opcode.is_phi() {
                 break;
             }
             T::Lower::lower(
@@ -148,11 +213,13 @@ pub fn compile_function<T: TargetIsa>(isa: T, function: &IrFunction) -> Result<M
                     ir_data: &function.data,
                     mach_data: &mut data,
                     slots: &mut slots,
+                    constant_pool: &mut constant_pool,
                     inst_id_to_slot_id: &mut inst_id_to_slot_id,
                     inst_seq: &mut prologue_seq,
                     arg_idx_to_vreg: &mut arg_idx_to_vreg,
                     types: &function.types,
                     inst_id_to_vreg: &mut inst_id_to_vreg,
+                    high_vregs: &mut high_vregs,
                     merged_inst: &mut merged_inst,
                     block_map: &block_map,
                     call_conv,
@@ -165,7 +232,7 @@ pub fn compile_function<T: TargetIsa>(isa: T, function: &IrFunction) -> Result<M
         for inst_id in function.layout.inst_iter(block_id).rev() {
             let inst = function.data.inst_ref(inst_id);
 
-            if inst.opcode == Opcode::Alloca || inst.opcode == Opcode::Phi {
+            if inst.opcode.is_alloca() || inst.opcode.is_phi() {
                 break;
             }
 
@@ -186,11 +253,13 @@ pub fn compile_function<T: TargetIsa>(isa: T, function: &IrFunction) -> Result<M
                     ir_data: &function.data,
                     mach_data: &mut data,
                     slots: &mut slots,
+                    constant_pool: &mut constant_pool,
                     inst_id_to_slot_id: &mut inst_id_to_slot_id,
                     inst_seq: &mut inst_seq,
                     arg_idx_to_vreg: &mut arg_idx_to_vreg,
                     types: &function.types,
                     inst_id_to_vreg: &mut inst_id_to_vreg,
+                    high_vregs: &mut high_vregs,
                     merged_inst: &mut merged_inst,
                     block_map: &block_map,
                     call_conv,
@@ -217,11 +286,18 @@ pub fn compile_function<T: TargetIsa>(isa: T, function: &IrFunction) -> Result<M
         is_var_arg: function.is_var_arg,
         result_ty: function.result_ty,
         params: function.params.clone(),
+        linkage: function.linkage,
         preemption_specifier: function.preemption_specifier,
+        visibility: function.visibility,
         attributes: function.func_attrs.clone(),
+        personality: personality_symbol(function),
+        section: function.section.clone(),
+        comdat: function.comdat.as_ref().map(symbol_name),
+        align: function.align,
         data,
         layout,
         slots,
+        constant_pool,
         types: function.types.clone(),
         is_prototype: function.is_prototype(),
         isa,
@@ -229,6 +305,19 @@ pub fn compile_function<T: TargetIsa>(isa: T, function: &IrFunction) -> Result<M
     })
 }
 
+/// Returns the symbol of the personality function of `function`, looking
+/// through bitcasts.
+pub(crate) fn personality_symbol(function: &IrFunction) -> Option<String> {
+    let mut konst = &function.personality.as_ref()?.1;
+    loop {
+        match konst {
+            ConstantData::GlobalRef(name) => return name.to_str().map(str::to_owned),
+            ConstantData::Expr(ConstantExpr::Bitcast { arg, .. }) => konst = arg,
+            _ => return None,
+        }
+    }
+}
+
 impl<'a, T: TargetIsa> LoweringContext<'a, T> {
     pub fn set_output_for_inst(&mut self, id: IrInstructionId, vreg: VReg) {
         self.inst_id_to_vreg.insert(id, vreg);
@@ -241,6 +330,18 @@ impl<'a, T: TargetIsa> LoweringContext<'a, T> {
     pub fn is_merged(&self, inst: IrInstructionId) -> bool {
         self.merged_inst.contains(&inst)
     }
+
+    /// Returns the index of the constant of `bytes` in the constant pool,
+    /// adding it if needed.
+    pub fn constant_pool_index(&mut self, bytes: Vec<u8>) -> usize {
+        match self.constant_pool.iter().position(|c| *c == bytes) {
+            Some(i) => i,
+            None => {
+                self.constant_pool.push(bytes);
+                self.constant_pool.len() - 1
+            }
+        }
+    }
 }
 
 impl Error for LoweringError {}
@@ -1,7 +1,14 @@
+pub mod cache;
 pub mod call_conv;
+pub mod code_cache;
+pub mod driver;
 pub mod function;
 pub mod isa;
+#[cfg(all(unix, target_arch = "x86_64"))]
+pub mod jit;
 pub mod lower;
 pub mod module;
+pub mod object;
+pub mod options;
 pub mod pass;
 pub mod register;
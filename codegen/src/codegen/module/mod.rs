@@ -1,10 +1,15 @@
-use super::{function::Function, isa::TargetIsa};
+use super::{
+    function::Function,
+    isa::TargetIsa,
+    options::{AsmSyntax, CodeModel, RelocModel},
+};
 use id_arena::Arena;
 use rustc_hash::FxHashMap;
 use std::fmt;
 use vicis_core::ir::{
     module::{
-        attributes::Attribute, global_variable::GlobalVariable, name::Name, Target as ModuleTarget,
+        attributes::Attribute, global_variable::GlobalVariable, name::Name, FxIndexMap,
+        Target as ModuleTarget,
     },
     types::Types,
 };
@@ -14,9 +19,24 @@ pub struct Module<T: TargetIsa> {
     pub source_filename: String,
     pub target: ModuleTarget, // TODO
     pub functions: Arena<Function<T>>,
-    pub attributes: FxHashMap<u32, Vec<Attribute>>,
-    pub global_variables: FxHashMap<Name, GlobalVariable>,
+    pub attributes: FxIndexMap<u32, Vec<Attribute>>,
+    pub global_variables: FxIndexMap<Name, GlobalVariable>,
     pub types: Types,
+    /// Assembly of functions reused from a `CodegenCache`, keyed by function index.
+    /// Such functions are left as prototypes in `functions`.
+    pub precompiled: FxHashMap<usize, String>,
+    /// The syntax the module is printed in, from `CodegenOptions`.
+    pub asm_syntax: AsmSyntax,
+    /// The relocation model the passes generate code for, from `CodegenOptions`.
+    pub reloc_model: RelocModel,
+    /// The code model the passes generate code for, from `CodegenOptions`.
+    pub code_model: CodeModel,
+    /// Whether the prologue and epilogue inserters describe frames with call
+    /// frame information, from `CodegenOptions`.
+    pub emit_cfi: bool,
+    /// Whether each function and global variable is placed in a section of
+    /// its own, from `CodegenOptions`.
+    pub function_sections: bool,
     // TODO: Metadata
     pub isa: T,
 }
@@ -0,0 +1,273 @@
+//! Global variables as assembler directives, and the sections of functions.
+//!
+//! Each global variable with an initializer is put in `.data`, `.rodata` or
+//! `.bss` as [`DataKind::of`] decides, with the alignment of its data object,
+//! unless placed in a section of its own by [`section_of`]. Members of a
+//! comdat are each placed in a section of their own, in the section group of
+//! the comdat.
+//! Its initializer is written field by field: integers and addresses by the
+//! directive of their size, padding and zeros by `.zero`, and addresses of
+//! elements by an offset from the symbol, left to the assembler to relocate.
+//! Strings are written by `.asciz`, or `.ascii` without a terminating NUL,
+//! with every other byte escaped.
+//! Every module ends with an empty `.note.GNU-stack` section, by
+//! [`print_stack_note`], so that the stack of the program is not executable.
+
+use super::{address_offset, data_align, section_of, symbol_name, Binding, DataKind};
+use crate::codegen::{function::Function, isa::TargetIsa, module::Module};
+use std::fmt;
+use vicis_core::ir::{
+    module::{linkage::Linkage, visibility::Visibility},
+    types::{Type, Types},
+    value::{ConstantData, ConstantExpr, ConstantFloat},
+};
+
+/// Prints the empty section telling the linker that the code doesn't need an
+/// executable stack.
+pub fn print_stack_note(f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "  .section .note.GNU-stack,\"\",@progbits")
+}
+
+/// Prints the global variables of `module` with an initializer, laid out by
+/// the data layout of `T`, and switches back to `.text`.
+pub fn print_global_variables<T: TargetIsa>(
+    f: &mut fmt::Formatter<'_>,
+    module: &Module<T>,
+) -> fmt::Result {
+    let mut printed = false;
+    for gv in module.global_variables.values() {
+        let Some(init) = &gv.init else {
+            print_binding(f, &symbol_name(&gv.name), gv.linkage, false)?;
+            print_visibility(f, &symbol_name(&gv.name), gv.visibility)?;
+            continue;
+        };
+        let name = symbol_name(&gv.name);
+        let kind = DataKind::of(gv, init);
+        let size = T::type_size(&module.types, gv.ty);
+        let group = gv.comdat.as_ref().map(symbol_name);
+        let section = section_of(
+            gv.section.as_deref(),
+            kind.section(),
+            &name,
+            module.function_sections || group.is_some(),
+        );
+        let (flags, ty) = match kind {
+            DataKind::Data => ("aw", "progbits"),
+            DataKind::ReadOnly => ("a", "progbits"),
+            DataKind::Zeroed => ("aw", "nobits"),
+        };
+        match (section, kind) {
+            (None, DataKind::Data) => writeln!(f, "  .data")?,
+            (None, DataKind::ReadOnly) => writeln!(f, "  .section .rodata")?,
+            (None, DataKind::Zeroed) => writeln!(f, "  .bss")?,
+            (Some(section), _) => writeln!(
+                f,
+                "{}",
+                section_directive(&section, flags, ty, group.as_deref())
+            )?,
+        }
+        print_binding(f, &name, gv.linkage, true)?;
+        print_visibility(f, &name, gv.visibility)?;
+        let align = data_align::<T>(&module.types, gv);
+        writeln!(f, "  .p2align {}", align.trailing_zeros())?;
+        writeln!(f, "  .type {},@object", name)?;
+        writeln!(f, "  .size {}, {}", name, size)?;
+        writeln!(f, "{}:", name)?;
+        match kind {
+            DataKind::Zeroed => zero(f, size)?,
+            _ => print_init::<T>(f, &module.types, gv.ty, init)?,
+        }
+        printed = true;
+    }
+    if printed {
+        writeln!(f, "  .text")?;
+    }
+    Ok(())
+}
+
+/// Switches to the section of `func`, unless it is `current`, i.e. the
+/// directive of the one the previous function was put in, `None` standing
+/// for `.text`. Then aligns the function as requested.
+pub fn print_function_section<T: TargetIsa>(
+    f: &mut fmt::Formatter<'_>,
+    module: &Module<T>,
+    func: &Function<T>,
+    current: &mut Option<String>,
+) -> fmt::Result {
+    let section = section_of(
+        func.section.as_deref(),
+        ".text",
+        &func.name,
+        module.function_sections || func.comdat.is_some(),
+    );
+    let directive = section
+        .map(|section| section_directive(&section, "ax", "progbits", func.comdat.as_deref()));
+    if directive != *current {
+        writeln!(f, "{}", directive.as_deref().unwrap_or("  .text"))?;
+        *current = directive;
+    }
+    if func.align > 1 {
+        writeln!(f, "  .p2align {}", func.align.trailing_zeros())?;
+    }
+    Ok(())
+}
+
+/// Returns the directive switching to `section` of `flags` and `ty`, in the
+/// section group of the comdat `group` if any. Groups of the same name in
+/// several objects are all discarded by the linker but one.
+pub(crate) fn section_directive(
+    section: &str,
+    flags: &str,
+    ty: &str,
+    group: Option<&str>,
+) -> String {
+    match group {
+        Some(group) => format!(
+            "  .section {},\"{}G\",@{},{},comdat",
+            section, flags, ty, group
+        ),
+        None => format!("  .section {},\"{}\",@{}", section, flags, ty),
+    }
+}
+
+/// Prints the directive giving the binding of the symbol `name` of
+/// `linkage`, defined in the module or not. Undefined symbols only get one
+/// as weak references.
+pub fn print_binding(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    linkage: Option<Linkage>,
+    defined: bool,
+) -> fmt::Result {
+    match (Binding::of_linkage(linkage), defined) {
+        (Binding::Global, true) => writeln!(f, "  .globl {}", name),
+        (Binding::Weak, _) => writeln!(f, "  .weak {}", name),
+        _ => Ok(()),
+    }
+}
+
+/// Prints the directive giving the visibility of the symbol `name`, if not
+/// `default`.
+pub fn print_visibility(
+    f: &mut fmt::Formatter<'_>,
+    name: &str,
+    visibility: Visibility,
+) -> fmt::Result {
+    match visibility {
+        Visibility::Default => Ok(()),
+        Visibility::Hidden => writeln!(f, "  .hidden {}", name),
+        Visibility::Protected => writeln!(f, "  .protected {}", name),
+    }
+}
+
+/// Prints `init`, a constant of `ty`, taking as many bytes as the size of
+/// `ty`.
+fn print_init<T: TargetIsa>(
+    f: &mut fmt::Formatter<'_>,
+    types: &Types,
+    ty: Type,
+    init: &ConstantData,
+) -> fmt::Result {
+    let size = T::type_size(types, ty);
+    match init {
+        // 128-bit integers in two quads, from the low one
+        ConstantData::Int(int) if size == 16 => {
+            let i = int.cast_to_i128();
+            writeln!(f, "  .quad {}", i as i64)?;
+            writeln!(f, "  .quad {}", (i >> 64) as i64)
+        }
+        ConstantData::Int(int) => writeln!(f, "  {} {}", int_directive(size), int.cast_to_i64()),
+        ConstantData::Float(ConstantFloat::Float(bits)) => writeln!(f, "  .long {:#x}", bits),
+        ConstantData::Float(ConstantFloat::Double(bits)) => writeln!(f, "  .quad {:#x}", bits),
+        ConstantData::Array(arr) if arr.is_string => {
+            let bytes: Vec<u8> = arr
+                .elems
+                .iter()
+                .map(|e| *e.as_int().as_i8() as u8)
+                .collect();
+            match bytes.split_last() {
+                Some((0, s)) => writeln!(f, "  .asciz \"{}\"", escape(s)),
+                Some(_) => writeln!(f, "  .ascii \"{}\"", escape(&bytes)),
+                None => Ok(()),
+            }
+        }
+        ConstantData::Array(arr) => {
+            for elem in &arr.elems {
+                print_init::<T>(f, types, arr.elem_ty, elem)?;
+            }
+            Ok(())
+        }
+        ConstantData::Vector(v) => {
+            for elem in &v.elems {
+                print_init::<T>(f, types, v.elem_ty, elem)?;
+            }
+            Ok(())
+        }
+        ConstantData::Struct(s) => {
+            let layout = T::data_layout();
+            let mut end = 0;
+            for (i, (elem, &elem_ty)) in s.elems.iter().zip(s.elems_ty.iter()).enumerate() {
+                let offset = layout.field_offset(types, ty, i).unwrap();
+                zero(f, offset - end)?;
+                print_init::<T>(f, types, elem_ty, elem)?;
+                end = offset + T::type_size(types, elem_ty);
+            }
+            zero(f, size - end)
+        }
+        ConstantData::GlobalRef(name) => {
+            writeln!(f, "  {} {}", int_directive(size), symbol_name(name))
+        }
+        ConstantData::Expr(ConstantExpr::Bitcast { tys, arg }) => {
+            print_init::<T>(f, types, tys[0], arg)
+        }
+        ConstantData::Expr(ConstantExpr::GetElementPtr { tys, args, .. }) => {
+            let (name, offset) = address_offset::<T>(types, tys, args).map_err(|_| fmt::Error)?;
+            match offset {
+                0 => writeln!(f, "  {} {}", int_directive(size), symbol_name(name)),
+                _ => writeln!(
+                    f,
+                    "  {} {}{:+}",
+                    int_directive(size),
+                    symbol_name(name),
+                    offset
+                ),
+            }
+        }
+        ConstantData::AggregateZero | ConstantData::Null | ConstantData::Undef => zero(f, size),
+    }
+}
+
+/// Escapes `bytes` for a string of the assembler. Bytes other than printable
+/// ASCII are written in octal, which takes at most three digits and cannot
+/// run into the characters that follow, unlike `\x`.
+fn escape(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len());
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => {
+                s.push('\\');
+                s.push(b as char);
+            }
+            b' '..=b'~' => s.push(b as char),
+            _ => s.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    s
+}
+
+fn zero(f: &mut fmt::Formatter<'_>, size: u32) -> fmt::Result {
+    match size {
+        0 => Ok(()),
+        _ => writeln!(f, "  .zero {}", size),
+    }
+}
+
+/// Returns the directive of an integer of `size` bytes.
+fn int_directive(size: u32) -> &'static str {
+    match size {
+        1 => ".byte",
+        2 => ".short",
+        4 => ".long",
+        _ => ".quad",
+    }
+}
@@ -0,0 +1,52 @@
+//! ELF relocatable objects for x86-64.
+//!
+//! Functions go to `.text`, and data objects to `.data`, `.rodata` or `.bss`
+//! by their [`DataKind`](super::DataKind). Relocations map as follows, all
+//! with explicit addends:
+//!
+//! | [`RelocKind`] | ELF                      |
+//! |---------------|--------------------------|
+//! | `Abs8`        | `R_X86_64_64`            |
+//! | `PcRel4`      | `R_X86_64_PC32`          |
+//! | `Call4`       | `R_X86_64_PLT32`         |
+//! | `GotPcRel4`   | `R_X86_64_REX_GOTPCRELX` |
+//!
+//! An empty `.note.GNU-stack` section marks the stack as not executable.
+
+use super::{Flavor, ObjectModule};
+use crate::codegen::code_cache::RelocKind;
+use anyhow::Result;
+use object::{elf, BinaryFormat, RelocationKind, SectionKind};
+use std::{fs, path::Path};
+
+const FLAVOR: Flavor = Flavor {
+    format: BinaryFormat::Elf,
+    symbol_prefix: "",
+    reloc_kind,
+};
+
+/// Returns the bytes of the ELF object for `module`.
+pub fn write(module: &ObjectModule) -> Result<Vec<u8>> {
+    let mut obj = module.to_object(&FLAVOR);
+    obj.add_section(
+        vec![],
+        b".note.GNU-stack".to_vec(),
+        SectionKind::Elf(elf::SHT_PROGBITS),
+    );
+    Ok(obj.write()?)
+}
+
+/// Writes the ELF object for `module` to `path`.
+pub fn write_to_file(module: &ObjectModule, path: impl AsRef<Path>) -> Result<()> {
+    fs::write(path, write(module)?)?;
+    Ok(())
+}
+
+fn reloc_kind(kind: RelocKind) -> RelocationKind {
+    RelocationKind::Elf(match kind {
+        RelocKind::Abs8 => elf::R_X86_64_64,
+        RelocKind::PcRel4 => elf::R_X86_64_PC32,
+        RelocKind::Call4 => elf::R_X86_64_PLT32,
+        RelocKind::GotPcRel4 => elf::R_X86_64_REX_GOTPCRELX,
+    })
+}
@@ -0,0 +1,59 @@
+//! Mach-O relocatable objects for x86-64 macOS.
+//!
+//! Symbol names get the leading underscore of the platform, e.g. `main`
+//! becomes `_main` and `puts` refers to `_puts`. Functions go to
+//! `__TEXT,__text`, and data objects to `__DATA,__data`, `__TEXT,__const` or
+//! `__DATA,__bss` by their [`DataKind`](super::DataKind). Relocations map as follows:
+//!
+//! | [`RelocKind`] | Mach-O                  |
+//! |---------------|-------------------------|
+//! | `Abs8`        | `X86_64_RELOC_UNSIGNED` |
+//! | `PcRel4`      | `X86_64_RELOC_SIGNED`   |
+//! | `Call4`       | `X86_64_RELOC_BRANCH`   |
+//! | `GotPcRel4`   | `X86_64_RELOC_GOT_LOAD` |
+//!
+//! Mach-O keeps addends in the relocated fields, measured from the end of
+//! the field for PC-relative kinds. The explicit addends of relocations are
+//! converted accordingly, so that code is encoded the same for both formats.
+
+use super::{Flavor, ObjectModule};
+use crate::codegen::code_cache::RelocKind;
+use anyhow::Result;
+use object::{macho, write::MachOBuildVersion, BinaryFormat, RelocationKind};
+use std::{fs, path::Path};
+
+/// The minimum macOS version recorded in objects, 10.13 encoded as
+/// `xxxx.yy.zz` in nibbles.
+pub const MIN_MACOS_VERSION: u32 = 0x000a_0d00;
+
+const FLAVOR: Flavor = Flavor {
+    format: BinaryFormat::MachO,
+    symbol_prefix: "_",
+    reloc_kind,
+};
+
+/// Returns the bytes of the Mach-O object for `module`.
+pub fn write(module: &ObjectModule) -> Result<Vec<u8>> {
+    let mut obj = module.to_object(&FLAVOR);
+    let mut version = MachOBuildVersion::default();
+    version.platform = macho::PLATFORM_MACOS;
+    version.minos = MIN_MACOS_VERSION;
+    obj.set_macho_build_version(version);
+    Ok(obj.write()?)
+}
+
+/// Writes the Mach-O object for `module` to `path`.
+pub fn write_to_file(module: &ObjectModule, path: impl AsRef<Path>) -> Result<()> {
+    fs::write(path, write(module)?)?;
+    Ok(())
+}
+
+fn reloc_kind(kind: RelocKind) -> RelocationKind {
+    let (value, relative) = match kind {
+        RelocKind::Abs8 => (macho::X86_64_RELOC_UNSIGNED, false),
+        RelocKind::PcRel4 => (macho::X86_64_RELOC_SIGNED, true),
+        RelocKind::Call4 => (macho::X86_64_RELOC_BRANCH, true),
+        RelocKind::GotPcRel4 => (macho::X86_64_RELOC_GOT_LOAD, true),
+    };
+    RelocationKind::MachO { value, relative }
+}
@@ -0,0 +1,603 @@
+//! Relocatable object files.
+//!
+//! An [`ObjectModule`] gathers the encoded functions of a module, in the form
+//! kept by a [`CodeCache`](super::code_cache::CodeCache), and the contents of
+//! its global variables. The writers of this module turn it into an object
+//! file for a linker, without going through an external assembler:
+//!
+//! - [`elf`] writes an ELF `.o` for x86-64.
+//! - [`macho`] writes a Mach-O `.o` for x86-64 macOS.
+//!
+//! The assembly printers lay out global variables by [`asm`], in the same
+//! sections and with the same alignment.
+//!
+//! Functions and data objects are placed in the standard section of their
+//! kind, unless given one of their own by [`ObjectModule::set_section`].
+//! Those of the members of a comdat are put in its section group on ELF, by
+//! [`ObjectModule::set_comdat`].
+//!
+//! Each function and data object gets a symbol. Relocations refer to symbols
+//! by name, so that those defined in the same object bind to their
+//! definitions and the others are left undefined for the linker to resolve.
+
+pub mod asm;
+pub mod elf;
+pub mod macho;
+
+use super::{
+    code_cache::{CompiledFunction, RelocKind, Relocation},
+    isa::TargetIsa,
+    module::Module,
+};
+use anyhow::{bail, Result};
+use object::write::{
+    self, Comdat, Mangling, Object, SectionId, StandardSection, StandardSegment, Symbol, SymbolId,
+    SymbolSection,
+};
+use object::{
+    BinaryFormat, ComdatKind, RelocationEncoding, RelocationKind, SectionFlags, SymbolFlags,
+    SymbolKind, SymbolScope,
+};
+use rustc_hash::FxHashMap;
+use std::collections::BTreeMap;
+use vicis_core::ir::{
+    module::{
+        global_variable::GlobalVariable, linkage::Linkage, name::Name, visibility::Visibility,
+    },
+    types::{Type, Types},
+    value::{ConstantData, ConstantExpr},
+};
+
+/// What differs between the object formats written.
+struct Flavor {
+    format: BinaryFormat,
+    /// Prepended to the names of symbols, e.g. `_` on macOS.
+    symbol_prefix: &'static str,
+    /// Returns the relocation kind of the format for a [`RelocKind`].
+    reloc_kind: fn(RelocKind) -> RelocationKind,
+}
+
+/// Functions and data to be written to an object file.
+#[derive(Debug, Clone, Default)]
+pub struct ObjectModule {
+    /// The symbol table referred to by relocations.
+    symbols: Vec<String>,
+    functions: Vec<(CompiledFunction, Binding)>,
+    data: Vec<DataObject>,
+    /// The sections of the symbols not placed in the standard section of
+    /// their kind.
+    sections: FxHashMap<String, String>,
+    /// The visibilities of the symbols other than `default`.
+    visibilities: FxHashMap<String, Visibility>,
+    /// The comdats the symbols are members of, if any.
+    comdats: FxHashMap<String, String>,
+}
+
+/// Whether a symbol is visible to other objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Global,
+    /// Visible to other objects, which may define the symbol as well, the
+    /// linker keeping one of the definitions, as for `weak` and `linkonce`
+    /// linkage. A weak reference may be left undefined.
+    Weak,
+    /// Only visible within the object, as for `private` and `internal`
+    /// linkage.
+    Local,
+}
+
+/// Where a data object is placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataKind {
+    /// Writable data, in `.data`.
+    Data,
+    /// Constants, in `.rodata`.
+    ReadOnly,
+    /// Writable data initialized to zero, in `.bss`. Takes no room in the
+    /// file.
+    Zeroed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataObject {
+    pub name: String,
+    pub kind: DataKind,
+    pub binding: Binding,
+    pub align: u32,
+    pub bytes: Vec<u8>,
+    /// Relocations within `bytes`, referring to [`ObjectModule::symbols`].
+    pub relocs: Vec<Relocation>,
+}
+
+impl DataKind {
+    /// Returns where a global variable initialized to `init` is placed.
+    /// Variables in a section given explicitly are not left out of the file,
+    /// as the section may hold other data.
+    pub fn of(gv: &GlobalVariable, init: &ConstantData) -> Self {
+        if gv.is_constant {
+            Self::ReadOnly
+        } else if is_zero(init) && gv.section.is_none() {
+            Self::Zeroed
+        } else {
+            Self::Data
+        }
+    }
+
+    /// Returns the name of the standard section of the kind.
+    pub fn section(self) -> &'static str {
+        match self {
+            Self::Data => ".data",
+            Self::ReadOnly => ".rodata",
+            Self::Zeroed => ".bss",
+        }
+    }
+}
+
+impl Binding {
+    pub fn of_linkage(linkage: Option<Linkage>) -> Self {
+        match linkage {
+            Some(Linkage::Private | Linkage::Internal | Linkage::LinkerPrivate) => Self::Local,
+            Some(
+                Linkage::LinkOnceAny
+                | Linkage::LinkOnceODR
+                | Linkage::LinkOnceODRAutoHide
+                | Linkage::WeakAny
+                | Linkage::WeakODR
+                | Linkage::Common
+                | Linkage::ExternalWeak
+                | Linkage::LinkerPrivateWeak,
+            ) => Self::Weak,
+            _ => Self::Global,
+        }
+    }
+}
+
+impl ObjectModule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the symbol table referred to by relocations.
+    pub fn symbols(&self) -> &[String] {
+        &self.symbols
+    }
+
+    /// Returns the index of `name` in the symbol table, adding it if needed.
+    pub fn symbol(&mut self, name: &str) -> u32 {
+        match self.symbols.iter().position(|s| s == name) {
+            Some(i) => i as u32,
+            None => {
+                self.symbols.push(name.to_owned());
+                self.symbols.len() as u32 - 1
+            }
+        }
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = (&CompiledFunction, Binding)> {
+        self.functions
+            .iter()
+            .map(|(func, binding)| (func, *binding))
+    }
+
+    pub fn data(&self) -> &[DataObject] {
+        &self.data
+    }
+
+    /// Returns the section the function or data object `symbol` is placed
+    /// in, if not the standard one of its kind.
+    pub fn section(&self, symbol: &str) -> Option<&str> {
+        self.sections.get(symbol).map(String::as_str)
+    }
+
+    /// Places the function or data object `symbol` in `section`.
+    pub fn set_section(&mut self, symbol: &str, section: String) {
+        self.sections.insert(symbol.to_owned(), section);
+    }
+
+    /// Returns the visibility of `symbol` to other components, i.e.
+    /// executables and shared libraries, that the object is linked into.
+    pub fn visibility(&self, symbol: &str) -> Visibility {
+        self.visibilities
+            .get(symbol)
+            .copied()
+            .unwrap_or(Visibility::Default)
+    }
+
+    pub fn set_visibility(&mut self, symbol: &str, visibility: Visibility) {
+        match visibility {
+            Visibility::Default => self.visibilities.remove(symbol),
+            _ => self.visibilities.insert(symbol.to_owned(), visibility),
+        };
+    }
+
+    /// Returns the comdat the function or data object `symbol` is a member
+    /// of, if any.
+    pub fn comdat(&self, symbol: &str) -> Option<&str> {
+        self.comdats.get(symbol).map(String::as_str)
+    }
+
+    /// Makes the function or data object `symbol` a member of the comdat
+    /// `comdat`. Its section is put in the section group of the comdat on
+    /// ELF, and should be one of its own.
+    pub fn set_comdat(&mut self, symbol: &str, comdat: String) {
+        self.comdats.insert(symbol.to_owned(), comdat);
+    }
+
+    /// Adds `func`, whose relocations refer to `symbols`, e.g. those of the
+    /// [`CodeCache`](super::code_cache::CodeCache) it comes from.
+    pub fn add_function(&mut self, func: &CompiledFunction, symbols: &[String], binding: Binding) {
+        let mut func = func.clone();
+        for reloc in &mut func.relocs {
+            reloc.symbol = self.symbol(&symbols[reloc.symbol as usize]);
+        }
+        self.functions.push((func, binding));
+    }
+
+    /// Adds `data`, whose relocations refer to [`Self::symbols`].
+    pub fn add_data(&mut self, data: DataObject) {
+        self.data.push(data);
+    }
+
+    /// Adds a data object for each global variable of `module` with an
+    /// initializer, laid out by the data layout of `T`. Declarations are left
+    /// as undefined symbols, but for their visibility.
+    pub fn add_global_variables<T: TargetIsa>(&mut self, module: &Module<T>) -> Result<()> {
+        for gv in module.global_variables.values() {
+            let Some(init) = &gv.init else {
+                self.set_visibility(&symbol_name(&gv.name), gv.visibility);
+                continue;
+            };
+            let data = self.data_object::<T>(&module.types, gv, init)?;
+            if let Some(section) = section_of(
+                gv.section.as_deref(),
+                data.kind.section(),
+                &data.name,
+                module.function_sections || gv.comdat.is_some(),
+            ) {
+                self.set_section(&data.name, section);
+            }
+            if let Some(comdat) = &gv.comdat {
+                self.set_comdat(&data.name, symbol_name(comdat));
+            }
+            self.set_visibility(&data.name, gv.visibility);
+            self.add_data(data);
+        }
+        Ok(())
+    }
+
+    fn data_object<T: TargetIsa>(
+        &mut self,
+        types: &Types,
+        gv: &GlobalVariable,
+        init: &ConstantData,
+    ) -> Result<DataObject> {
+        let mut bytes = vec![];
+        let mut relocs = vec![];
+        self.write_init::<T>(&mut bytes, &mut relocs, types, gv.ty, init)?;
+        Ok(DataObject {
+            name: symbol_name(&gv.name),
+            kind: DataKind::of(gv, init),
+            binding: Binding::of_linkage(gv.linkage),
+            align: data_align::<T>(types, gv),
+            bytes,
+            relocs,
+        })
+    }
+
+    /// Appends the little-endian bytes of `init`, a constant of `ty`, and the
+    /// relocations for the addresses in it.
+    fn write_init<T: TargetIsa>(
+        &mut self,
+        bytes: &mut Vec<u8>,
+        relocs: &mut Vec<Relocation>,
+        types: &Types,
+        ty: Type,
+        init: &ConstantData,
+    ) -> Result<()> {
+        let layout = T::data_layout();
+        let start = bytes.len();
+        let size = T::type_size(types, ty) as usize;
+        match init {
+            ConstantData::Int(int) => {
+                bytes.extend_from_slice(&int.cast_to_i128().to_le_bytes()[..size]);
+            }
+            ConstantData::Float(fp) => bytes.extend_from_slice(&fp.to_le_bytes()),
+            ConstantData::Array(arr) => {
+                for elem in &arr.elems {
+                    self.write_init::<T>(bytes, relocs, types, arr.elem_ty, elem)?;
+                }
+            }
+            ConstantData::Vector(v) => {
+                for elem in &v.elems {
+                    self.write_init::<T>(bytes, relocs, types, v.elem_ty, elem)?;
+                }
+            }
+            ConstantData::Struct(s) => {
+                for (i, (elem, &elem_ty)) in s.elems.iter().zip(s.elems_ty.iter()).enumerate() {
+                    let offset = layout.field_offset(types, ty, i).unwrap() as usize;
+                    bytes.resize(start + offset, 0);
+                    self.write_init::<T>(bytes, relocs, types, elem_ty, elem)?;
+                }
+            }
+            ConstantData::GlobalRef(name) => {
+                self.write_address(bytes, relocs, size, name, 0)?;
+            }
+            ConstantData::Expr(ConstantExpr::Bitcast { tys, arg }) => {
+                self.write_init::<T>(bytes, relocs, types, tys[0], arg)?;
+            }
+            ConstantData::Expr(ConstantExpr::GetElementPtr { tys, args, .. }) => {
+                let (name, offset) = address_offset::<T>(types, tys, args)?;
+                self.write_address(bytes, relocs, size, name, offset)?;
+            }
+            ConstantData::AggregateZero | ConstantData::Null | ConstantData::Undef => {}
+        }
+        bytes.resize(start + size, 0);
+        Ok(())
+    }
+
+    fn write_address(
+        &mut self,
+        bytes: &mut Vec<u8>,
+        relocs: &mut Vec<Relocation>,
+        size: usize,
+        name: &Name,
+        addend: i64,
+    ) -> Result<()> {
+        if size != RelocKind::Abs8.size() {
+            bail!("{}-byte addresses are not supported", size)
+        }
+        relocs.push(Relocation {
+            offset: bytes.len() as u32,
+            kind: RelocKind::Abs8,
+            symbol: self.symbol(&symbol_name(name)),
+            addend,
+        });
+        bytes.extend_from_slice(&[0; 8]);
+        Ok(())
+    }
+
+    /// Returns the section `symbol` is placed in, the `standard` one unless
+    /// set otherwise. Sections of other names are added to `obj` once per
+    /// comdat, in the segment of the standard one, and kept in `sections`.
+    fn section_id<'a>(
+        &'a self,
+        obj: &mut Object,
+        sections: &mut FxHashMap<(&'a str, Option<&'a str>), SectionId>,
+        symbol: &str,
+        standard: StandardSection,
+    ) -> SectionId {
+        let Some(name) = self.sections.get(symbol) else {
+            return obj.section_id(standard);
+        };
+        *sections
+            .entry((name, self.comdat(symbol)))
+            .or_insert_with(|| {
+                let segment = match standard {
+                    StandardSection::Text | StandardSection::ReadOnlyData => StandardSegment::Text,
+                    _ => StandardSegment::Data,
+                };
+                let segment = obj.segment_name(segment).to_vec();
+                let id = obj.add_section(segment, name.as_bytes().to_vec(), standard.kind());
+                // Members of section groups are flagged as such, which the
+                // writer leaves to the flags given.
+                if self.comdat(symbol).is_some() && obj.format() == BinaryFormat::Elf {
+                    use object::elf::{SHF_ALLOC, SHF_EXECINSTR, SHF_GROUP, SHF_WRITE};
+                    let sh_flags = match standard {
+                        StandardSection::Text => SHF_ALLOC | SHF_EXECINSTR,
+                        StandardSection::ReadOnlyData => SHF_ALLOC,
+                        _ => SHF_ALLOC | SHF_WRITE,
+                    };
+                    obj.section_mut(id).flags = SectionFlags::Elf {
+                        sh_flags: (sh_flags | SHF_GROUP) as u64,
+                    };
+                }
+                id
+            })
+    }
+
+    /// Builds the object for x86-64 in the format of `flavor`. Shared by the
+    /// writers of each format.
+    fn to_object(&self, flavor: &Flavor) -> Object<'static> {
+        let mut obj = Object::new(
+            flavor.format,
+            object::Architecture::X86_64,
+            object::Endianness::Little,
+        );
+        // Names are mangled here, the same way for every kind of symbol.
+        obj.mangling = Mangling::None;
+        let new_symbol = |name: &str, kind, binding| Symbol {
+            name: format!("{}{}", flavor.symbol_prefix, name).into_bytes(),
+            value: 0,
+            size: 0,
+            kind,
+            scope: match (binding, self.visibility(name)) {
+                (Binding::Local, _) => SymbolScope::Compilation,
+                (_, Visibility::Hidden) => SymbolScope::Linkage,
+                _ => SymbolScope::Dynamic,
+            },
+            weak: binding == Binding::Weak,
+            section: SymbolSection::Undefined,
+            flags: match (binding, self.visibility(name), flavor.format) {
+                // Only ELF has protected symbols, which no scope stands for.
+                (Binding::Global | Binding::Weak, Visibility::Protected, BinaryFormat::Elf) => {
+                    let st_bind = match binding {
+                        Binding::Weak => object::elf::STB_WEAK,
+                        _ => object::elf::STB_GLOBAL,
+                    };
+                    let st_type = match kind {
+                        SymbolKind::Text => object::elf::STT_FUNC,
+                        SymbolKind::Data => object::elf::STT_OBJECT,
+                        _ => object::elf::STT_NOTYPE,
+                    };
+                    SymbolFlags::Elf {
+                        st_info: (st_bind << 4) | st_type,
+                        st_other: object::elf::STV_PROTECTED,
+                    }
+                }
+                _ => SymbolFlags::None,
+            },
+        };
+        let mut ids: FxHashMap<&str, SymbolId> = FxHashMap::default();
+        let mut placed = vec![];
+        let mut sections = FxHashMap::default();
+
+        for (func, binding) in &self.functions {
+            let section =
+                self.section_id(&mut obj, &mut sections, &func.name, StandardSection::Text);
+            let id = obj.add_symbol(new_symbol(&func.name, SymbolKind::Text, *binding));
+            let offset = obj.add_symbol_data(id, section, &func.code, 16);
+            ids.insert(&func.name, id);
+            placed.push((section, offset, &func.relocs));
+        }
+
+        for data in &self.data {
+            let standard = match data.kind {
+                DataKind::Data => StandardSection::Data,
+                DataKind::ReadOnly => StandardSection::ReadOnlyData,
+                DataKind::Zeroed => StandardSection::UninitializedData,
+            };
+            let section = self.section_id(&mut obj, &mut sections, &data.name, standard);
+            let id = obj.add_symbol(new_symbol(&data.name, SymbolKind::Data, data.binding));
+            let offset = match data.kind {
+                DataKind::Zeroed => {
+                    obj.add_symbol_bss(id, section, data.bytes.len() as u64, data.align as u64)
+                }
+                _ => obj.add_symbol_data(id, section, &data.bytes, data.align as u64),
+            };
+            ids.insert(&data.name, id);
+            placed.push((section, offset, &data.relocs));
+        }
+
+        // Only ELF has section groups. Mach-O leaves the duplicates of weak
+        // definitions to the linker to drop.
+        if flavor.format == BinaryFormat::Elf {
+            let mut groups: BTreeMap<&str, Vec<SectionId>> = BTreeMap::new();
+            for (&(_, comdat), &section) in &sections {
+                if let Some(comdat) = comdat {
+                    groups.entry(comdat).or_default().push(section);
+                }
+            }
+            for (comdat, mut sections) in groups {
+                sections.sort();
+                // The group is named after its signature symbol, a member of
+                // the same name if any.
+                let symbol = match ids.get(comdat) {
+                    Some(&id) => id,
+                    None => obj.add_symbol(Symbol {
+                        section: SymbolSection::Section(sections[0]),
+                        ..new_symbol(comdat, SymbolKind::Unknown, Binding::Local)
+                    }),
+                };
+                obj.add_comdat(Comdat {
+                    kind: ComdatKind::Any,
+                    symbol,
+                    sections,
+                });
+            }
+        }
+
+        for (section, offset, relocs) in placed {
+            for reloc in relocs {
+                let name = self.symbols[reloc.symbol as usize].as_str();
+                let symbol = *ids.entry(name).or_insert_with(|| {
+                    obj.add_symbol(Symbol {
+                        section: SymbolSection::Undefined,
+                        ..new_symbol(name, SymbolKind::Unknown, Binding::Global)
+                    })
+                });
+                obj.add_relocation(
+                    section,
+                    write::Relocation {
+                        offset: offset + reloc.offset as u64,
+                        size: reloc.kind.size() as u8 * 8,
+                        kind: (flavor.reloc_kind)(reloc.kind),
+                        encoding: RelocationEncoding::Generic,
+                        symbol,
+                        addend: reloc.addend,
+                    },
+                )
+                .expect("relocation supported by every format written");
+            }
+        }
+
+        obj
+    }
+}
+
+/// Returns the alignment of the data of `gv`, at least that of its type.
+pub(crate) fn data_align<T: TargetIsa>(types: &Types, gv: &GlobalVariable) -> u32 {
+    gv.align.max(T::data_layout().align_of(types, gv.ty)).max(1)
+}
+
+/// Returns the section a function or global variable named `name` is placed
+/// in: `explicit` if given, or a section of its own named after the standard
+/// one of its kind if `separate`, or `None` for the standard one itself.
+pub(crate) fn section_of(
+    explicit: Option<&str>,
+    standard: &str,
+    name: &str,
+    separate: bool,
+) -> Option<String> {
+    match explicit {
+        Some(section) => Some(section.to_owned()),
+        None if separate => Some(format!("{}.{}", standard, name)),
+        None => None,
+    }
+}
+
+/// Returns whether all the bytes of `init` are zero.
+fn is_zero(init: &ConstantData) -> bool {
+    match init {
+        ConstantData::Int(int) => int.cast_to_i128() == 0,
+        ConstantData::Float(fp) => fp.to_le_bytes().iter().all(|&b| b == 0),
+        ConstantData::Array(arr) => arr.elems.iter().all(is_zero),
+        ConstantData::Struct(s) => s.elems.iter().all(is_zero),
+        ConstantData::Vector(v) => v.elems.iter().all(is_zero),
+        ConstantData::Expr(ConstantExpr::Bitcast { arg, .. }) => is_zero(arg),
+        ConstantData::AggregateZero | ConstantData::Null | ConstantData::Undef => true,
+        ConstantData::GlobalRef(_) | ConstantData::Expr(_) => false,
+    }
+}
+
+/// Returns the global and the offset from it addressed by a constant
+/// `getelementptr` of `tys` and `args`.
+pub(crate) fn address_offset<'a, T: TargetIsa>(
+    types: &Types,
+    tys: &[Type],
+    args: &'a [ConstantData],
+) -> Result<(&'a Name, i64)> {
+    let layout = T::data_layout();
+    let mut base = &args[0];
+    while let ConstantData::Expr(ConstantExpr::Bitcast { arg, .. }) = base {
+        base = arg;
+    }
+    let ConstantData::GlobalRef(name) = base else {
+        bail!("unsupported constant expression in an initializer")
+    };
+    let mut offset = 0i64;
+    let mut elem_ty = tys[0];
+    for (i, idx) in args[1..].iter().enumerate() {
+        let ConstantData::Int(idx) = idx else {
+            bail!("unsupported constant expression in an initializer")
+        };
+        let idx = idx.cast_to_i64();
+        if i == 0 {
+            offset += idx * T::type_size(types, elem_ty) as i64;
+        } else if types.is_struct(elem_ty) {
+            offset += layout.field_offset(types, elem_ty, idx as usize).unwrap() as i64;
+            elem_ty = types.base().element_at(elem_ty, idx as usize).unwrap();
+        } else {
+            elem_ty = types.get_element(elem_ty).unwrap();
+            offset += idx * T::type_size(types, elem_ty) as i64;
+        }
+    }
+    Ok((name, offset))
+}
+
+/// Returns the name of the symbol for a global value.
+pub fn symbol_name(name: &Name) -> String {
+    match name.to_str() {
+        Some(name) => name.to_owned(),
+        None => format!("__unnamed_{}", name.as_number()),
+    }
+}
@@ -0,0 +1,122 @@
+use super::{isa::TargetIsa, module::Module};
+use anyhow::Result;
+
+/// A machine pass supplied from outside of the backend.
+pub type BoxedModulePass<T> = Box<dyn Fn(&mut Module<T>) -> Result<()>>;
+
+/// The dialect of printed assembly. Only x86 targets print more than one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AsmSyntax {
+    /// Intel syntax, as accepted by the GNU assembler after
+    /// `.intel_syntax noprefix`.
+    #[default]
+    Intel,
+    /// AT&T syntax, the default of the GNU assembler.
+    Att,
+}
+
+/// How code refers to global variables and functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RelocModel {
+    /// Absolute addresses, for code linked at a fixed address.
+    #[default]
+    Static,
+    /// Position-independent code, for shared libraries and PIE. Globals that
+    /// may be defined in another module are reached through the GOT, and
+    /// calls through the PLT.
+    Pic,
+}
+
+/// How far apart code and data may be placed, which decides how code refers
+/// to globals. Only x86-64 has more than one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CodeModel {
+    /// Code and data lie within 2 GiB of each other, and static code within
+    /// the low 2 GiB of the address space. Globals are referred to by 32-bit
+    /// addresses or displacements.
+    #[default]
+    Small,
+    /// Code lies within 2 GiB, but data may be anywhere. The address of a
+    /// global variable is materialized as a 64-bit immediate by `movabs`, or
+    /// loaded from the GOT in position-independent code.
+    Medium,
+    /// Code and data may be anywhere. Functions are also called through a
+    /// register holding their 64-bit address. Not supported for
+    /// position-independent code.
+    Large,
+}
+
+/// Options controlling `compile_module_with_options`.
+pub struct CodegenOptions<T: TargetIsa> {
+    /// Passes run in order after the target's own `module_pass_list()`.
+    pub extra_module_passes: Vec<BoxedModulePass<T>>,
+    /// The syntax the compiled module is printed in.
+    pub asm_syntax: AsmSyntax,
+    pub reloc_model: RelocModel,
+    pub code_model: CodeModel,
+    /// Whether call frame information is emitted, as `.cfi_*` directives
+    /// around prologues and epilogues, for unwinders and profilers.
+    pub emit_cfi: bool,
+    /// Whether each function and global variable is placed in a section of
+    /// its own, named after it, e.g. `.text.main` and `.data.counter`, as by
+    /// `-ffunction-sections -fdata-sections`. The linker can then drop those
+    /// not referred to with `--gc-sections`.
+    pub function_sections: bool,
+}
+
+impl<T: TargetIsa> Default for CodegenOptions<T> {
+    fn default() -> Self {
+        Self {
+            extra_module_passes: vec![],
+            asm_syntax: AsmSyntax::default(),
+            reloc_model: RelocModel::default(),
+            code_model: CodeModel::default(),
+            emit_cfi: false,
+            function_sections: false,
+        }
+    }
+}
+
+impl<T: TargetIsa> CodegenOptions<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `pass` to the machine pass pipeline.
+    pub fn with_module_pass<F>(mut self, pass: F) -> Self
+    where
+        F: Fn(&mut Module<T>) -> Result<()> + 'static,
+    {
+        self.extra_module_passes.push(Box::new(pass));
+        self
+    }
+
+    /// Prints the compiled module in `syntax`.
+    pub fn with_asm_syntax(mut self, syntax: AsmSyntax) -> Self {
+        self.asm_syntax = syntax;
+        self
+    }
+
+    pub fn with_reloc_model(mut self, model: RelocModel) -> Self {
+        self.reloc_model = model;
+        self
+    }
+
+    pub fn with_code_model(mut self, model: CodeModel) -> Self {
+        self.code_model = model;
+        self
+    }
+
+    /// Emits call frame information if `emit` is true.
+    pub fn with_cfi(mut self, emit: bool) -> Self {
+        self.emit_cfi = emit;
+        self
+    }
+
+    /// Places each function and global variable in its own section if
+    /// `separate` is true.
+    pub fn with_function_sections(mut self, separate: bool) -> Self {
+        self.function_sections = separate;
+        self
+    }
+}
@@ -1,3 +1,16 @@
+//! Liveness of virtual and physical registers in a machine function.
+//!
+//! [`Liveness::analyze_function`] computes the registers live on entry to and
+//! on exit from each block, and the [`LiveRange`] of each register, which is
+//! a list of [`LiveSegment`]s between [`ProgramPoint`]s. Each instruction is
+//! numbered with a program point, spaced so that instructions inserted later,
+//! e.g. by the spiller, can be numbered in between.
+//!
+//! The queries, e.g. [`Liveness::is_live_at`] and [`Liveness::live_in`],
+//! are meant for machine passes and target authors as well as the register
+//! allocator. They reflect the function as it was analyzed, so a pass that
+//! changes the function must run the analysis again before querying.
+
 use crate::codegen::{
     function::{
         basic_block::BasicBlockId,
@@ -28,7 +41,7 @@ pub struct LiveSegment {
     pub end: ProgramPoint,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct BlockData {
     def: FxHashSet<Reg>,
     live_in: FxHashSet<Reg>,
@@ -48,33 +61,13 @@ const STEP: u32 = 16;
 
 impl PartialOrd for ProgramPoint {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.0 < other.0 {
-            return Some(Ordering::Less);
-        }
-
-        if self.0 > other.0 {
-            return Some(Ordering::Greater);
-        }
-
-        assert_eq!(self.0, other.0);
-
-        if self.1 < other.1 {
-            return Some(Ordering::Less);
-        }
-
-        if self.1 == other.1 {
-            return Some(Ordering::Equal);
-        }
-
-        assert!(self.1 > other.1);
-
-        Some(Ordering::Greater)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for ProgramPoint {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        self.0.cmp(&other.0).then(self.1.cmp(&other.1))
     }
 }
 
@@ -174,6 +167,90 @@ impl<T: TargetIsa> Liveness<T> {
         self.vreg_lrs_map.get(vreg)
     }
 
+    /// Returns the program point of `inst`.
+    pub fn program_point(
+        &self,
+        inst: InstructionId<<T::InstInfo as II>::Data>,
+    ) -> Option<ProgramPoint> {
+        self.inst_to_pp.get(&inst).copied()
+    }
+
+    /// Returns the virtual registers live on entry to `block`.
+    pub fn live_in(&self, block: BasicBlockId) -> impl Iterator<Item = VReg> + '_ {
+        self.block_data
+            .get(&block)
+            .into_iter()
+            .flat_map(|data| virt_regs(&data.live_in))
+    }
+
+    /// Returns the virtual registers live on exit from `block`.
+    pub fn live_out(&self, block: BasicBlockId) -> impl Iterator<Item = VReg> + '_ {
+        self.block_data
+            .get(&block)
+            .into_iter()
+            .flat_map(|data| virt_regs(&data.live_out))
+    }
+
+    pub fn is_live_in(&self, block: BasicBlockId, vreg: VReg) -> bool {
+        self.block_data
+            .get(&block)
+            .is_some_and(|data| data.live_in.contains(&Reg::Virt(vreg)))
+    }
+
+    pub fn is_live_out(&self, block: BasicBlockId, vreg: VReg) -> bool {
+        self.block_data
+            .get(&block)
+            .is_some_and(|data| data.live_out.contains(&Reg::Virt(vreg)))
+    }
+
+    /// Returns true if `vreg` is read or written by `inst`, or holds a value
+    /// across it.
+    pub fn is_live_at(&self, vreg: VReg, inst: InstructionId<<T::InstInfo as II>::Data>) -> bool {
+        match (self.vreg_lrs_map.get(&vreg), self.program_point(inst)) {
+            (Some(lr), Some(pp)) => lr.contains(pp),
+            _ => false,
+        }
+    }
+
+    /// Returns true if `vreg` holds a value defined before `inst` that is
+    /// used after it, i.e. that must survive `inst`, e.g. a call.
+    pub fn is_live_across(
+        &self,
+        vreg: VReg,
+        inst: InstructionId<<T::InstInfo as II>::Data>,
+    ) -> bool {
+        match (self.vreg_lrs_map.get(&vreg), self.program_point(inst)) {
+            (Some(lr), Some(pp)) => lr.interfere_with_segment(&LiveSegment::new_point(pp)),
+            _ => false,
+        }
+    }
+
+    /// Returns the virtual registers live at `inst` (see [`Self::is_live_at`]),
+    /// in no particular order.
+    pub fn live_vregs_at(&self, inst: InstructionId<<T::InstInfo as II>::Data>) -> Vec<VReg> {
+        let Some(pp) = self.program_point(inst) else {
+            return vec![];
+        };
+        self.vreg_lrs_map
+            .iter()
+            .filter(|(_, lr)| lr.contains(pp))
+            .map(|(&vreg, _)| vreg)
+            .collect()
+    }
+
+    /// Returns true if the physical register `reg` is read or written by
+    /// `inst`, or holds a value across it.
+    pub fn is_reg_live_at(
+        &self,
+        reg: RegUnit,
+        inst: InstructionId<<T::InstInfo as II>::Data>,
+    ) -> bool {
+        match (self.reg_lrs_map.get(&reg), self.program_point(inst)) {
+            (Some(lr), Some(pp)) => lr.contains(pp),
+            _ => false,
+        }
+    }
+
     pub fn interfere(&self, reg: RegUnit, vreg: VReg) -> bool {
         let reg_lr = match self.reg_lrs_map.get(&reg) {
             Some(lr) => lr,
@@ -322,7 +399,7 @@ impl<T: TargetIsa> Liveness<T> {
                     .entry(reg)
                     .or_insert_with(|| LiveRange(vec![]))
                     .0
-                    .extend(local_lr.0.into_iter())
+                    .extend(local_lr.0)
             }
         }
     }
@@ -337,9 +414,7 @@ impl<T: TargetIsa> Liveness<T> {
 
     fn set_def(&mut self, func: &Function<T>) {
         for block_id in func.layout.block_iter() {
-            self.block_data
-                .entry(block_id)
-                .or_insert_with(BlockData::new);
+            self.block_data.entry(block_id).or_default();
             for inst_id in func.layout.inst_iter(block_id) {
                 let inst = func.data.inst_ref(inst_id);
                 self.set_def_on_inst(inst, block_id);
@@ -355,14 +430,14 @@ impl<T: TargetIsa> Liveness<T> {
         for output in inst.data.output_vregs() {
             self.block_data
                 .entry(block_id)
-                .or_insert_with(BlockData::new)
+                .or_default()
                 .def
                 .insert(Reg::Virt(output));
         }
         for output in inst.data.output_regs() {
             self.block_data
                 .entry(block_id)
-                .or_insert_with(BlockData::new)
+                .or_default()
                 .def
                 .insert(Reg::Phys(T::RegInfo::to_reg_unit(output)));
         }
@@ -370,9 +445,10 @@ impl<T: TargetIsa> Liveness<T> {
 
     fn visit(&mut self, func: &Function<T>) {
         for block_id in func.layout.block_iter() {
+            let mut defined = FxHashSet::default();
             for inst_id in func.layout.inst_iter(block_id) {
                 let inst = func.data.inst_ref(inst_id);
-                self.visit_inst(func, inst, block_id);
+                self.visit_inst(func, inst, block_id, &mut defined);
             }
         }
     }
@@ -382,26 +458,49 @@ impl<T: TargetIsa> Liveness<T> {
         func: &Function<T>,
         inst: &Instruction<<T::InstInfo as II>::Data>,
         block_id: BasicBlockId,
+        defined: &mut FxHashSet<Reg>,
     ) {
-        for input in inst.data.input_vregs() {
-            self.propagate_reg(func, Reg::Virt(input), block_id);
-        }
-        for input in inst.data.input_regs() {
-            self.propagate_reg(func, Reg::Phys(T::RegInfo::to_reg_unit(input)), block_id);
+        let inputs = inst.data.input_vregs().into_iter().map(Reg::Virt).chain(
+            inst.data
+                .input_regs()
+                .into_iter()
+                .map(|r| Reg::Phys(T::RegInfo::to_reg_unit(r))),
+        );
+        for input in inputs {
+            // A physical register may be read before it is written in the same
+            // block, e.g. an argument register that is reused for the result.
+            match input {
+                Reg::Phys(_) if !defined.contains(&input) => {
+                    self.propagate_live_in(func, input, block_id)
+                }
+                _ => self.propagate_reg(func, input, block_id),
+            }
         }
+        let outputs = inst.data.output_vregs().into_iter().map(Reg::Virt).chain(
+            inst.data
+                .output_regs()
+                .into_iter()
+                .map(|r| Reg::Phys(T::RegInfo::to_reg_unit(r))),
+        );
+        defined.extend(outputs);
     }
 
     fn propagate_reg(&mut self, func: &Function<T>, input: Reg, block_id: BasicBlockId) {
-        {
-            let data = self.block_data.get_mut(&block_id).unwrap();
-
-            if data.def.contains(&input) {
-                return;
-            }
+        if self.block_data[&block_id].def.contains(&input) {
+            return;
+        }
+        self.propagate_live_in(func, input, block_id)
+    }
 
-            if !data.live_in.insert(input) {
-                return;
-            }
+    fn propagate_live_in(&mut self, func: &Function<T>, input: Reg, block_id: BasicBlockId) {
+        if !self
+            .block_data
+            .get_mut(&block_id)
+            .unwrap()
+            .live_in
+            .insert(input)
+        {
+            return;
         }
 
         for pred_id in &func.data.basic_blocks[block_id].preds {
@@ -418,9 +517,21 @@ impl<T: TargetIsa> Liveness<T> {
     }
 }
 
+fn virt_regs(regs: &FxHashSet<Reg>) -> impl Iterator<Item = VReg> + '_ {
+    regs.iter().filter_map(|reg| match reg {
+        Reg::Virt(vreg) => Some(*vreg),
+        Reg::Phys(_) => None,
+    })
+}
+
 impl LiveRange {
     pub fn first_seg(&self) -> Option<&LiveSegment> {
-        self.0.get(0)
+        self.0.first()
+    }
+
+    /// Returns true if a segment of the range contains `pp`, its ends included.
+    pub fn contains(&self, pp: ProgramPoint) -> bool {
+        self.0.iter().any(|seg| seg.contains(pp))
     }
 
     pub fn interfere(&self, other: &Self) -> bool {
@@ -446,7 +557,7 @@ impl LiveRange {
     // Assume self doesn't interfere with other
     pub fn merge(&mut self, other: &Self) {
         self.0.append(&mut other.0.clone());
-        self.0.sort_by(|x, y| x.start.cmp(&y.start));
+        self.0.sort_by_key(|x| x.start);
     }
 }
 
@@ -458,15 +569,9 @@ impl LiveSegment {
     pub fn interfere(&self, other: &Self) -> bool {
         self.start < other.end && self.end > other.start
     }
-}
 
-impl Default for BlockData {
-    fn default() -> Self {
-        Self {
-            def: FxHashSet::default(),
-            live_in: FxHashSet::default(),
-            live_out: FxHashSet::default(),
-        }
+    pub fn contains(&self, pp: ProgramPoint) -> bool {
+        self.start <= pp && pp <= self.end
     }
 }
 
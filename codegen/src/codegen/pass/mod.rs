@@ -1,3 +1,4 @@
 pub mod liveness;
+pub mod private_symbols;
 pub mod regalloc;
 pub mod spiller;
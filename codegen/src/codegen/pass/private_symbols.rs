@@ -0,0 +1,82 @@
+//! Renames the globals of private linkage with the `.L` prefix, which ELF
+//! assemblers keep out of the symbol tables of object files.
+//!
+//! Runs after the passes deciding how globals are referred to by linkage, as
+//! they look globals up by name.
+
+use crate::codegen::{
+    function::instruction::InstructionData, isa::TargetIsa, module::Module, object::symbol_name,
+};
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use vicis_core::ir::{
+    module::{linkage::Linkage, name::Name},
+    value::{ConstantData, ConstantExpr},
+};
+
+pub fn run_on_module<T: TargetIsa>(module: &mut Module<T>) -> Result<()> {
+    let mut renamed = FxHashMap::default();
+    for gv in module.global_variables.values() {
+        if matches!(gv.linkage, Some(Linkage::Private)) {
+            let name = symbol_name(&gv.name);
+            renamed.insert(name.clone(), format!(".L{}", name));
+        }
+    }
+    for (_, func) in &module.functions {
+        if matches!(func.linkage, Linkage::Private) {
+            renamed.insert(func.name.clone(), format!(".L{}", func.name));
+        }
+    }
+    if renamed.is_empty() {
+        return Ok(());
+    }
+    let rename = |name: &str| renamed.get(name).cloned();
+
+    module.global_variables = std::mem::take(&mut module.global_variables)
+        .into_values()
+        .map(|mut gv| {
+            if let Some(new) = rename(&symbol_name(&gv.name)) {
+                gv.name = Name::from(new);
+            }
+            if let Some(init) = &mut gv.init {
+                rename_constant(init, &rename);
+            }
+            (gv.name, gv)
+        })
+        .collect();
+
+    for (_, func) in &mut module.functions {
+        if let Some(new) = rename(&func.name) {
+            func.name = new;
+        }
+        for block in func.layout.block_iter() {
+            for inst in func.layout.inst_iter(block) {
+                func.data.inst_ref_mut(inst).data.rename_symbols(&rename);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn rename_constant(konst: &mut ConstantData, rename: &dyn Fn(&str) -> Option<String>) {
+    match konst {
+        ConstantData::GlobalRef(name) => {
+            if let Some(new) = rename(&symbol_name(name)) {
+                *name = Name::from(new);
+            }
+        }
+        ConstantData::Array(arr) => arr
+            .elems
+            .iter_mut()
+            .for_each(|elem| rename_constant(elem, rename)),
+        ConstantData::Struct(s) => s
+            .elems
+            .iter_mut()
+            .for_each(|elem| rename_constant(elem, rename)),
+        ConstantData::Expr(ConstantExpr::GetElementPtr { args, .. }) => {
+            args.iter_mut().for_each(|arg| rename_constant(arg, rename))
+        }
+        ConstantData::Expr(ConstantExpr::Bitcast { arg, .. }) => rename_constant(arg, rename),
+        _ => {}
+    }
+}
@@ -38,7 +38,7 @@ pub fn run_on_function<T: TargetIsa>(function: &mut Function<T>) {
         let mut new_vregs = vec![];
         spiller::Spiller::new(function, &mut liveness).spill(vreg, &mut new_vregs);
         all_vregs.remove(&vreg);
-        all_vregs.extend(new_vregs.into_iter())
+        all_vregs.extend(new_vregs)
     }
 
     let preferred = collect_preferred_registers(function, &all_vregs);
@@ -110,11 +110,9 @@ pub fn collect_vregs_alive_around_call<T: TargetIsa>(
             if !inst.data.is_call() {
                 continue;
             }
-            let call_lr = liveness::LiveSegment::new_point(liveness.inst_to_pp[&inst_id]);
-            for vreg in all_vregs {
-                let vreg_lrs = &liveness.vreg_lrs_map[vreg];
-                if vreg_lrs.interfere_with_segment(&call_lr) {
-                    output.push(*vreg);
+            for &vreg in all_vregs {
+                if liveness.is_live_across(vreg, inst_id) {
+                    output.push(vreg);
                 }
             }
         }
@@ -154,7 +152,7 @@ pub fn find_preferred_registers<T: TargetIsa>(function: &Function<T>, vreg: VReg
             let regs = inst.data.output_vregs();
             if !regs.is_empty() {
                 let dst = regs[0]; // TODO: Why do we know the first element of `regs` is destination?
-                list.extend(find_preferred_registers(function, dst).into_iter());
+                list.extend(find_preferred_registers(function, dst));
             }
         }
     }
@@ -1,14 +1,15 @@
 use super::liveness::{Liveness, ProgramPoint};
 use crate::codegen::{
     function::{
-        basic_block::BasicBlockId,
+        basic_block::{BasicBlock, BasicBlockId},
         instruction::{InstructionData as ID, InstructionId, InstructionInfo as II},
-        slot::SlotId,
+        slot::{SlotId, SlotOrigin},
         Function,
     },
     isa::TargetIsa,
     register::VReg,
 };
+use vicis_core::pass::analysis::block_freq::BlockFrequency;
 
 pub struct Spiller<'a, T: TargetIsa> {
     function: &'a mut Function<T>,
@@ -21,12 +22,14 @@ impl<'a, T: TargetIsa> Spiller<'a, T> {
     }
 
     pub fn spill(&mut self, vreg: VReg, new_vregs: &mut Vec<VReg>) {
+        // The slot is of the size of the type, which the target stores and
+        // reloads it by.
         let ty = self.function.data.vregs.type_for(vreg);
-        assert!(ty.is_i32());
-        let slot = self
-            .function
-            .slots
-            .add_slot(ty, T::type_size(&self.function.types, ty));
+        let slot = self.function.slots.add_slot(
+            ty,
+            T::type_size(&self.function.types, ty),
+            SlotOrigin::Spill(vreg),
+        );
 
         self.insert_spill(vreg, slot, new_vregs);
         self.insert_reload(vreg, slot, new_vregs);
@@ -56,7 +59,7 @@ impl<'a, T: TargetIsa> Spiller<'a, T> {
 
         // Most cases
         if defs.len() == 1 {
-            let def_id = *defs.get(0).unwrap();
+            let def_id = *defs.first().unwrap();
             let def_block;
             {
                 let inst = &mut self.function.data.instructions[def_id];
@@ -148,3 +151,20 @@ impl<'a, T: TargetIsa> Spiller<'a, T> {
         self.function.layout.insert_inst_before(before, inst, block);
     }
 }
+
+/// Estimates the cost of spilling `vreg` as the number of times its
+/// definitions and uses are expected to run. Registers used in hot loops
+/// are the last to be spilled.
+pub fn spill_weight<T: TargetIsa>(
+    function: &Function<T>,
+    freq: &BlockFrequency<BasicBlock>,
+    vreg: VReg,
+) -> f64 {
+    function
+        .data
+        .vreg_users
+        .get(vreg)
+        .iter()
+        .map(|user| freq.frequency(function.data.inst_ref(user.inst_id).parent))
+        .sum()
+}
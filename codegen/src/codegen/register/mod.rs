@@ -14,6 +14,7 @@ pub struct RegUnit(pub u16, pub u16); // Same as top-level register
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct VReg(pub u32);
 
+#[derive(Default)]
 pub struct VRegs {
     pub map: FxHashMap<VReg, VRegData>,
     pub cur: u32,
@@ -53,15 +54,6 @@ impl RegUnit {
     }
 }
 
-impl Default for VRegs {
-    fn default() -> Self {
-        Self {
-            map: FxHashMap::default(),
-            cur: 0,
-        }
-    }
-}
-
 impl VRegs {
     pub fn new() -> Self {
         Self::default()
@@ -103,14 +95,11 @@ impl<Data: ID> VRegUsers<Data> {
     }
 
     pub fn add_use(&mut self, vreg: VReg, inst_id: InstructionId<Data>, read: bool, write: bool) {
-        self.vreg_to_insts
-            .entry(vreg)
-            .or_insert_with(Vec::new)
-            .push(VRegUser {
-                inst_id,
-                read,
-                write,
-            })
+        self.vreg_to_insts.entry(vreg).or_default().push(VRegUser {
+            inst_id,
+            read,
+            write,
+        })
     }
 
     pub fn get(&self, vreg: VReg) -> &Vec<VRegUser<Data>> {
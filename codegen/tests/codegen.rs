@@ -1,8 +1,60 @@
-use vicis_codegen::codegen::{isa::x86_64::X86_64, lower::compile_module};
-use vicis_core::ir::module;
+use std::fmt;
+use vicis_codegen::codegen::{
+    cache::{compile_module_cached, CodegenCache},
+    isa::{aarch64::AArch64, i686::I686, wasm32::Wasm32, x86_64::X86_64, TargetIsa},
+    lower::{compile_module, compile_module_with_options},
+    module::Module as MachModule,
+    options::{AsmSyntax, CodegenOptions},
+};
+use vicis_core::{ir::module, pass::analysis::block_freq::BlockFrequency};
 
 #[test]
 fn compile_tests() {
+    compile_tests_for(X86_64, &CodegenOptions::new(), "./tests/codegen", "s");
+}
+
+#[test]
+fn compile_tests_att() {
+    let options = CodegenOptions::new().with_asm_syntax(AsmSyntax::Att);
+    compile_tests_for(X86_64, &options, "./tests/codegen/att", "s");
+}
+
+#[test]
+fn compile_tests_aarch64() {
+    compile_tests_for(
+        AArch64,
+        &CodegenOptions::new(),
+        "./tests/codegen/aarch64",
+        "s",
+    );
+}
+
+#[test]
+fn compile_tests_i686() {
+    compile_tests_for(I686, &CodegenOptions::new(), "./tests/codegen/i686", "s");
+}
+
+#[test]
+fn compile_tests_wasm32() {
+    compile_tests_for(
+        Wasm32,
+        &CodegenOptions::new(),
+        "./tests/codegen/wasm32",
+        "wat",
+    );
+}
+
+/// Compiles every `.ll` file in `./tests/codegen` for `isa` with `options` and
+/// compares the assembly with the file of the same name and extension
+/// `expected_ext` in `expected_dir`.
+fn compile_tests_for<T: TargetIsa>(
+    isa: T,
+    options: &CodegenOptions<T>,
+    expected_dir: &str,
+    expected_ext: &str,
+) where
+    MachModule<T>: fmt::Display,
+{
     use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
     use std::fs;
 
@@ -15,18 +67,24 @@ fn compile_tests() {
     pb.set_style(ProgressStyle::default_bar().template("{bar:60} {pos:>4}/{len:>4} {msg}"));
 
     for path in paths {
-        let input = path.as_ref().unwrap().path().to_str().unwrap().to_string();
+        let path = path.unwrap().path();
+        let input = path.to_str().unwrap().to_string();
         if !input.ends_with(".ll") {
             continue;
         }
-        let output = format!("{}.s", input.trim_end_matches(".ll"));
+        let output = format!(
+            "{}/{}.{}",
+            expected_dir,
+            path.file_stem().unwrap().to_str().unwrap(),
+            expected_ext
+        );
         pb.set_message(input.as_str());
 
         let input_body = &fs::read_to_string(&input).unwrap();
         let output_body = &fs::read_to_string(output).unwrap();
 
         let module = module::parse_assembly(input_body).unwrap();
-        let mach_module = compile_module(X86_64, &module).unwrap();
+        let mach_module = compile_module_with_options(isa, &module, options).unwrap();
 
         assert_eq!(
             &format!("{}", mach_module),
@@ -40,3 +98,2569 @@ fn compile_tests() {
 
     pb.finish();
 }
+
+#[test]
+fn extra_module_pass() {
+    let module = module::parse_assembly(
+        r#"
+define dso_local i32 @main() {
+  ret i32 0
+}"#,
+    )
+    .unwrap();
+    let options = CodegenOptions::new().with_module_pass(|module| {
+        for (_, func) in &mut module.functions {
+            func.name = format!("instrumented_{}", func.name);
+        }
+        Ok(())
+    });
+    let mach_module = compile_module_with_options(X86_64, &module, &options).unwrap();
+    assert!(format!("{}", mach_module).contains("instrumented_main:"));
+}
+
+#[test]
+fn cached_compilation() {
+    let source = |ret: i32| {
+        format!(
+            r#"
+declare dso_local i32 @g(i32)
+
+define dso_local i32 @f(i32 %0) {{
+  %2 = add i32 %0, 1
+  ret i32 %2
+}}
+
+define dso_local i32 @main() {{
+  %1 = call i32 @g(i32 {})
+  ret i32 %1
+}}"#,
+            ret
+        )
+    };
+    let dir = std::env::temp_dir().join(format!("vicis-codegen-cache-{}", std::process::id()));
+    let options = CodegenOptions::new();
+
+    let module = module::parse_assembly(&source(1)).unwrap();
+    let expected = format!("{}", compile_module(X86_64, &module).unwrap());
+    let mut cache = CodegenCache::open(&dir).unwrap();
+    let mach_module = compile_module_cached(X86_64, &module, &options, &mut cache).unwrap();
+    assert_eq!(format!("{}", mach_module), expected);
+    assert_eq!((cache.hits(), cache.misses()), (0, 2));
+
+    // A fresh cache opened on the same directory reuses the unchanged @f.
+    let module = module::parse_assembly(&source(2)).unwrap();
+    let expected = format!("{}", compile_module(X86_64, &module).unwrap());
+    let mut cache = CodegenCache::open(&dir).unwrap();
+    let mach_module = compile_module_cached(X86_64, &module, &options, &mut cache).unwrap();
+    assert_eq!(format!("{}", mach_module), expected);
+    assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+    // Nothing is reused once the options change.
+    let options = CodegenOptions::new().with_cfi(true);
+    let expected = format!(
+        "{}",
+        compile_module_with_options(X86_64, &module, &options).unwrap()
+    );
+    let mut cache = CodegenCache::open(&dir).unwrap();
+    let mach_module = compile_module_cached(X86_64, &module, &options, &mut cache).unwrap();
+    assert_eq!(format!("{}", mach_module), expected);
+    assert_eq!((cache.hits(), cache.misses()), (0, 2));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cached_compilation_in_both_syntaxes() {
+    let module = module::parse_assembly(
+        r#"
+define dso_local i32 @f(i32 %0) {
+  %2 = add i32 %0, 1
+  ret i32 %2
+}"#,
+    )
+    .unwrap();
+    let mut cache = CodegenCache::new();
+    for syntax in [AsmSyntax::Intel, AsmSyntax::Att] {
+        let options = CodegenOptions::new().with_asm_syntax(syntax);
+        let expected = format!(
+            "{}",
+            compile_module_with_options(X86_64, &module, &options).unwrap()
+        );
+        let mach_module = compile_module_cached(X86_64, &module, &options, &mut cache).unwrap();
+        assert_eq!(format!("{}", mach_module), expected);
+    }
+    assert_eq!((cache.hits(), cache.misses()), (0, 2));
+}
+
+#[test]
+fn block_frequency() {
+    let module =
+        module::parse_assembly(&std::fs::read_to_string("./tests/codegen/sum.ll").unwrap())
+            .unwrap();
+    let mach_module = compile_module(X86_64, &module).unwrap();
+    let (_, main) = mach_module.functions.iter().next().unwrap();
+    let freq = BlockFrequency::new(main);
+    let freqs: Vec<f64> = main
+        .layout
+        .block_iter()
+        .map(|block| freq.frequency(block))
+        .collect();
+    // The loop exits one time out of eight, so it is expected to run 8 times.
+    assert_eq!(freqs, vec![1.0, 8.0, 7.0, 7.0, 1.0]);
+}
+
+/// A scratch directory to build and run programs in with the host toolchain,
+/// removed when dropped.
+struct Native {
+    dir: std::path::PathBuf,
+}
+
+impl Native {
+    /// Creates the directory of the test `name`. If `tool` can't be run, the
+    /// test fails, unless `VICIS_SKIP_NATIVE_TESTS` is set, in which case
+    /// `None` is returned for the test to skip running anything.
+    fn new(name: &str, tool: &str) -> Option<Self> {
+        if std::process::Command::new(tool)
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            if std::env::var_os("VICIS_SKIP_NATIVE_TESTS").is_some() {
+                eprintln!("skipping the native part of {}: no {}", name, tool);
+                return None;
+            }
+            panic!(
+                "{} needs {} to run; set VICIS_SKIP_NATIVE_TESTS to skip it",
+                name, tool
+            );
+        }
+        let dir =
+            std::env::temp_dir().join(format!("vicis-codegen-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Some(Self { dir })
+    }
+
+    fn path(&self, file: &str) -> std::path::PathBuf {
+        self.dir.join(file)
+    }
+
+    fn write(&self, file: &str, contents: impl AsRef<[u8]>) {
+        std::fs::write(self.path(file), contents).unwrap();
+    }
+
+    /// Builds the files `inputs` with `compiler` and `flags` into an
+    /// executable, and returns its path.
+    fn build(&self, compiler: &str, inputs: &[&str], flags: &[&str]) -> std::path::PathBuf {
+        let exe = self.path("a.out");
+        let status = std::process::Command::new(compiler)
+            .args(inputs.iter().map(|input| self.path(input)))
+            .args(flags)
+            .arg("-o")
+            .arg(&exe)
+            .status()
+            .unwrap();
+        assert!(status.success(), "failed to build {:?}", inputs);
+        exe
+    }
+
+    /// Builds `inputs` with `cc` and runs the executable.
+    fn run(&self, inputs: &[&str], flags: &[&str]) -> std::process::Output {
+        let exe = self.build("cc", inputs, flags);
+        std::process::Command::new(exe).output().unwrap()
+    }
+
+    /// Builds `inputs` with `cc` and checks that the executable exits with
+    /// `expected`.
+    fn assert_exit_code(&self, inputs: &[&str], flags: &[&str], expected: i32) {
+        let status = self.run(inputs, flags).status;
+        assert_eq!(
+            status.code(),
+            Some(expected),
+            "wrong exit code for {:?}",
+            inputs
+        );
+    }
+}
+
+impl Drop for Native {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Checks that `module` exits with `expected` when compiled for x86-64 in
+/// both syntaxes and when encoded to an object file.
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn assert_exit_code_in_all_forms(name: &str, module: &module::Module, expected: i32) {
+    use vicis_codegen::codegen::{isa::x86_64::encode::encode_module, object::elf};
+
+    let Some(native) = Native::new(name, "cc") else {
+        return;
+    };
+    let intel = compile_module(X86_64, module).unwrap();
+    let att = compile_module_with_options(
+        X86_64,
+        module,
+        &CodegenOptions::new().with_asm_syntax(AsmSyntax::Att),
+    )
+    .unwrap();
+    elf::write_to_file(&encode_module(&intel).unwrap(), native.path("obj.o")).unwrap();
+    native.write("intel.s", intel.to_string());
+    native.write("att.s", att.to_string());
+    for input in ["obj.o", "intel.s", "att.s"] {
+        native.assert_exit_code(&[input], &[], expected);
+    }
+}
+
+/// Compiles `module` for the host.
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn compile_native(module: &module::Module) -> String {
+    format!("{}", compile_module(X86_64, module).unwrap())
+}
+
+/// Compiles `module` for the host.
+#[cfg(all(target_arch = "aarch64", target_os = "linux"))]
+fn compile_native(module: &module::Module) -> String {
+    format!("{}", compile_module(AArch64, module).unwrap())
+}
+
+/// Assembles and runs recursive programs, comparing their exit codes with
+/// those of the C programs they were compiled from, built with `cc`.
+#[test]
+#[cfg(all(
+    any(target_arch = "x86_64", target_arch = "aarch64"),
+    target_os = "linux"
+))]
+fn recursive_exit_codes() {
+    let Some(native) = Native::new("recursive_exit_codes", "cc") else {
+        return;
+    };
+    for name in ["fibo", "ack", "even_odd"] {
+        let c = format!("{}.c", name);
+        native.write(&c, std::fs::read(format!("./tests/codegen/c/{}", c)).unwrap());
+        let expected = native.run(&[&c], &[]).status.code().unwrap();
+
+        let source = std::fs::read_to_string(format!("./tests/codegen/{}.ll", name)).unwrap();
+        let module = module::parse_assembly(&source).unwrap();
+        let asm = format!("{}.s", name);
+        native.write(&asm, compile_native(&module));
+        native.assert_exit_code(&[&asm], &[], expected);
+    }
+}
+
+/// Assembles the expected AArch64 output of every test, which other hosts
+/// can't run.
+#[test]
+fn aarch64_assembly() {
+    let Some(native) = Native::new("aarch64_assembly", "llvm-mc") else {
+        return;
+    };
+    for entry in std::fs::read_dir("./tests/codegen/aarch64").unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        native.write(name, std::fs::read(&path).unwrap());
+        native.build(
+            "llvm-mc",
+            &[name],
+            &["-triple=aarch64-linux-gnu", "-filetype=obj"],
+        );
+    }
+}
+
+/// Compiles 64-bit arithmetic, bytes in memory, loads through pointers and a
+/// pointer live across calls for AArch64, assembles the output, and runs it on
+/// AArch64 hosts.
+#[test]
+fn aarch64_lowering() {
+    let source = r#"
+@.str = private unnamed_addr constant [3 x i8] c"hi\00", align 1
+@n = global [1 x i32] [i32 5], align 4
+
+declare i32 @puts(i8*)
+
+define void @twice(i8* %s) {
+  %1 = call i32 @puts(i8* %s)
+  %2 = call i32 @puts(i8* %s)
+  ret void
+}
+
+define i64 @wide(i64 %a, i64 %b) {
+  %c = mul i64 %a, %b
+  %d = sdiv i64 %c, 3
+  %e = shl i64 %d, 4
+  %f = lshr i64 %e, 2
+  %g = sub i64 %f, 4294967296
+  ret i64 %g
+}
+
+define i32 @bytes(i32 %x) {
+  %p = alloca i8, align 1
+  %t = trunc i32 %x to i8
+  store i8 %t, i8* %p, align 1
+  %v = load i8, i8* %p, align 1
+  %s = sext i8 %v to i32
+  ret i32 %s
+}
+
+define i32 @deref(i32* %p) {
+  %v = load i32, i32* %p, align 4
+  %w = shl i32 %v, %v
+  ret i32 %w
+}
+
+define i32 @main() {
+  call void @twice(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.str, i64 0, i64 0))
+  %w = call i64 @wide(i64 6, i64 7)
+  %b = call i32 @bytes(i32 200)
+  %d = call i32 @deref(i32* getelementptr inbounds ([1 x i32], [1 x i32]* @n, i64 0, i64 0))
+  %1 = icmp eq i64 %w, -4294967240
+  br i1 %1, label %bytes, label %fail
+bytes:
+  %2 = icmp eq i32 %b, -56
+  br i1 %2, label %deref, label %fail
+deref:
+  %3 = icmp eq i32 %d, 160
+  br i1 %3, label %ok, label %fail
+ok:
+  ret i32 42
+fail:
+  ret i32 1
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let asm = compile_module(AArch64, &module).unwrap().to_string();
+    for inst in [
+        // `%s` is spilled across the first call.
+        "  str x9, [sp",
+        "  ldr x9, [sp",
+        "  sdiv x",
+        "  lsl x",
+        "  lsr x",
+        "  strb w",
+        "  ldrb w",
+        "  sxtb w",
+        "  ldr w9, [x9]",
+        "  cmp x",
+    ] {
+        assert!(asm.contains(inst), "no {}", inst.trim());
+    }
+
+    if let Some(native) = Native::new("aarch64_lowering", "llvm-mc") {
+        native.write("lowering.s", &asm);
+        native.build(
+            "llvm-mc",
+            &["lowering.s"],
+            &["-triple=aarch64-linux-gnu", "-filetype=obj"],
+        );
+    }
+
+    #[cfg(all(target_arch = "aarch64", target_os = "linux"))]
+    {
+        let Some(native) = Native::new("aarch64_lowering_run", "cc") else {
+            return;
+        };
+        native.write("lowering.s", &asm);
+        let output = native.run(&["lowering.s"], &[]);
+        assert_eq!(output.status.code(), Some(42));
+        assert_eq!(output.stdout, b"hi\nhi\n");
+    }
+}
+
+#[test]
+fn code_cache_round_trip() {
+    use vicis_codegen::codegen::code_cache::{
+        CodeCache, CodeCacheError, CompiledFunction, RelocKind, Relocation, TargetInfo,
+    };
+
+    let module = module::parse_assembly(
+        r#"
+declare dso_local i32 @g(i32)
+
+define dso_local i32 @f(i32 %0) {
+  %2 = call i32 @g(i32 %0)
+  ret i32 %2
+}"#,
+    )
+    .unwrap();
+    let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+    let target = TargetInfo::new("x86_64-pc-linux-gnu", &["sse2", "avx"]);
+
+    let mut cache = CodeCache::new(target.clone());
+    let g = cache.symbol("g");
+    cache.insert(CompiledFunction {
+        name: "f".into(),
+        ir_hash: func.structural_hash(),
+        // call g; ret
+        code: vec![0xe8, 0, 0, 0, 0, 0xc3],
+        relocs: vec![Relocation {
+            offset: 1,
+            kind: RelocKind::Call4,
+            symbol: g,
+            addend: -4,
+        }],
+    });
+
+    let path = std::env::temp_dir().join(format!("vicis-code-cache-{}", std::process::id()));
+    cache.save(&path).unwrap();
+    let loaded = CodeCache::load(&path, &target).unwrap();
+    assert_eq!(loaded, cache);
+    assert_eq!(
+        loaded.to_string(),
+        "target: x86_64-pc-linux-gnu [avx,sse2]\n\
+         \nf:\n  0000  e8 00 00 00 00 c3\n          0001  call4 g-4\n"
+    );
+    assert_eq!(loaded.get_for(func).unwrap().code.len(), 6);
+    assert!(loaded.get("f", func.structural_hash() ^ 1).is_none());
+
+    let other = TargetInfo::new("x86_64-pc-linux-gnu", &["sse2"]);
+    assert!(matches!(
+        CodeCache::load(&path, &other),
+        Err(CodeCacheError::TargetMismatch { .. })
+    ));
+
+    let mut bytes = std::fs::read(&path).unwrap();
+    let last = bytes.len() - 9;
+    bytes[last] ^= 1;
+    assert!(matches!(
+        CodeCache::read(bytes.as_slice()),
+        Err(CodeCacheError::Corrupt)
+    ));
+    bytes[4] = 0xff;
+    assert!(matches!(
+        CodeCache::read(bytes.as_slice()),
+        Err(CodeCacheError::UnsupportedVersion(_))
+    ));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+/// Writes hand-encoded code along with the data of global variables to ELF
+/// and Mach-O objects, and links the ELF one into a program printing through a
+/// relocated pointer.
+#[test]
+fn object_files() {
+    use vicis_codegen::codegen::{
+        code_cache::{CompiledFunction, RelocKind, Relocation},
+        object::{elf, macho, Binding, DataKind, ObjectModule},
+    };
+
+    let module = module::parse_assembly(
+        r#"
+@.str = private unnamed_addr constant [12 x i8] c"hello world\00", align 1
+@answer = global i32 42, align 4
+@p = global i8* getelementptr inbounds ([12 x i8], [12 x i8]* @.str, i64 0, i64 6), align 8
+@zero = global i32 0, align 4"#,
+    )
+    .unwrap();
+    let mach_module = compile_module(X86_64, &module).unwrap();
+
+    let symbols: Vec<String> = ["p", "puts", "answer", "zero"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let pcrel = |offset, symbol, kind| Relocation {
+        offset,
+        kind,
+        symbol,
+        addend: -4,
+    };
+    let main = CompiledFunction {
+        name: "main".into(),
+        ir_hash: 0,
+        code: vec![
+            0x48, 0x83, 0xec, 0x08, // sub rsp, 8
+            0x48, 0x8b, 0x3d, 0, 0, 0, 0, // mov rdi, [rip + p]
+            0xe8, 0, 0, 0, 0, // call puts
+            0x8b, 0x05, 0, 0, 0, 0, // mov eax, [rip + answer]
+            0x03, 0x05, 0, 0, 0, 0, // add eax, [rip + zero]
+            0x48, 0x83, 0xc4, 0x08, // add rsp, 8
+            0xc3, // ret
+        ],
+        relocs: vec![
+            pcrel(7, 0, RelocKind::PcRel4),
+            pcrel(12, 1, RelocKind::Call4),
+            pcrel(18, 2, RelocKind::PcRel4),
+            pcrel(24, 3, RelocKind::PcRel4),
+        ],
+    };
+
+    let mut obj = ObjectModule::new();
+    obj.add_function(&main, &symbols, Binding::Global);
+    obj.add_global_variables(&mach_module).unwrap();
+    let kinds: Vec<_> = obj
+        .data()
+        .iter()
+        .map(|d| (d.name.as_str(), d.kind, d.binding))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            (".L.str", DataKind::ReadOnly, Binding::Local),
+            ("answer", DataKind::Data, Binding::Global),
+            ("p", DataKind::Data, Binding::Global),
+            ("zero", DataKind::Zeroed, Binding::Global),
+        ]
+    );
+    let p = &obj.data()[2];
+    assert_eq!(p.bytes, vec![0; 8]);
+    assert_eq!(p.relocs.len(), 1);
+    assert_eq!(p.relocs[0].kind, RelocKind::Abs8);
+    assert_eq!(obj.symbols()[p.relocs[0].symbol as usize], ".L.str");
+    assert_eq!(p.relocs[0].addend, 6);
+
+    let bytes = elf::write(&obj).unwrap();
+    assert_eq!(&bytes[..4], b"\x7fELF");
+    let note = b"\0.note.GNU-stack\0";
+    assert!(bytes.windows(note.len()).any(|w| w == note));
+    let bytes = macho::write(&obj).unwrap();
+    assert_eq!(&bytes[..4], &0xfeedfacf_u32.to_le_bytes());
+    let has_symbol = |name: &[u8]| bytes.windows(name.len()).any(|w| w == name);
+    assert!(has_symbol(b"\0_main\0") && has_symbol(b"\0_puts\0"));
+
+    if !cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        return;
+    }
+    let Some(native) = Native::new("object_files", "cc") else {
+        return;
+    };
+    elf::write_to_file(&obj, native.path("main.o")).unwrap();
+    let output = native.run(&["main.o"], &[]);
+    assert_eq!(output.status.code(), Some(42));
+    assert_eq!(output.stdout, b"world\n");
+}
+
+#[test]
+fn x86_64_encoding() {
+    use vicis_codegen::codegen::{
+        code_cache::RelocKind,
+        isa::x86_64::encode::{encode_function, encode_module},
+    };
+
+    let source = std::fs::read_to_string("./tests/codegen/puts.ll").unwrap();
+    let module = compile_module(X86_64, &module::parse_assembly(&source).unwrap()).unwrap();
+    let (_, main) = module.functions.iter().next().unwrap();
+    let mut symbols = vec![];
+    let (code, relocs) = encode_function(main, |name| {
+        symbols.push(name.to_owned());
+        symbols.len() as u32 - 1
+    })
+    .unwrap();
+    assert_eq!(
+        code,
+        vec![
+            0x55, // push rbp
+            0x48, 0x89, 0xe5, // mov rbp, rsp
+            0x48, 0x83, 0xec, 0x10, // sub rsp, 16
+            0xc7, 0x45, 0xfc, 0, 0, 0, 0, // mov dword ptr [rbp-4], 0
+            0x48, 0x8d, 0x3d, 0, 0, 0, 0, // lea rdi, [rip + .L.str]
+            0xe8, 0, 0, 0, 0, // call puts
+            0xb8, 0, 0, 0, 0, // mov eax, 0
+            0x48, 0x83, 0xc4, 0x10, // add rsp, 16
+            0x5d, // pop rbp
+            0xc3, // ret
+        ]
+    );
+    let relocs: Vec<_> = relocs
+        .iter()
+        .map(|r| (r.offset, r.kind, &*symbols[r.symbol as usize], r.addend))
+        .collect();
+    assert_eq!(
+        relocs,
+        vec![
+            (18, RelocKind::PcRel4, ".L.str", -4),
+            (23, RelocKind::Call4, "puts", -4),
+        ]
+    );
+
+    let obj = encode_module(&module).unwrap();
+    assert_eq!(obj.functions().count(), 1);
+    assert_eq!(obj.data()[0].name, ".L.str");
+}
+
+#[test]
+fn position_independent_code() {
+    use vicis_codegen::codegen::{
+        code_cache::RelocKind, isa::x86_64::encode::encode_module, options::RelocModel,
+    };
+
+    let source = r#"
+@.str = private unnamed_addr constant [6 x i8] c"hello\00", align 1
+@msg = global [4 x i8] c"abc\00", align 1
+
+declare dso_local i32 @puts(i8*)
+
+define dso_local i32 @f() {
+  ret i32 3
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @puts(i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.str, i64 0, i64 0))
+  %2 = call i32 @puts(i8* getelementptr inbounds ([4 x i8], [4 x i8]* @msg, i64 0, i64 0))
+  %3 = call i32 @f()
+  ret i32 %3
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let options = CodegenOptions::new().with_reloc_model(RelocModel::Pic);
+
+    let intel = compile_module_with_options(X86_64, &module, &options).unwrap();
+    let asm = format!("{}", intel);
+    assert!(asm.contains("lea rdi, [rip + .L.str]"));
+    assert!(asm.contains("mov rdi, qword ptr [rip + msg@GOTPCREL]"));
+    assert!(asm.contains("call puts@PLT"));
+    assert!(asm.contains("call f\n"));
+
+    let options = CodegenOptions::new()
+        .with_reloc_model(RelocModel::Pic)
+        .with_asm_syntax(AsmSyntax::Att);
+    let att = compile_module_with_options(X86_64, &module, &options).unwrap();
+    let asm = format!("{}", att);
+    assert!(asm.contains("leaq .L.str(%rip), %rdi"));
+    assert!(asm.contains("movq msg@GOTPCREL(%rip), %rdi"));
+    assert!(asm.contains("call puts@PLT"));
+
+    let obj = encode_module(&intel).unwrap();
+    let (main, _) = obj.functions().find(|(f, _)| f.name == "main").unwrap();
+    let relocs: Vec<_> = main
+        .relocs
+        .iter()
+        .map(|r| (r.kind, &*obj.symbols()[r.symbol as usize]))
+        .collect();
+    assert_eq!(
+        relocs,
+        vec![
+            (RelocKind::PcRel4, ".L.str"),
+            (RelocKind::Call4, "puts"),
+            (RelocKind::GotPcRel4, "msg"),
+            (RelocKind::Call4, "puts"),
+            (RelocKind::Call4, "f"),
+        ]
+    );
+
+    let options = CodegenOptions::new().with_reloc_model(RelocModel::Pic);
+    assert!(compile_module_with_options(I686, &module, &options).is_err());
+}
+
+#[test]
+fn code_models() {
+    use vicis_codegen::codegen::{
+        code_cache::RelocKind,
+        isa::x86_64::encode::encode_module,
+        options::{CodeModel, RelocModel},
+    };
+
+    let source = std::fs::read_to_string("./tests/codegen/puts.ll").unwrap();
+    let module = module::parse_assembly(&source).unwrap();
+    let compile = |model, reloc_model| {
+        let options = CodegenOptions::new()
+            .with_code_model(model)
+            .with_reloc_model(reloc_model);
+        compile_module_with_options(X86_64, &module, &options)
+    };
+    let relocs = |module: &MachModule<X86_64>| {
+        let obj = encode_module(module).unwrap();
+        let (main, _) = obj.functions().next().unwrap();
+        main.relocs
+            .iter()
+            .map(|r| (r.kind, obj.symbols()[r.symbol as usize].clone()))
+            .collect::<Vec<_>>()
+    };
+
+    let medium = compile(CodeModel::Medium, RelocModel::Static).unwrap();
+    let asm = format!("{}", medium);
+    assert!(asm.contains("movabs rdi, offset .L.str"));
+    assert!(asm.contains("call puts\n"));
+    assert_eq!(
+        relocs(&medium),
+        vec![
+            (RelocKind::Abs8, ".L.str".to_owned()),
+            (RelocKind::Call4, "puts".to_owned()),
+        ]
+    );
+
+    let large = compile(CodeModel::Large, RelocModel::Static).unwrap();
+    let asm = format!("{}", large);
+    assert!(asm.contains("movabs rdi, offset .L.str"));
+    assert!(asm.contains("movabs r11, offset puts\n  call r11"));
+    assert_eq!(
+        relocs(&large),
+        vec![
+            (RelocKind::Abs8, ".L.str".to_owned()),
+            (RelocKind::Abs8, "puts".to_owned()),
+        ]
+    );
+
+    let asm = format!("{}", compile(CodeModel::Medium, RelocModel::Pic).unwrap());
+    assert!(asm.contains("mov rdi, qword ptr [rip + .L.str@GOTPCREL]"));
+    assert!(compile(CodeModel::Large, RelocModel::Pic).is_err());
+}
+
+#[test]
+fn cfi_directives() {
+    let source = r#"
+define dso_local i32 @f(i32 %x) {
+  %c = icmp eq i32 %x, 0
+  br i1 %c, label %a, label %b
+a:
+  ret i32 1
+b:
+  ret i32 %x
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let lines = |asm: String| -> Vec<String> {
+        asm.lines()
+            .filter(|l| l.contains(".cfi") || l.contains("ret") || l.contains("bp"))
+            .map(|l| l.trim().to_owned())
+            .collect()
+    };
+
+    let plain = compile_module(X86_64, &module).unwrap();
+    assert!(!format!("{}", plain).contains(".cfi"));
+
+    let options = CodegenOptions::new().with_cfi(true);
+    let x86_64 = compile_module_with_options(X86_64, &module, &options).unwrap();
+    assert_eq!(
+        lines(format!("{}", x86_64)),
+        vec![
+            ".cfi_startproc",
+            "push rbp",
+            ".cfi_def_cfa_offset 16",
+            ".cfi_offset rbp, -16",
+            "mov rbp, rsp",
+            ".cfi_def_cfa_register rbp",
+            ".cfi_remember_state",
+            "pop rbp",
+            ".cfi_def_cfa rsp, 8",
+            "ret",
+            ".cfi_restore_state",
+            "pop rbp",
+            ".cfi_def_cfa rsp, 8",
+            "ret",
+            ".cfi_endproc",
+        ]
+    );
+
+    let options = CodegenOptions::new()
+        .with_cfi(true)
+        .with_asm_syntax(AsmSyntax::Att);
+    let i686 = compile_module_with_options(I686, &module, &options).unwrap();
+    let asm = format!("{}", i686);
+    assert!(asm.contains(".cfi_def_cfa_offset 8\n  .cfi_offset %ebp, -8\n"));
+    assert!(asm.contains(".cfi_def_cfa %esp, 4\n"));
+
+    let options = CodegenOptions::new().with_cfi(true);
+    let aarch64 = compile_module_with_options(AArch64, &module, &options).unwrap();
+    let asm = format!("{}", aarch64);
+    assert!(asm.contains(
+        "stp x29, x30, [sp, #-16]!\n  .cfi_def_cfa_offset 16\n  \
+         .cfi_offset x30, -8\n  .cfi_offset x29, -16\n  mov x29, sp\n  .cfi_def_cfa x29, 16\n"
+    ));
+    assert!(asm.contains("ldp x29, x30, [sp], #16\n  .cfi_def_cfa sp, 0\n"));
+    assert!(asm.contains(".cfi_endproc\n  .section .note.GNU-stack"));
+}
+
+/// Unwinds a C++ exception through a function whose landing pad runs a
+/// cleanup and resumes, and catches it in C++.
+#[test]
+fn exception_handling() {
+    let source = r#"
+declare void @thrower(i32)
+declare void @cleanup(i32)
+declare i32 @__gxx_personality_v0(...)
+
+define i32 @f(i32 %x) personality i8* bitcast (i32 (...)* @__gxx_personality_v0 to i8*) {
+entry:
+  invoke void @thrower(i32 %x) to label %ok unwind label %lpad
+ok:
+  ret i32 0
+lpad:
+  %lp = landingpad { i8*, i32 } cleanup
+  call void @cleanup(i32 7)
+  %exn = extractvalue { i8*, i32 } %lp, 0
+  %sel = extractvalue { i8*, i32 } %lp, 1
+  %1 = insertvalue { i8*, i32 } undef, i8* %exn, 0
+  %2 = insertvalue { i8*, i32 } %1, i32 %sel, 1
+  resume { i8*, i32 } %2
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let asm = format!("{}", compile_module(X86_64, &module).unwrap());
+    assert!(asm.contains(
+        ".cfi_startproc\n  .cfi_personality 155, DW.ref.__gxx_personality_v0\n  \
+         .cfi_lsda 27, .Lexception3\n"
+    ));
+    assert!(asm.contains(".LEH3_0:\n  call thrower\n.LEH3_0_end:\n"));
+    assert!(asm.contains(".pushsection .gcc_except_table"));
+    assert!(asm.contains("  .uleb128 .LBL3_2-f\n"));
+    assert!(asm.contains("call _Unwind_Resume\n"));
+    assert!(asm.contains("DW.ref.__gxx_personality_v0:\n  .quad __gxx_personality_v0\n"));
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    {
+        let Some(native) = Native::new("exception_handling", "c++") else {
+            return;
+        };
+        let main = r#"
+#include <cstdio>
+extern "C" int f(int);
+extern "C" void thrower(int x) { if (x) throw x; }
+extern "C" void cleanup(int x) { std::printf("cleanup %d\n", x); }
+int main() {
+  std::printf("ok %d\n", f(0));
+  try { f(5); } catch (int e) { std::printf("caught %d\n", e); }
+}
+"#;
+        native.write("f.s", &asm);
+        native.write("main.cc", main);
+        let exe = native.build("c++", &["main.cc", "f.s"], &[]);
+        let output = std::process::Command::new(exe).output().unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "ok 0\ncleanup 7\ncaught 5\n"
+        );
+    }
+}
+
+#[test]
+fn global_variable_data() {
+    let source = r#"
+%struct.S = type { i8, i32, i64* }
+
+@n = global i32 42, align 4
+@z = global [4 x i32] zeroinitializer, align 16
+@arr = internal global [3 x i8] [i8 1, i8 -2, i8 3], align 1
+@s = global %struct.S { i8 7, i32 -1, i64* @q }, align 8
+@q = global i64 0, align 8
+@p = constant i32* getelementptr inbounds ([4 x i32], [4 x i32]* @z, i64 0, i64 2), align 8
+@e = external global i32
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let x86_64 = format!("{}", compile_module(X86_64, &module).unwrap());
+    assert_eq!(
+        x86_64,
+        "  .text
+  .intel_syntax noprefix
+  .data
+  .globl n
+  .p2align 2
+  .type n,@object
+  .size n, 4
+n:
+  .long 42
+  .bss
+  .globl z
+  .p2align 4
+  .type z,@object
+  .size z, 16
+z:
+  .zero 16
+  .data
+  .p2align 0
+  .type arr,@object
+  .size arr, 3
+arr:
+  .byte 1
+  .byte -2
+  .byte 3
+  .data
+  .globl s
+  .p2align 3
+  .type s,@object
+  .size s, 16
+s:
+  .byte 7
+  .zero 3
+  .long -1
+  .quad q
+  .bss
+  .globl q
+  .p2align 3
+  .type q,@object
+  .size q, 8
+q:
+  .zero 8
+  .section .rodata
+  .globl p
+  .p2align 3
+  .type p,@object
+  .size p, 8
+p:
+  .quad z+8
+  .text
+  .section .note.GNU-stack,\"\",@progbits
+"
+    );
+
+    // Addresses take the size of pointers.
+    let i686 = format!("{}", compile_module(I686, &module).unwrap());
+    assert!(i686.contains("s:\n  .byte 7\n  .zero 3\n  .long -1\n  .long q\n"));
+    assert!(i686.contains("p:\n  .long z+8\n"));
+}
+
+/// Prints strings with every byte escaped, and runs a program printing one.
+#[test]
+fn string_literals() {
+    let source = r#"
+@s = private unnamed_addr constant [9 x i8] c"a\09\22\5C\01F\FFz\00", align 1
+@nul = private unnamed_addr constant [4 x i8] c"a\00b\00", align 1
+@raw = private unnamed_addr constant [2 x i8] c"ab", align 1
+
+declare i32 @puts(i8*)
+
+define i32 @main() {
+  %1 = call i32 @puts(i8* getelementptr inbounds ([9 x i8], [9 x i8]* @s, i64 0, i64 0))
+  ret i32 0
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let asm = format!("{}", compile_module(X86_64, &module).unwrap());
+    assert!(asm.contains(r#"  .asciz "a\011\"\\\001F\377z""#));
+    assert!(asm.contains(r#"  .asciz "a\000b""#));
+    assert!(asm.contains(r#"  .ascii "ab""#));
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    {
+        let Some(native) = Native::new("string_literals", "cc") else {
+            return;
+        };
+        native.write("str.s", &asm);
+        let output = native.run(&["str.s"], &["-no-pie"]);
+        assert_eq!(output.stdout, b"a\t\"\\\x01F\xffz\n");
+    }
+}
+
+#[test]
+fn linkage_symbols() {
+    use vicis_codegen::codegen::{isa::x86_64::encode::encode_module, object::Binding};
+
+    let module = |n: i32| {
+        format!(
+            r#"
+@count = linkonce_odr global i32 {n}, align 4
+@hidden = internal global i32 {n}, align 4
+@msg = private unnamed_addr constant [3 x i8] c"ok\00", align 1
+
+declare extern_weak i32 @missing()
+
+define linkonce_odr i32 @inline_fn() {{
+  ret i32 40
+}}
+
+define internal i32 @helper() {{
+  ret i32 {n}
+}}
+
+define private i32 @secret() {{
+  ret i32 {n}
+}}
+
+define i32 @f{n}() {{
+  %1 = call i32 @inline_fn()
+  %2 = call i32 @helper()
+  %3 = call i32 @secret()
+  %4 = add i32 %1, %2
+  %5 = sub i32 %4, %3
+  ret i32 %5
+}}
+"#
+        )
+    };
+    let asm = |n: i32| {
+        let module = module::parse_assembly(&module(n)).unwrap();
+        format!("{}", compile_module(X86_64, &module).unwrap())
+    };
+    let first = asm(1);
+    assert!(first.contains("  .weak inline_fn\ninline_fn:"));
+    assert!(first.contains("  .weak missing\n"));
+    assert!(first.contains("  .globl f1\nf1:"));
+    assert!(first.contains("  .weak count\n"));
+    assert!(!first.contains(".globl helper") && first.contains("\nhelper:"));
+    assert!(!first.contains(".globl hidden") && first.contains("\nhidden:"));
+    assert!(first.contains("\n.Lsecret:") && first.contains("call .Lsecret"));
+    assert!(first.contains("\n.Lmsg:"));
+    assert!(!first.contains(" msg") && !first.contains(" secret"));
+
+    let obj = encode_module(
+        &compile_module(X86_64, &module::parse_assembly(&module(1)).unwrap()).unwrap(),
+    )
+    .unwrap();
+    let bindings: Vec<_> = obj
+        .functions()
+        .map(|(f, binding)| (f.name.as_str(), binding))
+        .chain(obj.data().iter().map(|d| (d.name.as_str(), d.binding)))
+        .collect();
+    assert_eq!(
+        bindings,
+        vec![
+            ("inline_fn", Binding::Weak),
+            ("helper", Binding::Local),
+            (".Lsecret", Binding::Local),
+            ("f1", Binding::Global),
+            ("count", Binding::Weak),
+            ("hidden", Binding::Local),
+            (".Lmsg", Binding::Local),
+        ]
+    );
+
+    // Both objects define `inline_fn`, `count`, `helper`, `hidden` and
+    // `secret`, which links as only the weak and local symbols may be
+    // defined twice.
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    {
+        let Some(native) = Native::new("linkage_symbols", "cc") else {
+            return;
+        };
+        native.write("a.s", &first);
+        native.write("b.s", asm(2));
+        native.write(
+            "main.c",
+            "int f1(void); int f2(void); int main(void) { return f1() + f2(); }",
+        );
+        native.assert_exit_code(&["main.c", "a.s", "b.s"], &["-no-pie"], 80);
+    }
+}
+
+#[test]
+fn sections_and_alignment() {
+    use vicis_codegen::codegen::{
+        isa::x86_64::encode::encode_module,
+        object::{elf, DataKind},
+    };
+
+    let source = r#"
+@v = global i32 1, section ".mydata", align 4
+@z = global i32 0, align 4
+@w = global i32 0, section ".mydata", align 4
+
+define i32 @unused() {
+  ret i32 1
+}
+
+define i32 @hot() section ".text.hot" align 32 {
+  ret i32 42
+}
+
+define i32 @main() {
+  %1 = call i32 @hot()
+  ret i32 %1
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let compile = |separate: bool| {
+        let options = CodegenOptions::new().with_function_sections(separate);
+        compile_module_with_options(X86_64, &module, &options).unwrap()
+    };
+
+    let asm = format!("{}", compile(false));
+    assert!(asm.contains("  .section .text.hot,\"ax\",@progbits\n  .p2align 5\n  .globl hot\n"));
+    assert!(asm.contains("  .text\n  .globl main\n"));
+    assert!(asm.contains("  .section .mydata,\"aw\",@progbits\n  .globl v\n"));
+    assert!(asm.contains("  .bss\n  .globl z\n"));
+    // Zeros in a section given explicitly take room in the file.
+    assert!(asm.contains("  .section .mydata,\"aw\",@progbits\n  .globl w\n"));
+
+    let separate = compile(true);
+    let asm = format!("{}", separate);
+    assert!(asm.contains("  .section .text.unused,\"ax\",@progbits\n"));
+    assert!(asm.contains("  .section .text.hot,\"ax\",@progbits\n"));
+    assert!(asm.contains("  .section .text.main,\"ax\",@progbits\n"));
+    assert!(asm.contains("  .section .bss.z,\"aw\",@nobits\n"));
+    assert!(asm.contains("  .section .mydata,\"aw\",@progbits\n  .globl v\n"));
+
+    let obj = encode_module(&separate).unwrap();
+    assert_eq!(obj.section("main"), Some(".text.main"));
+    assert_eq!(obj.section("hot"), Some(".text.hot"));
+    assert_eq!(obj.section("v"), Some(".mydata"));
+    assert_eq!(obj.section("z"), Some(".bss.z"));
+    assert_eq!(obj.data()[2].kind, DataKind::Data);
+    let bytes = elf::write(&obj).unwrap();
+    assert!(bytes.windows(11).any(|w| w == b".text.main\0"));
+
+    // The linker drops `unused`, alone in its section and never referred to.
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    {
+        use std::process::Command;
+
+        let Some(native) = Native::new("sections_and_alignment", "cc") else {
+            return;
+        };
+        for (file, contents) in [("a.s", asm.into_bytes()), ("b.o", bytes)] {
+            native.write(file, contents);
+            let exe = native.build("cc", &[file], &["-no-pie", "-Wl,--gc-sections"]);
+            assert_eq!(Command::new(&exe).status().unwrap().code(), Some(42));
+            let symbols = Command::new("nm").arg(&exe).output().unwrap().stdout;
+            let symbols = String::from_utf8(symbols).unwrap();
+            assert!(symbols.contains(" hot\n"));
+            assert!(!symbols.contains(" unused\n"));
+        }
+    }
+}
+
+#[test]
+fn comdat_groups() {
+    use vicis_codegen::codegen::isa::x86_64::encode::encode_module;
+
+    let module = |n: i32| {
+        format!(
+            r#"
+$inl = comdat any
+$shared = comdat any
+
+@shared = linkonce_odr global i32 2, comdat, align 4
+
+define linkonce_odr i32 @inl() comdat {{
+  ret i32 40
+}}
+
+define i32 @f{n}() {{
+  %1 = call i32 @inl()
+  ret i32 %1
+}}
+"#
+        )
+    };
+    let compile =
+        |n: i32| compile_module(X86_64, &module::parse_assembly(&module(n)).unwrap()).unwrap();
+    let asm = format!("{}", compile(1));
+    assert!(asm.contains("  .section .text.inl,\"axG\",@progbits,inl,comdat\n"));
+    assert!(asm.contains("  .section .data.shared,\"awG\",@progbits,shared,comdat\n"));
+    assert!(asm.contains("  .text\n  .globl f1\n"));
+
+    let obj = encode_module(&compile(2)).unwrap();
+    assert_eq!(obj.comdat("inl"), Some("inl"));
+    assert_eq!(obj.section("inl"), Some(".text.inl"));
+    assert_eq!(obj.comdat("shared"), Some("shared"));
+    assert_eq!(obj.comdat("f2"), None);
+
+    // Both the assembly and the object define `inl` and `shared` in section
+    // groups, of which the linker keeps one each.
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    {
+        use vicis_codegen::codegen::object::elf;
+
+        let Some(native) = Native::new("comdat_groups", "cc") else {
+            return;
+        };
+        native.write("a.s", &asm);
+        elf::write_to_file(&obj, native.path("b.o")).unwrap();
+        let output = std::process::Command::new("readelf")
+            .arg("-gW")
+            .arg(native.path("b.o"))
+            .output()
+            .unwrap();
+        let groups = String::from_utf8(output.stdout).unwrap();
+        assert!(groups.contains("[inl]") && groups.contains("   .text.inl\n"));
+        assert!(groups.contains("[shared]") && groups.contains("   .data.shared\n"));
+        native.write(
+            "main.c",
+            "int f1(void); int f2(void); int main(void) { return f1() + f2(); }",
+        );
+        native.assert_exit_code(&["main.c", "a.s", "b.o"], &["-no-pie"], 80);
+    }
+}
+
+#[test]
+fn symbol_visibility() {
+    use vicis_codegen::codegen::{isa::x86_64::encode::encode_module, options::RelocModel};
+    use vicis_core::ir::module::visibility::Visibility;
+
+    let source = r#"
+@h = hidden global i32 1, align 4
+@p = dso_local protected global i32 2, align 4
+
+declare hidden i32 @callee()
+
+define hidden i32 @f() {
+  ret i32 0
+}
+
+define protected i32 @g() {
+  %1 = call i32 @callee()
+  ret i32 %1
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let options = CodegenOptions::new().with_reloc_model(RelocModel::Pic);
+    let module = compile_module_with_options(X86_64, &module, &options).unwrap();
+    let asm = format!("{}", module);
+    assert!(asm.contains("  .hidden callee\n"));
+    assert!(asm.contains("  .globl f\n  .hidden f\nf:"));
+    assert!(asm.contains("  .globl g\n  .protected g\ng:"));
+    assert!(asm.contains("  .globl h\n  .hidden h\n"));
+    assert!(asm.contains("  .globl p\n  .protected p\n"));
+    // Symbols not preempted by other components are not reached through the
+    // PLT.
+    assert!(asm.contains("  call callee\n"));
+
+    let obj = encode_module(&module).unwrap();
+    assert_eq!(obj.visibility("callee"), Visibility::Hidden);
+    assert_eq!(obj.visibility("g"), Visibility::Protected);
+    assert_eq!(obj.visibility("h"), Visibility::Hidden);
+    assert_eq!(obj.visibility("p"), Visibility::Protected);
+
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    {
+        use vicis_codegen::codegen::object::elf;
+
+        let Some(native) = Native::new("symbol_visibility", "readelf") else {
+            return;
+        };
+        elf::write_to_file(&obj, native.path("v.o")).unwrap();
+        let output = std::process::Command::new("readelf")
+            .arg("-sW")
+            .arg(native.path("v.o"))
+            .output()
+            .unwrap();
+        let symbols = String::from_utf8(output.stdout).unwrap();
+        let visibility = |name: &str| {
+            let line = symbols
+                .lines()
+                .find(|l| l.ends_with(&format!(" {}", name)))
+                .unwrap();
+            line.split_whitespace().nth(5).unwrap().to_owned()
+        };
+        assert_eq!(visibility("f"), "HIDDEN");
+        assert_eq!(visibility("g"), "PROTECTED");
+        assert_eq!(visibility("h"), "HIDDEN");
+        assert_eq!(visibility("p"), "PROTECTED");
+    }
+}
+
+/// Links the programs of `recursive_exit_codes` from object files written
+/// without an assembler, and runs them.
+#[test]
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn object_exit_codes() {
+    use vicis_codegen::codegen::{isa::x86_64::encode::encode_module, object::elf};
+
+    let Some(native) = Native::new("object_exit_codes", "cc") else {
+        return;
+    };
+    for (name, expected) in [("fibo", 55), ("ack", 9), ("even_odd", 2), ("puts", 0)] {
+        let source = std::fs::read_to_string(format!("./tests/codegen/{}.ll", name)).unwrap();
+        let module = compile_module(X86_64, &module::parse_assembly(&source).unwrap()).unwrap();
+
+        let obj = format!("{}.o", name);
+        elf::write_to_file(&encode_module(&module).unwrap(), native.path(&obj)).unwrap();
+        native.assert_exit_code(&[&obj], &[], expected);
+    }
+}
+
+/// Runs a program on integers of every width, assembled from both syntaxes
+/// and linked from an object file.
+#[test]
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn integer_widths() {
+    use vicis_codegen::codegen::function::slot::SlotOrigin;
+
+    let source = r#"
+define i64 @add64(i64 %a, i64 %b) {
+  %c = add i64 %a, %b
+  %d = add i64 %c, 4294967296
+  ret i64 %d
+}
+
+define i32 @narrow(i8 %x, i16 %y) {
+  %a = alloca i8, align 1
+  %b = alloca i16, align 2
+  store i8 %x, i8* %a, align 1
+  store i16 %y, i16* %b, align 2
+  %x1 = load i8, i8* %a, align 1
+  %x2 = add i8 %x1, 1
+  %s = sext i8 %x2 to i32
+  %y1 = load i16, i16* %b, align 2
+  %y2 = add i16 %y1, 5
+  %z = zext i16 %y2 to i32
+  %r = add i32 %s, %z
+  ret i32 %r
+}
+
+define i8 @id(i8 %x) {
+  ret i8 %x
+}
+
+define i8 @spill(i8 %a, i8 %b) {
+  %e = add i8 %a, %b
+  %x = call i8 @id(i8 %b)
+  %r = sub i8 %e, %x
+  ret i8 %r
+}
+
+define i32 @main() {
+  %m = alloca i64, align 8
+  %r = call i32 @narrow(i8 127, i16 -1)
+  %c1 = icmp eq i32 %r, -124
+  br i1 %c1, label %ok1, label %fail
+ok1:
+  %w = call i64 @add64(i64 -1, i64 3)
+  %t = trunc i64 %w to i32
+  %c2 = icmp eq i32 %t, 2
+  br i1 %c2, label %ok2, label %fail
+ok2:
+  store i64 -5, i64* %m, align 8
+  %v = load i64, i64* %m, align 8
+  %v2 = add i64 %v, %w
+  %v3 = sub i64 %v2, 4294967296
+  %c3 = icmp eq i64 %v3, -3
+  br i1 %c3, label %ok3, label %fail
+ok3:
+  %s = call i8 @spill(i8 30, i8 20)
+  %c4 = icmp slt i8 %s, 31
+  br i1 %c4, label %ok4, label %fail
+ok4:
+  %e = zext i8 %s to i64
+  %tr = trunc i64 %e to i16
+  %se = sext i16 %tr to i64
+  %c5 = icmp eq i64 %se, 30
+  br i1 %c5, label %ok5, label %fail
+ok5:
+  ret i32 42
+fail:
+  ret i32 1
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let intel = compile_module(X86_64, &module).unwrap();
+    let att = compile_module_with_options(
+        X86_64,
+        &module,
+        &CodegenOptions::new().with_asm_syntax(AsmSyntax::Att),
+    )
+    .unwrap();
+    let asm = intel.to_string();
+    assert!(asm.contains("  movabs "));
+    assert!(asm.contains("word ptr [rbp"));
+    assert!(att.to_string().contains("  movsbl "));
+
+    // The `i8`s live across the call are spilled to slots of a byte.
+    let (_, spill) = intel
+        .functions
+        .iter()
+        .find(|(_, f)| f.name == "spill")
+        .unwrap();
+    let spill_slots: Vec<_> = spill
+        .slots
+        .iter()
+        .filter(|(_, slot)| matches!(slot.origin(), SlotOrigin::Spill(_)))
+        .map(|(_, slot)| slot.size())
+        .collect();
+    assert!(!spill_slots.is_empty() && spill_slots.iter().all(|&size| size == 1));
+
+    assert_exit_code_in_all_forms("integer_widths", &module, 42);
+}
+
+/// Runs a program dividing, shifting and comparing integers narrower than
+/// registers, and using comparisons and `i1` casts as values, assembled from
+/// both syntaxes and linked from an object file.
+#[test]
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn integer_operations() {
+    let source = r#"
+define i8 @div8(i8 %a, i8 %b) {
+  %q = sdiv i8 %a, %b
+  %r = srem i8 %a, %b
+  %s = mul i8 %q, 10
+  %t = add i8 %s, %r
+  ret i8 %t
+}
+
+define i8 @shift8(i8 %a, i8 %n) {
+  %l = lshr i8 %a, %n
+  %c = lshr i8 %a, 1
+  %s = shl i8 %l, 2
+  %x = and i8 %s, %c
+  %o = or i8 %x, 1
+  %k = icmp slt i8 %a, %n
+  %kz = zext i1 %k to i8
+  %r = add i8 %o, %kz
+  ret i8 %r
+}
+
+define i32 @bits(i32 %a, i32 %b, double %x, double %y) {
+  %lt = icmp ult i32 %a, %b
+  %gt = icmp sgt i32 %b, %a
+  %t = trunc i32 %a to i1
+  br i1 %t, label %next, label %out
+next:
+  %z = zext i1 %lt to i32
+  %s = sext i1 %gt to i32
+  %f = fcmp oeq double %x, %y
+  %fz = zext i1 %f to i32
+  %u = fcmp une double %x, %y
+  %uz = zext i1 %u to i32
+  %tz = zext i1 %t to i32
+  %w = sext i1 %gt to i128
+  %wh = lshr i128 %w, 100
+  %wt = trunc i128 %wh to i32
+  %wb = trunc i128 %w to i1
+  %wz = zext i1 %wb to i32
+  %a1 = mul i32 %s, 2
+  %a2 = mul i32 %fz, 4
+  %a3 = mul i32 %uz, 8
+  %a4 = mul i32 %tz, 16
+  %a5 = mul i32 %wz, 32
+  %a6 = and i32 %wt, 1024
+  %r1 = add i32 %z, %a1
+  %r2 = add i32 %r1, %a2
+  %r3 = add i32 %r2, %a3
+  %r4 = add i32 %r3, %a4
+  %r5 = add i32 %r4, %a5
+  %r6 = add i32 %r5, %a6
+  ret i32 %r6
+out:
+  ret i32 0
+}
+
+define i32 @main() {
+  %d = call i8 @div8(i8 -50, i8 7)
+  %c1 = icmp eq i8 %d, -71
+  br i1 %c1, label %ok1, label %fail
+ok1:
+  %s = call i8 @shift8(i8 -128, i8 3)
+  %c2 = icmp eq i8 %s, 66
+  br i1 %c2, label %ok2, label %fail
+ok2:
+  %b = call i32 @bits(i32 -1, i32 1, double 1.5, double 1.5)
+  %c3 = icmp eq i32 %b, 1074
+  br i1 %c3, label %ok3, label %fail
+ok3:
+  ret i32 42
+fail:
+  ret i32 1
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let intel = compile_module(X86_64, &module).unwrap();
+    let att = compile_module_with_options(
+        X86_64,
+        &module,
+        &CodegenOptions::new().with_asm_syntax(AsmSyntax::Att),
+    )
+    .unwrap();
+    let asm = intel.to_string();
+    assert!(asm.contains("  idiv "));
+    assert!(asm.contains("  setnp "));
+    assert!(att.to_string().contains("  cltd"));
+
+    assert_exit_code_in_all_forms("integer_operations", &module, 42);
+}
+
+/// Runs a program loading and storing globals, directly, through
+/// `getelementptr` and through a pointer argument, in every code model and
+/// relocation model it may be compiled for, assembled and linked from an
+/// object file.
+#[test]
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn global_loads_and_stores() {
+    use vicis_codegen::codegen::{
+        isa::x86_64::encode::encode_module,
+        object::elf,
+        options::{CodeModel, RelocModel},
+    };
+
+    let Some(native) = Native::new("global_loads_and_stores", "cc") else {
+        return;
+    };
+
+    let source = r#"
+@n = global i32 40, align 4
+@b = internal global i8 2, align 1
+@z = global [4 x i64] zeroinitializer, align 16
+
+define i32 @get(i32* %p) {
+  %v = load i32, i32* %p, align 4
+  ret i32 %v
+}
+
+define i32 @main() {
+  %v = load i32, i32* @n, align 4
+  %b = load i8, i8* @b, align 1
+  %bz = zext i8 %b to i32
+  %s = add i32 %v, %bz
+  store i32 %s, i32* @n, align 4
+  %e = getelementptr inbounds [4 x i64], [4 x i64]* @z, i64 0, i64 3
+  store i64 5, i64* %e, align 8
+  %x = load i64, i64* %e, align 8
+  %xt = trunc i64 %x to i32
+  %r = call i32 @get(i32* @n)
+  %d = sub i32 %r, %xt
+  ret i32 %d
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    for (model, reloc_model) in [
+        (CodeModel::Small, RelocModel::Static),
+        (CodeModel::Medium, RelocModel::Static),
+        (CodeModel::Large, RelocModel::Static),
+        (CodeModel::Small, RelocModel::Pic),
+        (CodeModel::Medium, RelocModel::Pic),
+    ] {
+        let options = CodegenOptions::new()
+            .with_code_model(model)
+            .with_reloc_model(reloc_model);
+        let mach = compile_module_with_options(X86_64, &module, &options).unwrap();
+        elf::write_to_file(&encode_module(&mach).unwrap(), native.path("globals.o")).unwrap();
+        native.write("globals.s", mach.to_string());
+        let pie = if reloc_model == RelocModel::Pic {
+            "-pie"
+        } else {
+            "-no-pie"
+        };
+        for input in ["globals.o", "globals.s"] {
+            let status = native.run(&[input], &[pie]).status;
+            assert_eq!(
+                status.code(),
+                Some(37),
+                "wrong exit code for {} ({:?}, {:?})",
+                input,
+                model,
+                reloc_model
+            );
+        }
+    }
+}
+
+/// Runs a program on `i128`s, passed, returned and stored in pairs of
+/// registers.
+#[test]
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn i128_pairs() {
+    let source = r#"
+define i128 @mul_add(i128 %a, i128 %b, i64 %c) {
+entry:
+  %m = mul i128 %a, %b
+  %z = zext i64 %c to i128
+  %s = add i128 %m, %z
+  ret i128 %s
+}
+
+define i128 @shifts(i128 %a, i128 %n) {
+entry:
+  %x = shl i128 %a, %n
+  %y = lshr i128 %x, 3
+  %w = shl i128 %y, 70
+  %v = lshr i128 %a, %n
+  %u = sub i128 %w, %v
+  ret i128 %u
+}
+
+define i32 @check(i128 %got, i64 %lo, i64 %hi) {
+entry:
+  %l = trunc i128 %got to i64
+  %h0 = lshr i128 %got, 64
+  %h = trunc i128 %h0 to i64
+  %a = sub i64 %l, %lo
+  %b = sub i64 %h, %hi
+  %ok = icmp eq i64 %a, 0
+  br i1 %ok, label %same, label %bad
+same:
+  %zero = icmp eq i64 %b, 0
+  br i1 %zero, label %good, label %bad
+good:
+  ret i32 0
+bad:
+  ret i32 1
+}
+
+define i32 @main() {
+entry:
+  %p = alloca i128, align 16
+  %s = sext i32 -3 to i128
+  store i128 %s, i128* %p, align 16
+  %a = load i128, i128* %p, align 16
+  %m = call i128 @mul_add(i128 %a, i128 18446744073709551621, i64 7)
+  %r0 = call i32 @check(i128 %m, i64 -8, i64 -4)
+  %sh = call i128 @shifts(i128 81985529216486895, i128 36)
+  %r1 = call i32 @check(i128 %sh, i64 -1193046, i64 -3033465169859575809)
+  %sl = call i128 @shl_var(i128 81985529216486895, i128 68)
+  %r2 = call i32 @check(i128 %sl, i64 0, i64 1311768467463790320)
+  %sr = call i128 @lshr_var(i128 -81985529216486895, i128 68)
+  %r3 = call i32 @check(i128 %sr, i64 1152921504606846975, i64 0)
+  %r4 = add i32 %r2, %r3
+  %r5 = add i32 %r0, %r1
+  %r6 = add i32 %r5, %r4
+  %pk = call i128 @pick(i32 0, i128 -1)
+  %r7 = call i32 @check(i128 %pk, i64 0, i64 1)
+  %r = add i32 %r6, %r7
+  %c = icmp eq i32 %r, 0
+  br i1 %c, label %ok, label %fail
+ok:
+  ret i32 42
+fail:
+  ret i32 1
+}
+
+define i128 @shl_var(i128 %a, i128 %n) {
+entry:
+  %x = shl i128 %a, %n
+  ret i128 %x
+}
+
+define i128 @lshr_var(i128 %a, i128 %n) {
+entry:
+  %x = lshr i128 %a, %n
+  ret i128 %x
+}
+
+define i128 @pick(i32 %c, i128 %a) {
+entry:
+  %z = icmp eq i32 %c, 0
+  br i1 %z, label %then, label %else
+then:
+  %b = add i128 %a, 18446744073709551617
+  br label %join
+else:
+  %d = sub i128 %a, 1
+  br label %join
+join:
+  %r = phi i128 [ %b, %then ], [ %d, %else ]
+  ret i128 %r
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let asm = compile_module(X86_64, &module).unwrap().to_string();
+    for inst in [
+        "  adc ",
+        "  sbb ",
+        "  mul ",
+        "  shld ",
+        "  shrd ",
+        "  cmovne ",
+    ] {
+        assert!(asm.contains(inst), "no {}", inst.trim());
+    }
+
+    assert_exit_code_in_all_forms("i128_pairs", &module, 42);
+}
+
+/// Runs a program on `float`s and `double`s, passed and returned in XMM
+/// registers.
+#[test]
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn sse_floats() {
+    let source = r#"
+define double @poly(double %x, i32 %n, float %y) {
+entry:
+  %a = fmul double %x, %x
+  %b = fadd double %a, 1.500000e+00
+  %c = fpext float %y to double
+  %d = fsub double %b, %c
+  %e = sitofp i32 %n to double
+  %f = fdiv double %d, %e
+  %g = fneg double %f
+  ret double %g
+}
+
+define float @half(float %x) {
+entry:
+  %r = fmul float %x, 5.000000e-01
+  ret float %r
+}
+
+define i32 @conv(double %x, i64 %big) {
+entry:
+  %t = fptrunc double %x to float
+  %i = fptosi float %t to i32
+  %u = fptoui double %x to i32
+  %b = uitofp i8 -56 to double
+  %bi = fptoui double %b to i8
+  %bz = zext i8 %bi to i32
+  %l = sitofp i64 %big to double
+  %li = fptosi double %l to i64
+  %lt = trunc i64 %li to i32
+  %w = uitofp i32 %lt to double
+  %wi = fptosi double %w to i64
+  %wt = trunc i64 %wi to i32
+  %s0 = add i32 %i, %u
+  %s1 = add i32 %s0, %bz
+  %s2 = add i32 %s1, %lt
+  %s = add i32 %s2, %wt
+  %ok = icmp eq i32 %s, 278
+  br i1 %ok, label %good, label %bad
+good:
+  ret i32 0
+bad:
+  ret i32 1
+}
+
+define i32 @check(double %got, double %want) {
+entry:
+  %c = fcmp oeq double %got, %want
+  br i1 %c, label %good, label %bad
+good:
+  ret i32 0
+bad:
+  ret i32 1
+}
+
+define i32 @olt(double %a, double %b) {
+entry:
+  %c = fcmp olt double %a, %b
+  br i1 %c, label %yes, label %no
+yes:
+  ret i32 1
+no:
+  ret i32 0
+}
+
+define i32 @une(double %a, double %b) {
+entry:
+  %c = fcmp une double %a, %b
+  br i1 %c, label %yes, label %no
+yes:
+  ret i32 1
+no:
+  ret i32 0
+}
+
+define i32 @ugt(float %a, float %b) {
+entry:
+  %c = fcmp ugt float %a, %b
+  br i1 %c, label %yes, label %no
+yes:
+  ret i32 1
+no:
+  ret i32 0
+}
+
+define i32 @uno(double %a, double %b) {
+entry:
+  %c = fcmp uno double %a, %b
+  br i1 %c, label %yes, label %no
+yes:
+  ret i32 1
+no:
+  ret i32 0
+}
+
+define double @pick(i32 %n, double %x) {
+entry:
+  %p = alloca double
+  store double %x, double* %p
+  %c = icmp sgt i32 %n, 0
+  br i1 %c, label %pos, label %neg
+pos:
+  br label %join
+neg:
+  br label %join
+join:
+  %k = phi double [ 2.500000e+00, %pos ], [ -1.000000e+00, %neg ]
+  %v = load double, double* %p
+  %r = fmul double %k, %v
+  ret double %r
+}
+
+define i32 @main() {
+entry:
+  %p = call double @poly(double 3.000000e+00, i32 2, float 1.000000e+00)
+  %r0 = call i32 @check(double %p, double -4.750000e+00)
+  %h = call float @half(float 8.000000e+00)
+  %hd = fpext float %h to double
+  %r1 = call i32 @check(double %hd, double 4.000000e+00)
+  %r2 = call i32 @conv(double 4.200000e+01, i64 -3)
+  %k = call double @pick(i32 1, double 2.000000e+00)
+  %r3 = call i32 @check(double %k, double 5.000000e+00)
+  %c0 = call i32 @olt(double 1.000000e+00, double 2.000000e+00)
+  %c1 = call i32 @olt(double 0x7FF8000000000000, double 1.000000e+00)
+  %c2 = call i32 @une(double 2.000000e+00, double 2.000000e+00)
+  %c3 = call i32 @une(double 0x7FF8000000000000, double 0x7FF8000000000000)
+  %c4 = call i32 @ugt(float 0x7FF8000000000000, float 1.000000e+00)
+  %c5 = call i32 @ugt(float 1.000000e+00, float 2.000000e+00)
+  %c6 = call i32 @uno(double 1.000000e+00, double 0x7FF8000000000000)
+  %n0 = call i32 @check(double 0x7FF8000000000000, double 0x7FF8000000000000)
+  %s0 = add i32 %r0, %r1
+  %s1 = add i32 %s0, %r2
+  %s2 = add i32 %s1, %r3
+  %s3 = add i32 %s2, %c1
+  %s4 = add i32 %s3, %c2
+  %s5 = add i32 %s4, %c5
+  %t0 = add i32 %c0, %c3
+  %t1 = add i32 %t0, %c4
+  %t2 = add i32 %t1, %c6
+  %t = add i32 %t2, %n0
+  %ok0 = icmp eq i32 %s5, 0
+  br i1 %ok0, label %next, label %fail
+next:
+  %ok1 = icmp eq i32 %t, 5
+  br i1 %ok1, label %ok, label %fail
+ok:
+  ret i32 42
+fail:
+  ret i32 1
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let asm = compile_module(X86_64, &module).unwrap().to_string();
+    for inst in [
+        "  movsd ",
+        "  mulss ",
+        "  xorps ",
+        "  ucomisd ",
+        "  cvtsi2sd ",
+        "  cvttsd2si ",
+        "  jp ",
+    ] {
+        assert!(asm.contains(inst), "no {}", inst.trim());
+    }
+
+    assert_exit_code_in_all_forms("sse_floats", &module, 42);
+}
+
+/// Runs a program on vectors of 128 bits, lowered to SSE2 and scalarized where
+/// SSE2 has no instruction for them.
+#[test]
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn sse_vectors() {
+    let source = r#"
+define <4 x i32> @arith(<4 x i32> %a, <4 x i32> %b) {
+entry:
+  %s = add <4 x i32> %a, %b
+  %m = mul <4 x i32> %s, %b
+  %d = sub <4 x i32> %m, <i32 1, i32 2, i32 3, i32 4>
+  %sh = shl <4 x i32> %d, <i32 1, i32 0, i32 2, i32 1>
+  %r = lshr <4 x i32> %sh, <i32 0, i32 1, i32 0, i32 0>
+  ret <4 x i32> %r
+}
+
+define i32 @sum(<4 x i32> %v) {
+entry:
+  %e0 = extractelement <4 x i32> %v, i32 0
+  %e1 = extractelement <4 x i32> %v, i32 1
+  %e2 = extractelement <4 x i32> %v, i64 2
+  %e3 = extractelement <4 x i32> %v, i32 3
+  %s0 = add i32 %e0, %e1
+  %s1 = add i32 %s0, %e2
+  %s2 = add i32 %s1, %e3
+  ret i32 %s2
+}
+
+define <4 x float> @fops(<4 x float> %a, <4 x float> %b) {
+entry:
+  %s = fadd <4 x float> %a, %b
+  %m = fmul <4 x float> %s, <float 2.000000e+00, float 2.000000e+00, float 5.000000e-01, float 1.000000e+00>
+  %d = fdiv <4 x float> %m, %b
+  %n = fneg <4 x float> %d
+  %r = fsub <4 x float> %n, %a
+  ret <4 x float> %r
+}
+
+define i32 @fsum(<4 x float> %v) {
+entry:
+  %e0 = extractelement <4 x float> %v, i32 0
+  %e1 = extractelement <4 x float> %v, i32 1
+  %e2 = extractelement <4 x float> %v, i32 2
+  %e3 = extractelement <4 x float> %v, i32 3
+  %s0 = fadd float %e0, %e1
+  %s1 = fadd float %s0, %e2
+  %s2 = fadd float %s1, %e3
+  %i = fptosi float %s2 to i32
+  ret i32 %i
+}
+
+define i32 @cmps(<4 x i32> %a, <4 x i32> %b) {
+entry:
+  %gt = icmp sgt <4 x i32> %a, %b
+  %ult = icmp ult <4 x i32> %a, %b
+  %ne = icmp ne <4 x i32> %a, %b
+  %sle = icmp sle <4 x i32> %a, %b
+  %x = zext <4 x i1> %gt to <4 x i32>
+  %y = sext <4 x i1> %ult to <4 x i32>
+  %z = zext <4 x i1> %ne to <4 x i32>
+  %w = zext <4 x i1> %sle to <4 x i32>
+  %xy = add <4 x i32> %x, %y
+  %m = mul <4 x i32> %z, <i32 10, i32 10, i32 10, i32 10>
+  %xyz = add <4 x i32> %xy, %m
+  %w100 = mul <4 x i32> %w, <i32 100, i32 100, i32 100, i32 100>
+  %all = add <4 x i32> %xyz, %w100
+  %uge = icmp uge <4 x i32> %a, %b
+  %lane = extractelement <4 x i1> %uge, i32 2
+  %lz = zext i1 %lane to i32
+  %s = call i32 @sum(<4 x i32> %all)
+  %t = add i32 %s, %lz
+  ret i32 %t
+}
+
+define i64 @dcmps(<2 x double> %a, <2 x double> %b) {
+entry:
+  %lt = fcmp olt <2 x double> %a, %b
+  %one = fcmp one <2 x double> %a, %b
+  %ueq = fcmp ueq <2 x double> %a, %b
+  %uge = fcmp uge <2 x double> %a, %b
+  %x = zext <2 x i1> %lt to <2 x i64>
+  %y = zext <2 x i1> %one to <2 x i64>
+  %z = zext <2 x i1> %ueq to <2 x i64>
+  %w = zext <2 x i1> %uge to <2 x i64>
+  %y2 = add <2 x i64> %y, %y
+  %z4 = shl <2 x i64> %z, <i64 2, i64 2>
+  %w8 = shl <2 x i64> %w, <i64 3, i64 3>
+  %s0 = add <2 x i64> %x, %y2
+  %s1 = add <2 x i64> %s0, %z4
+  %s2 = add <2 x i64> %s1, %w8
+  %eq = icmp eq <2 x i64> %s2, <i64 3, i64 12>
+  %e = sext <2 x i1> %eq to <2 x i64>
+  %l0 = extractelement <2 x i64> %s2, i32 0
+  %l1 = extractelement <2 x i64> %s2, i32 1
+  %k = extractelement <2 x i64> %e, i32 1
+  %h2 = add i64 %l1, %l1
+  %h4 = add i64 %h2, %h2
+  %h8 = add i64 %h4, %h4
+  %hi = add i64 %h8, %h8
+  %r0 = add i64 %l0, %hi
+  %r = sub i64 %r0, %k
+  ret i64 %r
+}
+
+define i32 @shuffles(<4 x i32> %a, <4 x i32> %b) {
+entry:
+  %rev = shufflevector <4 x i32> %a, <4 x i32> undef, <4 x i32> <i32 3, i32 2, i32 1, i32 0>
+  %lohi = shufflevector <4 x i32> %a, <4 x i32> %b, <4 x i32> <i32 1, i32 0, i32 7, i32 6>
+  %mix = shufflevector <4 x i32> %a, <4 x i32> %b, <4 x i32> <i32 0, i32 4, i32 undef, i32 5>
+  %bb = shufflevector <4 x i32> %a, <4 x i32> %b, <4 x i32> <i32 5, i32 6, i32 4, i32 7>
+  %m = insertelement <4 x i32> %mix, i32 1000, i32 2
+  %r0 = mul <4 x i32> %rev, <i32 1, i32 10, i32 100, i32 1000>
+  %r1 = mul <4 x i32> %lohi, <i32 1, i32 10, i32 100, i32 1000>
+  %r2 = mul <4 x i32> %m, <i32 1, i32 10, i32 100, i32 1000>
+  %r3 = mul <4 x i32> %bb, <i32 1, i32 10, i32 100, i32 1000>
+  %s0 = call i32 @sum(<4 x i32> %r0)
+  %s1 = call i32 @sum(<4 x i32> %r1)
+  %s2 = call i32 @sum(<4 x i32> %r2)
+  %s3 = call i32 @sum(<4 x i32> %r3)
+  %t0 = add i32 %s0, %s0
+  %t1 = add i32 %t0, %s1
+  %t2 = add i32 %s2, %s2
+  %t3 = add i32 %t1, %t2
+  %t4 = add i32 %t3, %s3
+  ret i32 %t4
+}
+
+define i32 @words(<4 x i32> %a, i32 %i) {
+entry:
+  %w = bitcast <4 x i32> %a to <8 x i16>
+  %p = mul <8 x i16> %w, %w
+  %q = add <8 x i16> %p, %w
+  %e = extractelement <8 x i16> %q, i32 3
+  %ins = insertelement <8 x i16> %q, i16 %e, i32 6
+  %f = extractelement <8 x i16> %ins, i32 %i
+  %g = extractelement <8 x i16> %ins, i32 6
+  %ez = zext i16 %f to i32
+  %gz = zext i16 %g to i32
+  %b = bitcast <4 x i32> %a to <16 x i8>
+  %bs = sub <16 x i8> %b, <i8 1, i8 1, i8 1, i8 1, i8 1, i8 1, i8 1, i8 1, i8 1, i8 1, i8 1, i8 1, i8 1, i8 1, i8 1, i8 1>
+  %bm = mul <16 x i8> %bs, %b
+  %bx = extractelement <16 x i8> %bm, i32 4
+  %by = extractelement <16 x i8> %bm, i32 %i
+  %bxz = zext i8 %bx to i32
+  %byz = zext i8 %by to i32
+  %s0 = add i32 %ez, %gz
+  %s1 = add i32 %s0, %bxz
+  %s2 = add i32 %s1, %byz
+  ret i32 %s2
+}
+
+define <2 x double> @mem(<2 x double> %v, i32 %n) {
+entry:
+  %p = alloca <2 x double>
+  store <2 x double> %v, <2 x double>* %p
+  %c = icmp sgt i32 %n, 0
+  br i1 %c, label %pos, label %neg
+pos:
+  br label %join
+neg:
+  br label %join
+join:
+  %k = phi <2 x double> [ <double 2.000000e+00, double 3.000000e+00>, %pos ], [ zeroinitializer, %neg ]
+  %l = load <2 x double>, <2 x double>* %p
+  %r = fmul <2 x double> %k, %l
+  %x = insertelement <2 x double> %r, double 1.000000e+00, i32 0
+  ret <2 x double> %x
+}
+
+define i32 @main() {
+entry:
+  %v = call <4 x i32> @arith(<4 x i32> <i32 1, i32 2, i32 3, i32 4>, <4 x i32> <i32 5, i32 -6, i32 7, i32 8>)
+  %s = call i32 @sum(<4 x i32> %v)
+  %ok0 = icmp eq i32 %s, 521
+  br i1 %ok0, label %l1, label %fail
+l1:
+  %f = call <4 x float> @fops(<4 x float> <float 1.000000e+00, float 2.000000e+00, float 3.000000e+00, float 4.000000e+00>, <4 x float> <float 1.000000e+00, float 2.000000e+00, float 1.000000e+00, float 4.000000e+00>)
+  %fs = call i32 @fsum(<4 x float> %f)
+  %ok1 = icmp eq i32 %fs, -22
+  br i1 %ok1, label %l2, label %fail
+l2:
+  %c = call i32 @cmps(<4 x i32> <i32 1, i32 -5, i32 3, i32 9>, <4 x i32> <i32 1, i32 7, i32 -2, i32 8>)
+  %ok2 = icmp eq i32 %c, 231
+  br i1 %ok2, label %l3, label %fail
+l3:
+  %d = call i64 @dcmps(<2 x double> <double 1.000000e+00, double 0x7FF8000000000000>, <2 x double> <double 2.000000e+00, double 1.000000e+00>)
+  %ok3 = icmp eq i64 %d, 196
+  br i1 %ok3, label %l4, label %fail
+l4:
+  %sh = call i32 @shuffles(<4 x i32> <i32 1, i32 2, i32 3, i32 4>, <4 x i32> <i32 5, i32 6, i32 7, i32 8>)
+  %ok4 = icmp eq i32 %sh, 230958
+  br i1 %ok4, label %l5, label %fail
+l5:
+  %w = call i32 @words(<4 x i32> <i32 65539, i32 327686, i32 -1, i32 1234567>, i32 5)
+  %ok5 = icmp eq i32 %w, 60
+  br i1 %ok5, label %l6, label %fail
+l6:
+  %m = call <2 x double> @mem(<2 x double> <double 4.000000e+00, double 5.000000e+00>, i32 1)
+  %m1 = extractelement <2 x double> %m, i32 1
+  %m0 = extractelement <2 x double> %m, i32 0
+  %ms = fadd double %m0, %m1
+  %mi = fptosi double %ms to i32
+  %ok6 = icmp eq i32 %mi, 16
+  br i1 %ok6, label %ok, label %fail
+ok:
+  ret i32 42
+fail:
+  ret i32 1
+}
+"#;
+    let module = module::parse_assembly(source).unwrap();
+    let asm = compile_module(X86_64, &module).unwrap().to_string();
+    for inst in [
+        "  movups ",
+        "  paddd ",
+        "  pmullw ",
+        "  mulps ",
+        "  pcmpgtd ",
+        "  cmppd ",
+        "  pshufd ",
+        "  shufps ",
+        "  pextrw ",
+        "  pinsrw ",
+        "  imul ",
+    ] {
+        assert!(asm.contains(inst), "no {}", inst.trim());
+    }
+
+    assert_exit_code_in_all_forms("sse_vectors", &module, 42);
+}
+
+/// Compiles modules to executables of each kind and to a shared library
+/// through the system linker, and runs them.
+#[test]
+#[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+fn driver_outputs() {
+    use std::process::Command;
+    use vicis_codegen::codegen::driver::{compile_to_file, LinkOptions, OutputKind};
+
+    let Some(native) = Native::new("driver_outputs", "cc") else {
+        return;
+    };
+    let source = std::fs::read_to_string("./tests/codegen/fibo.ll").unwrap();
+    let module = module::parse_assembly(&source).unwrap();
+    // The ELF type, at offset 16, is 2 for executables at a fixed address and
+    // 3 for position-independent ones and shared libraries.
+    let elf_type = |path: &std::path::Path| std::fs::read(path).unwrap()[16];
+    for (pie, ty) in [(true, 3), (false, 2)] {
+        let exe = native.path(&format!("fibo-{}", pie));
+        compile_to_file(&module, &LinkOptions::new().with_pie(pie), &exe).unwrap();
+        assert_eq!(elf_type(&exe), ty);
+        let status = Command::new(&exe).status().unwrap();
+        assert_eq!(status.code(), Some(55));
+    }
+    let obj = native.path("fibo.o");
+    let options = LinkOptions::new().with_output_kind(OutputKind::Object);
+    compile_to_file(&module, &options, &obj).unwrap();
+    assert_eq!(elf_type(&obj), 1);
+
+    let module = module::parse_assembly(
+        r#"
+define i32 @answer() {
+  ret i32 42
+}
+"#,
+    )
+    .unwrap();
+    let lib = native.path("libanswer.so");
+    let options = LinkOptions::new().with_output_kind(OutputKind::SharedLibrary);
+    compile_to_file(&module, &options, &lib).unwrap();
+    assert_eq!(elf_type(&lib), 3);
+    unsafe {
+        let lib = libloading::Library::new(&lib).unwrap();
+        let answer = lib.get::<extern "C" fn() -> i32>(b"answer").unwrap();
+        assert_eq!(answer(), 42);
+    }
+
+    let err = compile_to_file(
+        &module,
+        &LinkOptions::new().with_target("aarch64-unknown-linux-gnu"),
+        native.path("answer"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unsupported target aarch64-unknown-linux-gnu"
+    );
+}
+
+/// Runs modules in the JIT, linking functions as they are called and
+/// resolving the other symbols in the host process and to Rust functions.
+#[test]
+#[cfg(all(unix, target_arch = "x86_64"))]
+fn jit_symbol_resolution() {
+    use vicis_codegen::codegen::jit::Jit;
+
+    extern "C" fn add_one(x: i32) -> i32 {
+        x + 1
+    }
+
+    let source = r#"
+declare i32 @abs(i32)
+declare i32 @add_one(i32)
+declare i32 @missing()
+
+define i32 @twice(i32 %x) {
+  %1 = call i32 @add_one(i32 %x)
+  %2 = add i32 %1, %1
+  ret i32 %2
+}
+
+define i32 @unused() {
+  %1 = call i32 @missing()
+  ret i32 %1
+}
+
+define i32 @main(i32 %x) {
+  %1 = call i32 @abs(i32 %x)
+  %2 = call i32 @twice(i32 %1)
+  ret i32 %2
+}
+"#;
+    let module = compile_module(X86_64, &module::parse_assembly(source).unwrap()).unwrap();
+    let jit = Jit::from_module(&module)
+        .unwrap()
+        .with_symbol("add_one", add_one as extern "C" fn(i32) -> i32 as *const u8);
+    let main = jit.get_function("main").unwrap();
+    assert!(!jit.is_linked("main"));
+    let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn(i32) -> i32>(main) };
+    assert_eq!(main(-4), 10);
+    assert_eq!(main(2), 6);
+    assert!(jit.is_linked("main") && jit.is_linked("twice"));
+    // Never called, so its undefined callee is only found out when linked.
+    assert!(!jit.is_linked("unused"));
+    let err = jit.link_function("unused").unwrap_err();
+    assert_eq!(err.to_string(), "undefined symbol missing");
+
+    let source = std::fs::read_to_string("./tests/codegen/fibo.ll").unwrap();
+    let module = compile_module(X86_64, &module::parse_assembly(&source).unwrap()).unwrap();
+    let jit = Jit::from_module(&module).unwrap();
+    let main = jit.get_function("main").unwrap();
+    let main = unsafe { std::mem::transmute::<*const u8, extern "C" fn() -> i32>(main) };
+    assert_eq!(main(), 55);
+}
+
+#[test]
+fn liveness_queries() {
+    use vicis_codegen::codegen::{lower::compile_function, pass::liveness::Liveness};
+
+    let module = module::parse_assembly(
+        r#"
+define dso_local i32 @f(i32 %0) {
+  %2 = add i32 %0, 1
+  br label %3
+3:
+  %4 = add i32 %2, 2
+  ret i32 %4
+}"#,
+    )
+    .unwrap();
+    let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+    let mach_func = compile_function(X86_64, func).unwrap();
+    let mut liveness = Liveness::new();
+    liveness.analyze_function(&mach_func);
+
+    let blocks: Vec<_> = mach_func.layout.block_iter().collect();
+    let (entry, exit) = (blocks[0], blocks[1]);
+    let live_out: Vec<_> = liveness.live_out(entry).collect();
+    let live_in: Vec<_> = liveness.live_in(exit).collect();
+    // Only `%2` flows from the entry block to the next.
+    assert_eq!(live_out.len(), 1);
+    assert_eq!(live_out, live_in);
+    let vreg = live_out[0];
+    assert!(liveness.is_live_out(entry, vreg));
+    assert!(!liveness.is_live_out(exit, vreg));
+    assert_eq!(liveness.live_in(entry).count(), 0);
+
+    let br = mach_func.layout.last_inst_of(entry).unwrap();
+    assert!(liveness.is_live_across(vreg, br));
+    assert!(liveness.live_vregs_at(br).contains(&vreg));
+
+    for (&vreg, users) in &mach_func.data.vreg_users.vreg_to_insts {
+        for user in users {
+            assert!(liveness.is_live_at(vreg, user.inst_id));
+            if user.write && !user.read {
+                assert!(!liveness.is_live_across(vreg, user.inst_id));
+            }
+        }
+    }
+}
+
+#[test]
+fn frame_layout() {
+    use vicis_codegen::codegen::function::slot::SlotOrigin;
+
+    let module = module::parse_assembly(
+        r#"
+define dso_local i32 @main() {
+  %a = alloca i32, align 4
+  %b = alloca i32, align 4
+  store i32 1, i32* %a, align 4
+  store i32 2, i32* %b, align 4
+  %1 = load i32, i32* %a, align 4
+  ret i32 %1
+}"#,
+    )
+    .unwrap();
+    let main = &module.functions()[module.find_function_by_name("main").unwrap()];
+    let mach_module = compile_module(X86_64, &module).unwrap();
+    let (_, mach_main) = mach_module
+        .functions
+        .iter()
+        .find(|(_, f)| f.name == "main")
+        .unwrap();
+
+    let mut slots: Vec<_> = mach_main
+        .slots
+        .iter()
+        .map(|(_, slot)| {
+            let SlotOrigin::Alloca(inst) = slot.origin() else {
+                panic!("unexpected spill slot");
+            };
+            let dest = main.data.inst_ref(inst).dest.unwrap();
+            (dest.to_str().unwrap(), slot.size(), slot.offset().unwrap())
+        })
+        .collect();
+    slots.sort();
+    assert_eq!(slots.len(), 2);
+    let (a, b) = (&slots[0], &slots[1]);
+    assert_eq!((a.0, a.1), ("a", 4));
+    assert_eq!((b.0, b.1), ("b", 4));
+    // Slots are below the frame pointer and don't overlap.
+    assert!(a.2 < 0 && b.2 < 0);
+    assert!(a.2 + 4 <= b.2 || b.2 + 4 <= a.2);
+}
+
+#[test]
+fn expand_mem_intrinsics() {
+    use vicis_codegen::codegen::lower::expand;
+
+    let mut module = module::parse_assembly(
+        r#"
+%struct.S = type { i32, i8, i32 }
+
+declare void @llvm.memcpy.p0i8.p0i8.i64(i8*, i8*, i64, i1)
+declare void @llvm.memset.p0i8.i64(i8*, i8, i64, i1)
+declare i32 @take(%struct.S*)
+
+define internal i32 @take_local(%struct.S* byval %0) {
+  ret i32 0
+}
+
+define dso_local i32 @main(i8* %0, i8* %1, %struct.S* %2) {
+  call void @llvm.memcpy.p0i8.p0i8.i64(i8* %0, i8* %1, i64 6, i1 false)
+  call void @llvm.memset.p0i8.i64(i8* %0, i8 1, i64 4, i1 false)
+  call void @llvm.memcpy.p0i8.p0i8.i64(i8* %0, i8* %1, i64 4096, i1 false)
+  %4 = call i32 @take_local(%struct.S* byval %2)
+  %5 = call i32 @take(%struct.S* byval %2)
+  %6 = add i32 %4, %5
+  ret i32 %6
+}"#,
+    )
+    .unwrap();
+    assert_eq!(expand::run_on_module(&mut module, &X86_64), 3);
+
+    let id = module.find_function_by_name("main").unwrap();
+    let ir = format!("{:?}", module.functions()[id]);
+    // The 4096-byte copy is too long to be expanded.
+    assert_eq!(ir.matches("@llvm.memcpy").count(), 1);
+    assert!(!ir.contains("@llvm.memset"));
+    assert!(ir.contains("store i32 16843009"));
+    // 6 bytes are copied as an i32 and two i8s, and %struct.S as an i64 and
+    // an i32.
+    assert_eq!(ir.matches("load i64").count(), 1);
+    assert_eq!(ir.matches("load i32").count(), 2);
+    assert_eq!(ir.matches("load i8").count(), 2);
+    assert_eq!(ir.matches("alloca %struct.S").count(), 1);
+    // Only the local callee gets the copy. @take expects it in the stack
+    // argument area, which is not lowered yet.
+    assert_eq!(ir.matches("byval").count(), 1);
+
+    let module = module::parse_assembly(
+        r#"
+%struct.S = type { i32, i8, i32 }
+
+declare i32 @take(%struct.S*)
+
+define dso_local i32 @main(%struct.S* %0) {
+  %2 = call i32 @take(%struct.S* byval %0)
+  ret i32 %2
+}"#,
+    )
+    .unwrap();
+    assert!(compile_module(X86_64, &module).is_err());
+}
+
+#[test]
+fn system_v_abi() {
+    use vicis_codegen::codegen::{
+        call_conv::testing::{self, ArgRegs, ArgTy::*, Expect::*, Golden, Loc::*},
+        isa::x86_64::register::{GR64, XMM},
+    };
+
+    // Integers and structs of up to two eightbytes go in the next free
+    // integer registers, if there are enough left for the whole argument.
+    // `float`s and `double`s go in the next free SSE registers.
+    const GOLDEN: &[Golden] = &[
+        (&[I32], Supported(&[Int(0, 1)])),
+        (&[I64], Supported(&[Int(0, 1)])),
+        (&[F32], Supported(&[Float(0)])),
+        (&[F64], Supported(&[Float(0)])),
+        (
+            &[I32, I32, I32, I32, I32, I32],
+            Supported(&[
+                Int(0, 1),
+                Int(1, 1),
+                Int(2, 1),
+                Int(3, 1),
+                Int(4, 1),
+                Int(5, 1),
+            ]),
+        ),
+        (
+            &[I64, I64, I64, I64, I64, I64],
+            Supported(&[
+                Int(0, 1),
+                Int(1, 1),
+                Int(2, 1),
+                Int(3, 1),
+                Int(4, 1),
+                Int(5, 1),
+            ]),
+        ),
+        (
+            &[I32, I32, I32, I32, I32, I32, I32],
+            Unsupported(&[
+                Int(0, 1),
+                Int(1, 1),
+                Int(2, 1),
+                Int(3, 1),
+                Int(4, 1),
+                Int(5, 1),
+                Memory,
+            ]),
+        ),
+        (
+            &[F64, F64, F64, F64, F64, F64, F64, F64],
+            Supported(&[
+                Float(0),
+                Float(1),
+                Float(2),
+                Float(3),
+                Float(4),
+                Float(5),
+                Float(6),
+                Float(7),
+            ]),
+        ),
+        (
+            &[F32, F32, F32, F32, F32, F32, F32, F32, F32],
+            Unsupported(&[
+                Float(0),
+                Float(1),
+                Float(2),
+                Float(3),
+                Float(4),
+                Float(5),
+                Float(6),
+                Float(7),
+                Memory,
+            ]),
+        ),
+        (&[Struct(1)], Unsupported(&[Int(0, 1)])),
+        (&[Struct(2)], Unsupported(&[Int(0, 1)])),
+        (&[Struct(3)], Unsupported(&[Int(0, 2)])),
+        (&[Struct(4)], Unsupported(&[Int(0, 2)])),
+        (&[Struct(5)], Unsupported(&[Memory])),
+        (
+            &[Struct(3), Struct(3), Struct(3), Struct(3)],
+            Unsupported(&[Int(0, 2), Int(2, 2), Int(4, 2), Memory]),
+        ),
+        (
+            &[I32, I32, I32, I32, I32, Struct(3), I32],
+            Unsupported(&[
+                Int(0, 1),
+                Int(1, 1),
+                Int(2, 1),
+                Int(3, 1),
+                Int(4, 1),
+                Memory,
+                Int(5, 1),
+            ]),
+        ),
+        (
+            &[I32, F64, I64, F32],
+            Supported(&[Int(0, 1), Float(0), Int(1, 1), Float(1)]),
+        ),
+        (
+            &[I32, Struct(3), F64, Struct(5), I64],
+            Unsupported(&[Int(0, 1), Int(1, 2), Float(0), Memory, Int(3, 1)]),
+        ),
+    ];
+
+    let regs = ArgRegs {
+        int: &[
+            GR64::RDI.into(),
+            GR64::RSI.into(),
+            GR64::RDX.into(),
+            GR64::RCX.into(),
+            GR64::R8.into(),
+            GR64::R9.into(),
+        ],
+        float: &[
+            XMM::XMM0.into(),
+            XMM::XMM1.into(),
+            XMM::XMM2.into(),
+            XMM::XMM3.into(),
+            XMM::XMM4.into(),
+            XMM::XMM5.into(),
+            XMM::XMM6.into(),
+            XMM::XMM7.into(),
+        ],
+    };
+    let report = testing::check(X86_64, &regs, GOLDEN);
+    assert!(report.is_ok(), "{:#?}", report);
+}
+
+#[test]
+fn aapcs64_abi() {
+    use vicis_codegen::codegen::{
+        call_conv::testing::{self, ArgRegs, ArgTy::*, Expect::*, Golden, Loc::*},
+        isa::aarch64::register::GR64,
+    };
+
+    // Integers and structs of up to 16 bytes go in the next free registers.
+    // Once an argument doesn't fit in the registers left, it and all the
+    // following ones go in memory. Larger structs are passed by reference.
+    const GOLDEN: &[Golden] = &[
+        (&[I32], Supported(&[Int(0, 1)])),
+        (&[I64], Supported(&[Int(0, 1)])),
+        (&[F32], Unsupported(&[Float(0)])),
+        (&[F64], Unsupported(&[Float(0)])),
+        (
+            &[I32, I32, I32, I32, I32, I32, I32, I32],
+            Supported(&[
+                Int(0, 1),
+                Int(1, 1),
+                Int(2, 1),
+                Int(3, 1),
+                Int(4, 1),
+                Int(5, 1),
+                Int(6, 1),
+                Int(7, 1),
+            ]),
+        ),
+        (
+            &[I64, I64, I64, I64, I64, I64, I64, I64],
+            Supported(&[
+                Int(0, 1),
+                Int(1, 1),
+                Int(2, 1),
+                Int(3, 1),
+                Int(4, 1),
+                Int(5, 1),
+                Int(6, 1),
+                Int(7, 1),
+            ]),
+        ),
+        (
+            &[I32, I32, I32, I32, I32, I32, I32, I32, I32],
+            Unsupported(&[
+                Int(0, 1),
+                Int(1, 1),
+                Int(2, 1),
+                Int(3, 1),
+                Int(4, 1),
+                Int(5, 1),
+                Int(6, 1),
+                Int(7, 1),
+                Memory,
+            ]),
+        ),
+        (&[Struct(1)], Unsupported(&[Int(0, 1)])),
+        (&[Struct(2)], Unsupported(&[Int(0, 1)])),
+        (&[Struct(3)], Unsupported(&[Int(0, 2)])),
+        (&[Struct(4)], Unsupported(&[Int(0, 2)])),
+        (&[Struct(5)], Unsupported(&[Int(0, 1)])),
+        (
+            &[I32, I32, I32, I32, I32, I32, I32, Struct(3), I32],
+            Unsupported(&[
+                Int(0, 1),
+                Int(1, 1),
+                Int(2, 1),
+                Int(3, 1),
+                Int(4, 1),
+                Int(5, 1),
+                Int(6, 1),
+                Memory,
+                Memory,
+            ]),
+        ),
+        (
+            &[I32, F64, I64, F32],
+            Unsupported(&[Int(0, 1), Float(0), Int(1, 1), Float(1)]),
+        ),
+    ];
+
+    let regs = ArgRegs {
+        int: &[
+            GR64::X0.into(),
+            GR64::X1.into(),
+            GR64::X2.into(),
+            GR64::X3.into(),
+            GR64::X4.into(),
+            GR64::X5.into(),
+            GR64::X6.into(),
+            GR64::X7.into(),
+        ],
+        // The backend has no floating-point registers yet.
+        float: &[],
+    };
+    let report = testing::check(AArch64, &regs, GOLDEN);
+    assert!(report.is_ok(), "{:#?}", report);
+}
+
+#[test]
+fn cdecl_abi() {
+    use vicis_codegen::codegen::call_conv::testing::{
+        self, ArgRegs, ArgTy::*, Expect::*, Golden, Loc::*,
+    };
+
+    // All arguments are pushed on the stack.
+    const GOLDEN: &[Golden] = &[
+        (&[I32], Supported(&[Memory])),
+        (&[I64], Unsupported(&[Memory])),
+        (&[F32], Unsupported(&[Memory])),
+        (&[F64], Unsupported(&[Memory])),
+        (
+            &[I32, I32, I32, I32, I32, I32, I32, I32],
+            Supported(&[
+                Memory, Memory, Memory, Memory, Memory, Memory, Memory, Memory,
+            ]),
+        ),
+        (&[Struct(3)], Unsupported(&[Memory])),
+        (
+            &[I32, F64, I64, F32],
+            Unsupported(&[Memory, Memory, Memory, Memory]),
+        ),
+    ];
+
+    let regs = ArgRegs {
+        int: &[],
+        float: &[],
+    };
+    let report = testing::check(I686, &regs, GOLDEN);
+    assert!(report.is_ok(), "{:#?}", report);
+}
@@ -0,0 +1,69 @@
+  .text
+  .globl ack
+  .p2align 2
+ack:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, w0
+  mov w10, w1
+  str w9, [sp, #8]
+  str w10, [sp, #4]
+  ldr w9, [sp, #8]
+  cmp w9, #0
+  b.eq .LBL0_1
+  b .LBL0_2
+.LBL0_1:
+  ldr w9, [sp, #4]
+  add w9, w9, #1
+  str w9, [sp, #12]
+  b .LBL0_5
+.LBL0_2:
+  ldr w9, [sp, #4]
+  cmp w9, #0
+  b.eq .LBL0_3
+  b .LBL0_4
+.LBL0_3:
+  ldr w9, [sp, #8]
+  sub w0, w9, #1
+  mov w1, #1
+  bl ack
+  mov w9, w0
+  str w9, [sp, #12]
+  b .LBL0_5
+.LBL0_4:
+  ldr w9, [sp, #8]
+  str w9, [sp]
+  ldr w0, [sp, #8]
+  ldr w9, [sp, #4]
+  sub w1, w9, #1
+  bl ack
+  mov w1, w0
+  ldr w9, [sp]
+  sub w0, w9, #1
+  bl ack
+  mov w9, w0
+  str w9, [sp, #12]
+  b .LBL0_5
+.LBL0_5:
+  ldr w0, [sp, #12]
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .globl main
+  .p2align 2
+main:
+.LBL1_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, #0
+  str w9, [sp, #12]
+  mov w0, #2
+  mov w1, #3
+  bl ack
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
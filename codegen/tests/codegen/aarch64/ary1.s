@@ -0,0 +1,23 @@
+  .text
+  .globl main
+  .p2align 2
+main:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #32
+  mov w9, #0
+  str w9, [sp, #28]
+  mov w9, #0
+  str w9, [sp, #8]
+  mov w9, #1
+  str w9, [sp, #12]
+  mov w9, #2
+  str w9, [sp, #16]
+  mov w9, #3
+  str w9, [sp, #20]
+  mov w0, #0
+  add sp, sp, #32
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,20 @@
+  .text
+  .globl main
+  .p2align 2
+main:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #32
+  mov w9, #42
+  str w9, [sp, #28]
+  ldr w9, [sp, #28]
+  str w9, [sp, #8]
+  str w9, [sp, #12]
+  str w9, [sp, #16]
+  str w9, [sp, #20]
+  mov w0, #0
+  add sp, sp, #32
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,27 @@
+  .text
+  .globl main
+  .p2align 2
+main:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #32
+  mov w9, #0
+  str w9, [sp, #28]
+  mov w9, #0
+  str w9, [sp, #12]
+  ldrsw x9, [sp, #12]
+  add x10, sp, #16
+  mov w11, #1
+  str w11, [x10, x9, lsl #2]
+  mov w9, #1
+  str w9, [sp, #12]
+  ldrsw x9, [sp, #12]
+  add x10, sp, #16
+  mov w11, #2
+  str w11, [x10, x9, lsl #2]
+  mov w0, #0
+  add sp, sp, #32
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,21 @@
+  .text
+  .globl main
+  .p2align 2
+main:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, #0
+  str w9, [sp, #12]
+  mov w9, #1
+  str w9, [sp]
+  mov w9, #2
+  str w9, [sp, #4]
+  ldr w9, [sp]
+  ldr w10, [sp, #4]
+  add w0, w9, w10
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,60 @@
+  .text
+  .globl main
+  .p2align 2
+main:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #64
+  mov w9, #0
+  str w9, [sp, #60]
+  mov w9, #0
+  str w9, [sp, #12]
+  mov w9, #0
+  str w9, [sp, #8]
+  b .LBL0_1
+.LBL0_1:
+  ldr w9, [sp, #8]
+  cmp w9, #10
+  b.lt .LBL0_2
+  b .LBL0_4
+.LBL0_2:
+  ldr w9, [sp, #8]
+  ldrsw x10, [sp, #8]
+  add x11, sp, #16
+  add w9, w9, #1
+  str w9, [x11, x10, lsl #2]
+  b .LBL0_3
+.LBL0_3:
+  ldr w9, [sp, #8]
+  add w9, w9, #1
+  str w9, [sp, #8]
+  b .LBL0_1
+.LBL0_4:
+  mov w9, #0
+  str w9, [sp, #4]
+  b .LBL0_5
+.LBL0_5:
+  ldr w9, [sp, #4]
+  cmp w9, #10
+  b.lt .LBL0_6
+  b .LBL0_8
+.LBL0_6:
+  ldrsw x9, [sp, #4]
+  add x10, sp, #16
+  ldr w9, [x10, x9, lsl #2]
+  ldr w10, [sp, #12]
+  add w9, w10, w9
+  str w9, [sp, #12]
+  b .LBL0_7
+.LBL0_7:
+  ldr w9, [sp, #4]
+  add w9, w9, #1
+  str w9, [sp, #4]
+  b .LBL0_5
+.LBL0_8:
+  ldr w0, [sp, #12]
+  add sp, sp, #64
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,17 @@
+  .text
+  .globl main
+  .p2align 2
+main:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, #2
+  str w9, [sp, #12]
+  b .LBL0_1
+.LBL0_1:
+  ldr w0, [sp, #12]
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
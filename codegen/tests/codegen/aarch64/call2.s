@@ -0,0 +1,20 @@
+  .text
+  .globl f
+  .p2align 2
+f:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  ldp x29, x30, [sp], #16
+  ret
+  .globl main
+  .p2align 2
+main:
+.LBL1_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  mov w0, #1
+  bl f
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
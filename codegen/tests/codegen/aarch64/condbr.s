@@ -0,0 +1,25 @@
+  .text
+  .globl main
+  .p2align 2
+main:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, #2
+  str w9, [sp, #12]
+  ldr w9, [sp, #12]
+  cmp w9, #2
+  b.eq .LBL0_1
+  b .LBL0_2
+.LBL0_1:
+  mov w0, #1
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+.LBL0_2:
+  mov w0, #2
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,87 @@
+  .text
+  .globl is_even
+  .p2align 2
+is_even:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, w0
+  str w9, [sp, #8]
+  ldr w9, [sp, #8]
+  cmp w9, #0
+  b.eq .LBL0_1
+  b .LBL0_2
+.LBL0_1:
+  mov w9, #1
+  str w9, [sp, #12]
+  b .LBL0_3
+.LBL0_2:
+  ldr w9, [sp, #8]
+  sub w0, w9, #1
+  bl is_odd
+  mov w9, w0
+  str w9, [sp, #12]
+  b .LBL0_3
+.LBL0_3:
+  ldr w0, [sp, #12]
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .globl is_odd
+  .p2align 2
+is_odd:
+.LBL1_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, w0
+  str w9, [sp, #8]
+  ldr w9, [sp, #8]
+  cmp w9, #0
+  b.eq .LBL1_1
+  b .LBL1_2
+.LBL1_1:
+  mov w9, #0
+  str w9, [sp, #12]
+  b .LBL1_3
+.LBL1_2:
+  ldr w9, [sp, #8]
+  sub w0, w9, #1
+  bl is_even
+  mov w9, w0
+  str w9, [sp, #12]
+  b .LBL1_3
+.LBL1_3:
+  ldr w0, [sp, #12]
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .globl main
+  .p2align 2
+main:
+.LBL2_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, #0
+  str w9, [sp, #12]
+  mov w0, #10
+  bl is_even
+  mov w9, w0
+  str w9, [sp, #8]
+  mov w0, #7
+  bl is_odd
+  mov w9, w0
+  str w9, [sp]
+  mov w0, #8
+  bl is_odd
+  mov w9, w0
+  ldr w10, [sp, #8]
+  ldr w11, [sp]
+  add w10, w10, w11
+  add w0, w10, w9
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
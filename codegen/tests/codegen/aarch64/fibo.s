@@ -0,0 +1,52 @@
+  .text
+  .globl fibo
+  .p2align 2
+fibo:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, w0
+  str w9, [sp, #8]
+  ldr w9, [sp, #8]
+  cmp w9, #2
+  b.le .LBL0_1
+  b .LBL0_2
+.LBL0_1:
+  mov w9, #1
+  str w9, [sp, #12]
+  b .LBL0_3
+.LBL0_2:
+  ldr w9, [sp, #8]
+  sub w0, w9, #1
+  bl fibo
+  mov w9, w0
+  str w9, [sp, #4]
+  ldr w9, [sp, #8]
+  sub w0, w9, #2
+  bl fibo
+  mov w9, w0
+  ldr w10, [sp, #4]
+  add w9, w10, w9
+  str w9, [sp, #12]
+  b .LBL0_3
+.LBL0_3:
+  ldr w0, [sp, #12]
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .globl main
+  .p2align 2
+main:
+.LBL1_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, #0
+  str w9, [sp, #12]
+  mov w0, #10
+  bl fibo
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,18 @@
+  .text
+  .globl main
+  .p2align 2
+main:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, #2
+  str w9, [sp, #12]
+  ldr w9, [sp, #12]
+  add w10, w9, #1
+  add w9, w9, #2
+  add w0, w10, w9
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,25 @@
+  .text
+  .globl main
+  .p2align 2
+main:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  mov w0, #0
+  mov w10, #1
+  b .LBL0_1
+.LBL0_1:
+  cmp w10, #10
+  b.le .LBL0_2
+  b .LBL0_4
+.LBL0_2:
+  add w9, w0, w10
+  b .LBL0_3
+.LBL0_3:
+  add w10, w10, #1
+  mov w0, w9
+  b .LBL0_1
+.LBL0_4:
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
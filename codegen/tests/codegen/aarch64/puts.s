@@ -0,0 +1,25 @@
+  .text
+  .globl main
+  .p2align 2
+main:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, #0
+  str w9, [sp, #12]
+  adrp x0, .L.str
+  add x0, x0, :lo12:.L.str
+  bl puts
+  mov w0, #0
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .section .rodata
+  .p2align 0
+  .type .L.str,@object
+  .size .L.str, 12
+.L.str:
+  .asciz "hello world"
+  .text
+  .section .note.GNU-stack,"",@progbits
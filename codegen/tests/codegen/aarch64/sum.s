@@ -0,0 +1,37 @@
+  .text
+  .globl main
+  .p2align 2
+main:
+.LBL0_0:
+  stp x29, x30, [sp, #-16]!
+  mov x29, sp
+  sub sp, sp, #16
+  mov w9, #0
+  str w9, [sp, #12]
+  mov w9, #0
+  str w9, [sp, #8]
+  mov w9, #1
+  str w9, [sp, #4]
+  b .LBL0_1
+.LBL0_1:
+  ldr w9, [sp, #4]
+  cmp w9, #10
+  b.le .LBL0_2
+  b .LBL0_4
+.LBL0_2:
+  ldr w9, [sp, #4]
+  ldr w10, [sp, #8]
+  add w9, w10, w9
+  str w9, [sp, #8]
+  b .LBL0_3
+.LBL0_3:
+  ldr w9, [sp, #4]
+  add w9, w9, #1
+  str w9, [sp, #4]
+  b .LBL0_1
+.LBL0_4:
+  ldr w0, [sp, #8]
+  add sp, sp, #16
+  ldp x29, x30, [sp], #16
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,65 @@
+; ModuleID = 'ack.c'
+source_filename = "ack.c"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+; Function Attrs: noinline nounwind optnone uwtable
+define dso_local i32 @ack(i32 %0, i32 %1) #0 {
+  %3 = alloca i32, align 4
+  %4 = alloca i32, align 4
+  %5 = alloca i32, align 4
+  store i32 %0, i32* %4, align 4
+  store i32 %1, i32* %5, align 4
+  %6 = load i32, i32* %4, align 4
+  %7 = icmp eq i32 %6, 0
+  br i1 %7, label %8, label %11
+
+8:                                                ; preds = %2
+  %9 = load i32, i32* %5, align 4
+  %10 = add nsw i32 %9, 1
+  store i32 %10, i32* %3, align 4
+  br label %26
+
+11:                                               ; preds = %2
+  %12 = load i32, i32* %5, align 4
+  %13 = icmp eq i32 %12, 0
+  br i1 %13, label %14, label %18
+
+14:                                               ; preds = %11
+  %15 = load i32, i32* %4, align 4
+  %16 = sub nsw i32 %15, 1
+  %17 = call i32 @ack(i32 %16, i32 1)
+  store i32 %17, i32* %3, align 4
+  br label %26
+
+18:                                               ; preds = %11
+  %19 = load i32, i32* %4, align 4
+  %20 = sub nsw i32 %19, 1
+  %21 = load i32, i32* %4, align 4
+  %22 = load i32, i32* %5, align 4
+  %23 = sub nsw i32 %22, 1
+  %24 = call i32 @ack(i32 %21, i32 %23)
+  %25 = call i32 @ack(i32 %20, i32 %24)
+  store i32 %25, i32* %3, align 4
+  br label %26
+
+26:                                               ; preds = %18, %14, %8
+  %27 = load i32, i32* %3, align 4
+  ret i32 %27
+}
+
+; Function Attrs: noinline nounwind optnone uwtable
+define dso_local i32 @main() #0 {
+  %1 = alloca i32, align 4
+  store i32 0, i32* %1, align 4
+  %2 = call i32 @ack(i32 2, i32 3)
+  ret i32 %2
+}
+
+attributes #0 = { noinline nounwind optnone uwtable "frame-pointer"="all" "target-cpu"="x86-64" }
+
+!llvm.module.flags = !{!0}
+!llvm.ident = !{!1}
+
+!0 = !{i32 1, !"wchar_size", i32 4}
+!1 = !{!"clang version 10.0.0-4ubuntu1 "}
@@ -0,0 +1,65 @@
+  .text
+  .intel_syntax noprefix
+  .globl ack
+ack:
+.LBL0_0:
+  push rbp
+  mov rbp, rsp
+  sub rsp, 16
+  mov eax, edi
+  mov ecx, esi
+  mov dword ptr [rbp-16], eax
+  mov dword ptr [rbp-12], ecx
+  mov eax, dword ptr [rbp-16]
+  cmp eax, 0
+  je .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  mov eax, dword ptr [rbp-12]
+  add eax, 1
+  mov dword ptr [rbp-4], eax
+  jmp .LBL0_5
+.LBL0_2:
+  mov eax, dword ptr [rbp-12]
+  cmp eax, 0
+  je .LBL0_3
+  jmp .LBL0_4
+.LBL0_3:
+  mov edi, dword ptr [rbp-16]
+  sub edi, 1
+  mov esi, 1
+  call ack
+  mov dword ptr [rbp-4], eax
+  jmp .LBL0_5
+.LBL0_4:
+  mov eax, dword ptr [rbp-16]
+  mov dword ptr [rbp-8], eax
+  mov edi, dword ptr [rbp-16]
+  mov esi, dword ptr [rbp-12]
+  sub esi, 1
+  call ack
+  mov esi, eax
+  mov edi, dword ptr [rbp-8]
+  sub edi, 1
+  call ack
+  mov dword ptr [rbp-4], eax
+  jmp .LBL0_5
+.LBL0_5:
+  mov eax, dword ptr [rbp-4]
+  add rsp, 16
+  pop rbp
+  ret 
+  .globl main
+main:
+.LBL1_0:
+  push rbp
+  mov rbp, rsp
+  sub rsp, 16
+  mov dword ptr [rbp-4], 0
+  mov edi, 2
+  mov esi, 3
+  call ack
+  add rsp, 16
+  pop rbp
+  ret 
+  .section .note.GNU-stack,"",@progbits
@@ -15,3 +15,4 @@ main:
   add rsp, 32
   pop rbp
   ret 
+  .section .note.GNU-stack,"",@progbits
@@ -16,3 +16,4 @@ main:
   add rsp, 32
   pop rbp
   ret 
+  .section .note.GNU-stack,"",@progbits
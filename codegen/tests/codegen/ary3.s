@@ -17,3 +17,4 @@ main:
   add rsp, 16
   pop rbp
   ret 
+  .section .note.GNU-stack,"",@progbits
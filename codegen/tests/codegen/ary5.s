@@ -51,3 +51,4 @@ main:
   add rsp, 64
   pop rbp
   ret 
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,64 @@
+  .text
+  .globl ack
+ack:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl %edi, %eax
+  movl %esi, %ecx
+  movl %eax, -16(%rbp)
+  movl %ecx, -12(%rbp)
+  movl -16(%rbp), %eax
+  cmpl $0, %eax
+  je .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  movl -12(%rbp), %eax
+  addl $1, %eax
+  movl %eax, -4(%rbp)
+  jmp .LBL0_5
+.LBL0_2:
+  movl -12(%rbp), %eax
+  cmpl $0, %eax
+  je .LBL0_3
+  jmp .LBL0_4
+.LBL0_3:
+  movl -16(%rbp), %edi
+  subl $1, %edi
+  movl $1, %esi
+  call ack
+  movl %eax, -4(%rbp)
+  jmp .LBL0_5
+.LBL0_4:
+  movl -16(%rbp), %eax
+  movl %eax, -8(%rbp)
+  movl -16(%rbp), %edi
+  movl -12(%rbp), %esi
+  subl $1, %esi
+  call ack
+  movl %eax, %esi
+  movl -8(%rbp), %edi
+  subl $1, %edi
+  call ack
+  movl %eax, -4(%rbp)
+  jmp .LBL0_5
+.LBL0_5:
+  movl -4(%rbp), %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .globl main
+main:
+.LBL1_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl $0, -4(%rbp)
+  movl $2, %edi
+  movl $3, %esi
+  call ack
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
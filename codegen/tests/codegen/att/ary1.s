@@ -0,0 +1,17 @@
+  .text
+  .globl main
+main:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $32, %rsp
+  movl $0, -20(%rbp)
+  movl $0, -16(%rbp)
+  movl $1, -12(%rbp)
+  movl $2, -8(%rbp)
+  movl $3, -4(%rbp)
+  movl $0, %eax
+  addq $32, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,18 @@
+  .text
+  .globl main
+main:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $32, %rsp
+  movl $42, -20(%rbp)
+  movl -20(%rbp), %eax
+  movl %eax, -16(%rbp)
+  movl %eax, -12(%rbp)
+  movl %eax, -8(%rbp)
+  movl %eax, -4(%rbp)
+  movl $0, %eax
+  addq $32, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,19 @@
+  .text
+  .globl main
+main:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl $0, -16(%rbp)
+  movl $0, -12(%rbp)
+  movslq -12(%rbp), %rax
+  movl $1, -8(%rbp,%rax,4)
+  movl $1, -12(%rbp)
+  movslq -12(%rbp), %rax
+  movl $2, -8(%rbp,%rax,4)
+  movl $0, %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
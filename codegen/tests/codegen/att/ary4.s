@@ -0,0 +1,17 @@
+  .text
+  .globl main
+main:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl $0, -12(%rbp)
+  movl $1, -8(%rbp)
+  movl $2, -4(%rbp)
+  movl -8(%rbp), %eax
+  movl -4(%rbp), %ecx
+  addl %ecx, %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,53 @@
+  .text
+  .globl main
+main:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $64, %rsp
+  movl $0, -56(%rbp)
+  movl $0, -4(%rbp)
+  movl $0, -52(%rbp)
+  jmp .LBL0_1
+.LBL0_1:
+  movl -52(%rbp), %eax
+  cmpl $10, %eax
+  jl .LBL0_2
+  jmp .LBL0_4
+.LBL0_2:
+  movl -52(%rbp), %eax
+  movslq -52(%rbp), %rcx
+  addl $1, %eax
+  movl %eax, -48(%rbp,%rcx,4)
+  jmp .LBL0_3
+.LBL0_3:
+  movl -52(%rbp), %eax
+  addl $1, %eax
+  movl %eax, -52(%rbp)
+  jmp .LBL0_1
+.LBL0_4:
+  movl $0, -8(%rbp)
+  jmp .LBL0_5
+.LBL0_5:
+  movl -8(%rbp), %eax
+  cmpl $10, %eax
+  jl .LBL0_6
+  jmp .LBL0_8
+.LBL0_6:
+  movslq -8(%rbp), %rax
+  movl -48(%rbp,%rax,4), %eax
+  movl -4(%rbp), %ecx
+  addl %eax, %ecx
+  movl %ecx, -4(%rbp)
+  jmp .LBL0_7
+.LBL0_7:
+  movl -8(%rbp), %eax
+  addl $1, %eax
+  movl %eax, -8(%rbp)
+  jmp .LBL0_5
+.LBL0_8:
+  movl -4(%rbp), %eax
+  addq $64, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,15 @@
+  .text
+  .globl main
+main:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl $2, -4(%rbp)
+  jmp .LBL0_1
+.LBL0_1:
+  movl -4(%rbp), %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
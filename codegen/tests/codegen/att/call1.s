@@ -0,0 +1,21 @@
+  .text
+  .globl f
+f:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  movl $1, %eax
+  popq %rbp
+  ret
+  .globl main
+main:
+.LBL1_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl $0, -4(%rbp)
+  call f
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
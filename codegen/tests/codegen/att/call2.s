@@ -0,0 +1,19 @@
+  .text
+  .globl f
+f:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  movl %edi, %eax
+  popq %rbp
+  ret
+  .globl main
+main:
+.LBL1_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  movl $1, %edi
+  call f
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
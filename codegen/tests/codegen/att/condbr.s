@@ -0,0 +1,23 @@
+  .text
+  .globl main
+main:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl $2, -4(%rbp)
+  movl -4(%rbp), %eax
+  cmpl $2, %eax
+  je .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  movl $1, %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+.LBL0_2:
+  movl $2, %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,77 @@
+  .text
+  .globl is_even
+is_even:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl %edi, %eax
+  movl %eax, -8(%rbp)
+  movl -8(%rbp), %eax
+  cmpl $0, %eax
+  je .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  movl $1, -4(%rbp)
+  jmp .LBL0_3
+.LBL0_2:
+  movl -8(%rbp), %edi
+  subl $1, %edi
+  call is_odd
+  movl %eax, -4(%rbp)
+  jmp .LBL0_3
+.LBL0_3:
+  movl -4(%rbp), %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .globl is_odd
+is_odd:
+.LBL1_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl %edi, %eax
+  movl %eax, -8(%rbp)
+  movl -8(%rbp), %eax
+  cmpl $0, %eax
+  je .LBL1_1
+  jmp .LBL1_2
+.LBL1_1:
+  movl $0, -4(%rbp)
+  jmp .LBL1_3
+.LBL1_2:
+  movl -8(%rbp), %edi
+  subl $1, %edi
+  call is_even
+  movl %eax, -4(%rbp)
+  jmp .LBL1_3
+.LBL1_3:
+  movl -4(%rbp), %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .globl main
+main:
+.LBL2_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl $0, -12(%rbp)
+  movl $10, %edi
+  call is_even
+  movl %eax, -8(%rbp)
+  movl $7, %edi
+  call is_odd
+  movl %eax, -4(%rbp)
+  movl $8, %edi
+  call is_odd
+  movl -8(%rbp), %ecx
+  movl -4(%rbp), %edx
+  addl %edx, %ecx
+  addl %eax, %ecx
+  movl %ecx, %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,46 @@
+  .text
+  .globl fibo
+fibo:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl %edi, %eax
+  movl %eax, -12(%rbp)
+  movl -12(%rbp), %eax
+  cmpl $2, %eax
+  jle .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  movl $1, -4(%rbp)
+  jmp .LBL0_3
+.LBL0_2:
+  movl -12(%rbp), %edi
+  subl $1, %edi
+  call fibo
+  movl %eax, -8(%rbp)
+  movl -12(%rbp), %edi
+  subl $2, %edi
+  call fibo
+  movl -8(%rbp), %ecx
+  addl %eax, %ecx
+  movl %ecx, -4(%rbp)
+  jmp .LBL0_3
+.LBL0_3:
+  movl -4(%rbp), %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .globl main
+main:
+.LBL1_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl $0, -4(%rbp)
+  movl $10, %edi
+  call fibo
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
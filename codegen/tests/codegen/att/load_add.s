@@ -0,0 +1,18 @@
+  .text
+  .globl main
+main:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl $2, -4(%rbp)
+  movl -4(%rbp), %eax
+  movl %eax, %ecx
+  addl $1, %ecx
+  addl $2, %eax
+  addl %eax, %ecx
+  movl %ecx, %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
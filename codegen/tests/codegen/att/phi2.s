@@ -0,0 +1,23 @@
+  .text
+  .globl main
+main:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  movl $0, %eax
+  movl $1, %ecx
+  jmp .LBL0_1
+.LBL0_1:
+  cmpl $10, %ecx
+  jle .LBL0_2
+  jmp .LBL0_4
+.LBL0_2:
+  addl %ecx, %eax
+  jmp .LBL0_3
+.LBL0_3:
+  addl $1, %ecx
+  jmp .LBL0_1
+.LBL0_4:
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,22 @@
+  .text
+  .globl main
+main:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl $0, -4(%rbp)
+  movq $.L.str, %rdi
+  call puts
+  movl $0, %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .section .rodata
+  .p2align 0
+  .type .L.str,@object
+  .size .L.str, 12
+.L.str:
+  .asciz "hello world"
+  .text
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,33 @@
+  .text
+  .globl main
+main:
+.LBL0_0:
+  pushq %rbp
+  movq %rsp, %rbp
+  subq $16, %rsp
+  movl $0, -12(%rbp)
+  movl $0, -4(%rbp)
+  movl $1, -8(%rbp)
+  jmp .LBL0_1
+.LBL0_1:
+  movl -8(%rbp), %eax
+  cmpl $10, %eax
+  jle .LBL0_2
+  jmp .LBL0_4
+.LBL0_2:
+  movl -8(%rbp), %eax
+  movl -4(%rbp), %ecx
+  addl %eax, %ecx
+  movl %ecx, -4(%rbp)
+  jmp .LBL0_3
+.LBL0_3:
+  movl -8(%rbp), %eax
+  addl $1, %eax
+  movl %eax, -8(%rbp)
+  jmp .LBL0_1
+.LBL0_4:
+  movl -4(%rbp), %eax
+  addq $16, %rsp
+  popq %rbp
+  ret
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,7 @@
+int fibo(int n) {
+  if (n <= 2)
+    return 1;
+  return fibo(n - 1) + fibo(n - 2);
+}
+
+int main() { return fibo(10); }
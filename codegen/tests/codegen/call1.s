@@ -19,3 +19,4 @@ main:
   add rsp, 16
   pop rbp
   ret 
+  .section .note.GNU-stack,"",@progbits
@@ -17,3 +17,4 @@ main:
   call f
   pop rbp
   ret 
+  .section .note.GNU-stack,"",@progbits
@@ -21,3 +21,4 @@ main:
   add rsp, 16
   pop rbp
   ret 
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,74 @@
+; ModuleID = 'even_odd.c'
+source_filename = "even_odd.c"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+; Function Attrs: noinline nounwind optnone uwtable
+define dso_local i32 @is_even(i32 %0) #0 {
+  %2 = alloca i32, align 4
+  %3 = alloca i32, align 4
+  store i32 %0, i32* %3, align 4
+  %4 = load i32, i32* %3, align 4
+  %5 = icmp eq i32 %4, 0
+  br i1 %5, label %6, label %7
+
+6:                                                ; preds = %1
+  store i32 1, i32* %2, align 4
+  br label %11
+
+7:                                                ; preds = %1
+  %8 = load i32, i32* %3, align 4
+  %9 = sub nsw i32 %8, 1
+  %10 = call i32 @is_odd(i32 %9)
+  store i32 %10, i32* %2, align 4
+  br label %11
+
+11:                                               ; preds = %7, %6
+  %12 = load i32, i32* %2, align 4
+  ret i32 %12
+}
+
+; Function Attrs: noinline nounwind optnone uwtable
+define dso_local i32 @is_odd(i32 %0) #0 {
+  %2 = alloca i32, align 4
+  %3 = alloca i32, align 4
+  store i32 %0, i32* %3, align 4
+  %4 = load i32, i32* %3, align 4
+  %5 = icmp eq i32 %4, 0
+  br i1 %5, label %6, label %7
+
+6:                                                ; preds = %1
+  store i32 0, i32* %2, align 4
+  br label %11
+
+7:                                                ; preds = %1
+  %8 = load i32, i32* %3, align 4
+  %9 = sub nsw i32 %8, 1
+  %10 = call i32 @is_even(i32 %9)
+  store i32 %10, i32* %2, align 4
+  br label %11
+
+11:                                               ; preds = %7, %6
+  %12 = load i32, i32* %2, align 4
+  ret i32 %12
+}
+
+; Function Attrs: noinline nounwind optnone uwtable
+define dso_local i32 @main() #0 {
+  %1 = alloca i32, align 4
+  store i32 0, i32* %1, align 4
+  %2 = call i32 @is_even(i32 10)
+  %3 = call i32 @is_odd(i32 7)
+  %4 = add nsw i32 %2, %3
+  %5 = call i32 @is_odd(i32 8)
+  %6 = add nsw i32 %4, %5
+  ret i32 %6
+}
+
+attributes #0 = { noinline nounwind optnone uwtable "frame-pointer"="all" "target-cpu"="x86-64" }
+
+!llvm.module.flags = !{!0}
+!llvm.ident = !{!1}
+
+!0 = !{i32 1, !"wchar_size", i32 4}
+!1 = !{!"clang version 10.0.0-4ubuntu1 "}
@@ -0,0 +1,78 @@
+  .text
+  .intel_syntax noprefix
+  .globl is_even
+is_even:
+.LBL0_0:
+  push rbp
+  mov rbp, rsp
+  sub rsp, 16
+  mov eax, edi
+  mov dword ptr [rbp-8], eax
+  mov eax, dword ptr [rbp-8]
+  cmp eax, 0
+  je .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  mov dword ptr [rbp-4], 1
+  jmp .LBL0_3
+.LBL0_2:
+  mov edi, dword ptr [rbp-8]
+  sub edi, 1
+  call is_odd
+  mov dword ptr [rbp-4], eax
+  jmp .LBL0_3
+.LBL0_3:
+  mov eax, dword ptr [rbp-4]
+  add rsp, 16
+  pop rbp
+  ret 
+  .globl is_odd
+is_odd:
+.LBL1_0:
+  push rbp
+  mov rbp, rsp
+  sub rsp, 16
+  mov eax, edi
+  mov dword ptr [rbp-8], eax
+  mov eax, dword ptr [rbp-8]
+  cmp eax, 0
+  je .LBL1_1
+  jmp .LBL1_2
+.LBL1_1:
+  mov dword ptr [rbp-4], 0
+  jmp .LBL1_3
+.LBL1_2:
+  mov edi, dword ptr [rbp-8]
+  sub edi, 1
+  call is_even
+  mov dword ptr [rbp-4], eax
+  jmp .LBL1_3
+.LBL1_3:
+  mov eax, dword ptr [rbp-4]
+  add rsp, 16
+  pop rbp
+  ret 
+  .globl main
+main:
+.LBL2_0:
+  push rbp
+  mov rbp, rsp
+  sub rsp, 16
+  mov dword ptr [rbp-12], 0
+  mov edi, 10
+  call is_even
+  mov dword ptr [rbp-8], eax
+  mov edi, 7
+  call is_odd
+  mov dword ptr [rbp-4], eax
+  mov edi, 8
+  call is_odd
+  mov ecx, dword ptr [rbp-8]
+  mov edx, dword ptr [rbp-4]
+  add ecx, edx
+  add ecx, eax
+  mov eax, ecx
+  add rsp, 16
+  pop rbp
+  ret 
+  .section .note.GNU-stack,"",@progbits
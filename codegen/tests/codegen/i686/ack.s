@@ -0,0 +1,77 @@
+  .text
+  .intel_syntax noprefix
+  .globl ack
+ack:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 24
+  mov eax, dword ptr [ebp+8]
+  mov ecx, dword ptr [ebp+12]
+  mov dword ptr [ebp-16], eax
+  mov dword ptr [ebp-12], ecx
+  mov eax, dword ptr [ebp-16]
+  cmp eax, 0
+  je .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  mov eax, dword ptr [ebp-12]
+  add eax, 1
+  mov dword ptr [ebp-4], eax
+  jmp .LBL0_5
+.LBL0_2:
+  mov eax, dword ptr [ebp-12]
+  cmp eax, 0
+  je .LBL0_3
+  jmp .LBL0_4
+.LBL0_3:
+  mov eax, dword ptr [ebp-16]
+  sub eax, 1
+  sub esp, 8
+  push 1
+  push eax
+  call ack
+  add esp, 16
+  mov dword ptr [ebp-4], eax
+  jmp .LBL0_5
+.LBL0_4:
+  mov eax, dword ptr [ebp-16]
+  mov dword ptr [ebp-8], eax
+  mov eax, dword ptr [ebp-16]
+  mov ecx, dword ptr [ebp-12]
+  sub ecx, 1
+  sub esp, 8
+  push ecx
+  push eax
+  call ack
+  add esp, 16
+  mov ecx, dword ptr [ebp-8]
+  sub ecx, 1
+  sub esp, 8
+  push eax
+  push ecx
+  call ack
+  add esp, 16
+  mov dword ptr [ebp-4], eax
+  jmp .LBL0_5
+.LBL0_5:
+  mov eax, dword ptr [ebp-4]
+  add esp, 24
+  pop ebp
+  ret 
+  .globl main
+main:
+.LBL1_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov dword ptr [ebp-4], 0
+  sub esp, 8
+  push 3
+  push 2
+  call ack
+  add esp, 16
+  add esp, 8
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
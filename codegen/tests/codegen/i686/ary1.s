@@ -0,0 +1,18 @@
+  .text
+  .intel_syntax noprefix
+  .globl main
+main:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 24
+  mov dword ptr [ebp-20], 0
+  mov dword ptr [ebp-16], 0
+  mov dword ptr [ebp-12], 1
+  mov dword ptr [ebp-8], 2
+  mov dword ptr [ebp-4], 3
+  mov eax, 0
+  add esp, 24
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
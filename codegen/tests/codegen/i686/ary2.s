@@ -0,0 +1,19 @@
+  .text
+  .intel_syntax noprefix
+  .globl main
+main:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 24
+  mov dword ptr [ebp-20], 42
+  mov eax, dword ptr [ebp-20]
+  mov dword ptr [ebp-16], eax
+  mov dword ptr [ebp-12], eax
+  mov dword ptr [ebp-8], eax
+  mov dword ptr [ebp-4], eax
+  mov eax, 0
+  add esp, 24
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,20 @@
+  .text
+  .intel_syntax noprefix
+  .globl main
+main:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 24
+  mov dword ptr [ebp-16], 0
+  mov dword ptr [ebp-12], 0
+  mov eax, dword ptr [ebp-12]
+  mov dword ptr [ebp-8+eax*4], 1
+  mov dword ptr [ebp-12], 1
+  mov eax, dword ptr [ebp-12]
+  mov dword ptr [ebp-8+eax*4], 2
+  mov eax, 0
+  add esp, 24
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
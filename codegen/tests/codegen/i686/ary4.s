@@ -0,0 +1,18 @@
+  .text
+  .intel_syntax noprefix
+  .globl main
+main:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 24
+  mov dword ptr [ebp-12], 0
+  mov dword ptr [ebp-8], 1
+  mov dword ptr [ebp-4], 2
+  mov eax, dword ptr [ebp-8]
+  mov ecx, dword ptr [ebp-4]
+  add eax, ecx
+  add esp, 24
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,54 @@
+  .text
+  .intel_syntax noprefix
+  .globl main
+main:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 56
+  mov dword ptr [ebp-56], 0
+  mov dword ptr [ebp-4], 0
+  mov dword ptr [ebp-52], 0
+  jmp .LBL0_1
+.LBL0_1:
+  mov eax, dword ptr [ebp-52]
+  cmp eax, 10
+  jl .LBL0_2
+  jmp .LBL0_4
+.LBL0_2:
+  mov eax, dword ptr [ebp-52]
+  mov ecx, dword ptr [ebp-52]
+  add eax, 1
+  mov dword ptr [ebp-48+ecx*4], eax
+  jmp .LBL0_3
+.LBL0_3:
+  mov eax, dword ptr [ebp-52]
+  add eax, 1
+  mov dword ptr [ebp-52], eax
+  jmp .LBL0_1
+.LBL0_4:
+  mov dword ptr [ebp-8], 0
+  jmp .LBL0_5
+.LBL0_5:
+  mov eax, dword ptr [ebp-8]
+  cmp eax, 10
+  jl .LBL0_6
+  jmp .LBL0_8
+.LBL0_6:
+  mov eax, dword ptr [ebp-8]
+  mov eax, dword ptr [ebp-48+eax*4]
+  mov ecx, dword ptr [ebp-4]
+  add ecx, eax
+  mov dword ptr [ebp-4], ecx
+  jmp .LBL0_7
+.LBL0_7:
+  mov eax, dword ptr [ebp-8]
+  add eax, 1
+  mov dword ptr [ebp-8], eax
+  jmp .LBL0_5
+.LBL0_8:
+  mov eax, dword ptr [ebp-4]
+  add esp, 56
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,16 @@
+  .text
+  .intel_syntax noprefix
+  .globl main
+main:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov dword ptr [ebp-4], 2
+  jmp .LBL0_1
+.LBL0_1:
+  mov eax, dword ptr [ebp-4]
+  add esp, 8
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
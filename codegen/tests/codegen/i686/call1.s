@@ -0,0 +1,24 @@
+  .text
+  .intel_syntax noprefix
+  .globl f
+f:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov eax, 1
+  add esp, 8
+  pop ebp
+  ret 
+  .globl main
+main:
+.LBL1_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov dword ptr [ebp-4], 0
+  call f
+  add esp, 8
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,26 @@
+  .text
+  .intel_syntax noprefix
+  .globl f
+f:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov eax, dword ptr [ebp+8]
+  add esp, 8
+  pop ebp
+  ret 
+  .globl main
+main:
+.LBL1_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  sub esp, 12
+  push 1
+  call f
+  add esp, 16
+  add esp, 8
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,24 @@
+  .text
+  .intel_syntax noprefix
+  .globl main
+main:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov dword ptr [ebp-4], 2
+  mov eax, dword ptr [ebp-4]
+  cmp eax, 2
+  je .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  mov eax, 1
+  add esp, 8
+  pop ebp
+  ret 
+.LBL0_2:
+  mov eax, 2
+  add esp, 8
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,90 @@
+  .text
+  .intel_syntax noprefix
+  .globl is_even
+is_even:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov eax, dword ptr [ebp+8]
+  mov dword ptr [ebp-8], eax
+  mov eax, dword ptr [ebp-8]
+  cmp eax, 0
+  je .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  mov dword ptr [ebp-4], 1
+  jmp .LBL0_3
+.LBL0_2:
+  mov eax, dword ptr [ebp-8]
+  sub eax, 1
+  sub esp, 12
+  push eax
+  call is_odd
+  add esp, 16
+  mov dword ptr [ebp-4], eax
+  jmp .LBL0_3
+.LBL0_3:
+  mov eax, dword ptr [ebp-4]
+  add esp, 8
+  pop ebp
+  ret 
+  .globl is_odd
+is_odd:
+.LBL1_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov eax, dword ptr [ebp+8]
+  mov dword ptr [ebp-8], eax
+  mov eax, dword ptr [ebp-8]
+  cmp eax, 0
+  je .LBL1_1
+  jmp .LBL1_2
+.LBL1_1:
+  mov dword ptr [ebp-4], 0
+  jmp .LBL1_3
+.LBL1_2:
+  mov eax, dword ptr [ebp-8]
+  sub eax, 1
+  sub esp, 12
+  push eax
+  call is_even
+  add esp, 16
+  mov dword ptr [ebp-4], eax
+  jmp .LBL1_3
+.LBL1_3:
+  mov eax, dword ptr [ebp-4]
+  add esp, 8
+  pop ebp
+  ret 
+  .globl main
+main:
+.LBL2_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 24
+  mov dword ptr [ebp-12], 0
+  sub esp, 12
+  push 10
+  call is_even
+  add esp, 16
+  mov dword ptr [ebp-8], eax
+  sub esp, 12
+  push 7
+  call is_odd
+  add esp, 16
+  mov dword ptr [ebp-4], eax
+  sub esp, 12
+  push 8
+  call is_odd
+  add esp, 16
+  mov ecx, dword ptr [ebp-8]
+  mov edx, dword ptr [ebp-4]
+  add ecx, edx
+  add ecx, eax
+  mov eax, ecx
+  add esp, 24
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
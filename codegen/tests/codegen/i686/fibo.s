@@ -0,0 +1,55 @@
+  .text
+  .intel_syntax noprefix
+  .globl fibo
+fibo:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 24
+  mov eax, dword ptr [ebp+8]
+  mov dword ptr [ebp-12], eax
+  mov eax, dword ptr [ebp-12]
+  cmp eax, 2
+  jle .LBL0_1
+  jmp .LBL0_2
+.LBL0_1:
+  mov dword ptr [ebp-4], 1
+  jmp .LBL0_3
+.LBL0_2:
+  mov eax, dword ptr [ebp-12]
+  sub eax, 1
+  sub esp, 12
+  push eax
+  call fibo
+  add esp, 16
+  mov dword ptr [ebp-8], eax
+  mov eax, dword ptr [ebp-12]
+  sub eax, 2
+  sub esp, 12
+  push eax
+  call fibo
+  add esp, 16
+  mov ecx, dword ptr [ebp-8]
+  add ecx, eax
+  mov dword ptr [ebp-4], ecx
+  jmp .LBL0_3
+.LBL0_3:
+  mov eax, dword ptr [ebp-4]
+  add esp, 24
+  pop ebp
+  ret 
+  .globl main
+main:
+.LBL1_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov dword ptr [ebp-4], 0
+  sub esp, 12
+  push 10
+  call fibo
+  add esp, 16
+  add esp, 8
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,19 @@
+  .text
+  .intel_syntax noprefix
+  .globl main
+main:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov dword ptr [ebp-4], 2
+  mov eax, dword ptr [ebp-4]
+  mov ecx, eax
+  add ecx, 1
+  add eax, 2
+  add ecx, eax
+  mov eax, ecx
+  add esp, 8
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
@@ -0,0 +1,26 @@
+  .text
+  .intel_syntax noprefix
+  .globl main
+main:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov eax, 0
+  mov ecx, 1
+  jmp .LBL0_1
+.LBL0_1:
+  cmp ecx, 10
+  jle .LBL0_2
+  jmp .LBL0_4
+.LBL0_2:
+  add eax, ecx
+  jmp .LBL0_3
+.LBL0_3:
+  add ecx, 1
+  jmp .LBL0_1
+.LBL0_4:
+  add esp, 8
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
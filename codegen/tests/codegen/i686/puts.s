@@ -0,0 +1,26 @@
+  .text
+  .intel_syntax noprefix
+  .globl main
+main:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 8
+  mov dword ptr [ebp-4], 0
+  mov eax, offset .L.str
+  sub esp, 12
+  push eax
+  call puts
+  add esp, 16
+  mov eax, 0
+  add esp, 8
+  pop ebp
+  ret 
+  .section .rodata
+  .p2align 0
+  .type .L.str,@object
+  .size .L.str, 12
+.L.str:
+  .asciz "hello world"
+  .text
+  .section .note.GNU-stack,"",@progbits
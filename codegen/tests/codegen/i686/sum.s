@@ -0,0 +1,34 @@
+  .text
+  .intel_syntax noprefix
+  .globl main
+main:
+.LBL0_0:
+  push ebp
+  mov ebp, esp
+  sub esp, 24
+  mov dword ptr [ebp-12], 0
+  mov dword ptr [ebp-4], 0
+  mov dword ptr [ebp-8], 1
+  jmp .LBL0_1
+.LBL0_1:
+  mov eax, dword ptr [ebp-8]
+  cmp eax, 10
+  jle .LBL0_2
+  jmp .LBL0_4
+.LBL0_2:
+  mov eax, dword ptr [ebp-8]
+  mov ecx, dword ptr [ebp-4]
+  add ecx, eax
+  mov dword ptr [ebp-4], ecx
+  jmp .LBL0_3
+.LBL0_3:
+  mov eax, dword ptr [ebp-8]
+  add eax, 1
+  mov dword ptr [ebp-8], eax
+  jmp .LBL0_1
+.LBL0_4:
+  mov eax, dword ptr [ebp-4]
+  add esp, 24
+  pop ebp
+  ret 
+  .section .note.GNU-stack,"",@progbits
@@ -21,3 +21,4 @@ main:
 .LBL0_4:
   pop rbp
   ret 
+  .section .note.GNU-stack,"",@progbits
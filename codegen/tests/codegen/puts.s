@@ -1,7 +1,5 @@
   .text
   .intel_syntax noprefix
-.str:
-  .string "hello world"
   .globl main
 main:
 .LBL0_0:
@@ -9,9 +7,17 @@ main:
   mov rbp, rsp
   sub rsp, 16
   mov dword ptr [rbp-4], 0
-  mov rdi, offset .str
+  mov rdi, offset .L.str
   call puts
   mov eax, 0
   add rsp, 16
   pop rbp
   ret 
+  .section .rodata
+  .p2align 0
+  .type .L.str,@object
+  .size .L.str, 12
+.L.str:
+  .asciz "hello world"
+  .text
+  .section .note.GNU-stack,"",@progbits
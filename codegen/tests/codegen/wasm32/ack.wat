@@ -0,0 +1,81 @@
+(module
+  (memory (export "memory") 2)
+  (global $__stack_pointer (mut i32) (i32.const 66560))
+  (func $ack (export "ack") (param $v0 i32) (param $v1 i32) (result i32)
+    (local $v2 i32)
+    (local $v3 i32)
+    (local $v4 i32)
+    (local $v5 i32)
+    (local $v6 i32)
+    (local $v7 i32)
+    (local $v8 i32)
+    (local $v9 i32)
+    (local $v10 i32)
+    (local $v11 i32)
+    (local $v12 i32)
+    (local $v13 i32)
+    (local $v14 i32)
+    (local $v15 i32)
+    (local $v16 i32)
+    (local $v17 i32)
+    (local $v18 i32)
+    (local $v19 i32)
+    (local $v20 i32)
+    (block $B5
+      (local.set $v19 (i32.sub (global.get $__stack_pointer) (i32.const 16)))
+      (global.set $__stack_pointer (local.get $v19))
+      (i32.store offset=4 (local.get $v19) (local.get $v0))
+      (i32.store offset=8 (local.get $v19) (local.get $v1))
+      (local.set $v2 (i32.load offset=4 (local.get $v19)))
+      (local.set $v3 (i32.eq (local.get $v2) (i32.const 0)))
+      (if (local.get $v3)
+        (then
+          (local.set $v4 (i32.load offset=8 (local.get $v19)))
+          (local.set $v5 (i32.add (local.get $v4) (i32.const 1)))
+          (i32.store (local.get $v19) (local.get $v5))
+          (br $B5)
+        )
+        (else
+          (local.set $v6 (i32.load offset=8 (local.get $v19)))
+          (local.set $v7 (i32.eq (local.get $v6) (i32.const 0)))
+          (if (local.get $v7)
+            (then
+              (local.set $v9 (i32.load offset=4 (local.get $v19)))
+              (local.set $v10 (i32.sub (local.get $v9) (i32.const 1)))
+              (local.set $v8 (call $ack (local.get $v10) (i32.const 1)))
+              (i32.store (local.get $v19) (local.get $v8))
+              (br $B5)
+            )
+            (else
+              (local.set $v12 (i32.load offset=4 (local.get $v19)))
+              (local.set $v15 (i32.load offset=4 (local.get $v19)))
+              (local.set $v16 (i32.load offset=8 (local.get $v19)))
+              (local.set $v17 (i32.sub (local.get $v16) (i32.const 1)))
+              (local.set $v14 (call $ack (local.get $v15) (local.get $v17)))
+              (local.set $v13 (i32.sub (local.get $v12) (i32.const 1)))
+              (local.set $v11 (call $ack (local.get $v13) (local.get $v14)))
+              (i32.store (local.get $v19) (local.get $v11))
+              (br $B5)
+            )
+          )
+        )
+      )
+    )
+    (local.set $v18 (i32.load (local.get $v19)))
+    (local.set $v20 (i32.add (local.get $v19) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v20))
+    (return (local.get $v18))
+  )
+  (func $main (export "main") (result i32)
+    (local $v0 i32)
+    (local $v1 i32)
+    (local $v2 i32)
+    (local.set $v1 (i32.sub (global.get $__stack_pointer) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v1))
+    (i32.store (local.get $v1) (i32.const 0))
+    (local.set $v0 (call $ack (i32.const 2) (i32.const 3)))
+    (local.set $v2 (i32.add (local.get $v1) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v2))
+    (return (local.get $v0))
+  )
+)
@@ -0,0 +1,20 @@
+(module
+  (memory (export "memory") 2)
+  (global $__stack_pointer (mut i32) (i32.const 66560))
+  (func $main (export "main") (result i32)
+    (local $v0 i32)
+    (local $v1 i32)
+    (local $v2 i32)
+    (local.set $v1 (i32.sub (global.get $__stack_pointer) (i32.const 32)))
+    (global.set $__stack_pointer (local.get $v1))
+    (i32.store (local.get $v1) (i32.const 42))
+    (local.set $v0 (i32.load (local.get $v1)))
+    (i32.store offset=4 (local.get $v1) (local.get $v0))
+    (i32.store offset=8 (local.get $v1) (local.get $v0))
+    (i32.store offset=12 (local.get $v1) (local.get $v0))
+    (i32.store offset=16 (local.get $v1) (local.get $v0))
+    (local.set $v2 (i32.add (local.get $v1) (i32.const 32)))
+    (global.set $__stack_pointer (local.get $v2))
+    (return (i32.const 0))
+  )
+)
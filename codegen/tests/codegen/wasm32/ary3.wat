@@ -0,0 +1,42 @@
+(module
+  (memory (export "memory") 2)
+  (global $__stack_pointer (mut i32) (i32.const 66560))
+  (func $main (export "main") (result i32)
+    (local $v0 i32)
+    (local $v1 i64)
+    (local $v2 i32)
+    (local $v3 i32)
+    (local $v4 i32)
+    (local $v5 i32)
+    (local $v6 i32)
+    (local $v7 i64)
+    (local $v8 i32)
+    (local $v9 i32)
+    (local $v10 i32)
+    (local $v11 i32)
+    (local $v12 i32)
+    (local $v13 i32)
+    (local.set $v12 (i32.sub (global.get $__stack_pointer) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v12))
+    (i32.store (local.get $v12) (i32.const 0))
+    (i32.store offset=12 (local.get $v12) (i32.const 0))
+    (local.set $v6 (i32.load offset=12 (local.get $v12)))
+    (local.set $v7 (i64.extend_i32_s (local.get $v6)))
+    (local.set $v8 (i32.wrap_i64 (local.get $v7)))
+    (local.set $v9 (i32.mul (local.get $v8) (i32.const 4)))
+    (local.set $v10 (i32.add (local.get $v12) (i32.const 4)))
+    (local.set $v11 (i32.add (local.get $v10) (local.get $v9)))
+    (i32.store (local.get $v11) (i32.const 1))
+    (i32.store offset=12 (local.get $v12) (i32.const 1))
+    (local.set $v0 (i32.load offset=12 (local.get $v12)))
+    (local.set $v1 (i64.extend_i32_s (local.get $v0)))
+    (local.set $v2 (i32.wrap_i64 (local.get $v1)))
+    (local.set $v3 (i32.mul (local.get $v2) (i32.const 4)))
+    (local.set $v4 (i32.add (local.get $v12) (i32.const 4)))
+    (local.set $v5 (i32.add (local.get $v4) (local.get $v3)))
+    (i32.store (local.get $v5) (i32.const 2))
+    (local.set $v13 (i32.add (local.get $v12) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v13))
+    (return (i32.const 0))
+  )
+)
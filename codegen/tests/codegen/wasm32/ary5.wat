@@ -0,0 +1,92 @@
+(module
+  (memory (export "memory") 2)
+  (global $__stack_pointer (mut i32) (i32.const 66560))
+  (func $main (export "main") (result i32)
+    (local $v0 i32)
+    (local $v1 i32)
+    (local $v2 i32)
+    (local $v3 i32)
+    (local $v4 i32)
+    (local $v5 i64)
+    (local $v6 i32)
+    (local $v7 i32)
+    (local $v8 i32)
+    (local $v9 i32)
+    (local $v10 i32)
+    (local $v11 i32)
+    (local $v12 i32)
+    (local $v13 i32)
+    (local $v14 i32)
+    (local $v15 i32)
+    (local $v16 i32)
+    (local $v17 i32)
+    (local $v18 i64)
+    (local $v19 i32)
+    (local $v20 i32)
+    (local $v21 i32)
+    (local $v22 i32)
+    (local $v23 i32)
+    (local $v24 i32)
+    (local $v25 i32)
+    (local $v26 i32)
+    (local $v27 i32)
+    (local.set $v26 (i32.sub (global.get $__stack_pointer) (i32.const 64)))
+    (global.set $__stack_pointer (local.get $v26))
+    (i32.store (local.get $v26) (i32.const 0))
+    (i32.store offset=44 (local.get $v26) (i32.const 0))
+    (i32.store offset=48 (local.get $v26) (i32.const 0))
+    (loop $L1
+      (local.set $v0 (i32.load offset=48 (local.get $v26)))
+      (local.set $v1 (i32.lt_s (local.get $v0) (i32.const 10)))
+      (if (local.get $v1)
+        (then
+          (local.set $v2 (i32.load offset=48 (local.get $v26)))
+          (local.set $v4 (i32.load offset=48 (local.get $v26)))
+          (local.set $v3 (i32.add (local.get $v2) (i32.const 1)))
+          (local.set $v5 (i64.extend_i32_s (local.get $v4)))
+          (local.set $v6 (i32.wrap_i64 (local.get $v5)))
+          (local.set $v7 (i32.mul (local.get $v6) (i32.const 4)))
+          (local.set $v8 (i32.add (local.get $v26) (i32.const 4)))
+          (local.set $v9 (i32.add (local.get $v8) (local.get $v7)))
+          (i32.store (local.get $v9) (local.get $v3))
+          (local.set $v10 (i32.load offset=48 (local.get $v26)))
+          (local.set $v11 (i32.add (local.get $v10) (i32.const 1)))
+          (i32.store offset=48 (local.get $v26) (local.get $v11))
+          (br $L1)
+        )
+        (else
+          (i32.store offset=52 (local.get $v26) (i32.const 0))
+          (loop $L5
+            (local.set $v12 (i32.load offset=52 (local.get $v26)))
+            (local.set $v13 (i32.lt_s (local.get $v12) (i32.const 10)))
+            (if (local.get $v13)
+              (then
+                (local.set $v17 (i32.load offset=52 (local.get $v26)))
+                (local.set $v18 (i64.extend_i32_s (local.get $v17)))
+                (local.set $v19 (i32.wrap_i64 (local.get $v18)))
+                (local.set $v20 (i32.mul (local.get $v19) (i32.const 4)))
+                (local.set $v21 (i32.add (local.get $v26) (i32.const 4)))
+                (local.set $v22 (i32.add (local.get $v21) (local.get $v20)))
+                (local.set $v15 (i32.load (local.get $v22)))
+                (local.set $v14 (i32.load offset=44 (local.get $v26)))
+                (local.set $v16 (i32.add (local.get $v14) (local.get $v15)))
+                (i32.store offset=44 (local.get $v26) (local.get $v16))
+                (local.set $v23 (i32.load offset=52 (local.get $v26)))
+                (local.set $v24 (i32.add (local.get $v23) (i32.const 1)))
+                (i32.store offset=52 (local.get $v26) (local.get $v24))
+                (br $L5)
+              )
+              (else
+                (local.set $v25 (i32.load offset=44 (local.get $v26)))
+                (local.set $v27 (i32.add (local.get $v26) (i32.const 64)))
+                (global.set $__stack_pointer (local.get $v27))
+                (return (local.get $v25))
+              )
+            )
+          )
+        )
+      )
+    )
+    unreachable
+  )
+)
@@ -0,0 +1,12 @@
+(module
+  (memory (export "memory") 2)
+  (global $__stack_pointer (mut i32) (i32.const 66560))
+  (func $f (export "f") (param $v0 i32) (result i32)
+    (return (local.get $v0))
+  )
+  (func $main (export "main") (result i32)
+    (local $v0 i32)
+    (local.set $v0 (call $f (i32.const 1)))
+    (return (local.get $v0))
+  )
+)
@@ -0,0 +1,92 @@
+(module
+  (memory (export "memory") 2)
+  (global $__stack_pointer (mut i32) (i32.const 66560))
+  (func $is_even (export "is_even") (param $v0 i32) (result i32)
+    (local $v1 i32)
+    (local $v2 i32)
+    (local $v3 i32)
+    (local $v4 i32)
+    (local $v5 i32)
+    (local $v6 i32)
+    (local $v7 i32)
+    (local $v8 i32)
+    (block $B3
+      (local.set $v7 (i32.sub (global.get $__stack_pointer) (i32.const 16)))
+      (global.set $__stack_pointer (local.get $v7))
+      (i32.store offset=4 (local.get $v7) (local.get $v0))
+      (local.set $v1 (i32.load offset=4 (local.get $v7)))
+      (local.set $v2 (i32.eq (local.get $v1) (i32.const 0)))
+      (if (local.get $v2)
+        (then
+          (i32.store (local.get $v7) (i32.const 1))
+          (br $B3)
+        )
+        (else
+          (local.set $v4 (i32.load offset=4 (local.get $v7)))
+          (local.set $v5 (i32.sub (local.get $v4) (i32.const 1)))
+          (local.set $v3 (call $is_odd (local.get $v5)))
+          (i32.store (local.get $v7) (local.get $v3))
+          (br $B3)
+        )
+      )
+    )
+    (local.set $v6 (i32.load (local.get $v7)))
+    (local.set $v8 (i32.add (local.get $v7) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v8))
+    (return (local.get $v6))
+  )
+  (func $is_odd (export "is_odd") (param $v0 i32) (result i32)
+    (local $v1 i32)
+    (local $v2 i32)
+    (local $v3 i32)
+    (local $v4 i32)
+    (local $v5 i32)
+    (local $v6 i32)
+    (local $v7 i32)
+    (local $v8 i32)
+    (block $B3
+      (local.set $v7 (i32.sub (global.get $__stack_pointer) (i32.const 16)))
+      (global.set $__stack_pointer (local.get $v7))
+      (i32.store offset=4 (local.get $v7) (local.get $v0))
+      (local.set $v1 (i32.load offset=4 (local.get $v7)))
+      (local.set $v2 (i32.eq (local.get $v1) (i32.const 0)))
+      (if (local.get $v2)
+        (then
+          (i32.store (local.get $v7) (i32.const 0))
+          (br $B3)
+        )
+        (else
+          (local.set $v4 (i32.load offset=4 (local.get $v7)))
+          (local.set $v5 (i32.sub (local.get $v4) (i32.const 1)))
+          (local.set $v3 (call $is_even (local.get $v5)))
+          (i32.store (local.get $v7) (local.get $v3))
+          (br $B3)
+        )
+      )
+    )
+    (local.set $v6 (i32.load (local.get $v7)))
+    (local.set $v8 (i32.add (local.get $v7) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v8))
+    (return (local.get $v6))
+  )
+  (func $main (export "main") (result i32)
+    (local $v0 i32)
+    (local $v1 i32)
+    (local $v2 i32)
+    (local $v3 i32)
+    (local $v4 i32)
+    (local $v5 i32)
+    (local $v6 i32)
+    (local.set $v5 (i32.sub (global.get $__stack_pointer) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v5))
+    (i32.store (local.get $v5) (i32.const 0))
+    (local.set $v0 (call $is_even (i32.const 10)))
+    (local.set $v1 (call $is_odd (i32.const 7)))
+    (local.set $v3 (call $is_odd (i32.const 8)))
+    (local.set $v2 (i32.add (local.get $v0) (local.get $v1)))
+    (local.set $v4 (i32.add (local.get $v2) (local.get $v3)))
+    (local.set $v6 (i32.add (local.get $v5) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v6))
+    (return (local.get $v4))
+  )
+)
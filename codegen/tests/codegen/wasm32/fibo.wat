@@ -0,0 +1,58 @@
+(module
+  (memory (export "memory") 2)
+  (global $__stack_pointer (mut i32) (i32.const 66560))
+  (func $fibo (export "fibo") (param $v0 i32) (result i32)
+    (local $v1 i32)
+    (local $v2 i32)
+    (local $v3 i32)
+    (local $v4 i32)
+    (local $v5 i32)
+    (local $v6 i32)
+    (local $v7 i32)
+    (local $v8 i32)
+    (local $v9 i32)
+    (local $v10 i32)
+    (local $v11 i32)
+    (local $v12 i32)
+    (block $B3
+      (local.set $v11 (i32.sub (global.get $__stack_pointer) (i32.const 16)))
+      (global.set $__stack_pointer (local.get $v11))
+      (i32.store offset=4 (local.get $v11) (local.get $v0))
+      (local.set $v1 (i32.load offset=4 (local.get $v11)))
+      (local.set $v2 (i32.le_s (local.get $v1) (i32.const 2)))
+      (if (local.get $v2)
+        (then
+          (i32.store (local.get $v11) (i32.const 1))
+          (br $B3)
+        )
+        (else
+          (local.set $v8 (i32.load offset=4 (local.get $v11)))
+          (local.set $v9 (i32.sub (local.get $v8) (i32.const 1)))
+          (local.set $v3 (call $fibo (local.get $v9)))
+          (local.set $v6 (i32.load offset=4 (local.get $v11)))
+          (local.set $v7 (i32.sub (local.get $v6) (i32.const 2)))
+          (local.set $v4 (call $fibo (local.get $v7)))
+          (local.set $v5 (i32.add (local.get $v3) (local.get $v4)))
+          (i32.store (local.get $v11) (local.get $v5))
+          (br $B3)
+        )
+      )
+    )
+    (local.set $v10 (i32.load (local.get $v11)))
+    (local.set $v12 (i32.add (local.get $v11) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v12))
+    (return (local.get $v10))
+  )
+  (func $main (export "main") (result i32)
+    (local $v0 i32)
+    (local $v1 i32)
+    (local $v2 i32)
+    (local.set $v1 (i32.sub (global.get $__stack_pointer) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v1))
+    (i32.store (local.get $v1) (i32.const 0))
+    (local.set $v0 (call $fibo (i32.const 10)))
+    (local.set $v2 (i32.add (local.get $v1) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v2))
+    (return (local.get $v0))
+  )
+)
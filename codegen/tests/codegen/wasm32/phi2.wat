@@ -0,0 +1,29 @@
+(module
+  (memory (export "memory") 2)
+  (global $__stack_pointer (mut i32) (i32.const 66560))
+  (func $main (export "main") (result i32)
+    (local $v0 i32)
+    (local $v1 i32)
+    (local $v2 i32)
+    (local $v3 i32)
+    (local $v4 i32)
+    (local.set $v0 (i32.const 0))
+    (local.set $v2 (i32.const 1))
+    (loop $L1
+      (local.set $v4 (i32.le_s (local.get $v2) (i32.const 10)))
+      (if (local.get $v4)
+        (then
+          (local.set $v1 (i32.add (local.get $v0) (local.get $v2)))
+          (local.set $v3 (i32.add (local.get $v2) (i32.const 1)))
+          (local.set $v0 (local.get $v1))
+          (local.set $v2 (local.get $v3))
+          (br $L1)
+        )
+        (else
+          (return (local.get $v0))
+        )
+      )
+    )
+    unreachable
+  )
+)
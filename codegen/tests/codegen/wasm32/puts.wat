@@ -0,0 +1,19 @@
+(module
+  (import "env" "puts" (func $puts (param $v0 i32) (result i32)))
+  (memory (export "memory") 2)
+  (global $__stack_pointer (mut i32) (i32.const 66576))
+  (global $.str i32 (i32.const 1024))
+  (data (i32.const 1024) "hello world\00")
+  (func $main (export "main") (result i32)
+    (local $v0 i32)
+    (local $v1 i32)
+    (local $v2 i32)
+    (local.set $v1 (i32.sub (global.get $__stack_pointer) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v1))
+    (i32.store (local.get $v1) (i32.const 0))
+    (local.set $v0 (call $puts (global.get $.str)))
+    (local.set $v2 (i32.add (local.get $v1) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v2))
+    (return (i32.const 0))
+  )
+)
@@ -0,0 +1,44 @@
+(module
+  (memory (export "memory") 2)
+  (global $__stack_pointer (mut i32) (i32.const 66560))
+  (func $main (export "main") (result i32)
+    (local $v0 i32)
+    (local $v1 i32)
+    (local $v2 i32)
+    (local $v3 i32)
+    (local $v4 i32)
+    (local $v5 i32)
+    (local $v6 i32)
+    (local $v7 i32)
+    (local $v8 i32)
+    (local $v9 i32)
+    (local.set $v8 (i32.sub (global.get $__stack_pointer) (i32.const 16)))
+    (global.set $__stack_pointer (local.get $v8))
+    (i32.store (local.get $v8) (i32.const 0))
+    (i32.store offset=4 (local.get $v8) (i32.const 0))
+    (i32.store offset=8 (local.get $v8) (i32.const 1))
+    (loop $L1
+      (local.set $v0 (i32.load offset=8 (local.get $v8)))
+      (local.set $v1 (i32.le_s (local.get $v0) (i32.const 10)))
+      (if (local.get $v1)
+        (then
+          (local.set $v3 (i32.load offset=8 (local.get $v8)))
+          (local.set $v2 (i32.load offset=4 (local.get $v8)))
+          (local.set $v4 (i32.add (local.get $v2) (local.get $v3)))
+          (i32.store offset=4 (local.get $v8) (local.get $v4))
+          (local.set $v5 (i32.load offset=8 (local.get $v8)))
+          (local.set $v6 (i32.add (local.get $v5) (i32.const 1)))
+          (i32.store offset=8 (local.get $v8) (local.get $v6))
+          (br $L1)
+        )
+        (else
+          (local.set $v7 (i32.load offset=4 (local.get $v8)))
+          (local.set $v9 (i32.add (local.get $v8) (i32.const 16)))
+          (global.set $__stack_pointer (local.get $v9))
+          (return (local.get $v7))
+        )
+      )
+    )
+    unreachable
+  )
+)
@@ -48,6 +48,6 @@ fn main() {
     let obj = product.emit().unwrap();
     std::fs::File::create(opt.output_file.unwrap_or("a.o".to_owned()))
         .unwrap()
-        .write(&obj)
+        .write_all(&obj)
         .unwrap();
 }
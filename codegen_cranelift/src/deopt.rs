@@ -0,0 +1,185 @@
+//! Deoptimization hooks.
+//!
+//! A side exit from compiled code is written in IR as a call to the
+//! `vicis.deopt` intrinsic, whose result is immediately returned:
+//!
+//! ```llvm
+//! %r = call i32 @vicis.deopt(i32 7, i32 %x, i64 %y)
+//! ret i32 %r
+//! ```
+//!
+//! The first argument is a constant identifying the deoptimization point and
+//! the rest are the IR values live there. The call is compiled to a call to
+//! [`DEOPT_HANDLER`], which the embedder must provide (e.g. with
+//! `JITBuilder::symbol`) as a [`DeoptHandler`]. The handler receives the id and
+//! a pointer to the live values, each sign-extended to 64 bits and in the order
+//! they appear in the call. [`deopt_points`] maps these values back to the IR,
+//! so the handler can, for example, continue execution in the interpreter.
+//! Whatever the handler returns is returned from the compiled function.
+
+use crate::LowerCtx;
+use cranelift::prelude::AbiParam;
+use cranelift_codegen::ir::types;
+use cranelift_module::{FuncId, Linkage, Module};
+use vicis_core::ir::{
+    function::{
+        instruction::{Call, InstructionId, Operand},
+        Function,
+    },
+    module::name::Name,
+    value::{ConstantData, Value, ValueId},
+};
+
+/// Name of the intrinsic marking a deoptimization point.
+pub const DEOPT_INTRINSIC: &str = "vicis.deopt";
+
+/// Name of the function called when a deoptimization point is reached.
+pub const DEOPT_HANDLER: &str = "__vicis_deopt";
+
+/// Signature of the function to be registered as [`DEOPT_HANDLER`].
+pub type DeoptHandler = extern "C" fn(id: u32, live: *const i64) -> i64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeoptPoint {
+    pub id: u32,
+    /// The call to `vicis.deopt`.
+    pub call: InstructionId,
+    /// The IR values passed to the handler, in order.
+    pub live: Vec<ValueId>,
+}
+
+/// Returns the deoptimization points in `func` in layout order.
+pub fn deopt_points(func: &Function) -> Vec<DeoptPoint> {
+    let mut points = vec![];
+    for block in func.layout.block_iter() {
+        for inst in func.layout.inst_iter(block) {
+            let args = match &func.data.inst_ref(inst).operand {
+                Operand::Call(Call { args, .. }) if is_deopt_callee(func, args[0]) => args,
+                _ => continue,
+            };
+            let id = match func.data.value_ref(args[1]) {
+                Value::Constant(ConstantData::Int(id)) => id.cast_to_i64() as u32,
+                _ => panic!("the id of a deoptimization point must be a constant"),
+            };
+            points.push(DeoptPoint {
+                id,
+                call: inst,
+                live: args[2..].to_vec(),
+            });
+        }
+    }
+    points
+}
+
+pub(crate) fn is_deopt_callee(func: &Function, callee: ValueId) -> bool {
+    matches!(
+        func.data.value_ref(callee),
+        Value::Constant(ConstantData::GlobalRef(Name::Name(name))) if name == DEOPT_INTRINSIC
+    )
+}
+
+/// Declares [`DEOPT_HANDLER`] in the cranelift module if not declared yet.
+pub(crate) fn declare_handler<M: Module>(lower_ctx: &mut LowerCtx<'_, M>) -> FuncId {
+    if let Some(id) = lower_ctx.deopt_handler {
+        return id;
+    }
+    let ptr_ty = lower_ctx.clif_mod.target_config().pointer_type();
+    let mut sig = lower_ctx.clif_mod.make_signature();
+    sig.params.push(AbiParam::new(types::I32));
+    sig.params.push(AbiParam::new(ptr_ty));
+    sig.returns.push(AbiParam::new(types::I64));
+    let id = lower_ctx
+        .clif_mod
+        .declare_function(DEOPT_HANDLER, Linkage::Import, &sig)
+        .unwrap();
+    lower_ctx.deopt_handler = Some(id);
+    id
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+    use crate::function::{compile_function, declare_and_define_function};
+    use cranelift_jit::{JITBuilder, JITModule};
+    use std::mem::transmute;
+    use vicis_core::ir::module::{self, Module as LlvmModule};
+    use vicis_interpreter::{generic_value::GenericValue, interpreter};
+
+    // `@f` speculates that `%x <= 10` and deoptimizes to `@f.baseline` otherwise.
+    const SOURCE: &str = r#"
+define dso_local i32 @f(i32 %x) {
+  %c = icmp sle i32 %x, 10
+  br i1 %c, label %fast, label %slow
+fast:
+  %y = add nsw i32 %x, 1
+  ret i32 %y
+slow:
+  %r = call i32 @vicis.deopt(i32 7, i32 %x)
+  ret i32 %r
+}
+
+define dso_local i32 @f.baseline(i32 %x) {
+  %c = icmp sle i32 %x, 10
+  br i1 %c, label %small, label %big
+small:
+  %y = add nsw i32 %x, 1
+  ret i32 %y
+big:
+  %z = mul i32 %x, 100
+  ret i32 %z
+}"#;
+
+    thread_local! {
+        static MODULE: LlvmModule = module::parse_assembly(SOURCE).unwrap();
+    }
+
+    extern "C" fn handler(id: u32, live: *const i64) -> i64 {
+        assert_eq!(id, 7);
+        let x = unsafe { *live } as i32;
+        MODULE.with(|module| {
+            // Continue at the block of @f.baseline corresponding to the deoptimization point.
+            let baseline = module.find_function_by_name("f.baseline").unwrap();
+            let func = &module.functions()[baseline];
+            let big = func.layout.block_iter().nth(2).unwrap();
+            let at = func.layout.inst_iter(big).next().unwrap();
+            let ctx = interpreter::Context::new(module);
+            let ret = interpreter::resume_function(
+                &ctx,
+                baseline,
+                vec![GenericValue::Int32(x)],
+                at,
+                vec![],
+            );
+            match ret {
+                Ok(GenericValue::Int32(i)) => i as i64,
+                ret => panic!("unexpected result {:?}", ret),
+            }
+        })
+    }
+
+    #[test]
+    fn deopt_to_interpreter() {
+        let module = module::parse_assembly(SOURCE).unwrap();
+        let f = module.find_function_by_name("f").unwrap();
+        let points = deopt_points(&module.functions()[f]);
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].id, 7);
+        assert!(matches!(
+            module.functions()[f].data.value_ref(points[0].live[0]),
+            Value::Argument(0)
+        ));
+
+        let mut builder = JITBuilder::new(cranelift_module::default_libcall_names());
+        builder.symbol(DEOPT_HANDLER, handler as DeoptHandler as *const u8);
+        let mut clif_mod = JITModule::new(builder);
+        let mut clif_ctx = clif_mod.make_context();
+        compile_function(&mut LowerCtx::new(&module, &mut clif_mod), &mut clif_ctx, f);
+        let id = declare_and_define_function(&mut clif_mod, &mut clif_ctx, "f");
+        clif_mod.finalize_definitions();
+
+        let code = clif_mod.get_finalized_function(id);
+        let code_fn = unsafe { transmute::<*const u8, fn(i32) -> i32>(code) };
+        assert_eq!(code_fn(3), 4);
+        assert_eq!(code_fn(20), 2000);
+    }
+}
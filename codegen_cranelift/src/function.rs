@@ -1,4 +1,4 @@
-use crate::{instruction, LowerCtx};
+use crate::{deopt, instruction, LowerCtx};
 use cranelift::{
     codegen::binemit::{NullStackMapSink, NullTrapSink},
     frontend::{FunctionBuilder, FunctionBuilderContext},
@@ -41,6 +41,10 @@ pub fn compile_function<M: Module>(
         return;
     }
 
+    if !deopt::deopt_points(llvm_func).is_empty() {
+        deopt::declare_handler(lower_ctx);
+    }
+
     compile_body(lower_ctx, &mut builder_ctx, clif_ctx, llvm_func);
 
     #[cfg(debug_assertions)]
@@ -84,9 +88,9 @@ fn compile_body<M: Module>(
         compiler.create_block_for(block_id);
     }
 
-    for (i, block_id) in llvm_func.layout.block_iter().enumerate() {
+    for block_id in llvm_func.layout.block_iter() {
         let block = compiler.blocks[&block_id];
-        if i == 0 {
+        if llvm_func.is_entry_block(block_id) {
             compiler
                 .builder
                 .append_block_params_for_function_params(block);
@@ -294,7 +298,7 @@ define dso_local i32 @main() {
         clif_mod.finalize_definitions();
 
         let code = clif_mod.get_finalized_function(func_id);
-        let code_fn = unsafe { transmute::<_, fn() -> i32>(code) };
+        let code_fn = unsafe { transmute::<*const u8, fn() -> i32>(code) };
         assert_eq!(code_fn(), 42);
     }
 
@@ -326,7 +330,7 @@ define dso_local i32 @main(i32 %arg.0) {
         clif_mod.finalize_definitions();
 
         let code = clif_mod.get_finalized_function(id);
-        let code_fn = unsafe { transmute::<_, fn(i32) -> i32>(code) };
+        let code_fn = unsafe { transmute::<*const u8, fn(i32) -> i32>(code) };
         assert_eq!(code_fn(41), 42);
     }
 }
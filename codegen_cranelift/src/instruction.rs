@@ -1,9 +1,9 @@
-use super::LowerCtx;
+use super::{deopt, LowerCtx};
 use cranelift::{
     frontend::FunctionBuilder,
     prelude::{Block, InstBuilder, IntCC, StackSlotData, StackSlotKind, Value},
 };
-use cranelift_codegen::ir::StackSlot;
+use cranelift_codegen::ir::{types, StackSlot};
 use cranelift_module::{FuncOrDataId, Module};
 use rustc_hash::FxHashMap;
 use vicis_core::ir::{
@@ -111,6 +111,11 @@ impl<'a, M: Module> InstCompiler<'a, M> {
                 self.builder.ins().brnz(arg, self.blocks[&blocks[0]], &[]); // TODO: Set block parameters.
                 self.builder.ins().jump(self.blocks[&blocks[1]], &[]); // TODO: Set block parameters.
             }
+            Operand::Call(Call {
+                ref args, ref tys, ..
+            }) if deopt::is_deopt_callee(self.llvm_func, args[0]) => {
+                self.compile_deopt(inst_id, args, tys)
+            }
             Operand::Call(Call {
                 ref args, ref tys, ..
             }) => {
@@ -130,7 +135,7 @@ impl<'a, M: Module> InstCompiler<'a, M> {
                 let callee = self
                     .lower_ctx
                     .clif_mod
-                    .declare_func_in_func(func_id, &mut self.builder.func);
+                    .declare_func_in_func(func_id, self.builder.func);
                 let call = self.builder.ins().call(callee, &args);
                 let result_ty = tys[0];
                 if !result_ty.is_void() {
@@ -148,6 +153,47 @@ impl<'a, M: Module> InstCompiler<'a, M> {
         };
     }
 
+    /// Stores the live values into a stack slot and passes it to the deoptimization handler.
+    fn compile_deopt(&mut self, inst_id: InstructionId, args: &[ValueId], tys: &[LlvmTy]) {
+        let id = self.value(args[1], tys[1]).as_value().unwrap();
+        let live = &args[2..];
+        let slot = self.builder.create_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            8 * live.len().max(1) as u32,
+        ));
+        for (i, (&arg, &ty)) in live.iter().zip(&tys[2..]).enumerate() {
+            let val = self.value(arg, ty).as_value().expect("must be value");
+            let val = if self.lower_ctx.into_clif_ty(ty) == types::I64 {
+                val
+            } else {
+                self.builder.ins().sextend(types::I64, val)
+            };
+            self.builder.ins().stack_store(val, slot, 8 * i as i32);
+        }
+        let ptr_ty = self.lower_ctx.clif_mod.target_config().pointer_type();
+        let live = self.builder.ins().stack_addr(ptr_ty, slot, 0);
+        let handler = self
+            .lower_ctx
+            .deopt_handler
+            .expect("handler must be declared");
+        let handler = self
+            .lower_ctx
+            .clif_mod
+            .declare_func_in_func(handler, self.builder.func);
+        let call = self.builder.ins().call(handler, &[id, live]);
+        let result_ty = tys[0];
+        if !result_ty.is_void() {
+            let result = self.builder.inst_results(call)[0];
+            let clif_ty = self.lower_ctx.into_clif_ty(result_ty);
+            let result = if clif_ty == types::I64 {
+                result
+            } else {
+                self.builder.ins().ireduce(clif_ty, result)
+            };
+            self.insts.insert(inst_id, result);
+        }
+    }
+
     pub fn create_block_for(&mut self, block_id: BasicBlockId) -> Block {
         if let Some(block) = self.blocks.get(&block_id) {
             return *block;
@@ -165,10 +211,10 @@ impl<'a, M: Module> InstCompiler<'a, M> {
                     .iconst(self.lower_ctx.into_clif_ty(ty), i.cast_to_i64()),
             ),
             LlvmValue::Constant(ConstantData::GlobalRef(Name::Name(name))) => {
-                ValueKind::GlobalName(name.to_owned())
+                ValueKind::GlobalName(name.to_string())
             }
             LlvmValue::Argument(idx) => {
-                let entry = self.llvm_func.layout.get_entry_block().unwrap();
+                let entry = self.llvm_func.entry_block().unwrap();
                 let entry = self.blocks[&entry];
                 ValueKind::Value(self.builder.block_params(entry)[*idx])
             }
@@ -3,22 +3,28 @@ extern crate cranelift_codegen;
 extern crate cranelift_module;
 extern crate vicis_core;
 
+pub mod deopt;
 pub mod function;
 mod instruction;
 pub mod module;
 
 use cranelift::codegen::{ir::types, ir::types::Type};
-use cranelift_module::Module;
+use cranelift_module::{FuncId, Module};
 use vicis_core::ir::{module::Module as LlvmModule, types::Type as LlvmType};
 
 pub struct LowerCtx<'a, M: Module> {
     llvm_mod: &'a LlvmModule,
     clif_mod: &'a mut M,
+    deopt_handler: Option<FuncId>,
 }
 
 impl<'a, M: Module> LowerCtx<'a, M> {
     pub fn new(llvm_mod: &'a LlvmModule, clif_mod: &'a mut M) -> Self {
-        Self { llvm_mod, clif_mod }
+        Self {
+            llvm_mod,
+            clif_mod,
+            deopt_handler: None,
+        }
     }
 
     pub fn into_clif_ty(&self, ty: LlvmType) -> Type {
@@ -0,0 +1,15 @@
+use std::fs::read_to_string;
+
+use vicis_core::ir::{function::builder_code::emit_builder_code, module};
+
+fn main() {
+    let filename = std::env::args().nth(1).expect("expect *.ll file");
+    let source = read_to_string(filename).expect("failed to load file");
+    let m = module::parse_assembly(source.as_str()).expect("failed to parse file");
+    for (_, func) in m.functions() {
+        if func.is_prototype() {
+            continue;
+        }
+        println!("// @{}\n{}", func.name(), emit_builder_code(func));
+    }
+}
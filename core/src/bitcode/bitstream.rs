@@ -0,0 +1,305 @@
+//! The generic bitstream container format that LLVM bitcode is written in.
+//!
+//! A stream is a sequence of nested blocks of records. Records are either
+//! unabbreviated, or laid out by an abbreviation defined in the block, or in
+//! the `BLOCKINFO` block for every block of an ID. [`read`] reads the whole
+//! stream into a tree of [`Block`]s with the abbreviations expanded, so that
+//! the IR reader only deals with records.
+
+use super::BitcodeError;
+use crate::collections::FxHashMap;
+use crate::prelude::*;
+use alloc::rc::Rc;
+
+const END_BLOCK: u64 = 0;
+const ENTER_SUBBLOCK: u64 = 1;
+const DEFINE_ABBREV: u64 = 2;
+const UNABBREV_RECORD: u64 = 3;
+
+const BLOCKINFO_BLOCK_ID: u32 = 0;
+const BLOCKINFO_CODE_SETBID: u32 = 1;
+
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub code: u32,
+    pub ops: Vec<u64>,
+    pub blob: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub enum Item {
+    Record(Record),
+    Block(Block),
+}
+
+#[derive(Debug)]
+pub struct Block {
+    pub id: u32,
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug)]
+enum AbbrevOp {
+    Literal(u64),
+    Fixed(u32),
+    Vbr(u32),
+    Array,
+    Char6,
+    Blob,
+}
+
+type Abbrev = Rc<Vec<AbbrevOp>>;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    /// The position in bits.
+    pos: usize,
+    /// The abbreviations defined in `BLOCKINFO`, by block ID.
+    blockinfo: FxHashMap<u32, Vec<Abbrev>>,
+}
+
+/// Reads the top-level blocks of `bytes`, which start right after the magic
+/// number.
+pub fn read(bytes: &[u8]) -> Result<Vec<Block>, BitcodeError> {
+    let mut reader = Reader {
+        bytes,
+        pos: 0,
+        blockinfo: FxHashMap::default(),
+    };
+    let mut blocks = vec![];
+    while reader.pos < bytes.len() * 8 {
+        if reader.read(2)? != ENTER_SUBBLOCK {
+            return Err(BitcodeError::Malformed(
+                "expected a block at the top level".into(),
+            ));
+        }
+        if let Some(block) = reader.enter_block()? {
+            blocks.push(block);
+        }
+    }
+    Ok(blocks)
+}
+
+impl Reader<'_> {
+    fn read(&mut self, width: u32) -> Result<u64, BitcodeError> {
+        debug_assert!(width <= 64);
+        let mut value = 0;
+        let mut done = 0;
+        while done < width {
+            let byte = *self
+                .bytes
+                .get(self.pos / 8)
+                .ok_or(BitcodeError::UnexpectedEof)?;
+            let offset = (self.pos % 8) as u32;
+            let take = (8 - offset).min(width - done);
+            let bits = (byte as u64 >> offset) & ((1 << take) - 1);
+            value |= bits << done;
+            done += take;
+            self.pos += take as usize;
+        }
+        Ok(value)
+    }
+
+    fn read_vbr(&mut self, width: u32) -> Result<u64, BitcodeError> {
+        let hi = 1 << (width - 1);
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let piece = self.read(width)?;
+            if shift >= 64 {
+                return Err(BitcodeError::Malformed("VBR value too large".into()));
+            }
+            value |= (piece & (hi - 1)) << shift;
+            if piece & hi == 0 {
+                return Ok(value);
+            }
+            shift += width - 1;
+        }
+    }
+
+    fn align32(&mut self) {
+        self.pos = self.pos.next_multiple_of(32);
+    }
+
+    /// Reads the header of a block, after `ENTER_SUBBLOCK`, and the block.
+    /// Returns `None` for `BLOCKINFO`, which only defines abbreviations.
+    fn enter_block(&mut self) -> Result<Option<Block>, BitcodeError> {
+        let id = self.read_vbr(8)? as u32;
+        let width = self.read_vbr(4)? as u32;
+        self.align32();
+        let _num_words = self.read(32)?;
+        if width == 0 || width > 32 {
+            return Err(BitcodeError::Malformed(format!(
+                "invalid abbreviation width {}",
+                width
+            )));
+        }
+        if id == BLOCKINFO_BLOCK_ID {
+            self.read_blockinfo(width)?;
+            return Ok(None);
+        }
+        self.read_block(id, width).map(Some)
+    }
+
+    fn read_block(&mut self, id: u32, width: u32) -> Result<Block, BitcodeError> {
+        let mut abbrevs = self.blockinfo.get(&id).cloned().unwrap_or_default();
+        let mut items = vec![];
+        loop {
+            match self.read(width)? {
+                END_BLOCK => {
+                    self.align32();
+                    return Ok(Block { id, items });
+                }
+                ENTER_SUBBLOCK => {
+                    if let Some(block) = self.enter_block()? {
+                        items.push(Item::Block(block))
+                    }
+                }
+                DEFINE_ABBREV => abbrevs.push(self.read_abbrev()?),
+                UNABBREV_RECORD => items.push(Item::Record(self.read_unabbrev()?)),
+                n => {
+                    let abbrev = abbrevs
+                        .get(n as usize - 4)
+                        .ok_or_else(|| {
+                            BitcodeError::Malformed(format!("undefined abbreviation {}", n))
+                        })?
+                        .clone();
+                    items.push(Item::Record(self.read_abbreviated(&abbrev)?));
+                }
+            }
+        }
+    }
+
+    fn read_blockinfo(&mut self, width: u32) -> Result<(), BitcodeError> {
+        let mut cur = None;
+        loop {
+            match self.read(width)? {
+                END_BLOCK => {
+                    self.align32();
+                    return Ok(());
+                }
+                ENTER_SUBBLOCK => {
+                    self.enter_block()?;
+                }
+                DEFINE_ABBREV => {
+                    let abbrev = self.read_abbrev()?;
+                    let id = cur.ok_or_else(|| {
+                        BitcodeError::Malformed("abbreviation before SETBID".into())
+                    })?;
+                    self.blockinfo.entry(id).or_default().push(abbrev);
+                }
+                UNABBREV_RECORD => {
+                    let record = self.read_unabbrev()?;
+                    if record.code == BLOCKINFO_CODE_SETBID {
+                        cur = record.ops.first().map(|&id| id as u32);
+                    }
+                }
+                _ => {
+                    return Err(BitcodeError::Malformed(
+                        "abbreviated record in BLOCKINFO".into(),
+                    ))
+                }
+            }
+        }
+    }
+
+    fn read_abbrev(&mut self) -> Result<Abbrev, BitcodeError> {
+        let num_ops = self.read_vbr(5)?;
+        let mut ops = vec![];
+        for _ in 0..num_ops {
+            if self.read(1)? == 1 {
+                ops.push(AbbrevOp::Literal(self.read_vbr(8)?));
+                continue;
+            }
+            ops.push(match self.read(3)? {
+                1 => AbbrevOp::Fixed(self.read_vbr(5)? as u32),
+                2 => AbbrevOp::Vbr(self.read_vbr(5)? as u32),
+                3 => AbbrevOp::Array,
+                4 => AbbrevOp::Char6,
+                5 => AbbrevOp::Blob,
+                e => {
+                    return Err(BitcodeError::Malformed(format!(
+                        "invalid abbreviation encoding {}",
+                        e
+                    )))
+                }
+            });
+        }
+        Ok(Rc::new(ops))
+    }
+
+    fn read_unabbrev(&mut self) -> Result<Record, BitcodeError> {
+        let code = self.read_vbr(6)? as u32;
+        let num_ops = self.read_vbr(6)?;
+        let ops = (0..num_ops)
+            .map(|_| self.read_vbr(6))
+            .collect::<Result<_, _>>()?;
+        Ok(Record {
+            code,
+            ops,
+            blob: None,
+        })
+    }
+
+    fn read_scalar(&mut self, op: &AbbrevOp) -> Result<u64, BitcodeError> {
+        match *op {
+            AbbrevOp::Literal(value) => Ok(value),
+            AbbrevOp::Fixed(0) | AbbrevOp::Vbr(0) => Ok(0),
+            AbbrevOp::Fixed(width) => self.read(width),
+            AbbrevOp::Vbr(width) => self.read_vbr(width),
+            AbbrevOp::Char6 => {
+                let c = self.read(6)? as u8;
+                Ok(match c {
+                    0..=25 => b'a' + c,
+                    26..=51 => b'A' + c - 26,
+                    52..=61 => b'0' + c - 52,
+                    62 => b'.',
+                    _ => b'_',
+                } as u64)
+            }
+            AbbrevOp::Array | AbbrevOp::Blob => Err(BitcodeError::Malformed(
+                "array or blob as an element".into(),
+            )),
+        }
+    }
+
+    fn read_abbreviated(&mut self, abbrev: &[AbbrevOp]) -> Result<Record, BitcodeError> {
+        let mut values = vec![];
+        let mut blob = None;
+        let mut ops = abbrev.iter();
+        while let Some(op) = ops.next() {
+            match op {
+                AbbrevOp::Array => {
+                    let elem = ops.next().ok_or_else(|| {
+                        BitcodeError::Malformed("array without an element type".into())
+                    })?;
+                    for _ in 0..self.read_vbr(6)? {
+                        values.push(self.read_scalar(elem)?);
+                    }
+                }
+                AbbrevOp::Blob => {
+                    let len = self.read_vbr(6)? as usize;
+                    self.align32();
+                    let start = self.pos / 8;
+                    let bytes = self
+                        .bytes
+                        .get(start..start + len)
+                        .ok_or(BitcodeError::UnexpectedEof)?;
+                    blob = Some(bytes.to_vec());
+                    self.pos += len * 8;
+                    self.align32();
+                }
+                op => values.push(self.read_scalar(op)?),
+            }
+        }
+        if values.is_empty() {
+            return Err(BitcodeError::Malformed("record without a code".into()));
+        }
+        let code = values.remove(0) as u32;
+        Ok(Record {
+            code,
+            ops: values,
+            blob,
+        })
+    }
+}
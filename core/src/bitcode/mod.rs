@@ -0,0 +1,86 @@
+//! Reading LLVM bitcode, as written by `clang -emit-llvm -c` or `llvm-as`.
+//!
+//! [`parse`] reads a `.bc` file into a [`Module`], as
+//! [`parse_assembly`](crate::ir::module::parse_assembly) reads a `.ll` one.
+//! Only what the IR can represent is supported: typed pointers, as written
+//! up to LLVM 14, integers of the widths `ConstantInt` has, and the
+//! instructions of `Opcode`. Anything else is reported as
+//! [`BitcodeError::Unsupported`] rather than approximated. Metadata, use
+//! lists and debug locations are dropped.
+
+mod bitstream;
+mod reader;
+
+use crate::ir::module::Module;
+use crate::prelude::*;
+use core::{error::Error, fmt};
+
+const WRAPPER_MAGIC: [u8; 4] = [0xDE, 0xC0, 0x17, 0x0B];
+const MAGIC: [u8; 4] = [b'B', b'C', 0xC0, 0xDE];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BitcodeError {
+    /// The input does not start with the bitcode magic number.
+    NotBitcode,
+    UnexpectedEof,
+    Malformed(String),
+    /// The input uses a feature the IR can't represent.
+    Unsupported(String),
+}
+
+/// Parses the bitcode `bytes` into a module.
+pub fn parse(bytes: &[u8]) -> Result<Module, BitcodeError> {
+    let bytes = unwrap(bytes)?;
+    match bytes.strip_prefix(&MAGIC) {
+        Some(stream) => reader::read(&bitstream::read(stream)?),
+        None => Err(BitcodeError::NotBitcode),
+    }
+}
+
+/// Returns the bitcode in `bytes`, stripping the wrapper header that Darwin
+/// toolchains put in front of it.
+fn unwrap(bytes: &[u8]) -> Result<&[u8], BitcodeError> {
+    if !bytes.starts_with(&WRAPPER_MAGIC) {
+        return Ok(bytes);
+    }
+    let field = |i: usize| {
+        bytes
+            .get(i..i + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+            .ok_or(BitcodeError::UnexpectedEof)
+    };
+    let (offset, size) = (field(8)?, field(12)?);
+    bytes
+        .get(offset..offset + size)
+        .ok_or(BitcodeError::UnexpectedEof)
+}
+
+impl fmt::Display for BitcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotBitcode => write!(f, "not an LLVM bitcode file"),
+            Self::UnexpectedEof => write!(f, "unexpected end of bitcode"),
+            Self::Malformed(what) => write!(f, "malformed bitcode: {}", what),
+            Self::Unsupported(what) => write!(f, "unsupported bitcode: {}", what),
+        }
+    }
+}
+
+impl Error for BitcodeError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn not_bitcode() {
+        assert_eq!(
+            parse(b"; ModuleID = 'a.ll'").unwrap_err(),
+            BitcodeError::NotBitcode
+        );
+        assert_eq!(
+            parse(&MAGIC).unwrap_err(),
+            BitcodeError::Malformed("no module block".into())
+        );
+    }
+}
@@ -0,0 +1,1613 @@
+//! Turning the records of a bitcode file into a [`Module`].
+//!
+//! Values are numbered as LLVM numbers them: globals and functions in the
+//! order of their records, then the module-level constants, and in each
+//! function, its arguments, its constants and the instructions that produce
+//! a value. Instructions refer to their operands relatively to their own
+//! number, and to values defined later through placeholders that are
+//! replaced once the value is read, as the text parser does.
+
+use super::{
+    bitstream::{Block, Item, Record},
+    BitcodeError,
+};
+use crate::collections::FxHashMap;
+use crate::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        data::Data,
+        instruction::{
+            Alloca, Br, Call, Cast, CondBr, ExtractValue, FCmp, FCmpCond, FloatBinary, FloatUnary,
+            GetElementPtr, ICmp, ICmpCond, InsertValue, IntBinary, Invoke, LandingPad, Load,
+            Opcode, Operand, Phi, Resume, Ret, Store,
+        },
+        layout::Layout,
+        param_attrs::ParameterAttribute,
+        Function, FunctionId, Parameter,
+    },
+    module::{
+        attributes::Attribute, comdat::SelectionKind, global_variable::GlobalVariable,
+        linkage::Linkage, name::Name, preemption_specifier::PreemptionSpecifier,
+        unnamed_addr::UnnamedAddr, visibility::Visibility, Module,
+    },
+    types::{
+        ArrayType, CompoundType, FunctionType, StructType, Type, Types, DOUBLE, FLOAT, I1, I128,
+        I16, I32, I64, I8, VOID,
+    },
+    value::{
+        ConstantArray, ConstantData, ConstantExpr, ConstantFloat, ConstantInt, ConstantStruct,
+        InlineAsm, Value, ValueId,
+    },
+};
+use crate::prelude::*;
+
+const MODULE_BLOCK: u32 = 8;
+const PARAMATTR_BLOCK: u32 = 9;
+const PARAMATTR_GROUP_BLOCK: u32 = 10;
+const CONSTANTS_BLOCK: u32 = 11;
+const FUNCTION_BLOCK: u32 = 12;
+const VALUE_SYMTAB_BLOCK: u32 = 14;
+const TYPE_BLOCK: u32 = 17;
+const STRTAB_BLOCK: u32 = 23;
+
+/// The index of the function itself in an attribute group.
+const FUNCTION_INDEX: u64 = 0xFFFF_FFFF;
+
+type Result<T> = core::result::Result<T, BitcodeError>;
+
+/// Reads the module in `blocks`, the top-level blocks of a bitcode file.
+pub fn read(blocks: &[Block]) -> Result<Module> {
+    let strtab = blocks
+        .iter()
+        .filter(|block| block.id == STRTAB_BLOCK)
+        .flat_map(records)
+        .find_map(|record| record.blob.as_deref())
+        .unwrap_or_default();
+    let block = blocks
+        .iter()
+        .find(|block| block.id == MODULE_BLOCK)
+        .ok_or_else(|| malformed("no module block"))?;
+    let mut reader = Reader {
+        module: Module::new(),
+        strtab,
+        types: vec![],
+        values: vec![],
+        groups: FxHashMap::default(),
+        lists: vec![],
+        function_groups: FxHashMap::default(),
+        bodies: vec![],
+        inits: vec![],
+        personalities: vec![],
+        sections: vec![],
+        comdats: vec![],
+        num_unnamed: 0,
+    };
+    reader.read_module(block)?;
+    Ok(reader.module)
+}
+
+struct Reader<'a> {
+    module: Module,
+    strtab: &'a [u8],
+    /// The types by ID, or why they are unsupported.
+    types: Vec<core::result::Result<Type, String>>,
+    values: Vec<Entry>,
+    groups: FxHashMap<u64, Group>,
+    /// The attribute lists, i.e. the groups applying to a function or call.
+    lists: Vec<Vec<u64>>,
+    /// The numbers given to the groups of function attributes, as in `#0`.
+    function_groups: FxHashMap<u64, u32>,
+    /// The functions defined in the module, in the order of their bodies.
+    bodies: Vec<FunctionId>,
+    /// The initializers of global variables, by value number.
+    inits: Vec<(Name, usize)>,
+    personalities: Vec<(FunctionId, usize)>,
+    /// The names of sections, referred to by global values.
+    sections: Vec<String>,
+    /// The names of comdats, referred to by global values.
+    comdats: Vec<Name>,
+    num_unnamed: usize,
+}
+
+#[derive(Clone)]
+enum Entry {
+    Global {
+        name: Name,
+        ty: Type,
+    },
+    /// A constant, resolved when used since it may refer to later values.
+    Constant {
+        ty: u64,
+        record: Record,
+    },
+    /// An argument or instruction of the function being read.
+    Local {
+        val: ValueId,
+        ty: Type,
+    },
+}
+
+struct Group {
+    index: u64,
+    attrs: Vec<RawAttr>,
+}
+
+enum RawAttr {
+    Enum(u64),
+    Int(u64, u64),
+    Str(String, String),
+    Type(u64, Option<u64>),
+}
+
+/// The function being read.
+struct Body {
+    data: Data,
+    layout: Layout,
+    blocks: Vec<BasicBlockId>,
+    cur_block: usize,
+    /// Placeholders for values used before being defined, by value number.
+    forward: FxHashMap<usize, ValueId>,
+}
+
+struct Ops<'a> {
+    ops: &'a [u64],
+    pos: usize,
+}
+
+impl<'a> Ops<'a> {
+    fn new(ops: &'a [u64]) -> Self {
+        Self { ops, pos: 0 }
+    }
+
+    fn next(&mut self) -> Result<u64> {
+        let op = self
+            .optional()
+            .ok_or_else(|| malformed("record too short"))?;
+        Ok(op)
+    }
+
+    fn optional(&mut self) -> Option<u64> {
+        let op = self.ops.get(self.pos).copied();
+        self.pos += 1;
+        op
+    }
+
+    fn remaining(&self) -> usize {
+        self.ops.len().saturating_sub(self.pos)
+    }
+
+    fn rest(&self) -> &'a [u64] {
+        self.ops.get(self.pos..).unwrap_or_default()
+    }
+}
+
+impl Reader<'_> {
+    fn read_module(&mut self, block: &Block) -> Result<()> {
+        let mut functions = vec![];
+        for item in &block.items {
+            match item {
+                Item::Block(block) => match block.id {
+                    TYPE_BLOCK => self.read_types(block)?,
+                    PARAMATTR_GROUP_BLOCK => self.read_attribute_groups(block)?,
+                    PARAMATTR_BLOCK => self.lists.extend(
+                        records(block)
+                            .filter(|record| record.code == 2)
+                            .map(|record| record.ops.clone()),
+                    ),
+                    CONSTANTS_BLOCK => self.read_constants(block)?,
+                    FUNCTION_BLOCK => functions.push(block),
+                    // Metadata, value symbol tables superseded by the string
+                    // table, etc.
+                    _ => {}
+                },
+                Item::Record(record) => self.read_module_record(record)?,
+            }
+        }
+
+        for (name, init) in core::mem::take(&mut self.inits) {
+            let init = self.constant(init)?;
+            self.module.global_variables.get_mut(&name).unwrap().init = Some(init);
+        }
+        for (id, personality) in core::mem::take(&mut self.personalities) {
+            let ty = self.value_ty(personality)?;
+            let personality = self.constant(personality)?;
+            self.module.functions[id].personality = Some((ty, personality));
+        }
+
+        if functions.len() != self.bodies.len() {
+            return Err(malformed("function bodies don't match definitions"));
+        }
+        for (id, block) in self.bodies.clone().into_iter().zip(functions) {
+            self.read_function(id, block)?;
+        }
+        Ok(())
+    }
+
+    fn read_module_record(&mut self, record: &Record) -> Result<()> {
+        match record.code {
+            // VERSION
+            1 => match record.ops.first() {
+                Some(2) => Ok(()),
+                version => Err(BitcodeError::Unsupported(format!(
+                    "bitcode version {:?} (only version 2, written since LLVM 5, is supported)",
+                    version
+                ))),
+            },
+            // TRIPLE
+            2 => {
+                self.module.target.triple = string(&record.ops);
+                Ok(())
+            }
+            // DATALAYOUT
+            3 => {
+                self.module.target.datalayout = string(&record.ops);
+                Ok(())
+            }
+            // SOURCE_FILENAME
+            16 => {
+                self.module.source_filename = string(&record.ops);
+                Ok(())
+            }
+            // SECTIONNAME
+            5 => {
+                self.sections.push(string(&record.ops));
+                Ok(())
+            }
+            // COMDAT: [strtab_offset, strtab_size, selection_kind]
+            12 => {
+                let mut ops = Ops::new(&record.ops);
+                let name = self.name(ops.next()?, ops.next()?)?;
+                let kind = match ops.next()? {
+                    1 => SelectionKind::Any,
+                    2 => SelectionKind::ExactMatch,
+                    3 => SelectionKind::Largest,
+                    4 => SelectionKind::NoDeduplicate,
+                    5 => SelectionKind::SameSize,
+                    _ => return Err(malformed("unknown comdat selection kind")),
+                };
+                self.comdats.push(name);
+                self.module.comdats.insert(name, kind);
+                Ok(())
+            }
+            // GLOBALVAR
+            7 => self.read_global_variable(record),
+            // FUNCTION
+            8 => self.read_function_record(record),
+            // ALIAS_OLD, ALIAS, IFUNC
+            9 | 14 | 15 => Err(unsupported("aliases and ifuncs")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads `[strtab_offset, strtab_size, type, isconst|explicittype<<1|addrspace<<2,
+    /// initid, linkage, alignment, section, visibility, threadlocal, unnamed_addr, ...]`.
+    fn read_global_variable(&mut self, record: &Record) -> Result<()> {
+        let mut ops = Ops::new(&record.ops);
+        let name = self.name(ops.next()?, ops.next()?)?;
+        let ty = self.ty(ops.next()?)?;
+        let flags = ops.next()?;
+        let (ty, addr_space) = if flags & 2 != 0 {
+            (ty, (flags >> 2) as u32)
+        } else {
+            match self.module.types.get(ty).as_deref() {
+                Some(CompoundType::Pointer(ptr)) => (ptr.inner, ptr.addr_space),
+                _ => return Err(malformed("global variable of a non-pointer type")),
+            }
+        };
+        let init = ops.next()?;
+        let linkage = linkage(ops.next()?)?;
+        let align = align(ops.optional().unwrap_or(0))?;
+        let section = self.section(ops.optional().unwrap_or(0))?;
+        let gv = GlobalVariable {
+            name,
+            // Definitions are external unless told otherwise.
+            linkage: if matches!(linkage, Linkage::External) && init != 0 {
+                None
+            } else {
+                Some(linkage)
+            },
+            preemption_specifier: match record.ops.get(15) {
+                Some(1) => PreemptionSpecifier::DsoLocal,
+                _ => PreemptionSpecifier::DsoPreemptable,
+            },
+            visibility: visibility(record.ops.get(8)),
+            unnamed_addr: unnamed_addr(record.ops.get(10)),
+            is_constant: flags & 1 != 0,
+            ty,
+            init: None,
+            section,
+            comdat: self.comdat(record.ops.get(13))?,
+            align,
+        };
+        if init != 0 {
+            self.inits.push((name, init as usize - 1));
+        }
+        self.module.global_variables.insert(name, gv);
+        let ty = self.module.types.ptr_to_in(ty, addr_space);
+        self.values.push(Entry::Global { name, ty });
+        Ok(())
+    }
+
+    /// Reads `[strtab_offset, strtab_size, type, callingconv, isproto, linkage,
+    /// paramattrs, alignment, section, visibility, gc, unnamed_addr,
+    /// prologuedata, dllstorageclass, comdat, prefixdata, personalityfn,
+    /// preemptionspecifier, ...]`.
+    fn read_function_record(&mut self, record: &Record) -> Result<()> {
+        let mut ops = Ops::new(&record.ops);
+        let name = match self.name(ops.next()?, ops.next()?)? {
+            Name::Name(name) => name.to_string(),
+            Name::Number(n) => n.to_string(),
+        };
+        let mut ty = self.ty(ops.next()?)?;
+        if let Some(inner) = self.module.types.get_element(ty) {
+            ty = inner;
+        }
+        let FunctionType {
+            ret,
+            params,
+            is_var_arg,
+        } = match self.module.types.get(ty).as_deref() {
+            Some(CompoundType::Function(func)) => func.clone(),
+            _ => return Err(malformed("function of a non-function type")),
+        };
+        let _cc = ops.next()?;
+        let is_prototype = ops.next()? != 0;
+        let linkage = linkage(ops.next()?)?;
+        let list = ops.optional().unwrap_or(0);
+        let func_align = align(ops.optional().unwrap_or(0))?;
+        let section = self.section(ops.optional().unwrap_or(0))?;
+
+        let params = params
+            .iter()
+            .enumerate()
+            .map(|(i, &ty)| {
+                Ok(Parameter {
+                    name: Name::Number(i),
+                    ty,
+                    attrs: self.param_attrs(list, i as u64 + 1)?,
+                })
+            })
+            .collect::<Result<_>>()?;
+        let mut func = Function::new(&name, ret, params, is_var_arg, self.module.types.clone());
+        func.linkage = linkage;
+        func.section = section;
+        func.comdat = self.comdat(record.ops.get(14))?;
+        func.align = func_align;
+        func.visibility = visibility(record.ops.get(9));
+        func.unnamed_addr = unnamed_addr(record.ops.get(11));
+        func.preemption_specifier = match record.ops.get(17) {
+            Some(1) => PreemptionSpecifier::DsoLocal,
+            _ => PreemptionSpecifier::DsoPreemptable,
+        };
+        func.func_attrs = self.func_attrs(list);
+        func.ret_attrs = self.param_attrs(list, 0)?;
+        let id = self.module.functions.alloc(func);
+
+        if !is_prototype {
+            self.bodies.push(id);
+        }
+        if let Some(&personality) = record.ops.get(16).filter(|&&p| p != 0) {
+            self.personalities.push((id, personality as usize - 1));
+        }
+        let ty = self.module.types.ptr_to(ty);
+        self.values.push(Entry::Global {
+            name: Name::from(name),
+            ty,
+        });
+        Ok(())
+    }
+
+    fn name(&mut self, offset: u64, size: u64) -> Result<Name> {
+        if size == 0 {
+            self.num_unnamed += 1;
+            return Ok(Name::Number(self.num_unnamed - 1));
+        }
+        let name = self
+            .strtab
+            .get(offset as usize..(offset + size) as usize)
+            .ok_or_else(|| malformed("name out of the string table"))?;
+        Ok(Name::from(String::from_utf8_lossy(name).as_ref()))
+    }
+
+    fn read_types(&mut self, block: &Block) -> Result<()> {
+        enum Raw {
+            Resolved(Type),
+            Pointer(u64, u32),
+            Array(u64, u64),
+            Function(bool, u64, Vec<u64>),
+            Struct(bool, Vec<u64>),
+            Unsupported(&'static str),
+        }
+
+        let types = self.module.types.clone();
+        let mut raws = vec![];
+        let mut bodies = vec![];
+        let mut name = None;
+        for record in records(block) {
+            let mut ops = Ops::new(&record.ops);
+            let raw = match record.code {
+                // NUMENTRY
+                1 => continue,
+                // STRUCT_NAME
+                19 => {
+                    name = Some(string(&record.ops));
+                    continue;
+                }
+                2 => Raw::Resolved(VOID),
+                7 => match ops.next()? {
+                    1 => Raw::Resolved(I1),
+                    8 => Raw::Resolved(I8),
+                    16 => Raw::Resolved(I16),
+                    32 => Raw::Resolved(I32),
+                    64 => Raw::Resolved(I64),
+                    128 => Raw::Resolved(I128),
+                    _ => Raw::Unsupported("integers of this width"),
+                },
+                8 => Raw::Pointer(ops.next()?, ops.optional().unwrap_or(0) as u32),
+                11 => Raw::Array(ops.next()?, ops.next()?),
+                16 => Raw::Resolved(types.metadata()),
+                18 => Raw::Struct(ops.next()? != 0, ops.rest().to_vec()),
+                // OPAQUE, STRUCT_NAMED
+                6 | 20 => {
+                    let name = name
+                        .take()
+                        .filter(|name| !name.is_empty())
+                        .ok_or_else(|| unsupported("unnamed identified structs"))?;
+                    let ty = types.named_struct(name);
+                    if record.code == 20 {
+                        bodies.push((ty, ops.next()? != 0, ops.rest().to_vec()));
+                    }
+                    Raw::Resolved(ty)
+                }
+                21 => Raw::Function(ops.next()? != 0, ops.next()?, ops.rest().to_vec()),
+                3 => Raw::Resolved(FLOAT),
+                4 => Raw::Resolved(DOUBLE),
+                10 | 13 | 14 | 15 | 23 => {
+                    Raw::Unsupported("floating-point types but float and double")
+                }
+                12 => Raw::Unsupported("vectors"),
+                25 => Raw::Unsupported("opaque pointers"),
+                5 => Raw::Unsupported("labels"),
+                _ => Raw::Unsupported("this type"),
+            };
+            raws.push(raw);
+        }
+
+        fn resolve(
+            raws: &[Raw],
+            resolved: &mut [Option<core::result::Result<Type, String>>],
+            types: &Types,
+            id: u64,
+        ) -> core::result::Result<Type, String> {
+            let i = id as usize;
+            if let Some(ty) = resolved.get(i).cloned().flatten() {
+                return ty;
+            }
+            let mut elem = |id| resolve(raws, resolved, types, id);
+            let ty = match raws.get(i).ok_or("an undefined type")? {
+                Raw::Resolved(ty) => Ok(*ty),
+                Raw::Pointer(inner, addr_space) => {
+                    elem(*inner).map(|inner| types.ptr_to_in(inner, *addr_space))
+                }
+                Raw::Array(n, inner) => elem(*inner).map(|inner| types.array_of(inner, *n as u32)),
+                Raw::Function(is_var_arg, ret, params) => {
+                    let ret = elem(*ret);
+                    let params: core::result::Result<Vec<_>, _> =
+                        params.iter().map(|&p| elem(p)).collect();
+                    ret.and_then(|ret| Ok(types.func(ret, &params?, *is_var_arg)))
+                }
+                Raw::Struct(is_packed, elems) => elems
+                    .iter()
+                    .map(|&e| elem(e))
+                    .collect::<core::result::Result<Vec<_>, _>>()
+                    .map(|elems| {
+                        if *is_packed {
+                            types.packed_struct_of(&elems)
+                        } else {
+                            types.struct_of(&elems)
+                        }
+                    }),
+                Raw::Unsupported(what) => Err(what.to_string()),
+            };
+            resolved[i] = Some(ty.clone());
+            ty
+        }
+
+        let mut resolved = vec![None; raws.len()];
+        for i in 0..raws.len() {
+            resolve(&raws, &mut resolved, &types, i as u64).ok();
+        }
+        for (ty, is_packed, elems) in bodies {
+            let elems = elems
+                .iter()
+                .map(|&e| resolve(&raws, &mut resolved, &types, e))
+                .collect::<core::result::Result<Vec<_>, _>>()
+                .map_err(BitcodeError::Unsupported)?;
+            types.set_struct_body(ty, &elems, is_packed);
+        }
+        self.types = resolved.into_iter().map(Option::unwrap).collect();
+        Ok(())
+    }
+
+    fn ty(&self, id: u64) -> Result<Type> {
+        self.types
+            .get(id as usize)
+            .ok_or_else(|| malformed("undefined type"))?
+            .clone()
+            .map_err(BitcodeError::Unsupported)
+    }
+
+    /// Reads `[grpid, paramidx, (kind, key[, value])...]` records.
+    fn read_attribute_groups(&mut self, block: &Block) -> Result<()> {
+        for record in records(block).filter(|record| record.code == 3) {
+            let mut ops = Ops::new(&record.ops);
+            let id = ops.next()?;
+            let index = ops.next()?;
+            let mut attrs = vec![];
+            while ops.remaining() > 0 {
+                attrs.push(match ops.next()? {
+                    0 => RawAttr::Enum(ops.next()?),
+                    1 => RawAttr::Int(ops.next()?, ops.next()?),
+                    kind @ (3 | 4) => {
+                        let key = c_string(&mut ops)?;
+                        let value = if kind == 4 {
+                            c_string(&mut ops)?
+                        } else {
+                            String::new()
+                        };
+                        RawAttr::Str(key, value)
+                    }
+                    5 => RawAttr::Type(ops.next()?, None),
+                    6 => RawAttr::Type(ops.next()?, Some(ops.next()?)),
+                    _ => return Err(malformed("invalid attribute")),
+                });
+            }
+            if index == FUNCTION_INDEX {
+                let n = self.function_groups.len() as u32;
+                self.function_groups.insert(id, n);
+                self.module
+                    .attributes
+                    .insert(n, attrs.iter().filter_map(attribute).collect());
+            }
+            self.groups.insert(id, Group { index, attrs });
+        }
+        Ok(())
+    }
+
+    /// Returns the groups in the attribute list `list`, numbered from 1.
+    fn groups_of(&self, list: u64) -> impl Iterator<Item = (u64, &Group)> {
+        let list = match list {
+            0 => None,
+            _ => self.lists.get(list as usize - 1),
+        };
+        list.into_iter()
+            .flatten()
+            .filter_map(|id| Some((*id, self.groups.get(id)?)))
+    }
+
+    fn func_attrs(&self, list: u64) -> Vec<Attribute> {
+        self.groups_of(list)
+            .filter(|(_, group)| group.index == FUNCTION_INDEX)
+            .map(|(id, _)| Attribute::Ref(self.function_groups[&id]))
+            .collect()
+    }
+
+    /// Returns the attributes of the return value if `index` is 0, or of the
+    /// `index`-th parameter.
+    /// Returns the comdat numbered `id`, counted from 1, or `None` for 0.
+    fn comdat(&self, id: Option<&u64>) -> Result<Option<Name>> {
+        match id {
+            None | Some(0) => Ok(None),
+            Some(&id) => self
+                .comdats
+                .get(id as usize - 1)
+                .copied()
+                .map(Some)
+                .ok_or_else(|| malformed("comdat number out of range")),
+        }
+    }
+
+    /// Returns the section numbered `id`, counted from 1, or `None` for 0.
+    fn section(&self, id: u64) -> Result<Option<String>> {
+        match id {
+            0 => Ok(None),
+            _ => self
+                .sections
+                .get(id as usize - 1)
+                .cloned()
+                .map(Some)
+                .ok_or_else(|| malformed("section number out of range")),
+        }
+    }
+
+    fn param_attrs(&self, list: u64, index: u64) -> Result<Vec<ParameterAttribute>> {
+        let mut attrs = vec![];
+        for (_, group) in self.groups_of(list).filter(|(_, g)| g.index == index) {
+            for attr in &group.attrs {
+                attrs.extend(self.param_attribute(attr)?);
+            }
+        }
+        Ok(attrs)
+    }
+
+    fn param_attribute(&self, attr: &RawAttr) -> Result<Option<ParameterAttribute>> {
+        use ParameterAttribute::*;
+        Ok(Some(match *attr {
+            RawAttr::Enum(3) | RawAttr::Type(3, _) => ByVal,
+            RawAttr::Enum(5) => InReg,
+            RawAttr::Enum(8) => Nest,
+            RawAttr::Enum(9) => NoAlias,
+            RawAttr::Enum(11) => NoCapture,
+            RawAttr::Enum(21) => ReadOnly,
+            RawAttr::Enum(22) => Returned,
+            RawAttr::Enum(24) => SignExt,
+            RawAttr::Enum(29) | RawAttr::Type(29, None) => SRet(None),
+            RawAttr::Type(29, Some(ty)) => SRet(Some(self.ty(ty)?)),
+            RawAttr::Enum(34) => ZeroExt,
+            RawAttr::Enum(38) | RawAttr::Type(38, _) => InAlloca,
+            RawAttr::Enum(39) => NonNull,
+            RawAttr::Enum(46) => SwiftSelf,
+            RawAttr::Enum(47) => SwiftError,
+            RawAttr::Enum(52) => WriteOnly,
+            RawAttr::Enum(60) => ImmArg,
+            RawAttr::Enum(62) => NoFree,
+            RawAttr::Int(1, align) => Alignment(align),
+            RawAttr::Int(41, bytes) => Dereferenceable(bytes),
+            RawAttr::Int(42, bytes) => DereferenceableOrNull(bytes),
+            RawAttr::Str(ref kind, ref value) => StringAttribute {
+                kind: kind.as_str().into(),
+                value: value.as_str().into(),
+            },
+            _ => return Ok(None),
+        }))
+    }
+
+    fn read_constants(&mut self, block: &Block) -> Result<()> {
+        let mut ty = None;
+        for record in records(block) {
+            if record.code == 1 {
+                ty = record.ops.first().copied();
+                continue;
+            }
+            self.values.push(Entry::Constant {
+                ty: ty.ok_or_else(|| malformed("constant without a type"))?,
+                record: record.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn value_ty(&self, id: usize) -> Result<Type> {
+        match self.values.get(id) {
+            Some(Entry::Global { ty, .. } | Entry::Local { ty, .. }) => Ok(*ty),
+            Some(Entry::Constant { ty, .. }) => self.ty(*ty),
+            None => Err(malformed("undefined value")),
+        }
+    }
+
+    fn constant(&self, id: usize) -> Result<ConstantData> {
+        let (ty, record) = match self.values.get(id) {
+            Some(Entry::Global { name, .. }) => return Ok(ConstantData::GlobalRef(*name)),
+            Some(Entry::Constant { ty, record }) => (self.ty(*ty)?, record),
+            Some(Entry::Local { .. }) | None => return Err(malformed("invalid constant")),
+        };
+        let ops = &record.ops;
+        let types = &self.module.types;
+        let constants = |ids: &[u64]| {
+            ids.iter()
+                .map(|&id| self.constant(id as usize))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(match record.code {
+            // NULL
+            2 if ty == FLOAT => ConstantFloat::from_f32(0.0).into(),
+            2 if ty == DOUBLE => ConstantFloat::from_f64(0.0).into(),
+            2 if ty.is_primitive() => int(ty, 0)?,
+            2 if types.is_pointer(ty) => ConstantData::Null,
+            2 => ConstantData::AggregateZero,
+            // UNDEF, POISON
+            3 | 26 => ConstantData::Undef,
+            // INTEGER
+            4 => int(ty, signed(*ops.first().unwrap_or(&0)))?,
+            // WIDE_INTEGER, of words from the least significant
+            5 if ty == I128 && ops.len() == 2 => ConstantData::Int(ConstantInt::Int128(
+                (signed(ops[1]) as i128) << 64 | signed(ops[0]) as u64 as i128,
+            )),
+            // FLOAT
+            6 => match ty {
+                FLOAT => ConstantFloat::Float(ops[0] as u32).into(),
+                DOUBLE => ConstantFloat::Double(ops[0]).into(),
+                _ => return Err(malformed("invalid floating-point constant")),
+            },
+            // AGGREGATE
+            7 => match types.get(ty).as_deref().cloned() {
+                Some(CompoundType::Array(ArrayType { inner, .. })) => {
+                    ConstantData::Array(ConstantArray {
+                        elem_ty: inner,
+                        elems: constants(ops)?,
+                        is_string: false,
+                    })
+                }
+                Some(CompoundType::Struct(StructType {
+                    elems, is_packed, ..
+                })) => ConstantData::Struct(ConstantStruct {
+                    elems_ty: elems,
+                    elems: constants(ops)?,
+                    is_packed,
+                }),
+                _ => return Err(unsupported("vector constants")),
+            },
+            // STRING, CSTRING
+            8 | 9 => {
+                let mut elems: Vec<_> = ops
+                    .iter()
+                    .map(|&c| ConstantData::Int(ConstantInt::Int8(c as i8)))
+                    .collect();
+                if record.code == 9 {
+                    elems.push(ConstantData::Int(ConstantInt::Int8(0)));
+                }
+                ConstantData::Array(ConstantArray {
+                    elem_ty: I8,
+                    elems,
+                    is_string: true,
+                })
+            }
+            // DATA
+            22 => {
+                let elem_ty = types
+                    .get_element(ty)
+                    .ok_or_else(|| unsupported("vector constants"))?;
+                ConstantData::Array(ConstantArray {
+                    elem_ty,
+                    elems: ops
+                        .iter()
+                        .map(|&e| int(elem_ty, e as i64))
+                        .collect::<Result<_>>()?,
+                    is_string: false,
+                })
+            }
+            // CE_CAST
+            11 => match ops[..] {
+                [11, from, arg] => ConstantData::Expr(ConstantExpr::Bitcast {
+                    tys: [self.ty(from)?, ty],
+                    arg: Box::new(self.constant(arg as usize)?),
+                }),
+                _ => return Err(unsupported("constant casts other than bitcast")),
+            },
+            // CE_GEP, CE_INBOUNDS_GEP, CE_GEP_WITH_INRANGE_INDEX
+            12 | 20 | 24 => {
+                let mut ops = Ops::new(ops);
+                let elem_ty = if record.code == 24 || ops.remaining() % 2 == 1 {
+                    Some(self.ty(ops.next()?)?)
+                } else {
+                    None
+                };
+                let inbounds = match record.code {
+                    20 => true,
+                    24 => ops.next()? & 1 != 0,
+                    _ => false,
+                };
+                let mut tys = vec![];
+                let mut args = vec![];
+                while ops.remaining() > 0 {
+                    tys.push(self.ty(ops.next()?)?);
+                    args.push(self.constant(ops.next()? as usize)?);
+                }
+                let elem_ty = match elem_ty {
+                    Some(ty) => ty,
+                    None => tys
+                        .first()
+                        .and_then(|&ty| types.get_element(ty))
+                        .ok_or_else(|| malformed("invalid getelementptr"))?,
+                };
+                tys.insert(0, elem_ty);
+                ConstantData::Expr(ConstantExpr::GetElementPtr {
+                    inbounds,
+                    tys,
+                    args,
+                })
+            }
+            code => {
+                return Err(BitcodeError::Unsupported(format!(
+                    "constants of code {}",
+                    code
+                )))
+            }
+        })
+    }
+
+    fn read_function(&mut self, id: FunctionId, block: &Block) -> Result<()> {
+        let num_module_values = self.values.len();
+        let mut body = Body {
+            data: Data::new(),
+            layout: Layout::new(),
+            blocks: vec![],
+            cur_block: 0,
+            forward: FxHashMap::default(),
+        };
+        let param_tys: Vec<Type> = self.module.functions[id]
+            .params
+            .iter()
+            .map(|param| param.ty)
+            .collect();
+        for (i, ty) in param_tys.into_iter().enumerate() {
+            let val = body.data.create_value(Value::Argument(i));
+            self.values.push(Entry::Local { val, ty });
+        }
+
+        let mut symtabs = vec![];
+        for item in &block.items {
+            match item {
+                Item::Block(block) if block.id == CONSTANTS_BLOCK => self.read_constants(block)?,
+                Item::Block(block) if block.id == VALUE_SYMTAB_BLOCK => symtabs.push(block),
+                // Metadata and use lists.
+                Item::Block(_) => {}
+                Item::Record(record) => self.read_inst(&mut body, record)?,
+            }
+        }
+        if !body.forward.is_empty() {
+            return Err(malformed("use of an undefined value"));
+        }
+
+        let func = &mut self.module.functions[id];
+        for record in symtabs.into_iter().flat_map(records) {
+            let name = Name::from(string(record.ops.get(1..).unwrap_or_default()));
+            match (record.code, record.ops.first()) {
+                // ENTRY
+                (1, Some(&id)) => {
+                    if let Some(Entry::Local { val, .. }) = self.values.get(id as usize) {
+                        match *body.data.value_ref(*val) {
+                            Value::Argument(i) => func.params[i].name = name,
+                            Value::Instruction(inst) => {
+                                body.data.inst_ref_mut(inst).dest = Some(name)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                // BBENTRY
+                (2, Some(&id)) => {
+                    let block = *body
+                        .blocks
+                        .get(id as usize)
+                        .ok_or_else(|| malformed("undefined block"))?;
+                    body.data.block_ref_mut(block).name = Some(name);
+                }
+                _ => {}
+            }
+        }
+
+        for &block in &body.blocks {
+            let last = match body.layout.block_node(block).last_inst() {
+                Some(last) => *last,
+                None => return Err(malformed("block without a terminator")),
+            };
+            let succs = body.data.inst_ref(last).operand.successors().to_vec();
+            for succ in succs {
+                body.data.basic_blocks[block].succs.insert(succ);
+                body.data.basic_blocks[succ].preds.insert(block);
+            }
+        }
+
+        func.data = body.data;
+        func.layout = body.layout;
+        self.values.truncate(num_module_values);
+        Ok(())
+    }
+
+    fn read_inst(&mut self, body: &mut Body, record: &Record) -> Result<()> {
+        let mut ops = Ops::new(&record.ops);
+        let (opcode, operand, ty) = match record.code {
+            // DECLAREBLOCKS
+            1 => {
+                for _ in 0..ops.next()? {
+                    let block = body.data.create_block();
+                    body.layout.append_block(block);
+                    body.blocks.push(block);
+                }
+                return Ok(());
+            }
+            // BINOP: [opval, ty, opval, opcode, flags]
+            2 => {
+                let (lhs, ty) = self.value_and_type(body, &mut ops)?;
+                let rhs = self.value(body, &mut ops, ty)?;
+                // The same codes are used for the floating-point operations,
+                // whose flags are fast-math ones.
+                if ty.is_floating_point() {
+                    let opcode = match ops.next()? {
+                        0 => Opcode::FAdd,
+                        1 => Opcode::FSub,
+                        2 => Opcode::FMul,
+                        4 => Opcode::FDiv,
+                        6 => Opcode::FRem,
+                        _ => return Err(malformed("invalid floating-point binary operator")),
+                    };
+                    let operand = Operand::FloatBinary(FloatBinary {
+                        ty,
+                        args: [lhs, rhs],
+                    });
+                    (opcode, operand, Some(ty))
+                } else {
+                    let opcode = match ops.next()? {
+                        0 => Opcode::Add,
+                        1 => Opcode::Sub,
+                        2 => Opcode::Mul,
+                        4 => Opcode::SDiv,
+                        6 => Opcode::SRem,
+                        7 => Opcode::Shl,
+                        8 => Opcode::LShr,
+                        10 => Opcode::And,
+                        11 => Opcode::Or,
+                        _ => return Err(unsupported("this binary operator")),
+                    };
+                    let flags = ops.optional().unwrap_or(0);
+                    let wraps = matches!(
+                        opcode,
+                        Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::Shl
+                    );
+                    let operand = Operand::IntBinary(IntBinary {
+                        ty,
+                        nuw: wraps && flags & 1 != 0,
+                        nsw: wraps && flags & 2 != 0,
+                        exact: !wraps && flags & 1 != 0,
+                        args: [lhs, rhs],
+                    });
+                    (opcode, operand, Some(ty))
+                }
+            }
+            // UNOP: [opval, opcode]
+            56 => {
+                let (arg, ty) = self.value_and_type(body, &mut ops)?;
+                if ops.next()? != 0 {
+                    return Err(unsupported("this unary operator"));
+                }
+                let operand = Operand::FloatUnary(FloatUnary { ty, arg });
+                (Opcode::FNeg, operand, Some(ty))
+            }
+            // CAST: [opval, opty, destty, castopc]
+            3 => {
+                let (arg, from) = self.value_and_type(body, &mut ops)?;
+                let to = self.ty(ops.next()?)?;
+                let opcode = match ops.next()? {
+                    0 => Opcode::Trunc,
+                    1 => Opcode::Zext,
+                    2 => Opcode::Sext,
+                    3 => Opcode::FPToUI,
+                    4 => Opcode::FPToSI,
+                    5 => Opcode::UIToFP,
+                    6 => Opcode::SIToFP,
+                    7 => Opcode::FPTrunc,
+                    8 => Opcode::FPExt,
+                    10 => Opcode::IntToPtr,
+                    11 => Opcode::Bitcast,
+                    _ => return Err(unsupported("this cast")),
+                };
+                let operand = Operand::Cast(Cast {
+                    tys: [from, to],
+                    arg,
+                });
+                (opcode, operand, Some(to))
+            }
+            // RET: [opty, opval]
+            10 if ops.remaining() == 0 => (
+                Opcode::Ret,
+                Operand::Ret(Ret {
+                    ty: VOID,
+                    val: None,
+                }),
+                None,
+            ),
+            10 => {
+                let (val, ty) = self.value_and_type(body, &mut ops)?;
+                let operand = Operand::Ret(Ret { ty, val: Some(val) });
+                (Opcode::Ret, operand, None)
+            }
+            // BR: [bb#, bb#, cond] or [bb#]
+            11 if ops.remaining() == 1 => {
+                let block = body.block(ops.next()?)?;
+                (Opcode::Br, Operand::Br(Br { block }), None)
+            }
+            11 => {
+                let blocks = [body.block(ops.next()?)?, body.block(ops.next()?)?];
+                let arg = self.value(body, &mut ops, I1)?;
+                (
+                    Opcode::CondBr,
+                    Operand::CondBr(CondBr { arg, blocks }),
+                    None,
+                )
+            }
+            // INVOKE: [attrs, cc, normbb, unwindbb, fnty, fnid, args...]
+            13 => {
+                let list = ops.next()?;
+                let cc = ops.next()?;
+                let blocks = vec![body.block(ops.next()?)?, body.block(ops.next()?)?];
+                let explicit_ty = cc & (1 << 13) != 0;
+                let (call, ret) = self.call(body, &mut ops, list, explicit_ty)?;
+                let operand = Operand::Invoke(Invoke {
+                    args: call.args,
+                    tys: call.tys,
+                    param_attrs: call.param_attrs,
+                    ret_attrs: call.ret_attrs,
+                    func_attrs: call.func_attrs,
+                    blocks,
+                });
+                (Opcode::Invoke, operand, Some(ret))
+            }
+            // UNREACHABLE
+            15 => (Opcode::Unreachable, Operand::Unreachable, None),
+            // PHI: [ty, val0, bb0, ...]
+            16 => {
+                let ty = self.ty(ops.next()?)?;
+                let mut args = vec![];
+                let mut blocks = vec![];
+                while ops.remaining() >= 2 {
+                    let id = (self.values.len() as i64 - signed(ops.next()?)) as usize;
+                    args.push(self.value_by_id(body, id, ty)?);
+                    blocks.push(body.block(ops.next()?)?);
+                }
+                (
+                    Opcode::Phi,
+                    Operand::Phi(Phi { ty, args, blocks }),
+                    Some(ty),
+                )
+            }
+            // ALLOCA: [instty, opty, op, align]
+            19 => {
+                let ty = self.ty(ops.next()?)?;
+                let num_ty = self.ty(ops.next()?)?;
+                let num_elements = self.constant(ops.next()? as usize)?;
+                let align_record = ops.next()?;
+                let ty = if align_record & (1 << 6) != 0 {
+                    ty
+                } else {
+                    self.module
+                        .types
+                        .get_element(ty)
+                        .ok_or_else(|| malformed("alloca of a non-pointer type"))?
+                };
+                let operand = Operand::Alloca(Alloca {
+                    tys: [ty, num_ty],
+                    num_elements,
+                    align: align((align_record & 31) | ((align_record >> 8) & 7) << 5)?,
+                });
+                (Opcode::Alloca, operand, Some(self.module.types.ptr_to(ty)))
+            }
+            // LOAD: [opty, op, ty, align, vol]
+            20 => {
+                let (addr, addr_ty) = self.value_and_type(body, &mut ops)?;
+                let ty = if ops.remaining() == 3 {
+                    self.ty(ops.next()?)?
+                } else {
+                    self.module
+                        .types
+                        .get_element(addr_ty)
+                        .ok_or_else(|| malformed("load from a non-pointer"))?
+                };
+                let operand = Operand::Load(Load {
+                    tys: [ty, addr_ty],
+                    addr,
+                    align: align(ops.next()?)?,
+                });
+                (Opcode::Load, operand, Some(ty))
+            }
+            // EXTRACTVAL: [opty, opval, idx...]
+            26 => {
+                let (agg, ty) = self.value_and_type(body, &mut ops)?;
+                let mut elem_ty = ty;
+                let mut args = vec![agg];
+                for &idx in ops.rest() {
+                    elem_ty = self
+                        .module
+                        .types
+                        .base()
+                        .element_at(elem_ty, idx as usize)
+                        .ok_or_else(|| malformed("invalid extractvalue index"))?;
+                    args.push(body.index(idx));
+                }
+                let operand = Operand::ExtractValue(ExtractValue { ty, args });
+                (Opcode::ExtractValue, operand, Some(elem_ty))
+            }
+            // INSERTVAL: [opty, opval, opty, opval, idx...]
+            27 => {
+                let (agg, agg_ty) = self.value_and_type(body, &mut ops)?;
+                let (elem, elem_ty) = self.value_and_type(body, &mut ops)?;
+                let mut args = vec![agg, elem];
+                args.extend(ops.rest().iter().map(|&idx| body.index(idx)));
+                let operand = Operand::InsertValue(InsertValue {
+                    tys: [agg_ty, elem_ty],
+                    args,
+                });
+                (Opcode::InsertValue, operand, Some(agg_ty))
+            }
+            // CMP, CMP2: [opty, opval, opval, pred]
+            9 | 28 => {
+                let (lhs, ty) = self.value_and_type(body, &mut ops)?;
+                let rhs = self.value(body, &mut ops, ty)?;
+                let pred = ops.next()?;
+                if ty.is_floating_point() {
+                    const CONDS: [FCmpCond; 16] = [
+                        FCmpCond::False,
+                        FCmpCond::Oeq,
+                        FCmpCond::Ogt,
+                        FCmpCond::Oge,
+                        FCmpCond::Olt,
+                        FCmpCond::Ole,
+                        FCmpCond::One,
+                        FCmpCond::Ord,
+                        FCmpCond::Uno,
+                        FCmpCond::Ueq,
+                        FCmpCond::Ugt,
+                        FCmpCond::Uge,
+                        FCmpCond::Ult,
+                        FCmpCond::Ule,
+                        FCmpCond::Une,
+                        FCmpCond::True,
+                    ];
+                    let cond = *CONDS
+                        .get(pred as usize)
+                        .ok_or_else(|| malformed("invalid floating-point predicate"))?;
+                    let operand = Operand::FCmp(FCmp {
+                        ty,
+                        args: [lhs, rhs],
+                        cond,
+                    });
+                    (Opcode::FCmp, operand, Some(I1))
+                } else {
+                    let cond = match pred {
+                        32 => ICmpCond::Eq,
+                        33 => ICmpCond::Ne,
+                        34 => ICmpCond::Ugt,
+                        35 => ICmpCond::Uge,
+                        36 => ICmpCond::Ult,
+                        37 => ICmpCond::Ule,
+                        38 => ICmpCond::Sgt,
+                        39 => ICmpCond::Sge,
+                        40 => ICmpCond::Slt,
+                        41 => ICmpCond::Sle,
+                        _ => return Err(malformed("invalid integer predicate")),
+                    };
+                    let operand = Operand::ICmp(ICmp {
+                        ty,
+                        args: [lhs, rhs],
+                        cond,
+                    });
+                    (Opcode::ICmp, operand, Some(I1))
+                }
+            }
+            // CALL: [paramattrs, cc, fmf, fnty, fnid, args...]
+            34 => {
+                let list = ops.next()?;
+                let cc = ops.next()?;
+                if cc & (1 << 17) != 0 {
+                    ops.next()?;
+                }
+                let explicit_ty = cc & (1 << 15) != 0;
+                let (call, ret) = self.call(body, &mut ops, list, explicit_ty)?;
+                (Opcode::Call, Operand::Call(call), Some(ret))
+            }
+            // DEBUG_LOC_AGAIN, DEBUG_LOC, BLOCKADDR_USERS
+            33 | 35 | 60 => return Ok(()),
+            // RESUME: [opval]
+            39 => {
+                let (arg, ty) = self.value_and_type(body, &mut ops)?;
+                (Opcode::Resume, Operand::Resume(Resume { ty, arg }), None)
+            }
+            // GEP: [inbounds, ty, opty, opval, ...]
+            43 => {
+                let inbounds = ops.next()? != 0;
+                let mut tys = vec![self.ty(ops.next()?)?];
+                let mut args = vec![];
+                while ops.remaining() > 0 {
+                    let (arg, ty) = self.value_and_type(body, &mut ops)?;
+                    tys.push(ty);
+                    args.push(arg);
+                }
+                let ty = self.gep_ty(body, &tys, &args)?;
+                let operand = Operand::GetElementPtr(GetElementPtr {
+                    inbounds,
+                    tys,
+                    args,
+                });
+                (Opcode::GetElementPtr, operand, Some(ty))
+            }
+            // STORE: [ptrty, ptr, valty, val, align, vol]
+            44 => {
+                let (dst, dst_ty) = self.value_and_type(body, &mut ops)?;
+                let (src, src_ty) = self.value_and_type(body, &mut ops)?;
+                let operand = Operand::Store(Store {
+                    tys: [src_ty, dst_ty],
+                    args: [src, dst],
+                    align: align(ops.next()?)?,
+                });
+                (Opcode::Store, operand, None)
+            }
+            // LANDINGPAD: [ty, iscleanup, nclauses, ...]. Clauses are dropped
+            // as they are by the text parser.
+            47 => {
+                let ty = self.ty(ops.next()?)?;
+                let operand = Operand::LandingPad(LandingPad { ty });
+                (Opcode::LandingPad, operand, Some(ty))
+            }
+            code => {
+                return Err(BitcodeError::Unsupported(format!(
+                    "instructions of code {}",
+                    code
+                )))
+            }
+        };
+        self.define(body, opcode, operand, ty.filter(|ty| !ty.is_void()))
+    }
+
+    /// Appends an instruction to the current block, numbering its result if
+    /// `ty` is given.
+    fn define(
+        &mut self,
+        body: &mut Body,
+        opcode: Opcode,
+        operand: Operand,
+        ty: Option<Type>,
+    ) -> Result<()> {
+        let block = *body
+            .blocks
+            .get(body.cur_block)
+            .ok_or_else(|| malformed("instruction out of blocks"))?;
+        let inst = opcode.with_block(block).with_operand(operand);
+        let id = match ty {
+            Some(ty) => {
+                let val = match body.forward.remove(&self.values.len()) {
+                    Some(val) => {
+                        let dummy = *body.data.value_ref(val).as_inst();
+                        body.data.replace_inst(dummy, inst);
+                        val
+                    }
+                    None => {
+                        let id = body.data.create_inst(inst);
+                        body.data.create_value(Value::Instruction(id))
+                    }
+                };
+                self.values.push(Entry::Local { val, ty });
+                *body.data.value_ref(val).as_inst()
+            }
+            None => body.data.create_inst(inst),
+        };
+        body.layout.append_inst(id, block);
+        if opcode.is_terminator() {
+            body.cur_block += 1;
+        }
+        Ok(())
+    }
+
+    /// Reads the callee and arguments of a call or invoke, and returns them
+    /// along with the type of the result.
+    fn call(
+        &mut self,
+        body: &mut Body,
+        ops: &mut Ops,
+        list: u64,
+        explicit_ty: bool,
+    ) -> Result<(Call, Type)> {
+        let fn_ty = if explicit_ty {
+            Some(self.ty(ops.next()?)?)
+        } else {
+            None
+        };
+        let (callee, callee_ty) = self.value_and_type(body, ops)?;
+        let fn_ty = match fn_ty {
+            Some(ty) => ty,
+            None => self
+                .module
+                .types
+                .get_element(callee_ty)
+                .ok_or_else(|| malformed("call of a non-pointer"))?,
+        };
+        let FunctionType {
+            ret,
+            params,
+            is_var_arg,
+        } = match self.module.types.get(fn_ty).as_deref() {
+            Some(CompoundType::Function(func)) => func.clone(),
+            _ => return Err(malformed("call of a non-function")),
+        };
+
+        let mut tys = vec![if is_var_arg { fn_ty } else { ret }];
+        let mut args = vec![callee];
+        for ty in params {
+            args.push(self.value(body, ops, ty)?);
+            tys.push(ty);
+        }
+        if is_var_arg {
+            while ops.remaining() > 0 {
+                let (arg, ty) = self.value_and_type(body, ops)?;
+                args.push(arg);
+                tys.push(ty);
+            }
+        }
+        let param_attrs = (1..args.len() as u64)
+            .map(|i| self.param_attrs(list, i))
+            .collect::<Result<_>>()?;
+        let call = Call {
+            args,
+            tys,
+            param_attrs,
+            ret_attrs: self.param_attrs(list, 0)?,
+            func_attrs: self.func_attrs(list),
+        };
+        Ok((call, ret))
+    }
+
+    /// Returns the type of the pointer computed by a `getelementptr` whose
+    /// types and arguments are `tys` and `args`.
+    fn gep_ty(&self, body: &Body, tys: &[Type], args: &[ValueId]) -> Result<Type> {
+        let types = &self.module.types;
+        let invalid = || malformed("invalid getelementptr");
+        let mut ty = tys[0];
+        for &idx in args.iter().skip(2) {
+            ty = if types.is_struct(ty) {
+                let i = match body.data.value_ref(idx) {
+                    Value::Constant(ConstantData::Int(i)) => i.cast_to_usize(),
+                    _ => return Err(invalid()),
+                };
+                types.base().element_at(ty, i)
+            } else {
+                types.get_element(ty)
+            }
+            .ok_or_else(invalid)?;
+        }
+        let addr_space = match tys.get(1).and_then(|&ptr| types.get(ptr)).as_deref() {
+            Some(CompoundType::Pointer(ptr)) => ptr.addr_space,
+            _ => return Err(unsupported("getelementptr on vectors")),
+        };
+        Ok(types.ptr_to_in(ty, addr_space))
+    }
+
+    /// Reads a relative value ID, followed by the type of the value if it is
+    /// a forward reference.
+    fn value_and_type(&mut self, body: &mut Body, ops: &mut Ops) -> Result<(ValueId, Type)> {
+        let id = self.relative(ops.next()?);
+        if id < self.values.len() {
+            Ok((self.value_by_id(body, id, VOID)?, self.value_ty(id)?))
+        } else {
+            let ty = self.ty(ops.next()?)?;
+            Ok((self.value_by_id(body, id, ty)?, ty))
+        }
+    }
+
+    /// Reads a relative value ID of type `ty`.
+    fn value(&mut self, body: &mut Body, ops: &mut Ops, ty: Type) -> Result<ValueId> {
+        let id = self.relative(ops.next()?);
+        self.value_by_id(body, id, ty)
+    }
+
+    fn relative(&self, op: u64) -> usize {
+        (self.values.len() as u32).wrapping_sub(op as u32) as usize
+    }
+
+    /// Returns a new use of the value numbered `id`, a placeholder if it is
+    /// not defined yet.
+    fn value_by_id(&mut self, body: &mut Body, id: usize, _ty: Type) -> Result<ValueId> {
+        let value = match self.values.get(id) {
+            Some(Entry::Local { val, .. }) => return Ok(*val),
+            Some(Entry::Constant { record, .. }) if INLINE_ASM_CODES.contains(&record.code) => {
+                Value::InlineAsm(inline_asm(record)?)
+            }
+            Some(_) => Value::Constant(self.constant(id)?),
+            None => {
+                if let Some(val) = body.forward.get(&id) {
+                    return Ok(*val);
+                }
+                let block = *body
+                    .blocks
+                    .first()
+                    .ok_or_else(|| malformed("instruction out of blocks"))?;
+                let dummy = body.data.create_inst(Opcode::Invalid.with_block(block));
+                let val = body.data.create_value(Value::Instruction(dummy));
+                body.forward.insert(id, val);
+                return Ok(val);
+            }
+        };
+        Ok(body.data.create_value(value))
+    }
+}
+
+impl Body {
+    fn block(&self, id: u64) -> Result<BasicBlockId> {
+        self.blocks
+            .get(id as usize)
+            .copied()
+            .ok_or_else(|| malformed("undefined block"))
+    }
+
+    /// Returns an index of `extractvalue` or `insertvalue`.
+    fn index(&mut self, idx: u64) -> ValueId {
+        self.data
+            .create_value(Value::Constant(ConstantData::Int(ConstantInt::Int32(
+                idx as i32,
+            ))))
+    }
+}
+
+/// INLINEASM_OLD, INLINEASM_OLD2, INLINEASM_OLD3, INLINEASM
+const INLINE_ASM_CODES: [u32; 4] = [18, 23, 28, 30];
+
+/// Reads `[fnty, flags, asmstrsize, asmstr..., constraintsize, constraints...]`,
+/// without `fnty` before `INLINEASM`.
+fn inline_asm(record: &Record) -> Result<InlineAsm> {
+    let mut ops = Ops::new(&record.ops);
+    if record.code == 30 {
+        ops.next()?;
+    }
+    let flags = ops.next()?;
+    let mut string = || -> Result<String> {
+        let len = ops.next()? as usize;
+        let chars = ops
+            .rest()
+            .get(..len)
+            .ok_or_else(|| malformed("record too short"))?;
+        ops.pos += len;
+        Ok(string(chars))
+    };
+    let asm = string()?;
+    let constraints = string()?;
+    // `InlineAsm` calls the assembly `constraints` and the constraints
+    // `body`, as the text parser fills them.
+    Ok(InlineAsm {
+        body: constraints,
+        constraints: asm,
+        sideeffect: flags & 1 != 0,
+    })
+}
+
+fn records(block: &Block) -> impl Iterator<Item = &Record> {
+    block.items.iter().filter_map(|item| match item {
+        Item::Record(record) => Some(record),
+        Item::Block(_) => None,
+    })
+}
+
+fn string(ops: &[u64]) -> String {
+    let bytes: Vec<u8> = ops.iter().map(|&c| c as u8).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Reads a null-terminated string.
+fn c_string(ops: &mut Ops) -> Result<String> {
+    let mut bytes = vec![];
+    loop {
+        match ops.next()? {
+            0 => return Ok(String::from_utf8_lossy(&bytes).into_owned()),
+            c => bytes.push(c as u8),
+        }
+    }
+}
+
+/// Decodes a sign-rotated value, whose lowest bit is the sign.
+fn signed(op: u64) -> i64 {
+    if op & 1 == 0 {
+        (op >> 1) as i64
+    } else if op != 1 {
+        -((op >> 1) as i64)
+    } else {
+        i64::MIN
+    }
+}
+
+/// Decodes an alignment, stored as its log2 plus one, or 0 if unspecified.
+fn align(encoded: u64) -> Result<u32> {
+    match encoded {
+        0 => Ok(0),
+        1..=32 => Ok(1 << (encoded - 1)),
+        _ => Err(malformed("invalid alignment")),
+    }
+}
+
+fn int(ty: Type, i: i64) -> Result<ConstantData> {
+    Ok(ConstantData::Int(match ty {
+        I1 => ConstantInt::Int1(i & 1 != 0),
+        I8 => ConstantInt::Int8(i as i8),
+        I16 => ConstantInt::Int16(i as i16),
+        I32 => ConstantInt::Int32(i as i32),
+        I64 => ConstantInt::Int64(i),
+        I128 => ConstantInt::Int128(i.into()),
+        _ => return Err(unsupported("integer constants of this type")),
+    }))
+}
+
+fn linkage(code: u64) -> Result<Linkage> {
+    Ok(match code {
+        0 | 5 | 6 => Linkage::External,
+        1 | 16 => Linkage::WeakAny,
+        2 => Linkage::Appending,
+        3 => Linkage::Internal,
+        4 | 18 => Linkage::LinkOnceAny,
+        7 => Linkage::ExternalWeak,
+        8 => Linkage::Common,
+        9 | 13 | 14 => Linkage::Private,
+        10 | 17 => Linkage::WeakODR,
+        11 | 15 | 19 => Linkage::LinkOnceODR,
+        12 => Linkage::AvailableExternally,
+        _ => return Err(malformed("invalid linkage")),
+    })
+}
+
+fn unnamed_addr(code: Option<&u64>) -> Option<UnnamedAddr> {
+    match code {
+        Some(1) => Some(UnnamedAddr::Global),
+        Some(2) => Some(UnnamedAddr::Local),
+        _ => None,
+    }
+}
+
+fn visibility(code: Option<&u64>) -> Visibility {
+    match code {
+        Some(1) => Visibility::Hidden,
+        Some(2) => Visibility::Protected,
+        _ => Visibility::Default,
+    }
+}
+
+fn attribute(attr: &RawAttr) -> Option<Attribute> {
+    use Attribute::*;
+    Some(match *attr {
+        RawAttr::Enum(kind) => match kind {
+            2 => AlwaysInline,
+            4 => InlineHint,
+            6 => MinimizeSize,
+            7 => Naked,
+            10 => NoBuiltin,
+            12 => NoDuplicate,
+            13 => NoImplicitFloat,
+            14 => NoInline,
+            15 => NonLazyBind,
+            16 => NoRedZone,
+            17 => NoReturn,
+            18 => NoUnwind,
+            19 => OptSize,
+            20 => ReadNone,
+            21 => ReadOnly,
+            23 => ReturnsTwice,
+            26 => StackProtect,
+            27 => StackProtectReq,
+            28 => StackProtectStrong,
+            30 => SanitizeAddress,
+            31 => SanitizeThread,
+            32 => SanitizeMemory,
+            33 => UWTable,
+            35 => Builtin,
+            36 => Cold,
+            37 => OptNone,
+            40 => JumpTable,
+            43 => Convergent,
+            44 => SafeStack,
+            45 => ArgMemOnly,
+            48 => NoRecurse,
+            49 => InaccessibleMemOnly,
+            50 => InaccessibleMemOrArgMemOnly,
+            52 => WriteOnly,
+            53 => Speculatable,
+            54 => StrictFP,
+            55 => SanitizeHWAddress,
+            56 => NoCFCheck,
+            57 => OptForFuzzing,
+            58 => ShadowCallStack,
+            59 => SpeculativeLoadHardening,
+            61 => WillReturn,
+            62 => NoFree,
+            63 => NoSync,
+            64 => SanitizeMemTag,
+            _ => return None,
+        },
+        RawAttr::Str(ref kind, ref value) => StringAttribute {
+            kind: kind.as_str().into(),
+            value: value.as_str().into(),
+        },
+        RawAttr::Int(..) | RawAttr::Type(..) => return None,
+    })
+}
+
+fn malformed(what: &str) -> BitcodeError {
+    BitcodeError::Malformed(what.to_string())
+}
+
+fn unsupported(what: &str) -> BitcodeError {
+    BitcodeError::Unsupported(what.to_string())
+}
@@ -0,0 +1,13 @@
+//! Hash maps and sets hashed with `FxHasher`. They are those of `std` with
+//! the `std` feature, so that they can be passed to and from other crates,
+//! and those of `hashbrown` without it.
+
+#[cfg(feature = "std")]
+pub use rustc_hash::{FxHashMap, FxHashSet};
+
+#[cfg(not(feature = "std"))]
+pub type FxHashMap<K, V> =
+    hashbrown::HashMap<K, V, core::hash::BuildHasherDefault<rustc_hash::FxHasher>>;
+
+#[cfg(not(feature = "std"))]
+pub type FxHashSet<K> = hashbrown::HashSet<K, core::hash::BuildHasherDefault<rustc_hash::FxHasher>>;
@@ -1,26 +1,19 @@
+use crate::collections::FxHashSet;
 use crate::{ir::module::name::Name, traits::basic_block::BasicBlock as BB};
 use id_arena::Id;
-use rustc_hash::FxHashSet;
 
 pub type BasicBlockId = Id<BasicBlock>;
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BasicBlock {
     pub name: Option<Name>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub preds: FxHashSet<BasicBlockId>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub succs: FxHashSet<BasicBlockId>,
 }
 
-impl Default for BasicBlock {
-    fn default() -> Self {
-        Self {
-            name: None,
-            preds: FxHashSet::default(),
-            succs: FxHashSet::default(),
-        }
-    }
-}
-
 impl BasicBlock {
     pub fn new() -> Self {
         Self::default()
@@ -1,14 +1,33 @@
-use crate::ir::value::{Value, ValueId};
+use crate::ir::{
+    module::name::Name,
+    types::{Type, I32},
+    value::{ConstantData, ConstantInt, Value, ValueId},
+};
+use crate::prelude::*;
 
-use super::{basic_block::BasicBlockId, instruction::builder::Builder as InstBuilder, Function};
-use rustc_hash::FxHashSet;
+use super::{
+    basic_block::BasicBlockId,
+    instruction::{
+        builder::Builder as InstBuilder, Alloca, Br, Call, Cast, CondBr, GetElementPtr, ICmp,
+        ICmpCond, Instruction, InstructionId, IntBinary, Load, Opcode, Operand, Phi, Ret, Store,
+    },
+    Function,
+};
+use crate::collections::FxHashSet;
 
+/// Alias for [`Builder`], named after LLVM's `IRBuilder`.
+pub type IRBuilder<'a> = Builder<'a>;
+
+/// Builds instructions into a [`Function`] while keeping its layout, use lists
+/// and block predecessors/successors up to date.
 pub struct Builder<'a> {
     ctx: Context,
     pub(super) func: &'a mut Function,
     pub(super) cur_block: Option<BasicBlockId>,
+    insert_before: Option<InstructionId>,
 }
 
+#[derive(Default)]
 struct Context {
     is_inserted: FxHashSet<BasicBlockId>,
 }
@@ -16,21 +35,56 @@ struct Context {
 impl<'a> Builder<'a> {
     pub fn new(func: &'a mut Function) -> Self {
         Self {
-            ctx: Context::default(),
+            ctx: Context {
+                is_inserted: func.layout.block_iter().collect(),
+            },
             func,
             cur_block: None,
+            insert_before: None,
         }
     }
 
+    pub fn func(&self) -> &Function {
+        self.func
+    }
+
     pub fn create_block(&mut self) -> BasicBlockId {
         self.func.data.create_block()
     }
 
+    /// Makes new instructions be appended to the end of `block`.
     pub fn switch_to_block(&mut self, block: BasicBlockId) {
         self.cur_block = Some(block);
+        self.insert_before = None;
         self.ensure_inserted_block(block);
     }
 
+    /// Same as [`switch_to_block`](Self::switch_to_block).
+    pub fn position_at_end(&mut self, block: BasicBlockId) {
+        self.switch_to_block(block)
+    }
+
+    /// Makes new instructions be inserted at the beginning of `block`.
+    pub fn position_at_start(&mut self, block: BasicBlockId) {
+        self.switch_to_block(block);
+        self.insert_before = *self.func.layout.block_node(block).first_inst();
+    }
+
+    /// Makes new instructions be inserted right before `inst`.
+    pub fn position_before(&mut self, inst: InstructionId) {
+        let block = self
+            .func
+            .layout
+            .block_of(inst)
+            .expect("instruction must be placed in a block");
+        self.cur_block = Some(block);
+        self.insert_before = Some(inst);
+    }
+
+    pub fn current_block(&self) -> Option<BasicBlockId> {
+        self.cur_block
+    }
+
     pub fn ensure_inserted_block(&mut self, block: BasicBlockId) {
         if self.ctx.is_inserted(block) {
             return;
@@ -46,12 +100,258 @@ impl<'a> Builder<'a> {
     pub fn value<T: Into<Value>>(&mut self, val: T) -> ValueId {
         self.func.data.create_value(val.into())
     }
-}
 
-impl Default for Context {
-    fn default() -> Self {
-        Self {
-            is_inserted: FxHashSet::default(),
+    /// Returns a value referring to the `index`-th parameter of the function.
+    pub fn arg(&mut self, index: usize) -> ValueId {
+        self.value(Value::Argument(index))
+    }
+
+    /// Returns a value referring to a global (e.g. a function) named `name`.
+    pub fn global_ref<T: AsRef<str>>(&mut self, name: T) -> ValueId {
+        self.value(ConstantData::GlobalRef(Name::from(name.as_ref())))
+    }
+
+    /// Creates an instruction at the current position and returns its id.
+    pub fn insert(&mut self, opcode: Opcode, operand: Operand) -> InstructionId {
+        let cur_block = self
+            .cur_block
+            .expect("builder must be positioned before inserting instructions");
+        let inst = self
+            .func
+            .data
+            .create_inst(opcode.with_block(cur_block).with_operand(operand));
+        match self.insert_before {
+            Some(before) => self.func.layout.insert_inst_before(inst, before),
+            None => self.func.layout.append_inst(inst, cur_block),
+        }
+        self.update_block_edges(inst);
+        inst
+    }
+
+    pub fn build_alloca(&mut self, ty: Type) -> ValueId {
+        let inst = self.insert(
+            Opcode::Alloca,
+            Operand::Alloca(Alloca {
+                tys: [ty, I32],
+                num_elements: ConstantData::Int(ConstantInt::Int32(1)),
+                align: 0,
+            }),
+        );
+        self.value(Value::Instruction(inst))
+    }
+
+    pub fn build_load(&mut self, ty: Type, addr: ValueId) -> ValueId {
+        let ptr_ty = self.func.types.ptr_to(ty);
+        let inst = self.insert(
+            Opcode::Load,
+            Operand::Load(Load {
+                tys: [ty, ptr_ty],
+                addr,
+                align: 0,
+            }),
+        );
+        self.value(Value::Instruction(inst))
+    }
+
+    pub fn build_store(&mut self, ty: Type, val: ValueId, addr: ValueId) -> InstructionId {
+        let ptr_ty = self.func.types.ptr_to(ty);
+        self.insert(
+            Opcode::Store,
+            Operand::Store(Store {
+                tys: [ty, ptr_ty],
+                args: [val, addr],
+                align: 0,
+            }),
+        )
+    }
+
+    pub fn build_add(&mut self, ty: Type, lhs: ValueId, rhs: ValueId) -> ValueId {
+        self.build_int_binary(Opcode::Add, ty, lhs, rhs)
+    }
+
+    pub fn build_sub(&mut self, ty: Type, lhs: ValueId, rhs: ValueId) -> ValueId {
+        self.build_int_binary(Opcode::Sub, ty, lhs, rhs)
+    }
+
+    pub fn build_mul(&mut self, ty: Type, lhs: ValueId, rhs: ValueId) -> ValueId {
+        self.build_int_binary(Opcode::Mul, ty, lhs, rhs)
+    }
+
+    pub fn build_sdiv(&mut self, ty: Type, lhs: ValueId, rhs: ValueId) -> ValueId {
+        self.build_int_binary(Opcode::SDiv, ty, lhs, rhs)
+    }
+
+    pub fn build_srem(&mut self, ty: Type, lhs: ValueId, rhs: ValueId) -> ValueId {
+        self.build_int_binary(Opcode::SRem, ty, lhs, rhs)
+    }
+
+    pub fn build_and(&mut self, ty: Type, lhs: ValueId, rhs: ValueId) -> ValueId {
+        self.build_int_binary(Opcode::And, ty, lhs, rhs)
+    }
+
+    pub fn build_or(&mut self, ty: Type, lhs: ValueId, rhs: ValueId) -> ValueId {
+        self.build_int_binary(Opcode::Or, ty, lhs, rhs)
+    }
+
+    pub fn build_shl(&mut self, ty: Type, lhs: ValueId, rhs: ValueId) -> ValueId {
+        self.build_int_binary(Opcode::Shl, ty, lhs, rhs)
+    }
+
+    pub fn build_lshr(&mut self, ty: Type, lhs: ValueId, rhs: ValueId) -> ValueId {
+        self.build_int_binary(Opcode::LShr, ty, lhs, rhs)
+    }
+
+    fn build_int_binary(
+        &mut self,
+        opcode: Opcode,
+        ty: Type,
+        lhs: ValueId,
+        rhs: ValueId,
+    ) -> ValueId {
+        let inst = self.insert(
+            opcode,
+            Operand::IntBinary(IntBinary {
+                ty,
+                nsw: false,
+                nuw: false,
+                exact: false,
+                args: [lhs, rhs],
+            }),
+        );
+        self.value(Value::Instruction(inst))
+    }
+
+    pub fn build_icmp(&mut self, cond: ICmpCond, ty: Type, lhs: ValueId, rhs: ValueId) -> ValueId {
+        let inst = self.insert(
+            Opcode::ICmp,
+            Operand::ICmp(ICmp {
+                ty,
+                args: [lhs, rhs],
+                cond,
+            }),
+        );
+        self.value(Value::Instruction(inst))
+    }
+
+    /// Builds a cast instruction such as `sext`, `zext`, `trunc` or `bitcast`.
+    pub fn build_cast(&mut self, opcode: Opcode, from: Type, to: Type, arg: ValueId) -> ValueId {
+        let inst = self.insert(
+            opcode,
+            Operand::Cast(Cast {
+                tys: [from, to],
+                arg,
+            }),
+        );
+        self.value(Value::Instruction(inst))
+    }
+
+    /// Builds a `getelementptr`. `tys[0]` is the source element type and
+    /// `tys[1..]` are the types of `args`.
+    pub fn build_gep(&mut self, inbounds: bool, tys: Vec<Type>, args: Vec<ValueId>) -> ValueId {
+        let inst = self.insert(
+            Opcode::GetElementPtr,
+            Operand::GetElementPtr(GetElementPtr {
+                inbounds,
+                tys,
+                args,
+            }),
+        );
+        self.value(Value::Instruction(inst))
+    }
+
+    pub fn build_phi(&mut self, ty: Type, incoming: Vec<(ValueId, BasicBlockId)>) -> ValueId {
+        let (args, blocks) = incoming.into_iter().unzip();
+        let inst = self.insert(Opcode::Phi, Operand::Phi(Phi { ty, args, blocks }));
+        self.value(Value::Instruction(inst))
+    }
+
+    /// Appends an incoming `(value, block)` pair to `phi`. Useful when the
+    /// incoming value is defined after the phi itself.
+    pub fn add_phi_incoming(&mut self, phi: ValueId, val: ValueId, block: BasicBlockId) {
+        let phi = *self.func.data.value_ref(phi).as_inst();
+        match &mut self.func.data.inst_ref_mut(phi).operand {
+            Operand::Phi(Phi { args, blocks, .. }) => {
+                args.push(val);
+                blocks.push(block);
+            }
+            _ => panic!("not a phi"),
+        }
+        self.func.data.validate_inst_uses(phi);
+    }
+
+    /// Builds a `call` to `callee` returning `result_ty`.
+    /// Returns `None` if `result_ty` is void.
+    pub fn build_call(
+        &mut self,
+        result_ty: Type,
+        callee: ValueId,
+        args: Vec<(Type, ValueId)>,
+    ) -> Option<ValueId> {
+        let mut call_args = vec![callee];
+        let mut tys = vec![result_ty];
+        for (ty, arg) in args {
+            tys.push(ty);
+            call_args.push(arg);
+        }
+        let inst = self.insert(
+            Opcode::Call,
+            Operand::Call(Call {
+                param_attrs: vec![vec![]; call_args.len() - 1],
+                args: call_args,
+                tys,
+                ret_attrs: vec![],
+                func_attrs: vec![],
+            }),
+        );
+        if result_ty.is_void() {
+            None
+        } else {
+            Some(self.value(Value::Instruction(inst)))
+        }
+    }
+
+    pub fn build_br(&mut self, block: BasicBlockId) -> InstructionId {
+        self.insert(Opcode::Br, Operand::Br(Br { block }))
+    }
+
+    pub fn build_cond_br(
+        &mut self,
+        cond: ValueId,
+        then_block: BasicBlockId,
+        else_block: BasicBlockId,
+    ) -> InstructionId {
+        self.insert(
+            Opcode::CondBr,
+            Operand::CondBr(CondBr {
+                arg: cond,
+                blocks: [then_block, else_block],
+            }),
+        )
+    }
+
+    pub fn build_ret(&mut self, val: ValueId) -> InstructionId {
+        let ty = self.func.result_ty;
+        self.insert(Opcode::Ret, Operand::Ret(Ret { ty, val: Some(val) }))
+    }
+
+    pub fn build_ret_void(&mut self) -> InstructionId {
+        let ty = self.func.result_ty;
+        self.insert(Opcode::Ret, Operand::Ret(Ret { ty, val: None }))
+    }
+
+    pub fn build_unreachable(&mut self) -> InstructionId {
+        self.insert(Opcode::Unreachable, Operand::Unreachable)
+    }
+
+    fn update_block_edges(&mut self, inst: InstructionId) {
+        let Instruction {
+            operand, parent, ..
+        } = self.func.data.inst_ref(inst);
+        let parent = *parent;
+        let succs = operand.successors().to_vec();
+        for succ in succs {
+            self.func.data.block_ref_mut(parent).succs.insert(succ);
+            self.func.data.block_ref_mut(succ).preds.insert(parent);
         }
     }
 }
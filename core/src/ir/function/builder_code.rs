@@ -0,0 +1,394 @@
+//! Converts a parsed function into Rust source code that rebuilds it with
+//! [`Builder`](super::builder::Builder).
+
+use super::{
+    basic_block::BasicBlockId,
+    instruction::{
+        Alloca, Br, Call, Cast, CondBr, GetElementPtr, ICmp, ICmpCond, InstructionId, IntBinary,
+        Load, Opcode, Operand, Phi, Ret, Store,
+    },
+    Function,
+};
+use crate::collections::FxHashMap;
+use crate::ir::{
+    types::{self, CompoundType, Type},
+    value::{ConstantData, ConstantInt, Value, ValueId},
+};
+use crate::prelude::*;
+use core::fmt::Write;
+
+/// Emits a sequence of Rust statements that recreates `func` in a
+/// `module: vicis_core::ir::module::Module` in scope.
+///
+/// Instructions the builder cannot express yet are emitted as `todo!()`.
+pub fn emit_builder_code(func: &Function) -> String {
+    Emitter::new(func).emit()
+}
+
+struct Emitter<'a> {
+    func: &'a Function,
+    type_names: FxHashMap<Type, String>,
+    type_defs: String,
+    inst_names: FxHashMap<InstructionId, String>,
+    block_names: FxHashMap<BasicBlockId, String>,
+    body: String,
+    phi_fixups: String,
+    num_consts: usize,
+}
+
+impl<'a> Emitter<'a> {
+    fn new(func: &'a Function) -> Self {
+        Self {
+            func,
+            type_names: FxHashMap::default(),
+            type_defs: String::new(),
+            inst_names: FxHashMap::default(),
+            block_names: FxHashMap::default(),
+            body: String::new(),
+            phi_fixups: String::new(),
+            num_consts: 0,
+        }
+    }
+
+    fn emit(mut self) -> String {
+        let func = self.func;
+
+        for (i, block) in func.layout.block_iter().enumerate() {
+            self.block_names.insert(block, format!("bb{}", i));
+            for inst in func.layout.inst_iter(block) {
+                let name = format!("v{}", self.inst_names.len());
+                self.inst_names.insert(inst, name);
+            }
+        }
+
+        for block in func.layout.block_iter() {
+            writeln!(
+                self.body,
+                "builder.switch_to_block({});",
+                self.block_names[&block]
+            )
+            .unwrap();
+            for inst in func.layout.inst_iter(block) {
+                self.emit_inst(inst);
+            }
+        }
+
+        let result_ty = self.ty(func.result_ty);
+        let params = func
+            .params
+            .iter()
+            .map(|p| format!("Parameter::new({})", self.ty(p.ty)))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut out = String::new();
+        if !self.type_defs.is_empty() {
+            writeln!(out, "let types = module.types.clone();").unwrap();
+            out.push_str(&self.type_defs);
+        }
+        writeln!(
+            out,
+            "let func_id = module.create_function({:?}, {}, vec![{}], {});",
+            func.name, result_ty, params, func.is_var_arg
+        )
+        .unwrap();
+        writeln!(out, "let func = &mut module.functions_mut()[func_id];").unwrap();
+        writeln!(out, "let mut builder = Builder::new(func);").unwrap();
+        for block in func.layout.block_iter() {
+            writeln!(
+                out,
+                "let {} = builder.create_block();",
+                self.block_names[&block]
+            )
+            .unwrap();
+        }
+        out.push_str(&self.body);
+        out.push_str(&self.phi_fixups);
+        out
+    }
+
+    fn emit_inst(&mut self, id: InstructionId) {
+        let inst = self.func.data.inst_ref(id);
+        let dest = self.inst_names[&id].clone();
+        let stmt = match &inst.operand {
+            Operand::Alloca(Alloca { tys, .. }) => {
+                format!("let {} = builder.build_alloca({});", dest, self.ty(tys[0]))
+            }
+            Operand::Load(Load { tys, addr, .. }) => {
+                let addr = self.value(*addr);
+                let ty = self.ty(tys[0]);
+                format!("let {} = builder.build_load({}, {});", dest, ty, addr)
+            }
+            Operand::Store(Store { tys, args, .. }) => {
+                let val = self.value(args[0]);
+                let addr = self.value(args[1]);
+                let ty = self.ty(tys[0]);
+                format!("builder.build_store({}, {}, {});", ty, val, addr)
+            }
+            Operand::IntBinary(IntBinary { ty, args, .. }) => {
+                let method = match inst.opcode {
+                    Opcode::Add => "build_add",
+                    Opcode::Sub => "build_sub",
+                    Opcode::Mul => "build_mul",
+                    Opcode::SDiv => "build_sdiv",
+                    Opcode::SRem => "build_srem",
+                    Opcode::And => "build_and",
+                    Opcode::Or => "build_or",
+                    Opcode::Shl => "build_shl",
+                    Opcode::LShr => "build_lshr",
+                    _ => return self.unsupported(&dest, inst.opcode),
+                };
+                let lhs = self.value(args[0]);
+                let rhs = self.value(args[1]);
+                let ty = self.ty(*ty);
+                format!(
+                    "let {} = builder.{}({}, {}, {});",
+                    dest, method, ty, lhs, rhs
+                )
+            }
+            Operand::ICmp(ICmp { ty, args, cond }) => {
+                let lhs = self.value(args[0]);
+                let rhs = self.value(args[1]);
+                let ty = self.ty(*ty);
+                format!(
+                    "let {} = builder.build_icmp(ICmpCond::{}, {}, {}, {});",
+                    dest,
+                    icmp_cond_name(*cond),
+                    ty,
+                    lhs,
+                    rhs
+                )
+            }
+            Operand::Cast(Cast { tys, arg }) => {
+                let opcode = match inst.opcode {
+                    Opcode::Sext => "Sext",
+                    Opcode::Zext => "Zext",
+                    Opcode::Bitcast => "Bitcast",
+                    Opcode::Trunc => "Trunc",
+                    Opcode::IntToPtr => "IntToPtr",
+                    _ => return self.unsupported(&dest, inst.opcode),
+                };
+                let arg = self.value(*arg);
+                let from = self.ty(tys[0]);
+                let to = self.ty(tys[1]);
+                format!(
+                    "let {} = builder.build_cast(Opcode::{}, {}, {}, {});",
+                    dest, opcode, from, to, arg
+                )
+            }
+            Operand::GetElementPtr(GetElementPtr {
+                inbounds,
+                tys,
+                args,
+            }) => {
+                let args = self.values(args);
+                let tys = tys.iter().map(|&t| self.ty(t)).collect::<Vec<_>>();
+                format!(
+                    "let {} = builder.build_gep({}, vec![{}], vec![{}]);",
+                    dest,
+                    inbounds,
+                    tys.join(", "),
+                    args.join(", ")
+                )
+            }
+            Operand::Phi(Phi { ty, args, blocks }) => {
+                for (&arg, block) in args.iter().zip(blocks.iter()) {
+                    let stmt = format!(
+                        "builder.add_phi_incoming({}, {}, {});\n",
+                        dest,
+                        self.value_in(arg, true),
+                        self.block_names[block]
+                    );
+                    self.phi_fixups.push_str(&stmt);
+                }
+                format!(
+                    "let {} = builder.build_phi({}, vec![]);",
+                    dest,
+                    self.ty(*ty)
+                )
+            }
+            Operand::Call(Call { args, tys, .. }) => {
+                let callee = self.value(args[0]);
+                let call_args = args[1..]
+                    .iter()
+                    .zip(tys[1..].iter())
+                    .map(|(&arg, &ty)| {
+                        let arg = self.value(arg);
+                        format!("({}, {})", self.ty(ty), arg)
+                    })
+                    .collect::<Vec<_>>();
+                let ret = self.ty(tys[0]);
+                let call = format!(
+                    "builder.build_call({}, {}, vec![{}])",
+                    ret,
+                    callee,
+                    call_args.join(", ")
+                );
+                if tys[0].is_void() {
+                    format!("{};", call)
+                } else {
+                    format!("let {} = {}.unwrap();", dest, call)
+                }
+            }
+            Operand::Br(Br { block }) => {
+                format!("builder.build_br({});", self.block_names[block])
+            }
+            Operand::CondBr(CondBr { arg, blocks }) => {
+                let arg = self.value(*arg);
+                format!(
+                    "builder.build_cond_br({}, {}, {});",
+                    arg, self.block_names[&blocks[0]], self.block_names[&blocks[1]]
+                )
+            }
+            Operand::Ret(Ret { val: Some(val), .. }) => {
+                format!("builder.build_ret({});", self.value(*val))
+            }
+            Operand::Ret(Ret { val: None, .. }) => "builder.build_ret_void();".to_string(),
+            Operand::Unreachable => "builder.build_unreachable();".to_string(),
+            _ => return self.unsupported(&dest, inst.opcode),
+        };
+        writeln!(self.body, "{}", stmt).unwrap();
+    }
+
+    fn unsupported(&mut self, dest: &str, opcode: Opcode) {
+        writeln!(
+            self.body,
+            "let {}: ValueId = todo!(\"{:?} is not supported by the builder\");",
+            dest, opcode
+        )
+        .unwrap();
+    }
+
+    fn values(&mut self, vals: &[ValueId]) -> Vec<String> {
+        vals.iter().map(|&v| self.value(v)).collect()
+    }
+
+    fn value(&mut self, val: ValueId) -> String {
+        self.value_in(val, false)
+    }
+
+    /// Returns an expression for `val`. Constants are bound to a variable first
+    /// so that they can be passed to `builder` methods.
+    fn value_in(&mut self, val: ValueId, fixup: bool) -> String {
+        let expr = match self.func.data.value_ref(val) {
+            Value::Instruction(id) => return self.inst_names[id].clone(),
+            Value::Argument(i) => format!("builder.arg({})", i),
+            Value::Constant(ConstantData::Int(i)) => format!(
+                "builder.value({})",
+                match i {
+                    ConstantInt::Int1(i) => format!("ConstantInt::Int1({})", i),
+                    ConstantInt::Int8(i) => format!("ConstantInt::Int8({})", i),
+                    ConstantInt::Int16(i) => format!("ConstantInt::Int16({})", i),
+                    ConstantInt::Int32(i) => format!("ConstantInt::Int32({})", i),
+                    ConstantInt::Int64(i) => format!("ConstantInt::Int64({})", i),
+                    ConstantInt::Int128(i) => format!("ConstantInt::Int128({})", i),
+                }
+            ),
+            Value::Constant(ConstantData::Undef) => "builder.value(ConstantData::Undef)".into(),
+            Value::Constant(ConstantData::Null) => "builder.value(ConstantData::Null)".into(),
+            Value::Constant(ConstantData::AggregateZero) => {
+                "builder.value(ConstantData::AggregateZero)".into()
+            }
+            Value::Constant(ConstantData::GlobalRef(name)) => {
+                format!("builder.global_ref({:?})", format!("{:?}", name))
+            }
+            _ => "todo!(\"unsupported value\")".into(),
+        };
+        let name = format!("c{}", self.num_consts);
+        self.num_consts += 1;
+        let stmt = format!("let {} = {};\n", name, expr);
+        if fixup {
+            self.phi_fixups.push_str(&stmt);
+        } else {
+            self.body.push_str(&stmt);
+        }
+        name
+    }
+
+    /// Returns an expression for `ty`, defining it in the prelude if needed.
+    fn ty(&mut self, ty: Type) -> String {
+        match ty {
+            types::VOID => return "types::VOID".into(),
+            types::I1 => return "types::I1".into(),
+            types::I8 => return "types::I8".into(),
+            types::I16 => return "types::I16".into(),
+            types::I32 => return "types::I32".into(),
+            types::I64 => return "types::I64".into(),
+            _ => {}
+        }
+        if let Some(name) = self.type_names.get(&ty) {
+            return name.clone();
+        }
+
+        let compound = self.func.types.get(ty).map(|t| t.clone());
+        let expr = match compound {
+            Some(CompoundType::Pointer(p)) => {
+                format!("types.ptr_to({})", self.ty(p.inner))
+            }
+            Some(CompoundType::Array(a)) => {
+                format!("types.array_of({}, {})", self.ty(a.inner), a.num_elements)
+            }
+            Some(CompoundType::Vector(v)) => {
+                format!("types.vector_of({}, {})", self.ty(v.inner), v.num_elements)
+            }
+            Some(CompoundType::Function(f)) => {
+                let ret = self.ty(f.ret);
+                let params = f.params.iter().map(|&p| self.ty(p)).collect::<Vec<_>>();
+                format!(
+                    "types.func({}, &[{}], {})",
+                    ret,
+                    params.join(", "),
+                    f.is_var_arg
+                )
+            }
+            Some(CompoundType::Struct(s)) => match s.name {
+                Some(name) => format!(
+                    "types.base().get_struct({:?}).unwrap()",
+                    format!("{:?}", name)
+                ),
+                None => {
+                    let elems = s.elems.iter().map(|&e| self.ty(e)).collect::<Vec<_>>();
+                    format!(
+                        "types.{}(&[{}])",
+                        if s.is_packed {
+                            "packed_struct_of"
+                        } else {
+                            "struct_of"
+                        },
+                        elems.join(", ")
+                    )
+                }
+            },
+            Some(CompoundType::Alias(t)) => return self.ty(t),
+            Some(CompoundType::Metadata) => "types.metadata()".into(),
+            None => "todo!(\"unknown type\")".into(),
+        };
+        let name = format!("ty{}", self.type_names.len());
+        writeln!(self.type_defs, "let {} = {};", name, expr).unwrap();
+        self.type_names.insert(ty, name.clone());
+        name
+    }
+}
+
+fn icmp_cond_name(cond: ICmpCond) -> &'static str {
+    match cond {
+        ICmpCond::Eq => "Eq",
+        ICmpCond::Ne => "Ne",
+        ICmpCond::Ugt => "Ugt",
+        ICmpCond::Uge => "Uge",
+        ICmpCond::Ult => "Ult",
+        ICmpCond::Ule => "Ule",
+        ICmpCond::Sgt => "Sgt",
+        ICmpCond::Sge => "Sge",
+        ICmpCond::Slt => "Slt",
+        ICmpCond::Sle => "Sle",
+    }
+}
+
+#[test]
+fn emit_phi_loop() {
+    use crate::ir::module::parse_assembly;
+    let module = parse_assembly(include_str!("../../../examples/phi_loop.ll")).unwrap();
+    let func = module.find_function_by_name("main").unwrap();
+    insta::assert_snapshot!(emit_builder_code(&module.functions()[func]));
+}
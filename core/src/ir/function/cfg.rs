@@ -0,0 +1,510 @@
+//! Editing operations on the control flow graph of a [`Function`].
+//!
+//! All operations keep block preds/succs, phi nodes, the users map and the
+//! layout consistent.
+//!
+//! The entry block of a function is the first block in its layout. It must
+//! have no predecessors and no phis; see [`Function::verify_entry_block`].
+//! The parser rejects functions that break this, and passes branching back to
+//! the start of a function restore it with [`Function::ensure_canonical_entry`].
+
+use super::{
+    basic_block::BasicBlockId,
+    instruction::{Br, InstructionId, Opcode, Operand, Phi},
+    Function,
+};
+use crate::collections::FxHashSet;
+use crate::ir::value::Value;
+use crate::prelude::*;
+use core::{error::Error, fmt};
+
+/// A violation of the invariants of the entry block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryBlockError {
+    /// The entry block is branched to from `0`.
+    HasPredecessor(BasicBlockId),
+    /// The entry block contains the phi `0`.
+    HasPhi(InstructionId),
+}
+
+impl Function {
+    /// Splits `block` right before `inst`. `inst` and all the following
+    /// instructions are moved to a new block placed after `block`, and `block`
+    /// is terminated with a branch to the new block, which is returned.
+    pub fn split_block(&mut self, block: BasicBlockId, inst: InstructionId) -> BasicBlockId {
+        assert_eq!(self.layout.block_of(inst), Some(block));
+
+        let new_block = self.data.create_block();
+        self.layout.insert_block_after(new_block, block);
+
+        let moved: Vec<InstructionId> = self
+            .layout
+            .inst_iter(block)
+            .skip_while(|&i| i != inst)
+            .collect();
+        for i in moved {
+            self.layout.remove_inst(i);
+            self.layout.append_inst(i, new_block);
+            self.data.inst_ref_mut(i).parent = new_block;
+        }
+
+        let succs = core::mem::take(&mut self.data.block_ref_mut(block).succs);
+        for &succ in &succs {
+            self.data.remove_block_pred(succ, block);
+            self.data.block_ref_mut(succ).preds.insert(new_block);
+            self.replace_phi_incoming_block(succ, block, new_block);
+        }
+        self.data.block_ref_mut(new_block).succs = succs;
+
+        let br = self.data.create_inst(
+            Opcode::Br
+                .with_block(block)
+                .with_operand(Operand::Br(Br { block: new_block })),
+        );
+        self.layout.append_inst(br, block);
+        self.data.block_ref_mut(block).succs.insert(new_block);
+        self.data.block_ref_mut(new_block).preds.insert(block);
+
+        new_block
+    }
+
+    /// Inserts a new block on the edge `from` -> `to` and returns it.
+    pub fn split_edge(&mut self, from: BasicBlockId, to: BasicBlockId) -> BasicBlockId {
+        assert!(self.data.block_ref(from).succs.contains(&to));
+
+        let new_block = self.data.create_block();
+        self.layout.insert_block_after(new_block, from);
+
+        let br = self.data.create_inst(
+            Opcode::Br
+                .with_block(new_block)
+                .with_operand(Operand::Br(Br { block: to })),
+        );
+        self.layout.append_inst(br, new_block);
+
+        if let Some(term) = *self.layout.block_node(from).last_inst() {
+            for succ in self.data.inst_ref_mut(term).operand.successors_mut() {
+                if *succ == to {
+                    *succ = new_block;
+                }
+            }
+        }
+        self.replace_phi_incoming_block(to, from, new_block);
+
+        self.data.remove_block_succ(from, to);
+        self.data.remove_block_pred(to, from);
+        self.data.block_ref_mut(from).succs.insert(new_block);
+        self.data.block_ref_mut(new_block).preds.insert(from);
+        self.data.block_ref_mut(new_block).succs.insert(to);
+        self.data.block_ref_mut(to).preds.insert(new_block);
+
+        new_block
+    }
+
+    /// Merges `succ` into `pred` if `pred` unconditionally branches to `succ`
+    /// and `pred` is the only predecessor of `succ`. Returns true if merged.
+    pub fn merge_blocks(&mut self, pred: BasicBlockId, succ: BasicBlockId) -> bool {
+        if pred == succ
+            || self.data.block_ref(pred).succs.len() != 1
+            || self.data.block_ref(succ).preds.len() != 1
+            || !self.data.block_ref(pred).succs.contains(&succ)
+        {
+            return false;
+        }
+        let br = match *self.layout.block_node(pred).last_inst() {
+            Some(br) if self.data.inst_ref(br).opcode == Opcode::Br => br,
+            _ => return false,
+        };
+        self.erase_instruction(br);
+
+        // Phis in `succ` have exactly one incoming value, which is now always taken.
+        let insts: Vec<InstructionId> = self.layout.inst_iter(succ).collect();
+        for inst in insts {
+            if let Operand::Phi(Phi { args, .. }) = &self.data.inst_ref(inst).operand {
+                let val = args[0];
+                self.replace_all_uses_with(inst, val);
+                self.erase_instruction(inst);
+                continue;
+            }
+            self.layout.remove_inst(inst);
+            self.layout.append_inst(inst, pred);
+            self.data.inst_ref_mut(inst).parent = pred;
+        }
+
+        let succs = core::mem::take(&mut self.data.block_ref_mut(succ).succs);
+        for &s in &succs {
+            self.data.remove_block_pred(s, succ);
+            self.data.block_ref_mut(s).preds.insert(pred);
+            self.replace_phi_incoming_block(s, succ, pred);
+        }
+        self.data.block_ref_mut(pred).succs = succs;
+        self.data.block_ref_mut(succ).preds.clear();
+        self.layout.remove_block(succ);
+
+        true
+    }
+
+    /// Checks that the entry block has no predecessors and no phis.
+    pub fn verify_entry_block(&self) -> Result<(), EntryBlockError> {
+        let Some(entry) = self.entry_block() else {
+            return Ok(());
+        };
+        if let Some(&pred) = self.data.block_ref(entry).preds.iter().min() {
+            return Err(EntryBlockError::HasPredecessor(pred));
+        }
+        if let Some(&phi) = self.phis_of(entry).first() {
+            return Err(EntryBlockError::HasPhi(phi));
+        }
+        Ok(())
+    }
+
+    /// Makes the entry block satisfy [`verify_entry_block`](Self::verify_entry_block)
+    /// by inserting a new entry block branching to the old one if needed.
+    /// Returns the new entry block, if any.
+    pub fn ensure_canonical_entry(&mut self) -> Option<BasicBlockId> {
+        let entry = self.entry_block()?;
+        if self.verify_entry_block().is_ok() {
+            return None;
+        }
+
+        let new_entry = self.data.create_block();
+        self.layout.insert_block_before(new_entry, entry);
+        let br = self.data.create_inst(
+            Opcode::Br
+                .with_block(new_entry)
+                .with_operand(Operand::Br(Br { block: entry })),
+        );
+        self.layout.append_inst(br, new_entry);
+        self.add_undef_phi_incoming(entry, new_entry);
+        self.data.block_ref_mut(new_entry).succs.insert(entry);
+        self.data.block_ref_mut(entry).preds.insert(new_entry);
+
+        Some(new_entry)
+    }
+
+    /// Removes blocks not reachable from the entry block and returns how many were removed.
+    pub fn remove_unreachable_blocks(&mut self) -> usize {
+        let entry = match self.entry_block() {
+            Some(entry) => entry,
+            None => return 0,
+        };
+
+        let mut reachable = FxHashSet::default();
+        let mut worklist = vec![entry];
+        while let Some(block) = worklist.pop() {
+            if !reachable.insert(block) {
+                continue;
+            }
+            worklist.extend(self.data.block_ref(block).succs.iter().copied());
+        }
+
+        let unreachable: Vec<BasicBlockId> = self
+            .layout
+            .block_iter()
+            .filter(|b| !reachable.contains(b))
+            .collect();
+
+        for &block in &unreachable {
+            let succs = core::mem::take(&mut self.data.block_ref_mut(block).succs);
+            for succ in succs {
+                self.data.remove_block_pred(succ, block);
+                self.remove_phi_incoming_block(succ, block);
+            }
+            let preds = core::mem::take(&mut self.data.block_ref_mut(block).preds);
+            for pred in preds {
+                self.data.remove_block_succ(pred, block);
+            }
+        }
+
+        for &block in &unreachable {
+            let insts: Vec<InstructionId> = self.layout.inst_iter(block).collect();
+            for inst in insts {
+                self.remove_inst(inst);
+                self.data.users_map.remove(&inst);
+                let inst = self.data.inst_ref_mut(inst);
+                inst.opcode = Opcode::Invalid;
+                inst.operand = Operand::Invalid;
+            }
+            self.layout.remove_block(block);
+        }
+
+        unreachable.len()
+    }
+
+    /// Replaces the terminator of `block` with an unconditional branch to
+    /// `target`. Phis in the blocks no longer reached from `block` drop their
+    /// incoming values for it, and phis in `target` get undef for a new edge.
+    pub fn replace_terminator_with_br(&mut self, block: BasicBlockId, target: BasicBlockId) {
+        if let Some(term) = *self.layout.block_node(block).last_inst() {
+            if self.data.inst_ref(term).opcode.is_terminator() {
+                self.remove_inst(term);
+            }
+        }
+
+        let succs = core::mem::take(&mut self.data.block_ref_mut(block).succs);
+        for &succ in succs.iter().filter(|&&succ| succ != target) {
+            self.data.remove_block_pred(succ, block);
+            self.remove_phi_incoming_block(succ, block);
+        }
+        if !succs.contains(&target) {
+            self.add_undef_phi_incoming(target, block);
+        }
+
+        let br = self.data.create_inst(
+            Opcode::Br
+                .with_block(block)
+                .with_operand(Operand::Br(Br { block: target })),
+        );
+        self.layout.append_inst(br, block);
+        self.data.block_ref_mut(block).succs.insert(target);
+        self.data.block_ref_mut(target).preds.insert(block);
+    }
+
+    /// Retargets the edges `block` -> `from` of the terminator of `block` to
+    /// `to`. Phis in `from` drop their incoming values for `block`, and phis in
+    /// `to` get undef for a new edge. Returns false if `block` does not branch
+    /// to `from`.
+    pub fn replace_successor(
+        &mut self,
+        block: BasicBlockId,
+        from: BasicBlockId,
+        to: BasicBlockId,
+    ) -> bool {
+        let Some(term) = *self.layout.block_node(block).last_inst() else {
+            return false;
+        };
+        let succs = self.data.inst_ref_mut(term).operand.successors_mut();
+        if from == to || !succs.contains(&from) {
+            return false;
+        }
+        for succ in succs.iter_mut().filter(|succ| **succ == from) {
+            *succ = to;
+        }
+
+        self.data.remove_block_succ(block, from);
+        self.data.remove_block_pred(from, block);
+        self.remove_phi_incoming_block(from, block);
+        if self.data.block_ref_mut(block).succs.insert(to) {
+            self.add_undef_phi_incoming(to, block);
+        }
+        self.data.block_ref_mut(to).preds.insert(block);
+
+        true
+    }
+
+    fn phis_of(&self, block: BasicBlockId) -> Vec<InstructionId> {
+        self.layout
+            .inst_iter(block)
+            .filter(|&i| self.data.inst_ref(i).opcode == Opcode::Phi)
+            .collect()
+    }
+
+    fn replace_phi_incoming_block(
+        &mut self,
+        block: BasicBlockId,
+        from: BasicBlockId,
+        to: BasicBlockId,
+    ) {
+        for phi in self.phis_of(block) {
+            if let Operand::Phi(Phi { blocks, .. }) = &mut self.data.inst_ref_mut(phi).operand {
+                for b in blocks.iter_mut().filter(|b| **b == from) {
+                    *b = to;
+                }
+            }
+        }
+    }
+
+    fn add_undef_phi_incoming(&mut self, block: BasicBlockId, incoming: BasicBlockId) {
+        for phi in self.phis_of(block) {
+            let undef = self.data.create_value(Value::undef());
+            if let Operand::Phi(Phi { args, blocks, .. }) = &mut self.data.inst_ref_mut(phi).operand
+            {
+                args.push(undef);
+                blocks.push(incoming);
+            }
+            self.data.validate_inst_uses(phi);
+        }
+    }
+
+    fn remove_phi_incoming_block(&mut self, block: BasicBlockId, incoming: BasicBlockId) {
+        for phi in self.phis_of(block) {
+            self.data.remove_uses(phi);
+            if let Operand::Phi(Phi { args, blocks, .. }) = &mut self.data.inst_ref_mut(phi).operand
+            {
+                while let Some(i) = blocks.iter().position(|&b| b == incoming) {
+                    blocks.remove(i);
+                    args.remove(i);
+                }
+            }
+            self.data.validate_inst_uses(phi);
+        }
+    }
+}
+
+impl Error for EntryBlockError {}
+
+impl fmt::Display for EntryBlockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::HasPredecessor(pred) => {
+                write!(f, "entry block has predecessor {}", pred.index())
+            }
+            Self::HasPhi(phi) => write!(f, "entry block contains phi {}", phi.index()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::EntryBlockError;
+    use crate::ir::{function::Function, module::parse_assembly};
+
+    fn with_func(source: &str, f: impl FnOnce(&mut Function)) -> String {
+        let mut module = parse_assembly(source).unwrap();
+        let id = module.find_function_by_name("f").unwrap();
+        let func = &mut module.functions_mut()[id];
+        f(func);
+        format!("{:?}", func)
+    }
+
+    fn nth_block(func: &Function, n: usize) -> crate::ir::function::basic_block::BasicBlockId {
+        func.layout.block_iter().nth(n).unwrap()
+    }
+
+    const DIAMOND: &str = r#"
+define i32 @f(i1 %0) {
+  br i1 %0, label %2, label %3
+2:
+  br label %4
+3:
+  br label %4
+4:
+  %5 = phi i32 [ 1, %2 ], [ 2, %3 ]
+  %6 = add i32 %5, 1
+  ret i32 %6
+}"#;
+
+    #[test]
+    fn split_block() {
+        let printed = with_func(DIAMOND, |func| {
+            let block = nth_block(func, 3);
+            let add = func.layout.inst_iter(block).nth(1).unwrap();
+            let new = func.split_block(block, add);
+            assert!(func.data.block_ref(block).succs.contains(&new));
+            assert!(func.data.block_ref(new).preds.contains(&block));
+        });
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn split_edge() {
+        let printed = with_func(DIAMOND, |func| {
+            let (from, to) = (nth_block(func, 1), nth_block(func, 3));
+            let new = func.split_edge(from, to);
+            assert!(func.data.block_ref(to).preds.contains(&new));
+            assert!(!func.data.block_ref(to).preds.contains(&from));
+        });
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn merge_blocks() {
+        let printed = with_func(
+            r#"
+define i32 @f(i32 %0) {
+  br label %2
+2:
+  %3 = phi i32 [ %0, %1 ]
+  %4 = add i32 %3, 1
+  br label %5
+5:
+  ret i32 %4
+}"#,
+            |func| {
+                let (entry, second, last) =
+                    (nth_block(func, 0), nth_block(func, 1), nth_block(func, 2));
+                assert!(func.merge_blocks(entry, second));
+                assert!(func.merge_blocks(entry, last));
+                assert!(func.data.block_ref(entry).succs.is_empty());
+            },
+        );
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn replace_terminator_with_br() {
+        let printed = with_func(DIAMOND, |func| {
+            let (entry, left, join) = (nth_block(func, 0), nth_block(func, 1), nth_block(func, 3));
+            func.replace_terminator_with_br(entry, join);
+            func.replace_terminator_with_br(left, join);
+            assert!(func.data.block_ref(left).preds.is_empty());
+            assert_eq!(func.data.block_ref(join).preds.len(), 3);
+        });
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn replace_successor() {
+        let printed = with_func(DIAMOND, |func| {
+            let (entry, left, right, join) = (
+                nth_block(func, 0),
+                nth_block(func, 1),
+                nth_block(func, 2),
+                nth_block(func, 3),
+            );
+            assert!(func.replace_successor(entry, right, join));
+            assert!(!func.replace_successor(entry, right, join));
+            assert!(func.data.block_ref(right).preds.is_empty());
+            assert!(func.data.block_ref(join).preds.contains(&entry));
+            assert!(func.data.block_ref(entry).succs.contains(&left));
+        });
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn ensure_canonical_entry() {
+        let printed = with_func(
+            r#"
+define i32 @f(i32 %0) {
+  %2 = add i32 %0, 1
+  br label %3
+3:
+  ret i32 %2
+}"#,
+            |func| {
+                let (entry, exit) = (nth_block(func, 0), nth_block(func, 1));
+                func.replace_terminator_with_br(exit, entry);
+                assert_eq!(
+                    func.verify_entry_block(),
+                    Err(EntryBlockError::HasPredecessor(exit))
+                );
+                let new_entry = func.ensure_canonical_entry().unwrap();
+                assert!(func.is_entry_block(new_entry));
+                assert_eq!(func.verify_entry_block(), Ok(()));
+                assert_eq!(func.ensure_canonical_entry(), None);
+            },
+        );
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn remove_unreachable_blocks() {
+        let printed = with_func(
+            r#"
+define i32 @f(i32 %0) {
+  br label %3
+2:
+  br label %3
+3:
+  %4 = phi i32 [ %0, %1 ], [ 1, %2 ]
+  ret i32 %4
+}"#,
+            |func| {
+                assert_eq!(func.remove_unreachable_blocks(), 1);
+                let last = nth_block(func, 1);
+                assert_eq!(func.data.block_ref(last).preds.len(), 1);
+            },
+        );
+        insta::assert_snapshot!(printed);
+    }
+}
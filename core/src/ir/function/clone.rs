@@ -0,0 +1,230 @@
+//! Cloning functions.
+//!
+//! [`clone_function`] returns a deep copy of a function, and [`clone_into`]
+//! copies the body of a function into another one, e.g. a callee into its
+//! caller. Both go through a [`ValueMap`], which tells what the arguments of
+//! the original function become in the copy, and records which instruction
+//! and block each one of the original was copied to.
+//!
+//! Mapping arguments to constants specializes the copy:
+//!
+//! ```ignore
+//! // `f` with its first argument fixed to 42.
+//! let vmap = ValueMap::new().with_arg(0, Value::Constant(ConstantInt::Int32(42).into()));
+//! let f42 = clone_function(&f, &vmap);
+//! ```
+
+use super::{
+    basic_block::BasicBlockId,
+    instruction::{InstructionId, Operand, Phi},
+    Function,
+};
+use crate::collections::FxHashMap;
+use crate::ir::value::{Value, ValueId};
+use crate::prelude::*;
+
+/// Maps arguments, instructions and blocks of a function to their
+/// counterparts in a copy of it.
+#[derive(Debug, Clone, Default)]
+pub struct ValueMap {
+    args: FxHashMap<usize, Value>,
+    insts: FxHashMap<InstructionId, InstructionId>,
+    blocks: FxHashMap<BasicBlockId, BasicBlockId>,
+}
+
+impl ValueMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the argument at `index` with `val` in the copy.
+    pub fn with_arg(mut self, index: usize, val: Value) -> Self {
+        self.set_arg(index, val);
+        self
+    }
+
+    pub fn set_arg(&mut self, index: usize, val: Value) {
+        self.args.insert(index, val);
+    }
+
+    pub fn arg(&self, index: usize) -> Option<&Value> {
+        self.args.get(&index)
+    }
+
+    /// Returns the copy of `inst`, once cloned.
+    pub fn inst(&self, inst: InstructionId) -> Option<InstructionId> {
+        self.insts.get(&inst).copied()
+    }
+
+    /// Returns the copy of `block`, once cloned.
+    pub fn block(&self, block: BasicBlockId) -> Option<BasicBlockId> {
+        self.blocks.get(&block).copied()
+    }
+}
+
+/// Returns a copy of `func`.
+///
+/// The arguments mapped by `vmap` are replaced with the values they are
+/// mapped to, and removed from the parameters of the copy. The others keep
+/// their order.
+pub fn clone_function(func: &Function, vmap: &ValueMap) -> Function {
+    let mut vmap = vmap.clone();
+    let mut params = vec![];
+    for (i, param) in func.params.iter().enumerate() {
+        if vmap.arg(i).is_none() {
+            vmap.set_arg(i, Value::Argument(params.len()));
+            params.push(param.clone());
+        }
+    }
+
+    let mut clone = Function::new(
+        &func.name,
+        func.result_ty,
+        params,
+        func.is_var_arg,
+        func.types.clone(),
+    );
+    clone.linkage = func.linkage;
+    clone.preemption_specifier = func.preemption_specifier;
+    clone.visibility = func.visibility;
+    clone.unnamed_addr = func.unnamed_addr;
+    clone.func_attrs = func.func_attrs.clone();
+    clone.ret_attrs = func.ret_attrs.clone();
+    clone.personality = func.personality.clone();
+    clone.section = func.section.clone();
+    clone.comdat = func.comdat;
+    clone.align = func.align;
+
+    clone_into(&mut clone, func, &mut vmap, None);
+    copy_names(&mut clone, func, &vmap);
+
+    clone
+}
+
+/// Names the blocks and instructions `vmap` copied from `src` into `dst` as
+/// in `src`. Names are unique within a function, so they can be kept as is.
+pub(crate) fn copy_names(dst: &mut Function, src: &Function, vmap: &ValueMap) {
+    for (&block, &new_block) in &vmap.blocks {
+        dst.data.block_ref_mut(new_block).name = src.data.block_ref(block).name;
+    }
+    for (&inst, &new_inst) in &vmap.insts {
+        dst.data.inst_ref_mut(new_inst).dest = src.data.inst_ref(inst).dest;
+    }
+}
+
+/// Copies the blocks of `src` into `dst`, in the same order, before
+/// `before` or at the end of the layout if `None`.
+///
+/// Uses of the arguments of `src` are replaced as given by `vmap`, which
+/// must map them all unless `dst` takes the same arguments. `vmap` is then
+/// filled with the copied instructions and blocks. The copies are unnamed.
+pub fn clone_into(
+    dst: &mut Function,
+    src: &Function,
+    vmap: &mut ValueMap,
+    before: Option<BasicBlockId>,
+) {
+    for block in src.layout.block_iter() {
+        let new_block = dst.data.create_block();
+        match before {
+            Some(before) => dst.layout.insert_block_before(new_block, before),
+            None => dst.layout.append_block(new_block),
+        }
+        vmap.blocks.insert(block, new_block);
+    }
+
+    let mut cloned = vec![];
+    for block in src.layout.block_iter() {
+        let new_block = vmap.blocks[&block];
+        for inst in src.layout.inst_iter(block) {
+            let orig = src.data.inst_ref(inst);
+            let new_inst = dst.data.create_inst(
+                orig.opcode
+                    .with_block(new_block)
+                    .with_metadata(orig.metadata.clone()),
+            );
+            dst.layout.append_inst(new_inst, new_block);
+            vmap.insts.insert(inst, new_inst);
+            cloned.push((inst, new_inst));
+        }
+    }
+
+    // Instructions may use ones defined later in the layout, e.g. in phis,
+    // so operands are copied once all instructions exist.
+    let mut values: FxHashMap<ValueId, ValueId> = FxHashMap::default();
+    for (inst, new_inst) in cloned {
+        let mut operand = src.data.inst_ref(inst).operand.clone();
+        for arg in operand.args_mut() {
+            *arg = *values.entry(*arg).or_insert_with(|| {
+                let val = match src.data.value_ref(*arg) {
+                    Value::Instruction(inst) => Value::Instruction(vmap.insts[inst]),
+                    Value::Argument(i) => vmap.args.get(i).cloned().unwrap_or(Value::Argument(*i)),
+                    v => v.clone(),
+                };
+                dst.data.create_value(val)
+            });
+        }
+        for target in operand.successors_mut() {
+            *target = vmap.blocks[target];
+        }
+        if let Operand::Phi(Phi { blocks, .. }) = &mut operand {
+            for b in blocks.iter_mut() {
+                *b = vmap.blocks[b];
+            }
+        }
+        let new_block = dst.data.inst_ref(new_inst).parent;
+        for &succ in operand.successors() {
+            dst.data.block_ref_mut(new_block).succs.insert(succ);
+            dst.data.block_ref_mut(succ).preds.insert(new_block);
+        }
+        dst.data.inst_ref_mut(new_inst).operand = operand;
+        dst.data.validate_inst_uses(new_inst);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::{module::parse_assembly, value::ConstantInt};
+
+    const SOURCE: &str = r#"
+define i32 @f(i32 %x, i32 %n) {
+entry:
+  %c = icmp sgt i32 %n, 0
+  br i1 %c, label %loop, label %exit
+loop:
+  %i = phi i32 [ 0, %entry ], [ %i.next, %loop ]
+  %s = phi i32 [ %x, %entry ], [ %s.next, %loop ]
+  %s.next = add i32 %s, %i
+  %i.next = add i32 %i, 1
+  %done = icmp eq i32 %i.next, %n
+  br i1 %done, label %exit, label %loop
+exit:
+  %r = phi i32 [ %x, %entry ], [ %s.next, %loop ]
+  ret i32 %r
+}"#;
+
+    #[test]
+    fn clone() {
+        let module = parse_assembly(SOURCE).unwrap();
+        let f = &module.functions()[module.find_function_by_name("f").unwrap()];
+        let clone = clone_function(f, &ValueMap::new());
+        assert_eq!(format!("{:?}", clone), format!("{:?}", f));
+        for block in clone.layout.block_iter() {
+            let succs = clone.data.block_ref(block).succs.clone();
+            for succ in succs {
+                assert!(clone.data.block_ref(succ).preds.contains(&block));
+            }
+        }
+    }
+
+    #[test]
+    fn specialize() {
+        let module = parse_assembly(SOURCE).unwrap();
+        let f = &module.functions()[module.find_function_by_name("f").unwrap()];
+        let vmap = ValueMap::new().with_arg(1, Value::Constant(ConstantInt::Int32(8).into()));
+        let clone = clone_function(f, &vmap);
+        assert_eq!(clone.params().len(), 1);
+        insta::assert_debug_snapshot!(clone);
+    }
+}
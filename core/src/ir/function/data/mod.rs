@@ -1,3 +1,4 @@
+use crate::collections::{FxHashMap, FxHashSet};
 use crate::ir::{
     function::{
         basic_block::{BasicBlock, BasicBlockId},
@@ -5,8 +6,8 @@ use crate::ir::{
     },
     value::{Value, ValueId},
 };
+use crate::prelude::*;
 use id_arena::Arena;
-use rustc_hash::{FxHashMap, FxHashSet};
 
 pub struct Data {
     pub values: Arena<Value>,
@@ -125,10 +126,7 @@ impl Data {
         }
 
         if let Value::Instruction(to) = self.values[to] {
-            self.users_map
-                .entry(to)
-                .or_insert_with(FxHashSet::default)
-                .insert(inst_id);
+            self.users_map.entry(to).or_default().insert(inst_id);
         }
     }
 
@@ -149,10 +147,7 @@ impl Data {
             })
             .collect::<Vec<InstructionId>>();
         for arg in args {
-            self.users_map
-                .entry(arg)
-                .or_insert_with(FxHashSet::default)
-                .insert(id);
+            self.users_map.entry(arg).or_default().insert(id);
         }
     }
 
@@ -0,0 +1,223 @@
+//! Exporting control flow graphs in the DOT format of GraphViz.
+//!
+//! [`Function::cfg_dot`] renders a node per block, labeled with the block
+//! and its instructions as they print, and an edge per successor, labeled
+//! `T` or `F` for conditional branches. Render the output with
+//! `dot -Tsvg cfg.dot -o cfg.svg`.
+//!
+//! The helpers here are shared with the exporters of analyses, e.g.
+//! [`DominatorTree::to_dot`](crate::pass::analysis::dom_tree::DominatorTree::to_dot),
+//! so that their nodes are named and labeled alike.
+
+use super::{
+    basic_block::BasicBlockId,
+    instruction::{CondBr, Operand},
+    Function,
+};
+use crate::collections::FxHashMap;
+use crate::ir::module::Module;
+use crate::prelude::*;
+use core::fmt::Write;
+
+impl Function {
+    /// Returns the control flow graph of the function in the DOT format.
+    pub fn cfg_dot(&self) -> String {
+        let mut dot = begin(&format!("CFG for @{}", self.name));
+        self.write_cfg(&mut dot, "", "    ");
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Writes the nodes and edges of the control flow graph, naming nodes
+    /// with `prefix` so that several functions can share a graph.
+    fn write_cfg(&self, dot: &mut String, prefix: &str, indent: &str) {
+        for (block, label, insts) in printed_blocks(self) {
+            let mut text = format!("{}:\\l", escape(&label));
+            for inst in insts {
+                write!(text, "  {}\\l", escape(&inst)).unwrap();
+            }
+            writeln!(
+                dot,
+                "{}{} [label=\"{}\"];",
+                indent,
+                node(prefix, block),
+                text
+            )
+            .unwrap();
+        }
+
+        for block in self.layout.block_iter() {
+            let operand = match self.layout.block_node(block).last_inst() {
+                Some(last) => &self.data.inst_ref(*last).operand,
+                None => continue,
+            };
+            for (i, &succ) in successors(self, block).iter().enumerate() {
+                let label = match operand {
+                    Operand::CondBr(CondBr { .. }) if i == 0 => " [label=\"T\"]",
+                    Operand::CondBr(CondBr { .. }) => " [label=\"F\"]",
+                    _ => "",
+                };
+                writeln!(
+                    dot,
+                    "{}{} -> {}{};",
+                    indent,
+                    node(prefix, block),
+                    node(prefix, succ),
+                    label
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+impl Module {
+    /// Returns the control flow graphs of the functions defined in the
+    /// module in the DOT format, as a cluster per function.
+    pub fn cfg_dot(&self) -> String {
+        let mut dot = begin(&format!("CFG for {}", self.name));
+        for (i, (_, func)) in self
+            .functions
+            .iter()
+            .filter(|(_, func)| !func.is_prototype())
+            .enumerate()
+        {
+            writeln!(dot, "    subgraph cluster_{} {{", i).unwrap();
+            writeln!(dot, "        label=\"@{}\";", escape(&func.name)).unwrap();
+            func.write_cfg(&mut dot, &format!("f{}_", i), "        ");
+            writeln!(dot, "    }}").unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Returns the blocks of `func` in layout order, with their labels and
+/// instructions as printed.
+pub(crate) fn printed_blocks(func: &Function) -> Vec<(BasicBlockId, String, Vec<String>)> {
+    let printed = format!("{:?}", func);
+    let mut blocks: Vec<(String, Vec<String>)> = vec![];
+    for line in printed.lines().skip(1) {
+        if let Some(label) = line.strip_suffix(':').filter(|_| !line.starts_with(' ')) {
+            blocks.push((label.to_string(), vec![]));
+        } else if let Some((_, insts)) = blocks.last_mut() {
+            if line.starts_with(' ') {
+                insts.push(line.trim().to_string());
+            }
+        }
+    }
+    func.layout
+        .block_iter()
+        .zip(blocks)
+        .map(|(block, (label, insts))| (block, label, insts))
+        .collect()
+}
+
+/// Returns the labels of the blocks of `func`, as printed.
+pub(crate) fn block_labels(func: &Function) -> FxHashMap<BasicBlockId, String> {
+    printed_blocks(func)
+        .into_iter()
+        .map(|(block, label, _)| (block, label))
+        .collect()
+}
+
+/// Returns the successors of `block`, in the order of the operands of its
+/// terminator.
+pub(crate) fn successors(func: &Function, block: BasicBlockId) -> &[BasicBlockId] {
+    match func.layout.block_node(block).last_inst() {
+        Some(last) => func.data.inst_ref(*last).operand.successors(),
+        None => &[],
+    }
+}
+
+/// Starts a graph named `title`, to be closed with `}`.
+pub(crate) fn begin(title: &str) -> String {
+    format!(
+        "digraph \"{}\" {{\n    node [shape=box, fontname=monospace];\n",
+        escape(title)
+    )
+}
+
+/// Returns the ID of the node of `block`.
+pub(crate) fn node(prefix: &str, block: BasicBlockId) -> String {
+    format!("{}b{}", prefix, block.index())
+}
+
+/// Escapes `s` to be quoted in a DOT file.
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ir::module::parse_assembly, pass::analysis::dom_tree::DominatorTree};
+
+    #[test]
+    fn cfg_dot() {
+        let module = parse_assembly(
+            r#"
+define i32 @f(i32 %x) {
+entry:
+  %c = icmp slt i32 %x, 0
+  br i1 %c, label %neg, label %exit
+neg:
+  %y = sub i32 0, %x
+  br label %exit
+exit:
+  %r = phi i32 [ %x, %entry ], [ %y, %neg ]
+  ret i32 %r
+}"#,
+        )
+        .unwrap();
+        let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+        assert_eq!(
+            func.cfg_dot(),
+            r#"digraph "CFG for @f" {
+    node [shape=box, fontname=monospace];
+    b1 [label="entry:\l  %c = icmp slt i32 %x, 0\l  br i1 %c, label %neg, label %exit\l"];
+    b2 [label="neg:\l  %y = sub i32 0, %x\l  br label %exit\l"];
+    b3 [label="exit:\l  %r = phi i32 [%x, %entry], [%y, %neg]\l  ret i32 %r\l"];
+    b1 -> b2 [label="T"];
+    b1 -> b3 [label="F"];
+    b2 -> b3;
+}
+"#
+        );
+        assert!(module
+            .cfg_dot()
+            .contains("    subgraph cluster_0 {\n        label=\"@f\";\n        f0_b1 [label="));
+    }
+
+    #[test]
+    fn dom_tree_dot() {
+        let module = parse_assembly(
+            r#"
+define void @f(i1 %c) {
+entry:
+  br i1 %c, label %then, label %else
+then:
+  br label %exit
+else:
+  br label %exit
+exit:
+  ret void
+}"#,
+        )
+        .unwrap();
+        let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+        assert_eq!(
+            DominatorTree::new(func).to_dot(func),
+            r#"digraph "Dominator tree for @f" {
+    node [shape=box, fontname=monospace];
+    b1 [label="entry"];
+    b2 [label="then"];
+    b3 [label="else"];
+    b4 [label="exit"];
+    b1 -> b2;
+    b1 -> b3;
+    b1 -> b4;
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use super::{print::FunctionAsmPrinter, Function};
+use crate::prelude::*;
+use core::{fmt, hash::Hasher};
+
+impl Function {
+    /// Returns a hash of the whole function (signature, attributes and body).
+    ///
+    /// Ids and names of arguments, blocks and instructions are ignored, so
+    /// renaming `%x` to `%y` does not change the hash. The hash is stable
+    /// across runs, platforms and Rust releases, so it can be used as a key of
+    /// on-disk caches.
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        fmt::write(&mut hasher, format_args!("{:?}", Structure(self))).unwrap();
+        hasher.finish()
+    }
+}
+
+/// Returns true if `a` and `b` are the same function except for the ids and
+/// names of their arguments, blocks and instructions.
+pub fn structurally_equal(a: &Function, b: &Function) -> bool {
+    format!("{:?}", Structure(a)) == format!("{:?}", Structure(b))
+}
+
+/// Prints a function with all its local values numbered in order.
+pub(crate) struct Structure<'a>(pub &'a Function);
+
+impl fmt::Debug for Structure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        FunctionAsmPrinter::new(f)
+            .with_anonymous_locals()
+            .print(self.0)
+    }
+}
+
+/// 64-bit FNV-1a hasher. Unlike `alloc::collections::hash_map::DefaultHasher`,
+/// its output is guaranteed to never change.
+#[derive(Debug, Clone, Copy)]
+pub struct StableHasher(u64);
+
+impl StableHasher {
+    pub const fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    // The defaults write integers in native byte order.
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+}
+
+impl fmt::Write for StableHasher {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        Hasher::write(self, s.as_bytes());
+        Ok(())
+    }
+}
+
+#[test]
+fn structural_hash() {
+    use crate::ir::module::parse_assembly;
+
+    let hash_of = |source: &str| {
+        let module = parse_assembly(source).unwrap();
+        let f = module.find_function_by_name("f").unwrap();
+        module.functions()[f].structural_hash()
+    };
+    let a = hash_of("define i32 @f(i32 %0) {\n  %2 = add i32 %0, 1\n  ret i32 %2\n}");
+    let b = hash_of("define i32 @f(i32 %x) {\nentry:\n  %y = add i32 %x, 1\n  ret i32 %y\n}");
+    let c = hash_of("define i32 @f(i32 %0) {\n  %2 = add i32 %0, 2\n  ret i32 %2\n}");
+    let d = hash_of("define i32 @g(i32 %0) {\n  %2 = add i32 %0, 1\n  ret i32 %2\n}\ndefine i32 @f(i32 %0) {\n  %2 = add i32 %0, 1\n  ret i32 %2\n}");
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+    assert_eq!(a, d);
+
+    let mut hasher = StableHasher::new();
+    hasher.write(b"a");
+    assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+}
+
+#[test]
+fn structurally_equal_ignores_local_names() {
+    use crate::ir::module::parse_assembly;
+
+    let module = parse_assembly(
+        r#"
+define i32 @f(i32 %a) {
+entry:
+  %c = icmp eq i32 %a, 0
+  br i1 %c, label %then, label %else
+then:
+  ret i32 1
+else:
+  ret i32 %a
+}
+
+define i32 @g(i32 %0) {
+  %2 = icmp eq i32 %0, 0
+  br i1 %2, label %3, label %4
+3:
+  ret i32 1
+4:
+  ret i32 %0
+}
+
+define i32 @h(i32 %0) {
+  %2 = icmp eq i32 %0, 0
+  br i1 %2, label %4, label %3
+3:
+  ret i32 1
+4:
+  ret i32 %0
+}"#,
+    )
+    .unwrap();
+    let func = |name| &module.functions()[module.find_function_by_name(name).unwrap()];
+    let (f, g, h) = (func("f"), func("g"), func("h"));
+    assert!(!structurally_equal(f, g)); // names of functions are significant
+
+    let mut renamed = parse_assembly(&format!("{:?}", g)).unwrap();
+    let id = renamed.find_function_by_name("g").unwrap();
+    renamed.functions_mut()[id].name = "f".to_string();
+    assert!(structurally_equal(f, &renamed.functions()[id]));
+    assert_eq!(
+        f.structural_hash(),
+        renamed.functions()[id].structural_hash()
+    );
+    assert!(!structurally_equal(g, h));
+}
@@ -1,30 +1,35 @@
 pub mod builder;
 pub mod parser;
 
+use crate::collections::FxHashMap;
+use crate::prelude::*;
 pub use parser::parse;
-use rustc_hash::FxHashMap;
 
 use crate::ir::{
     function::{basic_block::BasicBlockId, data::Data, param_attrs::ParameterAttribute},
     module::{attributes::Attribute, metadata::Metadata, name::Name},
-    types::Type,
-    value::{ConstantData, ConstantInt, Value, ValueId},
+    types::{Type, Types},
+    value::{fold::fold_constant, ConstantData, Value, ValueId},
 };
+use core::{fmt, slice};
 use id_arena::Id;
-use std::{fmt, slice};
 
 pub type InstructionId = Id<Instruction>;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Instruction {
     pub opcode: Opcode,
     pub operand: Operand,
     pub dest: Option<Name>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::option_id"))]
     pub id: Option<InstructionId>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::id"))]
     pub parent: BasicBlockId,
     pub metadata: FxHashMap<String, Metadata>,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Opcode {
     Alloca,
     Phi,
@@ -32,19 +37,37 @@ pub enum Opcode {
     Store,
     InsertValue,
     ExtractValue,
+    ExtractElement,
+    InsertElement,
+    ShuffleVector,
     Add,
     Sub,
     Mul,
     SDiv,
     SRem,
     And,
+    Or,
+    Shl,
     LShr,
+    FAdd,
+    FSub,
+    FMul,
+    FDiv,
+    FRem,
+    FNeg,
     ICmp,
+    FCmp,
     Sext,
     Zext,
     Bitcast,
     Trunc,
     IntToPtr,
+    FPTrunc,
+    FPExt,
+    FPToUI,
+    FPToSI,
+    UIToFP,
+    SIToFP,
     GetElementPtr,
     Call,
     Invoke,
@@ -57,7 +80,8 @@ pub enum Opcode {
     Invalid,
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ICmpCond {
     Eq,
     Ne,
@@ -71,7 +95,31 @@ pub enum ICmpCond {
     Sle,
 }
 
+/// The conditions of `fcmp`. An ordered condition is false if either
+/// operand is a NaN, and an unordered one is true.
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FCmpCond {
+    False,
+    Oeq,
+    Ogt,
+    Oge,
+    Olt,
+    Ole,
+    One,
+    Ord,
+    Ueq,
+    Ugt,
+    Uge,
+    Ult,
+    Ule,
+    Une,
+    Uno,
+    True,
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Alloca {
     pub tys: [Type; 2],
     pub num_elements: ConstantData,
@@ -79,124 +127,218 @@ pub struct Alloca {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Phi {
     pub ty: Type,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub args: Vec<ValueId>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub blocks: Vec<BasicBlockId>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Load {
     pub tys: [Type; 2],
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::id"))]
     pub addr: ValueId,
     pub align: u32,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IntBinary {
     pub ty: Type,
     pub nsw: bool,
     pub nuw: bool,
     pub exact: bool,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub args: [ValueId; 2],
 }
 
+/// The operand of the floating-point binary operations. Fast-math flags are
+/// not kept.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FloatBinary {
+    pub ty: Type,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
+    pub args: [ValueId; 2],
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FloatUnary {
+    pub ty: Type,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::id"))]
+    pub arg: ValueId,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Store {
     pub tys: [Type; 2],
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub args: [ValueId; 2],
     pub align: u32,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InsertValue {
     pub tys: [Type; 2],
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub args: Vec<ValueId>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExtractValue {
     pub ty: Type,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub args: Vec<ValueId>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtractElement {
+    pub tys: [Type; 2], // vector, index
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
+    pub args: [ValueId; 2],
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InsertElement {
+    pub tys: [Type; 3], // vector, element, index
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
+    pub args: [ValueId; 3],
+}
+
+/// Picks the elements of the result from the concatenation of `args[0]` and
+/// `args[1]`, by the indices in the constant mask `args[2]`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShuffleVector {
+    pub tys: [Type; 2], // vectors, mask
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
+    pub args: [ValueId; 3],
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ICmp {
     pub ty: Type,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub args: [ValueId; 2],
     pub cond: ICmpCond,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FCmp {
+    pub ty: Type,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
+    pub args: [ValueId; 2],
+    pub cond: FCmpCond,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Cast {
     pub tys: [Type; 2], // from, to
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::id"))]
     pub arg: ValueId,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GetElementPtr {
     pub inbounds: bool,
     pub tys: Vec<Type>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub args: Vec<ValueId>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Call {
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub args: Vec<ValueId>, // args[0] = callee, args[1..] = arguments
-    pub tys: Vec<Type>,     // tys[0] = callee's result type, args[1..] = argument types
+    pub tys: Vec<Type>, // tys[0] = callee's result type, args[1..] = argument types
     pub param_attrs: Vec<Vec<ParameterAttribute>>, // param_attrs[0] = attrs of args[1]
     pub ret_attrs: Vec<ParameterAttribute>,
     pub func_attrs: Vec<Attribute>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Invoke {
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub args: Vec<ValueId>, // args[0] = callee, args[1..] = arguments
-    pub tys: Vec<Type>,     // tys[0] = callee's result type, args[1..] = argument types
+    pub tys: Vec<Type>, // tys[0] = callee's result type, args[1..] = argument types
     pub param_attrs: Vec<Vec<ParameterAttribute>>, // param_attrs[0] = attrs of args[1]
     pub ret_attrs: Vec<ParameterAttribute>,
     pub func_attrs: Vec<Attribute>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub blocks: Vec<BasicBlockId>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LandingPad {
     pub ty: Type,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Resume {
     pub ty: Type,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::id"))]
     pub arg: ValueId,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Br {
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::id"))]
     pub block: BasicBlockId,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CondBr {
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::id"))]
     pub arg: ValueId,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::ids"))]
     pub blocks: [BasicBlockId; 2], // iftrue, iffalse
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ret {
     pub ty: Type,
+    #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::option_id"))]
     pub val: Option<ValueId>,
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Operand {
     Alloca(Alloca),
     Phi(Phi),
     Load(Load),
     IntBinary(IntBinary),
+    FloatBinary(FloatBinary),
+    FloatUnary(FloatUnary),
     Store(Store),
     InsertValue(InsertValue),
     ExtractValue(ExtractValue),
+    ExtractElement(ExtractElement),
+    InsertElement(InsertElement),
+    ShuffleVector(ShuffleVector),
     ICmp(ICmp),
+    FCmp(FCmp),
     Cast(Cast),
     GetElementPtr(GetElementPtr),
     Call(Call),
@@ -234,46 +376,19 @@ impl Instruction {
         self
     }
 
-    pub fn fold_consts(&self, data: &Data) -> Option<ConstantData> {
-        match self.operand {
-            Operand::IntBinary(ref i) => {
-                let args: Vec<&Value> = i.args.iter().map(|&id| data.value_ref(id)).collect();
-                match args.as_slice() {
-                    [Value::Constant(ConstantData::Int(ConstantInt::Int32(x))), Value::Constant(ConstantData::Int(ConstantInt::Int32(y)))] => {
-                        match self.opcode {
-                            Opcode::Add => Some(ConstantData::Int(ConstantInt::Int32(x + y))),
-                            Opcode::Sub => Some(ConstantData::Int(ConstantInt::Int32(x - y))),
-                            Opcode::Mul => Some(ConstantData::Int(ConstantInt::Int32(x * y))),
-                            Opcode::SRem => Some(ConstantData::Int(ConstantInt::Int32(x % y))),
-                            _ => None,
-                        }
-                    }
-                    _ => None,
-                }
-            }
-            Operand::ICmp(ref i) => {
-                let args: Vec<&Value> = i.args.iter().map(|&id| data.value_ref(id)).collect();
-                match args.as_slice() {
-                    [Value::Constant(ConstantData::Int(ConstantInt::Int32(x))), Value::Constant(ConstantData::Int(ConstantInt::Int32(y)))] => {
-                        match i.cond {
-                            ICmpCond::Eq => Some(ConstantData::Int(ConstantInt::Int1(x == y))),
-                            ICmpCond::Ne => Some(ConstantData::Int(ConstantInt::Int1(x != y))),
-                            ICmpCond::Ugt => Some(ConstantData::Int(ConstantInt::Int1(x > y))),
-                            ICmpCond::Uge => Some(ConstantData::Int(ConstantInt::Int1(x >= y))),
-                            ICmpCond::Ult => Some(ConstantData::Int(ConstantInt::Int1(x < y))),
-                            ICmpCond::Ule => Some(ConstantData::Int(ConstantInt::Int1(x <= y))),
-                            ICmpCond::Sgt => Some(ConstantData::Int(ConstantInt::Int1(x > y))),
-                            ICmpCond::Sge => Some(ConstantData::Int(ConstantInt::Int1(x >= y))),
-                            ICmpCond::Slt => Some(ConstantData::Int(ConstantInt::Int1(x < y))),
-                            ICmpCond::Sle => Some(ConstantData::Int(ConstantInt::Int1(x <= y))),
-                        }
-                    }
-                    _ => None,
-                }
-            }
-            Operand::Cast(_) => todo!(),
-            _ => None,
-        }
+    /// Returns the constant the instruction computes if its arguments are all
+    /// constants and it can be folded (see [`fold_constant`]).
+    pub fn fold_consts(&self, data: &Data, types: &Types) -> Option<ConstantData> {
+        let args = self
+            .operand
+            .args()
+            .iter()
+            .map(|&arg| match data.value_ref(arg) {
+                Value::Constant(c) => Some(c.clone()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        fold_constant(self.opcode, &self.operand, &args, types)
     }
 }
 
@@ -289,10 +404,72 @@ impl Opcode {
         }
     }
 
+    /// Returns true if the instruction ends a basic block.
     pub fn is_terminator(&self) -> bool {
         matches!(
             self,
-            Self::Ret | Self::Br | Self::CondBr | Self::Invoke | Self::Resume
+            Self::Ret | Self::Br | Self::CondBr | Self::Invoke | Self::Resume | Self::Unreachable
+        )
+    }
+
+    /// Returns true for `br`, conditional or not.
+    pub fn is_branch(&self) -> bool {
+        matches!(self, Self::Br | Self::CondBr)
+    }
+
+    /// Returns true for the integer binary operations, i.e. those with an `IntBinary` operand.
+    pub fn is_binary(&self) -> bool {
+        matches!(
+            self,
+            Self::Add
+                | Self::Sub
+                | Self::Mul
+                | Self::SDiv
+                | Self::SRem
+                | Self::And
+                | Self::Or
+                | Self::Shl
+                | Self::LShr
+        )
+    }
+
+    /// Returns true for the floating-point binary operations, i.e. those with
+    /// a `FloatBinary` operand.
+    pub fn is_float_binary(&self) -> bool {
+        matches!(
+            self,
+            Self::FAdd | Self::FSub | Self::FMul | Self::FDiv | Self::FRem
+        )
+    }
+
+    /// Returns true for the conversions, i.e. those with a `Cast` operand.
+    pub fn is_cast(&self) -> bool {
+        matches!(
+            self,
+            Self::Sext
+                | Self::Zext
+                | Self::Bitcast
+                | Self::Trunc
+                | Self::IntToPtr
+                | Self::FPTrunc
+                | Self::FPExt
+                | Self::FPToUI
+                | Self::FPToSI
+                | Self::UIToFP
+                | Self::SIToFP
+        )
+    }
+
+    /// Returns true for the casts from or to `float` or `double`.
+    pub fn is_fp_cast(&self) -> bool {
+        matches!(
+            self,
+            Self::FPTrunc
+                | Self::FPExt
+                | Self::FPToUI
+                | Self::FPToSI
+                | Self::UIToFP
+                | Self::SIToFP
         )
     }
 
@@ -320,14 +497,35 @@ impl Opcode {
         self == &Self::Invoke
     }
 
+    /// Returns true if the two operands of the binary operation can be swapped.
+    pub fn is_commutative(&self) -> bool {
+        matches!(self, Self::Add | Self::Mul | Self::And | Self::Or)
+    }
+
+    pub fn may_read_memory(&self) -> bool {
+        matches!(self, Self::Load | Self::Call | Self::Invoke)
+    }
+
+    pub fn may_write_memory(&self) -> bool {
+        matches!(self, Self::Store | Self::Call | Self::Invoke)
+    }
+
+    /// Returns false if the instruction never produces a value. Calls and
+    /// invokes don't either when the callee returns void.
+    pub fn has_result(&self) -> bool {
+        !(self.is_store() || self.is_terminator() && !self.is_invoke())
+    }
+
+    /// Returns true if the instruction must stay where it is even if its
+    /// result is unused. A `landingpad` receives the exception on entry to
+    /// its block, so it is never moved or removed either.
     pub fn has_side_effects(&self) -> bool {
-        self.is_load()
-            || self.is_store()
+        self.may_read_memory()
+            || self.may_write_memory()
             || self.is_alloca()
             || self.is_phi()
-            || self.is_call()
-            || self.is_invoke()
             || self.is_terminator()
+            || *self == Self::LandingPad
     }
 }
 
@@ -361,8 +559,14 @@ impl Operand {
             Self::Store(Store { args, .. }) => args,
             Self::InsertValue(InsertValue { args, .. }) => args,
             Self::ExtractValue(ExtractValue { args, .. }) => args,
+            Self::ExtractElement(ExtractElement { args, .. }) => args,
+            Self::InsertElement(InsertElement { args, .. }) => args,
+            Self::ShuffleVector(ShuffleVector { args, .. }) => args,
             Self::IntBinary(IntBinary { args, .. }) => args,
+            Self::FloatBinary(FloatBinary { args, .. }) => args,
+            Self::FloatUnary(FloatUnary { arg, .. }) => slice::from_ref(arg),
             Self::ICmp(ICmp { args, .. }) => args,
+            Self::FCmp(FCmp { args, .. }) => args,
             Self::Cast(Cast { arg, .. }) => slice::from_ref(arg),
             Self::GetElementPtr(GetElementPtr { args, .. }) => args.as_slice(),
             Self::Call(Call { args, .. }) | Self::Invoke(Invoke { args, .. }) => args.as_slice(),
@@ -385,8 +589,14 @@ impl Operand {
             Self::Store(Store { args, .. }) => args,
             Self::InsertValue(InsertValue { args, .. }) => args,
             Self::ExtractValue(ExtractValue { args, .. }) => args,
+            Self::ExtractElement(ExtractElement { args, .. }) => args,
+            Self::InsertElement(InsertElement { args, .. }) => args,
+            Self::ShuffleVector(ShuffleVector { args, .. }) => args,
             Self::IntBinary(IntBinary { args, .. }) => args,
+            Self::FloatBinary(FloatBinary { args, .. }) => args,
+            Self::FloatUnary(FloatUnary { arg, .. }) => slice::from_mut(arg),
             Self::ICmp(ICmp { args, .. }) => args,
+            Self::FCmp(FCmp { args, .. }) => args,
             Self::Cast(Cast { arg, .. }) => slice::from_mut(arg),
             Self::GetElementPtr(GetElementPtr { args, .. }) => args.as_mut_slice(),
             Self::Call(Call { args, .. }) | Self::Invoke(Invoke { args, .. }) => args.as_mut(),
@@ -405,11 +615,17 @@ impl Operand {
             Self::Phi(Phi { ty, .. }) => slice::from_ref(ty),
             Self::Ret(Ret { ty, .. }) => slice::from_ref(ty),
             Self::Load(Load { tys, .. }) => tys,
-            Self::Store(Store { .. }) => &[],
+            Self::Store(Store { tys, .. }) => tys,
             Self::InsertValue(InsertValue { tys, .. }) => tys,
             Self::ExtractValue(ExtractValue { ty, .. }) => slice::from_ref(ty),
+            Self::ExtractElement(ExtractElement { tys, .. }) => tys,
+            Self::InsertElement(InsertElement { tys, .. }) => tys,
+            Self::ShuffleVector(ShuffleVector { tys, .. }) => tys,
             Self::IntBinary(IntBinary { ty, .. }) => slice::from_ref(ty),
+            Self::FloatBinary(FloatBinary { ty, .. }) => slice::from_ref(ty),
+            Self::FloatUnary(FloatUnary { ty, .. }) => slice::from_ref(ty),
             Self::ICmp(ICmp { ty, .. }) => slice::from_ref(ty),
+            Self::FCmp(FCmp { ty, .. }) => slice::from_ref(ty),
             Self::Cast(Cast { tys, .. }) => tys,
             Self::GetElementPtr(GetElementPtr { tys, .. }) => tys.as_slice(),
             Self::Call(Call { tys, .. }) | Self::Invoke(Invoke { tys, .. }) => tys.as_slice(),
@@ -422,6 +638,35 @@ impl Operand {
         }
     }
 
+    pub fn types_mut(&mut self) -> &mut [Type] {
+        match self {
+            Self::Alloca(Alloca { tys, .. }) => tys,
+            Self::Phi(Phi { ty, .. }) => slice::from_mut(ty),
+            Self::Ret(Ret { ty, .. }) => slice::from_mut(ty),
+            Self::Load(Load { tys, .. }) => tys,
+            Self::Store(Store { tys, .. }) => tys,
+            Self::InsertValue(InsertValue { tys, .. }) => tys,
+            Self::ExtractValue(ExtractValue { ty, .. }) => slice::from_mut(ty),
+            Self::ExtractElement(ExtractElement { tys, .. }) => tys,
+            Self::InsertElement(InsertElement { tys, .. }) => tys,
+            Self::ShuffleVector(ShuffleVector { tys, .. }) => tys,
+            Self::IntBinary(IntBinary { ty, .. }) => slice::from_mut(ty),
+            Self::FloatBinary(FloatBinary { ty, .. }) => slice::from_mut(ty),
+            Self::FloatUnary(FloatUnary { ty, .. }) => slice::from_mut(ty),
+            Self::ICmp(ICmp { ty, .. }) => slice::from_mut(ty),
+            Self::FCmp(FCmp { ty, .. }) => slice::from_mut(ty),
+            Self::Cast(Cast { tys, .. }) => tys,
+            Self::GetElementPtr(GetElementPtr { tys, .. }) => tys.as_mut_slice(),
+            Self::Call(Call { tys, .. }) | Self::Invoke(Invoke { tys, .. }) => tys.as_mut_slice(),
+            Self::LandingPad(LandingPad { ty }) => slice::from_mut(ty),
+            Self::Resume(Resume { ty, .. }) => slice::from_mut(ty),
+            Self::Br(Br { .. }) => &mut [],
+            Self::CondBr(CondBr { .. }) => &mut [],
+            Self::Unreachable => &mut [],
+            Self::Invalid => &mut [],
+        }
+    }
+
     pub fn blocks(&self) -> &[BasicBlockId] {
         match self {
             Self::Phi(Phi { blocks, .. }) => blocks,
@@ -432,6 +677,26 @@ impl Operand {
         }
     }
 
+    /// Returns the blocks a terminator may branch to, in operand order.
+    /// A block appears as many times as it is named. Empty for non-terminators.
+    pub fn successors(&self) -> &[BasicBlockId] {
+        match self {
+            Self::Br(Br { block }) => slice::from_ref(block),
+            Self::CondBr(CondBr { blocks, .. }) => blocks,
+            Self::Invoke(Invoke { blocks, .. }) => blocks,
+            _ => &[],
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> &mut [BasicBlockId] {
+        match self {
+            Self::Br(Br { block }) => slice::from_mut(block),
+            Self::CondBr(CondBr { blocks, .. }) => blocks,
+            Self::Invoke(Invoke { blocks, .. }) => blocks,
+            _ => &mut [],
+        }
+    }
+
     pub fn call_result_ty(&self) -> Option<Type> {
         match self {
             Self::Call(Call { tys, .. }) | Self::Invoke(Invoke { tys, .. }) => Some(tys[0]),
@@ -445,6 +710,17 @@ impl Operand {
     as_inst!(as_phi, Phi);
     as_inst!(mut as_phi_mut, Phi);
     as_inst!(as_condbr, CondBr);
+    as_inst!(as_call, Call);
+    as_inst!(as_int_binary, IntBinary);
+    as_inst!(mut as_int_binary_mut, IntBinary);
+    as_inst!(as_float_binary, FloatBinary);
+    as_inst!(as_icmp, ICmp);
+    as_inst!(mut as_icmp_mut, ICmp);
+    as_inst!(as_fcmp, FCmp);
+    as_inst!(as_cast, Cast);
+    as_inst!(mut as_cast_mut, Cast);
+    as_inst!(as_gep, GetElementPtr);
+    as_inst!(mut as_gep_mut, GetElementPtr);
 }
 
 impl Alloca {
@@ -491,19 +767,37 @@ impl fmt::Debug for Opcode {
                 Opcode::Store => "store",
                 Opcode::InsertValue => "insertvalue",
                 Opcode::ExtractValue => "extractvalue",
+                Opcode::ExtractElement => "extractelement",
+                Opcode::InsertElement => "insertelement",
+                Opcode::ShuffleVector => "shufflevector",
                 Opcode::Add => "add",
                 Opcode::Sub => "sub",
                 Opcode::Mul => "mul",
                 Opcode::SDiv => "sdiv",
                 Opcode::SRem => "srem",
                 Opcode::And => "and",
+                Opcode::Or => "or",
+                Opcode::Shl => "shl",
                 Opcode::LShr => "lshr",
+                Opcode::FAdd => "fadd",
+                Opcode::FSub => "fsub",
+                Opcode::FMul => "fmul",
+                Opcode::FDiv => "fdiv",
+                Opcode::FRem => "frem",
+                Opcode::FNeg => "fneg",
                 Opcode::ICmp => "icmp",
+                Opcode::FCmp => "fcmp",
                 Opcode::Sext => "sext",
                 Opcode::Zext => "zext",
                 Opcode::Bitcast => "bitcast",
                 Opcode::Trunc => "trunc",
                 Opcode::IntToPtr => "inttoptr",
+                Opcode::FPTrunc => "fptrunc",
+                Opcode::FPExt => "fpext",
+                Opcode::FPToUI => "fptoui",
+                Opcode::FPToSI => "fptosi",
+                Opcode::UIToFP => "uitofp",
+                Opcode::SIToFP => "sitofp",
                 Opcode::GetElementPtr => "getelementptr",
                 Opcode::Call => "call",
                 Opcode::Invoke => "invoke",
@@ -518,6 +812,24 @@ impl fmt::Debug for Opcode {
     }
 }
 
+impl ICmpCond {
+    /// Returns the condition that holds for `(y, x)` when `self` holds for `(x, y)`.
+    pub fn swapped(self) -> Self {
+        match self {
+            Self::Eq => Self::Eq,
+            Self::Ne => Self::Ne,
+            Self::Ugt => Self::Ult,
+            Self::Uge => Self::Ule,
+            Self::Ult => Self::Ugt,
+            Self::Ule => Self::Uge,
+            Self::Sgt => Self::Slt,
+            Self::Sge => Self::Sle,
+            Self::Slt => Self::Sgt,
+            Self::Sle => Self::Sge,
+        }
+    }
+}
+
 impl fmt::Debug for ICmpCond {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -538,3 +850,47 @@ impl fmt::Debug for ICmpCond {
         )
     }
 }
+
+impl FCmpCond {
+    /// Returns the condition that holds for `(y, x)` when `self` holds for `(x, y)`.
+    pub fn swapped(self) -> Self {
+        match self {
+            Self::Ogt => Self::Olt,
+            Self::Oge => Self::Ole,
+            Self::Olt => Self::Ogt,
+            Self::Ole => Self::Oge,
+            Self::Ugt => Self::Ult,
+            Self::Uge => Self::Ule,
+            Self::Ult => Self::Ugt,
+            Self::Ule => Self::Uge,
+            cond => cond,
+        }
+    }
+}
+
+impl fmt::Debug for FCmpCond {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::False => "false",
+                Self::Oeq => "oeq",
+                Self::Ogt => "ogt",
+                Self::Oge => "oge",
+                Self::Olt => "olt",
+                Self::Ole => "ole",
+                Self::One => "one",
+                Self::Ord => "ord",
+                Self::Ueq => "ueq",
+                Self::Ugt => "ugt",
+                Self::Uge => "uge",
+                Self::Ult => "ult",
+                Self::Ule => "ule",
+                Self::Une => "une",
+                Self::Uno => "uno",
+                Self::True => "true",
+            }
+        )
+    }
+}
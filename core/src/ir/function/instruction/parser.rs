@@ -1,10 +1,12 @@
 use super::{
-    Alloca, Br, Call, Cast, CondBr, GetElementPtr, ICmp, ICmpCond, Instruction, InstructionId,
-    IntBinary, Invoke, LandingPad, Load, Opcode, Operand, Phi, Resume, Ret, Store,
+    Alloca, Br, Call, Cast, CondBr, FCmp, FCmpCond, FloatBinary, FloatUnary, GetElementPtr, ICmp,
+    ICmpCond, Instruction, InstructionId, IntBinary, Invoke, LandingPad, Load, Opcode, Operand,
+    Phi, Resume, Ret, Store,
 };
+use crate::collections::FxHashMap;
 use crate::ir::{
     function::{
-        instruction::{ExtractValue, InsertValue},
+        instruction::{ExtractElement, ExtractValue, InsertElement, InsertValue, ShuffleVector},
         param_attrs::{parser::parse_param_attrs, ParameterAttribute},
         parser::ParserContext,
     },
@@ -16,17 +18,18 @@ use crate::ir::{
     util::string_literal,
 };
 use crate::ir::{module::name, types, util::spaces, value};
+use crate::prelude::*;
 use nom::{
     branch::alt,
     bytes::complete::tag,
     character::complete::{char, digit1},
     combinator::{map, opt},
     error::VerboseError,
+    multi::many0,
     sequence::{preceded, tuple},
     Err::Error,
     IResult,
 };
-use rustc_hash::FxHashMap;
 
 pub fn parse_alloca<'a, 'b>(
     source: &'a str,
@@ -34,6 +37,14 @@ pub fn parse_alloca<'a, 'b>(
 ) -> IResult<&'a str, Instruction, VerboseError<&'a str>> {
     let (source, _) = preceded(spaces, tag("alloca"))(source)?;
     let (source, ty) = types::parse(source, ctx.types)?;
+    let (source, num_elements) = opt(preceded(
+        spaces,
+        preceded(char(','), |source| {
+            let (source, num_ty) = types::parse(source, ctx.types)?;
+            let (source, num) = value::parser::parse_constant_int(source, num_ty)?;
+            Ok((source, (num_ty, num)))
+        }),
+    ))(source)?;
     let (source, align) = opt(preceded(
         spaces,
         preceded(
@@ -41,13 +52,12 @@ pub fn parse_alloca<'a, 'b>(
             preceded(spaces, preceded(tag("align"), preceded(spaces, digit1))),
         ),
     ))(source)?;
-    // TODO: Implement parser for num_elements
-    let num_elements = value::ConstantData::Int(value::ConstantInt::Int32(1));
+    let (num_ty, num_elements) = num_elements.unwrap_or((I32, value::ConstantInt::Int32(1)));
     let inst = Opcode::Alloca
         .with_block(ctx.cur_block)
         .with_operand(Operand::Alloca(Alloca {
-            tys: [ty, I32],
-            num_elements,
+            tys: [ty, num_ty],
+            num_elements: num_elements.into(),
             align: align.map_or(0, |align| align.parse::<u32>().unwrap_or(0)),
         }));
     Ok((source, inst))
@@ -198,6 +208,75 @@ pub fn parse_extractvalue<'a, 'b>(
     }
 }
 
+pub fn parse_extractelement<'a, 'b>(
+    source: &'a str,
+    ctx: &mut ParserContext<'b>,
+) -> IResult<&'a str, Instruction, VerboseError<&'a str>> {
+    let (source, _) = preceded(spaces, tag("extractelement"))(source)?;
+    let (source, vec_ty) = types::parse(source, ctx.types)?;
+    let (source, vec) = value::parse(source, ctx, vec_ty)?;
+    let (source, _) = preceded(spaces, char(','))(source)?;
+    let (source, idx_ty) = types::parse(source, ctx.types)?;
+    let (source, idx) = value::parse(source, ctx, idx_ty)?;
+    Ok((
+        source,
+        Opcode::ExtractElement
+            .with_block(ctx.cur_block)
+            .with_operand(Operand::ExtractElement(ExtractElement {
+                tys: [vec_ty, idx_ty],
+                args: [vec, idx],
+            })),
+    ))
+}
+
+pub fn parse_insertelement<'a, 'b>(
+    source: &'a str,
+    ctx: &mut ParserContext<'b>,
+) -> IResult<&'a str, Instruction, VerboseError<&'a str>> {
+    let (source, _) = preceded(spaces, tag("insertelement"))(source)?;
+    let (source, vec_ty) = types::parse(source, ctx.types)?;
+    let (source, vec) = value::parse(source, ctx, vec_ty)?;
+    let (source, _) = preceded(spaces, char(','))(source)?;
+    let (source, elt_ty) = types::parse(source, ctx.types)?;
+    let (source, elt) = value::parse(source, ctx, elt_ty)?;
+    let (source, _) = preceded(spaces, char(','))(source)?;
+    let (source, idx_ty) = types::parse(source, ctx.types)?;
+    let (source, idx) = value::parse(source, ctx, idx_ty)?;
+    Ok((
+        source,
+        Opcode::InsertElement
+            .with_block(ctx.cur_block)
+            .with_operand(Operand::InsertElement(InsertElement {
+                tys: [vec_ty, elt_ty, idx_ty],
+                args: [vec, elt, idx],
+            })),
+    ))
+}
+
+pub fn parse_shufflevector<'a, 'b>(
+    source: &'a str,
+    ctx: &mut ParserContext<'b>,
+) -> IResult<&'a str, Instruction, VerboseError<&'a str>> {
+    let (source, _) = preceded(spaces, tag("shufflevector"))(source)?;
+    let (source, vec_ty) = types::parse(source, ctx.types)?;
+    let (source, v1) = value::parse(source, ctx, vec_ty)?;
+    let (source, _) = preceded(spaces, char(','))(source)?;
+    let (source, _) = types::parse(source, ctx.types)?;
+    let (source, v2) = value::parse(source, ctx, vec_ty)?;
+    let (source, _) = preceded(spaces, char(','))(source)?;
+    let (source, mask_ty) = types::parse(source, ctx.types)?;
+    let (source, mask) = value::parse(source, ctx, mask_ty)?;
+    Ok((
+        source,
+        Opcode::ShuffleVector
+            .with_block(ctx.cur_block)
+            .with_operand(Operand::ShuffleVector(ShuffleVector {
+                tys: [vec_ty, mask_ty],
+                args: [v1, v2, mask],
+            })),
+    ))
+}
+
 pub fn parse_add_sub_mul<'a, 'b>(
     source: &'a str,
     ctx: &mut ParserContext<'b>,
@@ -211,6 +290,8 @@ pub fn parse_add_sub_mul<'a, 'b>(
             map(tag("sdiv"), |_| Opcode::SDiv),
             map(tag("srem"), |_| Opcode::SRem),
             map(tag("and"), |_| Opcode::And),
+            map(tag("or"), |_| Opcode::Or),
+            map(tag("shl"), |_| Opcode::Shl),
             map(tag("lshr"), |_| Opcode::LShr),
         )),
     )(source)?;
@@ -227,13 +308,73 @@ pub fn parse_add_sub_mul<'a, 'b>(
         .with_operand(Operand::IntBinary(IntBinary {
             ty,
             args: [lhs, rhs],
-            nuw: nuw.map_or(false, |_| true),
-            nsw: nsw.map_or(false, |_| true),
-            exact: exact.map_or(false, |_| true),
+            nuw: nuw.is_some_and(|_| true),
+            nsw: nsw.is_some_and(|_| true),
+            exact: exact.is_some_and(|_| true),
         }));
     Ok((source, inst))
 }
 
+/// Skips fast-math flags, which aren't kept.
+fn fast_math_flags(source: &str) -> IResult<&str, (), VerboseError<&str>> {
+    let (source, _) = many0(preceded(
+        spaces,
+        alt((
+            tag("nnan"),
+            tag("ninf"),
+            tag("nsz"),
+            tag("arcp"),
+            tag("contract"),
+            tag("afn"),
+            tag("reassoc"),
+            tag("fast"),
+        )),
+    ))(source)?;
+    Ok((source, ()))
+}
+
+pub fn parse_float_binary<'a, 'b>(
+    source: &'a str,
+    ctx: &mut ParserContext<'b>,
+) -> IResult<&'a str, Instruction, VerboseError<&'a str>> {
+    let (source, opcode) = preceded(
+        spaces,
+        alt((
+            map(tag("fadd"), |_| Opcode::FAdd),
+            map(tag("fsub"), |_| Opcode::FSub),
+            map(tag("fmul"), |_| Opcode::FMul),
+            map(tag("fdiv"), |_| Opcode::FDiv),
+            map(tag("frem"), |_| Opcode::FRem),
+        )),
+    )(source)?;
+    let (source, _) = fast_math_flags(source)?;
+    let (source, ty) = types::parse(source, ctx.types)?;
+    let (source, lhs) = value::parse(source, ctx, ty)?;
+    let (source, _) = preceded(spaces, char(','))(source)?;
+    let (source, rhs) = value::parse(source, ctx, ty)?;
+    let inst = opcode
+        .with_block(ctx.cur_block)
+        .with_operand(Operand::FloatBinary(FloatBinary {
+            ty,
+            args: [lhs, rhs],
+        }));
+    Ok((source, inst))
+}
+
+pub fn parse_fneg<'a, 'b>(
+    source: &'a str,
+    ctx: &mut ParserContext<'b>,
+) -> IResult<&'a str, Instruction, VerboseError<&'a str>> {
+    let (source, _) = preceded(spaces, tag("fneg"))(source)?;
+    let (source, _) = fast_math_flags(source)?;
+    let (source, ty) = types::parse(source, ctx.types)?;
+    let (source, arg) = value::parse(source, ctx, ty)?;
+    let inst = Opcode::FNeg
+        .with_block(ctx.cur_block)
+        .with_operand(Operand::FloatUnary(FloatUnary { ty, arg }));
+    Ok((source, inst))
+}
+
 pub fn parse_icmp<'a, 'b>(
     source: &'a str,
     ctx: &mut ParserContext<'b>,
@@ -269,6 +410,48 @@ pub fn parse_icmp<'a, 'b>(
     Ok((source, inst))
 }
 
+pub fn parse_fcmp<'a, 'b>(
+    source: &'a str,
+    ctx: &mut ParserContext<'b>,
+) -> IResult<&'a str, Instruction, VerboseError<&'a str>> {
+    pub fn fcmp_cond(source: &str) -> IResult<&str, FCmpCond, VerboseError<&str>> {
+        alt((
+            map(tag("false"), |_| FCmpCond::False),
+            map(tag("oeq"), |_| FCmpCond::Oeq),
+            map(tag("ogt"), |_| FCmpCond::Ogt),
+            map(tag("oge"), |_| FCmpCond::Oge),
+            map(tag("olt"), |_| FCmpCond::Olt),
+            map(tag("ole"), |_| FCmpCond::Ole),
+            map(tag("one"), |_| FCmpCond::One),
+            map(tag("ord"), |_| FCmpCond::Ord),
+            map(tag("ueq"), |_| FCmpCond::Ueq),
+            map(tag("ugt"), |_| FCmpCond::Ugt),
+            map(tag("uge"), |_| FCmpCond::Uge),
+            map(tag("ult"), |_| FCmpCond::Ult),
+            map(tag("ule"), |_| FCmpCond::Ule),
+            map(tag("une"), |_| FCmpCond::Une),
+            map(tag("uno"), |_| FCmpCond::Uno),
+            map(tag("true"), |_| FCmpCond::True),
+        ))(source)
+    }
+
+    let (source, _) = preceded(spaces, tag("fcmp"))(source)?;
+    let (source, _) = fast_math_flags(source)?;
+    let (source, cond) = preceded(spaces, fcmp_cond)(source)?;
+    let (source, ty) = types::parse(source, ctx.types)?;
+    let (source, lhs) = value::parse(source, ctx, ty)?;
+    let (source, _) = preceded(spaces, char(','))(source)?;
+    let (source, rhs) = value::parse(source, ctx, ty)?;
+    let inst = Opcode::FCmp
+        .with_block(ctx.cur_block)
+        .with_operand(Operand::FCmp(FCmp {
+            ty,
+            args: [lhs, rhs],
+            cond,
+        }));
+    Ok((source, inst))
+}
+
 pub fn parse_cast<'a, 'b>(
     source: &'a str,
     ctx: &mut ParserContext<'b>,
@@ -281,6 +464,12 @@ pub fn parse_cast<'a, 'b>(
             map(tag("bitcast"), |_| Opcode::Bitcast),
             map(tag("trunc"), |_| Opcode::Trunc),
             map(tag("inttoptr"), |_| Opcode::IntToPtr),
+            map(tag("fptrunc"), |_| Opcode::FPTrunc),
+            map(tag("fpext"), |_| Opcode::FPExt),
+            map(tag("fptoui"), |_| Opcode::FPToUI),
+            map(tag("fptosi"), |_| Opcode::FPToSI),
+            map(tag("uitofp"), |_| Opcode::UIToFP),
+            map(tag("sitofp"), |_| Opcode::SIToFP),
         )),
     )(source)?;
     let (source, from) = types::parse(source, ctx.types)?;
@@ -614,8 +803,14 @@ pub fn parse<'a, 'b>(
         parse_store,
         parse_insertvalue,
         parse_extractvalue,
+        parse_extractelement,
+        parse_insertelement,
+        parse_shufflevector,
         parse_add_sub_mul,
+        parse_float_binary,
+        parse_fneg,
         parse_icmp,
+        parse_fcmp,
         parse_cast,
         parse_getelementptr,
         parse_call,
@@ -628,26 +823,29 @@ pub fn parse<'a, 'b>(
     ]
     .iter()
     {
-        if let Ok((source, mut inst)) = f(source, ctx) {
-            let (source, metadata) = parse_metadata_if_any(ctx.types)(source)?;
-            inst = inst.with_metadata(metadata);
-
-            if let Some(name) = name {
-                if let Some(inner) = ctx.name_to_value.get(&name) {
-                    if let value::Value::Instruction(id) = ctx.data.values[*inner] {
-                        ctx.data.replace_inst(id, inst.with_dest(name));
-                        return Ok((source, id));
-                    }
+        let (source, mut inst) = match f(source, ctx) {
+            Ok(ok) => ok,
+            Err(e @ nom::Err::Failure(_)) => return Err(e),
+            Err(_) => continue,
+        };
+        let (source, metadata) = parse_metadata_if_any(ctx.types)(source)?;
+        inst = inst.with_metadata(metadata);
+
+        if let Some(name) = name {
+            if let Some(inner) = ctx.name_to_value.get(&name) {
+                if let value::Value::Instruction(id) = ctx.data.values[*inner] {
+                    ctx.data.replace_inst(id, inst.with_dest(name));
+                    return Ok((source, id));
                 }
-
-                let id = ctx.data.create_inst(inst.with_dest(name.clone()));
-                ctx.name_to_value
-                    .insert(name, ctx.data.create_value(value::Value::Instruction(id)));
-                return Ok((source, id));
             }
 
-            return Ok((source, ctx.data.create_inst(inst)));
+            let id = ctx.data.create_inst(inst.with_dest(name));
+            ctx.name_to_value
+                .insert(name, ctx.data.create_value(value::Value::Instruction(id)));
+            return Ok((source, id));
         }
+
+        return Ok((source, ctx.data.create_inst(inst)));
     }
     Err(Error(VerboseError { errors: vec![] }))
 }
@@ -1,6 +1,8 @@
+use crate::collections::FxHashMap;
 use crate::ir::function::{basic_block::BasicBlockId, instruction::InstructionId};
-use rustc_hash::FxHashMap;
+use crate::prelude::*;
 
+#[derive(Default)]
 pub struct Layout {
     basic_blocks: FxHashMap<BasicBlockId, BasicBlockNode>,
     instructions: FxHashMap<InstructionId, InstructionNode>,
@@ -35,17 +37,6 @@ pub struct InstructionIter<'a> {
     tail: Option<InstructionId>,
 }
 
-impl Default for Layout {
-    fn default() -> Self {
-        Self {
-            basic_blocks: FxHashMap::default(),
-            instructions: FxHashMap::default(),
-            first_block: None,
-            last_block: None,
-        }
-    }
-}
-
 impl Layout {
     pub fn new() -> Self {
         Self::default()
@@ -59,14 +50,14 @@ impl Layout {
         &self.basic_blocks[&id]
     }
 
-    pub fn block_iter(&self) -> BasicBlockIter {
+    pub fn block_iter(&self) -> BasicBlockIter<'_> {
         BasicBlockIter {
             layout: self,
             cur: self.first_block,
         }
     }
 
-    pub fn inst_iter(&self, block: BasicBlockId) -> InstructionIter {
+    pub fn inst_iter(&self, block: BasicBlockId) -> InstructionIter<'_> {
         InstructionIter {
             layout: self,
             block,
@@ -75,6 +66,7 @@ impl Layout {
         }
     }
 
+    /// Returns the first block, which is the entry block of the function.
     pub fn get_entry_block(&self) -> Option<BasicBlockId> {
         self.first_block
     }
@@ -103,6 +95,62 @@ impl Layout {
         }
     }
 
+    pub fn insert_block_after(&mut self, block: BasicBlockId, after: BasicBlockId) {
+        let next = self.basic_blocks[&after].next;
+        self.basic_blocks.insert(
+            block,
+            BasicBlockNode {
+                prev: Some(after),
+                next,
+                first_inst: None,
+                last_inst: None,
+            },
+        );
+        self.basic_blocks.get_mut(&after).unwrap().next = Some(block);
+        match next {
+            Some(next) => self.basic_blocks.get_mut(&next).unwrap().prev = Some(block),
+            None => self.last_block = Some(block),
+        }
+    }
+
+    /// Inserts `block` right before `before`. Inserting before the first block
+    /// makes `block` the entry block.
+    pub fn insert_block_before(&mut self, block: BasicBlockId, before: BasicBlockId) {
+        let prev = self.basic_blocks[&before].prev;
+        self.basic_blocks.insert(
+            block,
+            BasicBlockNode {
+                prev,
+                next: Some(before),
+                first_inst: None,
+                last_inst: None,
+            },
+        );
+        self.basic_blocks.get_mut(&before).unwrap().prev = Some(block);
+        match prev {
+            Some(prev) => self.basic_blocks.get_mut(&prev).unwrap().next = Some(block),
+            None => self.first_block = Some(block),
+        }
+    }
+
+    /// Unlinks `block` from the layout. Instructions in `block` are unlinked too.
+    pub fn remove_block(&mut self, block: BasicBlockId) -> Option<()> {
+        let insts: Vec<InstructionId> = self.inst_iter(block).collect();
+        for inst in insts {
+            self.remove_inst(inst)?;
+        }
+        let node = self.basic_blocks.remove(&block)?;
+        match node.prev {
+            Some(prev) => self.basic_blocks.get_mut(&prev)?.next = node.next,
+            None => self.first_block = node.next,
+        }
+        match node.next {
+            Some(next) => self.basic_blocks.get_mut(&next)?.prev = node.prev,
+            None => self.last_block = node.prev,
+        }
+        Some(())
+    }
+
     pub fn append_inst(&mut self, inst: InstructionId, block: BasicBlockId) {
         self.instructions
             .entry(inst)
@@ -147,6 +195,32 @@ impl Layout {
         }
     }
 
+    pub fn insert_inst_before(&mut self, inst: InstructionId, before: InstructionId) {
+        let block = self.instructions[&before].block.unwrap();
+        let prev = self.instructions[&before].prev;
+
+        self.instructions
+            .entry(inst)
+            .or_insert(InstructionNode {
+                prev: None,
+                next: None,
+                block: None,
+            })
+            .block = Some(block);
+        self.instructions.get_mut(&inst).unwrap().prev = prev;
+        self.instructions.get_mut(&inst).unwrap().next = Some(before);
+        self.instructions.get_mut(&before).unwrap().prev = Some(inst);
+
+        match prev {
+            Some(prev) => self.instructions.get_mut(&prev).unwrap().next = Some(inst),
+            None => self.basic_blocks.get_mut(&block).unwrap().first_inst = Some(inst),
+        }
+    }
+
+    pub fn block_of(&self, inst: InstructionId) -> Option<BasicBlockId> {
+        self.instructions.get(&inst)?.block
+    }
+
     pub fn remove_inst(&mut self, inst: InstructionId) -> Option<()> {
         let block = self.instructions[&inst].block?;
         let prev;
@@ -172,6 +246,10 @@ impl Layout {
 }
 
 impl BasicBlockNode {
+    pub fn first_inst(&self) -> &Option<InstructionId> {
+        &self.first_inst
+    }
+
     pub fn last_inst(&self) -> &Option<InstructionId> {
         &self.last_inst
     }
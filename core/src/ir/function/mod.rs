@@ -1,11 +1,19 @@
 pub mod basic_block;
 pub mod builder;
+pub mod builder_code;
+pub mod cfg;
+pub mod clone;
 pub mod data;
+pub mod dot;
+pub mod hash;
 pub mod instruction;
 pub mod layout;
 pub mod param_attrs;
 pub mod parser;
+pub mod pattern;
 pub mod print;
+pub mod verify;
+pub mod visitor;
 
 pub use parser::parse;
 
@@ -16,14 +24,15 @@ use super::{
         visibility::Visibility,
     },
     types::{Type, Types},
-    value::ConstantData,
+    value::{ConstantData, ValueId},
 };
+use crate::prelude::*;
 use crate::traits::basic_block::{BasicBlockData, BasicBlockLayout};
 use basic_block::BasicBlock;
+use core::fmt;
 use id_arena::Id;
-use instruction::InstructionId;
+use instruction::{InstructionId, Opcode, Operand};
 use param_attrs::ParameterAttribute;
-use std::fmt;
 
 pub type FunctionId = Id<Function>;
 
@@ -41,6 +50,12 @@ pub struct Function {
     pub func_attrs: Vec<Attribute>,
     pub ret_attrs: Vec<param_attrs::ParameterAttribute>,
     pub personality: Option<PersonalityFunc>,
+    /// The section the function is placed in, instead of that of code.
+    pub section: Option<String>,
+    /// The comdat the function belongs to.
+    pub comdat: Option<Name>,
+    /// The alignment of the function in bytes, or 0 if unspecified.
+    pub align: u32,
     pub data: data::Data,
     pub layout: layout::Layout,
     pub types: Types,
@@ -48,6 +63,7 @@ pub struct Function {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Parameter {
     pub name: Name,
     pub ty: Type,
@@ -74,6 +90,9 @@ impl Function {
             func_attrs: vec![],
             ret_attrs: vec![],
             personality: None,
+            section: None,
+            comdat: None,
+            align: 0,
             data: data::Data::default(),
             layout: layout::Layout::default(),
             types,
@@ -96,10 +115,49 @@ impl Function {
         self.layout.is_empty()
     }
 
+    /// Returns the entry block, i.e. the first block in the layout.
+    /// `None` for prototypes.
+    pub fn entry_block(&self) -> Option<basic_block::BasicBlockId> {
+        self.layout.get_entry_block()
+    }
+
+    pub fn is_entry_block(&self, block: basic_block::BasicBlockId) -> bool {
+        self.entry_block() == Some(block)
+    }
+
     pub fn remove_inst(&mut self, inst: InstructionId) -> Option<()> {
         self.data.remove_uses(inst);
         self.layout.remove_inst(inst)
     }
+
+    /// Replaces every use of `inst` with `to`, keeping the users map consistent.
+    pub fn replace_all_uses_with(&mut self, inst: InstructionId, to: ValueId) {
+        self.data.replace_all_uses(inst, to)
+    }
+
+    /// Removes `inst` from the function and invalidates it.
+    /// `inst` must not have any users. If `inst` is a branch, the CFG edges it
+    /// introduced are removed as well.
+    pub fn erase_instruction(&mut self, inst: InstructionId) -> Option<()> {
+        assert!(
+            self.data.users_of(inst).is_empty(),
+            "erasing an instruction that still has users"
+        );
+        self.remove_inst(inst)?;
+        self.data.users_map.remove(&inst);
+
+        let inst = self.data.inst_ref_mut(inst);
+        let parent = inst.parent;
+        let succs = inst.operand.successors().to_vec();
+        inst.opcode = Opcode::Invalid;
+        inst.operand = Operand::Invalid;
+        for succ in succs {
+            self.data.remove_block_succ(parent, succ);
+            self.data.remove_block_pred(succ, parent);
+        }
+
+        Some(())
+    }
 }
 
 impl fmt::Debug for Function {
@@ -133,3 +191,54 @@ impl BasicBlockLayout<BasicBlock> for Function {
         Box::new(self.layout.block_iter())
     }
 }
+
+#[test]
+fn replace_all_uses_with_and_erase() {
+    use crate::ir::{module::parse_assembly, value::Value};
+
+    let mut module = parse_assembly(
+        r#"
+define i32 @f(i32 %0) {
+  %2 = add i32 %0, 0
+  %3 = mul i32 %2, %2
+  ret i32 %3
+}"#,
+    )
+    .unwrap();
+    let func_id = module.find_function_by_name("f").unwrap();
+    let func = &mut module.functions_mut()[func_id];
+    let entry = func.layout.get_entry_block().unwrap();
+    let add = func.layout.inst_iter(entry).next().unwrap();
+    let arg = func.data.create_value(Value::Argument(0));
+
+    func.replace_all_uses_with(add, arg);
+    assert!(func.data.users_of(add).is_empty());
+    func.erase_instruction(add).unwrap();
+    assert_eq!(func.data.inst_ref(add).opcode, Opcode::Invalid);
+    assert_eq!(
+        format!("{:?}", func),
+        "define external dso_preemptable default i32 @f(i32 %0) {\n\
+         1:\n    %2 = mul i32 %0, %0\n    ret i32 %2\n}\n"
+    );
+}
+
+#[test]
+fn unreachable_is_kept_by_dce() {
+    use crate::{ir::module::parse_assembly, pass::transform::dce};
+
+    let mut module = parse_assembly(
+        r#"
+define void @f() {
+  %1 = add i32 1, 2
+  unreachable
+}"#,
+    )
+    .unwrap();
+    dce::run_on_module(&mut module);
+    let func_id = module.find_function_by_name("f").unwrap();
+    assert_eq!(
+        format!("{:?}", module.functions()[func_id]),
+        "define external dso_preemptable default void @f() {\n\
+         0:\n    unreachable\n}\n"
+    );
+}
@@ -1,9 +1,14 @@
 pub mod parser;
 
-use crate::ir::types::{Type, Types};
-use std::fmt;
+use crate::ir::{
+    symbol::Symbol,
+    types::{Type, Types},
+};
+use crate::prelude::*;
+use core::fmt;
 
 #[derive(PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ParameterAttribute {
     ZeroExt,
     SignExt,
@@ -25,7 +30,7 @@ pub enum ParameterAttribute {
     SwiftError,
     ImmArg,
     WriteOnly,
-    StringAttribute { kind: String, value: String },
+    StringAttribute { kind: Symbol, value: Symbol },
     Ref(u32),
     UnknownAttribute,
 }
@@ -3,6 +3,7 @@ use crate::ir::{
     types,
     util::{spaces, string_literal},
 };
+use crate::prelude::*;
 use nom::{
     branch::alt,
     bytes::complete::tag,
@@ -66,7 +67,10 @@ pub fn parse_param_attr<'a>(
         alt((
             map(
                 tuple((string_literal, spaces, char('='), spaces, string_literal)),
-                |(kind, _, _, _, value)| ParameterAttribute::StringAttribute { kind, value },
+                |(kind, _, _, _, value)| ParameterAttribute::StringAttribute {
+                    kind: kind.into(),
+                    value: value.into(),
+                },
             ),
             map(preceded(char('#'), digit1), |num: &str| {
                 ParameterAttribute::Ref(num.parse::<u32>().unwrap())
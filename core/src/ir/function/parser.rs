@@ -1,30 +1,32 @@
+use crate::collections::FxHashMap;
 use crate::ir::{
     function::{
         basic_block::BasicBlockId,
         data::Data,
-        instruction::{self, Br, CondBr, Opcode, Operand},
+        instruction::{self, Opcode},
         layout::Layout,
         param_attrs::parser::parse_param_attrs,
         Function, Parameter, PersonalityFunc,
     },
     module::{
-        attributes, global_variable, linkage, name, preemption_specifier, unnamed_addr, visibility,
+        attributes, comdat, global_variable, linkage, name, preemption_specifier, unnamed_addr,
+        visibility,
     },
     types,
     types::Types,
-    util::spaces,
+    util::{skip_nested, spaces, string_literal},
     value::{Value, ValueId},
 };
+use crate::prelude::*;
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::char,
+    character::complete::{char, digit1},
     combinator::opt,
-    error::VerboseError,
+    error::{VerboseError, VerboseErrorKind},
     sequence::{preceded, terminated, tuple},
     IResult,
 };
-use rustc_hash::FxHashMap;
 
 // define [linkage] [PreemptionSpecifier] [visibility] [DLLStorageClass]
 //        [cconv] [ret attrs]
@@ -124,9 +126,15 @@ pub fn parse_body<'a, 'b>(
         ctx.layout.append_block(block);
         ctx.cur_block = block;
 
-        while let Ok((source_, inst)) = instruction::parse(source, ctx) {
-            ctx.layout.append_inst(inst, ctx.cur_block);
-            source = source_
+        loop {
+            match instruction::parse(source, ctx) {
+                Ok((source_, inst)) => {
+                    ctx.layout.append_inst(inst, ctx.cur_block);
+                    source = source_
+                }
+                Err(e @ nom::Err::Failure(_)) => return Err(e),
+                Err(_) => break,
+            }
         }
 
         if let Ok((source, _)) = tuple((spaces, char('}')))(source) {
@@ -143,7 +151,9 @@ pub fn parse_body<'a, 'b>(
             continue;
         }
 
-        todo!("unsupported syntax at {:?}", source)
+        return Err(nom::Err::Failure(VerboseError {
+            errors: vec![(spaces(source)?.0, VerboseErrorKind::Context("instruction"))],
+        }));
     }
 }
 
@@ -160,6 +170,23 @@ pub fn parse_personality<'a>(
 }
 
 pub fn parse(source: &str, types: Types) -> IResult<&str, Function, VerboseError<&str>> {
+    parse_function(source, types, true)
+}
+
+/// Parses a function like [`parse`], but skips its body, if any, so that it
+/// results in a declaration.
+pub fn parse_declaration(
+    source: &str,
+    types: Types,
+) -> IResult<&str, Function, VerboseError<&str>> {
+    parse_function(source, types, false)
+}
+
+fn parse_function(
+    source: &str,
+    types: Types,
+    with_body: bool,
+) -> IResult<&str, Function, VerboseError<&str>> {
     let (source, define_or_declare) =
         preceded(spaces, alt((tag("define"), tag("declare"))))(source)?;
     let is_prototype = define_or_declare == "declare";
@@ -170,11 +197,24 @@ pub fn parse(source: &str, types: Types) -> IResult<&str, Function, VerboseError
     let (source, ret_attrs) = parse_param_attrs(source, &types)?;
     let (source, result_ty) = types::parse(source, &types)?;
     let (source, (_, _, _, name)) = tuple((spaces, char('@'), spaces, name::parse))(source)?;
-    let name = name.to_string().cloned().unwrap();
+    let name = name.to_str().unwrap().to_string();
     let (source, (params, is_var_arg)) = parse_argument_list(source, &types)?;
     let (source, unnamed_addr) = opt(preceded(spaces, unnamed_addr::parse))(source)?;
     let (source, func_attrs) = attributes::parser::parse_attributes(source)?;
+    let (source, section) = opt(preceded(
+        spaces,
+        preceded(tag("section"), preceded(spaces, string_literal)),
+    ))(source)?;
+    let (source, comdat) = opt(preceded(
+        spaces,
+        comdat::parse_comdat_ref(name::Name::from(name.as_str())),
+    ))(source)?;
+    let (source, align) = opt(preceded(
+        spaces,
+        preceded(tag("align"), preceded(spaces, digit1)),
+    ))(source)?;
     let (mut source, personality) = parse_personality(source, &types)?;
+    let mut body = None;
 
     let mut data = Data::new();
     let mut layout = Layout::new();
@@ -184,10 +224,14 @@ pub fn parse(source: &str, types: Types) -> IResult<&str, Function, VerboseError
 
     for (i, param) in params.iter().enumerate() {
         let arg = data.create_value(Value::Argument(i));
-        name_to_value.insert(param.name.clone(), arg);
+        name_to_value.insert(param.name, arg);
     }
 
-    if !is_prototype {
+    if !is_prototype && !with_body {
+        let (source_, _) = preceded(spaces, char('{'))(source)?;
+        source = char('}')(skip_nested(source_, &['}']))?.0;
+    } else if !is_prototype {
+        body = Some(spaces(source)?.0);
         source = parse_body(
             source,
             &mut ParserContext {
@@ -203,27 +247,36 @@ pub fn parse(source: &str, types: Types) -> IResult<&str, Function, VerboseError
         .0;
     }
 
-    Ok((
-        source,
-        Function {
-            name,
-            is_var_arg,
-            result_ty,
-            linkage: linkage.unwrap_or(linkage::Linkage::External),
-            preemption_specifier: preemption_specifier
-                .unwrap_or(preemption_specifier::PreemptionSpecifier::DsoPreemptable),
-            visibility: visibility.unwrap_or(visibility::Visibility::Default),
-            unnamed_addr,
-            ret_attrs,
-            func_attrs,
-            params,
-            data,
-            layout,
-            types,
-            // is_prototype,
-            personality,
-        },
-    ))
+    let func = Function {
+        name,
+        is_var_arg,
+        result_ty,
+        linkage: linkage.unwrap_or(linkage::Linkage::External),
+        preemption_specifier: preemption_specifier
+            .unwrap_or(preemption_specifier::PreemptionSpecifier::DsoPreemptable),
+        visibility: visibility.unwrap_or(visibility::Visibility::Default),
+        unnamed_addr,
+        ret_attrs,
+        func_attrs,
+        params,
+        data,
+        layout,
+        types,
+        // is_prototype,
+        personality,
+        section,
+        comdat,
+        align: align.map_or(0, |align| align.parse::<u32>().unwrap()),
+    };
+
+    // The entry block can't be branched to, nor start with phis.
+    if let (Some(body), Err(_)) = (body, func.verify_entry_block()) {
+        return Err(nom::Err::Failure(VerboseError {
+            errors: vec![(body, VerboseErrorKind::Context("entry block"))],
+        }));
+    }
+
+    Ok((source, func))
 }
 
 impl<'a> ParserContext<'a> {
@@ -244,7 +297,7 @@ impl<'a> ParserContext<'a> {
             return *block;
         }
         let block = self.data.create_block();
-        self.data.block_ref_mut(block).name = Some(name.clone());
+        self.data.block_ref_mut(block).name = Some(name);
         self.name_to_block.insert(name, block);
         block
     }
@@ -261,18 +314,9 @@ impl<'a> ParserContext<'a> {
                 continue;
             }
             let br = maybe_br;
-            match br.operand {
-                Operand::Br(Br { block }) => {
-                    self.data.basic_blocks[br.parent].succs.insert(block);
-                    self.data.basic_blocks[block].preds.insert(br.parent);
-                }
-                Operand::CondBr(CondBr { blocks, .. }) => {
-                    for &block in blocks.iter() {
-                        self.data.basic_blocks[br.parent].succs.insert(block);
-                        self.data.basic_blocks[block].preds.insert(br.parent);
-                    }
-                }
-                _ => continue,
+            for &block in br.operand.successors() {
+                self.data.basic_blocks[br.parent].succs.insert(block);
+                self.data.basic_blocks[block].preds.insert(br.parent);
             }
         }
     }
@@ -322,3 +366,53 @@ fn test_parse_function2() {
     );
     println!("{:?}", result);
 }
+
+#[test]
+fn test_parse_vector_instructions() {
+    let types = Types::new();
+    let (_, func) = parse(
+        r#"
+        define <4 x i32> @f(<4 x i32> %0, i32 %1) {
+            %3 = add <4 x i32> %0, <i32 1, i32 2, i32 3, i32 4>
+            %4 = icmp sgt <4 x i32> %3, zeroinitializer
+            %5 = sext <4 x i1> %4 to <4 x i32>
+            %6 = insertelement <4 x i32> %5, i32 %1, i64 2
+            %7 = extractelement <4 x i32> %6, i32 0
+            %8 = insertelement <4 x i32> undef, i32 %7, i32 3
+            %9 = shufflevector <4 x i32> %6, <4 x i32> %8, <4 x i32> <i32 7, i32 1, i32 undef, i32 4>
+            ret <4 x i32> %9
+        }
+        "#,
+        types,
+    )
+    .unwrap();
+    let printed = format!("{:?}", func);
+    for line in [
+        "%3 = add <4 x i32> %0, <i32 1, i32 2, i32 3, i32 4>",
+        "%4 = icmp sgt <4 x i32> %3, zeroinitializer",
+        "%6 = insertelement <4 x i32> %5, i32 %1, i64 2",
+        "%7 = extractelement <4 x i32> %6, i32 0",
+        "%9 = shufflevector <4 x i32> %6, <4 x i32> %8, <4 x i32> <i32 7, i32 1, i32 undef, i32 4>",
+    ] {
+        assert!(printed.contains(line), "{}", printed);
+    }
+}
+
+#[test]
+fn test_parse_i16_constants() {
+    let types = Types::new();
+    let (_, func) = parse(
+        r#"
+        define i16 @f(i16 %0) {
+            %2 = add i16 %0, 5
+            %3 = sub i16 %2, -32768
+            ret i16 %3
+        }
+        "#,
+        types,
+    )
+    .unwrap();
+    let printed = format!("{:?}", func);
+    assert!(printed.contains("%2 = add i16 %0, 5"), "{}", printed);
+    assert!(printed.contains("%3 = sub i16 %2, -32768"), "{}", printed);
+}
@@ -0,0 +1,367 @@
+//! Declarative matching of IR, after LLVM's `PatternMatch.h`.
+//!
+//! A [`Pattern`] is built from combinators mirroring the structure of the
+//! values to match, and binds the parts of interest to variables:
+//!
+//! ```ignore
+//! let (mut x, mut c) = (None, None);
+//! if match_inst(func, id, m_add(m_value(&mut x), m_const_int(&mut c))) {
+//!     // `id` is `add x, c`.
+//!     let (x, c) = (x.unwrap(), c.unwrap());
+//! }
+//! ```
+//!
+//! Bindings are only meaningful when the whole pattern matched.
+
+use super::{
+    instruction::{Cast, ICmp, ICmpCond, InstructionId, IntBinary, Opcode, Operand},
+    Function,
+};
+use crate::ir::value::{ConstantData, ConstantInt, Value, ValueId};
+
+pub trait Pattern {
+    /// Returns true if `val` matches.
+    fn match_value(&mut self, func: &Function, val: ValueId) -> bool;
+
+    /// Returns true if the value computed by `inst` matches. Only patterns
+    /// of instructions can match without a `ValueId` to bind.
+    fn match_inst(&mut self, _func: &Function, _inst: InstructionId) -> bool {
+        false
+    }
+}
+
+/// Returns true if `val` matches `pattern`.
+pub fn match_value<P: Pattern>(func: &Function, val: ValueId, mut pattern: P) -> bool {
+    pattern.match_value(func, val)
+}
+
+/// Returns true if the value computed by `inst` matches `pattern`.
+pub fn match_inst<P: Pattern>(func: &Function, inst: InstructionId, mut pattern: P) -> bool {
+    pattern.match_inst(func, inst)
+}
+
+/// Matches `val` with `p` if it is computed by an instruction.
+fn match_inst_value<P: Pattern + ?Sized>(p: &mut P, func: &Function, val: ValueId) -> bool {
+    match func.data.value_ref(val) {
+        Value::Instruction(inst) => p.match_inst(func, *inst),
+        _ => false,
+    }
+}
+
+pub struct AnyValue<'a>(Option<&'a mut Option<ValueId>>);
+
+/// Matches any value.
+pub fn m_any() -> AnyValue<'static> {
+    AnyValue(None)
+}
+
+/// Matches any value, binding it to `val`.
+pub fn m_value(val: &mut Option<ValueId>) -> AnyValue<'_> {
+    AnyValue(Some(val))
+}
+
+impl Pattern for AnyValue<'_> {
+    fn match_value(&mut self, _func: &Function, val: ValueId) -> bool {
+        if let Some(bind) = &mut self.0 {
+            **bind = Some(val);
+        }
+        true
+    }
+
+    fn match_inst(&mut self, _func: &Function, _inst: InstructionId) -> bool {
+        self.0.is_none()
+    }
+}
+
+pub struct Specific(ValueId);
+
+/// Matches `val`, or a value equal to it such as the same constant.
+pub fn m_specific(val: ValueId) -> Specific {
+    Specific(val)
+}
+
+impl Pattern for Specific {
+    fn match_value(&mut self, func: &Function, val: ValueId) -> bool {
+        val == self.0 || func.data.value_ref(val) == func.data.value_ref(self.0)
+    }
+}
+
+pub struct ConstInt<'a>(&'a mut Option<ConstantInt>);
+
+/// Matches an integer constant, binding it to `c`.
+pub fn m_const_int(c: &mut Option<ConstantInt>) -> ConstInt<'_> {
+    ConstInt(c)
+}
+
+impl Pattern for ConstInt<'_> {
+    fn match_value(&mut self, func: &Function, val: ValueId) -> bool {
+        match func.data.value_ref(val) {
+            Value::Constant(ConstantData::Int(c)) => {
+                *self.0 = Some(*c);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+pub struct SpecificInt(i64);
+
+/// Matches the integer constant `v` of any type.
+pub fn m_specific_int(v: i64) -> SpecificInt {
+    SpecificInt(v)
+}
+
+pub fn m_zero() -> SpecificInt {
+    SpecificInt(0)
+}
+
+pub fn m_one() -> SpecificInt {
+    SpecificInt(1)
+}
+
+impl Pattern for SpecificInt {
+    fn match_value(&mut self, func: &Function, val: ValueId) -> bool {
+        matches!(
+            func.data.value_ref(val),
+            Value::Constant(ConstantData::Int(c)) if c.cast_to_i128() == self.0.into()
+        )
+    }
+}
+
+pub struct Binary<L, R> {
+    opcode: Opcode,
+    lhs: L,
+    rhs: R,
+    commutative: bool,
+}
+
+/// Matches the integer binary operation `opcode` with operands matching
+/// `lhs` and `rhs`.
+pub fn m_binary<L: Pattern, R: Pattern>(opcode: Opcode, lhs: L, rhs: R) -> Binary<L, R> {
+    Binary {
+        opcode,
+        lhs,
+        rhs,
+        commutative: false,
+    }
+}
+
+/// Same as [`m_binary`], also trying the operands the other way around.
+pub fn m_c_binary<L: Pattern, R: Pattern>(opcode: Opcode, lhs: L, rhs: R) -> Binary<L, R> {
+    Binary {
+        opcode,
+        lhs,
+        rhs,
+        commutative: true,
+    }
+}
+
+macro_rules! binary_matchers {
+    ($($name:ident, $c_name:ident => $opcode:ident),* $(,)?) => {
+        $(
+            pub fn $name<L: Pattern, R: Pattern>(lhs: L, rhs: R) -> Binary<L, R> {
+                m_binary(Opcode::$opcode, lhs, rhs)
+            }
+
+            pub fn $c_name<L: Pattern, R: Pattern>(lhs: L, rhs: R) -> Binary<L, R> {
+                m_c_binary(Opcode::$opcode, lhs, rhs)
+            }
+        )*
+    };
+}
+
+binary_matchers! {
+    m_add, m_c_add => Add,
+    m_mul, m_c_mul => Mul,
+    m_and, m_c_and => And,
+    m_or, m_c_or => Or,
+}
+
+pub fn m_sub<L: Pattern, R: Pattern>(lhs: L, rhs: R) -> Binary<L, R> {
+    m_binary(Opcode::Sub, lhs, rhs)
+}
+
+pub fn m_sdiv<L: Pattern, R: Pattern>(lhs: L, rhs: R) -> Binary<L, R> {
+    m_binary(Opcode::SDiv, lhs, rhs)
+}
+
+pub fn m_srem<L: Pattern, R: Pattern>(lhs: L, rhs: R) -> Binary<L, R> {
+    m_binary(Opcode::SRem, lhs, rhs)
+}
+
+pub fn m_shl<L: Pattern, R: Pattern>(lhs: L, rhs: R) -> Binary<L, R> {
+    m_binary(Opcode::Shl, lhs, rhs)
+}
+
+pub fn m_lshr<L: Pattern, R: Pattern>(lhs: L, rhs: R) -> Binary<L, R> {
+    m_binary(Opcode::LShr, lhs, rhs)
+}
+
+impl<L: Pattern, R: Pattern> Pattern for Binary<L, R> {
+    fn match_value(&mut self, func: &Function, val: ValueId) -> bool {
+        match_inst_value(self, func, val)
+    }
+
+    fn match_inst(&mut self, func: &Function, inst: InstructionId) -> bool {
+        let inst = func.data.inst_ref(inst);
+        let Operand::IntBinary(IntBinary { args, .. }) = &inst.operand else {
+            return false;
+        };
+        inst.opcode == self.opcode
+            && ((self.lhs.match_value(func, args[0]) && self.rhs.match_value(func, args[1]))
+                || (self.commutative
+                    && self.lhs.match_value(func, args[1])
+                    && self.rhs.match_value(func, args[0])))
+    }
+}
+
+pub struct ICmpPattern<'a, L, R> {
+    cond: &'a mut Option<ICmpCond>,
+    lhs: L,
+    rhs: R,
+}
+
+/// Matches an `icmp` of any condition, binding it to `cond`.
+pub fn m_icmp<L: Pattern, R: Pattern>(
+    cond: &mut Option<ICmpCond>,
+    lhs: L,
+    rhs: R,
+) -> ICmpPattern<'_, L, R> {
+    ICmpPattern { cond, lhs, rhs }
+}
+
+impl<L: Pattern, R: Pattern> Pattern for ICmpPattern<'_, L, R> {
+    fn match_value(&mut self, func: &Function, val: ValueId) -> bool {
+        match_inst_value(self, func, val)
+    }
+
+    fn match_inst(&mut self, func: &Function, inst: InstructionId) -> bool {
+        let Operand::ICmp(ICmp { args, cond, .. }) = &func.data.inst_ref(inst).operand else {
+            return false;
+        };
+        *self.cond = Some(*cond);
+        self.lhs.match_value(func, args[0]) && self.rhs.match_value(func, args[1])
+    }
+}
+
+pub struct CastPattern<P> {
+    opcode: Opcode,
+    arg: P,
+}
+
+/// Matches the conversion `opcode` of a value matching `arg`.
+pub fn m_cast<P: Pattern>(opcode: Opcode, arg: P) -> CastPattern<P> {
+    CastPattern { opcode, arg }
+}
+
+pub fn m_zext<P: Pattern>(arg: P) -> CastPattern<P> {
+    m_cast(Opcode::Zext, arg)
+}
+
+pub fn m_sext<P: Pattern>(arg: P) -> CastPattern<P> {
+    m_cast(Opcode::Sext, arg)
+}
+
+pub fn m_trunc<P: Pattern>(arg: P) -> CastPattern<P> {
+    m_cast(Opcode::Trunc, arg)
+}
+
+pub fn m_bitcast<P: Pattern>(arg: P) -> CastPattern<P> {
+    m_cast(Opcode::Bitcast, arg)
+}
+
+impl<P: Pattern> Pattern for CastPattern<P> {
+    fn match_value(&mut self, func: &Function, val: ValueId) -> bool {
+        match_inst_value(self, func, val)
+    }
+
+    fn match_inst(&mut self, func: &Function, inst: InstructionId) -> bool {
+        let inst = func.data.inst_ref(inst);
+        let Operand::Cast(Cast { arg, .. }) = &inst.operand else {
+            return false;
+        };
+        inst.opcode == self.opcode && self.arg.match_value(func, *arg)
+    }
+}
+
+pub struct OneUse<P>(P);
+
+/// Matches an instruction matching `p` that has a single user.
+pub fn m_one_use<P: Pattern>(p: P) -> OneUse<P> {
+    OneUse(p)
+}
+
+impl<P: Pattern> Pattern for OneUse<P> {
+    fn match_value(&mut self, func: &Function, val: ValueId) -> bool {
+        match_inst_value(self, func, val)
+    }
+
+    fn match_inst(&mut self, func: &Function, inst: InstructionId) -> bool {
+        func.data.users_of(inst).len() == 1 && self.0.match_inst(func, inst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn match_insts() {
+        let module = parse_assembly(
+            r#"
+define i32 @f(i32 %0, i64 %1) {
+  %3 = add i32 %0, 4
+  %4 = mul i32 8, %3
+  %5 = trunc i64 %1 to i32
+  %6 = icmp slt i32 %5, %4
+  %7 = zext i1 %6 to i32
+  ret i32 %7
+}"#,
+        )
+        .unwrap();
+        let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+        let insts: Vec<InstructionId> = func
+            .layout
+            .inst_iter(func.layout.get_entry_block().unwrap())
+            .collect();
+
+        let (mut x, mut c) = (None, None);
+        assert!(match_inst(
+            func,
+            insts[0],
+            m_add(m_value(&mut x), m_const_int(&mut c))
+        ));
+        assert_eq!(func.data.value_ref(x.unwrap()), &Value::Argument(0));
+        assert_eq!(c, Some(ConstantInt::Int32(4)));
+
+        // The constant is on the left.
+        assert!(!match_inst(
+            func,
+            insts[1],
+            m_mul(m_any(), m_specific_int(8))
+        ));
+        let mut c = None;
+        assert!(match_inst(
+            func,
+            insts[1],
+            m_c_mul(m_one_use(m_add(m_any(), m_any())), m_const_int(&mut c))
+        ));
+        assert_eq!(c, Some(ConstantInt::Int32(8)));
+
+        let (mut cond, mut y) = (None, None);
+        assert!(match_inst(
+            func,
+            insts[4],
+            m_zext(m_icmp(
+                &mut cond,
+                m_trunc(m_value(&mut y)),
+                m_mul(m_any(), m_any())
+            ))
+        ));
+        assert_eq!(cond, Some(ICmpCond::Slt));
+        assert_eq!(func.data.value_ref(y.unwrap()), &Value::Argument(1));
+        assert!(!match_inst(func, insts[4], m_sext(m_any())));
+    }
+}
@@ -5,19 +5,21 @@ use super::{
     basic_block::BasicBlockId,
     data::Data,
     instruction::{
-        Alloca, Cast, GetElementPtr, ICmp, Instruction, InstructionId, IntBinary, Load, Opcode,
-        Operand, Phi, Store,
+        Alloca, Cast, FCmp, FloatBinary, FloatUnary, GetElementPtr, ICmp, Instruction,
+        InstructionId, IntBinary, Load, Operand, Phi, Store,
     },
     Function,
 };
+use crate::collections::FxHashMap;
 use crate::ir::{
     function::instruction::{
-        Br, Call, CondBr, ExtractValue, InsertValue, Invoke, LandingPad, Resume, Ret,
+        Br, Call, CondBr, ExtractElement, ExtractValue, InsertElement, InsertValue, Invoke,
+        LandingPad, Resume, Ret, ShuffleVector,
     },
     types::Type,
 };
-use rustc_hash::FxHashMap;
-use std::fmt;
+use crate::prelude::*;
+use core::fmt;
 
 pub type Index = usize;
 pub type Indexes = FxHashMap<Ids, Name>;
@@ -26,6 +28,7 @@ pub struct FunctionAsmPrinter<'a, 'b: 'a> {
     fmt: &'a mut fmt::Formatter<'b>,
     indexes: Indexes,
     cur_index: Index,
+    anonymous_locals: bool,
 }
 
 #[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
@@ -41,9 +44,17 @@ impl<'a, 'b: 'a> FunctionAsmPrinter<'a, 'b> {
             fmt,
             indexes: FxHashMap::default(),
             cur_index: 0,
+            anonymous_locals: false,
         }
     }
 
+    /// Makes the printer number every argument, block and instruction instead
+    /// of using their names, so that the output only depends on the structure.
+    pub fn with_anonymous_locals(mut self) -> Self {
+        self.anonymous_locals = true;
+        self
+    }
+
     pub fn print(&mut self, f: &Function) -> fmt::Result {
         if f.is_prototype() {
             write!(self.fmt, "declare ")?
@@ -65,10 +76,10 @@ impl<'a, 'b: 'a> FunctionAsmPrinter<'a, 'b> {
             for attr in &param.attrs {
                 write!(self.fmt, "{} ", attr.to_string(&f.types))?;
             }
-            match param.name.to_string() {
+            match param.name.to_str().filter(|_| !self.anonymous_locals) {
                 Some(name) => {
                     write!(self.fmt, "%{}", name)?;
-                    self.indexes.insert(Ids::Arg(i), Name::Name(name.clone()));
+                    self.indexes.insert(Ids::Arg(i), Name::from(name));
                 }
                 None => {
                     let name = self.new_name_for_arg(i);
@@ -96,6 +107,22 @@ impl<'a, 'b: 'a> FunctionAsmPrinter<'a, 'b> {
             write!(self.fmt, "{:?} ", attr)?
         }
 
+        if let Some(section) = &f.section {
+            write!(self.fmt, "section {:?} ", section)?
+        }
+
+        match f.comdat {
+            Some(comdat) if comdat.to_str() == Some(f.name.as_str()) => {
+                write!(self.fmt, "comdat ")?
+            }
+            Some(comdat) => write!(self.fmt, "comdat(${}) ", comdat)?,
+            None => {}
+        }
+
+        if f.align != 0 {
+            write!(self.fmt, "align {} ", f.align)?
+        }
+
         if let Some((ty, func)) = &f.personality {
             write!(
                 self.fmt,
@@ -113,10 +140,9 @@ impl<'a, 'b: 'a> FunctionAsmPrinter<'a, 'b> {
 
         for block_id in f.layout.block_iter() {
             if let Some(name) = &f.data.block_ref(block_id).name {
-                match name.to_string() {
+                match name.to_str().filter(|_| !self.anonymous_locals) {
                     Some(name) => {
-                        self.indexes
-                            .insert(Ids::Block(block_id), Name::Name(name.clone()));
+                        self.indexes.insert(Ids::Block(block_id), Name::from(name));
                     }
                     None => {
                         self.new_name_for_block(block_id);
@@ -128,24 +154,21 @@ impl<'a, 'b: 'a> FunctionAsmPrinter<'a, 'b> {
 
             for inst_id in f.layout.inst_iter(block_id) {
                 let inst = f.data.inst_ref(inst_id);
-                if matches!(
-                    inst.opcode,
-                    Opcode::Store | Opcode::Br | Opcode::CondBr | Opcode::Ret | Opcode::Resume
-                ) || (inst
-                    .operand
-                    .call_result_ty()
-                    .as_ref()
-                    .map_or(false, Type::is_void))
+                if !inst.opcode.has_result()
+                    || (inst
+                        .operand
+                        .call_result_ty()
+                        .as_ref()
+                        .is_some_and(Type::is_void))
                 {
                     continue;
                 }
                 if let Some(name) = &inst.dest {
                     match name {
-                        Name::Name(name) => {
-                            self.indexes
-                                .insert(Ids::Inst(inst_id), Name::Name(name.clone()));
+                        Name::Name(name) if !self.anonymous_locals => {
+                            self.indexes.insert(Ids::Inst(inst_id), Name::Name(*name));
                         }
-                        Name::Number(_) => {
+                        _ => {
                             self.new_name_for_inst(inst_id);
                         }
                     }
@@ -288,6 +311,43 @@ impl<'a, 'b: 'a> FunctionAsmPrinter<'a, 'b> {
                         .trim_end_matches(", ")
                 )
             }
+            Operand::ExtractElement(ExtractElement { tys, args }) => {
+                write!(
+                    self.fmt,
+                    "%{:?} = extractelement {} {}, {} {}",
+                    dest,
+                    types.to_string(tys[0]),
+                    self.value_to_string(data.value_ref(args[0]), types),
+                    types.to_string(tys[1]),
+                    self.value_to_string(data.value_ref(args[1]), types),
+                )
+            }
+            Operand::InsertElement(InsertElement { tys, args }) => {
+                write!(
+                    self.fmt,
+                    "%{:?} = insertelement {} {}, {} {}, {} {}",
+                    dest,
+                    types.to_string(tys[0]),
+                    self.value_to_string(data.value_ref(args[0]), types),
+                    types.to_string(tys[1]),
+                    self.value_to_string(data.value_ref(args[1]), types),
+                    types.to_string(tys[2]),
+                    self.value_to_string(data.value_ref(args[2]), types),
+                )
+            }
+            Operand::ShuffleVector(ShuffleVector { tys, args }) => {
+                write!(
+                    self.fmt,
+                    "%{:?} = shufflevector {} {}, {} {}, {} {}",
+                    dest,
+                    types.to_string(tys[0]),
+                    self.value_to_string(data.value_ref(args[0]), types),
+                    types.to_string(tys[0]),
+                    self.value_to_string(data.value_ref(args[1]), types),
+                    types.to_string(tys[1]),
+                    self.value_to_string(data.value_ref(args[2]), types),
+                )
+            }
             Operand::IntBinary(IntBinary {
                 ty,
                 nuw,
@@ -308,6 +368,38 @@ impl<'a, 'b: 'a> FunctionAsmPrinter<'a, 'b> {
                     self.value_to_string(data.value_ref(args[1]), types),
                 )
             }
+            Operand::FloatBinary(FloatBinary { ty, args }) => {
+                write!(
+                    self.fmt,
+                    "%{:?} = {:?} {} {}, {}",
+                    dest,
+                    inst.opcode,
+                    types.to_string(*ty),
+                    self.value_to_string(data.value_ref(args[0]), types),
+                    self.value_to_string(data.value_ref(args[1]), types),
+                )
+            }
+            Operand::FloatUnary(FloatUnary { ty, arg }) => {
+                write!(
+                    self.fmt,
+                    "%{:?} = {:?} {} {}",
+                    dest,
+                    inst.opcode,
+                    types.to_string(*ty),
+                    self.value_to_string(data.value_ref(*arg), types),
+                )
+            }
+            Operand::FCmp(FCmp { ty, args, cond }) => {
+                write!(
+                    self.fmt,
+                    "%{:?} = fcmp {:?} {} {}, {}",
+                    dest,
+                    cond,
+                    types.to_string(*ty),
+                    self.value_to_string(data.value_ref(args[0]), types),
+                    self.value_to_string(data.value_ref(args[1]), types)
+                )
+            }
             Operand::ICmp(ICmp { ty, args, cond }) => {
                 write!(
                     self.fmt,
@@ -382,7 +474,6 @@ impl<'a, 'b: 'a> FunctionAsmPrinter<'a, 'b> {
                         .iter()
                         .zip(args[1..].iter())
                         .zip(param_attrs.iter())
-                        .into_iter()
                         .fold("".to_string(), |acc, ((&ty, &arg), attrs)| {
                             format!(
                                 "{}{} {}{}, ",
@@ -427,7 +518,6 @@ impl<'a, 'b: 'a> FunctionAsmPrinter<'a, 'b> {
                         .iter()
                         .zip(args[1..].iter())
                         .zip(param_attrs.iter())
-                        .into_iter()
                         .fold("".to_string(), |acc, ((&ty, &arg), attrs)| {
                             format!(
                                 "{}{} {}{}, ",
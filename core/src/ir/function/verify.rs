@@ -0,0 +1,178 @@
+//! Structural checks on a [`Function`].
+//!
+//! [`Function::verify`] checks what every pass and the code generator rely on
+//! without checking it themselves: that the entry block is canonical, that
+//! blocks are terminated, that phis come first, and that every instruction has
+//! the operand its opcode expects.
+
+use super::{
+    basic_block::BasicBlockId,
+    cfg::EntryBlockError,
+    instruction::{InstructionId, Opcode, Operand},
+    Function,
+};
+use core::{error::Error, fmt};
+
+/// A violation of the structure of a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The entry block has predecessors or phis.
+    EntryBlock(EntryBlockError),
+    /// The block `0` is empty or doesn't end with a terminator.
+    MissingTerminator(BasicBlockId),
+    /// The terminator `0` is followed by other instructions in its block.
+    TerminatorNotLast(InstructionId),
+    /// The phi `0` follows an instruction that is not a phi.
+    PhiNotAtStart(InstructionId),
+    /// The operand of `0` is not of the kind its opcode expects.
+    OperandMismatch(InstructionId),
+    /// `0` is named although its opcode produces no value.
+    UnexpectedResult(InstructionId),
+}
+
+impl Function {
+    /// Checks the structure of the function, returning the first violation
+    /// found in layout order. Declarations are always valid.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        self.verify_entry_block().map_err(VerifyError::EntryBlock)?;
+        for block in self.layout.block_iter() {
+            let mut insts = self.layout.inst_iter(block).peekable();
+            let mut seen_non_phi = false;
+            let mut last = None;
+            while let Some(id) = insts.next() {
+                let inst = self.data.inst_ref(id);
+                if inst.opcode.is_phi() {
+                    if seen_non_phi {
+                        return Err(VerifyError::PhiNotAtStart(id));
+                    }
+                } else {
+                    seen_non_phi = true;
+                }
+                if inst.opcode.is_terminator() && insts.peek().is_some() {
+                    return Err(VerifyError::TerminatorNotLast(id));
+                }
+                if !operand_matches(inst.opcode, &inst.operand) {
+                    return Err(VerifyError::OperandMismatch(id));
+                }
+                if !inst.opcode.has_result() && inst.dest.is_some() {
+                    return Err(VerifyError::UnexpectedResult(id));
+                }
+                last = Some(inst.opcode);
+            }
+            if !last.is_some_and(|opcode| opcode.is_terminator()) {
+                return Err(VerifyError::MissingTerminator(block));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if `operand` is of the kind instructions with `opcode` have.
+fn operand_matches(opcode: Opcode, operand: &Operand) -> bool {
+    match operand {
+        Operand::IntBinary(_) => opcode.is_binary(),
+        Operand::FloatBinary(_) => opcode.is_float_binary(),
+        Operand::Cast(_) => opcode.is_cast(),
+        Operand::Alloca(_) => opcode.is_alloca(),
+        Operand::Phi(_) => opcode.is_phi(),
+        Operand::Load(_) => opcode.is_load(),
+        Operand::Store(_) => opcode.is_store(),
+        Operand::Call(_) => opcode.is_call(),
+        Operand::Invoke(_) => opcode.is_invoke(),
+        Operand::FloatUnary(_) => opcode == Opcode::FNeg,
+        Operand::InsertValue(_) => opcode == Opcode::InsertValue,
+        Operand::ExtractValue(_) => opcode == Opcode::ExtractValue,
+        Operand::ExtractElement(_) => opcode == Opcode::ExtractElement,
+        Operand::InsertElement(_) => opcode == Opcode::InsertElement,
+        Operand::ShuffleVector(_) => opcode == Opcode::ShuffleVector,
+        Operand::ICmp(_) => opcode == Opcode::ICmp,
+        Operand::FCmp(_) => opcode == Opcode::FCmp,
+        Operand::GetElementPtr(_) => opcode == Opcode::GetElementPtr,
+        Operand::LandingPad(_) => opcode == Opcode::LandingPad,
+        Operand::Resume(_) => opcode == Opcode::Resume,
+        Operand::Br(_) => opcode == Opcode::Br,
+        Operand::CondBr(_) => opcode == Opcode::CondBr,
+        Operand::Ret(_) => opcode == Opcode::Ret,
+        Operand::Unreachable => opcode == Opcode::Unreachable,
+        Operand::Invalid => false,
+    }
+}
+
+impl Error for VerifyError {}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EntryBlock(err) => err.fmt(f),
+            Self::MissingTerminator(block) => {
+                write!(f, "block {} doesn't end with a terminator", block.index())
+            }
+            Self::TerminatorNotLast(inst) => {
+                write!(f, "terminator {} is not the last instruction", inst.index())
+            }
+            Self::PhiNotAtStart(inst) => {
+                write!(f, "phi {} follows a non-phi instruction", inst.index())
+            }
+            Self::OperandMismatch(inst) => {
+                write!(f, "operand of {} doesn't match its opcode", inst.index())
+            }
+            Self::UnexpectedResult(inst) => {
+                write!(f, "{} is named but produces no value", inst.index())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::VerifyError;
+    use crate::ir::{
+        function::instruction::{Opcode, Operand},
+        module::parse_assembly,
+    };
+
+    #[test]
+    fn verify() {
+        let mut module = parse_assembly(
+            r#"
+define i32 @f(i32 %0) {
+  %2 = add i32 %0, 1
+  br label %3
+3:
+  %4 = phi i32 [ %2, %1 ]
+  ret i32 %4
+}"#,
+        )
+        .unwrap();
+        let id = module.find_function_by_name("f").unwrap();
+        let func = &mut module.functions_mut()[id];
+        assert_eq!(func.verify(), Ok(()));
+
+        let add = func
+            .layout
+            .inst_iter(func.entry_block().unwrap())
+            .next()
+            .unwrap();
+        func.data.inst_ref_mut(add).opcode = Opcode::Load;
+        assert_eq!(func.verify(), Err(VerifyError::OperandMismatch(add)));
+        func.data.inst_ref_mut(add).opcode = Opcode::Sub;
+        assert_eq!(func.verify(), Ok(()));
+
+        let block = func.layout.block_iter().nth(1).unwrap();
+        let ret = func.layout.inst_iter(block).next_back().unwrap();
+        func.layout.remove_inst(ret);
+        assert_eq!(func.verify(), Err(VerifyError::MissingTerminator(block)));
+
+        let unreachable = func.data.create_inst(
+            Opcode::Unreachable
+                .with_block(block)
+                .with_operand(Operand::Unreachable),
+        );
+        func.layout.insert_inst_at_start(unreachable, block);
+        func.layout.append_inst(ret, block);
+        assert_eq!(
+            func.verify(),
+            Err(VerifyError::TerminatorNotLast(unreachable))
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! Visiting instructions by opcode, after LLVM's `InstVisitor`.
+//!
+//! An [`InstVisitor`] overrides the methods of the instructions it cares
+//! about. Each method defaults to the one of the instruction's category,
+//! e.g. `visit_add` to `visit_int_binary`, which in turn defaults to
+//! [`InstVisitor::visit_instruction`], doing nothing. So a visitor handling
+//! all casts the same way only overrides `visit_cast`, and one handling every
+//! instruction only overrides `visit_instruction`.
+//!
+//! ```ignore
+//! struct CountLoads(usize);
+//!
+//! impl InstVisitor for CountLoads {
+//!     fn visit_load(&mut self, _: &Function, _: &Instruction, _: &Load) {
+//!         self.0 += 1;
+//!     }
+//! }
+//!
+//! let mut count = CountLoads(0);
+//! count.visit_module(&module);
+//! ```
+
+use super::{
+    basic_block::BasicBlockId,
+    instruction::{
+        Alloca, Br, Call, Cast, CondBr, ExtractElement, ExtractValue, FCmp, FloatBinary,
+        FloatUnary, GetElementPtr, ICmp, InsertElement, InsertValue, Instruction, IntBinary,
+        Invoke, LandingPad, Load, Opcode, Operand, Phi, Resume, Ret, ShuffleVector, Store,
+    },
+    Function,
+};
+use crate::ir::module::Module;
+
+pub trait InstVisitor {
+    /// Visits the functions of `module` in order.
+    fn visit_module(&mut self, module: &Module) {
+        for (_, func) in module.functions().iter() {
+            self.visit_function(func);
+        }
+    }
+
+    /// Visits the blocks of `func` in layout order.
+    fn visit_function(&mut self, func: &Function) {
+        for block in func.layout.block_iter() {
+            self.visit_block(func, block);
+        }
+    }
+
+    fn visit_block(&mut self, func: &Function, block: BasicBlockId) {
+        for inst in func.layout.inst_iter(block) {
+            self.visit(func, func.data.inst_ref(inst));
+        }
+    }
+
+    /// Dispatches `inst` to the method of its opcode.
+    fn visit(&mut self, func: &Function, inst: &Instruction) {
+        match (&inst.operand, inst.opcode) {
+            (Operand::Alloca(op), _) => self.visit_alloca(func, inst, op),
+            (Operand::Phi(op), _) => self.visit_phi(func, inst, op),
+            (Operand::Load(op), _) => self.visit_load(func, inst, op),
+            (Operand::Store(op), _) => self.visit_store(func, inst, op),
+            (Operand::InsertValue(op), _) => self.visit_insert_value(func, inst, op),
+            (Operand::ExtractValue(op), _) => self.visit_extract_value(func, inst, op),
+            (Operand::ExtractElement(op), _) => self.visit_extract_element(func, inst, op),
+            (Operand::InsertElement(op), _) => self.visit_insert_element(func, inst, op),
+            (Operand::ShuffleVector(op), _) => self.visit_shuffle_vector(func, inst, op),
+            (Operand::IntBinary(op), Opcode::Add) => self.visit_add(func, inst, op),
+            (Operand::IntBinary(op), Opcode::Sub) => self.visit_sub(func, inst, op),
+            (Operand::IntBinary(op), Opcode::Mul) => self.visit_mul(func, inst, op),
+            (Operand::IntBinary(op), Opcode::SDiv) => self.visit_sdiv(func, inst, op),
+            (Operand::IntBinary(op), Opcode::SRem) => self.visit_srem(func, inst, op),
+            (Operand::IntBinary(op), Opcode::And) => self.visit_and(func, inst, op),
+            (Operand::IntBinary(op), Opcode::Or) => self.visit_or(func, inst, op),
+            (Operand::IntBinary(op), Opcode::Shl) => self.visit_shl(func, inst, op),
+            (Operand::IntBinary(op), Opcode::LShr) => self.visit_lshr(func, inst, op),
+            (Operand::IntBinary(op), _) => self.visit_int_binary(func, inst, op),
+            (Operand::FloatBinary(op), _) => self.visit_float_binary(func, inst, op),
+            (Operand::FloatUnary(op), _) => self.visit_fneg(func, inst, op),
+            (Operand::ICmp(op), _) => self.visit_icmp(func, inst, op),
+            (Operand::FCmp(op), _) => self.visit_fcmp(func, inst, op),
+            (Operand::Cast(op), Opcode::Sext) => self.visit_sext(func, inst, op),
+            (Operand::Cast(op), Opcode::Zext) => self.visit_zext(func, inst, op),
+            (Operand::Cast(op), Opcode::Bitcast) => self.visit_bitcast(func, inst, op),
+            (Operand::Cast(op), Opcode::Trunc) => self.visit_trunc(func, inst, op),
+            (Operand::Cast(op), Opcode::IntToPtr) => self.visit_int_to_ptr(func, inst, op),
+            (Operand::Cast(op), _) => self.visit_cast(func, inst, op),
+            (Operand::GetElementPtr(op), _) => self.visit_gep(func, inst, op),
+            (Operand::Call(op), _) => self.visit_call(func, inst, op),
+            (Operand::Invoke(op), _) => self.visit_invoke(func, inst, op),
+            (Operand::LandingPad(op), _) => self.visit_landing_pad(func, inst, op),
+            (Operand::Resume(op), _) => self.visit_resume(func, inst, op),
+            (Operand::Br(op), _) => self.visit_br(func, inst, op),
+            (Operand::CondBr(op), _) => self.visit_cond_br(func, inst, op),
+            (Operand::Ret(op), _) => self.visit_ret(func, inst, op),
+            (Operand::Unreachable, _) => self.visit_unreachable(func, inst),
+            (Operand::Invalid, _) => {}
+        }
+    }
+
+    /// Called for every instruction whose method isn't overridden.
+    fn visit_instruction(&mut self, _func: &Function, _inst: &Instruction) {}
+
+    fn visit_alloca(&mut self, func: &Function, inst: &Instruction, _op: &Alloca) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_phi(&mut self, func: &Function, inst: &Instruction, _op: &Phi) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_load(&mut self, func: &Function, inst: &Instruction, _op: &Load) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_store(&mut self, func: &Function, inst: &Instruction, _op: &Store) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_insert_value(&mut self, func: &Function, inst: &Instruction, _op: &InsertValue) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_extract_value(&mut self, func: &Function, inst: &Instruction, _op: &ExtractValue) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_extract_element(&mut self, func: &Function, inst: &Instruction, _op: &ExtractElement) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_insert_element(&mut self, func: &Function, inst: &Instruction, _op: &InsertElement) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_shuffle_vector(&mut self, func: &Function, inst: &Instruction, _op: &ShuffleVector) {
+        self.visit_instruction(func, inst)
+    }
+
+    /// Called for the integer binary operations whose method isn't overridden.
+    fn visit_int_binary(&mut self, func: &Function, inst: &Instruction, _op: &IntBinary) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_add(&mut self, func: &Function, inst: &Instruction, op: &IntBinary) {
+        self.visit_int_binary(func, inst, op)
+    }
+
+    fn visit_sub(&mut self, func: &Function, inst: &Instruction, op: &IntBinary) {
+        self.visit_int_binary(func, inst, op)
+    }
+
+    fn visit_mul(&mut self, func: &Function, inst: &Instruction, op: &IntBinary) {
+        self.visit_int_binary(func, inst, op)
+    }
+
+    fn visit_sdiv(&mut self, func: &Function, inst: &Instruction, op: &IntBinary) {
+        self.visit_int_binary(func, inst, op)
+    }
+
+    fn visit_srem(&mut self, func: &Function, inst: &Instruction, op: &IntBinary) {
+        self.visit_int_binary(func, inst, op)
+    }
+
+    fn visit_and(&mut self, func: &Function, inst: &Instruction, op: &IntBinary) {
+        self.visit_int_binary(func, inst, op)
+    }
+
+    fn visit_or(&mut self, func: &Function, inst: &Instruction, op: &IntBinary) {
+        self.visit_int_binary(func, inst, op)
+    }
+
+    fn visit_shl(&mut self, func: &Function, inst: &Instruction, op: &IntBinary) {
+        self.visit_int_binary(func, inst, op)
+    }
+
+    fn visit_lshr(&mut self, func: &Function, inst: &Instruction, op: &IntBinary) {
+        self.visit_int_binary(func, inst, op)
+    }
+
+    /// Called for `fadd`, `fsub`, `fmul`, `fdiv` and `frem`.
+    fn visit_float_binary(&mut self, func: &Function, inst: &Instruction, _op: &FloatBinary) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_fneg(&mut self, func: &Function, inst: &Instruction, _op: &FloatUnary) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_icmp(&mut self, func: &Function, inst: &Instruction, _op: &ICmp) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_fcmp(&mut self, func: &Function, inst: &Instruction, _op: &FCmp) {
+        self.visit_instruction(func, inst)
+    }
+
+    /// Called for the conversions whose method isn't overridden.
+    fn visit_cast(&mut self, func: &Function, inst: &Instruction, _op: &Cast) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_sext(&mut self, func: &Function, inst: &Instruction, op: &Cast) {
+        self.visit_cast(func, inst, op)
+    }
+
+    fn visit_zext(&mut self, func: &Function, inst: &Instruction, op: &Cast) {
+        self.visit_cast(func, inst, op)
+    }
+
+    fn visit_bitcast(&mut self, func: &Function, inst: &Instruction, op: &Cast) {
+        self.visit_cast(func, inst, op)
+    }
+
+    fn visit_trunc(&mut self, func: &Function, inst: &Instruction, op: &Cast) {
+        self.visit_cast(func, inst, op)
+    }
+
+    fn visit_int_to_ptr(&mut self, func: &Function, inst: &Instruction, op: &Cast) {
+        self.visit_cast(func, inst, op)
+    }
+
+    fn visit_gep(&mut self, func: &Function, inst: &Instruction, _op: &GetElementPtr) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_call(&mut self, func: &Function, inst: &Instruction, _op: &Call) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_landing_pad(&mut self, func: &Function, inst: &Instruction, _op: &LandingPad) {
+        self.visit_instruction(func, inst)
+    }
+
+    /// Called for the terminators whose method isn't overridden.
+    fn visit_terminator(&mut self, func: &Function, inst: &Instruction) {
+        self.visit_instruction(func, inst)
+    }
+
+    fn visit_invoke(&mut self, func: &Function, inst: &Instruction, _op: &Invoke) {
+        self.visit_terminator(func, inst)
+    }
+
+    fn visit_resume(&mut self, func: &Function, inst: &Instruction, _op: &Resume) {
+        self.visit_terminator(func, inst)
+    }
+
+    fn visit_br(&mut self, func: &Function, inst: &Instruction, _op: &Br) {
+        self.visit_terminator(func, inst)
+    }
+
+    fn visit_cond_br(&mut self, func: &Function, inst: &Instruction, _op: &CondBr) {
+        self.visit_terminator(func, inst)
+    }
+
+    fn visit_ret(&mut self, func: &Function, inst: &Instruction, _op: &Ret) {
+        self.visit_terminator(func, inst)
+    }
+
+    fn visit_unreachable(&mut self, func: &Function, inst: &Instruction) {
+        self.visit_terminator(func, inst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[derive(Default)]
+    struct Counts {
+        adds: usize,
+        binaries: usize,
+        casts: usize,
+        terminators: usize,
+        others: usize,
+    }
+
+    impl InstVisitor for Counts {
+        fn visit_instruction(&mut self, _: &Function, _: &Instruction) {
+            self.others += 1;
+        }
+
+        fn visit_add(&mut self, _: &Function, _: &Instruction, _: &IntBinary) {
+            self.adds += 1;
+        }
+
+        fn visit_int_binary(&mut self, _: &Function, _: &Instruction, _: &IntBinary) {
+            self.binaries += 1;
+        }
+
+        fn visit_cast(&mut self, _: &Function, _: &Instruction, _: &Cast) {
+            self.casts += 1;
+        }
+
+        fn visit_terminator(&mut self, _: &Function, _: &Instruction) {
+            self.terminators += 1;
+        }
+    }
+
+    #[test]
+    fn dispatch() {
+        let module = parse_assembly(
+            r#"
+define i64 @f(i32 %0) {
+  %2 = add i32 %0, 1
+  %3 = mul i32 %2, %2
+  %4 = icmp slt i32 %3, 0
+  br i1 %4, label %5, label %7
+5:
+  %6 = sext i32 %3 to i64
+  ret i64 %6
+7:
+  %8 = zext i32 %2 to i64
+  ret i64 %8
+}
+
+define void @g() {
+  ret void
+}"#,
+        )
+        .unwrap();
+        let mut counts = Counts::default();
+        counts.visit_module(&module);
+        assert_eq!(counts.adds, 1);
+        assert_eq!(counts.binaries, 1);
+        assert_eq!(counts.casts, 2);
+        assert_eq!(counts.terminators, 4);
+        assert_eq!(counts.others, 1);
+    }
+}
@@ -0,0 +1,203 @@
+//! Custom target-independent intrinsics.
+//!
+//! An intrinsic is a function with a reserved name (e.g. `vicis.trace`) that
+//! is understood by the tools rather than defined by the program. Register it
+//! once in an [`IntrinsicRegistry`], then:
+//!
+//! - call [`IntrinsicRegistry::declare_missing`] after parsing, so modules may
+//!   call the intrinsic without declaring it;
+//! - give the interpreter its behavior with
+//!   `vicis_interpreter::interpreter::Context::with_intrinsic`;
+//! - call [`IntrinsicRegistry::lower_module`] before code generation to apply
+//!   the default lowering.
+//!
+//! See `interpreter/examples/custom_intrinsic.rs` for a complete example.
+
+use super::{
+    function::{
+        instruction::{Call, InstructionId, Opcode, Operand},
+        Function, Parameter,
+    },
+    module::{linkage::Linkage, name::Name, Module},
+    value::{ConstantData, Value, ValueId},
+};
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+
+/// How an intrinsic is lowered for targets that do not know it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntrinsicLowering {
+    /// Remove the call. Uses of its result become `undef`.
+    Erase,
+    /// Call the given (runtime) function instead.
+    Call(String),
+}
+
+#[derive(Debug, Default)]
+pub struct IntrinsicRegistry {
+    intrinsics: BTreeMap<String, IntrinsicLowering>,
+}
+
+impl IntrinsicRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an intrinsic named `name`. An intrinsic already registered with the same name is replaced.
+    pub fn register<N: Into<String>>(&mut self, name: N, lowering: IntrinsicLowering) {
+        self.intrinsics.insert(name.into(), lowering);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.intrinsics.contains_key(name)
+    }
+
+    pub fn lowering(&self, name: &str) -> Option<&IntrinsicLowering> {
+        self.intrinsics.get(name)
+    }
+
+    /// Returns the registered intrinsic names in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.intrinsics.keys().map(String::as_str)
+    }
+
+    /// Adds a declaration for every registered intrinsic called in `module`
+    /// but not declared there. The signature is taken from the first call site.
+    /// Returns the number of added declarations.
+    pub fn declare_missing(&self, module: &mut Module) -> usize {
+        let mut missing: BTreeMap<String, Function> = BTreeMap::new();
+        for (_, func) in module.functions() {
+            for (_, inst) in calls(func) {
+                let name = match callee_name(func, inst) {
+                    Some(name) => name,
+                    None => continue,
+                };
+                if !self.contains(name)
+                    || missing.contains_key(name)
+                    || module.find_function_by_name(name).is_some()
+                {
+                    continue;
+                }
+                let tys = &func.data.inst_ref(inst).operand.as_call().unwrap().tys;
+                let params = tys[1..].iter().map(|&ty| Parameter::new(ty)).collect();
+                let mut decl = Function::new(name, tys[0], params, false, module.types.clone());
+                decl.linkage = Linkage::External;
+                missing.insert(name.to_owned(), decl);
+            }
+        }
+        let num = missing.len();
+        for (_, decl) in missing {
+            module.add_function(decl);
+        }
+        num
+    }
+
+    /// Replaces calls to registered intrinsics according to their [`IntrinsicLowering`].
+    /// Returns the number of rewritten calls.
+    pub fn lower_module(&self, module: &mut Module) -> usize {
+        let mut num = 0;
+        for (_, func) in module.functions_mut().iter_mut() {
+            let targets: Vec<(ValueId, InstructionId, IntrinsicLowering)> = calls(func)
+                .filter_map(|(callee, inst)| {
+                    let lowering = self.lowering(callee_name(func, inst)?)?;
+                    Some((callee, inst, lowering.clone()))
+                })
+                .collect();
+            for (callee, inst, lowering) in targets {
+                match lowering {
+                    IntrinsicLowering::Erase => {
+                        let undef = func.data.create_value(Value::undef());
+                        func.replace_all_uses_with(inst, undef);
+                        func.erase_instruction(inst);
+                    }
+                    IntrinsicLowering::Call(symbol) => {
+                        *func.data.value_ref_mut(callee) =
+                            Value::Constant(ConstantData::GlobalRef(Name::from(symbol)));
+                    }
+                }
+                num += 1;
+            }
+        }
+
+        // Declarations of intrinsics lowered to calls now stand for the runtime functions.
+        for (name, lowering) in &self.intrinsics {
+            if let IntrinsicLowering::Call(symbol) = lowering {
+                if module.find_function_by_name(symbol).is_some() {
+                    continue;
+                }
+                if let Some(id) = module.find_function_by_name(name) {
+                    module.functions_mut()[id].name = symbol.to_owned();
+                }
+            }
+        }
+
+        num
+    }
+}
+
+/// Returns the value ids of the callees and the call instructions in `func`.
+fn calls(func: &Function) -> impl Iterator<Item = (ValueId, InstructionId)> + '_ {
+    func.layout
+        .block_iter()
+        .flat_map(move |block| func.layout.inst_iter(block))
+        .filter_map(move |inst| match &func.data.inst_ref(inst).operand {
+            Operand::Call(Call { args, .. }) if func.data.inst_ref(inst).opcode == Opcode::Call => {
+                Some((args[0], inst))
+            }
+            _ => None,
+        })
+}
+
+fn callee_name(func: &Function, inst: InstructionId) -> Option<&str> {
+    let callee = func.data.inst_ref(inst).operand.as_call()?.args[0];
+    match func.data.value_ref(callee) {
+        Value::Constant(ConstantData::GlobalRef(Name::Name(name))) => Some(name),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    const SOURCE: &str = r#"
+define i32 @main() {
+  call void @vicis.trace(i32 1)
+  %1 = call i32 @vicis.probe(i32 2)
+  %2 = add i32 %1, 1
+  call void @vicis.trace(i32 %2)
+  ret i32 %2
+}"#;
+
+    fn registry() -> IntrinsicRegistry {
+        let mut registry = IntrinsicRegistry::new();
+        registry.register(
+            "vicis.trace",
+            IntrinsicLowering::Call("__vicis_trace".to_string()),
+        );
+        registry.register("vicis.probe", IntrinsicLowering::Erase);
+        registry
+    }
+
+    #[test]
+    fn declare_missing() {
+        let mut module = parse_assembly(SOURCE).unwrap();
+        assert_eq!(registry().declare_missing(&mut module), 2);
+        assert_eq!(registry().declare_missing(&mut module), 0);
+        let trace = module.find_function_by_name("vicis.trace").unwrap();
+        assert_eq!(
+            format!("{:?}", module.functions()[trace]),
+            "declare external dso_local default void @vicis.trace(i32 %0) \n"
+        );
+    }
+
+    #[test]
+    fn lower_module() {
+        let mut module = parse_assembly(SOURCE).unwrap();
+        let registry = registry();
+        registry.declare_missing(&mut module);
+        assert_eq!(registry.lower_module(&mut module), 3);
+        insta::assert_debug_snapshot!(module);
+    }
+}
@@ -1,5 +1,9 @@
 pub mod function;
+pub mod intrinsic;
 pub mod module;
+#[cfg(feature = "serde")]
+pub mod serialize;
+pub mod symbol;
 pub mod types;
 pub mod util;
 pub mod value;
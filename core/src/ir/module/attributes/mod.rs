@@ -1,8 +1,10 @@
 pub mod parser;
 
-use std::fmt;
+use crate::ir::symbol::Symbol;
+use core::fmt;
 
 #[derive(PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Attribute {
     AlignStack(u64),
     AllocSize {
@@ -55,8 +57,8 @@ pub enum Attribute {
     StrictFP,
     UWTable,
     StringAttribute {
-        kind: String,
-        value: String,
+        kind: Symbol,
+        value: Symbol,
     },
     Ref(u32),
     // UnknownAttribute,
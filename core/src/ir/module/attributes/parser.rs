@@ -1,5 +1,6 @@
 use super::Attribute;
 use crate::ir::util::{spaces, string_literal};
+use crate::prelude::*;
 use nom::{
     branch::alt,
     bytes::complete::tag,
@@ -72,11 +73,14 @@ pub fn parse_attribute(source: &str) -> IResult<&str, Attribute, VerboseError<&s
             // map(tag("unknownattribute"), |_| Attribute::UnknownAttribute),
             map(
                 tuple((string_literal, spaces, char('='), spaces, string_literal)),
-                |(kind, _, _, _, value)| Attribute::StringAttribute { kind, value },
+                |(kind, _, _, _, value)| Attribute::StringAttribute {
+                    kind: kind.into(),
+                    value: value.into(),
+                },
             ),
             map(string_literal, |kind| Attribute::StringAttribute {
-                kind,
-                value: "".to_string(),
+                kind: kind.into(),
+                value: "".into(),
             }),
             map(preceded(char('#'), digit1), |num: &str| {
                 Attribute::Ref(num.parse::<u32>().unwrap())
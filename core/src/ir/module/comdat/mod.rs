@@ -0,0 +1,29 @@
+pub mod parser;
+
+pub use parser::{parse, parse_comdat_ref};
+
+use core::fmt;
+
+/// How the linker selects the definition kept among those of a comdat in
+/// several objects, as in `$name = comdat any`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SelectionKind {
+    Any,
+    ExactMatch,
+    Largest,
+    NoDeduplicate,
+    SameSize,
+}
+
+impl fmt::Debug for SelectionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::ExactMatch => write!(f, "exactmatch"),
+            Self::Largest => write!(f, "largest"),
+            Self::NoDeduplicate => write!(f, "nodeduplicate"),
+            Self::SameSize => write!(f, "samesize"),
+        }
+    }
+}
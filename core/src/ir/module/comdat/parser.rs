@@ -0,0 +1,45 @@
+use super::SelectionKind;
+use crate::ir::{module::name, util::spaces};
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::char,
+    combinator::{map, opt},
+    error::VerboseError,
+    sequence::{delimited, preceded, tuple},
+    IResult,
+};
+
+/// Parses a comdat, i.e. `$name = comdat <SelectionKind>`.
+pub fn parse(source: &str) -> IResult<&str, (name::Name, SelectionKind), VerboseError<&str>> {
+    let (source, name) = preceded(char('$'), name::parse)(source)?;
+    let (source, _) = tuple((spaces, char('='), spaces, tag("comdat"), spaces))(source)?;
+    let (source, kind) = alt((
+        map(tag("any"), |_| SelectionKind::Any),
+        map(tag("exactmatch"), |_| SelectionKind::ExactMatch),
+        map(tag("largest"), |_| SelectionKind::Largest),
+        map(tag("nodeduplicate"), |_| SelectionKind::NoDeduplicate),
+        map(tag("noduplicates"), |_| SelectionKind::NoDeduplicate),
+        map(tag("samesize"), |_| SelectionKind::SameSize),
+    ))(source)?;
+    Ok((source, (name, kind)))
+}
+
+/// Parses the comdat a global value belongs to, i.e. `comdat [($name)]`.
+/// Without a name, the comdat is named after the global value `global`.
+pub fn parse_comdat_ref(
+    global: name::Name,
+) -> impl Fn(&str) -> IResult<&str, name::Name, VerboseError<&str>> {
+    move |source| {
+        let (source, _) = tag("comdat")(source)?;
+        let (source, name) = opt(preceded(
+            spaces,
+            delimited(
+                char('('),
+                preceded(spaces, preceded(char('$'), name::parse)),
+                preceded(spaces, char(')')),
+            ),
+        ))(source)?;
+        Ok((source, name.unwrap_or(global)))
+    }
+}
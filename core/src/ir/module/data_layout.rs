@@ -0,0 +1,178 @@
+//! Target data layouts, as given by `target datalayout = "..."`.
+//!
+//! Only what is needed to lay out types is kept: the endianness, the size and
+//! alignment of pointers in address space 0, the alignments of integers and
+//! floating-point types and the natural alignment of the stack. Other specifications are ignored.
+
+use crate::ir::types::{Type, Types};
+use crate::prelude::*;
+use core::{error::Error, fmt, str::FromStr};
+
+/// Sizes and alignments are in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataLayout {
+    pub big_endian: bool,
+    pub pointer_size: u32,
+    pub pointer_align: u32,
+    /// 0 if unspecified.
+    pub stack_align: u32,
+    /// ABI alignments of integers by bit width, sorted by width.
+    int_aligns: Vec<(u32, u32)>,
+    /// ABI alignments of floating-point types by bit width.
+    float_aligns: Vec<(u32, u32)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataLayoutError {
+    /// The malformed specification.
+    pub spec: String,
+}
+
+impl Default for DataLayout {
+    /// The layout of x86-64, used for modules without a `target datalayout`.
+    fn default() -> Self {
+        Self {
+            big_endian: false,
+            pointer_size: 8,
+            pointer_align: 8,
+            stack_align: 16,
+            int_aligns: vec![(1, 1), (8, 1), (16, 2), (32, 4), (64, 8), (128, 16)],
+            float_aligns: vec![(32, 4), (64, 8)],
+        }
+    }
+}
+
+impl FromStr for DataLayout {
+    type Err = DataLayoutError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut layout = Self::default();
+        for spec in s.split('-').filter(|spec| !spec.is_empty()) {
+            let fields: Vec<&str> = spec[1..].split(':').collect();
+            let bytes = |i: usize| -> Result<u32, DataLayoutError> {
+                let bits: Option<u32> = fields.get(i).and_then(|field| field.parse().ok());
+                bits.map(|bits| bits / 8).ok_or_else(|| DataLayoutError {
+                    spec: spec.to_string(),
+                })
+            };
+            match spec.as_bytes()[0] {
+                b'e' => layout.big_endian = false,
+                b'E' => layout.big_endian = true,
+                b'p' if fields[0].is_empty() || fields[0] == "0" => {
+                    layout.pointer_size = bytes(1)?;
+                    layout.pointer_align = bytes(2)?;
+                }
+                b'i' => {
+                    let bits = bytes(0)? * 8;
+                    let align = bytes(1)?;
+                    match layout.int_aligns.binary_search_by_key(&bits, |&(b, _)| b) {
+                        Ok(i) => layout.int_aligns[i].1 = align,
+                        Err(i) => layout.int_aligns.insert(i, (bits, align)),
+                    }
+                }
+                b'f' => {
+                    let bits = bytes(0)? * 8;
+                    let align = bytes(1)?;
+                    match layout.float_aligns.iter_mut().find(|(b, _)| *b == bits) {
+                        Some(entry) => entry.1 = align,
+                        None => layout.float_aligns.push((bits, align)),
+                    }
+                }
+                b'S' => layout.stack_align = bytes(0)?,
+                _ => {}
+            }
+        }
+        Ok(layout)
+    }
+}
+
+impl DataLayout {
+    /// Returns the ABI alignment of integers of `bits` bits: that of the
+    /// smallest specified width at least as wide, or of the widest one.
+    pub fn int_align(&self, bits: u32) -> u32 {
+        self.int_aligns
+            .iter()
+            .find(|&&(b, _)| b >= bits)
+            .or(self.int_aligns.last())
+            .map_or(1, |&(_, align)| align)
+    }
+
+    /// Returns the ABI alignment of floating-point types of `bits` bits, or
+    /// their size if unspecified.
+    pub fn float_align(&self, bits: u32) -> u32 {
+        self.float_aligns
+            .iter()
+            .find(|&&(b, _)| b == bits)
+            .map_or(bits / 8, |&(_, align)| align)
+    }
+
+    /// Returns the ABI alignment of `ty`.
+    pub fn align_of(&self, types: &Types, ty: Type) -> u32 {
+        types.size_and_align(ty, self).1
+    }
+
+    /// Returns the size of `ty` including the padding up to its alignment,
+    /// i.e. the distance between consecutive elements of an array of `ty`.
+    pub fn size_of(&self, types: &Types, ty: Type) -> u32 {
+        types.size_and_align(ty, self).0
+    }
+
+    /// Returns the offset of the `index`-th field of the struct `ty`.
+    pub fn field_offset(&self, types: &Types, ty: Type, index: usize) -> Option<u32> {
+        types.field_offsets(ty, self).nth(index)
+    }
+}
+
+impl fmt::Display for DataLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "malformed data layout specification `{}`", self.spec)
+    }
+}
+
+impl Error for DataLayoutError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn parse_and_lay_out() {
+        let module = parse_assembly(
+            r#"
+target datalayout = "E-p:32:32-i64:32-f64:32:64-S64"
+%struct.S = type { i8, i64, i16 }
+%struct.P = type <{ i8, i64, i16 }>
+%struct.D = type { i8, double, float }
+"#,
+        )
+        .unwrap();
+        let dl: DataLayout = module.target().datalayout().parse().unwrap();
+        assert!(dl.big_endian);
+        assert_eq!(
+            (dl.pointer_size, dl.pointer_align, dl.stack_align),
+            (4, 4, 8)
+        );
+
+        let types = &module.types;
+        let s = types.base().get_struct("struct.S").unwrap();
+        let p = types.base().get_struct("struct.P").unwrap();
+        assert_eq!(dl.align_of(types, s), 4);
+        assert_eq!(dl.field_offset(types, s, 1), Some(4));
+        assert_eq!(dl.size_of(types, s), 16);
+        assert_eq!(dl.align_of(types, p), 1);
+        assert_eq!(dl.field_offset(types, p, 2), Some(9));
+        assert_eq!(dl.size_of(types, p), 11);
+        let d = types.base().get_struct("struct.D").unwrap();
+        assert_eq!(dl.field_offset(types, d, 1), Some(4));
+        assert_eq!(dl.size_of(types, d), 16);
+
+        let x86_64 = DataLayout::default();
+        assert_eq!(x86_64.field_offset(types, s, 2), Some(16));
+        assert_eq!(x86_64.size_of(types, s), 24);
+        assert_eq!(x86_64.field_offset(types, d, 2), Some(16));
+        assert_eq!(x86_64.size_of(types, d), 24);
+
+        assert!("i64:x".parse::<DataLayout>().is_err());
+    }
+}
@@ -0,0 +1,323 @@
+//! Structural diffs of modules.
+//!
+//! [`diff_modules`] tells how a module differs from another one, e.g. before
+//! and after a pass. Functions and global variables are matched by name, and
+//! the instructions of functions found in both are matched in order, as
+//! `diff` matches lines. Numbers of unnamed values and blocks are ignored
+//! when matching, so that an instruction inserted early in a function does
+//! not make every later one look changed.
+//!
+//! A removed instruction directly followed by an added one is reported as a
+//! change of the former into the latter.
+
+use super::Module;
+use crate::ir::function::{hash::structurally_equal, Function};
+use crate::prelude::*;
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineDiff {
+    Added(String),
+    Removed(String),
+    Changed(String, String),
+}
+
+/// A difference in an instruction, or in the signature if `block` is empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstDiff {
+    /// The label of the block, as printed in the second module if the
+    /// instruction was added there, and in the first one otherwise.
+    pub block: String,
+    pub diff: LineDiff,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FunctionDiff {
+    Added(String),
+    Removed(String),
+    Changed { name: String, insts: Vec<InstDiff> },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleDiff {
+    pub global_variables: Vec<LineDiff>,
+    pub functions: Vec<FunctionDiff>,
+}
+
+impl ModuleDiff {
+    pub fn is_empty(&self) -> bool {
+        self.global_variables.is_empty() && self.functions.is_empty()
+    }
+}
+
+/// Returns how `b` differs from `a`.
+pub fn diff_modules(a: &Module, b: &Module) -> ModuleDiff {
+    let mut diff = ModuleDiff::default();
+
+    let mut names: Vec<_> = a.global_variables.keys().collect();
+    names.extend(
+        b.global_variables
+            .keys()
+            .filter(|name| !a.global_variables.contains_key(*name)),
+    );
+    names.sort_by_key(|name| format!("{:?}", name));
+    for name in names {
+        let gv_a = a
+            .global_variables
+            .get(name)
+            .map(|gv| gv.to_string(&a.types));
+        let gv_b = b
+            .global_variables
+            .get(name)
+            .map(|gv| gv.to_string(&b.types));
+        match (gv_a, gv_b) {
+            (Some(x), Some(y)) if x == y => {}
+            (Some(x), Some(y)) => diff.global_variables.push(LineDiff::Changed(x, y)),
+            (Some(x), None) => diff.global_variables.push(LineDiff::Removed(x)),
+            (None, Some(y)) => diff.global_variables.push(LineDiff::Added(y)),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    for (_, func_a) in &a.functions {
+        match b.find_function_by_name(&func_a.name) {
+            None => diff
+                .functions
+                .push(FunctionDiff::Removed(func_a.name.clone())),
+            Some(id) if structurally_equal(func_a, &b.functions[id]) => {}
+            Some(id) => {
+                let insts = diff_functions(func_a, &b.functions[id]);
+                if !insts.is_empty() {
+                    diff.functions.push(FunctionDiff::Changed {
+                        name: func_a.name.clone(),
+                        insts,
+                    })
+                }
+            }
+        }
+    }
+    for (_, func_b) in &b.functions {
+        if a.find_function_by_name(&func_b.name).is_none() {
+            diff.functions
+                .push(FunctionDiff::Added(func_b.name.clone()));
+        }
+    }
+
+    diff
+}
+
+/// A printed line of a function.
+struct Line {
+    text: String,
+    /// `text` without the numbers of unnamed values and blocks.
+    key: String,
+    block: String,
+}
+
+fn lines(func: &Function) -> Vec<Line> {
+    let printed = format!("{:?}", func);
+    let mut lines = vec![];
+    let mut block = String::new();
+    for text in printed.lines() {
+        if text == "}" || text.is_empty() {
+            continue;
+        }
+        if !text.starts_with(' ') && text.ends_with(':') {
+            block = text.trim_end_matches(':').to_string();
+            continue;
+        }
+        lines.push(Line {
+            text: text.trim().to_string(),
+            key: normalize(text),
+            block: block.clone(),
+        });
+    }
+    lines
+}
+
+/// Replaces `%N` with `%_`.
+fn normalize(line: &str) -> String {
+    let mut normalized = String::with_capacity(line.len());
+    let mut chars = line.trim().chars().peekable();
+    while let Some(c) = chars.next() {
+        normalized.push(c);
+        if c == '%' && chars.peek().is_some_and(char::is_ascii_digit) {
+            while chars.peek().is_some_and(char::is_ascii_digit) {
+                chars.next();
+            }
+            normalized.push('_');
+        }
+    }
+    normalized
+}
+
+fn diff_functions(a: &Function, b: &Function) -> Vec<InstDiff> {
+    let (a, b) = (lines(a), lines(b));
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..]
+    // and b[j..].
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i].key == b[j].key {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diffs = vec![];
+    let (mut removed, mut added) = (vec![], vec![]);
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i].key == b[j].key {
+            flush(&mut diffs, &mut removed, &mut added);
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            added.push(&b[j]);
+            j += 1;
+        } else {
+            removed.push(&a[i]);
+            i += 1;
+        }
+    }
+    flush(&mut diffs, &mut removed, &mut added);
+    diffs
+}
+
+/// Reports a run of removed and added lines, pairing them up as changes.
+fn flush(diffs: &mut Vec<InstDiff>, removed: &mut Vec<&Line>, added: &mut Vec<&Line>) {
+    let num_changed = removed.len().min(added.len());
+    for (x, y) in removed.iter().zip(added.iter()) {
+        diffs.push(InstDiff {
+            block: x.block.clone(),
+            diff: LineDiff::Changed(x.text.clone(), y.text.clone()),
+        });
+    }
+    for x in &removed[num_changed..] {
+        diffs.push(InstDiff {
+            block: x.block.clone(),
+            diff: LineDiff::Removed(x.text.clone()),
+        });
+    }
+    for y in &added[num_changed..] {
+        diffs.push(InstDiff {
+            block: y.block.clone(),
+            diff: LineDiff::Added(y.text.clone()),
+        });
+    }
+    removed.clear();
+    added.clear();
+}
+
+impl fmt::Display for LineDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added(y) => writeln!(f, "+ {}", y),
+            Self::Removed(x) => writeln!(f, "- {}", x),
+            Self::Changed(x, y) => writeln!(f, "- {}\n+ {}", x, y),
+        }
+    }
+}
+
+impl fmt::Display for ModuleDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diff in &self.global_variables {
+            write!(f, "{}", diff)?;
+        }
+        for diff in &self.functions {
+            match diff {
+                FunctionDiff::Added(name) => writeln!(f, "+ function @{}", name)?,
+                FunctionDiff::Removed(name) => writeln!(f, "- function @{}", name)?,
+                FunctionDiff::Changed { name, insts } => {
+                    writeln!(f, "function @{}:", name)?;
+                    let mut block = None;
+                    for inst in insts {
+                        if block != Some(&inst.block) && !inst.block.is_empty() {
+                            writeln!(f, "{}:", inst.block)?;
+                        }
+                        block = Some(&inst.block);
+                        write!(f, "{}", inst.diff)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn diff() {
+        let a = parse_assembly(
+            r#"
+@g = global i32 0
+@h = global i32 1
+
+define i32 @f(i32 %x) {
+entry:
+  %1 = add i32 %x, 1
+  %2 = mul i32 %1, 2
+  %3 = sub i32 %2, %x
+  ret i32 %3
+}
+
+define i32 @same(i32 %x) {
+  ret i32 %x
+}
+
+define i32 @old() {
+  ret i32 0
+}
+"#,
+        )
+        .unwrap();
+        let b = parse_assembly(
+            r#"
+@g = global i32 2
+@i = global i32 1
+
+define i32 @f(i32 %x) {
+entry:
+  %y = load i32, i32* @g
+  %1 = add i32 %x, %y
+  %2 = mul i32 %1, 2
+  %3 = sub i32 %2, %x
+  ret i32 %3
+}
+
+define i32 @same(i32 %a) {
+  ret i32 %a
+}
+
+define i32 @new() {
+  ret i32 0
+}
+"#,
+        )
+        .unwrap();
+
+        let diff = diff_modules(&a, &b);
+        assert_eq!(
+            diff.to_string(),
+            "- @g = global i32 0\n\
+             + @g = global i32 2\n\
+             - @h = global i32 1\n\
+             + @i = global i32 1\n\
+             function @f:\n\
+             entry:\n\
+             - %0 = add i32 %x, 1\n\
+             + %y = load i32, i32* @g\n\
+             + %0 = add i32 %x, %y\n\
+             - function @old\n\
+             + function @new\n"
+        );
+        assert!(diff_modules(&a, &a).is_empty());
+    }
+}
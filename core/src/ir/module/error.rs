@@ -0,0 +1,123 @@
+use crate::prelude::*;
+use core::{error::Error, fmt};
+use nom::error::{VerboseError, VerboseErrorKind};
+#[cfg(feature = "std")]
+use std::io;
+
+/// An error produced while parsing LLVM Assembly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-origin line number where the error was found.
+    pub line: usize,
+    /// 1-origin column number (in characters) where the error was found.
+    pub column: usize,
+    /// The whole source line containing the error.
+    pub snippet: String,
+    /// What the parser was trying to parse (e.g. `function`, `instruction`).
+    pub construct: &'static str,
+}
+
+impl ParseError {
+    /// Creates a `ParseError` pointing at `rest`, which must be a suffix of `source`.
+    pub fn new(source: &str, rest: &str, construct: &'static str) -> Self {
+        let offset = source.len().saturating_sub(rest.len());
+        let consumed = &source[..offset];
+        let line_start = consumed.rfind('\n').map_or(0, |i| i + 1);
+        let line = consumed.matches('\n').count() + 1;
+        let column = consumed[line_start..].chars().count() + 1;
+        let snippet = source[line_start..]
+            .lines()
+            .next()
+            .unwrap_or("")
+            .to_string();
+        Self {
+            line,
+            column,
+            snippet,
+            construct,
+        }
+    }
+
+    /// Creates a `ParseError` from the innermost location recorded in `err`.
+    /// Falls back to `rest` and `construct` if `err` carries no location.
+    pub fn from_nom(
+        source: &str,
+        rest: &str,
+        construct: &'static str,
+        err: nom::Err<VerboseError<&str>>,
+    ) -> Self {
+        let errors = match err {
+            nom::Err::Error(e) | nom::Err::Failure(e) => e.errors,
+            nom::Err::Incomplete(_) => vec![],
+        };
+        let construct = errors
+            .iter()
+            .find_map(|(_, kind)| match kind {
+                VerboseErrorKind::Context(ctx) => Some(*ctx),
+                _ => None,
+            })
+            .unwrap_or(construct);
+        let rest = errors.first().map_or(rest, |(rest, _)| rest);
+        Self::new(source, rest, construct)
+    }
+}
+
+/// An error produced while reading LLVM Assembly from an [`io::Read`].
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl Error for ParseError {}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lineno = self.line.to_string();
+        let pad = " ".repeat(lineno.len());
+        let caret = " ".repeat(self.column - 1);
+        writeln!(
+            f,
+            "{}:{}: failed to parse {}",
+            self.line, self.column, self.construct
+        )?;
+        writeln!(f, "{} |", pad)?;
+        writeln!(f, "{} | {}", lineno, self.snippet)?;
+        write!(f, "{} | {}^", pad, caret)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<ParseError> for ReadError {
+    fn from(err: ParseError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Parse(err) => Some(err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "failed to read assembly: {}", err),
+            Self::Parse(err) => write!(f, "{}", err),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Extracting functions into a module of their own, after `llvm-extract`.
+//!
+//! [`Module::extract_functions`] copies the chosen function definitions into
+//! a new module, along with what they need to be valid: the global variables
+//! they refer to, directly or through initializers, and declarations of the
+//! other functions they refer to. Only the types and the numbered metadata
+//! in use are copied over, so that a single function can be isolated from a
+//! huge module, e.g. to reduce a bug.
+
+use super::{linkage::Linkage, name::Name, Module};
+use crate::collections::FxHashSet;
+use crate::prelude::*;
+use crate::{
+    ir::function::{
+        clone::{clone_function, ValueMap},
+        Function,
+    },
+    pass::transform::{global_dce::for_each_global_ref_in, strip},
+};
+use core::{error::Error, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtractError {
+    /// No function of the name is defined in the module.
+    UnknownFunction(String),
+}
+
+impl Module {
+    /// Returns a new module containing the definitions of the functions
+    /// named `names`, and the globals they refer to.
+    pub fn extract_functions<N: AsRef<str>>(&self, names: &[N]) -> Result<Module, ExtractError> {
+        let mut extracted = FxHashSet::default();
+        for name in names {
+            let name = name.as_ref();
+            match self.find_function_by_name(name) {
+                Some(id) if !self.functions[id].is_prototype() => {
+                    extracted.insert(name.to_string());
+                }
+                _ => return Err(ExtractError::UnknownFunction(name.to_string())),
+            }
+        }
+
+        let mut referred = FxHashSet::default();
+        let mut worklist: Vec<Name> = extracted
+            .iter()
+            .map(|name| Name::from(name.as_str()))
+            .collect();
+        while let Some(name) = worklist.pop() {
+            if !referred.insert(name) {
+                continue;
+            }
+            let mut refer = |name: &Name| worklist.push(*name);
+            if let Some(gv) = self.global_variables.get(&name) {
+                if let Some(init) = &gv.init {
+                    init.for_each_global_ref(&mut refer);
+                }
+            } else if let Name::Name(name) = &name {
+                if extracted.contains(name.as_str()) {
+                    let id = self.find_function_by_name(name).unwrap();
+                    for_each_global_ref_in(&self.functions[id], &mut refer);
+                }
+            }
+        }
+
+        let mut module = Module {
+            name: self.name.clone(),
+            source_filename: self.source_filename.clone(),
+            target: self.target.clone(),
+            attributes: self.attributes.clone(),
+            types: self.types.clone(),
+            metas: self.metas.clone(),
+            comdats: self.comdats.clone(),
+            ..Module::default()
+        };
+        for (name, gv) in &self.global_variables {
+            if referred.contains(name) {
+                module.global_variables.insert(*name, gv.clone());
+            }
+        }
+        for (_, func) in &self.functions {
+            if extracted.contains(&func.name) {
+                module
+                    .functions
+                    .alloc(clone_function(func, &ValueMap::new()));
+            } else if referred.contains(&Name::from(func.name.as_str())) {
+                module.functions.alloc(declaration_of(func));
+            }
+        }
+        strip::remove_unreferenced_metadata(&mut module);
+
+        // Linking into an empty module copies over only the types in use.
+        let mut new = Module::new();
+        new.link(module)
+            .expect("linking into an empty module never fails");
+        new.name = self.name.clone();
+        new.source_filename = self.source_filename.clone();
+        Ok(new)
+    }
+}
+
+/// Returns `func` without its body, as declared in another module.
+pub(super) fn declaration_of(func: &Function) -> Function {
+    let mut decl = Function::new(
+        &func.name,
+        func.result_ty,
+        func.params.clone(),
+        func.is_var_arg,
+        func.types.clone(),
+    );
+    decl.linkage = match func.linkage {
+        Linkage::Internal | Linkage::Private => Linkage::External,
+        linkage => linkage,
+    };
+    decl.preemption_specifier = func.preemption_specifier;
+    decl.visibility = func.visibility;
+    decl.unnamed_addr = func.unnamed_addr;
+    decl.func_attrs = func.func_attrs.clone();
+    decl.ret_attrs = func.ret_attrs.clone();
+    decl
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFunction(name) => write!(f, "no function named `{}` is defined", name),
+        }
+    }
+}
+
+impl Error for ExtractError {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn extract() {
+        let module = parse_assembly(
+            r#"
+%struct.S = type { i32, %struct.S* }
+%struct.Unused = type { i64 }
+
+@g = global i32 1
+@p = internal global i32* @g
+@unused = global i32 2
+
+define internal i32 @helper(i32 %x) {
+  ret i32 %x
+}
+
+define i32 @f(%struct.S* %s) {
+  %1 = load i32*, i32** @p
+  %2 = load i32, i32* %1
+  %3 = call i32 @helper(i32 %2), !dbg !1
+  ret i32 %3
+}
+
+define i32 @main() {
+  %1 = call i32 @f(%struct.S* null)
+  ret i32 %1
+}
+
+!0 = !{!"unused"}
+!1 = !{i32 1, i32 2}
+"#,
+        )
+        .unwrap();
+
+        let extracted = module.extract_functions(&["f"]).unwrap();
+        assert!(extracted.find_function_by_name("main").is_none());
+        let helper = extracted.find_function_by_name("helper").unwrap();
+        assert!(extracted.functions()[helper].is_prototype());
+        assert!(matches!(
+            extracted.functions()[helper].linkage,
+            Linkage::External
+        ));
+        let f = extracted.find_function_by_name("f").unwrap();
+        assert!(!extracted.functions()[f].is_prototype());
+
+        let mut gvs: Vec<_> = extracted
+            .global_variables()
+            .keys()
+            .map(|name| format!("{:?}", name))
+            .collect();
+        gvs.sort();
+        assert_eq!(gvs, vec!["g", "p"]);
+        assert!(extracted.types.base().get_struct("struct.S").is_some());
+        assert!(extracted.types.base().get_struct("struct.Unused").is_none());
+        assert_eq!(extracted.metas.len(), 1);
+
+        assert_eq!(
+            module.extract_functions(&["helper", "nothing"]).err(),
+            Some(ExtractError::UnknownFunction("nothing".into()))
+        );
+    }
+}
@@ -0,0 +1,368 @@
+//! Generating random modules, for fuzzing.
+//!
+//! [`generate`] turns arbitrary bytes, e.g. from a fuzzer, into a module that
+//! is type-correct and well-formed: every value is defined before it is used
+//! and dominates its uses, and every block ends with a terminator. Blocks
+//! only branch forward and functions only call functions defined before them,
+//! so that running any generated function terminates.
+//!
+//! Values flow between blocks through stack slots allocated in the entry
+//! block, as in unoptimized code, rather than through phis. The types and
+//! opcodes used are chosen with a [`GenConfig`].
+//!
+//! With the `arbitrary` feature, [`ArbitraryModule`] implements
+//! `arbitrary::Arbitrary`.
+
+use super::{linkage::Linkage, name::Name, Module};
+use crate::collections::FxHashMap;
+use crate::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        builder::Builder,
+        instruction::{ICmpCond, IntBinary, Opcode, Operand},
+        Parameter,
+    },
+    types::{Type, I1, I16, I32, I64, I8},
+    value::{ConstantInt, Value, ValueId},
+};
+use crate::prelude::*;
+
+/// What [`generate`] may use. The vectors must not be empty.
+#[derive(Debug, Clone)]
+pub struct GenConfig {
+    pub max_functions: usize,
+    pub max_params: usize,
+    /// The maximum number of blocks in a function.
+    pub max_blocks: usize,
+    /// The maximum number of instructions in a block, besides its terminator.
+    pub max_insts: usize,
+    /// The maximum number of stack slots in a function.
+    pub max_slots: usize,
+    /// The types of parameters, results and slots.
+    pub int_types: Vec<Type>,
+    /// The opcodes of instructions other than terminators and allocas.
+    pub opcodes: Vec<Opcode>,
+}
+
+/// A module generated from arbitrary data.
+#[derive(Debug)]
+pub struct ArbitraryModule(pub Module);
+
+impl Default for GenConfig {
+    fn default() -> Self {
+        Self {
+            max_functions: 3,
+            max_params: 3,
+            max_blocks: 4,
+            max_insts: 8,
+            max_slots: 3,
+            int_types: vec![I8, I32, I64],
+            opcodes: GenConfig::SUPPORTED_OPCODES.to_vec(),
+        }
+    }
+}
+
+impl GenConfig {
+    pub const SUPPORTED_OPCODES: [Opcode; 15] = [
+        Opcode::Add,
+        Opcode::Sub,
+        Opcode::Mul,
+        Opcode::SDiv,
+        Opcode::SRem,
+        Opcode::And,
+        Opcode::Or,
+        Opcode::Shl,
+        Opcode::LShr,
+        Opcode::ICmp,
+        Opcode::Sext,
+        Opcode::Zext,
+        Opcode::Trunc,
+        Opcode::Load,
+        Opcode::Store,
+    ];
+}
+
+/// Returns a module generated from `data`. The same data always gives the
+/// same module, and data running out reads as zeros.
+pub fn generate(config: &GenConfig, data: &[u8]) -> Module {
+    let mut gen = Gen { config, data };
+    let mut module = Module::new();
+    let mut signatures: Vec<(String, Type, Vec<Type>)> = vec![];
+    let num_functions = 1 + gen.below(config.max_functions.max(1));
+    for i in 0..num_functions {
+        let name = format!("f{}", i);
+        let result_ty = gen.int_type();
+        let params: Vec<Type> = (0..gen.below(config.max_params + 1))
+            .map(|_| gen.int_type())
+            .collect();
+        let id = module.create_function(
+            &name,
+            result_ty,
+            params
+                .iter()
+                .enumerate()
+                .map(|(i, &ty)| Parameter {
+                    name: Name::Number(i),
+                    ty,
+                    attrs: vec![],
+                })
+                .collect(),
+            false,
+        );
+        let func = &mut module.functions[id];
+        func.linkage = Linkage::External;
+        gen.body(&mut Builder::new(func), &params, &signatures);
+        signatures.push((name, result_ty, params));
+    }
+    module
+}
+
+struct Gen<'a> {
+    config: &'a GenConfig,
+    data: &'a [u8],
+}
+
+/// The values usable at the current position, by type.
+type Pool = FxHashMap<Type, Vec<ValueId>>;
+
+impl Gen<'_> {
+    /// Returns a number below `n`, or 0 if `n` is 0.
+    fn below(&mut self, n: usize) -> usize {
+        if n <= 1 {
+            return 0;
+        }
+        let len = if n <= 0x100 { 1 } else { 4 };
+        let mut bytes = [0; 4];
+        for byte in bytes.iter_mut().take(len) {
+            if let Some((&first, rest)) = self.data.split_first() {
+                *byte = first;
+                self.data = rest;
+            }
+        }
+        u32::from_le_bytes(bytes) as usize % n
+    }
+
+    fn choose<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+
+    fn int_type(&mut self) -> Type {
+        let tys = self.config.int_types.clone();
+        self.choose(&tys)
+    }
+
+    fn constant(&mut self, builder: &mut Builder, ty: Type) -> ValueId {
+        let n = self.below(0x100) as i64 - 0x80;
+        let konst = match ty {
+            I1 => ConstantInt::Int1(n & 1 == 1),
+            I8 => ConstantInt::Int8(n as i8),
+            I16 => ConstantInt::Int16(n as i16),
+            I32 => ConstantInt::Int32(n as i32),
+            I64 => ConstantInt::Int64(n),
+            _ => panic!("unsupported type"),
+        };
+        builder.value(konst)
+    }
+
+    /// Returns a value of `ty`, defined before or constant.
+    fn operand(&mut self, builder: &mut Builder, pool: &Pool, ty: Type) -> ValueId {
+        match pool.get(&ty) {
+            Some(vals) if !vals.is_empty() && self.below(4) != 0 => self.choose(vals),
+            _ => self.constant(builder, ty),
+        }
+    }
+
+    fn body(
+        &mut self,
+        builder: &mut Builder,
+        params: &[Type],
+        callees: &[(String, Type, Vec<Type>)],
+    ) {
+        let blocks: Vec<BasicBlockId> = (0..1 + self.below(self.config.max_blocks.max(1)))
+            .map(|_| builder.create_block())
+            .collect();
+        builder.switch_to_block(blocks[0]);
+        let slots: Vec<(Type, ValueId)> = (0..self.below(self.config.max_slots + 1))
+            .map(|_| {
+                let ty = self.int_type();
+                (ty, builder.build_alloca(ty))
+            })
+            .collect();
+        for &(ty, slot) in &slots {
+            let val = self.constant(builder, ty);
+            builder.build_store(ty, val, slot);
+        }
+
+        for (i, &block) in blocks.iter().enumerate() {
+            builder.switch_to_block(block);
+            let mut pool = Pool::default();
+            for (j, &ty) in params.iter().enumerate() {
+                let arg = builder.arg(j);
+                pool.entry(ty).or_default().push(arg);
+            }
+            for _ in 0..self.below(self.config.max_insts + 1) {
+                self.inst(builder, &mut pool, &slots, callees);
+            }
+
+            let later = &blocks[i + 1..];
+            if later.is_empty() {
+                let ty = builder.func().result_ty;
+                let val = self.operand(builder, &pool, ty);
+                builder.build_ret(val);
+            } else if pool.get(&I1).is_some_and(|c| !c.is_empty()) && self.below(2) == 0 {
+                let cond = self.operand(builder, &pool, I1);
+                let (then, els) = (self.choose(later), self.choose(later));
+                builder.build_cond_br(cond, then, els);
+            } else {
+                let dest = self.choose(later);
+                builder.build_br(dest);
+            }
+        }
+    }
+
+    fn inst(
+        &mut self,
+        builder: &mut Builder,
+        pool: &mut Pool,
+        slots: &[(Type, ValueId)],
+        callees: &[(String, Type, Vec<Type>)],
+    ) {
+        let opcodes = self.config.opcodes.clone();
+        let opcode = if !callees.is_empty() && self.below(8) == 0 {
+            Opcode::Call
+        } else {
+            self.choose(&opcodes)
+        };
+        let (ty, val) = match opcode {
+            Opcode::ICmp => {
+                let ty = self.int_type();
+                let cond = self.choose(&[
+                    ICmpCond::Eq,
+                    ICmpCond::Ne,
+                    ICmpCond::Ugt,
+                    ICmpCond::Uge,
+                    ICmpCond::Ult,
+                    ICmpCond::Ule,
+                    ICmpCond::Sgt,
+                    ICmpCond::Sge,
+                    ICmpCond::Slt,
+                    ICmpCond::Sle,
+                ]);
+                let lhs = self.operand(builder, pool, ty);
+                let rhs = self.operand(builder, pool, ty);
+                (I1, builder.build_icmp(cond, ty, lhs, rhs))
+            }
+            Opcode::Sext | Opcode::Zext | Opcode::Trunc => {
+                let ty = self.int_type();
+                let from = self.choose(&[I1, ty]);
+                let tos: Vec<Type> = self
+                    .config
+                    .int_types
+                    .iter()
+                    .copied()
+                    .filter(|&to| (bits(to) > bits(from)) == (opcode != Opcode::Trunc))
+                    .filter(|&to| to != from)
+                    .collect();
+                if tos.is_empty() {
+                    return;
+                }
+                let to = self.choose(&tos);
+                let arg = self.operand(builder, pool, from);
+                (to, builder.build_cast(opcode, from, to, arg))
+            }
+            Opcode::Load | Opcode::Store => {
+                if slots.is_empty() {
+                    return;
+                }
+                let (ty, slot) = self.choose(slots);
+                if opcode == Opcode::Store {
+                    let val = self.operand(builder, pool, ty);
+                    builder.build_store(ty, val, slot);
+                    return;
+                }
+                (ty, builder.build_load(ty, slot))
+            }
+            Opcode::Call => {
+                let (name, result_ty, params) = &callees[self.below(callees.len())];
+                let args = params
+                    .iter()
+                    .map(|&ty| (ty, self.operand(builder, pool, ty)))
+                    .collect();
+                let callee = builder.global_ref(name);
+                let val = builder.build_call(*result_ty, callee, args).unwrap();
+                (*result_ty, val)
+            }
+            opcode => {
+                let ty = self.int_type();
+                let lhs = self.operand(builder, pool, ty);
+                let rhs = self.operand(builder, pool, ty);
+                let inst = builder.insert(
+                    opcode,
+                    Operand::IntBinary(IntBinary {
+                        ty,
+                        nsw: false,
+                        nuw: false,
+                        exact: false,
+                        args: [lhs, rhs],
+                    }),
+                );
+                (ty, builder.value(Value::Instruction(inst)))
+            }
+        };
+        pool.entry(ty).or_default().push(val);
+    }
+}
+
+fn bits(ty: Type) -> u32 {
+    match ty {
+        I1 => 1,
+        I8 => 8,
+        I16 => 16,
+        I32 => 32,
+        I64 => 64,
+        _ => 0,
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for ArbitraryModule {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        let data = u.bytes(u.len())?;
+        Ok(Self(generate(&GenConfig::default(), data)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    /// Returns `len` pseudo-random bytes.
+    fn bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut x = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let config = GenConfig::default();
+        for seed in 0..100 {
+            let module = generate(&config, &bytes(seed, 512));
+            assert_eq!(
+                generate(&config, &bytes(seed, 512)).functions.len(),
+                module.functions.len()
+            );
+            let printed = format!("{:?}", module);
+            let reparsed =
+                parse_assembly(&printed).unwrap_or_else(|e| panic!("{:?}\n{}", e, printed));
+            assert_eq!(format!("{:?}", reparsed), printed);
+        }
+    }
+}
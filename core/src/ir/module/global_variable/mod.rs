@@ -1,31 +1,52 @@
 mod parser;
 
-pub use parser::{parse, parse_global_type_and_const};
+pub use parser::{parse, parse_declaration, parse_global_type_and_const};
 
 use crate::ir::{
-    module::{linkage::Linkage, name::Name, unnamed_addr::UnnamedAddr},
+    module::{
+        linkage::Linkage, name::Name, preemption_specifier::PreemptionSpecifier,
+        unnamed_addr::UnnamedAddr, visibility::Visibility,
+    },
     types::{Type, Types},
     value::ConstantData,
 };
+use crate::prelude::*;
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GlobalVariable {
     pub name: Name,
     pub linkage: Option<Linkage>,
+    pub preemption_specifier: PreemptionSpecifier,
+    pub visibility: Visibility,
     pub unnamed_addr: Option<UnnamedAddr>,
     pub is_constant: bool,
     pub ty: Type,
     pub init: Option<ConstantData>,
+    /// The section the variable is placed in, instead of that of its kind.
+    pub section: Option<String>,
+    /// The comdat the variable belongs to.
+    pub comdat: Option<Name>,
     pub align: u32,
 }
 
 impl GlobalVariable {
     pub fn to_string(&self, types: &Types) -> String {
         format!(
-            "@{} = {}{}{}{} {}{}",
+            "@{} = {}{}{}{}{}{} {}{}{}{}",
             self.name,
             self.linkage
                 .map_or("".to_string(), |linkage| format!("{:?} ", linkage)),
+            // Implied by local linkage, as LLVM prints it.
+            match (self.preemption_specifier, self.linkage) {
+                (_, Some(Linkage::Private | Linkage::Internal)) => "",
+                (PreemptionSpecifier::DsoLocal, _) => "dso_local ",
+                (PreemptionSpecifier::DsoPreemptable, _) => "",
+            },
+            match self.visibility {
+                Visibility::Default => "".to_string(),
+                visibility => format!("{:?} ", visibility),
+            },
             self.unnamed_addr
                 .map_or("".to_string(), |u| format!("{:?} ", u)),
             if self.is_constant {
@@ -41,6 +62,14 @@ impl GlobalVariable {
                     init.to_string(types)
                 }
             }),
+            self.section
+                .as_ref()
+                .map_or("".to_string(), |section| format!(", section {:?}", section)),
+            match self.comdat {
+                Some(comdat) if comdat == self.name => ", comdat".to_string(),
+                Some(comdat) => format!(", comdat(${})", comdat),
+                None => "".to_string(),
+            },
             if self.align == 0 {
                 "".to_string()
             } else {
@@ -1,8 +1,11 @@
 use crate::ir::{
-    module::{global_variable::GlobalVariable, linkage, name, unnamed_addr},
+    module::{
+        comdat, global_variable::GlobalVariable, linkage, name, preemption_specifier, unnamed_addr,
+        visibility,
+    },
     types,
     types::Types,
-    util::spaces,
+    util::{skip_nested, spaces, string_literal},
     value,
 };
 use nom::{
@@ -26,14 +29,51 @@ use nom::{
 pub fn parse<'a>(
     source: &'a str,
     types: &Types,
+) -> IResult<&'a str, GlobalVariable, VerboseError<&'a str>> {
+    parse_global_variable(source, types, true)
+}
+
+/// Parses a global variable like [`parse`], but skips its initializer.
+pub fn parse_declaration<'a>(
+    source: &'a str,
+    types: &Types,
+) -> IResult<&'a str, GlobalVariable, VerboseError<&'a str>> {
+    parse_global_variable(source, types, false)
+}
+
+fn parse_global_variable<'a>(
+    source: &'a str,
+    types: &Types,
+    with_init: bool,
 ) -> IResult<&'a str, GlobalVariable, VerboseError<&'a str>> {
     let (source, name) = preceded(spaces, preceded(char('@'), name::parse))(source)?;
     let (source, _) = preceded(spaces, char('='))(source)?;
     let (source, linkage) = opt(preceded(spaces, linkage::parse))(source)?;
+    let (source, preemption_specifier) =
+        opt(preceded(spaces, preemption_specifier::parse))(source)?;
+    let (source, visibility) = opt(preceded(spaces, visibility::parse))(source)?;
     let (source, unnamed_addr) = opt(preceded(spaces, unnamed_addr::parse))(source)?;
     let (source, kind) = preceded(spaces, alt((tag("global"), tag("constant"))))(source)?;
     let (source, ty) = types::parse(source, types)?;
-    let (source, init) = parse_init(source, types, ty)?;
+    let (source, init) = if with_init {
+        parse_init(source, types, ty)?
+    } else {
+        (skip_nested(source, &[',', '\n']), None)
+    };
+    let (source, section) = opt(preceded(
+        spaces,
+        preceded(
+            char(','),
+            preceded(
+                spaces,
+                preceded(tag("section"), preceded(spaces, string_literal)),
+            ),
+        ),
+    ))(source)?;
+    let (source, comdat) = opt(preceded(
+        spaces,
+        preceded(char(','), preceded(spaces, comdat::parse_comdat_ref(name))),
+    ))(source)?;
     let (source, align) = opt(preceded(
         spaces,
         preceded(
@@ -46,10 +86,15 @@ pub fn parse<'a>(
         GlobalVariable {
             name,
             linkage,
+            preemption_specifier: preemption_specifier
+                .unwrap_or(preemption_specifier::PreemptionSpecifier::DsoPreemptable),
+            visibility: visibility.unwrap_or(visibility::Visibility::Default),
             unnamed_addr,
             is_constant: kind == "constant",
             ty,
             init,
+            section,
+            comdat,
             align: align.map_or(0, |align| align.parse::<u32>().unwrap()),
         },
     ))
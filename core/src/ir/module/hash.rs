@@ -0,0 +1,98 @@
+use super::Module;
+use crate::ir::function::hash::{StableHasher, Structure};
+use crate::prelude::*;
+use core::{fmt, hash::Hasher};
+
+impl Module {
+    /// Returns a hash of the target, global variables, functions and attribute
+    /// groups of the module. The module name, source filename and metadata are
+    /// ignored, as are local names inside functions
+    /// (see [`Function::structural_hash`](crate::ir::function::Function::structural_hash)).
+    pub fn structural_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        fmt::write(&mut hasher, format_args!("{:?}", ModuleStructure(self))).unwrap();
+        hasher.finish()
+    }
+}
+
+/// Returns true if `a` and `b` have the same structure in the sense of
+/// [`Module::structural_hash`].
+pub fn structurally_equal(a: &Module, b: &Module) -> bool {
+    format!("{:?}", ModuleStructure(a)) == format!("{:?}", ModuleStructure(b))
+}
+
+struct ModuleStructure<'a>(&'a Module);
+
+impl fmt::Debug for ModuleStructure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let module = self.0;
+        writeln!(f, "target datalayout = \"{}\"", module.target.datalayout)?;
+        writeln!(f, "target triple = \"{}\"", module.target.triple)?;
+
+        // Hash maps are printed in sorted order so that the result does not
+        // depend on insertion order.
+        let mut gvs: Vec<String> = module
+            .global_variables
+            .values()
+            .map(|gv| gv.to_string(&module.types))
+            .collect();
+        gvs.sort();
+        for gv in gvs {
+            writeln!(f, "{}", gv)?;
+        }
+
+        for (_, func) in &module.functions {
+            writeln!(f, "{:?}", Structure(func))?;
+        }
+
+        let mut attrs: Vec<_> = module.attributes.iter().collect();
+        attrs.sort_by_key(|(id, _)| **id);
+        for (id, attrs) in attrs {
+            writeln!(f, "attributes #{} = {:?}", id, attrs)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn module_structural_hash() {
+    use super::parse_assembly;
+
+    let a = parse_assembly(
+        r#"
+source_filename = "a.c"
+@x = global i32 1
+@y = global i32 2
+define i32 @main() {
+  %1 = load i32, i32* @x
+  ret i32 %1
+}"#,
+    )
+    .unwrap();
+    let b = parse_assembly(
+        r#"
+source_filename = "b.c"
+@y = global i32 2
+@x = global i32 1
+define i32 @main() {
+entry:
+  %v = load i32, i32* @x
+  ret i32 %v
+}"#,
+    )
+    .unwrap();
+    let c = parse_assembly(
+        r#"
+@x = global i32 1
+@y = global i32 3
+define i32 @main() {
+  %1 = load i32, i32* @x
+  ret i32 %1
+}"#,
+    )
+    .unwrap();
+    assert!(structurally_equal(&a, &b));
+    assert_eq!(a.structural_hash(), b.structural_hash());
+    assert!(!structurally_equal(&a, &c));
+    assert_ne!(a.structural_hash(), c.structural_hash());
+}
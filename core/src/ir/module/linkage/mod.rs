@@ -2,9 +2,10 @@ pub mod parser;
 
 pub use parser::parse;
 
-use std::fmt;
+use core::fmt;
 
 #[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Linkage {
     Private,
     Internal,
@@ -25,19 +26,20 @@ pub enum Linkage {
     LinkerPrivateWeak,
 }
 
+/// Prints the linkage as spelled in LLVM IR.
 impl fmt::Debug for Linkage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Private => write!(f, "private"),
             Self::Internal => write!(f, "internal"),
             Self::External => write!(f, "external"),
-            Self::ExternalWeak => write!(f, "externalweak"),
-            Self::AvailableExternally => write!(f, "availableexternally"),
-            Self::LinkOnceAny => write!(f, "linkonceany"),
-            Self::LinkOnceODR => write!(f, "linkonceodr"),
+            Self::ExternalWeak => write!(f, "extern_weak"),
+            Self::AvailableExternally => write!(f, "available_externally"),
+            Self::LinkOnceAny => write!(f, "linkonce"),
+            Self::LinkOnceODR => write!(f, "linkonce_odr"),
             Self::LinkOnceODRAutoHide => write!(f, "linkonceodrautohide"),
-            Self::WeakAny => write!(f, "weakany"),
-            Self::WeakODR => write!(f, "weakodr"),
+            Self::WeakAny => write!(f, "weak"),
+            Self::WeakODR => write!(f, "weak_odr"),
             Self::Common => write!(f, "common"),
             Self::Appending => write!(f, "appending"),
             Self::DLLImport => write!(f, "dllimport"),
@@ -1,24 +1,38 @@
 use super::Linkage;
 use nom::{branch::alt, bytes::complete::tag, combinator::map, error::VerboseError, IResult};
 
+/// Parses a linkage, spelled as in LLVM IR, or as printed by its `Debug`.
+/// Longer spellings are tried before their prefixes.
 pub fn parse(source: &str) -> IResult<&str, Linkage, VerboseError<&str>> {
     alt((
-        map(tag("private"), |_| Linkage::Private),
-        map(tag("internal"), |_| Linkage::Internal),
-        map(tag("external"), |_| Linkage::External),
-        map(tag("externalweak"), |_| Linkage::ExternalWeak),
-        map(tag("availableexternally"), |_| Linkage::AvailableExternally),
-        map(tag("linkonceany"), |_| Linkage::LinkOnceAny),
-        map(tag("linkonceodr"), |_| Linkage::LinkOnceODR),
-        map(tag("linkonceodrautohide"), |_| Linkage::LinkOnceODRAutoHide),
-        map(tag("weakany"), |_| Linkage::WeakAny),
-        map(tag("weakodr"), |_| Linkage::WeakODR),
-        map(tag("common"), |_| Linkage::Common),
-        map(tag("appending"), |_| Linkage::Appending),
-        map(tag("dllimport"), |_| Linkage::DLLImport),
-        map(tag("dllexport"), |_| Linkage::DLLExport),
-        map(tag("ghost"), |_| Linkage::Ghost),
-        map(tag("linkerprivate"), |_| Linkage::LinkerPrivate),
-        map(tag("linkerprivateweak"), |_| Linkage::LinkerPrivateWeak),
+        alt((
+            map(tag("private"), |_| Linkage::Private),
+            map(tag("internal"), |_| Linkage::Internal),
+            map(tag("extern_weak"), |_| Linkage::ExternalWeak),
+            map(tag("externalweak"), |_| Linkage::ExternalWeak),
+            map(tag("external"), |_| Linkage::External),
+            map(tag("available_externally"), |_| {
+                Linkage::AvailableExternally
+            }),
+            map(tag("availableexternally"), |_| Linkage::AvailableExternally),
+            map(tag("linkonce_odr"), |_| Linkage::LinkOnceODR),
+            map(tag("linkonceany"), |_| Linkage::LinkOnceAny),
+            map(tag("linkonceodrautohide"), |_| Linkage::LinkOnceODRAutoHide),
+            map(tag("linkonceodr"), |_| Linkage::LinkOnceODR),
+            map(tag("linkonce"), |_| Linkage::LinkOnceAny),
+        )),
+        alt((
+            map(tag("weak_odr"), |_| Linkage::WeakODR),
+            map(tag("weakany"), |_| Linkage::WeakAny),
+            map(tag("weakodr"), |_| Linkage::WeakODR),
+            map(tag("weak"), |_| Linkage::WeakAny),
+            map(tag("common"), |_| Linkage::Common),
+            map(tag("appending"), |_| Linkage::Appending),
+            map(tag("dllimport"), |_| Linkage::DLLImport),
+            map(tag("dllexport"), |_| Linkage::DLLExport),
+            map(tag("ghost"), |_| Linkage::Ghost),
+            map(tag("linkerprivateweak"), |_| Linkage::LinkerPrivateWeak),
+            map(tag("linkerprivate"), |_| Linkage::LinkerPrivate),
+        )),
     ))(source)
 }
@@ -0,0 +1,567 @@
+//! Linking modules, after `llvm-link`.
+//!
+//! [`Module::link`] moves the functions, global variables, attribute groups
+//! and metadata of a module into another one. A declaration is resolved
+//! against a definition of the same name in either module. Two definitions
+//! of the same name conflict, unless one of them may be discarded, e.g. is
+//! `linkonce_odr` or `weak`, in which case the other one is kept.
+//!
+//! Types are moved over to the destination module. Named structs are merged
+//! by name, and must then have the same body. Symbols local to the linked
+//! module, i.e. `internal` or `private` ones, are renamed if their names are
+//! already taken.
+//!
+//! The target of the destination module is kept, unless it has none.
+
+use super::{attributes::Attribute, linkage::Linkage, metadata::Metadata, name::Name, Module};
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::ir::{
+    function::{
+        instruction::{Call, Invoke, Operand},
+        param_attrs::ParameterAttribute,
+        Function,
+    },
+    symbol::Symbol,
+    types::{
+        ArrayType, CompoundType, FunctionType, PointerType, StructType, Type, Types, VectorType,
+    },
+    value::{ConstantData, ConstantExpr, Value},
+};
+use crate::prelude::*;
+use core::{error::Error, fmt};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    /// Both modules define the symbol.
+    DuplicateDefinition(String),
+    /// The symbol has different types in the modules.
+    TypeMismatch(String),
+    /// The named struct has different bodies in the modules.
+    StructMismatch(String),
+}
+
+impl Module {
+    /// Links `other` into `self`. On error, `self` may be partially linked.
+    pub fn link(&mut self, other: Module) -> Result<(), LinkError> {
+        let mut linker = Linker {
+            types: TypeMapper {
+                from: other.types.clone(),
+                to: self.types.clone(),
+                map: FxHashMap::default(),
+            },
+            renames: FxHashMap::default(),
+            attr_offset: self.attributes.keys().max().map_or(0, |id| id + 1),
+            meta_offset: self
+                .metas
+                .keys()
+                .filter_map(|name| match name {
+                    Name::Number(n) => Some(n + 1),
+                    Name::Name(_) => None,
+                })
+                .max()
+                .unwrap_or(0),
+        };
+        linker.rename_locals(self, &other);
+
+        if self.target.triple.is_empty() {
+            self.target.triple = other.target.triple.clone();
+        }
+        if self.target.datalayout.is_empty() {
+            self.target.datalayout = other.target.datalayout.clone();
+        }
+
+        for (id, attrs) in other.attributes {
+            self.attributes.insert(id + linker.attr_offset, attrs);
+        }
+        for (name, meta) in other.metas {
+            let meta = linker.meta(meta);
+            match linker.meta_name(name) {
+                name @ Name::Number(_) => {
+                    self.metas.insert(name, meta);
+                }
+                name => match (self.metas.get_mut(&name), meta) {
+                    // Named metadata, e.g. `!llvm.module.flags`, is concatenated.
+                    (Some(Metadata::Node(elems)), Metadata::Node(new)) => elems.extend(new),
+                    (Some(_), _) => {}
+                    (None, meta) => {
+                        self.metas.insert(name, meta);
+                    }
+                },
+            }
+        }
+
+        for (name, kind) in other.comdats {
+            self.comdats.entry(name).or_insert(kind);
+        }
+
+        for (_, gv) in other.global_variables {
+            let mut gv = gv;
+            gv.name = linker.rename(&gv.name);
+            gv.ty = linker.types.map(gv.ty)?;
+            gv.init = gv.init.map(|init| linker.constant(init)).transpose()?;
+            let name = gv.name;
+            match self.global_variables.get(&name) {
+                None => {
+                    self.global_variables.insert(name, gv);
+                }
+                Some(existing) => {
+                    if existing.ty != gv.ty {
+                        return Err(LinkError::TypeMismatch(format!("{}", name)));
+                    }
+                    let replace = match (&existing.init, &gv.init) {
+                        (_, None) => false,
+                        (None, Some(_)) => true,
+                        (Some(_), Some(_)) => resolve(
+                            existing.linkage.unwrap_or(Linkage::External),
+                            gv.linkage.unwrap_or(Linkage::External),
+                            &format!("{}", name),
+                        )?,
+                    };
+                    if replace {
+                        self.global_variables.insert(name, gv);
+                    }
+                }
+            }
+        }
+
+        for (_, func) in other.functions {
+            let func = linker.function(func, &self.types)?;
+            let Some(id) = self.find_function_by_name(&func.name) else {
+                self.functions.alloc(func);
+                continue;
+            };
+            let existing = &self.functions[id];
+            let same_type = existing.result_ty == func.result_ty
+                && existing.is_var_arg == func.is_var_arg
+                && existing.params.len() == func.params.len()
+                && existing
+                    .params
+                    .iter()
+                    .zip(func.params.iter())
+                    .all(|(x, y)| x.ty == y.ty);
+            if !same_type {
+                return Err(LinkError::TypeMismatch(func.name));
+            }
+            let replace = match (existing.is_prototype(), func.is_prototype()) {
+                (_, true) => false,
+                (true, false) => true,
+                (false, false) => resolve(existing.linkage, func.linkage, &func.name)?,
+            };
+            if replace {
+                self.functions[id] = func;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns true if the definition with linkage `new` replaces the one with
+/// `old`, or an error if neither can be discarded.
+fn resolve(old: Linkage, new: Linkage, name: &str) -> Result<bool, LinkError> {
+    match (is_discardable(old), is_discardable(new)) {
+        (_, true) => Ok(false),
+        (true, false) => Ok(true),
+        (false, false) => Err(LinkError::DuplicateDefinition(name.to_string())),
+    }
+}
+
+fn is_discardable(linkage: Linkage) -> bool {
+    matches!(
+        linkage,
+        Linkage::LinkOnceAny
+            | Linkage::LinkOnceODR
+            | Linkage::LinkOnceODRAutoHide
+            | Linkage::WeakAny
+            | Linkage::WeakODR
+            | Linkage::Common
+            | Linkage::AvailableExternally
+    )
+}
+
+fn is_local(linkage: Linkage) -> bool {
+    matches!(linkage, Linkage::Internal | Linkage::Private)
+}
+
+struct Linker {
+    types: TypeMapper,
+    /// Local symbols of the linked module renamed to avoid clashes.
+    renames: FxHashMap<Name, Name>,
+    attr_offset: u32,
+    meta_offset: usize,
+}
+
+impl Linker {
+    fn rename_locals(&mut self, module: &Module, other: &Module) {
+        let mut taken: FxHashSet<Name> = module.global_variables.keys().cloned().collect();
+        taken.extend(
+            module
+                .functions
+                .iter()
+                .map(|(_, f)| Name::from(f.name.as_str())),
+        );
+        taken.extend(other.global_variables.keys().cloned());
+        taken.extend(
+            other
+                .functions
+                .iter()
+                .map(|(_, f)| Name::from(f.name.as_str())),
+        );
+
+        let locals = other
+            .global_variables
+            .values()
+            .filter(|gv| gv.linkage.is_some_and(is_local))
+            .map(|gv| gv.name)
+            .chain(
+                other
+                    .functions
+                    .iter()
+                    .filter(|(_, f)| is_local(f.linkage))
+                    .map(|(_, f)| Name::from(f.name.as_str())),
+            );
+        let clashing: Vec<Name> = locals
+            .filter(|name| {
+                module.global_variables.contains_key(name)
+                    || module.find_function_by_name(format!("{}", name)).is_some()
+            })
+            .collect();
+        for name in clashing {
+            let base = format!("{}", name);
+            let new_name = (1..)
+                .map(|i| Name::from(format!("{}.{}", base, i)))
+                .find(|n| !taken.contains(n))
+                .unwrap();
+            taken.insert(new_name);
+            self.renames.insert(name, new_name);
+        }
+    }
+
+    fn rename(&self, name: &Name) -> Name {
+        *self.renames.get(name).unwrap_or(name)
+    }
+
+    fn function(&mut self, mut func: Function, types: &Types) -> Result<Function, LinkError> {
+        if let Name::Name(name) = self.rename(&Name::from(func.name.as_str())) {
+            func.name = name.to_string();
+        }
+        func.result_ty = self.types.map(func.result_ty)?;
+        for param in &mut func.params {
+            param.ty = self.types.map(param.ty)?;
+            self.param_attrs(&mut param.attrs)?;
+        }
+        self.param_attrs(&mut func.ret_attrs)?;
+        self.attrs(&mut func.func_attrs);
+        if let Some((ty, konst)) = func.personality.take() {
+            func.personality = Some((self.types.map(ty)?, self.constant(konst)?));
+        }
+
+        for (_, val) in func.data.values.iter_mut() {
+            if let Value::Constant(konst) = val {
+                *konst = self.constant(core::mem::replace(konst, ConstantData::Undef))?;
+            }
+        }
+        for (_, inst) in func.data.instructions.iter_mut() {
+            for ty in inst.operand.types_mut() {
+                *ty = self.types.map(*ty)?;
+            }
+            match &mut inst.operand {
+                Operand::Call(Call {
+                    param_attrs,
+                    ret_attrs,
+                    func_attrs,
+                    ..
+                })
+                | Operand::Invoke(Invoke {
+                    param_attrs,
+                    ret_attrs,
+                    func_attrs,
+                    ..
+                }) => {
+                    for attrs in param_attrs {
+                        self.param_attrs(attrs)?;
+                    }
+                    self.param_attrs(ret_attrs)?;
+                    self.attrs(func_attrs);
+                }
+                Operand::Alloca(alloca) => {
+                    let num_elements =
+                        core::mem::replace(&mut alloca.num_elements, ConstantData::Undef);
+                    alloca.num_elements = self.constant(num_elements)?;
+                }
+                _ => {}
+            }
+            for meta in inst.metadata.values_mut() {
+                *meta = self.meta(core::mem::replace(meta, Metadata::Node(vec![])));
+            }
+        }
+        func.types = types.clone();
+        Ok(func)
+    }
+
+    fn constant(&mut self, konst: ConstantData) -> Result<ConstantData, LinkError> {
+        Ok(match konst {
+            ConstantData::Array(mut arr) => {
+                arr.elem_ty = self.types.map(arr.elem_ty)?;
+                arr.elems = self.constants(arr.elems)?;
+                ConstantData::Array(arr)
+            }
+            ConstantData::Struct(mut strukt) => {
+                for ty in &mut strukt.elems_ty {
+                    *ty = self.types.map(*ty)?;
+                }
+                strukt.elems = self.constants(strukt.elems)?;
+                ConstantData::Struct(strukt)
+            }
+            ConstantData::Vector(mut vector) => {
+                vector.elem_ty = self.types.map(vector.elem_ty)?;
+                vector.elems = self.constants(vector.elems)?;
+                ConstantData::Vector(vector)
+            }
+            ConstantData::Expr(ConstantExpr::GetElementPtr {
+                inbounds,
+                tys,
+                args,
+            }) => ConstantData::Expr(ConstantExpr::GetElementPtr {
+                inbounds,
+                tys: tys
+                    .into_iter()
+                    .map(|ty| self.types.map(ty))
+                    .collect::<Result<_, _>>()?,
+                args: self.constants(args)?,
+            }),
+            ConstantData::Expr(ConstantExpr::Bitcast { tys, arg }) => {
+                ConstantData::Expr(ConstantExpr::Bitcast {
+                    tys: [self.types.map(tys[0])?, self.types.map(tys[1])?],
+                    arg: Box::new(self.constant(*arg)?),
+                })
+            }
+            ConstantData::GlobalRef(name) => ConstantData::GlobalRef(self.rename(&name)),
+            konst => konst,
+        })
+    }
+
+    fn constants(&mut self, konsts: Vec<ConstantData>) -> Result<Vec<ConstantData>, LinkError> {
+        konsts.into_iter().map(|k| self.constant(k)).collect()
+    }
+
+    fn attrs(&self, attrs: &mut [Attribute]) {
+        for attr in attrs {
+            if let Attribute::Ref(id) = attr {
+                *id += self.attr_offset;
+            }
+        }
+    }
+
+    fn param_attrs(&mut self, attrs: &mut [ParameterAttribute]) -> Result<(), LinkError> {
+        for attr in attrs {
+            match attr {
+                ParameterAttribute::Ref(id) => *id += self.attr_offset,
+                ParameterAttribute::SRet(Some(ty)) => *ty = self.types.map(*ty)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn meta_name(&self, name: Name) -> Name {
+        match name {
+            Name::Number(n) => Name::Number(n + self.meta_offset),
+            name => name,
+        }
+    }
+
+    fn meta(&self, meta: Metadata) -> Metadata {
+        match meta {
+            Metadata::Name(name) => Metadata::Name(self.meta_name(name)),
+            Metadata::Node(elems) => {
+                Metadata::Node(elems.into_iter().map(|m| self.meta(m)).collect())
+            }
+            meta => meta,
+        }
+    }
+}
+
+/// Maps the types of a module to the same types of another one.
+struct TypeMapper {
+    from: Types,
+    to: Types,
+    map: FxHashMap<Type, Type>,
+}
+
+impl TypeMapper {
+    fn map(&mut self, ty: Type) -> Result<Type, LinkError> {
+        if ty.is_primitive() {
+            return Ok(ty);
+        }
+        if let Some(&mapped) = self.map.get(&ty) {
+            return Ok(mapped);
+        }
+        let compound = self.from.get(ty).unwrap().clone();
+        let mapped = match compound {
+            CompoundType::Pointer(PointerType { inner, addr_space }) => {
+                let inner = self.map(inner)?;
+                self.to.ptr_to_in(inner, addr_space)
+            }
+            CompoundType::Array(ArrayType {
+                inner,
+                num_elements,
+            }) => {
+                let inner = self.map(inner)?;
+                self.to.array_of(inner, num_elements)
+            }
+            CompoundType::Vector(VectorType {
+                inner,
+                num_elements,
+            }) => {
+                let inner = self.map(inner)?;
+                self.to.vector_of(inner, num_elements)
+            }
+            CompoundType::Function(FunctionType {
+                ret,
+                params,
+                is_var_arg,
+            }) => {
+                let ret = self.map(ret)?;
+                let params = self.map_all(params)?;
+                self.to.func(ret, &params, is_var_arg)
+            }
+            CompoundType::Struct(StructType {
+                name: Some(Name::Name(name)),
+                elems,
+                is_packed,
+            }) => return self.named_struct(ty, name, elems, is_packed),
+            CompoundType::Struct(StructType {
+                elems, is_packed, ..
+            }) => {
+                let elems = self.map_all(elems)?;
+                if is_packed {
+                    self.to.packed_struct_of(&elems)
+                } else {
+                    self.to.struct_of(&elems)
+                }
+            }
+            CompoundType::Alias(inner) => self.map(inner)?,
+            CompoundType::Metadata => self.to.metadata(),
+        };
+        self.map.insert(ty, mapped);
+        Ok(mapped)
+    }
+
+    fn map_all(&mut self, tys: Vec<Type>) -> Result<Vec<Type>, LinkError> {
+        tys.into_iter().map(|ty| self.map(ty)).collect()
+    }
+
+    /// Maps a named struct to the struct of the same name, creating it if
+    /// needed. Mapped before its elements, so that it can refer to itself.
+    fn named_struct(
+        &mut self,
+        ty: Type,
+        name: Symbol,
+        elems: Vec<Type>,
+        is_packed: bool,
+    ) -> Result<Type, LinkError> {
+        let mapped = self.to.named_struct(name);
+        self.map.insert(ty, mapped);
+        let elems = self.map_all(elems)?;
+        let current = match self.to.get(mapped).as_deref() {
+            Some(CompoundType::Struct(strukt)) => strukt.clone(),
+            _ => unreachable!(),
+        };
+        if current.elems.is_empty() {
+            self.to.set_struct_body(mapped, &elems, is_packed);
+        } else if !elems.is_empty() && (current.elems != elems || current.is_packed != is_packed) {
+            return Err(LinkError::StructMismatch(name.to_string()));
+        }
+        Ok(mapped)
+    }
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DuplicateDefinition(name) => write!(f, "symbol `{}` is defined twice", name),
+            Self::TypeMismatch(name) => write!(f, "symbol `{}` has conflicting types", name),
+            Self::StructMismatch(name) => {
+                write!(f, "struct `%{}` has conflicting definitions", name)
+            }
+        }
+    }
+}
+
+impl Error for LinkError {}
+
+#[cfg(test)]
+mod test {
+    use super::LinkError;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn link_modules() {
+        let mut module = parse_assembly(
+            r#"
+%struct.P = type { i32, i32 }
+
+@count = global i32 0
+@s = internal global i32 1
+
+declare i32 @get(%struct.P*)
+
+define i32 @main() #0 {
+  %p = alloca %struct.P
+  %1 = call i32 @get(%struct.P* %p)
+  ret i32 %1
+}
+
+attributes #0 = { noinline }
+"#,
+        )
+        .unwrap();
+        let other = parse_assembly(
+            r#"
+%struct.P = type { i32, i32 }
+
+@count = weakany global i32 5
+@s = internal global i32 2
+
+define linkonceodr i32 @main() {
+  ret i32 1
+}
+
+define i32 @get(%struct.P* %0) #0 {
+  %2 = getelementptr inbounds %struct.P, %struct.P* %0, i64 0, i32 1
+  %3 = load i32, i32* @s
+  ret i32 %3
+}
+
+attributes #0 = { nounwind }
+"#,
+        )
+        .unwrap();
+        module.link(other).unwrap();
+        insta::assert_debug_snapshot!(module);
+    }
+
+    #[test]
+    fn conflicts() {
+        let source = "define i32 @f() {\n  ret i32 0\n}";
+        let mut module = parse_assembly(source).unwrap();
+        assert_eq!(
+            module.link(parse_assembly(source).unwrap()),
+            Err(LinkError::DuplicateDefinition("f".to_string()))
+        );
+
+        let mut module = parse_assembly("declare i32 @f(i32)").unwrap();
+        assert_eq!(
+            module.link(parse_assembly(source).unwrap()),
+            Err(LinkError::TypeMismatch("f".to_string()))
+        );
+
+        let mut module = parse_assembly("%T = type { i32 }\n@g = global %T* null").unwrap();
+        let other = parse_assembly("%T = type { i64 }\n@h = global %T* null").unwrap();
+        assert_eq!(
+            module.link(other),
+            Err(LinkError::StructMismatch("T".to_string()))
+        );
+    }
+}
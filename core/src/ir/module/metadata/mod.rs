@@ -3,12 +3,14 @@ pub mod parser;
 pub use parser::operand as parse_operand;
 pub use parser::parse;
 
-use crate::ir::{module::name::Name, value::ConstantInt};
-use std::fmt;
+use crate::ir::{module::name::Name, symbol::Symbol, value::ConstantInt};
+use crate::prelude::*;
+use core::fmt;
 
 #[derive(PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Metadata {
-    String(String),
+    String(Symbol),
     Name(Name),
     Int(ConstantInt),
     Node(Vec<Self>),
@@ -25,8 +27,10 @@ impl fmt::Debug for Metadata {
             match i {
                 ConstantInt::Int1(_) => write!(f, "i1 "),
                 ConstantInt::Int8(_) => write!(f, "i8 "),
+                ConstantInt::Int16(_) => write!(f, "i16 "),
                 ConstantInt::Int32(_) => write!(f, "i32 "),
                 ConstantInt::Int64(_) => write!(f, "i64 "),
+                ConstantInt::Int128(_) => write!(f, "i128 "),
             }?;
             write!(f, "{}", i)
         }
@@ -33,7 +33,7 @@ fn exclamation(source: &str) -> IResult<&str, &str, VerboseError<&str>> {
 
 fn string(source: &str) -> IResult<&str, Metadata, VerboseError<&str>> {
     preceded(exclamation, preceded(spaces, string_literal))(source)
-        .map(|(i, source)| (i, Metadata::String(source)))
+        .map(|(i, source)| (i, Metadata::String(source.into())))
 }
 
 fn name(source: &str) -> IResult<&str, Metadata, VerboseError<&str>> {
@@ -1,42 +1,67 @@
 pub mod attributes;
+pub mod comdat;
+pub mod data_layout;
+pub mod diff;
+pub mod error;
+pub mod extract;
+pub mod generate;
 pub mod global_variable;
+pub mod hash;
 pub mod linkage;
+pub mod linker;
 pub mod metadata;
 pub mod name;
 pub mod parser;
 pub mod preemption_specifier;
+pub mod reduce;
 pub mod unnamed_addr;
 pub mod visibility;
 
-pub use parser::parse as parse_assembly;
+pub use error::ParseError;
+#[cfg(feature = "std")]
+pub use error::ReadError;
+#[cfg(feature = "std")]
+pub use parser::parse_from_reader;
+#[cfg(feature = "parallel")]
+pub use parser::parse_parallel;
+pub use parser::{parse as parse_assembly, parse_declarations};
 
 use super::{
     function::{Function, FunctionId, Parameter},
     types::{Type, Types},
 };
+use crate::prelude::*;
 use attributes::Attribute;
+use comdat::SelectionKind;
+use core::{fmt, hash::BuildHasherDefault};
 use global_variable::GlobalVariable;
 use id_arena::{Arena, Id};
+use indexmap::IndexMap;
 use metadata::Metadata;
 use name::Name;
-use rustc_hash::FxHashMap;
-use std::fmt;
+use rustc_hash::FxHasher;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Target {
-    triple: String,
-    datalayout: String,
+    pub(crate) triple: String,
+    pub(crate) datalayout: String,
 }
 
+/// A map that iterates in insertion order, so that modules print the same
+/// from run to run.
+pub type FxIndexMap<K, V> = IndexMap<K, V, BuildHasherDefault<FxHasher>>;
+
 pub struct Module {
     pub(crate) name: String,
     pub(crate) source_filename: String,
     pub(crate) target: Target,
     pub(crate) functions: Arena<Function>,
-    pub(crate) attributes: FxHashMap<u32, Vec<Attribute>>,
-    pub(crate) global_variables: FxHashMap<Name, GlobalVariable>,
+    pub(crate) attributes: FxIndexMap<u32, Vec<Attribute>>,
+    pub(crate) global_variables: FxIndexMap<Name, GlobalVariable>,
+    pub(crate) comdats: FxIndexMap<Name, SelectionKind>,
     pub types: Types,
-    pub metas: FxHashMap<Name, Metadata>,
+    pub metas: FxIndexMap<Name, Metadata>,
 }
 
 impl Default for Module {
@@ -46,10 +71,11 @@ impl Default for Module {
             source_filename: "".to_string(),
             target: Target::new(),
             functions: Arena::new(),
-            attributes: FxHashMap::default(),
-            global_variables: FxHashMap::default(),
+            attributes: FxIndexMap::default(),
+            global_variables: FxIndexMap::default(),
+            comdats: FxIndexMap::default(),
             types: Types::new(),
-            metas: FxHashMap::default(),
+            metas: FxIndexMap::default(),
         }
     }
 }
@@ -79,14 +105,19 @@ impl Module {
         &mut self.functions
     }
 
-    pub fn attributes(&self) -> &FxHashMap<u32, Vec<Attribute>> {
+    pub fn attributes(&self) -> &FxIndexMap<u32, Vec<Attribute>> {
         &self.attributes
     }
 
-    pub fn global_variables(&self) -> &FxHashMap<Name, GlobalVariable> {
+    pub fn global_variables(&self) -> &FxIndexMap<Name, GlobalVariable> {
         &self.global_variables
     }
 
+    /// Returns the comdats of the module, by name.
+    pub fn comdats(&self) -> &FxIndexMap<Name, SelectionKind> {
+        &self.comdats
+    }
+
     pub fn add_function(&mut self, f: Function) -> Id<Function> {
         self.functions.alloc(f)
     }
@@ -147,6 +178,9 @@ impl fmt::Debug for Module {
         writeln!(f, "target triple = \"{}\"", self.target.triple)?;
         writeln!(f)?;
         write!(f, "{:?}", self.types)?;
+        for (name, kind) in &self.comdats {
+            writeln!(f, "${} = comdat {:?}", name, kind)?;
+        }
         for gv in self.global_variables.values() {
             writeln!(f, "{}", gv.to_string(&self.types))?;
         }
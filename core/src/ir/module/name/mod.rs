@@ -1,20 +1,22 @@
 pub mod parser;
 
-use crate::ir::util::escape;
+use crate::ir::{symbol::Symbol, util::escape};
+use crate::prelude::*;
 pub use parser::parse;
 
-use std::fmt;
+use core::fmt;
 
-#[derive(Clone, Eq, PartialEq, Hash)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Name {
-    Name(String),
+    Name(Symbol),
     Number(usize),
 }
 
 impl Name {
-    pub fn as_string(&self) -> &String {
+    pub fn as_str(&self) -> &'static str {
         match self {
-            Self::Name(name) => name,
+            Self::Name(name) => name.as_str(),
             _ => panic!(),
         }
     }
@@ -26,14 +28,26 @@ impl Name {
         }
     }
 
-    pub fn to_string(&self) -> Option<&String> {
+    pub fn to_str(&self) -> Option<&'static str> {
         match self {
-            Self::Name(name) => Some(name),
+            Self::Name(name) => Some(name.as_str()),
             _ => None,
         }
     }
 }
 
+impl From<&str> for Name {
+    fn from(name: &str) -> Self {
+        Self::Name(Symbol::intern(name))
+    }
+}
+
+impl From<String> for Name {
+    fn from(name: String) -> Self {
+        Self::Name(Symbol::intern(&name))
+    }
+}
+
 impl fmt::Debug for Name {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
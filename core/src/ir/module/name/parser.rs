@@ -10,8 +10,8 @@ pub fn parse(source: &str) -> IResult<&str, Name, VerboseError<&str>> {
         spaces,
         alt((
             map(digit1, |i: &str| Name::Number(i.parse().unwrap())),
-            map(identifier, |n: &str| Name::Name(n.to_string())),
-            map(string_literal, Name::Name),
+            map(identifier, Name::from),
+            map(string_literal, Name::from),
         )),
     )(source)
 }
@@ -2,12 +2,15 @@ use super::super::function;
 use super::Module;
 use super::{
     attributes::{parser::parse_attributes, Attribute},
+    comdat,
+    error::ParseError,
     global_variable, metadata, name,
 };
 use crate::ir::{
     types,
-    util::{spaces, string_literal},
+    util::{skip_nested, spaces, string_literal},
 };
+use crate::prelude::*;
 use nom;
 use nom::{
     bytes::complete::tag,
@@ -16,6 +19,12 @@ use nom::{
     sequence::{preceded, tuple},
     IResult,
 };
+#[cfg(feature = "std")]
+use {
+    super::error::ReadError,
+    crate::ir::util::nesting_depth,
+    std::io::{BufRead, BufReader, Read},
+};
 
 fn parse_source_filename(source: &str) -> IResult<&str, String, VerboseError<&str>> {
     tuple((
@@ -64,17 +73,112 @@ fn parse_local_type<'a>(
     types: &types::Types,
 ) -> IResult<&'a str, (), VerboseError<&'a str>> {
     let (source, name) = preceded(spaces, preceded(char('%'), name::parse))(source)?;
-    types.base_mut().empty_named_type(name.clone()); // register a named type
+    types.named_type(name); // register a named type
     let (source, _) = preceded(spaces, preceded(char('='), preceded(spaces, tag("type"))))(source)?;
     let (source, ty) = types::parse(source, types)?;
-    types.base_mut().change_to_named_type(ty, name);
+    types.define_named_type(name, ty);
     Ok((source, ()))
 }
 
-pub fn parse(mut source: &str) -> Result<Module, nom::Err<VerboseError<&str>>> {
+pub fn parse(input: &str) -> Result<Module, ParseError> {
+    parse_module(input, false)
+}
+
+/// Parses only the symbols, types and signatures of a module. Function
+/// bodies, global variable initializers and metadata are skipped without
+/// being parsed, so that defined functions result in declarations and
+/// global variables have no initializer.
+pub fn parse_declarations(input: &str) -> Result<Module, ParseError> {
+    parse_module(input, true)
+}
+
+/// The size from which the source read by [`parse_from_reader`] is parsed,
+/// once it ends with a complete top-level entity.
+#[cfg(feature = "std")]
+const CHUNK_SIZE: usize = 1 << 16;
+
+/// Parses a module read from `reader`. The source is read and parsed in
+/// chunks of whole top-level entities, so that it is never entirely in
+/// memory, unlike with [`parse`].
+#[cfg(feature = "std")]
+pub fn parse_from_reader<R: Read>(reader: R) -> Result<Module, ReadError> {
+    parse_chunks(reader, CHUNK_SIZE)
+}
+
+#[cfg(feature = "std")]
+fn parse_chunks<R: Read>(reader: R, chunk_size: usize) -> Result<Module, ReadError> {
+    let mut reader = BufReader::new(reader);
+    let mut module = Module::new();
+    let mut chunk = String::new();
+    // The line number of the first line of `chunk`, and its number of lines.
+    let mut first_line = 1;
+    let mut num_lines = 0;
+    let mut depth = 0;
+    loop {
+        let start = chunk.len();
+        let eof = reader.read_line(&mut chunk)? == 0;
+        num_lines += 1;
+        depth = nesting_depth(&chunk[start..], depth);
+        if eof || (depth == 0 && chunk.len() >= chunk_size) {
+            parse_into(&mut module, &chunk, false, None).map_err(|mut err| {
+                err.line += first_line - 1;
+                err
+            })?;
+            if eof {
+                return Ok(module);
+            }
+            first_line += num_lines;
+            num_lines = 0;
+            chunk.clear();
+        }
+    }
+}
+
+/// Parses a module like [`parse`], but parses the functions on the rayon
+/// thread pool once the rest of the module is parsed. Types created while
+/// parsing functions are then numbered in no particular order, which only
+/// shows in their IDs.
+#[cfg(feature = "parallel")]
+pub fn parse_parallel(input: &str) -> Result<Module, ParseError> {
+    use rayon::prelude::*;
+
+    let mut module = Module::new();
+    let mut functions = vec![];
+    parse_into(&mut module, input, false, Some(&mut functions))?;
+    let types = &module.types;
+    let functions = functions
+        .into_par_iter()
+        .map(|source| {
+            function::parse(source, types.clone())
+                .map(|(_, func)| func)
+                .map_err(|e| ParseError::from_nom(input, source, "function", e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    for func in functions {
+        module.functions.alloc(func);
+    }
+    Ok(module)
+}
+
+fn parse_module(input: &str, declarations_only: bool) -> Result<Module, ParseError> {
     let mut module = Module::new();
+    parse_into(&mut module, input, declarations_only, None)?;
+    Ok(module)
+}
+
+/// Parses the top-level entities in `input` into `module`. Functions are
+/// pushed to `deferred` instead of being parsed, if given.
+fn parse_into<'a>(
+    module: &mut Module,
+    input: &'a str,
+    declarations_only: bool,
+    mut deferred: Option<&mut Vec<&'a str>>,
+) -> Result<(), ParseError> {
+    let mut source = input;
     loop {
-        source = spaces(source)?.0;
+        source = spaces(source)
+            .map_err(|e| ParseError::from_nom(input, source, "comment", e))?
+            .0;
 
         if source.is_empty() {
             break;
@@ -104,48 +208,74 @@ pub fn parse(mut source: &str) -> Result<Module, nom::Err<VerboseError<&str>>> {
             continue;
         }
 
+        if let Ok((source_, (name, kind))) = comdat::parse(source) {
+            module.comdats.insert(name, kind);
+            source = source_;
+            continue;
+        }
+
         if let Ok((source_, _)) = parse_local_type(source, &module.types) {
             source = source_;
             continue;
         }
 
-        if let Ok((source_, gv)) = global_variable::parse(source, &module.types) {
-            module.global_variables.insert(gv.name.clone(), gv);
+        if declarations_only && source.starts_with('!') {
+            source = skip_nested(source, &['\n']);
+            continue;
+        }
+
+        let parse_global_variable = if declarations_only {
+            global_variable::parse_declaration
+        } else {
+            global_variable::parse
+        };
+        if let Ok((source_, gv)) = parse_global_variable(source, &module.types) {
+            module.global_variables.insert(gv.name, gv);
             source = source_;
             continue;
         }
 
-        if let Ok((source_, func)) = function::parse(source, module.types.clone()) {
+        if source.starts_with("define") || source.starts_with("declare") {
+            if let Some(deferred) = deferred.as_mut() {
+                deferred.push(source);
+                source = function::parser::parse_declaration(source, module.types.clone())
+                    .map_err(|e| ParseError::from_nom(input, source, "function", e))?
+                    .0;
+                continue;
+            }
+            let parse_function = if declarations_only {
+                function::parser::parse_declaration
+            } else {
+                function::parse
+            };
+            let (source_, func) = parse_function(source, module.types.clone())
+                .map_err(|e| ParseError::from_nom(input, source, "function", e))?;
             module.functions.alloc(func);
             source = source_;
             continue;
         }
+
         if let Ok((source_, (name_, meta))) = metadata::parse(&module.types)(source) {
             module.metas.insert(name_, meta);
             source = source_;
             continue;
         }
 
-        todo!("unsupported syntax at {:?}", source)
+        return Err(ParseError::new(input, source, "top-level entity"));
     }
 
-    Ok(module)
+    Ok(())
 }
 
 macro_rules! generate_test {
     ($fname:ident, $name:literal) => {
         #[test]
         fn $fname() {
-            use nom::error::convert_error;
             use std::fs;
             let source = fs::read_to_string(concat!("./examples/", $name)).unwrap();
             let module = match parse(&source) {
                 Ok(ok) => ok,
-                Err(nom::Err::Error(e)) => {
-                    println!("{}", convert_error(source.as_str(), e));
-                    panic!()
-                }
-                Err(e) => panic!("{:?}", e),
+                Err(e) => panic!("{}", e),
             };
             insta::assert_debug_snapshot!(module);
         }
@@ -209,7 +339,7 @@ fn parse_module1() {
 #[test]
 #[rustfmt::skip]
 fn parse_module2() {
-    use rustc_hash::FxHashMap;
+    use super::FxIndexMap;
     let result = parse(include_str!("../../../examples/ret42.ll"));
     assert!(result.is_ok());
     let result = result.unwrap();
@@ -226,27 +356,291 @@ fn parse_module2() {
             Attribute::NoUnwind,
             Attribute::OptNone,
             Attribute::UWTable, 
-            Attribute::StringAttribute {kind: "correctly-rounded-divide-sqrt-fp-math".to_string(), value: "false"                     .to_string()},
-            Attribute::StringAttribute {kind: "disable-tail-calls"                   .to_string(), value: "false"                     .to_string()},
-            Attribute::StringAttribute {kind: "frame-pointer"                        .to_string(), value: "all"                       .to_string()},
-            Attribute::StringAttribute {kind: "less-precise-fpmad"                   .to_string(), value: "false"                     .to_string()},
-            Attribute::StringAttribute {kind: "min-legal-vector-width"               .to_string(), value: "0"                         .to_string()},
-            Attribute::StringAttribute {kind: "no-infs-fp-math"                      .to_string(), value: "false"                     .to_string()},
-            Attribute::StringAttribute {kind: "no-jump-tables"                       .to_string(), value: "false"                     .to_string()},
-            Attribute::StringAttribute {kind: "no-nans-fp-math"                      .to_string(), value: "false"                     .to_string()},
-            Attribute::StringAttribute {kind: "no-signed-zeros-fp-math"              .to_string(), value: "false"                     .to_string()},
-            Attribute::StringAttribute {kind: "no-trapping-math"                     .to_string(), value: "false"                     .to_string()},
-            Attribute::StringAttribute {kind: "stack-protector-buffer-size"          .to_string(), value: "8"                         .to_string()},
-            Attribute::StringAttribute {kind: "target-cpu"                           .to_string(), value: "x86-64"                    .to_string()},
-            Attribute::StringAttribute {kind: "target-features"                      .to_string(), value: "+fxsr,+mmx,+sse,+sse2,+x87".to_string()},
-            Attribute::StringAttribute {kind: "unsafe-fp-math"                       .to_string(), value: "false"                     .to_string()},
-            Attribute::StringAttribute {kind: "use-soft-float"                       .to_string(), value: "false"                     .to_string()},
+            Attribute::StringAttribute {kind: "correctly-rounded-divide-sqrt-fp-math".into(), value: "false"                     .into()},
+            Attribute::StringAttribute {kind: "disable-tail-calls"                   .into(), value: "false"                     .into()},
+            Attribute::StringAttribute {kind: "frame-pointer"                        .into(), value: "all"                       .into()},
+            Attribute::StringAttribute {kind: "less-precise-fpmad"                   .into(), value: "false"                     .into()},
+            Attribute::StringAttribute {kind: "min-legal-vector-width"               .into(), value: "0"                         .into()},
+            Attribute::StringAttribute {kind: "no-infs-fp-math"                      .into(), value: "false"                     .into()},
+            Attribute::StringAttribute {kind: "no-jump-tables"                       .into(), value: "false"                     .into()},
+            Attribute::StringAttribute {kind: "no-nans-fp-math"                      .into(), value: "false"                     .into()},
+            Attribute::StringAttribute {kind: "no-signed-zeros-fp-math"              .into(), value: "false"                     .into()},
+            Attribute::StringAttribute {kind: "no-trapping-math"                     .into(), value: "false"                     .into()},
+            Attribute::StringAttribute {kind: "stack-protector-buffer-size"          .into(), value: "8"                         .into()},
+            Attribute::StringAttribute {kind: "target-cpu"                           .into(), value: "x86-64"                    .into()},
+            Attribute::StringAttribute {kind: "target-features"                      .into(), value: "+fxsr,+mmx,+sse,+sse2,+x87".into()},
+            Attribute::StringAttribute {kind: "unsafe-fp-math"                       .into(), value: "false"                     .into()},
+            Attribute::StringAttribute {kind: "use-soft-float"                       .into(), value: "false"                     .into()},
         ],
     )]
     .into_iter()
-    .collect::<FxHashMap<u32, Vec<Attribute>>>();
+    .collect::<FxIndexMap<u32, Vec<Attribute>>>();
     for (key1, val1) in &result.attributes {
         assert!(&attrs[key1] == val1)
     }
     println!("{:?}", result);
 }
+
+#[test]
+fn parse_error_unknown_entity() {
+    let source = "source_filename = \"c.c\"\n  foo bar\n";
+    let err = parse(source).unwrap_err();
+    assert_eq!(err.line, 2);
+    assert_eq!(err.column, 3);
+    assert_eq!(err.snippet, "  foo bar");
+    assert_eq!(err.construct, "top-level entity");
+    assert_eq!(
+        err.to_string(),
+        "2:3: failed to parse top-level entity\n  |\n2 |   foo bar\n  |   ^"
+    );
+}
+
+#[test]
+fn parse_error_in_function_body() {
+    let source = r#"define i32 @main() {
+  %1 = add i32 1, 2
+  bogus
+  ret i32 %1
+}"#;
+    let err = parse(source).unwrap_err();
+    assert_eq!(err.line, 3);
+    assert_eq!(err.column, 3);
+    assert_eq!(err.snippet, "  bogus");
+    assert_eq!(err.construct, "instruction");
+}
+
+#[test]
+fn parse_error_entry_block_with_predecessor() {
+    let source = r#"define i32 @main() {
+  br label %0
+}"#;
+    let err = parse(source).unwrap_err();
+    assert_eq!(err.line, 1);
+    assert_eq!(err.column, 20);
+    assert_eq!(err.construct, "entry block");
+}
+
+#[test]
+fn parse_error_oversized_hex_float() {
+    let source = r#"define double @main() {
+  ret double 0x3FF80000000000000
+}"#;
+    let err = parse(source).unwrap_err();
+    assert_eq!(err.line, 2);
+    assert_eq!(err.column, 14);
+    assert_eq!(err.construct, "float");
+}
+
+#[test]
+fn parse_declarations_only() {
+    let source = r#"
+%pair = type { i32, i8* }
+@s = private unnamed_addr constant [6 x i8] c"a;{}\22\00", align 1 ; "{"
+@p = global %pair { i32 1, i8* getelementptr inbounds ([6 x i8], [6 x i8]* @s, i32 0, i32 0) }, align 8
+define i32 @f(i32 %x) #0 {
+entry:
+  ; }
+  %y = call i32 @g(i32 %x)
+  br label %exit
+exit:
+  ret i32 %y
+}
+declare i32 @g(i32)
+attributes #0 = { noinline }
+!0 = !{!"clang", !{}}
+"#;
+    let module = parse_declarations(source).unwrap();
+    assert_eq!(
+        format!("{:?}", module),
+        r#"source_filename = ""
+target datalayout = ""
+target triple = ""
+
+%pair = type { i32, i8* }
+@s = private unnamed_addr constant [6 x i8] , align 1
+@p = global %pair , align 8
+
+declare external dso_preemptable default i32 @f(i32 %x) #0 
+
+declare external dso_preemptable default i32 @g(i32 %0) 
+
+attributes #0 = { noinline }
+"#
+    );
+    assert!(module.metas.is_empty());
+}
+
+#[test]
+fn parse_constant_arrays() {
+    let source = r#"
+@a = global [3 x i32] [i32 1, i32 -2, i32 3], align 4
+@m = constant [2 x [2 x i8]] [[2 x i8] [i8 1, i8 2], [2 x i8] c"\01\02"], align 1
+"#;
+    let module = parse(source).unwrap();
+    let gvs: Vec<_> = module
+        .global_variables()
+        .values()
+        .map(|gv| gv.to_string(&module.types))
+        .collect();
+    assert_eq!(
+        gvs,
+        vec![
+            "@a = global [3 x i32] [i32 1, i32 -2, i32 3], align 4",
+            r#"@m = constant [2 x [2 x i8]] [[2 x i8] [i8 1, i8 2], [2 x i8] c"\01\02"], align 1"#,
+        ]
+    );
+}
+
+#[test]
+fn parse_string_constants() {
+    let source = r#"@s = constant [8 x i8] c"a\09\22\5C\00\FF\C3\A9", align 1"#;
+    let module = parse(source).unwrap();
+    let gv = module.global_variables().values().next().unwrap();
+    let bytes: Vec<u8> = gv
+        .init
+        .as_ref()
+        .unwrap()
+        .as_array()
+        .elems
+        .iter()
+        .map(|e| *e.as_int().as_i8() as u8)
+        .collect();
+    assert_eq!(bytes, b"a\t\"\\\0\xff\xc3\xa9");
+    assert_eq!(
+        gv.to_string(&module.types),
+        r#"@s = constant [8 x i8] c"a\09\22\5c\00\ff\c3\a9", align 1"#
+    );
+}
+
+#[test]
+fn parse_sections_and_alignments() {
+    let source = r#"
+@v = global i32 1, section ".mydata", align 8
+
+define i32 @f() #0 section ".text.hot" align 32 {
+  ret i32 0
+}
+"#;
+    let module = parse(source).unwrap();
+    let gv = module.global_variables().values().next().unwrap();
+    assert_eq!(gv.section.as_deref(), Some(".mydata"));
+    assert_eq!(
+        gv.to_string(&module.types),
+        r#"@v = global i32 1, section ".mydata", align 8"#
+    );
+    let f = module.find_function_by_name("f").unwrap();
+    let f = &module.functions()[f];
+    assert_eq!(f.section.as_deref(), Some(".text.hot"));
+    assert_eq!(f.align, 32);
+    assert!(format!("{:?}", f).contains(r#"#0 section ".text.hot" align 32 {"#));
+}
+
+#[test]
+fn parse_global_visibility() {
+    let source = r#"
+@h = hidden global i32 1, align 4
+@p = dso_local protected global i32 2, align 4
+"#;
+    let module = parse(source).unwrap();
+    let gvs: Vec<_> = module
+        .global_variables()
+        .values()
+        .map(|gv| gv.to_string(&module.types))
+        .collect();
+    assert_eq!(
+        gvs,
+        vec![
+            "@h = hidden global i32 1, align 4",
+            "@p = dso_local protected global i32 2, align 4",
+        ]
+    );
+}
+
+#[test]
+fn parse_comdats() {
+    use super::{comdat::SelectionKind, name::Name};
+
+    let source = r#"
+$f = comdat any
+$v = comdat largest
+
+@v = linkonce_odr global i32 1, comdat, align 4
+@w = linkonce_odr global i32 2, comdat($f), align 4
+
+define linkonce_odr i32 @f() comdat {
+  ret i32 0
+}
+
+define linkonce_odr i32 @g() comdat($f) align 16 {
+  ret i32 0
+}
+"#;
+    let module = parse(source).unwrap();
+    let comdats: Vec<_> = module.comdats().iter().map(|(n, k)| (*n, *k)).collect();
+    assert_eq!(
+        comdats,
+        vec![
+            (Name::from("f"), SelectionKind::Any),
+            (Name::from("v"), SelectionKind::Largest),
+        ]
+    );
+    let printed = format!("{:?}", module);
+    assert!(printed.contains("$f = comdat any\n$v = comdat largest\n"));
+    assert!(printed.contains("@v = linkonce_odr global i32 1, comdat, align 4\n"));
+    assert!(printed.contains("@w = linkonce_odr global i32 2, comdat($f), align 4\n"));
+    assert!(printed.contains("@f() comdat {"));
+    assert!(printed.contains("@g() comdat($f) align 16 {"));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn parse_from_reader_in_chunks() {
+    use std::fs;
+    for entry in fs::read_dir("./examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "ll") {
+            continue;
+        }
+        let source = fs::read_to_string(&path).unwrap();
+        let module = parse_chunks(source.as_bytes(), 1).unwrap();
+        assert_eq!(
+            format!("{:?}", module),
+            format!("{:?}", parse(&source).unwrap()),
+            "{}",
+            path.display()
+        );
+    }
+
+    let source = "define i32 @f() {\n  ret i32 0\n}\n\n@g = global i32 0\n  foo bar\n";
+    match parse_chunks(source.as_bytes(), 1) {
+        Err(ReadError::Parse(err)) => {
+            assert_eq!(err.line, 6);
+            assert_eq!(err.snippet, "  foo bar");
+        }
+        res => panic!("unexpected result: {:?}", res.map(|_| ())),
+    }
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parse_parallel_examples() {
+    use std::fs;
+    for entry in fs::read_dir("./examples").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "ll") {
+            continue;
+        }
+        let source = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            format!("{:?}", parse_parallel(&source).unwrap()),
+            format!("{:?}", parse(&source).unwrap()),
+            "{}",
+            path.display()
+        );
+    }
+
+    let err = parse_parallel("define i32 @f() {\n  ret i32 0\n}\ndefine i32 @g() {\n  bogus\n}\n")
+        .unwrap_err();
+    assert_eq!(err.line, 5);
+    assert_eq!(err.construct, "instruction");
+}
@@ -2,9 +2,10 @@ pub mod parser;
 
 pub use parser::parse;
 
-use std::fmt;
+use core::fmt;
 
 #[derive(Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PreemptionSpecifier {
     DsoPreemptable,
     DsoLocal,
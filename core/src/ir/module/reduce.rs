@@ -0,0 +1,332 @@
+//! Reducing test cases, after `llvm-reduce`.
+//!
+//! [`Module::reduce`] shrinks a module while an "interestingness" test keeps
+//! holding for it, e.g. that compiling it still panics:
+//!
+//! ```ignore
+//! let reduced = module.reduce(|module| {
+//!     panic::catch_unwind(AssertUnwindSafe(|| compile_module(X86_64, module))).is_err()
+//! });
+//! ```
+//!
+//! The module is reduced by the following reductions, run in turn until none
+//! of them shrinks it any further:
+//!
+//! - function definitions become declarations,
+//! - functions and global variables no longer referred to are deleted,
+//! - conditional branches become unconditional, and blocks no longer reached
+//!   are deleted,
+//! - instructions other than terminators are deleted, their uses replaced
+//!   with `undef`.
+//!
+//! Each reduction is tried on the whole module at once, then on halves of
+//! it, quarters, and so on down to single functions, blocks or instructions,
+//! keeping whatever still passes the test. The test is run on copies of the
+//! module and may reject invalid ones.
+
+use super::{extract::declaration_of, name::Name, Module};
+use crate::collections::FxHashSet;
+use crate::prelude::*;
+use crate::{
+    ir::{
+        function::{
+            basic_block::BasicBlockId,
+            clone::{clone_function, ValueMap},
+            instruction::InstructionId,
+            Function,
+        },
+        value::Value,
+    },
+    pass::transform::global_dce::for_each_global_ref_in,
+};
+use core::{mem, ops::Range};
+use id_arena::Arena;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reduction {
+    Bodies,
+    Globals,
+    Branches,
+    Instructions,
+}
+
+impl Module {
+    /// Returns the smallest module found for which `interesting` holds, or a
+    /// copy of `self` if it doesn't hold for `self`.
+    pub fn reduce<F: FnMut(&Module) -> bool>(&self, mut interesting: F) -> Module {
+        let mut module = copy(self);
+        if !interesting(&module) {
+            return module;
+        }
+        loop {
+            let mut reduced = false;
+            for reduction in [
+                Reduction::Bodies,
+                Reduction::Globals,
+                Reduction::Branches,
+                Reduction::Instructions,
+            ] {
+                reduced |= reduction.run(&mut module, &mut interesting);
+            }
+            if !reduced {
+                return module;
+            }
+        }
+    }
+}
+
+impl Reduction {
+    /// Applies the reduction to chunks of halving size of the targets of
+    /// `module`, keeping what is interesting. Returns true if any was kept.
+    fn run<F: FnMut(&Module) -> bool>(self, module: &mut Module, interesting: &mut F) -> bool {
+        let mut reduced = false;
+        let mut chunk = self.num_targets(module);
+        while chunk > 0 {
+            let mut start = 0;
+            while start < self.num_targets(module) {
+                let mut candidate = copy(module);
+                self.apply(&mut candidate, start..start + chunk);
+                if interesting(&candidate) {
+                    // The targets after the chunk have moved to its start.
+                    *module = candidate;
+                    reduced = true;
+                } else {
+                    start += chunk;
+                }
+            }
+            chunk /= 2;
+        }
+        reduced
+    }
+
+    fn num_targets(self, module: &Module) -> usize {
+        match self {
+            Self::Bodies => module
+                .functions
+                .iter()
+                .filter(|(_, f)| !f.is_prototype())
+                .count(),
+            Self::Globals => unreferenced_globals(module).len(),
+            Self::Branches => module
+                .functions
+                .iter()
+                .map(|(_, f)| branching_blocks(f).len())
+                .sum(),
+            Self::Instructions => module
+                .functions
+                .iter()
+                .map(|(_, f)| non_terminators(f).len())
+                .sum(),
+        }
+    }
+
+    /// Applies the reduction to the targets of `module` in `range`.
+    fn apply(self, module: &mut Module, range: Range<usize>) {
+        match self {
+            Self::Bodies => {
+                for (_, func) in module
+                    .functions
+                    .iter_mut()
+                    .filter(|(_, f)| !f.is_prototype())
+                    .skip(range.start)
+                    .take(range.len())
+                {
+                    *func = declaration_of(func);
+                }
+            }
+            Self::Globals => {
+                let globals: FxHashSet<Name> = unreferenced_globals(module)
+                    .into_iter()
+                    .skip(range.start)
+                    .take(range.len())
+                    .collect();
+                module
+                    .global_variables
+                    .retain(|name, _| !globals.contains(name));
+                let functions = mem::replace(&mut module.functions, Arena::new());
+                for (_, func) in functions {
+                    if !globals.contains(&Name::from(func.name.as_str())) {
+                        module.functions.alloc(func);
+                    }
+                }
+            }
+            Self::Branches => {
+                let mut index = 0;
+                for (_, func) in module.functions.iter_mut() {
+                    let blocks = branching_blocks(func);
+                    let first = index;
+                    index += blocks.len();
+                    let chosen = &blocks[range.start.clamp(first, index) - first
+                        ..range.end.clamp(first, index) - first];
+                    if chosen.is_empty() {
+                        continue;
+                    }
+                    for &(block, target) in chosen {
+                        func.replace_terminator_with_br(block, target);
+                    }
+                    func.remove_unreachable_blocks();
+                }
+            }
+            Self::Instructions => {
+                let mut index = 0;
+                for (_, func) in module.functions.iter_mut() {
+                    let insts = non_terminators(func);
+                    let first = index;
+                    index += insts.len();
+                    let chosen = &insts[range.start.clamp(first, index) - first
+                        ..range.end.clamp(first, index) - first];
+                    for &inst in chosen {
+                        let undef = func.data.create_value(Value::undef());
+                        func.replace_all_uses_with(inst, undef);
+                        func.erase_instruction(inst);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Returns a copy of `module`, sharing its types.
+fn copy(module: &Module) -> Module {
+    let mut functions = Arena::new();
+    for (_, func) in &module.functions {
+        functions.alloc(clone_function(func, &ValueMap::new()));
+    }
+    Module {
+        name: module.name.clone(),
+        source_filename: module.source_filename.clone(),
+        target: module.target.clone(),
+        functions,
+        attributes: module.attributes.clone(),
+        global_variables: module.global_variables.clone(),
+        comdats: module.comdats.clone(),
+        types: module.types.clone(),
+        metas: module.metas.clone(),
+    }
+}
+
+/// Returns the names of the functions and global variables that nothing else
+/// refers to, functions first.
+fn unreferenced_globals(module: &Module) -> Vec<Name> {
+    let mut referred = FxHashSet::default();
+    let mut refer = |name: &Name| {
+        referred.insert(*name);
+    };
+    for (_, func) in &module.functions {
+        for_each_global_ref_in(func, &mut refer);
+    }
+    for gv in module.global_variables.values() {
+        if let Some(init) = &gv.init {
+            init.for_each_global_ref(&mut refer);
+        }
+    }
+
+    let mut gvs: Vec<Name> = module
+        .global_variables
+        .keys()
+        .filter(|name| !referred.contains(name))
+        .cloned()
+        .collect();
+    gvs.sort_by_key(|name| format!("{:?}", name));
+    module
+        .functions
+        .iter()
+        .map(|(_, f)| Name::from(f.name.as_str()))
+        .filter(|name| !referred.contains(name))
+        .chain(gvs)
+        .collect()
+}
+
+/// Returns the blocks of `func` with several successors, each with the first
+/// of them.
+fn branching_blocks(func: &Function) -> Vec<(BasicBlockId, BasicBlockId)> {
+    func.layout
+        .block_iter()
+        .filter_map(|block| {
+            let term = func.layout.block_node(block).last_inst().as_ref()?;
+            let succs = func.data.inst_ref(*term).operand.successors();
+            let &first = succs.first()?;
+            succs
+                .iter()
+                .any(|&succ| succ != first)
+                .then_some((block, first))
+        })
+        .collect()
+}
+
+fn non_terminators(func: &Function) -> Vec<InstructionId> {
+    func.layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .filter(|&inst| !func.data.inst_ref(inst).opcode.is_terminator())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::{function::instruction::Opcode, module::parse_assembly};
+
+    #[test]
+    fn reduce() {
+        let module = parse_assembly(
+            r#"
+@g = global i32 1
+@unused = global i32 2
+
+define internal i32 @helper(i32 %x) {
+  %1 = add i32 %x, 1
+  ret i32 %1
+}
+
+define i32 @f(i32 %x, i32 %y) {
+entry:
+  %c = icmp eq i32 %x, 0
+  br i1 %c, label %then, label %else
+then:
+  %a = call i32 @helper(i32 %y)
+  %b = load i32, i32* @g
+  %q = sdiv i32 %a, %b
+  br label %exit
+else:
+  %r = mul i32 %x, %y
+  br label %exit
+exit:
+  %p = phi i32 [ %q, %then ], [ %r, %else ]
+  ret i32 %p
+}
+
+define i32 @main() {
+  %1 = call i32 @f(i32 0, i32 1)
+  ret i32 %1
+}
+"#,
+        )
+        .unwrap();
+
+        let mut tests = 0;
+        let has_sdiv = |module: &Module| {
+            module.functions.iter().any(|(_, f)| {
+                f.layout.block_iter().any(|block| {
+                    f.layout
+                        .inst_iter(block)
+                        .any(|inst| f.data.inst_ref(inst).opcode == Opcode::SDiv)
+                })
+            })
+        };
+        let reduced = module.reduce(|module| {
+            tests += 1;
+            has_sdiv(module)
+        });
+        assert!(tests > 1);
+        assert!(has_sdiv(&reduced));
+        assert_eq!(reduced.functions.len(), 1);
+        assert!(reduced.global_variables.is_empty());
+        let f = reduced.find_function_by_name("f").unwrap();
+        insta::assert_debug_snapshot!(reduced.functions[f]);
+
+        // Nothing is done unless the module is interesting.
+        let same = module.reduce(|_| false);
+        assert_eq!(same.functions.len(), 3);
+    }
+}
@@ -2,9 +2,10 @@ pub mod parser;
 
 pub use parser::parse;
 
-use std::fmt;
+use core::fmt;
 
 #[derive(Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnnamedAddr {
     Local,
     Global,
@@ -2,8 +2,10 @@ pub mod parser;
 
 pub use parser::parse_visibility as parse;
 
-use std::fmt;
+use core::fmt;
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Visibility {
     Default,
     Hidden,
@@ -0,0 +1,417 @@
+//! `serde` support, with the `serde` feature.
+//!
+//! [`Module`] implements `Serialize` and `Deserialize`, so that modules can be
+//! cached to disk or sent to another process without going through the
+//! textual assembly. So do functions, types and the rest of the IR, but a
+//! [`Function`] or a [`Type`](crate::ir::types::Type) only deserializes as
+//! part of a module, since it refers to the types of its module.
+//!
+//! Arena IDs serialize as their index in the arena. A deserialized module
+//! gets new arenas, and the IDs in it are rebuilt to point into them.
+
+use crate::ir::{
+    function::{
+        basic_block::BasicBlock, data::Data, instruction::Instruction, layout::Layout, Function,
+    },
+    module::{global_variable::GlobalVariable, name::Name, Module},
+    types::Types,
+    value::Value,
+};
+use core::{any::TypeId, cell::RefCell, fmt};
+use id_arena::{Arena, ArenaBehavior, DefaultArenaBehavior, Id};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+#[derive(Default)]
+struct Context {
+    /// The types of the module being deserialized.
+    types: Option<Types>,
+    /// The arena IDs of the function being deserialized, by type of element.
+    arenas: Vec<(TypeId, u32)>,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::default());
+}
+
+/// Runs `f` with `types` as the types of the module being deserialized.
+pub(crate) fn with_types<R>(types: &Types, f: impl FnOnce() -> R) -> R {
+    let prev = CONTEXT.with(|c| c.borrow_mut().types.replace(types.clone()));
+    let result = f();
+    CONTEXT.with(|c| c.borrow_mut().types = prev);
+    result
+}
+
+/// Returns the types of the module being deserialized.
+pub(crate) fn types<E: de::Error>() -> Result<Types, E> {
+    CONTEXT
+        .with(|c| c.borrow().types.clone())
+        .ok_or_else(|| E::custom("types can only be deserialized as part of a module"))
+}
+
+fn with_arenas<R>(arenas: Vec<(TypeId, u32)>, f: impl FnOnce() -> R) -> R {
+    let prev = CONTEXT.with(|c| core::mem::replace(&mut c.borrow_mut().arenas, arenas));
+    let result = f();
+    CONTEXT.with(|c| c.borrow_mut().arenas = prev);
+    result
+}
+
+fn arena_id<T>(arena: &Arena<T>) -> u32 {
+    DefaultArenaBehavior::<T>::arena_id(arena.next_id())
+}
+
+fn deserialize_index<T: 'static, E: de::Error>(index: usize) -> Result<Id<T>, E> {
+    let arena_id = CONTEXT.with(|c| {
+        c.borrow()
+            .arenas
+            .iter()
+            .find(|(ty, _)| *ty == TypeId::of::<T>())
+            .map(|&(_, id)| id)
+    });
+    arena_id
+        .map(|arena_id| DefaultArenaBehavior::<T>::new_id(arena_id, index))
+        .ok_or_else(|| E::custom("IDs can only be deserialized as part of a function"))
+}
+
+/// `#[serde(with)]` for an `Id<T>`.
+pub(crate) mod id {
+    use super::*;
+
+    pub fn serialize<T, S: Serializer>(id: &Id<T>, serializer: S) -> Result<S::Ok, S::Error> {
+        id.index().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: 'static, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Id<T>, D::Error> {
+        deserialize_index(usize::deserialize(deserializer)?)
+    }
+}
+
+/// `#[serde(with)]` for an `Option<Id<T>>`.
+pub(crate) mod option_id {
+    use super::*;
+
+    pub fn serialize<T, S: Serializer>(
+        id: &Option<Id<T>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        id.map(|id| id.index()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, T: 'static, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Id<T>>, D::Error> {
+        Option::<usize>::deserialize(deserializer)?
+            .map(deserialize_index)
+            .transpose()
+    }
+}
+
+/// `#[serde(with)]` for a collection of `Id<T>`s.
+pub(crate) mod ids {
+    use super::*;
+
+    pub trait Ids<T>: Sized {
+        fn from_ids(ids: Vec<Id<T>>) -> Option<Self>;
+    }
+
+    impl<T> Ids<T> for Vec<Id<T>> {
+        fn from_ids(ids: Vec<Id<T>>) -> Option<Self> {
+            Some(ids)
+        }
+    }
+
+    impl<T, const N: usize> Ids<T> for [Id<T>; N] {
+        fn from_ids(ids: Vec<Id<T>>) -> Option<Self> {
+            ids.try_into().ok()
+        }
+    }
+
+    impl<T, H: core::hash::BuildHasher + Default> Ids<T> for std::collections::HashSet<Id<T>, H> {
+        fn from_ids(ids: Vec<Id<T>>) -> Option<Self> {
+            Some(ids.into_iter().collect())
+        }
+    }
+
+    pub fn serialize<'a, T: 'a, C, S: Serializer>(
+        ids: &'a C,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        &'a C: IntoIterator<Item = &'a Id<T>>,
+    {
+        serializer.collect_seq(ids.into_iter().map(|id| id.index()))
+    }
+
+    pub fn deserialize<'de, T: 'static, C: Ids<T>, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<C, D::Error> {
+        let ids = Vec::<usize>::deserialize(deserializer)?
+            .into_iter()
+            .map(deserialize_index)
+            .collect::<Result<Vec<_>, _>>()?;
+        let len = ids.len();
+        C::from_ids(ids).ok_or_else(|| de::Error::invalid_length(len, &"a fixed number of IDs"))
+    }
+}
+
+/// Reads the next element of a tuple.
+pub(crate) fn next<'de, T: Deserialize<'de>, A: SeqAccess<'de>>(
+    seq: &mut A,
+    index: usize,
+    expected: &dyn de::Expected,
+) -> Result<T, A::Error> {
+    seq.next_element()?
+        .ok_or_else(|| de::Error::invalid_length(index, expected))
+}
+
+impl Serialize for Module {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(8)?;
+        // The types come first, as everything else refers to them.
+        tuple.serialize_element(&self.types)?;
+        tuple.serialize_element(&self.name)?;
+        tuple.serialize_element(&self.source_filename)?;
+        tuple.serialize_element(&self.target)?;
+        tuple.serialize_element(&self.attributes)?;
+        tuple.serialize_element(&self.global_variables.values().collect::<Vec<_>>())?;
+        tuple.serialize_element(&self.metas.iter().collect::<Vec<_>>())?;
+        tuple.serialize_element(&self.functions.iter().map(|(_, f)| f).collect::<Vec<_>>())?;
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Module {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ModuleVisitor;
+
+        impl<'de> Visitor<'de> for ModuleVisitor {
+            type Value = Module;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a module")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Module, A::Error> {
+                let types: Types = next(&mut seq, 0, &self)?;
+                with_types(&types, || {
+                    let mut module = Module {
+                        name: next(&mut seq, 1, &self)?,
+                        source_filename: next(&mut seq, 2, &self)?,
+                        target: next(&mut seq, 3, &self)?,
+                        attributes: next(&mut seq, 4, &self)?,
+                        types: types.clone(),
+                        ..Module::default()
+                    };
+                    let gvs: Vec<GlobalVariable> = next(&mut seq, 5, &self)?;
+                    for gv in gvs {
+                        module.global_variables.insert(gv.name, gv);
+                    }
+                    let metas: Vec<(Name, _)> = next(&mut seq, 6, &self)?;
+                    module.metas.extend(metas);
+                    let funcs: Vec<Function> = next(&mut seq, 7, &self)?;
+                    for func in funcs {
+                        module.functions.alloc(func);
+                    }
+                    Ok(module)
+                })
+            }
+        }
+
+        deserializer.deserialize_tuple(8, ModuleVisitor)
+    }
+}
+
+impl Serialize for Function {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let layout: Vec<(usize, Vec<usize>)> = self
+            .layout
+            .block_iter()
+            .map(|block| {
+                let insts = self.layout.inst_iter(block).map(|inst| inst.index());
+                (block.index(), insts.collect())
+            })
+            .collect();
+        let mut tuple = serializer.serialize_tuple(15)?;
+        tuple.serialize_element(&self.name)?;
+        tuple.serialize_element(&self.is_var_arg)?;
+        tuple.serialize_element(&self.result_ty)?;
+        tuple.serialize_element(&self.params)?;
+        tuple.serialize_element(&self.linkage)?;
+        tuple.serialize_element(&self.preemption_specifier)?;
+        tuple.serialize_element(&self.visibility)?;
+        tuple.serialize_element(&self.unnamed_addr)?;
+        tuple.serialize_element(&self.func_attrs)?;
+        tuple.serialize_element(&self.ret_attrs)?;
+        tuple.serialize_element(&self.personality)?;
+        tuple.serialize_element(&self.data.values.iter().map(|(_, v)| v).collect::<Vec<_>>())?;
+        tuple.serialize_element(
+            &self
+                .data
+                .instructions
+                .iter()
+                .map(|(_, i)| i)
+                .collect::<Vec<_>>(),
+        )?;
+        tuple.serialize_element(
+            &self
+                .data
+                .basic_blocks
+                .iter()
+                .map(|(_, b)| b)
+                .collect::<Vec<_>>(),
+        )?;
+        tuple.serialize_element(&layout)?;
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Function {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FunctionVisitor;
+
+        impl<'de> Visitor<'de> for FunctionVisitor {
+            type Value = Function;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a function")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Function, A::Error> {
+                let name: String = next(&mut seq, 0, &self)?;
+                let is_var_arg = next(&mut seq, 1, &self)?;
+                let result_ty = next(&mut seq, 2, &self)?;
+                let params = next(&mut seq, 3, &self)?;
+                let mut func = Function::new(name, result_ty, params, is_var_arg, types()?);
+                func.linkage = next(&mut seq, 4, &self)?;
+                func.preemption_specifier = next(&mut seq, 5, &self)?;
+                func.visibility = next(&mut seq, 6, &self)?;
+                func.unnamed_addr = next(&mut seq, 7, &self)?;
+                func.func_attrs = next(&mut seq, 8, &self)?;
+                func.ret_attrs = next(&mut seq, 9, &self)?;
+                func.personality = next(&mut seq, 10, &self)?;
+
+                let mut data = Data::new();
+                let arenas = vec![
+                    (TypeId::of::<Value>(), arena_id(&data.values)),
+                    (TypeId::of::<Instruction>(), arena_id(&data.instructions)),
+                    (TypeId::of::<BasicBlock>(), arena_id(&data.basic_blocks)),
+                ];
+                let (values, insts, blocks, layout) = with_arenas(arenas, || {
+                    Ok::<_, A::Error>((
+                        next::<Vec<Value>, _>(&mut seq, 11, &self)?,
+                        next::<Vec<Instruction>, _>(&mut seq, 12, &self)?,
+                        next::<Vec<BasicBlock>, _>(&mut seq, 13, &self)?,
+                        next::<Vec<(usize, Vec<usize>)>, _>(&mut seq, 14, &self)?,
+                    ))
+                })?;
+
+                for val in values {
+                    data.values.alloc(val);
+                }
+                let insts: Vec<_> = insts
+                    .into_iter()
+                    .map(|inst| data.instructions.alloc(inst))
+                    .collect();
+                let blocks: Vec<_> = blocks
+                    .into_iter()
+                    .map(|block| data.basic_blocks.alloc(block))
+                    .collect();
+                let num_values = data.values.len();
+                let out_of_range = || de::Error::custom("ID out of range");
+                for &id in &insts {
+                    data.users_map.insert(id, Default::default());
+                    if data.instructions[id]
+                        .operand
+                        .args()
+                        .iter()
+                        .any(|arg| arg.index() >= num_values)
+                    {
+                        return Err(out_of_range());
+                    }
+                }
+                for &id in &insts {
+                    data.validate_inst_uses(id);
+                }
+
+                let mut func_layout = Layout::new();
+                for (block, block_insts) in layout {
+                    let block = *blocks.get(block).ok_or_else(out_of_range)?;
+                    func_layout.append_block(block);
+                    for inst in block_insts {
+                        func_layout.append_inst(*insts.get(inst).ok_or_else(out_of_range)?, block);
+                    }
+                }
+                func.data = data;
+                func.layout = func_layout;
+                Ok(func)
+            }
+        }
+
+        deserializer.deserialize_tuple(15, FunctionVisitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn round_trip() {
+        let module = parse_assembly(
+            r#"
+%struct.S = type { i32, %struct.S* }
+
+@g = global i32 1
+@s = private constant [3 x i8] c"hi\00"
+
+declare i32 @puts(i8*)
+
+define internal i32 @f(%struct.S* %s, i32 %n) #0 {
+entry:
+  %p = getelementptr inbounds %struct.S, %struct.S* %s, i32 0, i32 0
+  %x = load i32, i32* %p, align 4, !dbg !1
+  br label %loop
+loop:
+  %i = phi i32 [ 0, %entry ], [ %j, %loop ]
+  %j = add nsw i32 %i, %x
+  %c = icmp slt i32 %j, %n
+  br i1 %c, label %loop, label %exit
+exit:
+  %1 = call i32 @puts(i8* getelementptr inbounds ([3 x i8], [3 x i8]* @s, i64 0, i64 0))
+  ret i32 %j
+}
+
+attributes #0 = { noinline nounwind }
+
+!0 = !{!"unused"}
+!1 = !{i32 1, i32 2}
+"#,
+        )
+        .unwrap();
+
+        let json = serde_json::to_string(&module).unwrap();
+        let deserialized: Module = serde_json::from_str(&json).unwrap();
+        assert_eq!(format!("{:?}", deserialized), format!("{:?}", module));
+        assert!(deserialized.types.base().get_struct("struct.S").is_some());
+
+        let f = &deserialized.functions[deserialized.find_function_by_name("f").unwrap()];
+        let x = f
+            .data
+            .instructions
+            .iter()
+            .find(|(_, inst)| inst.dest == Some(Name::Name("x".into())))
+            .unwrap()
+            .0;
+        assert_eq!(f.data.users_of(x).len(), 1);
+
+        // Compound types refer to the types of their module.
+        assert!(serde_json::from_str::<crate::ir::types::Type>("[true, 1]").is_err());
+        assert!(serde_json::from_str::<crate::ir::types::Type>("[false, 4]").is_ok());
+    }
+}
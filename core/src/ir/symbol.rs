@@ -0,0 +1,148 @@
+//! Interned strings.
+//!
+//! A [`Symbol`] is a handle to a string interned in a table shared by the
+//! whole process, so that it is `Copy` and compares as an integer. The
+//! table is not per module since names print without a module at hand, e.g.
+//! in `Debug` output. Interned strings are never freed, which suits names,
+//! attribute keys and metadata strings, as few distinct ones recur across
+//! modules.
+
+use crate::collections::FxHashMap;
+use crate::prelude::*;
+use crate::sync::RwLock;
+use core::{
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    symbols: FxHashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+fn interner() -> &'static RwLock<Interner> {
+    #[cfg(feature = "std")]
+    {
+        static INTERNER: std::sync::OnceLock<RwLock<Interner>> = std::sync::OnceLock::new();
+        INTERNER.get_or_init(Default::default)
+    }
+    #[cfg(not(feature = "std"))]
+    {
+        static INTERNER: spin::Once<RwLock<Interner>> = spin::Once::new();
+        INTERNER.call_once(Default::default)
+    }
+}
+
+impl Symbol {
+    pub fn intern(s: &str) -> Self {
+        if let Some(&symbol) = interner().read().symbols.get(s) {
+            return symbol;
+        }
+        let mut interner = interner().write();
+        if let Some(&symbol) = interner.symbols.get(s) {
+            return symbol;
+        }
+        let s: &'static str = Box::leak(s.into());
+        let symbol = Self(interner.strings.len() as u32);
+        interner.strings.push(s);
+        interner.symbols.insert(s, symbol);
+        symbol
+    }
+
+    pub fn as_str(self) -> &'static str {
+        interner().read().strings[self.0 as usize]
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Symbol {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Self::intern(s)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(s: String) -> Self {
+        Self::intern(&s)
+    }
+}
+
+/// Hashes the string rather than the index, which depends on the order
+/// threads intern in, so that maps keyed by symbols iterate alike from run
+/// to run.
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn intern() {
+        let a = Symbol::intern("main");
+        assert_eq!(a, Symbol::from(String::from("main")));
+        assert_ne!(a, Symbol::intern("mai"));
+        assert_eq!(a, "main");
+        assert_eq!(a.len(), 4);
+        assert_eq!(format!("{} {:?}", a, a), "main \"main\"");
+    }
+}
@@ -0,0 +1,183 @@
+//! Sizes, alignments and field offsets of types, laid out by a [`DataLayout`].
+//!
+//! This is the one layout computation shared by passes, the interpreter and
+//! backends, so that they agree on where things are in memory. Sizes and
+//! alignments are in bytes.
+
+use super::{
+    ArrayType, CompoundType, StructType, Type, Types, VectorType, DOUBLE, FLOAT, I1, I128, I16,
+    I32, I64, I8,
+};
+use crate::ir::module::data_layout::DataLayout;
+use crate::prelude::*;
+
+/// An iterator over the offsets of the fields of a struct, in order.
+pub struct FieldOffsets<'a> {
+    types: &'a Types,
+    layout: &'a DataLayout,
+    elems: Vec<Type>,
+    is_packed: bool,
+    next: usize,
+    offset: u32,
+}
+
+impl Types {
+    /// Returns the size and the ABI alignment of `ty`. The size includes the
+    /// padding up to the alignment, i.e. it is the distance between
+    /// consecutive elements of an array of `ty`.
+    pub fn size_and_align(&self, ty: Type, layout: &DataLayout) -> (u32, u32) {
+        if let Some(bits) = int_bits(ty) {
+            let align = layout.int_align(bits);
+            return (round_up(bits.div_ceil(8), align), align);
+        }
+        if let Some(bits) = float_bits(ty) {
+            let align = layout.float_align(bits);
+            return (round_up(bits / 8, align), align);
+        }
+        let Some(compound) = self.get(ty).map(|ty| ty.clone()) else {
+            return (0, 1);
+        };
+        match compound {
+            CompoundType::Pointer(_) => (layout.pointer_size, layout.pointer_align),
+            CompoundType::Array(ArrayType {
+                inner,
+                num_elements,
+            }) => {
+                let (size, align) = self.size_and_align(inner, layout);
+                (size * num_elements, align)
+            }
+            // Vectors are aligned to their size, which is a power of two on
+            // the targets supported.
+            CompoundType::Vector(VectorType {
+                inner,
+                num_elements,
+            }) => {
+                let elem_bits = int_bits(inner)
+                    .or_else(|| float_bits(inner))
+                    .unwrap_or(layout.pointer_size * 8);
+                let size = (elem_bits * num_elements).div_ceil(8);
+                let align = size.next_power_of_two();
+                (round_up(size, align), align)
+            }
+            CompoundType::Struct(StructType {
+                elems, is_packed, ..
+            }) => {
+                let align = if is_packed {
+                    1
+                } else {
+                    elems
+                        .iter()
+                        .map(|&elem| self.size_and_align(elem, layout).1)
+                        .max()
+                        .unwrap_or(1)
+                };
+                let end = match (self.field_offsets(ty, layout).last(), elems.last()) {
+                    (Some(offset), Some(&last)) => offset + self.size_and_align(last, layout).0,
+                    _ => 0,
+                };
+                (round_up(end, align), align)
+            }
+            CompoundType::Alias(ty) => self.size_and_align(ty, layout),
+            CompoundType::Function(_) | CompoundType::Metadata => (0, 1),
+        }
+    }
+
+    /// Returns the offsets of the fields of the struct `ty`. There are none
+    /// if `ty` is not a struct.
+    pub fn field_offsets<'a>(&'a self, ty: Type, layout: &'a DataLayout) -> FieldOffsets<'a> {
+        let (elems, is_packed) = match self.get(ty).as_deref() {
+            Some(CompoundType::Struct(StructType {
+                elems, is_packed, ..
+            })) => (elems.clone(), *is_packed),
+            Some(&CompoundType::Alias(ty)) => return self.field_offsets(ty, layout),
+            _ => (vec![], false),
+        };
+        FieldOffsets {
+            types: self,
+            layout,
+            elems,
+            is_packed,
+            next: 0,
+            offset: 0,
+        }
+    }
+}
+
+impl Iterator for FieldOffsets<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let &elem = self.elems.get(self.next)?;
+        let (size, align) = self.types.size_and_align(elem, self.layout);
+        if !self.is_packed {
+            self.offset = round_up(self.offset, align);
+        }
+        let offset = self.offset;
+        self.offset += size;
+        self.next += 1;
+        Some(offset)
+    }
+}
+
+fn int_bits(ty: Type) -> Option<u32> {
+    match ty {
+        I1 => Some(1),
+        I8 => Some(8),
+        I16 => Some(16),
+        I32 => Some(32),
+        I64 => Some(64),
+        I128 => Some(128),
+        _ => None,
+    }
+}
+
+fn float_bits(ty: Type) -> Option<u32> {
+    match ty {
+        FLOAT => Some(32),
+        DOUBLE => Some(64),
+        _ => None,
+    }
+}
+
+fn round_up(n: u32, align: u32) -> u32 {
+    n.div_ceil(align.max(1)) * align.max(1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn lay_out_32_and_64_bit() {
+        let module = parse_assembly(
+            r#"
+%struct.S = type { i8, i32*, i64, [3 x i16] }
+%struct.T = type { %struct.S, i1 }
+%struct.W = type { i8, i128 }
+"#,
+        )
+        .unwrap();
+        let types = &module.types;
+        let s = types.base().get_struct("struct.S").unwrap();
+        let t = types.base().get_struct("struct.T").unwrap();
+
+        let x86_64 = DataLayout::default();
+        let offsets: Vec<u32> = types.field_offsets(s, &x86_64).collect();
+        assert_eq!(offsets, vec![0, 8, 16, 24]);
+        assert_eq!(types.size_and_align(s, &x86_64), (32, 8));
+        assert_eq!(types.field_offsets(t, &x86_64).nth(1), Some(32));
+        assert_eq!(types.size_and_align(t, &x86_64), (40, 8));
+        let w = types.base().get_struct("struct.W").unwrap();
+        assert_eq!(types.field_offsets(w, &x86_64).nth(1), Some(16));
+        assert_eq!(types.size_and_align(w, &x86_64), (32, 16));
+
+        let i386: DataLayout = "e-p:32:32-i64:32".parse().unwrap();
+        let offsets: Vec<u32> = types.field_offsets(s, &i386).collect();
+        assert_eq!(offsets, vec![0, 4, 8, 16]);
+        assert_eq!(types.size_and_align(s, &i386), (24, 4));
+        assert_eq!(types.size_and_align(t, &i386), (28, 4));
+
+        assert_eq!(types.field_offsets(I32, &x86_64).count(), 0);
+    }
+}
@@ -1,13 +1,19 @@
+pub mod layout;
 pub mod parser;
-
-use crate::ir::module::name::Name;
-use rustc_hash::FxHashMap;
-use std::{
-    cell::{Ref, RefCell, RefMut},
-    fmt, mem,
-    sync::{atomic, atomic::AtomicU32, Arc},
+#[cfg(feature = "serde")]
+mod serialize;
+
+use crate::collections::FxHashMap;
+use crate::ir::{module::name::Name, symbol::Symbol};
+use crate::prelude::*;
+use crate::sync::{
+    read_recursive, MappedRwLockReadGuard, MappedRwLockWriteGuard, RwLock, RwLockReadGuard,
+    RwLockWriteGuard,
 };
+use alloc::sync::Arc;
+use core::{fmt, hash::Hash, sync::atomic, sync::atomic::AtomicU32};
 
+pub use layout::FieldOffsets;
 pub use parser::parse;
 
 pub type AddrSpace = u32;
@@ -23,9 +29,20 @@ pub const I8: Type = Type(0, 2);
 pub const I16: Type = Type(0, 3);
 pub const I32: Type = Type(0, 4);
 pub const I64: Type = Type(0, 5);
-
+pub const I128: Type = Type(0, 6);
+pub const FLOAT: Type = Type(0, 7);
+pub const DOUBLE: Type = Type(0, 8);
+
+/// The types of a module, shared by its functions. Types can be created from
+/// several threads at once, e.g. while parsing functions in parallel, which
+/// makes a [`Module`](crate::ir::module::Module) `Send` and `Sync`. Types
+/// that already exist are looked up under the read lock only.
+///
+/// Cloning `Types` shares the table rather than copying it, so the functions
+/// of a module, and the machine functions compiled from them, all see the
+/// same types.
 #[derive(Clone)]
-pub struct Types(Arc<RefCell<TypesBase>>);
+pub struct Types(Arc<RwLock<TypesBase>>);
 
 pub struct TypesBase {
     arena_id: Idx,
@@ -38,15 +55,20 @@ pub struct TypesBase {
 struct Caches {
     pointer: Cache<PointerType>,
     array: Cache<ArrayType>,
-    named_struct: Cache<String>,
+    vector: Cache<VectorType>,
+    function: Cache<FunctionType>,
+    anonymous_struct: Cache<(Vec<Type>, bool)>,
+    named_struct: Cache<Symbol>,
     named_types: Cache<Name>,
     metadata: Type,
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CompoundType {
     Pointer(PointerType),
     Array(ArrayType),
+    Vector(VectorType),
     Function(FunctionType),
     Struct(StructType),
     Alias(Type),
@@ -54,18 +76,30 @@ pub enum CompoundType {
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PointerType {
     pub inner: Type,
     pub addr_space: AddrSpace,
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ArrayType {
     pub inner: Type,
     pub num_elements: u32,
 }
 
+/// A fixed-width vector `<num_elements x inner>` of integers, floating
+/// points or pointers.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VectorType {
+    pub inner: Type,
+    pub num_elements: u32,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FunctionType {
     pub ret: Type,
     pub params: Vec<Type>,
@@ -73,6 +107,7 @@ pub struct FunctionType {
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StructType {
     pub name: Option<Name>,
     pub elems: Vec<Type>,
@@ -81,7 +116,7 @@ pub struct StructType {
 
 impl Default for Types {
     fn default() -> Self {
-        Self(Arc::new(RefCell::new(TypesBase::new())))
+        Self(Arc::new(RwLock::new(TypesBase::new())))
     }
 }
 
@@ -90,22 +125,30 @@ impl Types {
         Self::default()
     }
 
+    /// Returns `true` if `self` and `other` share the same table, so that
+    /// types of one are types of the other.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
     pub fn to_string(&self, ty: Type) -> String {
         self.base().to_string(ty)
     }
 
-    pub fn get(&self, ty: Type) -> Option<Ref<CompoundType>> {
+    pub fn get(&self, ty: Type) -> Option<MappedRwLockReadGuard<'_, CompoundType>> {
         if ty.is_primitive() {
             return None;
         }
-        Some(Ref::map(self.0.borrow(), |base| base.get(ty).unwrap()))
+        Some(RwLockReadGuard::map(self.base(), |base| {
+            base.get(ty).unwrap()
+        }))
     }
 
-    pub fn get_mut(&self, ty: Type) -> Option<RefMut<CompoundType>> {
+    pub fn get_mut(&self, ty: Type) -> Option<MappedRwLockWriteGuard<'_, CompoundType>> {
         if ty.is_primitive() {
             return None;
         }
-        Some(RefMut::map(self.0.borrow_mut(), |base| {
+        Some(RwLockWriteGuard::map(self.base_mut(), |base| {
             base.get_mut(ty).unwrap()
         }))
     }
@@ -114,12 +157,14 @@ impl Types {
         self.base().element(ty)
     }
 
-    pub fn base(&self) -> Ref<TypesBase> {
-        self.0.borrow()
+    /// Locks the types for reading. Reads may be nested, but like with a
+    /// `RefCell`, writing while reading on the same thread deadlocks.
+    pub fn base(&self) -> RwLockReadGuard<'_, TypesBase> {
+        read_recursive(&self.0)
     }
 
-    pub fn base_mut(&self) -> RefMut<TypesBase> {
-        self.0.borrow_mut()
+    pub fn base_mut(&self) -> RwLockWriteGuard<'_, TypesBase> {
+        self.0.write()
     }
 
     pub fn is_pointer(&self, ty: Type) -> bool {
@@ -130,9 +175,131 @@ impl Types {
         self.base().is_struct(ty)
     }
 
+    pub fn is_vector(&self, ty: Type) -> bool {
+        self.base().is_vector(ty)
+    }
+
+    /// Returns the element type and the number of elements of the vector
+    /// `ty`, if it is one.
+    pub fn vector_elements(&self, ty: Type) -> Option<(Type, u32)> {
+        match self.get(ty).as_deref()? {
+            CompoundType::Vector(VectorType {
+                inner,
+                num_elements,
+            }) => Some((*inner, *num_elements)),
+            &CompoundType::Alias(ty) => self.vector_elements(ty),
+            _ => None,
+        }
+    }
+
     pub fn metadata(&self) -> Type {
         self.base().caches.metadata
     }
+
+    /// Returns `ty*`.
+    pub fn ptr_to(&self, ty: Type) -> Type {
+        self.ptr_to_in(ty, 0)
+    }
+
+    /// Returns `ty addrspace(addr_space)*`.
+    pub fn ptr_to_in(&self, ty: Type, addr_space: AddrSpace) -> Type {
+        let t = PointerType {
+            inner: ty,
+            addr_space,
+        };
+        self.cached_or(t, |c| &c.pointer, TypesBase::pointer)
+    }
+
+    /// Returns `[num_elements x ty]`.
+    pub fn array_of(&self, ty: Type, num_elements: u32) -> Type {
+        let t = ArrayType::new(ty, num_elements);
+        self.cached_or(t, |c| &c.array, TypesBase::array)
+    }
+
+    /// Returns `<num_elements x ty>`.
+    pub fn vector_of(&self, ty: Type, num_elements: u32) -> Type {
+        let t = VectorType::new(ty, num_elements);
+        self.cached_or(t, |c| &c.vector, TypesBase::vector)
+    }
+
+    /// Returns the literal struct `{ elems }`.
+    pub fn struct_of(&self, elems: &[Type]) -> Type {
+        self.cached_or(
+            (elems.to_vec(), false),
+            |c| &c.anonymous_struct,
+            |base, (elems, is_packed)| base.anonymous_struct(elems, is_packed),
+        )
+    }
+
+    /// Returns the literal struct `<{ elems }>`.
+    pub fn packed_struct_of(&self, elems: &[Type]) -> Type {
+        self.cached_or(
+            (elems.to_vec(), true),
+            |c| &c.anonymous_struct,
+            |base, (elems, is_packed)| base.anonymous_struct(elems, is_packed),
+        )
+    }
+
+    /// Returns the function type `ret (params)`, or `ret (params, ...)` if
+    /// `is_var_arg`.
+    pub fn func(&self, ret: Type, params: &[Type], is_var_arg: bool) -> Type {
+        let t = FunctionType::new(ret, params.to_vec(), is_var_arg);
+        self.cached_or(t, |c| &c.function, TypesBase::function)
+    }
+
+    /// Returns the struct named `name`, opaque until given a body with
+    /// [`Types::set_struct_body`] if it doesn't exist yet.
+    pub fn named_struct<N: Into<Symbol>>(&self, name: N) -> Type {
+        self.cached_or(
+            name.into(),
+            |c| &c.named_struct,
+            |base, name| base.empty_struct_named(name, false),
+        )
+    }
+
+    /// Returns the type named `name`, a placeholder until defined with
+    /// [`Types::define_named_type`] if it doesn't exist yet.
+    pub fn named_type(&self, name: Name) -> Type {
+        self.cached_or(name, |c| &c.named_types, TypesBase::empty_named_type)
+    }
+
+    /// Defines the type named `name` as `ty`, which must be a primitive type
+    /// or a struct.
+    pub fn define_named_type(&self, name: Name, ty: Type) {
+        self.base_mut().change_to_named_type(ty, name)
+    }
+
+    /// Returns the type cached for `key`, looked up under the read lock, or
+    /// else creates it under the write lock, so that threads only looking up
+    /// existing types don't wait for each other.
+    fn cached_or<K: Eq + Hash>(
+        &self,
+        key: K,
+        cache: fn(&Caches) -> &Cache<K>,
+        create: impl FnOnce(&mut TypesBase, K) -> Type,
+    ) -> Type {
+        if let Some(&ty) = cache(&self.base().caches).get(&key) {
+            return ty;
+        }
+        create(&mut self.base_mut(), key)
+    }
+
+    /// Sets the elements of the named struct `ty`.
+    pub fn set_struct_body(&self, ty: Type, elems: &[Type], is_packed: bool) {
+        match self.get_mut(ty).as_deref_mut() {
+            Some(CompoundType::Struct(strukt)) if strukt.name.is_some() => {
+                strukt.elems = elems.to_vec();
+                strukt.is_packed = is_packed;
+            }
+            _ => panic!("not a named struct"),
+        }
+    }
+}
+
+impl Default for TypesBase {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TypesBase {
@@ -147,6 +314,9 @@ impl TypesBase {
                 metadata: Type(arena_id, 0),
                 pointer: Cache::default(),
                 array: Cache::default(),
+                vector: Cache::default(),
+                function: Cache::default(),
+                anonymous_struct: Cache::default(),
                 named_struct: Cache::default(),
                 named_types: Cache::default(),
             },
@@ -206,38 +376,64 @@ impl TypesBase {
         ty
     }
 
+    pub fn vector(&mut self, t: VectorType) -> Type {
+        if let Some(ty) = self.caches.vector.get(&t) {
+            return *ty;
+        }
+        let ty = self.new_type(CompoundType::Vector(t.clone()));
+        self.caches.vector.insert(t, ty);
+        ty
+    }
+
     pub fn function(&mut self, t: FunctionType) -> Type {
-        // TODO: FIXME: Should cache function type?
-        self.new_type(CompoundType::Function(t))
+        if let Some(ty) = self.caches.function.get(&t) {
+            return *ty;
+        }
+        let ty = self.new_type(CompoundType::Function(t.clone()));
+        self.caches.function.insert(t, ty);
+        ty
     }
 
     pub fn metadata(&mut self) -> Type {
         self.caches.metadata
     }
 
-    pub fn empty_struct_named(&mut self, name: String, is_packed: bool) -> Type {
+    pub fn empty_struct_named(&mut self, name: Symbol, is_packed: bool) -> Type {
         if let Some(ty) = self.caches.named_struct.get(&name) {
             return *ty;
         }
-        let ty = self.new_type(CompoundType::Struct(StructType {
-            name: Some(Name::Name(name.clone())),
-            elems: vec![],
-            is_packed,
-        }));
-        self.caches.named_struct.insert(name, ty);
+        // Registered as a named type too, so that it's defined when printed.
+        let ty = self.empty_named_type(Name::Name(name));
+        if self.compound_types[ty.1 as usize] == CompoundType::Alias(VOID) {
+            self.compound_types[ty.1 as usize] = CompoundType::Struct(StructType {
+                name: Some(Name::Name(name)),
+                elems: vec![],
+                is_packed,
+            });
+            self.caches.named_struct.insert(name, ty);
+        }
         ty
     }
 
     pub fn anonymous_struct(&mut self, elems: Vec<Type>, is_packed: bool) -> Type {
-        self.new_type(CompoundType::Struct(StructType {
+        let key = (elems, is_packed);
+        if let Some(ty) = self.caches.anonymous_struct.get(&key) {
+            return *ty;
+        }
+        let ty = self.new_type(CompoundType::Struct(StructType {
             name: None,
-            elems,
+            elems: key.0.clone(),
             is_packed,
-        }))
+        }));
+        self.caches.anonymous_struct.insert(key, ty);
+        ty
     }
 
     pub fn get_struct(&self, name: impl AsRef<str>) -> Option<Type> {
-        self.caches.named_struct.get(name.as_ref()).copied()
+        self.caches
+            .named_struct
+            .get(&Symbol::from(name.as_ref()))
+            .copied()
     }
 
     pub fn is_struct(&self, ty: Type) -> bool {
@@ -247,8 +443,16 @@ impl TypesBase {
         matches!(self.compound_types[ty.1 as usize], CompoundType::Struct(_))
     }
 
+    pub fn is_vector(&self, ty: Type) -> bool {
+        match self.get(ty) {
+            Some(CompoundType::Vector(_)) => true,
+            Some(&CompoundType::Alias(ty)) => self.is_vector(ty),
+            _ => false,
+        }
+    }
+
     pub fn change_to_named_type(&mut self, ty: Type, name: Name) {
-        let named_ty = self.empty_named_type(name.clone());
+        let named_ty = self.empty_named_type(name);
 
         match self.get_mut(ty) {
             // primitive types
@@ -256,9 +460,10 @@ impl TypesBase {
                 self.compound_types[named_ty.1 as usize] = CompoundType::Alias(ty);
             }
             // If `ty` is a struct type, name it.
-            Some(CompoundType::Struct(ref mut strukt)) => {
-                let mut strukt = mem::replace(strukt, StructType::default());
-                strukt.name = Some(name.clone());
+            // The literal struct is shared, so it is left as is.
+            Some(CompoundType::Struct(strukt)) => {
+                let mut strukt = strukt.clone();
+                strukt.name = Some(name);
                 if let Name::Name(name) = name {
                     self.caches.named_struct.insert(name, named_ty);
                 }
@@ -272,6 +477,7 @@ impl TypesBase {
         match self.get(ty)? {
             CompoundType::Pointer(PointerType { inner, .. }) => Some(*inner),
             CompoundType::Array(ArrayType { inner, .. }) => Some(*inner),
+            CompoundType::Vector(VectorType { inner, .. }) => Some(*inner),
             CompoundType::Struct(_) => None,
             CompoundType::Function(_) => None,
             CompoundType::Alias(t) => self.element(*t),
@@ -284,6 +490,7 @@ impl TypesBase {
             CompoundType::Pointer(PointerType { inner, .. }) if i == 0 => Some(*inner),
             CompoundType::Pointer(_) => None,
             CompoundType::Array(ArrayType { inner, .. }) => Some(*inner),
+            CompoundType::Vector(VectorType { inner, .. }) => Some(*inner),
             CompoundType::Struct(StructType { elems, .. }) => elems.get(i).copied(),
             CompoundType::Function(_) => None,
             CompoundType::Alias(t) => self.element_at(*t, i),
@@ -310,6 +517,12 @@ impl TypesBase {
             }) => {
                 format!("[{} x {}]", num_elements, self.to_string(*inner))
             }
+            CompoundType::Vector(VectorType {
+                inner,
+                num_elements,
+            }) => {
+                format!("<{} x {}>", num_elements, self.to_string(*inner))
+            }
             CompoundType::Function(FunctionType {
                 ret,
                 params,
@@ -396,6 +609,23 @@ impl Type {
         self == &I64
     }
 
+    pub fn is_i128(&self) -> bool {
+        self == &I128
+    }
+
+    pub fn is_float(&self) -> bool {
+        self == &FLOAT
+    }
+
+    pub fn is_double(&self) -> bool {
+        self == &DOUBLE
+    }
+
+    /// Returns true for `float` and `double`.
+    pub fn is_floating_point(&self) -> bool {
+        self.is_float() || self.is_double()
+    }
+
     pub fn is_pointer(&self, types: &Types) -> bool {
         types.is_pointer(*self)
     }
@@ -403,6 +633,10 @@ impl Type {
     pub fn is_struct(&self, types: &Types) -> bool {
         types.is_struct(*self)
     }
+
+    pub fn is_vector(&self, types: &Types) -> bool {
+        types.is_vector(*self)
+    }
 }
 
 impl ArrayType {
@@ -414,6 +648,15 @@ impl ArrayType {
     }
 }
 
+impl VectorType {
+    pub fn new(inner: Type, num_elements: u32) -> Self {
+        Self {
+            inner,
+            num_elements,
+        }
+    }
+}
+
 impl FunctionType {
     pub fn new(ret: Type, params: Vec<Type>, is_var_arg: bool) -> Self {
         Self {
@@ -424,20 +667,23 @@ impl FunctionType {
     }
 }
 
-impl ToString for Type {
-    fn to_string(&self) -> String {
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.is_primitive() {
-            return match self {
-                &VOID => "void".to_string(),
-                &I1 => "i1".to_string(),
-                &I8 => "i8".to_string(),
-                &I16 => "i16".to_string(),
-                &I32 => "i32".to_string(),
-                &I64 => "i64".to_string(),
+            return match *self {
+                VOID => write!(f, "void"),
+                I1 => write!(f, "i1"),
+                I8 => write!(f, "i8"),
+                I16 => write!(f, "i16"),
+                I32 => write!(f, "i32"),
+                I64 => write!(f, "i64"),
+                I128 => write!(f, "i128"),
+                FLOAT => write!(f, "float"),
+                DOUBLE => write!(f, "double"),
                 _ => todo!(),
             };
         }
-        format!("{:?}", self)
+        write!(f, "{:?}", self)
     }
 }
 
@@ -492,3 +738,61 @@ fn types_identity() {
 
     assert_eq!(i32_ptr_ty, i32_ptr_ty2);
 }
+
+#[test]
+fn types_constructors() {
+    let types = Types::new();
+    assert_eq!(types.ptr_to(I8), types.ptr_to(I8));
+    assert_eq!(types.array_of(I32, 4), types.array_of(I32, 4));
+    assert_eq!(types.struct_of(&[I32, I64]), types.struct_of(&[I32, I64]));
+    assert_ne!(
+        types.struct_of(&[I32, I64]),
+        types.packed_struct_of(&[I32, I64])
+    );
+    let f = types.func(I32, &[I32], true);
+    assert_eq!(f, types.func(I32, &[I32], true));
+    assert_eq!(types.to_string(f), "i32 (i32, ...)");
+
+    let list = types.named_struct("list");
+    let list_ptr = types.ptr_to(list);
+    types.set_struct_body(list, &[I32, list_ptr], false);
+    assert_eq!(list, types.named_struct("list"));
+    assert_eq!(types.base().get_struct("list"), Some(list));
+    assert_eq!(
+        types.to_string(types.struct_of(&[list, list_ptr])),
+        "{ %list, %list* }"
+    );
+    assert_eq!(types.named_type(Name::from("list")), list);
+    assert_eq!(format!("{:?}", types), "%list = type { i32, %list* }\n");
+
+    let node = types.named_type(Name::from("node"));
+    types.define_named_type(Name::from("node"), types.struct_of(&[list]));
+    assert_eq!(types.named_struct("node"), node);
+    assert_eq!(types.to_string(types.ptr_to(node)), "%node*");
+}
+
+#[test]
+fn types_shared_across_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<crate::ir::module::Module>();
+
+    let types = Types::new();
+    let ptrs: Vec<Type> = std::thread::scope(|s| {
+        let threads: Vec<_> = (0..4)
+            .map(|_| s.spawn(|| (0..100).fold(I8, |ty, _| types.ptr_to(ty))))
+            .collect();
+        threads.into_iter().map(|t| t.join().unwrap()).collect()
+    });
+    assert!(ptrs.iter().all(|&ty| ty == ptrs[0]));
+    assert_eq!(types.to_string(ptrs[0]).matches('*').count(), 100);
+}
+
+#[test]
+fn types_clones_share_table() {
+    let types = Types::new();
+    let shared = types.clone();
+    let ptr = shared.ptr_to(I32);
+    assert!(types.ptr_eq(&shared));
+    assert!(!types.ptr_eq(&Types::new()));
+    assert_eq!(types.ptr_to(I32), ptr);
+}
@@ -1,5 +1,9 @@
-use crate::ir::types::{ArrayType, FunctionType, Type, Types, I1, I32, I64, I8, VOID};
+use crate::ir::types::{
+    ArrayType, FunctionType, Type, Types, VectorType, DOUBLE, FLOAT, I1, I128, I16, I32, I64, I8,
+    VOID,
+};
 use crate::ir::{module::name, util::spaces};
+use crate::prelude::*;
 use nom::{
     branch::alt,
     bytes::complete::tag,
@@ -17,17 +21,23 @@ pub fn parse<'a>(source: &'a str, types: &Types) -> IResult<&'a str, Type, Verbo
         parse_struct(source, types, false)?
     } else if let Ok((source, _)) = preceded(spaces, tag("<{"))(source) {
         parse_struct(source, types, true)?
+    } else if let Ok((source, _)) = preceded(spaces, char('<'))(source) {
+        parse_vector(source, types)?
     } else if let Ok((source, name)) = preceded(spaces, preceded(char('%'), name::parse))(source) {
-        (source, types.base_mut().empty_named_type(name))
+        (source, types.named_type(name))
     } else {
         preceded(
             spaces,
             alt((
                 map(tag("void"), |_| VOID),
+                map(tag("i128"), |_| I128),
+                map(tag("i16"), |_| I16),
                 map(tag("i1"), |_| I1),
                 map(tag("i8"), |_| I8),
                 map(tag("i32"), |_| I32),
                 map(tag("i64"), |_| I64),
+                map(tag("float"), |_| FLOAT),
+                map(tag("double"), |_| DOUBLE),
                 map(tag("metadata"), |_| types.metadata()),
             )),
         )(source)?
@@ -67,6 +77,20 @@ fn parse_array<'a>(
     Ok((source, ary_ty))
 }
 
+fn parse_vector<'a>(
+    source: &'a str,
+    types: &Types,
+) -> IResult<&'a str, Type, VerboseError<&'a str>> {
+    let (source, n) = preceded(spaces, digit1)(source)?;
+    let (source, _) = preceded(spaces, char('x'))(source)?;
+    let (source, ty) = parse(source, types)?;
+    let (source, _) = preceded(spaces, char('>'))(source)?;
+    let vec_ty = types
+        .base_mut()
+        .vector(VectorType::new(ty, n.parse::<u32>().unwrap()));
+    Ok((source, vec_ty))
+}
+
 fn parse_struct<'a>(
     mut source: &'a str,
     types: &Types,
@@ -137,3 +161,33 @@ fn test_metadata() {
     let (_, ty) = parse(source, &types).unwrap();
     assert!(types.metadata() == ty)
 }
+
+#[test]
+fn test_i128() {
+    let types = Types::default();
+    let (_, ty) = parse("  i128 ", &types).unwrap();
+    assert!(ty.is_i128());
+    assert_eq!(ty.to_string(), "i128");
+}
+
+#[test]
+fn test_floating_point() {
+    let types = Types::default();
+    let (_, ty) = parse("  double ", &types).unwrap();
+    assert!(ty.is_double() && ty.is_floating_point());
+    assert_eq!(ty.to_string(), "double");
+    let (_, ty) = parse("float*", &types).unwrap();
+    assert_eq!(types.to_string(ty), "float*");
+}
+
+#[test]
+fn test_vector() {
+    let types = Types::default();
+    let (_, ty) = parse(" <4 x float>", &types).unwrap();
+    assert!(ty.is_vector(&types));
+    assert_eq!(types.vector_elements(ty), Some((FLOAT, 4)));
+    assert_eq!(types.to_string(ty), "<4 x float>");
+    assert_eq!(types.vector_of(FLOAT, 4), ty);
+    let (_, ty) = parse("<{ <2 x i64>, i8 }>", &types).unwrap();
+    assert_eq!(types.to_string(ty), "<{ <2 x i64>, i8 }>");
+}
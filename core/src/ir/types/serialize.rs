@@ -0,0 +1,107 @@
+use super::{Cache, CompoundType, StructType, Type, Types};
+use crate::ir::{
+    module::name::Name,
+    serialize::{next, with_types},
+    symbol::Symbol,
+};
+use core::{fmt, hash::Hash};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// A type serializes as whether it is compound, and its index among the
+/// primitive or the compound types.
+impl Serialize for Type {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (!self.is_primitive(), self.1).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Type {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (is_compound, id) = <(bool, u32)>::deserialize(deserializer)?;
+        if !is_compound {
+            return Ok(Type(0, id));
+        }
+        let types = crate::ir::serialize::types()?;
+        let base = types.base();
+        Ok(Type(base.arena_id, id))
+    }
+}
+
+impl Serialize for Types {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let base = self.base();
+        let mut tuple = serializer.serialize_tuple(3)?;
+        tuple.serialize_element(&base.compound_types)?;
+        tuple.serialize_element(&base.caches.named_struct.iter().collect::<Vec<_>>())?;
+        tuple.serialize_element(&base.caches.named_types.iter().collect::<Vec<_>>())?;
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Types {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TypesVisitor;
+
+        impl<'de> Visitor<'de> for TypesVisitor {
+            type Value = Types;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("types")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Types, A::Error> {
+                let types = Types::new();
+                let (compound_types, named_struct, named_types) = with_types(&types, || {
+                    let compound_types: Vec<CompoundType> = next(&mut seq, 0, &self)?;
+                    let named_struct: Vec<(Symbol, Type)> = next(&mut seq, 1, &self)?;
+                    let named_types: Vec<(Name, Type)> = next(&mut seq, 2, &self)?;
+                    Ok::<_, A::Error>((compound_types, named_struct, named_types))
+                })?;
+                if !matches!(compound_types.first(), Some(CompoundType::Metadata)) {
+                    return Err(de::Error::custom(
+                        "the first compound type must be metadata",
+                    ));
+                }
+
+                let mut base = types.base_mut();
+                let caches = &mut base.caches;
+                for (i, ty) in compound_types.iter().enumerate() {
+                    let id = Type(caches.metadata.0, i as u32);
+                    match ty {
+                        CompoundType::Pointer(t) => cache(&mut caches.pointer, t, id),
+                        CompoundType::Array(t) => cache(&mut caches.array, t, id),
+                        CompoundType::Vector(t) => cache(&mut caches.vector, t, id),
+                        CompoundType::Function(t) => cache(&mut caches.function, t, id),
+                        CompoundType::Struct(StructType {
+                            name: None,
+                            elems,
+                            is_packed,
+                        }) => cache(
+                            &mut caches.anonymous_struct,
+                            &(elems.clone(), *is_packed),
+                            id,
+                        ),
+                        _ => {}
+                    }
+                }
+                caches.named_struct.extend(named_struct);
+                caches.named_types.extend(named_types);
+                base.id = compound_types.len() as u32;
+                base.compound_types = compound_types;
+                drop(base);
+                Ok(types)
+            }
+        }
+
+        deserializer.deserialize_tuple(3, TypesVisitor)
+    }
+}
+
+/// Maps `key` to the first type created for it, as `TypesBase` does.
+fn cache<K: Clone + Eq + Hash>(cache: &mut Cache<K>, key: &K, ty: Type) {
+    cache.entry(key.clone()).or_insert(ty);
+}
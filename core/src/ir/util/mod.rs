@@ -1,3 +1,6 @@
+use crate::prelude::*;
+use alloc::collections::VecDeque;
+use core::str;
 use nom::{
     branch::alt,
     bytes::complete::take_until,
@@ -8,7 +11,6 @@ use nom::{
     sequence::{preceded, terminated, tuple},
     IResult,
 };
-use std::{collections::VecDeque, str};
 
 pub fn spaces(source: &str) -> IResult<&str, (), VerboseError<&str>> {
     alt((
@@ -24,6 +26,51 @@ pub fn spaces(source: &str) -> IResult<&str, (), VerboseError<&str>> {
     ))(source)
 }
 
+/// Returns `source` from the first character in `stops` that is not nested
+/// in brackets, braces, parentheses, angle brackets or a string, skipping
+/// comments. Returns an empty string if there is no such character.
+pub fn skip_nested<'a>(source: &'a str, stops: &[char]) -> &'a str {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut in_comment = false;
+    for (i, c) in source.char_indices() {
+        if in_comment {
+            if c != '\n' {
+                continue;
+            }
+            in_comment = false;
+        }
+        match c {
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            ';' => in_comment = true,
+            _ if depth == 0 && stops.contains(&c) => return &source[i..],
+            '(' | '[' | '{' | '<' => depth += 1,
+            ')' | ']' | '}' | '>' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    &source[source.len()..]
+}
+
+/// Returns the nesting depth in brackets, braces, parentheses and angle
+/// brackets after `line`, starting at `depth`, ignoring strings and
+/// comments as [`skip_nested`] does.
+pub fn nesting_depth(line: &str, mut depth: usize) -> usize {
+    let mut in_string = false;
+    for c in line.chars() {
+        match c {
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            ';' => break,
+            '(' | '[' | '{' | '<' => depth += 1,
+            ')' | ']' | '}' | '>' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    depth
+}
+
 pub fn string_literal(source: &str) -> IResult<&str, String, VerboseError<&str>> {
     map(
         preceded(char('\"'), cut(terminated(take_until("\""), char('\"')))),
@@ -31,6 +78,54 @@ pub fn string_literal(source: &str) -> IResult<&str, String, VerboseError<&str>>
     )(source)
 }
 
+/// Parses a string literal like [`string_literal`], as the bytes it escapes,
+/// e.g. the contents of a `c"..."` array.
+pub fn byte_string_literal(source: &str) -> IResult<&str, Vec<u8>, VerboseError<&str>> {
+    map(
+        preceded(char('\"'), cut(terminated(take_until("\""), char('\"')))),
+        |s| unescape_bytes(s).unwrap(),
+    )(source)
+}
+
+/// Returns the bytes escaped by `s`, where `\XX` is the byte of hex `XX`.
+pub fn unescape_bytes(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, rest_)) = rest.split_first() {
+        rest = rest_;
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        match rest {
+            [b'\\', rest_ @ ..] => {
+                bytes.push(b'\\');
+                rest = rest_;
+            }
+            [hi, lo, rest_ @ ..] if hi.is_ascii_hexdigit() && lo.is_ascii_hexdigit() => {
+                bytes.push(u8::from_str_radix(str::from_utf8(&[*hi, *lo]).ok()?, 16).ok()?);
+                rest = rest_;
+            }
+            _ => return None,
+        }
+    }
+    Some(bytes)
+}
+
+/// Escapes `bytes` for a `c"..."` array: bytes other than printable ASCII,
+/// and `"` and `\`, are written as `\XX`.
+pub fn escape_bytes(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len());
+    for &b in bytes {
+        if (b.is_ascii_graphic() || b == b' ') && b != b'"' && b != b'\\' {
+            s.push(b as char);
+        } else {
+            s.push_str(&format!("\\{:02x}", b));
+        }
+    }
+    s
+}
+
 pub fn unescape(s: &str) -> Option<String> {
     let mut queue: VecDeque<_> = String::from(s).chars().collect();
     let mut s = String::new();
@@ -43,7 +138,7 @@ pub fn unescape(s: &str) -> Option<String> {
 
         match queue.pop_front() {
             Some('\\') => s.push('\\'),
-            Some(c) if c.is_digit(16) => {
+            Some(c) if c.is_ascii_hexdigit() => {
                 let cc = queue.pop_front().unwrap();
                 s.push(
                     char::from_u32(
@@ -0,0 +1,276 @@
+//! Constant folding.
+//!
+//! [`fold_constant`] evaluates an instruction whose operands are all
+//! constants. It is shared by the passes folding instructions, i.e.
+//! [`sccp`](crate::pass::transform::sccp),
+//! [`inst_combine`](crate::pass::transform::inst_combine) and
+//! [`constant_fold`](crate::pass::transform::constant_fold), and by the
+//! interpreter, so that they all agree on what each operation computes.
+//!
+//! Operations whose result is undefined, i.e. divisions by zero, signed
+//! divisions overflowing and shifts by the bit width or more, are not folded.
+
+use crate::ir::{
+    function::instruction::{Cast, GetElementPtr, ICmp, ICmpCond, Opcode, Operand},
+    types::{Type, Types},
+    value::{ConstantData, ConstantExpr, ConstantInt},
+};
+use crate::prelude::*;
+
+/// Returns the constant computed by an instruction with `opcode` and
+/// `operand` whose arguments are the constants `args`, in the order of
+/// [`Operand::args`], or `None` if it can't be folded.
+///
+/// `getelementptr`s are folded into constant expressions when they index a
+/// global with constant indices, as are `bitcast`s of such pointers.
+pub fn fold_constant(
+    opcode: Opcode,
+    operand: &Operand,
+    args: &[ConstantData],
+    types: &Types,
+) -> Option<ConstantData> {
+    match (operand, args) {
+        (Operand::IntBinary(_), [ConstantData::Int(x), ConstantData::Int(y)]) => {
+            int_binary(opcode, *x, *y).map(ConstantData::Int)
+        }
+        (Operand::ICmp(ICmp { cond, .. }), [x, y]) => {
+            let is_true = match (x, y) {
+                (ConstantData::Int(x), ConstantData::Int(y)) => icmp(*cond, *x, *y)?,
+                (ConstantData::Null, ConstantData::Null) => icmp_eq(*cond, true)?,
+                // Globals are never null.
+                (ConstantData::GlobalRef(_), ConstantData::Null)
+                | (ConstantData::Null, ConstantData::GlobalRef(_)) => icmp_eq(*cond, false)?,
+                _ => return None,
+            };
+            Some(ConstantInt::Int1(is_true).into())
+        }
+        (Operand::Cast(Cast { tys, .. }), [arg]) => match (opcode, arg) {
+            (_, ConstantData::Int(c)) if opcode != Opcode::IntToPtr => {
+                cast_int(opcode, *c, tys[1]).map(ConstantData::Int)
+            }
+            (Opcode::IntToPtr, ConstantData::Int(c)) if c.cast_to_i64() == 0 => {
+                Some(ConstantData::Null)
+            }
+            (Opcode::Bitcast, _) if tys[0] == tys[1] => Some(arg.clone()),
+            (Opcode::Bitcast, ConstantData::Null) => Some(ConstantData::Null),
+            (Opcode::Bitcast, ConstantData::GlobalRef(_) | ConstantData::Expr(_))
+                if types.is_pointer(tys[1]) =>
+            {
+                Some(ConstantData::Expr(ConstantExpr::Bitcast {
+                    tys: *tys,
+                    arg: Box::new(arg.clone()),
+                }))
+            }
+            _ => None,
+        },
+        (Operand::GetElementPtr(gep), [ConstantData::GlobalRef(_), indices @ ..]) => {
+            fold_gep(gep, args, indices)
+        }
+        _ => None,
+    }
+}
+
+/// Returns `x opcode y`, or `None` if the result is undefined or `x` and
+/// `y` are of different types.
+pub fn int_binary(opcode: Opcode, x: ConstantInt, y: ConstantInt) -> Option<ConstantInt> {
+    let width = bits(x);
+    if width != bits(y) {
+        return None;
+    }
+    let (sx, sy) = (sext(x), sext(y));
+    let (ux, uy) = (zext(x), zext(y));
+    let v = match opcode {
+        Opcode::Add => sx.wrapping_add(sy),
+        Opcode::Sub => sx.wrapping_sub(sy),
+        Opcode::Mul => sx.wrapping_mul(sy),
+        Opcode::And => sx & sy,
+        Opcode::Or => sx | sy,
+        Opcode::SDiv | Opcode::SRem => {
+            let min = -1i128 << (width - 1);
+            if sy == 0 || (sx == min && sy == -1) {
+                return None;
+            }
+            if opcode == Opcode::SDiv {
+                sx / sy
+            } else {
+                sx % sy
+            }
+        }
+        Opcode::Shl if uy < width as u128 => sx << uy,
+        Opcode::LShr if uy < width as u128 => (ux >> uy) as i128,
+        _ => return None,
+    };
+    Some(truncate(x, v))
+}
+
+/// Returns `icmp cond x, y`, or `None` if `x` and `y` are of different types.
+pub fn icmp(cond: ICmpCond, x: ConstantInt, y: ConstantInt) -> Option<bool> {
+    if bits(x) != bits(y) {
+        return None;
+    }
+    let (sx, sy) = (sext(x), sext(y));
+    let (ux, uy) = (zext(x), zext(y));
+    Some(match cond {
+        ICmpCond::Eq => ux == uy,
+        ICmpCond::Ne => ux != uy,
+        ICmpCond::Ugt => ux > uy,
+        ICmpCond::Uge => ux >= uy,
+        ICmpCond::Ult => ux < uy,
+        ICmpCond::Ule => ux <= uy,
+        ICmpCond::Sgt => sx > sy,
+        ICmpCond::Sge => sx >= sy,
+        ICmpCond::Slt => sx < sy,
+        ICmpCond::Sle => sx <= sy,
+    })
+}
+
+/// Returns `c` converted to the integer type `to` by `sext`, `zext` or
+/// `trunc`, or `None` for other conversions.
+pub fn cast_int(opcode: Opcode, c: ConstantInt, to: Type) -> Option<ConstantInt> {
+    let v = match opcode {
+        Opcode::Sext | Opcode::Trunc => sext(c),
+        Opcode::Zext => zext(c) as i128,
+        Opcode::Bitcast if int_of_type(to, 0).map(bits) == Some(bits(c)) => sext(c),
+        _ => return None,
+    };
+    int_of_type(to, v)
+}
+
+/// The result of `icmp eq` or `icmp ne` on operands that are equal or not.
+fn icmp_eq(cond: ICmpCond, equal: bool) -> Option<bool> {
+    match cond {
+        ICmpCond::Eq => Some(equal),
+        ICmpCond::Ne => Some(!equal),
+        _ => None,
+    }
+}
+
+fn fold_gep(
+    gep: &GetElementPtr,
+    args: &[ConstantData],
+    indices: &[ConstantData],
+) -> Option<ConstantData> {
+    if !indices
+        .iter()
+        .all(|idx| matches!(idx, ConstantData::Int(_)))
+    {
+        return None;
+    }
+    // `getelementptr T, T* @g, 0` → `@g`.
+    if let [ConstantData::Int(idx)] = indices {
+        if idx.cast_to_i64() == 0 {
+            return Some(args[0].clone());
+        }
+    }
+    Some(ConstantData::Expr(ConstantExpr::GetElementPtr {
+        inbounds: gep.inbounds,
+        tys: gep.tys.clone(),
+        args: args.to_vec(),
+    }))
+}
+
+fn bits(c: ConstantInt) -> u32 {
+    match c {
+        ConstantInt::Int1(_) => 1,
+        ConstantInt::Int8(_) => 8,
+        ConstantInt::Int16(_) => 16,
+        ConstantInt::Int32(_) => 32,
+        ConstantInt::Int64(_) => 64,
+        ConstantInt::Int128(_) => 128,
+    }
+}
+
+/// Returns `c` sign-extended, i.e. `true` is -1.
+fn sext(c: ConstantInt) -> i128 {
+    match c {
+        ConstantInt::Int1(b) => -(b as i128),
+        c => c.cast_to_i128(),
+    }
+}
+
+fn zext(c: ConstantInt) -> u128 {
+    match c {
+        ConstantInt::Int1(b) => b as u128,
+        ConstantInt::Int8(i) => i as u8 as u128,
+        ConstantInt::Int16(i) => i as u16 as u128,
+        ConstantInt::Int32(i) => i as u32 as u128,
+        ConstantInt::Int64(i) => i as u64 as u128,
+        ConstantInt::Int128(i) => i as u128,
+    }
+}
+
+/// Returns the low bits of `v` as a constant of the same type as `c`.
+fn truncate(c: ConstantInt, v: i128) -> ConstantInt {
+    match c {
+        ConstantInt::Int1(_) => ConstantInt::Int1(v & 1 != 0),
+        ConstantInt::Int8(_) => ConstantInt::Int8(v as i8),
+        ConstantInt::Int16(_) => ConstantInt::Int16(v as i16),
+        ConstantInt::Int32(_) => ConstantInt::Int32(v as i32),
+        ConstantInt::Int64(_) => ConstantInt::Int64(v as i64),
+        ConstantInt::Int128(_) => ConstantInt::Int128(v),
+    }
+}
+
+fn int_of_type(ty: Type, v: i128) -> Option<ConstantInt> {
+    let template = if ty.is_i1() {
+        ConstantInt::Int1(false)
+    } else if ty.is_i8() {
+        ConstantInt::Int8(0)
+    } else if ty.is_i16() {
+        ConstantInt::Int16(0)
+    } else if ty.is_i32() {
+        ConstantInt::Int32(0)
+    } else if ty.is_i64() {
+        ConstantInt::Int64(0)
+    } else if ty.is_i128() {
+        ConstantInt::Int128(0)
+    } else {
+        return None;
+    };
+    Some(truncate(template, v))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::types::{I128, I32, I64, I8};
+
+    #[test]
+    fn fold_ints() {
+        use ConstantInt::*;
+        assert_eq!(
+            int_binary(Opcode::Add, Int8(127), Int8(1)),
+            Some(Int8(-128))
+        );
+        assert_eq!(
+            int_binary(Opcode::LShr, Int32(-1), Int32(28)),
+            Some(Int32(15))
+        );
+        assert_eq!(int_binary(Opcode::Shl, Int32(1), Int32(32)), None);
+        assert_eq!(int_binary(Opcode::SDiv, Int64(i64::MIN), Int64(-1)), None);
+        assert_eq!(
+            int_binary(Opcode::SRem, Int32(-7), Int32(2)),
+            Some(Int32(-1))
+        );
+        assert_eq!(int_binary(Opcode::Add, Int32(1), Int64(1)), None);
+        assert_eq!(icmp(ICmpCond::Ult, Int32(1), Int32(-1)), Some(true));
+        assert_eq!(icmp(ICmpCond::Slt, Int32(1), Int32(-1)), Some(false));
+        assert_eq!(icmp(ICmpCond::Sgt, Int1(false), Int1(true)), Some(true));
+        assert_eq!(cast_int(Opcode::Zext, Int8(-1), I32), Some(Int32(255)));
+        assert_eq!(cast_int(Opcode::Sext, Int1(true), I64), Some(Int64(-1)));
+        assert_eq!(cast_int(Opcode::Trunc, Int64(0x1ff), I8), Some(Int8(-1)));
+        assert_eq!(
+            int_binary(Opcode::Shl, Int128(1), Int128(100)),
+            Some(Int128(1 << 100))
+        );
+        assert_eq!(
+            int_binary(Opcode::SDiv, Int128(i128::MIN), Int128(-1)),
+            None
+        );
+        assert_eq!(icmp(ICmpCond::Ugt, Int128(-1), Int128(1 << 64)), Some(true));
+        assert_eq!(
+            cast_int(Opcode::Zext, Int64(-1), I128),
+            Some(Int128(u64::MAX as i128))
+        );
+    }
+}
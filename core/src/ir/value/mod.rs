@@ -1,3 +1,4 @@
+pub mod fold;
 pub mod parser;
 
 pub use parser::parse;
@@ -6,10 +7,11 @@ use super::{
     function::{data::Data, instruction::InstructionId},
     module::name::Name,
     types::{Type, Types},
-    util::escape,
+    util::escape_bytes,
 };
+use crate::prelude::*;
+use core::fmt;
 use id_arena::Id;
-use std::{fmt, str};
 
 pub type ValueId = Id<Value>;
 
@@ -19,49 +21,79 @@ pub type ValueId = Id<Value>;
 /// However, `Value` here does not have such information.
 /// Instead, only for [`Instruction`](super::function::instruction::Instruction)s
 /// we track uses & users. (See [`Data`](Data) for details.)
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
-    Instruction(InstructionId),
+    Instruction(
+        #[cfg_attr(feature = "serde", serde(with = "crate::ir::serialize::id"))] InstructionId,
+    ),
     Argument(usize),
     Constant(ConstantData),
     InlineAsm(InlineAsm),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConstantData {
     Undef,
     AggregateZero,
     Null,
     Int(ConstantInt),
+    Float(ConstantFloat),
     Array(ConstantArray),
     Struct(ConstantStruct),
+    Vector(ConstantVector),
     Expr(ConstantExpr), // TODO: Boxing?
     GlobalRef(Name),
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConstantInt {
     Int1(bool),
     Int8(i8),
+    Int16(i16),
     Int32(i32),
     Int64(i64),
+    Int128(i128),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// A floating-point constant, kept as its bits so that constants compare and
+/// hash bit for bit, e.g. NaNs as equal to themselves and `-0.0` as not equal
+/// to `0.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConstantFloat {
+    Float(u32),
+    Double(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantArray {
     pub elem_ty: Type,
     pub elems: Vec<ConstantData>,
     pub is_string: bool,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantStruct {
     pub elems_ty: Vec<Type>,
     pub elems: Vec<ConstantData>,
     pub is_packed: bool,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// A vector constant, e.g. `<i32 1, i32 2>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstantVector {
+    pub elem_ty: Type,
+    pub elems: Vec<ConstantData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConstantExpr {
     GetElementPtr {
         inbounds: bool,
@@ -74,7 +106,8 @@ pub enum ConstantExpr {
     },
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InlineAsm {
     pub body: String,
     pub constraints: String,
@@ -123,8 +156,10 @@ impl ConstantData {
             Self::AggregateZero => "zeroinitializer".to_string(),
             Self::Null => "null".to_string(),
             Self::Int(i) => i.to_string(),
+            Self::Float(f) => f.to_string(),
             Self::Array(a) => a.to_string(types),
             Self::Struct(s) => s.to_string(types),
+            Self::Vector(v) => v.to_string(types),
             Self::Expr(e) => e.to_string(types),
             Self::GlobalRef(name) => format!("@{:?}", name),
         }
@@ -150,6 +185,22 @@ impl ConstantData {
             _ => panic!(),
         }
     }
+
+    /// Calls `f` with every global referred to in the constant, including
+    /// in nested aggregates and expressions.
+    pub fn for_each_global_ref<F: FnMut(&Name)>(&self, f: &mut F) {
+        match self {
+            Self::GlobalRef(name) => f(name),
+            Self::Array(a) => a.elems.iter().for_each(|e| e.for_each_global_ref(f)),
+            Self::Struct(s) => s.elems.iter().for_each(|e| e.for_each_global_ref(f)),
+            Self::Vector(v) => v.elems.iter().for_each(|e| e.for_each_global_ref(f)),
+            Self::Expr(ConstantExpr::GetElementPtr { args, .. }) => {
+                args.iter().for_each(|e| e.for_each_global_ref(f))
+            }
+            Self::Expr(ConstantExpr::Bitcast { arg, .. }) => arg.for_each_global_ref(f),
+            Self::Undef | Self::AggregateZero | Self::Null | Self::Int(_) | Self::Float(_) => {}
+        }
+    }
 }
 
 impl ConstantInt {
@@ -165,8 +216,10 @@ impl ConstantInt {
         match self {
             Self::Int1(i) => i as usize,
             Self::Int8(i) => i as usize,
+            Self::Int16(i) => i as usize,
             Self::Int32(i) => i as usize,
             Self::Int64(i) => i as usize,
+            Self::Int128(i) => i as usize,
         }
     }
 
@@ -174,8 +227,43 @@ impl ConstantInt {
         match self {
             Self::Int1(i) => i as i64,
             Self::Int8(i) => i as i64,
+            Self::Int16(i) => i as i64,
             Self::Int32(i) => i as i64,
-            Self::Int64(i) => i as i64,
+            Self::Int64(i) => i,
+            Self::Int128(i) => i as i64,
+        }
+    }
+
+    pub fn cast_to_i128(self) -> i128 {
+        match self {
+            Self::Int128(i) => i,
+            i => i.cast_to_i64().into(),
+        }
+    }
+}
+
+impl ConstantFloat {
+    pub fn from_f32(f: f32) -> Self {
+        Self::Float(f.to_bits())
+    }
+
+    pub fn from_f64(f: f64) -> Self {
+        Self::Double(f.to_bits())
+    }
+
+    /// Returns the value as a `double`, which holds any `float` exactly.
+    pub fn to_f64(self) -> f64 {
+        match self {
+            Self::Float(bits) => f32::from_bits(bits) as f64,
+            Self::Double(bits) => f64::from_bits(bits),
+        }
+    }
+
+    /// Returns the bytes of the value in memory, in little endian.
+    pub fn to_le_bytes(self) -> Vec<u8> {
+        match self {
+            Self::Float(bits) => bits.to_le_bytes().to_vec(),
+            Self::Double(bits) => bits.to_le_bytes().to_vec(),
         }
     }
 }
@@ -183,19 +271,12 @@ impl ConstantInt {
 impl ConstantArray {
     pub fn to_string(&self, types: &Types) -> String {
         if self.is_string {
-            return format!(
-                "c\"{}\"",
-                escape(
-                    str::from_utf8(
-                        self.elems
-                            .iter()
-                            .map(|i| *i.as_int().as_i8() as u8)
-                            .collect::<Vec<u8>>()
-                            .as_slice(),
-                    )
-                    .unwrap()
-                )
-            );
+            let bytes: Vec<u8> = self
+                .elems
+                .iter()
+                .map(|i| *i.as_int().as_i8() as u8)
+                .collect();
+            return format!("c\"{}\"", escape_bytes(&bytes));
         }
 
         format!(
@@ -232,6 +313,19 @@ impl ConstantStruct {
     }
 }
 
+impl ConstantVector {
+    pub fn to_string(&self, types: &Types) -> String {
+        format!(
+            "<{}>",
+            self.elems
+                .iter()
+                .map(|e| format!("{} {}", types.to_string(self.elem_ty), e.to_string(types)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
 impl ConstantExpr {
     pub fn to_string(&self, types: &Types) -> String {
         match self {
@@ -283,6 +377,18 @@ impl From<ConstantInt> for ConstantData {
     }
 }
 
+impl From<ConstantFloat> for ConstantData {
+    fn from(f: ConstantFloat) -> Self {
+        Self::Float(f)
+    }
+}
+
+impl From<ConstantData> for Value {
+    fn from(c: ConstantData) -> Self {
+        Self::Constant(c)
+    }
+}
+
 impl From<ConstantInt> for Value {
     fn from(i: ConstantInt) -> Self {
         Self::Constant(i.into())
@@ -294,8 +400,34 @@ impl fmt::Display for ConstantInt {
         match self {
             Self::Int1(i) => write!(f, "{}", i),
             Self::Int8(i) => write!(f, "{}", i),
+            Self::Int16(i) => write!(f, "{}", i),
             Self::Int32(i) => write!(f, "{}", i),
             Self::Int64(i) => write!(f, "{}", i),
+            Self::Int128(i) => write!(f, "{}", i),
+        }
+    }
+}
+
+/// Prints the value as LLVM does: in scientific notation with six decimals
+/// if that reads back as the same value, or else as the bits of the `double`
+/// it is equal to, in hex.
+impl fmt::Display for ConstantFloat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let val = self.to_f64();
+        if val.is_finite() {
+            let sci = format!("{:.6e}", val);
+            let (mantissa, exp) = sci.split_once('e').unwrap();
+            let exp: i32 = exp.parse().unwrap();
+            let sci = format!(
+                "{}e{}{:02}",
+                mantissa,
+                if exp < 0 { '-' } else { '+' },
+                exp.abs()
+            );
+            if sci.parse::<f64>().map(f64::to_bits) == Ok(val.to_bits()) {
+                return write!(f, "{}", sci);
+            }
         }
+        write!(f, "0x{:016X}", val.to_bits())
     }
 }
@@ -1,18 +1,20 @@
 use crate::ir::{
     function::parser::ParserContext,
     module::name,
-    types::{self, Type, Types, I1, I32, I64, I8},
-    util::{spaces, string_literal},
+    types::{self, Type, Types, DOUBLE, FLOAT, I1, I128, I16, I32, I64, I8},
+    util::{byte_string_literal, spaces},
     value::{
-        ConstantArray, ConstantData, ConstantExpr, ConstantInt, ConstantStruct, Value, ValueId,
+        ConstantArray, ConstantData, ConstantExpr, ConstantFloat, ConstantInt, ConstantStruct,
+        ConstantVector, Value, ValueId,
     },
 };
+use crate::prelude::*;
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{char, digit1},
+    character::complete::{char, digit1, hex_digit1, one_of},
     combinator::{opt, recognize},
-    error::VerboseError,
+    error::{VerboseError, VerboseErrorKind},
     sequence::{preceded, tuple},
     IResult,
 };
@@ -31,6 +33,13 @@ pub fn parse_constant<'a>(
     if let Ok((source, _)) = preceded(spaces, tag("zeroinitializer"))(source) {
         return Ok((source, ConstantData::AggregateZero));
     }
+    if ty.is_floating_point() {
+        match parse_constant_float(source, ty) {
+            Ok((source, f)) => return Ok((source, f.into())),
+            Err(e @ nom::Err::Failure(_)) => return Err(e),
+            Err(_) => {}
+        }
+    }
     if let Ok((source, id)) = parse_constant_int(source, ty) {
         return Ok((source, id.into()));
     }
@@ -43,13 +52,16 @@ pub fn parse_constant<'a>(
     if let Ok((source, id)) = parse_constant_struct(source, types) {
         return Ok((source, id));
     }
+    if let Ok((source, id)) = parse_constant_vector(source, types) {
+        return Ok((source, id));
+    }
     parse_constant_expr(source, types)
 }
 
-pub fn parse_constant_int<'a>(
-    source: &'a str,
+pub fn parse_constant_int(
+    source: &str,
     ty: Type,
-) -> IResult<&'a str, ConstantInt, VerboseError<&'a str>> {
+) -> IResult<&str, ConstantInt, VerboseError<&str>> {
     let (source, num) = preceded(
         spaces,
         recognize(tuple((
@@ -60,46 +72,92 @@ pub fn parse_constant_int<'a>(
     let val = match ty {
         I1 => ConstantInt::Int1(num == "true"),
         I8 => ConstantInt::Int8(num.parse::<i8>().unwrap()),
+        I16 => ConstantInt::Int16(num.parse::<i16>().unwrap()),
         I32 => ConstantInt::Int32(num.parse::<i32>().unwrap()),
         I64 => ConstantInt::Int64(num.parse::<i64>().unwrap()),
+        I128 => ConstantInt::Int128(num.parse::<i128>().unwrap()),
         _ => todo!(),
     };
     Ok((source, val))
 }
 
+/// Parses a floating-point constant of `ty`, either in decimal, e.g.
+/// `-1.5e+00`, or as the bits of a `double` in hex, e.g. `0x3FF8000000000000`,
+/// which `float`s are written as too.
+pub fn parse_constant_float(
+    source: &str,
+    ty: Type,
+) -> IResult<&str, ConstantFloat, VerboseError<&str>> {
+    let (source, val) =
+        if let Ok((rest, bits)) = preceded(spaces, preceded(tag("0x"), hex_digit1))(source) {
+            let start = spaces(source)?.0;
+            let bits = u64::from_str_radix(bits, 16).map_err(|_| {
+                nom::Err::Failure(VerboseError {
+                    errors: vec![(start, VerboseErrorKind::Context("float"))],
+                })
+            })?;
+            (rest, f64::from_bits(bits))
+        } else {
+            let (source, num) = preceded(
+                spaces,
+                recognize(tuple((
+                    opt(one_of("+-")),
+                    digit1,
+                    char('.'),
+                    opt(digit1),
+                    opt(tuple((one_of("eE"), opt(one_of("+-")), digit1))),
+                ))),
+            )(source)?;
+            (source, num.parse::<f64>().unwrap())
+        };
+    let val = match ty {
+        FLOAT => ConstantFloat::from_f32(val as f32),
+        DOUBLE => ConstantFloat::from_f64(val),
+        _ => unreachable!("{:?} is not a floating-point type", ty),
+    };
+    Ok((source, val))
+}
+
 pub fn parse_constant_array<'a>(
     source: &'a str,
-    _types: &Types,
-    // ty: Type,
+    types: &Types,
 ) -> IResult<&'a str, ConstantData, VerboseError<&'a str>> {
-    // TODO: Support arrays in the form of [a, b, c]
-    let (source, _) = preceded(spaces, char('c'))(source)?;
-    let (source, s) = preceded(spaces, string_literal)(source)?;
-    let val = ConstantData::Array(ConstantArray {
-        elem_ty: I8,
-        elems: s
-            .as_bytes()
-            .iter()
-            .map(|c| ConstantData::Int(ConstantInt::Int8(*c as i8)))
-            .collect(),
-        is_string: true,
-    });
-    Ok((source, val))
-
-    // let (mut source, _) = preceded(spaces, char('['))(source)?;
-    // loop {
-    //     let (source_, ty) = types::parse(source, ctx.types)?;
-    //
-    // }
+    if let Ok((source, _)) = preceded(spaces, char('c'))(source) {
+        let (source, s) = preceded(spaces, byte_string_literal)(source)?;
+        let val = ConstantData::Array(ConstantArray {
+            elem_ty: I8,
+            elems: s
+                .iter()
+                .map(|c| ConstantData::Int(ConstantInt::Int8(*c as i8)))
+                .collect(),
+            is_string: true,
+        });
+        return Ok((source, val));
+    }
 
-    // let (source, num) = preceded(spaces, digit1)(source)?;
-    // let val = match &*ctx.types.get(ty) {
-    //     Type::Int(32) => Value::Constant(ConstantData::Int(ConstantInt::Int32(
-    //         num.parse::<i32>().unwrap(),
-    //     ))),
-    //     _ => todo!(),
-    // };
-    // Ok((source, ctx.data.create_value(val)))
+    // [<ty> <elem>, ...]
+    let (mut source, _) = preceded(spaces, char('['))(source)?;
+    let mut elems = vec![];
+    let mut elem_ty = None;
+    loop {
+        let (source_, t) = types::parse(source, types)?;
+        let (source_, konst) = parse_constant(source_, types, t)?;
+        elems.push(konst);
+        elem_ty = elem_ty.or(Some(t));
+        if let Ok((source_, _)) = preceded(spaces, char(','))(source_) {
+            source = source_;
+            continue;
+        }
+        let (source_, _) = preceded(spaces, char(']'))(source_)?;
+        return Ok((
+            source_,
+            ConstantData::Array(ConstantArray {
+                elem_ty: elem_ty.unwrap(),
+                elems,
+                is_string: false,
+            }),
+        ));
+    }
 }
 
 pub fn parse_constant_expr<'a>(
@@ -199,6 +257,29 @@ pub fn parse_constant_struct<'a>(
     }
 }
 
+// <<ty> <elem>, ...>
+pub fn parse_constant_vector<'a>(
+    source: &'a str,
+    types: &Types,
+) -> IResult<&'a str, ConstantData, VerboseError<&'a str>> {
+    let (mut source, _) = preceded(spaces, char('<'))(source)?;
+    let mut elems = vec![];
+    loop {
+        let (source_, elem_ty) = types::parse(source, types)?;
+        let (source_, konst) = parse_constant(source_, types, elem_ty)?;
+        elems.push(konst);
+        if let Ok((source_, _)) = preceded(spaces, char(','))(source_) {
+            source = source_;
+            continue;
+        }
+        let (source_, _) = preceded(spaces, char('>'))(source_)?;
+        return Ok((
+            source_,
+            ConstantData::Vector(ConstantVector { elem_ty, elems }),
+        ));
+    }
+}
+
 pub fn parse_local<'a, 'b>(
     source: &'a str,
     ctx: &mut ParserContext<'b>,
@@ -213,9 +294,13 @@ pub fn parse<'a, 'b>(
     ctx: &mut ParserContext<'b>,
     ty: Type,
 ) -> IResult<&'a str, ValueId, VerboseError<&'a str>> {
-    if let Ok((source, konst)) = parse_constant(source, ctx.types, ty) {
-        let id = ctx.data.create_value(Value::Constant(konst));
-        return Ok((source, id));
+    match parse_constant(source, ctx.types, ty) {
+        Ok((source, konst)) => {
+            let id = ctx.data.create_value(Value::Constant(konst));
+            return Ok((source, id));
+        }
+        Err(e @ nom::Err::Failure(_)) => return Err(e),
+        Err(_) => {}
     }
 
     parse_local(source, ctx, ty)
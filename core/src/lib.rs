@@ -1,10 +1,18 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
 // pub mod codegen;
 // pub mod exec;
 #[macro_use]
 pub mod macros;
+pub mod bitcode;
 pub mod ir;
 pub mod pass;
 pub mod traits;
 
+mod collections;
+mod prelude;
+mod sync;
+
+extern crate alloc;
 extern crate anyhow;
 extern crate nom;
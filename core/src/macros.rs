@@ -1,6 +1,6 @@
 macro_rules! debug {
     ($($arg:tt)*) => {
-        #[cfg(debug_assertions)]
+        #[cfg(all(debug_assertions, feature = "std"))]
         {
             dbg!($($arg)*);
         }
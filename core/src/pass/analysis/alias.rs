@@ -0,0 +1,219 @@
+//! A simple alias analysis.
+//!
+//! Pointers are traced back through `getelementptr`s and `bitcast`s to the
+//! object they point into. Distinct allocas and globals never alias, and
+//! neither does an alloca whose address never escapes the function with any
+//! pointer not derived from it.
+
+use crate::collections::FxHashSet;
+use crate::ir::{
+    function::{
+        instruction::{Cast, GetElementPtr, InstructionId, Load, Opcode, Operand, Store},
+        Function,
+    },
+    module::name::Name,
+    value::{ConstantData, ConstantExpr, Value, ValueId},
+};
+use crate::pass::AnalysisPass;
+use crate::prelude::*;
+use core::any::Any;
+
+/// Computes the [`AliasAnalysis`] of a function.
+pub struct AliasAnalysisPass;
+
+impl AnalysisPass<Function> for AliasAnalysisPass {
+    fn run_on(&self, func: &Function, result: &mut Box<dyn Any>) {
+        *result = Box::new(AliasAnalysis::new(func));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AliasResult {
+    /// The two pointers never point into the same object.
+    NoAlias,
+    /// Nothing is known about the two pointers.
+    MayAlias,
+    /// The two pointers are always equal.
+    MustAlias,
+}
+
+#[derive(Debug)]
+pub struct AliasAnalysis {
+    non_escaping: FxHashSet<InstructionId>,
+}
+
+/// The object a pointer points into.
+#[derive(Debug, Clone, PartialEq)]
+enum Object<'a> {
+    Alloca(InstructionId),
+    Global(&'a Name),
+    Unknown,
+}
+
+impl AliasAnalysis {
+    pub fn new(func: &Function) -> Self {
+        let mut non_escaping = FxHashSet::default();
+        for block in func.layout.block_iter() {
+            for inst in func.layout.inst_iter(block) {
+                if func.data.inst_ref(inst).opcode == Opcode::Alloca && !escapes(func, inst) {
+                    non_escaping.insert(inst);
+                }
+            }
+        }
+        Self { non_escaping }
+    }
+
+    /// Returns whether `a` and `b`, both pointers in `func`, may alias.
+    pub fn alias(&self, func: &Function, a: ValueId, b: ValueId) -> AliasResult {
+        if same_address(func, a, b) {
+            return AliasResult::MustAlias;
+        }
+        match (object_of(func, a), object_of(func, b)) {
+            (Object::Unknown, Object::Unknown) => AliasResult::MayAlias,
+            (x, y) if x == y => AliasResult::MayAlias,
+            (Object::Alloca(x), _) | (_, Object::Alloca(x)) if self.non_escaping.contains(&x) => {
+                AliasResult::NoAlias
+            }
+            (Object::Unknown, _) | (_, Object::Unknown) => AliasResult::MayAlias,
+            _ => AliasResult::NoAlias,
+        }
+    }
+
+    /// Returns true if the address of `alloca` is never used outside of the function.
+    pub fn is_non_escaping(&self, alloca: InstructionId) -> bool {
+        self.non_escaping.contains(&alloca)
+    }
+
+    /// Returns true if `ptr` points into an alloca whose address never
+    /// escapes, so that the memory cannot be accessed by called functions.
+    pub fn is_local(&self, func: &Function, ptr: ValueId) -> bool {
+        matches!(object_of(func, ptr), Object::Alloca(x) if self.non_escaping.contains(&x))
+    }
+}
+
+/// Returns true if the address computed by `inst` is used by anything other
+/// than loads, stores to it and address computations that don't escape either.
+fn escapes(func: &Function, inst: InstructionId) -> bool {
+    func.data.users_of(inst).iter().any(|&user| {
+        let user_inst = func.data.inst_ref(user);
+        match &user_inst.operand {
+            Operand::Load(Load { .. }) => false,
+            Operand::Store(Store { args, .. }) => {
+                func.data.value_ref(args[0]) == &Value::Instruction(inst)
+            }
+            Operand::GetElementPtr(GetElementPtr { .. }) => escapes(func, user),
+            Operand::Cast(Cast { .. }) if user_inst.opcode == Opcode::Bitcast => {
+                escapes(func, user)
+            }
+            _ => true,
+        }
+    })
+}
+
+/// Returns true if `a` and `b` are the same value, or computed the same way
+/// from the same pointer.
+fn same_address(func: &Function, a: ValueId, b: ValueId) -> bool {
+    let (x, y) = (func.data.value_ref(a), func.data.value_ref(b));
+    if a == b || x == y {
+        return true;
+    }
+    match (x, y) {
+        (Value::Instruction(x), Value::Instruction(y)) => {
+            match (
+                &func.data.inst_ref(*x).operand,
+                &func.data.inst_ref(*y).operand,
+            ) {
+                (Operand::GetElementPtr(x), Operand::GetElementPtr(y)) => {
+                    x.tys == y.tys
+                        && x.args.len() == y.args.len()
+                        && x.args
+                            .iter()
+                            .zip(y.args.iter())
+                            .all(|(&x, &y)| same_address(func, x, y))
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+fn object_of(func: &Function, ptr: ValueId) -> Object<'_> {
+    match func.data.value_ref(ptr) {
+        Value::Instruction(id) => {
+            let inst = func.data.inst_ref(*id);
+            match &inst.operand {
+                Operand::Alloca(_) => Object::Alloca(*id),
+                Operand::GetElementPtr(GetElementPtr { args, .. }) => object_of(func, args[0]),
+                Operand::Cast(Cast { arg, .. }) if inst.opcode == Opcode::Bitcast => {
+                    object_of(func, *arg)
+                }
+                _ => Object::Unknown,
+            }
+        }
+        Value::Constant(konst) => constant_object_of(konst),
+        _ => Object::Unknown,
+    }
+}
+
+fn constant_object_of(konst: &ConstantData) -> Object<'_> {
+    match konst {
+        ConstantData::GlobalRef(name) => Object::Global(name),
+        ConstantData::Expr(ConstantExpr::GetElementPtr { args, .. }) => {
+            constant_object_of(&args[0])
+        }
+        ConstantData::Expr(ConstantExpr::Bitcast { arg, .. }) => constant_object_of(arg),
+        _ => Object::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn alias() {
+        let module = parse_assembly(
+            r#"
+@g = global i32 0, align 4
+define void @f(i32* %0) {
+  %2 = alloca i32, align 4
+  %3 = alloca [2 x i32], align 4
+  %4 = getelementptr inbounds [2 x i32], [2 x i32]* %3, i64 0, i64 1
+  %5 = getelementptr inbounds [2 x i32], [2 x i32]* %3, i64 0, i64 1
+  %6 = alloca i32*, align 8
+  store i32* %2, i32** %6, align 8
+  store i32 1, i32* %4, align 4
+  store i32 1, i32* %5, align 4
+  store i32 1, i32* @g, align 4
+  store i32 1, i32* %0, align 4
+  store i32 1, i32* %2, align 4
+  ret void
+}"#,
+        )
+        .unwrap();
+        let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+        let aa = AliasAnalysis::new(func);
+        // The addresses stored to, in order.
+        let ptrs: Vec<ValueId> = func
+            .layout
+            .block_iter()
+            .flat_map(|block| func.layout.inst_iter(block))
+            .filter_map(|inst| match &func.data.inst_ref(inst).operand {
+                Operand::Store(Store { args, .. }) => Some(args[1]),
+                _ => None,
+            })
+            .collect();
+        let [slot, elem, elem_again, global, arg, escaped] = ptrs[..] else {
+            panic!()
+        };
+
+        assert_eq!(aa.alias(func, elem, elem_again), AliasResult::MustAlias);
+        assert_eq!(aa.alias(func, elem, global), AliasResult::NoAlias);
+        assert_eq!(aa.alias(func, arg, elem), AliasResult::NoAlias);
+        assert_eq!(aa.alias(func, slot, escaped), AliasResult::NoAlias);
+        assert_eq!(aa.alias(func, arg, escaped), AliasResult::MayAlias);
+        assert_eq!(aa.alias(func, arg, global), AliasResult::MayAlias);
+    }
+}
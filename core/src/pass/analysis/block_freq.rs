@@ -0,0 +1,288 @@
+//! Static block frequency estimation.
+//!
+//! Each edge of the CFG is given a probability, from the `!prof` branch
+//! weights when known and from the loop structure otherwise: a branch leaving
+//! a loop is taken one time out of eight. Frequencies are then propagated
+//! from the entry block following Wu and Larus, "Static Branch Frequency and
+//! Program Profile Analysis": each loop header is scaled by the number of
+//! iterations its loop is expected to run.
+
+use super::{dom_tree::DominatorTree, loops::LoopInfo};
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::prelude::*;
+use crate::{
+    ir::{
+        function::{
+            basic_block::BasicBlock as IrBasicBlock,
+            instruction::{CondBr, Operand},
+            Function,
+        },
+        module::{metadata::Metadata, name::Name, FxIndexMap},
+        value::ConstantInt,
+    },
+    pass::AnalysisPass,
+    traits::basic_block::{BasicBlock, BasicBlockData, BasicBlockLayout},
+};
+use core::any::Any;
+use id_arena::Id;
+
+/// Computes the [`BlockFrequency`] of a function. Only branch weights
+/// attached as inline metadata nodes are used; see [`BlockFrequency::of_function`].
+pub struct BlockFrequencyPass;
+
+impl AnalysisPass<Function> for BlockFrequencyPass {
+    fn run_on(&self, func: &Function, result: &mut Box<dyn Any>) {
+        if !func.is_prototype() {
+            *result = Box::new(BlockFrequency::of_function(func, &FxIndexMap::default()));
+        }
+    }
+}
+
+/// The probability of leaving a loop through one of its exiting branches.
+const LOOP_EXIT_PROBABILITY: f64 = 1.0 / 8.0;
+
+/// The most times a loop is assumed to iterate, bounding the frequencies of
+/// loops that are never or hardly ever left.
+const MAX_LOOP_SCALE: f64 = 1024.0;
+
+#[derive(Debug)]
+pub struct BlockFrequency<BB: BasicBlock> {
+    freqs: FxHashMap<Id<BB>, f64>,
+    probs: FxHashMap<(Id<BB>, Id<BB>), f64>,
+}
+
+impl<BB: BasicBlock> BlockFrequency<BB> {
+    /// Estimates the block frequencies of `f` from its loop structure only.
+    pub fn new<F: BasicBlockData<BB> + BasicBlockLayout<BB>>(f: &F) -> Self {
+        Self::with_branch_weights(f, |_| None)
+    }
+
+    /// Estimates the block frequencies of `f`. `weights(block)` returns the
+    /// relative weight of each successor of `block`, if known.
+    pub fn with_branch_weights<F, W>(f: &F, weights: W) -> Self
+    where
+        F: BasicBlockData<BB> + BasicBlockLayout<BB>,
+        W: Fn(Id<BB>) -> Option<Vec<(Id<BB>, u64)>>,
+    {
+        let dom_tree = DominatorTree::new(f);
+        let loops = LoopInfo::with_dom_tree(f, &dom_tree);
+        let rpo = reverse_post_order(f, *dom_tree.root());
+
+        let mut probs = FxHashMap::default();
+        for &block in &rpo {
+            for (succ, prob) in edge_probabilities(f, &loops, block, weights(block)) {
+                probs.insert((block, succ), prob);
+            }
+        }
+
+        let is_back_edge = |from: Id<BB>, to: Id<BB>| {
+            loops.is_loop_header(to) && loops.loop_of(to).is_some_and(|l| l.contains(from))
+        };
+        // Sums up the frequencies flowing into `block`, ignoring back edges.
+        let incoming = |freqs: &FxHashMap<Id<BB>, f64>, block: Id<BB>| -> f64 {
+            f.get(block)
+                .preds()
+                .iter()
+                .filter(|&&pred| !is_back_edge(pred, block))
+                .filter_map(|pred| Some(freqs.get(pred)? * probs[&(*pred, block)]))
+                .sum()
+        };
+
+        // How many times each loop is expected to iterate, inner loops first.
+        let mut scales: FxHashMap<Id<BB>, f64> = FxHashMap::default();
+        for l in loops.loops() {
+            let mut local = FxHashMap::default();
+            local.insert(l.header, 1.0);
+            for &block in rpo.iter().filter(|&&b| b != l.header && l.contains(b)) {
+                let freq = incoming(&local, block) * scales.get(&block).unwrap_or(&1.0);
+                local.insert(block, freq);
+            }
+            let cyclic: f64 = l
+                .latches
+                .iter()
+                .map(|latch| local[latch] * probs[&(*latch, l.header)])
+                .sum();
+            let scale = if cyclic < 1.0 {
+                (1.0 / (1.0 - cyclic)).min(MAX_LOOP_SCALE)
+            } else {
+                MAX_LOOP_SCALE
+            };
+            scales.insert(l.header, scale);
+        }
+
+        let mut freqs = FxHashMap::default();
+        for (i, &block) in rpo.iter().enumerate() {
+            let freq = if i == 0 { 1.0 } else { incoming(&freqs, block) };
+            freqs.insert(block, freq * scales.get(&block).unwrap_or(&1.0));
+        }
+
+        Self { freqs, probs }
+    }
+
+    /// Returns how many times `block` is expected to run per run of the
+    /// function. Unreachable blocks have a frequency of 0.
+    pub fn frequency(&self, block: Id<BB>) -> f64 {
+        self.freqs.get(&block).copied().unwrap_or(0.0)
+    }
+
+    /// Returns the probability of branching to `to` once `from` is run.
+    pub fn edge_probability(&self, from: Id<BB>, to: Id<BB>) -> f64 {
+        self.probs.get(&(from, to)).copied().unwrap_or(0.0)
+    }
+}
+
+impl BlockFrequency<IrBasicBlock> {
+    /// Estimates the block frequencies of `func` using the `!prof` branch
+    /// weights of its conditional branches. References to metadata nodes are
+    /// looked up in `metas`, usually `Module::metas`.
+    pub fn of_function(func: &Function, metas: &FxIndexMap<Name, Metadata>) -> Self {
+        Self::with_branch_weights(func, |block| {
+            let term = (*func.layout.block_node(block).last_inst())?;
+            let inst = func.data.inst_ref(term);
+            let Operand::CondBr(CondBr { blocks, .. }) = &inst.operand else {
+                return None;
+            };
+            let prof = match inst.metadata.get("prof")? {
+                Metadata::Name(name) => metas.get(name)?,
+                node => node,
+            };
+            match prof {
+                Metadata::Node(node) => match node.as_slice() {
+                    [Metadata::String(kind), Metadata::Int(t), Metadata::Int(f)]
+                        if kind == "branch_weights" =>
+                    {
+                        Some(vec![(blocks[0], weight(t)?), (blocks[1], weight(f)?)])
+                    }
+                    _ => None,
+                },
+                _ => None,
+            }
+        })
+    }
+}
+
+fn weight(i: &ConstantInt) -> Option<u64> {
+    u64::try_from(i.cast_to_i64()).ok()
+}
+
+fn edge_probabilities<BB, F>(
+    f: &F,
+    loops: &LoopInfo<BB>,
+    block: Id<BB>,
+    weights: Option<Vec<(Id<BB>, u64)>>,
+) -> FxHashMap<Id<BB>, f64>
+where
+    BB: BasicBlock,
+    F: BasicBlockData<BB> + BasicBlockLayout<BB>,
+{
+    let succs = f.get(block).succs();
+    let mut probs = FxHashMap::default();
+
+    if let Some(weights) = weights {
+        let total: u64 = weights.iter().map(|(_, w)| w).sum();
+        if total > 0 {
+            for (succ, weight) in weights {
+                *probs.entry(succ).or_insert(0.0) += weight as f64 / total as f64;
+            }
+            return probs;
+        }
+    }
+
+    let (exiting, staying): (Vec<Id<BB>>, Vec<Id<BB>>) = succs
+        .iter()
+        .partition(|&&succ| loops.loop_of(block).is_some_and(|l| !l.contains(succ)));
+    if exiting.is_empty() || staying.is_empty() {
+        for &succ in succs {
+            probs.insert(succ, 1.0 / succs.len() as f64);
+        }
+    } else {
+        for succ in &exiting {
+            probs.insert(*succ, LOOP_EXIT_PROBABILITY / exiting.len() as f64);
+        }
+        for succ in &staying {
+            probs.insert(*succ, (1.0 - LOOP_EXIT_PROBABILITY) / staying.len() as f64);
+        }
+    }
+    probs
+}
+
+fn reverse_post_order<BB, F>(f: &F, entry: Id<BB>) -> Vec<Id<BB>>
+where
+    BB: BasicBlock,
+    F: BasicBlockData<BB> + BasicBlockLayout<BB>,
+{
+    let mut order = vec![];
+    let mut visited = FxHashSet::default();
+    let mut stack = vec![(entry, false)];
+    while let Some((block, exiting)) = stack.pop() {
+        if exiting {
+            order.push(block);
+            continue;
+        }
+        if !visited.insert(block) {
+            continue;
+        }
+        stack.push((block, true));
+        for &succ in f.get(block).succs() {
+            if !visited.contains(&succ) {
+                stack.push((succ, false));
+            }
+        }
+    }
+    order.reverse();
+    order
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    fn frequencies(source: &str) -> Vec<f64> {
+        let module = parse_assembly(source).unwrap();
+        let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+        let freq = BlockFrequency::of_function(func, &module.metas);
+        func.layout
+            .block_iter()
+            .map(|block| freq.frequency(block))
+            .collect()
+    }
+
+    #[test]
+    fn loops() {
+        let freqs = frequencies(
+            r#"
+define void @f(i1 %0) {
+  br label %2
+2:
+  br label %3
+3:
+  br i1 %0, label %3, label %4
+4:
+  br i1 %0, label %2, label %5
+5:
+  ret void
+}"#,
+        );
+        let expected = [1.0, 8.0, 64.0, 8.0, 1.0];
+        for (freq, expected) in freqs.iter().zip(expected) {
+            assert!((freq - expected).abs() < 1e-9, "{:?}", freqs);
+        }
+    }
+
+    #[test]
+    fn branch_weights() {
+        let freqs = frequencies(
+            r#"
+define void @f(i1 %0) {
+  br i1 %0, label %2, label %3, !prof !0
+2:
+  br label %3
+3:
+  ret void
+}
+!0 = !{!"branch_weights", i32 1, i32 3}"#,
+        );
+        assert_eq!(freqs, vec![1.0, 0.25, 1.0]);
+    }
+}
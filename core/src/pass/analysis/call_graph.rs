@@ -0,0 +1,120 @@
+//! The call graph of a module.
+//!
+//! Only direct calls, i.e. `call`s and `invoke`s whose callee is a function
+//! of the module passing as many arguments as it has parameters, are edges.
+//! A function referred to in any other way is *address taken*: it may be
+//! called from places not in the graph.
+
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::ir::{
+    function::{instruction::InstructionId, FunctionId},
+    module::{name::Name, Module},
+    value::{ConstantData, Value},
+};
+use crate::pass::AnalysisPass;
+use crate::prelude::*;
+use core::any::Any;
+
+/// Computes the [`CallGraph`] of a module.
+pub struct CallGraphPass;
+
+impl AnalysisPass<Module> for CallGraphPass {
+    fn run_on(&self, module: &Module, result: &mut Box<dyn Any>) {
+        *result = Box::new(CallGraph::new(module));
+    }
+}
+
+/// A direct call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallSite {
+    pub caller: FunctionId,
+    /// The `call` or `invoke` instruction in `caller`.
+    pub inst: InstructionId,
+}
+
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    call_sites: FxHashMap<FunctionId, Vec<CallSite>>,
+    callees: FxHashMap<FunctionId, Vec<FunctionId>>,
+    address_taken: FxHashSet<FunctionId>,
+}
+
+impl CallGraph {
+    pub fn new(module: &Module) -> Self {
+        let ids: FxHashMap<Name, FunctionId> = module
+            .functions()
+            .iter()
+            .map(|(id, func)| (Name::from(func.name.as_str()), id))
+            .collect();
+        let mut graph = Self::default();
+
+        for gv in module.global_variables().values() {
+            if let Some(init) = &gv.init {
+                init.for_each_global_ref(&mut |name| {
+                    graph.address_taken.extend(ids.get(name));
+                });
+            }
+        }
+
+        for (caller, func) in module.functions().iter() {
+            if let Some((_, personality)) = &func.personality {
+                personality.for_each_global_ref(&mut |name| {
+                    graph.address_taken.extend(ids.get(name));
+                });
+            }
+            for block in func.layout.block_iter() {
+                for inst in func.layout.inst_iter(block) {
+                    let operand = &func.data.inst_ref(inst).operand;
+                    let is_call = operand.call_result_ty().is_some();
+                    for (i, &arg) in operand.args().iter().enumerate() {
+                        let Value::Constant(konst) = func.data.value_ref(arg) else {
+                            continue;
+                        };
+                        let direct = match konst {
+                            ConstantData::GlobalRef(name) if is_call && i == 0 => {
+                                ids.get(name).copied().filter(|&callee| {
+                                    module.functions()[callee].params().len()
+                                        == operand.args().len() - 1
+                                })
+                            }
+                            _ => None,
+                        };
+                        match direct {
+                            Some(callee) => {
+                                graph
+                                    .call_sites
+                                    .entry(callee)
+                                    .or_default()
+                                    .push(CallSite { caller, inst });
+                                let callees = graph.callees.entry(caller).or_default();
+                                if !callees.contains(&callee) {
+                                    callees.push(callee);
+                                }
+                            }
+                            None => konst.for_each_global_ref(&mut |name| {
+                                graph.address_taken.extend(ids.get(name));
+                            }),
+                        }
+                    }
+                }
+            }
+        }
+
+        graph
+    }
+
+    /// Returns the direct calls to `callee`.
+    pub fn call_sites(&self, callee: FunctionId) -> &[CallSite] {
+        self.call_sites.get(&callee).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the functions directly called by `caller`, each once.
+    pub fn callees(&self, caller: FunctionId) -> &[FunctionId] {
+        self.callees.get(&caller).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns true if `func` may be called other than through its call sites.
+    pub fn is_address_taken(&self, func: FunctionId) -> bool {
+        self.address_taken.contains(&func)
+    }
+}
@@ -1,6 +1,23 @@
-use crate::traits::basic_block::{BasicBlock, BasicBlockData, BasicBlockLayout};
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::prelude::*;
+use crate::{
+    ir::function::{basic_block::BasicBlock as IrBasicBlock, dot, Function},
+    pass::AnalysisPass,
+    traits::basic_block::{BasicBlock, BasicBlockData, BasicBlockLayout},
+};
+use core::{any::Any, fmt::Write};
 use id_arena::Id;
-use rustc_hash::{FxHashMap, FxHashSet};
+
+/// Computes the [`DominatorTree`] of a function.
+pub struct DominatorTreePass;
+
+impl AnalysisPass<Function> for DominatorTreePass {
+    fn run_on(&self, func: &Function, result: &mut Box<dyn Any>) {
+        if !func.is_prototype() {
+            *result = Box::new(DominatorTree::<IrBasicBlock>::new(func));
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct DominatorTree<BB: BasicBlock> {
@@ -51,7 +68,7 @@ impl<BB: BasicBlock> DominatorTree<BB> {
             }
         }
 
-        let entry = f.order().next().unwrap();
+        let entry = f.entry().unwrap();
         let frontier = Self::compute_dom_frontier(&ctx, &dom, entry);
         let mut level = FxHashMap::default();
         leveling(&mut level, &dom, entry, 0);
@@ -66,7 +83,7 @@ impl<BB: BasicBlock> DominatorTree<BB> {
 
     pub fn dominates(&self, x: Id<BB>, y: Id<BB>) -> bool {
         x == y
-            || self.dom.get(&x).map_or(false, |children| {
+            || self.dom.get(&x).is_some_and(|children| {
                 children.contains(&y) || children.iter().any(|&child| self.dominates(child, y))
             })
     }
@@ -101,13 +118,13 @@ impl<BB: BasicBlock> DominatorTree<BB> {
         frontier.insert(x, FxHashSet::default());
 
         for succ in ctx.f.get(x).succs() {
-            if ctx.idom.get(succ).map_or(true, |&x_| x != x_) {
+            if ctx.idom.get(succ).is_none_or(|&x_| x != x_) {
                 frontier.get_mut(&x).unwrap().insert(*succ);
             }
             for child in dom.get(&x).unwrap_or(&FxHashSet::default()) {
                 Self::compute_dom_frontier_of(ctx, dom, *child, frontier);
                 for y in frontier.get(child).unwrap().clone() {
-                    if ctx.idom.get(&y).map_or(true, |&x_| x_ != x) {
+                    if ctx.idom.get(&y).is_none_or(|&x_| x_ != x) {
                         frontier.get_mut(&x).unwrap().insert(y);
                     }
                 }
@@ -129,6 +146,46 @@ impl<BB: BasicBlock> DominatorTree<BB> {
     }
 }
 
+impl DominatorTree<IrBasicBlock> {
+    /// Returns the tree in the DOT format of GraphViz, as `opt -dot-dom`
+    /// does, with a node per block reachable in `func`.
+    pub fn to_dot(&self, func: &Function) -> String {
+        let labels = dot::block_labels(func);
+        let blocks: Vec<_> = func
+            .layout
+            .block_iter()
+            .filter(|&block| self.level_of(block).is_some())
+            .collect();
+        let mut out = dot::begin(&format!("Dominator tree for @{}", func.name));
+        for &block in &blocks {
+            writeln!(
+                out,
+                "    {} [label=\"{}\"];",
+                dot::node("", block),
+                dot::escape(&labels[&block])
+            )
+            .unwrap();
+        }
+        for &block in &blocks {
+            let children = match self.children_of(block) {
+                Some(children) => children,
+                None => continue,
+            };
+            for child in blocks.iter().filter(|child| children.contains(child)) {
+                writeln!(
+                    out,
+                    "    {} -> {};",
+                    dot::node("", block),
+                    dot::node("", *child)
+                )
+                .unwrap();
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
 impl<'a, BB: BasicBlock, F: BasicBlockData<BB> + BasicBlockLayout<BB>> Context<'a, BB, F> {
     fn new(f: &'a F) -> Self {
         Self {
@@ -145,7 +202,7 @@ impl<'a, BB: BasicBlock, F: BasicBlockData<BB> + BasicBlockLayout<BB>> Context<'
     }
 
     fn compute(mut self) -> Self {
-        let entry = self.f.order().next().unwrap();
+        let entry = self.f.entry().unwrap();
         let mut bucket = FxHashMap::default();
         let mut num = 0;
 
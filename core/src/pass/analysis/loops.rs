@@ -0,0 +1,284 @@
+use super::dom_tree::DominatorTree;
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::prelude::*;
+use crate::{
+    ir::function::{basic_block::BasicBlock as IrBasicBlock, dot, Function},
+    pass::AnalysisPass,
+    traits::basic_block::{BasicBlock, BasicBlockData, BasicBlockLayout},
+};
+use core::{any::Any, fmt::Write};
+use id_arena::Id;
+
+/// Computes the [`LoopInfo`] of a function.
+pub struct LoopInfoPass;
+
+impl AnalysisPass<Function> for LoopInfoPass {
+    fn run_on(&self, func: &Function, result: &mut Box<dyn Any>) {
+        if !func.is_prototype() {
+            *result = Box::new(LoopInfo::<IrBasicBlock>::new(func));
+        }
+    }
+}
+
+/// The natural loops of a function, found from the back edges of its CFG,
+/// i.e. edges to a block dominating their source. Back edges to the same
+/// header form a single loop.
+#[derive(Debug)]
+pub struct LoopInfo<BB: BasicBlock> {
+    loops: Vec<Loop<BB>>,
+    /// The innermost loop containing each block in a loop.
+    innermost: FxHashMap<Id<BB>, usize>,
+}
+
+#[derive(Debug)]
+pub struct Loop<BB: BasicBlock> {
+    pub header: Id<BB>,
+    /// The blocks in the loop, including the header and the blocks of inner loops.
+    pub blocks: FxHashSet<Id<BB>>,
+    /// The sources of the back edges.
+    pub latches: Vec<Id<BB>>,
+    /// Index of the innermost loop containing this one.
+    pub parent: Option<usize>,
+    /// 1 for outermost loops.
+    pub depth: usize,
+}
+
+impl<BB: BasicBlock> LoopInfo<BB> {
+    pub fn new<F: BasicBlockData<BB> + BasicBlockLayout<BB>>(f: &F) -> Self {
+        Self::with_dom_tree(f, &DominatorTree::new(f))
+    }
+
+    pub fn with_dom_tree<F: BasicBlockData<BB> + BasicBlockLayout<BB>>(
+        f: &F,
+        dom_tree: &DominatorTree<BB>,
+    ) -> Self {
+        let mut loops: Vec<Loop<BB>> = vec![];
+        for block in f.order() {
+            if dom_tree.level_of(block).is_none() {
+                // Unreachable
+                continue;
+            }
+            let latches: Vec<Id<BB>> = f
+                .get(block)
+                .preds()
+                .iter()
+                .copied()
+                .filter(|&pred| dom_tree.dominates(block, pred))
+                .collect();
+            if latches.is_empty() {
+                continue;
+            }
+            let mut blocks = FxHashSet::default();
+            blocks.insert(block);
+            let mut worklist = latches.clone();
+            while let Some(b) = worklist.pop() {
+                if dom_tree.level_of(b).is_some() && blocks.insert(b) {
+                    worklist.extend(f.get(b).preds().iter().copied());
+                }
+            }
+            loops.push(Loop {
+                header: block,
+                blocks,
+                latches,
+                parent: None,
+                depth: 0,
+            });
+        }
+
+        // Inner loops have fewer blocks than the loops containing them.
+        loops.sort_by_key(|l| l.blocks.len());
+        let mut innermost = FxHashMap::default();
+        for (i, l) in loops.iter().enumerate() {
+            for &block in &l.blocks {
+                innermost.entry(block).or_insert(i);
+            }
+        }
+        for i in 0..loops.len() {
+            loops[i].parent =
+                (i + 1..loops.len()).find(|&j| loops[j].blocks.contains(&loops[i].header));
+        }
+        for i in (0..loops.len()).rev() {
+            loops[i].depth = loops[i].parent.map_or(1, |p| loops[p].depth + 1);
+        }
+
+        Self { loops, innermost }
+    }
+
+    /// Returns the loops, innermost first.
+    pub fn loops(&self) -> &[Loop<BB>] {
+        &self.loops
+    }
+
+    /// Returns the innermost loop containing `block`.
+    pub fn loop_of(&self, block: Id<BB>) -> Option<&Loop<BB>> {
+        self.innermost.get(&block).map(|&i| &self.loops[i])
+    }
+
+    /// Returns the number of loops containing `block`.
+    pub fn loop_depth(&self, block: Id<BB>) -> usize {
+        self.loop_of(block).map_or(0, |l| l.depth)
+    }
+
+    pub fn is_loop_header(&self, block: Id<BB>) -> bool {
+        self.loop_of(block).is_some_and(|l| l.header == block)
+    }
+}
+
+impl LoopInfo<IrBasicBlock> {
+    /// Returns the control flow graph of `func` in the DOT format of
+    /// GraphViz, with the blocks of each loop in a cluster nested in the
+    /// clusters of the loops containing it.
+    pub fn to_dot(&self, func: &Function) -> String {
+        let labels = dot::block_labels(func);
+        let mut out = dot::begin(&format!("Loops of @{}", func.name));
+        self.write_cluster(&mut out, func, &labels, None, "    ");
+        for block in func.layout.block_iter() {
+            for &succ in dot::successors(func, block) {
+                writeln!(
+                    out,
+                    "    {} -> {};",
+                    dot::node("", block),
+                    dot::node("", succ)
+                )
+                .unwrap();
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Writes the blocks directly in the loop `parent`, or in no loop, and
+    /// the clusters of the loops directly in it.
+    fn write_cluster(
+        &self,
+        out: &mut String,
+        func: &Function,
+        labels: &FxHashMap<Id<IrBasicBlock>, String>,
+        parent: Option<usize>,
+        indent: &str,
+    ) {
+        for block in func.layout.block_iter() {
+            if self.innermost.get(&block).copied() == parent {
+                writeln!(
+                    out,
+                    "{}{} [label=\"{}\"];",
+                    indent,
+                    dot::node("", block),
+                    dot::escape(&labels[&block])
+                )
+                .unwrap();
+            }
+        }
+        // Outer loops first, in the order of their headers.
+        for block in func.layout.block_iter() {
+            let i = match (0..self.loops.len())
+                .find(|&i| self.loops[i].header == block && self.loops[i].parent == parent)
+            {
+                Some(i) => i,
+                None => continue,
+            };
+            writeln!(out, "{}subgraph cluster_{} {{", indent, i).unwrap();
+            writeln!(
+                out,
+                "{}    label=\"loop %{} (depth {})\";",
+                indent,
+                dot::escape(&labels[&block]),
+                self.loops[i].depth
+            )
+            .unwrap();
+            self.write_cluster(out, func, labels, Some(i), &format!("{}    ", indent));
+            writeln!(out, "{}}}", indent).unwrap();
+        }
+    }
+}
+
+impl<BB: BasicBlock> Loop<BB> {
+    pub fn contains(&self, block: Id<BB>) -> bool {
+        self.blocks.contains(&block)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn nested_loops() {
+        let module = parse_assembly(
+            r#"
+define void @f(i1 %0) {
+  br label %2
+2:
+  br label %3
+3:
+  br i1 %0, label %3, label %4
+4:
+  br i1 %0, label %2, label %5
+5:
+  ret void
+}"#,
+        )
+        .unwrap();
+        let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+        let blocks: Vec<_> = func.layout.block_iter().collect();
+        let info = LoopInfo::new(func);
+
+        assert_eq!(info.loops().len(), 2);
+        assert_eq!(
+            blocks
+                .iter()
+                .map(|&b| info.loop_depth(b))
+                .collect::<Vec<_>>(),
+            vec![0, 1, 2, 1, 0]
+        );
+        assert!(info.is_loop_header(blocks[2]));
+        let inner = info.loop_of(blocks[2]).unwrap();
+        assert_eq!(inner.latches, vec![blocks[2]]);
+        assert_eq!(info.loops()[inner.parent.unwrap()].header, blocks[1]);
+    }
+
+    #[test]
+    fn to_dot() {
+        let module = parse_assembly(
+            r#"
+define void @f(i1 %0) {
+  br label %2
+2:
+  br label %3
+3:
+  br i1 %0, label %3, label %4
+4:
+  br i1 %0, label %2, label %5
+5:
+  ret void
+}"#,
+        )
+        .unwrap();
+        let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+        assert_eq!(
+            LoopInfo::new(func).to_dot(func),
+            r#"digraph "Loops of @f" {
+    node [shape=box, fontname=monospace];
+    b1 [label="1"];
+    b5 [label="5"];
+    subgraph cluster_1 {
+        label="loop %2 (depth 1)";
+        b2 [label="2"];
+        b4 [label="4"];
+        subgraph cluster_0 {
+            label="loop %3 (depth 2)";
+            b3 [label="3"];
+        }
+    }
+    b1 -> b2;
+    b2 -> b3;
+    b3 -> b3;
+    b3 -> b4;
+    b4 -> b2;
+    b4 -> b5;
+}
+"#
+        );
+    }
+}
@@ -1 +1,6 @@
+pub mod alias;
+pub mod block_freq;
+pub mod call_graph;
 pub mod dom_tree;
+pub mod loops;
+pub mod post_dom_tree;
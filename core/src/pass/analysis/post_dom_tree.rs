@@ -0,0 +1,187 @@
+use super::dom_tree::DominatorTree;
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::prelude::*;
+use crate::{
+    ir::function::{basic_block::BasicBlockId, Function},
+    pass::AnalysisPass,
+    traits::basic_block::{BasicBlock, BasicBlockData, BasicBlockLayout},
+};
+use core::any::Any;
+use id_arena::{Arena, Id};
+
+/// Computes the [`PostDominatorTree`] of a function.
+pub struct PostDominatorTreePass;
+
+impl AnalysisPass<Function> for PostDominatorTreePass {
+    fn run_on(&self, func: &Function, result: &mut Box<dyn Any>) {
+        if !func.is_prototype() {
+            *result = Box::new(PostDominatorTree::new(func));
+        }
+    }
+}
+
+/// The dominator tree of the reversed control flow graph, rooted at a virtual
+/// exit block succeeding every block without successors. Blocks that cannot
+/// reach an exit (e.g. in infinite loops) are not part of the tree.
+#[derive(Debug)]
+pub struct PostDominatorTree {
+    tree: DominatorTree<Node>,
+    nodes: FxHashMap<BasicBlockId, Id<Node>>,
+    blocks: FxHashMap<Id<Node>, BasicBlockId>,
+    parent: FxHashMap<Id<Node>, Id<Node>>,
+}
+
+#[derive(Debug)]
+struct Node {
+    preds: FxHashSet<Id<Node>>,
+    succs: FxHashSet<Id<Node>>,
+}
+
+struct ReversedCfg {
+    nodes: Arena<Node>,
+    order: Vec<Id<Node>>,
+}
+
+impl PostDominatorTree {
+    pub fn new(func: &Function) -> Self {
+        let mut nodes = Arena::new();
+        let exit = nodes.alloc(Node {
+            preds: FxHashSet::default(),
+            succs: FxHashSet::default(),
+        });
+        let mut order = vec![exit];
+        let mut block_to_node = FxHashMap::default();
+        let mut node_to_block = FxHashMap::default();
+        for block in func.layout.block_iter() {
+            let node = nodes.alloc(Node {
+                preds: FxHashSet::default(),
+                succs: FxHashSet::default(),
+            });
+            order.push(node);
+            block_to_node.insert(block, node);
+            node_to_block.insert(node, block);
+        }
+
+        for block in func.layout.block_iter() {
+            let node = block_to_node[&block];
+            let succs = func.data.basic_blocks[block].succs();
+            if succs.is_empty() {
+                nodes[node].preds.insert(exit);
+                nodes[exit].succs.insert(node);
+            }
+            for succ in succs {
+                let succ = block_to_node[succ];
+                nodes[node].preds.insert(succ);
+                nodes[succ].succs.insert(node);
+            }
+        }
+
+        let tree = DominatorTree::new(&ReversedCfg { nodes, order });
+        let mut parent = FxHashMap::default();
+        let mut worklist = vec![exit];
+        while let Some(node) = worklist.pop() {
+            for &child in tree.children_of(node).into_iter().flatten() {
+                parent.insert(child, node);
+                worklist.push(child);
+            }
+        }
+
+        Self {
+            tree,
+            nodes: block_to_node,
+            blocks: node_to_block,
+            parent,
+        }
+    }
+
+    /// Returns true if every path from `y` to an exit goes through `x`.
+    pub fn post_dominates(&self, x: BasicBlockId, y: BasicBlockId) -> bool {
+        x == y || (self.contains(y) && self.tree.dominates(self.nodes[&x], self.nodes[&y]))
+    }
+
+    /// Returns the nearest block post-dominating `x`, or `None` if there is
+    /// no such block (i.e. `x` can reach different exits, or none at all).
+    pub fn immediate_post_dominator(&self, x: BasicBlockId) -> Option<BasicBlockId> {
+        let parent = self.parent.get(self.nodes.get(&x)?)?;
+        self.blocks.get(parent).copied()
+    }
+
+    /// Returns the blocks whose terminator decides whether `x` is executed,
+    /// i.e. the post-dominance frontier of `x`.
+    pub fn control_dependences_of(
+        &self,
+        x: BasicBlockId,
+    ) -> impl Iterator<Item = BasicBlockId> + '_ {
+        self.nodes
+            .get(&x)
+            .and_then(|node| self.tree.dominance_frontier_of(*node))
+            .into_iter()
+            .flatten()
+            .filter_map(|node| self.blocks.get(node).copied())
+    }
+
+    /// Returns true if `x` can reach an exit.
+    pub fn contains(&self, x: BasicBlockId) -> bool {
+        self.nodes
+            .get(&x)
+            .is_some_and(|node| self.parent.contains_key(node))
+    }
+}
+
+impl BasicBlock for Node {
+    fn preds(&self) -> &FxHashSet<Id<Self>> {
+        &self.preds
+    }
+
+    fn succs(&self) -> &FxHashSet<Id<Self>> {
+        &self.succs
+    }
+}
+
+impl BasicBlockData<Node> for ReversedCfg {
+    fn get(&self, id: Id<Node>) -> &Node {
+        &self.nodes[id]
+    }
+}
+
+impl BasicBlockLayout<Node> for ReversedCfg {
+    fn order(&self) -> Box<dyn Iterator<Item = Id<Node>> + '_> {
+        Box::new(self.order.iter().copied())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn post_dominators() {
+        let module = parse_assembly(
+            r#"
+define i32 @f(i1 %0) {
+  br i1 %0, label %2, label %3
+2:
+  br label %4
+3:
+  br label %4
+4:
+  ret i32 0
+}"#,
+        )
+        .unwrap();
+        let func = &module.functions()[module.find_function_by_name("f").unwrap()];
+        let blocks: Vec<_> = func.layout.block_iter().collect();
+        let tree = PostDominatorTree::new(func);
+
+        assert!(tree.post_dominates(blocks[3], blocks[0]));
+        assert!(!tree.post_dominates(blocks[1], blocks[0]));
+        assert_eq!(tree.immediate_post_dominator(blocks[0]), Some(blocks[3]));
+        assert_eq!(tree.immediate_post_dominator(blocks[3]), None);
+        assert_eq!(
+            tree.control_dependences_of(blocks[1]).collect::<Vec<_>>(),
+            vec![blocks[0]]
+        );
+        assert_eq!(tree.control_dependences_of(blocks[3]).count(), 0);
+    }
+}
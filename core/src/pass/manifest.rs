@@ -0,0 +1,246 @@
+//! Pipeline manifests.
+//!
+//! A [`PipelineManifest`] records everything that determines what a driver
+//! produced: the pass pipeline, its options, the target and the version of
+//! vicis. Drivers emit one next to their output, and accept one to rerun the
+//! exact same pipeline, e.g. to reproduce a miscompilation reported with it.
+//!
+//! Manifests are plain text, one `key = value` per line:
+//!
+//! ```text
+//! # vicis pipeline manifest
+//! version = 0.2.1
+//! target = x86_64-pc-linux-gnu
+//! features = sse2,sse4.2
+//! passes = mem2reg,instcombine,dce
+//! option.opt-level = 2
+//! ```
+//!
+//! Blank lines and lines starting with `#` are ignored.
+
+use super::{
+    registry::{PassRegistry, UnknownPassError},
+    PassManager,
+};
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use core::{error::Error, fmt, str::FromStr};
+
+/// The version of vicis-core recorded in new manifests.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineManifest {
+    /// The version of vicis-core the pipeline was run with.
+    pub version: String,
+    /// The target triple, empty if unspecified.
+    pub target: String,
+    /// Enabled target features. Kept sorted.
+    pub features: Vec<String>,
+    /// Pass names in the order they run, as registered in a [`PassRegistry`].
+    pub passes: Vec<String>,
+    /// Driver options, e.g. `opt-level`.
+    pub options: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestError {
+    /// 1-based line number.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Default for PipelineManifest {
+    fn default() -> Self {
+        Self {
+            version: VERSION.to_string(),
+            target: String::new(),
+            features: vec![],
+            passes: vec![],
+            options: BTreeMap::new(),
+        }
+    }
+}
+
+impl PipelineManifest {
+    /// Creates a manifest for `pipeline`, a comma separated list of pass names
+    /// as given to [`PassRegistry::build_pipeline`].
+    pub fn new(pipeline: &str) -> Self {
+        Self {
+            passes: pass_names(pipeline).map(str::to_string).collect(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_target<T: Into<String>>(mut self, triple: T, features: &[&str]) -> Self {
+        self.target = triple.into();
+        self.features = features.iter().map(|f| f.to_string()).collect();
+        self.features.sort();
+        self.features.dedup();
+        self
+    }
+
+    pub fn with_option<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.options.insert(key.into(), value.into());
+        self
+    }
+
+    /// Returns the pipeline as a comma separated list of pass names.
+    pub fn pipeline(&self) -> String {
+        self.passes.join(",")
+    }
+
+    /// Returns true if the manifest was produced by this version of vicis.
+    /// Others may run passes that behave differently.
+    pub fn is_current_version(&self) -> bool {
+        self.version == VERSION
+    }
+}
+
+impl<T> PassRegistry<T> {
+    /// Builds the pipeline recorded in `manifest`.
+    pub fn build_from_manifest(
+        &self,
+        manifest: &PipelineManifest,
+    ) -> Result<PassManager<T>, UnknownPassError> {
+        self.build_pipeline(&manifest.pipeline())
+    }
+}
+
+/// Splits a comma separated pipeline into pass names.
+pub(super) fn pass_names(pipeline: &str) -> impl Iterator<Item = &str> {
+    pipeline.split(',').map(str::trim).filter(|n| !n.is_empty())
+}
+
+impl fmt::Display for PipelineManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# vicis pipeline manifest")?;
+        writeln!(f, "version = {}", self.version)?;
+        if !self.target.is_empty() {
+            writeln!(f, "target = {}", self.target)?;
+        }
+        if !self.features.is_empty() {
+            writeln!(f, "features = {}", self.features.join(","))?;
+        }
+        writeln!(f, "passes = {}", self.pipeline())?;
+        for (key, value) in &self.options {
+            writeln!(f, "option.{} = {}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PipelineManifest {
+    type Err = ManifestError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut manifest = Self {
+            version: String::new(),
+            ..Self::default()
+        };
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| ManifestError {
+                line: i + 1,
+                message: message.to_string(),
+            };
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected `key = value`"))?;
+            let (key, value) = (key.trim(), value.trim());
+            let list = || pass_names(value).map(str::to_string).collect();
+            match key {
+                "version" => manifest.version = value.to_string(),
+                "target" => manifest.target = value.to_string(),
+                "features" => {
+                    manifest.features = list();
+                    manifest.features.sort();
+                }
+                "passes" => manifest.passes = list(),
+                _ => match key.strip_prefix("option.") {
+                    Some(option) if !option.is_empty() => {
+                        manifest
+                            .options
+                            .insert(option.to_string(), value.to_string());
+                    }
+                    _ => return Err(error(&format!("unknown key `{}`", key))),
+                },
+            }
+        }
+        if manifest.version.is_empty() {
+            return Err(ManifestError {
+                line: s.lines().count(),
+                message: "missing `version`".to_string(),
+            });
+        }
+        Ok(manifest)
+    }
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ManifestError {}
+
+#[cfg(test)]
+mod test {
+    use super::{PipelineManifest, VERSION};
+    use crate::{ir::module::parse_assembly, pass::registry::PassRegistry};
+
+    #[test]
+    fn round_trip() {
+        let manifest = PipelineManifest::new("mem2reg, instcombine,dce")
+            .with_target("x86_64-pc-linux-gnu", &["sse4.2", "sse2"])
+            .with_option("opt-level", "2");
+        let text = manifest.to_string();
+        assert_eq!(
+            text,
+            format!(
+                "# vicis pipeline manifest\nversion = {}\ntarget = x86_64-pc-linux-gnu\n\
+                 features = sse2,sse4.2\npasses = mem2reg,instcombine,dce\noption.opt-level = 2\n",
+                VERSION
+            )
+        );
+        let parsed: PipelineManifest = text.parse().unwrap();
+        assert_eq!(parsed, manifest);
+        assert!(parsed.is_current_version());
+
+        let err = "version = 0.1.0\npasses dce".parse::<PipelineManifest>();
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "line 2: expected `key = value`"
+        );
+        assert!("passes = dce".parse::<PipelineManifest>().is_err());
+    }
+
+    #[test]
+    fn rerun_pipeline() {
+        let mut module = parse_assembly(
+            r#"
+define i32 @main() {
+  %1 = add i32 1, 2
+  ret i32 0
+}"#,
+        )
+        .unwrap();
+        let manifest: PipelineManifest = "version = 0.0.0\npasses = dce".parse().unwrap();
+        assert!(!manifest.is_current_version());
+        let registry = PassRegistry::with_default_passes();
+        let mut pm = registry.build_from_manifest(&manifest).unwrap();
+        pm.run_on_module(&mut module);
+        let main = module.find_function_by_name("main").unwrap();
+        assert_eq!(
+            format!("{:?}", module.functions()[main]),
+            "define external dso_preemptable default i32 @main() {\n0:\n    ret i32 0\n}\n"
+        );
+
+        let manifest = PipelineManifest::new("dce,foo");
+        assert!(registry.build_from_manifest(&manifest).is_err());
+    }
+}
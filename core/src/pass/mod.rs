@@ -1,15 +1,81 @@
 pub mod analysis;
+pub mod manifest;
+pub mod registry;
+pub mod remarks;
+pub mod stats;
 pub mod transform;
 
-use rustc_hash::FxHashMap;
-use std::any::{Any, TypeId};
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::prelude::*;
+use core::{
+    any::{Any, TypeId},
+    time::Duration,
+};
+#[cfg(feature = "std")]
+use std::time::Instant;
 
 pub trait AnalysisPass<T> {
     fn run_on(&self, _: &T, _: &mut Box<dyn Any>) {}
+
+    /// Names the pass in reports. Defaults to the name of its type.
+    fn name(&self) -> &'static str {
+        type_name::<Self>()
+    }
+
+    /// Identifies the pass so that instances of the same analysis share a cached result.
+    fn pass_type(&self) -> TypeId
+    where
+        Self: 'static,
+    {
+        TypeId::of::<Self>()
+    }
 }
 
 pub trait TransformPass<T> {
     fn run_on(&self, _: &mut T, _: &mut Box<dyn Any>) {}
+
+    /// Names the pass in reports. Defaults to the name of its type.
+    fn name(&self) -> &'static str {
+        type_name::<Self>()
+    }
+
+    /// Returns the analysis results that are still valid after this pass has run.
+    /// Defaults to none, so passes that keep some valid must override this.
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+/// A set of analysis results, identified by their types, kept across a transform pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreservedAnalyses {
+    All,
+    Some(FxHashSet<TypeId>),
+}
+
+impl PreservedAnalyses {
+    pub fn all() -> Self {
+        Self::All
+    }
+
+    pub fn none() -> Self {
+        Self::Some(FxHashSet::default())
+    }
+
+    /// Adds the analysis whose result type is `R`.
+    pub fn preserve<R: 'static>(mut self) -> Self {
+        if let Self::Some(set) = &mut self {
+            set.insert(TypeId::of::<R>());
+        }
+        self
+    }
+
+    pub fn is_preserved(&self, result: TypeId) -> bool {
+        match self {
+            Self::All => true,
+            Self::Some(set) => set.contains(&result),
+        }
+    }
 }
 
 pub enum Pass<T> {
@@ -17,9 +83,19 @@ pub enum Pass<T> {
     Transform(Box<dyn TransformPass<T>>),
 }
 
+/// Runs passes in order on a target.
+///
+/// Analysis results are cached while running on a target: an analysis whose
+/// result is still cached is not run again, and each transform pass
+/// invalidates the results it does not preserve (see
+/// [`TransformPass::preserved_analyses`]).
 pub struct PassManager<T> {
     passes: Vec<Pass<T>>,
     results: FxHashMap<TypeId, Box<dyn Any>>,
+    /// The result type of each kind of analysis pass, known once it has run.
+    result_types: FxHashMap<TypeId, TypeId>,
+    /// The time spent in each pass so far, if timed.
+    timings: Option<Vec<Duration>>,
 }
 
 impl<T> Default for PassManager<T> {
@@ -27,6 +103,8 @@ impl<T> Default for PassManager<T> {
         Self {
             passes: vec![],
             results: FxHashMap::default(),
+            result_types: FxHashMap::default(),
+            timings: None,
         }
     }
 }
@@ -37,27 +115,86 @@ impl<T> PassManager<T> {
     }
 
     pub fn add(&mut self, pass: Pass<T>) {
-        self.passes.push(pass)
+        self.passes.push(pass);
+        if let Some(timings) = &mut self.timings {
+            timings.push(Duration::ZERO);
+        }
+    }
+
+    /// Enables timing each pass, after LLVM's `-time-passes`. Times add up
+    /// over runs.
+    #[cfg(feature = "std")]
+    pub fn time_passes(&mut self) {
+        self.timings = Some(vec![Duration::ZERO; self.passes.len()]);
+    }
+
+    /// Returns the name of each pass and the time spent in it, if timed.
+    pub fn timings(&self) -> Option<Vec<(&'static str, Duration)>> {
+        let timings = self.timings.as_ref()?;
+        Some(
+            self.passes
+                .iter()
+                .map(Pass::name)
+                .zip(timings.iter().copied())
+                .collect(),
+        )
+    }
+
+    /// Returns the time spent in each pass, if timed, followed by the
+    /// statistics collected.
+    pub fn report(&self) -> String {
+        let mut report = self
+            .timings()
+            .map_or_else(String::new, |timings| stats::timing_report(&timings));
+        let stats = stats::report();
+        if !report.is_empty() && !stats.is_empty() {
+            report.push('\n');
+        }
+        report + &stats
     }
 
     pub fn add_analysis<P: 'static + AnalysisPass<T>>(&mut self, pass: P) {
-        self.passes.push(Pass::Analysis(Box::new(pass)))
+        self.add(Pass::Analysis(Box::new(pass)))
     }
 
     pub fn add_transform<P: 'static + TransformPass<T>>(&mut self, pass: P) {
-        self.passes.push(Pass::Transform(Box::new(pass)))
+        self.add(Pass::Transform(Box::new(pass)))
     }
 
+    pub fn get_result<P: 'static>(&self) -> Option<&P> {
+        self.results
+            .get(&TypeId::of::<P>())
+            .and_then(|result| result.downcast_ref())
+    }
+}
+
+impl<T: 'static> PassManager<T> {
     pub fn run_on(&mut self, target: &mut T) {
         self.results.clear();
 
-        for pass in &self.passes {
-            let mut result: Box<dyn Any> = Box::new(());
+        #[cfg_attr(not(feature = "std"), allow(unused_variables))]
+        for (i, pass) in self.passes.iter().enumerate() {
+            #[cfg(feature = "std")]
+            let start = self.timings.is_some().then(Instant::now);
             match pass {
-                Pass::Analysis(analysis) => analysis.run_on(target, &mut result),
-                Pass::Transform(transform) => transform.run_on(target, &mut result),
+                Pass::Analysis(analysis) => run_analysis(
+                    analysis.as_ref(),
+                    target,
+                    &mut self.results,
+                    &mut self.result_types,
+                ),
+                Pass::Transform(transform) => {
+                    let mut result: Box<dyn Any> = Box::new(());
+                    transform.run_on(target, &mut result);
+                    let preserved = transform.preserved_analyses();
+                    self.results.retain(|ty, _| preserved.is_preserved(*ty));
+                    self.results.insert((*result).type_id(), result);
+                }
+            }
+            #[cfg(feature = "std")]
+            if let (Some(timings), Some(start)) = (&mut self.timings, start) {
+                timings[i] += start.elapsed();
             }
-            self.results.insert((*result).type_id(), result);
         }
     }
 
@@ -65,20 +202,37 @@ impl<T> PassManager<T> {
         self.results.clear();
 
         for pass in &self.passes {
-            let mut result: Box<dyn Any> = Box::new(());
-            match pass {
-                Pass::Analysis(analysis) => analysis.run_on(target, &mut result),
-                Pass::Transform(_) => {}
+            if let Pass::Analysis(analysis) = pass {
+                run_analysis(
+                    analysis.as_ref(),
+                    target,
+                    &mut self.results,
+                    &mut self.result_types,
+                )
             }
-            self.results.insert((*result).type_id(), result);
         }
     }
+}
 
-    pub fn get_result<P: 'static>(&self) -> Option<&P> {
-        self.results
-            .get(&TypeId::of::<P>())
-            .and_then(|result| result.downcast_ref())
+/// Runs `analysis` unless its result is already cached.
+fn run_analysis<T: 'static>(
+    analysis: &(dyn AnalysisPass<T> + 'static),
+    target: &T,
+    results: &mut FxHashMap<TypeId, Box<dyn Any>>,
+    result_types: &mut FxHashMap<TypeId, TypeId>,
+) {
+    let pass_ty = analysis.pass_type();
+    if result_types
+        .get(&pass_ty)
+        .is_some_and(|ty| results.contains_key(ty))
+    {
+        return;
     }
+    let mut result: Box<dyn Any> = Box::new(());
+    analysis.run_on(target, &mut result);
+    let ty = (*result).type_id();
+    result_types.insert(pass_ty, ty);
+    results.insert(ty, result);
 }
 
 use crate::ir::{function::Function, module::Module};
@@ -97,10 +251,46 @@ impl PassManager<Function> {
     }
 }
 
+/// Runs a function transform pass on each function of a module, so that
+/// function passes can be mixed with module passes in one pipeline. The
+/// results of the function pass are dropped.
+pub struct FunctionPassAdaptor(pub Box<dyn TransformPass<Function>>);
+
+impl TransformPass<Module> for FunctionPassAdaptor {
+    fn run_on(&self, module: &mut Module, _result: &mut Box<dyn Any>) {
+        for (_, func) in &mut module.functions {
+            let mut result: Box<dyn Any> = Box::new(());
+            self.0.run_on(func, &mut result);
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        self.0.preserved_analyses()
+    }
+}
+
 impl<T> Pass<T> {
     pub fn analysis<P: 'static + AnalysisPass<T>>(pass: P) -> Self {
         Self::Analysis(Box::new(pass))
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Analysis(analysis) => analysis.name(),
+            Self::Transform(transform) => transform.name(),
+        }
+    }
+}
+
+/// Returns the name of `P` without its path.
+fn type_name<P: ?Sized>() -> &'static str {
+    let name = core::any::type_name::<P>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
 }
 
 #[cfg(test)]
@@ -110,6 +300,8 @@ mod test {
         function::Function,
         module::{parse_assembly, Module},
     };
+    use alloc::rc::Rc;
+    use core::cell::Cell;
 
     pub struct TestFunctionAnalysisPass {}
     pub struct TestFunctionAnalysisResult(String);
@@ -124,6 +316,10 @@ mod test {
 
     impl TransformPass<Function> for TestFunctionTransformPass {
         fn run_on(&self, _func: &mut Function, _result: &mut Box<dyn Any>) {}
+
+        fn preserved_analyses(&self) -> PreservedAnalyses {
+            PreservedAnalyses::all()
+        }
     }
 
     fn test_module() -> Module {
@@ -155,6 +351,96 @@ define dso_local i32 @main() {
         );
     }
 
+    struct CountingAnalysisPass(Rc<Cell<usize>>);
+
+    impl AnalysisPass<Function> for CountingAnalysisPass {
+        fn run_on(&self, _func: &Function, result: &mut Box<dyn Any>) {
+            self.0.set(self.0.get() + 1);
+            *result = Box::new(TestFunctionAnalysisResult("counted".to_string()));
+        }
+    }
+
+    struct PreservingTransformPass;
+
+    impl TransformPass<Function> for PreservingTransformPass {
+        fn preserved_analyses(&self) -> PreservedAnalyses {
+            PreservedAnalyses::none().preserve::<TestFunctionAnalysisResult>()
+        }
+    }
+
+    struct InvalidatingTransformPass;
+
+    impl TransformPass<Function> for InvalidatingTransformPass {}
+
+    #[test]
+    fn analysis_caching() {
+        let mut module = test_module();
+        let runs = Rc::new(Cell::new(0));
+
+        let mut pm = PassManager::new();
+        pm.add_analysis(CountingAnalysisPass(runs.clone()));
+        pm.add_transform(PreservingTransformPass);
+        pm.add_analysis(CountingAnalysisPass(runs.clone()));
+        pm.run_on_module(&mut module);
+        assert_eq!(runs.get(), 1);
+
+        pm.add_transform(InvalidatingTransformPass);
+        pm.add_analysis(CountingAnalysisPass(runs.clone()));
+        pm.run_on_module(&mut module);
+        assert_eq!(runs.get(), 3);
+        assert_eq!(
+            pm.get_result::<TestFunctionAnalysisResult>().unwrap().0,
+            "counted"
+        );
+    }
+
+    #[test]
+    fn textual_pipeline() {
+        let mut module = test_module();
+        let mut pm = PassManager::new();
+        pm.add_analysis(TestFunctionAnalysisPass {});
+        for pass in registry::PassRegistry::with_default_passes()
+            .build_pipeline("canonicalize,domtree")
+            .unwrap()
+            .passes
+        {
+            pm.add(pass);
+        }
+        pm.run_on_module(&mut module);
+        // `canonicalize` preserves all analyses.
+        assert_eq!(
+            pm.get_result::<TestFunctionAnalysisResult>().unwrap().0,
+            "main"
+        );
+        assert!(pm
+            .get_result::<analysis::dom_tree::DominatorTree<crate::ir::function::basic_block::BasicBlock>>()
+            .is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn time_passes() {
+        let mut module = parse_assembly(
+            r#"
+define i32 @main() {
+  %1 = add i32 1, 2
+  ret i32 0
+}"#,
+        )
+        .unwrap();
+        let mut pm = PassManager::new();
+        pm.add_analysis(TestFunctionAnalysisPass {});
+        pm.time_passes();
+        pm.add_transform(transform::dce::DCEPass);
+        pm.run_on_module(&mut module);
+        let names: Vec<_> = pm.timings().unwrap().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(names, vec!["TestFunctionAnalysisPass", "DCEPass"]);
+        let report = pm.report();
+        assert!(report.contains("... Pass execution timing report ..."));
+        assert!(report.contains("... Statistics Collected ..."));
+        assert!(report.contains(" - Number of instructions removed\n"));
+    }
+
     #[test]
     fn analysis_transform() {
         let mut module = test_module();
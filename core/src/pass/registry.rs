@@ -0,0 +1,270 @@
+use super::transform::strip::{StripDebugPass, StripMetadataPass, StripNamesPass};
+use super::{
+    analysis::{block_freq::BlockFrequencyPass, dom_tree::DominatorTreePass, loops::LoopInfoPass},
+    manifest::pass_names,
+    transform::{
+        adce::ADCEPass, canonicalize::CanonicalizePass, compact::CompactPass,
+        constant_fold::ConstantFoldPass, dae::DeadArgElimPass, dce::DCEPass, dse::DSEPass,
+        global_dce::GlobalDCEPass, global_opt::GlobalOptPass, gvn::GVNPass, icf::ICFPass,
+        indvars::IndVarSimplifyPass, infer_alignment::InferAlignmentPass, inline::InlinePass,
+        inst_combine::InstCombinePass, load_store_combine::LoadStoreCombinePass,
+        loop_rotate::LoopRotatePass, loop_unroll::LoopUnrollPass, mem2reg::Mem2RegPass,
+        namer::NamerPass, reassociate::ReassociatePass, sccp::SCCPPass,
+        tail_call_elim::TailCallElimPass,
+    },
+    FunctionPassAdaptor, Pass, PassManager,
+};
+use crate::ir::{function::Function, module::Module};
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use core::{error::Error, fmt};
+
+pub type PassConstructor<T> = Box<dyn Fn() -> Pass<T>>;
+
+/// Maps pass names (as given to e.g. `--passes=dce,my-pass`) to constructors.
+///
+/// Downstream crates can register their own passes and build a pipeline from
+/// a textual description in a custom driver.
+pub struct PassRegistry<T> {
+    constructors: BTreeMap<String, PassConstructor<T>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPassError(pub String);
+
+impl<T> Default for PassRegistry<T> {
+    fn default() -> Self {
+        Self {
+            constructors: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> PassRegistry<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a pass under `name`. A pass already registered with the same name is replaced.
+    pub fn register<N, F>(&mut self, name: N, constructor: F)
+    where
+        N: Into<String>,
+        F: Fn() -> Pass<T> + 'static,
+    {
+        self.constructors.insert(name.into(), Box::new(constructor));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.constructors.contains_key(name)
+    }
+
+    /// Returns the registered pass names in sorted order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.constructors.keys().map(String::as_str)
+    }
+
+    pub fn create(&self, name: &str) -> Option<Pass<T>> {
+        self.constructors.get(name).map(|constructor| constructor())
+    }
+
+    /// Builds a `PassManager` from a comma separated list of pass names.
+    pub fn build_pipeline(&self, pipeline: &str) -> Result<PassManager<T>, UnknownPassError> {
+        let mut pm = PassManager::new();
+        for name in pass_names(pipeline) {
+            let pass = self
+                .create(name)
+                .ok_or_else(|| UnknownPassError(name.to_string()))?;
+            pm.add(pass);
+        }
+        Ok(pm)
+    }
+}
+
+impl PassRegistry<Function> {
+    /// Creates a registry populated with the passes shipped with vicis.
+    pub fn with_default_passes() -> Self {
+        let mut registry = Self::new();
+        registry.register("adce", || Pass::Transform(Box::new(ADCEPass)));
+        registry.register("block-freq", || Pass::analysis(BlockFrequencyPass));
+        registry.register("canonicalize", || {
+            Pass::Transform(Box::new(CanonicalizePass))
+        });
+        registry.register("compact", || Pass::Transform(Box::new(CompactPass)));
+        registry.register("constfold", || Pass::Transform(Box::new(ConstantFoldPass)));
+        registry.register("domtree", || Pass::analysis(DominatorTreePass));
+        registry.register("dce", || Pass::Transform(Box::new(DCEPass)));
+        registry.register("dse", || Pass::Transform(Box::new(DSEPass)));
+        registry.register("gvn", || Pass::Transform(Box::new(GVNPass)));
+        registry.register("indvars", || Pass::Transform(Box::new(IndVarSimplifyPass)));
+        registry.register("infer-alignment", || {
+            Pass::Transform(Box::new(InferAlignmentPass::default()))
+        });
+        registry.register("instcombine", || Pass::Transform(Box::new(InstCombinePass)));
+        registry.register("load-store-combine", || {
+            Pass::Transform(Box::new(LoadStoreCombinePass::default()))
+        });
+        registry.register("loop-rotate", || Pass::Transform(Box::new(LoopRotatePass)));
+        registry.register("loop-unroll", || {
+            Pass::Transform(Box::new(LoopUnrollPass::default()))
+        });
+        registry.register("loops", || Pass::analysis(LoopInfoPass));
+        registry.register("mem2reg", || Pass::Transform(Box::new(Mem2RegPass)));
+        registry.register("instnamer", || Pass::Transform(Box::new(NamerPass)));
+        registry.register("reassociate", || Pass::Transform(Box::new(ReassociatePass)));
+        registry.register("sccp", || Pass::Transform(Box::new(SCCPPass)));
+        registry.register("tailcallelim", || {
+            Pass::Transform(Box::new(TailCallElimPass))
+        });
+        registry
+    }
+}
+
+impl PassRegistry<Module> {
+    /// Creates a registry populated with the module passes shipped with vicis,
+    /// and with the function transform passes of
+    /// [`PassRegistry::with_default_passes`] under the same names,
+    /// run on each function.
+    pub fn with_default_module_passes() -> Self {
+        let mut registry = Self::new();
+        for (name, constructor) in PassRegistry::<Function>::with_default_passes().constructors {
+            // Function analyses have nowhere to keep their results in a module pipeline.
+            if let Pass::Transform(_) = constructor() {
+                registry.register(name, move || match constructor() {
+                    Pass::Transform(pass) => Pass::Transform(Box::new(FunctionPassAdaptor(pass))),
+                    Pass::Analysis(_) => unreachable!(),
+                });
+            }
+        }
+        registry.register("deadargelim", || Pass::Transform(Box::new(DeadArgElimPass)));
+        registry.register("globaldce", || Pass::Transform(Box::new(GlobalDCEPass)));
+        registry.register("globalopt", || Pass::Transform(Box::new(GlobalOptPass)));
+        registry.register("icf", || Pass::Transform(Box::new(ICFPass)));
+        registry.register("inline", || Pass::Transform(Box::new(InlinePass::new())));
+        registry.register("strip-debug", || Pass::Transform(Box::new(StripDebugPass)));
+        registry.register("strip-metadata", || {
+            Pass::Transform(Box::new(StripMetadataPass))
+        });
+        registry.register("strip-names", || Pass::Transform(Box::new(StripNamesPass)));
+        registry
+    }
+}
+
+impl Error for UnknownPassError {}
+
+impl fmt::Display for UnknownPassError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown pass '{}'", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ir::module::parse_assembly,
+        pass::{PreservedAnalyses, TransformPass},
+    };
+    use core::any::Any;
+
+    struct RenamePass;
+
+    impl TransformPass<Function> for RenamePass {
+        fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+            func.name = format!("renamed_{}", func.name);
+        }
+
+        fn preserved_analyses(&self) -> PreservedAnalyses {
+            PreservedAnalyses::all()
+        }
+    }
+
+    #[test]
+    fn custom_pass() {
+        let mut module = parse_assembly(
+            r#"
+define dso_local i32 @main() {
+  %1 = add i32 1, 2
+  ret i32 0
+}"#,
+        )
+        .unwrap();
+
+        let mut registry = PassRegistry::with_default_passes();
+        registry.register("rename", || Pass::Transform(Box::new(RenamePass)));
+        assert_eq!(
+            registry.names().collect::<Vec<_>>(),
+            vec![
+                "adce",
+                "block-freq",
+                "canonicalize",
+                "compact",
+                "constfold",
+                "dce",
+                "domtree",
+                "dse",
+                "gvn",
+                "indvars",
+                "infer-alignment",
+                "instcombine",
+                "instnamer",
+                "load-store-combine",
+                "loop-rotate",
+                "loop-unroll",
+                "loops",
+                "mem2reg",
+                "reassociate",
+                "rename",
+                "sccp",
+                "tailcallelim"
+            ]
+        );
+
+        let mut pm = registry.build_pipeline("dce, rename").unwrap();
+        pm.run_on_module(&mut module);
+        let func = module.find_function_by_name("renamed_main").unwrap();
+        assert_eq!(
+            format!("{:?}", module.functions()[func]),
+            "define external dso_local default i32 @renamed_main() {\n0:\n    ret i32 0\n}\n"
+        );
+    }
+
+    #[test]
+    fn module_pipeline() {
+        let mut module = parse_assembly(
+            r#"
+define internal i32 @one() {
+  ret i32 1
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @one()
+  %2 = add i32 %1, 2
+  ret i32 0
+}"#,
+        )
+        .unwrap();
+
+        let registry = PassRegistry::with_default_module_passes();
+        assert!(registry.contains("inline") && registry.contains("dce"));
+        assert!(!registry.contains("domtree"));
+        let mut pm = registry
+            .build_pipeline("inline,dce,compact,globaldce")
+            .unwrap();
+        pm.run_on(&mut module);
+        assert!(module.find_function_by_name("one").is_none());
+        let main = module.find_function_by_name("main").unwrap();
+        assert_eq!(
+            format!("{:?}", module.functions()[main]),
+            "define external dso_local default i32 @main() {\n0:\n    br label %1\n1:\n    br label %2\n2:\n    ret i32 0\n}\n"
+        );
+    }
+
+    #[test]
+    fn unknown_pass() {
+        let registry = PassRegistry::with_default_passes();
+        assert_eq!(
+            registry.build_pipeline("dce,foo").err(),
+            Some(UnknownPassError("foo".to_string()))
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! Optimization remarks, after LLVM's `-pass-remarks` and `-fsave-optimization-record`.
+//!
+//! A pass given a [`Remarks`] collector, e.g. with
+//! [`InlinePass::with_remarks`](super::transform::inline::InlinePass::with_remarks),
+//! records what it did or could not do there:
+//!
+//! ```ignore
+//! let remarks = Remarks::new();
+//! InlinePass::new().with_remarks(remarks.clone()).run_on_module(&mut module);
+//! for remark in remarks.take() {
+//!     println!("{}", remark); // main: foo inlined into main
+//! }
+//! ```
+//!
+//! A remark is made of arguments, each a key and a value, whose values read
+//! as a sentence. Remarks serialize to the YAML of LLVM's optimization
+//! records with [`Remarks::to_yaml`], or to JSON with [`Remarks::to_json`].
+
+use crate::prelude::*;
+use alloc::{collections::BTreeMap, rc::Rc};
+use core::{cell::RefCell, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemarkKind {
+    /// An optimization was applied.
+    Passed,
+    /// An optimization was not applied.
+    Missed,
+    /// Something found out while analyzing.
+    Analysis,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remark {
+    pub kind: RemarkKind,
+    /// The name of the pass, as registered.
+    pub pass: &'static str,
+    /// Identifies the remark within the pass.
+    pub name: &'static str,
+    pub function: String,
+    pub args: Vec<(&'static str, String)>,
+}
+
+/// A collector of remarks, shared by the passes it is given to.
+#[derive(Debug, Clone, Default)]
+pub struct Remarks(Rc<RefCell<Vec<Remark>>>);
+
+impl Remark {
+    pub fn new<F: Into<String>>(
+        kind: RemarkKind,
+        pass: &'static str,
+        name: &'static str,
+        function: F,
+    ) -> Self {
+        Self {
+            kind,
+            pass,
+            name,
+            function: function.into(),
+            args: vec![],
+        }
+    }
+
+    pub fn passed<F: Into<String>>(pass: &'static str, name: &'static str, function: F) -> Self {
+        Self::new(RemarkKind::Passed, pass, name, function)
+    }
+
+    pub fn missed<F: Into<String>>(pass: &'static str, name: &'static str, function: F) -> Self {
+        Self::new(RemarkKind::Missed, pass, name, function)
+    }
+
+    pub fn analysis<F: Into<String>>(pass: &'static str, name: &'static str, function: F) -> Self {
+        Self::new(RemarkKind::Analysis, pass, name, function)
+    }
+
+    /// Appends an argument.
+    pub fn arg<V: ToString>(mut self, key: &'static str, val: V) -> Self {
+        self.args.push((key, val.to_string()));
+        self
+    }
+
+    /// Appends text, i.e. an argument keyed `String`.
+    pub fn string<S: Into<String>>(self, s: S) -> Self {
+        self.arg("String", s.into())
+    }
+
+    /// Returns the values of the arguments, concatenated.
+    pub fn message(&self) -> String {
+        self.args.iter().map(|(_, val)| val.as_str()).collect()
+    }
+}
+
+impl Remarks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn emit(&self, remark: Remark) {
+        self.0.borrow_mut().push(remark)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    /// Returns the remarks emitted so far, in order, and forgets them.
+    pub fn take(&self) -> Vec<Remark> {
+        self.0.take()
+    }
+
+    /// Returns a copy of the remarks emitted so far, by function.
+    pub fn by_function(&self) -> BTreeMap<String, Vec<Remark>> {
+        let mut map: BTreeMap<String, Vec<Remark>> = BTreeMap::new();
+        for remark in self.0.borrow().iter() {
+            map.entry(remark.function.clone())
+                .or_default()
+                .push(remark.clone());
+        }
+        map
+    }
+
+    /// Returns the remarks as a stream of YAML documents, one per remark.
+    pub fn to_yaml(&self) -> String {
+        let mut yaml = String::new();
+        for remark in self.0.borrow().iter() {
+            yaml.push_str(&format!("--- !{:?}\n", remark.kind));
+            yaml.push_str(&format!("Pass:            {}\n", remark.pass));
+            yaml.push_str(&format!("Name:            {}\n", remark.name));
+            yaml.push_str(&format!(
+                "Function:        {}\n",
+                yaml_str(&remark.function)
+            ));
+            if !remark.args.is_empty() {
+                yaml.push_str("Args:\n");
+                for (key, val) in &remark.args {
+                    yaml.push_str(&format!("  - {}: {}\n", key, yaml_str(val)));
+                }
+            }
+            yaml.push_str("...\n");
+        }
+        yaml
+    }
+
+    /// Returns the remarks as a JSON array.
+    pub fn to_json(&self) -> String {
+        let remarks: Vec<String> = self
+            .0
+            .borrow()
+            .iter()
+            .map(|remark| {
+                let args: Vec<String> = remark
+                    .args
+                    .iter()
+                    .map(|(key, val)| format!("{{{}: {}}}", json_str(key), json_str(val)))
+                    .collect();
+                format!(
+                    "{{\"kind\": {}, \"pass\": {}, \"name\": {}, \"function\": {}, \"args\": [{}]}}",
+                    json_str(&format!("{:?}", remark.kind)),
+                    json_str(remark.pass),
+                    json_str(remark.name),
+                    json_str(&remark.function),
+                    args.join(", ")
+                )
+            })
+            .collect();
+        format!("[{}]", remarks.join(",\n "))
+    }
+}
+
+/// Quotes `s` unless it is a plain YAML scalar.
+fn yaml_str(s: &str) -> String {
+    let plain = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_.$-".contains(c))
+        && !s.starts_with('-');
+    if plain {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "''"))
+    }
+}
+
+fn json_str(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+impl fmt::Display for Remark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.function, self.message())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn serialize() {
+        let remarks = Remarks::new();
+        remarks.emit(
+            Remark::passed("inline", "Inlined", "main")
+                .arg("Callee", "foo")
+                .string(" inlined into ")
+                .arg("Caller", "main"),
+        );
+        remarks.emit(
+            Remark::missed("loop-unroll", "TripCount", "f")
+                .string("could not unroll: \"n\" unknown"),
+        );
+        assert_eq!(remarks.len(), 2);
+        assert_eq!(
+            remarks.by_function()["main"][0].to_string(),
+            "main: foo inlined into main"
+        );
+        assert_eq!(
+            remarks.to_yaml(),
+            "--- !Passed\n\
+             Pass:            inline\n\
+             Name:            Inlined\n\
+             Function:        main\n\
+             Args:\n  \
+             - Callee: foo\n  \
+             - String: ' inlined into '\n  \
+             - Caller: main\n\
+             ...\n\
+             --- !Missed\n\
+             Pass:            loop-unroll\n\
+             Name:            TripCount\n\
+             Function:        f\n\
+             Args:\n  \
+             - String: 'could not unroll: \"n\" unknown'\n\
+             ...\n"
+        );
+        assert_eq!(
+            remarks.to_json(),
+            "[{\"kind\": \"Passed\", \"pass\": \"inline\", \"name\": \"Inlined\", \"function\": \"main\", \
+             \"args\": [{\"Callee\": \"foo\"}, {\"String\": \" inlined into \"}, {\"Caller\": \"main\"}]},\n \
+             {\"kind\": \"Missed\", \"pass\": \"loop-unroll\", \"name\": \"TripCount\", \"function\": \"f\", \
+             \"args\": [{\"String\": \"could not unroll: \\\"n\\\" unknown\"}]}]"
+        );
+        assert_eq!(remarks.take().len(), 2);
+        assert!(remarks.is_empty());
+    }
+}
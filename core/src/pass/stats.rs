@@ -0,0 +1,192 @@
+//! Pass statistics, after LLVM's `STATISTIC` and `-stats`.
+//!
+//! A pass declares its counters as statics and bumps them as it goes:
+//!
+//! ```ignore
+//! static NUM_INLINED: Statistic =
+//!     Statistic::new("inline", "NumInlined", "Number of functions inlined");
+//!
+//! NUM_INLINED.inc();
+//! ```
+//!
+//! A counter is registered the first time it is bumped. [`report`] lists the
+//! registered counters, and [`PassManager::report`](super::PassManager::report)
+//! adds the time spent in each pass when timing is enabled with
+//! [`PassManager::time_passes`](super::PassManager::time_passes).
+//!
+//! Counters are global to the process, and are shared by all modules and
+//! threads.
+
+use crate::prelude::*;
+use crate::sync::Mutex;
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+/// A named counter.
+pub struct Statistic {
+    /// The name of the pass counting, as registered.
+    pub pass: &'static str,
+    pub name: &'static str,
+    pub desc: &'static str,
+    /// A `usize` rather than a `u64`, for targets without 64-bit atomics.
+    value: AtomicUsize,
+    registered: AtomicBool,
+}
+
+static STATISTICS: Mutex<Vec<&'static Statistic>> = Mutex::new(Vec::new());
+
+impl Statistic {
+    pub const fn new(pass: &'static str, name: &'static str, desc: &'static str) -> Self {
+        Self {
+            pass,
+            name,
+            desc,
+            value: AtomicUsize::new(0),
+            registered: AtomicBool::new(false),
+        }
+    }
+
+    pub fn inc(&'static self) {
+        self.add(1)
+    }
+
+    pub fn add(&'static self, n: u64) {
+        if n == 0 {
+            return;
+        }
+        self.value.fetch_add(n as usize, Ordering::Relaxed);
+        if !self.registered.swap(true, Ordering::AcqRel) {
+            STATISTICS.lock().push(self);
+        }
+    }
+
+    pub fn get(&self) -> u64 {
+        self.value.load(Ordering::Relaxed) as u64
+    }
+}
+
+/// Returns the registered counters, sorted by pass and name.
+pub fn statistics() -> Vec<&'static Statistic> {
+    let mut stats = STATISTICS.lock().clone();
+    stats.sort_by_key(|stat| (stat.pass, stat.name));
+    stats
+}
+
+/// Sets the registered counters back to zero.
+pub fn reset() {
+    for stat in STATISTICS.lock().iter() {
+        stat.value.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Returns the nonzero counters in the format of LLVM's `-stats`, or an empty
+/// string if there are none.
+pub fn report() -> String {
+    let stats: Vec<_> = statistics()
+        .into_iter()
+        .filter(|stat| stat.get() > 0)
+        .collect();
+    if stats.is_empty() {
+        return String::new();
+    }
+    let value_width = stats
+        .iter()
+        .map(|s| s.get().to_string().len())
+        .max()
+        .unwrap();
+    let pass_width = stats.iter().map(|s| s.pass.len()).max().unwrap();
+    let mut report = banner("Statistics Collected");
+    for stat in stats {
+        writeln!(
+            report,
+            "{:>vw$} {:<pw$} - {}",
+            stat.get(),
+            stat.pass,
+            stat.desc,
+            vw = value_width,
+            pw = pass_width
+        )
+        .unwrap();
+    }
+    report
+}
+
+/// Returns the time spent in each pass, slowest first, in the format of
+/// LLVM's `-time-passes`.
+pub(super) fn timing_report(timings: &[(&'static str, Duration)]) -> String {
+    let total: Duration = timings.iter().map(|(_, time)| *time).sum();
+    let mut timings = timings.to_vec();
+    timings.sort_by_key(|&(_, time)| core::cmp::Reverse(time));
+    let percent = |time: Duration| {
+        if total.is_zero() {
+            0.0
+        } else {
+            time.as_secs_f64() / total.as_secs_f64() * 100.0
+        }
+    };
+    let mut report = banner("Pass execution timing report");
+    writeln!(
+        report,
+        "  Total Execution Time: {:.4} seconds\n",
+        total.as_secs_f64()
+    )
+    .unwrap();
+    writeln!(report, "   ---Wall Time---  --- Name ---").unwrap();
+    for (name, time) in timings {
+        writeln!(
+            report,
+            "   {:.4} ({:5.1}%)  {}",
+            time.as_secs_f64(),
+            percent(time),
+            name
+        )
+        .unwrap();
+    }
+    writeln!(report, "   {:.4} (100.0%)  Total", total.as_secs_f64()).unwrap();
+    report
+}
+
+fn banner(title: &str) -> String {
+    let rule = "===-------------------------------------------------------------------------===";
+    format!(
+        "{}\n{:^w$}\n{}\n\n",
+        rule,
+        format!("... {} ...", title),
+        rule,
+        w = rule.len()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static NUM_TESTED: Statistic = Statistic::new("stats-test", "NumTested", "Number of tests");
+
+    #[test]
+    fn count_and_report() {
+        NUM_TESTED.inc();
+        NUM_TESTED.add(2);
+        assert_eq!(NUM_TESTED.get(), 3);
+        assert!(statistics()
+            .iter()
+            .any(|stat| core::ptr::eq(*stat, &NUM_TESTED)));
+        assert!(report()
+            .lines()
+            .any(|line| line.trim_start().starts_with("3 stats-test")
+                && line.ends_with(" - Number of tests")));
+
+        let report = timing_report(&[
+            ("dce", Duration::from_millis(10)),
+            ("inline", Duration::from_millis(30)),
+        ]);
+        assert!(report.contains("... Pass execution timing report ..."));
+        assert!(report.contains("Total Execution Time: 0.0400 seconds"));
+        let inline = report.find("0.0300 ( 75.0%)  inline").unwrap();
+        let dce = report.find("0.0100 ( 25.0%)  dce").unwrap();
+        assert!(inline < dce);
+    }
+}
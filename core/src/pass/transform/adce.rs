@@ -0,0 +1,294 @@
+//! Aggressive dead code elimination.
+//!
+//! Unlike [`DCEPass`](super::dce::DCEPass), which removes instructions without
+//! users, everything starts out dead here and only instructions needed by an
+//! instruction with side effects are marked live. A conditional branch is live
+//! only if a live block is control dependent on it; dead ones are replaced with
+//! a branch to the nearest post-dominator, and the blocks left unreachable are
+//! removed. Branches forming loops are always kept so that a loop that might
+//! not terminate is never removed.
+
+use crate::collections::FxHashSet;
+use crate::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{InstructionId, Opcode, Operand, Phi},
+        Function,
+    },
+    module::Module,
+    value::Value,
+};
+use crate::pass::{analysis::post_dom_tree::PostDominatorTree, PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct ADCEPass;
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, function) in module.functions_mut().iter_mut() {
+        run_on_function(function);
+    }
+}
+
+impl TransformPass<Function> for ADCEPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+struct Liveness<'a> {
+    func: &'a Function,
+    post_dom: PostDominatorTree,
+    insts: FxHashSet<InstructionId>,
+    blocks: FxHashSet<BasicBlockId>,
+    worklist: Vec<InstructionId>,
+}
+
+/// Removes dead code from `func` and returns the number of instructions removed.
+pub fn run_on_function(func: &mut Function) -> usize {
+    if func.is_prototype() {
+        return 0;
+    }
+
+    let (dead_insts, dead_branches) = {
+        let mut liveness = Liveness::new(func);
+        liveness.compute();
+        let dead_insts: Vec<InstructionId> = func
+            .layout
+            .block_iter()
+            .flat_map(|block| func.layout.inst_iter(block))
+            .filter(|inst| {
+                !liveness.insts.contains(inst) && !func.data.inst_ref(*inst).opcode.is_terminator()
+            })
+            .collect();
+        let dead_branches: Vec<(BasicBlockId, BasicBlockId)> = func
+            .layout
+            .block_iter()
+            .filter_map(|block| {
+                let term = (*func.layout.block_node(block).last_inst())?;
+                if func.data.inst_ref(term).opcode != Opcode::CondBr
+                    || liveness.insts.contains(&term)
+                {
+                    return None;
+                }
+                Some((block, liveness.post_dom.immediate_post_dominator(block)?))
+            })
+            .collect();
+        (dead_insts, dead_branches)
+    };
+
+    for &inst in &dead_insts {
+        func.remove_inst(inst);
+    }
+    for (block, target) in dead_branches {
+        func.replace_terminator_with_br(block, target);
+    }
+    func.remove_unreachable_blocks();
+
+    dead_insts.len()
+}
+
+impl<'a> Liveness<'a> {
+    fn new(func: &'a Function) -> Self {
+        Self {
+            func,
+            post_dom: PostDominatorTree::new(func),
+            insts: FxHashSet::default(),
+            blocks: FxHashSet::default(),
+            worklist: vec![],
+        }
+    }
+
+    fn compute(&mut self) {
+        let func = self.func;
+        let entry = func.entry_block().unwrap();
+        self.mark_block_live(entry);
+
+        for block in func.layout.block_iter() {
+            for inst in func.layout.inst_iter(block) {
+                if is_always_live(func.data.inst_ref(inst).opcode) {
+                    self.mark_live(inst);
+                }
+            }
+        }
+        self.mark_loops_live(entry);
+
+        loop {
+            self.propagate();
+            // A dead conditional branch is rewritten to jump to its immediate
+            // post-dominator. One without, e.g. in an infinite loop, has to stay
+            // as it is, and so do its operands.
+            let kept: Vec<InstructionId> = func
+                .layout
+                .block_iter()
+                .filter(|&block| self.post_dom.immediate_post_dominator(block).is_none())
+                .filter_map(|block| *func.layout.block_node(block).last_inst())
+                .filter(|term| {
+                    func.data.inst_ref(*term).opcode == Opcode::CondBr && !self.insts.contains(term)
+                })
+                .collect();
+            if kept.is_empty() {
+                break;
+            }
+            for term in kept {
+                self.mark_live(term);
+            }
+        }
+    }
+
+    fn propagate(&mut self) {
+        let func = self.func;
+        while let Some(inst) = self.worklist.pop() {
+            let inst = func.data.inst_ref(inst);
+            self.mark_block_live(inst.parent);
+            for &arg in inst.operand.args() {
+                if let Value::Instruction(arg) = func.data.value_ref(arg) {
+                    self.mark_live(*arg);
+                }
+            }
+            if let Operand::Phi(Phi { blocks, .. }) = &inst.operand {
+                for &block in blocks {
+                    self.mark_block_live(block);
+                    self.mark_terminator_live(block);
+                }
+            }
+        }
+    }
+
+    /// Keeps the branches of every back edge found by a depth first search.
+    fn mark_loops_live(&mut self, entry: BasicBlockId) {
+        let mut on_stack = FxHashSet::default();
+        let mut visited = FxHashSet::default();
+        let mut stack = vec![(entry, false)];
+        while let Some((block, exiting)) = stack.pop() {
+            if exiting {
+                on_stack.remove(&block);
+                continue;
+            }
+            if !visited.insert(block) {
+                continue;
+            }
+            on_stack.insert(block);
+            stack.push((block, true));
+            for &succ in self.func.data.basic_blocks[block].succs() {
+                if on_stack.contains(&succ) {
+                    self.mark_terminator_live(block);
+                } else if !visited.contains(&succ) {
+                    stack.push((succ, false));
+                }
+            }
+        }
+    }
+
+    fn mark_live(&mut self, inst: InstructionId) {
+        if self.insts.insert(inst) {
+            self.worklist.push(inst);
+        }
+    }
+
+    fn mark_terminator_live(&mut self, block: BasicBlockId) {
+        if let Some(term) = *self.func.layout.block_node(block).last_inst() {
+            self.mark_live(term);
+        }
+    }
+
+    fn mark_block_live(&mut self, block: BasicBlockId) {
+        if !self.blocks.insert(block) {
+            return;
+        }
+        // Unconditional branches of live blocks are kept as they are.
+        if let Some(term) = *self.func.layout.block_node(block).last_inst() {
+            if self.func.data.inst_ref(term).opcode == Opcode::Br {
+                self.mark_live(term);
+            }
+        }
+        let deps: Vec<BasicBlockId> = self.post_dom.control_dependences_of(block).collect();
+        for dep in deps {
+            self.mark_terminator_live(dep);
+        }
+    }
+}
+
+fn is_always_live(opcode: Opcode) -> bool {
+    // Branches are only live if something depends on the path taken.
+    opcode.is_alloca()
+        || opcode.may_write_memory()
+        || opcode == Opcode::LandingPad
+        || (opcode.is_terminator() && !opcode.is_branch())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pass::transform::run_on_f;
+
+    #[test]
+    fn remove_dead_branch() {
+        let (removed, printed) = run_on_f(
+            super::run_on_function,
+            r#"
+define i32 @f(i32 %0) {
+  %2 = icmp eq i32 %0, 0
+  br i1 %2, label %3, label %5
+3:
+  %4 = add i32 %0, 1
+  br label %6
+5:
+  br label %6
+6:
+  %7 = phi i32 [ %4, %3 ], [ 0, %5 ]
+  ret i32 %0
+}"#,
+        );
+        assert_eq!(removed, 3);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn keep_branch_without_post_dominator() {
+        let (removed, printed) = run_on_f(
+            super::run_on_function,
+            r#"
+define void @f(i32 %0) {
+  br label %2
+2:
+  %3 = icmp eq i32 %0, 0
+  br i1 %3, label %4, label %5
+4:
+  br label %2
+5:
+  br label %2
+}"#,
+        );
+        assert_eq!(removed, 0);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn keep_live_branch_and_loop() {
+        let source = r#"
+define i32 @f(i32 %0) {
+  br label %2
+2:
+  %3 = phi i32 [ 0, %1 ], [ %5, %2 ]
+  %4 = phi i32 [ 0, %1 ], [ %6, %2 ]
+  %5 = add i32 %3, 1
+  %6 = add i32 %4, 2
+  %7 = icmp slt i32 %5, %0
+  br i1 %7, label %2, label %8
+8:
+  %9 = icmp eq i32 %0, 0
+  br i1 %9, label %10, label %11
+10:
+  ret i32 1
+11:
+  ret i32 %5
+}"#;
+        let (removed, printed) = run_on_f(super::run_on_function, source);
+        assert_eq!(removed, 2);
+        insta::assert_snapshot!(printed);
+    }
+}
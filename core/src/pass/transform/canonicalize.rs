@@ -0,0 +1,136 @@
+//! Operand canonicalization.
+//!
+//! Reorders the operands of commutative binary operations and `icmp` so that
+//! constants come last and the remaining values are ordered as arguments (by
+//! index) first, then instructions (by position in the layout). Equivalent
+//! expressions then look the same, which helps value numbering, instruction
+//! selection patterns and structural hashing.
+
+use crate::collections::FxHashMap;
+use crate::ir::{
+    function::{
+        instruction::{ICmp, InstructionId, IntBinary, Operand},
+        Function,
+    },
+    module::Module,
+    value::{Value, ValueId},
+};
+use crate::pass::{PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct CanonicalizePass;
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, function) in module.functions_mut().iter_mut() {
+        run_on_function(function);
+    }
+}
+
+impl TransformPass<Function> for CanonicalizePass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::all()
+    }
+}
+
+/// Canonicalizes `func` and returns the number of instructions whose operands were swapped.
+pub fn run_on_function(func: &mut Function) -> usize {
+    let order: FxHashMap<InstructionId, usize> = func
+        .layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .enumerate()
+        .map(|(i, inst)| (inst, i))
+        .collect();
+    let rank = |func: &Function, val: ValueId| match func.data.value_ref(val) {
+        Value::Argument(i) => (0, *i),
+        Value::Instruction(inst) => (1, order.get(inst).copied().unwrap_or(usize::MAX)),
+        Value::InlineAsm(_) => (2, 0),
+        Value::Constant(_) => (3, 0),
+    };
+
+    let mut swapped = 0;
+    for &inst_id in order.keys() {
+        let inst = func.data.inst_ref(inst_id);
+        let args = match &inst.operand {
+            Operand::IntBinary(IntBinary { args, .. }) if inst.opcode.is_commutative() => args,
+            Operand::ICmp(ICmp { args, .. }) => args,
+            _ => continue,
+        };
+        if rank(func, args[0]) <= rank(func, args[1]) {
+            continue;
+        }
+        match &mut func.data.inst_ref_mut(inst_id).operand {
+            Operand::IntBinary(IntBinary { args, .. }) => args.swap(0, 1),
+            Operand::ICmp(ICmp { args, cond, .. }) => {
+                args.swap(0, 1);
+                *cond = cond.swapped();
+            }
+            _ => unreachable!(),
+        }
+        swapped += 1;
+    }
+    swapped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::{function::hash::structurally_equal, module::parse_assembly};
+
+    #[test]
+    fn canonicalize() {
+        let mut module = parse_assembly(
+            r#"
+define i32 @f(i32 %0, i32 %1) {
+  %3 = add i32 1, %0
+  %4 = mul i32 %3, %1
+  %5 = sub i32 1, %4
+  %6 = icmp slt i32 10, %5
+  %7 = and i32 %1, %0
+  br i1 %6, label %8, label %9
+8:
+  ret i32 %7
+9:
+  ret i32 %5
+}"#,
+        )
+        .unwrap();
+        let id = module.find_function_by_name("f").unwrap();
+        assert_eq!(run_on_function(&mut module.functions_mut()[id]), 4);
+        insta::assert_debug_snapshot!(module.functions()[id]);
+    }
+
+    #[test]
+    fn canonical_forms_are_structurally_equal() {
+        let mut module = parse_assembly(
+            r#"
+define i32 @f(i32 %0, i32 %1) {
+  %3 = mul i32 %1, %0
+  %4 = add i32 %3, 2
+  %5 = or i32 %4, %0
+  ret i32 %5
+}
+
+define i32 @f2(i32 %0, i32 %1) {
+  %3 = mul i32 %0, %1
+  %4 = add i32 2, %3
+  %5 = or i32 %0, %4
+  ret i32 %5
+}"#,
+        )
+        .unwrap();
+        run_on_module(&mut module);
+        let f = module.find_function_by_name("f").unwrap();
+        let f2 = module.find_function_by_name("f2").unwrap();
+        module.functions_mut()[f2].name = "f".to_string();
+        assert!(structurally_equal(
+            &module.functions()[f],
+            &module.functions()[f2]
+        ));
+    }
+}
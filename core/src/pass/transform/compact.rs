@@ -0,0 +1,104 @@
+//! Compacting the arenas of functions.
+//!
+//! Arenas never free what they allocate, so instructions removed from the
+//! layout, and the blocks and values they leave unused, stay in `Data` for
+//! as long as the function lives. Compaction copies what the layout still
+//! refers to into fresh arenas and drops the rest, which renumbers blocks,
+//! instructions and values. Ids taken before compacting must be mapped
+//! through the returned [`ValueMap`], and analyses must be recomputed.
+
+use crate::ir::{
+    function::{
+        clone::{clone_into, copy_names, ValueMap},
+        Function,
+    },
+    module::Module,
+};
+use crate::pass::{stats::Statistic, PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::{any::Any, mem};
+
+pub struct CompactPass;
+
+static NUM_FREED: Statistic = Statistic::new(
+    "compact",
+    "NumFreed",
+    "Number of removed instructions freed",
+);
+
+impl TransformPass<Function> for CompactPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    /// Every id changes.
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, func) in module.functions_mut().iter_mut() {
+        run_on_function(func);
+    }
+}
+
+/// Compacts the arenas of `func` and returns what its blocks and
+/// instructions became.
+pub fn run_on_function(func: &mut Function) -> ValueMap {
+    let mut vmap = ValueMap::new();
+    if func.is_prototype() {
+        return vmap;
+    }
+
+    let mut compacted = Function::new(
+        &func.name,
+        func.result_ty,
+        vec![],
+        func.is_var_arg,
+        func.types.clone(),
+    );
+    clone_into(&mut compacted, func, &mut vmap, None);
+    copy_names(&mut compacted, func, &vmap);
+
+    let num_insts = func.data.instructions.len();
+    func.data = mem::take(&mut compacted.data);
+    func.layout = mem::take(&mut compacted.layout);
+    NUM_FREED.add((num_insts - func.data.instructions.len()) as u64);
+    vmap
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn compact() {
+        let mut module = parse_assembly(
+            r#"
+define i32 @f(i32 %x) {
+entry:
+  %a = add i32 %x, 1
+  %b = mul i32 %a, 2
+  %c = sub i32 %x, 3
+  ret i32 %c
+}"#,
+        )
+        .unwrap();
+        let func_id = module.find_function_by_name("f").unwrap();
+        let func = &mut module.functions_mut()[func_id];
+        let entry = func.layout.get_entry_block().unwrap();
+        let insts: Vec<_> = func.layout.inst_iter(entry).collect();
+        func.erase_instruction(insts[1]).unwrap();
+        func.erase_instruction(insts[0]).unwrap();
+        let printed = format!("{:?}", func);
+
+        let vmap = run_on_function(func);
+        assert_eq!(func.data.instructions.len(), 2);
+        assert_eq!(format!("{:?}", func), printed);
+        assert_eq!(vmap.inst(insts[0]), None);
+        let c = vmap.inst(insts[2]).unwrap();
+        assert_eq!(func.data.inst_ref(c).dest, Some("c".into()));
+    }
+}
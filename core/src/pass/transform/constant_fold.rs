@@ -0,0 +1,99 @@
+//! Constant folding.
+//!
+//! Every instruction whose arguments are all constants is replaced with the
+//! constant it computes, as given by
+//! [`fold_constant`](crate::ir::value::fold::fold_constant). The users of a
+//! folded instruction are then revisited, so chains of constant operations
+//! are folded at once. Unlike [`sccp`](super::sccp), branches are left alone.
+
+use crate::ir::{
+    function::{basic_block::BasicBlock, instruction::InstructionId, Function},
+    module::Module,
+    value::Value,
+};
+use crate::pass::{analysis::dom_tree::DominatorTree, PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct ConstantFoldPass;
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, function) in module.functions_mut().iter_mut() {
+        run_on_function(function);
+    }
+}
+
+impl TransformPass<Function> for ConstantFoldPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none().preserve::<DominatorTree<BasicBlock>>()
+    }
+}
+
+/// Folds the instructions of `func` and returns how many were folded.
+pub fn run_on_function(func: &mut Function) -> usize {
+    if func.is_prototype() {
+        return 0;
+    }
+
+    let mut worklist: Vec<InstructionId> = func
+        .layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .collect();
+    worklist.reverse();
+    let mut folded = 0;
+
+    while let Some(id) = worklist.pop() {
+        // Folded instructions are gone from the layout.
+        if func.layout.block_of(id).is_none() {
+            continue;
+        }
+        let Some(konst) = func.data.inst_ref(id).fold_consts(&func.data, &func.types) else {
+            continue;
+        };
+        worklist.extend(func.data.users_of(id).iter().copied());
+        let val = func.data.create_value(Value::Constant(konst));
+        func.replace_all_uses_with(id, val);
+        func.erase_instruction(id);
+        folded += 1;
+    }
+
+    folded
+}
+
+#[cfg(test)]
+mod test {
+    use super::run_on_function;
+    use crate::pass::transform::run_on_f;
+
+    #[test]
+    fn fold_chains() {
+        let (folded, printed) = run_on_f(
+            run_on_function,
+            r#"
+@g = global [4 x i32] zeroinitializer
+
+define i32 @f(i32 %0) {
+  %2 = add i32 3, 4
+  %3 = shl i32 %2, 2
+  %4 = icmp ult i32 -1, %3
+  %5 = zext i1 %4 to i32
+  %6 = sdiv i32 %3, 0
+  %7 = add i32 %0, %5
+  %8 = getelementptr inbounds [4 x i32], [4 x i32]* @g, i64 0, i64 2
+  %9 = bitcast i32* %8 to i8*
+  %10 = load i32, i32* %8
+  %11 = trunc i64 4294967297 to i32
+  %12 = add i32 %7, %11
+  ret i32 %12
+}"#,
+        );
+        // Everything but the division by zero and the uses of `%0`.
+        assert_eq!(folded, 7);
+        insta::assert_snapshot!(printed);
+    }
+}
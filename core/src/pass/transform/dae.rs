@@ -0,0 +1,196 @@
+//! Dead argument elimination.
+//!
+//! For `internal` and `private` functions whose address is not taken, the
+//! parameters never used in the body are removed, and so is the return value
+//! when no call site uses it, the function then returning `void`. Every
+//! call site, found through the [`CallGraph`], is rewritten accordingly.
+
+use super::global_dce::is_local;
+use crate::collections::FxHashSet;
+use crate::ir::{
+    function::{
+        instruction::{Call, Invoke, Operand, Ret},
+        Function, FunctionId,
+    },
+    module::Module,
+    types::VOID,
+    value::Value,
+};
+use crate::pass::{
+    analysis::call_graph::{CallGraph, CallSite},
+    PreservedAnalyses, TransformPass,
+};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct DeadArgElimPass;
+
+/// The number of parameters and return values removed by [`DeadArgElimPass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadArgElimResult(pub usize);
+
+impl TransformPass<Module> for DeadArgElimPass {
+    fn run_on(&self, module: &mut Module, result: &mut Box<dyn Any>) {
+        *result = Box::new(DeadArgElimResult(run_on_module(module)))
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+/// Removes dead parameters and return values and returns how many were removed.
+pub fn run_on_module(module: &mut Module) -> usize {
+    let graph = CallGraph::new(module);
+    let ids: Vec<FunctionId> = module.functions.iter().map(|(id, _)| id).collect();
+    let mut removed = 0;
+
+    for id in ids {
+        let func = &module.functions[id];
+        if !is_local(func.linkage)
+            || func.is_prototype()
+            || func.is_var_arg()
+            || graph.is_address_taken(id)
+        {
+            continue;
+        }
+        let call_sites = graph.call_sites(id);
+
+        let used = used_params(func);
+        let dead_params: Vec<usize> = (0..func.params.len())
+            .filter(|i| !used.contains(i))
+            .collect();
+        let dead_ret = !func.result_ty.is_void()
+            && call_sites.iter().all(|&CallSite { caller, inst }| {
+                module.functions[caller].data.users_of(inst).is_empty()
+            });
+        if dead_params.is_empty() && !dead_ret {
+            continue;
+        }
+
+        let func = &mut module.functions[id];
+        remove_params(func, &dead_params);
+        if dead_ret {
+            remove_return_value(func);
+        }
+        for &CallSite { caller, inst } in call_sites {
+            let caller = &mut module.functions[caller];
+            caller.data.remove_uses(inst);
+            let (args, tys, param_attrs) = match &mut caller.data.inst_ref_mut(inst).operand {
+                Operand::Call(Call {
+                    args,
+                    tys,
+                    param_attrs,
+                    ..
+                })
+                | Operand::Invoke(Invoke {
+                    args,
+                    tys,
+                    param_attrs,
+                    ..
+                }) => (args, tys, param_attrs),
+                _ => unreachable!(),
+            };
+            for &i in dead_params.iter().rev() {
+                args.remove(i + 1);
+                tys.remove(i + 1);
+                if i < param_attrs.len() {
+                    param_attrs.remove(i);
+                }
+            }
+            if dead_ret {
+                tys[0] = VOID;
+                caller.data.inst_ref_mut(inst).dest = None;
+            }
+            caller.data.validate_inst_uses(inst);
+        }
+        removed += dead_params.len() + dead_ret as usize;
+    }
+
+    removed
+}
+
+fn used_params(func: &Function) -> FxHashSet<usize> {
+    func.layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .flat_map(|inst| func.data.inst_ref(inst).operand.args())
+        .filter_map(|&arg| match func.data.value_ref(arg) {
+            Value::Argument(i) => Some(*i),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Removes the parameters `dead`, in ascending order, renumbering the others.
+fn remove_params(func: &mut Function, dead: &[usize]) {
+    for &i in dead.iter().rev() {
+        func.params.remove(i);
+    }
+    for (_, val) in func.data.values.iter_mut() {
+        if let Value::Argument(i) = val {
+            *i -= dead.iter().filter(|&&d| d < *i).count();
+        }
+    }
+}
+
+fn remove_return_value(func: &mut Function) {
+    func.result_ty = VOID;
+    let rets: Vec<_> = func
+        .layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .filter(|&inst| matches!(func.data.inst_ref(inst).operand, Operand::Ret(_)))
+        .collect();
+    for ret in rets {
+        func.data.remove_uses(ret);
+        func.data.inst_ref_mut(ret).operand = Operand::Ret(Ret {
+            ty: VOID,
+            val: None,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn dead_args_and_return_values() {
+        let mut module = parse_assembly(
+            r#"
+declare void @use(i32)
+
+define internal i32 @f(i32 %0, i32 %1, i32 %2) {
+  call void @use(i32 %1)
+  ret i32 %1
+}
+
+define internal i32 @g(i32 %0) {
+  %2 = add i32 %0, 1
+  ret i32 %2
+}
+
+define i32 @exported(i32 %0) {
+  ret i32 0
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @f(i32 1, i32 2, i32 3)
+  %2 = call i32 @g(i32 4)
+  %3 = call i32 @exported(i32 5)
+  ret i32 %2
+}"#,
+        )
+        .unwrap();
+        // Two parameters and the return value of `f`.
+        assert_eq!(run_on_module(&mut module), 3);
+        let printed = module
+            .functions()
+            .iter()
+            .map(|(_, f)| format!("{:?}", f))
+            .collect::<String>();
+        insta::assert_snapshot!(printed);
+    }
+}
@@ -1,12 +1,18 @@
 use crate::ir::{
-    function::{
-        data::Data,
-        instruction::{InstructionId, Opcode},
-        Function,
-    },
+    function::{basic_block::BasicBlock, data::Data, instruction::InstructionId, Function},
     module::Module,
     value::Value,
 };
+use crate::pass::{
+    analysis::dom_tree::DominatorTree, stats::Statistic, PreservedAnalyses, TransformPass,
+};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct DCEPass;
+
+static NUM_REMOVED: Statistic =
+    Statistic::new("dce", "NumRemoved", "Number of instructions removed");
 
 pub fn run_on_module(module: &mut Module) {
     for (_, function) in module.functions_mut().iter_mut() {
@@ -14,6 +20,17 @@ pub fn run_on_module(module: &mut Module) {
     }
 }
 
+impl TransformPass<Function> for DCEPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func)
+    }
+
+    /// Only instructions other than terminators are changed, so the CFG is kept.
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none().preserve::<DominatorTree<BasicBlock>>()
+    }
+}
+
 pub fn run_on_function(func: &mut Function) {
     let mut worklist = vec![];
     let mut elimination_list = vec![];
@@ -26,12 +43,14 @@ pub fn run_on_function(func: &mut Function) {
 
     while let Some(inst) = elimination_list.pop() {
         func.remove_inst(inst).unwrap();
+        NUM_REMOVED.inc();
     }
 
     while let Some(inst) = worklist.pop() {
         check_if_elimination_possible(&func.data, inst, &mut elimination_list, &mut worklist);
         while let Some(inst) = elimination_list.pop() {
             func.remove_inst(inst).unwrap();
+            NUM_REMOVED.inc();
         }
     }
 }
@@ -44,8 +63,8 @@ fn check_if_elimination_possible(
 ) {
     let no_users = data.users_of(inst).is_empty();
     let inst = data.inst_ref(inst);
-    let do_not_eliminate = matches!(inst.opcode, Opcode::Alloca | Opcode::Store | Opcode::Call)
-        || inst.opcode.is_terminator();
+    let do_not_eliminate =
+        inst.opcode.is_alloca() || inst.opcode.may_write_memory() || inst.opcode.is_terminator();
     if do_not_eliminate {
         return;
     }
@@ -0,0 +1,357 @@
+//! Dead store elimination.
+//!
+//! Two kinds of stores are removed:
+//!
+//! - Stores overwritten by a later store to the same address on every path to
+//!   the end of the function, with nothing in between that may read the memory
+//!   (see [`AliasAnalysis`]). Calls are assumed to read any memory except
+//!   allocas whose address never escapes, and such allocas are dead once the
+//!   function returns. This is found by a backward dataflow analysis over the
+//!   blocks; a store in a block from which the function never returns is kept.
+//! - Stores to allocas whose address never escapes and which are never
+//!   loaded from. Such allocas are removed as well.
+
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::ir::{
+    function::{
+        basic_block::{BasicBlock, BasicBlockId},
+        instruction::{InstructionId, Load, Opcode, Operand, Store},
+        Function,
+    },
+    module::Module,
+    types::Type,
+    value::{Value, ValueId},
+};
+use crate::pass::{
+    analysis::{
+        alias::{AliasAnalysis, AliasResult},
+        dom_tree::DominatorTree,
+    },
+    PreservedAnalyses, TransformPass,
+};
+use crate::prelude::*;
+use core::any::Any;
+
+/// Addresses, with the type stored to them, that are written to before they
+/// may be read on every path from a program point.
+type Overwritten = Vec<(ValueId, Type)>;
+
+pub struct DSEPass;
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, function) in module.functions_mut().iter_mut() {
+        run_on_function(function);
+    }
+}
+
+impl TransformPass<Function> for DSEPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func)
+    }
+
+    /// Only stores and allocas are removed, so the CFG is kept.
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none().preserve::<DominatorTree<BasicBlock>>()
+    }
+}
+
+pub fn run_on_function(func: &mut Function) {
+    if func.is_prototype() {
+        return;
+    }
+    let aa = AliasAnalysis::new(func);
+
+    let out = overwritten_at_block_ends(func, &aa);
+    let mut dead = vec![];
+    for block in func.layout.block_iter() {
+        transfer(func, &aa, block, out[&block].clone(), &mut dead);
+    }
+    for inst in dead {
+        func.remove_inst(inst);
+    }
+
+    let allocas: Vec<InstructionId> = func
+        .layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .filter(|&inst| func.data.inst_ref(inst).opcode == Opcode::Alloca)
+        .collect();
+    for alloca in allocas {
+        if !aa.is_non_escaping(alloca) {
+            continue;
+        }
+        let mut derived = vec![];
+        if is_loaded(func, alloca, &mut derived) {
+            continue;
+        }
+        // Users come after the instructions they use in `derived`.
+        for &inst in derived.iter().rev() {
+            func.remove_inst(inst);
+        }
+        func.remove_inst(alloca);
+    }
+}
+
+/// Solves the overwritten addresses at the end of each block, intersecting
+/// those at the start of its successors until nothing changes.
+fn overwritten_at_block_ends(
+    func: &Function,
+    aa: &AliasAnalysis,
+) -> FxHashMap<BasicBlockId, Overwritten> {
+    let blocks: Vec<BasicBlockId> = func.layout.block_iter().collect();
+    let mut all: Overwritten = vec![];
+    for &block in &blocks {
+        for inst in func.layout.inst_iter(block) {
+            if let Operand::Store(Store { tys, args, .. }) = &func.data.inst_ref(inst).operand {
+                if !all.contains(&(args[1], tys[0])) {
+                    all.push((args[1], tys[0]));
+                }
+            }
+        }
+    }
+
+    // Blocks that return start from the allocas, as they are dead after
+    // returning. Those from which the function never returns are assumed to
+    // have everything read, and the rest start from everything.
+    let returns = |block: BasicBlockId| {
+        func.layout
+            .block_node(block)
+            .last_inst()
+            .is_some_and(|term| {
+                matches!(
+                    func.data.inst_ref(term).opcode,
+                    Opcode::Ret | Opcode::Resume
+                )
+            })
+    };
+    let mut reaches_return: FxHashSet<BasicBlockId> =
+        blocks.iter().copied().filter(|&b| returns(b)).collect();
+    let mut worklist: Vec<BasicBlockId> = reaches_return.iter().copied().collect();
+    while let Some(block) = worklist.pop() {
+        for &pred in func.data.basic_blocks[block].preds() {
+            if reaches_return.insert(pred) {
+                worklist.push(pred);
+            }
+        }
+    }
+    let mut out: FxHashMap<BasicBlockId, Overwritten> = blocks
+        .iter()
+        .map(|&block| {
+            let start = if returns(block) {
+                all.iter()
+                    .copied()
+                    .filter(|&(addr, _)| aa.is_local(func, addr))
+                    .collect()
+            } else if reaches_return.contains(&block) {
+                all.clone()
+            } else {
+                vec![]
+            };
+            (block, start)
+        })
+        .collect();
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &block in blocks.iter().rev() {
+            if returns(block) || !reaches_return.contains(&block) {
+                continue;
+            }
+            let mut new = out[&block].clone();
+            for &succ in func.data.basic_blocks[block].succs() {
+                let at_start = transfer(func, aa, succ, out[&succ].clone(), &mut vec![]);
+                new.retain(|x| at_start.contains(x));
+            }
+            if new != out[&block] {
+                out.insert(block, new);
+                changed = true;
+            }
+        }
+    }
+
+    out
+}
+
+/// Walks `block` backward from `overwritten`, its state at the end of the
+/// block, pushing the stores found dead to `dead`, and returns the state at
+/// the start of the block.
+fn transfer(
+    func: &Function,
+    aa: &AliasAnalysis,
+    block: BasicBlockId,
+    mut overwritten: Overwritten,
+    dead: &mut Vec<InstructionId>,
+) -> Overwritten {
+    let insts: Vec<InstructionId> = func.layout.inst_iter(block).collect();
+    for &inst in insts.iter().rev() {
+        let inst_ref = func.data.inst_ref(inst);
+        match &inst_ref.operand {
+            Operand::Store(Store { tys, args, .. }) => {
+                if overwritten.iter().any(|&(addr, ty)| {
+                    ty == tys[0] && aa.alias(func, addr, args[1]) == AliasResult::MustAlias
+                }) {
+                    dead.push(inst);
+                } else if !overwritten.contains(&(args[1], tys[0])) {
+                    overwritten.push((args[1], tys[0]));
+                }
+            }
+            Operand::Load(Load { addr, .. }) => {
+                overwritten.retain(|&(ptr, _)| aa.alias(func, ptr, *addr) == AliasResult::NoAlias)
+            }
+            _ if inst_ref.opcode.may_read_memory() => {
+                overwritten.retain(|&(ptr, _)| aa.is_local(func, ptr))
+            }
+            _ => {}
+        }
+        // Before `inst`, addresses computed from its result are those of its
+        // previous execution, e.g. a phi in a loop.
+        overwritten.retain(|&(ptr, _)| !depends_on(func, ptr, inst));
+    }
+    overwritten
+}
+
+/// Returns true if `val` is, or is computed from, the result of `inst`.
+fn depends_on(func: &Function, val: ValueId, inst: InstructionId) -> bool {
+    match func.data.value_ref(val) {
+        Value::Instruction(id) if *id == inst => true,
+        Value::Instruction(id) => {
+            let operand = &func.data.inst_ref(*id).operand;
+            !matches!(operand, Operand::Phi(_))
+                && operand
+                    .args()
+                    .iter()
+                    .any(|&arg| depends_on(func, arg, inst))
+        }
+        _ => false,
+    }
+}
+
+/// Returns true if the memory `ptr` points into may be loaded from. Otherwise,
+/// `derived` is extended with the stores to it and the pointers computed from it.
+fn is_loaded(func: &Function, ptr: InstructionId, derived: &mut Vec<InstructionId>) -> bool {
+    func.data.users_of(ptr).iter().any(|&user| {
+        let inst = func.data.inst_ref(user);
+        match inst.opcode {
+            Opcode::Store => {
+                derived.push(user);
+                false
+            }
+            Opcode::GetElementPtr | Opcode::Bitcast => {
+                derived.push(user);
+                is_loaded(func, user, derived)
+            }
+            _ => true,
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::ir::module::parse_assembly;
+
+    fn dse(source: &str) -> String {
+        let mut module = parse_assembly(source).unwrap();
+        let id = module.find_function_by_name("f").unwrap();
+        super::run_on_function(&mut module.functions_mut()[id]);
+        format!("{:?}", module.functions()[id])
+    }
+
+    #[test]
+    fn overwritten_stores() {
+        let printed = dse(r#"
+declare void @g()
+define i32 @f(i32* %0, i32* %1) {
+  %3 = alloca i32, align 4
+  store i32 1, i32* %0, align 4
+  store i32 2, i32* %0, align 4
+  store i32 3, i32* %1, align 4
+  %4 = load i32, i32* %1, align 4
+  store i32 4, i32* %1, align 4
+  store i32 5, i32* %0, align 4
+  store i32 1, i32* %3, align 4
+  call void @g()
+  store i32 2, i32* %3, align 4
+  store i32 6, i32* %0, align 4
+  call void @g()
+  store i32 7, i32* %0, align 4
+  %5 = load i32, i32* %3, align 4
+  ret i32 %5
+}"#);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn stores_overwritten_in_every_successor() {
+        let printed = dse(r#"
+define void @f(i32* %0, i32* %1, i1 %2) {
+  store i32 1, i32* %0, align 4
+  store i32 1, i32* %1, align 4
+  br i1 %2, label %4, label %5
+4:
+  store i32 2, i32* %0, align 4
+  store i32 2, i32* %1, align 4
+  br label %6
+5:
+  store i32 3, i32* %0, align 4
+  br label %6
+6:
+  ret void
+}"#);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn stores_in_loops() {
+        let printed = dse(r#"
+define i32 @f(i32* %0, i32 %1) {
+  %3 = alloca i32, align 4
+  store i32 0, i32* %0, align 4
+  br label %4
+4:
+  %5 = phi i32 [ 0, %2 ], [ %8, %4 ]
+  %6 = getelementptr inbounds i32, i32* %0, i32 %5
+  store i32 %5, i32* %6, align 4
+  %7 = load i32, i32* %3, align 4
+  store i32 %5, i32* %3, align 4
+  %8 = add i32 %5, 1
+  %9 = icmp slt i32 %8, %1
+  br i1 %9, label %4, label %10
+10:
+  store i32 %1, i32* %0, align 4
+  store i32 %1, i32* %6, align 4
+  ret i32 %7
+}"#);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn stores_before_infinite_loops() {
+        let printed = dse(r#"
+define void @f(i32* %0, i1 %1) {
+  store i32 1, i32* %0, align 4
+  br i1 %1, label %3, label %4
+3:
+  br label %3
+4:
+  store i32 2, i32* %0, align 4
+  ret void
+}"#);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn never_loaded_allocas() {
+        let printed = dse(r#"
+define i32 @f(i32 %0) {
+  %2 = alloca [2 x i32], align 4
+  %3 = getelementptr inbounds [2 x i32], [2 x i32]* %2, i64 0, i64 1
+  store i32 %0, i32* %3, align 4
+  %4 = alloca i32, align 4
+  store i32 %0, i32* %4, align 4
+  %5 = load i32, i32* %4, align 4
+  ret i32 %5
+}"#);
+        insta::assert_snapshot!(printed);
+    }
+}
@@ -0,0 +1,157 @@
+//! Global dead code elimination.
+//!
+//! Functions and global variables with `internal` or `private` linkage that
+//! are not reachable from any other global, through references in function
+//! bodies, personality functions and initializers, are removed. Globals
+//! that refer only to each other are removed together.
+
+use crate::collections::FxHashSet;
+use crate::ir::{
+    function::Function,
+    module::{linkage::Linkage, name::Name, Module},
+    value::Value,
+};
+use crate::pass::{PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::{any::Any, mem};
+use id_arena::Arena;
+
+pub struct GlobalDCEPass;
+
+/// The number of functions and global variables removed by [`GlobalDCEPass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlobalDCEResult(pub usize);
+
+impl TransformPass<Module> for GlobalDCEPass {
+    fn run_on(&self, module: &mut Module, result: &mut Box<dyn Any>) {
+        *result = Box::new(GlobalDCEResult(run_on_module(module)))
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+/// Returns true if a global of `linkage` cannot be referred to from other modules.
+pub fn is_local(linkage: Linkage) -> bool {
+    matches!(linkage, Linkage::Private | Linkage::Internal)
+}
+
+/// Calls `f` with every global referred to in the body of `func`.
+pub fn for_each_global_ref_in<F: FnMut(&Name)>(func: &Function, f: &mut F) {
+    for (_, val) in func.data.values.iter() {
+        if let Value::Constant(konst) = val {
+            konst.for_each_global_ref(f);
+        }
+    }
+    if let Some((_, personality)) = &func.personality {
+        personality.for_each_global_ref(f);
+    }
+}
+
+/// Removes unreachable local globals from `module` and returns how many were removed.
+pub fn run_on_module(module: &mut Module) -> usize {
+    let mut live: FxHashSet<Name> = FxHashSet::default();
+    let mut worklist = vec![];
+    let mut visit = |name: &Name, worklist: &mut Vec<Name>| {
+        if live.insert(*name) {
+            worklist.push(*name);
+        }
+    };
+    for (_, func) in module.functions.iter() {
+        if !is_local(func.linkage) {
+            visit(&Name::from(func.name.as_str()), &mut worklist);
+        }
+    }
+    for (name, gv) in &module.global_variables {
+        if !gv.linkage.is_some_and(is_local) {
+            visit(name, &mut worklist);
+        }
+    }
+
+    while let Some(name) = worklist.pop() {
+        let mut refs = vec![];
+        let mut collect = |n: &Name| refs.push(*n);
+        if let Some(gv) = module.global_variables.get(&name) {
+            if let Some(init) = &gv.init {
+                init.for_each_global_ref(&mut collect);
+            }
+        }
+        if let Name::Name(n) = &name {
+            if let Some(id) = module.find_function_by_name(n) {
+                for_each_global_ref_in(&module.functions[id], &mut collect);
+            }
+        }
+        for r in refs {
+            visit(&r, &mut worklist);
+        }
+    }
+
+    let num_globals = module.global_variables.len();
+    module
+        .global_variables
+        .retain(|name, _| live.contains(name));
+    let mut removed = num_globals - module.global_variables.len();
+
+    let functions = mem::replace(&mut module.functions, Arena::new());
+    for (_, func) in functions {
+        if live.contains(&Name::from(func.name.as_str())) {
+            module.functions.alloc(func);
+        } else {
+            removed += 1;
+        }
+    }
+
+    removed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn remove_unreachable_locals() {
+        let mut module = parse_assembly(
+            r#"
+@used = internal global i32 1, align 4
+@unused = internal global i32 2, align 4
+@exported = global i32 3, align 4
+@table = internal global i32* @cycle_g, align 8
+@cycle_g = internal global i32* bitcast (i32** @table to i32*), align 8
+
+define internal i32 @helper() {
+  %1 = load i32, i32* @used, align 4
+  ret i32 %1
+}
+
+define internal i32 @dead() {
+  %1 = call i32 @dead_too()
+  ret i32 %1
+}
+
+define internal i32 @dead_too() {
+  %1 = call i32 @dead()
+  ret i32 %1
+}
+
+define dso_local i32 @main() {
+  %1 = call i32 @helper()
+  ret i32 %1
+}"#,
+        )
+        .unwrap();
+        assert_eq!(run_on_module(&mut module), 5);
+
+        let mut functions: Vec<_> = module.functions().iter().map(|(_, f)| f.name()).collect();
+        functions.sort();
+        assert_eq!(functions, vec!["helper", "main"]);
+        let mut globals: Vec<_> = module
+            .global_variables()
+            .keys()
+            .map(|n| format!("{:?}", n))
+            .collect();
+        globals.sort();
+        assert_eq!(globals, vec!["exported", "used"]);
+    }
+}
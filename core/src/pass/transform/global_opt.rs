@@ -0,0 +1,215 @@
+//! Global variable optimization.
+//!
+//! Only `internal` and `private` globals whose address does not escape are
+//! handled, i.e. globals used only as the address of loads and stores:
+//!
+//! - A global that is never stored to is marked `constant`.
+//! - A global of integer or pointer type accessed only from `main`, which is
+//!   itself never referred to, is replaced with an alloca in the entry block
+//!   of `main` initialized with the initializer of the global. `main` is
+//!   assumed to run at most once, so the value cannot be observed across
+//!   calls.
+
+use super::global_dce::is_local;
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::ir::{
+    function::{builder::Builder, instruction::Operand, Function, FunctionId},
+    module::{name::Name, Module},
+    types::Type,
+    value::{ConstantData, Value},
+};
+use crate::pass::{PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct GlobalOptPass;
+
+/// The number of global variables changed by [`GlobalOptPass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlobalOptResult(pub usize);
+
+impl TransformPass<Module> for GlobalOptPass {
+    fn run_on(&self, module: &mut Module, result: &mut Box<dyn Any>) {
+        *result = Box::new(GlobalOptResult(run_on_module(module)))
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+/// How a global variable is used.
+#[derive(Default)]
+struct Uses {
+    escapes: bool,
+    stored: bool,
+    functions: FxHashSet<FunctionId>,
+}
+
+/// Optimizes the local globals of `module` and returns how many were changed.
+pub fn run_on_module(module: &mut Module) -> usize {
+    let uses = collect_uses(module);
+    let main = module.find_function_by_name("main").filter(|&id| {
+        !module.functions[id].is_prototype() && !uses.contains_key(&Name::from("main"))
+    });
+
+    let mut names: Vec<Name> = module
+        .global_variables
+        .iter()
+        .filter(|(_, gv)| gv.linkage.is_some_and(is_local) && !gv.is_constant)
+        .map(|(name, _)| *name)
+        .collect();
+    names.sort_by_key(|name| format!("{:?}", name));
+
+    let unused = Uses::default();
+    let mut changed = 0;
+    for name in names {
+        let Uses {
+            escapes,
+            stored,
+            functions,
+        } = uses.get(&name).unwrap_or(&unused);
+        if *escapes {
+            continue;
+        }
+        if !stored {
+            module.global_variables.get_mut(&name).unwrap().is_constant = true;
+            changed += 1;
+            continue;
+        }
+        let Some(main) = main.filter(|main| functions.len() == 1 && functions.contains(main))
+        else {
+            continue;
+        };
+        let gv = &module.global_variables[&name];
+        if !matches!(gv.init, Some(ConstantData::Int(_) | ConstantData::Null)) {
+            continue;
+        }
+        let (ty, init) = (gv.ty, gv.init.clone().unwrap());
+        module.global_variables.shift_remove(&name);
+        localize(&mut module.functions[main], &name, ty, init);
+        changed += 1;
+    }
+    changed
+}
+
+fn collect_uses(module: &Module) -> FxHashMap<Name, Uses> {
+    let mut uses: FxHashMap<Name, Uses> = FxHashMap::default();
+    for gv in module.global_variables.values() {
+        if let Some(init) = &gv.init {
+            init.for_each_global_ref(&mut |name| uses.entry(*name).or_default().escapes = true);
+        }
+    }
+
+    for (id, func) in module.functions.iter() {
+        if let Some((_, personality)) = &func.personality {
+            personality
+                .for_each_global_ref(&mut |name| uses.entry(*name).or_default().escapes = true);
+        }
+        for block in func.layout.block_iter() {
+            for inst in func.layout.inst_iter(block) {
+                let operand = &func.data.inst_ref(inst).operand;
+                // The argument used as an address, if any.
+                let (addr, is_store) = match operand {
+                    Operand::Load(load) => (Some(load.addr), false),
+                    Operand::Store(store) => (Some(store.args[1]), true),
+                    _ => (None, false),
+                };
+                for (i, &arg) in operand.args().iter().enumerate() {
+                    let Value::Constant(konst) = func.data.value_ref(arg) else {
+                        continue;
+                    };
+                    let is_addr = Some(arg) == addr && !(is_store && i == 0);
+                    konst.for_each_global_ref(&mut |name| {
+                        let entry = uses.entry(*name).or_default();
+                        entry.functions.insert(id);
+                        if is_addr && matches!(konst, ConstantData::GlobalRef(_)) {
+                            entry.stored |= is_store;
+                        } else {
+                            entry.escapes = true;
+                        }
+                    });
+                }
+            }
+        }
+    }
+    uses
+}
+
+/// Replaces the global `name` with an alloca at the beginning of `func`.
+fn localize(func: &mut Function, name: &Name, ty: Type, init: ConstantData) {
+    let entry = func.entry_block().unwrap();
+    let mut builder = Builder::new(func);
+    builder.position_at_start(entry);
+    let alloca = builder.build_alloca(ty);
+    let init = builder.value(Value::Constant(init));
+    builder.build_store(ty, init, alloca);
+    let alloca = *func.data.value_ref(alloca).as_inst();
+
+    let global = Value::Constant(ConstantData::GlobalRef(*name));
+    for (_, val) in func.data.values.iter_mut() {
+        if *val == global {
+            *val = Value::Instruction(alloca);
+        }
+    }
+    let insts: Vec<_> = func
+        .layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .collect();
+    for inst in insts {
+        func.data.validate_inst_uses(inst);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn constify_and_localize() {
+        let mut module = parse_assembly(
+            r#"
+@ro = internal global i32 1, align 4
+@counter = internal global i32 2, align 4
+@shared = internal global i32 3, align 4
+@escaped = internal global i32 4, align 4
+@exported = global i32 5, align 4
+
+declare void @use(i32*)
+
+define internal i32 @get() {
+  %1 = load i32, i32* @shared, align 4
+  ret i32 %1
+}
+
+define dso_local i32 @main() {
+  %1 = load i32, i32* @ro, align 4
+  %2 = load i32, i32* @counter, align 4
+  %3 = add i32 %2, %1
+  store i32 %3, i32* @counter, align 4
+  store i32 %3, i32* @shared, align 4
+  call void @use(i32* @escaped)
+  %4 = call i32 @get()
+  %5 = load i32, i32* @counter, align 4
+  %6 = add i32 %4, %5
+  ret i32 %6
+}"#,
+        )
+        .unwrap();
+        assert_eq!(run_on_module(&mut module), 2);
+
+        let name = |n: &str| Name::from(n);
+        let globals = module.global_variables();
+        assert!(globals[&name("ro")].is_constant);
+        assert!(!globals.contains_key(&name("counter")));
+        assert!(!globals[&name("shared")].is_constant);
+        assert!(!globals[&name("escaped")].is_constant);
+        assert!(!globals[&name("exported")].is_constant);
+
+        let main = module.find_function_by_name("main").unwrap();
+        let printed = format!("{:?}", module.functions()[main]);
+        insta::assert_snapshot!(printed);
+    }
+}
@@ -0,0 +1,286 @@
+//! Global value numbering.
+//!
+//! Blocks are visited in dominator tree order, numbering every instruction
+//! without side effects by its opcode and operands. An instruction computing
+//! the same value as an instruction dominating it is replaced with the latter.
+//!
+//! Loads are replaced with the value last stored to or loaded from the same
+//! address when no store that may alias it (see [`AliasAnalysis`]) or call is
+//! in between. This is only tracked within a block and into successors whose
+//! single predecessor it is.
+
+use crate::collections::FxHashMap;
+use crate::ir::{
+    function::{
+        basic_block::{BasicBlock, BasicBlockId},
+        instruction::{
+            Cast, ExtractElement, ExtractValue, GetElementPtr, ICmp, ICmpCond, InsertElement,
+            InsertValue, InstructionId, IntBinary, Load, Opcode, Operand, ShuffleVector, Store,
+        },
+        Function,
+    },
+    module::Module,
+    types::Type,
+    value::{Value, ValueId},
+};
+use crate::pass::{
+    analysis::{
+        alias::{AliasAnalysis, AliasResult},
+        dom_tree::DominatorTree,
+    },
+    PreservedAnalyses, TransformPass,
+};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct GVNPass;
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, function) in module.functions_mut().iter_mut() {
+        run_on_function(function);
+    }
+}
+
+impl TransformPass<Function> for GVNPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    /// Terminators are never removed, so the CFG is kept.
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none().preserve::<DominatorTree<BasicBlock>>()
+    }
+}
+
+/// The value computed by an instruction, with the instructions among its
+/// operands replaced by their leaders.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Expression {
+    opcode: Opcode,
+    tys: Vec<Type>,
+    flags: [bool; 3],
+    cond: Option<ICmpCond>,
+    args: Vec<Value>,
+}
+
+/// A value known to be in memory at `addr`.
+#[derive(Debug, Clone)]
+struct Available {
+    addr: ValueId,
+    ty: Type,
+    val: Value,
+}
+
+/// Removes redundant instructions from `func` and returns how many were removed.
+pub fn run_on_function(func: &mut Function) -> usize {
+    if func.is_prototype() {
+        return 0;
+    }
+
+    let dom_tree = DominatorTree::new(func);
+    let aa = AliasAnalysis::new(func);
+    let mut leaders: FxHashMap<Expression, InstructionId> = FxHashMap::default();
+    let mut removed = 0;
+
+    // (block, memory available on entry, expressions numbered in the block)
+    let mut stack: Vec<(BasicBlockId, Vec<Available>, Option<Vec<Expression>>)> =
+        vec![(*dom_tree.root(), vec![], None)];
+    while let Some((block, mut memory, numbered)) = stack.pop() {
+        if let Some(numbered) = numbered {
+            // All the blocks dominated by `block` are done.
+            for expr in numbered {
+                leaders.remove(&expr);
+            }
+            continue;
+        }
+
+        let mut numbered = vec![];
+        let insts: Vec<InstructionId> = func.layout.inst_iter(block).collect();
+        for inst in insts {
+            let replacement = if let Some(expr) = expression_of(func, inst) {
+                match leader_of(&leaders, &expr) {
+                    Some(leader) => Some(Value::Instruction(leader)),
+                    None => {
+                        leaders.insert(expr.clone(), inst);
+                        numbered.push(expr);
+                        None
+                    }
+                }
+            } else {
+                process_memory(func, &aa, &mut memory, inst)
+            };
+            if let Some(val) = replacement {
+                let val = func.data.create_value(val);
+                func.replace_all_uses_with(inst, val);
+                func.remove_inst(inst);
+                removed += 1;
+            }
+        }
+
+        stack.push((block, vec![], Some(numbered)));
+        for &child in dom_tree.children_of(block).into_iter().flatten() {
+            let preds = func.data.basic_blocks[child].preds();
+            let memory = if preds.len() == 1 && preds.contains(&block) {
+                memory.clone()
+            } else {
+                vec![]
+            };
+            stack.push((child, memory, None));
+        }
+    }
+
+    removed
+}
+
+fn leader_of(
+    leaders: &FxHashMap<Expression, InstructionId>,
+    expr: &Expression,
+) -> Option<InstructionId> {
+    if let Some(&leader) = leaders.get(expr) {
+        return Some(leader);
+    }
+    if expr.opcode.is_commutative() {
+        let mut swapped = expr.clone();
+        swapped.args.swap(0, 1);
+        return leaders.get(&swapped).copied();
+    }
+    None
+}
+
+/// Updates `memory` with the effects of `inst` and returns the value to
+/// replace `inst` with if it is a redundant load.
+fn process_memory(
+    func: &Function,
+    aa: &AliasAnalysis,
+    memory: &mut Vec<Available>,
+    id: InstructionId,
+) -> Option<Value> {
+    let inst = func.data.inst_ref(id);
+    match &inst.operand {
+        Operand::Load(Load { tys, addr, .. }) => {
+            let available = memory.iter().rev().find(|a| {
+                a.ty == tys[0] && aa.alias(func, a.addr, *addr) == AliasResult::MustAlias
+            });
+            if let Some(available) = available {
+                return Some(available.val.clone());
+            }
+            memory.push(Available {
+                addr: *addr,
+                ty: tys[0],
+                val: Value::Instruction(id),
+            });
+        }
+        Operand::Store(Store { tys, args, .. }) => {
+            memory.retain(|a| aa.alias(func, a.addr, args[1]) == AliasResult::NoAlias);
+            memory.push(Available {
+                addr: args[1],
+                ty: tys[0],
+                val: func.data.value_ref(args[0]).clone(),
+            });
+        }
+        Operand::Call(_) | Operand::Invoke(_) => memory.clear(),
+        _ => {}
+    }
+    None
+}
+
+fn expression_of(func: &Function, inst: InstructionId) -> Option<Expression> {
+    let inst = func.data.inst_ref(inst);
+    let (tys, flags, cond, args) = match &inst.operand {
+        Operand::IntBinary(IntBinary {
+            ty,
+            nsw,
+            nuw,
+            exact,
+            args,
+        }) => (vec![*ty], [*nsw, *nuw, *exact], None, &args[..]),
+        Operand::ICmp(ICmp { ty, args, cond }) => (vec![*ty], [false; 3], Some(*cond), &args[..]),
+        Operand::Cast(Cast { tys, arg }) => {
+            (tys.to_vec(), [false; 3], None, core::slice::from_ref(arg))
+        }
+        Operand::GetElementPtr(GetElementPtr {
+            inbounds,
+            tys,
+            args,
+        }) => (tys.clone(), [*inbounds, false, false], None, &args[..]),
+        Operand::ExtractValue(ExtractValue { ty, args }) => {
+            (vec![*ty], [false; 3], None, &args[..])
+        }
+        Operand::InsertValue(InsertValue { tys, args }) => {
+            (tys.to_vec(), [false; 3], None, &args[..])
+        }
+        Operand::ExtractElement(ExtractElement { tys, args }) => {
+            (tys.to_vec(), [false; 3], None, &args[..])
+        }
+        Operand::InsertElement(InsertElement { tys, args }) => {
+            (tys.to_vec(), [false; 3], None, &args[..])
+        }
+        Operand::ShuffleVector(ShuffleVector { tys, args }) => {
+            (tys.to_vec(), [false; 3], None, &args[..])
+        }
+        _ => return None,
+    };
+    Some(Expression {
+        opcode: inst.opcode,
+        tys,
+        flags,
+        cond,
+        args: args
+            .iter()
+            .map(|&arg| func.data.value_ref(arg).clone())
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pass::transform::run_on_f;
+
+    #[test]
+    fn redundant_computations() {
+        let (removed, printed) = run_on_f(
+            super::run_on_function,
+            r#"
+define i32 @f(i32 %0, i32 %1) {
+  %3 = add nsw i32 %0, %1
+  %4 = icmp slt i32 %0, 10
+  br i1 %4, label %5, label %9
+5:
+  %6 = add nsw i32 %1, %0
+  %7 = icmp slt i32 %0, 10
+  %8 = zext i1 %7 to i32
+  ret i32 %8
+9:
+  %10 = add i32 %0, %1
+  ret i32 %10
+}"#,
+        );
+        assert_eq!(removed, 2);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn redundant_loads() {
+        let (removed, printed) = run_on_f(
+            super::run_on_function,
+            r#"
+define i32 @f(i32* %0) {
+  %2 = alloca i32, align 4
+  store i32 1, i32* %2, align 4
+  store i32 2, i32* %0, align 4
+  %3 = load i32, i32* %2, align 4
+  %4 = load i32, i32* %0, align 4
+  %5 = load i32, i32* %0, align 4
+  call void @g()
+  %6 = load i32, i32* %0, align 4
+  %7 = add i32 %3, %4
+  %8 = add i32 %7, %5
+  %9 = add i32 %8, %6
+  ret i32 %9
+}
+declare void @g()"#,
+        );
+        assert_eq!(removed, 3);
+        insta::assert_snapshot!(printed);
+    }
+}
@@ -0,0 +1,246 @@
+//! Identical code folding.
+//!
+//! Functions whose bodies are structurally equal are merged into the first of
+//! them that is kept. The others become thunks calling the kept function, and when their
+//! address is insignificant (`unnamed_addr`) every reference to them is
+//! redirected to the kept function as well.
+//!
+//! Functions whose definition may be replaced at link time, e.g. `weak` or
+//! `linkonce` ones, are never kept, as the others would then call whichever
+//! definition the linker picks. They are still folded into the others.
+
+use crate::collections::FxHashMap;
+use crate::ir::{
+    function::{
+        builder::Builder, data::Data, hash::Structure, layout::Layout, Function, FunctionId,
+    },
+    module::{linkage::Linkage, name::Name, unnamed_addr::UnnamedAddr, Module},
+    value::{ConstantData, ConstantExpr, Value},
+};
+use crate::pass::{PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::{any::Any, mem};
+
+pub struct ICFPass;
+
+/// The number of functions folded by [`ICFPass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ICFResult(pub usize);
+
+impl TransformPass<Module> for ICFPass {
+    fn run_on(&self, module: &mut Module, result: &mut Box<dyn Any>) {
+        *result = Box::new(ICFResult(run_on_module(module)))
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+/// Folds identical functions in `module` and returns how many were folded.
+pub fn run_on_module(module: &mut Module) -> usize {
+    let mut canonical: FxHashMap<String, FunctionId> = FxHashMap::default();
+    let mut bodies = vec![];
+
+    for (id, func) in module.functions.iter_mut() {
+        if func.is_prototype() {
+            continue;
+        }
+        let body = body_of(func);
+        if !is_interposable(func.linkage) {
+            canonical.entry(body.clone()).or_insert(id);
+        }
+        bodies.push((id, body));
+    }
+
+    let folded: Vec<_> = bodies
+        .into_iter()
+        .filter_map(|(id, body)| match canonical.get(&body) {
+            Some(&target) if target != id => Some((id, target)),
+            _ => None,
+        })
+        .collect();
+
+    let mut redirect = FxHashMap::default();
+    for &(id, target) in &folded {
+        let target_name = module.functions[target].name.clone();
+        let func = &mut module.functions[id];
+        if matches!(func.unnamed_addr, Some(UnnamedAddr::Global)) {
+            redirect.insert(
+                Name::from(func.name.as_str()),
+                Name::from(target_name.as_str()),
+            );
+        }
+        make_thunk(func, target_name);
+    }
+
+    if !redirect.is_empty() {
+        for (_, func) in module.functions.iter_mut() {
+            for (_, val) in func.data.values.iter_mut() {
+                if let Value::Constant(konst) = val {
+                    redirect_refs(konst, &redirect);
+                }
+            }
+            if let Some((_, personality)) = &mut func.personality {
+                redirect_refs(personality, &redirect);
+            }
+        }
+        for gv in module.global_variables.values_mut() {
+            if let Some(init) = &mut gv.init {
+                redirect_refs(init, &redirect);
+            }
+        }
+    }
+
+    folded.len()
+}
+
+/// Returns the structure of `func` without its name, linkage and `unnamed_addr`,
+/// which are kept by the thunk and thus need not match.
+fn body_of(func: &mut Function) -> String {
+    let name = mem::take(&mut func.name);
+    let unnamed_addr = func.unnamed_addr.take();
+    let linkage = mem::replace(&mut func.linkage, Linkage::External);
+    let body = format!("{:?}", Structure(func));
+    func.name = name;
+    func.unnamed_addr = unnamed_addr;
+    func.linkage = linkage;
+    body
+}
+
+/// Returns true if a definition with `linkage` may be replaced by another one
+/// when linking.
+fn is_interposable(linkage: Linkage) -> bool {
+    matches!(
+        linkage,
+        Linkage::ExternalWeak
+            | Linkage::AvailableExternally
+            | Linkage::LinkOnceAny
+            | Linkage::LinkOnceODR
+            | Linkage::LinkOnceODRAutoHide
+            | Linkage::WeakAny
+            | Linkage::WeakODR
+            | Linkage::Common
+    )
+}
+
+/// Replaces the body of `func` with a call to `target` forwarding all the arguments.
+fn make_thunk(func: &mut Function, target: String) {
+    func.data = Data::default();
+    func.layout = Layout::default();
+
+    let result_ty = func.result_ty;
+    let param_tys: Vec<_> = func.params.iter().map(|p| p.ty).collect();
+    let mut builder = Builder::new(func);
+    let entry = builder.create_block();
+    builder.switch_to_block(entry);
+    let callee = builder.global_ref(target);
+    let args = param_tys
+        .into_iter()
+        .enumerate()
+        .map(|(i, ty)| (ty, builder.arg(i)))
+        .collect();
+    match builder.build_call(result_ty, callee, args) {
+        Some(ret) => builder.build_ret(ret),
+        None => builder.build_ret_void(),
+    };
+}
+
+fn redirect_refs(konst: &mut ConstantData, redirect: &FxHashMap<Name, Name>) {
+    match konst {
+        ConstantData::GlobalRef(name) => {
+            if let Some(to) = redirect.get(name) {
+                *name = *to;
+            }
+        }
+        ConstantData::Array(arr) => arr
+            .elems
+            .iter_mut()
+            .for_each(|e| redirect_refs(e, redirect)),
+        ConstantData::Struct(s) => s.elems.iter_mut().for_each(|e| redirect_refs(e, redirect)),
+        ConstantData::Vector(v) => v.elems.iter_mut().for_each(|e| redirect_refs(e, redirect)),
+        ConstantData::Expr(ConstantExpr::GetElementPtr { args, .. }) => {
+            args.iter_mut().for_each(|e| redirect_refs(e, redirect))
+        }
+        ConstantData::Expr(ConstantExpr::Bitcast { arg, .. }) => redirect_refs(arg, redirect),
+        ConstantData::Undef
+        | ConstantData::AggregateZero
+        | ConstantData::Null
+        | ConstantData::Float(_)
+        | ConstantData::Int(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn fold_identical_functions() {
+        let mut module = parse_assembly(
+            r#"
+@ptr = global i32 (i32)* @g
+
+define i32 @f(i32 %0) {
+  %2 = mul i32 %0, 3
+  ret i32 %2
+}
+
+define i32 @g(i32 %x) unnamed_addr {
+  %y = mul i32 %x, 3
+  ret i32 %y
+}
+
+define i32 @h(i32 %0) {
+  %2 = mul i32 %0, 3
+  ret i32 %2
+}
+
+define i32 @k(i32 %0) {
+  %2 = mul i32 %0, 4
+  ret i32 %2
+}
+
+define i32 @main() {
+  %1 = call i32 @g(i32 1)
+  %2 = call i32 @h(i32 %1)
+  %3 = call i32 @k(i32 %2)
+  ret i32 %3
+}"#,
+        )
+        .unwrap();
+        assert_eq!(run_on_module(&mut module), 2);
+        insta::assert_debug_snapshot!(module);
+    }
+
+    #[test]
+    fn keep_no_interposable_function() {
+        let mut module = parse_assembly(
+            r#"
+define weak i32 @a(i32 %0) {
+  %2 = mul i32 %0, 3
+  ret i32 %2
+}
+
+define linkonce_odr i32 @b(i32 %0) {
+  %2 = mul i32 %0, 3
+  ret i32 %2
+}
+
+define i32 @c(i32 %0) {
+  %2 = mul i32 %0, 3
+  ret i32 %2
+}"#,
+        )
+        .unwrap();
+        assert_eq!(run_on_module(&mut module), 2);
+        let body = |module: &Module, name: &str| {
+            let id = module.find_function_by_name(name).unwrap();
+            format!("{:?}", module.functions()[id])
+        };
+        assert!(body(&module, "a").contains("call i32 @c(i32 %0)"));
+        assert!(body(&module, "b").contains("call i32 @c(i32 %0)"));
+        assert!(body(&module, "c").contains("mul i32 %0, 3"));
+    }
+}
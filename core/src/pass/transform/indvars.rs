@@ -0,0 +1,244 @@
+//! Induction variable simplification.
+//!
+//! An induction variable is a phi of a loop header `phi [start, %preheader],
+//! [add %iv, step, %latch]` whose `step` is a constant. In each loop, the
+//! canonical induction variable `%i`, starting at 0 with a step of 1, is
+//! created if the loop has none, and the other induction variables of its
+//! type are rewritten as `start + step * %i`, leaving a single phi to be
+//! incremented.
+//!
+//! The rewritten values wrap exactly as the original ones did, so the new
+//! instructions need no `nsw` or `nuw` flag. The pass is meant to run after
+//! [`loop_rotate`](super::loop_rotate), which puts loops in `do`-`while`
+//! form, and before [`loop_unroll`](super::loop_unroll).
+
+use super::inst_combine::{int_constant, int_of};
+use crate::ir::{
+    function::{
+        basic_block::{BasicBlock, BasicBlockId},
+        builder::Builder,
+        instruction::{InstructionId, IntBinary, Opcode, Phi},
+        Function,
+    },
+    module::Module,
+    types::Type,
+    value::{Value, ValueId},
+};
+use crate::pass::{
+    analysis::{
+        dom_tree::DominatorTree,
+        loops::{Loop, LoopInfo},
+    },
+    PreservedAnalyses, TransformPass,
+};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct IndVarSimplifyPass;
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, function) in module.functions_mut().iter_mut() {
+        run_on_function(function);
+    }
+}
+
+impl TransformPass<Function> for IndVarSimplifyPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none().preserve::<DominatorTree<BasicBlock>>()
+    }
+}
+
+struct InductionVariable {
+    phi: InstructionId,
+    ty: Type,
+    start: ValueId,
+    step: i64,
+    /// `add %phi, step`.
+    next: InstructionId,
+}
+
+impl InductionVariable {
+    fn is_canonical(&self, func: &Function) -> bool {
+        self.step == 1 && int_of(func, self.start).is_some_and(|c| c.cast_to_i64() == 0)
+    }
+}
+
+/// Rewrites the induction variables of the loops in `func` from their
+/// canonical one and returns how many were rewritten.
+pub fn run_on_function(func: &mut Function) -> usize {
+    if func.is_prototype() {
+        return 0;
+    }
+
+    let loops = LoopInfo::new(func);
+    loops.loops().iter().map(|l| simplify_loop(func, l)).sum()
+}
+
+fn simplify_loop(func: &mut Function, l: &Loop<BasicBlock>) -> usize {
+    let [latch] = l.latches[..] else {
+        return 0;
+    };
+    let preds = &func.data.block_ref(l.header).preds;
+    let Some(&preheader) = preds.iter().find(|&&b| b != latch) else {
+        return 0;
+    };
+    if preds.len() != 2 {
+        return 0;
+    }
+
+    let ivs: Vec<InductionVariable> = func
+        .layout
+        .inst_iter(l.header)
+        .filter_map(|inst| induction_variable(func, inst, preheader, latch))
+        .collect();
+    let canonical = ivs.iter().find(|iv| iv.is_canonical(func));
+    let Some(ty) = canonical.or(ivs.first()).map(|iv| iv.ty) else {
+        return 0;
+    };
+    let others: Vec<&InductionVariable> = ivs
+        .iter()
+        .filter(|iv| iv.ty == ty && !iv.is_canonical(func))
+        .collect();
+    if others.is_empty() {
+        return 0;
+    }
+
+    let first_non_phi = func
+        .layout
+        .inst_iter(l.header)
+        .find(|&inst| !func.data.inst_ref(inst).opcode.is_phi())
+        .unwrap();
+    let latch_term = (*func.layout.block_node(latch).last_inst()).unwrap();
+    let canonical_phi = canonical.map(|iv| iv.phi);
+
+    let mut builder = Builder::new(func);
+    let i = match canonical_phi {
+        Some(phi) => builder.value(Value::Instruction(phi)),
+        None => {
+            let zero = builder.value(int_constant(ty, 0).unwrap());
+            let one = builder.value(int_constant(ty, 1).unwrap());
+            builder.position_at_start(l.header);
+            let i = builder.build_phi(ty, vec![(zero, preheader)]);
+            builder.position_before(latch_term);
+            let next = builder.build_add(ty, i, one);
+            builder.add_phi_incoming(i, next, latch);
+            i
+        }
+    };
+    let mut rewritten = vec![];
+    for iv in &others {
+        builder.position_before(first_non_phi);
+        let mut val = i;
+        if iv.step != 1 {
+            let step = builder.value(int_constant(ty, iv.step).unwrap());
+            val = builder.build_mul(ty, val, step);
+        }
+        if int_of(builder.func(), iv.start).is_none_or(|c| c.cast_to_i64() != 0) {
+            val = builder.build_add(ty, iv.start, val);
+        }
+        rewritten.push((iv.phi, iv.next, val));
+    }
+
+    for &(phi, next, val) in &rewritten {
+        func.replace_all_uses_with(phi, val);
+        func.erase_instruction(phi);
+        if func.data.users_of(next).is_empty() {
+            func.erase_instruction(next);
+        }
+    }
+    rewritten.len()
+}
+
+/// Returns the induction variable defined by `inst`, if it is one.
+fn induction_variable(
+    func: &Function,
+    inst: InstructionId,
+    preheader: BasicBlockId,
+    latch: BasicBlockId,
+) -> Option<InductionVariable> {
+    let Phi { ty, args, blocks } = func.data.inst_ref(inst).operand.as_phi()?;
+    let [_, _] = args[..] else {
+        return None;
+    };
+    int_constant(*ty, 0)?;
+    let from_latch = blocks.iter().position(|&b| b == latch)?;
+    if blocks[1 - from_latch] != preheader {
+        return None;
+    }
+    let &Value::Instruction(next) = func.data.value_ref(args[from_latch]) else {
+        return None;
+    };
+    let next_inst = func.data.inst_ref(next);
+    if next_inst.opcode != Opcode::Add {
+        return None;
+    }
+    let IntBinary { args: add_args, .. } = next_inst.operand.as_int_binary()?;
+    let is_phi = |arg: ValueId| func.data.value_ref(arg) == &Value::Instruction(inst);
+    let step = match (int_of(func, add_args[0]), int_of(func, add_args[1])) {
+        (None, Some(step)) if is_phi(add_args[0]) => step,
+        (Some(step), None) if is_phi(add_args[1]) => step,
+        _ => return None,
+    };
+    Some(InductionVariable {
+        phi: inst,
+        ty: *ty,
+        start: args[1 - from_latch],
+        step: step.cast_to_i64(),
+        next,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::run_on_function;
+    use crate::pass::transform::run_on_f;
+
+    #[test]
+    fn rewrite_from_canonical() {
+        let (rewritten, printed) = run_on_f(
+            run_on_function,
+            r#"
+define i32 @f(i32 %0) {
+  br label %2
+2:
+  %3 = phi i32 [ 0, %1 ], [ %7, %2 ]
+  %4 = phi i32 [ %0, %1 ], [ %8, %2 ]
+  %5 = phi i32 [ 5, %1 ], [ %9, %2 ]
+  %6 = add i32 %4, %5
+  %7 = add i32 %3, 1
+  %8 = add i32 %4, 1
+  %9 = add i32 3, %5
+  %10 = icmp slt i32 %7, 10
+  br i1 %10, label %2, label %11
+11:
+  ret i32 %6
+}"#,
+        );
+        assert_eq!(rewritten, 2);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn create_canonical() {
+        let (rewritten, printed) = run_on_f(
+            run_on_function,
+            r#"
+define i64 @f(i64 %0) {
+  br label %2
+2:
+  %3 = phi i64 [ 10, %1 ], [ %4, %2 ]
+  %4 = add i64 %3, -2
+  %5 = icmp sgt i64 %4, 0
+  br i1 %5, label %2, label %6
+6:
+  ret i64 %4
+}"#,
+        );
+        assert_eq!(rewritten, 1);
+        insta::assert_snapshot!(printed);
+    }
+}
@@ -0,0 +1,244 @@
+//! Alignment inference.
+//!
+//! The alignment of the address of each load and store is inferred from the
+//! object it points into, i.e. an alloca, a global or a parameter with an
+//! `align` attribute, and from the offsets the `getelementptr`s in between
+//! add to it, laid out by a [`DataLayout`]. The access is annotated with
+//! that alignment when it is larger than the one it had. Allocas are
+//! aligned to at least the ABI alignment of their type.
+//!
+//! Accesses with an alignment of 0 are taken to be aligned to their type.
+
+use crate::collections::FxHashMap;
+use crate::ir::{
+    function::{
+        basic_block::BasicBlock,
+        instruction::{Alloca, Cast, GetElementPtr, InstructionId, Load, Opcode, Operand, Store},
+        param_attrs::ParameterAttribute,
+        Function,
+    },
+    module::{data_layout::DataLayout, name::Name, Module},
+    types::CompoundType,
+    value::{ConstantData, Value, ValueId},
+};
+use crate::pass::{analysis::dom_tree::DominatorTree, PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::any::Any;
+
+#[derive(Default)]
+pub struct InferAlignmentPass {
+    pub data_layout: DataLayout,
+}
+
+impl TransformPass<Function> for InferAlignmentPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        self.run_on_function(func, &FxHashMap::default());
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none().preserve::<DominatorTree<BasicBlock>>()
+    }
+}
+
+impl InferAlignmentPass {
+    pub fn new(data_layout: DataLayout) -> Self {
+        Self { data_layout }
+    }
+
+    /// Annotates the functions of `module`, knowing the alignments of its
+    /// globals, and returns how many instructions were annotated.
+    pub fn run_on_module(&self, module: &mut Module) -> usize {
+        let globals: FxHashMap<Name, u32> = module
+            .global_variables
+            .iter()
+            .map(|(name, gv)| {
+                let align = gv
+                    .align
+                    .max(self.data_layout.align_of(&module.types, gv.ty));
+                (*name, align)
+            })
+            .collect();
+        module
+            .functions
+            .iter_mut()
+            .map(|(_, func)| self.run_on_function(func, &globals))
+            .sum()
+    }
+
+    /// Annotates the allocas, loads and stores of `func` and returns how many
+    /// were annotated. `globals` are the known alignments of globals.
+    pub fn run_on_function(&self, func: &mut Function, globals: &FxHashMap<Name, u32>) -> usize {
+        if func.is_prototype() {
+            return 0;
+        }
+
+        let insts: Vec<InstructionId> = func
+            .layout
+            .block_iter()
+            .flat_map(|block| func.layout.inst_iter(block))
+            .collect();
+        let mut annotated = 0;
+
+        for &inst in &insts {
+            if let Operand::Alloca(Alloca { tys, .. }) = &func.data.inst_ref(inst).operand {
+                let abi_align = self.data_layout.align_of(&func.types, tys[0]);
+                let Operand::Alloca(Alloca { align, .. }) =
+                    &mut func.data.inst_ref_mut(inst).operand
+                else {
+                    unreachable!()
+                };
+                if *align < abi_align {
+                    *align = abi_align;
+                    annotated += 1;
+                }
+            }
+        }
+
+        for &inst in &insts {
+            let (ty, ptr, align) = match &func.data.inst_ref(inst).operand {
+                Operand::Load(Load { tys, addr, align }) => (tys[0], *addr, *align),
+                Operand::Store(Store { tys, args, align }) => (tys[0], args[1], *align),
+                _ => continue,
+            };
+            let align = match align {
+                0 => self.data_layout.align_of(&func.types, ty),
+                align => align,
+            };
+            let known = self.known_align(func, globals, ptr);
+            if known <= align {
+                continue;
+            }
+            match &mut func.data.inst_ref_mut(inst).operand {
+                Operand::Load(Load { align, .. }) | Operand::Store(Store { align, .. }) => {
+                    *align = known
+                }
+                _ => unreachable!(),
+            }
+            annotated += 1;
+        }
+
+        annotated
+    }
+
+    /// Returns the largest power of two `ptr` is known to be a multiple of.
+    fn known_align(&self, func: &Function, globals: &FxHashMap<Name, u32>, ptr: ValueId) -> u32 {
+        match func.data.value_ref(ptr) {
+            Value::Instruction(id) => {
+                let inst = func.data.inst_ref(*id);
+                match &inst.operand {
+                    Operand::Alloca(Alloca { align, .. }) => (*align).max(1),
+                    Operand::GetElementPtr(gep) => self.gep_align(func, globals, gep),
+                    Operand::Cast(Cast { arg, .. }) if inst.opcode == Opcode::Bitcast => {
+                        self.known_align(func, globals, *arg)
+                    }
+                    _ => 1,
+                }
+            }
+            Value::Argument(i) => func.params[*i]
+                .attrs
+                .iter()
+                .find_map(|attr| match attr {
+                    ParameterAttribute::Alignment(align) => Some(*align as u32),
+                    _ => None,
+                })
+                .unwrap_or(1),
+            Value::Constant(ConstantData::GlobalRef(name)) => {
+                globals.get(name).copied().unwrap_or(1)
+            }
+            _ => 1,
+        }
+    }
+
+    fn gep_align(
+        &self,
+        func: &Function,
+        globals: &FxHashMap<Name, u32>,
+        gep: &GetElementPtr,
+    ) -> u32 {
+        let dl = &self.data_layout;
+        let mut align = self.known_align(func, globals, gep.args[0]);
+        let mut offset: i64 = 0;
+        let mut ty = gep.tys[0];
+        for (i, &index) in gep.args[1..].iter().enumerate() {
+            let index = match func.data.value_ref(index) {
+                Value::Constant(ConstantData::Int(index)) => Some(index.cast_to_i64()),
+                _ => None,
+            };
+            let stride = if i == 0 {
+                dl.size_of(&func.types, ty)
+            } else {
+                let compound = func.types.get(ty).map(|ty| ty.clone());
+                match compound {
+                    Some(CompoundType::Struct(_)) => {
+                        let Some(field) = index else {
+                            return 1;
+                        };
+                        offset += dl
+                            .field_offset(&func.types, ty, field as usize)
+                            .unwrap_or(0) as i64;
+                        ty = func.types.base().element_at(ty, field as usize).unwrap();
+                        continue;
+                    }
+                    Some(CompoundType::Array(array)) => {
+                        ty = array.inner;
+                        dl.size_of(&func.types, ty)
+                    }
+                    _ => return 1,
+                }
+            };
+            match index {
+                Some(index) => offset += index * stride as i64,
+                None => align = align.min(largest_power_of_two_dividing(stride as i64)),
+            }
+        }
+        align.min(largest_power_of_two_dividing(offset))
+    }
+}
+
+fn largest_power_of_two_dividing(n: i64) -> u32 {
+    match n {
+        0 => u32::MAX,
+        n => 1 << n.trailing_zeros().min(31),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::InferAlignmentPass;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn infer_alignment() {
+        let mut module = parse_assembly(
+            r#"
+%struct.S = type { i8, i8, i32, [4 x i16] }
+
+@g = global [4 x i32] zeroinitializer, align 16
+
+define void @f(i8* align 8 %0, i64 %1) {
+  %3 = alloca %struct.S, align 1
+  %4 = getelementptr inbounds %struct.S, %struct.S* %3, i64 0, i32 1
+  store i8 1, i8* %4, align 1
+  %5 = getelementptr inbounds %struct.S, %struct.S* %3, i64 0, i32 2
+  store i32 2, i32* %5, align 1
+  %6 = getelementptr inbounds %struct.S, %struct.S* %3, i64 0, i32 3, i64 %1
+  %x = load i16, i16* %6, align 1
+  %7 = getelementptr inbounds [4 x i32], [4 x i32]* @g, i64 0, i64 2
+  store i32 4, i32* %7, align 4
+  %8 = getelementptr inbounds i8, i8* %0, i64 4
+  %9 = bitcast i8* %8 to i32*
+  store i32 5, i32* %9, align 1
+  %10 = bitcast i8* %0 to i64*
+  store i64 6, i64* %10
+  ret void
+}"#,
+        )
+        .unwrap();
+        let pass = InferAlignmentPass::default();
+        // The alloca and all the accesses but the first and the last.
+        assert_eq!(pass.run_on_module(&mut module), 5);
+        let id = module.find_function_by_name("f").unwrap();
+        let printed = format!("{:?}", module.functions()[id]);
+        insta::assert_snapshot!(printed);
+    }
+}
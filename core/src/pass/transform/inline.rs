@@ -0,0 +1,466 @@
+//! Function inlining.
+//!
+//! A direct call to a function defined in the module is replaced with a copy
+//! of the callee's body when the callee is small enough: its size, counted in
+//! instructions, must not exceed [`InlinePass::threshold`]. Callees marked
+//! `inlinehint` get the larger [`InlinePass::hint_threshold`] and `cold`
+//! ones the smaller [`InlinePass::cold_threshold`]. `alwaysinline` and
+//! `noinline`, on the callee or on the call site, bypass the size check.
+//!
+//! A call site may also carry `!inline` metadata, a string or a node holding
+//! a string, which takes precedence over everything else:
+//!
+//! ```text
+//! %r = call i32 @f(i32 %x), !inline !{!"always"}
+//! %s = call i32 @g(i32 %x), !inline !0    ; !0 = !{!"never"}
+//! ```
+//!
+//! Only the calls present before the pass runs are considered; calls copied
+//! from inlined bodies are left as they are. Recursive calls, variadic
+//! callees and callees with a personality function are never inlined.
+
+use crate::ir::{
+    function::{
+        clone::{clone_into, ValueMap},
+        instruction::{Br, InstructionId, Opcode, Operand, Phi, Ret},
+        Function, FunctionId,
+    },
+    module::{attributes::Attribute, metadata::Metadata, name::Name, Module},
+    value::{ConstantData, Value},
+};
+use crate::pass::{
+    remarks::{Remark, Remarks},
+    stats::Statistic,
+    PreservedAnalyses, TransformPass,
+};
+use crate::prelude::*;
+use core::{any::Any, mem};
+
+pub struct InlinePass {
+    /// Callees with at most this many instructions are inlined.
+    pub threshold: usize,
+    /// The threshold for callees marked `inlinehint`.
+    pub hint_threshold: usize,
+    /// The threshold for callees marked `cold`.
+    pub cold_threshold: usize,
+    pub remarks: Option<Remarks>,
+}
+
+/// The number of call sites inlined by [`InlinePass`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InlineResult(pub usize);
+
+static NUM_INLINED: Statistic =
+    Statistic::new("inline", "NumInlined", "Number of functions inlined");
+
+impl Default for InlinePass {
+    fn default() -> Self {
+        Self {
+            threshold: 225,
+            hint_threshold: 325,
+            cold_threshold: 45,
+            remarks: None,
+        }
+    }
+}
+
+impl TransformPass<Module> for InlinePass {
+    fn run_on(&self, module: &mut Module, result: &mut Box<dyn Any>) {
+        *result = Box::new(InlineResult(self.run_on_module(module)))
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+/// What a call site asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hint {
+    Always,
+    Never,
+}
+
+impl InlinePass {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_hint_threshold(mut self, hint_threshold: usize) -> Self {
+        self.hint_threshold = hint_threshold;
+        self
+    }
+
+    pub fn with_cold_threshold(mut self, cold_threshold: usize) -> Self {
+        self.cold_threshold = cold_threshold;
+        self
+    }
+
+    /// Reports the calls inlined or not to `remarks`.
+    pub fn with_remarks(mut self, remarks: Remarks) -> Self {
+        self.remarks = Some(remarks);
+        self
+    }
+
+    /// Inlines calls in `module` and returns how many were inlined.
+    pub fn run_on_module(&self, module: &mut Module) -> usize {
+        let ids: Vec<FunctionId> = module.functions.iter().map(|(id, _)| id).collect();
+        let mut inlined = 0;
+        for id in ids {
+            if module.functions[id].is_prototype() {
+                continue;
+            }
+            // The caller is taken out of the module while its callees are
+            // copied into it. A recursive call then finds a prototype in its
+            // place and is left alone.
+            let placeholder = {
+                let caller = &module.functions[id];
+                Function::new("", caller.result_ty, vec![], false, caller.types.clone())
+            };
+            let mut caller = mem::replace(&mut module.functions[id], placeholder);
+            let calls: Vec<(InstructionId, FunctionId)> = caller
+                .layout
+                .block_iter()
+                .flat_map(|block| caller.layout.inst_iter(block))
+                .filter_map(|inst| Some((inst, callee_of(module, &caller, inst)?)))
+                .collect();
+            let caller_name = caller.name.clone();
+            for (call, callee) in calls {
+                let callee = &module.functions[callee];
+                match self.should_inline(module, &caller, call, callee) {
+                    Ok(()) => {
+                        inline_call(&mut caller, call, callee);
+                        inlined += 1;
+                        NUM_INLINED.inc();
+                        self.remark(
+                            Remark::passed("inline", "Inlined", &caller_name)
+                                .arg("Callee", &callee.name)
+                                .string(" inlined into ")
+                                .arg("Caller", &caller_name),
+                        );
+                    }
+                    Err(reason) => self.remark(
+                        Remark::missed("inline", "NotInlined", &caller_name)
+                            .arg("Callee", &callee.name)
+                            .string(" not inlined into ")
+                            .arg("Caller", &caller_name)
+                            .string(": ")
+                            .arg("Reason", reason),
+                    ),
+                }
+            }
+            module.functions[id] = caller;
+        }
+        inlined
+    }
+
+    fn remark(&self, remark: Remark) {
+        if let Some(remarks) = &self.remarks {
+            remarks.emit(remark)
+        }
+    }
+
+    /// Returns why `call` is not to be inlined, if so.
+    fn should_inline(
+        &self,
+        module: &Module,
+        caller: &Function,
+        call: InstructionId,
+        callee: &Function,
+    ) -> Result<(), &'static str> {
+        if callee.is_prototype() {
+            return Err("callee is a declaration");
+        }
+        if callee.is_var_arg() {
+            return Err("callee is variadic");
+        }
+        if callee.personality.is_some() {
+            return Err("callee has a personality function");
+        }
+        let inst = caller.data.inst_ref(call);
+        match call_site_hint(module, inst.metadata.get("inline")) {
+            Some(Hint::Always) => return Ok(()),
+            Some(Hint::Never) => return Err("call site is marked never to be inlined"),
+            None => {}
+        }
+
+        let has_attr = |attrs: &[Attribute], attr: Attribute| {
+            attrs.iter().any(|a| match a {
+                Attribute::Ref(id) => module
+                    .attributes
+                    .get(id)
+                    .is_some_and(|group| group.contains(&attr)),
+                a => *a == attr,
+            })
+        };
+        let site_attrs = &inst.operand.as_call().unwrap().func_attrs;
+        if has_attr(site_attrs, Attribute::NoInline) {
+            return Err("call site is noinline");
+        }
+        if has_attr(site_attrs, Attribute::AlwaysInline) {
+            return Ok(());
+        }
+        if has_attr(&callee.func_attrs, Attribute::NoInline) {
+            return Err("callee is noinline");
+        }
+        if has_attr(&callee.func_attrs, Attribute::AlwaysInline) {
+            return Ok(());
+        }
+
+        let threshold = if has_attr(&callee.func_attrs, Attribute::Cold) {
+            self.cold_threshold
+        } else if has_attr(&callee.func_attrs, Attribute::InlineHint) {
+            self.hint_threshold
+        } else {
+            self.threshold
+        };
+        if size_of(callee) > threshold {
+            return Err("callee is too large");
+        }
+        Ok(())
+    }
+}
+
+/// Returns the function defined in `module` that `inst` calls directly.
+fn callee_of(module: &Module, caller: &Function, inst: InstructionId) -> Option<FunctionId> {
+    let call = caller.data.inst_ref(inst).operand.as_call()?;
+    match caller.data.value_ref(call.args[0]) {
+        Value::Constant(ConstantData::GlobalRef(Name::Name(name))) => {
+            let id = module.find_function_by_name(name)?;
+            (module.functions[id].params().len() == call.args.len() - 1).then_some(id)
+        }
+        _ => None,
+    }
+}
+
+/// Reads the `!inline` metadata of a call site, looking up named nodes in `module`.
+fn call_site_hint(module: &Module, meta: Option<&Metadata>) -> Option<Hint> {
+    match meta? {
+        Metadata::String(s) if s == "always" => Some(Hint::Always),
+        Metadata::String(s) if s == "never" => Some(Hint::Never),
+        Metadata::Name(name) => call_site_hint(module, module.metas.get(name)),
+        Metadata::Node(elems) => call_site_hint(module, elems.first()),
+        _ => None,
+    }
+}
+
+fn size_of(func: &Function) -> usize {
+    func.layout
+        .block_iter()
+        .map(|block| func.layout.inst_iter(block).count())
+        .sum()
+}
+
+/// Replaces `call` in `caller` with a copy of the body of `callee`.
+fn inline_call(caller: &mut Function, call: InstructionId, callee: &Function) {
+    let block = caller.layout.block_of(call).unwrap();
+    let next = caller
+        .layout
+        .inst_iter(block)
+        .skip_while(|&i| i != call)
+        .nth(1)
+        .unwrap();
+    let after = caller.split_block(block, next);
+    let br = (*caller.layout.block_node(block).last_inst()).unwrap();
+    caller.erase_instruction(br).unwrap();
+
+    let call_args = caller.data.inst_ref(call).operand.args()[1..].to_vec();
+    let mut vmap = ValueMap::new();
+    for (i, &arg) in call_args.iter().enumerate() {
+        vmap.set_arg(i, caller.data.value_ref(arg).clone());
+    }
+    clone_into(caller, callee, &mut vmap, Some(after));
+
+    // Static allocas go to the entry block of the caller so that inlining
+    // into a loop does not grow the stack at every iteration.
+    let caller_entry = caller.entry_block().unwrap();
+    let caller_first = (*caller.layout.block_node(caller_entry).first_inst()).unwrap();
+    let callee_entry = callee.entry_block().unwrap();
+    for inst in callee.layout.inst_iter(callee_entry) {
+        if callee.data.inst_ref(inst).opcode == Opcode::Alloca {
+            let alloca = vmap.inst(inst).unwrap();
+            caller.layout.remove_inst(alloca);
+            caller.layout.insert_inst_before(alloca, caller_first);
+            caller.data.inst_ref_mut(alloca).parent = caller_entry;
+        }
+    }
+
+    let mut returns = vec![];
+    for b in callee.layout.block_iter() {
+        let new_block = vmap.block(b).unwrap();
+        let term = (*caller.layout.block_node(new_block).last_inst()).unwrap();
+        let Operand::Ret(Ret { val, .. }) = caller.data.inst_ref(term).operand else {
+            continue;
+        };
+        returns.push((new_block, val));
+        caller.data.remove_uses(term);
+        let inst = caller.data.inst_ref_mut(term);
+        inst.opcode = Opcode::Br;
+        inst.operand = Operand::Br(Br { block: after });
+        caller.data.block_ref_mut(new_block).succs.insert(after);
+        caller.data.block_ref_mut(after).preds.insert(new_block);
+    }
+
+    let entry = vmap.block(callee_entry).unwrap();
+    let br = caller.data.create_inst(
+        Opcode::Br
+            .with_block(block)
+            .with_operand(Operand::Br(Br { block: entry })),
+    );
+    caller.layout.append_inst(br, block);
+    caller.data.block_ref_mut(block).succs.insert(entry);
+    caller.data.block_ref_mut(entry).preds.insert(block);
+
+    if !caller.data.users_of(call).is_empty() {
+        let result = match returns.as_slice() {
+            [(_, Some(val))] => *val,
+            [] => caller
+                .data
+                .create_value(Value::Constant(ConstantData::Undef)),
+            _ => {
+                let (args, blocks) = returns.iter().map(|&(b, v)| (v.unwrap(), b)).unzip();
+                let phi =
+                    caller
+                        .data
+                        .create_inst(Opcode::Phi.with_block(after).with_operand(Operand::Phi(
+                            Phi {
+                                ty: callee.result_ty,
+                                args,
+                                blocks,
+                            },
+                        )));
+                caller.layout.insert_inst_at_start(phi, after);
+                caller.data.create_value(Value::Instruction(phi))
+            }
+        };
+        caller.replace_all_uses_with(call, result);
+    }
+    caller.erase_instruction(call).unwrap();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    fn run(pass: InlinePass, source: &str) -> (usize, String) {
+        let mut module = parse_assembly(source).unwrap();
+        let inlined = pass.run_on_module(&mut module);
+        let main = module.find_function_by_name("main").unwrap();
+        (inlined, format!("{:?}", module.functions()[main]))
+    }
+
+    const CALLEES: &str = r#"
+define i32 @small(i32 %0) {
+  %2 = add i32 %0, 1
+  ret i32 %2
+}
+
+define i32 @big(i32 %0) {
+  %2 = alloca i32, align 4
+  store i32 %0, i32* %2, align 4
+  %3 = icmp slt i32 %0, 0
+  br i1 %3, label %4, label %6
+4:
+  %5 = sub i32 0, %0
+  ret i32 %5
+6:
+  %7 = load i32, i32* %2, align 4
+  ret i32 %7
+}
+
+define i32 @hinted(i32 %0) #0 {
+  %2 = add i32 %0, 2
+  %3 = add i32 %2, 3
+  ret i32 %3
+}
+
+define i32 @chilly(i32 %0) #1 {
+  %2 = add i32 %0, 4
+  ret i32 %2
+}
+
+attributes #0 = { inlinehint }
+attributes #1 = { cold }
+"#;
+
+    #[test]
+    fn inline_small_calls() {
+        let (inlined, printed) = run(
+            InlinePass::default(),
+            &format!(
+                r#"{}
+define i32 @main(i32 %0) {{
+  %2 = call i32 @small(i32 %0)
+  %3 = call i32 @big(i32 %2)
+  %4 = call i32 @main(i32 %3)
+  ret i32 %4
+}}"#,
+                CALLEES
+            ),
+        );
+        assert_eq!(inlined, 2);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn thresholds_and_hints() {
+        let source = format!(
+            r#"{}
+define i32 @main(i32 %0) {{
+  %2 = call i32 @small(i32 %0)
+  %3 = call i32 @hinted(i32 %2)
+  %4 = call i32 @chilly(i32 %3)
+  %5 = call i32 @big(i32 %4), !inline !0
+  %6 = call i32 @small(i32 %5), !inline !{{!"never"}}
+  ret i32 %6
+}}
+
+!0 = !{{!"always"}}"#,
+            CALLEES
+        );
+
+        // `small` and `chilly` are 2 instructions large and `hinted` is 3.
+        let pass = InlinePass::new()
+            .with_threshold(2)
+            .with_hint_threshold(3)
+            .with_cold_threshold(1);
+        let (inlined, printed) = run(pass, &source);
+        assert_eq!(inlined, 3);
+        insta::assert_snapshot!(printed);
+
+        let pass = InlinePass::new()
+            .with_threshold(0)
+            .with_hint_threshold(0)
+            .with_cold_threshold(0);
+        let (inlined, _) = run(pass, &source);
+        // Only the call with `!inline !{!"always"}` is left.
+        assert_eq!(inlined, 1);
+    }
+
+    #[test]
+    fn remarks() {
+        let remarks = Remarks::new();
+        let source = format!(
+            "{}\ndefine i32 @main(i32 %0) {{\n  %2 = call i32 @small(i32 %0)\n  %3 = call i32 @big(i32 %2)\n  ret i32 %3\n}}",
+            CALLEES
+        );
+        let pass = InlinePass::new()
+            .with_threshold(2)
+            .with_remarks(remarks.clone());
+        assert_eq!(run(pass, &source).0, 1);
+        let messages: Vec<String> = remarks.take().iter().map(|r| r.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "main: small inlined into main",
+                "main: big not inlined into main: callee is too large"
+            ]
+        );
+    }
+}
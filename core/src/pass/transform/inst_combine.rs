@@ -0,0 +1,480 @@
+//! Peephole simplifications.
+//!
+//! Each [`Rule`] looks at a single instruction and either finds a simpler
+//! value computing the same thing, or rewrites the instruction in place into
+//! a cheaper or canonical form. Instructions are revisited whenever one of
+//! their operands changes, until no rule applies anymore. Instructions left
+//! unused by a simplification are removed.
+//!
+//! New rules are written as plain functions and listed in [`RULES`].
+
+use crate::collections::FxHashSet;
+use crate::ir::{
+    function::{
+        basic_block::BasicBlock,
+        instruction::{
+            Cast, GetElementPtr, ICmp, ICmpCond, InstructionId, IntBinary, Opcode, Operand,
+        },
+        pattern::{m_c_mul, m_const_int, m_value, match_inst},
+        Function,
+    },
+    module::Module,
+    types::Type,
+    value::{ConstantData, ConstantInt, Value, ValueId},
+};
+use crate::pass::{analysis::dom_tree::DominatorTree, PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct InstCombinePass;
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, function) in module.functions_mut().iter_mut() {
+        run_on_function(function);
+    }
+}
+
+impl TransformPass<Function> for InstCombinePass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    /// Terminators are never touched, so the CFG is kept.
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none().preserve::<DominatorTree<BasicBlock>>()
+    }
+}
+
+/// The outcome of a rule applied to an instruction.
+pub enum Combined {
+    /// The instruction computes the value, and is to be removed.
+    Replaced(ValueId),
+    /// The instruction was rewritten in place.
+    Rewritten,
+}
+
+/// A peephole rule. Returns `None`, leaving the function untouched, if the
+/// rule doesn't apply to the instruction.
+pub type Rule = fn(&mut Function, InstructionId) -> Option<Combined>;
+
+/// The rules applied by [`run_on_function`], in order.
+pub const RULES: &[Rule] = &[
+    fold_constants,
+    identities,
+    mul_to_shl,
+    cast_of_cast,
+    canonicalize_icmp,
+    fold_gep_indices,
+];
+
+/// Simplifies `func` and returns the number of rules applied.
+pub fn run_on_function(func: &mut Function) -> usize {
+    run_with_rules(func, RULES)
+}
+
+/// Simplifies `func` with `rules` only.
+pub fn run_with_rules(func: &mut Function, rules: &[Rule]) -> usize {
+    if func.is_prototype() {
+        return 0;
+    }
+
+    let mut worklist: Vec<InstructionId> = func
+        .layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .collect();
+    worklist.reverse();
+    let mut removed = FxHashSet::default();
+    let mut combined = 0;
+
+    while let Some(id) = worklist.pop() {
+        if removed.contains(&id) {
+            continue;
+        }
+        let operands = operand_insts(func, id);
+        let Some(result) = rules.iter().find_map(|rule| rule(func, id)) else {
+            continue;
+        };
+        combined += 1;
+        worklist.extend(func.data.users_of(id).iter().copied());
+        match result {
+            Combined::Replaced(val) => {
+                func.replace_all_uses_with(id, val);
+                remove_if_dead(func, id, &mut removed);
+            }
+            Combined::Rewritten => worklist.push(id),
+        }
+        for op in operands {
+            remove_if_dead(func, op, &mut removed);
+        }
+    }
+
+    combined
+}
+
+fn operand_insts(func: &Function, id: InstructionId) -> Vec<InstructionId> {
+    func.data
+        .inst_ref(id)
+        .operand
+        .args()
+        .iter()
+        .filter_map(|&arg| match func.data.value_ref(arg) {
+            Value::Instruction(inst) => Some(*inst),
+            _ => None,
+        })
+        .collect()
+}
+
+fn remove_if_dead(func: &mut Function, id: InstructionId, removed: &mut FxHashSet<InstructionId>) {
+    if removed.contains(&id)
+        || !func.data.users_of(id).is_empty()
+        || func.data.inst_ref(id).opcode.has_side_effects()
+    {
+        return;
+    }
+    let operands = operand_insts(func, id);
+    func.remove_inst(id);
+    removed.insert(id);
+    for op in operands {
+        remove_if_dead(func, op, removed);
+    }
+}
+
+/// Instructions whose arguments are all constants → their value.
+fn fold_constants(func: &mut Function, id: InstructionId) -> Option<Combined> {
+    let konst = func
+        .data
+        .inst_ref(id)
+        .fold_consts(&func.data, &func.types)?;
+    Some(Combined::Replaced(
+        func.data.create_value(Value::Constant(konst)),
+    ))
+}
+
+/// `x + 0`, `x - 0`, `x * 1`, `x / 1`, `x << 0`, `x >> 0`, `x & -1`, `x & x` → `x`.
+/// `x * 0`, `x & 0`, `x % 1`, `x - x` → `0`.
+fn identities(func: &mut Function, id: InstructionId) -> Option<Combined> {
+    let inst = func.data.inst_ref(id);
+    let IntBinary { ty, args, .. } = inst.operand.as_int_binary()?.clone();
+    let (lhs, rhs) = (int_of(func, args[0]), int_of(func, args[1]));
+    let is = |c: Option<ConstantInt>, v: i128| c.is_some_and(|c| c.cast_to_i128() == v);
+    let same = func.data.value_ref(args[0]) == func.data.value_ref(args[1])
+        && !matches!(func.data.value_ref(args[0]), Value::Constant(_));
+    let zero = || int_constant(ty, 0);
+
+    let val = match inst.opcode {
+        Opcode::Add if is(rhs, 0) => args[0],
+        Opcode::Add if is(lhs, 0) => args[1],
+        Opcode::Sub if is(rhs, 0) => args[0],
+        Opcode::Sub if same => func.data.create_value(zero()?),
+        Opcode::Mul if is(rhs, 1) => args[0],
+        Opcode::Mul if is(lhs, 1) => args[1],
+        Opcode::Mul if is(rhs, 0) => args[1],
+        Opcode::Mul if is(lhs, 0) => args[0],
+        Opcode::SDiv if is(rhs, 1) => args[0],
+        Opcode::SRem if is(rhs, 1) => func.data.create_value(zero()?),
+        Opcode::Shl | Opcode::LShr if is(rhs, 0) => args[0],
+        Opcode::And if rhs.is_some_and(is_all_ones) || same => args[0],
+        Opcode::And if lhs.is_some_and(is_all_ones) => args[1],
+        Opcode::And if is(rhs, 0) => args[1],
+        Opcode::And if is(lhs, 0) => args[0],
+        _ => return None,
+    };
+    Some(Combined::Replaced(val))
+}
+
+/// `x * 2ⁿ` → `x << n`.
+fn mul_to_shl(func: &mut Function, id: InstructionId) -> Option<Combined> {
+    let (mut x, mut c) = (None, None);
+    if !match_inst(func, id, m_c_mul(m_value(&mut x), m_const_int(&mut c))) {
+        return None;
+    }
+    let (x, c) = (x?, c?);
+    let n = c.cast_to_i128();
+    if n <= 1 || n.count_ones() != 1 {
+        return None;
+    }
+    let shift = func
+        .data
+        .create_value(Value::Constant(ConstantData::Int(with_value(
+            c,
+            n.trailing_zeros() as i64,
+        )?)));
+    rewrite(func, id, |opcode, operand| {
+        *opcode = Opcode::Shl;
+        operand.as_int_binary_mut().unwrap().args = [x, shift];
+    });
+    Some(Combined::Rewritten)
+}
+
+/// `bitcast x to T` where `x: T` → `x`.
+/// `zext (zext x)`, `sext (zext x)` → `zext x`.
+/// `sext (sext x)`, `trunc (trunc x)`, `bitcast (bitcast x)` → `sext x`, `trunc x`, `bitcast x`.
+/// `trunc (zext x to T) to U` where `x: U` → `x`, likewise for `sext`.
+fn cast_of_cast(func: &mut Function, id: InstructionId) -> Option<Combined> {
+    let inst = func.data.inst_ref(id);
+    let Cast { tys, arg } = inst.operand.as_cast()?.clone();
+    let opcode = inst.opcode;
+    if opcode == Opcode::Bitcast && tys[0] == tys[1] {
+        return Some(Combined::Replaced(arg));
+    }
+
+    let Value::Instruction(inner_id) = *func.data.value_ref(arg) else {
+        return None;
+    };
+    let inner = func.data.inst_ref(inner_id);
+    let inner_opcode = inner.opcode;
+    let Cast {
+        tys: inner_tys,
+        arg: inner_arg,
+    } = inner.operand.as_cast()?.clone();
+
+    let new_opcode = match (inner_opcode, opcode) {
+        (Opcode::Zext | Opcode::Sext, Opcode::Trunc) if inner_tys[0] == tys[1] => {
+            return Some(Combined::Replaced(inner_arg))
+        }
+        (Opcode::Zext, Opcode::Zext | Opcode::Sext) => Opcode::Zext,
+        (Opcode::Sext, Opcode::Sext) => Opcode::Sext,
+        (Opcode::Trunc, Opcode::Trunc) => Opcode::Trunc,
+        (Opcode::Bitcast, Opcode::Bitcast) => Opcode::Bitcast,
+        _ => return None,
+    };
+    rewrite(func, id, |opcode, operand| {
+        *opcode = new_opcode;
+        *operand.as_cast_mut().unwrap() = Cast {
+            tys: [inner_tys[0], tys[1]],
+            arg: inner_arg,
+        };
+    });
+    Some(Combined::Rewritten)
+}
+
+/// `icmp x, x` → `true` or `false`.
+/// `icmp C, x` → `icmp x, C` with the condition swapped.
+/// `icmp sle x, C` → `icmp slt x, C+1`, and likewise for the other
+/// non-strict conditions, as long as `C±1` doesn't overflow.
+fn canonicalize_icmp(func: &mut Function, id: InstructionId) -> Option<Combined> {
+    let ICmp { args, cond, .. } = func.data.inst_ref(id).operand.as_icmp()?.clone();
+
+    if func.data.value_ref(args[0]) == func.data.value_ref(args[1])
+        && !matches!(func.data.value_ref(args[0]), Value::Constant(_))
+    {
+        let holds = matches!(
+            cond,
+            ICmpCond::Eq | ICmpCond::Uge | ICmpCond::Ule | ICmpCond::Sge | ICmpCond::Sle
+        );
+        let val = Value::Constant(ConstantData::Int(ConstantInt::Int1(holds)));
+        return Some(Combined::Replaced(func.data.create_value(val)));
+    }
+
+    match (int_of(func, args[0]), int_of(func, args[1])) {
+        (Some(_), Some(_)) | (None, None) => None,
+        (Some(_), None) => {
+            rewrite(func, id, |_, operand| {
+                let icmp = operand.as_icmp_mut().unwrap();
+                icmp.args.swap(0, 1);
+                icmp.cond = icmp.cond.swapped();
+            });
+            Some(Combined::Rewritten)
+        }
+        (None, Some(c)) => {
+            let v = i64::try_from(c.cast_to_i128()).ok()?;
+            let (new_cond, new_c) = match cond {
+                ICmpCond::Sle => (ICmpCond::Slt, with_value(c, v.checked_add(1)?)?),
+                ICmpCond::Sge => (ICmpCond::Sgt, with_value(c, v.checked_sub(1)?)?),
+                ICmpCond::Ule if !is_all_ones(c) => (ICmpCond::Ult, wrapping_add(c, 1)?),
+                ICmpCond::Uge if v != 0 => (ICmpCond::Ugt, wrapping_add(c, -1)?),
+                _ => return None,
+            };
+            let new_c = func
+                .data
+                .create_value(Value::Constant(ConstantData::Int(new_c)));
+            rewrite(func, id, |_, operand| {
+                let icmp = operand.as_icmp_mut().unwrap();
+                icmp.args[1] = new_c;
+                icmp.cond = new_cond;
+            });
+            Some(Combined::Rewritten)
+        }
+    }
+}
+
+/// `getelementptr T, T* p, 0` → `p`.
+/// `getelementptr T, (getelementptr U, U* p, ..., C1), C2` where the inner
+/// `getelementptr` points to a `T` → `getelementptr U, U* p, ..., C1+C2`,
+/// provided `C1` indexes an array or is the only index.
+fn fold_gep_indices(func: &mut Function, id: InstructionId) -> Option<Combined> {
+    let gep = func.data.inst_ref(id).operand.as_gep()?;
+    if gep.args.len() != 2 {
+        return None;
+    }
+    let c2 = int_of(func, gep.args[1])?;
+    if c2.cast_to_i64() == 0 {
+        return Some(Combined::Replaced(gep.args[0]));
+    }
+
+    let Value::Instruction(inner_id) = *func.data.value_ref(gep.args[0]) else {
+        return None;
+    };
+    let inner = func.data.inst_ref(inner_id).operand.as_gep()?;
+    let c1 = int_of(func, *inner.args.last().unwrap())?;
+    let indexed = indexed_types(func, inner)?;
+    let last = indexed.len() - 1;
+    if indexed[last] != gep.tys[0] || (last > 0 && func.types.is_struct(indexed[last - 1])) {
+        return None;
+    }
+
+    let sum = with_value(c1, c1.cast_to_i64().checked_add(c2.cast_to_i64())?)?;
+    let mut new_gep = inner.clone();
+    new_gep.inbounds &= gep.inbounds;
+    *new_gep.args.last_mut().unwrap() = func
+        .data
+        .create_value(Value::Constant(ConstantData::Int(sum)));
+    rewrite(func, id, |_, operand| {
+        *operand.as_gep_mut().unwrap() = new_gep;
+    });
+    Some(Combined::Rewritten)
+}
+
+/// Returns the types pointed to by `gep` after each of its indices.
+fn indexed_types(func: &Function, gep: &GetElementPtr) -> Option<Vec<Type>> {
+    let mut ty = gep.tys[0];
+    let mut tys = vec![ty];
+    for &idx in &gep.args[2..] {
+        ty = if func.types.is_struct(ty) {
+            let i = int_of(func, idx)?.cast_to_usize();
+            func.types.base().element_at(ty, i)?
+        } else {
+            func.types.get_element(ty)?
+        };
+        tys.push(ty);
+    }
+    Some(tys)
+}
+
+/// Rewrites the instruction `id`, keeping track of its uses.
+fn rewrite(func: &mut Function, id: InstructionId, f: impl FnOnce(&mut Opcode, &mut Operand)) {
+    func.data.remove_uses(id);
+    let inst = func.data.inst_ref_mut(id);
+    f(&mut inst.opcode, &mut inst.operand);
+    func.data.validate_inst_uses(id);
+}
+
+pub(crate) fn int_of(func: &Function, val: ValueId) -> Option<ConstantInt> {
+    match func.data.value_ref(val) {
+        Value::Constant(ConstantData::Int(i)) => Some(*i),
+        _ => None,
+    }
+}
+
+pub(crate) fn int_constant(ty: Type, v: i64) -> Option<Value> {
+    let i = if ty.is_i1() {
+        ConstantInt::Int1(v != 0)
+    } else if ty.is_i8() {
+        ConstantInt::Int8(v as i8)
+    } else if ty.is_i16() {
+        ConstantInt::Int16(v as i16)
+    } else if ty.is_i32() {
+        ConstantInt::Int32(v as i32)
+    } else if ty.is_i64() {
+        ConstantInt::Int64(v)
+    } else {
+        return None;
+    };
+    Some(Value::Constant(ConstantData::Int(i)))
+}
+
+/// Returns `v` as a constant of the same type as `c`, if it fits.
+fn with_value(c: ConstantInt, v: i64) -> Option<ConstantInt> {
+    Some(match c {
+        ConstantInt::Int1(_) => return None,
+        ConstantInt::Int8(_) => ConstantInt::Int8(v.try_into().ok()?),
+        ConstantInt::Int16(_) => ConstantInt::Int16(v.try_into().ok()?),
+        ConstantInt::Int32(_) => ConstantInt::Int32(v.try_into().ok()?),
+        ConstantInt::Int64(_) => ConstantInt::Int64(v),
+        ConstantInt::Int128(_) => ConstantInt::Int128(v.into()),
+    })
+}
+
+fn wrapping_add(c: ConstantInt, v: i64) -> Option<ConstantInt> {
+    Some(match c {
+        ConstantInt::Int1(_) => return None,
+        ConstantInt::Int8(i) => ConstantInt::Int8(i.wrapping_add(v as i8)),
+        ConstantInt::Int16(i) => ConstantInt::Int16(i.wrapping_add(v as i16)),
+        ConstantInt::Int32(i) => ConstantInt::Int32(i.wrapping_add(v as i32)),
+        ConstantInt::Int64(i) => ConstantInt::Int64(i.wrapping_add(v)),
+        ConstantInt::Int128(i) => ConstantInt::Int128(i.wrapping_add(v.into())),
+    })
+}
+
+fn is_all_ones(c: ConstantInt) -> bool {
+    match c {
+        ConstantInt::Int1(b) => b,
+        _ => c.cast_to_i128() == -1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::pass::transform::run_on_f;
+
+    #[test]
+    fn arithmetic() {
+        let (combined, printed) = run_on_f(
+            super::run_on_function,
+            r#"
+define i32 @f(i32 %0, i32 %1) {
+  %3 = add i32 %0, 0
+  %4 = mul nsw i32 1, %3
+  %5 = mul nsw i32 %4, 8
+  %6 = sub i32 %1, %1
+  %7 = and i32 %5, -1
+  %8 = add i32 %7, %6
+  %9 = srem i32 %8, 1
+  %10 = add i32 %8, %9
+  ret i32 %10
+}"#,
+        );
+        assert_eq!(combined, 8);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn casts_and_icmps() {
+        let (combined, printed) = run_on_f(
+            super::run_on_function,
+            r#"
+define i1 @f(i8 %0, i32 %1) {
+  %3 = zext i8 %0 to i32
+  %4 = sext i32 %3 to i64
+  %5 = sext i8 %0 to i32
+  %6 = trunc i32 %5 to i8
+  %7 = icmp sle i64 %4, 10
+  %8 = icmp ult i8 3, %6
+  %9 = icmp uge i32 %1, %1
+  %10 = and i1 %7, %8
+  %11 = and i1 %10, %9
+  ret i1 %11
+}"#,
+        );
+        assert_eq!(combined, 6);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn gep_indices() {
+        let (combined, printed) = run_on_f(
+            super::run_on_function,
+            r#"
+define i32 @f([4 x i32]* %0) {
+  %2 = getelementptr inbounds [4 x i32], [4 x i32]* %0, i64 0, i64 1
+  %3 = getelementptr inbounds i32, i32* %2, i64 2
+  %4 = getelementptr inbounds i32, i32* %3, i64 0
+  %5 = load i32, i32* %4, align 4
+  ret i32 %5
+}"#,
+        );
+        assert_eq!(combined, 2);
+        insta::assert_snapshot!(printed);
+    }
+}
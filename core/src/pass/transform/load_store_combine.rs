@@ -0,0 +1,634 @@
+//! Load and store combining.
+//!
+//! Frontends access packed structs and byte buffers one byte at a time. This
+//! pass merges such accesses into wider ones, assuming a little-endian target:
+//!
+//! - Runs of `store i8 C` to consecutive bytes of the same pointer in a block
+//!   become `store i16`, `store i32` or `store i64` of the combined
+//!   constant. The loads and stores in between must not alias the pointer
+//!   (see [`AliasAnalysis`]), and calls end a run.
+//! - `or` trees of byte loads shifted into place, e.g.
+//!   `(zext (load i8 %p)) | (zext (load i8 %p+1)) << 8`, become a single
+//!   load when no store in between may alias the pointer.
+//!
+//! Unless [`LoadStoreCombinePass::allow_unaligned`] is set, accesses are
+//! only widened up to their alignment, and `i32` and `i64` accesses with a
+//! smaller alignment are split into aligned pieces.
+
+use super::inst_combine::{int_constant, int_of};
+use crate::ir::{
+    function::{
+        basic_block::BasicBlock,
+        builder::Builder,
+        instruction::{
+            Cast, GetElementPtr, InstructionId, IntBinary, Load, Opcode, Operand, Store,
+        },
+        Function,
+    },
+    module::Module,
+    types::{Type, I16, I32, I64, I8},
+    value::{Value, ValueId},
+};
+use crate::pass::{
+    analysis::{
+        alias::{AliasAnalysis, AliasResult},
+        dom_tree::DominatorTree,
+    },
+    PreservedAnalyses, TransformPass,
+};
+use crate::prelude::*;
+use alloc::collections::BTreeMap;
+use core::any::Any;
+
+pub struct LoadStoreCombinePass {
+    /// Whether the target accesses memory at any alignment, as x86-64 does.
+    pub allow_unaligned: bool,
+}
+
+impl Default for LoadStoreCombinePass {
+    fn default() -> Self {
+        Self {
+            allow_unaligned: true,
+        }
+    }
+}
+
+impl TransformPass<Function> for LoadStoreCombinePass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        self.run_on_function(func);
+    }
+
+    /// Only loads, stores and the values around them change, so the CFG is kept.
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none().preserve::<DominatorTree<BasicBlock>>()
+    }
+}
+
+/// Byte stores to consecutive addresses, in order.
+struct StoreRun {
+    base: ValueId,
+    stores: Vec<InstructionId>,
+}
+
+/// A load shifted into place in an `or` tree.
+struct LoadLeaf {
+    load: InstructionId,
+    offset: i64,
+    size: i64,
+    shift: i64,
+}
+
+impl LoadStoreCombinePass {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_allow_unaligned(mut self, allow_unaligned: bool) -> Self {
+        self.allow_unaligned = allow_unaligned;
+        self
+    }
+
+    pub fn run_on_module(&self, module: &mut Module) {
+        for (_, function) in module.functions_mut().iter_mut() {
+            self.run_on_function(function);
+        }
+    }
+
+    /// Combines and splits the accesses in `func` and returns how many new
+    /// accesses were made.
+    pub fn run_on_function(&self, func: &mut Function) -> usize {
+        if func.is_prototype() {
+            return 0;
+        }
+
+        let mut changed = 0;
+        if !self.allow_unaligned {
+            changed += split_unaligned(func);
+        }
+        changed += self.merge_stores(func);
+        changed += self.combine_loads(func);
+        changed
+    }
+
+    fn merge_stores(&self, func: &mut Function) -> usize {
+        let aa = AliasAnalysis::new(func);
+        let mut runs = vec![];
+        for block in func.layout.block_iter() {
+            let mut run: Option<StoreRun> = None;
+            for inst in func.layout.inst_iter(block) {
+                if let Some((base, _)) = byte_store(func, inst) {
+                    match &mut run {
+                        Some(r) if same_value(func, r.base, base) => r.stores.push(inst),
+                        _ => {
+                            runs.extend(run.replace(StoreRun {
+                                base,
+                                stores: vec![inst],
+                            }));
+                        }
+                    }
+                    continue;
+                }
+                let Some(r) = &run else {
+                    continue;
+                };
+                let inst = func.data.inst_ref(inst);
+                let no_alias = |ptr: ValueId| aa.alias(func, ptr, r.base) == AliasResult::NoAlias;
+                let keeps_run = match &inst.operand {
+                    Operand::Load(Load { addr, .. }) => no_alias(*addr),
+                    Operand::Store(Store { args, .. }) => no_alias(args[1]),
+                    _ => !inst.opcode.may_read_memory() && !inst.opcode.may_write_memory(),
+                };
+                if !keeps_run {
+                    runs.extend(run.take());
+                }
+            }
+            runs.extend(run);
+        }
+
+        runs.iter().map(|run| self.merge_store_run(func, run)).sum()
+    }
+
+    fn merge_store_run(&self, func: &mut Function, run: &StoreRun) -> usize {
+        // The last store to each byte.
+        let mut bytes: BTreeMap<i64, (InstructionId, u8)> = BTreeMap::new();
+        for &store in &run.stores {
+            let (_, offset) = byte_store(func, store).unwrap();
+            let Store { args, .. } = func.data.inst_ref(store).operand.as_store().unwrap();
+            let byte = int_of(func, args[0]).unwrap().cast_to_i64() as u8;
+            bytes.insert(offset, (store, byte));
+        }
+
+        let mut chunks = vec![];
+        let offsets: Vec<i64> = bytes.keys().copied().collect();
+        let mut pos = 0;
+        while pos < offsets.len() {
+            let len = offsets[pos..]
+                .iter()
+                .enumerate()
+                .take_while(|&(i, &offset)| offset == offsets[pos] + i as i64)
+                .count();
+            let (store, _) = bytes[&offsets[pos]];
+            let align = func.data.inst_ref(store).operand.as_store().unwrap().align;
+            let width = [8, 4, 2]
+                .into_iter()
+                .find(|&w| w <= len && (self.allow_unaligned || align as usize >= w));
+            match width {
+                Some(width) => {
+                    chunks.push((offsets[pos], width));
+                    pos += width;
+                }
+                None => pos += 1,
+            }
+        }
+
+        let last = *run.stores.last().unwrap();
+        let mut covered = vec![];
+        for &(start, width) in &chunks {
+            let (first, _) = bytes[&start];
+            let Store { tys, args, align } = func.data.inst_ref(first).operand.as_store().unwrap();
+            let (ptr, ptr_ty, align) = (args[1], tys[1], *align);
+            let mut le = [0u8; 8];
+            for i in 0..width {
+                le[i] = bytes[&(start + i as i64)].1;
+            }
+            let ty = int_type(width);
+            let val = int_constant(ty, i64::from_le_bytes(le)).unwrap();
+
+            let mut builder = Builder::new(func);
+            builder.position_before(last);
+            let wide_ptr_ty = builder.func().types.ptr_to(ty);
+            let wide_ptr = builder.build_cast(Opcode::Bitcast, ptr_ty, wide_ptr_ty, ptr);
+            let val = builder.value(val);
+            let store = builder.build_store(ty, val, wide_ptr);
+            set_align(func, store, align);
+            covered.extend(start..start + width as i64);
+        }
+
+        for &store in &run.stores {
+            let (_, offset) = byte_store(func, store).unwrap();
+            if covered.contains(&offset) {
+                func.remove_inst(store);
+            }
+        }
+        chunks.len()
+    }
+
+    fn combine_loads(&self, func: &mut Function) -> usize {
+        let ors: Vec<InstructionId> = func
+            .layout
+            .block_iter()
+            .flat_map(|block| func.layout.inst_iter(block))
+            .filter(|&inst| func.data.inst_ref(inst).opcode == Opcode::Or)
+            .collect();
+        let mut combined = 0;
+        for root in ors {
+            let aa = AliasAnalysis::new(func);
+            let is_part_of_tree = func.data.only_one_user_of(root).is_some_and(|user| {
+                let user = func.data.inst_ref(user);
+                user.opcode == Opcode::Or && user.parent == func.data.inst_ref(root).parent
+            });
+            if is_part_of_tree {
+                continue;
+            }
+            if let Some((leaves, nodes)) = self.load_tree(func, &aa, root) {
+                replace_load_tree(func, root, &leaves, &nodes);
+                combined += 1;
+            }
+        }
+        combined
+    }
+
+    /// Returns the loads of the `or` tree rooted at `root` if they can be
+    /// combined, by increasing offset, with the other instructions of the
+    /// tree, users first.
+    fn load_tree(
+        &self,
+        func: &Function,
+        aa: &AliasAnalysis,
+        root: InstructionId,
+    ) -> Option<(Vec<LoadLeaf>, Vec<InstructionId>)> {
+        let IntBinary { ty, .. } = func.data.inst_ref(root).operand.as_int_binary()?;
+        let size = int_size(*ty)?;
+        let block = func.data.inst_ref(root).parent;
+        let mut leaves = vec![];
+        let mut nodes = vec![];
+        collect_leaves(func, root, *ty, &mut leaves, &mut nodes)?;
+        leaves.sort_by_key(|leaf| leaf.offset);
+
+        let first = func.data.inst_ref(leaves[0].load);
+        let Load { addr, align, .. } = first.operand.as_load()?;
+        let (base, start) = base_and_offset(func, *addr);
+        let mut offset = start;
+        for leaf in &leaves {
+            let load = func.data.inst_ref(leaf.load);
+            let (leaf_base, _) = base_and_offset(func, load.operand.as_load()?.addr);
+            if load.parent != block
+                || !same_value(func, leaf_base, base)
+                || leaf.offset != offset
+                || leaf.shift != 8 * (leaf.offset - start)
+            {
+                return None;
+            }
+            offset += leaf.size;
+        }
+        if offset - start != size || !self.allow_unaligned && (*align as i64) < size {
+            return None;
+        }
+
+        // The loads move down to the root.
+        let insts: Vec<InstructionId> = func.layout.inst_iter(block).collect();
+        let pos = |inst| insts.iter().position(|&i| i == inst).unwrap();
+        let from = leaves.iter().map(|leaf| pos(leaf.load)).min()?;
+        let clobbered = insts[from..pos(root)].iter().any(|&inst| {
+            let inst = func.data.inst_ref(inst);
+            match &inst.operand {
+                Operand::Store(Store { args, .. }) => {
+                    aa.alias(func, args[1], base) != AliasResult::NoAlias
+                }
+                _ => inst.opcode.may_write_memory(),
+            }
+        });
+        if clobbered {
+            return None;
+        }
+        Some((leaves, nodes))
+    }
+}
+
+/// Collects the loads shifted into place in the tree of `or`s, `shl`s and
+/// `zext`s computing `val`, each used only once.
+fn collect_leaves(
+    func: &Function,
+    inst: InstructionId,
+    ty: Type,
+    leaves: &mut Vec<LoadLeaf>,
+    nodes: &mut Vec<InstructionId>,
+) -> Option<()> {
+    let arg_inst = |arg: ValueId| match func.data.value_ref(arg) {
+        Value::Instruction(arg) if func.data.users_of(*arg).len() == 1 => Some(*arg),
+        _ => None,
+    };
+    let node = func.data.inst_ref(inst);
+    nodes.push(inst);
+    match &node.operand {
+        Operand::IntBinary(IntBinary {
+            ty: node_ty, args, ..
+        }) if node.opcode == Opcode::Or && *node_ty == ty => {
+            collect_leaves(func, arg_inst(args[0])?, ty, leaves, nodes)?;
+            collect_leaves(func, arg_inst(args[1])?, ty, leaves, nodes)
+        }
+        Operand::IntBinary(IntBinary { args, .. }) if node.opcode == Opcode::Shl => {
+            let shift = int_of(func, args[1])?.cast_to_i64();
+            let zext = arg_inst(args[0])?;
+            collect_load(func, zext, shift, leaves, nodes)
+        }
+        _ => {
+            nodes.pop();
+            collect_load(func, inst, 0, leaves, nodes)
+        }
+    }
+}
+
+fn collect_load(
+    func: &Function,
+    zext: InstructionId,
+    shift: i64,
+    leaves: &mut Vec<LoadLeaf>,
+    nodes: &mut Vec<InstructionId>,
+) -> Option<()> {
+    let inst = func.data.inst_ref(zext);
+    if inst.opcode != Opcode::Zext {
+        return None;
+    }
+    let Cast { tys, arg } = inst.operand.as_cast()?;
+    let &Value::Instruction(load) = func.data.value_ref(*arg) else {
+        return None;
+    };
+    let Load { addr, .. } = func.data.inst_ref(load).operand.as_load()?;
+    if func.data.users_of(load).len() != 1 {
+        return None;
+    }
+    nodes.push(zext);
+    leaves.push(LoadLeaf {
+        load,
+        offset: base_and_offset(func, *addr).1,
+        size: int_size(tys[0])?,
+        shift,
+    });
+    Some(())
+}
+
+fn replace_load_tree(
+    func: &mut Function,
+    root: InstructionId,
+    leaves: &[LoadLeaf],
+    nodes: &[InstructionId],
+) {
+    let ty = func.data.inst_ref(root).operand.as_int_binary().unwrap().ty;
+    let Load { tys, addr, align } = func
+        .data
+        .inst_ref(leaves[0].load)
+        .operand
+        .as_load()
+        .unwrap();
+    let (ptr, ptr_ty, align) = (*addr, tys[1], *align);
+
+    let mut builder = Builder::new(func);
+    builder.position_before(root);
+    let wide_ptr_ty = builder.func().types.ptr_to(ty);
+    let wide_ptr = builder.build_cast(Opcode::Bitcast, ptr_ty, wide_ptr_ty, ptr);
+    let load = builder.build_load(ty, wide_ptr);
+    let load_inst = *func.data.value_ref(load).as_inst();
+    set_align(func, load_inst, align);
+
+    func.replace_all_uses_with(root, load);
+    for &inst in nodes {
+        func.erase_instruction(inst);
+    }
+    for leaf in leaves {
+        func.erase_instruction(leaf.load);
+    }
+}
+
+/// Splits the `i32` and `i64` loads and stores less aligned than their size
+/// into aligned pieces, and returns how many were split.
+fn split_unaligned(func: &mut Function) -> usize {
+    let accesses: Vec<(InstructionId, Type, Type, u32)> = func
+        .layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .filter_map(|inst| match &func.data.inst_ref(inst).operand {
+            Operand::Load(Load { tys, align, .. }) | Operand::Store(Store { tys, align, .. })
+                if (tys[0].is_i32() || tys[0].is_i64())
+                    && *align != 0
+                    && (*align as i64) < int_size(tys[0]).unwrap() =>
+            {
+                Some((inst, tys[0], tys[1], *align))
+            }
+            _ => None,
+        })
+        .collect();
+
+    for &(inst, ty, ptr_ty, align) in &accesses {
+        let (val, addr) = match &func.data.inst_ref(inst).operand {
+            Operand::Load(Load { addr, .. }) => (None, *addr),
+            Operand::Store(Store { args, .. }) => (Some(args[0]), args[1]),
+            _ => unreachable!(),
+        };
+        let piece_ty = int_type(align as usize);
+        let pieces = int_size(ty).unwrap() / align as i64;
+
+        let mut builder = Builder::new(func);
+        builder.position_before(inst);
+        let piece_ptr_ty = builder.func().types.ptr_to(piece_ty);
+        let piece_ptr = builder.build_cast(Opcode::Bitcast, ptr_ty, piece_ptr_ty, addr);
+        let mut loaded = None;
+        let mut piece_accesses = vec![];
+        for i in 0..pieces {
+            let ptr = match i {
+                0 => piece_ptr,
+                _ => {
+                    let index = builder.value(int_constant(I64, i).unwrap());
+                    builder.build_gep(
+                        true,
+                        vec![piece_ty, piece_ptr_ty, I64],
+                        vec![piece_ptr, index],
+                    )
+                }
+            };
+            let shift = builder.value(int_constant(ty, 8 * align as i64 * i).unwrap());
+            let access = match (val, loaded) {
+                (Some(val), _) => {
+                    let shifted = match i {
+                        0 => val,
+                        _ => builder.build_lshr(ty, val, shift),
+                    };
+                    let piece = builder.build_cast(Opcode::Trunc, ty, piece_ty, shifted);
+                    builder.build_store(piece_ty, piece, ptr)
+                }
+                (None, acc) => {
+                    let piece = builder.build_load(piece_ty, ptr);
+                    let wide = builder.build_cast(Opcode::Zext, piece_ty, ty, piece);
+                    loaded = Some(match acc {
+                        Some(acc) => {
+                            let shifted = builder.build_shl(ty, wide, shift);
+                            builder.build_or(ty, acc, shifted)
+                        }
+                        None => wide,
+                    });
+                    *builder.func().data.value_ref(piece).as_inst()
+                }
+            };
+            piece_accesses.push(access);
+        }
+        for access in piece_accesses {
+            set_align(func, access, align);
+        }
+
+        if let Some(loaded) = loaded {
+            func.replace_all_uses_with(inst, loaded);
+        }
+        func.erase_instruction(inst);
+    }
+    accesses.len()
+}
+
+/// If `inst` stores an `i8` constant, returns the pointer it stores into and
+/// the offset of the byte from it.
+fn byte_store(func: &Function, inst: InstructionId) -> Option<(ValueId, i64)> {
+    let Store { tys, args, .. } = func.data.inst_ref(inst).operand.as_store()?;
+    if !tys[0].is_i8() {
+        return None;
+    }
+    int_of(func, args[0])?;
+    Some(base_and_offset(func, args[1]))
+}
+
+/// Splits `ptr` into a base pointer and a constant offset in bytes, looking
+/// through `bitcast`s and `getelementptr`s indexing integers by constants.
+fn base_and_offset(func: &Function, ptr: ValueId) -> (ValueId, i64) {
+    if let Value::Instruction(id) = func.data.value_ref(ptr) {
+        let inst = func.data.inst_ref(*id);
+        match &inst.operand {
+            Operand::GetElementPtr(GetElementPtr { tys, args, .. }) if args.len() == 2 => {
+                if let (Some(size), Some(index)) = (int_size(tys[0]), int_of(func, args[1])) {
+                    let (base, offset) = base_and_offset(func, args[0]);
+                    return (base, offset + size * index.cast_to_i64());
+                }
+            }
+            Operand::Cast(Cast { arg, .. }) if inst.opcode == Opcode::Bitcast => {
+                return base_and_offset(func, *arg)
+            }
+            _ => {}
+        }
+    }
+    (ptr, 0)
+}
+
+fn same_value(func: &Function, a: ValueId, b: ValueId) -> bool {
+    a == b || func.data.value_ref(a) == func.data.value_ref(b)
+}
+
+fn set_align(func: &mut Function, inst: InstructionId, new_align: u32) {
+    match &mut func.data.inst_ref_mut(inst).operand {
+        Operand::Load(Load { align, .. }) | Operand::Store(Store { align, .. }) => {
+            *align = new_align
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn int_size(ty: Type) -> Option<i64> {
+    if ty.is_i8() {
+        Some(1)
+    } else if ty.is_i16() {
+        Some(2)
+    } else if ty.is_i32() {
+        Some(4)
+    } else if ty.is_i64() {
+        Some(8)
+    } else {
+        None
+    }
+}
+
+fn int_type(size: usize) -> Type {
+    match size {
+        1 => I8,
+        2 => I16,
+        4 => I32,
+        _ => I64,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LoadStoreCombinePass;
+    use crate::ir::module::parse_assembly;
+
+    fn run(pass: LoadStoreCombinePass, source: &str) -> (usize, String) {
+        let mut module = parse_assembly(source).unwrap();
+        let id = module.find_function_by_name("f").unwrap();
+        let func = &mut module.functions_mut()[id];
+        let changed = pass.run_on_function(func);
+        (changed, format!("{:?}", func))
+    }
+
+    #[test]
+    fn merge_byte_stores() {
+        let (changed, printed) = run(
+            LoadStoreCombinePass::new(),
+            r#"
+declare void @g()
+define void @f(i8* %0) {
+  %2 = alloca i32, align 4
+  store i8 1, i8* %0, align 1
+  %3 = getelementptr inbounds i8, i8* %0, i64 1
+  store i8 2, i8* %3, align 1
+  %4 = getelementptr inbounds i8, i8* %0, i64 2
+  store i32 0, i32* %2, align 4
+  store i8 3, i8* %4, align 1
+  %5 = getelementptr inbounds i8, i8* %0, i64 3
+  store i8 4, i8* %5, align 1
+  %6 = getelementptr inbounds i8, i8* %0, i64 4
+  store i8 5, i8* %6, align 1
+  call void @g()
+  store i8 6, i8* %0, align 1
+  store i8 7, i8* %3, align 1
+  store i8 8, i8* %4, align 1
+  ret void
+}"#,
+        );
+        assert_eq!(changed, 2);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn combine_byte_loads() {
+        let (changed, printed) = run(
+            LoadStoreCombinePass::new(),
+            r#"
+define i32 @f(i8* %0) {
+  %2 = load i8, i8* %0, align 1
+  %3 = zext i8 %2 to i32
+  %4 = getelementptr inbounds i8, i8* %0, i64 1
+  %5 = load i8, i8* %4, align 1
+  %6 = zext i8 %5 to i32
+  %7 = shl i32 %6, 8
+  %8 = or i32 %3, %7
+  %9 = getelementptr inbounds i8, i8* %0, i64 2
+  %10 = bitcast i8* %9 to i16*
+  %11 = load i16, i16* %10, align 1
+  %12 = zext i16 %11 to i32
+  %13 = shl i32 %12, 16
+  %14 = or i32 %8, %13
+  ret i32 %14
+}"#,
+        );
+        assert_eq!(changed, 1);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn strict_alignment() {
+        let (changed, printed) = run(
+            LoadStoreCombinePass::new().with_allow_unaligned(false),
+            r#"
+define i32 @f(i32* %0, i8* %1) {
+  %3 = load i32, i32* %0, align 2
+  store i32 %3, i32* %0, align 1
+  store i8 1, i8* %1, align 1
+  %4 = getelementptr inbounds i8, i8* %1, i64 1
+  store i8 2, i8* %4, align 1
+  %5 = getelementptr inbounds i8, i8* %1, i64 2
+  store i8 3, i8* %5, align 1
+  %6 = getelementptr inbounds i8, i8* %1, i64 3
+  store i8 4, i8* %6, align 1
+  ret i32 %3
+}"#,
+        );
+        // Only the two accesses to %0 are split.
+        assert_eq!(changed, 2);
+        insta::assert_snapshot!(printed);
+    }
+}
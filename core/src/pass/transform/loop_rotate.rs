@@ -0,0 +1,469 @@
+//! Loop rotation.
+//!
+//! A loop whose header tests the exit condition, i.e. a `while` loop, is
+//! turned into a guarded `do`-`while` loop: the header is copied into the
+//! preheader, where it becomes the guard skipping the loop altogether, and
+//! into the latch, where it becomes the exit test. The header itself is then
+//! removed and the first block of the body becomes the new header:
+//!
+//! ```text
+//! preheader:                        preheader:
+//!   br label %header                  <header>
+//! header:                             br i1 %c0, label %body, label %exit
+//!   <header>                        body:
+//!   br i1 %c, label %body, ...  =>    ...
+//! body:                             latch:
+//!   ...                               <header>
+//! latch:                              br i1 %c1, label %body, label %exit
+//!   br label %header
+//! ```
+//!
+//! The body then runs with a single conditional branch per iteration, at its
+//! end, which lets code be laid out to fall through. Values of the header
+//! are merged by phis in the new header and in the exit block.
+
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::ir::{
+    function::{
+        basic_block::{BasicBlock, BasicBlockId},
+        instruction::{InstructionId, IntBinary, Opcode, Operand, Phi},
+        Function,
+    },
+    module::Module,
+    types::Type,
+    value::{Value, ValueId},
+};
+use crate::pass::{
+    analysis::{
+        dom_tree::DominatorTree,
+        loops::{Loop, LoopInfo},
+    },
+    PreservedAnalyses, TransformPass,
+};
+use crate::prelude::*;
+use core::any::Any;
+
+/// The most instructions a header may have to be copied.
+const MAX_HEADER_SIZE: usize = 16;
+
+pub struct LoopRotatePass;
+
+impl TransformPass<Function> for LoopRotatePass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+/// A loop in the form handled by the pass.
+struct RotatableLoop {
+    header: BasicBlockId,
+    /// The only predecessor of `header` outside of the loop, ending with `br label %header`.
+    preheader: BasicBlockId,
+    /// The only latch, ending with `br label %header`.
+    latch: BasicBlockId,
+    /// The successor of `header` in the loop, whose only predecessor is `header`.
+    body: BasicBlockId,
+    /// The only block outside of the loop branched to, whose only predecessor is `header`.
+    exit: BasicBlockId,
+    blocks: FxHashSet<BasicBlockId>,
+}
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, func) in module.functions_mut().iter_mut() {
+        run_on_function(func);
+    }
+}
+
+/// Rotates the loops in `func` and returns how many were rotated.
+pub fn run_on_function(func: &mut Function) -> usize {
+    if func.is_prototype() {
+        return 0;
+    }
+
+    let mut visited = FxHashSet::default();
+    let mut rotated = 0;
+    loop {
+        // Loops are found again after each change to the CFG.
+        let dom_tree = DominatorTree::new(func);
+        let loops = LoopInfo::with_dom_tree(func, &dom_tree);
+        let next = loops.loops().iter().find_map(|l| {
+            if !visited.insert(l.header) {
+                return None;
+            }
+            rotatable_loop(func, l)
+        });
+        let Some(l) = next else {
+            break;
+        };
+        rotate(func, &l);
+        rotated += 1;
+    }
+    rotated
+}
+
+fn rotatable_loop(func: &Function, l: &Loop<BasicBlock>) -> Option<RotatableLoop> {
+    let [latch] = l.latches[..] else {
+        return None;
+    };
+    if latch == l.header || !ends_with_br_to(func, latch, l.header) {
+        return None;
+    }
+    let preds = &func.data.block_ref(l.header).preds;
+    let preheader = *preds.iter().find(|&&b| b != latch)?;
+    if preds.len() != 2 || l.contains(preheader) || !ends_with_br_to(func, preheader, l.header) {
+        return None;
+    }
+
+    let term = (*func.layout.block_node(l.header).last_inst())?;
+    let Operand::CondBr(cond_br) = &func.data.inst_ref(term).operand else {
+        return None;
+    };
+    let (body, exit) = match cond_br.blocks {
+        [b, e] | [e, b] if l.contains(b) && !l.contains(e) => (b, e),
+        _ => return None,
+    };
+    if body == l.header
+        || func.data.block_ref(body).preds.len() != 1
+        || func.data.block_ref(exit).preds.len() != 1
+        || starts_with_phi(func, body)
+    {
+        return None;
+    }
+    // The header must be the only exiting block.
+    for &block in &l.blocks {
+        if block != l.header
+            && func
+                .data
+                .block_ref(block)
+                .succs
+                .iter()
+                .any(|s| !l.contains(*s))
+        {
+            return None;
+        }
+    }
+
+    let mut size = 0;
+    for inst in func.layout.inst_iter(l.header) {
+        let inst = func.data.inst_ref(inst);
+        match &inst.operand {
+            Operand::Phi(Phi { blocks, .. }) => {
+                if blocks.len() != 2 || !blocks.contains(&latch) || !blocks.contains(&preheader) {
+                    return None;
+                }
+            }
+            Operand::Alloca(_) => return None,
+            _ => size += 1,
+        }
+        if inst.opcode != Opcode::CondBr
+            && !func.data.users_of(inst.id.unwrap()).is_empty()
+            && result_ty(func, inst.id.unwrap()).is_none()
+        {
+            return None;
+        }
+    }
+    if size > MAX_HEADER_SIZE {
+        return None;
+    }
+
+    Some(RotatableLoop {
+        header: l.header,
+        preheader,
+        latch,
+        body,
+        exit,
+        blocks: l.blocks.clone(),
+    })
+}
+
+fn rotate(func: &mut Function, l: &RotatableLoop) {
+    let header_insts: Vec<InstructionId> = func.layout.inst_iter(l.header).collect();
+    let (phis, insts): (Vec<InstructionId>, Vec<InstructionId>) = header_insts
+        .iter()
+        .partition(|&&inst| func.data.inst_ref(inst).opcode.is_phi());
+
+    // Values of the header needed after it: those used elsewhere and those
+    // flowing around the back edge into its phis.
+    let back_edge_vals: FxHashSet<InstructionId> = phis
+        .iter()
+        .filter_map(|&phi| inst_of(func, incoming_from(func, phi, l.latch)))
+        .collect();
+    let live: Vec<InstructionId> = header_insts
+        .iter()
+        .copied()
+        .filter(|&inst| {
+            back_edge_vals.contains(&inst)
+                || func
+                    .data
+                    .users_of(inst)
+                    .iter()
+                    .any(|&user| func.data.inst_ref(user).parent != l.header)
+        })
+        .collect();
+
+    // The guard in the preheader.
+    let mut pre_vals: FxHashMap<InstructionId, ValueId> = phis
+        .iter()
+        .map(|&phi| (phi, incoming_from(func, phi, l.preheader)))
+        .collect();
+    copy_into(func, &insts, l.preheader, &mut pre_vals);
+
+    // The phis of the new header.
+    let body_first = (*func.layout.block_node(l.body).first_inst()).unwrap();
+    let mut body_phis: FxHashMap<InstructionId, ValueId> = FxHashMap::default();
+    for &inst in &live {
+        let phi = create_phi(func, inst, l.body);
+        func.layout.insert_inst_before(phi, body_first);
+        let val = func.data.create_value(Value::Instruction(phi));
+        body_phis.insert(inst, val);
+    }
+
+    // The exit test in the latch.
+    let mut latch_vals: FxHashMap<InstructionId, ValueId> = phis
+        .iter()
+        .map(|&phi| {
+            let val = incoming_from(func, phi, l.latch);
+            (phi, lookup(&body_phis, func, val))
+        })
+        .collect();
+    copy_into(func, &insts, l.latch, &mut latch_vals);
+
+    for &inst in &live {
+        let phi = *func.data.value_ref(body_phis[&inst]).as_inst();
+        add_incoming(func, phi, l.preheader, pre_vals[&inst]);
+        add_incoming(func, phi, l.latch, latch_vals[&inst]);
+    }
+
+    // Phis of the exit block coming from the header now come from the
+    // preheader and the latch.
+    let exit_phis: Vec<InstructionId> = func
+        .layout
+        .inst_iter(l.exit)
+        .filter(|&inst| func.data.inst_ref(inst).opcode.is_phi())
+        .collect();
+    for phi in exit_phis {
+        let val = incoming_from(func, phi, l.header);
+        let (pre_val, latch_val) = (lookup(&pre_vals, func, val), lookup(&latch_vals, func, val));
+        func.data.remove_uses(phi);
+        let phi_op = func.data.inst_ref_mut(phi).operand.as_phi_mut().unwrap();
+        let i = phi_op.blocks.iter().position(|&b| b == l.header).unwrap();
+        phi_op.blocks[i] = l.preheader;
+        phi_op.args[i] = pre_val;
+        add_incoming(func, phi, l.latch, latch_val);
+    }
+
+    // Other uses of the header are replaced with the phis of the new header
+    // in the loop and with new phis of the exit block after it.
+    for &inst in &live {
+        let users: Vec<InstructionId> = func.data.users_of(inst).iter().copied().collect();
+        let mut exit_phi = None;
+        for user in users {
+            let parent = func.data.inst_ref(user).parent;
+            if parent == l.header {
+                continue;
+            }
+            let to = if l.blocks.contains(&parent) {
+                body_phis[&inst]
+            } else {
+                *exit_phi.get_or_insert_with(|| {
+                    let phi = create_phi(func, inst, l.exit);
+                    func.layout.insert_inst_at_start(phi, l.exit);
+                    add_incoming(func, phi, l.preheader, pre_vals[&inst]);
+                    add_incoming(func, phi, l.latch, latch_vals[&inst]);
+                    func.data.create_value(Value::Instruction(phi))
+                })
+            };
+            func.data.replace_inst_arg(user, inst, to);
+        }
+    }
+
+    for inst in header_insts.into_iter().rev() {
+        func.remove_inst(inst);
+    }
+    func.layout.remove_block(l.header);
+    for block in [l.body, l.exit] {
+        func.data.remove_block_pred(block, l.header);
+        for from in [l.preheader, l.latch] {
+            func.data.block_ref_mut(from).succs.insert(block);
+            func.data.block_ref_mut(block).preds.insert(from);
+        }
+    }
+    for from in [l.preheader, l.latch] {
+        func.data.remove_block_succ(from, l.header);
+    }
+}
+
+/// Copies `insts`, the non-phi instructions of a header, to the end of
+/// `block` in place of its branch. `vals` maps the header's values to those
+/// available in `block` and is extended with the copies.
+fn copy_into(
+    func: &mut Function,
+    insts: &[InstructionId],
+    block: BasicBlockId,
+    vals: &mut FxHashMap<InstructionId, ValueId>,
+) {
+    let br = (*func.layout.block_node(block).last_inst()).unwrap();
+    func.remove_inst(br);
+    for &inst in insts {
+        let orig = func.data.inst_ref(inst);
+        let mut operand = orig.operand.clone();
+        let new_inst = orig
+            .opcode
+            .with_block(block)
+            .with_metadata(orig.metadata.clone());
+        for arg in operand.args_mut() {
+            *arg = lookup(vals, func, *arg);
+        }
+        let new_inst = func.data.create_inst(new_inst.with_operand(operand));
+        func.layout.append_inst(new_inst, block);
+        let val = func.data.create_value(Value::Instruction(new_inst));
+        vals.insert(inst, val);
+    }
+}
+
+/// Creates an empty phi in `block` of the type of `inst`.
+fn create_phi(func: &mut Function, inst: InstructionId, block: BasicBlockId) -> InstructionId {
+    let ty = result_ty(func, inst).unwrap();
+    func.data.create_inst(
+        Opcode::Phi
+            .with_block(block)
+            .with_operand(Operand::Phi(Phi {
+                ty,
+                args: vec![],
+                blocks: vec![],
+            })),
+    )
+}
+
+fn ends_with_br_to(func: &Function, block: BasicBlockId, to: BasicBlockId) -> bool {
+    func.layout.block_node(block).last_inst().is_some_and(
+        |term| matches!(&func.data.inst_ref(term).operand, Operand::Br(br) if br.block == to),
+    )
+}
+
+fn starts_with_phi(func: &Function, block: BasicBlockId) -> bool {
+    func.layout
+        .block_node(block)
+        .first_inst()
+        .is_some_and(|inst| func.data.inst_ref(inst).opcode.is_phi())
+}
+
+/// Returns the instruction `val` refers to, if any.
+fn inst_of(func: &Function, val: ValueId) -> Option<InstructionId> {
+    match func.data.value_ref(val) {
+        Value::Instruction(inst) => Some(*inst),
+        _ => None,
+    }
+}
+
+/// Returns the value `vals` maps `val` to, or `val` itself if it is not mapped.
+fn lookup(vals: &FxHashMap<InstructionId, ValueId>, func: &Function, val: ValueId) -> ValueId {
+    inst_of(func, val)
+        .and_then(|inst| vals.get(&inst).copied())
+        .unwrap_or(val)
+}
+
+fn incoming_from(func: &Function, phi: InstructionId, block: BasicBlockId) -> ValueId {
+    let Phi { args, blocks, .. } = func.data.inst_ref(phi).operand.as_phi().unwrap();
+    args[blocks.iter().position(|&b| b == block).unwrap()]
+}
+
+fn add_incoming(func: &mut Function, phi: InstructionId, block: BasicBlockId, val: ValueId) {
+    let phi_op = func.data.inst_ref_mut(phi).operand.as_phi_mut().unwrap();
+    phi_op.blocks.push(block);
+    phi_op.args.push(val);
+    func.data.validate_inst_uses(phi);
+}
+
+fn result_ty(func: &Function, inst: InstructionId) -> Option<Type> {
+    let operand = &func.data.inst_ref(inst).operand;
+    match operand {
+        Operand::Phi(Phi { ty, .. }) | Operand::IntBinary(IntBinary { ty, .. }) => Some(*ty),
+        Operand::ICmp(_) => Some(crate::ir::types::I1),
+        Operand::Load(_) => Some(operand.types()[0]),
+        Operand::Cast(_) => Some(operand.types()[1]),
+        Operand::Call(_) => operand.call_result_ty().filter(|ty| !ty.is_void()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::run_on_function;
+    use crate::pass::transform::run_on_f;
+
+    #[test]
+    fn rotate_while_loop() {
+        let (rotated, printed) = run_on_f(
+            run_on_function,
+            r#"
+define i32 @f(i32 %0) {
+  br label %2
+2:
+  %3 = phi i32 [ 0, %1 ], [ %8, %6 ]
+  %4 = phi i32 [ 0, %1 ], [ %7, %6 ]
+  %5 = icmp slt i32 %3, %0
+  br i1 %5, label %6, label %9
+6:
+  %7 = add nsw i32 %4, %3
+  %8 = add nsw i32 %3, 1
+  br label %2
+9:
+  %10 = add i32 %4, %3
+  ret i32 %10
+}"#,
+        );
+        assert_eq!(rotated, 1);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn rotate_multi_block_body() {
+        let (rotated, printed) = run_on_f(
+            run_on_function,
+            r#"
+define i32 @f(i32 %0) {
+  br label %2
+2:
+  %3 = phi i32 [ %0, %1 ], [ %9, %8 ]
+  %4 = add i32 %3, -1
+  %5 = icmp sgt i32 %3, 0
+  br i1 %5, label %6, label %10
+6:
+  %7 = icmp eq i32 %4, 5
+  br i1 %7, label %8, label %8
+8:
+  %9 = add i32 %4, 0
+  br label %2
+10:
+  %11 = phi i32 [ %4, %2 ]
+  ret i32 %11
+}"#,
+        );
+        assert_eq!(rotated, 1);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn do_while_loop_is_kept() {
+        let (rotated, _) = run_on_f(
+            run_on_function,
+            r#"
+define void @f() {
+  br label %1
+1:
+  %2 = phi i64 [ 0, %0 ], [ %3, %1 ]
+  %3 = add i64 %2, 1
+  %4 = icmp ne i64 %3, 10
+  br i1 %4, label %1, label %5
+5:
+  ret void
+}"#,
+        );
+        assert_eq!(rotated, 0);
+    }
+}
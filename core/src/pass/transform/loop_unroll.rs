@@ -0,0 +1,744 @@
+//! Loop unrolling.
+//!
+//! Innermost loops with a single latch and a single exit edge are unrolled by
+//! copying their blocks, the copies being chained through their back edges:
+//! the latch of each copy branches to the header of the next one, and the
+//! latch of the last copy to the original header. Values used after the loop
+//! are merged by phis in the exit block.
+//!
+//! A loop is fully unrolled when its trip count is a small constant, found
+//! from an induction variable `phi [C1, ...], [add %iv, C2, ...]` compared
+//! against a constant. The exit test of each copy then has a known outcome,
+//! and the loop disappears. Other loops are unrolled by
+//! [`LoopUnrollPass::factor`], each copy keeping its exit test.
+
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::ir::{
+    function::{
+        basic_block::{BasicBlock, BasicBlockId},
+        instruction::{CondBr, ICmp, ICmpCond, InstructionId, IntBinary, Opcode, Operand, Phi},
+        Function,
+    },
+    module::Module,
+    types::Type,
+    value::{ConstantData, Value, ValueId},
+};
+use crate::pass::{
+    analysis::{
+        dom_tree::DominatorTree,
+        loops::{Loop, LoopInfo},
+    },
+    remarks::{Remark, Remarks},
+    PreservedAnalyses, TransformPass,
+};
+use crate::prelude::*;
+use core::any::Any;
+
+/// The most instructions a loop may have once unrolled.
+const MAX_UNROLLED_SIZE: usize = 512;
+
+pub struct LoopUnrollPass {
+    /// Loops running at most this many times are fully unrolled.
+    pub max_trip_count: usize,
+    /// The number of copies of their body other loops are unrolled into.
+    /// 1 disables partial unrolling.
+    pub factor: usize,
+    pub remarks: Option<Remarks>,
+}
+
+impl Default for LoopUnrollPass {
+    fn default() -> Self {
+        Self {
+            max_trip_count: 8,
+            factor: 1,
+            remarks: None,
+        }
+    }
+}
+
+impl TransformPass<Function> for LoopUnrollPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        self.run_on_function(func);
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+/// A loop in the form handled by the pass.
+struct SimpleLoop {
+    header: BasicBlockId,
+    latch: BasicBlockId,
+    /// The only block branching out of the loop.
+    exiting: BasicBlockId,
+    /// The only block outside of the loop branched to, whose only predecessor is `exiting`.
+    exit: BasicBlockId,
+    /// In layout order.
+    blocks: Vec<BasicBlockId>,
+    /// Instructions defined in the loop and used outside of it, other than
+    /// by the phis in `exit`, with their type and users.
+    live_out: Vec<(InstructionId, Type, Vec<InstructionId>)>,
+    size: usize,
+}
+
+/// The blocks and values of a copy of a loop, by their original counterpart.
+#[derive(Default)]
+struct LoopCopy {
+    blocks: FxHashMap<BasicBlockId, BasicBlockId>,
+    values: FxHashMap<InstructionId, ValueId>,
+}
+
+impl LoopUnrollPass {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_trip_count(mut self, max_trip_count: usize) -> Self {
+        self.max_trip_count = max_trip_count;
+        self
+    }
+
+    pub fn with_factor(mut self, factor: usize) -> Self {
+        self.factor = factor;
+        self
+    }
+
+    /// Reports the loops unrolled or not to `remarks`.
+    pub fn with_remarks(mut self, remarks: Remarks) -> Self {
+        self.remarks = Some(remarks);
+        self
+    }
+
+    fn remark(&self, remark: Remark) {
+        if let Some(remarks) = &self.remarks {
+            remarks.emit(remark)
+        }
+    }
+
+    pub fn run_on_module(&self, module: &mut Module) {
+        for (_, function) in module.functions_mut().iter_mut() {
+            self.run_on_function(function);
+        }
+    }
+
+    /// Unrolls the loops in `func` and returns how many were unrolled.
+    pub fn run_on_function(&self, func: &mut Function) -> usize {
+        if func.is_prototype() {
+            return 0;
+        }
+
+        let mut visited = FxHashSet::default();
+        let mut unrolled = 0;
+        loop {
+            // Loops are found again after each change to the CFG.
+            let dom_tree = DominatorTree::new(func);
+            let loops = LoopInfo::with_dom_tree(func, &dom_tree);
+            let next = loops.loops().iter().enumerate().find_map(|(i, l)| {
+                let innermost = !loops.loops().iter().any(|l| l.parent == Some(i));
+                if !innermost || !visited.insert(l.header) {
+                    return None;
+                }
+                simple_loop(func, &dom_tree, l)
+            });
+            let Some(l) = next else {
+                break;
+            };
+
+            let count = trip_count(func, &l, self.max_trip_count);
+            match count {
+                Some(count) if count * l.size <= MAX_UNROLLED_SIZE => {
+                    let copies = unroll(func, &l, count);
+                    fold_exit_tests(func, &l, &copies);
+                    self.remark(
+                        Remark::passed("loop-unroll", "FullyUnrolled", &func.name)
+                            .string("completely unrolled loop with ")
+                            .arg("TripCount", count)
+                            .string(" iterations"),
+                    );
+                }
+                _ if self.factor > 1 && self.factor * l.size <= MAX_UNROLLED_SIZE => {
+                    unroll(func, &l, self.factor);
+                    self.remark(
+                        Remark::passed("loop-unroll", "PartialUnrolled", &func.name)
+                            .string("unrolled loop by a factor of ")
+                            .arg("UnrollCount", self.factor),
+                    );
+                }
+                _ => {
+                    let reason = match count {
+                        Some(_) => "unrolled loop would be too large",
+                        None => "trip count unknown",
+                    };
+                    self.remark(
+                        Remark::missed("loop-unroll", "NotUnrolled", &func.name)
+                            .string("could not unroll: ")
+                            .arg("Reason", reason),
+                    );
+                    continue;
+                }
+            }
+            unrolled += 1;
+        }
+        unrolled
+    }
+}
+
+fn simple_loop(
+    func: &Function,
+    dom_tree: &DominatorTree<BasicBlock>,
+    l: &Loop<BasicBlock>,
+) -> Option<SimpleLoop> {
+    let [latch] = l.latches[..] else {
+        return None;
+    };
+    let blocks: Vec<BasicBlockId> = func
+        .layout
+        .block_iter()
+        .filter(|&b| l.contains(b))
+        .collect();
+
+    let mut exits = vec![];
+    for &block in &blocks {
+        let term = (*func.layout.block_node(block).last_inst())?;
+        if !func.data.inst_ref(term).opcode.is_branch() {
+            return None;
+        }
+        for &succ in &func.data.block_ref(block).succs {
+            if !l.contains(succ) {
+                exits.push((block, succ));
+            }
+        }
+    }
+    let [(exiting, exit)] = exits[..] else {
+        return None;
+    };
+    if func.data.block_ref(exit).preds.len() != 1 || !dom_tree.dominates(exiting, latch) {
+        return None;
+    }
+
+    for inst in func.layout.inst_iter(l.header) {
+        if let Operand::Phi(Phi { blocks, .. }) = &func.data.inst_ref(inst).operand {
+            if blocks.iter().filter(|&&b| b == latch).count() != 1 {
+                return None;
+            }
+        }
+    }
+
+    let mut live_out = vec![];
+    let mut size = 0;
+    for &block in &blocks {
+        for inst in func.layout.inst_iter(block) {
+            size += 1;
+            let users: Vec<InstructionId> = func
+                .data
+                .users_of(inst)
+                .iter()
+                .copied()
+                .filter(|&user| {
+                    let user = func.data.inst_ref(user);
+                    let by_exit_phi = user.parent == exit && user.opcode.is_phi();
+                    !l.contains(user.parent) && !by_exit_phi
+                })
+                .collect();
+            if !users.is_empty() {
+                live_out.push((inst, result_ty(func, inst)?, users));
+            }
+        }
+    }
+
+    Some(SimpleLoop {
+        header: l.header,
+        latch,
+        exiting,
+        exit,
+        blocks,
+        live_out,
+        size,
+    })
+}
+
+/// Returns the number of times the exit test of `l` runs, if it is at most `max`.
+fn trip_count(func: &Function, l: &SimpleLoop, max: usize) -> Option<usize> {
+    let term = (*func.layout.block_node(l.exiting).last_inst())?;
+    let CondBr { arg, blocks } = func.data.inst_ref(term).operand.as_condbr()?;
+    let exit_on_true = blocks[0] == l.exit;
+    let Value::Instruction(cmp) = func.data.value_ref(*arg) else {
+        return None;
+    };
+    let ICmp { ty, args, cond } = func.data.inst_ref(*cmp).operand.as_icmp()?;
+    let (operand, limit, cond) = match (int_of(func, args[0]), int_of(func, args[1])) {
+        (None, Some(limit)) => (args[0], limit, *cond),
+        (Some(limit), None) => (args[1], limit, cond.swapped()),
+        _ => return None,
+    };
+    let bits = if ty.is_i32() {
+        32
+    } else if ty.is_i64() {
+        64
+    } else {
+        return None;
+    };
+
+    // Find the induction variable compared, or its next value.
+    let &Value::Instruction(operand) = func.data.value_ref(operand) else {
+        return None;
+    };
+    let (start, step, compares_next) = func
+        .layout
+        .inst_iter(l.header)
+        .find_map(|phi| induction_variable(func, l, phi, operand))?;
+
+    let mut iv = start;
+    for count in 1..=max {
+        let next = wrap(iv.wrapping_add(step), bits);
+        let x = if compares_next { next } else { iv };
+        if icmp(cond, bits, x, limit) == exit_on_true {
+            return Some(count);
+        }
+        iv = next;
+    }
+    None
+}
+
+/// If `phi` is an induction variable `phi [start, ...], [add %phi, step, %latch]`
+/// and `operand` is either `phi` or its next value, returns `start`, `step`
+/// and whether `operand` is the next value.
+fn induction_variable(
+    func: &Function,
+    l: &SimpleLoop,
+    phi: InstructionId,
+    operand: InstructionId,
+) -> Option<(i64, i64, bool)> {
+    let Phi { args, blocks, .. } = func.data.inst_ref(phi).operand.as_phi()?;
+    let [_, _] = args[..] else {
+        return None;
+    };
+    let latch = blocks.iter().position(|&b| b == l.latch)?;
+    let start = int_of(func, args[1 - latch])?;
+    let &Value::Instruction(next) = func.data.value_ref(args[latch]) else {
+        return None;
+    };
+    let next_inst = func.data.inst_ref(next);
+    if next_inst.opcode != Opcode::Add {
+        return None;
+    }
+    let IntBinary { args, .. } = next_inst.operand.as_int_binary()?;
+    let is_phi = |arg: ValueId| func.data.value_ref(arg) == &Value::Instruction(phi);
+    let step = match (int_of(func, args[0]), int_of(func, args[1])) {
+        (None, Some(step)) if is_phi(args[0]) => step,
+        (Some(step), None) if is_phi(args[1]) => step,
+        _ => return None,
+    };
+    if operand != phi && operand != next {
+        return None;
+    }
+    Some((start, step, operand == next))
+}
+
+/// Makes `count - 1` copies of `l` and chains them with the original loop.
+/// The original loop is the first of the returned copies.
+fn unroll(func: &mut Function, l: &SimpleLoop, count: usize) -> Vec<LoopCopy> {
+    let mut copies = vec![LoopCopy {
+        blocks: l.blocks.iter().map(|&b| (b, b)).collect(),
+        values: FxHashMap::default(),
+    }];
+    let mut last = *l.blocks.last().unwrap();
+    for _ in 1..count {
+        let mut copy = LoopCopy::default();
+        for &block in &l.blocks {
+            let new_block = func.data.create_block();
+            func.layout.insert_block_after(new_block, last);
+            copy.blocks.insert(block, new_block);
+            last = new_block;
+        }
+        copies.push(copy);
+    }
+
+    let header_phis: Vec<InstructionId> = func
+        .layout
+        .inst_iter(l.header)
+        .filter(|&inst| func.data.inst_ref(inst).opcode.is_phi())
+        .collect();
+    let headers: Vec<BasicBlockId> = copies.iter().map(|copy| copy.blocks[&l.header]).collect();
+    let next_header = |c: usize| headers.get(c + 1).copied().unwrap_or(l.header);
+
+    for c in 1..count {
+        // Phis in the header of a copy are the values from the previous copy.
+        for &phi in &header_phis {
+            let val = incoming_from(func, phi, l.latch);
+            let val = copies[c - 1].remap(func, val);
+            copies[c].values.insert(phi, val);
+        }
+
+        let mut cloned = vec![];
+        for &block in &l.blocks {
+            let new_block = copies[c].blocks[&block];
+            let insts: Vec<InstructionId> = func.layout.inst_iter(block).collect();
+            for inst in insts.into_iter().filter(|i| !header_phis.contains(i)) {
+                let orig = func.data.inst_ref(inst);
+                let new_inst = orig
+                    .opcode
+                    .with_block(new_block)
+                    .with_operand(orig.operand.clone())
+                    .with_metadata(orig.metadata.clone());
+                let new_inst = func.data.create_inst(new_inst);
+                func.layout.append_inst(new_inst, new_block);
+                let val = func.data.create_value(Value::Instruction(new_inst));
+                copies[c].values.insert(inst, val);
+                cloned.push((block, new_inst));
+            }
+        }
+
+        for (block, inst) in cloned {
+            func.data.remove_uses(inst);
+            let args: Vec<ValueId> = func.data.inst_ref(inst).operand.args().to_vec();
+            let args: Vec<ValueId> = args.into_iter().map(|a| copies[c].remap(func, a)).collect();
+            let operand = &mut func.data.inst_ref_mut(inst).operand;
+            operand.args_mut().copy_from_slice(&args);
+            for target in operand.successors_mut() {
+                *target = if block == l.latch && *target == l.header {
+                    next_header(c)
+                } else {
+                    copies[c].blocks.get(target).copied().unwrap_or(*target)
+                };
+            }
+            if let Operand::Phi(Phi { blocks, .. }) = operand {
+                for b in blocks.iter_mut() {
+                    *b = copies[c].blocks.get(b).copied().unwrap_or(*b);
+                }
+            }
+            func.data.validate_inst_uses(inst);
+        }
+    }
+
+    // The original latch now branches to the first copy, and the header is
+    // reached back from the last one.
+    let last_latch = copies[count - 1].blocks[&l.latch];
+    let term = (*func.layout.block_node(l.latch).last_inst()).unwrap();
+    for target in func.data.inst_ref_mut(term).operand.successors_mut() {
+        if *target == l.header {
+            *target = next_header(0);
+        }
+    }
+    for &phi in &header_phis {
+        let val = incoming_from(func, phi, l.latch);
+        let val = copies[count - 1].remap(func, val);
+        set_incoming(func, phi, l.latch, last_latch, val);
+    }
+
+    // Each copy exits the loop.
+    let exit_phis: Vec<InstructionId> = func
+        .layout
+        .inst_iter(l.exit)
+        .filter(|&inst| func.data.inst_ref(inst).opcode.is_phi())
+        .collect();
+    for phi in exit_phis {
+        let val = incoming_from(func, phi, l.exiting);
+        for copy in &copies[1..] {
+            let val = copy.remap(func, val);
+            add_incoming(func, phi, copy.blocks[&l.exiting], val);
+        }
+    }
+    for (inst, ty, users) in &l.live_out {
+        let val = func.data.create_value(Value::Instruction(*inst));
+        let (args, blocks) = copies
+            .iter()
+            .map(|copy| (copy.remap(func, val), copy.blocks[&l.exiting]))
+            .unzip();
+        let phi =
+            func.data.create_inst(
+                Opcode::Phi
+                    .with_block(l.exit)
+                    .with_operand(Operand::Phi(Phi {
+                        ty: *ty,
+                        args,
+                        blocks,
+                    })),
+            );
+        func.layout.insert_inst_at_start(phi, l.exit);
+        let phi = func.data.create_value(Value::Instruction(phi));
+        for &user in users {
+            func.data.replace_inst_arg(user, *inst, phi);
+        }
+    }
+
+    let all_blocks: Vec<BasicBlockId> = copies
+        .iter()
+        .flat_map(|copy| copy.blocks.values().copied())
+        .collect();
+    recompute_edges(func, &all_blocks);
+
+    copies
+}
+
+/// Replaces the exit tests of a fully unrolled loop with branches: all the
+/// copies but the last stay in the loop.
+fn fold_exit_tests(func: &mut Function, l: &SimpleLoop, copies: &[LoopCopy]) {
+    for (c, copy) in copies.iter().enumerate() {
+        let exiting = copy.blocks[&l.exiting];
+        let term = (*func.layout.block_node(exiting).last_inst()).unwrap();
+        let CondBr { arg, blocks } = func.data.inst_ref(term).operand.as_condbr().unwrap();
+        let target = if c + 1 < copies.len() {
+            *blocks.iter().find(|&&b| b != l.exit).unwrap()
+        } else {
+            l.exit
+        };
+        let cond = match func.data.value_ref(*arg) {
+            Value::Instruction(cond) => Some(*cond),
+            _ => None,
+        };
+        func.replace_terminator_with_br(exiting, target);
+        if let Some(cond) = cond.filter(|&cond| func.data.users_of(cond).is_empty()) {
+            func.erase_instruction(cond);
+        }
+    }
+    func.remove_unreachable_blocks();
+    remove_single_incoming_phis(func, l.header);
+    remove_single_incoming_phis(func, l.exit);
+
+    // Merge the straight-line code left.
+    let region: FxHashSet<BasicBlockId> = copies
+        .iter()
+        .flat_map(|copy| copy.blocks.values().copied())
+        .collect();
+    let blocks: Vec<BasicBlockId> = func
+        .layout
+        .block_iter()
+        .filter(|b| region.contains(b))
+        .collect();
+    let mut merged = FxHashSet::default();
+    for block in blocks {
+        if merged.contains(&block) {
+            continue;
+        }
+        while let Some(&succ) = only_succ(func, block) {
+            if !region.contains(&succ) || !func.merge_blocks(block, succ) {
+                break;
+            }
+            merged.insert(succ);
+        }
+    }
+}
+
+fn only_succ(func: &Function, block: BasicBlockId) -> Option<&BasicBlockId> {
+    let succs = &func.data.block_ref(block).succs;
+    if succs.len() == 1 {
+        succs.iter().next()
+    } else {
+        None
+    }
+}
+
+fn remove_single_incoming_phis(func: &mut Function, block: BasicBlockId) {
+    let insts: Vec<InstructionId> = func.layout.inst_iter(block).collect();
+    for inst in insts {
+        if let Operand::Phi(Phi { args, .. }) = &func.data.inst_ref(inst).operand {
+            if let [val] = args[..] {
+                func.replace_all_uses_with(inst, val);
+                func.erase_instruction(inst);
+            }
+        }
+    }
+}
+
+/// Sets the successors of `blocks` from their terminators.
+fn recompute_edges(func: &mut Function, blocks: &[BasicBlockId]) {
+    for &block in blocks {
+        for succ in core::mem::take(&mut func.data.block_ref_mut(block).succs) {
+            func.data.remove_block_pred(succ, block);
+        }
+    }
+    for &block in blocks {
+        let term = (*func.layout.block_node(block).last_inst()).unwrap();
+        let succs = func.data.inst_ref(term).operand.successors().to_vec();
+        for succ in succs {
+            func.data.block_ref_mut(block).succs.insert(succ);
+            func.data.block_ref_mut(succ).preds.insert(block);
+        }
+    }
+}
+
+fn incoming_from(func: &Function, phi: InstructionId, block: BasicBlockId) -> ValueId {
+    let Phi { args, blocks, .. } = func.data.inst_ref(phi).operand.as_phi().unwrap();
+    args[blocks.iter().position(|&b| b == block).unwrap()]
+}
+
+fn set_incoming(
+    func: &mut Function,
+    phi: InstructionId,
+    from: BasicBlockId,
+    block: BasicBlockId,
+    val: ValueId,
+) {
+    func.data.remove_uses(phi);
+    let phi_op = func.data.inst_ref_mut(phi).operand.as_phi_mut().unwrap();
+    let i = phi_op.blocks.iter().position(|&b| b == from).unwrap();
+    phi_op.blocks[i] = block;
+    phi_op.args[i] = val;
+    func.data.validate_inst_uses(phi);
+}
+
+fn add_incoming(func: &mut Function, phi: InstructionId, block: BasicBlockId, val: ValueId) {
+    let phi_op = func.data.inst_ref_mut(phi).operand.as_phi_mut().unwrap();
+    phi_op.blocks.push(block);
+    phi_op.args.push(val);
+    func.data.validate_inst_uses(phi);
+}
+
+impl LoopCopy {
+    fn remap(&self, func: &Function, val: ValueId) -> ValueId {
+        match func.data.value_ref(val) {
+            Value::Instruction(inst) => self.values.get(inst).copied().unwrap_or(val),
+            _ => val,
+        }
+    }
+}
+
+fn result_ty(func: &Function, inst: InstructionId) -> Option<Type> {
+    let operand = &func.data.inst_ref(inst).operand;
+    match operand {
+        Operand::Phi(Phi { ty, .. }) | Operand::IntBinary(IntBinary { ty, .. }) => Some(*ty),
+        Operand::ICmp(_) => Some(crate::ir::types::I1),
+        Operand::Load(_) => Some(operand.types()[0]),
+        Operand::Cast(_) => Some(operand.types()[1]),
+        Operand::Call(_) => operand.call_result_ty().filter(|ty| !ty.is_void()),
+        _ => None,
+    }
+}
+
+fn int_of(func: &Function, val: ValueId) -> Option<i64> {
+    match func.data.value_ref(val) {
+        Value::Constant(ConstantData::Int(i)) => Some(i.cast_to_i64()),
+        _ => None,
+    }
+}
+
+fn wrap(x: i64, bits: u32) -> i64 {
+    if bits == 32 {
+        x as i32 as i64
+    } else {
+        x
+    }
+}
+
+fn icmp(cond: ICmpCond, bits: u32, x: i64, y: i64) -> bool {
+    let unsigned = |x: i64| {
+        if bits == 32 {
+            x as u32 as u64
+        } else {
+            x as u64
+        }
+    };
+    let (ux, uy) = (unsigned(x), unsigned(y));
+    match cond {
+        ICmpCond::Eq => x == y,
+        ICmpCond::Ne => x != y,
+        ICmpCond::Ugt => ux > uy,
+        ICmpCond::Uge => ux >= uy,
+        ICmpCond::Ult => ux < uy,
+        ICmpCond::Ule => ux <= uy,
+        ICmpCond::Sgt => x > y,
+        ICmpCond::Sge => x >= y,
+        ICmpCond::Slt => x < y,
+        ICmpCond::Sle => x <= y,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LoopUnrollPass;
+    use crate::{ir::module::parse_assembly, pass::remarks::Remarks};
+
+    fn run(pass: LoopUnrollPass, source: &str) -> (usize, String) {
+        let mut module = parse_assembly(source).unwrap();
+        let id = module.find_function_by_name("f").unwrap();
+        let func = &mut module.functions_mut()[id];
+        let unrolled = pass.run_on_function(func);
+        (unrolled, format!("{:?}", func))
+    }
+
+    #[test]
+    fn full_unroll() {
+        let (unrolled, printed) = run(
+            LoopUnrollPass::new(),
+            r#"
+define i32 @f(i32 %0) {
+  br label %2
+2:
+  %3 = phi i32 [ 0, %1 ], [ %8, %6 ]
+  %4 = phi i32 [ %0, %1 ], [ %7, %6 ]
+  %5 = icmp slt i32 %3, 3
+  br i1 %5, label %6, label %9
+6:
+  %7 = add nsw i32 %4, %3
+  %8 = add nsw i32 %3, 1
+  br label %2
+9:
+  ret i32 %4
+}"#,
+        );
+        assert_eq!(unrolled, 1);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn partial_unroll() {
+        let (unrolled, printed) = run(
+            LoopUnrollPass::new().with_factor(2),
+            r#"
+define i32 @f(i32 %0) {
+  br label %2
+2:
+  %3 = phi i32 [ 0, %1 ], [ %6, %2 ]
+  %4 = phi i32 [ 0, %1 ], [ %5, %2 ]
+  %5 = add nsw i32 %4, %3
+  %6 = add nsw i32 %3, 1
+  %7 = icmp slt i32 %6, %0
+  br i1 %7, label %2, label %8
+8:
+  %9 = phi i32 [ %5, %2 ]
+  %10 = add i32 %9, %6
+  ret i32 %10
+}"#,
+        );
+        assert_eq!(unrolled, 1);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn trip_count_too_high() {
+        let source = |n: usize| {
+            format!(
+                r#"
+define void @f() {{
+  br label %1
+1:
+  %2 = phi i64 [ 0, %0 ], [ %3, %1 ]
+  %3 = add i64 %2, 1
+  %4 = icmp ne i64 %3, {}
+  br i1 %4, label %1, label %5
+5:
+  ret void
+}}"#,
+                n
+            )
+        };
+        let remarks = Remarks::new();
+        let pass = LoopUnrollPass::new().with_remarks(remarks.clone());
+        assert_eq!(run(pass, &source(100)).0, 0);
+        assert_eq!(
+            remarks.take()[0].to_string(),
+            "f: could not unroll: trip count unknown"
+        );
+        assert_eq!(
+            run(LoopUnrollPass::new().with_max_trip_count(100), &source(100)).0,
+            1
+        );
+        // Too many instructions once unrolled.
+        assert_eq!(
+            run(LoopUnrollPass::new().with_max_trip_count(200), &source(200)).0,
+            0
+        );
+    }
+}
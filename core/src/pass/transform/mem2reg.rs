@@ -1,19 +1,28 @@
+use crate::collections::{FxHashMap, FxHashSet};
+use crate::prelude::*;
 use crate::{
     ir::{
         function::{
             basic_block::{BasicBlock, BasicBlockId},
+            data::Data,
             instruction::{Instruction, InstructionId, Opcode, Operand, Phi},
             Function,
         },
         value::{Value, ValueId},
     },
-    pass::{analysis::dom_tree, transform::sccp::SCCP, TransformPass},
+    pass::{
+        analysis::dom_tree, stats::Statistic, transform::sccp::SCCP, PreservedAnalyses,
+        TransformPass,
+    },
 };
-use rustc_hash::{FxHashMap, FxHashSet};
-use std::{any::Any, cmp::Ordering, collections::BinaryHeap};
+use alloc::collections::BinaryHeap;
+use core::{any::Any, cmp::Ordering};
 
 pub struct Mem2RegPass;
 
+static NUM_PROMOTED: Statistic =
+    Statistic::new("mem2reg", "NumPromoted", "Number of allocas promoted");
+
 pub struct Mem2Reg<'a> {
     func: &'a mut Function,
     dom_tree: dom_tree::DominatorTree<BasicBlock>,
@@ -22,6 +31,7 @@ pub struct Mem2Reg<'a> {
 
 type InstructionIndex = usize;
 
+#[derive(Default)]
 struct InstructionIndexes(FxHashMap<InstructionId, InstructionIndex>);
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -83,6 +93,12 @@ impl<'a> Mem2Reg<'a> {
             }
         }
 
+        NUM_PROMOTED.add(
+            (single_store_alloca_list.len()
+                + single_block_alloca_list.len()
+                + multi_block_alloca_list.len()) as u64,
+        );
+
         for alloca in single_store_alloca_list {
             self.promote_single_store_alloca(alloca);
         }
@@ -178,7 +194,7 @@ impl<'a> Mem2Reg<'a> {
             }
         }
 
-        store_indexes.sort_by(|(_, x), (_, y)| x.cmp(y));
+        store_indexes.sort_by_key(|(_, x)| *x);
 
         let mut remove_all_access = true;
         let mut stores_to_remove = vec![];
@@ -287,10 +303,7 @@ impl<'a> Mem2Reg<'a> {
                             }));
                         let phi_id = self.func.data.create_inst(phi);
                         self.func.layout.insert_inst_at_start(phi_id, succ_id);
-                        added_phis
-                            .entry(succ_id)
-                            .or_insert_with(Vec::new)
-                            .push(phi_id);
+                        added_phis.entry(succ_id).or_default().push(phi_id);
                         phi_to_alloca.insert(phi_id, alloca_id);
                     }
 
@@ -316,7 +329,7 @@ impl<'a> Mem2Reg<'a> {
         phi_to_alloca: FxHashMap<InstructionId, InstructionId>,
         mut added_phis: FxHashMap<BasicBlockId, Vec<InstructionId>>,
     ) {
-        let entry = self.func.layout.first_block.unwrap();
+        let entry = self.func.entry_block().unwrap();
 
         let mut visited = FxHashSet::default();
         let mut worklist = vec![RenameData {
@@ -353,16 +366,17 @@ impl<'a> Mem2Reg<'a> {
         loop {
             for phi_id in added_phis.get(&data.cur).unwrap_or(&vec![]) {
                 let alloca_id = phi_to_alloca[phi_id];
-                let incoming_id = data
-                    .incoming
-                    .get_mut(&alloca_id)
-                    .expect("TODO: return undef");
+                let incoming_id =
+                    incoming_value(&mut self.func.data, &mut data.incoming, alloca_id);
                 let phi = self.func.data.inst_ref_mut(*phi_id);
                 let phi = phi.operand.as_phi_mut().unwrap();
-                phi.args_mut().push(*incoming_id);
+                phi.args_mut().push(incoming_id);
                 phi.blocks_mut().push(data.pred.unwrap());
                 self.func.data.validate_inst_uses(*phi_id);
-                *incoming_id = self.func.data.create_value(Value::Instruction(*phi_id));
+                data.incoming.insert(
+                    alloca_id,
+                    self.func.data.create_value(Value::Instruction(*phi_id)),
+                );
             }
 
             if !visited.insert(data.cur) {
@@ -391,9 +405,9 @@ impl<'a> Mem2Reg<'a> {
                             .insert(alloca_id, inst.operand.as_store().unwrap().src_val());
                     }
                     Opcode::Load => {
-                        if let Some(val) = data.incoming.get(&alloca_id) {
-                            self.func.data.replace_all_uses(inst_id, *val);
-                        }
+                        let val =
+                            incoming_value(&mut self.func.data, &mut data.incoming, alloca_id);
+                        self.func.data.replace_all_uses(inst_id, val);
                     }
                     _ => unreachable!(),
                 }
@@ -464,10 +478,19 @@ impl<'a> Mem2Reg<'a> {
     }
 }
 
-impl Default for InstructionIndexes {
-    fn default() -> Self {
-        Self(FxHashMap::default())
+/// Returns the value last stored to `alloca_id`. Loading an alloca not yet
+/// stored to (e.g. an uninitialized local variable) yields undef.
+fn incoming_value(
+    data: &mut Data,
+    incoming: &mut FxHashMap<InstructionId, ValueId>,
+    alloca_id: InstructionId,
+) -> ValueId {
+    if let Some(&val) = incoming.get(&alloca_id) {
+        return val;
     }
+    let undef = data.create_value(Value::undef());
+    incoming.insert(alloca_id, undef);
+    undef
 }
 
 impl InstructionIndexes {
@@ -493,6 +516,11 @@ impl TransformPass<Function> for Mem2RegPass {
     fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
         Mem2Reg::new(func).run();
     }
+
+    /// Constant propagation run by mem2reg may fold branches.
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
 }
 
 impl Ord for BlockLevel {
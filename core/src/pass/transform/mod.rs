@@ -1,3 +1,38 @@
+pub mod adce;
+pub mod canonicalize;
+pub mod compact;
+pub mod constant_fold;
+pub mod dae;
 pub mod dce;
+pub mod dse;
+pub mod global_dce;
+pub mod global_opt;
+pub mod gvn;
+pub mod icf;
+pub mod indvars;
+pub mod infer_alignment;
+pub mod inline;
+pub mod inst_combine;
+pub mod load_store_combine;
+pub mod loop_rotate;
+pub mod loop_unroll;
 pub mod mem2reg;
+pub mod namer;
+pub mod reassociate;
 pub mod sccp;
+pub mod strip;
+pub mod tail_call_elim;
+
+/// Parses `source` and runs `pass` on its function `f`, returning what the
+/// pass returned and the function printed after it.
+#[cfg(test)]
+fn run_on_f<R>(
+    pass: impl FnOnce(&mut crate::ir::function::Function) -> R,
+    source: &str,
+) -> (R, String) {
+    let mut module = crate::ir::module::parse_assembly(source).unwrap();
+    let id = module.find_function_by_name("f").unwrap();
+    let func = &mut module.functions_mut()[id];
+    let result = pass(func);
+    (result, format!("{:?}", func))
+}
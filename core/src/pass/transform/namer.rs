@@ -0,0 +1,166 @@
+//! Naming unnamed values and blocks, after LLVM's `instnamer`.
+//!
+//! Printed IR numbers unnamed values in order, so inserting or removing a
+//! single instruction renumbers everything after it. Once named, values keep
+//! their names through later transformations, and printed functions diff
+//! line by line.
+//!
+//! Arguments are named `%argN`, instructions `%tmpN`, the entry block
+//! `%entry`, loop headers `%bb.loopN` and other blocks `%bb.N`, with `N`
+//! counting up in layout order and skipping names already taken.
+
+use crate::collections::FxHashSet;
+use crate::ir::{
+    function::{basic_block::BasicBlock, Function},
+    module::{name::Name, Module},
+};
+use crate::pass::{analysis::loops::LoopInfo, PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct NamerPass;
+
+impl TransformPass<Function> for NamerPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::all()
+    }
+}
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, func) in module.functions_mut().iter_mut() {
+        run_on_function(func);
+    }
+}
+
+/// Names the unnamed values and blocks of `func` and returns how many were
+/// named.
+pub fn run_on_function(func: &mut Function) -> usize {
+    if func.is_prototype() {
+        return 0;
+    }
+
+    let mut namer = Namer::default();
+    for param in &func.params {
+        namer.take(&param.name);
+    }
+    for block in func.layout.block_iter() {
+        if let Some(name) = &func.data.block_ref(block).name {
+            namer.take(name);
+        }
+        for inst in func.layout.inst_iter(block) {
+            if let Some(name) = &func.data.inst_ref(inst).dest {
+                namer.take(name);
+            }
+        }
+    }
+
+    let mut named = 0;
+    for param in &mut func.params {
+        if let Name::Number(_) = param.name {
+            param.name = namer.fresh("arg");
+            named += 1;
+        }
+    }
+
+    let loops = LoopInfo::<BasicBlock>::new(func);
+    let entry = func.layout.get_entry_block();
+    let blocks: Vec<_> = func.layout.block_iter().collect();
+    for block in blocks {
+        if !matches!(
+            func.data.block_ref(block).name,
+            None | Some(Name::Number(_))
+        ) {
+            continue;
+        }
+        let name = if Some(block) == entry && namer.is_free("entry") {
+            namer.take(&Name::Name("entry".into()));
+            Name::Name("entry".into())
+        } else if loops.is_loop_header(block) {
+            namer.fresh("bb.loop")
+        } else {
+            namer.fresh("bb.")
+        };
+        func.data.block_ref_mut(block).name = Some(name);
+        named += 1;
+
+        let insts: Vec<_> = func.layout.inst_iter(block).collect();
+        for inst in insts {
+            let inst = func.data.inst_ref_mut(inst);
+            let has_result = inst.opcode.has_result()
+                && !inst.operand.call_result_ty().is_some_and(|ty| ty.is_void());
+            if has_result && matches!(inst.dest, None | Some(Name::Number(_))) {
+                inst.dest = Some(namer.fresh("tmp"));
+                named += 1;
+            }
+        }
+    }
+    named
+}
+
+#[derive(Default)]
+struct Namer {
+    taken: FxHashSet<String>,
+    next: usize,
+}
+
+impl Namer {
+    fn take(&mut self, name: &Name) {
+        if let Name::Name(name) = name {
+            self.taken.insert(name.to_string());
+        }
+    }
+
+    fn is_free(&self, name: &str) -> bool {
+        !self.taken.contains(name)
+    }
+
+    /// Returns `prefix` followed by the next free number.
+    fn fresh(&mut self, prefix: &str) -> Name {
+        loop {
+            let name = format!("{}{}", prefix, self.next);
+            self.next += 1;
+            if self.taken.insert(name.clone()) {
+                return Name::from(name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    #[test]
+    fn name_values() {
+        let mut module = parse_assembly(
+            r#"
+define i32 @f(i32 %0, i32 %n) {
+  %3 = icmp sgt i32 %n, 0
+  br i1 %3, label %4, label %10
+4:
+  %5 = phi i32 [ 0, %2 ], [ %8, %4 ]
+  %6 = phi i32 [ %0, %2 ], [ %7, %4 ]
+  %7 = add i32 %6, %5
+  %8 = add i32 %5, 1
+  %9 = icmp eq i32 %8, %n
+  br i1 %9, label %10, label %4
+10:
+  %11 = phi i32 [ %0, %2 ], [ %7, %4 ]
+  store i32 %11, i32* null
+  ret i32 %11
+}"#,
+        )
+        .unwrap();
+        let func_id = module.find_function_by_name("f").unwrap();
+        let func = &mut module.functions_mut()[func_id];
+        assert_eq!(run_on_function(func), 11);
+        // Names are kept when run again.
+        assert_eq!(run_on_function(func), 0);
+        insta::assert_debug_snapshot!(func);
+    }
+}
@@ -0,0 +1,324 @@
+//! Reassociation of integer `add`s and `mul`s.
+//!
+//! A tree of `add`s (or `mul`s) of the same type in a block, each but the
+//! root used only by its parent, computes a sum of its leaves that can be
+//! taken in any order. The tree is rewritten into a chain adding the leaves
+//! by increasing rank, so that values available early, e.g. outside of a
+//! loop, are combined first and can be hoisted together. All the constant
+//! leaves are folded into a single one, added last.
+//!
+//! The rank of a constant is 0, of an argument 1, and of an instruction
+//! the position of its block in the layout plus 2.
+//!
+//! The partial sums of the new chain are not computed by the original code,
+//! so `nsw` is dropped. `nuw` is kept on chains of `add nuw`: every partial
+//! sum is then at most the total, which does not wrap.
+
+use super::inst_combine::{int_constant, int_of};
+use crate::collections::FxHashMap;
+use crate::ir::{
+    function::{
+        basic_block::{BasicBlock, BasicBlockId},
+        instruction::{InstructionId, IntBinary, Opcode, Operand},
+        Function,
+    },
+    module::Module,
+    types::Type,
+    value::{Value, ValueId},
+};
+use crate::pass::{analysis::dom_tree::DominatorTree, PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct ReassociatePass;
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, function) in module.functions_mut().iter_mut() {
+        run_on_function(function);
+    }
+}
+
+impl TransformPass<Function> for ReassociatePass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none().preserve::<DominatorTree<BasicBlock>>()
+    }
+}
+
+/// An expression tree being reassociated.
+struct Tree {
+    opcode: Opcode,
+    ty: Type,
+    block: BasicBlockId,
+    /// The instructions of the tree, the root first.
+    nodes: Vec<InstructionId>,
+    leaves: Vec<ValueId>,
+    /// True if the second argument of every node is a leaf.
+    left_deep: bool,
+    all_nuw: bool,
+}
+
+/// Reassociates the expressions of `func` and returns how many were rewritten.
+pub fn run_on_function(func: &mut Function) -> usize {
+    if func.is_prototype() {
+        return 0;
+    }
+
+    let block_ranks: FxHashMap<BasicBlockId, usize> = func
+        .layout
+        .block_iter()
+        .enumerate()
+        .map(|(i, block)| (block, i + 2))
+        .collect();
+    let roots: Vec<InstructionId> = func
+        .layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .filter(|&inst| is_root(func, inst))
+        .collect();
+
+    let mut rewritten = 0;
+    for root in roots {
+        let inst = func.data.inst_ref(root);
+        let ty = inst.operand.as_int_binary().unwrap().ty;
+        let mut tree = Tree {
+            opcode: inst.opcode,
+            ty,
+            block: inst.parent,
+            nodes: vec![],
+            leaves: vec![],
+            left_deep: true,
+            all_nuw: true,
+        };
+        linearize(func, root, &mut tree);
+        if rewrite(func, &tree, &block_ranks) {
+            rewritten += 1;
+        }
+    }
+    rewritten
+}
+
+fn is_reassociable(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::Add | Opcode::Mul)
+}
+
+/// Returns true if `inst` is an `add` or `mul` not part of a larger tree.
+fn is_root(func: &Function, inst: InstructionId) -> bool {
+    let inst = func.data.inst_ref(inst);
+    if !is_reassociable(inst.opcode) {
+        return false;
+    }
+    match func.data.only_one_user_of(inst.id.unwrap()) {
+        Some(user) if has_one_use(func, inst.id.unwrap()) => {
+            !is_node_of(func, user, inst.opcode, inst.parent)
+        }
+        _ => true,
+    }
+}
+
+/// Returns true if `inst` is used once, by a single argument of its only user.
+fn has_one_use(func: &Function, inst: InstructionId) -> bool {
+    let Some(user) = func.data.only_one_user_of(inst) else {
+        return false;
+    };
+    let args = func.data.inst_ref(user).operand.args();
+    args.iter()
+        .filter(|&&arg| matches!(func.data.value_ref(arg), Value::Instruction(i) if *i == inst))
+        .count()
+        == 1
+}
+
+fn is_node_of(func: &Function, inst: InstructionId, opcode: Opcode, block: BasicBlockId) -> bool {
+    let inst = func.data.inst_ref(inst);
+    inst.opcode == opcode && inst.parent == block
+}
+
+fn linearize(func: &Function, node: InstructionId, tree: &mut Tree) {
+    tree.nodes.push(node);
+    let IntBinary { ty, nuw, args, .. } = func.data.inst_ref(node).operand.as_int_binary().unwrap();
+    tree.all_nuw &= *nuw;
+    debug_assert_eq!(*ty, tree.ty);
+    for (i, &arg) in args.iter().enumerate() {
+        let child = match func.data.value_ref(arg) {
+            Value::Instruction(inst)
+                if is_node_of(func, *inst, tree.opcode, tree.block) && has_one_use(func, *inst) =>
+            {
+                Some(*inst)
+            }
+            _ => None,
+        };
+        match child {
+            Some(child) => {
+                tree.left_deep &= i == 0;
+                linearize(func, child, tree);
+            }
+            None => tree.leaves.push(arg),
+        }
+    }
+}
+
+/// Rewrites `tree` into a chain. Returns false if it already is one.
+fn rewrite(func: &mut Function, tree: &Tree, block_ranks: &FxHashMap<BasicBlockId, usize>) -> bool {
+    let rank = |val: ValueId| match func.data.value_ref(val) {
+        Value::Constant(_) => 0,
+        Value::Argument(_) => 1,
+        Value::Instruction(inst) => block_ranks[&func.data.inst_ref(*inst).parent],
+        Value::InlineAsm(_) => 1,
+    };
+
+    let mut consts = vec![];
+    let mut ops = vec![];
+    for &leaf in &tree.leaves {
+        match int_of(func, leaf) {
+            Some(c) => consts.push(c.cast_to_i64()),
+            None => ops.push(leaf),
+        }
+    }
+    let sorted = ops.windows(2).all(|w| rank(w[0]) <= rank(w[1]));
+    let const_last = consts.is_empty()
+        || consts.len() == 1 && int_of(func, *tree.leaves.last().unwrap()).is_some();
+    if tree.left_deep && sorted && const_last {
+        return false;
+    }
+    ops.sort_by_key(|&op| rank(op));
+
+    let (identity, fold): (i64, fn(i64, i64) -> i64) = match tree.opcode {
+        Opcode::Add => (0, i64::wrapping_add),
+        _ => (1, i64::wrapping_mul),
+    };
+    let c = consts.into_iter().fold(identity, fold);
+    let Some(konst) = int_constant(tree.ty, c) else {
+        return false;
+    };
+    // Compare the truncated constant, e.g. `i8 255` is `-1`.
+    let c = match &konst {
+        Value::Constant(konst) => konst.as_int().cast_to_i64(),
+        _ => unreachable!(),
+    };
+    let root = tree.nodes[0];
+    if tree.opcode == Opcode::Mul && c == 0 {
+        ops.clear();
+    }
+    if c != identity || ops.is_empty() {
+        ops.push(func.data.create_value(konst));
+    }
+
+    let result = match ops[..] {
+        [val] => {
+            func.replace_all_uses_with(root, val);
+            None
+        }
+        _ => {
+            let nuw = tree.opcode == Opcode::Add && tree.all_nuw;
+            let mut acc = ops[0];
+            for &op in &ops[1..ops.len() - 1] {
+                let inst = func
+                    .data
+                    .create_inst(tree.opcode.with_block(tree.block).with_operand(
+                        Operand::IntBinary(IntBinary {
+                            ty: tree.ty,
+                            nsw: false,
+                            nuw,
+                            exact: false,
+                            args: [acc, op],
+                        }),
+                    ));
+                func.layout.insert_inst_before(inst, root);
+                acc = func.data.create_value(Value::Instruction(inst));
+            }
+            func.data.remove_uses(root);
+            let operand = func
+                .data
+                .inst_ref_mut(root)
+                .operand
+                .as_int_binary_mut()
+                .unwrap();
+            operand.args = [acc, *ops.last().unwrap()];
+            operand.nsw = false;
+            operand.nuw = nuw;
+            func.data.validate_inst_uses(root);
+            Some(root)
+        }
+    };
+
+    // The old nodes are used only by each other now.
+    for &node in &tree.nodes {
+        if Some(node) != result {
+            func.erase_instruction(node);
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::run_on_function;
+    use crate::pass::transform::run_on_f;
+
+    #[test]
+    fn fold_constants() {
+        let (rewritten, printed) = run_on_f(
+            run_on_function,
+            r#"
+define i32 @f(i32 %0, i32 %1) {
+  %3 = add nsw i32 %0, 1
+  %4 = add nsw i32 %3, %1
+  %5 = add nsw i32 2, %4
+  %6 = mul i32 %5, 3
+  %7 = mul i32 5, %6
+  %8 = add nuw i32 %7, 4
+  %9 = add nuw i32 %8, %0
+  %10 = mul i32 %9, 0
+  %11 = mul i32 %10, %1
+  %12 = add nuw i32 %9, %11
+  ret i32 %12
+}"#,
+        );
+        assert_eq!(rewritten, 5);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn loop_invariants_first() {
+        let (rewritten, printed) = run_on_f(
+            run_on_function,
+            r#"
+define i32 @f(i32 %0, i32 %1, i32 %2) {
+  %4 = add i32 %0, %1
+  br label %5
+5:
+  %6 = phi i32 [ 0, %3 ], [ %9, %5 ]
+  %7 = add i32 %6, %0
+  %8 = add i32 %7, %4
+  %9 = add i32 %8, 1
+  %10 = icmp slt i32 %9, %2
+  br i1 %10, label %5, label %11
+11:
+  ret i32 %9
+}"#,
+        );
+        assert_eq!(rewritten, 1);
+        insta::assert_snapshot!(printed);
+
+        // Already in order.
+        let (rewritten, _) = run_on_f(run_on_function, &printed);
+        assert_eq!(rewritten, 0);
+    }
+
+    #[test]
+    fn node_used_twice() {
+        let (rewritten, _) = run_on_f(
+            run_on_function,
+            r#"
+define i32 @f(i32 %x) {
+  %y = add i32 %x, 3
+  %z = add i32 %y, %y
+  ret i32 %z
+}"#,
+        );
+        assert_eq!(rewritten, 0);
+    }
+}
@@ -8,12 +8,27 @@ use crate::ir::{
     },
     value::{ConstantData, Value},
 };
-use std::collections::VecDeque;
+use crate::pass::{PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use alloc::collections::VecDeque;
+use core::any::Any;
+
+pub struct SCCPPass;
 
 pub struct SCCP<'a> {
     func: &'a mut Function,
 }
 
+impl TransformPass<Function> for SCCPPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        SCCP::new(func).run();
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
 impl<'a> SCCP<'a> {
     pub fn new(func: &'a mut Function) -> Self {
         Self { func }
@@ -63,7 +78,7 @@ impl<'a> SCCP<'a> {
 
         while let Some(inst_id) = foldable.pop_front() {
             let inst = &self.func.data.inst_ref(inst_id);
-            let folded = match inst.fold_consts(&self.func.data) {
+            let folded = match inst.fold_consts(&self.func.data, &self.func.types) {
                 Some(folded) => folded,
                 None => continue,
             };
@@ -78,16 +93,20 @@ impl<'a> SCCP<'a> {
         }
     }
 
+    /// Only instructions computing integers are folded, leaving pointers to
+    /// the instructions computing them.
     fn is_foldable(&self, inst: &Instruction) -> bool {
-        matches!(
-            inst.opcode,
-            Opcode::Add | Opcode::Sub | Opcode::Mul | Opcode::ICmp | Opcode::Zext
-        ) && inst
-            .operand
-            .args()
-            .iter()
-            .map(|arg| self.func.data.value_ref(*arg))
-            .all(|arg| matches!(arg, Value::Constant(ConstantData::Int(_))))
+        (inst.opcode.is_binary()
+            || matches!(
+                inst.opcode,
+                Opcode::ICmp | Opcode::Sext | Opcode::Zext | Opcode::Trunc
+            ))
+            && inst
+                .operand
+                .args()
+                .iter()
+                .map(|arg| self.func.data.value_ref(*arg))
+                .all(|arg| matches!(arg, Value::Constant(ConstantData::Int(_))))
     }
 
     fn is_foldable_condbr(&self, inst: &Instruction) -> bool {
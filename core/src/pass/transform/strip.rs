@@ -0,0 +1,289 @@
+//! Stripping debug information, metadata and names, after `opt -strip-*`.
+//!
+//! - [`StripDebugPass`] removes `!dbg` attachments, calls to the `llvm.dbg.*`
+//!   intrinsics and their declarations, the `!llvm.dbg.*` named metadata and
+//!   the `Debug Info Version` module flag.
+//! - [`StripMetadataPass`] removes all metadata, attached or not.
+//! - [`StripNamesPass`] removes the names of arguments, blocks and
+//!   instructions, which are then numbered when printed.
+//!
+//! Numbered metadata no longer referred to is removed along the way.
+
+use crate::collections::FxHashSet;
+use crate::ir::{
+    function::{
+        instruction::{InstructionId, Operand},
+        Function,
+    },
+    module::{metadata::Metadata, name::Name, Module},
+    value::{ConstantData, Value},
+};
+use crate::pass::{PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::{any::Any, mem};
+use id_arena::Arena;
+
+pub struct StripDebugPass;
+
+pub struct StripMetadataPass;
+
+pub struct StripNamesPass;
+
+/// The number of things removed by a strip pass.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StripResult(pub usize);
+
+impl TransformPass<Module> for StripDebugPass {
+    fn run_on(&self, module: &mut Module, result: &mut Box<dyn Any>) {
+        *result = Box::new(StripResult(strip_debug(module)))
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+impl TransformPass<Module> for StripMetadataPass {
+    fn run_on(&self, module: &mut Module, result: &mut Box<dyn Any>) {
+        *result = Box::new(StripResult(strip_metadata(module)))
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::all()
+    }
+}
+
+impl TransformPass<Module> for StripNamesPass {
+    fn run_on(&self, module: &mut Module, result: &mut Box<dyn Any>) {
+        *result = Box::new(StripResult(strip_names(module)))
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::all()
+    }
+}
+
+fn is_debug_intrinsic(name: &str) -> bool {
+    name.starts_with("llvm.dbg.")
+}
+
+/// Removes debug information from `module` and returns how many attachments,
+/// instructions, declarations and metadata were removed.
+pub fn strip_debug(module: &mut Module) -> usize {
+    let mut removed = 0;
+    for (_, func) in module.functions.iter_mut() {
+        for (_, inst) in func.data.instructions.iter_mut() {
+            if inst.metadata.remove("dbg").is_some() {
+                removed += 1;
+            }
+        }
+        let calls: Vec<InstructionId> = func
+            .layout
+            .block_iter()
+            .flat_map(|block| func.layout.inst_iter(block))
+            .filter(|&inst| calls_debug_intrinsic(func, inst))
+            .collect();
+        for call in calls {
+            func.erase_instruction(call);
+            removed += 1;
+        }
+    }
+
+    let functions = mem::replace(&mut module.functions, Arena::new());
+    for (_, func) in functions {
+        if func.is_prototype() && is_debug_intrinsic(&func.name) {
+            removed += 1;
+        } else {
+            module.functions.alloc(func);
+        }
+    }
+
+    let num_metas = module.metas.len();
+    module
+        .metas
+        .retain(|name, _| !matches!(name, Name::Name(n) if is_debug_intrinsic(n)));
+    removed += num_metas - module.metas.len();
+    if let Some(Metadata::Node(flags)) = module.metas.get(&Name::Name("llvm.module.flags".into())) {
+        let flags: Vec<Metadata> = flags
+            .iter()
+            .filter(|flag| !is_debug_info_version(module, flag))
+            .cloned()
+            .collect();
+        module.metas.insert(
+            Name::Name("llvm.module.flags".into()),
+            Metadata::Node(flags),
+        );
+    }
+
+    removed + remove_unreferenced_metadata(module)
+}
+
+fn calls_debug_intrinsic(func: &Function, inst: InstructionId) -> bool {
+    let Operand::Call(call) = &func.data.inst_ref(inst).operand else {
+        return false;
+    };
+    matches!(
+        func.data.value_ref(call.args[0]),
+        Value::Constant(ConstantData::GlobalRef(Name::Name(name))) if is_debug_intrinsic(name)
+    )
+}
+
+/// Returns true if `flag`, an operand of `!llvm.module.flags`, is the
+/// `Debug Info Version` flag.
+fn is_debug_info_version(module: &Module, flag: &Metadata) -> bool {
+    let flag = match flag {
+        Metadata::Name(name) => module.metas.get(name),
+        flag => Some(flag),
+    };
+    matches!(
+        flag,
+        Some(Metadata::Node(elems))
+            if elems.get(1) == Some(&Metadata::String("Debug Info Version".into()))
+    )
+}
+
+/// Removes all metadata from `module` and returns how many attachments and
+/// metadata were removed.
+pub fn strip_metadata(module: &mut Module) -> usize {
+    let mut removed = module.metas.len();
+    module.metas.clear();
+    for (_, func) in module.functions.iter_mut() {
+        for (_, inst) in func.data.instructions.iter_mut() {
+            removed += inst.metadata.len();
+            inst.metadata.clear();
+        }
+    }
+    removed
+}
+
+/// Removes the names of the values and blocks of `module` and returns how
+/// many were removed.
+pub fn strip_names(module: &mut Module) -> usize {
+    let mut removed = 0;
+    for (_, func) in module.functions.iter_mut() {
+        for (i, param) in func.params.iter_mut().enumerate() {
+            if matches!(param.name, Name::Name(_)) {
+                param.name = Name::Number(i);
+                removed += 1;
+            }
+        }
+        for (_, block) in func.data.basic_blocks.iter_mut() {
+            if matches!(block.name, Some(Name::Name(_))) {
+                block.name = None;
+                removed += 1;
+            }
+        }
+        for (_, inst) in func.data.instructions.iter_mut() {
+            if matches!(inst.dest, Some(Name::Name(_))) {
+                inst.dest = None;
+                removed += 1;
+            }
+        }
+    }
+    removed
+}
+
+/// Removes numbered metadata that named metadata and attachments don't refer
+/// to, directly or not, and returns how many were removed.
+pub(crate) fn remove_unreferenced_metadata(module: &mut Module) -> usize {
+    fn refer(meta: &Metadata, live: &mut FxHashSet<Name>, worklist: &mut Vec<Name>) {
+        match meta {
+            Metadata::Name(name) if live.insert(*name) => worklist.push(*name),
+            Metadata::Node(elems) => {
+                for elem in elems {
+                    refer(elem, live, worklist);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut live = FxHashSet::default();
+    let mut worklist = vec![];
+    for (name, meta) in &module.metas {
+        if let Name::Name(_) = name {
+            refer(meta, &mut live, &mut worklist);
+        }
+    }
+    for (_, func) in module.functions.iter() {
+        for (_, inst) in func.data.instructions.iter() {
+            for meta in inst.metadata.values() {
+                refer(meta, &mut live, &mut worklist);
+            }
+        }
+    }
+    while let Some(name) = worklist.pop() {
+        if let Some(meta) = module.metas.get(&name) {
+            refer(meta, &mut live, &mut worklist);
+        }
+    }
+
+    let num_metas = module.metas.len();
+    module
+        .metas
+        .retain(|name, _| matches!(name, Name::Name(_)) || live.contains(name));
+    num_metas - module.metas.len()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ir::module::parse_assembly;
+
+    const SOURCE: &str = r#"
+declare void @llvm.dbg.value(metadata, metadata, metadata)
+
+define i32 @f(i32 %x) {
+entry:
+  %y = add i32 %x, 1, !dbg !6
+  %z = load i32, i32* @g, align 4, !tbaa !3
+  ret i32 %y
+}
+
+@g = global i32 0
+
+!llvm.module.flags = !{!0, !1}
+!llvm.dbg.cu = !{!2}
+
+!0 = !{i32 2, !"Debug Info Version", i32 3}
+!1 = !{i32 1, !"wchar_size", i32 4}
+!2 = !{!"cu"}
+!3 = !{!"int"}
+!4 = !{}
+!5 = !{!"y", !2}
+!6 = !{i32 4, i32 7, !5}
+"#;
+
+    #[test]
+    fn strip_debug_info() {
+        let mut module = parse_assembly(SOURCE).unwrap();
+        // An attachment, a declaration, `!llvm.dbg.cu` and !0, !2, !4, !5, !6.
+        assert_eq!(strip_debug(&mut module), 8);
+        assert!(module.find_function_by_name("llvm.dbg.value").is_none());
+        let mut metas: Vec<_> = module.metas.keys().map(|n| format!("{:?}", n)).collect();
+        metas.sort();
+        assert_eq!(metas, vec!["1", "3", "llvm.module.flags"]);
+        let f = &module.functions()[module.find_function_by_name("f").unwrap()];
+        let printed = format!("{:?}", f);
+        assert!(!printed.contains("dbg"));
+        assert!(printed.contains("!tbaa"));
+    }
+
+    #[test]
+    fn strip_all_metadata() {
+        let mut module = parse_assembly(SOURCE).unwrap();
+        assert_eq!(strip_metadata(&mut module), 11);
+        assert!(module.metas.is_empty());
+        let f = &module.functions()[module.find_function_by_name("f").unwrap()];
+        assert!(!format!("{:?}", f).contains('!'));
+    }
+
+    #[test]
+    fn strip_value_names() {
+        let mut module = parse_assembly(SOURCE).unwrap();
+        strip_metadata(&mut module);
+        assert_eq!(strip_names(&mut module), 4);
+        let f = &module.functions()[module.find_function_by_name("f").unwrap()];
+        insta::assert_debug_snapshot!(f);
+    }
+}
@@ -0,0 +1,236 @@
+//! Tail call elimination.
+//!
+//! A self-recursive `call` immediately followed by a `ret` of its result (or
+//! by a `ret void`) is replaced with a branch back to the start of the
+//! function, turning the recursion into a loop that needs no stack. The
+//! parameters become phis in the former entry block, taking the arguments
+//! of each eliminated call, and a new entry block holding the `alloca`s
+//! branches to it.
+//!
+//! The `alloca`s are then shared by all the iterations, so functions with an
+//! `alloca` whose address escapes, e.g. to the recursive call, or that is
+//! not in the entry block are left alone.
+
+use crate::ir::{
+    function::{
+        builder::Builder,
+        instruction::{InstructionId, Load, Opcode, Operand, Ret, Store},
+        Function,
+    },
+    module::{name::Name, Module},
+    value::{ConstantData, Value, ValueId},
+};
+use crate::pass::{PreservedAnalyses, TransformPass};
+use crate::prelude::*;
+use core::any::Any;
+
+pub struct TailCallElimPass;
+
+pub fn run_on_module(module: &mut Module) {
+    for (_, function) in module.functions_mut().iter_mut() {
+        run_on_function(function);
+    }
+}
+
+impl TransformPass<Function> for TailCallElimPass {
+    fn run_on(&self, func: &mut Function, _result: &mut Box<dyn Any>) {
+        run_on_function(func);
+    }
+
+    fn preserved_analyses(&self) -> PreservedAnalyses {
+        PreservedAnalyses::none()
+    }
+}
+
+/// Eliminates the self-recursive tail calls of `func` and returns how many
+/// were eliminated.
+pub fn run_on_function(func: &mut Function) -> usize {
+    if func.is_prototype() || func.is_var_arg() || !allocas_are_local(func) {
+        return 0;
+    }
+
+    let tail_calls: Vec<(InstructionId, InstructionId)> = func
+        .layout
+        .block_iter()
+        .filter_map(|block| {
+            let insts: Vec<InstructionId> = func.layout.inst_iter(block).collect();
+            match insts[..] {
+                [.., call, ret] if is_tail_call(func, call, ret) => Some((call, ret)),
+                _ => None,
+            }
+        })
+        .collect();
+    if tail_calls.is_empty() {
+        return 0;
+    }
+
+    // Branching back to the start of the function gives the entry block
+    // predecessors, so a new entry block is inserted to hold the `alloca`s and
+    // enter the loop.
+    let old_entry = func.entry_block().unwrap();
+    let mut builder = Builder::new(func);
+    for &(call, _) in &tail_calls {
+        let block = builder.func().data.inst_ref(call).parent;
+        builder.position_at_end(block);
+        builder.build_br(old_entry);
+    }
+    let entry = func.ensure_canonical_entry().unwrap();
+    let br = func.layout.inst_iter(entry).next().unwrap();
+    let allocas: Vec<InstructionId> = func
+        .layout
+        .inst_iter(old_entry)
+        .filter(|&inst| func.data.inst_ref(inst).opcode.is_alloca())
+        .collect();
+    for alloca in allocas {
+        func.layout.remove_inst(alloca);
+        func.layout.insert_inst_before(alloca, br);
+        func.data.inst_ref_mut(alloca).parent = entry;
+    }
+
+    let tys: Vec<_> = func.params.iter().map(|param| param.ty).collect();
+    let mut builder = Builder::new(func);
+    builder.position_at_start(old_entry);
+    let phis: Vec<ValueId> = tys
+        .into_iter()
+        .map(|ty| builder.build_phi(ty, vec![]))
+        .collect();
+
+    // Every use of a parameter becomes a use of its phi.
+    let phi_insts: Vec<InstructionId> = phis
+        .iter()
+        .map(|&phi| *func.data.value_ref(phi).as_inst())
+        .collect();
+    for (_, val) in func.data.values.iter_mut() {
+        if let Value::Argument(i) = val {
+            *val = Value::Instruction(phi_insts[*i]);
+        }
+    }
+    let insts: Vec<InstructionId> = func
+        .layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .collect();
+    for inst in insts {
+        func.data.validate_inst_uses(inst);
+    }
+
+    let mut builder = Builder::new(func);
+    for (i, &phi) in phis.iter().enumerate() {
+        let arg = builder.arg(i);
+        builder.add_phi_incoming(phi, arg, entry);
+    }
+    for &(call, _) in &tail_calls {
+        let block = builder.func().data.inst_ref(call).parent;
+        let args = builder.func().data.inst_ref(call).operand.args()[1..].to_vec();
+        for (&phi, arg) in phis.iter().zip(args) {
+            builder.add_phi_incoming(phi, arg, block);
+        }
+    }
+
+    for &(call, ret) in &tail_calls {
+        func.erase_instruction(ret);
+        func.erase_instruction(call);
+    }
+
+    tail_calls.len()
+}
+
+/// Returns true if `call` calls the function itself and `ret` returns its result.
+fn is_tail_call(func: &Function, call: InstructionId, ret: InstructionId) -> bool {
+    let Operand::Call(call_op) = &func.data.inst_ref(call).operand else {
+        return false;
+    };
+    let Operand::Ret(Ret { val, .. }) = &func.data.inst_ref(ret).operand else {
+        return false;
+    };
+    let is_self = matches!(
+        func.data.value_ref(call_op.args[0]),
+        Value::Constant(ConstantData::GlobalRef(Name::Name(name))) if name.as_str() == func.name
+    );
+    let returns_result = match val {
+        Some(val) => *func.data.value_ref(*val) == Value::Instruction(call),
+        None => call_op.tys[0].is_void(),
+    };
+    is_self && returns_result && call_op.args.len() == func.params.len() + 1
+}
+
+/// Returns true if every `alloca` is in the entry block and used only as the
+/// address of `load`s and `store`s.
+fn allocas_are_local(func: &Function) -> bool {
+    let entry = func.entry_block().unwrap();
+    func.layout
+        .block_iter()
+        .flat_map(|block| func.layout.inst_iter(block))
+        .map(|inst| func.data.inst_ref(inst))
+        .filter(|inst| inst.opcode == Opcode::Alloca)
+        .all(|alloca| {
+            let id = alloca.id.unwrap();
+            alloca.parent == entry
+                && func.data.users_of(id).iter().all(|&user| {
+                    let is_addr =
+                        |val: ValueId| *func.data.value_ref(val) == Value::Instruction(id);
+                    match &func.data.inst_ref(user).operand {
+                        Operand::Load(Load { addr, .. }) => is_addr(*addr),
+                        Operand::Store(Store { args, .. }) => !is_addr(args[0]),
+                        _ => false,
+                    }
+                })
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::run_on_function;
+    use crate::pass::transform::run_on_f;
+
+    #[test]
+    fn accumulator_loop() {
+        let (eliminated, printed) = run_on_f(
+            run_on_function,
+            r#"
+define i32 @f(i32 %0, i32 %1) {
+  %3 = alloca i32, align 4
+  store i32 %1, i32* %3, align 4
+  %4 = icmp eq i32 %0, 0
+  br i1 %4, label %5, label %7
+5:
+  %6 = load i32, i32* %3, align 4
+  ret i32 %6
+7:
+  %8 = sub i32 %0, 1
+  %9 = add i32 %1, %0
+  %10 = call i32 @f(i32 %8, i32 %9)
+  ret i32 %10
+}"#,
+        );
+        assert_eq!(eliminated, 1);
+        insta::assert_snapshot!(printed);
+    }
+
+    #[test]
+    fn not_tail_calls() {
+        // The result is used, the allocation escapes, or another function is called.
+        for source in [
+            r#"
+define i32 @f(i32 %0) {
+  %2 = call i32 @f(i32 %0)
+  %3 = add i32 %2, 1
+  ret i32 %3
+}"#,
+            r#"
+define void @f(i32* %0) {
+  %2 = alloca i32, align 4
+  call void @f(i32* %2)
+  ret void
+}"#,
+            r#"
+declare i32 @g(i32)
+define i32 @f(i32 %0) {
+  %2 = call i32 @g(i32 %0)
+  ret i32 %2
+}"#,
+        ] {
+            assert_eq!(run_on_f(run_on_function, source).0, 0);
+        }
+    }
+}
@@ -0,0 +1,11 @@
+//! The names the `std` prelude brings in from `alloc`, imported explicitly
+//! so that the crate builds without `std`.
+
+pub use alloc::{
+    borrow::ToOwned,
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
@@ -0,0 +1,27 @@
+//! Locks, from `parking_lot` with the `std` feature and spinning without it.
+
+#[cfg(feature = "std")]
+pub use parking_lot::{RawMutex, RawRwLock};
+
+#[cfg(not(feature = "std"))]
+pub type RawMutex = spin::Mutex<()>;
+
+#[cfg(not(feature = "std"))]
+pub type RawRwLock = spin::RwLock<()>;
+
+pub type Mutex<T> = lock_api::Mutex<RawMutex, T>;
+pub type RwLock<T> = lock_api::RwLock<RawRwLock, T>;
+pub type RwLockReadGuard<'a, T> = lock_api::RwLockReadGuard<'a, RawRwLock, T>;
+pub type RwLockWriteGuard<'a, T> = lock_api::RwLockWriteGuard<'a, RawRwLock, T>;
+pub type MappedRwLockReadGuard<'a, T> = lock_api::MappedRwLockReadGuard<'a, RawRwLock, T>;
+pub type MappedRwLockWriteGuard<'a, T> = lock_api::MappedRwLockWriteGuard<'a, RawRwLock, T>;
+
+/// Locks `lock` for reading even if a writer is waiting, so that a thread
+/// can take several read locks at once. Spinning locks never make readers
+/// wait for waiting writers.
+pub fn read_recursive<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    #[cfg(feature = "std")]
+    return lock.read_recursive();
+    #[cfg(not(feature = "std"))]
+    return lock.read();
+}
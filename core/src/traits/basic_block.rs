@@ -1,6 +1,7 @@
+use crate::collections::FxHashSet;
+use crate::prelude::*;
+use core::fmt;
 use id_arena::Id;
-use rustc_hash::FxHashSet;
-use std::fmt;
 
 pub trait BasicBlock: Sized + fmt::Debug {
     fn preds(&self) -> &FxHashSet<Id<Self>>;
@@ -13,4 +14,9 @@ pub trait BasicBlockData<BB: BasicBlock> {
 
 pub trait BasicBlockLayout<BB: BasicBlock> {
     fn order(&self) -> Box<dyn Iterator<Item = Id<BB>> + '_>;
+
+    /// Returns the entry block, which is the first block in the order.
+    fn entry(&self) -> Option<Id<BB>> {
+        self.order().next()
+    }
 }
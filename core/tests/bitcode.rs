@@ -0,0 +1,29 @@
+use std::{fs, path::Path};
+use vicis_core::{bitcode, ir::module};
+
+/// Checks that every `.bc` file in `tests/bitcode`, assembled from the `.ll`
+/// file of the same name by `llvm-as`, reads into the module the `.ll` file
+/// parses into.
+#[test]
+fn bitcode() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/bitcode");
+    let mut num_files = 0;
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "bc") {
+            continue;
+        }
+        let bc = bitcode::parse(&fs::read(&path).unwrap())
+            .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        let ll = module::parse_assembly(&fs::read_to_string(path.with_extension("ll")).unwrap())
+            .unwrap();
+        assert_eq!(
+            format!("{:?}", bc),
+            format!("{:?}", ll),
+            "{}",
+            path.display()
+        );
+        num_files += 1;
+    }
+    assert!(num_files > 0);
+}
@@ -0,0 +1,12 @@
+source_filename = "comdat.c"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+$inl = comdat any
+
+@v = linkonce_odr dso_local hidden global i32 1, comdat($inl), align 4
+@s = global i32 2, section ".mydata", align 4
+
+define linkonce_odr dso_local protected i32 @inl() section ".text.inl" comdat align 16 {
+  ret i32 0
+}
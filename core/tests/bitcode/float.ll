@@ -0,0 +1,29 @@
+source_filename = "float.ll"
+
+@half = global float 5.000000e-01
+@tenth = global double 1.000000e-01
+
+define double @poly(double %x, float %y) {
+  %1 = fmul double %x, 2.500000e+00
+  %2 = fpext float %y to double
+  %3 = fadd fast double %1, %2
+  %4 = fsub double %3, 0x7FF0000000000000
+  %5 = fdiv double %4, %x
+  %6 = frem double %5, 3.000000e+00
+  %7 = fneg double %6
+  ret double %7
+}
+
+define i32 @convert(double %x, i32 %y) {
+  %1 = fptosi double %x to i32
+  %2 = fptoui double %x to i64
+  %3 = sitofp i32 %y to float
+  %4 = uitofp i64 %2 to double
+  %5 = fptrunc double %4 to float
+  %6 = fcmp olt float %3, %5
+  %7 = fcmp une double %x, 0.000000e+00
+  %8 = and i1 %6, %7
+  %9 = zext i1 %8 to i32
+  %10 = add i32 %1, %9
+  ret i32 %10
+}
@@ -0,0 +1,10 @@
+source_filename = "i128.ll"
+
+@small = global i128 -5
+@wide = global i128 18446744073709551621
+
+define i128 @mul_add(i128 %x, i128 %y) {
+  %1 = mul i128 %x, %y
+  %2 = add i128 %1, 340282366920938463463374607431768211
+  ret i128 %2
+}
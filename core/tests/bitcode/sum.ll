@@ -0,0 +1,61 @@
+source_filename = "sum.c"
+target datalayout = "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"
+target triple = "x86_64-pc-linux-gnu"
+
+%struct.pair = type { i32, i64 }
+
+@.str = private unnamed_addr constant [13 x i8] c"sum = %d %s\0A\00", align 1
+@counter = global i32 0, align 4
+@table = internal global i32* @counter, align 8
+@p = global %struct.pair { i32 1, i64 -2 }, align 8
+
+define dso_local i32 @sum(i32 %n) #0 {
+entry:
+  %acc = alloca i32, align 4
+  store i32 0, i32* %acc, align 4
+  br label %loop
+
+loop:
+  %i = phi i32 [ 0, %entry ], [ %next, %body ]
+  %cmp = icmp slt i32 %i, %n
+  br i1 %cmp, label %body, label %exit
+
+body:
+  %0 = load i32, i32* %acc, align 4
+  %add = add nsw i32 %0, %i
+  store i32 %add, i32* %acc, align 4
+  %next = add nuw nsw i32 %i, 1
+  br label %loop
+
+exit:
+  %1 = load i32, i32* %acc, align 4
+  ret i32 %1
+}
+
+define dso_local i32 @main() #0 {
+  %1 = call i32 @sum(i32 10)
+  %2 = getelementptr inbounds [13 x i8], [13 x i8]* @.str, i64 0, i64 0
+  %3 = call i32 (i8*, ...) @printf(i8* %2, i32 %1, i8* getelementptr inbounds ([13 x i8], [13 x i8]* @.str, i64 0, i64 0))
+  %4 = getelementptr inbounds %struct.pair, %struct.pair* @p, i32 0, i32 1
+  %5 = load i64, i64* %4, align 8
+  %6 = trunc i64 %5 to i32
+  %7 = sext i32 %6 to i64
+  %8 = zext i1 true to i32
+  %9 = bitcast i32* @counter to i8*
+  %10 = sub i32 %6, %8
+  %11 = mul i32 %10, 3
+  %12 = sdiv exact i32 %11, 2
+  %13 = srem i32 %12, 5
+  %14 = shl i32 %13, 1
+  %15 = lshr i32 %14, 1
+  %16 = and i32 %15, 255
+  %17 = or i32 %16, 256
+  %18 = insertvalue { i32, i64 } undef, i32 %17, 0
+  %19 = extractvalue { i32, i64 } %18, 0
+  ret i32 %19
+}
+
+declare dso_local i32 @printf(i8*, ...) #1
+
+attributes #0 = { noinline nounwind optnone uwtable "frame-pointer"="all" }
+attributes #1 = { "frame-pointer"="all" }
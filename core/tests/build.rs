@@ -16,3 +16,95 @@ fn build() {
 
     insta::assert_debug_snapshot!(module);
 }
+
+#[test]
+fn build_control_flow() {
+    use vicis_core::ir::{
+        function::{instruction::ICmpCond, Parameter},
+        types::I1,
+        value::ConstantInt,
+    };
+
+    let mut module = Module::default();
+    module.create_function("callee", I32, vec![Parameter::new(I32)], false);
+    let func_id = module.create_function("func", I32, vec![Parameter::new(I32)], false);
+    let func = &mut module.functions_mut()[func_id];
+
+    let mut builder = Builder::new(func);
+    let entry = builder.create_block();
+    let then_ = builder.create_block();
+    let merge = builder.create_block();
+
+    builder.switch_to_block(entry);
+    let arg = builder.arg(0);
+    let slot = builder.build_alloca(I32);
+    builder.build_store(I32, arg, slot);
+    let x = builder.build_load(I32, slot);
+    let one = builder.value(1i32);
+    let y = builder.build_add(I32, x, one);
+    let zero = builder.value(0i32);
+    let cond = builder.build_icmp(ICmpCond::Sgt, I32, y, zero);
+    builder.build_cond_br(cond, then_, merge);
+
+    builder.switch_to_block(then_);
+    let callee = builder.global_ref("callee");
+    let z = builder.build_call(I32, callee, vec![(I32, y)]).unwrap();
+    builder.build_br(merge);
+
+    builder.switch_to_block(merge);
+    let result = builder.build_phi(I32, vec![(y, entry), (z, then_)]);
+    builder.build_ret(result);
+
+    // Insert an instruction before the terminator of `then_`.
+    let br = builder.func().layout.block_node(then_).last_inst().unwrap();
+    builder.position_before(br);
+    let t = builder.value(ConstantInt::Int1(true));
+    builder.build_icmp(ICmpCond::Eq, I1, t, t);
+
+    let func = &module.functions()[func_id];
+    assert!(func.data.block_ref(entry).succs.contains(&then_));
+    assert!(func.data.block_ref(merge).preds.contains(&entry));
+    assert!(func.data.block_ref(merge).preds.contains(&then_));
+    insta::assert_debug_snapshot!(module.functions()[func_id]);
+}
+
+// Code generated by `builder_code::emit_builder_code` from examples/phi_loop.ll.
+#[test]
+fn build_from_emitted_code() {
+    use vicis_core::ir::{function::instruction::ICmpCond, types, value::ConstantInt};
+
+    let mut module = Module::default();
+    let func_id = module.create_function("main", types::I32, vec![], false);
+    let func = &mut module.functions_mut()[func_id];
+    let mut builder = Builder::new(func);
+    let bb0 = builder.create_block();
+    let bb1 = builder.create_block();
+    let bb2 = builder.create_block();
+    let bb3 = builder.create_block();
+    let bb4 = builder.create_block();
+    builder.switch_to_block(bb0);
+    builder.build_br(bb1);
+    builder.switch_to_block(bb1);
+    let v1 = builder.build_phi(types::I32, vec![]);
+    let v2 = builder.build_phi(types::I32, vec![]);
+    let c2 = builder.value(ConstantInt::Int32(10));
+    let v3 = builder.build_icmp(ICmpCond::Sle, types::I32, v2, c2);
+    builder.build_cond_br(v3, bb2, bb4);
+    builder.switch_to_block(bb2);
+    let v5 = builder.build_add(types::I32, v1, v2);
+    builder.build_br(bb3);
+    builder.switch_to_block(bb3);
+    let c3 = builder.value(ConstantInt::Int32(1));
+    let v7 = builder.build_add(types::I32, v2, c3);
+    builder.build_br(bb1);
+    builder.switch_to_block(bb4);
+    builder.build_ret(v1);
+    let c0 = builder.value(ConstantInt::Int32(0));
+    builder.add_phi_incoming(v1, c0, bb0);
+    builder.add_phi_incoming(v1, v5, bb3);
+    let c1 = builder.value(ConstantInt::Int32(1));
+    builder.add_phi_incoming(v2, c1, bb0);
+    builder.add_phi_incoming(v2, v7, bb3);
+
+    insta::assert_debug_snapshot!(module.functions()[func_id]);
+}
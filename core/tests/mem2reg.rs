@@ -176,3 +176,33 @@ define dso_local i32 @main() {
     pm.run_on_module(&mut module);
     insta::assert_debug_snapshot!(module);
 }
+
+#[test]
+fn mem2reg_uninitialized() {
+    let ir = r#"
+define dso_local i32 @main(i32 %0) {
+  %2 = alloca i32, align 4
+  %3 = icmp eq i32 %0, 0
+  br i1 %3, label %4, label %5
+
+4:                                                ; preds = %1
+  store i32 1, i32* %2, align 4
+  br label %6
+
+5:                                                ; preds = %1
+  br label %6
+
+6:                                                ; preds = %5, %4
+  %7 = load i32, i32* %2, align 4
+  %8 = add nsw i32 %7, 1
+  store i32 %8, i32* %2, align 4
+  %9 = load i32, i32* %2, align 4
+  ret i32 %9
+}
+"#;
+    let mut module = module::parse_assembly(ir).expect("failed to parse ir");
+    let mut pm = PassManager::new();
+    pm.add_transform(Mem2RegPass);
+    pm.run_on_module(&mut module);
+    insta::assert_debug_snapshot!(module);
+}
@@ -0,0 +1,71 @@
+//! Call-heavy workloads for the interpreter. Run with `cargo bench -p vicis-interpreter`.
+
+use std::time::Instant;
+use vicis_core::ir::module;
+use vicis_interpreter::{generic_value::GenericValue, interpreter};
+
+const FIB: &str = r#"
+define dso_local i32 @fib(i32 %0) {
+  %2 = icmp slt i32 %0, 2
+  br i1 %2, label %3, label %4
+3:
+  ret i32 %0
+4:
+  %5 = sub nsw i32 %0, 1
+  %6 = call i32 @fib(i32 %5)
+  %7 = sub nsw i32 %0, 2
+  %8 = call i32 @fib(i32 %7)
+  %9 = add nsw i32 %6, %8
+  ret i32 %9
+}
+"#;
+
+const ACK: &str = r#"
+define dso_local i32 @ack(i32 %0, i32 %1) {
+  %3 = icmp eq i32 %0, 0
+  br i1 %3, label %4, label %6
+4:
+  %5 = add nsw i32 %1, 1
+  ret i32 %5
+6:
+  %7 = icmp eq i32 %1, 0
+  br i1 %7, label %8, label %11
+8:
+  %9 = sub nsw i32 %0, 1
+  %10 = call i32 @ack(i32 %9, i32 1)
+  ret i32 %10
+11:
+  %12 = sub nsw i32 %1, 1
+  %13 = call i32 @ack(i32 %0, i32 %12)
+  %14 = sub nsw i32 %0, 1
+  %15 = call i32 @ack(i32 %14, i32 %13)
+  ret i32 %15
+}
+"#;
+
+fn bench(name: &str, source: &str, func: &str, args: Vec<GenericValue>, expected: i32) {
+    let decls: String = (0..100)
+        .map(|i| format!("declare i32 @external{}(i32)\n", i))
+        .collect();
+    let module = module::parse_assembly(&(decls + source)).unwrap();
+    let ctx = interpreter::Context::new(&module);
+    let func_id = module.find_function_by_name(func).unwrap();
+    let start = Instant::now();
+    let ret = interpreter::run_function(&ctx, func_id, args);
+    let elapsed = start.elapsed();
+    assert_eq!(ret, Ok(GenericValue::Int32(expected)));
+    println!("{:<12} {:>10.2?}", name, elapsed);
+}
+
+fn main() {
+    // Real-world modules declare many external functions, which makes resolving callees by
+    // name expensive.
+    bench("fib(24)", FIB, "fib", vec![GenericValue::Int32(24)], 46368);
+    bench(
+        "ack(2, 300)",
+        ACK,
+        "ack",
+        vec![GenericValue::Int32(2), GenericValue::Int32(300)],
+        603,
+    );
+}
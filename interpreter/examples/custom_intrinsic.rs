@@ -0,0 +1,50 @@
+//! Defines a `vicis.trace` intrinsic, runs a module calling it in the
+//! interpreter, then lowers it to a call to a runtime function `__vicis_trace`
+//! and compiles the module to x86_64 assembly.
+
+use std::cell::RefCell;
+use vicis_codegen::codegen::{isa::x86_64::X86_64, lower::compile_module};
+use vicis_core::ir::{
+    intrinsic::{IntrinsicLowering, IntrinsicRegistry},
+    module,
+};
+use vicis_interpreter::{generic_value::GenericValue, interpreter};
+
+const SOURCE: &str = r#"
+define dso_local i32 @main() {
+  call void @vicis.trace(i32 1)
+  %1 = add i32 40, 2
+  call void @vicis.trace(i32 %1)
+  ret i32 0
+}"#;
+
+fn main() {
+    let mut intrinsics = IntrinsicRegistry::new();
+    intrinsics.register(
+        "vicis.trace",
+        IntrinsicLowering::Call("__vicis_trace".to_string()),
+    );
+
+    // `@vicis.trace` is not declared in the source.
+    let mut module = module::parse_assembly(SOURCE).expect("failed to parse LLVM Assembly");
+    intrinsics.declare_missing(&mut module);
+
+    // Interpreter behavior.
+    let trace = RefCell::new(vec![]);
+    {
+        let ctx = interpreter::Context::new(&module).with_intrinsic("vicis.trace", |args| {
+            trace.borrow_mut().push(args[0]);
+            GenericValue::Void
+        });
+        let main = module.find_function_by_name("main").unwrap();
+        let ret = interpreter::run_function(&ctx, main, vec![]).expect("execution trapped");
+        println!("interpreter returned {:?}", ret);
+    }
+    println!("traced {:?}", trace.into_inner());
+
+    // Default lowering, then code generation.
+    intrinsics.lower_module(&mut module);
+    println!("{:?}", module);
+    let mach_module = compile_module(X86_64, &module).expect("failed to compile");
+    println!("{}", mach_module);
+}
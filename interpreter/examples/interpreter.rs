@@ -4,7 +4,10 @@ extern crate vicis_interpreter;
 
 use std::{fs, process};
 use structopt::StructOpt;
-use vicis_core::ir::module;
+use vicis_core::{
+    ir::module,
+    pass::{manifest::PipelineManifest, registry::PassRegistry},
+};
 use vicis_interpreter::interpreter;
 
 #[derive(Debug, StructOpt)]
@@ -14,12 +17,59 @@ pub struct Opt {
 
     #[structopt(long = "load")]
     pub libs: Vec<String>,
+
+    /// Other *.ll files to link with `ir_file`, e.g. other translation units.
+    #[structopt(long = "link")]
+    pub links: Vec<String>,
+
+    /// Comma separated passes to run before interpreting, e.g. `mem2reg,dce`.
+    #[structopt(long = "passes", default_value = "")]
+    pub passes: String,
+
+    /// Runs the pipeline recorded in a manifest instead of `--passes`.
+    #[structopt(long = "manifest")]
+    pub manifest: Option<String>,
+
+    /// Writes the manifest of the pipeline run to a file.
+    #[structopt(long = "emit-manifest")]
+    pub emit_manifest: Option<String>,
 }
 
 fn main() {
     let opt = Opt::from_args();
     let ir = fs::read_to_string(opt.ir_file).expect("failed to load *.ll file");
-    let module = module::parse_assembly(ir.as_str()).expect("failed to parse LLVM Assembly");
+    let mut module = module::parse_assembly(ir.as_str()).expect("failed to parse LLVM Assembly");
+    for file in &opt.links {
+        let ir = fs::read_to_string(file).expect("failed to load *.ll file");
+        let other = module::parse_assembly(ir.as_str()).expect("failed to parse LLVM Assembly");
+        if let Err(err) = module.link(other) {
+            eprintln!("{}: {}", file, err);
+            process::exit(1);
+        }
+    }
+    let manifest = match &opt.manifest {
+        Some(file) => {
+            let manifest: PipelineManifest = fs::read_to_string(file)
+                .expect("failed to load manifest")
+                .parse()
+                .expect("failed to parse manifest");
+            if !manifest.is_current_version() {
+                eprintln!(
+                    "warning: the manifest was produced by vicis {}",
+                    manifest.version
+                );
+            }
+            manifest
+        }
+        None => PipelineManifest::new(&opt.passes).with_target(module.target().triple(), &[]),
+    };
+    PassRegistry::with_default_module_passes()
+        .build_from_manifest(&manifest)
+        .expect("unknown pass")
+        .run_on(&mut module);
+    if let Some(file) = &opt.emit_manifest {
+        fs::write(file, manifest.to_string()).expect("failed to write manifest");
+    }
     let main = module
         .find_function_by_name("main")
         .expect("failed to lookup 'main'");
@@ -27,5 +77,5 @@ fn main() {
         .with_libs(opt.libs)
         .expect("failed to load library");
     let ret = interpreter::run_function(&ctx, main, vec![]);
-    process::exit(ret.expect("unknown error").sext_to_i64().unwrap_or(0) as i32)
+    process::exit(ret.expect("execution trapped").sext_to_i64().unwrap_or(0) as i32)
 }
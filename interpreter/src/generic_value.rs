@@ -1,14 +1,26 @@
+use crate::interpreter::{self, Context, TypeSize};
 use id_arena::Id;
+use std::fmt;
+use vicis_core::ir::{
+    function::FunctionId,
+    module::data_layout::DataLayout,
+    types::{self, ArrayType, CompoundType, Type, Types},
+    value::ConstantInt,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum GenericValue {
     Void,
     Int1(bool),
     Int8(i8),
+    Int16(i16),
     Int32(i32),
     Int64(i64),
+    Int128(i128),
     Ptr(*mut u8),
-    Id([u8; 16]),
+    Id([u64; 2]),
+    /// Result of an operation LLVM leaves undefined (see `TrapMode::Poison`).
+    Poison,
 }
 
 impl GenericValue {
@@ -33,10 +45,23 @@ impl GenericValue {
         }
     }
 
+    pub fn to_constant_int(self) -> Option<ConstantInt> {
+        match self {
+            Self::Int1(i) => Some(ConstantInt::Int1(i)),
+            Self::Int8(i) => Some(ConstantInt::Int8(i)),
+            Self::Int16(i) => Some(ConstantInt::Int16(i)),
+            Self::Int32(i) => Some(ConstantInt::Int32(i)),
+            Self::Int64(i) => Some(ConstantInt::Int64(i)),
+            Self::Int128(i) => Some(ConstantInt::Int128(i)),
+            _ => None,
+        }
+    }
+
     pub fn sext_to_i64(&self) -> Option<i64> {
         match self {
             Self::Int1(i) => Some(*i as i64),
             Self::Int8(i) => Some(*i as i64),
+            Self::Int16(i) => Some(*i as i64),
             Self::Int32(i) => Some(*i as i64),
             Self::Int64(i) => Some(*i),
             _ => None,
@@ -51,6 +76,150 @@ impl GenericValue {
     }
 
     pub fn id<T>(id: Id<T>) -> Self {
-        Self::Id(unsafe { ::std::mem::transmute::<Id<T>, [u8; 16]>(id) })
+        Self::Id(unsafe { ::std::mem::transmute::<Id<T>, [u64; 2]>(id) })
+    }
+
+    /// Returns an object formatting `self` as a value of type `ty`, e.g.
+    /// `{ 1, [2, 3], c"ab\00" }`. `self` must be a pointer to the value if
+    /// `ty` is an aggregate type, which is then read from memory.
+    pub fn display<'a>(&self, types: &'a Types, ty: Type) -> ValueDisplay<'a, 'a> {
+        ValueDisplay {
+            val: *self,
+            types,
+            ty,
+            ctx: None,
+        }
+    }
+}
+
+impl From<ConstantInt> for GenericValue {
+    fn from(i: ConstantInt) -> Self {
+        match i {
+            ConstantInt::Int1(i) => Self::Int1(i),
+            ConstantInt::Int8(i) => Self::Int8(i),
+            ConstantInt::Int16(i) => Self::Int16(i),
+            ConstantInt::Int32(i) => Self::Int32(i),
+            ConstantInt::Int64(i) => Self::Int64(i),
+            ConstantInt::Int128(i) => Self::Int128(i),
+        }
+    }
+}
+
+/// Formats a [`GenericValue`] according to its type. See [`GenericValue::display`].
+pub struct ValueDisplay<'a, 'm> {
+    val: GenericValue,
+    types: &'a Types,
+    ty: Type,
+    ctx: Option<&'a Context<'m>>,
+}
+
+impl<'a> ValueDisplay<'a, '_> {
+    /// Prints pointers into global variables as `@name+offset` and functions
+    /// as `@name`, looking them up in `ctx`.
+    pub fn with_context<'m>(self, ctx: &'a Context<'m>) -> ValueDisplay<'a, 'm> {
+        ValueDisplay {
+            val: self.val,
+            types: self.types,
+            ty: self.ty,
+            ctx: Some(ctx),
+        }
+    }
+
+    fn fmt_value(&self, f: &mut fmt::Formatter<'_>, val: GenericValue, ty: Type) -> fmt::Result {
+        let compound = self.types.get(ty);
+        match (val, compound.as_deref()) {
+            (
+                GenericValue::Ptr(ptr),
+                Some(CompoundType::Array(ArrayType {
+                    inner,
+                    num_elements,
+                })),
+            ) if *inner == types::I8 => {
+                let bytes = unsafe { std::slice::from_raw_parts(ptr, *num_elements as usize) };
+                write!(f, "c\"")?;
+                for &b in bytes {
+                    if b.is_ascii_graphic() && b != b'"' && b != b'\\' || b == b' ' {
+                        write!(f, "{}", b as char)?;
+                    } else {
+                        write!(f, "\\{:02X}", b)?;
+                    }
+                }
+                write!(f, "\"")
+            }
+            (
+                GenericValue::Ptr(ptr),
+                Some(CompoundType::Array(ArrayType {
+                    inner,
+                    num_elements,
+                })),
+            ) => {
+                let size = self.types.size_of(*inner);
+                write!(f, "[")?;
+                for i in 0..*num_elements as usize {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.fmt_element(f, unsafe { ptr.add(i * size) }, *inner)?;
+                }
+                write!(f, "]")
+            }
+            (GenericValue::Ptr(ptr), Some(CompoundType::Struct(s))) => {
+                let layout = DataLayout::default();
+                let offsets = self.types.field_offsets(ty, &layout);
+                write!(f, "{{ ")?;
+                for (i, (&elem, offset)) in s.elems.iter().zip(offsets).enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.fmt_element(f, unsafe { ptr.add(offset as usize) }, elem)?;
+                }
+                write!(f, " }}")
+            }
+            (_, Some(CompoundType::Alias(ty))) => self.fmt_value(f, val, *ty),
+            (GenericValue::Ptr(ptr), _) if ptr.is_null() => write!(f, "null"),
+            (GenericValue::Ptr(ptr), _) => match self.ctx.and_then(|ctx| ctx.symbolize(ptr)) {
+                Some((name, 0)) => write!(f, "@{}", name),
+                Some((name, offset)) => write!(f, "@{}+{}", name, offset),
+                None => write!(f, "{:?}", ptr),
+            },
+            (GenericValue::Id(_), _) => match self.ctx {
+                Some(ctx) => {
+                    let id = val.to_id::<FunctionId>().unwrap();
+                    write!(f, "@{}", ctx.function(*id).name())
+                }
+                None => write!(f, "<function>"),
+            },
+            (GenericValue::Void, _) => write!(f, "void"),
+            (GenericValue::Int1(i), _) => write!(f, "{}", i),
+            (GenericValue::Int8(i), _) => write!(f, "{}", i),
+            (GenericValue::Int16(i), _) => write!(f, "{}", i),
+            (GenericValue::Int32(i), _) => write!(f, "{}", i),
+            (GenericValue::Int64(i), _) => write!(f, "{}", i),
+            (GenericValue::Int128(i), _) => write!(f, "{}", i),
+            (GenericValue::Poison, _) => write!(f, "poison"),
+        }
+    }
+
+    /// Formats the value of type `ty` stored at `ptr`.
+    fn fmt_element(&self, f: &mut fmt::Formatter<'_>, ptr: *mut u8, ty: Type) -> fmt::Result {
+        if is_aggregate(self.types, ty) {
+            self.fmt_value(f, GenericValue::Ptr(ptr), ty)
+        } else {
+            self.fmt_value(f, interpreter::load(self.types, ty, ptr), ty)
+        }
+    }
+}
+
+fn is_aggregate(types: &Types, ty: Type) -> bool {
+    match types.get(ty).as_deref() {
+        Some(CompoundType::Array(_) | CompoundType::Struct(_)) => true,
+        Some(CompoundType::Alias(ty)) => is_aggregate(types, *ty),
+        _ => false,
+    }
+}
+
+impl fmt::Display for ValueDisplay<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_value(f, self.val, self.ty)
     }
 }
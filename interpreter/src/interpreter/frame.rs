@@ -1,4 +1,5 @@
 use rustc_hash::FxHashMap;
+use std::{alloc, mem};
 
 use super::Context;
 use crate::{
@@ -6,87 +7,135 @@ use crate::{
     interpreter::TypeSize
 };
 use vicis_core::ir::{
-    function::{instruction::InstructionId, Function},
+    function::{instruction::InstructionId, Function, FunctionId},
+    module::{name::Name, Module},
     value::{ConstantData, ConstantExpr, ConstantInt, Value, ValueId},
 };
 
 pub struct StackFrame<'a> {
     pub ctx: &'a Context<'a>,
     pub func: &'a Function,
-    val_map: FxHashMap<InstructionId, GenericValue>,
+    pub func_id: FunctionId,
+    consts: &'a [Option<GenericValue>],
+    val_map: Vec<Option<GenericValue>>,
     args: Vec<GenericValue>,
+    /// Taint of each instruction and argument. Empty unless taint tracking is enabled.
+    taint: Vec<bool>,
+    arg_taint: Vec<bool>,
+    /// Memory allocated by the allocas run in this frame, freed when the frame is dropped.
+    allocas: Vec<(InstructionId, *mut u8, alloc::Layout)>,
 }
 
 impl<'a> StackFrame<'a> {
-    pub fn new(ctx: &'a Context<'a>, func: &'a Function, args: Vec<GenericValue>) -> Self {
+    pub fn new(ctx: &'a Context<'a>, func_id: FunctionId, args: Vec<GenericValue>) -> Self {
+        let func = ctx.function(func_id);
+        let (taint, arg_taint) = match &ctx.taint {
+            Some(state) => (
+                vec![false; func.data.instructions.len()],
+                mem::take(&mut state.borrow_mut().args),
+            ),
+            None => (vec![], vec![]),
+        };
         Self {
             ctx,
             func,
-            val_map: FxHashMap::default(),
+            func_id,
+            consts: &ctx.consts[&func_id],
+            val_map: vec![None; func.data.instructions.len()],
             args,
+            taint,
+            arg_taint,
+            allocas: vec![],
         }
     }
 
+    /// Records that `ptr` was allocated by the alloca `id` in this frame.
+    pub fn add_alloca(&mut self, id: InstructionId, ptr: *mut u8, layout: alloc::Layout) {
+        self.allocas.push((id, ptr, layout));
+    }
+
     pub fn get_inst_val(&self, id: InstructionId) -> Option<GenericValue> {
-        self.val_map.get(&id).copied()
+        self.val_map[id.index()]
     }
 
     pub fn set_inst_val(&mut self, id: InstructionId, val: GenericValue) {
-        self.val_map.insert(id, val);
+        self.val_map[id.index()] = Some(val);
+    }
+
+    pub fn is_tainted(&self, id: ValueId) -> bool {
+        match self.func.data.value_ref(id) {
+            Value::Instruction(id) => self.taint.get(id.index()).copied().unwrap_or(false),
+            Value::Argument(i) => self.arg_taint.get(*i).copied().unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    pub fn set_taint(&mut self, id: InstructionId, tainted: bool) {
+        self.taint[id.index()] = tainted;
     }
 
     pub fn get_val(&self, id: ValueId) -> Option<GenericValue> {
         match self.func.data.value_ref(id) {
             Value::Instruction(id) => self.get_inst_val(*id),
-            Value::Constant(ConstantData::Int(ConstantInt::Int1(i))) => {
-                Some(GenericValue::Int1(*i))
-            }
-            Value::Constant(ConstantData::Int(ConstantInt::Int8(i))) => {
-                Some(GenericValue::Int8(*i))
-            }
-            Value::Constant(ConstantData::Int(ConstantInt::Int32(i))) => {
-                Some(GenericValue::Int32(*i))
+            Value::Argument(i) => self.args.get(*i).copied(),
+            _ => self.consts[id.index()],
+        }
+    }
+}
+
+impl Drop for StackFrame<'_> {
+    fn drop(&mut self) {
+        for (id, ptr, layout) in mem::take(&mut self.allocas) {
+            if let Some(watch) = &self.ctx.watch {
+                let start = ptr as usize;
+                watch
+                    .borrow_mut()
+                    .freed(self.func_id, id, start..start + layout.size());
             }
-            Value::Constant(ConstantData::Int(ConstantInt::Int64(i))) => {
-                Some(GenericValue::Int64(*i))
+            unsafe { alloc::dealloc(ptr, layout) }
+        }
+    }
+}
+
+/// Evaluates a constant once so that it does not need to be resolved by name at every use.
+pub fn resolve_constant(
+    module: &Module,
+    globals: &FxHashMap<Name, GenericValue>,
+    konst: &ConstantData,
+) -> Option<GenericValue> {
+    match konst {
+        ConstantData::Int(i) => Some((*i).into()),
+        ConstantData::GlobalRef(name) => {
+            if let Some(f) = module.find_function_by_name(name.to_str().unwrap()) {
+                return Some(GenericValue::id(f));
             }
-            Value::Constant(ConstantData::GlobalRef(name)) => {
-                if let Some(f) = self
-                    .ctx
-                    .module
-                    .find_function_by_name(name.to_string().unwrap())
-                {
-                    return Some(GenericValue::id(f));
-                }
-                if let Some(g) = self.ctx.globals.get(name) {
-                    return Some(*g);
-                }
-                None
+            if let Some(g) = globals.get(name) {
+                return Some(*g);
             }
-            Value::Argument(i) => self.args.get(*i).copied(),
-            Value::Constant(ConstantData::Expr(ConstantExpr::GetElementPtr { args, .. })) => {
-                match args[0] {
-                    ConstantData::GlobalRef(ref name) => {
-                        let n = match args[2] {
-                            ConstantData::Int(ConstantInt::Int32(n)) => n as i64,
-                            ConstantData::Int(ConstantInt::Int64(n)) => n,
-                            _ => todo!(),
-                        };
-                        match self.ctx.globals.get(name).copied() {
-                            Some(GenericValue::Ptr(v)) => {
-                                let types = &self.ctx.module.types;
-                                let ty = types.get_element(self.ctx.module.global_variables().get(name).unwrap().ty).unwrap();
-                                let sz = types.size_of(ty) as i64;
-                                Some(GenericValue::Ptr( ((v as i64) + n*sz) as *mut u8))
-                            }
-                            Some(a) => Some(a),
-                            None => None
-                        }
-                    }
+            None
+        }
+        ConstantData::Expr(ConstantExpr::GetElementPtr { args, .. }) => match args[0] {
+            ConstantData::GlobalRef(ref name) => {
+                let n = match args[2] {
+                    ConstantData::Int(ConstantInt::Int32(n)) => n as i64,
+                    ConstantData::Int(ConstantInt::Int64(n)) => n,
                     _ => todo!(),
+                };
+                match globals.get(name).copied() {
+                    Some(GenericValue::Ptr(v)) => {
+                        let types = &module.types;
+                        let ty = types
+                            .get_element(module.global_variables().get(name).unwrap().ty)
+                            .unwrap();
+                        let sz = types.size_of(ty) as i64;
+                        Some(GenericValue::Ptr(((v as i64) + n * sz) as *mut u8))
+                    }
+                    Some(a) => Some(a),
+                    None => None,
                 }
             }
-            _ => None,
-        }
+            _ => todo!(),
+        },
+        _ => None,
     }
 }
@@ -1,4 +1,8 @@
 mod frame;
+pub mod symbolic;
+mod taint;
+mod trap;
+mod watch;
 
 extern crate libffi;
 extern crate libloading;
@@ -6,7 +10,16 @@ extern crate libloading;
 use super::generic_value::GenericValue;
 use frame::StackFrame;
 use rustc_hash::FxHashMap;
-use std::{alloc, ffi, os::raw::c_void, ptr};
+use std::{
+    alloc,
+    cell::{Cell, RefCell},
+    ffi, mem,
+    os::raw::c_void,
+    ptr,
+};
+use taint::TaintState;
+pub use taint::{TaintReport, TaintSink};
+pub use trap::{BacktraceFrame, Trap, TrapKind, TrapMode};
 use vicis_core::ir::{
     function::{
         basic_block::BasicBlockId,
@@ -16,39 +29,139 @@ use vicis_core::ir::{
         },
         Function, FunctionId,
     },
-    module::{name::Name, Module},
-    types::{self, ArrayType, CompoundType, Type, Types},
-    value::{ConstantArray, ConstantData, ValueId},
+    module::{
+        data_layout::DataLayout, global_variable::GlobalVariable, linkage::Linkage, name::Name,
+        Module,
+    },
+    types::{self, Type, Types},
+    value::{fold, ConstantArray, ConstantData, ConstantInt, Value, ValueId},
 };
+use watch::WatchState;
+pub use watch::{CallSite, WatchAccess, WatchHit, WatchTarget};
 
 pub struct Context<'a> {
+    /// The first module. See [`Context::new_multi`] for running several modules.
     pub module: &'a Module,
-    globals: FxHashMap<Name, GenericValue>,
+    modules: Vec<&'a Module>,
+    /// Pre-resolved constants of each function, indexed by value id.
+    consts: FxHashMap<FunctionId, Vec<Option<GenericValue>>>,
+    /// The externally visible function definitions, which declarations of the
+    /// same name in any module resolve to.
+    definitions: FxHashMap<&'a str, FunctionId>,
     libs: Vec<libloading::Library>,
+    trap_mode: TrapMode,
+    intrinsics: FxHashMap<String, Box<IntrinsicFn<'a>>>,
+    /// The addresses of the global variables of each module.
+    globals: Vec<FxHashMap<Name, GenericValue>>,
+    taint: Option<RefCell<TaintState>>,
+    watch: Option<RefCell<WatchState>>,
+    watch_handler: Option<Box<WatchHandler<'a>>>,
+    /// The number of instructions that may still be run.
+    step_budget: Option<Cell<u64>>,
 }
 
+/// Implementation of a custom intrinsic. See `vicis_core::ir::intrinsic`.
+pub type IntrinsicFn<'a> = dyn Fn(&[GenericValue]) -> GenericValue + 'a;
+
+/// Called on each watchpoint hit. Returns true to stop execution with [`TrapKind::Watchpoint`].
+pub type WatchHandler<'a> = dyn Fn(&WatchHit) -> bool + 'a;
+
 pub fn run_function(
     ctx: &Context,
     func_id: FunctionId,
     args: Vec<GenericValue>,
-) -> Option<GenericValue> {
-    let func = &ctx.module.functions()[func_id];
+) -> Result<GenericValue, Trap> {
+    let func_id = ctx.resolve(func_id);
+    let func = ctx.function(func_id);
 
     if func.is_prototype() {
-        return Some(call_external_func(ctx, func, &args));
+        if let Some(taint) = &ctx.taint {
+            let mut taint = taint.borrow_mut();
+            taint.ret = mem::take(&mut taint.args).contains(&true);
+        }
+        if let Some(intrinsic) = ctx.intrinsics.get(func.name()) {
+            return Ok(intrinsic(&args));
+        }
+        return Ok(call_external_func(ctx, func, &args));
     }
 
-    let mut frame = StackFrame::new(ctx, func, args);
-    let mut block = func.layout.first_block?;
+    let frame = StackFrame::new(ctx, func_id, args);
+    let block = func.entry_block().expect("function has no blocks");
+    run_frame(frame, block, None)
+}
+
+/// Same as [`run_function`], with the arguments at `tainted_args` marked as
+/// tainted. Requires taint tracking to be enabled on `ctx`.
+pub fn run_function_with_taint(
+    ctx: &Context,
+    func_id: FunctionId,
+    args: Vec<GenericValue>,
+    tainted_args: &[usize],
+) -> Result<GenericValue, Trap> {
+    let taint = ctx.taint.as_ref().expect("taint tracking is not enabled");
+    let mut arg_taint = vec![false; args.len()];
+    for &i in tainted_args {
+        arg_taint[i] = true;
+    }
+    taint.borrow_mut().args = arg_taint;
+    run_function(ctx, func_id, args)
+}
+
+/// Runs `func_id` from right before `at` instead of from its entry, with
+/// `values` as the results of the instructions executed so far. `at` must not
+/// be a phi. This lets compiled code deoptimize and continue in the interpreter.
+pub fn resume_function(
+    ctx: &Context,
+    func_id: FunctionId,
+    args: Vec<GenericValue>,
+    at: InstructionId,
+    values: Vec<(InstructionId, GenericValue)>,
+) -> Result<GenericValue, Trap> {
+    let func = ctx.function(func_id);
+    let mut frame = StackFrame::new(ctx, func_id, args);
+    for (inst, val) in values {
+        frame.set_inst_val(inst, val);
+    }
+    let block = func
+        .layout
+        .block_of(at)
+        .expect("instruction must be placed in a block");
+    run_frame(frame, block, Some(at))
+}
+
+fn run_frame(
+    mut frame: StackFrame,
+    mut block: BasicBlockId,
+    mut resume_at: Option<InstructionId>,
+) -> Result<GenericValue, Trap> {
+    let func = frame.func;
+    let ctx = frame.ctx;
     let mut last_block = block; // TODO: We need a more elegant way.
 
     'main: loop {
+        let start = resume_at.take();
         for (inst_id, inst) in func
             .layout
             .inst_iter(block)
-            .into_iter()
+            .skip_while(|&id| start.is_some_and(|at| at != id))
             .map(|id| (id, func.data.inst_ref(id)))
         {
+            let trap = |kind| Trap::new(kind, func, inst_id);
+            if let Some(budget) = &ctx.step_budget {
+                if budget.get() == 0 {
+                    return Err(trap(TrapKind::BudgetExceeded));
+                }
+                budget.set(budget.get() - 1);
+            }
+            if let Some(taint) = &ctx.taint {
+                taint::propagate(
+                    &mut frame,
+                    &mut taint.borrow_mut(),
+                    inst_id,
+                    inst,
+                    last_block,
+                );
+            }
             match &inst.operand {
                 Operand::Alloca(Alloca {
                     tys,
@@ -61,9 +174,11 @@ pub fn run_function(
                     blocks,
                 }) => run_phi(&mut frame, last_block, inst_id, args, blocks),
                 Operand::Store(Store { tys, args, align }) => {
+                    watch(&frame, inst_id, WatchAccess::Write, args[1], tys[0]).map_err(trap)?;
                     run_store(&mut frame, tys, args, *align)
                 }
                 Operand::Load(Load { tys, addr, align }) => {
+                    watch(&frame, inst_id, WatchAccess::Read, *addr, tys[0]).map_err(trap)?;
                     run_load(&mut frame, inst_id, tys, *addr, *align)
                 }
                 Operand::IntBinary(IntBinary {
@@ -72,7 +187,7 @@ pub fn run_function(
                     nuw: _,
                     exact: _,
                     args,
-                }) => run_int_binary(&mut frame, inst_id, inst.opcode, args),
+                }) => run_int_binary(&mut frame, inst_id, inst.opcode, args).map_err(trap)?,
                 Operand::ICmp(ICmp { ty: _, args, cond }) => {
                     run_icmp(&mut frame, inst_id, args, *cond)
                 }
@@ -84,9 +199,17 @@ pub fn run_function(
                     tys,
                     args,
                 }) => run_gep(&mut frame, inst_id, tys, args),
-                Operand::Call(Call { tys, args, .. }) => run_call(&mut frame, inst_id, tys, args),
+                Operand::Call(Call { tys, args, .. }) => {
+                    run_call(&mut frame, inst_id, tys, args)?;
+                    if let Some(taint) = &ctx.taint {
+                        frame.set_taint(inst_id, taint.borrow().ret);
+                    }
+                }
                 Operand::CondBr(CondBr { arg, blocks }) => {
                     let arg = frame.get_val(*arg).unwrap();
+                    if arg == GenericValue::Poison {
+                        return Err(trap(TrapKind::BranchOnPoison));
+                    }
                     last_block = block;
                     block = blocks[if matches!(arg, GenericValue::Int1(true)) {
                         0
@@ -100,13 +223,13 @@ pub fn run_function(
                     block = *b;
                     continue 'main;
                 }
-                Operand::Ret(Ret { val, .. }) if val.is_none() => return Some(GenericValue::Void),
+                Operand::Ret(Ret { val, .. }) if val.is_none() => return Ok(GenericValue::Void),
                 Operand::Ret(Ret {
                     ty: _,
                     val: Some(val),
                 }) => {
                     let val = frame.get_val(*val).unwrap();
-                    return Some(val);
+                    return Ok(val);
                 }
                 _ => todo!("{:?}", inst.opcode),
             }
@@ -133,13 +256,30 @@ fn run_alloca(
     num_elements: &ConstantData,
     align: u32,
 ) {
+    // Each run gets fresh memory, so recursive calls do not share their locals.
+    let layout = alloca_layout(&frame.func.types, tys, num_elements, align);
+    let ptr = unsafe { alloc::alloc(layout) };
+    frame.set_inst_val(id, GenericValue::Ptr(ptr));
+    frame.add_alloca(id, ptr, layout);
+    if let Some(watch) = &frame.ctx.watch {
+        let start = ptr as usize;
+        watch
+            .borrow_mut()
+            .allocated(frame.func_id, id, start..start + layout.size());
+    }
+}
+
+fn alloca_layout(
+    types: &Types,
+    tys: &[Type],
+    num_elements: &ConstantData,
+    align: u32,
+) -> alloc::Layout {
     let alloc_ty = tys[0];
-    let alloc_sz = frame.func.types.size_of(alloc_ty) * num_elements.as_int().cast_to_usize();
+    // Zero-sized allocations are not allowed by the global allocator.
+    let alloc_sz = (types.size_of(alloc_ty) * num_elements.as_int().cast_to_usize()).max(1);
     let alloc_align = if align > 0 { align } else { 8 } as usize;
-    let ptr = unsafe {
-        alloc::alloc(alloc::Layout::from_size_align(alloc_sz, alloc_align).expect("layout err"))
-    };
-    frame.set_inst_val(id, GenericValue::Ptr(ptr));
+    alloc::Layout::from_size_align(alloc_sz, alloc_align).expect("layout err")
 }
 
 fn run_phi(
@@ -162,267 +302,444 @@ fn run_store(frame: &mut StackFrame, _tys: &[Type], args: &[ValueId], _align: u3
     let dst = args[1];
     let dst = frame.get_val(dst).unwrap().to_ptr().unwrap();
     let src = frame.get_val(src).unwrap();
+    store(dst, src)
+}
+
+fn store(dst: *mut u8, src: GenericValue) {
     match src {
         GenericValue::Int1(i) => unsafe { *(dst as *mut bool) = i }
         GenericValue::Int8(i) => unsafe { *(dst as *mut i8) = i }
+        GenericValue::Int16(i) => unsafe { *(dst as *mut i16) = i }
         GenericValue::Int32(i) => unsafe { *(dst as *mut i32) = i }
         GenericValue::Int64(i) => unsafe { *(dst as *mut i64) = i }
+        GenericValue::Int128(i) => unsafe { (dst as *mut i128).write_unaligned(i) }
         GenericValue::Ptr(p) => unsafe { *(dst as *mut *mut u8) = p }
+        GenericValue::Poison => {} // the memory is left as it is
         t => todo!("{:?}", t),
     }
 }
 
+/// Reports an access to the memory at `addr` to the watchpoints.
+fn watch(
+    frame: &StackFrame,
+    id: InstructionId,
+    access: WatchAccess,
+    addr: ValueId,
+    ty: Type,
+) -> Result<(), TrapKind> {
+    let Some(watch) = &frame.ctx.watch else {
+        return Ok(());
+    };
+    let addr = frame.get_val(addr).unwrap().to_ptr().unwrap() as usize;
+    let size = frame.func.types.size_of(ty);
+    let hits = {
+        let mut watch = watch.borrow_mut();
+        match watch.access(access, addr, size, frame.func.name(), id) {
+            Some(first) => watch.hits[first..].to_vec(),
+            None => return Ok(()),
+        }
+    };
+    let handler = frame.ctx.watch_handler.as_ref();
+    if hits
+        .iter()
+        .any(|hit| handler.is_none_or(|handler| handler(hit)))
+    {
+        return Err(TrapKind::Watchpoint);
+    }
+    Ok(())
+}
+
 fn run_load(frame: &mut StackFrame, id: InstructionId, tys: &[Type], addr: ValueId, _align: u32) {
-    let ty = tys[0];
     let addr = frame.get_val(addr).unwrap().to_ptr().unwrap();
-    let val = match ty {
+    let val = load(&frame.func.types, tys[0], addr);
+    frame.set_inst_val(id, val);
+}
+
+pub(crate) fn load(types: &Types, ty: Type, addr: *mut u8) -> GenericValue {
+    match ty {
+        types::I1 => GenericValue::Int1(unsafe { *(addr as *const bool) }),
         types::I8 => GenericValue::Int8(unsafe { *(addr as *const i8) }),
+        types::I16 => GenericValue::Int16(unsafe { *(addr as *const i16) }),
         types::I32 => GenericValue::Int32(unsafe { *(addr as *const i32) }),
         types::I64 => GenericValue::Int64(unsafe { *(addr as *const i64) }),
-        _ if ty.is_pointer(&frame.func.types) =>
+        types::I128 => GenericValue::Int128(unsafe { (addr as *const i128).read_unaligned() }),
+        _ if ty.is_pointer(types) =>
             GenericValue::Ptr(unsafe { *(addr as *const *mut u8) }),
         _ => todo!(),
-    };
-    frame.set_inst_val(id, val);
+    }
 }
 
-fn run_int_binary(frame: &mut StackFrame, id: InstructionId, opcode: Opcode, args: &[ValueId]) {
+fn run_int_binary(
+    frame: &mut StackFrame,
+    id: InstructionId,
+    opcode: Opcode,
+    args: &[ValueId],
+) -> Result<(), TrapKind> {
     let x = frame.get_val(args[0]).unwrap();
     let y = frame.get_val(args[1]).unwrap();
-    match opcode {
-        Opcode::Add => frame.set_inst_val(id, add(x, y).unwrap()),
-        Opcode::Sub => frame.set_inst_val(id, sub(x, y).unwrap()),
-        Opcode::Mul => frame.set_inst_val(id, mul(x, y).unwrap()),
-        Opcode::SDiv => frame.set_inst_val(id, sdiv(x, y).unwrap()),
-        Opcode::SRem => frame.set_inst_val(id, srem(x, y).unwrap()),
-        _ => todo!(),
+    if x == GenericValue::Poison || y == GenericValue::Poison {
+        frame.set_inst_val(id, GenericValue::Poison);
+        return Ok(());
+    }
+    let val = match int_binary(opcode, x, y) {
+        Ok(val) => val,
+        Err(_) if frame.ctx.trap_mode == TrapMode::Poison => GenericValue::Poison,
+        Err(kind) => return Err(kind),
     };
+    frame.set_inst_val(id, val);
+    Ok(())
 }
 
 fn run_icmp(frame: &mut StackFrame, id: InstructionId, args: &[ValueId], cond: ICmpCond) {
     let x = frame.get_val(args[0]).unwrap();
     let y = frame.get_val(args[1]).unwrap();
-    let res = match cond {
-        ICmpCond::Eq => eq(x, y).unwrap(),
-        ICmpCond::Ne => ne(x, y).unwrap(),
-        ICmpCond::Ugt => ugt(x, y).unwrap(),
-        ICmpCond::Uge => uge(x, y).unwrap(),
-        ICmpCond::Ult => ult(x, y).unwrap(),
-        ICmpCond::Ule => ule(x, y).unwrap(),
-        ICmpCond::Slt => slt(x, y).unwrap(),
-        ICmpCond::Sle => sle(x, y).unwrap(),
-        ICmpCond::Sgt => sgt(x, y).unwrap(),
-        ICmpCond::Sge => sge(x, y).unwrap(),
-    };
-    frame.set_inst_val(id, res);
+    if x == GenericValue::Poison || y == GenericValue::Poison {
+        frame.set_inst_val(id, GenericValue::Poison);
+        return;
+    }
+    frame.set_inst_val(id, icmp(cond, x, y));
 }
 
 fn run_cast(frame: &mut StackFrame, id: InstructionId, opcode: Opcode, tys: &[Type], arg: ValueId) {
-    let _from = tys[0];
-    let to = tys[1];
     let arg = frame.get_val(arg).unwrap();
-    let val = match opcode {
-        Opcode::Sext => {
-            let arg = arg.sext_to_i64().unwrap();
-            match to {
-                types::I32 => GenericValue::Int32(arg as i32),
-                types::I64 => GenericValue::Int64(arg),
-                _ => todo!(),
-            }
-        }
-        Opcode::Trunc => {
-            let arg = arg.sext_to_i64().unwrap();
-            match to {
-                types::I32 => GenericValue::Int32(arg as i32),
-                types::I64 => GenericValue::Int64(arg),
-                _ => todo!(),
-            }
-        }
-        t => todo!("cast {:?}", t),
+    frame.set_inst_val(id, cast(opcode, tys, arg))
+}
+
+/// Evaluates an integer binary operation on non-poison operands.
+fn int_binary(opcode: Opcode, x: GenericValue, y: GenericValue) -> Result<GenericValue, TrapKind> {
+    let (x, y) = (x.to_constant_int().unwrap(), y.to_constant_int().unwrap());
+    match fold::int_binary(opcode, x, y) {
+        Some(val) => Ok(val.into()),
+        None if matches!(opcode, Opcode::Shl | Opcode::LShr) => Err(TrapKind::OversizedShift),
+        None if y.cast_to_i64() == 0 => Err(TrapKind::DivisionByZero),
+        None => Err(TrapKind::DivisionOverflow),
+    }
+}
+
+fn icmp(cond: ICmpCond, x: GenericValue, y: GenericValue) -> GenericValue {
+    // Pointers are compared as addresses.
+    let int = |v: GenericValue| match v {
+        GenericValue::Ptr(p) => ConstantInt::Int64(p as i64),
+        v => v.to_constant_int().unwrap(),
     };
-    frame.set_inst_val(id, val)
+    GenericValue::Int1(fold::icmp(cond, int(x), int(y)).unwrap())
+}
+
+fn cast(opcode: Opcode, tys: &[Type], arg: GenericValue) -> GenericValue {
+    match opcode {
+        Opcode::Bitcast => arg,
+        Opcode::IntToPtr => GenericValue::Ptr(arg.sext_to_i64().unwrap() as *mut u8),
+        _ => match fold::cast_int(opcode, arg.to_constant_int().unwrap(), tys[1]) {
+            Some(val) => val.into(),
+            None => todo!("cast {:?}", opcode),
+        },
+    }
 }
 
 fn run_gep(frame: &mut StackFrame, id: InstructionId, tys: &[Type], args: &[ValueId]) {
     let arg = frame.get_val(args[0]).unwrap().to_ptr().unwrap();
+    let indices: Vec<GenericValue> = args[1..]
+        .iter()
+        .map(|&idx| frame.get_val(idx).unwrap())
+        .collect();
+    let ptr = gep(&frame.func.types, tys, arg, &indices);
+    frame.set_inst_val(id, GenericValue::Ptr(ptr));
+}
+
+fn gep(types: &Types, tys: &[Type], base: *mut u8, indices: &[GenericValue]) -> *mut u8 {
     let mut total = 0;
     let mut cur_ty = tys[1];
-    for &idx in &args[1..] {
-        if cur_ty.is_struct(&frame.func.types) {
+    for idx in indices {
+        if cur_ty.is_struct(types) {
         } else {
-            let inner = frame.func.types.get_element(cur_ty).unwrap();
-            let idx = match frame.get_val(idx).unwrap() {
-                GenericValue::Int32(idx) => idx as usize,
-                GenericValue::Int64(idx) => idx as usize,
+            let inner = types.get_element(cur_ty).unwrap();
+            let idx = match idx {
+                GenericValue::Int32(idx) => *idx as usize,
+                GenericValue::Int64(idx) => *idx as usize,
                 _ => panic!(),
             };
-            total += frame.func.types.size_of(inner) * idx;
+            total += types.size_of(inner) * idx;
             cur_ty = inner;
         }
     }
-    frame.set_inst_val(id, GenericValue::Ptr(unsafe { arg.add(total) }));
+    unsafe { base.add(total) }
 }
 
-fn run_call(frame: &mut StackFrame, id: InstructionId, _tys: &[Type], args: &[ValueId]) {
+fn run_call(
+    frame: &mut StackFrame,
+    id: InstructionId,
+    _tys: &[Type],
+    args: &[ValueId],
+) -> Result<(), Trap> {
     let callee = frame.get_val(args[0]).unwrap();
     let args: Vec<GenericValue> = args[1..]
         .iter()
         .map(|&a| frame.get_val(a).unwrap())
         .collect();
-    let func_id = callee.to_id::<FunctionId>().unwrap();
-    if let Some(ret) = run_function(frame.ctx, *func_id, args) {
-        match ret {
-            GenericValue::Void => {}
-            v => frame.set_inst_val(id, v),
-        }
+    let func_id = frame.ctx.resolve(*callee.to_id::<FunctionId>().unwrap());
+    let callee = frame.ctx.function(func_id);
+    if callee.is_prototype()
+        && !frame.ctx.intrinsics.contains_key(callee.name())
+        && lookup(frame.ctx, callee.name()).is_none()
+    {
+        return Err(Trap::new(TrapKind::UnresolvedSymbol, frame.func, id));
+    }
+    if let Some(watch) = &frame.ctx.watch {
+        watch.borrow_mut().call_stack.push(CallSite {
+            func: frame.func.name().to_owned(),
+            inst: id,
+        });
+    }
+    let result = run_function(frame.ctx, func_id, args);
+    if let Some(watch) = &frame.ctx.watch {
+        watch.borrow_mut().call_stack.pop();
+    }
+    let result = result.map_err(|mut trap| {
+        trap.backtrace.push(BacktraceFrame::new(frame.func, id));
+        trap
+    })?;
+    match result {
+        GenericValue::Void => {}
+        v => frame.set_inst_val(id, v),
     }
+    Ok(())
 }
 
 // Utils
 
-fn add(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int32(x + y)),
-        _ => None,
-    }
-}
+// Context
 
-fn sub(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int32(x - y)),
-        _ => None,
+impl<'a> Context<'a> {
+    pub fn new(module: &'a Module) -> Self {
+        Self::new_multi([module])
     }
-}
 
-fn mul(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int32(x * y)),
-        _ => None,
-    }
-}
+    /// Creates a context running the functions of all `modules`, which must
+    /// not be empty. Functions and global variables declared in one module
+    /// resolve to their definitions in another at runtime, as if the modules
+    /// were linked together. Private and internal definitions are only visible
+    /// to their own module.
+    pub fn new_multi<I: IntoIterator<Item = &'a Module>>(modules: I) -> Self {
+        let modules: Vec<&'a Module> = modules.into_iter().collect();
+        let module = *modules.first().expect("no modules given");
+
+        let mut definitions = FxHashMap::default();
+        for m in &modules {
+            for (id, func) in m.functions() {
+                if !func.is_prototype() && is_exported(Some(func.linkage)) {
+                    definitions.entry(func.name().as_str()).or_insert(id);
+                }
+            }
+        }
 
-fn sdiv(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int32(x / y)),
-        _ => None,
-    }
-}
+        // Allocate the definitions first so that declarations can refer to them.
+        let mut globals: Vec<FxHashMap<Name, GenericValue>> = modules
+            .iter()
+            .map(|m| {
+                m.global_variables()
+                    .iter()
+                    .filter(|(_, gv)| gv.init.is_some())
+                    .map(|(name, gv)| (*name, GenericValue::Ptr(alloc_global(m, gv))))
+                    .collect()
+            })
+            .collect();
+        for (i, m) in modules.iter().enumerate() {
+            for (name, gv) in m
+                .global_variables()
+                .iter()
+                .filter(|(_, gv)| gv.init.is_none())
+            {
+                let def = modules.iter().zip(&globals).find_map(|(other, addrs)| {
+                    let def = other.global_variables().get(name)?;
+                    (def.init.is_some() && is_exported(def.linkage)).then(|| addrs[name])
+                });
+                let addr = def.unwrap_or_else(|| GenericValue::Ptr(alloc_global(m, gv)));
+                globals[i].insert(*name, addr);
+            }
+        }
+
+        let consts = modules
+            .iter()
+            .zip(&globals)
+            .flat_map(|(m, globals)| {
+                m.functions().iter().map(move |(id, func)| {
+                    let consts = func
+                        .data
+                        .values
+                        .iter()
+                        .map(|(_, val)| match val {
+                            Value::Constant(konst) => frame::resolve_constant(m, globals, konst),
+                            _ => None,
+                        })
+                        .collect();
+                    (id, consts)
+                })
+            })
+            .collect();
 
-fn srem(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int32(x % y)),
-        _ => None,
+        Self {
+            module,
+            modules,
+            consts,
+            definitions,
+            libs: vec![],
+            trap_mode: TrapMode::default(),
+            intrinsics: FxHashMap::default(),
+            globals,
+            taint: None,
+            watch: None,
+            watch_handler: None,
+            step_budget: None,
+        }
     }
-}
 
-fn eq(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int1(x), GenericValue::Int1(y)) => Some(GenericValue::Int1(x != y)),
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int1(x == y)),
-        _ => None,
+    /// Returns the function `id` of any of the modules.
+    pub fn function(&self, id: FunctionId) -> &'a Function {
+        self.modules
+            .iter()
+            .find_map(|m| m.functions().get(id))
+            .expect("function of an unknown module")
     }
-}
 
-fn ne(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int1(x), GenericValue::Int1(y)) => Some(GenericValue::Int1(x != y)),
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int1(x != y)),
-        _ => None,
+    /// Returns the definition the declaration `id` resolves to, or `id` itself.
+    pub fn resolve(&self, id: FunctionId) -> FunctionId {
+        let func = self.function(id);
+        if !func.is_prototype() {
+            return id;
+        }
+        self.definitions
+            .get(func.name().as_str())
+            .copied()
+            .unwrap_or(id)
     }
-}
 
-fn ult(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int1(x < y)),
-        _ => None,
+    /// Finds the function `name`, preferring a definition to declarations.
+    pub fn find_function_by_name(&self, name: &str) -> Option<FunctionId> {
+        self.definitions.get(name).copied().or_else(|| {
+            self.modules
+                .iter()
+                .find_map(|m| m.find_function_by_name(name))
+        })
     }
-}
 
-fn ule(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int1(x <= y)),
-        _ => None,
+    /// Returns the name of the global variable `addr` points into, and the offset from its start.
+    pub fn symbolize(&self, addr: *mut u8) -> Option<(&str, usize)> {
+        let addr = addr as usize;
+        self.modules
+            .iter()
+            .zip(&self.globals)
+            .find_map(|(m, globals)| {
+                m.global_variables().iter().find_map(|(name, gv)| {
+                    let start = globals[name].to_ptr()? as usize;
+                    let size = m.types.size_of(gv.ty);
+                    match name {
+                        Name::Name(name) if (start..start + size.max(1)).contains(&addr) => {
+                            Some((name.as_str(), addr - start))
+                        }
+                        _ => None,
+                    }
+                })
+            })
     }
-}
 
-fn ugt(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int1(x > y)),
-        _ => None,
+    /// Returns the address and size of the global variable `name`, looking
+    /// for a definition in every module.
+    fn global_memory(&self, name: &str) -> Option<(*mut u8, usize)> {
+        let name = Name::from(name);
+        let mut found = None;
+        for (m, globals) in self.modules.iter().zip(&self.globals) {
+            if let Some(gv) = m.global_variables().get(&name) {
+                let memory = (globals[&name].to_ptr()?, m.types.size_of(gv.ty));
+                if gv.init.is_some() {
+                    return Some(memory);
+                }
+                found.get_or_insert(memory);
+            }
+        }
+        found
     }
-}
 
-fn uge(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int1(x >= y)),
-        _ => None,
+    /// Makes execution stop with [`TrapKind::BudgetExceeded`] once `steps`
+    /// instructions have been run, e.g. to bound programs that may not terminate.
+    pub fn with_step_budget(mut self, steps: u64) -> Self {
+        self.step_budget = Some(Cell::new(steps));
+        self
     }
-}
 
-fn slt(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int1(x < y)),
-        _ => None,
+    /// Enables taint tracking. See [`run_function_with_taint`].
+    pub fn with_taint_tracking(mut self) -> Self {
+        self.taint.get_or_insert_with(Default::default);
+        self
     }
-}
 
-fn sle(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int1(x <= y)),
-        _ => None,
+    /// Marks the contents of the global variable `name` as tainted, enabling taint tracking.
+    pub fn with_tainted_global(self, name: &str) -> Self {
+        let ctx = self.with_taint_tracking();
+        let (ptr, size) = ctx.global_memory(name).expect("unknown global variable");
+        ctx.taint
+            .as_ref()
+            .unwrap()
+            .borrow_mut()
+            .set_memory(ptr, size, true);
+        ctx
     }
-}
 
-fn sgt(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int1(x > y)),
-        _ => None,
+    /// Returns the uses of tainted values seen so far, in execution order.
+    pub fn taint_reports(&self) -> Vec<TaintReport> {
+        self.taint
+            .as_ref()
+            .map_or(vec![], |taint| taint.borrow().reports.clone())
     }
-}
 
-fn sge(x: GenericValue, y: GenericValue) -> Option<GenericValue> {
-    match (x, y) {
-        (GenericValue::Int32(x), GenericValue::Int32(y)) => Some(GenericValue::Int1(x >= y)),
-        _ => None,
+    /// Watches the memory `target` for `access`. By default, execution stops
+    /// with [`TrapKind::Watchpoint`] on each hit (see [`Self::with_watch_handler`]).
+    /// Only loads and stores are watched, not accesses made by external functions.
+    pub fn with_watchpoint(mut self, target: WatchTarget, access: WatchAccess) -> Self {
+        let range = match &target {
+            WatchTarget::Range(range) => Some(range.clone()),
+            WatchTarget::Global(name) => {
+                let (ptr, size) = self.global_memory(name).expect("unknown global variable");
+                Some(ptr as usize..ptr as usize + size)
+            }
+            WatchTarget::Alloca(..) => None,
+        };
+        self.watch
+            .get_or_insert_with(Default::default)
+            .get_mut()
+            .add(target, access, range);
+        self
     }
-}
 
-// Context
+    /// Makes `handler` decide whether to stop on each watchpoint hit.
+    pub fn with_watch_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(&WatchHit) -> bool + 'a,
+    {
+        self.watch_handler = Some(Box::new(handler));
+        self
+    }
 
-impl<'a> Context<'a> {
-    pub fn new(module: &'a Module) -> Self {
-        let mut globals = FxHashMap::default();
+    /// Returns the watchpoint hits seen so far, in execution order.
+    pub fn watch_hits(&self) -> Vec<WatchHit> {
+        self.watch
+            .as_ref()
+            .map_or(vec![], |watch| watch.borrow().hits.clone())
+    }
 
-        for (name, gv) in module.global_variables() {
-            let sz = module.types.size_of(gv.ty);
-            let align = if gv.align > 0 { gv.align } else { 8 } as usize;
-            let ptr = unsafe {
-                alloc::alloc(alloc::Layout::from_size_align(sz, align).expect("layout err"))
-            };
-            if let Some(init) = &gv.init {
-                match init {
-                    ConstantData::Array(ConstantArray {
-                        is_string: true,
-                        elems,
-                        ..
-                    }) => {
-                        let s: Vec<u8> = elems.iter().map(|e| *e.as_int().as_i8() as u8).collect();
-                        unsafe { ptr::copy_nonoverlapping(s.as_ptr(), ptr, s.len()) };
-                    }
-                    ConstantData::AggregateZero => {
-                        unsafe{ptr::write_bytes(ptr, 0, sz)};
-                    },
-                    _ => todo!(),
-                }
-            }
-            globals.insert(name.clone(), GenericValue::Ptr(ptr));
-        }
+    pub fn with_trap_mode(mut self, trap_mode: TrapMode) -> Self {
+        self.trap_mode = trap_mode;
+        self
+    }
 
-        Self {
-            module,
-            globals,
-            libs: vec![],
-        }
+    /// Makes calls to the function declared as `name` run `f` instead of
+    /// looking the function up in the loaded libraries.
+    pub fn with_intrinsic<N, F>(mut self, name: N, f: F) -> Self
+    where
+        N: Into<String>,
+        F: Fn(&[GenericValue]) -> GenericValue + 'a,
+    {
+        self.intrinsics.insert(name.into(), Box::new(f));
+        self
     }
 
     pub fn with_lib<T: AsRef<ffi::OsStr>>(mut self, lib: T) -> Option<Self> {
@@ -446,34 +763,47 @@ impl<'a> Context<'a> {
     }
 }
 
+fn alloc_global(module: &Module, gv: &GlobalVariable) -> *mut u8 {
+    let sz = module.types.size_of(gv.ty);
+    let align = if gv.align > 0 { gv.align } else { 8 } as usize;
+    let ptr = unsafe {
+        alloc::alloc(alloc::Layout::from_size_align(sz.max(1), align).expect("layout err"))
+    };
+    if let Some(init) = &gv.init {
+        match init {
+            ConstantData::Array(ConstantArray {
+                is_string: true,
+                elems,
+                ..
+            }) => {
+                let s: Vec<u8> = elems.iter().map(|e| *e.as_int().as_i8() as u8).collect();
+                unsafe { ptr::copy_nonoverlapping(s.as_ptr(), ptr, s.len()) };
+            }
+            ConstantData::AggregateZero => {
+                unsafe { ptr::write_bytes(ptr, 0, sz) };
+            }
+            _ => todo!(),
+        }
+    }
+    ptr
+}
+
+/// Returns true if a symbol with `linkage` is visible to other modules.
+fn is_exported(linkage: Option<Linkage>) -> bool {
+    !matches!(linkage, Some(Linkage::Private | Linkage::Internal))
+}
+
 // dummy
 
-trait TypeSize {
+pub(crate) trait TypeSize {
     fn size_of(&self, ty: Type) -> usize;
 }
 
+/// Values live in the memory of the host, laid out as on x86-64.
 impl TypeSize for Types {
     // Returns the size of the type in byte
     fn size_of(&self, ty: Type) -> usize {
-        match self.get(ty) {
-            Some(ty) => match &*ty {
-                CompoundType::Array(ArrayType {
-                    inner,
-                    num_elements,
-                }) => self.size_of(*inner) * *num_elements as usize,
-                CompoundType::Pointer(_) => 8,
-                _ => todo!(),
-            },
-            None => match ty {
-                types::VOID => return 0,
-                types::I1 => return 1,
-                types::I8 => return 1,
-                types::I16 => return 2,
-                types::I32 => return 4,
-                types::I64 => return 8,
-                a => todo!("sizeof {:?}", a),
-            },
-        }
+        self.size_and_align(ty, &DataLayout::default()).0 as usize
     }
 }
 
@@ -487,30 +817,31 @@ fn ffitype(ty:Type,types: &Types) -> libffi::low::ffi_type {
     }
 }
 
+fn lookup<'a>(
+    ctx: &'a Context,
+    name: &'a str,
+) -> Option<libloading::Symbol<'a, unsafe extern "C" fn()>> {
+    ctx.libs
+        .iter()
+        .find_map(|lib| unsafe { lib.get(name.as_bytes()) }.ok())
+}
+
 fn call_external_func(ctx: &Context, func: &Function, args: &[GenericValue]) -> GenericValue {
-    fn lookup<'a>(
-        ctx: &'a Context,
-        name: &'a str,
-    ) -> Option<libloading::Symbol<'a, unsafe extern "C" fn()>> {
-        ctx.libs
-            .iter()
-            .find_map(|lib| unsafe { lib.get(name.as_bytes()) }.ok())
-    }
     let mut args_ty = Vec::with_capacity(args.len());
     let mut new_args = Vec::with_capacity(args.len());
     let mut args: Vec<GenericValue> = args.to_vec();
     for arg in &mut args {
         match arg {
             GenericValue::Int32(ref mut i) => {
-                args_ty.push(unsafe { &mut libffi::low::types::sint32 as *mut _ });
+                args_ty.push(std::ptr::addr_of_mut!(libffi::low::types::sint32));
                 new_args.push(i as *mut _ as *mut c_void)
             }
             GenericValue::Int64(ref mut i) => {
-                args_ty.push(unsafe { &mut libffi::low::types::sint64 as *mut _ });
+                args_ty.push(std::ptr::addr_of_mut!(libffi::low::types::sint64));
                 new_args.push(i as *mut _ as *mut c_void)
             }
             GenericValue::Ptr(ref mut p) => {
-                args_ty.push(unsafe { &mut libffi::low::types::pointer as *mut _ });
+                args_ty.push(std::ptr::addr_of_mut!(libffi::low::types::pointer));
                 new_args.push(&mut *p as *mut _ as *mut c_void);
             }
             _ => todo!(),
@@ -0,0 +1,821 @@
+//! Symbolic execution.
+//!
+//! Runs a function with some of its arguments left unknown. Values computed
+//! from unknown arguments are kept as expressions, and a conditional branch on
+//! such a value forks execution into both directions when the other direction
+//! is feasible. Every explored path is reported as a [`Path`] carrying the
+//! constraints it took and concrete arguments that drive
+//! [`run_function`](super::run_function) down the same path, which makes the
+//! results usable as generated test inputs.
+//!
+//! Execution is concolic: each state keeps a model, i.e. values for the
+//! unknown arguments satisfying its path, and whatever cannot stay symbolic
+//! (call arguments, addresses) is concretized with the model and pinned with
+//! an equality constraint. Finding a model for a new path is left to a
+//! [`Solver`]; [`EnumerativeSolver`] is a simple one trying interesting values.
+
+use super::{
+    alloca_layout, cast, gep, icmp, int_binary, load, run_function, store, Context, TrapKind,
+    TypeSize,
+};
+use crate::generic_value::GenericValue;
+use rustc_hash::FxHashMap;
+use std::{alloc, cell::RefCell, collections::BTreeMap, fmt, mem, ops::Range, rc::Rc, slice};
+use vicis_core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{
+            Alloca, Br, Call, Cast, CondBr, GetElementPtr, ICmp, ICmpCond, InstructionId,
+            IntBinary, Load, Opcode, Operand, Phi, Ret, Store,
+        },
+        Function, FunctionId,
+    },
+    types::{self, Type, Types},
+    value::{ConstantData, Value, ValueId},
+};
+
+/// A value computed from unknown arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// The `n`-th unknown argument.
+    Input(usize),
+    Const(GenericValue),
+    IntBinary(Opcode, Rc<Expr>, Rc<Expr>),
+    ICmp(ICmpCond, Rc<Expr>, Rc<Expr>),
+    /// A cast from `tys[0]` to `tys[1]`.
+    Cast(Opcode, [Type; 2], Rc<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SymValue {
+    Concrete(GenericValue),
+    Symbolic(Rc<Expr>),
+}
+
+/// Requires the `i1` expression `expr` to evaluate to `expected`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    pub expr: Rc<Expr>,
+    pub expected: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathOutcome {
+    Returned(SymValue),
+    Trapped(TrapKind),
+    /// Reached an instruction symbolic execution does not support.
+    Unsupported(Opcode),
+    /// Ran more blocks than allowed, e.g. in a loop bounded by an unknown value.
+    StepLimit,
+}
+
+/// A path explored by [`SymbolicExecutor`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    /// Arguments making the function take this path.
+    pub args: Vec<GenericValue>,
+    /// Values of the unknown arguments in `args`, to evaluate expressions with.
+    pub model: Vec<GenericValue>,
+    pub constraints: Vec<Constraint>,
+    pub outcome: PathOutcome,
+}
+
+/// Constraint backend deciding whether a path is feasible.
+pub trait Solver {
+    /// Returns values for unknown arguments of types `inputs` that satisfy
+    /// all the `constraints`, or `None` if no such values were found.
+    fn solve(&mut self, inputs: &[Type], constraints: &[Constraint]) -> Option<Vec<GenericValue>>;
+}
+
+/// Tries combinations of zero, one, minus one, the type bounds and every
+/// constant in the constraints (plus and minus one) for each unknown argument.
+pub struct EnumerativeSolver {
+    pub max_tries: usize,
+}
+
+pub struct SymbolicExecutor<'a, S> {
+    ctx: &'a Context<'a>,
+    solver: S,
+    max_paths: usize,
+    max_steps: usize,
+}
+
+/// An execution state, forked at branches on symbolic values.
+#[derive(Clone)]
+struct State {
+    block: BasicBlockId,
+    last_block: BasicBlockId,
+    vals: FxHashMap<InstructionId, SymValue>,
+    /// Values stored to memory on this path, by address. The stored ranges
+    /// don't overlap.
+    memory: BTreeMap<usize, Stored>,
+    constraints: Vec<Constraint>,
+    model: Vec<GenericValue>,
+    steps: usize,
+}
+
+/// A value stored to memory on a path.
+#[derive(Clone)]
+struct Stored {
+    size: usize,
+    ty: Type,
+    val: SymValue,
+}
+
+/// What is shared by all the states of one run.
+struct Run<'r> {
+    func: &'r Function,
+    consts: &'r [Option<GenericValue>],
+    args: &'r [SymValue],
+    inputs: &'r [Type],
+    /// Memory allocated by the allocas run on any path, freed when the run
+    /// returns as the states share what was allocated before they forked.
+    allocas: RefCell<Vec<(*mut u8, alloc::Layout)>>,
+}
+
+/// A buffer to convert values from and to bytes, aligned for all of them.
+#[repr(C, align(16))]
+struct Bytes([u8; 16]);
+
+impl<'a> SymbolicExecutor<'a, EnumerativeSolver> {
+    pub fn new(ctx: &'a Context<'a>) -> Self {
+        Self {
+            ctx,
+            solver: EnumerativeSolver::default(),
+            max_paths: 64,
+            max_steps: 256,
+        }
+    }
+}
+
+impl<'a, S: Solver> SymbolicExecutor<'a, S> {
+    pub fn with_solver<T: Solver>(self, solver: T) -> SymbolicExecutor<'a, T> {
+        SymbolicExecutor {
+            ctx: self.ctx,
+            solver,
+            max_paths: self.max_paths,
+            max_steps: self.max_steps,
+        }
+    }
+
+    /// Stops exploring once `max_paths` paths have been found.
+    pub fn with_max_paths(mut self, max_paths: usize) -> Self {
+        self.max_paths = max_paths;
+        self
+    }
+
+    /// Gives up on a path after it has run `max_steps` blocks.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Explores the paths through `func_id`. `None` arguments are unknown and
+    /// must be integers.
+    pub fn run(&mut self, func_id: FunctionId, args: Vec<Option<GenericValue>>) -> Vec<Path> {
+        let func = self.ctx.function(func_id);
+        assert!(!func.is_prototype(), "cannot run a prototype symbolically");
+
+        let mut inputs = vec![];
+        let args: Vec<SymValue> = args
+            .into_iter()
+            .zip(func.params.iter())
+            .map(|(arg, param)| match arg {
+                Some(arg) => SymValue::Concrete(arg),
+                None => {
+                    assert!(
+                        int_value(param.ty, 0).is_some(),
+                        "only integer arguments can be unknown"
+                    );
+                    inputs.push(param.ty);
+                    SymValue::Symbolic(Rc::new(Expr::Input(inputs.len() - 1)))
+                }
+            })
+            .collect();
+        let run = Run {
+            func,
+            consts: &self.ctx.consts[&func_id],
+            args: &args,
+            inputs: &inputs,
+            allocas: RefCell::default(),
+        };
+
+        let entry = func.entry_block().expect("function has no blocks");
+        let mut worklist = vec![State {
+            block: entry,
+            last_block: entry,
+            vals: FxHashMap::default(),
+            memory: BTreeMap::default(),
+            constraints: vec![],
+            model: inputs.iter().map(|&ty| int_value(ty, 0).unwrap()).collect(),
+            steps: 0,
+        }];
+        let mut paths = vec![];
+        while let Some(state) = worklist.pop() {
+            if paths.len() >= self.max_paths {
+                break;
+            }
+            self.explore(&run, state, &mut worklist, &mut paths);
+        }
+        paths.truncate(self.max_paths);
+        paths
+    }
+
+    fn explore(
+        &mut self,
+        run: &Run,
+        mut state: State,
+        worklist: &mut Vec<State>,
+        paths: &mut Vec<Path>,
+    ) {
+        let func = run.func;
+        let outcome = 'exec: loop {
+            if state.steps == self.max_steps {
+                break PathOutcome::StepLimit;
+            }
+            state.steps += 1;
+
+            for inst_id in func.layout.inst_iter(state.block) {
+                let inst = func.data.inst_ref(inst_id);
+                let val = match &inst.operand {
+                    Operand::Alloca(Alloca {
+                        tys,
+                        num_elements,
+                        align,
+                    }) => SymValue::Concrete(GenericValue::Ptr(run.allocate(
+                        tys,
+                        num_elements,
+                        *align,
+                    ))),
+                    Operand::Phi(Phi { args, blocks, .. }) => {
+                        let idx = blocks
+                            .iter()
+                            .position(|&block| block == state.last_block)
+                            .unwrap();
+                        state.get(run, args[idx])
+                    }
+                    Operand::Store(Store { tys, args, .. }) => {
+                        let val = state.get(run, args[0]);
+                        let addr = state.get(run, args[1]);
+                        let stored = match state.concretize(addr) {
+                            Ok(GenericValue::Ptr(addr)) => {
+                                state.store(&func.types, tys[0], addr, val)
+                            }
+                            Ok(_) => unreachable!(),
+                            Err(kind) => Err(kind),
+                        };
+                        match stored {
+                            Ok(()) => continue,
+                            Err(kind) => break 'exec PathOutcome::Trapped(kind),
+                        }
+                    }
+                    Operand::Load(Load { tys, addr, .. }) => {
+                        let addr = state.get(run, *addr);
+                        let loaded = match state.concretize(addr) {
+                            Ok(GenericValue::Ptr(addr)) => state.load(&func.types, tys[0], addr),
+                            Ok(_) => unreachable!(),
+                            Err(kind) => Err(kind),
+                        };
+                        match loaded {
+                            Ok(val) => val,
+                            Err(kind) => break 'exec PathOutcome::Trapped(kind),
+                        }
+                    }
+                    Operand::GetElementPtr(GetElementPtr { tys, args, .. }) => {
+                        let args: Result<Vec<GenericValue>, TrapKind> = args
+                            .iter()
+                            .map(|&arg| {
+                                let arg = state.get(run, arg);
+                                state.concretize(arg)
+                            })
+                            .collect();
+                        match args {
+                            Ok(args) => SymValue::Concrete(GenericValue::Ptr(gep(
+                                &func.types,
+                                tys,
+                                args[0].to_ptr().unwrap(),
+                                &args[1..],
+                            ))),
+                            Err(kind) => break 'exec PathOutcome::Trapped(kind),
+                        }
+                    }
+                    Operand::IntBinary(IntBinary { ty, args, .. }) => {
+                        let x = state.get(run, args[0]);
+                        let y = state.get(run, args[1]);
+                        if matches!(inst.opcode, Opcode::SDiv | Opcode::SRem)
+                            && matches!(y, SymValue::Symbolic(_))
+                            && !self.fork_on_zero_divisor(run, &mut state, *ty, &y, paths)
+                        {
+                            return;
+                        }
+                        match (x, y) {
+                            (SymValue::Concrete(x), SymValue::Concrete(y)) => {
+                                match int_binary(inst.opcode, x, y) {
+                                    Ok(val) => SymValue::Concrete(val),
+                                    Err(kind) => break 'exec PathOutcome::Trapped(kind),
+                                }
+                            }
+                            (x, y) => SymValue::Symbolic(Rc::new(Expr::IntBinary(
+                                inst.opcode,
+                                x.expr(),
+                                y.expr(),
+                            ))),
+                        }
+                    }
+                    Operand::ICmp(ICmp { args, cond, .. }) => {
+                        match (state.get(run, args[0]), state.get(run, args[1])) {
+                            (SymValue::Concrete(x), SymValue::Concrete(y)) => {
+                                SymValue::Concrete(icmp(*cond, x, y))
+                            }
+                            (x, y) => {
+                                SymValue::Symbolic(Rc::new(Expr::ICmp(*cond, x.expr(), y.expr())))
+                            }
+                        }
+                    }
+                    Operand::Cast(Cast { tys, arg }) => match state.get(run, *arg) {
+                        SymValue::Concrete(arg) => SymValue::Concrete(cast(inst.opcode, tys, arg)),
+                        SymValue::Symbolic(arg) => SymValue::Symbolic(Rc::new(Expr::Cast(
+                            inst.opcode,
+                            [tys[0], tys[1]],
+                            arg,
+                        ))),
+                    },
+                    Operand::Call(Call { args, .. }) => {
+                        let args: Result<Vec<GenericValue>, TrapKind> = args
+                            .iter()
+                            .map(|&arg| {
+                                let arg = state.get(run, arg);
+                                state.concretize(arg)
+                            })
+                            .collect();
+                        let args = match args {
+                            Ok(args) => args,
+                            Err(kind) => break 'exec PathOutcome::Trapped(kind),
+                        };
+                        let callee = *args[0].to_id::<FunctionId>().unwrap();
+                        match state.call(self.ctx, run, callee, args[1..].to_vec()) {
+                            Ok(GenericValue::Void) => continue,
+                            Ok(val) => SymValue::Concrete(val),
+                            Err(kind) => break 'exec PathOutcome::Trapped(kind),
+                        }
+                    }
+                    Operand::CondBr(CondBr { arg, blocks }) => {
+                        let taken = match state.get(run, *arg) {
+                            SymValue::Concrete(GenericValue::Int1(cond)) => cond,
+                            SymValue::Concrete(_) => unreachable!(),
+                            SymValue::Symbolic(expr) => {
+                                self.fork(run, &mut state, expr, blocks, worklist)
+                            }
+                        };
+                        state.last_block = state.block;
+                        state.block = blocks[if taken { 0 } else { 1 }];
+                        continue 'exec;
+                    }
+                    Operand::Br(Br { block }) => {
+                        state.last_block = state.block;
+                        state.block = *block;
+                        continue 'exec;
+                    }
+                    Operand::Ret(Ret { val, .. }) => {
+                        break 'exec PathOutcome::Returned(match val {
+                            Some(val) => state.get(run, *val),
+                            None => SymValue::Concrete(GenericValue::Void),
+                        });
+                    }
+                    _ => break 'exec PathOutcome::Unsupported(inst.opcode),
+                };
+                state.vals.insert(inst_id, val);
+            }
+
+            match func.layout.next_block_of(state.block) {
+                Some(next) => {
+                    state.last_block = state.block;
+                    state.block = next;
+                }
+                None => panic!("reached end of function without terminator"),
+            }
+        };
+
+        paths.push(Path {
+            args: run.witness(&state.model),
+            model: state.model,
+            constraints: state.constraints,
+            outcome,
+        });
+    }
+
+    /// Continues `state` in the direction its model takes on a branch on
+    /// `cond`, queueing the other direction if it is feasible. Returns the
+    /// direction taken.
+    fn fork(
+        &mut self,
+        run: &Run,
+        state: &mut State,
+        cond: Rc<Expr>,
+        blocks: &[BasicBlockId; 2],
+        worklist: &mut Vec<State>,
+    ) -> bool {
+        let taken = matches!(cond.eval(&state.model), Ok(GenericValue::Int1(true)));
+        let mut other = state.constraints.clone();
+        other.push(Constraint {
+            expr: cond.clone(),
+            expected: !taken,
+        });
+        if let Some(model) = self.solver.solve(run.inputs, &other) {
+            let mut forked = state.clone();
+            forked.constraints = other;
+            forked.model = model;
+            forked.last_block = state.block;
+            forked.block = blocks[if taken { 1 } else { 0 }];
+            worklist.push(forked);
+        }
+        state.constraints.push(Constraint {
+            expr: cond,
+            expected: taken,
+        });
+        taken
+    }
+
+    /// Records a trapping path for a division by `divisor` if it can be zero,
+    /// and constrains `state` to a non-zero divisor. Returns false if the
+    /// divisor is always zero on this path.
+    fn fork_on_zero_divisor(
+        &mut self,
+        run: &Run,
+        state: &mut State,
+        ty: Type,
+        divisor: &SymValue,
+        paths: &mut Vec<Path>,
+    ) -> bool {
+        let is_zero = Rc::new(Expr::ICmp(
+            ICmpCond::Eq,
+            divisor.expr(),
+            Rc::new(Expr::Const(int_value(ty, 0).unwrap())),
+        ));
+        let zero = Constraint {
+            expr: is_zero.clone(),
+            expected: true,
+        };
+        let non_zero = Constraint {
+            expr: is_zero,
+            expected: false,
+        };
+
+        let mut constraints = state.constraints.clone();
+        constraints.push(zero);
+        if let Some(model) = self.solver.solve(run.inputs, &constraints) {
+            paths.push(Path {
+                args: run.witness(&model),
+                model,
+                constraints,
+                outcome: PathOutcome::Trapped(TrapKind::DivisionByZero),
+            });
+        }
+
+        state.constraints.push(non_zero);
+        if state
+            .constraints
+            .iter()
+            .all(|c| c.is_satisfied(&state.model))
+        {
+            return true;
+        }
+        match self.solver.solve(run.inputs, &state.constraints) {
+            Some(model) => {
+                state.model = model;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl State {
+    fn get(&self, run: &Run, id: ValueId) -> SymValue {
+        run.get(self, id)
+    }
+
+    /// Returns the value `val` has under the model, constraining the path to it.
+    fn concretize(&mut self, val: SymValue) -> Result<GenericValue, TrapKind> {
+        match val {
+            SymValue::Concrete(val) => Ok(val),
+            SymValue::Symbolic(expr) => {
+                let val = expr.eval(&self.model)?;
+                self.constraints.push(Constraint {
+                    expr: Rc::new(Expr::ICmp(ICmpCond::Eq, expr, Rc::new(Expr::Const(val)))),
+                    expected: true,
+                });
+                Ok(val)
+            }
+        }
+    }
+
+    /// Stores `val` of type `ty` to `addr`. The values it partially
+    /// overwrites are concretized and kept byte by byte where not overwritten.
+    fn store(
+        &mut self,
+        types: &Types,
+        ty: Type,
+        addr: *mut u8,
+        val: SymValue,
+    ) -> Result<(), TrapKind> {
+        let start = addr as usize;
+        let end = start + types.size_of(ty);
+        for (at, old) in self.overlapping(start, end) {
+            self.memory.remove(&at);
+            if start <= at && at + old.size <= end {
+                continue;
+            }
+            let bytes = to_bytes(self.concretize(old.val)?);
+            for (i, &byte) in bytes.0[..old.size].iter().enumerate() {
+                if !(start..end).contains(&(at + i)) {
+                    let byte = SymValue::Concrete(GenericValue::Int8(byte as i8));
+                    self.memory.insert(at + i, Stored::new(1, types::I8, byte));
+                }
+            }
+        }
+        self.memory.insert(start, Stored::new(end - start, ty, val));
+        Ok(())
+    }
+
+    /// Loads a value of type `ty` from `addr`. Unless it was stored as a whole
+    /// with this type, it is assembled from the concretized values overlapping
+    /// it and the memory where nothing was stored on this path.
+    fn load(&mut self, types: &Types, ty: Type, addr: *mut u8) -> Result<SymValue, TrapKind> {
+        let start = addr as usize;
+        let size = types.size_of(ty);
+        let overlapping = self.overlapping(start, start + size);
+        match overlapping.as_slice() {
+            [] => return Ok(SymValue::Concrete(load(types, ty, addr))),
+            [(at, old)] if *at == start && old.size == size && old.ty == ty => {
+                return Ok(old.val.clone())
+            }
+            _ => {}
+        }
+        let mut bytes = Bytes([0; 16]);
+        bytes.0[..size].copy_from_slice(unsafe { slice::from_raw_parts(addr, size) });
+        for (at, old) in overlapping {
+            let old_bytes = to_bytes(self.concretize(old.val)?);
+            for (i, &byte) in old_bytes.0[..old.size].iter().enumerate() {
+                if let Some(offset) = (at + i).checked_sub(start).filter(|&o| o < size) {
+                    bytes.0[offset] = byte;
+                }
+            }
+        }
+        Ok(SymValue::Concrete(load(types, ty, bytes.0.as_mut_ptr())))
+    }
+
+    /// Runs `callee` concretely on real memory. The values stored on this
+    /// path are concretized and written to it for the call, and the bytes
+    /// they cover or the call changes are taken back into the path, so that
+    /// the memory the other states read is left as it was.
+    fn call(
+        &mut self,
+        ctx: &Context,
+        run: &Run,
+        callee: FunctionId,
+        args: Vec<GenericValue>,
+    ) -> Result<GenericValue, TrapKind> {
+        let mut stored = vec![];
+        for (at, old) in mem::take(&mut self.memory) {
+            stored.push((at..at + old.size, to_bytes(self.concretize(old.val)?)));
+        }
+        let ranges: Vec<Range<usize>> = run
+            .allocas
+            .borrow()
+            .iter()
+            .map(|&(ptr, layout)| ptr as usize..ptr as usize + layout.size())
+            .chain(stored.iter().map(|(range, _)| range.clone()))
+            .collect();
+        let saved: Vec<Vec<u8>> = ranges
+            .iter()
+            .map(|range| unsafe { memory_at(range) }.to_vec())
+            .collect();
+        for (range, bytes) in &stored {
+            unsafe { memory_at(range) }.copy_from_slice(&bytes.0[..range.len()]);
+        }
+
+        let result = run_function(ctx, callee, args);
+
+        for (range, saved) in ranges.iter().zip(&saved) {
+            for (at, (&now, &before)) in range
+                .clone()
+                .zip(unsafe { memory_at(range) }.iter().zip(saved))
+            {
+                if now != before || stored.iter().any(|(range, _)| range.contains(&at)) {
+                    let byte = SymValue::Concrete(GenericValue::Int8(now as i8));
+                    self.memory.insert(at, Stored::new(1, types::I8, byte));
+                }
+            }
+        }
+        for (range, saved) in ranges.iter().zip(&saved).rev() {
+            unsafe { memory_at(range) }.copy_from_slice(saved);
+        }
+        result.map_err(|trap| trap.kind)
+    }
+
+    /// Returns the values stored on this path overlapping `start..end`.
+    fn overlapping(&self, start: usize, end: usize) -> Vec<(usize, Stored)> {
+        // The stored ranges are disjoint, so they end in the order they start.
+        self.memory
+            .range(..end)
+            .rev()
+            .take_while(|(&at, stored)| at + stored.size > start)
+            .map(|(&at, stored)| (at, stored.clone()))
+            .collect()
+    }
+}
+
+impl Stored {
+    fn new(size: usize, ty: Type, val: SymValue) -> Self {
+        Self { size, ty, val }
+    }
+}
+
+/// Returns the real memory in `range`.
+unsafe fn memory_at<'m>(range: &Range<usize>) -> &'m mut [u8] {
+    slice::from_raw_parts_mut(range.start as *mut u8, range.len())
+}
+
+/// Returns the bytes `val` is stored as in memory.
+fn to_bytes(val: GenericValue) -> Bytes {
+    let mut bytes = Bytes([0; 16]);
+    store(bytes.0.as_mut_ptr(), val);
+    bytes
+}
+
+impl Run<'_> {
+    fn get(&self, state: &State, id: ValueId) -> SymValue {
+        match self.func.data.value_ref(id) {
+            Value::Instruction(id) => state.vals[id].clone(),
+            Value::Argument(i) => self.args[*i].clone(),
+            _ => SymValue::Concrete(self.consts[id.index()].unwrap()),
+        }
+    }
+
+    /// Allocates the memory of an alloca, freed when the run returns.
+    fn allocate(&self, tys: &[Type], num_elements: &ConstantData, align: u32) -> *mut u8 {
+        let layout = alloca_layout(&self.func.types, tys, num_elements, align);
+        let ptr = unsafe { alloc::alloc(layout) };
+        self.allocas.borrow_mut().push((ptr, layout));
+        ptr
+    }
+
+    fn witness(&self, model: &[GenericValue]) -> Vec<GenericValue> {
+        self.args
+            .iter()
+            .map(|arg| arg.eval(model).unwrap())
+            .collect()
+    }
+}
+
+impl Drop for Run<'_> {
+    fn drop(&mut self) {
+        for (ptr, layout) in self.allocas.get_mut().drain(..) {
+            unsafe { alloc::dealloc(ptr, layout) }
+        }
+    }
+}
+
+impl Expr {
+    /// Evaluates the expression with `model` as the values of the unknown arguments.
+    pub fn eval(&self, model: &[GenericValue]) -> Result<GenericValue, TrapKind> {
+        match self {
+            Self::Input(i) => Ok(model[*i]),
+            Self::Const(val) => Ok(*val),
+            Self::IntBinary(opcode, x, y) => int_binary(*opcode, x.eval(model)?, y.eval(model)?),
+            Self::ICmp(cond, x, y) => Ok(icmp(*cond, x.eval(model)?, y.eval(model)?)),
+            Self::Cast(opcode, tys, x) => Ok(cast(*opcode, tys, x.eval(model)?)),
+        }
+    }
+
+    fn collect_consts(&self, consts: &mut Vec<i64>) {
+        match self {
+            Self::Input(_) => {}
+            Self::Const(val) => consts.extend(val.sext_to_i64()),
+            Self::IntBinary(_, x, y) | Self::ICmp(_, x, y) => {
+                x.collect_consts(consts);
+                y.collect_consts(consts);
+            }
+            Self::Cast(_, _, x) => x.collect_consts(consts),
+        }
+    }
+}
+
+impl SymValue {
+    pub fn eval(&self, model: &[GenericValue]) -> Result<GenericValue, TrapKind> {
+        match self {
+            Self::Concrete(val) => Ok(*val),
+            Self::Symbolic(expr) => expr.eval(model),
+        }
+    }
+
+    fn expr(&self) -> Rc<Expr> {
+        match self {
+            Self::Concrete(val) => Rc::new(Expr::Const(*val)),
+            Self::Symbolic(expr) => expr.clone(),
+        }
+    }
+}
+
+impl Constraint {
+    pub fn is_satisfied(&self, model: &[GenericValue]) -> bool {
+        matches!(self.expr.eval(model), Ok(GenericValue::Int1(b)) if b == self.expected)
+    }
+}
+
+impl Default for EnumerativeSolver {
+    fn default() -> Self {
+        Self { max_tries: 1 << 16 }
+    }
+}
+
+impl Solver for EnumerativeSolver {
+    fn solve(&mut self, inputs: &[Type], constraints: &[Constraint]) -> Option<Vec<GenericValue>> {
+        let mut consts = vec![];
+        for c in constraints {
+            c.expr.collect_consts(&mut consts);
+        }
+        let mut seeds = vec![0, 1, -1, i64::MIN, i64::MAX];
+        seeds.extend([i8::MIN, i8::MAX].map(i64::from));
+        seeds.extend([i32::MIN, i32::MAX].map(i64::from));
+        for c in consts {
+            seeds.extend([c.wrapping_sub(1), c, c.wrapping_add(1)]);
+        }
+        let candidates: Vec<Vec<GenericValue>> = inputs
+            .iter()
+            .map(|&ty| {
+                let mut vals = vec![];
+                for val in seeds.iter().filter_map(|&seed| int_value(ty, seed)) {
+                    if !vals.contains(&val) {
+                        vals.push(val);
+                    }
+                }
+                vals
+            })
+            .collect();
+
+        // Enumerates the combinations like an odometer.
+        let mut digits = vec![0; inputs.len()];
+        for _ in 0..self.max_tries {
+            let model: Vec<GenericValue> = digits
+                .iter()
+                .zip(candidates.iter())
+                .map(|(&d, vals)| vals[d])
+                .collect();
+            if constraints.iter().all(|c| c.is_satisfied(&model)) {
+                return Some(model);
+            }
+            let mut i = 0;
+            loop {
+                if i == digits.len() {
+                    return None;
+                }
+                digits[i] += 1;
+                if digits[i] < candidates[i].len() {
+                    break;
+                }
+                digits[i] = 0;
+                i += 1;
+            }
+        }
+        None
+    }
+}
+
+/// Returns `val` truncated to the integer type `ty`.
+fn int_value(ty: Type, val: i64) -> Option<GenericValue> {
+    match ty {
+        types::I1 => Some(GenericValue::Int1(val & 1 == 1)),
+        types::I8 => Some(GenericValue::Int8(val as i8)),
+        types::I16 => Some(GenericValue::Int16(val as i16)),
+        types::I32 => Some(GenericValue::Int32(val as i32)),
+        types::I64 => Some(GenericValue::Int64(val)),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Input(i) => write!(f, "${}", i),
+            Self::Const(val) => match val.sext_to_i64() {
+                Some(i) => write!(f, "{}", i),
+                None => write!(f, "{:?}", val),
+            },
+            Self::IntBinary(opcode, x, y) => write!(f, "({:?} {} {})", opcode, x, y),
+            Self::ICmp(cond, x, y) => write!(f, "(icmp {:?} {} {})", cond, x, y),
+            Self::Cast(opcode, _, x) => write!(f, "({:?} {})", opcode, x),
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.expected {
+            write!(f, "{}", self.expr)
+        } else {
+            write!(f, "!{}", self.expr)
+        }
+    }
+}
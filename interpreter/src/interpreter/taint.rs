@@ -0,0 +1,130 @@
+use super::{frame::StackFrame, TypeSize};
+use rustc_hash::FxHashSet;
+use vicis_core::ir::{
+    function::{
+        basic_block::BasicBlockId,
+        instruction::{
+            Call, Cast, CondBr, GetElementPtr, ICmp, Instruction, InstructionId, IntBinary, Load,
+            Operand, Phi, Ret, Store,
+        },
+        FunctionId,
+    },
+    value::ValueId,
+};
+
+/// Where a tainted value was used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaintSink {
+    /// A store of a tainted value, or to a tainted address.
+    Store,
+    /// A conditional branch on a tainted condition.
+    Branch,
+    /// A call to a function not defined in the module with a tainted argument.
+    ExternalCall,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaintReport {
+    pub sink: TaintSink,
+    /// Name of the function containing `inst`.
+    pub func: String,
+    pub inst: InstructionId,
+}
+
+/// Taint shared by all the frames of a run.
+#[derive(Debug, Default)]
+pub(super) struct TaintState {
+    /// Addresses of the tainted bytes in memory.
+    memory: FxHashSet<usize>,
+    /// Taint of the arguments passed to the next function called.
+    pub args: Vec<bool>,
+    /// Taint of the value last returned.
+    pub ret: bool,
+    pub reports: Vec<TaintReport>,
+}
+
+impl TaintState {
+    pub fn set_memory(&mut self, addr: *mut u8, size: usize, tainted: bool) {
+        for addr in addr as usize..addr as usize + size {
+            if tainted {
+                self.memory.insert(addr);
+            } else {
+                self.memory.remove(&addr);
+            }
+        }
+    }
+
+    fn report(&mut self, frame: &StackFrame, inst: InstructionId, sink: TaintSink) {
+        self.reports.push(TaintReport {
+            sink,
+            func: frame.func.name().to_owned(),
+            inst,
+        })
+    }
+
+    fn is_memory_tainted(&self, addr: *mut u8, size: usize) -> bool {
+        (addr as usize..addr as usize + size).any(|addr| self.memory.contains(&addr))
+    }
+}
+
+/// Propagates taint through `inst`, which is about to be run in `frame`.
+/// The taint of a call's result is set once the call has returned.
+pub(super) fn propagate(
+    frame: &mut StackFrame,
+    state: &mut TaintState,
+    id: InstructionId,
+    inst: &Instruction,
+    last_block: BasicBlockId,
+) {
+    let any = |frame: &StackFrame, args: &[ValueId]| args.iter().any(|&a| frame.is_tainted(a));
+    match &inst.operand {
+        Operand::IntBinary(IntBinary { args, .. }) | Operand::ICmp(ICmp { args, .. }) => {
+            let tainted = any(frame, args);
+            frame.set_taint(id, tainted)
+        }
+        Operand::GetElementPtr(GetElementPtr { args, .. }) => {
+            let tainted = any(frame, args);
+            frame.set_taint(id, tainted)
+        }
+        Operand::Cast(Cast { arg, .. }) => {
+            let tainted = frame.is_tainted(*arg);
+            frame.set_taint(id, tainted)
+        }
+        Operand::Phi(Phi { args, blocks, .. }) => {
+            let idx = blocks.iter().position(|&b| b == last_block).unwrap();
+            let tainted = frame.is_tainted(args[idx]);
+            frame.set_taint(id, tainted)
+        }
+        Operand::Load(Load { tys, addr, .. }) => {
+            let size = frame.func.types.size_of(tys[0]);
+            let ptr = frame.get_val(*addr).unwrap().to_ptr().unwrap();
+            let tainted = frame.is_tainted(*addr) || state.is_memory_tainted(ptr, size);
+            frame.set_taint(id, tainted)
+        }
+        Operand::Store(Store { tys, args, .. }) => {
+            let size = frame.func.types.size_of(tys[0]);
+            let ptr = frame.get_val(args[1]).unwrap().to_ptr().unwrap();
+            let tainted = frame.is_tainted(args[0]);
+            if tainted || frame.is_tainted(args[1]) {
+                state.report(frame, id, TaintSink::Store);
+            }
+            state.set_memory(ptr, size, tainted);
+        }
+        Operand::CondBr(CondBr { arg, .. }) if frame.is_tainted(*arg) => {
+            state.report(frame, id, TaintSink::Branch)
+        }
+        Operand::Call(Call { args, .. }) => {
+            let callee = frame.get_val(args[0]).unwrap();
+            let callee = *callee.to_id::<FunctionId>().unwrap();
+            let taint: Vec<bool> = args[1..].iter().map(|&a| frame.is_tainted(a)).collect();
+            if frame.ctx.function(frame.ctx.resolve(callee)).is_prototype() && taint.contains(&true)
+            {
+                state.report(frame, id, TaintSink::ExternalCall);
+            }
+            state.args = taint;
+        }
+        Operand::Ret(Ret { val: Some(val), .. }) => state.ret = frame.is_tainted(*val),
+        Operand::Ret(Ret { val: None, .. }) => state.ret = false,
+        _ => {}
+    }
+}
@@ -0,0 +1,120 @@
+use std::{error::Error, fmt};
+use vicis_core::ir::function::{instruction::InstructionId, Function};
+
+/// How the interpreter treats operations whose result LLVM leaves undefined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrapMode {
+    /// Stop execution with a [`Trap`].
+    #[default]
+    Trap,
+    /// Produce `GenericValue::Poison` and keep going. Branching on poison still traps.
+    Poison,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapKind {
+    /// `sdiv`/`srem` by zero.
+    DivisionByZero,
+    /// `sdiv`/`srem` of the minimum signed value by -1.
+    DivisionOverflow,
+    /// Shift amount not less than the bit width.
+    OversizedShift,
+    /// Conditional branch on a poison value.
+    BranchOnPoison,
+    /// Access to memory watched by a watchpoint.
+    Watchpoint,
+    /// Call to a function that is neither defined, an intrinsic, nor found in the loaded libraries.
+    UnresolvedSymbol,
+    /// More instructions run than allowed by `Context::with_step_budget`.
+    BudgetExceeded,
+}
+
+/// An error raised while running a function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trap {
+    pub kind: TrapKind,
+    /// Name of the function where the trap happened.
+    pub func: String,
+    /// The instruction that trapped.
+    pub inst: InstructionId,
+    /// The functions being run, innermost first.
+    pub backtrace: Vec<BacktraceFrame>,
+}
+
+/// Where execution was in a function when a trap happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BacktraceFrame {
+    pub func: String,
+    /// Position of the block in the function.
+    pub block: usize,
+    /// Position of the instruction in the block.
+    pub index: usize,
+    /// The instruction being run: the one that trapped or a call.
+    pub inst: InstructionId,
+}
+
+impl Trap {
+    pub(super) fn new(kind: TrapKind, func: &Function, inst: InstructionId) -> Self {
+        Self {
+            kind,
+            func: func.name().to_owned(),
+            inst,
+            backtrace: vec![BacktraceFrame::new(func, inst)],
+        }
+    }
+}
+
+impl BacktraceFrame {
+    pub(super) fn new(func: &Function, inst: InstructionId) -> Self {
+        let block = func.layout.block_of(inst).unwrap();
+        Self {
+            func: func.name().to_owned(),
+            block: func.layout.block_iter().position(|b| b == block).unwrap(),
+            index: func
+                .layout
+                .inst_iter(block)
+                .position(|i| i == inst)
+                .unwrap(),
+            inst,
+        }
+    }
+}
+
+impl Error for Trap {}
+
+/// With the alternate flag (`{:#}`), the backtrace is printed as well.
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "trap in @{}: {}", self.func, self.kind)?;
+        if f.alternate() {
+            for (i, frame) in self.backtrace.iter().enumerate() {
+                write!(f, "\n  #{} {}", i, frame)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BacktraceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "@{}: block {}, instruction {}",
+            self.func, self.block, self.index
+        )
+    }
+}
+
+impl fmt::Display for TrapKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DivisionByZero => write!(f, "division by zero"),
+            Self::DivisionOverflow => write!(f, "signed division overflow"),
+            Self::OversizedShift => write!(f, "shift amount exceeds bit width"),
+            Self::BranchOnPoison => write!(f, "branch on poison value"),
+            Self::Watchpoint => write!(f, "watchpoint hit"),
+            Self::UnresolvedSymbol => write!(f, "call to unresolved symbol"),
+            Self::BudgetExceeded => write!(f, "step budget exceeded"),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::ops::Range;
+use vicis_core::ir::function::{instruction::InstructionId, FunctionId};
+
+/// The memory watched by a watchpoint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchTarget {
+    /// The bytes at the addresses in the range.
+    Range(Range<usize>),
+    /// The contents of the global variable with the name.
+    Global(String),
+    /// The memory allocated by the alloca, each time it is run.
+    Alloca(FunctionId, InstructionId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchAccess {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// A call being run, i.e. a frame of the call stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallSite {
+    /// Name of the calling function.
+    pub func: String,
+    /// The call instruction.
+    pub inst: InstructionId,
+}
+
+/// An access to watched memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatchHit {
+    /// Index of the watchpoint, in the order they were added.
+    pub watchpoint: usize,
+    /// Either `Read` or `Write`.
+    pub access: WatchAccess,
+    pub addr: usize,
+    pub size: usize,
+    /// Name of the function containing `inst`.
+    pub func: String,
+    /// The load or store accessing the memory.
+    pub inst: InstructionId,
+    /// The calls that led to `func`, outermost first.
+    pub call_stack: Vec<CallSite>,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct WatchState {
+    watchpoints: Vec<(WatchTarget, WatchAccess)>,
+    /// Memory currently watched, with the index of the watchpoint.
+    ranges: Vec<(usize, Range<usize>)>,
+    pub call_stack: Vec<CallSite>,
+    pub hits: Vec<WatchHit>,
+}
+
+impl WatchAccess {
+    fn includes(self, access: WatchAccess) -> bool {
+        self == WatchAccess::ReadWrite || self == access
+    }
+}
+
+impl WatchState {
+    /// Adds a watchpoint. `range` is the memory watched from now on, if any.
+    pub fn add(&mut self, target: WatchTarget, access: WatchAccess, range: Option<Range<usize>>) {
+        if let Some(range) = range {
+            self.ranges.push((self.watchpoints.len(), range));
+        }
+        self.watchpoints.push((target, access));
+    }
+
+    /// Starts watching the memory newly allocated by `inst` if requested.
+    /// The memory allocated by earlier runs of `inst`, e.g. in callers of a
+    /// recursive function, stays watched until it is freed.
+    pub fn allocated(&mut self, func: FunctionId, inst: InstructionId, range: Range<usize>) {
+        for (i, (target, _)) in self.watchpoints.iter().enumerate() {
+            if *target == WatchTarget::Alloca(func, inst) {
+                self.ranges.push((i, range.clone()));
+            }
+        }
+    }
+
+    /// Stops watching the memory allocated by `inst` when its frame returns.
+    pub fn freed(&mut self, func: FunctionId, inst: InstructionId, range: Range<usize>) {
+        let watchpoints = &self.watchpoints;
+        self.ranges
+            .retain(|(i, r)| watchpoints[*i].0 != WatchTarget::Alloca(func, inst) || *r != range);
+    }
+
+    /// Records the hits caused by accessing `size` bytes at `addr`, and
+    /// returns the index of the first new one.
+    pub fn access(
+        &mut self,
+        access: WatchAccess,
+        addr: usize,
+        size: usize,
+        func: &str,
+        inst: InstructionId,
+    ) -> Option<usize> {
+        let first = self.hits.len();
+        for (i, range) in &self.ranges {
+            if !self.watchpoints[*i].1.includes(access)
+                || range.end <= addr
+                || addr + size <= range.start
+            {
+                continue;
+            }
+            self.hits.push(WatchHit {
+                watchpoint: *i,
+                access,
+                addr,
+                size,
+                func: func.to_owned(),
+                inst,
+                call_stack: self.call_stack.clone(),
+            });
+        }
+        (first < self.hits.len()).then_some(first)
+    }
+}
@@ -1,5 +1,12 @@
-use vicis_core::ir::module;
-use vicis_interpreter::{generic_value::GenericValue, interpreter};
+use vicis_core::ir::module::{self, name::Name};
+use vicis_interpreter::{
+    generic_value::GenericValue,
+    interpreter::{
+        self,
+        symbolic::{PathOutcome, SymValue, SymbolicExecutor},
+        TaintSink, Trap, TrapKind, TrapMode, WatchAccess, WatchTarget,
+    },
+};
 
 #[test]
 fn exec() {
@@ -586,6 +593,763 @@ fn exec_test_phi() {
     let rc = run(asm,vec![]);
     assert_eq!(rc,GenericValue::Int32(40320));
 }
+
+#[test]
+fn exec_i16_i128() {
+    let asm = r#"
+define i32 @main() {
+  %1 = alloca i16, align 2
+  %2 = alloca i128, align 16
+  store i16 -300, i16* %1, align 2
+  %3 = load i16, i16* %1, align 2
+  %4 = mul i16 %3, 2
+  %5 = sext i16 %4 to i64
+  %6 = zext i64 %5 to i128
+  %7 = shl i128 %6, 64
+  store i128 %7, i128* %2, align 16
+  %8 = load i128, i128* %2, align 16
+  %9 = lshr i128 %8, 64
+  %10 = trunc i128 %9 to i32
+  %11 = icmp eq i32 %10, -600
+  %12 = zext i1 %11 to i32
+  ret i32 %12
+}
+"#;
+    assert_eq!(run(asm, vec![]), GenericValue::Int32(1));
+}
+
+#[test]
+fn exec_trap_sdiv_by_zero() {
+    let asm = r#"
+    define i32 @main(i32 %0) {
+      %2 = sdiv i32 10, %0
+      ret i32 %2
+    }
+    "#;
+    let trap = run_with_trap_mode(asm, vec![GenericValue::Int32(0)], TrapMode::Trap).unwrap_err();
+    assert_eq!(trap.kind, TrapKind::DivisionByZero);
+    assert_eq!(trap.to_string(), "trap in @main: division by zero");
+    assert_eq!(
+        run_with_trap_mode(asm, vec![GenericValue::Int32(0)], TrapMode::Poison),
+        Ok(GenericValue::Poison)
+    );
+}
+
+#[test]
+fn exec_trap_sdiv_overflow() {
+    let asm = r#"
+    define i32 @main(i32 %0) {
+      %2 = srem i32 -2147483648, %0
+      ret i32 %2
+    }
+    "#;
+    let trap = run_with_trap_mode(asm, vec![GenericValue::Int32(-1)], TrapMode::Trap).unwrap_err();
+    assert_eq!(trap.kind, TrapKind::DivisionOverflow);
+    assert_eq!(
+        run(asm, vec![GenericValue::Int32(3)]),
+        GenericValue::Int32(-2)
+    );
+}
+
+#[test]
+fn exec_trap_oversized_shift() {
+    let asm = r#"
+    define i32 @main(i32 %0) {
+      %2 = lshr i32 -1, %0
+      ret i32 %2
+    }
+    "#;
+    let trap = run_with_trap_mode(asm, vec![GenericValue::Int32(32)], TrapMode::Trap).unwrap_err();
+    assert_eq!(trap.kind, TrapKind::OversizedShift);
+    assert_eq!(
+        run(asm, vec![GenericValue::Int32(28)]),
+        GenericValue::Int32(15)
+    );
+}
+
+#[test]
+fn exec_trap_branch_on_poison() {
+    let asm = r#"
+    define i32 @main(i32 %0) {
+      %2 = sdiv i32 1, %0
+      %3 = icmp eq i32 %2, 0
+      br i1 %3, label %4, label %5
+    4:
+      ret i32 1
+    5:
+      ret i32 2
+    }
+    "#;
+    let trap = run_with_trap_mode(asm, vec![GenericValue::Int32(0)], TrapMode::Poison).unwrap_err();
+    assert_eq!(trap.kind, TrapKind::BranchOnPoison);
+}
+
+#[test]
+fn display_value() {
+    let asm = r#"
+    %struct.S = type { i32, i8*, [3 x i8] }
+    @s = global %struct.S zeroinitializer, align 8
+    @str = private unnamed_addr constant [3 x i8] c"ab\00", align 1
+    define i8* @str1() {
+      %1 = getelementptr inbounds [3 x i8], [3 x i8]* @str, i64 0, i64 1
+      ret i8* %1
+    }
+    define %struct.S* @get_s() {
+      ret %struct.S* @s
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let types = &module.types;
+    let ctx = interpreter::Context::new(&module);
+    let run = |name| {
+        interpreter::run_function(&ctx, module.find_function_by_name(name).unwrap(), vec![])
+            .unwrap()
+    };
+    let s = run("get_s").to_ptr().unwrap();
+    let str1 = run("str1").to_ptr().unwrap();
+    unsafe {
+        *(s as *mut i32) = 7;
+        *(s.add(8) as *mut *mut u8) = str1;
+        std::ptr::copy_nonoverlapping([b'x', b'\n', 0].as_ptr(), s.add(16), 3);
+    }
+    let s_ty = module.global_variables()[&Name::Name("s".into())].ty;
+    assert_eq!(
+        GenericValue::Ptr(s)
+            .display(types, s_ty)
+            .with_context(&ctx)
+            .to_string(),
+        r#"{ 7, @str+1, c"x\0A\00" }"#
+    );
+    let s_ptr_ty = types.ptr_to(s_ty);
+    assert_eq!(
+        GenericValue::Ptr(s)
+            .display(types, s_ptr_ty)
+            .with_context(&ctx)
+            .to_string(),
+        "@s"
+    );
+    assert_eq!(
+        GenericValue::Int1(true)
+            .display(types, vicis_core::ir::types::I1)
+            .to_string(),
+        "true"
+    );
+    let arr = [1i32, -2, 3];
+    let arr_ty = types.array_of(vicis_core::ir::types::I32, 3);
+    assert_eq!(
+        GenericValue::Ptr(arr.as_ptr() as *mut u8)
+            .display(types, arr_ty)
+            .to_string(),
+        "[1, -2, 3]"
+    );
+}
+
+#[test]
+fn exec_trap_backtrace() {
+    let asm = r#"
+    define internal i32 @div(i32 %0) {
+      %2 = add i32 %0, 1
+      %3 = sdiv i32 10, %0
+      ret i32 %3
+    }
+    define internal i32 @call_div(i32 %0) {
+      %2 = call i32 @div(i32 %0)
+      ret i32 %2
+    }
+    define i32 @main(i32 %0) {
+      br label %2
+    2:
+      %3 = call i32 @call_div(i32 %0)
+      ret i32 %3
+    }
+    "#;
+    let trap = run_with_trap_mode(asm, vec![GenericValue::Int32(0)], TrapMode::Trap).unwrap_err();
+    assert_eq!(trap.kind, TrapKind::DivisionByZero);
+    assert_eq!(trap.backtrace.len(), 3);
+    assert_eq!(trap.backtrace[0].inst, trap.inst);
+    assert_eq!(
+        format!("{:#}", trap),
+        "trap in @div: division by zero
+  #0 @div: block 0, instruction 1
+  #1 @call_div: block 0, instruction 0
+  #2 @main: block 1, instruction 0"
+    );
+}
+
+#[test]
+fn exec_trap_unresolved_symbol() {
+    let asm = r#"
+    declare i32 @missing(i32)
+    define i32 @main(i32 %0) {
+      %2 = call i32 @missing(i32 %0)
+      ret i32 %2
+    }
+    "#;
+    let trap = run_with_trap_mode(asm, vec![GenericValue::Int32(0)], TrapMode::Trap).unwrap_err();
+    assert_eq!(trap.kind, TrapKind::UnresolvedSymbol);
+    assert_eq!(trap.func, "main");
+}
+
+#[test]
+fn exec_trap_budget_exceeded() {
+    let asm = r#"
+    define i32 @main() {
+      br label %1
+    1:
+      br label %1
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let ctx = interpreter::Context::new(&module).with_step_budget(100);
+    let main = module.find_function_by_name("main").unwrap();
+    let trap = interpreter::run_function(&ctx, main, vec![]).unwrap_err();
+    assert_eq!(trap.kind, TrapKind::BudgetExceeded);
+    assert_eq!(trap.backtrace[0].block, 1);
+}
+
+#[test]
+fn exec_custom_intrinsic() {
+    use std::cell::Cell;
+    use vicis_core::ir::intrinsic::{IntrinsicLowering, IntrinsicRegistry};
+
+    let asm = r#"
+    define i32 @main() {
+      %1 = call i32 @vicis.counter()
+      %2 = call i32 @vicis.counter()
+      %3 = add i32 %1, %2
+      ret i32 %3
+    }
+    "#;
+    let mut module = module::parse_assembly(asm).unwrap();
+    let mut registry = IntrinsicRegistry::new();
+    registry.register("vicis.counter", IntrinsicLowering::Erase);
+    assert_eq!(registry.declare_missing(&mut module), 1);
+
+    let counter = Cell::new(10);
+    let ctx = interpreter::Context::new(&module).with_intrinsic("vicis.counter", |_| {
+        counter.set(counter.get() + 1);
+        GenericValue::Int32(counter.get())
+    });
+    let main = module.find_function_by_name("main").unwrap();
+    assert_eq!(
+        interpreter::run_function(&ctx, main, vec![]),
+        Ok(GenericValue::Int32(23))
+    );
+}
+
+#[test]
+fn exec_resume_function() {
+    let asm = r#"
+    define i32 @main(i32 %0) {
+      %2 = add i32 %0, 1
+      %3 = add i32 %2, 10
+      ret i32 %3
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let ctx = interpreter::Context::new(&module);
+    let main = module.find_function_by_name("main").unwrap();
+    let func = &module.functions()[main];
+    let entry = func.layout.get_entry_block().unwrap();
+    let mut insts = func.layout.inst_iter(entry);
+    let (first, second) = (insts.next().unwrap(), insts.next().unwrap());
+    assert_eq!(
+        interpreter::resume_function(
+            &ctx,
+            main,
+            vec![GenericValue::Int32(0)],
+            second,
+            vec![(first, GenericValue::Int32(5))]
+        ),
+        Ok(GenericValue::Int32(15))
+    );
+}
+
+#[test]
+fn exec_symbolic() {
+    let asm = r#"
+    define i32 @main(i32 %0, i32 %1) {
+      %3 = icmp slt i32 %0, 10
+      br i1 %3, label %4, label %6
+    4:
+      %5 = sdiv i32 100, %1
+      ret i32 %5
+    6:
+      ret i32 -1
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let ctx = interpreter::Context::new(&module);
+    let main = module.find_function_by_name("main").unwrap();
+    let paths = SymbolicExecutor::new(&ctx).run(main, vec![None, None]);
+    assert_eq!(paths.len(), 3);
+    assert!(paths
+        .iter()
+        .any(|p| p.outcome == PathOutcome::Trapped(TrapKind::DivisionByZero)));
+    // The arguments found for each path reproduce it in the interpreter.
+    for path in paths {
+        let result = interpreter::run_function(&ctx, main, path.args.clone());
+        match path.outcome {
+            PathOutcome::Returned(val) => assert_eq!(result.ok(), val.eval(&path.model).ok()),
+            PathOutcome::Trapped(kind) => assert_eq!(result.unwrap_err().kind, kind),
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+    }
+}
+
+#[test]
+fn exec_symbolic_memory() {
+    let asm = r#"
+    define i32 @main(i32 %0) {
+      %2 = alloca i32, align 4
+      store i32 %0, i32* %2, align 4
+      %3 = load i32, i32* %2, align 4
+      %4 = icmp eq i32 %3, 42
+      br i1 %4, label %5, label %6
+    5:
+      ret i32 1
+    6:
+      ret i32 0
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let ctx = interpreter::Context::new(&module);
+    let main = module.find_function_by_name("main").unwrap();
+    let paths = SymbolicExecutor::new(&ctx).run(main, vec![None]);
+    let args: Vec<_> = paths.iter().map(|p| p.args.clone()).collect();
+    assert_eq!(
+        args,
+        vec![vec![GenericValue::Int32(0)], vec![GenericValue::Int32(42)]]
+    );
+    assert_eq!(paths[0].constraints[0].to_string(), "!(icmp eq $0 42)");
+    assert_eq!(
+        paths[1].outcome,
+        PathOutcome::Returned(SymValue::Concrete(GenericValue::Int32(1)))
+    );
+}
+
+#[test]
+fn exec_symbolic_memory_call() {
+    let asm = r#"
+    define internal i32 @swap(i32* %p) {
+      %v = load i32, i32* %p, align 4
+      store i32 3, i32* %p, align 4
+      ret i32 %v
+    }
+    define i32 @main(i32 %0) {
+      %2 = alloca i32, align 4
+      store i32 %0, i32* %2, align 4
+      %3 = call i32 @swap(i32* %2)
+      %4 = load i32, i32* %2, align 4
+      %5 = add i32 %3, %4
+      ret i32 %5
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let ctx = interpreter::Context::new(&module);
+    let main = module.find_function_by_name("main").unwrap();
+    let paths = SymbolicExecutor::new(&ctx).run(main, vec![None]);
+    // The callee sees the argument stored before the call, and the load after
+    // it sees what the callee stored.
+    assert_eq!(paths.len(), 1);
+    assert_eq!(paths[0].constraints[0].to_string(), "(icmp eq $0 0)");
+    assert_eq!(
+        paths[0].outcome,
+        PathOutcome::Returned(SymValue::Concrete(GenericValue::Int32(3)))
+    );
+    assert_eq!(
+        interpreter::run_function(&ctx, main, vec![GenericValue::Int32(5)]).ok(),
+        Some(GenericValue::Int32(8))
+    );
+}
+
+#[test]
+fn exec_symbolic_memory_overlap() {
+    let asm = r#"
+    define i32 @main(i64 %0) {
+      %2 = alloca i64, align 8
+      store i64 %0, i64* %2, align 8
+      %3 = bitcast i64* %2 to i32*
+      store i32 7, i32* %3, align 8
+      %4 = load i64, i64* %2, align 8
+      %5 = icmp eq i64 %4, 7
+      br i1 %5, label %6, label %7
+    6:
+      ret i32 1
+    7:
+      ret i32 0
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let ctx = interpreter::Context::new(&module);
+    let main = module.find_function_by_name("main").unwrap();
+    let paths = SymbolicExecutor::new(&ctx).run(main, vec![None]);
+    // The argument is pinned to its model once overwritten in part, so that
+    // the wider load sees the bytes of both stores.
+    assert_eq!(paths.len(), 1);
+    assert_eq!(paths[0].args, vec![GenericValue::Int64(0)]);
+    assert_eq!(paths[0].constraints[0].to_string(), "(icmp eq $0 0)");
+    assert_eq!(
+        paths[0].outcome,
+        PathOutcome::Returned(SymValue::Concrete(GenericValue::Int32(1)))
+    );
+}
+
+#[test]
+fn exec_taint() {
+    let asm = r#"
+    declare void @sink(i32)
+    define internal i32 @inc(i32 %0) {
+      %2 = add i32 %0, 1
+      ret i32 %2
+    }
+    define i32 @main(i32 %0, i32 %1) {
+      %3 = alloca i32, align 4
+      store i32 %1, i32* %3, align 4
+      %4 = call i32 @inc(i32 %0)
+      store i32 %4, i32* %3, align 4
+      %5 = load i32, i32* %3, align 4
+      %6 = icmp sgt i32 %5, 10
+      br i1 %6, label %7, label %8
+    7:
+      call void @sink(i32 %1)
+      call void @sink(i32 %5)
+      ret i32 1
+    8:
+      ret i32 0
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let ctx = interpreter::Context::new(&module)
+        .with_taint_tracking()
+        .with_intrinsic("sink", |_| GenericValue::Void);
+    let main = module.find_function_by_name("main").unwrap();
+    let args = vec![GenericValue::Int32(20), GenericValue::Int32(0)];
+    let result = interpreter::run_function_with_taint(&ctx, main, args, &[0]);
+    assert_eq!(result, Ok(GenericValue::Int32(1)));
+    let reports = ctx.taint_reports();
+    let sinks: Vec<_> = reports.iter().map(|r| r.sink).collect();
+    assert_eq!(
+        sinks,
+        vec![TaintSink::Store, TaintSink::Branch, TaintSink::ExternalCall]
+    );
+    assert!(reports.iter().all(|r| r.func == "main"));
+}
+
+#[test]
+fn exec_taint_global() {
+    let asm = r#"
+    @.str = private unnamed_addr constant [3 x i8] c"ab\00", align 1
+    define i32 @main() {
+      %1 = getelementptr inbounds [3 x i8], [3 x i8]* @.str, i64 0, i64 1
+      %2 = load i8, i8* %1, align 1
+      %3 = icmp eq i8 %2, 98
+      br i1 %3, label %4, label %5
+    4:
+      ret i32 1
+    5:
+      ret i32 0
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let ctx = interpreter::Context::new(&module).with_tainted_global(".str");
+    let main = module.find_function_by_name("main").unwrap();
+    assert_eq!(
+        interpreter::run_function(&ctx, main, vec![]),
+        Ok(GenericValue::Int32(1))
+    );
+    let sinks: Vec<_> = ctx.taint_reports().iter().map(|r| r.sink).collect();
+    assert_eq!(sinks, vec![TaintSink::Branch]);
+}
+
+#[test]
+fn exec_watchpoint_global() {
+    let asm = r#"
+    @counter = global [1 x i32] zeroinitializer, align 4
+    define internal void @bump() {
+      %1 = getelementptr inbounds [1 x i32], [1 x i32]* @counter, i64 0, i64 0
+      %2 = load i32, i32* %1, align 4
+      %3 = add i32 %2, 1
+      store i32 %3, i32* %1, align 4
+      ret void
+    }
+    define i32 @main() {
+      call void @bump()
+      call void @bump()
+      %1 = getelementptr inbounds [1 x i32], [1 x i32]* @counter, i64 0, i64 0
+      %2 = load i32, i32* %1, align 4
+      ret i32 %2
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let ctx = interpreter::Context::new(&module)
+        .with_watchpoint(WatchTarget::Global("counter".into()), WatchAccess::Write)
+        .with_watch_handler(|_| false);
+    let main = module.find_function_by_name("main").unwrap();
+    assert_eq!(
+        interpreter::run_function(&ctx, main, vec![]),
+        Ok(GenericValue::Int32(2))
+    );
+    let hits = ctx.watch_hits();
+    assert_eq!(hits.len(), 2);
+    assert!(hits
+        .iter()
+        .all(|hit| hit.access == WatchAccess::Write && hit.func == "bump"));
+    assert_eq!(hits[0].call_stack.len(), 1);
+    assert_eq!(hits[0].call_stack[0].func, "main");
+    assert_ne!(hits[0].call_stack[0].inst, hits[1].call_stack[0].inst);
+}
+
+#[test]
+fn exec_watchpoint_alloca() {
+    let asm = r#"
+    define i32 @main() {
+      %1 = alloca i32, align 4
+      %2 = alloca i32, align 4
+      store i32 1, i32* %1, align 4
+      store i32 2, i32* %2, align 4
+      %3 = load i32, i32* %2, align 4
+      %4 = load i32, i32* %1, align 4
+      ret i32 %4
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let main = module.find_function_by_name("main").unwrap();
+    let func = &module.functions()[main];
+    let insts: Vec<_> = func
+        .layout
+        .inst_iter(func.layout.first_block.unwrap())
+        .collect();
+    let ctx = interpreter::Context::new(&module)
+        .with_watchpoint(WatchTarget::Alloca(main, insts[0]), WatchAccess::Read);
+    let trap = interpreter::run_function(&ctx, main, vec![]).unwrap_err();
+    assert_eq!(trap.kind, TrapKind::Watchpoint);
+    assert_eq!(trap.inst, insts[5]);
+    assert_eq!(ctx.watch_hits().len(), 1);
+}
+
+#[test]
+fn exec_recursion() {
+    // Fixed expected values, the exit codes of the C programs in
+    // codegen/tests/codegen/c, which the codegen tests build and run.
+    let fibo = r#"
+    define dso_local i32 @fibo(i32 %0) {
+      %2 = alloca i32, align 4
+      %3 = alloca i32, align 4
+      store i32 %0, i32* %3, align 4
+      %4 = load i32, i32* %3, align 4
+      %5 = icmp sle i32 %4, 2
+      br i1 %5, label %6, label %7
+    6:
+      store i32 1, i32* %2, align 4
+      br label %15
+    7:
+      %8 = load i32, i32* %3, align 4
+      %9 = sub nsw i32 %8, 1
+      %10 = call i32 @fibo(i32 %9)
+      %11 = load i32, i32* %3, align 4
+      %12 = sub nsw i32 %11, 2
+      %13 = call i32 @fibo(i32 %12)
+      %14 = add nsw i32 %10, %13
+      store i32 %14, i32* %2, align 4
+      br label %15
+    15:
+      %16 = load i32, i32* %2, align 4
+      ret i32 %16
+    }
+
+    define dso_local i32 @main() {
+      %1 = call i32 @fibo(i32 10)
+      ret i32 %1
+    }
+    "#;
+    assert_eq!(run(fibo, vec![]), GenericValue::Int32(55));
+
+    let ack = r#"
+    define dso_local i32 @ack(i32 %0, i32 %1) {
+      %3 = alloca i32, align 4
+      %4 = alloca i32, align 4
+      %5 = alloca i32, align 4
+      store i32 %0, i32* %4, align 4
+      store i32 %1, i32* %5, align 4
+      %6 = load i32, i32* %4, align 4
+      %7 = icmp eq i32 %6, 0
+      br i1 %7, label %8, label %11
+    8:
+      %9 = load i32, i32* %5, align 4
+      %10 = add nsw i32 %9, 1
+      store i32 %10, i32* %3, align 4
+      br label %26
+    11:
+      %12 = load i32, i32* %5, align 4
+      %13 = icmp eq i32 %12, 0
+      br i1 %13, label %14, label %18
+    14:
+      %15 = load i32, i32* %4, align 4
+      %16 = sub nsw i32 %15, 1
+      %17 = call i32 @ack(i32 %16, i32 1)
+      store i32 %17, i32* %3, align 4
+      br label %26
+    18:
+      %19 = load i32, i32* %4, align 4
+      %20 = sub nsw i32 %19, 1
+      %21 = load i32, i32* %4, align 4
+      %22 = load i32, i32* %5, align 4
+      %23 = sub nsw i32 %22, 1
+      %24 = call i32 @ack(i32 %21, i32 %23)
+      %25 = call i32 @ack(i32 %20, i32 %24)
+      store i32 %25, i32* %3, align 4
+      br label %26
+    26:
+      %27 = load i32, i32* %3, align 4
+      ret i32 %27
+    }
+
+    define dso_local i32 @main() {
+      %1 = call i32 @ack(i32 2, i32 3)
+      ret i32 %1
+    }
+    "#;
+    assert_eq!(run(ack, vec![]), GenericValue::Int32(9));
+}
+
+#[test]
+fn exec_mutual_recursion() {
+    let asm = r#"
+    define dso_local i32 @is_even(i32 %0) {
+      %2 = icmp eq i32 %0, 0
+      br i1 %2, label %3, label %4
+    3:
+      ret i32 1
+    4:
+      %5 = sub nsw i32 %0, 1
+      %6 = call i32 @is_odd(i32 %5)
+      ret i32 %6
+    }
+
+    define dso_local i32 @is_odd(i32 %0) {
+      %2 = icmp eq i32 %0, 0
+      br i1 %2, label %3, label %4
+    3:
+      ret i32 0
+    4:
+      %5 = sub nsw i32 %0, 1
+      %6 = call i32 @is_even(i32 %5)
+      ret i32 %6
+    }
+
+    define dso_local i32 @main() {
+      %1 = call i32 @is_even(i32 10)
+      %2 = call i32 @is_odd(i32 7)
+      %3 = add nsw i32 %1, %2
+      %4 = call i32 @is_odd(i32 8)
+      %5 = add nsw i32 %3, %4
+      ret i32 %5
+    }
+    "#;
+    assert_eq!(run(asm, vec![]), GenericValue::Int32(2));
+}
+
+#[test]
+fn exec_watchpoint_alloca_recursion() {
+    // Each frame loads its own local after the recursive call returns.
+    let asm = r#"
+    define i32 @f(i32 %0) {
+      %2 = alloca i32, align 4
+      store i32 %0, i32* %2, align 4
+      %3 = icmp sgt i32 %0, 0
+      br i1 %3, label %4, label %7
+    4:
+      %5 = sub i32 %0, 1
+      %6 = call i32 @f(i32 %5)
+      br label %7
+    7:
+      %8 = load i32, i32* %2, align 4
+      ret i32 %8
+    }
+
+    define i32 @main() {
+      %1 = call i32 @f(i32 3)
+      ret i32 %1
+    }
+    "#;
+    let module = module::parse_assembly(asm).unwrap();
+    let f = module.find_function_by_name("f").unwrap();
+    let func = &module.functions()[f];
+    let alloca = func
+        .layout
+        .inst_iter(func.entry_block().unwrap())
+        .next()
+        .unwrap();
+    let ctx = interpreter::Context::new(&module)
+        .with_watchpoint(WatchTarget::Alloca(f, alloca), WatchAccess::Read)
+        .with_watch_handler(|_| false);
+    let main = module.find_function_by_name("main").unwrap();
+    assert_eq!(
+        interpreter::run_function(&ctx, main, vec![]),
+        Ok(GenericValue::Int32(3))
+    );
+    let depths: Vec<usize> = ctx
+        .watch_hits()
+        .iter()
+        .map(|hit| hit.call_stack.len())
+        .collect();
+    assert_eq!(depths, vec![4, 3, 2, 1]);
+}
+
+#[test]
+fn exec_multi_module() {
+    let main = r#"
+    @counter = external global i32, align 4
+    @id = private unnamed_addr constant [2 x i8] c"\01\00", align 1
+
+    declare i32 @twice(i32)
+
+    define i32 @inc(i32 %0) {
+      %2 = add i32 %0, 1
+      ret i32 %2
+    }
+
+    define i32 @main() {
+      %1 = call i32 @twice(i32 5)
+      %2 = load i32, i32* @counter, align 4
+      %3 = add i32 %1, %2
+      %4 = load i8, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @id, i64 0, i64 0), align 1
+      %5 = sext i8 %4 to i32
+      %6 = add i32 %3, %5
+      ret i32 %6
+    }
+    "#;
+    let lib = r#"
+    @counter = global i32 zeroinitializer, align 4
+    @id = private unnamed_addr constant [2 x i8] c"d\00", align 1
+
+    declare i32 @inc(i32)
+
+    define i32 @twice(i32 %0) {
+      %2 = call i32 @inc(i32 %0)
+      %3 = call i32 @inc(i32 %2)
+      %4 = load i8, i8* getelementptr inbounds ([2 x i8], [2 x i8]* @id, i64 0, i64 0), align 1
+      %5 = sext i8 %4 to i32
+      %6 = add i32 %5, 10
+      store i32 %6, i32* @counter, align 4
+      ret i32 %3
+    }
+    "#;
+    let modules = [
+        module::parse_assembly(main).unwrap(),
+        module::parse_assembly(lib).unwrap(),
+    ];
+    let ctx = interpreter::Context::new_multi(&modules);
+    let main = ctx.find_function_by_name("main").unwrap();
+    // 7 from @twice calling back into @inc, 110 stored to the shared @counter
+    // and 1 from the private @id of the first module.
+    assert_eq!(
+        interpreter::run_function(&ctx, main, vec![]),
+        Ok(GenericValue::Int32(118))
+    );
+}
+
 #[cfg(test)]
 fn run(asm: &str, args: Vec<GenericValue>) -> GenericValue {
     let module = module::parse_assembly(asm).unwrap();
@@ -594,6 +1358,18 @@ fn run(asm: &str, args: Vec<GenericValue>) -> GenericValue {
     interpreter::run_function(&ctx, main, args).unwrap()
 }
 
+#[cfg(test)]
+fn run_with_trap_mode(
+    asm: &str,
+    args: Vec<GenericValue>,
+    mode: TrapMode,
+) -> Result<GenericValue, Trap> {
+    let module = module::parse_assembly(asm).unwrap();
+    let ctx = interpreter::Context::new(&module).with_trap_mode(mode);
+    let main = module.find_function_by_name("main").unwrap();
+    interpreter::run_function(&ctx, main, args)
+}
+
 #[cfg(test)]
 fn run_libc(asm: &str, fname: &str,args: Vec<GenericValue>) -> GenericValue {
     let module = module::parse_assembly(asm).unwrap();